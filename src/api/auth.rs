@@ -0,0 +1,200 @@
+//! HTTP API authentication and role-based access control
+//!
+//! Clients authenticate with HTTP Basic auth (bcrypt password hashes) or a
+//! bearer token (SHA-256 hashes). Each endpoint needs a role: `reader` for
+//! reads, `writer` for writes, `admin` for `/admin/*`, dumps, snapshots and
+//! logs; a higher role includes the lower ones. `/health` stays open for load
+//! balancers.
+//!
+//! bcrypt is slow on purpose, so credentials that verified once are
+//! remembered by their SHA-256 and later requests skip the bcrypt check.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axum::http::{header, HeaderMap, Method};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use sha2::{Digest, Sha256};
+
+use crate::config::{ApiAuthConfig, ApiRole};
+use crate::metrics;
+
+/// Verified credentials remembered before the cache is cleared
+const MAX_CACHED_CREDENTIALS: usize = 1024;
+
+/// Why a request was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// No credentials (401)
+    Missing,
+    /// Unknown user, wrong password or unknown token (401)
+    Invalid,
+    /// Valid credentials without the required role (403)
+    Forbidden,
+}
+
+impl AuthFailure {
+    /// `reason` label of `wolfscale_api_auth_failures_total`
+    pub fn reason(self) -> &'static str {
+        match self {
+            AuthFailure::Missing => "missing_credentials",
+            AuthFailure::Invalid => "invalid_credentials",
+            AuthFailure::Forbidden => "forbidden",
+        }
+    }
+}
+
+/// Credentials checker built from `[api.auth]`
+pub struct ApiAuth {
+    config: ApiAuthConfig,
+    /// SHA-256 of verified `Authorization` headers → the role they grant
+    verified: Mutex<HashMap<[u8; 32], ApiRole>>,
+}
+
+impl ApiAuth {
+    pub fn new(config: ApiAuthConfig) -> Self {
+        Self { config, verified: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// `Authorization` header value for calls to other nodes, if configured
+    pub fn cluster_authorization(&self) -> Option<String> {
+        self.config.cluster_token.as_ref().map(|token| format!("Bearer {}", token))
+    }
+
+    /// Check that the request's credentials grant what the endpoint needs.
+    /// Failures are counted in `wolfscale_api_auth_failures_total`.
+    pub fn authorize(&self, method: &Method, path: &str, headers: &HeaderMap) -> Result<(), AuthFailure> {
+        let result = match required_role(method, path) {
+            _ if !self.config.enabled => Ok(()),
+            None => Ok(()),
+            Some(required) => self.authenticate(headers).and_then(|role| {
+                if role >= required { Ok(()) } else { Err(AuthFailure::Forbidden) }
+            }),
+        };
+        if let Err(failure) = result {
+            metrics::API_AUTH_FAILURES.with_label_values(&[failure.reason()]).inc();
+        }
+        result
+    }
+
+    /// The highest role the request's credentials grant
+    fn authenticate(&self, headers: &HeaderMap) -> Result<ApiRole, AuthFailure> {
+        let value = headers.get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(AuthFailure::Missing)?;
+        let key: [u8; 32] = Sha256::digest(value.as_bytes()).into();
+        if let Some(role) = self.verified.lock().unwrap().get(&key) {
+            return Ok(*role);
+        }
+
+        let (scheme, credentials) = value.split_once(' ').ok_or(AuthFailure::Invalid)?;
+        let role = if scheme.eq_ignore_ascii_case("basic") {
+            self.check_password(credentials.trim())
+        } else if scheme.eq_ignore_ascii_case("bearer") {
+            self.check_token(credentials.trim())
+        } else {
+            None
+        }
+        .ok_or(AuthFailure::Invalid)?;
+
+        let mut verified = self.verified.lock().unwrap();
+        if verified.len() >= MAX_CACHED_CREDENTIALS {
+            verified.clear();
+        }
+        verified.insert(key, role);
+        Ok(role)
+    }
+
+    fn check_password(&self, encoded: &str) -> Option<ApiRole> {
+        let decoded = String::from_utf8(BASE64.decode(encoded).ok()?).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+        let user = self.config.users.iter().find(|u| u.username == username)?;
+        if !bcrypt::verify(password, &user.password_hash).unwrap_or(false) {
+            return None;
+        }
+        user.roles.iter().max().copied()
+    }
+
+    fn check_token(&self, token: &str) -> Option<ApiRole> {
+        if self.config.cluster_token.as_deref().is_some_and(|t| constant_time_eq(t.as_bytes(), token.as_bytes())) {
+            return Some(ApiRole::Admin);
+        }
+        let hash = format!("sha256:{}", to_hex(&Sha256::digest(token.as_bytes())));
+        self.config.tokens.iter()
+            .filter(|t| t.token_hash.eq_ignore_ascii_case(&hash))
+            .flat_map(|t| t.roles.iter())
+            .max()
+            .copied()
+    }
+}
+
+/// The role an endpoint needs, `None` if it is open
+pub fn required_role(method: &Method, path: &str) -> Option<ApiRole> {
+    if path == "/health" {
+        None
+    } else if path.starts_with("/admin/") || path == "/dump" || path.starts_with("/dump/") || path == "/logs" || path == "/snapshot" {
+        Some(ApiRole::Admin)
+    } else if method == Method::GET || method == Method::HEAD || path == "/query" {
+        // `POST /query` is read-only
+        Some(ApiRole::Reader)
+    } else {
+        Some(ApiRole::Writer)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiToken, ApiUser};
+
+    fn auth() -> ApiAuth {
+        ApiAuth::new(ApiAuthConfig {
+            enabled: true,
+            users: vec![ApiUser {
+                username: "alice".to_string(),
+                password_hash: bcrypt::hash("secret", 4).unwrap(),
+                roles: vec![ApiRole::Reader],
+            }],
+            tokens: vec![ApiToken {
+                token_hash: format!("sha256:{}", to_hex(&Sha256::digest(b"tok"))),
+                roles: vec![ApiRole::Writer],
+            }],
+            cluster_token: None,
+        })
+    }
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_roles() {
+        let auth = auth();
+        let alice = headers(&format!("Basic {}", BASE64.encode("alice:secret")));
+        let token = headers("Bearer tok");
+
+        assert_eq!(auth.authorize(&Method::GET, "/status", &alice), Ok(()));
+        assert_eq!(auth.authorize(&Method::POST, "/write", &alice), Err(AuthFailure::Forbidden));
+        assert_eq!(auth.authorize(&Method::POST, "/write", &token), Ok(()));
+        assert_eq!(auth.authorize(&Method::POST, "/admin/promote", &token), Err(AuthFailure::Forbidden));
+
+        let wrong = headers(&format!("Basic {}", BASE64.encode("alice:wrong")));
+        assert_eq!(auth.authorize(&Method::GET, "/status", &wrong), Err(AuthFailure::Invalid));
+        assert_eq!(auth.authorize(&Method::GET, "/status", &HeaderMap::new()), Err(AuthFailure::Missing));
+        assert_eq!(auth.authorize(&Method::GET, "/health", &HeaderMap::new()), Ok(()));
+    }
+}
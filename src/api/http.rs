@@ -2,23 +2,36 @@
 //!
 //! REST API for write operations, status queries, and cluster management.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use axum::{
-    extract::{Path, State, Json},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{ConnectInfo, Path, Query, Request, State, Json},
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    http::{header, HeaderMap, StatusCode, Uri},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Router,
 };
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use std::collections::VecDeque;
 
-use crate::config::{ApiConfig, DatabaseConfig};
+use super::auth::{ApiAuth, AuthFailure};
+use super::maintenance::Maintenance;
+use super::progress::ReplicationProgress;
+use super::rate_limit::ClientRateLimiter;
+use super::topology::Topology;
+use crate::config::{ApiConfig, DatabaseConfig, WalConfig};
+use crate::executor::MariaDbExecutor;
 use sqlx::mysql::MySqlPoolOptions;
 use crate::wal::{LogEntry, Value, PrimaryKey};
-use crate::state::{ClusterMembership, NodeState, ClusterSummary};
+use crate::state::{ClusterMembership, NodeRole, NodeState, ClusterSummary};
+use crate::replication::{ReadBarrier, ReadConsistency, TransactionBuffer};
 use crate::error::{Error, Result};
+use crate::id::{self, IdRange, SnowflakeGenerator, SnowflakeId};
+use crate::telemetry::{self, Span};
 
 /// HTTP client for forwarding writes to leader
 static HTTP_CLIENT: std::sync::LazyLock<reqwest::Client> = std::sync::LazyLock::new(|| {
@@ -39,6 +52,15 @@ pub struct ErrorLogEntry {
 /// Maximum number of error log entries to keep
 const MAX_ERROR_LOG_SIZE: usize = 20;
 
+/// Cookie holding the LSN of the session's last write, for `consistency=session` reads
+const SESSION_COOKIE: &str = "wolfscale_session_lsn";
+
+/// How often `/ws/progress` connections get an update
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Updates kept for a slow `/ws/progress` connection before it skips ahead
+const PROGRESS_BACKLOG: usize = 8;
+
 /// Shared application state
 pub struct AppState {
     /// Node ID
@@ -57,6 +79,34 @@ pub struct AppState {
     pub recent_errors: RwLock<VecDeque<ErrorLogEntry>>,
     /// Database pool for processlist queries
     pub db_pool: Option<sqlx::MySqlPool>,
+    /// Leader's open transactions, set while this node leads
+    pub transactions: RwLock<Option<Arc<TransactionBuffer>>>,
+    /// Read barrier coordinator for strong and session reads
+    pub read_barrier: RwLock<Option<Arc<ReadBarrier>>>,
+    /// Adds a node to the running cluster, set while this node leads
+    pub add_node_handler: RwLock<Option<AddNodeHandler>>,
+    /// Executor used to answer `/query` on followers
+    pub executor: RwLock<Option<Arc<MariaDbExecutor>>>,
+    /// Whether followers answer `/query`
+    pub allow_follower_reads: bool,
+    /// Longest a `/query` may run
+    pub follower_read_timeout: Duration,
+    /// `/query` requests per second per client IP
+    pub follower_read_limiter: ClientRateLimiter,
+    /// Maintenance mode, which refuses writes
+    pub maintenance: Maintenance,
+    /// Credentials and roles from `[api.auth]`
+    pub auth: ApiAuth,
+    /// `logging.file`, whose tail `/logs` serves
+    pub log_file: RwLock<Option<std::path::PathBuf>>,
+    /// WAL settings for reading the WAL in `data_dir`, set to serve `/snapshot`
+    pub wal_config: RwLock<Option<WalConfig>>,
+    /// Replication progress updates (JSON) for `/ws/progress` connections
+    pub progress: broadcast::Sender<String>,
+    /// Whether the task publishing `progress` has been started
+    pub progress_started: std::sync::atomic::AtomicBool,
+    /// Generator behind `/id`, used while this node leads
+    pub id_generator: SnowflakeGenerator,
 }
 
 impl AppState {
@@ -79,6 +129,9 @@ impl AppState {
 /// Write handler callback
 pub type WriteHandler = Arc<dyn Fn(LogEntry) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<u64>> + Send>> + Send + Sync>;
 
+/// Add-node callback: (node_id, address) -> LSN of the snapshot sent to the node
+pub type AddNodeHandler = Arc<dyn Fn(String, String) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<u64>> + Send>> + Send + Sync>;
+
 /// HTTP API server
 pub struct HttpServer {
     config: ApiConfig,
@@ -127,6 +180,20 @@ impl HttpServer {
             current_lsn: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             recent_errors: RwLock::new(VecDeque::with_capacity(MAX_ERROR_LOG_SIZE)),
             db_pool,
+            transactions: RwLock::new(None),
+            read_barrier: RwLock::new(None),
+            add_node_handler: RwLock::new(None),
+            executor: RwLock::new(None),
+            allow_follower_reads: config.allow_follower_reads,
+            follower_read_timeout: Duration::from_secs(config.follower_read_timeout_secs),
+            follower_read_limiter: ClientRateLimiter::new(config.follower_read_rps),
+            maintenance: Maintenance::new(),
+            auth: ApiAuth::new(config.auth.clone()),
+            log_file: RwLock::new(None),
+            wal_config: RwLock::new(None),
+            progress: broadcast::channel(PROGRESS_BACKLOG).0,
+            progress_started: std::sync::atomic::AtomicBool::new(false),
+            id_generator: SnowflakeGenerator::new(0),
         });
 
         Self { config, state }
@@ -149,6 +216,20 @@ impl HttpServer {
             current_lsn: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             recent_errors: RwLock::new(VecDeque::with_capacity(MAX_ERROR_LOG_SIZE)),
             db_pool: None,
+            transactions: RwLock::new(None),
+            read_barrier: RwLock::new(None),
+            add_node_handler: RwLock::new(None),
+            executor: RwLock::new(None),
+            allow_follower_reads: config.allow_follower_reads,
+            follower_read_timeout: Duration::from_secs(config.follower_read_timeout_secs),
+            follower_read_limiter: ClientRateLimiter::new(config.follower_read_rps),
+            maintenance: Maintenance::new(),
+            auth: ApiAuth::new(config.auth.clone()),
+            log_file: RwLock::new(None),
+            wal_config: RwLock::new(None),
+            progress: broadcast::channel(PROGRESS_BACKLOG).0,
+            progress_started: std::sync::atomic::AtomicBool::new(false),
+            id_generator: SnowflakeGenerator::new(0),
         });
 
         Self { config, state }
@@ -164,6 +245,36 @@ impl HttpServer {
         *self.state.write_handler.write().await = Some(handler);
     }
 
+    /// Set the leader's transaction buffer used to serve `/txn` requests
+    pub async fn set_transactions(&self, transactions: Arc<TransactionBuffer>) {
+        *self.state.transactions.write().await = Some(transactions);
+    }
+
+    /// Set the read barrier coordinator used to serve strong and session reads
+    pub async fn set_read_barrier(&self, read_barrier: Arc<ReadBarrier>) {
+        *self.state.read_barrier.write().await = Some(read_barrier);
+    }
+
+    /// Set the executor used to serve `POST /query` on followers
+    pub async fn set_executor(&self, executor: Arc<MariaDbExecutor>) {
+        *self.state.executor.write().await = Some(executor);
+    }
+
+    /// Set the handler used to serve `POST /admin/add-node`
+    pub async fn set_add_node_handler(&self, handler: AddNodeHandler) {
+        *self.state.add_node_handler.write().await = Some(handler);
+    }
+
+    /// Set the log file served by `GET /logs`
+    pub async fn set_log_file(&self, path: std::path::PathBuf) {
+        *self.state.log_file.write().await = Some(path);
+    }
+
+    /// Set the WAL settings used to serve `GET /snapshot`
+    pub async fn set_wal_config(&self, wal: WalConfig) {
+        *self.state.wal_config.write().await = Some(wal);
+    }
+
     /// Get the state for sharing with other components
     pub fn state(&self) -> Arc<AppState> {
         Arc::clone(&self.state)
@@ -207,7 +318,8 @@ impl HttpServer {
 
     /// Create the router
     fn create_router(state: Arc<AppState>) -> Router {
-        Router::new()
+        // Refused with 503 while the node is in maintenance
+        let writes = Router::new()
             // Write operations
             .route("/write", post(handle_write))
             .route("/write/insert", post(handle_insert))
@@ -217,6 +329,24 @@ impl HttpServer {
             .route("/write/ddl", post(handle_ddl))
             // Raw SQL forwarding (for proxy write forwarding)
             .route("/sql", post(handle_sql))
+            // Transactions (buffered on the leader until commit)
+            .route("/txn/begin", post(handle_txn_begin))
+            .route("/txn/:txn_id/write", post(handle_txn_write))
+            .route("/txn/:txn_id/commit", post(handle_txn_commit))
+            // Cluster-wide Snowflake IDs (allocated on the leader)
+            .route("/id/generate", post(handle_generate_ids))
+            .route("/id/batch", post(handle_id_batch))
+            .route_layer(middleware::from_fn_with_state(Arc::clone(&state), maintenance_gate));
+
+        Router::new()
+            .merge(writes)
+            .route("/txn/:txn_id/rollback", post(handle_txn_rollback))
+            // Reads at a chosen consistency level
+            .route("/read", get(handle_read))
+            // Read-only queries answered by followers (analytics)
+            .route("/query", post(handle_follower_query))
+            // Metrics
+            .route("/metrics", get(handle_metrics))
             // Status and info
             .route("/status", get(handle_status))
             .route("/stats", get(handle_stats))
@@ -224,13 +354,21 @@ impl HttpServer {
             .route("/cluster", get(handle_cluster_info))
             .route("/cluster/nodes", get(handle_nodes))
             .route("/cluster/nodes/:node_id", get(handle_node_info))
+            .route("/topology", get(handle_topology))
+            .route("/topology.svg", get(handle_topology_svg))
+            .route("/ws/progress", get(handle_progress_ws))
+            .route("/logs", get(handle_logs))
             // Admin operations
             .route("/admin/promote", post(handle_promote))
             .route("/admin/demote", post(handle_demote))
             .route("/admin/reset", post(handle_reset))
+            .route("/admin/add-node", post(handle_add_node))
+            .route("/admin/maintenance", get(handle_maintenance_status).post(handle_maintenance))
             // Migration operations
             .route("/dump/info", get(handle_dump_info))
             .route("/dump", get(handle_dump))
+            .route("/snapshot", get(handle_snapshot))
+            .route_layer(middleware::from_fn_with_state(Arc::clone(&state), auth_gate))
             .with_state(state)
     }
 
@@ -246,7 +384,8 @@ impl HttpServer {
         let listener = tokio::net::TcpListener::bind(&self.config.bind_address).await?;
         tracing::info!("HTTP API listening on {}", self.config.bind_address);
 
-        axum::serve(listener, app)
+        // Client addresses are needed to rate-limit `/query`
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .map_err(|e| Error::Network(format!("HTTP server error: {}", e)))?;
 
@@ -302,6 +441,13 @@ pub struct DdlRequest {
     pub table: Option<String>,
 }
 
+/// Add node request
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AddNodeRequest {
+    pub node_id: String,
+    pub address: String,
+}
+
 /// Write response
 #[derive(Debug, Serialize)]
 pub struct WriteResponse {
@@ -453,18 +599,40 @@ async fn handle_sql(
             sql: req.sql.clone(),
             database: req.database.clone(),
             affects_table: None,
+            gtid: None,
         };
         
-        match handler(entry).await {
+        // Root span of this write's trace; followers attach to it via the WAL entry
+        let trace_id = telemetry::new_trace_id();
+        let mut span = Span::root("http_request", trace_id);
+        let result = telemetry::with_trace_id(trace_id, handler(entry)).await;
+        if let Err(ref e) = result {
+            span.set_error(e.to_string());
+        }
+
+        match result {
             Ok(lsn) => {
                 tracing::debug!("SQL forwarded and written to WAL, LSN: {}", lsn);
+                (
+                    [(header::SET_COOKIE, session_cookie(lsn))],
+                    Json(SqlResponse {
+                        success: true,
+                        affected_rows: 1, // We don't know exact count from WAL write
+                        last_insert_id: 0,
+                        message: Some(format!("LSN: {}", lsn)),
+                    }),
+                ).into_response()
+            }
+            Err(Error::RateLimited { retry_after_ms }) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_ms.div_ceil(1000).max(1).to_string())],
                 Json(SqlResponse {
-                    success: true,
-                    affected_rows: 1, // We don't know exact count from WAL write
+                    success: false,
+                    affected_rows: 0,
                     last_insert_id: 0,
-                    message: Some(format!("LSN: {}", lsn)),
-                }).into_response()
-            }
+                    message: Some(format!("Write rate limit exceeded, retry in {}ms", retry_after_ms)),
+                }),
+            ).into_response(),
             Err(e) => {
                 tracing::error!("Failed to write SQL to WAL: {}", e);
                 (
@@ -491,6 +659,607 @@ async fn handle_sql(
     }
 }
 
+/// Transaction response
+#[derive(Debug, Serialize)]
+struct TransactionResponse {
+    success: bool,
+    txn_id: String,
+    /// Writes buffered so far (write) or discarded (rollback)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    writes: Option<usize>,
+    /// LSN of the committed transaction (commit)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lsn: Option<u64>,
+}
+
+/// Get the leader's transaction buffer, or the response to return instead
+/// (forwarded from the leader, or an error)
+async fn transaction_buffer<T: Serialize>(
+    state: &AppState,
+    endpoint: &str,
+    body: &T,
+) -> std::result::Result<Arc<TransactionBuffer>, axum::response::Response> {
+    if let Some(transactions) = state.transactions.read().await.clone() {
+        return Ok(transactions);
+    }
+
+    if !*state.is_leader.read().await {
+        return Err(match forward_to_leader(state, endpoint, body).await {
+            Ok(response) => response,
+            Err(error_response) => error_response,
+        });
+    }
+
+    Err((
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ErrorResponse {
+            error: "Transactions are not available on this node".to_string(),
+            code: "NO_TRANSACTIONS".to_string(),
+        }),
+    ).into_response())
+}
+
+/// Convert a transaction error into an HTTP response
+fn transaction_error(e: Error) -> axum::response::Response {
+    let (status, code) = match e {
+        Error::TransactionNotFound(_) => (StatusCode::NOT_FOUND, "TXN_NOT_FOUND"),
+        Error::TransactionDatabaseMismatch { .. } => (StatusCode::BAD_REQUEST, "TXN_DATABASE_MISMATCH"),
+        Error::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, "RATE_LIMITED"),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, "TXN_FAILED"),
+    };
+    (
+        status,
+        Json(ErrorResponse {
+            error: e.to_string(),
+            code: code.to_string(),
+        }),
+    ).into_response()
+}
+
+/// Begin a transaction
+async fn handle_txn_begin(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let transactions = match transaction_buffer(&state, "/txn/begin", &serde_json::json!({})).await {
+        Ok(transactions) => transactions,
+        Err(response) => return response,
+    };
+
+    let txn_id = transactions.begin().await;
+    Json(TransactionResponse {
+        success: true,
+        txn_id: txn_id.to_string(),
+        writes: None,
+        lsn: None,
+    }).into_response()
+}
+
+/// Buffer a SQL statement in a transaction
+async fn handle_txn_write(
+    State(state): State<Arc<AppState>>,
+    Path(txn_id): Path<uuid::Uuid>,
+    Json(req): Json<SqlRequest>,
+) -> impl IntoResponse {
+    let endpoint = format!("/txn/{}/write", txn_id);
+    let body = serde_json::json!({ "sql": req.sql, "database": req.database });
+    let transactions = match transaction_buffer(&state, &endpoint, &body).await {
+        Ok(transactions) => transactions,
+        Err(response) => return response,
+    };
+
+    let entry = LogEntry::RawSql {
+        sql: req.sql,
+        database: req.database,
+        affects_table: None,
+        gtid: None,
+    };
+
+    match transactions.write(txn_id, entry).await {
+        Ok(writes) => Json(TransactionResponse {
+            success: true,
+            txn_id: txn_id.to_string(),
+            writes: Some(writes),
+            lsn: None,
+        }).into_response(),
+        Err(e) => transaction_error(e),
+    }
+}
+
+/// Commit a transaction - all buffered writes replicate as one batch
+async fn handle_txn_commit(
+    State(state): State<Arc<AppState>>,
+    Path(txn_id): Path<uuid::Uuid>,
+) -> impl IntoResponse {
+    let endpoint = format!("/txn/{}/commit", txn_id);
+    let transactions = match transaction_buffer(&state, &endpoint, &serde_json::json!({})).await {
+        Ok(transactions) => transactions,
+        Err(response) => return response,
+    };
+
+    let entry = match transactions.take(txn_id).await {
+        Ok(entry) => entry,
+        Err(e) => return transaction_error(e),
+    };
+
+    // Append the whole transaction as one WAL entry via the write handler,
+    // the same path as /sql; an empty transaction writes nothing
+    let result = match entry {
+        Some(entry) => match state.write_handler.read().await.as_ref() {
+            Some(handler) => handler(entry).await,
+            None => Err(Error::NotLeader("write handler not configured".to_string())),
+        },
+        None => Ok(state.current_lsn.load(std::sync::atomic::Ordering::Relaxed)),
+    };
+
+    match result {
+        Ok(lsn) => {
+            state.current_lsn.fetch_max(lsn, std::sync::atomic::Ordering::Relaxed);
+            (
+                [(header::SET_COOKIE, session_cookie(lsn))],
+                Json(TransactionResponse {
+                    success: true,
+                    txn_id: txn_id.to_string(),
+                    writes: None,
+                    lsn: Some(lsn),
+                }),
+            ).into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to commit transaction {}: {}", txn_id, e);
+            transaction_error(e)
+        }
+    }
+}
+
+/// Roll back a transaction, discarding its buffered writes
+async fn handle_txn_rollback(
+    State(state): State<Arc<AppState>>,
+    Path(txn_id): Path<uuid::Uuid>,
+) -> impl IntoResponse {
+    let endpoint = format!("/txn/{}/rollback", txn_id);
+    let transactions = match transaction_buffer(&state, &endpoint, &serde_json::json!({})).await {
+        Ok(transactions) => transactions,
+        Err(response) => return response,
+    };
+
+    match transactions.rollback(txn_id).await {
+        Ok(discarded) => Json(TransactionResponse {
+            success: true,
+            txn_id: txn_id.to_string(),
+            writes: Some(discarded),
+            lsn: None,
+        }).into_response(),
+        Err(e) => transaction_error(e),
+    }
+}
+
+/// Most IDs one `POST /id/generate` returns
+const MAX_GENERATE_IDS: u64 = 100_000;
+
+/// Most IDs one `POST /id/batch` reserves
+const MAX_BATCH_IDS: u64 = 10_000_000;
+
+/// Query parameters for `/id/generate`
+#[derive(Debug, Deserialize)]
+struct GenerateIdsParams {
+    #[serde(default = "default_id_count")]
+    count: u64,
+}
+
+fn default_id_count() -> u64 {
+    1
+}
+
+/// Body of `POST /id/batch`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IdBatchRequest {
+    pub count: u64,
+}
+
+/// IDs reserved by `POST /id/batch`, as runs of consecutive IDs
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IdBatchResponse {
+    pub count: u64,
+    pub ranges: Vec<IdRange>,
+}
+
+/// Generate `count` Snowflake IDs, returned as a JSON array
+async fn handle_generate_ids(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<GenerateIdsParams>,
+) -> impl IntoResponse {
+    let endpoint = format!("/id/generate?count={}", params.count);
+    match allocate_ids(&state, &endpoint, &serde_json::json!({}), params.count, MAX_GENERATE_IDS).await {
+        Ok(ranges) => Json(ranges.iter().flat_map(|r| r.iter()).map(u64::from).collect::<Vec<_>>()).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Reserve `count` Snowflake IDs in one go
+async fn handle_id_batch(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<IdBatchRequest>,
+) -> impl IntoResponse {
+    match allocate_ids(&state, "/id/batch", &req, req.count, MAX_BATCH_IDS).await {
+        Ok(ranges) => Json(IdBatchResponse { count: req.count, ranges }).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Allocate `count` IDs on the leader (forwarding the request there from
+/// other nodes). The IDs carry this node's index in the cluster and are only
+/// returned once the allocation is in the WAL.
+async fn allocate_ids<T: Serialize>(
+    state: &AppState,
+    endpoint: &str,
+    body: &T,
+    count: u64,
+    max: u64,
+) -> std::result::Result<Vec<IdRange>, axum::response::Response> {
+    if count == 0 || count > max {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                error: format!("count must be between 1 and {}", max),
+                code: "INVALID_COUNT".to_string(),
+            }),
+        ).into_response());
+    }
+
+    if !*state.is_leader.read().await {
+        return Err(match forward_to_leader(state, endpoint, body).await {
+            Ok(response) => response,
+            Err(error_response) => error_response,
+        });
+    }
+
+    let handler = state.write_handler.read().await.clone();
+    let Some(handler) = handler else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: "Write handler not configured".to_string(),
+                code: "NO_HANDLER".to_string(),
+            }),
+        ).into_response());
+    };
+
+    let members = state.cluster.all_nodes().await;
+    let machine_id = id::machine_id(&state.node_id, members.iter().map(|n| n.id.as_str()));
+    state.id_generator.set_node_id(machine_id);
+    // Carry on after IDs a previous leader handed out
+    state.id_generator.observe(SnowflakeId(id::highest_allocated()));
+    let ranges = state.id_generator.reserve_batch(count);
+    let highest_id = ranges.last().map(|r| r.last.as_u64()).unwrap_or(0);
+
+    if let Err(e) = handler(LogEntry::IdAllocation { machine_id, highest_id }).await {
+        tracing::error!("Failed to write ID allocation to WAL: {}", e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: format!("WAL write failed: {}", e),
+                code: "WAL_WRITE_FAILED".to_string(),
+            }),
+        ).into_response());
+    }
+    id::record_allocation(highest_id);
+    crate::metrics::record_ids_generated(count);
+    Ok(ranges)
+}
+
+/// Query parameters for `/read`
+#[derive(Debug, Deserialize)]
+struct ReadParams {
+    query: String,
+    #[serde(default)]
+    database: Option<String>,
+    #[serde(default)]
+    consistency: ReadConsistency,
+}
+
+/// Result of a read
+#[derive(Debug, Serialize)]
+struct ReadResponse {
+    node_id: String,
+    consistency: ReadConsistency,
+    /// LSN this node had applied when the query ran
+    applied_lsn: u64,
+    columns: Vec<String>,
+    rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// Run a read-only query at the requested consistency level
+async fn handle_read(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ReadParams>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> impl IntoResponse {
+    if !is_read_query(&params.query) {
+        return read_error(
+            StatusCode::BAD_REQUEST,
+            "READ_ONLY",
+            "Only a single SELECT, SHOW, DESCRIBE or EXPLAIN statement can be run on /read".to_string(),
+        );
+    }
+    if let Some(database) = params.database.as_deref().filter(|db| !valid_database_name(db)) {
+        return read_error(StatusCode::BAD_REQUEST, "INVALID_DATABASE",
+            format!("Invalid database name '{}'", database));
+    }
+
+    let read_barrier = state.read_barrier.read().await.clone();
+    match params.consistency {
+        ReadConsistency::Eventual => {}
+        ReadConsistency::Session => {
+            // Without a cookie the session has not written anything yet
+            if let Some(lsn) = session_lsn(&headers) {
+                let Some(read_barrier) = read_barrier else {
+                    return read_error(StatusCode::SERVICE_UNAVAILABLE, "READ_BARRIER_UNAVAILABLE",
+                        "Session reads are not available on this node".to_string());
+                };
+                if let Err(e) = read_barrier.wait_for_applied(lsn).await {
+                    return read_error(StatusCode::SERVICE_UNAVAILABLE, "SESSION_LSN_NOT_APPLIED", e.to_string());
+                }
+            }
+        }
+        ReadConsistency::Strong => {
+            // Only the leader knows the latest LSN; followers pass strong reads on
+            let leader = state.cluster.current_leader().await;
+            let is_leader = leader.as_ref().map(|l| l.id == state.node_id).unwrap_or(false)
+                || *state.is_leader.read().await;
+            if !is_leader {
+                let endpoint = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/read");
+                return match leader_api_url(&state, endpoint).await {
+                    Ok(url) => relay_leader_response(with_cluster_auth(&state, HTTP_CLIENT.get(&url)).send().await).await,
+                    Err(response) => Err(response),
+                }.unwrap_or_else(|response| response);
+            }
+
+            let Some(read_barrier) = read_barrier else {
+                return read_error(StatusCode::SERVICE_UNAVAILABLE, "READ_BARRIER_UNAVAILABLE",
+                    "Strong reads are not available on this node".to_string());
+            };
+            let lsn = state.cluster.get_self().await.last_applied_lsn
+                .max(state.current_lsn.load(std::sync::atomic::Ordering::Relaxed));
+            if let Err(e) = read_barrier.wait_for_quorum(lsn).await {
+                return read_error(StatusCode::SERVICE_UNAVAILABLE, "QUORUM_NOT_REACHED", e.to_string());
+            }
+        }
+    }
+
+    // The executor keeps a pool per database, so no connection is left
+    // switched to another caller's database
+    let Some(executor) = state.executor.read().await.clone() else {
+        return read_error(StatusCode::SERVICE_UNAVAILABLE, "DATABASE_UNAVAILABLE",
+            "No database connection on this node".to_string());
+    };
+    let applied_lsn = state.cluster.get_self().await.last_applied_lsn;
+    match executor.fetch_rows(params.database.as_deref(), &params.query).await {
+        Ok(rows) => {
+            let (columns, rows) = rows_to_json(&rows);
+            Json(ReadResponse {
+                node_id: state.node_id.clone(),
+                consistency: params.consistency,
+                applied_lsn,
+                columns,
+                rows,
+            }).into_response()
+        }
+        Err(e) => read_error(StatusCode::BAD_REQUEST, "QUERY_FAILED", e.to_string()),
+    }
+}
+
+fn read_error(status: StatusCode, code: &str, error: String) -> axum::response::Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error,
+            code: code.to_string(),
+        }),
+    ).into_response()
+}
+
+/// Whether `query` is a single statement that only reads data. SELECTs that
+/// write a file or variable (`INTO`) or take row locks (`FOR UPDATE`,
+/// `FOR SHARE`, `LOCK IN SHARE MODE`) are not reads.
+fn is_read_query(query: &str) -> bool {
+    let query = query.trim().trim_end_matches(';');
+    if query.contains(';') {
+        return false;
+    }
+    let words = sql_keywords(query);
+    let keyword = words.first().map(String::as_str).unwrap_or("");
+    if !matches!(keyword, "SELECT" | "SHOW" | "DESCRIBE" | "DESC" | "EXPLAIN") {
+        return false;
+    }
+    !words.windows(2).any(|pair| matches!(
+        (pair[0].as_str(), pair[1].as_str()),
+        ("FOR", "UPDATE") | ("FOR", "SHARE") | ("SHARE", "MODE")
+    )) && !words.iter().any(|word| word == "INTO")
+}
+
+/// Upper-cased words of `query` outside quoted strings and identifiers.
+/// Comments are kept, as MariaDB runs `/*! ... */` ones.
+fn sql_keywords(query: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        if quote.is_none() && (c.is_alphanumeric() || c == '_') {
+            word.push(c.to_ascii_uppercase());
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        match quote {
+            Some(q) if c == '\\' && q != '`' => { chars.next(); }
+            Some(q) if c == q => quote = None,
+            None if matches!(c, '\'' | '"' | '`') => quote = Some(c),
+            _ => {}
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Whether a database name is safe to put in a connection URL
+fn valid_database_name(database: &str) -> bool {
+    !database.is_empty() && database.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Column names and JSON rows of a text-protocol result set
+fn rows_to_json(rows: &[sqlx::mysql::MySqlRow]) -> (Vec<String>, Vec<Vec<serde_json::Value>>) {
+    use sqlx::{Column, Row};
+
+    let columns = rows.first()
+        .map(|row| row.columns().iter().map(|c| c.name().to_string()).collect())
+        .unwrap_or_default();
+    let rows = rows.iter()
+        .map(|row| (0..row.len()).map(|i| column_value(row, i)).collect())
+        .collect();
+    (columns, rows)
+}
+
+/// JSON value of one text-protocol column: numbers for numeric types, else strings
+fn column_value(row: &sqlx::mysql::MySqlRow, index: usize) -> serde_json::Value {
+    use sqlx::{Column, Row, TypeInfo};
+
+    let text = match row.try_get_unchecked::<Option<String>, _>(index) {
+        Ok(Some(text)) => text,
+        Ok(None) => return serde_json::Value::Null,
+        Err(_) => match row.try_get_unchecked::<Option<Vec<u8>>, _>(index) {
+            Ok(Some(bytes)) => String::from_utf8_lossy(&bytes).into_owned(),
+            _ => return serde_json::Value::Null,
+        },
+    };
+
+    let type_name = row.columns()[index].type_info().name();
+    if type_name.contains("INT") || matches!(type_name, "FLOAT" | "DOUBLE" | "DECIMAL") {
+        if let Ok(number) = serde_json::from_str::<serde_json::Number>(&text) {
+            return serde_json::Value::Number(number);
+        }
+    }
+    serde_json::Value::String(text)
+}
+
+/// Body of `POST /query`
+#[derive(Debug, Deserialize)]
+struct FollowerQueryRequest {
+    sql: String,
+    #[serde(default)]
+    database: Option<String>,
+}
+
+/// Query parameters for `POST /query`
+#[derive(Debug, Deserialize)]
+struct FollowerQueryParams {
+    /// Refuse the query unless this node has applied at least this LSN
+    #[serde(default)]
+    min_lsn: Option<u64>,
+}
+
+/// Result of a follower query
+#[derive(Debug, Serialize)]
+struct FollowerQueryResponse {
+    node_id: String,
+    /// LSN this node had applied when the query ran
+    applied_lsn: u64,
+    columns: Vec<String>,
+    rows: Vec<Vec<serde_json::Value>>,
+}
+
+/// Answer a read-only query from this follower's own database, so analytics
+/// can run without touching the leader
+async fn handle_follower_query(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Query(params): Query<FollowerQueryParams>,
+    Json(req): Json<FollowerQueryRequest>,
+) -> impl IntoResponse {
+    if !state.allow_follower_reads {
+        return read_error(StatusCode::NOT_FOUND, "FOLLOWER_READS_DISABLED",
+            "Follower reads are disabled (api.allow_follower_reads)".to_string());
+    }
+    if *state.is_leader.read().await {
+        return read_error(StatusCode::CONFLICT, "NOT_A_FOLLOWER",
+            "This node is the leader; send /query to a follower".to_string());
+    }
+    if !state.follower_read_limiter.try_acquire(client.ip()) {
+        return read_error(StatusCode::TOO_MANY_REQUESTS, "RATE_LIMITED",
+            "Too many follower queries from this client".to_string());
+    }
+    if !is_read_query(&req.sql) {
+        return read_error(StatusCode::BAD_REQUEST, "READ_ONLY",
+            "Only a single SELECT, SHOW, DESCRIBE or EXPLAIN statement can be run on /query".to_string());
+    }
+    if let Some(database) = req.database.as_deref().filter(|db| !valid_database_name(db)) {
+        return read_error(StatusCode::BAD_REQUEST, "INVALID_DATABASE",
+            format!("Invalid database name '{}'", database));
+    }
+
+    let applied_lsn = state.cluster.get_self().await.last_applied_lsn;
+    if let Some(min_lsn) = params.min_lsn {
+        if applied_lsn < min_lsn {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({ "error": "follower_lag", "current_lsn": applied_lsn })),
+            ).into_response();
+        }
+    }
+
+    let Some(executor) = state.executor.read().await.clone() else {
+        return read_error(StatusCode::SERVICE_UNAVAILABLE, "DATABASE_UNAVAILABLE",
+            "No database connection on this node".to_string());
+    };
+    let query = executor.fetch_rows(req.database.as_deref(), &req.sql);
+    match tokio::time::timeout(state.follower_read_timeout, query).await {
+        Ok(Ok(rows)) => {
+            let (columns, rows) = rows_to_json(&rows);
+            Json(FollowerQueryResponse {
+                node_id: state.node_id.clone(),
+                applied_lsn,
+                columns,
+                rows,
+            }).into_response()
+        }
+        Ok(Err(e)) => read_error(StatusCode::BAD_REQUEST, "QUERY_FAILED", e.to_string()),
+        Err(_) => read_error(StatusCode::GATEWAY_TIMEOUT, "QUERY_TIMEOUT",
+            format!("Query did not finish within {}s", state.follower_read_timeout.as_secs())),
+    }
+}
+
+/// `Set-Cookie` value recording the LSN of the session's last write
+fn session_cookie(lsn: u64) -> String {
+    format!("{}={}; Path=/; HttpOnly; SameSite=Strict", SESSION_COOKIE, lsn)
+}
+
+/// LSN of the session's last write, from the request's cookies
+fn session_lsn(headers: &HeaderMap) -> Option<u64> {
+    headers.get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .filter(|(name, _)| *name == SESSION_COOKIE)
+        .filter_map(|(_, value)| value.parse().ok())
+        .max()
+}
+
+/// Prometheus metrics in text exposition format
+async fn handle_metrics(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    crate::metrics::CLUSTER_SIZE.set(state.cluster.all_nodes().await.len() as i64);
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::metrics::gather(),
+    )
+}
+
 async fn handle_insert(
     State(state): State<Arc<AppState>>,
     Json(req): Json<InsertRequest>,
@@ -658,6 +1427,7 @@ async fn handle_ddl(
             sql: req.ddl,
             affects_table: req.table,
             database: None,
+            gtid: None,
         }
     };
 
@@ -828,6 +1598,272 @@ async fn handle_node_info(
     }
 }
 
+async fn current_topology(state: &AppState) -> Topology {
+    let nodes = state.cluster.all_nodes().await;
+    Topology::build(nodes, &state.node_id, state.cluster.heartbeat_timeout())
+}
+
+async fn handle_topology(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    Json(current_topology(&state).await)
+}
+
+async fn handle_topology_svg(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "image/svg+xml")],
+        current_topology(&state).await.to_svg(),
+    )
+}
+
+/// Stream replication progress over a WebSocket, one update a second
+async fn handle_progress_ws(
+    State(state): State<Arc<AppState>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let updates = state.progress.subscribe();
+    start_progress_updates(&state);
+    ws.on_upgrade(move |socket| send_progress(socket, updates))
+}
+
+/// Start publishing progress updates, once, on the first `/ws/progress` request
+fn start_progress_updates(state: &Arc<AppState>) {
+    if state.progress_started.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return;
+    }
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+        loop {
+            interval.tick().await;
+            if state.progress.receiver_count() == 0 {
+                continue;
+            }
+            let progress = ReplicationProgress::from_topology(&current_topology(&state).await);
+            if let Ok(json) = serde_json::to_string(&progress) {
+                let _ = state.progress.send(json);
+            }
+        }
+    });
+}
+
+/// Forward progress updates to one WebSocket until either side closes
+async fn send_progress(mut socket: WebSocket, mut updates: broadcast::Receiver<String>) {
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(json) => {
+                    if socket.send(Message::Text(json)).await.is_err() {
+                        return;
+                    }
+                }
+                // Fell behind: carry on with the latest updates
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+/// Lines returned by `GET /logs`
+const LOG_TAIL_LINES: usize = 100;
+
+/// The last lines of the log file, as a JSON array of strings
+async fn handle_logs(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(path) = state.log_file.read().await.clone() else {
+        return (StatusCode::NOT_FOUND, "logging.file is not set".to_string()).into_response();
+    };
+    let lines = tokio::task::spawn_blocking(move || {
+        match crate::logging::current_log_file(&path) {
+            Some(current) => crate::logging::tail_lines(&current, LOG_TAIL_LINES),
+            None => Ok(Vec::new()),
+        }
+    }).await;
+    match lines {
+        Ok(Ok(lines)) => Json(lines).into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read log file: {}", e)).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Refuse requests whose credentials don't grant the endpoint's role: 401
+/// with a Basic challenge if there are none or they are wrong, 403 if the
+/// role is too low
+async fn auth_gate(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.auth.enabled() {
+        return next.run(request).await;
+    }
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let headers = request.headers().clone();
+    let checker = Arc::clone(&state);
+    // bcrypt blocks for a while the first time new credentials are seen
+    let result = tokio::task::spawn_blocking(move || checker.auth.authorize(&method, &path, &headers))
+        .await
+        .unwrap_or(Err(AuthFailure::Invalid));
+
+    match result {
+        Ok(()) => next.run(request).await,
+        Err(AuthFailure::Forbidden) => (
+            StatusCode::FORBIDDEN,
+            Json(ErrorResponse {
+                error: "Your role does not allow this request".to_string(),
+                code: "FORBIDDEN".to_string(),
+            }),
+        ).into_response(),
+        Err(_) => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, r#"Basic realm="WolfScale""#)],
+            Json(ErrorResponse {
+                error: "Authentication required".to_string(),
+                code: "UNAUTHORIZED".to_string(),
+            }),
+        ).into_response(),
+    }
+}
+
+/// Attach this node's cluster credentials to a call to another node
+fn with_cluster_auth(state: &AppState, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match state.auth.cluster_authorization() {
+        Some(value) => request.header(reqwest::header::AUTHORIZATION, value),
+        None => request,
+    }
+}
+
+/// Refuse writes with 503 while this node is in maintenance, and count the
+/// ones let through as in flight
+async fn maintenance_gate(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.maintenance.start_write() {
+        Ok(_in_flight) => next.run(request).await,
+        Err(retry_after) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(ErrorResponse {
+                error: "Node is in maintenance mode".to_string(),
+                code: "MAINTENANCE".to_string(),
+            }),
+        ).into_response(),
+    }
+}
+
+/// Maintenance mode request
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    /// How long to wait for in-flight writes and follower ACKs
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+/// Maintenance mode state
+#[derive(Debug, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+    /// Writes are refused but the drain has not finished
+    pub draining: bool,
+    /// In-flight writes plus entries not yet ACKed by every follower
+    pub pending_operations: u64,
+    pub in_flight_writes: usize,
+    pub unacked_entries: u64,
+    pub duration_secs: Option<f64>,
+}
+
+/// Entries a healthy follower has yet to ACK (zero unless we lead)
+async fn unacked_entries(state: &AppState) -> u64 {
+    if !*state.is_leader.read().await {
+        return 0;
+    }
+    let lsn = state.cluster.get_self().await.last_applied_lsn
+        .max(state.current_lsn.load(std::sync::atomic::Ordering::Relaxed));
+    let timeout = state.cluster.heartbeat_timeout();
+    state.cluster.all_nodes().await.iter()
+        .filter(|n| n.id != state.node_id && n.role != NodeRole::LoadBalancer && n.is_healthy(timeout))
+        .map(|n| lsn.saturating_sub(n.last_applied_lsn))
+        .max()
+        .unwrap_or(0)
+}
+
+async fn maintenance_status(state: &AppState) -> MaintenanceStatus {
+    let in_flight_writes = state.maintenance.in_flight_writes();
+    let unacked_entries = unacked_entries(state).await;
+    let duration = state.maintenance.elapsed();
+    MaintenanceStatus {
+        enabled: duration.is_some(),
+        draining: duration.is_some() && !state.cluster.in_maintenance(),
+        pending_operations: in_flight_writes as u64 + unacked_entries,
+        in_flight_writes,
+        unacked_entries,
+        duration_secs: duration.map(|d| d.as_secs_f64()),
+    }
+}
+
+async fn handle_maintenance_status(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    Json(maintenance_status(&state).await)
+}
+
+/// Enter maintenance: refuse new writes at once, wait up to
+/// `drain_timeout_secs` for in-flight writes to finish and followers to ACK
+/// everything, then mark the node as in maintenance so the proxy refuses new
+/// clients. Leaving maintenance accepts writes again.
+async fn handle_maintenance(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MaintenanceRequest>,
+) -> impl IntoResponse {
+    if !req.enabled {
+        if let Some(duration) = state.maintenance.end() {
+            state.cluster.set_maintenance(false);
+            tracing::info!("Leaving maintenance mode after {:.1}s", duration.as_secs_f64());
+        }
+        return Json(maintenance_status(&state).await);
+    }
+
+    if state.maintenance.begin(req.drain_timeout_secs) {
+        tracing::info!("Entering maintenance mode, draining for up to {}s", req.drain_timeout_secs);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(req.drain_timeout_secs);
+        loop {
+            let status = maintenance_status(&state).await;
+            if status.pending_operations == 0 {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                tracing::warn!(
+                    "Maintenance drain timed out with {} writes in flight and {} entries unacknowledged",
+                    status.in_flight_writes, status.unacked_entries
+                );
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        // Unless maintenance was turned off while we drained
+        if state.maintenance.elapsed().is_some() {
+            state.cluster.set_maintenance(true);
+        }
+    }
+    Json(maintenance_status(&state).await)
+}
+
 async fn handle_promote(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
@@ -851,6 +1887,58 @@ async fn handle_demote(
     })
 }
 
+/// Add a node to the running cluster. The leader sends it a snapshot and
+/// replicates to it from the snapshot LSN, returned as `lsn`.
+async fn handle_add_node(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<AddNodeRequest>,
+) -> impl IntoResponse {
+    if !*state.is_leader.read().await {
+        return match forward_to_leader(&state, "/admin/add-node", &req).await {
+            Ok(response) => response,
+            Err(error_response) => error_response,
+        };
+    }
+
+    let valid_address = req.address.rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+    if req.node_id.is_empty() || !valid_address {
+        return (StatusCode::BAD_REQUEST, Json(WriteResponse {
+            success: false,
+            lsn: None,
+            message: Some("node_id and a host:port address are required".to_string()),
+        })).into_response();
+    }
+    if state.cluster.get_node(&req.node_id).await.is_some() {
+        return (StatusCode::CONFLICT, Json(WriteResponse {
+            success: false,
+            lsn: None,
+            message: Some(format!("Node {} is already a member", req.node_id)),
+        })).into_response();
+    }
+
+    let result = match state.add_node_handler.read().await.as_ref() {
+        Some(handler) => handler(req.node_id.clone(), req.address.clone()).await,
+        None => Err(Error::NotLeader("add-node handler not configured".to_string())),
+    };
+
+    match result {
+        Ok(snapshot_lsn) => (StatusCode::ACCEPTED, Json(WriteResponse {
+            success: true,
+            lsn: Some(snapshot_lsn),
+            message: Some(format!("Node {} added; sending snapshot at LSN {}", req.node_id, snapshot_lsn)),
+        })).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to add node {}: {}", req.node_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(WriteResponse {
+                success: false,
+                lsn: None,
+                message: Some(e.to_string()),
+            })).into_response()
+        }
+    }
+}
+
 /// Reset WAL and state - clears all log entries and resets LSN to 0
 async fn handle_reset(
     State(state): State<Arc<AppState>>,
@@ -956,6 +2044,19 @@ async fn forward_to_leader<T: Serialize>(
     endpoint: &str,
     body: &T,
 ) -> std::result::Result<axum::response::Response, axum::response::Response> {
+    let leader_api_url = leader_api_url(state, endpoint).await?;
+
+    tracing::debug!("Forwarding write to leader at {}", leader_api_url);
+
+    // Forward the request
+    relay_leader_response(with_cluster_auth(state, HTTP_CLIENT.post(&leader_api_url)).json(body).send().await).await
+}
+
+/// URL of `endpoint` on the current leader's HTTP API
+async fn leader_api_url(
+    state: &AppState,
+    endpoint: &str,
+) -> std::result::Result<String, axum::response::Response> {
     // Get the current leader
     let leader = match state.cluster.current_leader().await {
         Some(l) => l,
@@ -986,24 +2087,28 @@ async fn forward_to_leader<T: Serialize>(
     let leader_host = leader.address.split(':')
         .next()
         .unwrap_or(&leader.address);
-    let leader_api_url = format!("http://{}:8080{}", leader_host, endpoint);
-
-    tracing::debug!("Forwarding write to leader at {}", leader_api_url);
+    Ok(format!("http://{}:8080{}", leader_host, endpoint))
+}
 
-    // Forward the request
-    match HTTP_CLIENT
-        .post(&leader_api_url)
-        .json(body)
-        .send()
-        .await
-    {
+/// Pass the leader's response (status, body and session cookie) back to the client
+async fn relay_leader_response(
+    result: reqwest::Result<reqwest::Response>,
+) -> std::result::Result<axum::response::Response, axum::response::Response> {
+    match result {
         Ok(response) => {
             let status = response.status();
+            let cookie = response.headers().get(reqwest::header::SET_COOKIE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
             match response.text().await {
                 Ok(body) => {
                     let response_status = StatusCode::from_u16(status.as_u16())
                         .unwrap_or(StatusCode::OK);
-                    Ok((response_status, body).into_response())
+                    let mut response = (response_status, body).into_response();
+                    if let Some(value) = cookie.and_then(|c| c.parse().ok()) {
+                        response.headers_mut().insert(header::SET_COOKIE, value);
+                    }
+                    Ok(response)
                 }
                 Err(e) => Err((
                     StatusCode::BAD_GATEWAY,
@@ -1107,6 +2212,33 @@ async fn handle_dump(
     }
 }
 
+/// Stream the WAL up to its last LSN for `wolfscale bootstrap`
+async fn handle_snapshot(State(state): State<Arc<AppState>>) -> Response {
+    let Some(wal) = state.wal_config.read().await.clone() else {
+        return (StatusCode::NOT_FOUND, "This node does not serve snapshots".to_string()).into_response();
+    };
+    let data_dir = state.data_dir.clone();
+    let node_id = state.node_id.clone();
+    let opened = tokio::task::spawn_blocking(move || {
+        let reader = crate::wal::WalReader::new(data_dir, wal.segment_size_mb, wal.compression)?
+            .with_torn_write_detection(wal.torn_write_detection);
+        crate::replication::snapshot_stream(reader, node_id)
+    }).await;
+
+    match opened {
+        Ok(Ok((header, body))) => {
+            tracing::info!("Serving snapshot at LSN {}", header.lsn);
+            (
+                [(header::CONTENT_TYPE, crate::replication::snapshot_content_type())],
+                axum::body::Body::from_stream(body),
+            ).into_response()
+        }
+        Ok(Err(e @ Error::Replication(_))) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1124,4 +2256,494 @@ mod tests {
         assert!(matches!(json_to_primary_key(&serde_json::json!(123)), PrimaryKey::Int(123)));
         assert!(matches!(json_to_primary_key(&serde_json::json!("abc")), PrimaryKey::String(_)));
     }
+
+    #[test]
+    fn test_session_cookie_round_trip() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(session_lsn(&headers), None);
+
+        let cookie = session_cookie(42);
+        let pair = cookie.split(';').next().unwrap();
+        headers.insert(header::COOKIE, format!("theme=dark; {}", pair).parse().unwrap());
+        assert_eq!(session_lsn(&headers), Some(42));
+
+        headers.append(header::COOKIE, format!("{}=abc", SESSION_COOKIE).parse().unwrap());
+        assert_eq!(session_lsn(&headers), Some(42));
+    }
+
+    #[test]
+    fn test_is_read_query() {
+        assert!(is_read_query("SELECT * FROM users WHERE id = 1;"));
+        assert!(is_read_query("  show tables"));
+        assert!(is_read_query("EXPLAIN SELECT 1"));
+        assert!(!is_read_query("DELETE FROM users"));
+        assert!(!is_read_query("SELECT 1; DROP TABLE users"));
+        assert!(!is_read_query(""));
+
+        // Reads that write files or variables, or lock rows
+        assert!(!is_read_query("SELECT * FROM users INTO OUTFILE '/tmp/users.csv'"));
+        assert!(!is_read_query("select * into dumpfile '/tmp/x' from users"));
+        assert!(!is_read_query("SELECT id INTO @id FROM users LIMIT 1"));
+        assert!(!is_read_query("SELECT * FROM users /*!INTO OUTFILE '/tmp/x' */"));
+        assert!(!is_read_query("SELECT * FROM users WHERE id = 1 FOR UPDATE"));
+        assert!(!is_read_query("SELECT * FROM users FOR\n  SHARE"));
+        assert!(!is_read_query("SELECT * FROM users LOCK IN SHARE MODE"));
+        // The same words inside strings and identifiers are fine
+        assert!(is_read_query("SELECT * FROM users WHERE note = 'log into the FOR UPDATE page'"));
+        assert!(is_read_query("SELECT `into`, \"for update\" FROM users WHERE a = 'it\\'s into'"));
+        assert!(is_read_query("SELECT * FROM intotal WHERE mode_share = 1"));
+    }
+
+    /// Serve a follower's router on a free port
+    async fn serve_follower(api: ApiConfig, applied_lsn: u64) -> String {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-2".to_string(),
+            "127.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+        ));
+        cluster.update_node("node-2", |n| n.last_applied_lsn = applied_lsn).await.unwrap();
+        let write_handler: WriteHandler = Arc::new(|_| Box::pin(async { Ok(0) }));
+        let server = HttpServer::with_write_handler(api, "node-2".to_string(), cluster, write_handler, std::env::temp_dir());
+        server.set_leader(false).await;
+        server.set_executor(Arc::new(MariaDbExecutor::new_mock())).await;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = HttpServer::create_router(server.state());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_follower_query() {
+        let api = ApiConfig { allow_follower_reads: true, follower_read_rps: 3, ..ApiConfig::default() };
+        let base = serve_follower(api, 10).await;
+        let client = reqwest::Client::new();
+
+        let resp = client.post(format!("{}/query", base))
+            .json(&serde_json::json!({ "sql": "SELECT * FROM users", "database": "app" }))
+            .send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["node_id"], "node-2");
+        assert_eq!(body["applied_lsn"], 10);
+        assert!(body["rows"].as_array().unwrap().is_empty());
+
+        // Behind the LSN the client has already seen
+        let resp = client.post(format!("{}/query?min_lsn=11", base))
+            .json(&serde_json::json!({ "sql": "SELECT 1" }))
+            .send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body, serde_json::json!({ "error": "follower_lag", "current_lsn": 10 }));
+
+        let resp = client.post(format!("{}/query", base))
+            .json(&serde_json::json!({ "sql": "DELETE FROM users" }))
+            .send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        // Three requests per second per client
+        let resp = client.post(format!("{}/query", base))
+            .json(&serde_json::json!({ "sql": "SHOW TABLES" }))
+            .send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_read_runs_only_plain_reads() {
+        let base = serve_follower(ApiConfig::default(), 10).await;
+        let client = reqwest::Client::new();
+        let read = |query: &str, database: &str| client.get(format!("{}/read", base))
+            .query(&[("query", query), ("database", database)])
+            .send();
+
+        let resp = read("SELECT * FROM users", "app").await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["applied_lsn"], 10);
+
+        for query in ["SELECT * FROM users FOR UPDATE", "SELECT * FROM users INTO OUTFILE '/tmp/users'"] {
+            let body: serde_json::Value = read(query, "app").await.unwrap().json().await.unwrap();
+            assert_eq!(body["code"], "READ_ONLY");
+        }
+        let body: serde_json::Value = read("SELECT 1", "app`; DROP").await.unwrap().json().await.unwrap();
+        assert_eq!(body["code"], "INVALID_DATABASE");
+    }
+
+    #[tokio::test]
+    async fn test_follower_query_disabled() {
+        let base = serve_follower(ApiConfig::default(), 10).await;
+        let resp = reqwest::Client::new().post(format!("{}/query", base))
+            .json(&serde_json::json!({ "sql": "SELECT 1" }))
+            .send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_topology() {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "127.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+        ));
+        cluster.add_peer("node-2".to_string(), "127.0.0.2:7654".to_string()).await.unwrap();
+        cluster.add_peer("node-3".to_string(), "127.0.0.3:7654".to_string()).await.unwrap();
+        cluster.set_leader("node-1").await.unwrap();
+        cluster.update_node("node-1", |n| n.last_applied_lsn = 10).await.unwrap();
+        cluster.record_heartbeat("node-2", 8).await.unwrap();
+
+        let write_handler: WriteHandler = Arc::new(|_| Box::pin(async { Ok(0) }));
+        let server = HttpServer::with_write_handler(ApiConfig::default(), "node-1".to_string(), cluster, write_handler, std::env::temp_dir());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = HttpServer::create_router(server.state());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+
+        let body: serde_json::Value = client.get(format!("http://{}/topology", addr))
+            .send().await.unwrap().json().await.unwrap();
+        let nodes = body["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 3);
+        let role = |id: &str| nodes.iter().find(|n| n["id"] == id).unwrap()["role"].clone();
+        assert_eq!(role("node-1"), "leader");
+        assert_eq!(role("node-2"), "follower");
+        assert_eq!(role("node-3"), "follower");
+
+        let edges = body["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 2);
+        let edge = |to: &str| edges.iter().find(|e| e["to"] == to).unwrap().clone();
+        assert_eq!(edge("node-2")["from"], "node-1");
+        assert_eq!(edge("node-2")["replication_lag_lsn"], 2);
+        assert_eq!(edge("node-2")["healthy"], true);
+        // Never sent a heartbeat
+        assert_eq!(edge("node-3")["healthy"], false);
+
+        let resp = client.get(format!("http://{}/topology.svg", addr)).send().await.unwrap();
+        assert_eq!(resp.headers()[reqwest::header::CONTENT_TYPE], "image/svg+xml");
+        let svg = resp.text().await.unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert_eq!(svg.matches("class=\"edge\"").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_progress_websocket() {
+        use futures::StreamExt;
+
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "127.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+        ));
+        cluster.add_peer("node-2".to_string(), "127.0.0.2:7654".to_string()).await.unwrap();
+        cluster.set_leader("node-1").await.unwrap();
+        cluster.update_node("node-1", |n| n.last_applied_lsn = 10).await.unwrap();
+        cluster.record_heartbeat("node-2", 7).await.unwrap();
+
+        let write_handler: WriteHandler = Arc::new(|_| Box::pin(async { Ok(0) }));
+        let server = HttpServer::with_write_handler(ApiConfig::default(), "node-1".to_string(), Arc::clone(&cluster), write_handler, std::env::temp_dir());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = HttpServer::create_router(server.state());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+
+        let url = format!("ws://{}/ws/progress", addr);
+        let (mut first, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        let (mut second, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        // The next `count` updates and when each arrived
+        async fn updates<S>(ws: &mut S, count: usize) -> Vec<(std::time::Instant, ReplicationProgress)>
+        where S: futures::Stream<Item = std::result::Result<tokio_tungstenite::tungstenite::Message, tokio_tungstenite::tungstenite::Error>> + Unpin {
+            let mut received = Vec::new();
+            for _ in 0..count {
+                let message = tokio::time::timeout(Duration::from_secs(3), ws.next()).await.unwrap().unwrap().unwrap();
+                received.push((std::time::Instant::now(), serde_json::from_str(message.to_text().unwrap()).unwrap()));
+            }
+            received
+        }
+
+        let (a, b) = tokio::join!(updates(&mut first, 3), updates(&mut second, 3));
+        for received in [a, b] {
+            for (_, update) in &received {
+                assert_eq!(update.leader_lsn, 10);
+                assert_eq!(update.followers.len(), 1);
+                let follower = &update.followers[0];
+                assert_eq!((follower.node_id.as_str(), follower.lsn, follower.lag, follower.healthy), ("node-2", 7, 3, true));
+            }
+            let interval = received[2].0 - received[1].0;
+            assert!(interval > Duration::from_millis(800) && interval < Duration::from_millis(1200), "{:?}", interval);
+        }
+
+        cluster.update_node("node-1", |n| n.last_applied_lsn = 12).await.unwrap();
+        cluster.record_heartbeat("node-2", 12).await.unwrap();
+        let (a, b) = tokio::join!(updates(&mut first, 2), updates(&mut second, 2));
+        for received in [a, b] {
+            let update = &received[1].1;
+            assert_eq!(update.leader_lsn, 12);
+            assert_eq!((update.followers[0].lsn, update.followers[0].lag), (12, 0));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_unique_ids_concurrently() {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-2".to_string(),
+            "127.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+        ));
+        cluster.add_peer("node-1".to_string(), "127.0.0.2:7654".to_string()).await.unwrap();
+        cluster.set_leader("node-2").await.unwrap();
+
+        let logged = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&logged);
+        let write_handler: WriteHandler = Arc::new(move |entry| {
+            log.lock().unwrap().push(entry);
+            Box::pin(async { Ok(1) })
+        });
+        let server = HttpServer::with_write_handler(ApiConfig::default(), "node-2".to_string(), cluster, write_handler, std::env::temp_dir());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = HttpServer::create_router(server.state());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+
+        // Three clients, 1M IDs between them
+        let clients = [334_000u64, 333_000, 333_000].map(|total| {
+            let url = format!("http://{}/id/generate?count=1000", addr);
+            tokio::spawn(async move {
+                let client = reqwest::Client::new();
+                let mut ids = Vec::new();
+                while (ids.len() as u64) < total {
+                    let batch: Vec<u64> = client.post(&url).send().await.unwrap().json().await.unwrap();
+                    assert_eq!(batch.len(), 1_000);
+                    ids.extend(batch);
+                }
+                ids
+            })
+        });
+        let mut all = std::collections::HashSet::new();
+        for client in clients {
+            for id in client.await.unwrap() {
+                assert!(all.insert(id), "duplicate ID {}", id);
+            }
+        }
+        assert_eq!(all.len(), 1_000_000);
+        // node-2 is second in the cluster
+        assert!(all.iter().all(|&id| SnowflakeId(id).node_id() == 1));
+
+        let batch: IdBatchResponse = reqwest::Client::new()
+            .post(format!("http://{}/id/batch", addr))
+            .json(&IdBatchRequest { count: 5_000 })
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(batch.ranges.iter().map(|r| r.len()).sum::<u64>(), 5_000);
+        for range in &batch.ranges {
+            assert!(range.iter().all(|id| all.insert(id.as_u64())));
+        }
+
+        // One WAL entry per request, the last holding the highest ID
+        let logged = std::mem::take(&mut *logged.lock().unwrap());
+        assert_eq!(logged.len(), 1_001);
+        let highest = all.iter().max().copied().unwrap();
+        assert!(matches!(logged.last(), Some(LogEntry::IdAllocation { machine_id: 1, highest_id }) if *highest_id == highest));
+        assert!(id::highest_allocated() >= highest);
+
+        let response = reqwest::Client::new().post(format!("http://{}/id/generate?count=0", addr)).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_refuses_writes() {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "127.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+        ));
+        let write_handler: WriteHandler = Arc::new(|_| Box::pin(async { Ok(0) }));
+        let server = HttpServer::with_write_handler(ApiConfig::default(), "node-1".to_string(), Arc::clone(&cluster), write_handler, std::env::temp_dir());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = HttpServer::create_router(server.state());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let insert = || client.post(format!("{}/write/insert", base))
+            .json(&serde_json::json!({ "table": "users", "values": { "id": 1 } }))
+            .send();
+
+        assert_eq!(insert().await.unwrap().status(), reqwest::StatusCode::OK);
+
+        // No followers to wait for, so the drain finishes at once
+        let body: serde_json::Value = client.post(format!("{}/admin/maintenance", base))
+            .json(&serde_json::json!({ "enabled": true, "drain_timeout_secs": 30 }))
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(body["enabled"], true);
+        assert_eq!(body["draining"], false);
+        assert_eq!(body["pending_operations"], 0);
+        assert!(cluster.in_maintenance());
+
+        let resp = insert().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers()[reqwest::header::RETRY_AFTER], "30");
+        let resp = client.post(format!("{}/sql", base))
+            .json(&serde_json::json!({ "sql": "DELETE FROM users" }))
+            .send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: serde_json::Value = client.get(format!("{}/admin/maintenance", base))
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(body["enabled"], true);
+        assert!(body["duration_secs"].as_f64().is_some());
+
+        let body: serde_json::Value = client.post(format!("{}/admin/maintenance", base))
+            .json(&serde_json::json!({ "enabled": false }))
+            .send().await.unwrap().json().await.unwrap();
+        assert_eq!(body["enabled"], false);
+        assert!(!cluster.in_maintenance());
+        assert_eq!(insert().await.unwrap().status(), reqwest::StatusCode::OK);
+        assert!(crate::metrics::MAINTENANCE_DURATION.get() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_reader_cannot_write() {
+        use sha2::{Digest, Sha256};
+
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "127.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+        ));
+        let token_hash: String = Sha256::digest(b"writer-token").iter().map(|b| format!("{:02x}", b)).collect();
+        let mut config = ApiConfig::default();
+        config.auth = crate::config::ApiAuthConfig {
+            enabled: true,
+            users: vec![crate::config::ApiUser {
+                username: "reader".to_string(),
+                password_hash: bcrypt::hash("secret", 4).unwrap(),
+                roles: vec![crate::config::ApiRole::Reader],
+            }],
+            tokens: vec![crate::config::ApiToken {
+                token_hash: format!("sha256:{}", token_hash),
+                roles: vec![crate::config::ApiRole::Writer],
+            }],
+            cluster_token: None,
+        };
+        let write_handler: WriteHandler = Arc::new(|_| Box::pin(async { Ok(0) }));
+        let server = HttpServer::with_write_handler(config, "node-1".to_string(), cluster, write_handler, std::env::temp_dir());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let app = HttpServer::create_router(server.state());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        let client = reqwest::Client::new();
+        let write = || client.post(format!("{}/write/insert", base))
+            .json(&serde_json::json!({ "table": "users", "values": { "id": 1 } }));
+        let forbidden = crate::metrics::API_AUTH_FAILURES.with_label_values(&["forbidden"]).get();
+
+        let resp = write().send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(resp.headers()[reqwest::header::WWW_AUTHENTICATE], r#"Basic realm="WolfScale""#);
+
+        let resp = write().basic_auth("reader", Some("secret")).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+        assert_eq!(crate::metrics::API_AUTH_FAILURES.with_label_values(&["forbidden"]).get(), forbidden + 1);
+
+        let resp = client.get(format!("{}/status", base)).basic_auth("reader", Some("secret")).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        let resp = client.get(format!("{}/status", base)).basic_auth("reader", Some("wrong")).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let resp = write().bearer_auth("writer-token").send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        let resp = client.post(format!("{}/admin/promote", base)).bearer_auth("writer-token").send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::FORBIDDEN);
+
+        let resp = client.get(format!("{}/health", base)).send().await.unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_from_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal = WalConfig {
+            flush_interval_ms: 10,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
+            compression: true,
+            segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
+            retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
+            fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
+        };
+        let writer = crate::wal::WalWriter::new(dir.path().to_path_buf(), wal.clone(), "node-1".to_string())
+            .await.unwrap();
+        for i in 1..=50 {
+            writer.append(LogEntry::Insert {
+                table: "users".to_string(),
+                columns: vec!["id".to_string(), "name".to_string()],
+                values: vec![Value::Int(i), Value::String(format!("user-{}", i))],
+                primary_key: PrimaryKey::Int(i),
+            }).await.unwrap();
+        }
+        writer.flush().await.unwrap();
+
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "127.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+        ));
+        let write_handler: WriteHandler = Arc::new(|_| Box::pin(async { Ok(0) }));
+        let server = HttpServer::with_write_handler(ApiConfig::default(), "node-1".to_string(), cluster, write_handler, dir.path().to_path_buf());
+        server.set_wal_config(wal.clone()).await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = HttpServer::create_router(server.state());
+        tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+        });
+        let base = format!("http://{}", addr);
+
+        // The follower ends up with exactly the leader's entries
+        let mut download = crate::replication::SnapshotDownload::start(&base, None).await.unwrap();
+        assert_eq!(download.header().lsn, 50);
+        assert_eq!(download.header().node_id, "node-1");
+        let mut received = Vec::new();
+        while let Some(entries) = download.next_entries().await.unwrap() {
+            received.extend(entries);
+        }
+        let expected = crate::wal::WalReader::new(dir.path().to_path_buf(), wal.segment_size_mb, wal.compression)
+            .unwrap()
+            .read_from(1)
+            .unwrap();
+        assert_eq!(received.len(), expected.len());
+        for (got, want) in received.iter().zip(&expected) {
+            assert_eq!(got.header.lsn, want.header.lsn);
+            assert_eq!(got.header.checksum, want.header.checksum);
+            assert!(got.verify_checksum());
+        }
+
+        let executor = MariaDbExecutor::new_mock();
+        let lsn = crate::replication::bootstrap_from_http(&base, None, &executor).await.unwrap();
+        assert_eq!(lsn, 50);
+    }
 }
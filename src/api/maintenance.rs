@@ -0,0 +1,110 @@
+//! Maintenance mode
+//!
+//! While a node is in maintenance its write endpoints answer 503 with a
+//! `Retry-After` header. Writes already running when maintenance starts are
+//! counted so the drain can wait for them to finish.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+
+struct Window {
+    since: Instant,
+    retry_after_secs: u64,
+}
+
+/// Maintenance window and in-flight write count for one node
+pub struct Maintenance {
+    window: Mutex<Option<Window>>,
+    in_flight_writes: AtomicUsize,
+}
+
+/// Counts a write as in flight until dropped
+pub struct WriteGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WriteGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Maintenance {
+    pub fn new() -> Self {
+        Self {
+            window: Mutex::new(None),
+            in_flight_writes: AtomicUsize::new(0),
+        }
+    }
+
+    /// Start refusing writes, telling clients to retry after
+    /// `retry_after_secs`. Returns false if already in maintenance.
+    pub fn begin(&self, retry_after_secs: u64) -> bool {
+        let mut window = self.window.lock().unwrap();
+        if window.is_some() {
+            return false;
+        }
+        *window = Some(Window { since: Instant::now(), retry_after_secs: retry_after_secs.max(1) });
+        true
+    }
+
+    /// Accept writes again, recording how long maintenance lasted
+    pub fn end(&self) -> Option<Duration> {
+        let elapsed = self.window.lock().unwrap().take()?.since.elapsed();
+        metrics::MAINTENANCE_DURATION.inc_by(elapsed.as_secs_f64());
+        Some(elapsed)
+    }
+
+    /// How long this node has been in maintenance, if it is
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.window.lock().unwrap().as_ref().map(|w| w.since.elapsed())
+    }
+
+    /// Register a write. Returns the guard keeping it counted as in flight,
+    /// or the `Retry-After` seconds if writes are refused.
+    pub fn start_write(&self) -> std::result::Result<WriteGuard<'_>, u64> {
+        // Count the write before checking, so a drain that starts after the
+        // check always sees it
+        self.in_flight_writes.fetch_add(1, Ordering::SeqCst);
+        let guard = WriteGuard(&self.in_flight_writes);
+        match self.window.lock().unwrap().as_ref() {
+            Some(window) => Err(window.retry_after_secs),
+            None => Ok(guard),
+        }
+    }
+
+    /// Writes currently being handled
+    pub fn in_flight_writes(&self) -> usize {
+        self.in_flight_writes.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_refused_during_maintenance() {
+        let maintenance = Maintenance::new();
+        let running = maintenance.start_write().unwrap();
+
+        assert!(maintenance.begin(30));
+        assert!(!maintenance.begin(30));
+        assert_eq!(maintenance.start_write().err(), Some(30));
+        // The refused write is not left counted
+        assert_eq!(maintenance.in_flight_writes(), 1);
+        drop(running);
+        assert_eq!(maintenance.in_flight_writes(), 0);
+
+        assert!(maintenance.end().is_some());
+        assert!(maintenance.elapsed().is_none());
+        assert!(maintenance.start_write().is_ok());
+    }
+}
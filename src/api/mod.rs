@@ -2,6 +2,13 @@
 //!
 //! Provides a REST API for write operations and cluster management.
 
+mod auth;
 mod http;
+mod maintenance;
+mod progress;
+mod rate_limit;
+mod topology;
 
-pub use http::{HttpServer, WriteHandler};
+pub use http::{AddNodeHandler, HttpServer, WriteHandler};
+pub use progress::{FollowerProgress, ReplicationProgress};
+pub use topology::{Topology, TopologyEdge, TopologyNode};
@@ -0,0 +1,112 @@
+//! Replication progress
+//!
+//! `GET /ws/progress` pushes a `ReplicationProgress` to every connected
+//! WebSocket once a second. One ticker builds each update from cluster
+//! membership and hands it to the connections over a broadcast channel, so
+//! the cost doesn't grow with the number of watchers. `wolfscale repl-watch`
+//! renders the updates with `render`.
+
+use serde::{Deserialize, Serialize};
+
+use super::topology::Topology;
+
+/// How far one follower has got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FollowerProgress {
+    pub node_id: String,
+    pub lsn: u64,
+    /// Entries behind the leader
+    pub lag: u64,
+    pub healthy: bool,
+}
+
+/// One update of `GET /ws/progress`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationProgress {
+    pub leader_lsn: u64,
+    pub followers: Vec<FollowerProgress>,
+}
+
+/// Width of a progress bar in characters
+const BAR_WIDTH: usize = 40;
+
+impl ReplicationProgress {
+    /// Progress of every follower against the leader (or, with no leader,
+    /// against the most advanced node)
+    pub fn from_topology(topology: &Topology) -> Self {
+        let leader = topology.nodes.iter().find(|n| n.role == "leader");
+        let leader_lsn = match leader {
+            Some(leader) => leader.lsn,
+            None => topology.nodes.iter().map(|n| n.lsn).max().unwrap_or(0),
+        };
+        let followers = topology.nodes.iter()
+            .filter(|n| n.role != "leader" && n.role != "load_balancer")
+            .map(|n| FollowerProgress {
+                node_id: n.id.clone(),
+                lsn: n.lsn,
+                lag: leader_lsn.saturating_sub(n.lsn),
+                healthy: n.healthy,
+            })
+            .collect();
+        Self { leader_lsn, followers }
+    }
+
+    /// Followers more than `max_lag` entries behind
+    pub fn lagging(&self, max_lag: u64) -> impl Iterator<Item = &FollowerProgress> {
+        self.followers.iter().filter(move |f| f.lag > max_lag)
+    }
+
+    /// A text table with one progress bar per follower, filled as its LSN
+    /// approaches the leader's
+    pub fn render(&self) -> Vec<String> {
+        let id_width = self.followers.iter().map(|f| f.node_id.len()).max().unwrap_or(0).max(4);
+        let mut lines = vec![
+            format!("Leader LSN: {}", self.leader_lsn),
+            String::new(),
+            format!("{:<id_width$}  {:<bar$}  {:>12}  {:>10}  STATUS", "NODE", "PROGRESS", "LSN", "LAG",
+                id_width = id_width, bar = BAR_WIDTH + 2),
+        ];
+        if self.followers.is_empty() {
+            lines.push("(no followers)".to_string());
+        }
+        for follower in &self.followers {
+            let filled = match self.leader_lsn {
+                0 => BAR_WIDTH,
+                leader => (follower.lsn.min(leader) as u128 * BAR_WIDTH as u128 / leader as u128) as usize,
+            };
+            lines.push(format!(
+                "{:<id_width$}  [{}{}]  {:>12}  {:>10}  {}",
+                follower.node_id,
+                "█".repeat(filled),
+                "░".repeat(BAR_WIDTH - filled),
+                follower.lsn,
+                follower.lag,
+                if follower.healthy { "healthy" } else { "UNHEALTHY" },
+                id_width = id_width,
+            ));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_progress_bars() {
+        let progress = ReplicationProgress {
+            leader_lsn: 100,
+            followers: vec![
+                FollowerProgress { node_id: "n2".into(), lsn: 100, lag: 0, healthy: true },
+                FollowerProgress { node_id: "n3".into(), lsn: 25, lag: 75, healthy: false },
+            ],
+        };
+        let lines = progress.render();
+        assert_eq!(lines[0], "Leader LSN: 100");
+        assert!(lines[3].contains(&format!("[{}]", "█".repeat(BAR_WIDTH))));
+        assert!(lines[4].contains(&format!("[{}{}]", "█".repeat(10), "░".repeat(30))));
+        assert!(lines[4].ends_with("UNHEALTHY"));
+        assert_eq!(progress.lagging(50).map(|f| f.node_id.as_str()).collect::<Vec<_>>(), ["n3"]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Per-client rate limiting
+//!
+//! A token bucket per client IP: each bucket holds up to `rate` tokens and
+//! refills at `rate` tokens per second, so a client can burst one second's
+//! worth of requests and is then held to the steady rate.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets idle this long are full again and can be dropped
+const IDLE_EVICT: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets keyed by client IP
+pub struct ClientRateLimiter {
+    rate: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl ClientRateLimiter {
+    /// Allow `rate` requests per second per client
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate: rate.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `client`, returning false if it is over its rate
+    pub fn try_acquire(&self, client: IpAddr) -> bool {
+        self.try_acquire_at(client, Instant::now())
+    }
+
+    fn try_acquire_at(&self, client: IpAddr, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > 10_000 {
+            buckets.retain(|_, b| now.duration_since(b.updated) < IDLE_EVICT);
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: self.rate, updated: now });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_refill() {
+        let limiter = ClientRateLimiter::new(5);
+        let client: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            assert!(limiter.try_acquire_at(client, start));
+        }
+        assert!(!limiter.try_acquire_at(client, start));
+        // Other clients have their own bucket
+        assert!(limiter.try_acquire_at(other, start));
+
+        // 5 per second: one token back after 200ms
+        assert!(limiter.try_acquire_at(client, start + Duration::from_millis(200)));
+        assert!(!limiter.try_acquire_at(client, start + Duration::from_millis(200)));
+
+        // Never more than one second's worth
+        let later = start + Duration::from_secs(10);
+        for _ in 0..5 {
+            assert!(limiter.try_acquire_at(client, later));
+        }
+        assert!(!limiter.try_acquire_at(client, later));
+    }
+}
@@ -0,0 +1,198 @@
+//! Replication topology
+//!
+//! A snapshot of who replicates from whom, built from cluster membership:
+//! one node per cluster member and one edge from the leader to each node it
+//! replicates to. Served as JSON and as a small hand-written SVG graph.
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::state::{NodeRole, NodeState, NodeStatus};
+
+/// A cluster member in the topology
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologyNode {
+    pub id: String,
+    pub address: String,
+    /// `leader`, `follower`, `candidate` or `load_balancer`
+    pub role: &'static str,
+    pub lsn: u64,
+    /// Entries behind the most advanced node
+    pub lag_lsn: u64,
+    /// Milliseconds since the last heartbeat, `None` if never heard from
+    pub last_seen_ms: Option<u64>,
+    pub healthy: bool,
+}
+
+/// Replication from the leader to one follower
+#[derive(Debug, Clone, Serialize)]
+pub struct TopologyEdge {
+    pub from: String,
+    pub to: String,
+    pub replication_lag_lsn: u64,
+    pub healthy: bool,
+}
+
+/// The whole topology, as returned by `GET /topology`
+#[derive(Debug, Clone, Serialize)]
+pub struct Topology {
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+}
+
+fn role_name(role: NodeRole) -> &'static str {
+    match role {
+        NodeRole::Leader => "leader",
+        NodeRole::Follower => "follower",
+        NodeRole::Candidate => "candidate",
+        NodeRole::LoadBalancer => "load_balancer",
+    }
+}
+
+impl Topology {
+    /// Build the topology as seen by `self_id`. This node is always counted as
+    /// seen; peers are healthy while their heartbeats are within `timeout`.
+    pub fn build(mut members: Vec<NodeState>, self_id: &str, timeout: Duration) -> Self {
+        members.sort_by(|a, b| {
+            (a.role != NodeRole::Leader, &a.id).cmp(&(b.role != NodeRole::Leader, &b.id))
+        });
+        let head = members.iter().map(|n| n.last_applied_lsn).max().unwrap_or(0);
+
+        let nodes: Vec<TopologyNode> = members.iter().map(|n| {
+            let is_self = n.id == self_id;
+            let responsive = is_self || n.is_healthy(timeout);
+            let healthy = responsive && matches!(
+                n.status,
+                NodeStatus::Active | NodeStatus::Syncing | NodeStatus::Joining
+            );
+            TopologyNode {
+                id: n.id.clone(),
+                address: n.address.clone(),
+                role: role_name(n.role),
+                lsn: n.last_applied_lsn,
+                lag_lsn: head.saturating_sub(n.last_applied_lsn),
+                last_seen_ms: if is_self {
+                    Some(0)
+                } else {
+                    n.time_since_heartbeat().map(|d| d.as_millis() as u64)
+                },
+                healthy,
+            }
+        }).collect();
+
+        let edges = match members.iter().find(|n| n.role == NodeRole::Leader) {
+            Some(leader) => nodes.iter()
+                .filter(|n| n.id != leader.id && n.role != "load_balancer")
+                .map(|n| TopologyEdge {
+                    from: leader.id.clone(),
+                    to: n.id.clone(),
+                    replication_lag_lsn: leader.last_applied_lsn.saturating_sub(n.lsn),
+                    healthy: n.healthy,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Self { nodes, edges }
+    }
+
+    /// Render as an SVG graph: the leader in the middle, the other nodes on a
+    /// ring around it, green for healthy and red for unhealthy.
+    pub fn to_svg(&self) -> String {
+        const SIZE: f64 = 560.0;
+        const RING: f64 = 200.0;
+        const RADIUS: f64 = 42.0;
+        let centre = SIZE / 2.0;
+
+        let leader = self.nodes.iter().position(|n| n.role == "leader");
+        let ring_count = self.nodes.len() - usize::from(leader.is_some());
+        let mut ring_index = 0;
+        let positions: Vec<(f64, f64)> = self.nodes.iter().enumerate().map(|(i, _)| {
+            if Some(i) == leader || ring_count == 0 {
+                return (centre, centre);
+            }
+            let angle = std::f64::consts::TAU * ring_index as f64 / ring_count as f64
+                - std::f64::consts::FRAC_PI_2;
+            ring_index += 1;
+            (centre + RING * angle.cos(), centre + RING * angle.sin())
+        }).collect();
+        let position_of = |id: &str| {
+            self.nodes.iter().position(|n| n.id == id).map(|i| positions[i])
+        };
+
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{SIZE}" height="{SIZE}" viewBox="0 0 {SIZE} {SIZE}" font-family="sans-serif">
+<defs>
+<marker id="arrow-ok" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="#16a34a"/></marker>
+<marker id="arrow-bad" viewBox="0 0 10 10" refX="10" refY="5" markerWidth="8" markerHeight="8" orient="auto"><path d="M0,0 L10,5 L0,10 z" fill="#dc2626"/></marker>
+</defs>
+<rect width="100%" height="100%" fill="#ffffff"/>
+"##
+        );
+
+        for edge in &self.edges {
+            let (Some((x1, y1)), Some((x2, y2))) = (position_of(&edge.from), position_of(&edge.to)) else {
+                continue;
+            };
+            let length = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt().max(1.0);
+            let (dx, dy) = ((x2 - x1) / length * RADIUS, (y2 - y1) / length * RADIUS);
+            let (colour, marker) = if edge.healthy { ("#16a34a", "arrow-ok") } else { ("#dc2626", "arrow-bad") };
+            let _ = writeln!(
+                svg,
+                r#"<line class="edge" x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{colour}" stroke-width="2" marker-end="url(#{marker})"/>"#,
+                x1 + dx, y1 + dy, x2 - dx, y2 - dy,
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{:.1}" y="{:.1}" font-size="11" text-anchor="middle" fill="{colour}">lag {}</text>"#,
+                (x1 + x2) / 2.0, (y1 + y2) / 2.0 - 4.0, edge.replication_lag_lsn,
+            );
+        }
+
+        for (node, (x, y)) in self.nodes.iter().zip(&positions) {
+            let fill = if !node.healthy {
+                "#dc2626"
+            } else if node.role == "leader" {
+                "#2563eb"
+            } else {
+                "#16a34a"
+            };
+            let _ = writeln!(
+                svg,
+                r##"<circle class="node" cx="{x:.1}" cy="{y:.1}" r="{RADIUS}" fill="{fill}" stroke="#1f2937" stroke-width="2"/>"##,
+            );
+            let _ = writeln!(
+                svg,
+                r##"<text x="{x:.1}" y="{:.1}" font-size="12" font-weight="bold" text-anchor="middle" fill="#ffffff">{}</text>"##,
+                y - 4.0, xml_escape(&node.id),
+            );
+            let _ = writeln!(
+                svg,
+                r##"<text x="{x:.1}" y="{:.1}" font-size="10" text-anchor="middle" fill="#ffffff">{} · {}</text>"##,
+                y + 12.0, node.role, node.lsn,
+            );
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,220 @@
+//! Audit Log
+//!
+//! Append-only record of every data modification written to the WAL, for
+//! compliance reviews. Each entry becomes one tab-separated line:
+//!
+//! ```text
+//! {timestamp}\t{node_id}\t{lsn}\t{database}.{table}\t{operation}\t{primary_key}[\t{sql}]
+//! ```
+//!
+//! The SQL column is only written with `audit.include_query_data = true`,
+//! since statement values may contain personal data. Records are written
+//! unbuffered to a file opened with `O_APPEND`, and the WAL writer does not
+//! acknowledge an entry until its record has been written. When the file
+//! grows past `audit.max_file_mb` it is renamed with a timestamp suffix and a
+//! new file is started; rotated files are never deleted by WolfScale.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::config::AuditConfig;
+use crate::error::{Error, Result};
+use crate::metrics;
+use crate::wal::WalEntry;
+
+/// Writes audit records for WAL entries
+pub struct AuditLogger {
+    path: PathBuf,
+    file: File,
+    /// Current size of `file` in bytes
+    size: u64,
+    max_bytes: u64,
+    include_query_data: bool,
+}
+
+impl AuditLogger {
+    /// Open (or create) the configured audit log, or None if auditing is disabled
+    pub fn from_config(config: &AuditConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        Self::open(config).map(Some)
+    }
+
+    /// Open (or create) the audit log at `config.log_file`
+    pub fn open(config: &AuditConfig) -> Result<Self> {
+        if let Some(parent) = config.log_file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = Self::open_file(&config.log_file)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path: config.log_file.clone(),
+            file,
+            size,
+            max_bytes: config.max_file_mb * 1024 * 1024,
+            include_query_data: config.include_query_data,
+        })
+    }
+
+    fn open_file(path: &PathBuf) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::Config(format!("Cannot open audit log {}: {}", path.display(), e)))
+    }
+
+    /// Write the audit record for one WAL entry
+    pub fn log(&mut self, entry: &WalEntry) -> Result<()> {
+        let line = self.format_record(entry);
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        // One write per record so concurrent appenders never interleave lines
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        metrics::AUDIT_ENTRIES_WRITTEN.inc();
+        Ok(())
+    }
+
+    /// Flush written records to stable storage
+    pub fn sync(&self) -> Result<()> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    fn format_record(&self, entry: &WalEntry) -> String {
+        let object = match (entry.entry.database(), entry.entry.table_name()) {
+            (Some(db), Some(table)) => format!("{}.{}", db, table),
+            (None, Some(table)) => table.to_string(),
+            (Some(db), None) => db.to_string(),
+            (None, None) => "-".to_string(),
+        };
+        let primary_key = entry.entry.primary_key().map(|pk| pk.to_string()).unwrap_or_else(|| "-".to_string());
+
+        let mut line = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            entry.header.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            escape(&entry.header.origin_node),
+            entry.header.lsn,
+            escape(&object),
+            entry.entry.operation_name(),
+            escape(&primary_key),
+        );
+        if self.include_query_data {
+            line.push('\t');
+            line.push_str(&escape(&entry.entry.to_sql().join("; ")));
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Move the current file aside and start a new one
+    fn rotate(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        let suffix = chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f");
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(format!(".{}", suffix));
+        std::fs::rename(&self.path, &rotated)?;
+
+        self.file = Self::open_file(&self.path)?;
+        self.size = 0;
+        tracing::info!("Rotated audit log to {}", PathBuf::from(rotated).display());
+        Ok(())
+    }
+}
+
+/// Keep every record on one line with tab-separated fields
+fn escape(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::{LogEntry, PrimaryKey, Value};
+    use tempfile::tempdir;
+
+    fn config(dir: &std::path::Path, include_query_data: bool) -> AuditConfig {
+        AuditConfig {
+            enabled: true,
+            log_file: dir.join("audit").join("audit.log"),
+            include_query_data,
+            max_file_mb: 100,
+        }
+    }
+
+    fn entry(lsn: u64) -> WalEntry {
+        let log_entry = if lsn.is_multiple_of(2) {
+            LogEntry::Update {
+                table: "patients".to_string(),
+                set_columns: vec!["name".to_string()],
+                set_values: vec![Value::String("Jane\tDoe".to_string())],
+                primary_key: PrimaryKey::Int(lsn as i64),
+                key_columns: vec!["id".to_string()],
+            }
+        } else {
+            LogEntry::RawSql {
+                sql: "DELETE FROM sessions WHERE expired = 1".to_string(),
+                affects_table: Some("sessions".to_string()),
+                database: Some("app".to_string()),
+                gtid: None,
+            }
+        };
+        WalEntry::new(lsn, 1, "node-1".to_string(), log_entry)
+    }
+
+    #[test]
+    fn test_audit_log_format() {
+        let dir = tempdir().unwrap();
+        let cfg = config(dir.path(), false);
+        let mut logger = AuditLogger::open(&cfg).unwrap();
+        let before = metrics::AUDIT_ENTRIES_WRITTEN.get();
+        for lsn in 1..=100 {
+            logger.log(&entry(lsn)).unwrap();
+        }
+        assert!(metrics::AUDIT_ENTRIES_WRITTEN.get() >= before + 100);
+
+        let content = std::fs::read_to_string(&cfg.log_file).unwrap();
+        let lines: Vec<Vec<&str>> = content.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines.len(), 100);
+        for (i, fields) in lines.iter().enumerate() {
+            let lsn = i as u64 + 1;
+            assert_eq!(fields.len(), 6);
+            assert!(chrono::DateTime::parse_from_rfc3339(fields[0]).is_ok());
+            assert_eq!(fields[1], "node-1");
+            assert_eq!(fields[2], lsn.to_string());
+            if lsn.is_multiple_of(2) {
+                assert_eq!(&fields[3..], ["patients", "update", &lsn.to_string()]);
+            } else {
+                assert_eq!(&fields[3..], ["app.sessions", "raw_sql", "-"]);
+            }
+        }
+        // No values leak without include_query_data
+        assert!(!content.contains("Jane"));
+    }
+
+    #[test]
+    fn test_audit_log_query_data_and_rotation() {
+        let dir = tempdir().unwrap();
+        let cfg = AuditConfig { max_file_mb: 1, ..config(dir.path(), true) };
+        let mut logger = AuditLogger::open(&cfg).unwrap();
+        logger.log(&entry(2)).unwrap();
+
+        let content = std::fs::read_to_string(&cfg.log_file).unwrap();
+        let fields: Vec<&str> = content.trim_end().split('\t').collect();
+        assert_eq!(fields.len(), 7);
+        assert!(fields[6].contains("Jane\\tDoe"));
+
+        // Fill past 1 MB; the full file is moved aside and a new one started
+        logger.size = cfg.max_file_mb * 1024 * 1024;
+        logger.log(&entry(4)).unwrap();
+        let files = std::fs::read_dir(cfg.log_file.parent().unwrap()).unwrap().count();
+        assert_eq!(files, 2);
+        assert_eq!(std::fs::read_to_string(&cfg.log_file).unwrap().lines().count(), 1);
+    }
+}
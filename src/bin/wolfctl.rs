@@ -534,7 +534,7 @@ struct ProxyConfig {
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct WalConfig {
-    batch_size: Option<usize>,
+    group_commit_max_size: Option<usize>,
     segment_size_mb: Option<usize>,
 }
 
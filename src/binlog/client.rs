@@ -1,36 +1,97 @@
 //! Binlog Client
 //!
-//! Connects to MariaDB as a replica and streams binlog events.
+//! Connects to MariaDB as a replica and streams binlog events. A single
+//! client writes straight to the WAL; with `[[binlog.sources]]` one client
+//! runs per source and forwards its events to the `BinlogMerger`.
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio::sync::mpsc;
 
-use crate::config::{DatabaseConfig, BinlogConfig};
+use crate::config::{DatabaseConfig, BinlogConfig, BinlogSourceConfig};
 use crate::wal::WalWriter;
 use crate::error::Result;
 
 use super::event::{parse_event, TableMap, BinlogEvent};
 use super::converter::{binlog_to_wal, should_replicate_query};
+use super::gtid::{Gtid, GtidSet};
+use super::merger::SourceEvent;
+
+/// Where a client's events go
+enum Sink {
+    /// Single source: convert events and append them straight to the WAL
+    Wal {
+        wal_writer: Arc<WalWriter>,
+        /// Directory where the last applied GTID position is persisted
+        state_dir: PathBuf,
+    },
+    /// One of several sources: forward raw events to the merger, which
+    /// persists this source's position at `state_path` once they are applied
+    Merger {
+        tx: mpsc::Sender<SourceEvent>,
+        state_path: PathBuf,
+    },
+}
 
 /// Binlog replication client
 pub struct BinlogClient {
-    db_config: DatabaseConfig,
-    binlog_config: BinlogConfig,
-    wal_writer: Arc<WalWriter>,
+    source: BinlogSourceConfig,
+    start_file: Option<String>,
+    start_position: Option<u64>,
+    sink: Sink,
 }
 
 impl BinlogClient {
-    /// Create a new binlog client
+    /// Create a client for the single `[database]` server, writing to the WAL
     pub fn new(
         db_config: DatabaseConfig,
         binlog_config: BinlogConfig,
         wal_writer: Arc<WalWriter>,
+        state_dir: PathBuf,
+    ) -> Self {
+        Self {
+            source: BinlogSourceConfig {
+                host: db_config.host,
+                port: db_config.port,
+                user: db_config.user,
+                password: db_config.password,
+                server_id: binlog_config.server_id,
+                start_gtid: None,
+            },
+            start_file: binlog_config.start_file,
+            start_position: binlog_config.start_position,
+            sink: Sink::Wal { wal_writer, state_dir },
+        }
+    }
+
+    /// Create a client for one of several sources, feeding a `BinlogMerger`
+    pub fn for_source(
+        source: BinlogSourceConfig,
+        tx: mpsc::Sender<SourceEvent>,
+        state_dir: PathBuf,
     ) -> Self {
+        let state_path = GtidSet::source_state_path(&state_dir, &source.name());
         Self {
-            db_config,
-            binlog_config,
-            wal_writer,
+            source,
+            start_file: None,
+            start_position: None,
+            sink: Sink::Merger { tx, state_path },
+        }
+    }
+
+    /// Position to resume from: the persisted GTIDs, else the configured start GTID
+    fn resume_position(&self) -> Result<GtidSet> {
+        match &self.sink {
+            Sink::Wal { state_dir, .. } => GtidSet::load(state_dir),
+            Sink::Merger { state_path, .. } => {
+                let saved = GtidSet::load_file(state_path)?;
+                match &self.source.start_gtid {
+                    Some(start) if saved.is_empty() => start.parse(),
+                    _ => Ok(saved),
+                }
+            }
         }
     }
     
@@ -38,13 +99,13 @@ impl BinlogClient {
     pub async fn start(&self) -> Result<()> {
         tracing::info!(
             "Starting binlog client, connecting to {}:{} as server_id {}",
-            self.db_config.host,
-            self.db_config.port,
-            self.binlog_config.server_id
+            self.source.host,
+            self.source.port,
+            self.source.server_id
         );
         
         // Connect to MariaDB
-        let addr = format!("{}:{}", self.db_config.host, self.db_config.port);
+        let addr = self.source.name();
         let mut stream = TcpStream::connect(&addr).await?;
         
         // Read the initial handshake packet
@@ -55,20 +116,34 @@ impl BinlogClient {
         // Parse handshake and authenticate
         self.authenticate(&mut stream, &buf[..n]).await?;
         
-        // Get current binlog position if not specified
-        let (binlog_file, binlog_pos) = self.get_binlog_position(&mut stream).await?;
+        // Resume from the saved GTID position if we have one
+        let mut gtid_set = self.resume_position()?;
         
-        tracing::info!("Starting binlog replication from {}:{}", binlog_file, binlog_pos);
+        // Ask the server to send GTID events instead of rewriting them as BEGIN
+        self.execute(&mut stream, "SET @mariadb_slave_capability=4").await?;
         
         // Register as a replica
-        self.register_slave(&mut stream).await?;
-        
-        // Start binlog dump
-        self.send_binlog_dump(&mut stream, &binlog_file, binlog_pos).await?;
+        let mut current_file = if gtid_set.is_empty() {
+            // Get current binlog position if not specified
+            let (binlog_file, binlog_pos) = self.get_binlog_position(&mut stream).await?;
+            
+            tracing::info!("Starting binlog replication from {}:{}", binlog_file, binlog_pos);
+            
+            self.register_slave(&mut stream).await?;
+            self.send_binlog_dump(&mut stream, &binlog_file, binlog_pos).await?;
+            binlog_file
+        } else {
+            tracing::info!("Starting binlog replication from GTID {}", gtid_set);
+            
+            self.register_slave(&mut stream).await?;
+            self.send_binlog_dump_gtid(&mut stream, &gtid_set).await?;
+            String::new()
+        };
         
         // Process binlog events
         let mut table_map = TableMap::new();
-        let mut current_file = binlog_file;
+        let mut current_gtid: Option<Gtid> = None;
+        let mut in_transaction = false;
         
         loop {
             // Read packet length (3 bytes) + sequence (1 byte)
@@ -98,7 +173,14 @@ impl BinlogClient {
                     if packet.len() > 1 {
                         match parse_event(&packet[1..]) {
                             Ok(event) => {
-                                self.handle_event(event, &mut table_map, &mut current_file).await?;
+                                self.handle_event(
+                                    event,
+                                    &mut table_map,
+                                    &mut current_file,
+                                    &mut current_gtid,
+                                    &mut in_transaction,
+                                    &mut gtid_set,
+                                ).await?;
                             }
                             Err(e) => {
                                 tracing::warn!("Failed to parse event: {}", e);
@@ -162,18 +244,18 @@ impl BinlogClient {
         response.extend_from_slice(&[0u8; 23]);
         
         // Username (null-terminated)
-        response.extend_from_slice(self.db_config.user.as_bytes());
+        response.extend_from_slice(self.source.user.as_bytes());
         response.push(0);
         
         // Auth response (length-prefixed)
         // For native password: SHA1(password) XOR SHA1(auth_data + SHA1(SHA1(password)))
         // Simplified: just send empty for now if no password
-        if self.db_config.password.is_empty() {
+        if self.source.password.is_empty() {
             response.push(0);
         } else {
             // Simple password auth - this is a placeholder
             // A full implementation would compute the auth hash
-            let scramble = self.scramble_password(&self.db_config.password, auth_data_1);
+            let scramble = self.scramble_password(&self.source.password, auth_data_1);
             response.push(scramble.len() as u8);
             response.extend_from_slice(&scramble);
         }
@@ -225,7 +307,7 @@ impl BinlogClient {
     
     async fn get_binlog_position(&self, stream: &mut TcpStream) -> Result<(String, u64)> {
         // Check config first - if user specified position, use it
-        if let (Some(file), Some(pos)) = (&self.binlog_config.start_file, self.binlog_config.start_position) {
+        if let (Some(file), Some(pos)) = (&self.start_file, self.start_position) {
             tracing::info!("Using configured binlog position: {}:{}", file, pos);
             return Ok((file.clone(), pos));
         }
@@ -262,9 +344,9 @@ impl BinlogClient {
         }
         
         // Fallback if parsing fails - use config values or defaults
-        let file = self.binlog_config.start_file.clone()
+        let file = self.start_file.clone()
             .unwrap_or_else(|| "mysql-bin.000001".to_string());
-        let pos = self.binlog_config.start_position.unwrap_or(4);
+        let pos = self.start_position.unwrap_or(4);
         
         tracing::warn!("Could not parse SHOW MASTER STATUS, using defaults: {}:{}", file, pos);
         Ok((file, pos))
@@ -438,9 +520,23 @@ impl BinlogClient {
         Ok(())
     }
     
+    /// Run a statement and wait for its OK/ERR response
+    async fn execute(&self, stream: &mut TcpStream, query: &str) -> Result<()> {
+        self.send_query(stream, query).await?;
+        
+        let mut buf = [0u8; 1024];
+        let n = stream.read(&mut buf).await?;
+        
+        if n > 4 && buf[4] == 0xFF {
+            let error_msg = String::from_utf8_lossy(&buf[13.min(n)..n]);
+            return Err(crate::Error::Network(format!("{} failed: {}", query, error_msg)));
+        }
+        Ok(())
+    }
+    
     async fn register_slave(&self, stream: &mut TcpStream) -> Result<()> {
         // COM_REGISTER_SLAVE
-        let server_id = self.binlog_config.server_id;
+        let server_id = self.source.server_id;
         
         let mut payload = Vec::new();
         payload.push(0x15); // COM_REGISTER_SLAVE
@@ -479,7 +575,7 @@ impl BinlogClient {
     
     async fn send_binlog_dump(&self, stream: &mut TcpStream, file: &str, position: u64) -> Result<()> {
         // COM_BINLOG_DUMP
-        let server_id = self.binlog_config.server_id;
+        let server_id = self.source.server_id;
         
         let mut payload = Vec::new();
         payload.push(0x12); // COM_BINLOG_DUMP
@@ -502,12 +598,43 @@ impl BinlogClient {
         Ok(())
     }
     
+    /// Start a binlog dump from a GTID position
+    ///
+    /// MariaDB's equivalent of COM_BINLOG_DUMP_GTID: the position is passed in
+    /// `@slave_connect_state` and a regular COM_BINLOG_DUMP with an empty file
+    /// name tells the server to locate the start point from the GTIDs.
+    async fn send_binlog_dump_gtid(&self, stream: &mut TcpStream, gtid_set: &GtidSet) -> Result<()> {
+        self.execute(stream, &format!("SET @slave_connect_state='{}'", gtid_set)).await?;
+        self.execute(stream, "SET @slave_gtid_strict_mode=0").await?;
+        self.execute(stream, "SET @slave_gtid_ignore_duplicates=0").await?;
+        
+        self.send_binlog_dump(stream, "", 4).await?;
+        
+        tracing::debug!("Sent GTID binlog dump from {}", gtid_set);
+        Ok(())
+    }
+    
+    /// Mark the current event group as applied and persist the position
+    fn record_applied(&self, current_gtid: &Option<Gtid>, gtid_set: &mut GtidSet) {
+        let Sink::Wal { state_dir, .. } = &self.sink else { return };
+        if let Some(gtid) = current_gtid {
+            gtid_set.update(*gtid);
+            if let Err(e) = gtid_set.save(state_dir) {
+                tracing::error!("Failed to persist binlog GTID {}: {}", gtid, e);
+            }
+        }
+    }
+    
     async fn handle_event(
         &self,
         event: BinlogEvent,
         table_map: &mut TableMap,
         current_file: &mut String,
+        current_gtid: &mut Option<Gtid>,
+        in_transaction: &mut bool,
+        gtid_set: &mut GtidSet,
     ) -> Result<()> {
+        let ends_group = ends_group(&event, in_transaction);
         match &event {
             BinlogEvent::TableMap { table_id, database, table, column_count } => {
                 table_map.insert(*table_id, database.clone(), table.clone(), *column_count);
@@ -534,21 +661,70 @@ impl BinlogClient {
                 tracing::trace!("Transaction committed: XID {}", xid);
             }
             
+            BinlogEvent::Gtid { domain_id, server_id, sequence } => {
+                let gtid = Gtid::new(*domain_id, *server_id, *sequence);
+                tracing::trace!("GTID {}", gtid);
+                *current_gtid = Some(gtid);
+            }
+            
             _ => {}
         }
         
+        let wal_writer = match &self.sink {
+            Sink::Wal { wal_writer, .. } => wal_writer,
+            Sink::Merger { tx, .. } => {
+                // The merger keeps its own table map and GTID tracking per source
+                let event = SourceEvent { source: self.source.name(), event };
+                return tx.send(event).await.map_err(|_| {
+                    crate::Error::Replication("Binlog merger stopped".to_string())
+                });
+            }
+        };
+
         // Convert to WAL entry
-        if let Some(entry) = binlog_to_wal(event, table_map) {
-            match self.wal_writer.append(entry).await {
+        if let Some(entry) = binlog_to_wal(event, table_map, current_gtid.as_ref()) {
+            match wal_writer.append(entry).await {
                 Ok(lsn) => {
                     tracing::debug!("Wrote binlog event to WAL with LSN {}", lsn);
                 }
                 Err(e) => {
                     tracing::error!("Failed to write to WAL: {}", e);
+                    return Ok(());
                 }
             }
         }
+
+        // Only persist at the end of a group: resuming from the middle of a
+        // transaction would skip the rows not yet written
+        if ends_group {
+            self.record_applied(current_gtid, gtid_set);
+        }
         
         Ok(())
     }
 }
+
+/// Whether `event` completes its event group: the XID, COMMIT or ROLLBACK
+/// of a transaction, or a statement logged without BEGIN (such as DDL).
+/// `in_transaction` tracks whether the current group opened with BEGIN.
+pub(super) fn ends_group(event: &BinlogEvent, in_transaction: &mut bool) -> bool {
+    match event {
+        BinlogEvent::Gtid { .. } => {
+            *in_transaction = false;
+            false
+        }
+        BinlogEvent::Xid { .. } => true,
+        BinlogEvent::Query { query, .. } => {
+            let upper = query.trim_start().to_uppercase();
+            if upper.starts_with("BEGIN") {
+                *in_transaction = true;
+                false
+            } else if upper.starts_with("ROLLBACK TO") {
+                false
+            } else {
+                upper.starts_with("COMMIT") || upper.starts_with("ROLLBACK") || !*in_transaction
+            }
+        }
+        _ => false,
+    }
+}
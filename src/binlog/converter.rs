@@ -3,10 +3,14 @@
 //! Converts binlog events to WolfScale WAL entries.
 
 use super::event::{BinlogEvent, TableMap};
+use super::gtid::Gtid;
 use crate::wal::LogEntry;
 
 /// Convert a binlog event to a WAL LogEntry
-pub fn binlog_to_wal(event: BinlogEvent, table_map: &TableMap) -> Option<LogEntry> {
+///
+/// `gtid` is the GTID of the event group the event belongs to (the most
+/// recent GTID event seen on the stream) and is recorded on the entry.
+pub fn binlog_to_wal(event: BinlogEvent, table_map: &TableMap, gtid: Option<&Gtid>) -> Option<LogEntry> {
     match event {
         BinlogEvent::Query { database, query } => {
             // Skip internal queries
@@ -28,6 +32,7 @@ pub fn binlog_to_wal(event: BinlogEvent, table_map: &TableMap) -> Option<LogEntr
                 sql: query,
                 database: Some(database),
                 affects_table: None,
+                gtid: gtid.map(|g| g.to_string()),
             })
         }
         
@@ -144,6 +144,7 @@ pub fn parse_event(data: &[u8]) -> Result<BinlogEvent, String> {
     // 2 bytes: flags
     
     let type_code = data[4];
+    let server_id = u32::from_le_bytes([data[5], data[6], data[7], data[8]]);
     let event_length = u32::from_le_bytes([data[9], data[10], data[11], data[12]]) as usize;
     
     if data.len() < event_length {
@@ -167,7 +168,7 @@ pub fn parse_event(data: &[u8]) -> Result<BinlogEvent, String> {
         event_type::ROTATE_EVENT => parse_rotate_event(payload),
         event_type::FORMAT_DESCRIPTION_EVENT => parse_format_description_event(payload),
         event_type::XID_EVENT => parse_xid_event(payload),
-        event_type::MARIADB_GTID_EVENT => parse_mariadb_gtid_event(payload),
+        event_type::MARIADB_GTID_EVENT => parse_mariadb_gtid_event(payload, server_id),
         _ => Ok(BinlogEvent::Unknown { type_code }),
     }
 }
@@ -324,18 +325,18 @@ fn parse_xid_event(data: &[u8]) -> Result<BinlogEvent, String> {
     Ok(BinlogEvent::Xid { xid })
 }
 
-fn parse_mariadb_gtid_event(data: &[u8]) -> Result<BinlogEvent, String> {
+fn parse_mariadb_gtid_event(data: &[u8], server_id: u32) -> Result<BinlogEvent, String> {
     if data.len() < 13 {
         return Err("GTID event too short".to_string());
     }
     
     let sequence = u64::from_le_bytes([data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7]]);
     let domain_id = u32::from_le_bytes([data[8], data[9], data[10], data[11]]);
-    // Server ID is in the event header, not here
+    // Server ID comes from the event header
     
     Ok(BinlogEvent::Gtid {
         domain_id,
-        server_id: 0, // Would need to be extracted from header
+        server_id,
         sequence,
     })
 }
@@ -0,0 +1,476 @@
+//! Multi-Source Binlog Merger
+//!
+//! Combines the binlog streams of several MariaDB servers into one WAL.
+//! Galera nodes log the same transactions under the same GTIDs, and
+//! independent masters log under their own replication domains, so a GTID's
+//! domain and sequence number identify a transaction:
+//!
+//! - a transaction whose sequence number was already written for its domain
+//!   is a duplicate and is skipped (like MariaDB's `gtid_ignore_duplicates`)
+//! - completed transactions are written in sequence order per domain; one
+//!   that skips ahead waits until the gap is filled, every source has moved
+//!   past it, or the reorder window expires
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+use crate::error::Result;
+use crate::metrics;
+use crate::wal::{LogEntry, Lsn, WalWriter};
+
+use super::converter::binlog_to_wal;
+use super::event::{BinlogEvent, TableMap};
+use super::gtid::{Gtid, GtidSet};
+
+/// How long a transaction waits for a lower sequence number before it is written anyway
+pub const DEFAULT_REORDER_WINDOW: Duration = Duration::from_secs(2);
+
+/// Written sequence numbers remembered per domain, to tell late arrivals from duplicates
+const WRITTEN_HISTORY: usize = 10_000;
+
+/// A binlog event tagged with the source (`host:port`) it was read from
+#[derive(Debug)]
+pub struct SourceEvent {
+    pub source: String,
+    pub event: BinlogEvent,
+}
+
+/// Transaction being read from one source
+struct OpenTxn {
+    gtid: Gtid,
+    /// Started with BEGIN, so it ends at XID/COMMIT rather than after one statement
+    explicit: bool,
+    entries: Vec<LogEntry>,
+}
+
+/// Per-source stream state
+struct SourceState {
+    table_map: TableMap,
+    open: Option<OpenTxn>,
+    /// Highest completed sequence number per domain
+    high: HashMap<u32, u64>,
+    /// Everything from this source that has been written or skipped (persisted)
+    position: GtidSet,
+    /// LSN holding this source's latest transaction
+    last_lsn: Option<Lsn>,
+}
+
+/// Completed transaction waiting for its turn
+struct Pending {
+    gtid: Gtid,
+    /// Sources that delivered this GTID
+    sources: Vec<String>,
+    entries: Vec<LogEntry>,
+    since: Instant,
+}
+
+/// Merges events from several `BinlogClient`s into the WAL
+pub struct BinlogMerger {
+    rx: mpsc::Receiver<SourceEvent>,
+    wal_writer: Arc<WalWriter>,
+    state_dir: PathBuf,
+    reorder_window: Duration,
+    sources: HashMap<String, SourceState>,
+    /// Merged position: last GTID written per domain (persisted)
+    applied: GtidSet,
+    /// Completed transactions per domain, ordered by sequence number
+    pending: HashMap<u32, BTreeMap<u64, Pending>>,
+    /// Recently written sequence numbers per domain and the LSN they landed at
+    written: HashMap<u32, BTreeMap<u64, Lsn>>,
+    last_lsn: Lsn,
+}
+
+impl BinlogMerger {
+    /// Create a merger for the named sources (`host:port`), resuming from the
+    /// merged position in `state_dir`
+    pub async fn new(
+        rx: mpsc::Receiver<SourceEvent>,
+        sources: &[String],
+        wal_writer: Arc<WalWriter>,
+        state_dir: PathBuf,
+    ) -> Result<Self> {
+        let mut states = HashMap::new();
+        for name in sources {
+            let position = GtidSet::load_file(&GtidSet::source_state_path(&state_dir, name))?;
+            states.insert(name.clone(), SourceState {
+                table_map: TableMap::new(),
+                open: None,
+                high: HashMap::new(),
+                position,
+                last_lsn: None,
+            });
+        }
+
+        Ok(Self {
+            rx,
+            applied: GtidSet::load(&state_dir)?,
+            last_lsn: wal_writer.current_lsn().await,
+            wal_writer,
+            state_dir,
+            reorder_window: DEFAULT_REORDER_WINDOW,
+            sources: states,
+            pending: HashMap::new(),
+            written: HashMap::new(),
+        })
+    }
+
+    /// Set how long an out-of-order transaction waits for the gap before it
+    pub fn with_reorder_window(mut self, window: Duration) -> Self {
+        self.reorder_window = window;
+        self
+    }
+
+    /// LSNs written since this source's latest transaction (None until it delivers one)
+    pub fn source_lag(&self, source: &str) -> Option<u64> {
+        let state = self.sources.get(source)?;
+        state.last_lsn.map(|lsn| self.last_lsn.saturating_sub(lsn))
+    }
+
+    /// Merge until every client has dropped its sender, then write what is left
+    pub async fn run(mut self) -> Result<()> {
+        let tick = (self.reorder_window / 2).max(Duration::from_millis(100));
+        let mut interval = tokio::time::interval(tick);
+
+        loop {
+            tokio::select! {
+                received = self.rx.recv() => match received {
+                    Some(event) => self.handle(event).await?,
+                    None => break,
+                },
+                _ = interval.tick() => self.release_expired().await?,
+            }
+        }
+
+        let domains: Vec<u32> = self.pending.keys().copied().collect();
+        for domain in domains {
+            while self.write_head(domain).await? {}
+        }
+        Ok(())
+    }
+
+    async fn handle(&mut self, event: SourceEvent) -> Result<()> {
+        let SourceEvent { source, event } = event;
+        let Some(state) = self.sources.get_mut(&source) else {
+            tracing::warn!("Ignoring binlog event from unknown source {}", source);
+            return Ok(());
+        };
+
+        let ends_txn = match &event {
+            BinlogEvent::TableMap { table_id, database, table, column_count } => {
+                state.table_map.insert(*table_id, database.clone(), table.clone(), *column_count);
+                false
+            }
+            BinlogEvent::Gtid { domain_id, server_id, sequence } => {
+                // A new group implicitly ends one that never saw its commit
+                if let Some(txn) = state.open.take() {
+                    let gtid = txn.gtid;
+                    self.complete(&source, txn).await?;
+                    self.release(gtid.domain_id).await?;
+                }
+                let state = self.sources.get_mut(&source).expect("source checked above");
+                state.open = Some(OpenTxn {
+                    gtid: Gtid::new(*domain_id, *server_id, *sequence),
+                    explicit: false,
+                    entries: Vec::new(),
+                });
+                return Ok(());
+            }
+            BinlogEvent::Xid { .. } => true,
+            BinlogEvent::Query { query, .. } => {
+                let upper = query.trim_start().to_uppercase();
+                if upper.starts_with("BEGIN") {
+                    if let Some(txn) = state.open.as_mut() {
+                        txn.explicit = true;
+                    }
+                    false
+                } else {
+                    upper.starts_with("COMMIT") || upper.starts_with("ROLLBACK")
+                        || state.open.as_ref().is_some_and(|txn| !txn.explicit)
+                }
+            }
+            _ => false,
+        };
+
+        let gtid = state.open.as_ref().map(|txn| txn.gtid);
+        if let Some(entry) = binlog_to_wal(event, &state.table_map, gtid.as_ref()) {
+            match state.open.as_mut() {
+                Some(txn) => txn.entries.push(entry),
+                None => {
+                    // No GTID to order or deduplicate by: write straight through
+                    self.write(vec![entry]).await?;
+                }
+            }
+        }
+
+        if ends_txn {
+            let state = self.sources.get_mut(&source).expect("source checked above");
+            if let Some(txn) = state.open.take() {
+                let domain = txn.gtid.domain_id;
+                self.complete(&source, txn).await?;
+                self.release(domain).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue a finished transaction, or skip it if another source already delivered it
+    async fn complete(&mut self, source: &str, txn: OpenTxn) -> Result<()> {
+        let gtid = txn.gtid;
+        if let Some(state) = self.sources.get_mut(source) {
+            let high = state.high.entry(gtid.domain_id).or_insert(0);
+            *high = (*high).max(gtid.sequence);
+        }
+
+        let queue = self.pending.entry(gtid.domain_id).or_default();
+        if let Some(pending) = queue.get_mut(&gtid.sequence) {
+            if !pending.sources.iter().any(|s| s == source) {
+                pending.sources.push(source.to_string());
+            }
+            return Ok(());
+        }
+
+        if self.applied.get(gtid.domain_id).is_some_and(|a| gtid.sequence <= a.sequence) {
+            let written = self.written.get(&gtid.domain_id);
+            let landed = written.and_then(|w| w.get(&gtid.sequence)).copied();
+            let in_history = written
+                .and_then(|w| w.keys().next())
+                .is_some_and(|oldest| gtid.sequence > *oldest);
+
+            if landed.is_some() || !in_history {
+                tracing::trace!("Skipping GTID {} from {}: already applied", gtid, source);
+                self.mark_source(source, gtid, landed)?;
+                return Ok(());
+            }
+            // Its slot was given up after the reorder window: better late than lost
+            tracing::warn!("GTID {} from {} arrived after later transactions were written", gtid, source);
+        }
+
+        queue.insert(gtid.sequence, Pending {
+            gtid,
+            sources: vec![source.to_string()],
+            entries: txn.entries,
+            since: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Write queued transactions of a domain that can no longer be preceded by another
+    async fn release(&mut self, domain: u32) -> Result<()> {
+        loop {
+            let Some(head) = self.pending.get(&domain).and_then(|q| q.keys().next().copied()) else {
+                return Ok(());
+            };
+            let contiguous = self.applied.get(domain).is_some_and(|a| head <= a.sequence + 1);
+            // Every source has completed a transaction at or past the head
+            let all_passed = self.sources.values()
+                .all(|s| s.high.get(&domain).is_some_and(|h| *h >= head));
+            if !contiguous && !all_passed {
+                return Ok(());
+            }
+            self.write_head(domain).await?;
+        }
+    }
+
+    /// Stop waiting for gaps older than the reorder window
+    async fn release_expired(&mut self) -> Result<()> {
+        let domains: Vec<u32> = self.pending.keys().copied().collect();
+        for domain in domains {
+            while self.pending.get(&domain)
+                .and_then(|q| q.values().next())
+                .is_some_and(|p| p.since.elapsed() >= self.reorder_window)
+            {
+                let head = self.pending[&domain].values().next().map(|p| p.gtid);
+                if let Some(gtid) = head {
+                    tracing::warn!("Writing GTID {} without its predecessor after {:?}", gtid, self.reorder_window);
+                }
+                self.write_head(domain).await?;
+            }
+            self.release(domain).await?;
+        }
+        Ok(())
+    }
+
+    /// Write the lowest queued transaction of a domain; false if none is queued
+    async fn write_head(&mut self, domain: u32) -> Result<bool> {
+        let Some(queue) = self.pending.get_mut(&domain) else {
+            return Ok(false);
+        };
+        let Some((_, pending)) = queue.pop_first() else {
+            return Ok(false);
+        };
+        if queue.is_empty() {
+            self.pending.remove(&domain);
+        }
+
+        let gtid = pending.gtid;
+        let lsn = self.write(pending.entries).await?;
+
+        if self.applied.get(domain).is_none_or(|a| gtid.sequence > a.sequence) {
+            self.applied.update(gtid);
+            self.applied.save(&self.state_dir)?;
+        }
+        let history = self.written.entry(domain).or_default();
+        history.insert(gtid.sequence, lsn);
+        while history.len() > WRITTEN_HISTORY {
+            history.pop_first();
+        }
+        for source in &pending.sources {
+            self.mark_source(source, gtid, Some(lsn))?;
+        }
+        Ok(true)
+    }
+
+    /// Append entries to the WAL; returns the LSN of the last one
+    async fn write(&mut self, entries: Vec<LogEntry>) -> Result<Lsn> {
+        for lsn in self.wal_writer.append_batch(entries).await? {
+            tracing::debug!("Wrote merged binlog event to WAL with LSN {}", lsn);
+            self.last_lsn = lsn;
+        }
+        self.update_lag_metrics();
+        Ok(self.last_lsn)
+    }
+
+    /// Advance and persist a source's position once its transaction is in the WAL
+    fn mark_source(&mut self, source: &str, gtid: Gtid, lsn: Option<Lsn>) -> Result<()> {
+        let Some(state) = self.sources.get_mut(source) else {
+            return Ok(());
+        };
+        if lsn.is_some() {
+            state.last_lsn = lsn;
+        }
+        if state.position.get(gtid.domain_id).is_none_or(|p| gtid.sequence > p.sequence) {
+            state.position.update(gtid);
+            state.position.save_file(&GtidSet::source_state_path(&self.state_dir, source))?;
+        }
+        self.update_lag_metrics();
+        Ok(())
+    }
+
+    fn update_lag_metrics(&self) {
+        for (name, state) in &self.sources {
+            if let Some(lsn) = state.last_lsn {
+                metrics::BINLOG_SOURCE_LAG
+                    .with_label_values(&[name])
+                    .set(self.last_lsn.saturating_sub(lsn) as i64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WalConfig;
+    use crate::wal::WalReader;
+
+    fn wal_config() -> WalConfig {
+        WalConfig {
+            flush_interval_ms: 100,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
+            compression: false,
+            segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
+            retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
+            fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
+        }
+    }
+
+    /// Events of one autocommit statement as MariaDB logs it
+    fn txn(source: &str, domain: u32, seq: u64) -> Vec<SourceEvent> {
+        let event = |event| SourceEvent { source: source.to_string(), event };
+        vec![
+            event(BinlogEvent::Gtid { domain_id: domain, server_id: 1, sequence: seq }),
+            event(BinlogEvent::Query { database: "app".into(), query: "BEGIN".into() }),
+            event(BinlogEvent::Query {
+                database: "app".into(),
+                query: format!("INSERT INTO t VALUES ({})", seq),
+            }),
+            event(BinlogEvent::Xid { xid: seq }),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_merge_deduplicates_and_orders() {
+        let dir = tempfile::tempdir().unwrap();
+        let wal_dir = dir.path().join("wal");
+        let writer = Arc::new(WalWriter::new(wal_dir.clone(), wal_config(), "test-node".into()).await.unwrap());
+        let sources = vec!["db1:3306".to_string(), "db2:3306".to_string()];
+
+        let (tx, rx) = mpsc::channel(64);
+        let merger = BinlogMerger::new(rx, &sources, writer.clone(), dir.path().to_path_buf())
+            .await
+            .unwrap()
+            .with_reorder_window(Duration::from_secs(60));
+
+        // db1 is ahead and misses 0-1-3; db2 repeats what db1 already sent
+        let stream = [
+            txn("db1:3306", 0, 1), txn("db2:3306", 0, 1),
+            txn("db1:3306", 0, 2), txn("db1:3306", 0, 4),
+            txn("db2:3306", 1, 7),
+            txn("db2:3306", 0, 3), txn("db2:3306", 0, 4),
+        ];
+        for event in stream.into_iter().flatten() {
+            tx.send(event).await.unwrap();
+        }
+        drop(tx);
+        merger.run().await.unwrap();
+        writer.flush().await.unwrap();
+
+        let reader = WalReader::new(wal_dir, 1, false).unwrap();
+        let written: Vec<(String, String)> = reader.read_from(1).unwrap().into_iter().map(|e| match e.entry {
+            LogEntry::RawSql { sql, gtid, .. } => (gtid.unwrap(), sql),
+            other => panic!("unexpected entry {:?}", other),
+        }).collect();
+
+        let domain0: Vec<&str> = written.iter()
+            .filter(|(g, _)| g.starts_with("0-"))
+            .map(|(g, _)| g.as_str())
+            .collect();
+        assert_eq!(domain0, vec!["0-1-1", "0-1-2", "0-1-3", "0-1-4"]);
+        assert!(written.iter().any(|(g, sql)| g == "1-1-7" && sql == "INSERT INTO t VALUES (7)"));
+        assert_eq!(written.len(), 5);
+
+        let state = dir.path();
+        assert_eq!(GtidSet::load(state).unwrap().to_string(), "0-1-4,1-1-7");
+        let db2 = GtidSet::load_file(&GtidSet::source_state_path(state, "db2:3306")).unwrap();
+        assert_eq!(db2.to_string(), "0-1-4,1-1-7");
+        let db1 = GtidSet::load_file(&GtidSet::source_state_path(state, "db1:3306")).unwrap();
+        assert_eq!(db1.to_string(), "0-1-4");
+    }
+
+    #[tokio::test]
+    async fn test_source_lag() {
+        let dir = tempfile::tempdir().unwrap();
+        let writer = Arc::new(WalWriter::new(dir.path().join("wal"), wal_config(), "test-node".into()).await.unwrap());
+        let sources = vec!["db1:3306".to_string(), "db2:3306".to_string()];
+        let (_tx, rx) = mpsc::channel(64);
+        let mut merger = BinlogMerger::new(rx, &sources, writer, dir.path().to_path_buf()).await.unwrap();
+
+        for seq in 1..=5 {
+            for event in txn("db1:3306", 0, seq) {
+                merger.handle(event).await.unwrap();
+            }
+            if seq <= 2 {
+                for event in txn("db2:3306", 0, seq) {
+                    merger.handle(event).await.unwrap();
+                }
+            }
+        }
+
+        // db2 stopped at 0-1-2 while db1 went on to 0-1-5
+        assert_eq!(merger.source_lag("db1:3306"), Some(0));
+        assert_eq!(merger.source_lag("db2:3306"), Some(3));
+        assert_eq!(merger.source_lag("db3:3306"), None);
+    }
+}
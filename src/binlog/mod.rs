@@ -3,12 +3,17 @@
 //! This module provides support for reading MariaDB binary logs and converting
 //! them to WAL entries for replication. This enables WolfScale to capture
 //! writes from external MariaDB/Galera clusters without requiring writes to
-//! go through the WolfScale proxy.
+//! go through the WolfScale proxy. Several servers can be read at once, with
+//! their streams merged by GTID.
 
 mod client;
 mod event;
 mod converter;
+pub mod gtid;
+mod merger;
 
 pub use client::BinlogClient;
 pub use event::BinlogEvent;
 pub use converter::binlog_to_wal;
+pub use gtid::{Gtid, GtidSet};
+pub use merger::{BinlogMerger, SourceEvent};
@@ -45,6 +45,22 @@ pub struct WolfScaleConfig {
     /// Performance auto-tuning configuration
     #[serde(default)]
     pub performance: PerformanceConfig,
+
+    /// Distributed tracing configuration (requires the `otel` feature)
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// Audit log of every WAL write
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Replication executor configuration
+    #[serde(default)]
+    pub executor: ExecutorConfig,
+
+    /// Change data capture to Kafka (requires the `kafka` feature)
+    #[serde(default)]
+    pub kafka: KafkaConfig,
 }
 
 /// Node-specific configuration
@@ -92,19 +108,34 @@ pub struct DatabaseConfig {
     /// Connection timeout in seconds
     #[serde(default = "default_connect_timeout")]
     pub connect_timeout_secs: u64,
+
+    /// How often the connection pool is pinged with `SELECT 1`, in seconds
+    /// (0 = never)
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval_secs: u64,
+
+    /// Connections older than this are closed and replaced, in seconds
+    #[serde(default = "default_max_lifetime")]
+    pub max_lifetime_secs: u64,
 }
 
 /// Write-Ahead Log configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalConfig {
-    /// Number of entries to batch before flushing
-    #[serde(default = "default_batch_size")]
-    pub batch_size: usize,
-
-    /// Flush interval in milliseconds
+    /// How often an idle writer checks whether its segment is due for
+    /// rotation, in milliseconds
     #[serde(default = "default_flush_interval_ms")]
     pub flush_interval_ms: u64,
 
+    /// Longest an appended entry waits for others to share its flush, in
+    /// microseconds
+    #[serde(default = "default_group_commit_max_delay_us")]
+    pub group_commit_max_delay_us: u64,
+
+    /// Entries that trigger a flush without waiting for the delay
+    #[serde(default = "default_group_commit_max_size")]
+    pub group_commit_max_size: usize,
+
     /// Enable LZ4 compression for WAL entries
     #[serde(default = "default_compression")]
     pub compression: bool,
@@ -113,13 +144,44 @@ pub struct WalConfig {
     #[serde(default = "default_segment_size_mb")]
     pub segment_size_mb: u64,
 
+    /// Rotate to a new segment once the current one is this old (0 = size only)
+    #[serde(default = "default_max_segment_age_secs")]
+    pub max_segment_age_secs: u64,
+
+    /// Entries a segment must hold before it is rotated for age
+    #[serde(default = "default_min_segment_entries")]
+    pub min_segment_entries: u64,
+
     /// Retention period in hours (0 = infinite)
     #[serde(default)]
     pub retention_hours: u64,
 
-    /// Use fsync for durability (slower but safer)
+    /// Keep expired segments until the slowest follower has applied them
+    #[serde(default = "default_true")]
+    pub min_retention_follower_lsn: bool,
+
+    /// Delete expired segments after this many hours even if a follower
+    /// still needs them (0 = never)
+    #[serde(default = "default_max_retention_override_hours")]
+    pub max_retention_override_hours: u64,
+
+    /// Fsync each group commit before acknowledging it (slower but safer).
+    /// When off, entries are acknowledged once written to the page cache.
     #[serde(default = "default_fsync")]
     pub fsync: bool,
+
+    /// Recover from an entry left half-written by a crash by truncating it,
+    /// instead of failing replay with a corruption error
+    #[serde(default = "default_true")]
+    pub torn_write_detection: bool,
+
+    /// Periodically compact sealed segments on the leader
+    #[serde(default)]
+    pub compaction_enabled: bool,
+
+    /// Number of sealed segments that triggers a background compaction
+    #[serde(default = "default_compaction_threshold_segments")]
+    pub compaction_threshold_segments: usize,
 }
 
 /// Cluster configuration
@@ -178,6 +240,77 @@ pub struct ClusterConfig {
     /// Disable automatic leader election (require manual promotion)
     #[serde(default)]
     pub disable_auto_election: bool,
+
+    /// Number of concurrent workers a follower uses to apply a replication batch.
+    /// Entries for different tables are applied in parallel; entries for the
+    /// same table (and entries with no known table) keep their WAL order.
+    /// Set to 1 to apply strictly serially, e.g. when foreign keys rely on
+    /// cross-table ordering.
+    #[serde(default = "default_parallel_apply_workers")]
+    pub parallel_apply_workers: usize,
+
+    /// How long a strong (`consistency=strong`) or session read waits for
+    /// followers, or this node, to reach the required LSN before failing
+    #[serde(default = "default_read_barrier_timeout_ms")]
+    pub read_barrier_timeout_ms: u64,
+
+    /// A node added with `POST /admin/add-node` only has its acknowledgements
+    /// counted towards quorum once its applied LSN is within this many
+    /// entries of the leader's commit LSN
+    #[serde(default = "default_max_lag_lsn")]
+    pub max_lag_lsn: u64,
+
+    /// AppendEntries batches the leader keeps in flight to each follower
+    /// before waiting for an ACK. 1 sends the next batch only once the
+    /// previous one is acknowledged; higher values pipeline batches on
+    /// high-latency links.
+    #[serde(default = "default_pipeline_depth")]
+    pub pipeline_depth: usize,
+
+    /// Treat every follower as a cross-region peer: instead of a batch per
+    /// write, the leader collects entries for `wan_batch_timeout_ms` and
+    /// sends them as one large batch
+    #[serde(default)]
+    pub wan_mode: bool,
+
+    /// How long the leader collects entries for a WAN peer before sending
+    #[serde(default = "default_wan_batch_timeout_ms")]
+    pub wan_batch_timeout_ms: u64,
+
+    /// Followers whose measured AppendEntries round trip exceeds this are
+    /// handled as WAN peers even without `wan_mode` (0 = never)
+    #[serde(default = "default_wan_rtt_threshold_ms")]
+    pub wan_rtt_threshold_ms: u64,
+
+    /// Gzip AppendEntries batches on the wire. Every node must run a
+    /// version that understands compressed frames before enabling this.
+    #[serde(default)]
+    pub wan_compression: bool,
+
+    /// Wire protocol version this node sends. Set to 0 during a rolling upgrade
+    /// from a release without protocol versioning, and back to the default
+    /// once every node is upgraded. Nodes read every version up to their own.
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u8,
+
+    /// Writes per second the leader accepts across all connections
+    /// (0 = unlimited)
+    #[serde(default = "default_max_writes_per_second")]
+    pub max_writes_per_second: u64,
+
+    /// What happens to writes over `max_writes_per_second`: "reject" fails
+    /// them with HTTP 429, "delay" holds them until the rate allows
+    #[serde(default = "default_rate_limit_action")]
+    pub rate_limit_action: String,
+
+    /// POST leadership and membership changes to this URL as JSON
+    #[serde(default)]
+    pub event_webhook_url: Option<String>,
+
+    /// Events sent to `event_webhook_url`: any of "leader_elected",
+    /// "leader_lost", "follower_joined", "follower_lost" (default: all)
+    #[serde(default = "default_event_types")]
+    pub event_types: Vec<String>,
 }
 
 /// API configuration
@@ -194,6 +327,72 @@ pub struct ApiConfig {
     /// Enable CORS
     #[serde(default)]
     pub cors_enabled: bool,
+
+    /// Serve read-only `POST /query` on followers (for analytics)
+    #[serde(default)]
+    pub allow_follower_reads: bool,
+
+    /// Longest a follower query may run before it is abandoned
+    #[serde(default = "default_follower_read_timeout_secs")]
+    pub follower_read_timeout_secs: u64,
+
+    /// Follower queries allowed per second from each client IP
+    #[serde(default = "default_follower_read_rps")]
+    pub follower_read_rps: u32,
+
+    /// Authentication and role-based access control (`[api.auth]`)
+    #[serde(default)]
+    pub auth: ApiAuthConfig,
+}
+
+/// HTTP API authentication
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiAuthConfig {
+    /// Require credentials for every endpoint except `/health`
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Users allowed to log in with HTTP Basic auth (`[[api.auth.users]]`)
+    #[serde(default)]
+    pub users: Vec<ApiUser>,
+
+    /// Bearer tokens, as an alternative to users
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+
+    /// Shared secret the nodes send with their own API calls to each other
+    /// (forwarded writes, proxy writes, load balancer polling). Grants admin.
+    #[serde(default)]
+    pub cluster_token: Option<String>,
+}
+
+/// An HTTP Basic auth user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiUser {
+    pub username: String,
+    /// bcrypt hash of the password
+    pub password_hash: String,
+    pub roles: Vec<ApiRole>,
+}
+
+/// A bearer token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// `sha256:` followed by the hex SHA-256 of the token
+    pub token_hash: String,
+    pub roles: Vec<ApiRole>,
+}
+
+/// What an API user may do. Each role includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiRole {
+    /// Status, cluster, metrics and other read endpoints
+    Reader,
+    /// Also writes, SQL and transactions
+    Writer,
+    /// Also `/admin/*` and dumps
+    Admin,
 }
 
 /// Logging configuration
@@ -203,14 +402,27 @@ pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
     pub level: String,
 
-    /// Log format (pretty, json)
-    #[serde(default = "default_log_format")]
-    pub format: String,
+    /// Log format (pretty, json, compact)
+    #[serde(default)]
+    pub format: LogFormat,
 
-    /// Log to file path (optional)
+    /// Also log to this file, rotated daily (optional)
     pub file: Option<PathBuf>,
 }
 
+/// How log lines are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable, one line per event
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log collectors
+    Json,
+    /// Shorter human-readable lines
+    Compact,
+}
+
 /// MySQL proxy configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyConfig {
@@ -237,6 +449,28 @@ pub struct ProxyConfig {
     /// Require SSL from clients (reject non-SSL connections)
     #[serde(default)]
     pub ssl_required: bool,
+
+    /// Maximum LSNs a follower may trail the leader and still serve reads
+    #[serde(default = "default_max_stale_lsn")]
+    pub max_stale_lsn: u64,
+
+    /// What to do with reads on a follower beyond max_stale_lsn: "leader" or "error"
+    #[serde(default = "default_stale_read_response")]
+    pub stale_read_response: String,
+
+    /// Client connections proxied at once (0 = unlimited); later ones queue
+    #[serde(default = "default_proxy_max_connections")]
+    pub max_connections: usize,
+
+    /// Connections that may wait for a slot before new ones are refused
+    /// with error 1040
+    #[serde(default = "default_proxy_max_queue_depth")]
+    pub max_queue_depth: usize,
+
+    /// Queries read from a client before waiting for their responses
+    /// (1 = no pipelining). Only for clients with CLIENT_MULTI_STATEMENTS
+    #[serde(default = "default_proxy_pipeline_depth")]
+    pub pipeline_depth: usize,
 }
 
 /// Replication mode configuration
@@ -247,6 +481,27 @@ pub struct ReplicationModeConfig {
     /// - binlog: Reads from MariaDB's binary log (for external Galera clusters)
     #[serde(default = "default_replication_mode")]
     pub mode: String,
+
+    /// Columns kept out of the WAL, and so off every follower
+    /// (`[[replication.column_filter]]`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub column_filter: Vec<ColumnFilterConfig>,
+
+    /// Drop columns the follower's table doesn't have instead of failing
+    /// the entry (for followers whose schema leaves out filtered columns)
+    #[serde(default)]
+    pub allow_missing_columns: bool,
+}
+
+/// Columns of one table that are not replicated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnFilterConfig {
+    /// Database the table is in
+    pub database: String,
+    /// Table name
+    pub table: String,
+    /// Columns left out of replicated writes
+    pub exclude_columns: Vec<String>,
 }
 
 /// Binlog configuration (when replication.mode = "binlog")
@@ -264,6 +519,46 @@ pub struct BinlogConfig {
     /// Starting binlog position (optional - uses current position if not set)
     #[serde(default)]
     pub start_position: Option<u64>,
+
+    /// Read binlog from several servers at once (multi-master / Galera nodes)
+    /// When set, `[database]` is no longer used as a binlog source: each entry
+    /// gets its own client and a merger deduplicates and orders their events.
+    #[serde(default)]
+    pub sources: Vec<BinlogSourceConfig>,
+}
+
+/// One MariaDB server to stream binlog from (`[[binlog.sources]]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinlogSourceConfig {
+    /// MariaDB host
+    pub host: String,
+
+    /// MariaDB port
+    #[serde(default = "default_db_port")]
+    pub port: u16,
+
+    /// Replication user
+    pub user: String,
+
+    /// Replication password
+    #[serde(default)]
+    pub password: String,
+
+    /// Replica server ID used for this connection (unique per source)
+    #[serde(default = "default_binlog_server_id")]
+    pub server_id: u32,
+
+    /// GTID position to start from on first run, e.g. "0-1-100"
+    /// (uses the server's current position if not set)
+    #[serde(default)]
+    pub start_gtid: Option<String>,
+}
+
+impl BinlogSourceConfig {
+    /// `host:port`, used to name the source in logs, metrics and state files
+    pub fn name(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
 }
 
 /// Performance auto-tuning configuration
@@ -294,6 +589,70 @@ impl Default for PerformanceConfig {
     }
 }
 
+/// OpenTelemetry tracing configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// OTLP gRPC collector endpoint, e.g. "http://localhost:4317" (unset = tracing disabled)
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// Audit log configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Write an audit record for every WAL entry
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Audit log path
+    #[serde(default = "default_audit_log_file")]
+    pub log_file: PathBuf,
+
+    /// Append the full SQL to each record (may contain personal data)
+    #[serde(default)]
+    pub include_query_data: bool,
+
+    /// Rotate the log once it exceeds this size in MB
+    #[serde(default = "default_audit_max_file_mb")]
+    pub max_file_mb: u64,
+}
+
+/// Kafka change data capture configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KafkaConfig {
+    /// Publish every WAL entry to Kafka as change events (leader only)
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Bootstrap brokers, e.g. ["kafka:9092"]
+    #[serde(default)]
+    pub brokers: Vec<String>,
+
+    /// Topic the change events are written to
+    #[serde(default = "default_kafka_topic")]
+    pub topic: String,
+
+    /// Message encoding: "json" or "avro"
+    #[serde(default = "default_kafka_schema")]
+    pub schema: String,
+}
+
+/// Replication executor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutorConfig {
+    /// Consecutive connection failures before the circuit breaker opens (0 = disabled)
+    #[serde(default = "default_circuit_breaker_threshold")]
+    pub circuit_breaker_threshold: u32,
+
+    /// Seconds the circuit stays open before a trial request is let through
+    #[serde(default = "default_circuit_breaker_reset_secs")]
+    pub circuit_breaker_reset_secs: u64,
+
+    /// Size limit of the undo log of applied entries (0 = don't keep one)
+    #[serde(default = "default_undo_log_max_mb")]
+    pub undo_log_max_mb: u64,
+}
+
 fn default_db_port() -> u16 {
     3306
 }
@@ -306,14 +665,26 @@ fn default_connect_timeout() -> u64 {
     30
 }
 
-fn default_batch_size() -> usize {
-    1000
+fn default_health_check_interval() -> u64 {
+    30
+}
+
+fn default_max_lifetime() -> u64 {
+    1800
 }
 
 fn default_flush_interval_ms() -> u64 {
     10  // 10ms = up to 100 flushes/sec for high throughput
 }
 
+fn default_group_commit_max_delay_us() -> u64 {
+    1000
+}
+
+fn default_group_commit_max_size() -> usize {
+    100
+}
+
 fn default_compression() -> bool {
     true
 }
@@ -326,6 +697,22 @@ fn default_fsync() -> bool {
     true
 }
 
+fn default_max_segment_age_secs() -> u64 {
+    3600
+}
+
+fn default_min_segment_entries() -> u64 {
+    1
+}
+
+fn default_compaction_threshold_segments() -> usize {
+    10
+}
+
+fn default_max_retention_override_hours() -> u64 {
+    720
+}
+
 fn default_heartbeat_interval_ms() -> u64 {
     200
 }
@@ -346,6 +733,46 @@ fn default_max_batch_entries() -> usize {
     5000
 }
 
+fn default_parallel_apply_workers() -> usize {
+    4
+}
+
+fn default_read_barrier_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_max_lag_lsn() -> u64 {
+    1000
+}
+
+fn default_pipeline_depth() -> usize {
+    1
+}
+
+fn default_wan_batch_timeout_ms() -> u64 {
+    200
+}
+
+fn default_wan_rtt_threshold_ms() -> u64 {
+    50
+}
+
+fn default_protocol_version() -> u8 {
+    crate::replication::PROTOCOL_VERSION
+}
+
+fn default_max_writes_per_second() -> u64 {
+    10_000
+}
+
+fn default_rate_limit_action() -> String {
+    "reject".to_string()
+}
+
+fn default_event_types() -> Vec<String> {
+    crate::state::ClusterEvent::ALL.iter().map(|e| e.as_str().to_string()).collect()
+}
+
 fn default_true() -> bool {
     true
 }
@@ -354,18 +781,70 @@ fn default_api_address() -> String {
     "0.0.0.0:8080".to_string()
 }
 
-fn default_log_level() -> String {
-    "info".to_string()
+fn default_follower_read_timeout_secs() -> u64 {
+    30
+}
+
+fn default_follower_read_rps() -> u32 {
+    100
 }
 
-fn default_log_format() -> String {
-    "pretty".to_string()
+fn default_log_level() -> String {
+    "info".to_string()
 }
 
 fn default_proxy_address() -> String {
     "0.0.0.0:8007".to_string()
 }
 
+fn default_max_stale_lsn() -> u64 {
+    100
+}
+
+fn default_stale_read_response() -> String {
+    "leader".to_string()
+}
+
+fn default_proxy_max_connections() -> usize {
+    100
+}
+
+fn default_proxy_max_queue_depth() -> usize {
+    50
+}
+
+fn default_proxy_pipeline_depth() -> usize {
+    4
+}
+
+fn default_audit_log_file() -> PathBuf {
+    PathBuf::from("/var/log/wolfscale/audit.log")
+}
+
+fn default_audit_max_file_mb() -> u64 {
+    100
+}
+
+fn default_kafka_topic() -> String {
+    "wolfscale-cdc".to_string()
+}
+
+fn default_kafka_schema() -> String {
+    "json".to_string()
+}
+
+fn default_circuit_breaker_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_breaker_reset_secs() -> u64 {
+    30
+}
+
+fn default_undo_log_max_mb() -> u64 {
+    100
+}
+
 fn default_data_dir() -> PathBuf {
     PathBuf::from("/var/lib/wolfscale")
 }
@@ -384,6 +863,10 @@ impl Default for ApiConfig {
             enabled: true,
             bind_address: default_api_address(),
             cors_enabled: false,
+            allow_follower_reads: false,
+            follower_read_timeout_secs: default_follower_read_timeout_secs(),
+            follower_read_rps: default_follower_read_rps(),
+            auth: ApiAuthConfig::default(),
         }
     }
 }
@@ -392,7 +875,7 @@ impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
             level: default_log_level(),
-            format: default_log_format(),
+            format: LogFormat::default(),
             file: None,
         }
     }
@@ -407,6 +890,43 @@ impl Default for ProxyConfig {
             ssl_cert: None,
             ssl_key: None,
             ssl_required: false,
+            max_stale_lsn: default_max_stale_lsn(),
+            stale_read_response: default_stale_read_response(),
+            max_connections: default_proxy_max_connections(),
+            max_queue_depth: default_proxy_max_queue_depth(),
+            pipeline_depth: default_proxy_pipeline_depth(),
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            log_file: default_audit_log_file(),
+            include_query_data: false,
+            max_file_mb: default_audit_max_file_mb(),
+        }
+    }
+}
+
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            brokers: Vec::new(),
+            topic: default_kafka_topic(),
+            schema: default_kafka_schema(),
+        }
+    }
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            circuit_breaker_threshold: default_circuit_breaker_threshold(),
+            circuit_breaker_reset_secs: default_circuit_breaker_reset_secs(),
+            undo_log_max_mb: default_undo_log_max_mb(),
         }
     }
 }
@@ -415,6 +935,8 @@ impl Default for ReplicationModeConfig {
     fn default() -> Self {
         Self {
             mode: default_replication_mode(),
+            column_filter: Vec::new(),
+            allow_missing_columns: false,
         }
     }
 }
@@ -425,6 +947,7 @@ impl Default for BinlogConfig {
             server_id: default_binlog_server_id(),
             start_file: None,
             start_position: None,
+            sources: Vec::new(),
         }
     }
 }
@@ -459,6 +982,58 @@ impl WolfScaleConfig {
             return Err(crate::Error::Config("database.host cannot be empty".into()));
         }
 
+        if crate::proxy::StaleReadResponse::parse(&self.proxy.stale_read_response).is_none() {
+            return Err(crate::Error::Config(format!(
+                "proxy.stale_read_response must be \"leader\" or \"error\", got \"{}\"",
+                self.proxy.stale_read_response
+            )));
+        }
+
+        if self.cluster.read_barrier_timeout_ms == 0 {
+            return Err(crate::Error::Config("cluster.read_barrier_timeout_ms must be at least 1".into()));
+        }
+
+        if self.cluster.wan_batch_timeout_ms == 0 {
+            return Err(crate::Error::Config("cluster.wan_batch_timeout_ms must be at least 1".into()));
+        }
+
+        if self.cluster.protocol_version > crate::replication::PROTOCOL_VERSION {
+            return Err(crate::Error::Config(format!(
+                "cluster.protocol_version must be at most {}, got {}",
+                crate::replication::PROTOCOL_VERSION, self.cluster.protocol_version
+            )));
+        }
+
+        if crate::replication::RateLimitAction::parse(&self.cluster.rate_limit_action).is_none() {
+            return Err(crate::Error::Config(format!(
+                "cluster.rate_limit_action must be \"reject\" or \"delay\", got \"{}\"",
+                self.cluster.rate_limit_action
+            )));
+        }
+
+        if let Some(unknown) = self.cluster.event_types.iter().find(|t| crate::state::ClusterEvent::parse(t).is_none()) {
+            return Err(crate::Error::Config(format!(
+                "cluster.event_types: unknown event \"{}\" (expected leader_elected, leader_lost, follower_joined or follower_lost)",
+                unknown
+            )));
+        }
+
+        if self.audit.enabled && self.audit.max_file_mb == 0 {
+            return Err(crate::Error::Config("audit.max_file_mb must be at least 1".into()));
+        }
+
+        if self.kafka.enabled {
+            if self.kafka.brokers.is_empty() {
+                return Err(crate::Error::Config("kafka.brokers cannot be empty when kafka.enabled = true".into()));
+            }
+            if crate::kafka::ChangeSchema::parse(&self.kafka.schema).is_none() {
+                return Err(crate::Error::Config(format!(
+                    "kafka.schema must be \"json\" or \"avro\", got \"{}\"",
+                    self.kafka.schema
+                )));
+            }
+        }
+
         Ok(())
     }
 
@@ -485,6 +1060,11 @@ impl WolfScaleConfig {
         self.node.data_dir.join("state")
     }
 
+    /// Get the undo log path
+    pub fn undo_log_path(&self) -> PathBuf {
+        self.state_dir().join("undo.log")
+    }
+
     /// Get heartbeat interval as Duration
     pub fn heartbeat_interval(&self) -> Duration {
         Duration::from_millis(self.cluster.heartbeat_interval_ms)
@@ -495,6 +1075,11 @@ impl WolfScaleConfig {
         Duration::from_millis(self.cluster.election_timeout_ms)
     }
 
+    /// Get read barrier timeout as Duration
+    pub fn read_barrier_timeout(&self) -> Duration {
+        Duration::from_millis(self.cluster.read_barrier_timeout_ms)
+    }
+
     /// Get flush interval as Duration
     pub fn flush_interval(&self) -> Duration {
         Duration::from_millis(self.wal.flush_interval_ms)
@@ -552,7 +1137,7 @@ password = "secret"
 database = "myapp"
 
 [wal]
-batch_size = 1000
+group_commit_max_size = 50
 flush_interval_ms = 100
 compression = true
 
@@ -564,5 +1149,6 @@ peers = ["node-2:7654", "node-3:7654"]
         assert_eq!(config.node.id, "node-1");
         assert_eq!(config.cluster.peers.len(), 2);
         assert_eq!(config.quorum_size(), 2); // 3 nodes, quorum = 2
+        assert_eq!(config.wal.group_commit_max_size, 50);
     }
 }
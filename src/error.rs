@@ -38,6 +38,9 @@ pub enum Error {
     #[error("Query execution failed: {0}")]
     QueryExecution(String),
 
+    #[error("Circuit breaker open: database unreachable, retry in {retry_after_secs}s")]
+    CircuitOpen { retry_after_secs: u64 },
+
     // Replication errors
     #[error("Replication error: {0}")]
     Replication(String),
@@ -54,6 +57,15 @@ pub enum Error {
     #[error("Quorum not reached: {reached}/{required}")]
     QuorumNotReached { reached: usize, required: usize },
 
+    #[error("Transaction not found: {0}")]
+    TransactionNotFound(uuid::Uuid),
+
+    #[error("Transaction {txn_id} writes to database {expected:?}, not {requested:?}")]
+    TransactionDatabaseMismatch { txn_id: uuid::Uuid, expected: Option<String>, requested: Option<String> },
+
+    #[error("Write rate limit exceeded, retry in {retry_after_ms}ms")]
+    RateLimited { retry_after_ms: u64 },
+
     // Network errors
     #[error("Network error: {0}")]
     Network(String),
@@ -0,0 +1,196 @@
+//! Circuit Breaker
+//!
+//! Stops the executor from hammering MariaDB while it is down. After
+//! `threshold` consecutive connection failures the circuit opens and requests
+//! fail immediately with `Error::CircuitOpen`. Once `reset_after` has passed a
+//! single trial request is let through (half-open): success closes the
+//! circuit, failure opens it again.
+//!
+//! Only failures to reach the database count. A query that MariaDB rejects
+//! (duplicate key, syntax error, ...) proves the server is up.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::metrics;
+
+/// Circuit breaker state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests go through
+    Closed,
+    /// Requests fail immediately
+    Open,
+    /// One trial request is in flight
+    HalfOpen,
+}
+
+impl CircuitState {
+    /// Value reported by `wolfscale_circuit_breaker_state`
+    fn metric_value(self) -> i64 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::Open => 1,
+            CircuitState::HalfOpen => 2,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    failure_count: u32,
+    /// Last failure while closed/open, or when the trial started while half-open
+    last_failure: Instant,
+}
+
+/// Circuit breaker guarding the database connection
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    inner: Mutex<Inner>,
+    /// Consecutive failures that open the circuit (0 = never open)
+    threshold: u32,
+    reset_after: Duration,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, reset_after: Duration) -> Self {
+        metrics::CIRCUIT_BREAKER_STATE.set(CircuitState::Closed.metric_value());
+        Self {
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                failure_count: 0,
+                last_failure: Instant::now(),
+            }),
+            threshold,
+            reset_after,
+        }
+    }
+
+    /// Current state
+    pub fn state(&self) -> CircuitState {
+        self.lock().state
+    }
+
+    /// Check whether a request may go through, moving to half-open once the
+    /// reset period has passed
+    pub fn check(&self) -> Result<()> {
+        let mut inner = self.lock();
+        match inner.state {
+            CircuitState::Closed => Ok(()),
+            // A trial that never reported back (e.g. its future was dropped)
+            // must not keep the circuit half-open forever
+            CircuitState::Open | CircuitState::HalfOpen => {
+                let elapsed = inner.last_failure.elapsed();
+                if elapsed < self.reset_after {
+                    let remaining = self.reset_after - elapsed;
+                    return Err(Error::CircuitOpen { retry_after_secs: remaining.as_secs().max(1) });
+                }
+                if inner.state == CircuitState::Open {
+                    tracing::info!("Circuit breaker half-open, trying the database again");
+                }
+                inner.last_failure = Instant::now();
+                Self::set_state(&mut inner, CircuitState::HalfOpen);
+                Ok(())
+            }
+        }
+    }
+
+    /// Record a request that reached the database
+    pub fn record_success(&self) {
+        let mut inner = self.lock();
+        if inner.state != CircuitState::Closed {
+            tracing::info!("Database reachable again, circuit breaker closed");
+        }
+        inner.failure_count = 0;
+        Self::set_state(&mut inner, CircuitState::Closed);
+    }
+
+    /// Record a failure to reach the database
+    pub fn record_failure(&self) {
+        let mut inner = self.lock();
+        inner.failure_count = inner.failure_count.saturating_add(1);
+        inner.last_failure = Instant::now();
+        let open = match inner.state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => self.threshold > 0 && inner.failure_count >= self.threshold,
+            CircuitState::Open => false,
+        };
+        if open {
+            tracing::warn!(
+                "Circuit breaker open after {} consecutive connection failures, pausing database writes for {}s",
+                inner.failure_count,
+                self.reset_after.as_secs()
+            );
+            Self::set_state(&mut inner, CircuitState::Open);
+        }
+    }
+
+    /// Open the circuit now, whatever the failure count (the pool health
+    /// check gave up on the database)
+    pub fn trip(&self) {
+        let mut inner = self.lock();
+        inner.last_failure = Instant::now();
+        if inner.state != CircuitState::Open {
+            tracing::warn!(
+                "Circuit breaker open: database failed its health checks, pausing database writes for {}s",
+                self.reset_after.as_secs()
+            );
+            Self::set_state(&mut inner, CircuitState::Open);
+        }
+    }
+
+    fn set_state(inner: &mut Inner, state: CircuitState) {
+        inner.state = state;
+        metrics::CIRCUIT_BREAKER_STATE.set(state.metric_value());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold_and_recovers() {
+        let breaker = CircuitBreaker::new(3, Duration::from_millis(50));
+
+        for _ in 0..3 {
+            assert!(breaker.check().is_ok());
+            breaker.record_failure();
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(matches!(breaker.check(), Err(Error::CircuitOpen { .. })));
+
+        // After the reset period exactly one trial goes through
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.check().is_ok());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.check().is_err());
+
+        // A failed trial opens the circuit again
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.check().is_ok());
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(30));
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
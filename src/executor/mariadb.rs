@@ -3,15 +3,52 @@
 //! Executes log entries against a MariaDB database.
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 use std::collections::HashMap;
-use sqlx::{MySqlPool, Row};
-use sqlx::mysql::MySqlPoolOptions;
+use sqlx::{MySqlConnection, MySqlPool, Row};
+use sqlx::mysql::{MySqlDatabaseError, MySqlPoolOptions, MySqlRow};
 use tokio::sync::RwLock;
 
 use crate::config::DatabaseConfig;
-use crate::wal::LogEntry;
+use super::circuit_breaker::{CircuitBreaker, CircuitState};
+use super::undo::{self, UndoEntry, UndoLog};
+use crate::wal::{drop_column, row_targets, LogEntry, Lsn, RowTarget};
 use crate::error::{Error, Result};
+use crate::metrics;
+
+/// The column named by an "Unknown column" (1054) error
+fn unknown_column(e: &sqlx::Error) -> Option<String> {
+    let db_err = e.as_database_error()?.try_downcast_ref::<MySqlDatabaseError>()?;
+    if db_err.number() != 1054 {
+        return None;
+    }
+    // Unknown column 't.ssn' in 'field list'
+    let name = db_err.message().split('\'').nth(1)?;
+    name.rsplit('.').next().map(str::to_string)
+}
+
+/// Whether a sqlx error means the database could not be reached (as opposed
+/// to the database rejecting the query)
+fn is_connection_error(e: &sqlx::Error) -> bool {
+    matches!(
+        e,
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed
+    )
+}
+
+/// Consecutive failed pool health checks that open the circuit breaker
+const HEALTH_CHECK_TRIP_AFTER: u32 = 3;
+
+/// Pool options shared by every pool: `pool_size` connections, each
+/// replaced after `max_lifetime_secs`
+fn pool_options(config: &DatabaseConfig) -> MySqlPoolOptions {
+    MySqlPoolOptions::new()
+        .max_connections(config.pool_size)
+        .acquire_timeout(Duration::from_secs(config.connect_timeout_secs))
+        .max_lifetime(Duration::from_secs(config.max_lifetime_secs))
+}
 
 /// Safely truncate a string at char boundary (UTF-8 safe)
 fn safe_truncate(s: &str, max_chars: usize) -> String {
@@ -34,6 +71,23 @@ pub struct MariaDbExecutor {
     config: Option<DatabaseConfig>,
     /// Whether this is a mock executor (for testing)
     is_mock: bool,
+    /// Fails entries fast while the database is unreachable
+    breaker: CircuitBreaker,
+    /// Pool health checks that have failed in a row
+    health_failures: AtomicU32,
+    /// Drop columns the target table doesn't have instead of failing
+    allow_missing_columns: bool,
+    /// Where `execute_entry_at` saves how to revert each entry
+    undo_log: Option<Arc<std::sync::Mutex<UndoLog>>>,
+}
+
+/// One step of reverting an entry, worked out before it runs
+enum UndoStep {
+    /// Run this statement
+    Sql(String),
+    /// Delete the rows an INSERT gave `column` values above `above` (the
+    /// auto-increment column's maximum before the entry ran)
+    DeleteInserted { table: String, column: String, above: i128 },
 }
 
 impl MariaDbExecutor {
@@ -49,9 +103,8 @@ impl MariaDbExecutor {
         );
 
         // Create server-level pool for DDL operations - this always works
-        let server_pool = MySqlPoolOptions::new()
+        let server_pool = pool_options(config)
             .max_connections(2)
-            .acquire_timeout(Duration::from_secs(config.connect_timeout_secs))
             .connect(&server_url)
             .await?;
 
@@ -66,9 +119,7 @@ impl MariaDbExecutor {
                 db
             );
 
-            match MySqlPoolOptions::new()
-                .max_connections(config.pool_size)
-                .acquire_timeout(Duration::from_secs(config.connect_timeout_secs))
+            match pool_options(config)
                 .connect(&db_url)
                 .await
             {
@@ -81,9 +132,7 @@ impl MariaDbExecutor {
         } else {
             // No specific database configured - create a SEPARATE server-level pool for db_pool
             // (don't clone server_pool as they would share state and closing one closes both)
-            match MySqlPoolOptions::new()
-                .max_connections(config.pool_size)
-                .acquire_timeout(Duration::from_secs(config.connect_timeout_secs))
+            match pool_options(config)
                 .connect(&server_url)
                 .await
             {
@@ -101,6 +150,10 @@ impl MariaDbExecutor {
             db_pools: Arc::new(RwLock::new(HashMap::new())),
             config: Some(config.clone()),
             is_mock: false,
+            breaker: CircuitBreaker::new(5, Duration::from_secs(30)),
+            health_failures: AtomicU32::new(0),
+            allow_missing_columns: false,
+            undo_log: None,
         })
     }
 
@@ -112,6 +165,53 @@ impl MariaDbExecutor {
             db_pools: Arc::new(RwLock::new(HashMap::new())),
             config: None,
             is_mock: true,
+            breaker: CircuitBreaker::new(5, Duration::from_secs(30)),
+            health_failures: AtomicU32::new(0),
+            allow_missing_columns: false,
+            undo_log: None,
+        }
+    }
+
+    /// Open the circuit after `threshold` consecutive connection failures
+    /// (0 = never) and retry after `reset_after`
+    pub fn with_circuit_breaker(mut self, threshold: u32, reset_after: Duration) -> Self {
+        self.breaker = CircuitBreaker::new(threshold, reset_after);
+        self
+    }
+
+    /// Silently drop columns an entry sets that the target table lacks
+    /// (`replication.allow_missing_columns`)
+    pub fn with_missing_columns_allowed(mut self, allow: bool) -> Self {
+        self.allow_missing_columns = allow;
+        self
+    }
+
+    /// Save how to revert each entry applied with `execute_entry_at`
+    pub fn with_undo_log(mut self, undo_log: UndoLog) -> Self {
+        self.undo_log = Some(Arc::new(std::sync::Mutex::new(undo_log)));
+        self
+    }
+
+    /// The executor's circuit breaker
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Address of the database, for errors
+    fn address(&self) -> String {
+        match &self.config {
+            Some(c) => format!("{}:{}", c.host, c.port),
+            None => "mariadb".to_string(),
+        }
+    }
+
+    /// Map a failed statement to `ConnectionFailed` if the database could not
+    /// be reached, otherwise to `QueryExecution`
+    fn statement_error(&self, context: &str, stmt: &str, e: sqlx::Error) -> Error {
+        if is_connection_error(&e) {
+            Error::ConnectionFailed { address: self.address(), reason: e.to_string() }
+        } else {
+            Error::QueryExecution(format!("{} '{}': {}", context, safe_truncate(stmt, 50), e))
         }
     }
 
@@ -151,9 +251,7 @@ impl MariaDbExecutor {
 
         tracing::info!("Creating on-demand connection pool for database '{}'", database);
 
-        let pool = MySqlPoolOptions::new()
-            .max_connections(config.pool_size)
-            .acquire_timeout(Duration::from_secs(config.connect_timeout_secs))
+        let pool = pool_options(config)
             .connect(&db_url)
             .await
             .map_err(|e| {
@@ -181,17 +279,191 @@ impl MariaDbExecutor {
         }
     }
 
-    /// Execute a log entry
+    /// Execute a log entry. Fails with `Error::CircuitOpen` without touching
+    /// the database while the circuit breaker is open.
     pub async fn execute_entry(&self, entry: &LogEntry) -> Result<()> {
         if self.is_mock {
             return Ok(());
         }
 
-        // Extract database name from entry (only RawSql has this field currently)
-        let target_database = match entry {
+        self.breaker.check()?;
+        let result = self.apply_entry(entry).await;
+        match &result {
+            Err(Error::ConnectionFailed { .. }) => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
+
+    /// Execute the entry at `lsn`, first saving the rows it changes to the
+    /// undo log (if there is one). Failing to save them is logged and does
+    /// not stop the entry.
+    pub async fn execute_entry_at(&self, lsn: Lsn, entry: &LogEntry) -> Result<()> {
+        let Some(undo_log) = self.undo_log.as_ref().filter(|_| !self.is_mock) else {
+            return self.execute_entry(entry).await;
+        };
+
+        self.breaker.check()?;
+        let steps = match self.undo_steps(entry).await {
+            Ok(steps) => steps,
+            Err(e) => {
+                tracing::warn!("Could not save undo information for LSN {}: {}", lsn, e);
+                None
+            }
+        };
+        self.execute_entry(entry).await?;
+        let Some(steps) = steps else { return Ok(()) };
+
+        let database = match entry {
             LogEntry::RawSql { database, .. } => database.clone(),
             _ => None,
         };
+        let saved = match self.undo_sql(database.as_deref(), steps).await {
+            Ok(statements) if statements.is_empty() => return Ok(()),
+            Ok(statements) => {
+                let entry = UndoEntry { lsn, inverse_sql: statements.join(";\n"), database };
+                undo_log.lock().unwrap().append(&entry)
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = saved {
+            tracing::warn!("Could not save undo information for LSN {}: {}", lsn, e);
+        }
+        Ok(())
+    }
+
+    /// Revert an entry by running its undo log statements, in one transaction
+    pub async fn undo(&self, entry: &UndoEntry) -> Result<()> {
+        self.execute_entry(&LogEntry::RawSql {
+            sql: format!("START TRANSACTION;\n{};\nCOMMIT", entry.inverse_sql),
+            affects_table: None,
+            database: entry.database.clone(),
+            gtid: None,
+        }).await
+    }
+
+    /// Read the rows an entry is about to change, as the steps reverting
+    /// each of its statements. Returns `None` if the
+    /// entry changes rows that can't be told from its statements (DDL,
+    /// `INSERT ... SELECT`, ...) or that have no primary key.
+    async fn undo_steps(&self, entry: &LogEntry) -> Result<Option<Vec<Vec<UndoStep>>>> {
+        let database = match entry {
+            LogEntry::RawSql { database, .. } => database.as_deref(),
+            _ => None,
+        };
+        let Some(targets) = row_targets(&entry.to_sql().join(";\n")) else { return Ok(None) };
+
+        let mut steps = Vec::new();
+        for target in targets {
+            match target {
+                RowTarget::Matching { database: db, table, condition } => {
+                    let table = undo::table_ref(db.as_deref(), &table);
+                    let sql = format!("SELECT * FROM {} {}", table, condition);
+                    steps.push(self.saved_rows(database, &table, &sql).await?.map(UndoStep::Sql).into_iter().collect());
+                }
+                RowTarget::Inserted { database: db, table, columns, rows } => {
+                    let table = undo::table_ref(db.as_deref(), &table);
+                    let sql = format!("SHOW KEYS FROM {} WHERE Key_name = 'PRIMARY'", table);
+                    let key_columns: Vec<String> = self.fetch_rows(database, &sql).await?
+                        .iter()
+                        .filter_map(|row| row.try_get_unchecked::<String, _>("Column_name").ok())
+                        .collect();
+                    if key_columns.is_empty() {
+                        return Ok(None);
+                    }
+
+                    // Every key given: delete those rows and put back any they replaced
+                    let positions: Option<Vec<usize>> = key_columns.iter()
+                        .map(|k| columns.iter().position(|c| c.eq_ignore_ascii_case(k)))
+                        .collect();
+                    let keys: Option<Vec<Vec<String>>> = positions.map(|positions| {
+                        rows.iter().map(|row| positions.iter().map(|&p| row[p].clone()).collect()).collect()
+                    });
+                    if let Some(keys) = keys.filter(|keys| keys.iter().flatten().all(|k| undo::is_constant(k))) {
+                        let condition = undo::key_condition(&key_columns, &keys);
+                        let mut revert = vec![UndoStep::Sql(format!("DELETE FROM {} WHERE {}", table, condition))];
+                        let sql = format!("SELECT * FROM {} WHERE {}", table, condition);
+                        revert.extend(self.saved_rows(database, &table, &sql).await?.map(UndoStep::Sql));
+                        steps.push(revert);
+                        continue;
+                    }
+
+                    // Generated keys: the rows will be those above the current maximum
+                    let [column] = key_columns.as_slice() else { return Ok(None) };
+                    let sql = format!("SHOW COLUMNS FROM {} WHERE Field = '{}'", table, column.replace('\'', "''"));
+                    let auto_increment = self.fetch_rows(database, &sql).await?.first()
+                        .and_then(|row| row.try_get_unchecked::<String, _>("Extra").ok())
+                        .is_some_and(|extra| extra.contains("auto_increment"));
+                    if !auto_increment {
+                        return Ok(None);
+                    }
+                    let above = self.max_value(database, &table, column).await?;
+                    steps.push(vec![UndoStep::DeleteInserted { table, column: column.clone(), above }]);
+                }
+            }
+        }
+        Ok(Some(steps))
+    }
+
+    /// `REPLACE INTO` putting back the rows `sql` selects, if there are any
+    async fn saved_rows(&self, database: Option<&str>, table: &str, sql: &str) -> Result<Option<String>> {
+        use sqlx::{Column, TypeInfo};
+
+        let rows = self.fetch_rows(database, sql).await?;
+        let Some(first) = rows.first() else { return Ok(None) };
+        let columns: Vec<String> = first.columns().iter().map(|c| c.name().to_string()).collect();
+        let binary: Vec<bool> = first.columns().iter()
+            .map(|c| {
+                let name = c.type_info().name();
+                name.contains("BLOB") || name.contains("BINARY") || name == "BIT" || name == "GEOMETRY"
+            })
+            .collect();
+        let values: Vec<Vec<String>> = rows.iter()
+            .map(|row| (0..columns.len())
+                .map(|i| {
+                    let value = row.try_get_unchecked::<Option<Vec<u8>>, _>(i).ok().flatten();
+                    undo::literal(value.as_deref(), binary[i])
+                })
+                .collect())
+            .collect();
+        Ok(undo::replace_rows(table, &columns, &values))
+    }
+
+    /// Largest value of an integer column (0 for an empty table)
+    async fn max_value(&self, database: Option<&str>, table: &str, column: &str) -> Result<i128> {
+        let sql = format!("SELECT COALESCE(MAX(`{}`), 0) FROM {}", column.replace('`', "``"), table);
+        let rows = self.fetch_rows(database, &sql).await?;
+        rows.first()
+            .and_then(|row| row.try_get_unchecked::<String, _>(0).ok())
+            .and_then(|max| max.parse().ok())
+            .ok_or_else(|| Error::QueryExecution(format!("No integer maximum from '{}'", sql)))
+    }
+
+    /// The statements reverting an entry, once it has run: the steps of
+    /// its last statement first
+    async fn undo_sql(&self, database: Option<&str>, steps: Vec<Vec<UndoStep>>) -> Result<Vec<String>> {
+        let mut statements = Vec::new();
+        for step in steps.into_iter().rev().flatten() {
+            match step {
+                UndoStep::Sql(sql) => statements.push(sql),
+                UndoStep::DeleteInserted { table, column, above } => {
+                    let max = self.max_value(database, &table, &column).await?;
+                    if max > above {
+                        statements.push(format!(
+                            "DELETE FROM {} WHERE `{}` > {} AND `{}` <= {}",
+                            table, column.replace('`', "``"), above, column.replace('`', "``"), max
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(statements)
+    }
+
+    /// Run a log entry's statements
+    async fn apply_entry(&self, entry: &LogEntry) -> Result<()> {
+        // Extract database name from entry (RawSql, or the writes of a transaction)
+        let target_database = entry.database().map(str::to_string);
 
         // Get the appropriate pool for this entry
         // If entry specifies a database, try to get/create a pool for that database
@@ -291,14 +563,12 @@ impl MariaDbExecutor {
                     }
                     
                     let start = std::time::Instant::now();
-                    sqlx::query(stmt)
+                    let result = sqlx::query(stmt)
                         .execute(server_pool)
-                        .await
-                        .map_err(|e| {
-                            Error::QueryExecution(format!("Failed to execute DDL '{}': {}", 
-                                safe_truncate(stmt, 50), e))
-                        })?;
+                        .await;
                     let elapsed = start.elapsed();
+                    metrics::observe_query(stmt, elapsed);
+                    result.map_err(|e| self.statement_error("Failed to execute DDL", stmt, e))?;
                     if elapsed > Duration::from_secs(5) {
                         tracing::warn!("DDL took {:.1}s: {}", elapsed.as_secs_f64(), safe_truncate(stmt, 50));
                     }
@@ -313,38 +583,31 @@ impl MariaDbExecutor {
                 } else {
                     // Normal statements - use our held connection
                     if let Some(ref mut conn) = conn_opt {
-                        sqlx::query(stmt)
-                            .execute(&mut **conn)
-                            .await
-                            .map_err(|e| {
-                                Error::QueryExecution(format!("Failed to execute '{}': {}", 
-                                    safe_truncate(stmt, 50), e))
-                            })?;
+                        self.execute_statement(conn, stmt).await
+                            .map_err(|e| self.statement_error("Failed to execute", stmt, e))?;
                     } else {
                         // No database-specific pool - try server_pool as fallback
                         if let Some(server_pool) = &self.server_pool {
                             // Acquire and hold a connection from server_pool
                             match server_pool.acquire().await {
                                 Ok(mut server_conn) => {
-                                    sqlx::query(stmt)
-                                        .execute(&mut *server_conn)
-                                        .await
-                                        .map_err(|e| {
-                                            Error::QueryExecution(format!("Failed to execute (fallback) '{}': {}", 
-                                                safe_truncate(stmt, 50), e))
-                                        })?;
+                                    self.execute_statement(&mut server_conn, stmt).await
+                                        .map_err(|e| self.statement_error("Failed to execute (fallback)", stmt, e))?;
                                     // Keep this connection for subsequent statements
                                     conn_opt = Some(server_conn.into());
                                 }
                                 Err(e) => {
-                                    return Err(Error::QueryExecution(format!(
-                                        "No database connection available for '{}': {}",
-                                        safe_truncate(stmt, 50), e
-                                    )));
+                                    return Err(Error::ConnectionFailed {
+                                        address: self.address(),
+                                        reason: format!("no connection available for '{}': {}", safe_truncate(stmt, 50), e),
+                                    });
                                 }
                             }
                         } else {
-                            return Err(Error::Database(sqlx::Error::Configuration("No connection available".into())));
+                            return Err(Error::ConnectionFailed {
+                                address: self.address(),
+                                reason: "no connection available".into(),
+                            });
                         }
                     }
                 }
@@ -354,6 +617,33 @@ impl MariaDbExecutor {
         Ok(())
     }
 
+    /// Execute one statement. With `allow_missing_columns`, a column the
+    /// table doesn't have is dropped from the statement and it is retried.
+    async fn execute_statement(&self, conn: &mut MySqlConnection, stmt: &str) -> std::result::Result<(), sqlx::Error> {
+        let mut stmt = stmt.to_string();
+        loop {
+            let start = std::time::Instant::now();
+            let result = sqlx::query(&stmt).execute(&mut *conn).await;
+            metrics::observe_query(&stmt, start.elapsed());
+            let e = match result {
+                Ok(_) => return Ok(()),
+                Err(e) => e,
+            };
+            let rewritten = self.allow_missing_columns
+                .then(|| unknown_column(&e))
+                .flatten()
+                .and_then(|column| {
+                    tracing::debug!("Skipping column '{}' missing on this node: {}", column, safe_truncate(&stmt, 80));
+                    drop_column(&stmt, &column)
+                });
+            match rewritten {
+                Some(s) if s.is_empty() => return Ok(()),
+                Some(s) => stmt = s,
+                None => return Err(e),
+            }
+        }
+    }
+
     /// Try to reconnect the database pool synchronously (waits for result)
     /// Used when we need the pool immediately for normal writes
     async fn try_reconnect_db_pool_sync(&self) -> Result<()> {
@@ -374,8 +664,7 @@ impl MariaDbExecutor {
 
             tracing::info!("Reconnecting database pool to {}", db);
             
-            match MySqlPoolOptions::new()
-                .max_connections(config.pool_size)
+            match pool_options(&config)
                 .acquire_timeout(Duration::from_secs(5))
                 .connect(&db_url)
                 .await
@@ -473,6 +762,96 @@ impl MariaDbExecutor {
         Ok(result.0 == 1)
     }
 
+    /// Ping the connection pool with `SELECT 1` and update the pool metrics.
+    /// A failed ping replaces the pool; after `HEALTH_CHECK_TRIP_AFTER` in a
+    /// row the circuit breaker opens. Returns whether the ping succeeded.
+    pub async fn check_pool_health(&self) -> bool {
+        if self.is_mock {
+            return true;
+        }
+
+        // Without a database pool (the database didn't exist at startup)
+        // the server pool shows whether MariaDB is up
+        let pool = self.pool.read().await.clone();
+        let ping = match pool.as_ref().or(self.server_pool.as_ref()) {
+            Some(pool) => sqlx::query("SELECT 1").execute(pool).await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            None => Err("no connection pool".to_string()),
+        };
+
+        match ping {
+            Ok(()) => {
+                let failures = self.health_failures.swap(0, Ordering::Relaxed);
+                if failures > 0 {
+                    tracing::info!("Database pool healthy again after {} failed health checks", failures);
+                }
+                if failures >= HEALTH_CHECK_TRIP_AFTER && self.breaker.state() != CircuitState::Closed {
+                    self.breaker.record_success();
+                }
+                if pool.is_none() {
+                    if let Err(e) = self.recreate_pool().await {
+                        tracing::debug!("Database pool still unavailable: {}", e);
+                    }
+                }
+            }
+            Err(e) => {
+                metrics::DB_POOL_ERRORS.inc();
+                let failures = self.health_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                tracing::warn!("Database pool health check failed ({} in a row): {}", failures, e);
+                if let Err(e) = self.recreate_pool().await {
+                    tracing::warn!("Could not recreate database pool: {}", e);
+                }
+                if failures >= HEALTH_CHECK_TRIP_AFTER {
+                    self.breaker.trip();
+                }
+            }
+        }
+
+        self.update_pool_metrics().await;
+        self.health_failures.load(Ordering::Relaxed) == 0
+    }
+
+    /// Replace the main pool with a fresh one, and drop the cached
+    /// per-database pools so they are recreated on demand
+    async fn recreate_pool(&self) -> Result<()> {
+        let config = self.config.as_ref().ok_or_else(|| {
+            Error::Database(sqlx::Error::Configuration("No config available to create pool".into()))
+        })?;
+        let url = format!(
+            "mysql://{}:{}@{}:{}/{}",
+            config.user,
+            config.password,
+            config.host,
+            config.port,
+            config.database.as_deref().unwrap_or_default()
+        );
+
+        let new_pool = pool_options(config).connect(&url).await?;
+        let old_pool = self.pool.write().await.replace(new_pool);
+        self.db_pools.write().await.clear();
+        tracing::info!("Recreated database pool");
+
+        // Closing waits for borrowed connections to come back
+        if let Some(old_pool) = old_pool {
+            tokio::spawn(async move { old_pool.close().await });
+        }
+        Ok(())
+    }
+
+    /// Publish the size of all pools to `wolfscale_db_pool_*`
+    async fn update_pool_metrics(&self) {
+        let mut pools: Vec<MySqlPool> = self.db_pools.read().await.values().cloned().collect();
+        pools.extend(self.pool.read().await.clone());
+        pools.extend(self.server_pool.clone());
+
+        let size: u32 = pools.iter().map(|p| p.size()).sum();
+        let idle: usize = pools.iter().map(|p| p.num_idle()).sum();
+        metrics::DB_POOL_SIZE.set(size as i64);
+        metrics::DB_POOL_IDLE.set(idle as i64);
+        metrics::DB_POOL_ACTIVE.set(size as i64 - idle as i64);
+    }
+
     /// Get list of tables in the database
     pub async fn list_tables(&self) -> Result<Vec<String>> {
         if self.is_mock {
@@ -562,6 +941,26 @@ impl MariaDbExecutor {
         Ok(row.0 as u64)
     }
 
+    /// Run a query and return its rows, against `database` if given.
+    /// The query goes over the text protocol, so every value arrives as text.
+    pub async fn fetch_rows(&self, database: Option<&str>, sql: &str) -> Result<Vec<MySqlRow>> {
+        use sqlx::Executor;
+
+        if self.is_mock {
+            return Ok(vec![]);
+        }
+
+        let pool = match database {
+            Some(database) => self.get_or_create_db_pool(database).await?,
+            None => self.pool.read().await.clone().ok_or_else(|| {
+                Error::Database(sqlx::Error::Configuration("No pool".into()))
+            })?,
+        };
+
+        pool.fetch_all(sql).await
+            .map_err(|e| self.statement_error("Query failed", sql, e))
+    }
+
     /// Close the connection pool
     pub async fn close(&self) {
         let pool_guard = self.pool.read().await;
@@ -605,6 +1004,233 @@ mod tests {
         executor.execute_entry(&entry).await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_circuit_opens_while_database_unreachable() {
+        // No pools at all: every entry fails to reach the database
+        let mut executor = MariaDbExecutor::new_mock()
+            .with_circuit_breaker(5, Duration::from_millis(100));
+        executor.is_mock = false;
+
+        let entry = LogEntry::RawSql {
+            sql: "INSERT INTO t VALUES (1)".to_string(),
+            affects_table: Some("t".to_string()),
+            database: None,
+            gtid: None,
+        };
+
+        let mut results = Vec::new();
+        for _ in 0..10 {
+            results.push(executor.execute_entry(&entry).await);
+        }
+        assert!(results[..5].iter().all(|r| matches!(r, Err(Error::ConnectionFailed { .. }))));
+        assert!(results[5..].iter().all(|r| matches!(r, Err(Error::CircuitOpen { .. }))));
+        assert_eq!(executor.circuit_breaker().state(), CircuitState::Open);
+
+        // After the reset period one trial reaches for the database again;
+        // it fails, so the circuit opens straight away
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert!(matches!(executor.execute_entry(&entry).await, Err(Error::ConnectionFailed { .. })));
+        assert!(matches!(executor.execute_entry(&entry).await, Err(Error::CircuitOpen { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_failed_health_checks_open_circuit() {
+        // No pools and no config: every ping fails and the pool can't be recreated
+        let mut executor = MariaDbExecutor::new_mock()
+            .with_circuit_breaker(0, Duration::from_secs(30));
+        executor.is_mock = false;
+
+        let errors = metrics::DB_POOL_ERRORS.get();
+        for _ in 0..HEALTH_CHECK_TRIP_AFTER - 1 {
+            assert!(!executor.check_pool_health().await);
+            assert_eq!(executor.circuit_breaker().state(), CircuitState::Closed);
+        }
+        assert!(!executor.check_pool_health().await);
+        assert_eq!(executor.circuit_breaker().state(), CircuitState::Open);
+        assert!(metrics::DB_POOL_ERRORS.get() >= errors + HEALTH_CHECK_TRIP_AFTER as u64);
+    }
+
+    /// The scratch database named by
+    /// WOLFSCALE_TEST_MARIADB=user:password@host:port/database
+    fn test_database() -> Option<DatabaseConfig> {
+        let url = std::env::var("WOLFSCALE_TEST_MARIADB").ok()?;
+        let (credentials, address) = url.rsplit_once('@').unwrap();
+        let (user, password) = credentials.split_once(':').unwrap();
+        let (host, database) = address.split_once('/').unwrap();
+        let (host, port) = host.split_once(':').unwrap();
+        Some(DatabaseConfig {
+            host: host.to_string(),
+            port: port.parse().unwrap(),
+            user: user.to_string(),
+            password: password.to_string(),
+            database: Some(database.to_string()),
+            pool_size: 2,
+            connect_timeout_secs: 5,
+            health_check_interval_secs: 30,
+            max_lifetime_secs: 1800,
+        })
+    }
+
+    /// Needs a scratch database (see `test_database`)
+    #[tokio::test]
+    #[ignore]
+    async fn test_pool_recovers_from_killed_connections() {
+        let Some(config) = test_database() else { return };
+        let executor = MariaDbExecutor::new(&config).await.unwrap();
+        executor.execute_raw("DROP TABLE IF EXISTS wolfscale_pool_test").await.unwrap();
+        executor.execute_raw("CREATE TABLE wolfscale_pool_test (id INT PRIMARY KEY)").await.unwrap();
+
+        let insert = |id: usize| LogEntry::RawSql {
+            sql: format!("INSERT INTO wolfscale_pool_test VALUES ({})", id),
+            affects_table: Some("wolfscale_pool_test".to_string()),
+            database: None,
+            gtid: None,
+        };
+        for id in 0..50 {
+            executor.execute_entry(&insert(id)).await.unwrap();
+        }
+
+        // MariaDB drops every connection the pools hold, mid-replay
+        let killer = sqlx::MySqlPool::connect(&format!(
+            "mysql://{}:{}@{}:{}", config.user, config.password, config.host, config.port
+        )).await.unwrap();
+        let ids: Vec<(u64,)> = sqlx::query_as(
+            "SELECT ID FROM information_schema.PROCESSLIST WHERE USER = ? AND ID <> CONNECTION_ID()"
+        ).bind(&config.user).fetch_all(&killer).await.unwrap();
+        assert!(!ids.is_empty());
+        for (id,) in ids {
+            let _ = sqlx::query(&format!("KILL {}", id)).execute(&killer).await;
+        }
+        killer.close().await;
+
+        // The next ping replaces the pool, and replay carries on where it was
+        executor.check_pool_health().await;
+        assert!(executor.check_pool_health().await);
+        assert_eq!(executor.circuit_breaker().state(), CircuitState::Closed);
+        for id in 50..100 {
+            executor.execute_entry(&insert(id)).await.unwrap();
+        }
+        assert_eq!(executor.count_rows("wolfscale_pool_test").await.unwrap(), 100);
+        assert!(metrics::DB_POOL_SIZE.get() > 0);
+
+        executor.execute_raw("DROP TABLE wolfscale_pool_test").await.unwrap();
+    }
+
+    /// Needs a scratch database (see `test_database`) and rights to create
+    /// another one beside it
+    #[tokio::test]
+    #[ignore]
+    async fn test_transaction_runs_in_its_database() {
+        let Some(config) = test_database() else { return };
+        let executor = MariaDbExecutor::new(&config).await.unwrap();
+        let other = "wolfscale_txn_db_test";
+        executor.execute_raw(&format!("DROP DATABASE IF EXISTS {}", other)).await.unwrap();
+        executor.execute_raw(&format!("CREATE DATABASE {}", other)).await.unwrap();
+        executor.execute_raw("DROP TABLE IF EXISTS wolfscale_txn_test").await.unwrap();
+        executor.execute_raw("CREATE TABLE wolfscale_txn_test (id INT PRIMARY KEY)").await.unwrap();
+        executor.execute_raw(&format!("CREATE TABLE {}.wolfscale_txn_test (id INT PRIMARY KEY)", other)).await.unwrap();
+
+        let insert = |id: usize| LogEntry::RawSql {
+            sql: format!("INSERT INTO wolfscale_txn_test VALUES ({})", id),
+            affects_table: Some("wolfscale_txn_test".to_string()),
+            database: Some(other.to_string()),
+            gtid: None,
+        };
+        executor.execute_entry(&LogEntry::Transaction {
+            entries: vec![insert(1), insert(2)],
+            transaction_id: Some(uuid::Uuid::new_v4()),
+        }).await.unwrap();
+
+        let count = |database: Option<&'static str>| {
+            let executor = &executor;
+            async move {
+                let rows = executor.fetch_rows(database, "SELECT COUNT(*) FROM wolfscale_txn_test").await.unwrap();
+                rows[0].try_get_unchecked::<i64, _>(0).unwrap()
+            }
+        };
+        assert_eq!(count(Some(other)).await, 2);
+        assert_eq!(count(None).await, 0, "not the default database");
+
+        executor.execute_raw("DROP TABLE wolfscale_txn_test").await.unwrap();
+        executor.execute_raw(&format!("DROP DATABASE {}", other)).await.unwrap();
+    }
+
+    /// Needs a scratch database (see `test_database`)
+    #[tokio::test]
+    #[ignore]
+    async fn test_undo_last_entries() {
+        use crate::wal::entry::{PrimaryKey, Value};
+
+        let Some(config) = test_database() else { return };
+        let dir = tempfile::tempdir().unwrap();
+        let undo_path = dir.path().join("undo.log");
+        let executor = MariaDbExecutor::new(&config).await.unwrap()
+            .with_undo_log(UndoLog::open(&undo_path, 1 << 20).unwrap());
+        executor.execute_raw("DROP TABLE IF EXISTS wolfscale_undo_test").await.unwrap();
+        executor.execute_raw(
+            "CREATE TABLE wolfscale_undo_test (id INT AUTO_INCREMENT PRIMARY KEY, name VARCHAR(32), qty INT)"
+        ).await.unwrap();
+
+        let raw = |sql: &str| LogEntry::RawSql { sql: sql.to_string(), affects_table: None, database: None, gtid: None };
+        let mut entries: Vec<LogEntry> = (1..=4).map(|id| LogEntry::Insert {
+            table: "wolfscale_undo_test".to_string(),
+            columns: vec!["id".to_string(), "name".to_string(), "qty".to_string()],
+            values: vec![Value::Int(id), Value::String(format!("item-{}", id)), Value::Int(id)],
+            primary_key: PrimaryKey::Int(id),
+        }).collect();
+        entries.extend([
+            raw("INSERT INTO wolfscale_undo_test (name, qty) VALUES ('generated', 5)"),
+            LogEntry::Update {
+                table: "wolfscale_undo_test".to_string(),
+                set_columns: vec!["qty".to_string()],
+                set_values: vec![Value::Int(20)],
+                primary_key: PrimaryKey::Int(2),
+                key_columns: vec!["id".to_string()],
+            },
+            raw("UPDATE wolfscale_undo_test SET name = 'it''s \\ big' WHERE qty > 3"),
+            // The three to undo
+            LogEntry::Delete {
+                table: "wolfscale_undo_test".to_string(),
+                primary_key: PrimaryKey::Int(1),
+                key_columns: vec!["id".to_string()],
+            },
+            raw("DELETE FROM wolfscale_undo_test WHERE id = 3; INSERT INTO wolfscale_undo_test (name, qty) VALUES ('x', 0), ('y', 0)"),
+            LogEntry::Upsert {
+                table: "wolfscale_undo_test".to_string(),
+                columns: vec!["id".to_string(), "name".to_string()],
+                values: vec![Value::Int(4), Value::String("upserted".to_string())],
+                update_columns: vec!["name".to_string()],
+                primary_key: PrimaryKey::Int(4),
+            },
+        ]);
+
+        let table = || async {
+            executor.fetch_rows(None, "SELECT CONCAT_WS('|', id, name, qty) FROM wolfscale_undo_test ORDER BY id").await.unwrap()
+                .iter()
+                .map(|row| row.try_get_unchecked::<String, _>(0).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let mut before_last_three = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if i == 7 {
+                before_last_three = table().await;
+            }
+            executor.execute_entry_at(i as Lsn + 1, entry).await.unwrap();
+        }
+        assert_ne!(table().await, before_last_three);
+
+        let mut undo_log = UndoLog::open(&undo_path, 1 << 20).unwrap();
+        assert_eq!(undo_log.len(), 10);
+        for entry in undo_log.last(3).unwrap() {
+            executor.undo(&entry).await.unwrap();
+            undo_log.remove(entry.lsn).unwrap();
+        }
+        assert_eq!(table().await, before_last_three);
+        assert_eq!(undo_log.last(1).unwrap()[0].lsn, 7);
+
+        executor.execute_raw("DROP TABLE wolfscale_undo_test").await.unwrap();
+    }
+
     #[test]
     fn test_sql_generation() {
         let entry = LogEntry::Insert {
@@ -2,8 +2,12 @@
 //!
 //! Executes log entries against MariaDB databases.
 
+mod circuit_breaker;
 mod mariadb;
 mod schema;
+mod undo;
 
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
 pub use mariadb::MariaDbExecutor;
-pub use schema::SchemaManager;
+pub use schema::{SchemaManager, SchemaVersionTracker};
+pub use undo::{UndoEntry, UndoLog};
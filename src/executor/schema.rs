@@ -3,10 +3,12 @@
 //! Handles schema change tracking and validation.
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 
 use super::MariaDbExecutor;
 use crate::error::{Error, Result};
+use crate::wal::entry::{LogEntry, WalEntry};
 
 /// Schema version information
 #[allow(dead_code)]
@@ -279,6 +281,122 @@ pub enum AlterType {
     Other,
 }
 
+/// Schema versions a follower has applied, overall and per table
+///
+/// Versions come from the WAL (`EntryHeader::schema_version`), which the
+/// leader bumps on every schema change. They are persisted in
+/// `{state_dir}/schema_version` so a follower that rejoins knows which DDL
+/// it has already run.
+#[derive(Debug)]
+pub struct SchemaVersionTracker {
+    path: PathBuf,
+    state: TrackedVersions,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TrackedVersions {
+    version: u64,
+    tables: HashMap<String, u64>,
+}
+
+impl SchemaVersionTracker {
+    /// Path of the schema version file in a state directory
+    pub fn state_path(state_dir: &Path) -> PathBuf {
+        state_dir.join("schema_version")
+    }
+
+    /// Create a tracker at version 0 that saves into `state_dir`
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            path: Self::state_path(state_dir),
+            state: TrackedVersions::default(),
+        }
+    }
+
+    /// Load the applied versions from `state_dir` (version 0 if none were saved)
+    pub fn load(state_dir: &Path) -> Result<Self> {
+        let mut tracker = Self::new(state_dir);
+        match std::fs::read_to_string(&tracker.path) {
+            Ok(contents) => {
+                tracker.state = serde_json::from_str(&contents).map_err(|e| {
+                    Error::Schema(format!("Invalid schema version file {:?}: {}", tracker.path, e))
+                })?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(tracker)
+    }
+
+    /// Schema version applied so far
+    pub fn version(&self) -> u64 {
+        self.state.version
+    }
+
+    /// Version of the last schema change applied to a table
+    pub fn table_version(&self, table: &str) -> Option<u64> {
+        self.state.tables.get(&table.to_lowercase()).copied()
+    }
+
+    /// Check whether an entry expects a newer schema than the one applied here
+    pub fn is_behind(&self, entry: &WalEntry) -> bool {
+        entry.header.schema_version > self.state.version
+    }
+
+    /// Check whether an entry is a schema change that has already been applied.
+    /// Unversioned entries (written before schema versions existed) never are.
+    pub fn has_applied(&self, entry: &WalEntry) -> bool {
+        let version = entry.header.schema_version;
+        version > 0 && version <= self.state.version && entry.entry.changes_schema()
+    }
+
+    /// Record an applied entry: a schema change sets its table's version, and
+    /// any entry moves the overall version up to its own
+    pub fn observe(&mut self, entry: &WalEntry) {
+        let version = entry.header.schema_version;
+        if entry.entry.changes_schema() {
+            if let Some(table) = Self::ddl_table(&entry.entry) {
+                let tracked = self.state.tables.entry(table).or_insert(0);
+                *tracked = (*tracked).max(version);
+            }
+        }
+        self.advance(version);
+    }
+
+    /// Move the applied version up to `version` (never down)
+    pub fn advance(&mut self, version: u64) {
+        self.state.version = self.state.version.max(version);
+    }
+
+    /// Persist the applied versions
+    pub fn save(&self) -> Result<()> {
+        let contents = serde_json::to_string(&self.state)
+            .map_err(|e| Error::Schema(format!("Failed to serialize schema versions: {}", e)))?;
+        let mut tmp = self.path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Table changed by a schema-changing entry
+    fn ddl_table(entry: &LogEntry) -> Option<String> {
+        if let Some(table) = entry.table_name() {
+            return Some(table.trim_matches('`').to_lowercase());
+        }
+        match entry {
+            LogEntry::RawSql { sql, .. } => match SchemaManager::new().validate_ddl(sql.trim()).ok()? {
+                DdlChange::CreateTable { table }
+                | DdlChange::DropTable { table }
+                | DdlChange::AlterTable { table, .. }
+                | DdlChange::CreateIndex { table }
+                | DdlChange::DropIndex { table } => Some(table),
+            },
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -319,4 +437,34 @@ mod tests {
         };
         assert!(manager.is_safe_change(&change));
     }
+
+    #[test]
+    fn test_schema_version_tracker() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = |version: u64, sql: &str| {
+            let mut entry = WalEntry::new(version, 1, "leader".to_string(), LogEntry::RawSql {
+                sql: sql.to_string(),
+                affects_table: None,
+                database: None,
+                gtid: None,
+            });
+            entry.header.schema_version = version;
+            entry
+        };
+
+        let mut tracker = SchemaVersionTracker::load(dir.path()).unwrap();
+        assert_eq!(tracker.version(), 0);
+        tracker.observe(&entry(1, "CREATE TABLE `Users` (id INT)"));
+        tracker.observe(&entry(2, "CREATE INDEX idx_email ON orders (email)"));
+        tracker.observe(&entry(2, "INSERT INTO users VALUES (1)"));
+        tracker.save().unwrap();
+
+        let tracker = SchemaVersionTracker::load(dir.path()).unwrap();
+        assert_eq!(tracker.version(), 2);
+        assert_eq!(tracker.table_version("users"), Some(1));
+        assert_eq!(tracker.table_version("orders"), Some(2));
+        assert!(tracker.has_applied(&entry(2, "CREATE INDEX idx_email ON orders (email)")));
+        assert!(!tracker.has_applied(&entry(2, "INSERT INTO users VALUES (1)")));
+        assert!(tracker.is_behind(&entry(3, "ALTER TABLE users ADD COLUMN email TEXT")));
+    }
 }
@@ -0,0 +1,333 @@
+//! Undo Log
+//!
+//! Before the executor applies a replicated entry it saves the rows the
+//! entry is about to change, as the SQL that would put them back, to
+//! `{state_dir}/undo.log`. `wolfscale undo` replays those statements to
+//! revert a bad write by hand. This is an operator tool, not recovery: an
+//! undo is not replicated and does not care what later entries did to the
+//! same rows.
+//!
+//! Each record is `[len: u32][crc32: u32][bincode UndoEntry]`. Once the file
+//! grows past `executor.undo_log_max_mb` the oldest records are dropped until
+//! it is back under three quarters of that, so the file isn't rewritten on
+//! every append.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::wal::Lsn;
+
+/// Record header: payload length and checksum
+const HEADER_SIZE: u64 = 8;
+
+/// How to revert one applied entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoEntry {
+    pub lsn: Lsn,
+    /// Statements restoring the rows the entry changed, run in order
+    pub inverse_sql: String,
+    /// Database the statements run against
+    pub database: Option<String>,
+}
+
+/// Append-only file of `UndoEntry` records, bounded in size
+pub struct UndoLog {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    /// (LSN, record size) of each record, oldest first
+    records: VecDeque<(Lsn, u64)>,
+    size: u64,
+}
+
+impl UndoLog {
+    /// Open (or create) the log at `path`. A torn record at the end, left
+    /// by a crash mid-append, is cut off.
+    pub fn open(path: impl AsRef<Path>, max_bytes: u64) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(&path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let mut records = VecDeque::new();
+        let mut size = 0;
+        for (entry, len) in decode(&data) {
+            records.push_back((entry.lsn, len));
+            size += len;
+        }
+        if size < data.len() as u64 {
+            tracing::warn!("Undo log {}: dropping {} bytes of a torn record", path.display(), data.len() as u64 - size);
+            file.set_len(size)?;
+        }
+
+        Ok(Self { path, max_bytes, file, records, size })
+    }
+
+    /// Save `entry`, dropping the oldest records if the log is over its size.
+    /// An entry bigger than the whole log is not saved.
+    pub fn append(&mut self, entry: &UndoEntry) -> Result<()> {
+        let payload = bincode::serialize(entry)?;
+        let len = HEADER_SIZE + payload.len() as u64;
+        if len > self.max_bytes {
+            tracing::warn!("Undo record for LSN {} is {} bytes, over the undo log's size; not saved", entry.lsn, len);
+            return Ok(());
+        }
+
+        let mut record = Vec::with_capacity(len as usize);
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+        self.file.write_all(&record)?;
+        self.records.push_back((entry.lsn, len));
+        self.size += len;
+
+        if self.size > self.max_bytes {
+            let mut dropped = 0;
+            while self.size - dropped > self.max_bytes * 3 / 4 {
+                let Some((_, len)) = self.records.pop_front() else { break };
+                dropped += len;
+            }
+            self.keep_from(dropped)?;
+        }
+        Ok(())
+    }
+
+    /// Every saved entry, oldest first
+    pub fn entries(&self) -> Result<Vec<UndoEntry>> {
+        let mut data = Vec::new();
+        File::open(&self.path)?.read_to_end(&mut data)?;
+        Ok(decode(&data).map(|(entry, _)| entry).collect())
+    }
+
+    /// The saved entry for `lsn`
+    pub fn get(&self, lsn: Lsn) -> Result<Option<UndoEntry>> {
+        Ok(self.entries()?.into_iter().find(|e| e.lsn == lsn))
+    }
+
+    /// The newest `count` entries, newest first (the order to undo them in)
+    pub fn last(&self, count: usize) -> Result<Vec<UndoEntry>> {
+        Ok(self.entries()?.into_iter().rev().take(count).collect())
+    }
+
+    /// Forget the entry for `lsn`, once it has been undone
+    pub fn remove(&mut self, lsn: Lsn) -> Result<()> {
+        let mut data = Vec::new();
+        File::open(&self.path)?.read_to_end(&mut data)?;
+        let mut kept = Vec::with_capacity(data.len());
+        let mut records = VecDeque::new();
+        let mut offset = 0;
+        for (entry, len) in decode(&data) {
+            if entry.lsn != lsn {
+                kept.extend_from_slice(&data[offset..offset + len as usize]);
+                records.push_back((entry.lsn, len));
+            }
+            offset += len as usize;
+        }
+        if records.len() == self.records.len() {
+            return Ok(());
+        }
+        self.records = records;
+        self.size = kept.len() as u64;
+        self.rewrite(&kept)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Size of the log file in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Rewrite the file without its first `offset` bytes
+    fn keep_from(&mut self, offset: u64) -> Result<()> {
+        let mut data = Vec::new();
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_to_end(&mut data)?;
+        self.size -= offset;
+        self.rewrite(&data)
+    }
+
+    /// Replace the file's contents, through a temporary file so a crash
+    /// leaves either the old or the new log
+    fn rewrite(&mut self, data: &[u8]) -> Result<()> {
+        let tmp = self.path.with_extension("log.tmp");
+        let mut out = File::create(&tmp)?;
+        out.write_all(data)?;
+        out.sync_all()?;
+        std::fs::rename(&tmp, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Decode records from the start of `data`, stopping at the first
+/// incomplete or corrupt one. Yields each entry with its record size.
+fn decode(data: &[u8]) -> impl Iterator<Item = (UndoEntry, u64)> + '_ {
+    let mut offset = 0usize;
+    std::iter::from_fn(move || {
+        let header = data.get(offset..offset + HEADER_SIZE as usize)?;
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let start = offset + HEADER_SIZE as usize;
+        let payload = data.get(start..start + len)?;
+        if crc32fast::hash(payload) != checksum {
+            return None;
+        }
+        let entry: UndoEntry = bincode::deserialize(payload).ok()?;
+        offset = start + len;
+        Some((entry, HEADER_SIZE + len as u64))
+    })
+}
+
+/// `db`.`table` (or just `table`) quoted for a statement
+pub fn table_ref(database: Option<&str>, table: &str) -> String {
+    let quote = |name: &str| format!("`{}`", name.replace('`', "``"));
+    match database {
+        Some(db) => format!("{}.{}", quote(db), quote(table)),
+        None => quote(table),
+    }
+}
+
+/// SQL literal for a column value read over the text protocol. Binary
+/// columns, and anything that isn't UTF-8, are written as hex.
+pub fn literal(value: Option<&[u8]>, binary: bool) -> String {
+    match value {
+        None => "NULL".to_string(),
+        Some(bytes) => match std::str::from_utf8(bytes) {
+            Ok(text) if !binary => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "''")),
+            _ => format!("X'{}'", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+        },
+    }
+}
+
+/// `REPLACE INTO` putting back saved rows (of literals), or `None` for no rows
+pub fn replace_rows(table: &str, columns: &[String], rows: &[Vec<String>]) -> Option<String> {
+    if rows.is_empty() {
+        return None;
+    }
+    let columns: Vec<String> = columns.iter().map(|c| format!("`{}`", c.replace('`', "``"))).collect();
+    let rows: Vec<String> = rows.iter().map(|row| format!("({})", row.join(", "))).collect();
+    Some(format!("REPLACE INTO {} ({}) VALUES {}", table, columns.join(", "), rows.join(", ")))
+}
+
+/// Condition matching the rows with the given key values (texts of literals)
+pub fn key_condition(key_columns: &[String], keys: &[Vec<String>]) -> String {
+    let columns: Vec<String> = key_columns.iter().map(|c| format!("`{}`", c.replace('`', "``"))).collect();
+    if let [column] = columns.as_slice() {
+        let values: Vec<&str> = keys.iter().map(|k| k[0].as_str()).collect();
+        format!("{} IN ({})", column, values.join(", "))
+    } else {
+        let tuples: Vec<String> = keys.iter().map(|k| format!("({})", k.join(", "))).collect();
+        format!("({}) IN ({})", columns.join(", "), tuples.join(", "))
+    }
+}
+
+/// Whether a value's text is a constant, so the row it keys can be found
+/// again (not `NULL`/`DEFAULT` for a generated key, or a call like `UUID()`)
+pub fn is_constant(text: &str) -> bool {
+    let text = text.trim_start_matches(['-', '+']);
+    text.starts_with(|c: char| c.is_ascii_digit() || c == '\'' || c == '"')
+        || (text.len() > 1 && text[..2].eq_ignore_ascii_case("x'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(lsn: Lsn) -> UndoEntry {
+        UndoEntry {
+            lsn,
+            inverse_sql: format!("DELETE FROM `t` WHERE `id` IN ({})", lsn),
+            database: Some("app".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_append_lookup_and_remove() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("undo.log");
+        let mut log = UndoLog::open(&path, 1 << 20).unwrap();
+        for lsn in 1..=10 {
+            log.append(&entry(lsn)).unwrap();
+        }
+
+        let log = UndoLog::open(&path, 1 << 20).unwrap();
+        assert_eq!(log.len(), 10);
+        assert_eq!(log.get(4).unwrap(), Some(entry(4)));
+        let last: Vec<Lsn> = log.last(3).unwrap().iter().map(|e| e.lsn).collect();
+        assert_eq!(last, vec![10, 9, 8]);
+
+        let mut log = log;
+        log.remove(9).unwrap();
+        log.append(&entry(11)).unwrap();
+        let lsns: Vec<Lsn> = log.entries().unwrap().iter().map(|e| e.lsn).collect();
+        assert_eq!(lsns, vec![1, 2, 3, 4, 5, 6, 7, 8, 10, 11]);
+        assert_eq!(log.size(), std::fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn test_evicts_oldest_over_size() {
+        let dir = tempdir().unwrap();
+        let record = HEADER_SIZE + bincode::serialize(&entry(1)).unwrap().len() as u64;
+        let mut log = UndoLog::open(dir.path().join("undo.log"), record * 8).unwrap();
+        for lsn in 1..=9 {
+            log.append(&entry(lsn)).unwrap();
+        }
+        // The ninth record overflows the log: it drops back to 3/4 full
+        let lsns: Vec<Lsn> = log.entries().unwrap().iter().map(|e| e.lsn).collect();
+        assert_eq!(lsns, vec![4, 5, 6, 7, 8, 9]);
+        assert_eq!(log.size(), record * 6);
+    }
+
+    #[test]
+    fn test_torn_record_is_cut_off() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("undo.log");
+        let mut log = UndoLog::open(&path, 1 << 20).unwrap();
+        log.append(&entry(1)).unwrap();
+        log.append(&entry(2)).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+        drop(log);
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+
+        let mut log = UndoLog::open(&path, 1 << 20).unwrap();
+        assert_eq!(log.entries().unwrap(), vec![entry(1)]);
+        log.append(&entry(3)).unwrap();
+        assert_eq!(log.entries().unwrap(), vec![entry(1), entry(3)]);
+    }
+
+    #[test]
+    fn test_restore_statements() {
+        let columns = vec!["id".to_string(), "name".to_string(), "data".to_string()];
+        let rows = vec![vec![
+            literal(Some(b"1"), false),
+            literal(Some(br"it's a \ path"), false),
+            literal(Some(&[0x00, 0xff]), true),
+        ]];
+        assert_eq!(
+            replace_rows(&table_ref(Some("app"), "users"), &columns, &rows).unwrap(),
+            r"REPLACE INTO `app`.`users` (`id`, `name`, `data`) VALUES ('1', 'it''s a \\ path', X'00ff')"
+        );
+        assert_eq!(replace_rows("`t`", &columns, &[]), None);
+        assert_eq!(literal(None, false), "NULL");
+
+        let keys = vec![vec!["1".to_string(), "'a'".to_string()], vec!["2".to_string(), "'b'".to_string()]];
+        assert_eq!(key_condition(&["a".to_string(), "b".to_string()], &keys), "(`a`, `b`) IN ((1, 'a'), (2, 'b'))");
+        assert_eq!(key_condition(&["id".to_string()], &[vec!["7".to_string()]]), "`id` IN (7)");
+        assert!(is_constant("-1") && is_constant("'x'") && is_constant("X'00'"));
+        assert!(!is_constant("NULL") && !is_constant("DEFAULT") && !is_constant("UUID()"));
+    }
+}
@@ -8,6 +8,11 @@
 //! - 41 bits: timestamp (milliseconds since epoch, ~69 years)
 //! - 10 bits: node ID (0-1023)
 //! - 12 bits: sequence (0-4095 per millisecond)
+//!
+//! The HTTP API's `/id` endpoints hand out IDs from the leader only, with
+//! the node's index in the cluster as the node ID. Each allocation is written
+//! to the WAL as `LogEntry::IdAllocation`; followers record the highest ID
+//! they replay, so a node that takes over as leader carries on after it.
 
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -30,6 +35,30 @@ const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
 const NODE_ID_SHIFT: u64 = SEQUENCE_BITS;
 const TIMESTAMP_SHIFT: u64 = NODE_ID_BITS + SEQUENCE_BITS;
 
+/// Highest ID allocated cluster-wide that this node knows of
+static HIGHEST_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+/// Record an ID allocated by the leader (from its `/id` endpoints or a
+/// replayed `LogEntry::IdAllocation`)
+pub fn record_allocation(highest_id: u64) {
+    HIGHEST_ALLOCATED.fetch_max(highest_id, Ordering::SeqCst);
+}
+
+/// Highest ID allocated cluster-wide that this node knows of (0 if none)
+pub fn highest_allocated() -> u64 {
+    HIGHEST_ALLOCATED.load(Ordering::SeqCst)
+}
+
+/// Node ID for Snowflake IDs: the position of `node_id` among the sorted
+/// cluster member IDs, so no two members share one
+pub fn machine_id<'a>(node_id: &str, members: impl IntoIterator<Item = &'a str>) -> u16 {
+    let mut members: Vec<&str> = members.into_iter().collect();
+    members.sort_unstable();
+    members.dedup();
+    let index = members.iter().position(|m| *m == node_id).unwrap_or(members.len());
+    (index as u64 % (MAX_NODE_ID + 1)) as u16
+}
+
 /// Snowflake ID wrapper type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SnowflakeId(pub u64);
@@ -79,11 +108,35 @@ impl From<SnowflakeId> for u64 {
     }
 }
 
+/// A run of consecutive IDs, `first..=last`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdRange {
+    pub first: SnowflakeId,
+    pub last: SnowflakeId,
+}
+
+impl IdRange {
+    /// Number of IDs in the range
+    pub fn len(&self) -> u64 {
+        self.last.0 - self.first.0 + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SnowflakeId> {
+        (self.first.0..=self.last.0).map(SnowflakeId)
+    }
+}
+
 /// Snowflake ID Generator
 ///
 /// Thread-safe generator that produces unique IDs for a specific node.
 pub struct SnowflakeGenerator {
-    node_id: u64,
+    /// Node ID bits; may change (see `set_node_id`) without risking
+    /// duplicates, as the timestamp and sequence never repeat
+    node_id: AtomicU64,
     /// Packed state: upper 52 bits = last_timestamp, lower 12 bits = sequence
     state: AtomicU64,
 }
@@ -101,15 +154,43 @@ impl SnowflakeGenerator {
         );
 
         Self {
-            node_id: node_id as u64,
+            node_id: AtomicU64::new(node_id as u64),
             state: AtomicU64::new(0),
         }
     }
 
+    /// Node ID put into generated IDs
+    pub fn node_id(&self) -> u16 {
+        self.node_id.load(Ordering::Relaxed) as u16
+    }
+
+    /// Change the node ID put into generated IDs
+    ///
+    /// # Panics
+    /// Panics if node_id > 1023
+    pub fn set_node_id(&self, node_id: u16) {
+        assert!((node_id as u64) <= MAX_NODE_ID, "Node ID must be 0-1023, got {}", node_id);
+        self.node_id.store(node_id as u64, Ordering::Relaxed);
+    }
+
+    /// Never generate an ID at or before `id`'s timestamp and sequence,
+    /// whatever node generated it
+    pub fn observe(&self, id: SnowflakeId) {
+        let state = ((id.0 >> TIMESTAMP_SHIFT) << SEQUENCE_BITS) | (id.0 & MAX_SEQUENCE);
+        self.state.fetch_max(state, Ordering::SeqCst);
+    }
+
     /// Generate a new unique ID
     ///
     /// This method is lock-free and thread-safe.
     pub fn generate(&self) -> SnowflakeId {
+        self.reserve(1).first
+    }
+
+    /// Reserve up to `max` consecutive IDs (at least one) in a single step.
+    /// A range never spans milliseconds, so it holds at most 4096 IDs.
+    pub fn reserve(&self, max: u64) -> IdRange {
+        let max = max.clamp(1, MAX_SEQUENCE + 1);
         loop {
             let current_time = Self::current_time_millis();
             let old_state = self.state.load(Ordering::Relaxed);
@@ -139,17 +220,20 @@ impl SnowflakeGenerator {
                 (old_timestamp, next_seq)
             };
 
-            let new_state = (new_timestamp << SEQUENCE_BITS) | new_sequence;
+            let last_sequence = (new_sequence + max - 1).min(MAX_SEQUENCE);
+            let new_state = (new_timestamp << SEQUENCE_BITS) | last_sequence;
 
             if self
                 .state
                 .compare_exchange(old_state, new_state, Ordering::SeqCst, Ordering::Relaxed)
                 .is_ok()
             {
-                let id = (new_timestamp << TIMESTAMP_SHIFT)
-                    | (self.node_id << NODE_ID_SHIFT)
-                    | new_sequence;
-                return SnowflakeId(id);
+                let base = (new_timestamp << TIMESTAMP_SHIFT)
+                    | (self.node_id.load(Ordering::Relaxed) << NODE_ID_SHIFT);
+                return IdRange {
+                    first: SnowflakeId(base | new_sequence),
+                    last: SnowflakeId(base | last_sequence),
+                };
             }
             // CAS failed, retry
         }
@@ -164,6 +248,18 @@ impl SnowflakeGenerator {
         ids
     }
 
+    /// Reserve `count` IDs as a few ranges of consecutive IDs
+    pub fn reserve_batch(&self, count: u64) -> Vec<IdRange> {
+        let mut ranges = Vec::new();
+        let mut remaining = count;
+        while remaining > 0 {
+            let range = self.reserve(remaining);
+            remaining -= range.len();
+            ranges.push(range);
+        }
+        ranges
+    }
+
     /// Get current time in milliseconds since WOLFSCALE_EPOCH
     fn current_time_millis() -> u64 {
         SystemTime::now()
@@ -262,6 +358,31 @@ mod tests {
         assert!(id.timestamp() > WOLFSCALE_EPOCH);
     }
 
+    #[test]
+    fn test_reserve_and_observe() {
+        let gen = SnowflakeGenerator::new(3);
+        let ranges = gen.reserve_batch(10_000);
+        assert_eq!(ranges.iter().map(|r| r.len()).sum::<u64>(), 10_000);
+        assert!(ranges.iter().all(|r| r.len() <= 4096 && r.first.node_id() == 3));
+        assert!(ranges.windows(2).all(|w| w[0].last < w[1].first));
+
+        // Another node's allocation in the future: carry on after it
+        let ahead = SnowflakeId(ranges[0].last.0 + (1_000 << TIMESTAMP_SHIFT) + 5 - (3 << NODE_ID_SHIFT));
+        gen.set_node_id(4);
+        gen.observe(ahead);
+        let next = gen.generate();
+        assert_eq!((next.timestamp(), next.node_id()), (ahead.timestamp(), 4));
+        assert!(next.sequence() > ahead.sequence());
+    }
+
+    #[test]
+    fn test_machine_id_is_cluster_index() {
+        let members = ["node-c", "node-a", "node-b"];
+        assert_eq!(machine_id("node-a", members), 0);
+        assert_eq!(machine_id("node-c", members), 2);
+        assert_eq!(machine_id("node-d", members), 3);
+    }
+
     #[test]
     fn test_parse_node_id() {
         assert_eq!(SnowflakeGenerator::parse_node_id("node-5"), 5);
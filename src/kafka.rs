@@ -0,0 +1,618 @@
+//! Change Data Capture to Kafka
+//!
+//! With `[kafka] enabled = true` (and the `kafka` feature), the leader
+//! publishes every flushed WAL entry to a Kafka topic as row change events.
+//! Publishing reads the WAL rather than hooking the writer, so only entries
+//! that are durably written are ever sent, and it survives the writer being
+//! replaced after an election.
+//!
+//! Each event is keyed `database.table:primary_key`, so with Kafka's default
+//! partitioner every change to a row lands on the same partition, in order.
+//! The value is the event encoded as JSON or as Avro (binary, no container
+//! or registry framing; the writer schema is [`AVRO_SCHEMA`]). The message
+//! timestamp is the time the entry was written and the LSN travels in the
+//! `wolfscale-lsn` header; using the LSN as the timestamp would put every
+//! message decades in the past and have time-based retention delete it.
+//!
+//! The WAL only holds the new values of a change, so `before` carries just
+//! the key columns of updates and deletes. Delivery is at-least-once: the
+//! producer is idempotent, but a batch that fails part way is re-sent whole,
+//! and a new leader re-publishes the last `REPUBLISH_WINDOW` entries it
+//! applied as a follower in case the old leader hadn't sent them. Consumers
+//! should drop events whose LSN they have already seen.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(feature = "kafka")]
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+
+use crate::config::KafkaConfig;
+use crate::error::Result;
+use crate::state::ClusterMembership;
+use crate::wal::entry::{LogEntry, Lsn, PrimaryKey, Value, WalEntry};
+use crate::wal::WalReader;
+
+/// Avro writer schema of the change events when `kafka.schema = "avro"`
+pub const AVRO_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "ChangeEvent",
+  "namespace": "io.wolfscale",
+  "fields": [
+    {"name": "lsn", "type": "long"},
+    {"name": "timestamp_ms", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+    {"name": "op", "type": "string"},
+    {"name": "database", "type": ["null", "string"], "default": null},
+    {"name": "table", "type": ["null", "string"], "default": null},
+    {"name": "key", "type": ["null", "string"], "default": null},
+    {"name": "before", "type": ["null", {"type": "map", "values": "string"}], "default": null},
+    {"name": "after", "type": ["null", {"type": "map", "values": "string"}], "default": null},
+    {"name": "sql", "type": ["null", "string"], "default": null}
+  ]
+}"#;
+
+/// Header carrying the LSN of the entry an event came from
+pub const LSN_HEADER: &str = "wolfscale-lsn";
+
+/// Entries a follower keeps unpublished in case it becomes leader
+#[cfg(feature = "kafka")]
+const REPUBLISH_WINDOW: Lsn = 10_000;
+
+/// Entries read from the WAL and published per round
+#[cfg(feature = "kafka")]
+const BATCH_ENTRIES: usize = 500;
+
+/// How often the publisher looks for new entries
+#[cfg(feature = "kafka")]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Encoding of change event values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeSchema {
+    Json,
+    Avro,
+}
+
+impl ChangeSchema {
+    /// Parse the `kafka.schema` config value
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(ChangeSchema::Json),
+            "avro" => Some(ChangeSchema::Avro),
+            _ => None,
+        }
+    }
+}
+
+/// One row change (or statement) published to Kafka
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangeEvent {
+    pub lsn: Lsn,
+    /// When the entry was written, in milliseconds since the epoch
+    pub timestamp_ms: i64,
+    /// "insert", "update", "upsert", "delete", "ddl" or "sql"
+    pub op: &'static str,
+    pub database: Option<String>,
+    pub table: Option<String>,
+    /// Primary key, as shown by `PrimaryKey`'s Display
+    pub key: Option<String>,
+    /// Column values before the change (key columns only)
+    pub before: Option<Map<String, JsonValue>>,
+    /// Column values after the change
+    pub after: Option<Map<String, JsonValue>>,
+    /// Statement text for DDL and raw SQL
+    pub sql: Option<String>,
+}
+
+impl ChangeEvent {
+    fn new(entry: &WalEntry, op: &'static str, table: Option<&str>, default_database: Option<&str>) -> Self {
+        let (database, table) = match table.map(split_table) {
+            Some((Some(db), table)) => (Some(db), Some(table)),
+            Some((None, table)) => (default_database.map(str::to_string), Some(table)),
+            None => (default_database.map(str::to_string), None),
+        };
+        Self {
+            lsn: entry.header.lsn,
+            timestamp_ms: entry.header.timestamp.timestamp_millis(),
+            op,
+            database,
+            table,
+            key: None,
+            before: None,
+            after: None,
+            sql: None,
+        }
+    }
+
+    /// Kafka message key, `database.table:primary_key`. Changes without a
+    /// table have no key and are spread across partitions.
+    pub fn message_key(&self) -> Option<String> {
+        let table = self.table.as_deref()?;
+        let mut key = match self.database.as_deref() {
+            Some(db) => format!("{}.{}", db, table),
+            None => table.to_string(),
+        };
+        if let Some(pk) = &self.key {
+            key.push(':');
+            key.push_str(pk);
+        }
+        Some(key)
+    }
+
+    /// Encode the event as a message value
+    pub fn encode(&self, schema: ChangeSchema) -> Vec<u8> {
+        match schema {
+            ChangeSchema::Json => serde_json::to_vec(self).unwrap_or_default(),
+            ChangeSchema::Avro => self.encode_avro(),
+        }
+    }
+
+    /// Avro binary encoding against `AVRO_SCHEMA`. Map values are the JSON
+    /// text of each column value, since columns differ in type.
+    fn encode_avro(&self) -> Vec<u8> {
+        fn long(buf: &mut Vec<u8>, v: i64) {
+            let mut n = ((v << 1) ^ (v >> 63)) as u64;
+            while n >= 0x80 {
+                buf.push((n as u8) | 0x80);
+                n >>= 7;
+            }
+            buf.push(n as u8);
+        }
+        fn string(buf: &mut Vec<u8>, s: &str) {
+            long(buf, s.len() as i64);
+            buf.extend_from_slice(s.as_bytes());
+        }
+        fn opt_string(buf: &mut Vec<u8>, s: Option<&str>) {
+            match s {
+                Some(s) => {
+                    long(buf, 1);
+                    string(buf, s);
+                }
+                None => long(buf, 0),
+            }
+        }
+        fn opt_map(buf: &mut Vec<u8>, map: Option<&Map<String, JsonValue>>) {
+            let Some(map) = map else {
+                long(buf, 0);
+                return;
+            };
+            long(buf, 1);
+            if !map.is_empty() {
+                long(buf, map.len() as i64);
+                for (column, value) in map {
+                    string(buf, column);
+                    string(buf, &value.to_string());
+                }
+            }
+            long(buf, 0);
+        }
+
+        let mut buf = Vec::with_capacity(128);
+        long(&mut buf, self.lsn as i64);
+        long(&mut buf, self.timestamp_ms);
+        string(&mut buf, self.op);
+        opt_string(&mut buf, self.database.as_deref());
+        opt_string(&mut buf, self.table.as_deref());
+        opt_string(&mut buf, self.key.as_deref());
+        opt_map(&mut buf, self.before.as_ref());
+        opt_map(&mut buf, self.after.as_ref());
+        opt_string(&mut buf, self.sql.as_deref());
+        buf
+    }
+}
+
+/// The change events of one WAL entry (none for no-ops and ID allocations,
+/// one per row of a bulk insert, the contents of a transaction in order)
+pub fn change_events(entry: &WalEntry, default_database: Option<&str>) -> Vec<ChangeEvent> {
+    let mut events = Vec::new();
+    collect_events(entry, &entry.entry, default_database, &mut events);
+    events
+}
+
+fn collect_events(wal: &WalEntry, entry: &LogEntry, default_database: Option<&str>, events: &mut Vec<ChangeEvent>) {
+    let event = |op, table: Option<&str>| ChangeEvent::new(wal, op, table, default_database);
+    match entry {
+        LogEntry::Insert { table, columns, values, primary_key } => events.push(ChangeEvent {
+            key: Some(primary_key.to_string()),
+            after: Some(row(columns, values)),
+            ..event("insert", Some(table))
+        }),
+        LogEntry::Upsert { table, columns, values, primary_key, .. } => events.push(ChangeEvent {
+            key: Some(primary_key.to_string()),
+            after: Some(row(columns, values)),
+            ..event("upsert", Some(table))
+        }),
+        LogEntry::Update { table, set_columns, set_values, primary_key, key_columns } => events.push(ChangeEvent {
+            key: Some(primary_key.to_string()),
+            before: Some(key_row(primary_key, key_columns)),
+            after: Some(row(set_columns, set_values)),
+            ..event("update", Some(table))
+        }),
+        LogEntry::Delete { table, primary_key, key_columns } => events.push(ChangeEvent {
+            key: Some(primary_key.to_string()),
+            before: Some(key_row(primary_key, key_columns)),
+            ..event("delete", Some(table))
+        }),
+        LogEntry::BulkInsert { table, columns, rows } => {
+            for values in rows {
+                events.push(ChangeEvent { after: Some(row(columns, values)), ..event("insert", Some(table)) });
+            }
+        }
+        LogEntry::AlterTable { table, ddl }
+        | LogEntry::CreateTable { table, ddl }
+        | LogEntry::CreateIndex { table, ddl, .. } => {
+            events.push(ChangeEvent { sql: Some(ddl.clone()), ..event("ddl", Some(table)) });
+        }
+        LogEntry::DropTable { table } => events.push(ChangeEvent {
+            sql: Some(format!("DROP TABLE `{}`", table)),
+            ..event("ddl", Some(table))
+        }),
+        LogEntry::DropIndex { table, index_name } => events.push(ChangeEvent {
+            sql: Some(format!("DROP INDEX `{}` ON `{}`", index_name, table)),
+            ..event("ddl", Some(table))
+        }),
+        LogEntry::Transaction { entries, .. } => {
+            for inner in entries {
+                collect_events(wal, inner, default_database, events);
+            }
+        }
+        LogEntry::RawSql { sql, affects_table, database, .. } => {
+            let mut raw = event("sql", affects_table.as_deref());
+            if database.is_some() {
+                raw.database = database.clone();
+            }
+            raw.sql = Some(sql.clone());
+            events.push(raw);
+        }
+        LogEntry::Noop | LogEntry::IdAllocation { .. } => {}
+    }
+}
+
+/// Split `db.table` (optionally backquoted) into its parts
+fn split_table(table: &str) -> (Option<String>, String) {
+    let unquote = |s: &str| s.trim().trim_matches('`').to_string();
+    match table.split_once('.') {
+        Some((db, table)) => (Some(unquote(db)), unquote(table)),
+        None => (None, unquote(table)),
+    }
+}
+
+fn row(columns: &[String], values: &[Value]) -> Map<String, JsonValue> {
+    columns.iter().cloned().zip(values.iter().map(json_value)).collect()
+}
+
+/// Key columns and values, with the same `id` default as `to_where_clause`
+fn key_row(key: &PrimaryKey, key_columns: &[String]) -> Map<String, JsonValue> {
+    let first = || key_columns.first().cloned().unwrap_or_else(|| "id".to_string());
+    match key {
+        PrimaryKey::Int(v) => Map::from_iter([(first(), JsonValue::from(*v))]),
+        PrimaryKey::String(v) => Map::from_iter([(first(), JsonValue::from(v.as_str()))]),
+        PrimaryKey::Uuid(v) => Map::from_iter([(first(), JsonValue::from(v.to_string()))]),
+        PrimaryKey::Composite(values) => row(key_columns, values),
+    }
+}
+
+fn json_value(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => JsonValue::from(*b),
+        Value::Int(i) => JsonValue::from(*i),
+        Value::UInt(u) => JsonValue::from(*u),
+        Value::Float(f) => JsonValue::from(*f),
+        Value::String(s) => JsonValue::from(s.as_str()),
+        Value::Bytes(b) => JsonValue::from(b.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+        Value::Uuid(u) => JsonValue::from(u.to_string()),
+        Value::Timestamp(t) => JsonValue::from(t.to_rfc3339()),
+        Value::Json(j) => j.clone(),
+    }
+}
+
+/// Publishes change events to the configured topic
+#[cfg(feature = "kafka")]
+pub struct KafkaProducer {
+    producer: rdkafka::producer::FutureProducer,
+    topic: String,
+    schema: ChangeSchema,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaProducer {
+    /// Connect an idempotent producer to `config.brokers`
+    pub fn new(config: &KafkaConfig) -> Result<Self> {
+        let schema = ChangeSchema::parse(&config.schema)
+            .ok_or_else(|| crate::Error::Config(format!("Unknown kafka.schema \"{}\"", config.schema)))?;
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", config.brokers.join(","))
+            .set("enable.idempotence", "true")
+            .set("message.timeout.ms", "30000")
+            .create()
+            .map_err(|e| crate::Error::Config(format!("Kafka producer for {}: {}", config.brokers.join(","), e)))?;
+        Ok(Self { producer, topic: config.topic.clone(), schema })
+    }
+
+    /// Send `events` in order and wait until every one is acknowledged
+    pub async fn publish(&self, events: &[ChangeEvent]) -> Result<()> {
+        use rdkafka::message::{Header, OwnedHeaders};
+        use rdkafka::producer::FutureRecord;
+
+        let encoded: Vec<(Option<String>, Vec<u8>, String)> = events
+            .iter()
+            .map(|e| (e.message_key(), e.encode(self.schema), e.lsn.to_string()))
+            .collect();
+        // Enqueued in order on the first poll; the idempotent producer keeps
+        // that order per partition across its retries
+        let sends = encoded.iter().zip(events).map(|((key, payload, lsn), event)| {
+            let mut record = FutureRecord::<str, [u8]>::to(&self.topic)
+                .payload(payload.as_slice())
+                .timestamp(event.timestamp_ms)
+                .headers(OwnedHeaders::new().insert(Header { key: LSN_HEADER, value: Some(lsn.as_str()) }));
+            if let Some(key) = key {
+                record = record.key(key.as_str());
+            }
+            self.producer.send(record, Duration::from_secs(30))
+        });
+
+        let mut first_error = None;
+        for result in futures::future::join_all(sends).await {
+            match result {
+                Ok(_) => crate::metrics::KAFKA_MESSAGES_SENT.inc(),
+                Err((e, _)) => {
+                    crate::metrics::KAFKA_SEND_ERRORS.inc();
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(crate::Error::Network(format!("Kafka publish to {} failed: {}", self.topic, e))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Publish WAL entries while this node is leader, until the process exits.
+/// Progress is saved to `offset_path` so a restart resumes where it stopped.
+#[cfg(feature = "kafka")]
+pub async fn run_publisher(
+    producer: KafkaProducer,
+    mut reader: WalReader,
+    cluster: Arc<ClusterMembership>,
+    node_id: String,
+    offset_path: PathBuf,
+    default_database: Option<String>,
+) {
+    let _ = reader.refresh_index();
+    let mut next = match load_offset(&offset_path) {
+        Some(lsn) => lsn,
+        // First start: only changes from now on
+        None => reader.last_lsn().ok().flatten().map_or(1, |lsn| lsn + 1),
+    };
+    let mut saved = None;
+    tracing::info!("Publishing WAL changes to Kafka topic {} from LSN {}", producer.topic, next);
+
+    loop {
+        if saved != Some(next) {
+            save_offset(&offset_path, next);
+            saved = Some(next);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if reader.refresh_index().is_err() {
+            continue;
+        }
+
+        let is_leader = cluster.current_leader().await.is_some_and(|l| l.id == node_id);
+        if !is_leader {
+            // The leader publishes these; keep a window to re-send after a failover
+            if let Ok(Some(last)) = reader.last_lsn() {
+                next = next.max(last.saturating_sub(REPUBLISH_WINDOW) + 1);
+            }
+            continue;
+        }
+
+        let batch = match reader.read_batch(next, BATCH_ENTRIES) {
+            Ok(batch) if !batch.is_empty() => batch,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("Kafka publisher failed to read the WAL at LSN {}: {}", next, e);
+                continue;
+            }
+        };
+        let last = batch.last().map(|e| e.header.lsn).unwrap_or(next);
+        let events: Vec<ChangeEvent> = batch
+            .iter()
+            .flat_map(|entry| change_events(entry, default_database.as_deref()))
+            .collect();
+        match producer.publish(&events).await {
+            Ok(()) => next = last + 1,
+            Err(e) => {
+                tracing::warn!("{}; retrying from LSN {}", e, next);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+/// Start the publisher if `[kafka]` is enabled
+pub fn start(
+    config: &KafkaConfig,
+    reader: WalReader,
+    cluster: Arc<ClusterMembership>,
+    node_id: String,
+    offset_path: PathBuf,
+    default_database: Option<String>,
+) -> Result<()> {
+    if !config.enabled {
+        return Ok(());
+    }
+
+    #[cfg(feature = "kafka")]
+    {
+        let producer = KafkaProducer::new(config)?;
+        tokio::spawn(run_publisher(producer, reader, cluster, node_id, offset_path, default_database));
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    {
+        let _ = (reader, cluster, offset_path, default_database);
+        tracing::warn!(
+            "kafka.enabled ignored (node {}): built without the `kafka` feature",
+            node_id
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "kafka")]
+fn load_offset(path: &std::path::Path) -> Option<Lsn> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(feature = "kafka")]
+fn save_offset(path: &std::path::Path, lsn: Lsn) {
+    let tmp = path.with_extension("tmp");
+    let result = std::fs::write(&tmp, lsn.to_string()).and_then(|_| std::fs::rename(&tmp, path));
+    if let Err(e) = result {
+        tracing::warn!("Failed to save Kafka offset to {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wal_entry(lsn: Lsn, entry: LogEntry) -> WalEntry {
+        WalEntry::new(lsn, 1, "node-1".to_string(), entry)
+    }
+
+    #[test]
+    fn test_row_changes_keyed_by_table_and_primary_key() {
+        let insert = wal_entry(7, LogEntry::Insert {
+            table: "shop.orders".to_string(),
+            columns: vec!["id".to_string(), "total".to_string(), "note".to_string()],
+            values: vec![Value::Int(42), Value::Float(9.5), Value::Null],
+            primary_key: PrimaryKey::Int(42),
+        });
+        let events = change_events(&insert, Some("app"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message_key().as_deref(), Some("shop.orders:42"));
+        let json: JsonValue = serde_json::from_slice(&events[0].encode(ChangeSchema::Json)).unwrap();
+        assert_eq!(json["lsn"], 7);
+        assert_eq!(json["op"], "insert");
+        assert_eq!(json["before"], JsonValue::Null);
+        assert_eq!(json["after"], serde_json::json!({"id": 42, "total": 9.5, "note": null}));
+
+        // Unqualified tables take the default database; a transaction keeps its order
+        let txn = wal_entry(8, LogEntry::Transaction {
+            entries: vec![
+                LogEntry::Update {
+                    table: "users".to_string(),
+                    set_columns: vec!["name".to_string()],
+                    set_values: vec![Value::String("Ann".to_string())],
+                    primary_key: PrimaryKey::String("u1".to_string()),
+                    key_columns: vec!["uid".to_string()],
+                },
+                LogEntry::Delete {
+                    table: "users".to_string(),
+                    primary_key: PrimaryKey::Composite(vec![Value::Int(1), Value::Int(2)]),
+                    key_columns: vec!["a".to_string(), "b".to_string()],
+                },
+                LogEntry::Noop,
+            ],
+            transaction_id: None,
+        });
+        let events = change_events(&txn, Some("app"));
+        assert_eq!(events.iter().map(|e| e.op).collect::<Vec<_>>(), ["update", "delete"]);
+        assert_eq!(events[0].message_key().as_deref(), Some("app.users:u1"));
+        assert_eq!(events[0].before, Some(Map::from_iter([("uid".to_string(), JsonValue::from("u1"))])));
+        assert_eq!(events[0].after, Some(Map::from_iter([("name".to_string(), JsonValue::from("Ann"))])));
+        assert_eq!(events[1].message_key().as_deref(), Some("app.users:(1, 2)"));
+        assert_eq!(events[1].before, Some(serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap()));
+        assert!(events.iter().all(|e| e.lsn == 8));
+
+        let raw = wal_entry(9, LogEntry::RawSql {
+            sql: "TRUNCATE logs".to_string(),
+            affects_table: None,
+            database: None,
+            gtid: None,
+        });
+        let events = change_events(&raw, None);
+        assert_eq!(events[0].message_key(), None);
+        assert_eq!(events[0].sql.as_deref(), Some("TRUNCATE logs"));
+    }
+
+    #[test]
+    fn test_avro_encoding() {
+        let mut event = ChangeEvent::new(&wal_entry(1, LogEntry::Noop), "delete", Some("db.t"), None);
+        event.timestamp_ms = 1_000;
+        event.key = Some("5".to_string());
+        event.before = Some(Map::from_iter([("id".to_string(), JsonValue::from(5))]));
+
+        let expected: Vec<u8> = [
+            &[0x02][..],                   // lsn 1 (zigzag)
+            &[0xd0, 0x0f],                 // timestamp 1000
+            &[0x0c], b"delete",            // op
+            &[0x02, 0x04], b"db",          // database: union branch 1, "db"
+            &[0x02, 0x02], b"t",           // table
+            &[0x02, 0x02], b"5",           // key
+            &[0x02, 0x02, 0x04], b"id", &[0x02], b"5", &[0x00], // before: one-entry block, end
+            &[0x00],                       // after: null
+            &[0x00],                       // sql: null
+        ].concat();
+        assert_eq!(event.encode(ChangeSchema::Avro), expected);
+        assert!(serde_json::from_str::<JsonValue>(AVRO_SCHEMA).is_ok());
+    }
+
+    /// Needs a Kafka-compatible broker, e.g. Redpanda:
+    /// `docker run -d -p 9092:9092 redpandadata/redpanda redpanda start --mode dev-container`, then
+    /// `WOLFSCALE_KAFKA_TEST_BROKERS=localhost:9092 cargo test --features kafka kafka -- --ignored`
+    #[cfg(feature = "kafka")]
+    #[tokio::test]
+    #[ignore]
+    async fn test_events_reach_topic() {
+        use rdkafka::consumer::{Consumer, StreamConsumer};
+        use rdkafka::message::{Headers, Message};
+
+        let Ok(brokers) = std::env::var("WOLFSCALE_KAFKA_TEST_BROKERS") else { return };
+        let topic = format!("wolfscale-cdc-test-{}", uuid::Uuid::new_v4());
+        let config = KafkaConfig {
+            enabled: true,
+            brokers: vec![brokers.clone()],
+            topic: topic.clone(),
+            schema: "json".to_string(),
+        };
+        let producer = KafkaProducer::new(&config).unwrap();
+        let events: Vec<ChangeEvent> = (1..=20)
+            .flat_map(|i| change_events(&wal_entry(i, LogEntry::Insert {
+                table: "shop.orders".to_string(),
+                columns: vec!["id".to_string()],
+                values: vec![Value::Int(i as i64 % 3)],
+                primary_key: PrimaryKey::Int(i as i64 % 3),
+            }), None))
+            .collect();
+        producer.publish(&events).await.unwrap();
+
+        let consumer: StreamConsumer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", &brokers)
+            .set("group.id", &topic)
+            .set("auto.offset.reset", "earliest")
+            .create()
+            .unwrap();
+        consumer.subscribe(&[&topic]).unwrap();
+
+        // Per key, the LSNs arrive in the order they were written
+        let mut last_lsn: std::collections::HashMap<String, u64> = Default::default();
+        for _ in 0..events.len() {
+            let msg = tokio::time::timeout(Duration::from_secs(30), consumer.recv()).await.unwrap().unwrap();
+            assert_eq!(msg.topic(), topic);
+            let key = String::from_utf8(msg.key().unwrap().to_vec()).unwrap();
+            let value: JsonValue = serde_json::from_slice(msg.payload().unwrap()).unwrap();
+            let lsn = value["lsn"].as_u64().unwrap();
+            let header = msg.headers().unwrap().iter().find(|h| h.key == LSN_HEADER).unwrap();
+            assert_eq!(header.value, Some(lsn.to_string().as_bytes()));
+            assert_eq!(key, format!("shop.orders:{}", lsn % 3));
+            assert!(last_lsn.insert(key, lsn).is_none_or(|prev| prev < lsn));
+        }
+    }
+}
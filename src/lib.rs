@@ -33,6 +33,12 @@ pub mod proxy;
 pub mod binlog;
 pub mod tuning;
 pub mod lb;
+pub mod metrics;
+pub mod telemetry;
+pub mod logging;
+pub mod audit;
+pub mod kafka;
+pub mod reload;
 
 pub use config::WolfScaleConfig;
 pub use error::{Error, Result};
@@ -0,0 +1,177 @@
+//! Log output
+//!
+//! Events go to stdout and, with `logging.file`, to a file rotated daily
+//! (`wolfscale.log.2026-01-31`, ...), both in the configured `logging.format`.
+//! The JSON format writes one object per line with `timestamp`, `level`,
+//! `target`, `message`, the event's fields and the current span, ready for
+//! ELK or Datadog. `GET /logs` serves the tail of the current file.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{LogFormat, LoggingConfig};
+use crate::error::{Error, Result};
+
+/// A layer writing events to `writer` in `format`
+pub fn format_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(ansi);
+    match format {
+        LogFormat::Pretty => layer.boxed(),
+        LogFormat::Json => layer.json().flatten_event(true).boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+    }
+}
+
+/// Install the global subscriber, filtered at `level` unless `RUST_LOG` is
+/// set. Keep the returned guard until exit: dropping it stops the file
+/// writer, and lines still queued for it are flushed on drop.
+pub fn init(config: &LoggingConfig, level: &str) -> Result<Option<WorkerGuard>> {
+    let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| level.into());
+    let mut layers = vec![format_layer(config.format, io::stdout, true)];
+
+    let mut guard = None;
+    if let Some(path) = &config.file {
+        let (directory, prefix) = split_log_path(path)
+            .ok_or_else(|| Error::Config(format!("logging.file {} has no file name", path.display())))?;
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(prefix)
+            .build(&directory)
+            .map_err(|e| Error::Config(format!("logging.file {}: {}", path.display(), e)))?;
+        let (writer, file_guard) = tracing_appender::non_blocking(appender);
+        layers.push(format_layer(config.format, writer, false));
+        guard = Some(file_guard);
+    }
+
+    tracing_subscriber::registry().with(env_filter).with(layers).init();
+    Ok(guard)
+}
+
+/// Directory and file name of `logging.file`
+fn split_log_path(path: &Path) -> Option<(PathBuf, String)> {
+    let prefix = path.file_name()?.to_string_lossy().into_owned();
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Some((directory, prefix))
+}
+
+/// The file currently written for `logging.file`: the newest of its daily
+/// files, which are named after it with the date appended
+pub fn current_log_file(path: &Path) -> Option<PathBuf> {
+    let (directory, prefix) = split_log_path(path)?;
+    let dated = format!("{}.", prefix);
+    std::fs::read_dir(&directory).ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| *name == prefix || name.starts_with(&dated))
+        .max()
+        .map(|name| directory.join(name))
+}
+
+/// The last `count` lines of a file, reading backwards from its end so large
+/// logs aren't read in full
+pub fn tail_lines(path: &Path, count: usize) -> io::Result<Vec<String>> {
+    const BLOCK: u64 = 8192;
+
+    let mut file = File::open(path)?;
+    let mut start = file.metadata()?.len();
+    let mut tail = Vec::new();
+    // One newline more than `count`, so the first line kept is complete
+    while start > 0 && tail.iter().filter(|&&b| b == b'\n').count() <= count {
+        let block_start = start.saturating_sub(BLOCK);
+        let mut block = vec![0; (start - block_start) as usize];
+        file.seek(SeekFrom::Start(block_start))?;
+        file.read_exact(&mut block)?;
+        block.extend_from_slice(&tail);
+        tail = block;
+        start = block_start;
+    }
+
+    let text = String::from_utf8_lossy(&tail);
+    let mut lines: Vec<&str> = text.lines().collect();
+    if start > 0 && !lines.is_empty() {
+        // Cut off partway through
+        lines.remove(0);
+    }
+    let skip = lines.len().saturating_sub(count);
+    Ok(lines[skip..].iter().map(|line| line.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects what would have gone to stdout
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        let stdout = Captured::default();
+        let writer = stdout.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(format_layer(LogFormat::Json, move || writer.clone(), false));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("replicate", node_id = "node-2");
+            let _entered = span.enter();
+            tracing::info!(lsn = 42, "entry applied");
+        });
+
+        let output = String::from_utf8(stdout.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert!(line["timestamp"].is_string());
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["target"], "wolfscale::logging::tests");
+        assert_eq!(line["message"], "entry applied");
+        assert_eq!(line["lsn"], 42);
+        assert_eq!(line["span"]["name"], "replicate");
+        assert_eq!(line["span"]["node_id"], "node-2");
+    }
+
+    #[test]
+    fn test_tail_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wolfscale.log");
+        let mut file = File::create(&path).unwrap();
+        for i in 0..1000 {
+            writeln!(file, "line {} {}", i, "x".repeat(40)).unwrap();
+        }
+
+        let lines = tail_lines(&path, 100).unwrap();
+        assert_eq!(lines.len(), 100);
+        assert!(lines[0].starts_with("line 900 "));
+        assert!(lines[99].starts_with("line 999 "));
+        assert_eq!(tail_lines(&path, 5000).unwrap().len(), 1000);
+
+        File::create(dir.path().join("wolfscale.log.2026-01-01")).unwrap();
+        File::create(dir.path().join("wolfscale.log.2026-01-02")).unwrap();
+        assert_eq!(current_log_file(&path), Some(dir.path().join("wolfscale.log.2026-01-02")));
+    }
+}
@@ -9,16 +9,15 @@ use std::time::Duration;
 
 use clap::{Parser, Subcommand};
 use tokio::sync::RwLock;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use wolfscale::config::WolfScaleConfig;
-use wolfscale::wal::{WalWriter, WalReader};
-use wolfscale::state::{StateTracker, ClusterMembership, ElectionConfig};
-use wolfscale::executor::MariaDbExecutor;
-use wolfscale::api::HttpServer;
+use wolfscale::config::{LoggingConfig, WolfScaleConfig};
+use wolfscale::wal::{ColumnFilter, WalWriter, WalReader, WalCompactor, WalIndex, WalPaths, WalRetention, ExportOptions, export_wal};
+use wolfscale::state::{StateTracker, ClusterMembership, ElectionConfig, ClusterEvent, EventNotifier};
+use wolfscale::executor::{MariaDbExecutor, UndoLog};
+use wolfscale::api::{HttpServer, ReplicationProgress};
 use wolfscale::network::{NetworkServer, NetworkClient, Discovery};
-use wolfscale::replication::{LeaderNode, FollowerNode, ReplicationConfig};
-use wolfscale::proxy::{ProxyServer, ProxyConfig};
+use wolfscale::replication::{LeaderNode, FollowerNode, RateLimitAction, ReplicationConfig};
+use wolfscale::proxy::{ProxyServer, ProxyConfig, StaleReadResponse};
 use wolfscale::error::Result;
 
 /// WolfScale - Distributed MariaDB Synchronization Manager
@@ -30,9 +29,9 @@ struct Cli {
     #[arg(short, long, default_value = "wolfscale.toml")]
     config: PathBuf,
 
-    /// Log level (trace, debug, info, warn, error)
-    #[arg(short, long, default_value = "info")]
-    log_level: String,
+    /// Log level (trace, debug, info, warn, error) [default: `logging.level`]
+    #[arg(short, long)]
+    log_level: Option<String>,
 
     #[command(subcommand)]
     command: Commands,
@@ -53,6 +52,13 @@ enum Commands {
         leader: String,
     },
     
+    /// Load a snapshot from a node's HTTP API, then start as a follower
+    Bootstrap {
+        /// HTTP API of the node to copy (e.g. http://leader:8080)
+        #[arg(long)]
+        leader: String,
+    },
+    
     /// Check cluster status
     Status {
         /// Node address to query (defaults to localhost)
@@ -60,6 +66,17 @@ enum Commands {
         address: String,
     },
     
+    /// Watch per-follower replication progress live
+    ReplWatch {
+        /// Node address to watch, normally the leader
+        #[arg(short, long, default_value = "localhost:8080")]
+        address: String,
+        
+        /// Ring the terminal bell when a follower falls more than N entries behind
+        #[arg(long, value_name = "N")]
+        alert_lag: Option<u64>,
+    },
+    
     /// Force synchronization check
     Sync {
         /// Target node address
@@ -84,6 +101,32 @@ enum Commands {
     /// Show node information
     Info,
     
+    /// Show the binlog replication position (last applied GTID)
+    BinlogStatus,
+    
+    /// Compact sealed WAL segments, keeping the latest entry per row
+    Compact,
+    
+    /// Rebuild the WAL index from the segment files (node must be stopped)
+    Recover,
+    
+    /// Revert applied entries from the undo log (node must be stopped)
+    Undo {
+        /// Undo the entry with this LSN
+        #[arg(long, required_unless_present = "last", conflicts_with = "last")]
+        lsn: Option<u64>,
+        
+        /// Undo the newest N entries, newest first
+        #[arg(long)]
+        last: Option<usize>,
+    },
+    
+    /// Inspect the write-ahead log
+    Wal {
+        #[command(subcommand)]
+        action: WalCommand,
+    },
+    
     /// Start MySQL protocol proxy
     Proxy {
         /// Address to listen on
@@ -104,15 +147,59 @@ enum Commands {
         /// Cluster name for filtering auto-discovery (optional)
         #[arg(long)]
         cluster_name: Option<String>,
+
+        /// The nodes' `api.auth.cluster_token`, if their API requires authentication
+        #[arg(long)]
+        cluster_token: Option<String>,
     },
 }
 
+#[derive(Subcommand)]
+enum WalCommand {
+    /// Export WAL entries as newline-delimited JSON or CSV
+    Export {
+        /// Output format (json, csv)
+        #[arg(long, default_value = "json")]
+        format: String,
+        
+        /// First LSN to export
+        #[arg(long, default_value_t = 1)]
+        from_lsn: u64,
+        
+        /// Last LSN to export (default: end of log)
+        #[arg(long)]
+        to_lsn: Option<u64>,
+        
+        /// Output file (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Pretty-print JSON objects
+        #[arg(long)]
+        pretty: bool,
+        
+        /// Only export entries for this table
+        #[arg(long)]
+        filter_table: Option<String>,
+    },
+
+    /// Show WAL disk usage and whether a follower is holding retention
+    Status,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Initialize logging
-    init_logging(&cli.log_level);
+    // Initialize logging: nodes log as `[logging]` says, other commands to stdout
+    let logging = match cli.command {
+        Commands::Start { .. } | Commands::Join { .. } | Commands::Bootstrap { .. } | Commands::Proxy { .. } => {
+            WolfScaleConfig::from_file(&cli.config).map(|c| c.logging).unwrap_or_default()
+        }
+        _ => LoggingConfig::default(),
+    };
+    let level = cli.log_level.clone().unwrap_or_else(|| logging.level.clone());
+    let _log_guard = wolfscale::logging::init(&logging, &level)?;
 
     match cli.command {
         Commands::Start { bootstrap } => {
@@ -121,9 +208,15 @@ async fn main() -> Result<()> {
         Commands::Join { leader } => {
             run_join(cli.config, leader).await
         }
+        Commands::Bootstrap { leader } => {
+            run_bootstrap(cli.config, leader).await
+        }
         Commands::Status { address } => {
             run_status(address).await
         }
+        Commands::ReplWatch { address, alert_lag } => {
+            run_repl_watch(address, alert_lag).await
+        }
         Commands::Sync { address } => {
             run_sync(address).await
         }
@@ -136,26 +229,34 @@ async fn main() -> Result<()> {
         Commands::Info => {
             run_info(cli.config)
         }
+        Commands::BinlogStatus => {
+            run_binlog_status(cli.config)
+        }
+        Commands::Compact => {
+            run_compact(cli.config)
+        }
+        Commands::Recover => {
+            run_recover(cli.config)
+        }
+        Commands::Undo { lsn, last } => {
+            run_undo(cli.config, lsn, last).await
+        }
+        Commands::Wal { action: WalCommand::Export { format, from_lsn, to_lsn, output, pretty, filter_table } } => {
+            let options = ExportOptions { format: format.parse()?, from_lsn, to_lsn, pretty, filter_table };
+            run_wal_export(cli.config, options, output)
+        }
+        Commands::Wal { action: WalCommand::Status } => {
+            run_wal_status(cli.config)
+        }
         Commands::Proxy { listen } => {
             run_proxy(cli.config, listen).await
         }
-        Commands::LoadBalancer { peers, listen, cluster_name } => {
-            run_load_balancer(peers, listen, cluster_name).await
+        Commands::LoadBalancer { peers, listen, cluster_name, cluster_token } => {
+            run_load_balancer(peers, listen, cluster_name, cluster_token).await
         }
     }
 }
 
-/// Initialize logging
-fn init_logging(level: &str) {
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| level.into());
-
-    tracing_subscriber::registry()
-        .with(env_filter)
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-}
-
 /// Start the WolfScale node
 async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
     // Print ASCII art banner
@@ -185,6 +286,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         }
     };
     tracing::info!("WolfScale v{} - Node: {}", env!("CARGO_PKG_VERSION"), config.node.id);
+    wolfscale::telemetry::init(&config.telemetry, &config.node.id)?;
 
     // Ensure directories exist
     if let Err(e) = std::fs::create_dir_all(config.data_dir()) {
@@ -210,14 +312,20 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         None
     };
 
+    let audit = wolfscale::audit::AuditLogger::from_config(&config.audit)?;
+    if audit.is_some() {
+        tracing::info!("Audit log enabled: {}", config.audit.log_file.display());
+    }
+
     // Initialize WAL with optional tuning
     let wal_writer = match WalWriter::with_tuning(
         config.data_dir().clone(),
         config.wal.clone(),
         config.node.id.clone(),
         tuned.as_ref(),
+        audit,
     ).await {
-        Ok(w) => w,
+        Ok(w) => w.with_column_filter(ColumnFilter::new(&config.replication.column_filter)),
         Err(e) => {
             tracing::error!("Failed to initialize WAL: {}", e);
             return Err(e);
@@ -230,7 +338,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         config.wal.segment_size_mb,
         config.wal.compression,
     ) {
-        Ok(r) => r,
+        Ok(r) => r.with_torn_write_detection(config.wal.torn_write_detection),
         Err(e) => {
             tracing::error!("Failed to initialize WAL reader: {}", e);
             return Err(e);
@@ -242,7 +350,9 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         config.wal.segment_size_mb,
         config.wal.compression,
     ) {
-        Ok(r) => Arc::new(tokio::sync::RwLock::new(r)),
+        Ok(r) => Arc::new(tokio::sync::RwLock::new(
+            r.with_torn_write_detection(config.wal.torn_write_detection),
+        )),
         Err(e) => {
             tracing::error!("Failed to initialize sync WAL reader: {}", e);
             return Err(e);
@@ -273,6 +383,14 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         config.election_timeout(),
     ));
 
+    // Report leadership and membership changes to the webhook
+    if let Some(url) = &config.cluster.event_webhook_url {
+        let events = config.cluster.event_types.iter().filter_map(|t| ClusterEvent::parse(t)).collect();
+        cluster.set_term(state_tracker.current_term().await?);
+        cluster.set_event_notifier(EventNotifier::new(url.clone(), events));
+        tracing::info!("Sending cluster events {:?} to {}", config.cluster.event_types, url);
+    }
+
     // Add configured peers (automatically filter out our own address)
     let own_address = config.advertise_address();
     for peer in &config.cluster.peers {
@@ -313,7 +431,16 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
     // Initialize database executor
     tracing::info!("Connecting to MariaDB at {}:{}...", config.database.host, config.database.port);
     let executor = match MariaDbExecutor::new(&config.database).await {
-        Ok(e) => Arc::new(e),
+        Ok(e) => {
+            let mut e = e.with_circuit_breaker(
+                config.executor.circuit_breaker_threshold,
+                Duration::from_secs(config.executor.circuit_breaker_reset_secs),
+            ).with_missing_columns_allowed(config.replication.allow_missing_columns);
+            if config.executor.undo_log_max_mb > 0 {
+                e = e.with_undo_log(UndoLog::open(config.undo_log_path(), config.executor.undo_log_max_mb * 1024 * 1024)?);
+            }
+            Arc::new(e)
+        }
         Err(e) => {
             tracing::error!("Failed to connect to MariaDB: {}", e);
             tracing::error!("  Host: {}:{}", config.database.host, config.database.port);
@@ -331,6 +458,19 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         }
     }
 
+    // Ping the connection pool, replacing it when MariaDB drops its connections
+    if config.database.health_check_interval_secs > 0 {
+        let health_executor = Arc::clone(&executor);
+        let health_interval = Duration::from_secs(config.database.health_check_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(health_interval);
+            loop {
+                interval.tick().await;
+                health_executor.check_pool_health().await;
+            }
+        });
+    }
+
     // Initialize network - separate channels for incoming and outgoing messages
     // Outgoing: used by leader to queue messages to send to peers
     let (outgoing_tx, mut outgoing_rx) = tokio::sync::mpsc::channel::<(String, wolfscale::replication::Message)>(10000);
@@ -353,7 +493,8 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
     let network_client = Arc::new(NetworkClient::new(
         Duration::from_secs(2),   // connect timeout (short - each send is spawned separately)
         Duration::from_secs(5),   // request timeout
-    ));
+    ).with_batch_compression(config.cluster.wan_compression)
+     .with_protocol_version(config.cluster.protocol_version));
 
     // Start OUTGOING message delivery loop - sends queued messages to peers
     // Each send is spawned as a separate task so one failed connection doesn't
@@ -375,6 +516,16 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         }
     });
 
+    // Close pooled peer connections that have been idle for over a minute
+    let cleanup_client = Arc::clone(&network_client);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            cleanup_client.cleanup_stale(Duration::from_secs(60)).await;
+        }
+    });
+
     // Shared node instances for delegating message handling
     let shared_follower = Arc::new(RwLock::new(None::<Arc<FollowerNode>>));
     let shared_leader = Arc::new(RwLock::new(None::<Arc<LeaderNode>>));
@@ -396,8 +547,28 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
     let incoming_entry_tx = entry_tx;
     
     // Build set of configured peer addresses for validation
-    // Only accept nodes that are in our configured peers list (prevents cross-cluster pollution)
-    let configured_peers: std::collections::HashSet<String> = config.cluster.peers.iter().cloned().collect();
+    // Only accept nodes that are in our configured peers list (prevents cross-cluster pollution).
+    // Nodes added with POST /admin/add-node are admitted at runtime.
+    let configured_peers = Arc::new(std::sync::RwLock::new(
+        config.cluster.peers.iter().cloned().collect::<std::collections::HashSet<String>>()
+    ));
+    let admitted_peers = Arc::clone(&configured_peers);
+
+    // Snapshot sent by the leader when this node is added to a running cluster
+    let snapshot_db_config = config.database.clone();
+    let mut snapshot_receiver = wolfscale::replication::SnapshotReceiver::new();
+    let snapshot_loading = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    // Loaded snapshot LSNs, handed to the FollowerNode (which isn't Send)
+    let (snapshot_loaded_tx, mut snapshot_loaded_rx) = tokio::sync::mpsc::channel::<u64>(1);
+
+    // Strong and session reads on GET /read wait on this
+    let read_barrier = Arc::new(wolfscale::replication::ReadBarrier::new(
+        config.node.id.clone(),
+        Arc::clone(&cluster),
+        outgoing_tx.clone(),
+        config.read_barrier_timeout(),
+    ));
+    let incoming_read_barrier = Arc::clone(&read_barrier);
 
     tokio::spawn(async move {
         while let Some((peer_addr, message)) = incoming_rx.recv().await {
@@ -412,7 +583,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                         }
                         
                         // Only accept members whose address is in our configured peers list
-                        if !configured_peers.contains(&member_addr) {
+                        if !configured_peers.read().unwrap().contains(&member_addr) {
                             tracing::trace!("Ignoring member {} at {} - not in configured peers", member_id, member_addr);
                             continue;
                         }
@@ -489,6 +660,13 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                             }
                         });
                     
+                    // Entries follow the snapshot being loaded - drop them unACKed,
+                    // the leader resends once the snapshot LSN is reported
+                    if snapshot_receiver.is_receiving() || snapshot_loading.load(std::sync::atomic::Ordering::Acquire) {
+                        tracing::debug!("Loading snapshot, dropping {} entries from leader", entries.len());
+                        continue;
+                    }
+                    
                     // Forward entries to FollowerNode for processing via channel
                     // (FollowerNode isn't Send, so we can't call it directly from spawned task)
                     // FollowerNode will process and send ACK after entries are applied
@@ -530,7 +708,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                         };
                         
                         // Only accept followers whose address is in our configured peers list
-                        if !configured_peers.contains(&follower_addr) {
+                        if !configured_peers.read().unwrap().contains(&follower_addr) {
                             tracing::trace!("Ignoring follower {} at {} - not in configured peers", node_id, follower_addr);
                             continue;
                         }
@@ -545,6 +723,10 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                         let _ = incoming_cluster.record_heartbeat(&node_id, last_applied_lsn).await;
                     }
                 }
+                wolfscale::replication::Message::AppendEntriesRejected { node_id, match_lsn, reason, .. } => {
+                    // The batch stays unacknowledged and is resent once its ACK times out
+                    tracing::warn!("Follower {} refused entries after LSN {}: {}", node_id, match_lsn, reason);
+                }
                 wolfscale::replication::Message::AppendEntriesResponse { node_id, success, match_lsn, .. } => {
                     // Leader receives ACK from follower - update their progress
                     if success {
@@ -558,7 +740,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                             };
                             
                             // Only accept followers whose address is in our configured peers list
-                            if !configured_peers.contains(&follower_addr) {
+                            if !configured_peers.read().unwrap().contains(&follower_addr) {
                                 tracing::trace!("Ignoring follower {} at {} - not in configured peers", node_id, follower_addr);
                                 continue;
                             }
@@ -580,6 +762,62 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                         }
                     }
                 }
+                wolfscale::replication::Message::ReadBarrier { barrier_id, leader_id, lsn } => {
+                    // Only confirm barriers from the leader we follow - a deposed
+                    // leader must not gather a majority for a strong read
+                    let leader = match incoming_cluster.current_leader().await {
+                        Some(leader) if leader.id == leader_id => leader,
+                        _ => {
+                            tracing::debug!("Ignoring read barrier from {} - not our leader", leader_id);
+                            continue;
+                        }
+                    };
+                    let last_applied = incoming_cluster.get_self().await.last_applied_lsn;
+                    tracing::trace!("Read barrier {} for LSN {} (applied {})", barrier_id, lsn, last_applied);
+                    let response = wolfscale::replication::Message::ReadBarrierResponse {
+                        barrier_id,
+                        node_id: our_node_id.clone(),
+                        last_applied_lsn: last_applied,
+                    };
+                    let _ = response_tx.send((leader.address, response)).await;
+                }
+                wolfscale::replication::Message::ReadBarrierResponse { barrier_id, node_id, last_applied_lsn } => {
+                    incoming_read_barrier.record_response(barrier_id, &node_id, last_applied_lsn);
+                }
+                message @ (wolfscale::replication::Message::FullSyncStart { .. }
+                | wolfscale::replication::Message::FullSyncChunk { .. }
+                | wolfscale::replication::Message::FullSyncComplete { .. }) => {
+                    let (snapshot_lsn, dump) = match snapshot_receiver.receive(message) {
+                        Ok(Some(snapshot)) => snapshot,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            tracing::warn!("Discarding snapshot from {}: {}", peer_addr, e);
+                            continue;
+                        }
+                    };
+                    tracing::info!("Received snapshot at LSN {} ({} bytes), loading", snapshot_lsn, dump.len());
+                    
+                    // Loading can take minutes - keep the message loop running
+                    snapshot_loading.store(true, std::sync::atomic::Ordering::Release);
+                    let loading = Arc::clone(&snapshot_loading);
+                    let db_config = snapshot_db_config.clone();
+                    let cluster = Arc::clone(&incoming_cluster);
+                    let loaded_tx = snapshot_loaded_tx.clone();
+                    let node_id = our_node_id.clone();
+                    tokio::spawn(async move {
+                        match wolfscale::replication::load_snapshot(&db_config, &dump).await {
+                            Ok(()) => {
+                                // Follow the leader from the snapshot onwards (it only
+                                // sends entries after the snapshot LSN)
+                                let _ = loaded_tx.send(snapshot_lsn).await;
+                                let _ = cluster.update_node(&node_id, |node| node.last_applied_lsn = snapshot_lsn).await;
+                                tracing::info!("Snapshot loaded, replicating from LSN {}", snapshot_lsn + 1);
+                            }
+                            Err(e) => tracing::error!("Failed to load snapshot at LSN {}: {}", snapshot_lsn, e),
+                        }
+                        loading.store(false, std::sync::atomic::Ordering::Release);
+                    });
+                }
                 wolfscale::replication::Message::RequestVote { candidate_id, .. } => {
                     tracing::info!("Vote request from {}", candidate_id);
                 }
@@ -592,7 +830,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                         // Find this node's address from the members list
                         if let Some((_, addr)) = members.iter().find(|(id, _)| id == &node_id) {
                             // Only accept peers whose address is in our configured peers list
-                            if configured_peers.contains(addr) {
+                            if configured_peers.read().unwrap().contains(addr) {
                                 let _ = incoming_cluster.add_peer(node_id.clone(), addr.clone()).await;
                             } else {
                                 tracing::trace!("Ignoring peer {} at {} - not in configured peers", node_id, addr);
@@ -625,6 +863,12 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         config.data_dir().clone(),
         &config.database,
     ).await;
+    http_server.set_read_barrier(read_barrier).await;
+    http_server.set_executor(Arc::clone(&executor)).await;
+    http_server.set_wal_config(config.wal.clone()).await;
+    if let Some(log_file) = &config.logging.file {
+        http_server.set_log_file(log_file.clone()).await;
+    }
 
     // Determine role BEFORE starting proxy
     // Priority-based election: lowest node ID is leader
@@ -700,6 +944,12 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         cluster.set_leader(&config.node.id).await?;
     }
 
+    // Re-read reloadable settings on SIGHUP
+    let mut reloader = wolfscale::reload::ConfigReloader::new(config_path.clone(), config.clone(), Arc::clone(&cluster));
+    if let Err(e) = wolfscale::reload::install_sighup_handler() {
+        tracing::warn!("Failed to install SIGHUP handler, config reload disabled: {}", e);
+    }
+
     // Start built-in MySQL proxy if enabled
     if config.proxy.enabled {
         let proxy_config = ProxyConfig {
@@ -712,10 +962,18 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
             ssl_cert: config.proxy.ssl_cert.clone(),
             ssl_key: config.proxy.ssl_key.clone(),
             ssl_required: config.proxy.ssl_required,
+            max_stale_lsn: config.proxy.max_stale_lsn,
+            stale_read_response: StaleReadResponse::parse(&config.proxy.stale_read_response)
+                .unwrap_or(StaleReadResponse::Leader),
+            max_connections: config.proxy.max_connections,
+            max_queue_depth: config.proxy.max_queue_depth,
+            pipeline_depth: config.proxy.pipeline_depth,
+            cluster_token: config.api.auth.cluster_token.clone(),
         };
         let proxy_cluster = Arc::clone(&cluster);
         let proxy_wal = wal_writer.clone();
         let proxy = ProxyServer::with_wal(proxy_config, proxy_cluster, proxy_wal);
+        reloader = reloader.with_proxy_pool(proxy.pool());
         tracing::info!("MySQL proxy listening on {} (WAL-enabled)", config.proxy.bind_address);
         tokio::spawn(async move {
             if let Err(e) = proxy.start().await {
@@ -723,6 +981,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
             }
         });
     }
+    tokio::spawn(reloader.run(config.heartbeat_interval()));
 
     // Start periodic LSN tracker update for stats (100ms interval)
     let stats_lsn_tracker = http_server.get_lsn_tracker();
@@ -735,13 +994,110 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         }
     });
     
+    // Publish WAL entries to Kafka while leader
+    if config.kafka.enabled {
+        let kafka_reader = WalReader::new(
+            config.data_dir().clone(),
+            config.wal.segment_size_mb,
+            config.wal.compression,
+        )?
+        .with_torn_write_detection(config.wal.torn_write_detection);
+        wolfscale::kafka::start(
+            &config.kafka,
+            kafka_reader,
+            Arc::clone(&cluster),
+            config.node.id.clone(),
+            config.state_dir().join("kafka_cdc.lsn"),
+            config.database.database.clone(),
+        )?;
+    }
+
+    // Periodically compact sealed WAL segments while leader, and swap in the
+    // compacted segment once every follower has applied past it. Expired
+    // segments are deleted in the same pass.
+    let compactor = Arc::new(WalCompactor::new(config.data_dir().clone(), config.wal.clone()));
+    let retention = Arc::new(WalRetention::new(config.data_dir().clone(), config.wal.clone()));
+    let compaction_enabled = config.wal.compaction_enabled;
+    let compaction_threshold = config.wal.compaction_threshold_segments;
+    let compaction_cluster = Arc::clone(&cluster);
+    let compaction_wal = wal_writer.clone();
+    let compaction_node_id = config.node.id.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let is_leader = compaction_cluster.current_leader().await
+                .map(|l| l.id == compaction_node_id)
+                .unwrap_or(false);
+            if !is_leader {
+                continue;
+            }
+
+            let min_applied = compaction_cluster.real_peers().await
+                .iter()
+                .filter(|p| p.status != wolfscale::state::NodeStatus::Dropped)
+                .map(|p| p.last_applied_lsn)
+                .min();
+            let min_applied = match min_applied {
+                Some(lsn) => lsn,
+                None => compaction_wal.current_lsn().await,
+            };
+
+            let slowest_follower = compaction_cluster.min_follower_lsn().await;
+
+            let compactor = Arc::clone(&compactor);
+            let retention = Arc::clone(&retention);
+            let result = tokio::task::spawn_blocking(move || {
+                if compaction_enabled {
+                    compactor.compact(compaction_threshold)?;
+                }
+                compactor.promote(min_applied)?;
+                let slowest_follower = slowest_follower.as_ref().map(|(id, lsn)| (id.as_str(), *lsn));
+                retention.apply(slowest_follower, std::time::SystemTime::now())
+            }).await;
+            match result {
+                Ok(Err(e)) => tracing::warn!("WAL compaction/retention failed: {}", e),
+                Err(e) => tracing::warn!("WAL compaction/retention task panicked: {}", e),
+                Ok(Ok(_)) => {}
+            }
+        }
+    });
+
     // Start binlog client if configured (captures from MariaDB binlog to WAL)
-    if config.replication.mode == "binlog" {
+    if config.replication.mode == "binlog" && !config.binlog.sources.is_empty() {
+        use wolfscale::binlog::{BinlogClient, BinlogMerger};
+        
+        // One client per source, all feeding a merger that deduplicates by GTID
+        let sources = config.binlog.sources.clone();
+        let names: Vec<String> = sources.iter().map(|s| s.name()).collect();
+        let (tx, rx) = tokio::sync::mpsc::channel(1024);
+        let merger = BinlogMerger::new(rx, &names, Arc::new(wal_writer.clone()), config.state_dir()).await?;
+        
+        tracing::info!("Starting multi-source binlog replication from {}", names.join(", "));
+        
+        tokio::spawn(async move {
+            if let Err(e) = merger.run().await {
+                tracing::error!("Binlog merger stopped: {}", e);
+            }
+        });
+        for source in sources {
+            let client = BinlogClient::for_source(source, tx.clone(), config.state_dir());
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = client.start().await {
+                        tracing::error!("Binlog client error: {}, retrying in 5s...", e);
+                    }
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+            });
+        }
+    } else if config.replication.mode == "binlog" {
         use wolfscale::binlog::BinlogClient;
         
         let binlog_wal = Arc::new(wal_writer.clone());
         let binlog_db_config = config.database.clone();
         let binlog_config = config.binlog.clone();
+        let binlog_state_dir = config.state_dir();
         
         tracing::info!(
             "Starting binlog replication client (server_id: {})",
@@ -749,7 +1105,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         );
         
         tokio::spawn(async move {
-            let client = BinlogClient::new(binlog_db_config, binlog_config, binlog_wal);
+            let client = BinlogClient::new(binlog_db_config, binlog_config, binlog_wal, binlog_state_dir);
             loop {
                 if let Err(e) = client.start().await {
                     tracing::error!("Binlog client error: {}, retrying in 5s...", e);
@@ -766,17 +1122,8 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         // Configure HTTP server as leader with write handler
         http_server.set_leader(true).await;
         
-        // Create a write handler that appends to WAL
         let write_wal = wal_writer.clone();
-        let write_handler: wolfscale::api::WriteHandler = Arc::new(move |entry| {
-            let wal = write_wal.clone();
-            Box::pin(async move {
-                wal.append(entry).await
-            })
-        });
-        http_server.set_write_handler(write_handler).await;
-        tracing::info!("HTTP API configured as leader with write handler");
-
+        let snapshot_wal = wal_writer.clone();
         let leader = Arc::new(LeaderNode::new(
             config.node.id.clone(),
             wal_writer,
@@ -787,13 +1134,43 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                 max_batch_entries: config.cluster.max_batch_entries,
                 heartbeat_interval_ms: config.cluster.heartbeat_interval_ms,
                 replication_timeout_ms: config.cluster.election_timeout_ms,
+                parallel_apply_workers: config.cluster.parallel_apply_workers,
+                max_lag_lsn: config.cluster.max_lag_lsn,
+                pipeline_depth: config.cluster.pipeline_depth,
+                wan_mode: config.cluster.wan_mode,
+                wan_batch_timeout_ms: config.cluster.wan_batch_timeout_ms,
+                wan_rtt_threshold_ms: config.cluster.wan_rtt_threshold_ms,
+                max_writes_per_second: config.cluster.max_writes_per_second,
+                rate_limit_action: RateLimitAction::parse(&config.cluster.rate_limit_action)
+                    .unwrap_or(RateLimitAction::Reject),
             },
             msg_tx,
             Some(Arc::clone(&executor)),
         ));
 
+        // Create a write handler that appends to WAL, within the leader's write rate limit
+        let write_limiter = leader.write_limiter();
+        let write_handler: wolfscale::api::WriteHandler = Arc::new(move |entry| {
+            let wal = write_wal.clone();
+            let limiter = Arc::clone(&write_limiter);
+            Box::pin(async move {
+                limiter.acquire().await?;
+                wal.append(entry).await
+            })
+        });
+        http_server.set_write_handler(write_handler).await;
+        tracing::info!("HTTP API configured as leader with write handler");
+
         // Store in shared state for message delegation
         *shared_leader.write().await = Some(Arc::clone(&leader));
+        http_server.set_transactions(leader.transactions()).await;
+        http_server.set_add_node_handler(add_node_handler(
+            leader.admission(),
+            snapshot_wal,
+            Arc::clone(&network_client),
+            config.database.clone(),
+            Arc::clone(&admitted_peers),
+        )).await;
 
         // Start all components
         tokio::select! {
@@ -839,6 +1216,15 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                 max_batch_entries: config.cluster.max_batch_entries,
                 heartbeat_interval_ms: config.cluster.heartbeat_interval_ms,
                 replication_timeout_ms: config.cluster.election_timeout_ms,
+                parallel_apply_workers: config.cluster.parallel_apply_workers,
+                max_lag_lsn: config.cluster.max_lag_lsn,
+                pipeline_depth: config.cluster.pipeline_depth,
+                wan_mode: config.cluster.wan_mode,
+                wan_batch_timeout_ms: config.cluster.wan_batch_timeout_ms,
+                wan_rtt_threshold_ms: config.cluster.wan_rtt_threshold_ms,
+                max_writes_per_second: config.cluster.max_writes_per_second,
+                rate_limit_action: RateLimitAction::parse(&config.cluster.rate_limit_action)
+                    .unwrap_or(RateLimitAction::Reject),
             },
             msg_tx.clone(),
             ElectionConfig {
@@ -858,6 +1244,7 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         *shared_follower.write().await = Some(Arc::clone(&follower));
 
         let follower_clone = Arc::clone(&follower);
+        let http_state = http_server.state();
         let http_server_handle = tokio::spawn(async move {
             if let Err(e) = http_server.start().await {
                 tracing::error!("HTTP server error: {}", e);
@@ -933,6 +1320,11 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                     }
                     break;
                 }
+                Some(snapshot_lsn) = snapshot_loaded_rx.recv() => {
+                    if let Err(e) = follower_clone.restore_applied_lsn(snapshot_lsn).await {
+                        tracing::warn!("Failed to persist snapshot LSN: {}", e);
+                    }
+                }
                 _ = role_ticker.tick() => {
                     // Check for new heartbeat and reset election timer
                     let current_heartbeat = follower_heartbeat_time.load(std::sync::atomic::Ordering::Relaxed);
@@ -961,15 +1353,19 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                             config.data_dir().clone(),
                             config.wal.clone(),
                             config.node.id.clone(),
-                        ).await?;
+                        ).await?
+                        .with_column_filter(ColumnFilter::new(&config.replication.column_filter));
 
                         let wal_reader = WalReader::new(
                             config.data_dir().clone(),
                             config.wal.segment_size_mb,
                             config.wal.compression,
-                        )?;
+                        )?
+                        .with_torn_write_detection(config.wal.torn_write_detection);
 
+                        let write_wal = wal_writer.clone();
                         // Start as leader
+                        let snapshot_wal = wal_writer.clone();
                         let leader = LeaderNode::new(
                             config.node.id.clone(),
                             wal_writer,
@@ -980,11 +1376,40 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
                                 max_batch_entries: config.cluster.max_batch_entries,
                                 heartbeat_interval_ms: config.cluster.heartbeat_interval_ms,
                                 replication_timeout_ms: config.cluster.election_timeout_ms,
+                                parallel_apply_workers: config.cluster.parallel_apply_workers,
+                                max_lag_lsn: config.cluster.max_lag_lsn,
+                                pipeline_depth: config.cluster.pipeline_depth,
+                                wan_mode: config.cluster.wan_mode,
+                                wan_batch_timeout_ms: config.cluster.wan_batch_timeout_ms,
+                                wan_rtt_threshold_ms: config.cluster.wan_rtt_threshold_ms,
+                                max_writes_per_second: config.cluster.max_writes_per_second,
+                                rate_limit_action: RateLimitAction::parse(&config.cluster.rate_limit_action)
+                                    .unwrap_or(RateLimitAction::Reject),
                             },
                             msg_tx.clone(),
                             Some(executor.clone()),
                         );
 
+                        // Serve HTTP transaction commits through the new WAL writer
+                        let write_limiter = leader.write_limiter();
+                        let write_handler: wolfscale::api::WriteHandler = Arc::new(move |entry| {
+                            let wal = write_wal.clone();
+                            let limiter = Arc::clone(&write_limiter);
+                            Box::pin(async move {
+                                limiter.acquire().await?;
+                                wal.append(entry).await
+                            })
+                        });
+                        *http_state.write_handler.write().await = Some(write_handler);
+                        *http_state.transactions.write().await = Some(leader.transactions());
+                        *http_state.add_node_handler.write().await = Some(add_node_handler(
+                            leader.admission(),
+                            snapshot_wal,
+                            Arc::clone(&network_client),
+                            config.database.clone(),
+                            Arc::clone(&admitted_peers),
+                        ));
+
                         tracing::info!("Now running as LEADER");
 
                         tokio::select! {
@@ -1012,10 +1437,72 @@ async fn run_start(config_path: PathBuf, bootstrap: bool) -> Result<()> {
         network_server_handle.abort();
     }
 
+    wolfscale::telemetry::shutdown();
     tracing::info!("WolfScale shutdown complete");
     Ok(())
 }
 
+/// Serve `POST /admin/add-node` on a leader: admit the node, then dump the
+/// database and stream the snapshot to it in the background
+fn add_node_handler(
+    admission: wolfscale::replication::FollowerAdmission,
+    wal: WalWriter,
+    client: Arc<NetworkClient>,
+    database: wolfscale::config::DatabaseConfig,
+    admitted_peers: Arc<std::sync::RwLock<std::collections::HashSet<String>>>,
+) -> wolfscale::api::AddNodeHandler {
+    Arc::new(move |node_id, address| {
+        let admission = admission.clone();
+        let wal = wal.clone();
+        let client = Arc::clone(&client);
+        let database = database.clone();
+        let admitted_peers = Arc::clone(&admitted_peers);
+        Box::pin(async move {
+            // Entries up to here are in the dump, later ones are replicated
+            let snapshot_lsn = wal.current_lsn().await;
+            admitted_peers.write().unwrap().insert(address.clone());
+            admission.add_follower(&node_id, &address, snapshot_lsn).await?;
+
+            tokio::spawn(async move {
+                let result = async {
+                    let dump = wolfscale::replication::create_snapshot(&database).await?;
+                    tracing::info!("Sending {} byte snapshot at LSN {} to {}", dump.len(), snapshot_lsn, node_id);
+                    wolfscale::replication::send_snapshot(&client, &address, snapshot_lsn, &dump).await
+                }.await;
+                match result {
+                    Ok(()) => tracing::info!("Snapshot sent to {}", node_id),
+                    Err(e) => tracing::error!("Failed to send snapshot to {}: {}", node_id, e),
+                }
+            });
+            Ok(snapshot_lsn)
+        })
+    })
+}
+
+/// Replay a snapshot downloaded from `GET /snapshot` into the local
+/// database, then start following from its LSN
+async fn run_bootstrap(config_path: PathBuf, leader: String) -> Result<()> {
+    let config = WolfScaleConfig::from_file(&config_path)?;
+    std::fs::create_dir_all(config.state_dir())?;
+
+    let state_tracker = StateTracker::new(config.state_dir(), config.node.id.clone())?;
+    let applied = state_tracker.last_applied_lsn().await?;
+    if applied > 0 {
+        return Err(wolfscale::error::Error::Replication(format!(
+            "Node has already applied entries up to LSN {}; bootstrap only a new node", applied
+        )));
+    }
+
+    let executor = MariaDbExecutor::new(&config.database).await?;
+    let authorization = config.api.auth.cluster_token.as_ref().map(|token| format!("Bearer {}", token));
+    let snapshot_lsn = wolfscale::replication::bootstrap_from_http(&leader, authorization.as_deref(), &executor).await?;
+    executor.close().await;
+    state_tracker.set_last_applied_lsn(snapshot_lsn).await?;
+    drop(state_tracker);
+
+    run_start(config_path, false).await
+}
+
 /// Join an existing cluster
 async fn run_join(config_path: PathBuf, leader: String) -> Result<()> {
     tracing::info!("Joining cluster via leader: {}", leader);
@@ -1026,7 +1513,7 @@ async fn run_join(config_path: PathBuf, leader: String) -> Result<()> {
     let client = NetworkClient::new(
         Duration::from_secs(10),
         Duration::from_secs(30),
-    );
+    ).with_protocol_version(config.cluster.protocol_version);
 
     let join_msg = wolfscale::replication::Message::JoinRequest {
         node_id: config.node.id.clone(),
@@ -1079,6 +1566,62 @@ async fn run_status(address: String) -> Result<()> {
     }
 }
 
+/// Show the progress updates of `GET /ws/progress` as a live table until
+/// Ctrl+C, ringing the bell when a follower starts lagging more than `alert_lag`
+async fn run_repl_watch(address: String, alert_lag: Option<u64>) -> Result<()> {
+    use std::io::Write;
+    use crossterm::{cursor, execute, terminal};
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+    use wolfscale::error::Error;
+
+    let url = format!("ws://{}/ws/progress", address);
+    let (mut ws, _) = tokio_tungstenite::connect_async(&url).await
+        .map_err(|e| Error::Network(format!("Failed to connect to {}: {}", url, e)))?;
+
+    let mut stdout = std::io::stdout();
+    execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let mut lagging: Vec<String> = Vec::new();
+    let result = loop {
+        let message = tokio::select! {
+            message = ws.next() => message,
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        };
+        let progress: ReplicationProgress = match message {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                Ok(progress) => progress,
+                Err(e) => break Err(Error::Network(format!("Invalid progress update: {}", e))),
+            },
+            Some(Ok(Message::Close(_))) | None => break Err(Error::Network(format!("{} closed the connection", address))),
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => break Err(Error::Network(e.to_string())),
+        };
+
+        let mut lines = vec![format!("Replication progress from {} (Ctrl+C to quit)", address), String::new()];
+        lines.extend(progress.render());
+        if let Some(max_lag) = alert_lag {
+            let now_lagging: Vec<String> = progress.lagging(max_lag).map(|f| f.node_id.clone()).collect();
+            // Ring once when a follower starts lagging, not on every update
+            if now_lagging.iter().any(|id| !lagging.contains(id)) {
+                lines[0].insert(0, '\x07');
+            }
+            if !now_lagging.is_empty() {
+                lines.push(String::new());
+                lines.push(format!("More than {} entries behind: {}", max_lag, now_lagging.join(", ")));
+            }
+            lagging = now_lagging;
+        }
+
+        execute!(stdout, cursor::MoveTo(0, 0), terminal::Clear(terminal::ClearType::All))?;
+        for line in &lines {
+            writeln!(stdout, "{}", line)?;
+        }
+        stdout.flush()?;
+    };
+    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
+    result
+}
+
 /// Force synchronization
 async fn run_sync(address: String) -> Result<()> {
     let url = format!("http://{}/cluster", address);
@@ -1117,14 +1660,23 @@ password = "changeme"
 database = "myapp"
 pool_size = 10
 connect_timeout_secs = 30
+health_check_interval_secs = 30
+max_lifetime_secs = 1800
 
 [wal]
-batch_size = 1000
 flush_interval_ms = 100
+group_commit_max_delay_us = 1000
+group_commit_max_size = 100
 compression = true
 segment_size_mb = 64
+max_segment_age_secs = 3600
+min_segment_entries = 1
 retention_hours = 168
+min_retention_follower_lsn = true
+max_retention_override_hours = 720
 fsync = true
+compaction_enabled = false
+compaction_threshold_segments = 10
 
 [cluster]
 peers = []
@@ -1197,7 +1749,7 @@ fn run_info(config_path: PathBuf) -> Result<()> {
     println!("  Pool Size:      {}", config.database.pool_size);
     println!();
     println!("WAL Configuration:");
-    println!("  Batch Size:     {}", config.wal.batch_size);
+    println!("  Group Commit:   {} entries / {} us", config.wal.group_commit_max_size, config.wal.group_commit_max_delay_us);
     println!("  Compression:    {}", config.wal.compression);
     println!("  Segment Size:   {} MB", config.wal.segment_size_mb);
     println!("  Fsync:          {}", config.wal.fsync);
@@ -1211,6 +1763,179 @@ fn run_info(config_path: PathBuf) -> Result<()> {
     Ok(())
 }
 
+/// Show the binlog replication position
+fn run_binlog_status(config_path: PathBuf) -> Result<()> {
+    use wolfscale::binlog::GtidSet;
+    
+    let config = WolfScaleConfig::from_file(&config_path)?;
+    let gtid_set = GtidSet::load(&config.state_dir())?;
+    
+    println!("Binlog Replication Status");
+    println!("=========================");
+    println!();
+    println!("Mode:             {}", config.replication.mode);
+    println!("Server ID:        {}", config.binlog.server_id);
+    println!("State File:       {}", GtidSet::state_path(&config.state_dir()).display());
+    println!();
+    if gtid_set.is_empty() {
+        println!("GTID Position:    (none - will start from file/position)");
+    } else {
+        println!("GTID Position:    {}", gtid_set);
+        for gtid in gtid_set.iter() {
+            println!("  Domain {:<8} server {} seq {}", gtid.domain_id, gtid.server_id, gtid.sequence);
+        }
+    }
+    
+    if !config.binlog.sources.is_empty() {
+        println!();
+        println!("Sources:");
+        for source in &config.binlog.sources {
+            let path = GtidSet::source_state_path(&config.state_dir(), &source.name());
+            let position = GtidSet::load_file(&path)?;
+            let shown = if position.is_empty() { "(none)".to_string() } else { position.to_string() };
+            println!("  {:<24} server_id {:<6} {}", source.name(), source.server_id, shown);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Compact sealed WAL segments
+fn run_compact(config_path: PathBuf) -> Result<()> {
+    let config = WolfScaleConfig::from_file(&config_path)?;
+    let compactor = WalCompactor::new(config.data_dir().clone(), config.wal.clone());
+    
+    println!("WAL Compaction");
+    println!("==============");
+    println!();
+    match compactor.compact(1)? {
+        Some(stats) => {
+            println!("Segments:         {}", stats.segments);
+            println!("LSN Range:        {}-{}", stats.first_lsn, stats.last_lsn);
+            println!("Entries:          {} -> {}", stats.entries_before, stats.entries_after);
+            println!("Ratio:            {:.3}", stats.ratio());
+            println!();
+            println!("The compacted segment replaces the originals once all followers");
+            println!("have applied LSN {} (checked by the running leader).", stats.last_lsn);
+        }
+        None if compactor.pending()?.is_some() => {
+            println!("A previous compaction is still waiting for followers to catch up.");
+        }
+        None => {
+            println!("Nothing to compact (no sealed segments).");
+        }
+    }
+    
+    Ok(())
+}
+
+/// Rebuild the WAL index from the segment files
+fn run_recover(config_path: PathBuf) -> Result<()> {
+    let config = WolfScaleConfig::from_file(&config_path)?;
+    let paths = WalPaths::new(config.wal_dir());
+    let mut index = WalIndex::open(&paths.index_path())?;
+    
+    println!("WAL Recovery");
+    println!("============");
+    println!();
+    let report = index.check(&paths, true)?;
+    println!("Indexed LSN:      {}", report.indexed_lsn);
+    println!("Segment LSN:      {}", report.segment_lsn);
+    println!("Entries indexed:  {}", report.entries);
+    if let Some(segment) = report.corrupt_segment {
+        println!();
+        println!("Segment {} is corrupt; the index stops at the last good entry.", segment);
+    }
+    
+    Ok(())
+}
+
+/// Revert entries from the undo log, against the local database only
+async fn run_undo(config_path: PathBuf, lsn: Option<u64>, last: Option<usize>) -> Result<()> {
+    let config = WolfScaleConfig::from_file(&config_path)?;
+    let mut undo_log = UndoLog::open(config.undo_log_path(), config.executor.undo_log_max_mb.max(1) * 1024 * 1024)?;
+    let entries = match (lsn, last) {
+        (Some(lsn), _) => match undo_log.get(lsn)? {
+            Some(entry) => vec![entry],
+            None => {
+                return Err(wolfscale::error::Error::State(format!("No undo log entry for LSN {}", lsn)));
+            }
+        },
+        (None, last) => undo_log.last(last.unwrap_or(0))?,
+    };
+    if entries.is_empty() {
+        println!("Nothing to undo.");
+        return Ok(());
+    }
+    
+    let executor = MariaDbExecutor::new(&config.database).await?;
+    for entry in entries {
+        executor.undo(&entry).await?;
+        undo_log.remove(entry.lsn)?;
+        println!("Undid LSN {}", entry.lsn);
+    }
+    executor.close().await;
+    
+    Ok(())
+}
+
+/// Export WAL entries to a file or stdout
+fn run_wal_export(config_path: PathBuf, options: ExportOptions, output: Option<PathBuf>) -> Result<()> {
+    let config = WolfScaleConfig::from_file(&config_path)?;
+    let reader = WalReader::new(config.data_dir().clone(), config.wal.segment_size_mb, config.wal.compression)?
+        .with_torn_write_detection(config.wal.torn_write_detection);
+    
+    match output {
+        Some(path) => {
+            let file = std::fs::File::create(&path)?;
+            let written = export_wal(&reader, &options, std::io::BufWriter::new(file))?;
+            eprintln!("Exported {} entries to {}", written, path.display());
+        }
+        None => {
+            export_wal(&reader, &options, std::io::BufWriter::new(std::io::stdout().lock()))?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Show WAL segments on disk and what is holding retention
+fn run_wal_status(config_path: PathBuf) -> Result<()> {
+    let config = WolfScaleConfig::from_file(&config_path)?;
+    let retention = WalRetention::new(config.data_dir().clone(), config.wal.clone());
+    let usage = retention.usage()?;
+
+    println!("WAL Status");
+    println!("==========");
+    println!();
+    println!("Segments:         {}", usage.segments);
+    println!("Total Size:       {:.1} MB", usage.total_bytes as f64 / (1024.0 * 1024.0));
+    match usage.oldest {
+        Some((path, first_lsn)) => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            println!("Oldest Segment:   {} (from LSN {})", name, first_lsn);
+        }
+        None => println!("Oldest Segment:   (none)"),
+    }
+    match config.wal.retention_hours {
+        0 => println!("Retention:        infinite"),
+        hours => println!("Retention:        {} hours", hours),
+    }
+    println!();
+    match retention.last_report() {
+        Some(report) => match report.held_by {
+            Some(hold) => {
+                println!("Retention is held by follower {} (applied LSN {}),", hold.node_id, hold.applied_lsn);
+                println!("keeping {} expired segments.", hold.segments);
+            }
+            None => println!("No follower is holding retention."),
+        },
+        None => println!("Retention has not run yet (it runs on the leader every minute)."),
+    }
+
+    Ok(())
+}
+
 /// Run the MySQL protocol proxy
 async fn run_proxy(config_path: PathBuf, listen_address: String) -> Result<()> {
     let config = WolfScaleConfig::from_file(&config_path)?;
@@ -1238,6 +1963,13 @@ async fn run_proxy(config_path: PathBuf, listen_address: String) -> Result<()> {
         ssl_cert: None,
         ssl_key: None,
         ssl_required: false,
+        max_stale_lsn: config.proxy.max_stale_lsn,
+        stale_read_response: StaleReadResponse::parse(&config.proxy.stale_read_response)
+            .unwrap_or(StaleReadResponse::Leader),
+        max_connections: config.proxy.max_connections,
+        max_queue_depth: config.proxy.max_queue_depth,
+        pipeline_depth: config.proxy.pipeline_depth,
+        cluster_token: config.api.auth.cluster_token.clone(),
     };
     
     let proxy = ProxyServer::new(proxy_config, cluster);
@@ -1264,8 +1996,27 @@ async fn run_proxy(config_path: PathBuf, listen_address: String) -> Result<()> {
 }
 
 
+/// HTTP client for polling the cluster nodes' APIs
+fn cluster_http_client(cluster_token: Option<&str>) -> reqwest::Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = cluster_token {
+        if let Ok(value) = format!("Bearer {}", token).parse() {
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+        }
+    }
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .default_headers(headers)
+        .build()
+}
+
 /// Run as a load balancer (no local database)
-async fn run_load_balancer(mut peers: Vec<String>, listen_address: String, cluster_name: Option<String>) -> Result<()> {
+async fn run_load_balancer(
+    mut peers: Vec<String>,
+    listen_address: String,
+    cluster_name: Option<String>,
+    cluster_token: Option<String>,
+) -> Result<()> {
     use wolfscale::state::{ClusterMembership, NodeRole, NodeStatus};
     use wolfscale::network::discovery;
     
@@ -1334,9 +2085,7 @@ async fn run_load_balancer(mut peers: Vec<String>, listen_address: String, clust
     
     // Discover cluster by querying HTTP status endpoints
     println!("Connecting to cluster nodes...");
-    let http_client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
+    let http_client = cluster_http_client(cluster_token.as_deref())
         .map_err(|e| wolfscale::error::Error::Network(e.to_string()))?;
     
     let mut leader_address: Option<String> = None;
@@ -1431,11 +2180,8 @@ async fn run_load_balancer(mut peers: Vec<String>, listen_address: String, clust
     // Spawn cluster refresh task (every 5 seconds)
     let refresh_cluster = Arc::clone(&cluster);
     let refresh_peers = peers.clone();
+    let client = http_client.clone();
     tokio::spawn(async move {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(5))
-            .build()
-            .unwrap();
         
         let mut interval = tokio::time::interval(Duration::from_secs(5));
         loop {
@@ -0,0 +1,626 @@
+//! Prometheus Metrics
+//!
+//! Process-wide metrics registry, exposed in the Prometheus text format on
+//! the HTTP API's `/metrics` endpoint. Metrics live in memory only and reset
+//! when the daemon restarts.
+
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use prometheus::{Counter, Encoder, Gauge, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+
+/// Registry holding every WolfScale metric
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+/// Transactions buffered on the leader awaiting commit or rollback
+pub static OPEN_TRANSACTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_open_transactions_gauge",
+        "Number of open transactions buffered on the leader",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Nodes in the cluster, including this one
+pub static CLUSTER_SIZE: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_cluster_size",
+        "Number of nodes in the cluster, including this one",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Election term this node has seen, persisted across restarts
+pub static ELECTION_TERM: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_election_term",
+        "Current leader election term on this node",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Entries after / entries before for the most recent WAL compaction
+pub static WAL_COMPACTION_RATIO: LazyLock<Gauge> = LazyLock::new(|| {
+    let gauge = Gauge::new(
+        "wolfscale_wal_compaction_ratio",
+        "Ratio of entries kept to entries read by the last WAL compaction",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Reads a lagging follower's proxy sent to the leader or refused
+pub static STALE_READS_AVOIDED: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_stale_reads_avoided_total",
+        "Reads not served locally because this follower exceeded proxy.max_stale_lsn",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Client connections the MySQL proxy is serving
+pub static PROXY_ACTIVE_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_proxy_active_connections",
+        "Client connections being proxied (at most proxy.max_connections)",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Client connections waiting for a proxy slot
+pub static PROXY_QUEUED_CONNECTIONS: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_proxy_queued_connections",
+        "Client connections queued until the proxy is below proxy.max_connections",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Client connections refused with error 1040
+pub static PROXY_REJECTED_CONNECTIONS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_proxy_rejected_connections_total",
+        "Client connections refused because the proxy queue was full",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Queries the proxy sent to MariaDB together for one client
+pub static PROXY_PIPELINE_DEPTH: LazyLock<Histogram> = LazyLock::new(|| {
+    let histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "wolfscale_proxy_pipeline_depth_histogram",
+            "Queries a client sent without waiting that the proxy forwarded as one batch",
+        )
+        .buckets(vec![1.0, 2.0, 3.0, 4.0, 8.0, 16.0, 32.0, 64.0]),
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(histogram.clone()))
+        .expect("metric registered once");
+    histogram
+});
+
+/// Prepared statements cached by open proxy connections
+pub static PREPARED_STMT_CACHE_SIZE: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_prepared_stmt_cache_size",
+        "Prepared statements cached by open proxy connections",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Executor circuit breaker state (0 = closed, 1 = open, 2 = half-open)
+pub static CIRCUIT_BREAKER_STATE: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_circuit_breaker_state",
+        "MariaDB circuit breaker state (0 = closed, 1 = open, 2 = half-open)",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Connections open in the MariaDB pools
+pub static DB_POOL_SIZE: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_db_pool_size",
+        "Connections open in the MariaDB pools",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Pooled MariaDB connections waiting to be used
+pub static DB_POOL_IDLE: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_db_pool_idle",
+        "Pooled MariaDB connections waiting to be used",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Pooled MariaDB connections in use
+pub static DB_POOL_ACTIVE: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_db_pool_active",
+        "Pooled MariaDB connections in use",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Failed pool health checks
+pub static DB_POOL_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_db_pool_errors_total",
+        "MariaDB pool health checks that failed",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Records written to the audit log
+pub static AUDIT_ENTRIES_WRITTEN: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_audit_entries_written_total",
+        "WAL entries recorded in the audit log",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Startups that found the WAL index out of sync and rebuilt it
+pub static WAL_RECOVERY_RAN: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_recovery_ran_total",
+        "WAL index rebuilds after finding it out of sync with the segment files",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Entries written by each WAL group commit
+pub static GROUP_COMMIT_BATCH_SIZE: LazyLock<Histogram> = LazyLock::new(|| {
+    let histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "wolfscale_group_commit_batch_size_histogram",
+            "Entries written and synced together by one WAL group commit",
+        )
+        .buckets(vec![1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0]),
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(histogram.clone()))
+        .expect("metric registered once");
+    histogram
+});
+
+/// Expired WAL segments kept because a follower has not applied them yet
+pub static WAL_RETENTION_HELD_BY_FOLLOWER: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "wolfscale_wal_retention_held_by_follower",
+            "Expired WAL segments kept for the slowest follower",
+        ),
+        &["node_id"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// How far each `[[binlog.sources]]` server trails the merged binlog stream
+pub static BINLOG_SOURCE_LAG: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    let gauge = IntGaugeVec::new(
+        Opts::new(
+            "wolfscale_binlog_source_lag_lsn",
+            "WAL entries written by the binlog merger since this source's latest transaction",
+        ),
+        &["source_host"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// AppendEntries batches the leader has sent and not yet seen committed
+pub static PIPELINE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    let gauge = IntGauge::new(
+        "wolfscale_pipeline_depth_gauge",
+        "Replication batches in flight from the leader awaiting a quorum of ACKs",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Writes refused because the leader was over `cluster.max_writes_per_second`
+pub static RATE_LIMIT_REJECTIONS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_rate_limit_rejections_total",
+        "Writes rejected by the leader's write rate limit",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Writes held back until the leader's write rate limit allowed them
+pub static RATE_LIMIT_DELAYS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_rate_limit_delays_total",
+        "Writes delayed by the leader's write rate limit",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Writes accepted by the leader per second, averaged over one second
+pub static WRITES_PER_SECOND: LazyLock<Gauge> = LazyLock::new(|| {
+    let gauge = Gauge::new(
+        "wolfscale_writes_per_second",
+        "Writes accepted by the leader per second (1-second average)",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Smoothed AppendEntries round trip from the leader to each follower
+pub static PEER_RTT_MS: LazyLock<GaugeVec> = LazyLock::new(|| {
+    let gauge = GaugeVec::new(
+        Opts::new(
+            "wolfscale_peer_rtt_ms",
+            "Smoothed AppendEntries round trip to the follower in milliseconds",
+        ),
+        &["node_id"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Change events published to Kafka
+pub static KAFKA_MESSAGES_SENT: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_kafka_messages_sent_total",
+        "Change events the Kafka brokers acknowledged",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Change events Kafka failed to accept (they are retried)
+pub static KAFKA_SEND_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_kafka_send_errors_total",
+        "Change events the Kafka producer failed to deliver",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Time spent in maintenance mode, added when maintenance ends
+pub static MAINTENANCE_DURATION: LazyLock<Counter> = LazyLock::new(|| {
+    let counter = Counter::new(
+        "wolfscale_maintenance_duration_seconds_total",
+        "Seconds this node has spent in maintenance mode",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Replicated entries a follower refused because their SQL didn't match the entry, by reason
+pub static VALIDATION_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "wolfscale_validation_failures_total",
+            "Replicated entries refused because their SQL did not match the entry's type or table",
+        ),
+        &["reason"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// HTTP API requests refused by authentication, by reason
+pub static API_AUTH_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new(
+            "wolfscale_api_auth_failures_total",
+            "HTTP API requests refused for missing or invalid credentials or an insufficient role",
+        ),
+        &["reason"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Latency histogram buckets for executed queries, in seconds
+const QUERY_DURATION_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Time spent executing SQL against MariaDB, labelled by operation
+pub static QUERY_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    let histogram = HistogramVec::new(
+        HistogramOpts::new(
+            "wolfscale_query_duration_seconds",
+            "Query execution latency in seconds",
+        )
+        .buckets(QUERY_DURATION_BUCKETS.to_vec()),
+        &["operation"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(histogram.clone()))
+        .expect("metric registered once");
+    histogram
+});
+
+/// Snowflake IDs handed out per second by this node's `/id` endpoints
+pub static ID_GENERATION_RATE: LazyLock<Gauge> = LazyLock::new(|| {
+    let gauge = Gauge::new(
+        "wolfscale_id_generation_rate",
+        "Snowflake IDs generated per second, averaged over at least 10 seconds",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(gauge.clone()))
+        .expect("metric registered once");
+    gauge
+});
+
+/// Config file reloads applied after a SIGHUP
+pub static CONFIG_RELOADS: LazyLock<IntCounter> = LazyLock::new(|| {
+    let counter = IntCounter::new(
+        "wolfscale_config_reloads_total",
+        "Config file reloads applied after a SIGHUP",
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Cluster event notifications delivered to `cluster.event_webhook_url`, by event
+pub static WEBHOOK_SENT: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new("wolfscale_webhook_sent_total", "Cluster event notifications delivered to the webhook"),
+        &["event"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Failed webhook POSTs (each retry counts), by event
+pub static WEBHOOK_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let counter = IntCounterVec::new(
+        Opts::new("wolfscale_webhook_errors_total", "Cluster event webhook POSTs that failed or timed out"),
+        &["event"],
+    )
+    .expect("valid metric definition");
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("metric registered once");
+    counter
+});
+
+/// Shortest period `ID_GENERATION_RATE` is averaged over
+const ID_RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Start of the current `ID_GENERATION_RATE` period and IDs generated in it
+static ID_RATE_PERIOD: LazyLock<Mutex<(Instant, u64)>> = LazyLock::new(|| Mutex::new((Instant::now(), 0)));
+
+/// Count generated IDs towards `ID_GENERATION_RATE`, which is updated once
+/// the current period is long enough
+pub fn record_ids_generated(count: u64) {
+    let mut period = ID_RATE_PERIOD.lock().unwrap_or_else(|e| e.into_inner());
+    period.1 += count;
+    let elapsed = period.0.elapsed();
+    if elapsed >= ID_RATE_WINDOW {
+        ID_GENERATION_RATE.set(period.1 as f64 / elapsed.as_secs_f64());
+        *period = (Instant::now(), 0);
+    }
+}
+
+/// Classify a SQL statement into the `operation` label used by
+/// `QUERY_DURATION`. Returns None for statements that are not tracked
+/// (SET, BEGIN, USE, ...).
+pub fn query_operation(sql: &str) -> Option<&'static str> {
+    let upper = sql.trim_start().to_uppercase();
+    let keyword = upper.split(|c: char| !c.is_ascii_alphabetic()).next().unwrap_or("");
+    match keyword {
+        "INSERT" | "REPLACE" => Some("insert"),
+        "UPDATE" => Some("update"),
+        "DELETE" => Some("delete"),
+        "CREATE" | "ALTER" | "DROP" | "TRUNCATE" | "RENAME" => Some("ddl"),
+        "SELECT" | "SHOW" | "DESCRIBE" | "DESC" | "EXPLAIN" => Some("select"),
+        _ => None,
+    }
+}
+
+/// Record the latency of one executed statement
+pub fn observe_query(sql: &str, elapsed: Duration) {
+    if let Some(operation) = query_operation(sql) {
+        QUERY_DURATION
+            .with_label_values(&[operation])
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+/// Render all metrics in the Prometheus text exposition format
+pub fn gather() -> String {
+    // Metrics are registered lazily; make sure they all appear in the output
+    LazyLock::force(&OPEN_TRANSACTIONS);
+    LazyLock::force(&CLUSTER_SIZE);
+    LazyLock::force(&ELECTION_TERM);
+    // Falls to 0 once no IDs have been generated for a whole period
+    record_ids_generated(0);
+    LazyLock::force(&QUERY_DURATION);
+    LazyLock::force(&WAL_COMPACTION_RATIO);
+    LazyLock::force(&STALE_READS_AVOIDED);
+    LazyLock::force(&PREPARED_STMT_CACHE_SIZE);
+    LazyLock::force(&AUDIT_ENTRIES_WRITTEN);
+    LazyLock::force(&CIRCUIT_BREAKER_STATE);
+    LazyLock::force(&DB_POOL_SIZE);
+    LazyLock::force(&DB_POOL_IDLE);
+    LazyLock::force(&DB_POOL_ACTIVE);
+    LazyLock::force(&DB_POOL_ERRORS);
+    LazyLock::force(&BINLOG_SOURCE_LAG);
+    LazyLock::force(&WAL_RETENTION_HELD_BY_FOLLOWER);
+    LazyLock::force(&PIPELINE_DEPTH);
+    LazyLock::force(&PEER_RTT_MS);
+    LazyLock::force(&RATE_LIMIT_REJECTIONS);
+    LazyLock::force(&RATE_LIMIT_DELAYS);
+    LazyLock::force(&WRITES_PER_SECOND);
+    LazyLock::force(&MAINTENANCE_DURATION);
+    LazyLock::force(&VALIDATION_FAILURES);
+    LazyLock::force(&API_AUTH_FAILURES);
+    LazyLock::force(&WAL_RECOVERY_RAN);
+    LazyLock::force(&GROUP_COMMIT_BATCH_SIZE);
+    LazyLock::force(&PROXY_ACTIVE_CONNECTIONS);
+    LazyLock::force(&PROXY_QUEUED_CONNECTIONS);
+    LazyLock::force(&PROXY_REJECTED_CONNECTIONS);
+    LazyLock::force(&PROXY_PIPELINE_DEPTH);
+    LazyLock::force(&KAFKA_MESSAGES_SENT);
+    LazyLock::force(&KAFKA_SEND_ERRORS);
+    LazyLock::force(&WEBHOOK_SENT);
+    LazyLock::force(&WEBHOOK_ERRORS);
+    LazyLock::force(&CONFIG_RELOADS);
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer) {
+        tracing::warn!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_operation() {
+        assert_eq!(query_operation("INSERT INTO t VALUES (1)"), Some("insert"));
+        assert_eq!(query_operation("  replace into t values (1)"), Some("insert"));
+        assert_eq!(query_operation("UPDATE t SET a = 1"), Some("update"));
+        assert_eq!(query_operation("DELETE FROM t"), Some("delete"));
+        assert_eq!(query_operation("ALTER TABLE t ADD c INT"), Some("ddl"));
+        assert_eq!(query_operation("SELECT 1"), Some("select"));
+        assert_eq!(query_operation("SET NAMES utf8mb4"), None);
+    }
+
+    /// The scraped insert count. The registry is shared with every other
+    /// test, so only differences are meaningful.
+    fn scraped_insert_count() -> u64 {
+        let prefix = "wolfscale_query_duration_seconds_count{operation=\"insert\"} ";
+        gather()
+            .lines()
+            .find_map(|l| l.strip_prefix(prefix))
+            .map(|count| count.parse().unwrap())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_insert_latency_scraped() {
+        let before = scraped_insert_count();
+        for i in 0..100 {
+            let sql = format!("INSERT INTO latency_test VALUES ({})", i);
+            let start = std::time::Instant::now();
+            observe_query(&sql, start.elapsed());
+        }
+
+        assert_eq!(scraped_insert_count() - before, 100);
+        assert!(gather().contains("wolfscale_query_duration_seconds_bucket{operation=\"insert\",le=\"0.001\"}"));
+    }
+}
@@ -9,10 +9,13 @@ use tokio::net::TcpStream;
 use tokio::sync::{Mutex, RwLock};
 use tokio::time::timeout;
 
-use super::{read_message, write_message};
-use crate::replication::Message;
+use super::{read_message, write_message, write_message_as, write_message_compressed};
+use crate::replication::{Message, PROTOCOL_VERSION};
 use crate::error::{Error, Result};
 
+/// Pooled one-way connections idle longer than this are closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Connection pool entry
 struct PoolEntry {
     stream: TcpStream,
@@ -23,6 +26,9 @@ struct PoolEntry {
 pub struct NetworkClient {
     /// Connection pool: address -> connection
     pool: Arc<RwLock<HashMap<String, Arc<Mutex<PoolEntry>>>>>,
+    /// Persistent connections for fire-and-forget sends: address -> connection
+    /// (kept apart from `pool` because nothing is ever read back on them)
+    streams: Arc<Mutex<HashMap<String, Arc<Mutex<PoolEntry>>>>>,
     /// Connection timeout
     connect_timeout: Duration,
     /// Request timeout
@@ -30,6 +36,10 @@ pub struct NetworkClient {
     /// Max pool size per peer
     #[allow(dead_code)]
     max_connections: usize,
+    /// Gzip AppendEntries batches on the wire (`cluster.wan_compression`)
+    compress_batches: bool,
+    /// Protocol version messages are encoded in (`cluster.protocol_version`)
+    protocol_version: u8,
 }
 
 impl NetworkClient {
@@ -37,9 +47,34 @@ impl NetworkClient {
     pub fn new(connect_timeout: Duration, request_timeout: Duration) -> Self {
         Self {
             pool: Arc::new(RwLock::new(HashMap::new())),
+            streams: Arc::new(Mutex::new(HashMap::new())),
             connect_timeout,
             request_timeout,
             max_connections: 10,
+            compress_batches: false,
+            protocol_version: PROTOCOL_VERSION,
+        }
+    }
+
+    /// Encode messages in protocol `version`, so that nodes which predate
+    /// protocol versioning can read them during a rolling upgrade
+    pub fn with_protocol_version(mut self, version: u8) -> Self {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Gzip-compress replication batches sent with `send_async`
+    pub fn with_batch_compression(mut self, enabled: bool) -> Self {
+        self.compress_batches = enabled;
+        self
+    }
+
+    /// Write a one-way message, compressing it if it is a replication batch
+    async fn write_one_way(&self, stream: &mut TcpStream, message: &Message) -> Result<()> {
+        if self.compress_batches && matches!(message, Message::AppendEntries { .. }) {
+            write_message_compressed(stream, message, self.protocol_version).await
+        } else {
+            write_message_as(stream, message, self.protocol_version).await
         }
     }
 
@@ -65,7 +100,7 @@ impl NetworkClient {
             // Split for read/write
             let (mut reader, mut writer) = entry.stream.split();
             
-            if write_message(&mut writer, &message).await.is_err() {
+            if write_message_as(&mut writer, &message, self.protocol_version).await.is_err() {
                 // Connection is dead, remove and reconnect
                 drop(entry);
                 self.remove_connection(address).await;
@@ -88,7 +123,7 @@ impl NetworkClient {
         let stream = self.connect(address).await?;
         let (mut reader, mut writer) = stream.into_split();
 
-        write_message(&mut writer, &message).await?;
+        write_message_as(&mut writer, &message, self.protocol_version).await?;
         let response = read_message(&mut reader).await?;
 
         // Note: We don't store the split connection back to pool for simplicity
@@ -98,13 +133,67 @@ impl NetworkClient {
     }
 
     /// Send without waiting for response
+    ///
+    /// Reuses a persistent connection per address. If the write fails (peer
+    /// restarted, broken pipe) the connection is dropped and the message is
+    /// retried once on a fresh connection.
     pub async fn send_async(&self, address: &str, message: Message) -> Result<()> {
-        let stream = self.connect(address).await?;
-        let (_, mut writer) = stream.into_split();
-        write_message(&mut writer, &message).await?;
+        let entry = self.stream_for(address).await?;
+        let mut guard = entry.lock().await;
+        if self.write_one_way(&mut guard.stream, &message).await.is_ok() {
+            guard.last_used = std::time::Instant::now();
+            return Ok(());
+        }
+        drop(guard);
+        self.remove_stream(address, &entry).await;
+
+        let entry = self.stream_for(address).await?;
+        let mut guard = entry.lock().await;
+        if let Err(e) = self.write_one_way(&mut guard.stream, &message).await {
+            drop(guard);
+            self.remove_stream(address, &entry).await;
+            return Err(e);
+        }
+        guard.last_used = std::time::Instant::now();
         Ok(())
     }
 
+    /// Get the pooled one-way connection to an address, connecting if needed
+    async fn stream_for(&self, address: &str) -> Result<Arc<Mutex<PoolEntry>>> {
+        {
+            let mut streams = self.streams.lock().await;
+            if let Some(entry) = streams.get(address) {
+                let idle = entry.try_lock()
+                    .map(|e| e.last_used.elapsed() > IDLE_TIMEOUT)
+                    .unwrap_or(false);
+                if !idle {
+                    return Ok(entry.clone());
+                }
+                tracing::debug!("Closing idle connection to {}", address);
+                streams.remove(address);
+            }
+        }
+
+        // Connect without holding the map lock so other peers aren't blocked
+        let stream = self.connect(address).await?;
+        let mut streams = self.streams.lock().await;
+        let entry = streams.entry(address.to_string()).or_insert_with(|| {
+            Arc::new(Mutex::new(PoolEntry {
+                stream,
+                last_used: std::time::Instant::now(),
+            }))
+        });
+        Ok(entry.clone())
+    }
+
+    /// Drop a broken one-way connection, unless it was already replaced
+    async fn remove_stream(&self, address: &str, entry: &Arc<Mutex<PoolEntry>>) {
+        let mut streams = self.streams.lock().await;
+        if streams.get(address).is_some_and(|e| Arc::ptr_eq(e, entry)) {
+            streams.remove(address);
+        }
+    }
+
     /// Connect to an address
     async fn connect(&self, address: &str) -> Result<TcpStream> {
         let result = timeout(
@@ -152,10 +241,8 @@ impl NetworkClient {
 
     /// Clean up stale connections
     pub async fn cleanup_stale(&self, max_idle: Duration) {
-        let mut pool = self.pool.write().await;
         let now = std::time::Instant::now();
-
-        pool.retain(|addr, entry| {
+        let is_live = |addr: &String, entry: &mut Arc<Mutex<PoolEntry>>| {
             if let Ok(e) = entry.try_lock() {
                 if now.duration_since(e.last_used) > max_idle {
                     tracing::debug!("Removing stale connection to {}", addr);
@@ -163,18 +250,21 @@ impl NetworkClient {
                 }
             }
             true
-        });
+        };
+
+        self.pool.write().await.retain(|addr, entry| is_live(addr, entry));
+        self.streams.lock().await.retain(|addr, entry| is_live(addr, entry));
     }
 
     /// Close all connections
     pub async fn close_all(&self) {
-        let mut pool = self.pool.write().await;
-        pool.clear();
+        self.pool.write().await.clear();
+        self.streams.lock().await.clear();
     }
 
     /// Get connection count
     pub async fn connection_count(&self) -> usize {
-        self.pool.read().await.len()
+        self.pool.read().await.len() + self.streams.lock().await.len()
     }
 }
 
@@ -225,4 +315,105 @@ mod tests {
         let result = client.send("127.0.0.1:99999", Message::StatusRequest).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_send_async_reuses_connection() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(AtomicUsize::new(0));
+
+        let (acc, rec) = (accepted.clone(), received.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                acc.fetch_add(1, Ordering::SeqCst);
+                let rec = rec.clone();
+                tokio::spawn(async move {
+                    while read_message(&mut socket).await.is_ok() {
+                        rec.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+
+        let client = NetworkClient::new(Duration::from_secs(1), Duration::from_secs(1));
+        for _ in 0..1000 {
+            client.send_async(&address, Message::StatusRequest).await.unwrap();
+        }
+
+        for _ in 0..100 {
+            if received.load(Ordering::SeqCst) == 1000 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(received.load(Ordering::SeqCst), 1000);
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        assert_eq!(client.connection_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_async_compressed_batch() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            while let Ok(message) = read_message(&mut socket).await {
+                tx.send(message).unwrap();
+            }
+        });
+
+        let client = NetworkClient::new(Duration::from_secs(1), Duration::from_secs(1))
+            .with_batch_compression(true);
+        let batch = Message::AppendEntries {
+            term: 3,
+            leader_id: "node-1".to_string(),
+            prev_lsn: 41,
+            prev_term: 3,
+            entries: Vec::new(),
+            leader_commit_lsn: 40,
+        };
+        client.send_async(&address, batch).await.unwrap();
+        client.send_async(&address, Message::StatusRequest).await.unwrap();
+
+        match rx.recv().await.unwrap() {
+            Message::AppendEntries { term, leader_id, prev_lsn, leader_commit_lsn, .. } => {
+                assert_eq!((term, prev_lsn, leader_commit_lsn), (3, 41, 40));
+                assert_eq!(leader_id, "node-1");
+            }
+            other => panic!("unexpected message {}", other.type_name()),
+        }
+        assert!(matches!(rx.recv().await.unwrap(), Message::StatusRequest));
+    }
+
+    #[tokio::test]
+    async fn test_send_async_legacy_protocol() {
+        use crate::network::read_message_versioned;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            while let Ok(received) = read_message_versioned(&mut socket).await {
+                tx.send(received).unwrap();
+            }
+        });
+
+        let client = NetworkClient::new(Duration::from_secs(1), Duration::from_secs(1))
+            .with_protocol_version(crate::replication::LEGACY_PROTOCOL_VERSION);
+        client.send_async(&address, Message::StatusRequest).await.unwrap();
+        let barrier = Message::ReadBarrier { barrier_id: 1, leader_id: "node-1".to_string(), lsn: 1 };
+        assert!(client.send_async(&address, barrier).await.is_err());
+
+        let (message, version) = rx.recv().await.unwrap();
+        assert!(matches!(message, Message::StatusRequest));
+        assert_eq!(version, 0);
+    }
 }
@@ -10,7 +10,7 @@ pub use server::NetworkServer;
 pub use client::NetworkClient;
 pub use discovery::Discovery;
 
-use crate::replication::{Message, FrameHeader};
+use crate::replication::{Message, FrameHeader, PROTOCOL_VERSION};
 use crate::error::{Error, Result};
 
 /// Maximum allowed message size (1 GB) - prevents memory exhaustion from malformed messages
@@ -18,6 +18,11 @@ const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 1024;
 
 /// Read a framed message from a reader
 pub async fn read_message<R: tokio::io::AsyncRead + Unpin>(reader: &mut R) -> Result<Message> {
+    read_message_versioned(reader).await.map(|(message, _)| message)
+}
+
+/// Read a framed message along with the protocol version the sender used
+pub async fn read_message_versioned<R: tokio::io::AsyncRead + Unpin>(reader: &mut R) -> Result<(Message, u8)> {
     use tokio::io::AsyncReadExt;
 
     // Read header
@@ -25,6 +30,13 @@ pub async fn read_message<R: tokio::io::AsyncRead + Unpin>(reader: &mut R) -> Re
     reader.read_exact(&mut header_bytes).await?;
     let header = FrameHeader::from_bytes(&header_bytes);
 
+    if header.version > PROTOCOL_VERSION {
+        return Err(Error::Network(format!(
+            "Unsupported protocol version {} (this node speaks up to {})",
+            header.version, PROTOCOL_VERSION
+        )));
+    }
+
     // Safety check for message size - prevent memory exhaustion
     let msg_len = header.length as usize;
     if msg_len > MAX_MESSAGE_SIZE {
@@ -51,9 +63,13 @@ pub async fn read_message<R: tokio::io::AsyncRead + Unpin>(reader: &mut R) -> Re
         return Err(Error::Network("Message checksum mismatch".into()));
     }
 
+    if header.is_gzip() {
+        body = gunzip(&body)?;
+    }
+
     // Deserialize
-    let message = Message::deserialize(&body)?;
-    Ok(message)
+    let message = Message::deserialize_from(&body, header.version)?;
+    Ok((message, header.version))
 }
 
 /// Write a framed message to a writer
@@ -61,14 +77,61 @@ pub async fn write_message<W: tokio::io::AsyncWrite + Unpin>(
     writer: &mut W,
     message: &Message,
 ) -> Result<()> {
-    use tokio::io::AsyncWriteExt;
+    write_message_as(writer, message, PROTOCOL_VERSION).await
+}
+
+/// Write a framed message encoded for a peer speaking protocol `version`
+pub async fn write_message_as<W: tokio::io::AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+    version: u8,
+) -> Result<()> {
+    let body = message.serialize_for(version)?;
+    write_frame(writer, FrameHeader::new(&body).with_version(version), &body).await
+}
 
-    let body = message.serialize()?;
-    let header = FrameHeader::new(&body);
+/// Write a framed message with a gzip-compressed body, for batches sent
+/// over slow links (`cluster.wan_compression`)
+pub async fn write_message_compressed<W: tokio::io::AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+    version: u8,
+) -> Result<()> {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(&message.serialize_for(version)?)?;
+    let body = encoder.finish()?;
+    write_frame(writer, FrameHeader::gzip(&body).with_version(version), &body).await
+}
+
+async fn write_frame<W: tokio::io::AsyncWrite + Unpin>(
+    writer: &mut W,
+    header: FrameHeader,
+    body: &[u8],
+) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
 
     writer.write_all(&header.to_bytes()).await?;
-    writer.write_all(&body).await?;
+    writer.write_all(body).await?;
     writer.flush().await?;
 
     Ok(())
 }
+
+/// Decompress a gzip body, refusing to expand past `MAX_MESSAGE_SIZE`
+fn gunzip(body: &[u8]) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(body)
+        .take(MAX_MESSAGE_SIZE as u64 + 1)
+        .read_to_end(&mut decoded)?;
+    if decoded.len() > MAX_MESSAGE_SIZE {
+        return Err(Error::Network(format!(
+            "Decompressed message too large (max {} bytes)", MAX_MESSAGE_SIZE
+        )));
+    }
+    Ok(decoded)
+}
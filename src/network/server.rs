@@ -6,7 +6,7 @@ use std::sync::Arc;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
 
-use super::{read_message, write_message};
+use super::{read_message, read_message_versioned, write_message, write_message_as};
 use crate::replication::Message;
 use crate::error::{Error, Result};
 
@@ -101,14 +101,15 @@ async fn handle_connection(
     let (mut reader, mut writer) = socket.into_split();
 
     loop {
-        match read_message(&mut reader).await {
-            Ok(message) => {
-                tracing::trace!("Received {} from {}", message.type_name(), peer_addr);
+        match read_message_versioned(&mut reader).await {
+            Ok((message, version)) => {
+                tracing::trace!("Received {} from {} (protocol v{})", message.type_name(), peer_addr, version);
 
-                // Try to get immediate response from handler
+                // Try to get immediate response from handler, answering in
+                // the protocol version the peer spoke
                 if let Some(ref handler) = handler {
                     if let Some(response) = handler(peer_addr.clone(), message.clone()) {
-                        write_message(&mut writer, &response).await?;
+                        write_message_as(&mut writer, &response, version).await?;
                     }
                 }
 
@@ -0,0 +1,331 @@
+//! Replication Status Emulation
+//!
+//! Monitoring tools and connection pools written for MySQL replication ask a
+//! server about its replication state with `SHOW SLAVE STATUS`,
+//! `SHOW MASTER STATUS` and `SHOW PROCESSLIST`. MariaDB's own replication is
+//! not used in a WolfScale cluster, so the proxy answers these itself:
+//!
+//! - `SHOW SLAVE STATUS` is empty on the leader. On a follower it reports the
+//!   leader as master, its commit LSN as `Read_Master_Log_Pos` and the LSN
+//!   this node has applied as `Exec_Master_Log_Pos`. `Seconds_Behind_Master`
+//!   is the LSN difference divided by the leader's average commit rate over
+//!   the last minute (one entry per second when it committed nothing).
+//! - `SHOW MASTER STATUS` reports the current WAL LSN as `Position` in a
+//!   synthetic binlog file.
+//! - `SHOW PROCESSLIST` lists the connections open to this proxy, numbered by
+//!   the proxy rather than by MariaDB.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::protocol::{ColumnType, ResultSet};
+use crate::state::{ClusterMembership, NodeRole, NodeStatus};
+use crate::wal::entry::Lsn;
+use crate::wal::WalWriter;
+
+/// Synthetic binlog file the WAL is reported as
+pub const WAL_LOG_FILE: &str = "wolfscale-wal.000001";
+
+/// Synthetic relay log file reported by followers
+pub const RELAY_LOG_FILE: &str = "wolfscale-relay.000001";
+
+/// Window the leader's commit rate is averaged over
+const COMMIT_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// `Info` is cut to this many characters unless FULL is given, as in MySQL
+const PROCESSLIST_INFO_CHARS: usize = 100;
+
+/// Longest statement kept for `SHOW FULL PROCESSLIST`
+const MAX_INFO_BYTES: usize = 4096;
+
+/// A connection open to the proxy
+#[derive(Debug, Clone)]
+struct ConnectionInfo {
+    user: Option<String>,
+    host: String,
+    database: Option<String>,
+    /// Statement being executed, None while idle
+    info: Option<String>,
+    /// When the connection last changed between idle and busy
+    since: Instant,
+}
+
+/// State shared by all connections of a proxy
+pub struct ProxyStatus {
+    next_id: AtomicU64,
+    connections: Mutex<BTreeMap<u64, ConnectionInfo>>,
+    /// Samples of the leader's commit LSN, oldest first
+    commits: Mutex<VecDeque<(Instant, Lsn)>>,
+}
+
+impl ProxyStatus {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            connections: Mutex::new(BTreeMap::new()),
+            commits: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Add a connection to the process list until the handle is dropped
+    pub fn register(self: &Arc<Self>, host: String, user: Option<String>, database: Option<String>) -> ConnectionHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.connections.lock().unwrap().insert(id, ConnectionInfo {
+            user,
+            host,
+            database,
+            info: None,
+            since: Instant::now(),
+        });
+        ConnectionHandle { id, status: Arc::clone(self) }
+    }
+
+    /// Record the leader's commit LSN, sampled periodically
+    pub fn record_commit_lsn(&self, lsn: Lsn) {
+        self.record_commit_sample(Instant::now(), lsn);
+    }
+
+    fn record_commit_sample(&self, at: Instant, lsn: Lsn) {
+        let mut commits = self.commits.lock().unwrap();
+        commits.push_back((at, lsn));
+        while commits.front().is_some_and(|(t, _)| at.duration_since(*t) > COMMIT_RATE_WINDOW) {
+            commits.pop_front();
+        }
+    }
+
+    /// Average entries committed per second over the sampled window
+    pub fn commit_rate(&self) -> f64 {
+        let commits = self.commits.lock().unwrap();
+        match (commits.front(), commits.back()) {
+            (Some(&(t0, lsn0)), Some(&(t1, lsn1))) if t1 > t0 => {
+                lsn1.saturating_sub(lsn0) as f64 / t1.duration_since(t0).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// `SHOW [FULL] PROCESSLIST`
+    pub fn process_list(&self, full: bool) -> ResultSet {
+        let connections = self.connections.lock().unwrap();
+        let rows = connections.iter()
+            .map(|(id, conn)| {
+                let info = conn.info.as_ref().map(|info| {
+                    if full {
+                        info.clone()
+                    } else {
+                        info.chars().take(PROCESSLIST_INFO_CHARS).collect()
+                    }
+                });
+                vec![
+                    Some(id.to_string()),
+                    conn.user.clone(),
+                    Some(conn.host.clone()),
+                    conn.database.clone(),
+                    Some(if conn.info.is_some() { "Query" } else { "Sleep" }.to_string()),
+                    Some(conn.since.elapsed().as_secs().to_string()),
+                    Some(if conn.info.is_some() { "executing" } else { "" }.to_string()),
+                    info,
+                ]
+            })
+            .collect();
+
+        ResultSet {
+            columns: vec![
+                ("Id", ColumnType::Integer),
+                ("User", ColumnType::Text),
+                ("Host", ColumnType::Text),
+                ("db", ColumnType::Text),
+                ("Command", ColumnType::Text),
+                ("Time", ColumnType::Integer),
+                ("State", ColumnType::Text),
+                ("Info", ColumnType::Text),
+            ],
+            rows,
+        }
+    }
+}
+
+impl Default for ProxyStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A connection's entry in the process list, removed when dropped
+pub struct ConnectionHandle {
+    id: u64,
+    status: Arc<ProxyStatus>,
+}
+
+impl ConnectionHandle {
+    /// Mark the connection as executing `statement`
+    pub fn begin(&self, statement: &[u8]) {
+        let statement = &statement[..statement.len().min(MAX_INFO_BYTES)];
+        self.update(|conn| {
+            conn.info = Some(String::from_utf8_lossy(statement).into_owned());
+            conn.since = Instant::now();
+        });
+    }
+
+    /// Mark the connection as idle
+    pub fn idle(&self) {
+        self.update(|conn| {
+            if conn.info.take().is_some() {
+                conn.since = Instant::now();
+            }
+        });
+    }
+
+    /// Record a change of default database
+    pub fn set_database(&self, database: Option<String>) {
+        self.update(|conn| conn.database = database);
+    }
+
+    fn update(&self, f: impl FnOnce(&mut ConnectionInfo)) {
+        if let Some(conn) = self.status.connections.lock().unwrap().get_mut(&self.id) {
+            f(conn);
+        }
+    }
+}
+
+impl Drop for ConnectionHandle {
+    fn drop(&mut self) {
+        self.status.connections.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// `SHOW SLAVE STATUS`
+pub async fn slave_status(cluster: &ClusterMembership, status: &ProxyStatus) -> ResultSet {
+    let mut result = ResultSet {
+        columns: vec![
+            ("Slave_IO_State", ColumnType::Text),
+            ("Master_Host", ColumnType::Text),
+            ("Master_Log_File", ColumnType::Text),
+            ("Read_Master_Log_Pos", ColumnType::Integer),
+            ("Relay_Log_File", ColumnType::Text),
+            ("Relay_Master_Log_File", ColumnType::Text),
+            ("Slave_IO_Running", ColumnType::Text),
+            ("Slave_SQL_Running", ColumnType::Text),
+            ("Exec_Master_Log_Pos", ColumnType::Integer),
+            ("Seconds_Behind_Master", ColumnType::Integer),
+        ],
+        rows: Vec::new(),
+    };
+
+    let self_node = cluster.get_self().await;
+    if self_node.role == NodeRole::Leader {
+        // Like a MySQL primary: not a replica, so no row
+        return result;
+    }
+
+    let leader = cluster.current_leader().await;
+    let io_running = leader.as_ref().is_some_and(|l| l.status == NodeStatus::Active);
+    let sql_running = !matches!(
+        self_node.status,
+        NodeStatus::Offline | NodeStatus::Dropped | NodeStatus::NeedsMigration
+    );
+    let leader_lsn = leader.as_ref().map(|l| l.last_applied_lsn).unwrap_or(0);
+    let seconds_behind = (io_running && sql_running).then(|| {
+        seconds_behind(leader_lsn.saturating_sub(self_node.last_applied_lsn), status.commit_rate())
+    });
+
+    let yes_no = |running: bool| Some(if running { "Yes" } else { "No" }.to_string());
+    result.rows.push(vec![
+        Some(if io_running { "Waiting for master to send event" } else { "Connecting to master" }.to_string()),
+        Some(leader.as_ref()
+            .map(|l| l.address.split(':').next().unwrap_or_default().to_string())
+            .unwrap_or_default()),
+        Some(WAL_LOG_FILE.to_string()),
+        Some(leader_lsn.to_string()),
+        Some(RELAY_LOG_FILE.to_string()),
+        Some(WAL_LOG_FILE.to_string()),
+        yes_no(io_running),
+        yes_no(sql_running),
+        Some(self_node.last_applied_lsn.to_string()),
+        seconds_behind.map(|s| s.to_string()),
+    ]);
+    result
+}
+
+/// Replication lag in seconds for `lag` entries at `commit_rate` entries/s
+fn seconds_behind(lag: Lsn, commit_rate: f64) -> u64 {
+    if lag == 0 {
+        0
+    } else if commit_rate > 0.0 {
+        (lag as f64 / commit_rate).ceil() as u64
+    } else {
+        lag
+    }
+}
+
+/// `SHOW MASTER STATUS`
+pub async fn master_status(cluster: &ClusterMembership, wal_writer: Option<&WalWriter>) -> ResultSet {
+    let mut position = cluster.get_self().await.last_applied_lsn;
+    if let Some(wal) = wal_writer {
+        position = position.max(wal.current_lsn().await);
+    }
+    ResultSet {
+        columns: vec![
+            ("File", ColumnType::Text),
+            ("Position", ColumnType::Integer),
+            ("Binlog_Do_DB", ColumnType::Text),
+            ("Binlog_Ignore_DB", ColumnType::Text),
+        ],
+        rows: vec![vec![
+            Some(WAL_LOG_FILE.to_string()),
+            Some(position.to_string()),
+            Some(String::new()),
+            Some(String::new()),
+        ]],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_rate_and_lag() {
+        let status = ProxyStatus::new();
+        assert_eq!(status.commit_rate(), 0.0);
+
+        let now = Instant::now();
+        status.record_commit_sample(now - Duration::from_secs(90), 0);
+        status.record_commit_sample(now - Duration::from_secs(10), 400);
+        status.record_commit_sample(now, 500);
+        // The sample from 90s ago is outside the window
+        assert_eq!(status.commit_rate(), 10.0);
+
+        assert_eq!(seconds_behind(0, 10.0), 0);
+        assert_eq!(seconds_behind(30, 10.0), 3);
+        assert_eq!(seconds_behind(31, 10.0), 4);
+        assert_eq!(seconds_behind(7, 0.0), 7);
+    }
+
+    #[test]
+    fn test_process_list_tracks_connections() {
+        let status = Arc::new(ProxyStatus::new());
+        let a = status.register("10.0.0.5:40000".to_string(), Some("app".to_string()), None);
+        let b = status.register("10.0.0.6:40001".to_string(), None, Some("shop".to_string()));
+        a.begin("SELECT ".repeat(50).as_bytes());
+        a.set_database(Some("crm".to_string()));
+
+        let list = status.process_list(false);
+        assert_eq!(list.rows.len(), 2);
+        assert_eq!(list.rows[0][1].as_deref(), Some("app"));
+        assert_eq!(list.rows[0][3].as_deref(), Some("crm"));
+        assert_eq!(list.rows[0][4].as_deref(), Some("Query"));
+        assert_eq!(list.rows[0][7].as_ref().unwrap().len(), PROCESSLIST_INFO_CHARS);
+        assert_eq!(status.process_list(true).rows[0][7].as_ref().unwrap().len(), 350);
+        assert_eq!(list.rows[1][4].as_deref(), Some("Sleep"));
+        assert_eq!(list.rows[1][7], None);
+
+        a.idle();
+        drop(b);
+        let list = status.process_list(true);
+        assert_eq!(list.rows.len(), 1);
+        assert_eq!(list.rows[0][4].as_deref(), Some("Sleep"));
+    }
+}
@@ -8,7 +8,22 @@ use tokio::io::AsyncWriteExt;
 
 use crate::state::ClusterMembership;
 use crate::error::Result;
-use super::protocol::{MySqlPacket, build_ok_packet, build_error_packet};
+use super::protocol::{MySqlPacket, build_ok_packet, build_error_packet, is_write_statement};
+
+/// How the proxy handles a statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryClass {
+    /// Forwarded to a backend as a read
+    Read,
+    /// Replicated through the leader
+    Write,
+    /// `SHOW SLAVE STATUS`, answered by the proxy
+    SlaveStatus,
+    /// `SHOW MASTER STATUS`, answered by the proxy
+    MasterStatus,
+    /// `SHOW [FULL] PROCESSLIST`, answered by the proxy
+    ProcessList { full: bool },
+}
 
 /// Query handler that routes queries appropriately
 pub struct QueryHandler {
@@ -38,6 +53,25 @@ impl QueryHandler {
         }
     }
 
+    /// Classify a statement. Replication status statements are answered by
+    /// the proxy from cluster state, since MariaDB's own replication is not
+    /// what keeps the nodes in sync.
+    pub fn classify_query(query: &str) -> QueryClass {
+        let normalized = query.trim().trim_end_matches(';')
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_ascii_uppercase();
+        match normalized.as_str() {
+            "SHOW SLAVE STATUS" | "SHOW REPLICA STATUS" => QueryClass::SlaveStatus,
+            "SHOW MASTER STATUS" | "SHOW BINLOG STATUS" => QueryClass::MasterStatus,
+            "SHOW PROCESSLIST" => QueryClass::ProcessList { full: false },
+            "SHOW FULL PROCESSLIST" => QueryClass::ProcessList { full: true },
+            _ if is_write_statement(query) => QueryClass::Write,
+            _ => QueryClass::Read,
+        }
+    }
+
     /// Handle a query packet
     pub async fn handle_query(
         &self,
@@ -63,7 +97,7 @@ impl QueryHandler {
         tracing::debug!("Query: {}", query);
 
         // Check if this is a write query
-        if packet.is_write_query() {
+        if Self::classify_query(&query) == QueryClass::Write {
             self.handle_write_query(&query, packet.header.sequence_id, client_stream).await
         } else {
             self.handle_read_query(&query, packet.header.sequence_id, client_stream).await
@@ -174,3 +208,21 @@ impl QueryHandler {
         Ok((result.rows_affected(), 0)) // TODO: get last_insert_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_query() {
+        assert_eq!(QueryHandler::classify_query("SHOW SLAVE STATUS"), QueryClass::SlaveStatus);
+        assert_eq!(QueryHandler::classify_query("  show   slave\nstatus ;"), QueryClass::SlaveStatus);
+        assert_eq!(QueryHandler::classify_query("SHOW REPLICA STATUS"), QueryClass::SlaveStatus);
+        assert_eq!(QueryHandler::classify_query("show master status"), QueryClass::MasterStatus);
+        assert_eq!(QueryHandler::classify_query("SHOW PROCESSLIST"), QueryClass::ProcessList { full: false });
+        assert_eq!(QueryHandler::classify_query("SHOW FULL PROCESSLIST;"), QueryClass::ProcessList { full: true });
+        assert_eq!(QueryHandler::classify_query("SHOW SLAVE HOSTS"), QueryClass::Read);
+        assert_eq!(QueryHandler::classify_query("SELECT 'SHOW SLAVE STATUS'"), QueryClass::Read);
+        assert_eq!(QueryHandler::classify_query("INSERT INTO t VALUES (1)"), QueryClass::Write);
+    }
+}
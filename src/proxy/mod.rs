@@ -6,7 +6,12 @@
 mod server;
 mod protocol;
 mod handler;
+mod routing;
+mod emulation;
+mod prepared;
+mod pipeline;
 
-pub use server::{ProxyServer, ProxyConfig};
+pub use server::{ActiveConnection, Admission, ConnectionPool, ProxyServer, ProxyConfig};
 pub use protocol::{MySqlPacket, PacketType};
-pub use handler::QueryHandler;
+pub use handler::{QueryClass, QueryHandler};
+pub use routing::{route_read, ReadRoute, StaleReadResponse};
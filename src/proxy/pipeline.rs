@@ -0,0 +1,168 @@
+//! Query Pipelining
+//!
+//! Clients that negotiate `CLIENT_MULTI_STATEMENTS` may send several queries
+//! without waiting for the answers, as batched INSERTs often do. The proxy
+//! reads up to `proxy.pipeline_depth` such queries, sends them to MariaDB
+//! together and answers them in the order they were sent. Each answer is
+//! queued in a `ResponseOrdering`: a read's response as soon as MariaDB has
+//! sent it, a write's once its WAL append completes.
+
+use std::future::Future;
+use std::io;
+
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{FuturesOrdered, StreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::prepared::read_lenenc_int;
+use super::protocol::CLIENT_DEPRECATE_EOF;
+
+/// CLIENT_MULTI_STATEMENTS: the client may send more than one statement at once
+pub const CLIENT_MULTI_STATEMENTS: u32 = 0x0001_0000;
+
+/// SERVER_MORE_RESULTS_EXISTS: another result follows this one
+const SERVER_MORE_RESULTS_EXISTS: u16 = 0x0008;
+
+/// Payload length of a packet that continues in the next one
+const MAX_PACKET_PAYLOAD: usize = 0xff_ffff;
+
+/// Responses to pipelined queries, handed out in submission order however
+/// their futures complete. Futures are first polled in the order they were
+/// pushed, so WAL appends (whose channel is fair) keep statement order.
+#[derive(Default)]
+pub struct ResponseOrdering {
+    queue: FuturesOrdered<BoxFuture<'static, Vec<u8>>>,
+}
+
+impl ResponseOrdering {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue the future producing the next response's bytes
+    pub fn push(&mut self, response: impl Future<Output = Vec<u8>> + Send + 'static) {
+        self.queue.push_back(response.boxed());
+    }
+
+    /// The oldest queued response, waiting for it if needed
+    pub async fn next(&mut self) -> Option<Vec<u8>> {
+        self.queue.next().await
+    }
+
+    /// The oldest queued response if it is already complete. Also starts
+    /// the futures queued since the last poll.
+    pub fn ready(&mut self) -> Option<Vec<u8>> {
+        self.queue.next().now_or_never().flatten()
+    }
+}
+
+/// Read the complete response to one COM_QUERY: an OK or ERR packet or a
+/// result set, followed by further results while the server reports
+/// SERVER_MORE_RESULTS_EXISTS. Returns the packets as received.
+pub async fn read_response<S: AsyncRead + Unpin>(stream: &mut S, client_capabilities: u32) -> io::Result<Vec<u8>> {
+    let deprecate_eof = client_capabilities & CLIENT_DEPRECATE_EOF != 0;
+    let mut raw = Vec::new();
+    loop {
+        let first = read_packet(stream, &mut raw).await?;
+        let status = match first.first() {
+            Some(0xFF) => return Ok(raw),
+            Some(0x00) | Some(0xFE) => status_flags(&first, false)?,
+            Some(0xFB) => {
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "LOCAL INFILE cannot be pipelined"));
+            }
+            _ => {
+                let columns = read_lenenc_int(&first, &mut 0)?;
+                for _ in 0..columns {
+                    read_packet(stream, &mut raw).await?;
+                }
+                if !deprecate_eof {
+                    read_packet(stream, &mut raw).await?;
+                }
+                loop {
+                    let row = read_packet(stream, &mut raw).await?;
+                    match row.first() {
+                        Some(0xFF) => return Ok(raw),
+                        // A row can only start with 0xFE when it needs a longer packet
+                        Some(0xFE) if row.len() < MAX_PACKET_PAYLOAD => break status_flags(&row, !deprecate_eof)?,
+                        _ => {}
+                    }
+                }
+            }
+        };
+        if status & SERVER_MORE_RESULTS_EXISTS == 0 {
+            return Ok(raw);
+        }
+    }
+}
+
+/// Read one packet, joining continuation packets. The bytes read are
+/// appended to `raw` and the payload returned.
+async fn read_packet<S: AsyncRead + Unpin>(stream: &mut S, raw: &mut Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).await?;
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let start = payload.len();
+        payload.resize(start + len, 0);
+        stream.read_exact(&mut payload[start..]).await?;
+        raw.extend_from_slice(&header);
+        raw.extend_from_slice(&payload[start..]);
+        if len < MAX_PACKET_PAYLOAD {
+            return Ok(payload);
+        }
+    }
+}
+
+/// Status flags of an OK packet or (with `eof`) an EOF packet
+fn status_flags(packet: &[u8], eof: bool) -> io::Result<u16> {
+    let mut pos = 1;
+    if eof {
+        pos += 2; // warnings
+    } else {
+        read_lenenc_int(packet, &mut pos)?; // affected rows
+        read_lenenc_int(packet, &mut pos)?; // last insert id
+    }
+    packet
+        .get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Truncated status flags"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::protocol::{build_ok_packet, build_result_set, ColumnType, ResultSet};
+
+    #[tokio::test]
+    async fn test_read_multi_result_response() {
+        // OK with SERVER_MORE_RESULTS_EXISTS, then a result set, then the next response
+        let mut stream = vec![7, 0, 0, 1, 0x00, 1, 0, 0x08, 0, 0, 0];
+        let result = ResultSet {
+            columns: vec![("id", ColumnType::Integer)],
+            rows: vec![vec![Some("1".to_string())], vec![None]],
+        };
+        stream.extend_from_slice(&build_result_set(&result, 0));
+        let first_len = stream.len();
+        build_ok_packet(1, 1, 0).write(&mut stream);
+
+        let mut reader = &stream[..];
+        assert_eq!(read_response(&mut reader, 0).await.unwrap(), stream[..first_len]);
+        assert_eq!(read_response(&mut reader, 0).await.unwrap(), stream[first_len..]);
+        assert!(reader.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_responses_in_submission_order() {
+        let mut ordering = ResponseOrdering::new();
+        ordering.push(async {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            b"first".to_vec()
+        });
+        ordering.push(async { b"second".to_vec() });
+        assert_eq!(ordering.ready(), None);
+        assert_eq!(ordering.next().await.unwrap(), b"first");
+        assert_eq!(ordering.ready().unwrap(), b"second");
+        assert_eq!(ordering.next().await, None);
+    }
+}
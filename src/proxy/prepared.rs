@@ -0,0 +1,536 @@
+//! Prepared Statements
+//!
+//! Applications using the binary protocol send `COM_STMT_PREPARE` once and
+//! then `COM_STMT_EXECUTE` with binary parameter values. MariaDB runs these
+//! itself, but a write still has to reach the WAL (on the leader) or the
+//! leader (on a follower) as SQL text, and a read routed to the leader runs
+//! on a backend connection that has never seen the statement. Each proxy
+//! connection therefore keeps its statements: the SQL template, the
+//! parameter types from the last execute that sent them (clients only resend
+//! them when they change) and any `COM_STMT_SEND_LONG_DATA` chunks. On
+//! execute the parameters are bound into the template, giving a plain
+//! statement that is classified and replicated like a `COM_QUERY`.
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::metrics;
+
+/// Binary protocol commands
+pub const COM_STMT_PREPARE: u8 = 0x16;
+pub const COM_STMT_EXECUTE: u8 = 0x17;
+pub const COM_STMT_SEND_LONG_DATA: u8 = 0x18;
+pub const COM_STMT_CLOSE: u8 = 0x19;
+pub const COM_STMT_RESET: u8 = 0x1a;
+
+/// MariaDB: execute the statement prepared last on this connection
+const LAST_PREPARED_STMT_ID: u32 = 0xffff_ffff;
+
+/// Parameter type codes (MYSQL_TYPE_*)
+const TYPE_DECIMAL: u8 = 0x00;
+const TYPE_TINY: u8 = 0x01;
+const TYPE_SHORT: u8 = 0x02;
+const TYPE_LONG: u8 = 0x03;
+const TYPE_FLOAT: u8 = 0x04;
+const TYPE_DOUBLE: u8 = 0x05;
+const TYPE_NULL: u8 = 0x06;
+const TYPE_TIMESTAMP: u8 = 0x07;
+const TYPE_LONGLONG: u8 = 0x08;
+const TYPE_INT24: u8 = 0x09;
+const TYPE_DATE: u8 = 0x0a;
+const TYPE_TIME: u8 = 0x0b;
+const TYPE_DATETIME: u8 = 0x0c;
+const TYPE_YEAR: u8 = 0x0d;
+const TYPE_VARCHAR: u8 = 0x0f;
+const TYPE_BIT: u8 = 0x10;
+const TYPE_JSON: u8 = 0xf5;
+const TYPE_NEWDECIMAL: u8 = 0xf6;
+const TYPE_ENUM: u8 = 0xf7;
+const TYPE_SET: u8 = 0xf8;
+const TYPE_TINY_BLOB: u8 = 0xf9;
+const TYPE_MEDIUM_BLOB: u8 = 0xfa;
+const TYPE_LONG_BLOB: u8 = 0xfb;
+const TYPE_BLOB: u8 = 0xfc;
+const TYPE_VAR_STRING: u8 = 0xfd;
+const TYPE_STRING: u8 = 0xfe;
+const TYPE_GEOMETRY: u8 = 0xff;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// A statement prepared on this connection
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    /// SQL template with `?` placeholders
+    pub sql: String,
+    /// Number of placeholders
+    pub num_params: u16,
+    /// (type code, unsigned) per parameter, from the last execute that sent them
+    param_types: Vec<(u8, bool)>,
+    /// COM_STMT_SEND_LONG_DATA chunks since the last execute, by parameter
+    long_data: HashMap<u16, Vec<u8>>,
+}
+
+/// A COM_STMT_EXECUTE with its parameters bound into the template
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundExecute {
+    /// Statement id, with MariaDB's "last prepared" id resolved
+    pub stmt_id: u32,
+    /// The statement as plain SQL
+    pub sql: String,
+    /// The same execute for a backend that knows the statement under another
+    /// id, with parameter types always included. None if parameters were sent
+    /// as long data, which only the original backend has.
+    reroutable: Option<Vec<u8>>,
+}
+
+impl BoundExecute {
+    /// COM_STMT_EXECUTE payload for a backend that prepared the statement as `stmt_id`
+    pub fn payload_for(&self, stmt_id: u32) -> Option<Vec<u8>> {
+        let mut payload = self.reroutable.clone()?;
+        payload[1..5].copy_from_slice(&stmt_id.to_le_bytes());
+        Some(payload)
+    }
+}
+
+/// Statements prepared on one client connection, by the id the local backend assigned
+#[derive(Debug, Default)]
+pub struct StatementCache {
+    statements: HashMap<u32, PreparedStatement>,
+    last_prepared: Option<u32>,
+}
+
+impl StatementCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up a statement, resolving MariaDB's "last prepared" id
+    pub fn get(&self, stmt_id: u32) -> Option<&PreparedStatement> {
+        self.statements.get(&self.resolve(stmt_id))
+    }
+
+    fn resolve(&self, stmt_id: u32) -> u32 {
+        match (stmt_id, self.last_prepared) {
+            (LAST_PREPARED_STMT_ID, Some(last)) => last,
+            _ => stmt_id,
+        }
+    }
+
+    /// Record the backend's response to a COM_STMT_PREPARE of `sql`.
+    /// `response` is the payload of its first packet; error responses are ignored.
+    pub fn prepared(&mut self, sql: String, response: &[u8]) -> Option<u32> {
+        let (stmt_id, num_params) = parse_prepare_ok(response)?;
+        let statement = PreparedStatement { sql, num_params, param_types: Vec::new(), long_data: HashMap::new() };
+        if self.statements.insert(stmt_id, statement).is_none() {
+            metrics::PREPARED_STMT_CACHE_SIZE.inc();
+        }
+        self.last_prepared = Some(stmt_id);
+        Some(stmt_id)
+    }
+
+    /// Forget a statement (COM_STMT_CLOSE), returning its resolved id
+    pub fn close(&mut self, stmt_id: u32) -> Option<u32> {
+        let stmt_id = self.resolve(stmt_id);
+        self.statements.remove(&stmt_id)?;
+        metrics::PREPARED_STMT_CACHE_SIZE.dec();
+        if self.last_prepared == Some(stmt_id) {
+            self.last_prepared = None;
+        }
+        Some(stmt_id)
+    }
+
+    /// Forget every statement (COM_RESET_CONNECTION, COM_CHANGE_USER)
+    pub fn clear(&mut self) {
+        metrics::PREPARED_STMT_CACHE_SIZE.sub(self.statements.len() as i64);
+        self.statements.clear();
+        self.last_prepared = None;
+    }
+
+    /// Drop long data sent for a statement (COM_STMT_RESET)
+    pub fn reset(&mut self, stmt_id: u32) {
+        let stmt_id = self.resolve(stmt_id);
+        if let Some(statement) = self.statements.get_mut(&stmt_id) {
+            statement.long_data.clear();
+        }
+    }
+
+    /// Buffer a COM_STMT_SEND_LONG_DATA chunk; `payload` follows the command byte
+    pub fn send_long_data(&mut self, payload: &[u8]) -> io::Result<()> {
+        if payload.len() < 6 {
+            return Err(invalid("COM_STMT_SEND_LONG_DATA too short"));
+        }
+        let stmt_id = self.resolve(u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]));
+        let param = u16::from_le_bytes([payload[4], payload[5]]);
+        let statement = self.statements.get_mut(&stmt_id)
+            .ok_or_else(|| invalid(format!("Unknown statement {}", stmt_id)))?;
+        statement.long_data.entry(param).or_default().extend_from_slice(&payload[6..]);
+        Ok(())
+    }
+
+    /// Bind a COM_STMT_EXECUTE's parameters into its template; `payload`
+    /// follows the command byte. Long data is consumed even on error, as
+    /// the backend does.
+    pub fn bind(&mut self, payload: &[u8]) -> io::Result<BoundExecute> {
+        if payload.len() < 9 {
+            return Err(invalid("COM_STMT_EXECUTE too short"));
+        }
+        let stmt_id = self.resolve(u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]));
+        let statement = self.statements.get_mut(&stmt_id)
+            .ok_or_else(|| invalid(format!("Unknown statement {}", stmt_id)))?;
+        let long_data = std::mem::take(&mut statement.long_data);
+        let count = statement.num_params as usize;
+        if count == 0 {
+            let mut reroutable = vec![COM_STMT_EXECUTE];
+            reroutable.extend_from_slice(&payload[..9]);
+            return Ok(BoundExecute { stmt_id, sql: statement.sql.clone(), reroutable: Some(reroutable) });
+        }
+
+        // [stmt_id:4] [flags:1] [iteration_count:4] [null bitmap] [new_params_bound:1] [types] [values]
+        let mut pos = 9;
+        let bitmap_len = count.div_ceil(8);
+        let null_bitmap = payload.get(pos..pos + bitmap_len).ok_or_else(|| invalid("Truncated null bitmap"))?;
+        pos += bitmap_len;
+        let new_params_bound = *payload.get(pos).ok_or_else(|| invalid("Truncated execute"))?;
+        pos += 1;
+        if new_params_bound == 1 {
+            let types = payload.get(pos..pos + 2 * count).ok_or_else(|| invalid("Truncated parameter types"))?;
+            statement.param_types = types.chunks(2).map(|t| (t[0], t[1] & 0x80 != 0)).collect();
+            pos += 2 * count;
+        } else if statement.param_types.len() != count {
+            return Err(invalid("Parameter types were never sent"));
+        }
+        let values_start = pos;
+
+        let mut values = Vec::with_capacity(count);
+        for (i, &(type_code, unsigned)) in statement.param_types.iter().enumerate() {
+            if null_bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                values.push("NULL".to_string());
+            } else if let Some(data) = long_data.get(&(i as u16)) {
+                values.push(bytes_literal(type_code, data));
+            } else {
+                values.push(read_value(type_code, unsigned, payload, &mut pos)?);
+            }
+        }
+        let sql = substitute_placeholders(&statement.sql, &values)?;
+
+        let reroutable = long_data.is_empty().then(|| {
+            let mut reroutable = vec![COM_STMT_EXECUTE];
+            reroutable.extend_from_slice(&payload[..9 + bitmap_len]);
+            reroutable.push(1);
+            for &(type_code, unsigned) in &statement.param_types {
+                reroutable.extend_from_slice(&[type_code, if unsigned { 0x80 } else { 0 }]);
+            }
+            reroutable.extend_from_slice(&payload[values_start..]);
+            reroutable
+        });
+        Ok(BoundExecute { stmt_id, sql, reroutable })
+    }
+}
+
+impl Drop for StatementCache {
+    fn drop(&mut self) {
+        metrics::PREPARED_STMT_CACHE_SIZE.sub(self.statements.len() as i64);
+    }
+}
+
+/// Parse a COM_STMT_PREPARE_OK payload into (statement id, parameter count)
+pub fn parse_prepare_ok(payload: &[u8]) -> Option<(u32, u16)> {
+    // [0x00] [stmt_id:4] [num_columns:2] [num_params:2] [reserved:1] [warnings:2]
+    if payload.len() < 9 || payload[0] != 0x00 {
+        return None;
+    }
+    let stmt_id = u32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    Some((stmt_id, u16::from_le_bytes([payload[7], payload[8]])))
+}
+
+/// Read a length-encoded integer
+pub(super) fn read_lenenc_int(data: &[u8], pos: &mut usize) -> io::Result<u64> {
+    let first = *data.get(*pos).ok_or_else(|| invalid("Truncated length"))?;
+    let width = match first {
+        0xfc => 2,
+        0xfd => 3,
+        0xfe => 8,
+        0xfb | 0xff => return Err(invalid("Invalid length")),
+        _ => {
+            *pos += 1;
+            return Ok(first as u64);
+        }
+    };
+    let bytes = data.get(*pos + 1..*pos + 1 + width).ok_or_else(|| invalid("Truncated length"))?;
+    *pos += 1 + width;
+    Ok(bytes.iter().rev().fold(0, |value, &b| (value << 8) | b as u64))
+}
+
+/// Take `len` bytes from `data` at `pos`
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+    let bytes = data.get(*pos..*pos + len).ok_or_else(|| invalid("Truncated parameter value"))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+/// Decode one binary protocol value as an SQL literal
+fn read_value(type_code: u8, unsigned: bool, data: &[u8], pos: &mut usize) -> io::Result<String> {
+    Ok(match type_code {
+        TYPE_NULL => "NULL".to_string(),
+        TYPE_TINY => {
+            let b = take(data, pos, 1)?[0];
+            if unsigned { b.to_string() } else { (b as i8).to_string() }
+        }
+        TYPE_SHORT | TYPE_YEAR => {
+            let b = take(data, pos, 2)?;
+            let v = u16::from_le_bytes([b[0], b[1]]);
+            if unsigned { v.to_string() } else { (v as i16).to_string() }
+        }
+        TYPE_LONG | TYPE_INT24 => {
+            let b = take(data, pos, 4)?;
+            let v = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            if unsigned { v.to_string() } else { (v as i32).to_string() }
+        }
+        TYPE_LONGLONG => {
+            let b = take(data, pos, 8)?;
+            let v = u64::from_le_bytes(b.try_into().expect("8 bytes"));
+            if unsigned { v.to_string() } else { (v as i64).to_string() }
+        }
+        TYPE_FLOAT => {
+            let b = take(data, pos, 4)?;
+            float_literal(f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)?
+        }
+        TYPE_DOUBLE => {
+            let b = take(data, pos, 8)?;
+            float_literal(f64::from_le_bytes(b.try_into().expect("8 bytes")))?
+        }
+        TYPE_DATE | TYPE_DATETIME | TYPE_TIMESTAMP => {
+            let len = take(data, pos, 1)?[0] as usize;
+            datetime_literal(type_code, take(data, pos, len)?)?
+        }
+        TYPE_TIME => {
+            let len = take(data, pos, 1)?[0] as usize;
+            time_literal(take(data, pos, len)?)?
+        }
+        TYPE_DECIMAL | TYPE_NEWDECIMAL | TYPE_VARCHAR | TYPE_BIT | TYPE_JSON | TYPE_ENUM | TYPE_SET
+        | TYPE_TINY_BLOB | TYPE_MEDIUM_BLOB | TYPE_LONG_BLOB | TYPE_BLOB | TYPE_VAR_STRING
+        | TYPE_STRING | TYPE_GEOMETRY => {
+            let len = read_lenenc_int(data, pos)? as usize;
+            bytes_literal(type_code, take(data, pos, len)?)
+        }
+        other => return Err(invalid(format!("Unsupported parameter type 0x{:02x}", other))),
+    })
+}
+
+fn float_literal(value: f64) -> io::Result<String> {
+    if !value.is_finite() {
+        return Err(invalid(format!("{} cannot be stored", value)));
+    }
+    Ok(value.to_string())
+}
+
+/// A string or binary value as a literal that means the same whatever the
+/// connection's sql_mode: text is quoted with `''` escaping, and anything
+/// else (binary data, text containing backslashes) is hex-encoded
+fn bytes_literal(type_code: u8, bytes: &[u8]) -> String {
+    let binary = matches!(type_code, TYPE_BIT | TYPE_GEOMETRY);
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !binary && !text.contains('\\') => format!("'{}'", text.replace('\'', "''")),
+        _ if bytes.is_empty() => "''".to_string(),
+        _ => {
+            let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+/// [year:2] [month] [day] ([hour] [minute] [second] ([microsecond:4]))
+fn datetime_literal(type_code: u8, b: &[u8]) -> io::Result<String> {
+    let (year, month, day) = match b.len() {
+        0 => (0, 0, 0),
+        4 | 7 | 11 => (u16::from_le_bytes([b[0], b[1]]), b[2], b[3]),
+        len => return Err(invalid(format!("Invalid date length {}", len))),
+    };
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    if type_code == TYPE_DATE {
+        return Ok(format!("'{}'", date));
+    }
+    let (hour, minute, second) = if b.len() >= 7 { (b[4], b[5], b[6]) } else { (0, 0, 0) };
+    let micros = if b.len() == 11 { u32::from_le_bytes([b[7], b[8], b[9], b[10]]) } else { 0 };
+    Ok(format!("'{} {:02}:{:02}:{:02}{}'", date, hour, minute, second, fraction(micros)))
+}
+
+/// [negative] [days:4] [hour] [minute] [second] ([microsecond:4])
+fn time_literal(b: &[u8]) -> io::Result<String> {
+    if b.is_empty() {
+        return Ok("'00:00:00'".to_string());
+    }
+    if b.len() != 8 && b.len() != 12 {
+        return Err(invalid(format!("Invalid time length {}", b.len())));
+    }
+    let sign = if b[0] == 1 { "-" } else { "" };
+    let hours = u32::from_le_bytes([b[1], b[2], b[3], b[4]]) as u64 * 24 + b[5] as u64;
+    let micros = if b.len() == 12 { u32::from_le_bytes([b[8], b[9], b[10], b[11]]) } else { 0 };
+    Ok(format!("'{}{:02}:{:02}:{:02}{}'", sign, hours, b[6], b[7], fraction(micros)))
+}
+
+fn fraction(micros: u32) -> String {
+    if micros == 0 { String::new() } else { format!(".{:06}", micros) }
+}
+
+/// Replace each `?` placeholder outside quotes and comments with the next value
+pub fn substitute_placeholders(template: &str, values: &[String]) -> io::Result<String> {
+    let mut sql = String::with_capacity(template.len() + values.iter().map(String::len).sum::<usize>());
+    let mut values = values.iter();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '?' => sql.push_str(values.next().ok_or_else(|| invalid("More placeholders than parameters"))?),
+            '\'' | '"' | '`' => {
+                sql.push(c);
+                while let Some(inner) = chars.next() {
+                    sql.push(inner);
+                    if inner == '\\' && c != '`' {
+                        if let Some(escaped) = chars.next() {
+                            sql.push(escaped);
+                        }
+                    } else if inner == c {
+                        break;
+                    }
+                }
+            }
+            '#' => {
+                sql.push(c);
+                for inner in chars.by_ref() {
+                    sql.push(inner);
+                    if inner == '\n' {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                sql.push(c);
+                for inner in chars.by_ref() {
+                    sql.push(inner);
+                    if inner == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                sql.push(c);
+                let mut previous = '\0';
+                for inner in chars.by_ref() {
+                    sql.push(inner);
+                    if previous == '*' && inner == '/' {
+                        break;
+                    }
+                    previous = inner;
+                }
+            }
+            _ => sql.push(c),
+        }
+    }
+    if values.next().is_some() {
+        return Err(invalid("More parameters than placeholders"));
+    }
+    Ok(sql)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepare_ok(stmt_id: u32, num_params: u16) -> Vec<u8> {
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(&stmt_id.to_le_bytes());
+        payload.extend_from_slice(&0u16.to_le_bytes());
+        payload.extend_from_slice(&num_params.to_le_bytes());
+        payload.extend_from_slice(&[0, 0, 0]);
+        payload
+    }
+
+    #[test]
+    fn test_bind_all_types() {
+        let mut cache = StatementCache::new();
+        let sql = "INSERT INTO t VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, '?')";
+        assert_eq!(cache.prepared(sql.to_string(), &prepare_ok(3, 9)), Some(3));
+
+        let mut payload = 3u32.to_le_bytes().to_vec();
+        payload.extend_from_slice(&[0, 1, 0, 0, 0]); // flags, iteration count
+        payload.extend_from_slice(&[0b0100_0000, 0]); // parameter 6 is NULL
+        payload.push(1);
+        for (type_code, flags) in [
+            (TYPE_TINY, 0), (TYPE_LONGLONG, 0x80), (TYPE_DOUBLE, 0), (TYPE_VAR_STRING, 0),
+            (TYPE_BLOB, 0), (TYPE_DATETIME, 0), (TYPE_LONG, 0), (TYPE_TIME, 0), (TYPE_NEWDECIMAL, 0),
+        ] {
+            payload.extend_from_slice(&[type_code, flags]);
+        }
+        payload.push(0xff); // -1
+        payload.extend_from_slice(&u64::MAX.to_le_bytes());
+        payload.extend_from_slice(&2.5f64.to_le_bytes());
+        payload.extend_from_slice(b"\x04it's");
+        payload.extend_from_slice(b"\x02\x00\xff");
+        payload.extend_from_slice(&[11, 0xe8, 0x07, 2, 29, 13, 5, 9, 0x40, 0xe2, 0x01, 0x00]);
+        payload.extend_from_slice(&[8, 1, 1, 0, 0, 0, 2, 3, 4]);
+        payload.extend_from_slice(b"\x0412.5");
+
+        let bound = cache.bind(&payload).unwrap();
+        assert_eq!(bound.stmt_id, 3);
+        assert_eq!(
+            bound.sql,
+            "INSERT INTO t VALUES (-1, 18446744073709551615, 2.5, 'it''s', X'00FF', \
+             '2024-02-29 13:05:09.123456', NULL, '-26:03:04', '12.5', '?')"
+        );
+    }
+
+    #[test]
+    fn test_types_remembered_between_executes() {
+        let mut cache = StatementCache::new();
+        cache.prepared("UPDATE t SET a = ? WHERE id = ?".to_string(), &prepare_ok(9, 2));
+
+        let mut first = 9u32.to_le_bytes().to_vec();
+        first.extend_from_slice(&[0, 1, 0, 0, 0, 0, 1, TYPE_STRING, 0, TYPE_SHORT, 0]);
+        first.extend_from_slice(b"\x01x\x07\x00");
+        assert_eq!(cache.bind(&first).unwrap().sql, "UPDATE t SET a = 'x' WHERE id = 7");
+
+        // Types omitted, long data for the first parameter, "last prepared" id
+        cache.send_long_data(&[9, 0, 0, 0, 0, 0, b'a', b'\\']).unwrap();
+        let mut second = LAST_PREPARED_STMT_ID.to_le_bytes().to_vec();
+        second.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 8, 0]);
+        let bound = cache.bind(&second).unwrap();
+        assert_eq!(bound.sql, "UPDATE t SET a = X'615C' WHERE id = 8");
+        assert_eq!(bound.payload_for(1), None);
+
+        // Rerouted executes carry the remembered types
+        let mut third = 9u32.to_le_bytes().to_vec();
+        third.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0]);
+        third.extend_from_slice(b"\x01y\x09\x00");
+        let bound = cache.bind(&third).unwrap();
+        assert_eq!(bound.sql, "UPDATE t SET a = 'y' WHERE id = 9");
+        let mut rerouted = vec![COM_STMT_EXECUTE];
+        rerouted.extend_from_slice(&[4, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, TYPE_STRING, 0, TYPE_SHORT, 0]);
+        rerouted.extend_from_slice(b"\x01y\x09\x00");
+        assert_eq!(bound.payload_for(4), Some(rerouted));
+    }
+
+    #[test]
+    fn test_substitute_placeholders() {
+        let values = ["1".to_string(), "'b'".to_string()];
+        assert_eq!(
+            substitute_placeholders("SELECT ? /* ? */, \"?\", 'a\\'?' -- ?\n, ?", &values).unwrap(),
+            "SELECT 1 /* ? */, \"?\", 'a\\'?' -- ?\n, 'b'"
+        );
+        assert!(substitute_placeholders("SELECT ?", &values).is_err());
+        assert!(substitute_placeholders("SELECT ?, ?, ?", &values).is_err());
+    }
+
+    #[test]
+    fn test_close_updates_gauge() {
+        let mut cache = StatementCache::new();
+        cache.prepared("SELECT 1".to_string(), &prepare_ok(1, 0));
+        cache.prepared("SELECT 2".to_string(), &prepare_ok(2, 0));
+        assert!(cache.prepared("SELECT x".to_string(), &[0xff, 0x28, 0x04]).is_none());
+        assert!(cache.get(1).is_some());
+        assert_eq!(cache.close(1), Some(1));
+        assert!(cache.close(1).is_none());
+        assert_eq!(cache.get(2).unwrap().sql, "SELECT 2");
+    }
+}
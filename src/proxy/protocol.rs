@@ -137,20 +137,108 @@ impl MySqlPacket {
 
     /// Check if this is a write query
     pub fn is_write_query(&self) -> bool {
-        if let Some(query) = self.query_string() {
-            let upper = query.trim().to_uppercase();
-            upper.starts_with("INSERT") ||
-            upper.starts_with("UPDATE") ||
-            upper.starts_with("DELETE") ||
-            upper.starts_with("CREATE") ||
-            upper.starts_with("ALTER") ||
-            upper.starts_with("DROP") ||
-            upper.starts_with("TRUNCATE") ||
-            upper.starts_with("REPLACE")
-        } else {
-            false
+        self.query_string().is_some_and(|query| is_write_statement(&query))
+    }
+}
+
+/// Check if a statement modifies data or schema
+pub fn is_write_statement(query: &str) -> bool {
+    let upper = query.trim().to_uppercase();
+    upper.starts_with("INSERT") ||
+    upper.starts_with("UPDATE") ||
+    upper.starts_with("DELETE") ||
+    upper.starts_with("CREATE") ||
+    upper.starts_with("ALTER") ||
+    upper.starts_with("DROP") ||
+    upper.starts_with("TRUNCATE") ||
+    upper.starts_with("REPLACE")
+}
+
+/// Type of a result set column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// MYSQL_TYPE_VAR_STRING (0xfd)
+    Text,
+    /// MYSQL_TYPE_LONGLONG (0x08)
+    Integer,
+}
+
+/// A text-protocol result set built by the proxy itself
+#[derive(Debug, Clone, Default)]
+pub struct ResultSet {
+    pub columns: Vec<(&'static str, ColumnType)>,
+    /// Row values in column order, None for NULL
+    pub rows: Vec<Vec<Option<String>>>,
+}
+
+/// CLIENT_DEPRECATE_EOF: result sets end with an OK packet instead of EOF
+pub const CLIENT_DEPRECATE_EOF: u32 = 0x0100_0000;
+
+/// Encode a result set as the response to a COM_QUERY: column count,
+/// column definitions, EOF, one packet per row, then EOF (or OK when the
+/// client negotiated `CLIENT_DEPRECATE_EOF`)
+pub fn build_result_set(result: &ResultSet, client_capabilities: u32) -> Vec<u8> {
+    let deprecate_eof = client_capabilities & CLIENT_DEPRECATE_EOF != 0;
+    let mut buf = Vec::new();
+    let mut sequence_id = 1u8;
+    let mut push = |buf: &mut Vec<u8>, payload: Vec<u8>| {
+        MySqlPacket::new(sequence_id, payload).write(buf);
+        sequence_id = sequence_id.wrapping_add(1);
+    };
+
+    let mut count = Vec::new();
+    write_lenenc_int(&mut count, result.columns.len() as u64);
+    push(&mut buf, count);
+
+    for (name, column_type) in &result.columns {
+        let mut def = Vec::new();
+        write_lenenc_str(&mut def, b"def"); // catalog
+        write_lenenc_str(&mut def, b""); // schema
+        write_lenenc_str(&mut def, b""); // table
+        write_lenenc_str(&mut def, b""); // org_table
+        write_lenenc_str(&mut def, name.as_bytes());
+        write_lenenc_str(&mut def, name.as_bytes());
+        def.push(0x0c); // length of the fixed fields
+        let (charset, length, type_code, flags): (u16, u32, u8, u16) = match column_type {
+            ColumnType::Text => (33, 1024, 0xfd, 0),
+            // binary charset, UNSIGNED_FLAG | BINARY_FLAG | NUM_FLAG
+            ColumnType::Integer => (63, 20, 0x08, 0x0020 | 0x0080 | 0x8000),
+        };
+        def.extend_from_slice(&charset.to_le_bytes());
+        def.extend_from_slice(&length.to_le_bytes());
+        def.push(type_code);
+        def.extend_from_slice(&flags.to_le_bytes());
+        def.push(0); // decimals
+        def.extend_from_slice(&[0, 0]); // filler
+        push(&mut buf, def);
+    }
+    if !deprecate_eof {
+        push(&mut buf, eof_payload());
+    }
+
+    for row in &result.rows {
+        let mut payload = Vec::new();
+        for value in row {
+            match value {
+                Some(v) => write_lenenc_str(&mut payload, v.as_bytes()),
+                None => payload.push(0xfb),
+            }
         }
+        push(&mut buf, payload);
     }
+
+    if deprecate_eof {
+        // OK packet with the EOF header: affected rows, insert id, status, warnings
+        push(&mut buf, vec![0xfe, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]);
+    } else {
+        push(&mut buf, eof_payload());
+    }
+    buf
+}
+
+/// EOF payload: header, warnings, status flags (SERVER_STATUS_AUTOCOMMIT)
+fn eof_payload() -> Vec<u8> {
+    vec![0xfe, 0x00, 0x00, 0x02, 0x00]
 }
 
 /// Build an OK packet
@@ -271,6 +359,12 @@ fn write_lenenc_int(buf: &mut Vec<u8>, value: u64) {
     }
 }
 
+/// Write a length-encoded string
+fn write_lenenc_str(buf: &mut Vec<u8>, value: &[u8]) {
+    write_lenenc_int(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,298 @@
+//! Read Routing
+//!
+//! Decides where a SELECT received by a node's proxy runs. Reads normally go
+//! to the local MariaDB, but a follower that trails the leader's commit LSN
+//! by more than `max_stale_lsn` would serve outdated rows. Such reads are
+//! either sent to the leader's MariaDB or refused, depending on
+//! `stale_read_response`.
+
+use std::io;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::prepared::COM_STMT_PREPARE;
+use crate::metrics;
+use crate::state::{ClusterMembership, NodeRole};
+
+/// MySQL capability flags used when opening a connection on the client's behalf
+const CLIENT_CONNECT_WITH_DB: u32 = 0x0000_0008;
+const CLIENT_COMPRESS: u32 = 0x0000_0020;
+const CLIENT_SSL: u32 = 0x0000_0800;
+const CLIENT_SECURE_CONNECTION: u32 = 0x0000_8000;
+const CLIENT_PLUGIN_AUTH: u32 = 0x0008_0000;
+const CLIENT_CONNECT_ATTRS: u32 = 0x0010_0000;
+const CLIENT_PLUGIN_AUTH_LENENC_DATA: u32 = 0x0020_0000;
+const CLIENT_PROTOCOL_41: u32 = 0x0000_0200;
+const CLIENT_DEPRECATE_EOF: u32 = 0x0100_0000;
+
+/// What to do with a read when this node is too far behind the leader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleReadResponse {
+    /// Run the read on the leader instead
+    Leader,
+    /// Refuse the read with MySQL error 1290
+    Error,
+}
+
+impl StaleReadResponse {
+    /// Parse the `proxy.stale_read_response` config value
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "leader" => Some(StaleReadResponse::Leader),
+            "error" => Some(StaleReadResponse::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Where a read should run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadRoute {
+    /// Local backend (leader, or follower within the allowed lag)
+    Local,
+    /// The leader's MariaDB at this host
+    Leader(String),
+    /// Refuse the read; this node trails the leader by this many LSNs
+    Reject(u64),
+}
+
+/// Decide where a read should run based on this node's replication lag
+pub async fn route_read(
+    cluster: &ClusterMembership,
+    max_stale_lsn: u64,
+    on_stale: StaleReadResponse,
+) -> ReadRoute {
+    let self_node = cluster.get_self().await;
+    if self_node.role != NodeRole::Follower {
+        return ReadRoute::Local;
+    }
+    let Some(leader) = cluster.current_leader().await else {
+        return ReadRoute::Local;
+    };
+
+    // On a follower the leader's last_applied_lsn is the commit LSN from its heartbeats
+    let lag = leader.last_applied_lsn.saturating_sub(self_node.last_applied_lsn);
+    if lag <= max_stale_lsn {
+        return ReadRoute::Local;
+    }
+
+    metrics::STALE_READS_AVOIDED.inc();
+    match on_stale {
+        StaleReadResponse::Leader => {
+            let host = leader.address.split(':').next().unwrap_or("localhost").to_string();
+            tracing::debug!("Follower is {} LSNs behind, routing read to leader at {}", lag, host);
+            ReadRoute::Leader(host)
+        }
+        StaleReadResponse::Error => ReadRoute::Reject(lag),
+    }
+}
+
+/// Read one MySQL packet, returning (sequence id, payload)
+async fn read_packet(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await?;
+    Ok((header[3], payload))
+}
+
+/// Write one MySQL packet
+async fn write_packet(stream: &mut TcpStream, sequence_id: u8, payload: &[u8]) -> io::Result<()> {
+    let len = payload.len() as u32;
+    let mut packet = Vec::with_capacity(4 + payload.len());
+    packet.extend_from_slice(&len.to_le_bytes()[..3]);
+    packet.push(sequence_id);
+    packet.extend_from_slice(payload);
+    stream.write_all(&packet).await
+}
+
+/// mysql_native_password: SHA1(password) XOR SHA1(scramble + SHA1(SHA1(password)))
+fn scramble_native_password(password: &str, scramble: &[u8]) -> Vec<u8> {
+    use sha1::{Digest, Sha1};
+
+    if password.is_empty() {
+        return Vec::new();
+    }
+    let stage1 = Sha1::digest(password.as_bytes());
+    let stage2 = Sha1::digest(stage1);
+    let mut hasher = Sha1::new();
+    hasher.update(scramble);
+    hasher.update(stage2);
+    let stage3 = hasher.finalize();
+    stage1.iter().zip(stage3.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+/// Extract the 20-byte auth scramble from a v10 handshake payload
+fn handshake_scramble(handshake: &[u8]) -> Option<Vec<u8>> {
+    let version_end = 1 + handshake.get(1..)?.iter().position(|&b| b == 0)?;
+    let part1_start = version_end + 1 + 4; // NUL + connection id
+    let mut scramble = handshake.get(part1_start..part1_start + 8)?.to_vec();
+    // filler (1) + capabilities (2) + charset (1) + status (2) + capabilities (2) + auth len (1) + reserved (10)
+    let part2_start = part1_start + 8 + 19;
+    scramble.extend_from_slice(handshake.get(part2_start..part2_start + 12)?);
+    Some(scramble)
+}
+
+fn auth_error(payload: &[u8]) -> io::Error {
+    let message = payload.get(9..).map(String::from_utf8_lossy).unwrap_or_default();
+    io::Error::new(io::ErrorKind::PermissionDenied, format!("Backend auth failed: {}", message))
+}
+
+/// Open a connection to a MariaDB server, authenticating with
+/// mysql_native_password. `client_capabilities` are the flags the client
+/// negotiated with the local backend, so result sets come back in the
+/// format the client expects.
+pub async fn connect_backend(
+    addr: &str,
+    user: &str,
+    password: &str,
+    database: Option<&str>,
+    client_capabilities: u32,
+) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(addr).await?;
+
+    let (seq, handshake) = read_packet(&mut stream).await?;
+    if handshake.first() == Some(&0xFF) {
+        return Err(auth_error(&handshake));
+    }
+    let scramble = handshake_scramble(&handshake)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Malformed backend handshake"))?;
+
+    let mut capabilities = (client_capabilities
+        & !(CLIENT_SSL | CLIENT_COMPRESS | CLIENT_CONNECT_ATTRS | CLIENT_PLUGIN_AUTH_LENENC_DATA | CLIENT_CONNECT_WITH_DB))
+        | CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION | CLIENT_PLUGIN_AUTH;
+    if database.is_some() {
+        capabilities |= CLIENT_CONNECT_WITH_DB;
+    }
+
+    let auth = scramble_native_password(password, &scramble);
+    let mut response = Vec::new();
+    response.extend_from_slice(&capabilities.to_le_bytes());
+    response.extend_from_slice(&16_777_216u32.to_le_bytes());
+    response.push(33); // utf8_general_ci
+    response.extend_from_slice(&[0u8; 23]);
+    response.extend_from_slice(user.as_bytes());
+    response.push(0);
+    response.push(auth.len() as u8);
+    response.extend_from_slice(&auth);
+    if let Some(db) = database {
+        response.extend_from_slice(db.as_bytes());
+        response.push(0);
+    }
+    response.extend_from_slice(b"mysql_native_password\0");
+    write_packet(&mut stream, seq.wrapping_add(1), &response).await?;
+
+    let (seq, reply) = read_packet(&mut stream).await?;
+    match reply.first() {
+        Some(0x00) => Ok(stream),
+        Some(0xFE) => {
+            // Auth switch request: [0xFE] [plugin name NUL] [scramble NUL]
+            let rest = &reply[1..];
+            let name_end = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+            if &rest[..name_end] != b"mysql_native_password" {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("Unsupported auth plugin {}", String::from_utf8_lossy(&rest[..name_end])),
+                ));
+            }
+            let data = rest.get(name_end + 1..).unwrap_or_default();
+            let data = &data[..data.len().min(20)];
+            write_packet(&mut stream, seq.wrapping_add(1), &scramble_native_password(password, data)).await?;
+
+            let (_, reply) = read_packet(&mut stream).await?;
+            if reply.first() == Some(&0x00) {
+                Ok(stream)
+            } else {
+                Err(auth_error(&reply))
+            }
+        }
+        _ => Err(auth_error(&reply)),
+    }
+}
+
+/// Prepare `sql` on a connection opened by `connect_backend` with the same
+/// `client_capabilities`, returning the backend's statement id. The
+/// parameter and column definitions that follow are discarded.
+pub async fn prepare_on_backend(stream: &mut TcpStream, sql: &str, client_capabilities: u32) -> io::Result<u32> {
+    let mut payload = vec![COM_STMT_PREPARE];
+    payload.extend_from_slice(sql.as_bytes());
+    write_packet(stream, 0, &payload).await?;
+
+    let (_, reply) = read_packet(stream).await?;
+    if reply.first() != Some(&0x00) || reply.len() < 9 {
+        let message = reply.get(9..).map(String::from_utf8_lossy).unwrap_or_default();
+        return Err(io::Error::other(format!("Backend prepare failed: {}", message)));
+    }
+    let stmt_id = u32::from_le_bytes([reply[1], reply[2], reply[3], reply[4]]);
+    let columns = u16::from_le_bytes([reply[5], reply[6]]) as usize;
+    let params = u16::from_le_bytes([reply[7], reply[8]]) as usize;
+    let eof = usize::from(client_capabilities & CLIENT_DEPRECATE_EOF == 0);
+    let definitions = [params, columns].iter().filter(|&&n| n > 0).map(|n| n + eof).sum::<usize>();
+    for _ in 0..definitions {
+        read_packet(stream).await?;
+    }
+    Ok(stmt_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn follower_cluster() -> ClusterMembership {
+        let cluster = ClusterMembership::new(
+            "node-2".to_string(),
+            "10.0.0.2:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+        );
+        cluster.add_peer("node-1".to_string(), "10.0.0.1:7654".to_string()).await.unwrap();
+        cluster.set_leader("node-1").await.unwrap();
+        cluster.record_heartbeat("node-1", 100).await.unwrap();
+        cluster.record_heartbeat("node-2", 100).await.unwrap();
+        cluster
+    }
+
+    #[tokio::test]
+    async fn test_reads_rerouted_when_follower_stale() {
+        let cluster = follower_cluster().await;
+        assert_eq!(route_read(&cluster, 100, StaleReadResponse::Leader).await, ReadRoute::Local);
+
+        // Follower paused while the leader keeps committing writes
+        cluster.record_heartbeat("node-1", 350).await.unwrap();
+        let avoided = metrics::STALE_READS_AVOIDED.get();
+        assert_eq!(
+            route_read(&cluster, 100, StaleReadResponse::Leader).await,
+            ReadRoute::Leader("10.0.0.1".to_string())
+        );
+        assert_eq!(route_read(&cluster, 100, StaleReadResponse::Error).await, ReadRoute::Reject(250));
+        assert!(metrics::STALE_READS_AVOIDED.get() >= avoided + 2);
+
+        // Within the allowed lag once it catches up
+        cluster.record_heartbeat("node-2", 300).await.unwrap();
+        assert_eq!(route_read(&cluster, 100, StaleReadResponse::Leader).await, ReadRoute::Local);
+    }
+
+    #[tokio::test]
+    async fn test_leader_always_reads_locally() {
+        let cluster = follower_cluster().await;
+        cluster.set_leader("node-2").await.unwrap();
+        cluster.record_heartbeat("node-1", 10_000).await.unwrap();
+        assert_eq!(route_read(&cluster, 0, StaleReadResponse::Error).await, ReadRoute::Local);
+    }
+
+    #[test]
+    fn test_handshake_scramble() {
+        let mut handshake = vec![10];
+        handshake.extend_from_slice(b"10.11.6-MariaDB\0");
+        handshake.extend_from_slice(&[1, 0, 0, 0]);
+        handshake.extend_from_slice(b"abcdefgh");
+        handshake.extend_from_slice(&[0u8; 19]);
+        handshake.extend_from_slice(b"ijklmnopqrst\0");
+        handshake.extend_from_slice(b"mysql_native_password\0");
+        assert_eq!(handshake_scramble(&handshake).unwrap(), b"abcdefghijklmnopqrst");
+        assert_eq!(scramble_native_password("secret", b"abcdefghijklmnopqrst").len(), 20);
+    }
+}
@@ -6,20 +6,38 @@
 //! - Writes are logged to WAL for replication before execution
 //! - Smart routing: reads from local if caught up, otherwise from leader
 //! - Optional SSL/TLS encryption for client connections
+//! - At most `max_connections` clients at once; the overflow queues, and
+//!   beyond `max_queue_depth` is refused with error 1040
+//! - New clients are refused with error 1053 while the node is in maintenance
+//! - On the leader, queries a client sends without waiting are pipelined
+//!   (see `pipeline`)
 
-use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::path::PathBuf;
 use std::io::BufReader;
 use std::fs::File;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use futures::FutureExt;
 use tokio_rustls::TlsAcceptor;
 use rustls::pki_types::CertificateDer;
 
 use crate::state::{ClusterMembership, NodeRole};
 use crate::wal::{WalWriter, LogEntry};
 use crate::error::Result;
-use super::protocol::MySqlPacket;
+use crate::metrics;
+use super::emulation::{master_status, slave_status, ConnectionHandle, ProxyStatus};
+use super::handler::{QueryClass, QueryHandler};
+use super::pipeline::{read_response, ResponseOrdering, CLIENT_MULTI_STATEMENTS};
+use super::protocol::{build_error_packet, build_result_set, MySqlPacket};
+use super::prepared::{
+    StatementCache, COM_STMT_CLOSE, COM_STMT_EXECUTE, COM_STMT_PREPARE, COM_STMT_RESET, COM_STMT_SEND_LONG_DATA,
+};
+use super::routing::{connect_backend, prepare_on_backend, route_read, ReadRoute, StaleReadResponse};
 
 /// MySQL proxy server configuration
 #[derive(Debug, Clone)]
@@ -42,6 +60,163 @@ pub struct ProxyConfig {
     pub ssl_key: Option<PathBuf>,
     /// Require SSL from clients (reject non-SSL connections)
     pub ssl_required: bool,
+    /// Maximum LSNs this node may trail the leader and still serve reads
+    pub max_stale_lsn: u64,
+    /// What to do with reads beyond max_stale_lsn
+    pub stale_read_response: StaleReadResponse,
+    /// Client connections proxied at once (0 = unlimited)
+    pub max_connections: usize,
+    /// Connections that may queue for a slot before new ones are refused
+    pub max_queue_depth: usize,
+    /// Queries pipelined per batch for clients with CLIENT_MULTI_STATEMENTS (1 = off)
+    pub pipeline_depth: usize,
+    /// `api.auth.cluster_token`, sent with writes forwarded to the leader
+    pub cluster_token: Option<String>,
+}
+
+/// TCP keepalive period for queued clients. The MySQL protocol has nothing a
+/// server may send before its handshake, so idle-timeout protection for a
+/// client waiting in the queue has to come from the transport.
+const QUEUE_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Caps the connections being proxied. Connections over the cap wait in a
+/// FIFO queue (the semaphore hands out permits in order) and get no
+/// handshake until a slot frees up; once the queue is full, new connections
+/// are refused.
+pub struct ConnectionPool {
+    permits: Arc<Semaphore>,
+    max_connections: AtomicUsize,
+    /// Permits still to be retired after `max_connections` was lowered
+    retiring: Arc<AtomicUsize>,
+    max_queue_depth: usize,
+    /// IDs of the connections waiting for a permit, oldest first
+    queue: Mutex<VecDeque<u64>>,
+    next_id: AtomicU64,
+}
+
+/// Outcome of asking the pool for a slot
+pub enum Admission {
+    Active(ActiveConnection),
+    /// The queue is full
+    Rejected,
+    /// The client hung up while queued
+    Gone,
+}
+
+/// A connection's slot in the pool, released when dropped
+pub struct ActiveConnection {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Drop for ActiveConnection {
+    fn drop(&mut self) {
+        crate::metrics::PROXY_ACTIVE_CONNECTIONS.dec();
+    }
+}
+
+impl ConnectionPool {
+    pub fn new(max_connections: usize, max_queue_depth: usize) -> Self {
+        let max_connections = if max_connections == 0 { Semaphore::MAX_PERMITS } else { max_connections };
+        Self {
+            permits: Arc::new(Semaphore::new(max_connections)),
+            max_connections: AtomicUsize::new(max_connections),
+            retiring: Arc::new(AtomicUsize::new(0)),
+            max_queue_depth,
+            queue: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Connections holding a slot
+    pub fn active(&self) -> usize {
+        (self.max_connections.load(Ordering::Relaxed) + self.retiring.load(Ordering::Relaxed))
+            .saturating_sub(self.permits.available_permits())
+    }
+
+    /// Change the connection cap (config reload). Raising it admits queued
+    /// clients straight away; lowering it takes effect as connections close.
+    /// Switching to or from unlimited (0) needs a restart.
+    pub fn set_max_connections(&self, max_connections: usize) {
+        let current = self.max_connections.load(Ordering::Relaxed);
+        let unlimited = current == Semaphore::MAX_PERMITS;
+        if (max_connections == 0) != unlimited {
+            tracing::warn!("proxy.max_connections cannot be changed to or from unlimited (0) without a restart");
+            return;
+        }
+        if max_connections == 0 || max_connections == current {
+            return;
+        }
+
+        self.max_connections.store(max_connections, Ordering::Relaxed);
+        if max_connections > current {
+            self.permits.add_permits(max_connections - current);
+            return;
+        }
+
+        // Retire free slots now, and the rest as the connections using them close
+        let excess = current - max_connections;
+        let remaining = excess - self.permits.forget_permits(excess);
+        if remaining > 0 {
+            self.retiring.fetch_add(remaining, Ordering::Relaxed);
+            let permits = Arc::clone(&self.permits);
+            let retiring = Arc::clone(&self.retiring);
+            tokio::spawn(async move {
+                if let Ok(permit) = permits.acquire_many_owned(remaining as u32).await {
+                    permit.forget();
+                }
+                retiring.fetch_sub(remaining, Ordering::Relaxed);
+            });
+        }
+    }
+
+    /// Connections waiting for a slot
+    pub fn queued(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Take a slot for `client`, queueing if none is free
+    pub async fn admit(&self, client: &TcpStream) -> Admission {
+        if let Ok(permit) = Arc::clone(&self.permits).try_acquire_owned() {
+            return Admission::Active(Self::activate(permit));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.len() >= self.max_queue_depth {
+                crate::metrics::PROXY_REJECTED_CONNECTIONS.inc();
+                return Admission::Rejected;
+            }
+            queue.push_back(id);
+            crate::metrics::PROXY_QUEUED_CONNECTIONS.set(queue.len() as i64);
+        }
+        let keepalive = socket2::TcpKeepalive::new()
+            .with_time(QUEUE_KEEPALIVE_INTERVAL)
+            .with_interval(QUEUE_KEEPALIVE_INTERVAL);
+        if let Err(e) = socket2::SockRef::from(client).set_tcp_keepalive(&keepalive) {
+            tracing::debug!("Failed to enable keepalive on queued connection: {}", e);
+        }
+
+        // Clients send nothing before the handshake, so a readable socket
+        // while queued means it was closed
+        let admission = tokio::select! {
+            permit = Arc::clone(&self.permits).acquire_owned() => match permit {
+                Ok(permit) => Admission::Active(Self::activate(permit)),
+                Err(_) => Admission::Gone,
+            },
+            _ = client.readable() => Admission::Gone,
+        };
+
+        let mut queue = self.queue.lock().unwrap();
+        queue.retain(|&queued| queued != id);
+        crate::metrics::PROXY_QUEUED_CONNECTIONS.set(queue.len() as i64);
+        admission
+    }
+
+    fn activate(permit: OwnedSemaphorePermit) -> ActiveConnection {
+        crate::metrics::PROXY_ACTIVE_CONNECTIONS.inc();
+        ActiveConnection { _permit: permit }
+    }
 }
 
 /// MySQL proxy server
@@ -50,8 +225,15 @@ pub struct ProxyServer {
     cluster: Arc<ClusterMembership>,
     wal_writer: Option<WalWriter>,
     tls_acceptor: Option<TlsAcceptor>,
+    /// Open connections and leader commit rate, for emulated SHOW statements
+    status: Arc<ProxyStatus>,
+    /// Connection limit and queue
+    pool: Arc<ConnectionPool>,
 }
 
+/// How often the leader's commit LSN is sampled for `Seconds_Behind_Master`
+const COMMIT_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
 impl ProxyServer {
     pub fn new(config: ProxyConfig, cluster: Arc<ClusterMembership>) -> Self {
         let tls_acceptor = if config.ssl_enabled {
@@ -66,7 +248,8 @@ impl ProxyServer {
             None
         };
         
-        Self { config, cluster, wal_writer: None, tls_acceptor }
+        let pool = Arc::new(ConnectionPool::new(config.max_connections, config.max_queue_depth));
+        Self { config, cluster, wal_writer: None, tls_acceptor, status: Arc::new(ProxyStatus::new()), pool }
     }
 
     /// Create with WAL writer for replication support
@@ -83,9 +266,15 @@ impl ProxyServer {
             None
         };
         
-        Self { config, cluster, wal_writer: Some(wal_writer), tls_acceptor }
+        let pool = Arc::new(ConnectionPool::new(config.max_connections, config.max_queue_depth));
+        Self { config, cluster, wal_writer: Some(wal_writer), tls_acceptor, status: Arc::new(ProxyStatus::new()), pool }
     }
     
+    /// The connection limit and queue, kept for config reloads
+    pub fn pool(&self) -> Arc<ConnectionPool> {
+        Arc::clone(&self.pool)
+    }
+
     /// Create TLS acceptor from certificate and key files
     fn create_tls_acceptor(config: &ProxyConfig) -> std::result::Result<TlsAcceptor, String> {
         let cert_path = config.ssl_cert.as_ref()
@@ -134,6 +323,25 @@ impl ProxyServer {
             tracing::info!("MySQL proxy listening on {}", self.config.listen_address);
         }
 
+        let sampler = tokio::spawn({
+            let cluster = Arc::clone(&self.cluster);
+            let status = Arc::clone(&self.status);
+            async move {
+                let mut interval = tokio::time::interval(COMMIT_SAMPLE_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Some(leader) = cluster.current_leader().await {
+                        status.record_commit_lsn(leader.last_applied_lsn);
+                    }
+                }
+            }
+        });
+        let result = self.accept_loop(listener).await;
+        sampler.abort();
+        result
+    }
+
+    async fn accept_loop(&self, listener: TcpListener) -> Result<()> {
         loop {
             let (client_socket, addr) = listener.accept().await?;
             tracing::debug!("New MySQL client connection from {}", addr);
@@ -142,8 +350,30 @@ impl ProxyServer {
             let cluster = Arc::clone(&self.cluster);
             let wal_writer = self.wal_writer.clone();
             let tls_acceptor = self.tls_acceptor.clone();
+            let status = Arc::clone(&self.status);
+            let pool = Arc::clone(&self.pool);
 
             tokio::spawn(async move {
+                let mut client_socket = client_socket;
+                if cluster.in_maintenance() {
+                    tracing::debug!("Refusing MySQL client {}: node is in maintenance", addr);
+                    let mut packet = Vec::new();
+                    build_error_packet(0, 1053, "08S01", "Server shutdown in progress").write(&mut packet);
+                    let _ = client_socket.write_all(&packet).await;
+                    return;
+                }
+                let _slot = match pool.admit(&client_socket).await {
+                    Admission::Active(slot) => slot,
+                    Admission::Rejected => {
+                        tracing::warn!("Refusing MySQL client {}: connection queue is full", addr);
+                        let mut packet = Vec::new();
+                        build_error_packet(0, 1040, "08004", "Too many connections").write(&mut packet);
+                        let _ = client_socket.write_all(&packet).await;
+                        return;
+                    }
+                    Admission::Gone => return,
+                };
+
                 // If TLS is enabled, upgrade the connection
                 if let Some(acceptor) = tls_acceptor {
                     match acceptor.accept(client_socket).await {
@@ -158,7 +388,7 @@ impl ProxyServer {
                         }
                     }
                 } else {
-                    if let Err(e) = handle_connection(client_socket, config, cluster, wal_writer).await {
+                    if let Err(e) = handle_connection(client_socket, config, cluster, wal_writer, status).await {
                         tracing::error!("Proxy connection error: {}", e);
                     }
                 }
@@ -260,6 +490,7 @@ async fn forward_write_to_leader(
     leader_url: &str,
     query: &str,
     database: &Option<String>,
+    cluster_token: Option<&str>,
 ) -> std::result::Result<ForwardWriteResult, String> {
     let client = reqwest::Client::new();
     
@@ -268,7 +499,11 @@ async fn forward_write_to_leader(
         "database": database,
     });
     
-    let response = client.post(leader_url)
+    let mut request = client.post(leader_url);
+    if let Some(token) = cluster_token {
+        request = request.bearer_auth(token);
+    }
+    let response = request
         .json(&body)
         .timeout(std::time::Duration::from_secs(30))
         .send()
@@ -458,6 +693,14 @@ fn strip_leading_comments(query: &str) -> &str {
     s
 }
 
+/// Extract the user name from a MySQL handshake response packet
+fn extract_user_from_handshake(packet: &[u8]) -> Option<String> {
+    // 4-byte header, then caps(4) + max_pkt(4) + charset(1) + reserved(23)
+    let data = packet.get(36..)?;
+    let end = data.iter().position(|&b| b == 0)?;
+    String::from_utf8(data[..end].to_vec()).ok().filter(|u| !u.is_empty())
+}
+
 /// Extract database name from MySQL handshake response packet
 /// When client connects with `mysql -D dbname` or `mysql dbname`, the database
 /// is included in the handshake response packet, not sent as a separate command
@@ -611,6 +854,7 @@ async fn handle_connection(
     config: ProxyConfig,
     cluster: Arc<ClusterMembership>,
     wal_writer: Option<WalWriter>,
+    status: Arc<ProxyStatus>,
 ) -> Result<()> {
     // Determine initial backend (for handshake)
     // Use local backend for initial connection - it's faster and handles auth
@@ -660,6 +904,14 @@ async fn handle_connection(
     
     // Try to extract database name from handshake response
     let initial_database = extract_database_from_handshake(&response_buf[..first_packet_end]);
+    let client_user = extract_user_from_handshake(&response_buf[..first_packet_end]);
+
+    // Capability flags the client negotiated, reused for connections to the leader
+    let client_capabilities = if first_packet_end >= 8 {
+        u32::from_le_bytes([response_buf[4], response_buf[5], response_buf[6], response_buf[7]])
+    } else {
+        0
+    };
     
     // Forward ONLY the first packet (handshake response) to backend
     backend.write_all(&response_buf[..first_packet_end]).await?;
@@ -713,9 +965,21 @@ async fn handle_connection(
     let mut cmd_buf = vec![0u8; INITIAL_BUFFER_SIZE]; // Start with 64KB, grows dynamically
     let mut result_buf = vec![0u8; INITIAL_BUFFER_SIZE];
     let _current_backend_addr = initial_backend_addr;
+
+    // Connection to the leader's MariaDB for reads while this follower is stale: (host, stream)
+    let mut leader_backend: Option<(String, TcpStream)> = None;
     
+    // Listed in SHOW PROCESSLIST until this connection closes
+    let client_host = client.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    let connection = status.register(client_host, client_user, initial_database.clone());
+
     // Track current database context for replication
     let mut current_database: Option<String> = initial_database;
+
+    // Prepared statements, by the id the local backend assigned
+    let mut statements = StatementCache::new();
+    // The same statements prepared on leader_backend: local id -> leader id
+    let mut leader_statements: HashMap<u32, u32> = HashMap::new();
     
     // If we have leftover bytes from auth phase (mysql -e), process them first
     let mut pending_data: Option<Vec<u8>> = if !leftover_bytes.is_empty() {
@@ -723,11 +987,18 @@ async fn handle_connection(
     } else {
         None
     };
+
+    let pipelining = config.pipeline_depth > 1 && client_capabilities & CLIENT_MULTI_STATEMENTS != 0;
     
     loop {
+        connection.idle();
+
         // Get packet data from pending data or read from client
         let n = if let Some(data) = pending_data.take() {
             let n = data.len();
+            if n > cmd_buf.len() {
+                cmd_buf.resize(n, 0);
+            }
             cmd_buf[..n].copy_from_slice(&data);
             n
         } else {
@@ -742,6 +1013,93 @@ async fn handle_connection(
             }
         };
 
+        let command = if n > 4 { cmd_buf[4] } else { 0 };
+        let stmt_id = cmd_buf.get(5..9).filter(|_| n >= 9).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+        // Queries the client already sent behind this one go to MariaDB
+        // together. Leader only, as followers forward each write to the leader
+        if pipelining
+            && command == 0x03
+            && pipelinable(&cmd_buf[5..n])
+            && cluster.get_self().await.role == NodeRole::Leader
+        {
+            let mut batch = vec![cmd_buf[..n].to_vec()];
+            while batch.len() < config.pipeline_depth {
+                // Only take what has arrived; never wait for the client
+                let mut peek_buf = [0u8; 1];
+                if !matches!(client.peek(&mut peek_buf).now_or_never(), Some(Ok(1..))) {
+                    break;
+                }
+                let mut next = vec![0u8; INITIAL_BUFFER_SIZE];
+                match read_mysql_packet_dynamic(&mut client, &mut next).await {
+                    Ok(m) if m > 0 => next.truncate(m),
+                    _ => break,
+                }
+                if next.get(4) == Some(&0x03) && pipelinable(&next[5..]) {
+                    batch.push(next);
+                } else {
+                    pending_data = Some(next);
+                    break;
+                }
+            }
+            metrics::PROXY_PIPELINE_DEPTH.observe(batch.len() as f64);
+            if batch.len() > 1 {
+                if let Err(e) = run_pipeline(
+                    &mut client,
+                    &mut backend,
+                    &batch,
+                    wal_writer.as_ref(),
+                    &mut current_database,
+                    client_capabilities,
+                    &connection,
+                )
+                .await
+                {
+                    tracing::debug!("Pipelined queries failed: {}", e);
+                    break;
+                }
+                continue;
+            }
+        }
+
+        // COM_STMT_SEND_LONG_DATA and COM_STMT_CLOSE have no response
+        if command == COM_STMT_SEND_LONG_DATA || command == COM_STMT_CLOSE {
+            if command == COM_STMT_SEND_LONG_DATA {
+                if let Err(e) = statements.send_long_data(&cmd_buf[5..n]) {
+                    tracing::debug!("Ignoring long data: {}", e);
+                }
+            } else if let Some(local_id) = stmt_id.and_then(|id| statements.close(id)) {
+                if let (Some(leader_id), Some((_, leader))) = (leader_statements.remove(&local_id), leader_backend.as_mut()) {
+                    let mut close = Vec::new();
+                    MySqlPacket::new(0, [&[COM_STMT_CLOSE][..], &leader_id.to_le_bytes()].concat()).write(&mut close);
+                    if let Err(e) = leader.write_all(&close).await {
+                        tracing::debug!("Failed to close statement on leader MariaDB: {}", e);
+                    }
+                }
+            }
+            if let Err(e) = backend.write_all(&cmd_buf[..n]).await {
+                tracing::error!("Backend write error: {}", e);
+                break;
+            }
+            continue;
+        }
+        match command {
+            COM_STMT_RESET => {
+                if let Some(id) = stmt_id {
+                    statements.reset(id);
+                }
+            }
+            // COM_CHANGE_USER and COM_RESET_CONNECTION drop all statements
+            0x11 | 0x1f => {
+                statements.clear();
+                leader_statements.clear();
+                leader_backend = None;
+            }
+            _ => {}
+        }
+        let mut pending_prepare = (command == COM_STMT_PREPARE)
+            .then(|| String::from_utf8_lossy(&cmd_buf[5..n]).into_owned());
+
         // FAST PATH: Check for SELECT/SHOW queries early - skip write processing
         // Most WordPress queries are SELECTs, so this optimization matters a lot.
         // Check first byte after header for COM_QUERY (0x03), then check query prefix
@@ -763,10 +1121,57 @@ async fn handle_connection(
             false
         };
 
+        if n > 5 && cmd_buf[4] == 0x03 {
+            connection.begin(&cmd_buf[5..n]);
+        }
+
+        // Replication status statements are answered here rather than by MariaDB
+        if is_fast_read && cmd_buf[5..n.min(9)].eq_ignore_ascii_case(b"SHOW") {
+            let query = String::from_utf8_lossy(&cmd_buf[5..n]);
+            let result = match QueryHandler::classify_query(&query) {
+                QueryClass::SlaveStatus => Some(slave_status(&cluster, &status).await),
+                QueryClass::MasterStatus => Some(master_status(&cluster, wal_writer.as_ref()).await),
+                QueryClass::ProcessList { full } => Some(status.process_list(full)),
+                QueryClass::Read | QueryClass::Write => None,
+            };
+            if let Some(result) = result {
+                if let Err(e) = client.write_all(&build_result_set(&result, client_capabilities)).await {
+                    tracing::debug!("Client write error: {}", e);
+                    break;
+                }
+                continue;
+            }
+        }
+
         // For fast reads, skip all write processing - go straight to forwarding
         // (The response handling below works for both reads and writes)
+        let mut bound_execute = None;
         let (is_write, query_opt) = if is_fast_read {
             (false, None) // Fast path: definitely not a write, don't need query string
+        } else if command == COM_STMT_EXECUTE {
+            // Bound into SQL text so writes replicate like COM_QUERY
+            match statements.bind(&cmd_buf[5..n]) {
+                Ok(bound) => {
+                    connection.begin(bound.sql.as_bytes());
+                    let query = bound.sql.clone();
+                    bound_execute = Some(bound);
+                    (is_write_query(&query), Some(query))
+                }
+                Err(e) => {
+                    // MariaDB reports unknown statements itself, but a write
+                    // that cannot be bound would never be replicated
+                    if stmt_id.and_then(|id| statements.get(id)).is_some_and(|s| is_write_query(&s.sql)) {
+                        tracing::warn!("Rejecting prepared write that cannot be replicated: {}", e);
+                        let err_packet = create_mysql_error_packet(&format!("Cannot replicate prepared statement: {}", e));
+                        if let Err(e) = client.write_all(&err_packet).await {
+                            tracing::error!("Failed to send error packet to client: {}", e);
+                        }
+                        continue;
+                    }
+                    tracing::debug!("Cannot bind prepared statement: {}", e);
+                    (false, None)
+                }
+            }
         } else if let Ok((packet, _)) = MySqlPacket::read(&cmd_buf[..n]) {
             if let Some(query) = packet.query_string() {
                 let write = is_write_query(&query);
@@ -778,21 +1183,12 @@ async fn handle_connection(
             (false, None)
         };
 
-        // Track database context changes (USE statements) - only for potential writes
-        if is_write {
-            if let Some(ref query) = query_opt {
-                // Check for USE statement using our fast case-insensitive check
-                let trimmed = query.trim();
-                if trimmed.len() >= 4 {
-                    let prefix = &trimmed.as_bytes()[..4.min(trimmed.len())];
-                    if prefix.eq_ignore_ascii_case(b"USE ") || prefix.eq_ignore_ascii_case(b"USE`") {
-                        let db_name = trimmed[3..].trim().trim_matches('`').trim_matches(';').to_string();
-                        if !db_name.is_empty() {
-                            current_database = Some(db_name);
-                        }
-                    }
-                }
-            }
+        // Track database context changes (USE statements, and COM_INIT_DB,
+        // which query_string() turns into one) so later writes are logged
+        // against the right database
+        if let Some(db_name) = query_opt.as_deref().and_then(use_database) {
+            current_database = Some(db_name);
+            connection.set_database(current_database.clone());
         }
 
         // If this is a write query, handle based on role
@@ -818,7 +1214,7 @@ async fn handle_connection(
                         tracing::info!("Forwarding write to leader at {}", leader_api_url);
                         
                         // Create HTTP client and forward the query
-                        match forward_write_to_leader(&leader_api_url, query, &current_database).await {
+                        match forward_write_to_leader(&leader_api_url, query, &current_database, config.cluster_token.as_deref()).await {
                             Ok(result) => {
                                 // Send success response to client
                                 // This is an OK packet for MySQL protocol
@@ -850,19 +1246,102 @@ async fn handle_connection(
         }
         }
 
-        // Note: Backend routing removed for performance - we keep connections on their
-        // original backend for session consistency. Replication handles data sync.
+        // Writes and session commands stay on the original backend for session consistency.
+        // Reads only leave it when this follower trails the leader by more than max_stale_lsn.
+        let mut route = ReadRoute::Local;
+        if is_fast_read || (bound_execute.is_some() && !is_write) {
+            route = route_read(&cluster, config.max_stale_lsn, config.stale_read_response).await;
+        }
+
+        if let ReadRoute::Reject(lag) = route {
+            let mut err_packet = Vec::new();
+            build_error_packet(
+                1,
+                1290,
+                "HY000",
+                &format!("Node is {} LSNs behind the leader (max_stale_lsn = {})", lag, config.max_stale_lsn),
+            )
+            .write(&mut err_packet);
+            if let Err(e) = client.write_all(&err_packet).await {
+                tracing::error!("Failed to send error packet to client: {}", e);
+            }
+            continue;
+        }
+
+        if let ReadRoute::Leader(ref host) = route {
+            if leader_backend.as_ref().map(|(h, _)| h != host).unwrap_or(true) {
+                let addr = format!("{}:{}", host, config.backend_port);
+                leader_backend = match connect_backend(
+                    &addr,
+                    &config.backend_user,
+                    &config.backend_password,
+                    current_database.as_deref(),
+                    client_capabilities,
+                )
+                .await
+                {
+                    Ok(stream) => {
+                        leader_statements.clear();
+                        Some((host.clone(), stream))
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to connect to leader MariaDB at {}: {}. Reading locally", addr, e);
+                        None
+                    }
+                };
+            }
+        }
+
+        // A rerouted execute needs the statement prepared on the leader's connection too
+        let mut rerouted_execute = None;
+        if let (ReadRoute::Leader(_), Some(bound)) = (&route, &bound_execute) {
+            if let Some((_, stream)) = leader_backend.as_mut() {
+                let leader_id = match leader_statements.get(&bound.stmt_id) {
+                    Some(&id) => Ok(id),
+                    None => {
+                        let sql = statements.get(bound.stmt_id).map(|s| s.sql.clone()).unwrap_or_default();
+                        prepare_on_backend(stream, &sql, client_capabilities).await
+                    }
+                };
+                match leader_id {
+                    Ok(id) => {
+                        leader_statements.insert(bound.stmt_id, id);
+                        rerouted_execute = bound.payload_for(id).map(|payload| {
+                            let mut packet = Vec::new();
+                            MySqlPacket::new(0, payload).write(&mut packet);
+                            packet
+                        });
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to prepare statement on leader MariaDB: {}. Reading locally", e);
+                        leader_backend = None;
+                        leader_statements.clear();
+                    }
+                }
+            }
+            // Long data only reached the local backend
+            if rerouted_execute.is_none() {
+                route = ReadRoute::Local;
+            }
+        }
+
+        let backend: &mut TcpStream = match (&route, leader_backend.as_mut()) {
+            (ReadRoute::Leader(_), Some((_, stream))) => stream,
+            _ => &mut backend,
+        };
 
         // Forward command to backend
         let query_start = std::time::Instant::now();
         let query_size = n;
+        let mut unobserved_query = query_opt.as_deref();
         
         // Log large queries before sending
         if n > 1024 * 1024 {
             tracing::info!("Sending large query to backend: {} MB", n / (1024 * 1024));
         }
         
-        if let Err(e) = backend.write_all(&cmd_buf[..n]).await {
+        let outgoing = rerouted_execute.as_deref().unwrap_or(&cmd_buf[..n]);
+        if let Err(e) = backend.write_all(outgoing).await {
             tracing::error!("Backend write error: {}", e);
             break;
         }
@@ -874,7 +1353,7 @@ async fn handle_connection(
             // Use a timeout to periodically log progress for long-running queries
             let read_result = tokio::time::timeout(
                 std::time::Duration::from_secs(5),
-                read_with_dynamic_buffer(&mut backend, &mut result_buf)
+                read_with_dynamic_buffer(backend, &mut result_buf)
             ).await;
             
             let rn = match read_result {
@@ -901,12 +1380,22 @@ async fn handle_connection(
             
             // Log query completion time for slow queries
             let elapsed = query_start.elapsed();
+            if let Some(query) = unobserved_query.take() {
+                metrics::observe_query(query, elapsed);
+            }
             if elapsed.as_secs() > 5 {
                 tracing::warn!("Slow query completed: {} bytes in {:.1}s", query_size, elapsed.as_secs_f64());
             } else if elapsed.as_secs() > 1 && query_size > 100_000 {
                 tracing::info!("Query completed: {} KB in {:.1}s", query_size / 1024, elapsed.as_secs_f64());
             }
 
+            // The first read starts with the COM_STMT_PREPARE_OK (or error) packet
+            if let Some(sql) = pending_prepare.take() {
+                if let Some(stmt_id) = statements.prepared(sql, result_buf.get(4..rn).unwrap_or_default()) {
+                    tracing::debug!("Prepared statement {}", stmt_id);
+                }
+            }
+
             // Forward to client
             if let Err(e) = client.write_all(&result_buf[..rn]).await {
                 tracing::error!("Client write error: {}", e);
@@ -935,6 +1424,7 @@ async fn handle_connection(
                         sql,
                         affects_table: table,
                         database,
+                        gtid: None,
                     };
                     
                     match wal.append(entry).await {
@@ -953,6 +1443,86 @@ async fn handle_connection(
     Ok(())
 }
 
+/// Database a `USE db` statement switches to
+fn use_database(query: &str) -> Option<String> {
+    let trimmed = query.trim();
+    let prefix = trimmed.as_bytes().get(..4)?;
+    if !prefix.eq_ignore_ascii_case(b"USE ") && !prefix.eq_ignore_ascii_case(b"USE`") {
+        return None;
+    }
+    let db_name = trimmed[3..].trim().trim_end_matches(';').trim().trim_matches('`');
+    (!db_name.is_empty()).then(|| db_name.to_string())
+}
+
+/// Whether a COM_QUERY statement may be pipelined. Statements the proxy
+/// answers itself and LOAD DATA (which may ask for a local file) may not.
+fn pipelinable(statement: &[u8]) -> bool {
+    let query = String::from_utf8_lossy(statement);
+    let query = strip_leading_comments(&query);
+    let keyword = query.get(..4).unwrap_or_default();
+    if keyword.eq_ignore_ascii_case("LOAD") {
+        return false;
+    }
+    !keyword.eq_ignore_ascii_case("SHOW")
+        || matches!(QueryHandler::classify_query(query), QueryClass::Read | QueryClass::Write)
+}
+
+/// Send a batch of pipelined COM_QUERY packets to the backend and answer
+/// them in order. Writes that succeed are answered once they are in the WAL,
+/// logged against the database selected by any `USE` before them in the batch.
+async fn run_pipeline(
+    client: &mut TcpStream,
+    backend: &mut TcpStream,
+    batch: &[Vec<u8>],
+    wal_writer: Option<&WalWriter>,
+    database: &mut Option<String>,
+    client_capabilities: u32,
+    connection: &ConnectionHandle,
+) -> std::io::Result<()> {
+    let sent = std::time::Instant::now();
+    backend.write_all(&batch.concat()).await?;
+
+    let mut responses = ResponseOrdering::new();
+    for packet in batch {
+        let sql = String::from_utf8_lossy(&packet[5..]).into_owned();
+        connection.begin(sql.as_bytes());
+        let response = read_response(backend, client_capabilities).await?;
+        metrics::observe_query(&sql, sent.elapsed());
+        let failed = response.get(4) == Some(&0xFF);
+        if let Some(db_name) = use_database(&sql).filter(|_| !failed) {
+            *database = Some(db_name);
+            connection.set_database(database.clone());
+        }
+        match wal_writer.filter(|_| !failed && is_write_query(&sql)) {
+            Some(wal) => {
+                let wal = wal.clone();
+                let entry = LogEntry::RawSql {
+                    affects_table: extract_table_name(&sql),
+                    sql,
+                    database: database.clone(),
+                    gtid: None,
+                };
+                responses.push(async move {
+                    match wal.append(entry).await {
+                        Ok(lsn) => tracing::debug!("Pipelined WAL write completed: LSN {}", lsn),
+                        Err(e) => tracing::error!("Pipelined WAL write failed: {}", e),
+                    }
+                    response
+                });
+            }
+            None => responses.push(std::future::ready(response)),
+        }
+        // Answer what is done while later responses are still coming
+        while let Some(response) = responses.ready() {
+            client.write_all(&response).await?;
+        }
+    }
+    while let Some(response) = responses.next().await {
+        client.write_all(&response).await?;
+    }
+    Ok(())
+}
+
 /// Handle a TLS-wrapped client connection by proxying to backend MariaDB
 /// Note: TLS support is experimental - the connection handling is the same as non-TLS
 async fn handle_connection_tls(
@@ -966,3 +1536,526 @@ async fn handle_connection_tls(
     tracing::warn!("For now, please disable SSL in your client or use a TLS proxy like stunnel");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::proxy::protocol::build_ok_packet;
+
+    /// Backend that accepts any login and answers every command with OK,
+    /// numbering the OKs (from 0 for the login) through their last insert id.
+    /// COM_STMT_PREPARE gets statement id 1 (without parameter definitions);
+    /// COM_STMT_SEND_LONG_DATA and COM_STMT_CLOSE get no answer.
+    async fn fake_backend() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut handshake = Vec::new();
+                    MySqlPacket::new(0, b"\x0a10.11.6-MariaDB\0".to_vec()).write(&mut handshake);
+                    stream.write_all(&handshake).await.unwrap();
+                    let mut sequence_id = 2;
+                    let mut answered = 0;
+                    loop {
+                        let mut header = [0u8; 4];
+                        if stream.read_exact(&mut header).await.is_err() {
+                            break;
+                        }
+                        let mut payload = vec![0u8; u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize];
+                        stream.read_exact(&mut payload).await.unwrap();
+                        let mut reply = Vec::new();
+                        match payload.first() {
+                            Some(&COM_STMT_SEND_LONG_DATA) | Some(&COM_STMT_CLOSE) => continue,
+                            Some(&COM_STMT_PREPARE) => {
+                                let params = payload.iter().filter(|&&b| b == b'?').count() as u16;
+                                let mut prepare_ok = vec![0x00, 1, 0, 0, 0, 0, 0];
+                                prepare_ok.extend_from_slice(&params.to_le_bytes());
+                                prepare_ok.extend_from_slice(&[0, 0, 0]);
+                                MySqlPacket::new(1, prepare_ok).write(&mut reply);
+                            }
+                            _ => {
+                                build_ok_packet(sequence_id, 0, answered).write(&mut reply);
+                                answered += 1;
+                            }
+                        }
+                        stream.write_all(&reply).await.unwrap();
+                        sequence_id = 1;
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    async fn start_proxy(cluster: Arc<ClusterMembership>, status: Arc<ProxyStatus>) -> String {
+        start_proxy_with_wal(cluster, status, None).await
+    }
+
+    /// Proxy config in front of a fresh `fake_backend`, with no connection limit
+    async fn test_config() -> ProxyConfig {
+        let (backend_host, backend_port) = fake_backend().await.rsplit_once(':')
+            .map(|(h, p)| (h.to_string(), p.parse().unwrap()))
+            .unwrap();
+        ProxyConfig {
+            listen_address: "127.0.0.1:0".to_string(),
+            backend_host,
+            backend_port,
+            backend_user: "root".to_string(),
+            backend_password: String::new(),
+            ssl_enabled: false,
+            ssl_cert: None,
+            ssl_key: None,
+            ssl_required: false,
+            max_stale_lsn: 1000,
+            stale_read_response: StaleReadResponse::Leader,
+            max_connections: 0,
+            max_queue_depth: 0,
+            pipeline_depth: 4,
+            cluster_token: None,
+        }
+    }
+
+    async fn start_proxy_with_wal(
+        cluster: Arc<ClusterMembership>,
+        status: Arc<ProxyStatus>,
+        wal_writer: Option<WalWriter>,
+    ) -> String {
+        let config = test_config().await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((client, _)) = listener.accept().await {
+                let (config, cluster, status) = (config.clone(), Arc::clone(&cluster), Arc::clone(&status));
+                tokio::spawn(handle_connection(client, config, cluster, wal_writer.clone(), status));
+            }
+        });
+        addr
+    }
+
+    async fn read_packet(stream: &mut TcpStream) -> Vec<u8> {
+        let mut header = [0u8; 4];
+        stream.read_exact(&mut header).await.unwrap();
+        let len = u32::from_le_bytes([header[0], header[1], header[2], 0]) as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        payload
+    }
+
+    /// Connect like a MySQL client, logging in as `user`
+    async fn connect(proxy: &str, user: &str) -> TcpStream {
+        connect_with(proxy, user, 0).await
+    }
+
+    /// Connect with `capabilities` on top of the ones `connect` sends
+    async fn connect_with(proxy: &str, user: &str, capabilities: u32) -> TcpStream {
+        let mut stream = TcpStream::connect(proxy).await.unwrap();
+        read_packet(&mut stream).await;
+
+        // CLIENT_PROTOCOL_41 | CLIENT_SECURE_CONNECTION
+        let mut response = (0x0200u32 | 0x8000 | capabilities).to_le_bytes().to_vec();
+        response.extend_from_slice(&(1u32 << 24).to_le_bytes());
+        response.push(33);
+        response.extend_from_slice(&[0u8; 23]);
+        response.extend_from_slice(user.as_bytes());
+        response.extend_from_slice(&[0, 0]);
+        let mut buf = Vec::new();
+        MySqlPacket::new(1, response).write(&mut buf);
+        stream.write_all(&buf).await.unwrap();
+        assert_eq!(read_packet(&mut stream).await[0], 0x00);
+        stream
+    }
+
+    /// Send a query and parse the text result set into (column names, rows)
+    async fn query(stream: &mut TcpStream, sql: &str) -> (Vec<String>, Vec<Vec<Option<String>>>) {
+        let mut payload = vec![0x03];
+        payload.extend_from_slice(sql.as_bytes());
+        let mut buf = Vec::new();
+        MySqlPacket::new(0, payload).write(&mut buf);
+        stream.write_all(&buf).await.unwrap();
+
+        fn lenenc_str(data: &[u8], pos: &mut usize) -> Option<String> {
+            let len = match data[*pos] {
+                0xfb => {
+                    *pos += 1;
+                    return None;
+                }
+                0xfc => {
+                    let len = u16::from_le_bytes([data[*pos + 1], data[*pos + 2]]) as usize;
+                    *pos += 2;
+                    len
+                }
+                len => len as usize,
+            };
+            *pos += 1;
+            let value = String::from_utf8(data[*pos..*pos + len].to_vec()).unwrap();
+            *pos += len;
+            Some(value)
+        }
+
+        let column_count = read_packet(stream).await[0] as usize;
+        let mut columns = Vec::new();
+        for _ in 0..column_count {
+            let def = read_packet(stream).await;
+            let mut pos = 0;
+            for _ in 0..4 {
+                lenenc_str(&def, &mut pos); // catalog, schema, table, org_table
+            }
+            columns.push(lenenc_str(&def, &mut pos).unwrap());
+        }
+        assert_eq!(read_packet(stream).await[0], 0xfe);
+
+        let mut rows = Vec::new();
+        loop {
+            let packet = read_packet(stream).await;
+            if packet[0] == 0xfe && packet.len() < 9 {
+                break;
+            }
+            let mut pos = 0;
+            rows.push((0..column_count).map(|_| lenenc_str(&packet, &mut pos)).collect());
+        }
+        (columns, rows)
+    }
+
+    fn column<'a>(result: &'a (Vec<String>, Vec<Vec<Option<String>>>), row: usize, name: &str) -> Option<&'a str> {
+        let index = result.0.iter().position(|c| c == name).unwrap();
+        result.1[row][index].as_deref()
+    }
+
+    async fn follower_cluster() -> Arc<ClusterMembership> {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-2".to_string(),
+            "10.0.0.2:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+        ));
+        cluster.add_peer("node-1".to_string(), "10.0.0.1:7654".to_string()).await.unwrap();
+        cluster.set_leader("node-1").await.unwrap();
+        cluster.record_heartbeat("node-1", 100).await.unwrap();
+        cluster.record_heartbeat("node-2", 70).await.unwrap();
+        cluster
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit_queues_then_rejects() {
+        let config = ProxyConfig { max_connections: 100, max_queue_depth: 50, ..test_config().await };
+        let server = ProxyServer::new(config, follower_cluster().await);
+        let pool = Arc::clone(&server.pool);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { server.accept_loop(listener).await });
+
+        let mut clients = Vec::new();
+        for _ in 0..160 {
+            clients.push(TcpStream::connect(&addr).await.unwrap());
+        }
+        for _ in 0..100 {
+            if pool.active() == 100 && pool.queued() == 50 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!((pool.active(), pool.queued()), (100, 50));
+
+        // Active clients get the backend's handshake, rejected ones error
+        // 1040, and queued ones nothing yet
+        let first_packets = futures::future::join_all(clients.into_iter().map(|mut client| async move {
+            let packet = tokio::time::timeout(Duration::from_millis(200), read_packet(&mut client)).await;
+            (client, packet)
+        }))
+        .await;
+        let (mut active, mut rejected, mut queued) = (Vec::new(), 0, Vec::new());
+        for (client, packet) in first_packets {
+            match packet {
+                Ok(packet) if packet[0] == 0x0a => active.push(client),
+                Ok(packet) => {
+                    assert_eq!((packet[0], u16::from_le_bytes([packet[1], packet[2]])), (0xff, 1040));
+                    rejected += 1;
+                }
+                Err(_) => queued.push(client),
+            }
+        }
+        assert_eq!((active.len(), queued.len(), rejected), (100, 50, 10));
+
+        // Closing an active connection lets the oldest queued one in
+        drop(active.pop());
+        let handshakes = futures::future::join_all(queued.iter_mut().map(|client| {
+            tokio::time::timeout(Duration::from_millis(200), read_packet(client))
+        }))
+        .await;
+        let admitted: Vec<_> = handshakes.into_iter().filter_map(|packet| packet.ok()).collect();
+        assert_eq!(admitted.len(), 1);
+        assert_eq!(admitted[0][0], 0x0a);
+        assert_eq!(pool.queued(), 49);
+    }
+
+    #[tokio::test]
+    async fn test_resize_connection_pool() {
+        let pool = ConnectionPool::new(10, 5);
+        let held = Arc::clone(&pool.permits).try_acquire_many_owned(8).unwrap();
+        assert_eq!(pool.active(), 8);
+
+        pool.set_max_connections(20);
+        assert_eq!((pool.active(), pool.permits.available_permits()), (8, 12));
+
+        // Lowering below the connections in use retires their slots as they close
+        pool.set_max_connections(4);
+        assert_eq!((pool.active(), pool.permits.available_permits()), (8, 0));
+        drop(held);
+        for _ in 0..100 {
+            if pool.retiring.load(Ordering::Relaxed) == 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!((pool.active(), pool.permits.available_permits()), (0, 4));
+
+        // Unlimited needs a restart
+        pool.set_max_connections(0);
+        assert_eq!(pool.max_connections.load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_refuses_new_clients() {
+        let cluster = follower_cluster().await;
+        let server = ProxyServer::new(test_config().await, Arc::clone(&cluster));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { server.accept_loop(listener).await });
+
+        cluster.set_maintenance(true);
+        let mut client = TcpStream::connect(&addr).await.unwrap();
+        let packet = read_packet(&mut client).await;
+        assert_eq!((packet[0], u16::from_le_bytes([packet[1], packet[2]])), (0xff, 1053));
+
+        cluster.set_maintenance(false);
+        let mut client = TcpStream::connect(&addr).await.unwrap();
+        assert_eq!(read_packet(&mut client).await[0], 0x0a);
+    }
+
+    #[tokio::test]
+    async fn test_show_slave_and_master_status() {
+        let cluster = follower_cluster().await;
+        let status = Arc::new(ProxyStatus::new());
+        let proxy = start_proxy(Arc::clone(&cluster), Arc::clone(&status)).await;
+        let mut client = connect(&proxy, "monitor").await;
+
+        // No commit rate sampled yet: one second per entry behind
+        let result = query(&mut client, "SHOW SLAVE STATUS").await;
+        assert_eq!(result.1.len(), 1);
+        assert_eq!(column(&result, 0, "Slave_IO_Running"), Some("Yes"));
+        assert_eq!(column(&result, 0, "Slave_SQL_Running"), Some("Yes"));
+        assert_eq!(column(&result, 0, "Master_Host"), Some("10.0.0.1"));
+        assert_eq!(column(&result, 0, "Master_Log_File"), Some("wolfscale-wal.000001"));
+        assert_eq!(column(&result, 0, "Read_Master_Log_Pos"), Some("100"));
+        assert_eq!(column(&result, 0, "Exec_Master_Log_Pos"), Some("70"));
+        assert_eq!(column(&result, 0, "Relay_Log_File"), Some("wolfscale-relay.000001"));
+        assert_eq!(column(&result, 0, "Seconds_Behind_Master"), Some("30"));
+
+        let result = query(&mut client, "SHOW MASTER STATUS;").await;
+        assert_eq!(result.0, ["File", "Position", "Binlog_Do_DB", "Binlog_Ignore_DB"]);
+        assert_eq!(column(&result, 0, "File"), Some("wolfscale-wal.000001"));
+        assert_eq!(column(&result, 0, "Position"), Some("70"));
+
+        // Without a leader the IO thread is reported stopped and lag is NULL
+        cluster.remove_peer("node-1").await.unwrap();
+        let result = query(&mut client, "show slave status").await;
+        assert_eq!(column(&result, 0, "Slave_IO_Running"), Some("No"));
+        assert_eq!(column(&result, 0, "Seconds_Behind_Master"), None);
+
+        // The leader is not a replica
+        cluster.set_leader("node-2").await.unwrap();
+        let result = query(&mut client, "SHOW SLAVE STATUS").await;
+        assert!(result.0.iter().any(|c| c == "Seconds_Behind_Master"));
+        assert!(result.1.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_show_processlist() {
+        let cluster = follower_cluster().await;
+        let status = Arc::new(ProxyStatus::new());
+        let proxy = start_proxy(cluster, Arc::clone(&status)).await;
+        let mut monitor = connect(&proxy, "monitor").await;
+        let app = connect(&proxy, "app").await;
+
+        let result = query(&mut monitor, "SHOW FULL PROCESSLIST").await;
+        assert_eq!(result.0, ["Id", "User", "Host", "db", "Command", "Time", "State", "Info"]);
+        assert_eq!(result.1.len(), 2);
+        assert_eq!(column(&result, 0, "User"), Some("monitor"));
+        assert_eq!(column(&result, 0, "Command"), Some("Query"));
+        assert_eq!(column(&result, 0, "Info"), Some("SHOW FULL PROCESSLIST"));
+        assert_eq!(column(&result, 1, "User"), Some("app"));
+        assert_eq!(column(&result, 1, "Command"), Some("Sleep"));
+        assert!(column(&result, 1, "Host").unwrap().starts_with("127.0.0.1:"));
+
+        // Closed connections leave the list
+        drop(app);
+        for _ in 0..100 {
+            if query(&mut monitor, "SHOW PROCESSLIST").await.1.len() == 1 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("closed connection still listed");
+    }
+
+    /// Leader cluster of one with a WAL in a temporary directory
+    async fn leader_with_wal() -> (Arc<ClusterMembership>, WalWriter, tempfile::TempDir) {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "10.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+        ));
+        cluster.set_leader("node-1").await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let wal_config = crate::config::WalConfig {
+            flush_interval_ms: 10,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
+            compression: false,
+            segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
+            retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
+            fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
+        };
+        let wal = WalWriter::new(dir.path().to_path_buf(), wal_config, "node-1".to_string()).await.unwrap();
+        (cluster, wal, dir)
+    }
+
+    #[tokio::test]
+    async fn test_prepared_write_logged_to_wal() {
+        let (cluster, wal, dir) = leader_with_wal().await;
+        let proxy = start_proxy_with_wal(cluster, Arc::new(ProxyStatus::new()), Some(wal.clone())).await;
+        let mut client = connect(&proxy, "app").await;
+
+        let send = |payload: Vec<u8>| {
+            let mut buf = Vec::new();
+            MySqlPacket::new(0, payload).write(&mut buf);
+            buf
+        };
+        let mut prepare = vec![COM_STMT_PREPARE];
+        prepare.extend_from_slice(b"INSERT INTO users (id, name) VALUES (?, ?)");
+        client.write_all(&send(prepare)).await.unwrap();
+        assert_eq!(read_packet(&mut client).await[..5], [0x00, 1, 0, 0, 0]);
+
+        // id = 42 (LONGLONG), name = "O'Brien" (VAR_STRING)
+        let mut execute = vec![COM_STMT_EXECUTE, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0x08, 0, 0xfd, 0];
+        execute.extend_from_slice(&42u64.to_le_bytes());
+        execute.extend_from_slice(b"\x07O'Brien");
+        client.write_all(&send(execute)).await.unwrap();
+        assert_eq!(read_packet(&mut client).await[0], 0x00);
+
+        // Close has no response; the next command must still be answered
+        client.write_all(&send(vec![COM_STMT_CLOSE, 1, 0, 0, 0])).await.unwrap();
+        client.write_all(&send(b"\x0ePING".to_vec())).await.unwrap();
+        assert_eq!(read_packet(&mut client).await[0], 0x00);
+
+        for _ in 0..100 {
+            if wal.current_lsn().await == 1 {
+                wal.flush().await.unwrap();
+                let entries = crate::wal::WalReader::new(dir.path().to_path_buf(), 1, false).unwrap().read_from(1).unwrap();
+                match &entries[0].entry {
+                    LogEntry::RawSql { sql, affects_table, .. } => {
+                        assert_eq!(sql, "INSERT INTO users (id, name) VALUES (42, 'O''Brien')");
+                        assert_eq!(affects_table.as_deref(), Some("users"));
+                    }
+                    other => panic!("unexpected entry {:?}", other),
+                }
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("prepared write never reached the WAL");
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_inserts_answered_in_order() {
+        let (cluster, wal, dir) = leader_with_wal().await;
+        let proxy = start_proxy_with_wal(cluster, Arc::new(ProxyStatus::new()), Some(wal.clone())).await;
+        let mut client = connect_with(&proxy, "app", CLIENT_MULTI_STATEMENTS).await;
+        let (batches, queries) = (metrics::PROXY_PIPELINE_DEPTH.get_sample_count(), metrics::PROXY_PIPELINE_DEPTH.get_sample_sum());
+
+        let mut buf = Vec::new();
+        for i in 1..=100 {
+            MySqlPacket::new(0, format!("\x03INSERT INTO t VALUES ({})", i).into_bytes()).write(&mut buf);
+        }
+        client.write_all(&buf).await.unwrap();
+        for i in 1..=100u8 {
+            // OK: affected rows 0, last insert id counts the backend's answers
+            assert_eq!(read_packet(&mut client).await[..3], [0x00, 0, i]);
+        }
+        // Some queries went to the backend together
+        let batches = metrics::PROXY_PIPELINE_DEPTH.get_sample_count() - batches;
+        assert!(metrics::PROXY_PIPELINE_DEPTH.get_sample_sum() - queries > batches as f64);
+
+        // Answered once logged, in statement order
+        assert_eq!(wal.current_lsn().await, 100);
+        wal.flush().await.unwrap();
+        let entries = crate::wal::WalReader::new(dir.path().to_path_buf(), 1, false).unwrap().read_from(1).unwrap();
+        for (i, entry) in entries.iter().enumerate() {
+            match &entry.entry {
+                LogEntry::RawSql { sql, .. } => assert_eq!(sql, &format!("INSERT INTO t VALUES ({})", i + 1)),
+                other => panic!("unexpected entry {:?}", other),
+            }
+        }
+        assert_eq!(entries.len(), 100);
+    }
+
+    #[test]
+    fn test_use_database() {
+        assert_eq!(use_database("USE b").as_deref(), Some("b"));
+        assert_eq!(use_database("  use `my db`; ").as_deref(), Some("my db"));
+        assert_eq!(use_database("USE`b`").as_deref(), Some("b"));
+        assert_eq!(use_database("USE "), None);
+        assert_eq!(use_database("USER_FUNC()"), None);
+        assert_eq!(use_database("INSERT INTO t VALUES (1)"), None);
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_use_switches_database() {
+        let (cluster, wal, dir) = leader_with_wal().await;
+        let proxy = start_proxy_with_wal(cluster, Arc::new(ProxyStatus::new()), Some(wal.clone())).await;
+        let mut client = connect_with(&proxy, "app", CLIENT_MULTI_STATEMENTS).await;
+
+        let queries = ["INSERT INTO t VALUES (1)", "USE b", "INSERT INTO t VALUES (2)", "USE `c`", "INSERT INTO t VALUES (3)"];
+        let mut buf = Vec::new();
+        for query in queries {
+            MySqlPacket::new(0, format!("\x03{}", query).into_bytes()).write(&mut buf);
+        }
+        client.write_all(&buf).await.unwrap();
+        for _ in queries {
+            assert_eq!(read_packet(&mut client).await[0], 0x00);
+        }
+
+        // Every write is logged against the database selected before it
+        wal.flush().await.unwrap();
+        let entries = crate::wal::WalReader::new(dir.path().to_path_buf(), 1, false).unwrap().read_from(1).unwrap();
+        let logged: Vec<_> = entries.iter().map(|e| (e.entry.database(), e.entry.table_name())).collect();
+        assert_eq!(logged, [(None, Some("t")), (Some("b"), Some("t")), (Some("c"), Some("t"))]);
+
+        // And a write sent on its own afterwards too
+        client.write_all(&{
+            let mut buf = Vec::new();
+            MySqlPacket::new(0, b"\x03INSERT INTO t VALUES (4)".to_vec()).write(&mut buf);
+            buf
+        }).await.unwrap();
+        assert_eq!(read_packet(&mut client).await[0], 0x00);
+        for _ in 0..100 {
+            if wal.current_lsn().await == 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        wal.flush().await.unwrap();
+        let entries = crate::wal::WalReader::new(dir.path().to_path_buf(), 1, false).unwrap().read_from(4).unwrap();
+        assert_eq!(entries[0].entry.database(), Some("c"));
+    }
+}
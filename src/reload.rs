@@ -0,0 +1,303 @@
+//! Configuration Reload
+//!
+//! `kill -HUP` sets `RELOAD_FLAG`, which the node checks every heartbeat
+//! interval before re-reading its config file. Only settings that are safe
+//! to change under a running node are applied:
+//!
+//! - `cluster.peers`: new peers are added, and a peer listed on the same
+//!   host with a different port has its address updated. Peers taken out of
+//!   the list stay members until restart, since dropping a live member can
+//!   cost the cluster its quorum.
+//! - `proxy.max_connections`
+//!
+//! `node.id` and `node.data_dir` (and so the WAL) are never reloaded; other
+//! changes are picked up on the next restart.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::config::WolfScaleConfig;
+use crate::error::Result;
+use crate::proxy::ConnectionPool;
+use crate::state::ClusterMembership;
+
+/// Set by SIGHUP, cleared when the reload is picked up
+pub static RELOAD_FLAG: AtomicBool = AtomicBool::new(false);
+
+/// Set `RELOAD_FLAG` on every SIGHUP (must be called within a Tokio runtime)
+pub fn install_sighup_handler() -> std::io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            RELOAD_FLAG.store(true, Ordering::SeqCst);
+        }
+    });
+    Ok(())
+}
+
+/// Take a pending reload request
+pub fn reload_requested() -> bool {
+    RELOAD_FLAG.swap(false, Ordering::SeqCst)
+}
+
+/// Synthetic ID for a peer known only by its configured address
+fn synthetic_id(address: &str) -> String {
+    format!("peer-{}", address.replace(':', "-"))
+}
+
+fn host(address: &str) -> &str {
+    address.rsplit_once(':').map_or(address, |(host, _)| host)
+}
+
+/// What a reload did to the peer list
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct PeerChanges {
+    /// Addresses added as new members
+    pub added: Vec<String>,
+    /// (old, new) addresses of members that moved to another port
+    pub updated: Vec<(String, String)>,
+    /// Addresses no longer configured, still members until restart
+    pub removed: Vec<String>,
+}
+
+/// Bring `cluster` in line with a new `cluster.peers` list
+pub async fn apply_peers(
+    cluster: &ClusterMembership,
+    own_address: &str,
+    old_peers: &[String],
+    new_peers: &[String],
+) -> Result<PeerChanges> {
+    let mut changes = PeerChanges::default();
+    let mut added: Vec<&String> = new_peers.iter()
+        .filter(|p| p.as_str() != own_address && !old_peers.contains(p))
+        .collect();
+    let removed: Vec<&String> = old_peers.iter()
+        .filter(|p| p.as_str() != own_address && !new_peers.contains(p))
+        .collect();
+
+    for old in removed {
+        // The same host on another port is the same peer, moved, but only
+        // when it is the one peer configured there before and after: on a
+        // host running several nodes a new port is as likely another node
+        let on_host = |peers: &[String]| peers.iter()
+            .filter(|p| p.as_str() != own_address && host(p) == host(old))
+            .count();
+        let moved = (on_host(old_peers) == 1 && on_host(new_peers) == 1)
+            .then(|| added.iter().position(|new| host(new) == host(old)))
+            .flatten();
+        let Some(new) = moved.map(|i| added.remove(i)) else {
+            changes.removed.push(old.clone());
+            continue;
+        };
+        let member = cluster.peers().await.into_iter().find(|n| &n.address == old);
+        match member {
+            Some(node) if !node.id.starts_with("peer-") => {
+                let address = new.clone();
+                cluster.update_node(&node.id, |n| n.address = address).await?;
+            }
+            Some(node) => {
+                cluster.remove_peer(&node.id).await?;
+                cluster.add_peer(synthetic_id(new), new.clone()).await?;
+            }
+            None => cluster.add_peer(synthetic_id(new), new.clone()).await?,
+        }
+        changes.updated.push((old.clone(), new.clone()));
+    }
+
+    let known = cluster.peers().await;
+    for new in added {
+        // Already found by auto-discovery or a peer's membership list
+        if known.iter().any(|n| &n.address == new) {
+            continue;
+        }
+        cluster.add_peer(synthetic_id(new), new.clone()).await?;
+        changes.added.push(new.clone());
+    }
+    Ok(changes)
+}
+
+/// Applies the reloadable parts of the config file on request
+pub struct ConfigReloader {
+    path: PathBuf,
+    /// The config as running: the startup config plus reloaded settings
+    config: WolfScaleConfig,
+    cluster: Arc<ClusterMembership>,
+    proxy_pool: Option<Arc<ConnectionPool>>,
+}
+
+impl ConfigReloader {
+    pub fn new(path: PathBuf, config: WolfScaleConfig, cluster: Arc<ClusterMembership>) -> Self {
+        Self { path, config, cluster, proxy_pool: None }
+    }
+
+    /// Resize the proxy's connection pool on reload
+    pub fn with_proxy_pool(mut self, pool: Arc<ConnectionPool>) -> Self {
+        self.proxy_pool = Some(pool);
+        self
+    }
+
+    /// Check for a reload request every `interval`, forever
+    pub async fn run(mut self, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if reload_requested() {
+                if let Err(e) = self.reload().await {
+                    tracing::error!("Config reload from {:?} failed, keeping the running config: {}", self.path, e);
+                }
+            }
+        }
+    }
+
+    /// Re-read the config file and apply what can be applied live
+    pub async fn reload(&mut self) -> Result<()> {
+        let new = WolfScaleConfig::from_file(&self.path)?;
+        tracing::info!("Reloading configuration from {:?}", self.path);
+
+        if new.node.id != self.config.node.id {
+            tracing::warn!("node.id changed to {} - ignored until restart", new.node.id);
+        }
+        if new.node.data_dir != self.config.node.data_dir {
+            tracing::warn!("node.data_dir changed to {:?} - ignored until restart", new.node.data_dir);
+        }
+
+        let changes = apply_peers(
+            &self.cluster,
+            self.config.advertise_address(),
+            &self.config.cluster.peers,
+            &new.cluster.peers,
+        ).await?;
+        for address in &changes.added {
+            tracing::info!("Added peer {}", address);
+        }
+        for (old, new) in &changes.updated {
+            tracing::info!("Peer {} moved to {}", old, new);
+        }
+        for address in &changes.removed {
+            tracing::warn!("Peer {} removed from config but still a cluster member - restart to drop it", address);
+        }
+        self.config.cluster.peers = new.cluster.peers;
+
+        if new.proxy.max_connections != self.config.proxy.max_connections {
+            if let Some(pool) = &self.proxy_pool {
+                tracing::info!("proxy.max_connections: {} -> {}", self.config.proxy.max_connections, new.proxy.max_connections);
+                pool.set_max_connections(new.proxy.max_connections);
+            }
+            self.config.proxy.max_connections = new.proxy.max_connections;
+        }
+
+        crate::metrics::CONFIG_RELOADS.inc();
+        tracing::info!("Configuration reloaded, cluster has {} nodes", self.cluster.size().await);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config_toml(peers: &[&str]) -> String {
+        format!(r#"
+[node]
+id = "node-1"
+bind_address = "10.0.0.1:7654"
+data_dir = "/var/lib/wolfscale"
+
+[database]
+host = "localhost"
+user = "wolfscale"
+password = "secret"
+
+[wal]
+
+[cluster]
+peers = {:?}
+"#, peers)
+    }
+
+    fn cluster() -> Arc<ClusterMembership> {
+        Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "10.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_apply_peers() {
+        let cluster = cluster();
+        cluster.add_peer("node-2".to_string(), "10.0.0.2:7654".to_string()).await.unwrap();
+        cluster.add_peer(synthetic_id("10.0.0.3:7654"), "10.0.0.3:7654".to_string()).await.unwrap();
+        let old = ["10.0.0.2:7654".to_string(), "10.0.0.3:7654".to_string()];
+        let new = [
+            "10.0.0.1:7654".to_string(),
+            "10.0.0.2:7655".to_string(),
+            "10.0.0.4:7654".to_string(),
+        ];
+
+        let changes = apply_peers(&cluster, "10.0.0.1:7654", &old, &new).await.unwrap();
+        assert_eq!(changes, PeerChanges {
+            added: vec!["10.0.0.4:7654".to_string()],
+            updated: vec![("10.0.0.2:7654".to_string(), "10.0.0.2:7655".to_string())],
+            removed: vec!["10.0.0.3:7654".to_string()],
+        });
+        assert_eq!(cluster.get_node("node-2").await.unwrap().address, "10.0.0.2:7655");
+        assert!(cluster.get_node(&synthetic_id("10.0.0.3:7654")).await.is_some());
+        assert!(cluster.get_node(&synthetic_id("10.0.0.4:7654")).await.is_some());
+        assert_eq!(cluster.size().await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_apply_peers_two_nodes_on_one_host() {
+        let cluster = cluster();
+        cluster.add_peer("node-2".to_string(), "10.0.0.2:7654".to_string()).await.unwrap();
+        cluster.add_peer("node-a".to_string(), "10.0.0.2:7655".to_string()).await.unwrap();
+        let old = ["10.0.0.2:7654".to_string(), "10.0.0.2:7655".to_string()];
+        let new = ["10.0.0.2:7654".to_string(), "10.0.0.2:7656".to_string()];
+
+        // B is a new node beside A, not A moved
+        let changes = apply_peers(&cluster, "10.0.0.1:7654", &old, &new).await.unwrap();
+        assert_eq!(changes, PeerChanges {
+            added: vec!["10.0.0.2:7656".to_string()],
+            updated: vec![],
+            removed: vec!["10.0.0.2:7655".to_string()],
+        });
+        assert_eq!(cluster.get_node("node-a").await.unwrap().address, "10.0.0.2:7655");
+        assert!(cluster.get_node(&synthetic_id("10.0.0.2:7656")).await.is_some());
+        assert_eq!(cluster.size().await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_sighup_adds_peer() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("wolfscale.toml");
+        std::fs::write(&path, config_toml(&["10.0.0.2:7654"])).unwrap();
+        let config = WolfScaleConfig::from_file(&path).unwrap();
+
+        let cluster = cluster();
+        cluster.add_peer(synthetic_id("10.0.0.2:7654"), "10.0.0.2:7654".to_string()).await.unwrap();
+        install_sighup_handler().unwrap();
+        tokio::spawn(ConfigReloader::new(path.clone(), config, Arc::clone(&cluster)).run(Duration::from_millis(10)));
+        let reloads = crate::metrics::CONFIG_RELOADS.get();
+
+        std::fs::write(&path, config_toml(&["10.0.0.2:7654", "10.0.0.3:7654"])).unwrap();
+        let status = std::process::Command::new("kill")
+            .args(["-HUP", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while cluster.size().await < 3 {
+            assert!(tokio::time::Instant::now() < deadline, "peer not added after SIGHUP");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(cluster.get_node(&synthetic_id("10.0.0.3:7654")).await.is_some());
+        assert!(crate::metrics::CONFIG_RELOADS.get() > reloads);
+    }
+}
@@ -0,0 +1,337 @@
+//! Follower Bootstrap over HTTP
+//!
+//! `GET /snapshot` streams the WAL up to the serving node's last LSN as a
+//! `multipart/mixed` body: a JSON part with the snapshot LSN and node ID,
+//! then one base64 part per WAL segment holding its entries (bincode).
+//! `wolfscale bootstrap` replays the entries into the local database and
+//! starts following from the snapshot LSN, without the cluster protocol.
+//!
+//! Unlike `POST /admin/add-node`, this needs the whole history in the WAL:
+//! a WAL whose oldest segments have expired can't serve a snapshot.
+//! Compacted segments are fine, as they keep the latest entry per row.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::executor::MariaDbExecutor;
+use crate::wal::entry::{Lsn, WalEntry};
+use crate::wal::WalReader;
+
+/// Boundary between the parts of a snapshot
+pub const SNAPSHOT_BOUNDARY: &str = "wolfscale-snapshot-7f3a9c";
+
+/// First part of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    /// Entries up to and including this LSN are in the snapshot
+    pub lsn: Lsn,
+    /// Node that served the snapshot
+    pub node_id: String,
+}
+
+/// `Content-Type` of a snapshot response
+pub fn snapshot_content_type() -> String {
+    format!("multipart/mixed; boundary={}", SNAPSHOT_BOUNDARY)
+}
+
+fn header_part(header: &SnapshotHeader) -> Result<Bytes> {
+    let json = serde_json::to_string(header).map_err(|e| Error::Internal(e.to_string()))?;
+    Ok(Bytes::from(format!(
+        "--{}\r\nContent-Type: application/json\r\n\r\n{}\r\n",
+        SNAPSHOT_BOUNDARY, json
+    )))
+}
+
+fn entries_part(entries: &[WalEntry]) -> Result<Bytes> {
+    let encoded = BASE64.encode(bincode::serialize(entries)?);
+    Ok(Bytes::from(format!(
+        "--{}\r\nContent-Type: application/octet-stream\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+        SNAPSHOT_BOUNDARY, encoded
+    )))
+}
+
+fn closing_part() -> Bytes {
+    Bytes::from(format!("--{}--\r\n", SNAPSHOT_BOUNDARY))
+}
+
+/// The snapshot of the WAL read by `reader`: its header and the body
+/// parts, each segment read only when the stream gets to it
+pub fn snapshot_stream(
+    reader: WalReader,
+    node_id: String,
+) -> Result<(SnapshotHeader, impl Stream<Item = Result<Bytes>> + Send)> {
+    let header = SnapshotHeader { lsn: reader.last_lsn()?.unwrap_or(0), node_id };
+    let segments: Vec<Lsn> = reader.segments()?.iter()
+        .map(|s| s.first_lsn)
+        .filter(|first| *first <= header.lsn)
+        .collect();
+    if let Some(first) = segments.first().filter(|first| **first > 1) {
+        return Err(Error::Replication(format!(
+            "WAL starts at LSN {} (older segments have expired); add the node with POST /admin/add-node instead",
+            first
+        )));
+    }
+
+    let lsn = header.lsn;
+    let reader = Arc::new(reader);
+    let parts = futures::stream::iter(segments).then(move |first_lsn| {
+        let reader = Arc::clone(&reader);
+        async move {
+            tokio::task::spawn_blocking(move || entries_part(&reader.read_segment(first_lsn, lsn)?))
+                .await
+                .map_err(|e| Error::Internal(e.to_string()))?
+        }
+    });
+    let body = futures::stream::once(futures::future::ready(header_part(&header)))
+        .chain(parts)
+        .chain(futures::stream::once(futures::future::ready(Ok(closing_part()))));
+    Ok((header, body))
+}
+
+/// One part of a snapshot
+#[derive(Debug)]
+pub enum SnapshotPart {
+    Header(SnapshotHeader),
+    Entries(Vec<WalEntry>),
+}
+
+/// Splits a snapshot body into parts as it arrives
+#[derive(Debug, Default)]
+pub struct SnapshotDecoder {
+    buffer: Vec<u8>,
+    /// Bytes of `buffer` already searched for the next delimiter
+    scanned: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl SnapshotDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add received bytes, returning the parts they complete
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<SnapshotPart>> {
+        self.buffer.extend_from_slice(data);
+        let mut parts = Vec::new();
+
+        if !self.started {
+            let opening = format!("--{}\r\n", SNAPSHOT_BOUNDARY);
+            let len = self.buffer.len().min(opening.len());
+            if self.buffer[..len] != opening.as_bytes()[..len] {
+                return Err(Error::Replication("Snapshot does not start with a boundary".into()));
+            }
+            if len < opening.len() {
+                return Ok(parts);
+            }
+            self.buffer.drain(..opening.len());
+            self.started = true;
+        }
+
+        let delimiter = format!("\r\n--{}", SNAPSHOT_BOUNDARY);
+        while !self.finished {
+            let from = self.scanned.saturating_sub(delimiter.len());
+            let Some(end) = find(&self.buffer[from..], delimiter.as_bytes()).map(|i| from + i) else {
+                self.scanned = self.buffer.len();
+                break;
+            };
+            // The two bytes after the boundary tell another part from the end
+            let after = end + delimiter.len();
+            if self.buffer.len() < after + 2 {
+                self.scanned = end;
+                break;
+            }
+            match &self.buffer[after..after + 2] {
+                b"\r\n" => {}
+                b"--" => self.finished = true,
+                _ => return Err(Error::Replication("Malformed snapshot boundary".into())),
+            }
+            parts.push(parse_part(&self.buffer[..end])?);
+            self.buffer.drain(..after + 2);
+            self.scanned = 0;
+        }
+        Ok(parts)
+    }
+
+    /// Whether the closing boundary has been seen
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn parse_part(part: &[u8]) -> Result<SnapshotPart> {
+    let split = find(part, b"\r\n\r\n")
+        .ok_or_else(|| Error::Replication("Snapshot part has no headers".into()))?;
+    let headers = String::from_utf8_lossy(&part[..split]).to_ascii_lowercase();
+    let body = &part[split + 4..];
+
+    if headers.contains("content-type: application/json") {
+        let header = serde_json::from_slice(body)
+            .map_err(|e| Error::Replication(format!("Invalid snapshot header: {}", e)))?;
+        Ok(SnapshotPart::Header(header))
+    } else if headers.contains("content-transfer-encoding: base64") {
+        let bytes = BASE64.decode(body)
+            .map_err(|e| Error::Replication(format!("Invalid snapshot segment: {}", e)))?;
+        Ok(SnapshotPart::Entries(bincode::deserialize(&bytes)?))
+    } else {
+        Err(Error::Replication(format!("Unexpected snapshot part: {}", headers.trim())))
+    }
+}
+
+/// A snapshot being downloaded from `GET /snapshot`
+pub struct SnapshotDownload {
+    response: reqwest::Response,
+    decoder: SnapshotDecoder,
+    pending: VecDeque<SnapshotPart>,
+    header: SnapshotHeader,
+}
+
+impl SnapshotDownload {
+    /// Request a snapshot from the node at `base_url` (e.g.
+    /// `http://leader:8080`) and read its header. `authorization` is sent
+    /// as the `Authorization` header.
+    pub async fn start(base_url: &str, authorization: Option<&str>) -> Result<Self> {
+        let url = format!("{}/snapshot", base_url.trim_end_matches('/'));
+        let mut request = reqwest::Client::new().get(&url);
+        if let Some(authorization) = authorization {
+            request = request.header(reqwest::header::AUTHORIZATION, authorization);
+        }
+        let response = request.send().await
+            .map_err(|e| Error::Network(format!("GET {}: {}", url, e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Replication(format!("GET {} returned {}: {}", url, status, body.trim())));
+        }
+
+        let mut download = Self {
+            response,
+            decoder: SnapshotDecoder::new(),
+            pending: VecDeque::new(),
+            header: SnapshotHeader { lsn: 0, node_id: String::new() },
+        };
+        match download.next_part().await? {
+            Some(SnapshotPart::Header(header)) => download.header = header,
+            _ => return Err(Error::Replication("Snapshot does not start with its header".into())),
+        }
+        Ok(download)
+    }
+
+    pub fn header(&self) -> &SnapshotHeader {
+        &self.header
+    }
+
+    async fn next_part(&mut self) -> Result<Option<SnapshotPart>> {
+        while self.pending.is_empty() {
+            let chunk = self.response.chunk().await
+                .map_err(|e| Error::Network(format!("Snapshot download failed: {}", e)))?;
+            match chunk {
+                Some(chunk) => self.pending.extend(self.decoder.push(&chunk)?),
+                None if self.decoder.is_finished() => return Ok(None),
+                None => return Err(Error::Replication("Snapshot ended before its closing boundary".into())),
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    /// The entries of the next WAL segment, `None` once all have been read
+    pub async fn next_entries(&mut self) -> Result<Option<Vec<WalEntry>>> {
+        match self.next_part().await? {
+            Some(SnapshotPart::Entries(entries)) => Ok(Some(entries)),
+            Some(SnapshotPart::Header(_)) => Err(Error::Replication("Snapshot has a second header".into())),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Download a snapshot from `base_url` and replay it into the local
+/// database. Returns the snapshot LSN to follow the leader from.
+pub async fn bootstrap_from_http(
+    base_url: &str,
+    authorization: Option<&str>,
+    executor: &MariaDbExecutor,
+) -> Result<Lsn> {
+    let mut download = SnapshotDownload::start(base_url, authorization).await?;
+    let snapshot_lsn = download.header().lsn;
+    tracing::info!("Bootstrapping from {} at LSN {}", download.header().node_id, snapshot_lsn);
+
+    let mut last_lsn = 0;
+    let mut applied = 0u64;
+    while let Some(entries) = download.next_entries().await? {
+        for entry in entries {
+            let lsn = entry.header.lsn;
+            if lsn <= last_lsn || lsn > snapshot_lsn {
+                return Err(Error::Replication(format!(
+                    "Snapshot entry at LSN {} out of order (after LSN {}, snapshot at {})",
+                    lsn, last_lsn, snapshot_lsn
+                )));
+            }
+            if !entry.verify_checksum() {
+                return Err(Error::WalCorrupted { lsn, reason: "checksum mismatch".into() });
+            }
+            if !entry.entry.is_noop() {
+                executor.execute_entry(&entry.entry).await?;
+                applied += 1;
+            }
+            last_lsn = lsn;
+        }
+        tracing::info!("Bootstrap replayed up to LSN {} of {}", last_lsn, snapshot_lsn);
+    }
+    tracing::info!("Bootstrap complete: {} entries applied, following from LSN {}", applied, snapshot_lsn);
+    Ok(snapshot_lsn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::{LogEntry, PrimaryKey, Value};
+
+    fn entry(lsn: Lsn) -> WalEntry {
+        WalEntry::new(lsn, 1, "leader".to_string(), LogEntry::Insert {
+            table: "users".to_string(),
+            columns: vec!["id".to_string()],
+            values: vec![Value::Int(lsn as i64)],
+            primary_key: PrimaryKey::Int(lsn as i64),
+        })
+    }
+
+    #[test]
+    fn test_decoder_handles_split_input() {
+        let header = SnapshotHeader { lsn: 3, node_id: "leader".to_string() };
+        let mut body = Vec::new();
+        body.extend_from_slice(&header_part(&header).unwrap());
+        body.extend_from_slice(&entries_part(&[entry(1), entry(2)]).unwrap());
+        body.extend_from_slice(&entries_part(&[entry(3)]).unwrap());
+        body.extend_from_slice(&closing_part());
+
+        // Byte by byte, so every boundary is split somewhere
+        let mut decoder = SnapshotDecoder::new();
+        let mut parts = Vec::new();
+        for byte in &body {
+            parts.extend(decoder.push(std::slice::from_ref(byte)).unwrap());
+        }
+        assert!(decoder.is_finished());
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(&parts[0], SnapshotPart::Header(h) if *h == header));
+        let lsns: Vec<Lsn> = parts[1..].iter()
+            .flat_map(|part| match part {
+                SnapshotPart::Entries(entries) => entries.iter().map(|e| e.header.lsn).collect(),
+                SnapshotPart::Header(_) => Vec::new(),
+            })
+            .collect();
+        assert_eq!(lsns, vec![1, 2, 3]);
+
+        let mut decoder = SnapshotDecoder::new();
+        assert!(decoder.push(b"--some-other-boundary\r\n").is_err());
+    }
+}
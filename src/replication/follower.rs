@@ -3,16 +3,23 @@
 //! Handles follower responsibilities: receiving replicated entries,
 //! applying them to the local database, and requesting sync on gaps.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, Mutex, RwLock};
 
 use super::protocol::Message;
+use super::validation::validate_entry;
 use super::ReplicationConfig;
-use crate::wal::entry::{Lsn, WalEntry};
+use crate::wal::entry::{Lsn, LogEntry, WalEntry};
 use crate::state::{ClusterMembership, StateTracker, ElectionCoordinator, ElectionConfig, ElectionState};
-use crate::executor::MariaDbExecutor;
+use crate::executor::{MariaDbExecutor, SchemaVersionTracker};
 use crate::error::{Error, Result};
+use crate::metrics;
+use crate::telemetry::Span;
+
+/// `AppendEntriesRejected` reason for entries whose SQL failed validation
+const VALIDATION_FAILED: &str = "validation_failed";
 
 /// Batch of entries to replicate with metadata for ACK
 #[derive(Clone)]
@@ -33,6 +40,8 @@ pub struct FollowerNode {
     cluster: Arc<ClusterMembership>,
     /// Database executor
     executor: Arc<MariaDbExecutor>,
+    /// Schema version applied to the local database
+    schema: Arc<Mutex<SchemaVersionTracker>>,
     /// Replication configuration  
     config: ReplicationConfig,
     /// Current term
@@ -78,11 +87,17 @@ impl FollowerNode {
             message_tx.clone(),
         ));
 
+        let schema = SchemaVersionTracker::load(state_tracker.data_dir()).unwrap_or_else(|e| {
+            tracing::warn!("Could not load schema version, starting from 0: {}", e);
+            SchemaVersionTracker::new(state_tracker.data_dir())
+        });
+
         Self {
             node_id,
             state_tracker,
             cluster,
             executor,
+            schema: Arc::new(Mutex::new(schema)),
             config,
             term: RwLock::new(1),
             leader_id: RwLock::new(None),
@@ -184,6 +199,8 @@ impl FollowerNode {
                     self.node_id.clone(),
                     Arc::clone(&self.last_applied_lsn),
                     Arc::clone(&self.state_tracker),
+                    Arc::clone(&self.schema),
+                    self.config.parallel_apply_workers,
                 ).await;
                 
                 // Immediately check for more entries (no sleep)
@@ -229,6 +246,7 @@ impl FollowerNode {
         if term > *current_term {
             *current_term = term;
             self.state_tracker.set_current_term(term).await?;
+            self.cluster.set_term(term);
         }
         drop(current_term);
 
@@ -308,29 +326,22 @@ impl FollowerNode {
             });
         }
 
-        // Apply entries
+        // Apply entries, partitioned by table so independent tables run in parallel.
+        // Failed entries are logged and still count as processed, so a single bad
+        // query doesn't stop replication or make the leader resend it forever.
         let mut match_lsn = last_applied;
-        for entry in entries {
-            if entry.header.lsn <= last_applied {
-                continue;
-            }
-
-            // Apply to local log and database
-            match self.apply_entry(&entry).await {
-                Ok(_) => {
-                    match_lsn = entry.header.lsn;
-                }
-                Err(e) => {
-                    // Log error but continue to next entry if it's an application error
-                    // This prevents a single bad query from stopping all replication
-                    tracing::error!("Follower failed to apply entry LSN {}: {}", entry.header.lsn, e);
-                    
-                    // We still advance match_lsn because we've "processed" this entry (even if with error)
-                    // and we don't want the leader to keep sending it.
-                    match_lsn = entry.header.lsn;
-                }
-            }
+        let mut pending: Vec<WalEntry> = entries.into_iter()
+            .filter(|e| e.header.lsn > last_applied)
+            .collect();
+        // Entries before one that fails validation are still applied
+        let refused = refuse_invalid(&mut pending);
+        let (pending, schema_changes) = begin_schema_catch_up(&self.executor, &self.schema, pending).await;
+        for step in plan_apply(pending, self.config.parallel_apply_workers) {
+            let (step_lsn, _) = apply_step(&self.executor, step).await;
+            match_lsn = match_lsn.max(step_lsn);
         }
+        match_lsn = match_lsn.max(schema_changes.caught_up_lsn);
+        finish_schema_catch_up(&self.schema, schema_changes).await;
 
         // Update last applied if we moved forward
         if match_lsn > last_applied {
@@ -338,6 +349,19 @@ impl FollowerNode {
             let _ = self.state_tracker.set_last_applied_lsn(match_lsn).await;
         }
 
+        if let Some(refused_lsn) = refused {
+            // Ask for the refused entry again rather than skipping it
+            if let Err(e) = self.request_sync(refused_lsn).await {
+                tracing::warn!("Failed to request resync from LSN {}: {}", refused_lsn, e);
+            }
+            return Ok(Message::AppendEntriesRejected {
+                node_id: self.node_id.clone(),
+                term: *self.term.read().await,
+                match_lsn,
+                reason: VALIDATION_FAILED.to_string(),
+            });
+        }
+
         Ok(Message::AppendEntriesResponse {
             node_id: self.node_id.clone(),
             term: *self.term.read().await,
@@ -356,29 +380,37 @@ impl FollowerNode {
         let last_applied = *self.last_applied_lsn.read().await;
 
         let mut new_applied = last_applied;
-        for entry in entries {
-            if entry.header.lsn <= last_applied {
-                continue;
-            }
-
+        let mut pending: Vec<WalEntry> = entries.into_iter()
+            .filter(|e| e.header.lsn > last_applied)
+            .collect();
+        let refused = refuse_invalid(&mut pending);
+        let (pending, schema_changes) = begin_schema_catch_up(&self.executor, &self.schema, pending).await;
+        let mut complete = true;
+        for entry in pending {
             match self.apply_entry(&entry).await {
                 Ok(_) => {
                     new_applied = entry.header.lsn;
                 }
                 Err(e) => {
                     tracing::error!("Failed to apply synced entry {}: {}", entry.header.lsn, e);
+                    complete = false;
                     break;
                 }
             }
         }
+        if complete {
+            new_applied = new_applied.max(schema_changes.caught_up_lsn);
+            finish_schema_catch_up(&self.schema, schema_changes).await;
+        }
 
         if new_applied > last_applied {
             *self.last_applied_lsn.write().await = new_applied;
             self.state_tracker.set_last_applied_lsn(new_applied).await?;
         }
 
-        // Request more if needed
-        if has_more {
+        // Request more if needed. After a refused entry the next
+        // AppendEntries finds the gap and asks again.
+        if has_more && refused.is_none() {
             self.request_sync(new_applied + 1).await?;
         }
 
@@ -391,9 +423,12 @@ impl FollowerNode {
         // The entries already have LSNs assigned by the leader.
         // We just execute the SQL - state tracking happens in the caller.
         // This keeps apply_entry as fast as possible.
+        record_id_allocation(&entry.entry);
 
         // Execute against database
-        if let Err(e) = self.executor.execute_entry(&entry.entry).await {
+        let mut span = Span::child("db_execute", entry.header.trace_id, &[("lsn", entry.header.lsn.to_string())]);
+        if let Err(e) = self.executor.execute_entry_at(entry.header.lsn, &entry.entry).await {
+            span.set_error(e.to_string());
             // Check if this is a non-fatal SQL error (e.g. key constraint, etc.)
             // For now, we log it and return it, but the caller (handle_append_entries)
             // will decide whether to continue.
@@ -527,6 +562,12 @@ impl FollowerNode {
         *self.term.read().await
     }
 
+    /// Continue from `lsn` after a snapshot taken at that LSN was loaded
+    pub async fn restore_applied_lsn(&self, lsn: Lsn) -> Result<()> {
+        *self.last_applied_lsn.write().await = lsn;
+        self.state_tracker.set_last_applied_lsn(lsn).await
+    }
+
     /// Get the last applied LSN
     pub async fn last_applied_lsn(&self) -> Lsn {
         *self.last_applied_lsn.read().await
@@ -547,6 +588,7 @@ impl FollowerNode {
 
 /// Standalone batch processing function for background execution.
 /// This runs inline to avoid blocking the follower's main loop.
+#[allow(clippy::too_many_arguments)]
 async fn process_batch_background(
     batch: ReplicationBatch,
     executor: Arc<MariaDbExecutor>,
@@ -555,6 +597,8 @@ async fn process_batch_background(
     node_id: String,
     last_applied_lsn: Arc<RwLock<Lsn>>,
     state_tracker: Arc<StateTracker>,
+    schema: Arc<Mutex<SchemaVersionTracker>>,
+    parallel_workers: usize,
 ) {
     let current_lsn = *last_applied_lsn.read().await;
     let batch_max_lsn = batch.entries.last().map(|e| e.header.lsn).unwrap_or(0);
@@ -600,53 +644,39 @@ async fn process_batch_background(
     
     let mut highest_applied = current_lsn;
     let mut processed = 0usize;
-    let mut skipped = 0usize;
-    
-    for entry in &batch.entries {
-        // Skip already-applied entries
-        if entry.header.lsn <= highest_applied {
-            skipped += 1;
-            continue;
-        }
-        
-        // Execute the SQL - PERFORMANCE: Skip preview generation unless needed
-        let sql_stmts = entry.entry.to_sql();
-        
-        // Use 30 minute timeout for writes - large WordPress inserts can take a long time
-        let execute_result = tokio::time::timeout(
-            std::time::Duration::from_secs(1800), // 30 minutes for large data
-            executor.execute_entry(&entry.entry)
-        ).await;
-        
-        match execute_result {
-            Ok(Ok(())) => {}
-            Ok(Err(e)) => {
-                // Log FULL query for failed entries (truncated to 500 chars for sanity)
-                let sql_preview = if sql_stmts.is_empty() { "noop".to_string() } 
-                    else { sql_stmts[0].chars().take(500).collect::<String>() };
-                tracing::error!("QUERY FAILED - LSN {}: {} - SQL: {}", entry.header.lsn, e, sql_preview);
-            }
-            Err(_) => {
-                let sql_preview = if sql_stmts.is_empty() { "noop".to_string() } 
-                    else { sql_stmts[0].chars().take(500).collect::<String>() };
-                tracing::error!("QUERY TIMEOUT - LSN {} after 30min: {}", entry.header.lsn, sql_preview);
-            }
-        }
-        
-        // Mark as processed regardless of success/failure
-        highest_applied = entry.header.lsn;
-        processed += 1;
-        
+
+    // Skip already-applied entries
+    let mut pending: Vec<WalEntry> = batch.entries.iter()
+        .filter(|e| e.header.lsn > current_lsn)
+        .cloned()
+        .collect();
+    let skipped = batch.entries.len() - pending.len();
+    let refused = refuse_invalid(&mut pending);
+
+    let (pending, schema_changes) = begin_schema_catch_up(&executor, &schema, pending).await;
+    processed += schema_changes.count;
+
+    for step in plan_apply(pending, parallel_workers) {
+        // Every entry in a step is finished (successfully or not) once apply_step returns
+        let (step_lsn, step_count) = apply_step(&executor, step).await;
+        let before = processed;
+        highest_applied = highest_applied.max(step_lsn);
+        processed += step_count;
+
         // Update atomic counter so periodic ACK sender knows our progress
         progress_lsn.store(highest_applied, std::sync::atomic::Ordering::Relaxed);
-        
+
         // Batch state updates: save every 1000 entries for performance (was 100)
-        if processed % 1000 == 0 {
+        if processed / 1000 > before / 1000 {
             *last_applied_lsn.write().await = highest_applied;
             tracing::info!("Progress: {} entries processed, at LSN {}", processed, highest_applied);
         }
     }
     
+    // Schema changes run ahead of the DML only count once the DML before them is done
+    highest_applied = highest_applied.max(schema_changes.caught_up_lsn);
+    finish_schema_catch_up(&schema, schema_changes).await;
+
     // Signal periodic ACK sender to stop
     ack_done.store(true, std::sync::atomic::Ordering::Relaxed);
     
@@ -663,8 +693,279 @@ async fn process_batch_background(
     // Update cluster membership
     let _ = cluster.record_heartbeat(&node_id, highest_applied).await;
     
-    // Send ACK to leader
-    send_ack_background(&message_tx, &batch, highest_applied, &node_id).await;
+    // Send ACK to leader. A refusal leaves the batch unacknowledged, so the
+    // leader resends from the refused entry.
+    match refused {
+        None => send_ack_background(&message_tx, &batch, highest_applied, &node_id).await,
+        Some(_) => {
+            let refusal = Message::AppendEntriesRejected {
+                node_id: node_id.clone(),
+                term: batch.term,
+                match_lsn: highest_applied,
+                reason: VALIDATION_FAILED.to_string(),
+            };
+            if let Err(e) = message_tx.send((batch.leader_address.clone(), refusal)).await {
+                tracing::error!("Failed to send validation failure: {}", e);
+            }
+        }
+    }
+}
+
+/// Cut `entries` off at the first one whose SQL fails validation, returning
+/// its LSN
+fn refuse_invalid(entries: &mut Vec<WalEntry>) -> Option<Lsn> {
+    let (index, error) = entries.iter().enumerate()
+        .find_map(|(i, entry)| validate_entry(&entry.entry).err().map(|e| (i, e)))?;
+    let lsn = entries[index].header.lsn;
+    tracing::error!("Refusing replicated entry {}: {}", lsn, error);
+    metrics::VALIDATION_FAILURES.with_label_values(&[error.reason()]).inc();
+    entries.truncate(index);
+    Some(lsn)
+}
+
+/// Split a batch for schema catch-up. When it expects a newer schema than the
+/// one applied here, its schema changes past the applied version are pulled
+/// ahead of everything else, in WAL order, so DML written after them finds the
+/// columns and tables it needs. Schema changes that already ran (e.g. before a
+/// crash) are dropped. Returns (schema changes to run first, everything else).
+fn schema_catch_up(entries: Vec<WalEntry>, tracker: &SchemaVersionTracker) -> (Vec<WalEntry>, Vec<WalEntry>) {
+    let entries: Vec<WalEntry> = entries.into_iter()
+        .filter(|e| !tracker.has_applied(e))
+        .collect();
+    if !entries.iter().any(|e| tracker.is_behind(e)) {
+        return (Vec::new(), entries);
+    }
+    entries.into_iter().partition(|e| e.entry.changes_schema() && tracker.is_behind(e))
+}
+
+/// Schema bookkeeping for a batch whose catch-up has run
+struct SchemaChanges {
+    /// Highest LSN handled by the catch-up (run first or already applied)
+    caught_up_lsn: Lsn,
+    /// Entries handled by the catch-up
+    count: usize,
+    /// Schema changes left in the batch, recorded once they have run
+    remaining: Vec<WalEntry>,
+    /// Schema version of the batch
+    version: u64,
+}
+
+/// Run a batch's schema catch-up (see `schema_catch_up`) and return the entries
+/// still to apply. Each schema change is recorded as soon as it has run.
+async fn begin_schema_catch_up(
+    executor: &MariaDbExecutor,
+    schema: &Mutex<SchemaVersionTracker>,
+    entries: Vec<WalEntry>,
+) -> (Vec<WalEntry>, SchemaChanges) {
+    let total = entries.len();
+    let batch_lsn = entries.iter().map(|e| e.header.lsn).max().unwrap_or(0);
+    let version = entries.iter().map(|e| e.header.schema_version).max().unwrap_or(0);
+
+    let mut tracker = schema.lock().await;
+    let (ddl, rest) = schema_catch_up(entries, &tracker);
+    if !ddl.is_empty() {
+        tracing::info!(
+            "Schema version {} is behind {}; applying {} schema changes before the rest of the batch",
+            tracker.version(), version, ddl.len()
+        );
+    }
+    for entry in &ddl {
+        execute_logged(executor, entry).await;
+        tracker.observe(entry);
+        if let Err(e) = tracker.save() {
+            tracing::warn!("Failed to persist schema version: {}", e);
+        }
+    }
+
+    let changes = SchemaChanges {
+        caught_up_lsn: if rest.len() < total { batch_lsn } else { 0 },
+        count: total - rest.len(),
+        remaining: rest.iter().filter(|e| e.entry.changes_schema()).cloned().collect(),
+        version,
+    };
+    (rest, changes)
+}
+
+/// Record the schema version reached once the rest of a batch has been applied
+async fn finish_schema_catch_up(schema: &Mutex<SchemaVersionTracker>, changes: SchemaChanges) {
+    let mut tracker = schema.lock().await;
+    if changes.remaining.is_empty() && changes.version <= tracker.version() {
+        return;
+    }
+    for entry in &changes.remaining {
+        tracker.observe(entry);
+    }
+    tracker.advance(changes.version);
+    if let Err(e) = tracker.save() {
+        tracing::warn!("Failed to persist schema version: {}", e);
+    }
+}
+
+/// One step of a follower apply plan
+#[derive(Debug)]
+enum ApplyStep {
+    /// Entry that must run on its own, after everything before it and before anything after it
+    Serial(WalEntry),
+    /// Independent lanes run concurrently. Each lane keeps WAL order and no two
+    /// lanes touch the same table.
+    Parallel(Vec<Vec<WalEntry>>),
+}
+
+/// Key used to partition an entry by table, or `None` if the entry must act as a
+/// barrier (DDL, multi-table transactions, raw SQL that isn't a single DML statement).
+fn partition_key(entry: &LogEntry) -> Option<String> {
+    fn normalize(table: &str) -> String {
+        // Ignore schema qualifiers so `db.t` and `t` conflict rather than run concurrently
+        let name = table.rsplit('.').next().unwrap_or(table);
+        name.trim_matches('`').to_ascii_lowercase()
+    }
+
+    match entry {
+        LogEntry::Insert { table, .. }
+        | LogEntry::Update { table, .. }
+        | LogEntry::Delete { table, .. }
+        | LogEntry::Upsert { table, .. }
+        | LogEntry::BulkInsert { table, .. } => Some(normalize(table)),
+        LogEntry::Transaction { entries, .. } => {
+            let mut keys = entries.iter().map(partition_key);
+            let first = keys.next()??;
+            keys.all(|k| k.as_deref() == Some(first.as_str())).then_some(first)
+        }
+        LogEntry::RawSql { sql, affects_table, .. } => {
+            let table = affects_table.as_deref()?;
+            let stmt = sql.trim().trim_end_matches(';');
+            if stmt.contains(';') {
+                return None;
+            }
+            let verb = stmt.split_whitespace().next()?.to_ascii_uppercase();
+            matches!(verb.as_str(), "INSERT" | "UPDATE" | "DELETE" | "REPLACE")
+                .then(|| normalize(table))
+        }
+        _ => None,
+    }
+}
+
+/// Split a batch into apply steps. Consecutive partitionable entries are grouped
+/// into up to `workers` lanes by table; barrier entries flush the current group
+/// and run alone. With `workers <= 1` every entry is its own serial step.
+fn plan_apply(entries: Vec<WalEntry>, workers: usize) -> Vec<ApplyStep> {
+    fn flush(steps: &mut Vec<ApplyStep>, lanes: &mut Vec<Vec<WalEntry>>, lane_of: &mut HashMap<String, usize>) {
+        if !lanes.is_empty() {
+            steps.push(ApplyStep::Parallel(std::mem::take(lanes)));
+        }
+        lane_of.clear();
+    }
+
+    let mut steps = Vec::new();
+    let mut lanes: Vec<Vec<WalEntry>> = Vec::new();
+    let mut lane_of: HashMap<String, usize> = HashMap::new();
+
+    for entry in entries {
+        let key = if workers > 1 { partition_key(&entry.entry) } else { None };
+        match key {
+            Some(key) => {
+                let next = lane_of.len() % workers;
+                let lane = *lane_of.entry(key).or_insert(next);
+                if lanes.len() <= lane {
+                    lanes.resize_with(lane + 1, Vec::new);
+                }
+                lanes[lane].push(entry);
+            }
+            None => {
+                flush(&mut steps, &mut lanes, &mut lane_of);
+                steps.push(ApplyStep::Serial(entry));
+            }
+        }
+    }
+    flush(&mut steps, &mut lanes, &mut lane_of);
+    steps
+}
+
+/// Note the IDs allocated by a replayed `LogEntry::IdAllocation`, so they
+/// are not handed out again if this node becomes leader
+fn record_id_allocation(entry: &LogEntry) {
+    if let LogEntry::IdAllocation { highest_id, .. } = entry {
+        crate::id::record_allocation(*highest_id);
+    }
+}
+
+/// Execute a single entry, logging (but not propagating) failures and timeouts
+async fn execute_logged(executor: &MariaDbExecutor, entry: &WalEntry) {
+    record_id_allocation(&entry.entry);
+    let mut span = Span::child("db_execute", entry.header.trace_id, &[("lsn", entry.header.lsn.to_string())]);
+
+    let execute_result = loop {
+        // Use 30 minute timeout for writes - large WordPress inserts can take a long time
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(1800), // 30 minutes for large data
+            executor.execute_entry_at(entry.header.lsn, &entry.entry)
+        ).await;
+        // Database unreachable: wait for it to come back rather than skip the entry
+        if let Ok(Err(Error::CircuitOpen { retry_after_secs })) = result {
+            tracing::debug!("Circuit open, retrying LSN {} in {}s", entry.header.lsn, retry_after_secs);
+            tokio::time::sleep(std::time::Duration::from_secs(retry_after_secs)).await;
+            continue;
+        }
+        break result;
+    };
+
+    match execute_result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            // Log FULL query for failed entries (truncated to 500 chars for sanity)
+            let sql_stmts = entry.entry.to_sql();
+            let sql_preview = if sql_stmts.is_empty() { "noop".to_string() }
+                else { sql_stmts[0].chars().take(500).collect::<String>() };
+            tracing::error!("QUERY FAILED - LSN {}: {} - SQL: {}", entry.header.lsn, e, sql_preview);
+            span.set_error(e.to_string());
+        }
+        Err(_) => {
+            let sql_stmts = entry.entry.to_sql();
+            let sql_preview = if sql_stmts.is_empty() { "noop".to_string() }
+                else { sql_stmts[0].chars().take(500).collect::<String>() };
+            tracing::error!("QUERY TIMEOUT - LSN {} after 30min: {}", entry.header.lsn, sql_preview);
+            span.set_error("timed out after 30min");
+        }
+    }
+}
+
+/// Run one apply step to completion. Returns the highest LSN in the step and
+/// the number of entries processed (failed entries count as processed).
+async fn apply_step(executor: &Arc<MariaDbExecutor>, step: ApplyStep) -> (Lsn, usize) {
+    match step {
+        ApplyStep::Serial(entry) => {
+            execute_logged(executor, &entry).await;
+            (entry.header.lsn, 1)
+        }
+        ApplyStep::Parallel(mut lanes) if lanes.len() == 1 => {
+            let lane = lanes.pop().unwrap_or_default();
+            for entry in &lane {
+                execute_logged(executor, entry).await;
+            }
+            (lane.iter().map(|e| e.header.lsn).max().unwrap_or(0), lane.len())
+        }
+        ApplyStep::Parallel(lanes) => {
+            let count = lanes.iter().map(|l| l.len()).sum();
+            let max_lsn = lanes.iter().flatten().map(|e| e.header.lsn).max().unwrap_or(0);
+
+            let handles: Vec<_> = lanes.into_iter().map(|lane| {
+                let executor = Arc::clone(executor);
+                tokio::spawn(async move {
+                    for entry in &lane {
+                        execute_logged(&executor, entry).await;
+                    }
+                })
+            }).collect();
+
+            // Wait for every lane before reporting progress past any of them
+            for result in futures::future::join_all(handles).await {
+                if let Err(e) = result {
+                    tracing::error!("Parallel apply worker failed: {}", e);
+                }
+            }
+            (max_lsn, count)
+        }
+    }
 }
 
 /// Send ACK in background processing
@@ -694,53 +995,272 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
     use crate::config::WalConfig;
+    use crate::wal::WalWriter;
 
     fn test_wal_config() -> WalConfig {
         WalConfig {
-            batch_size: 10,
             flush_interval_ms: 10,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
             compression: false,
             segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
             retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
             fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
         }
     }
 
     #[tokio::test]
     async fn test_follower_creation() {
         let dir = tempdir().unwrap();
-        let (tx, _rx) = mpsc::channel(100);
-
-        let wal_writer = WalWriter::new(
-            dir.path().to_path_buf(),
-            test_wal_config(),
-            "follower".to_string(),
-        ).await.unwrap();
-
-        let state_tracker = Arc::new(StateTracker::new(
-            dir.path().join("state"),
-            "follower".to_string(),
-        ).unwrap());
+        let _follower = test_follower(dir.path()).await;
+    }
 
+    async fn test_follower(dir: &std::path::Path) -> FollowerNode {
+        let (tx, _rx) = mpsc::channel(100);
+        let wal_writer = WalWriter::new(dir.to_path_buf(), test_wal_config(), "follower".to_string()).await.unwrap();
+        let state_tracker = Arc::new(StateTracker::new(dir.join("state"), "follower".to_string()).unwrap());
         let cluster = Arc::new(ClusterMembership::new(
             "follower".to_string(),
             "localhost:7655".to_string(),
             Duration::from_secs(1),
             Duration::from_secs(5),
         ));
-
-        let executor = Arc::new(MariaDbExecutor::new_mock());
-
-        let _follower = FollowerNode::new(
+        FollowerNode::new(
             "follower".to_string(),
             wal_writer,
             state_tracker,
             cluster,
-            executor,
+            Arc::new(MariaDbExecutor::new_mock()),
             ReplicationConfig::default(),
             tx,
             ElectionConfig::default(),
             false,
-        );
+        )
+    }
+
+    fn raw_sql(sql: &str, table: &str) -> LogEntry {
+        LogEntry::RawSql {
+            sql: sql.to_string(),
+            affects_table: Some(table.to_string()),
+            database: None,
+            gtid: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_follower_catches_up_schema_after_rejoining() {
+        let dir = tempdir().unwrap();
+        let leader_dir = dir.path().join("leader");
+        let follower_dir = dir.path().join("follower");
+        let leader = WalWriter::new(leader_dir.clone(), test_wal_config(), "leader".to_string()).await.unwrap();
+        let read_from = |lsn| crate::wal::WalReader::new(leader_dir.clone(), 1, false).unwrap().read_from(lsn).unwrap();
+
+        // The follower applies the first DDL, then goes down
+        leader.append(raw_sql("CREATE TABLE users (id INT)", "users")).await.unwrap();
+        leader.flush().await.unwrap();
+        {
+            let follower = test_follower(&follower_dir).await;
+            let response = follower.handle_append_entries(1, "leader".into(), 0, 0, read_from(1), 1).await.unwrap();
+            assert!(matches!(response, Message::AppendEntriesResponse { success: true, match_lsn: 1, .. }));
+            assert_eq!(follower.schema.lock().await.version(), 1);
+        }
+
+        // Meanwhile the schema changes again and DML uses the new column
+        leader.append(raw_sql("ALTER TABLE users ADD COLUMN email VARCHAR(255)", "users")).await.unwrap();
+        leader.append(raw_sql("INSERT INTO users (id, email) VALUES (1, 'a@b.c')", "users")).await.unwrap();
+        leader.flush().await.unwrap();
+        let missed = read_from(2);
+        assert_eq!(missed.iter().map(|e| e.header.schema_version).collect::<Vec<_>>(), vec![2, 2]);
+
+        // Back up: it resumes from the persisted versions and applies the ALTER before the INSERT
+        let follower = test_follower(&follower_dir).await;
+        assert_eq!(follower.schema.lock().await.version(), 1);
+        let last_applied = follower.state_tracker.last_applied_lsn().await.unwrap();
+        follower.restore_applied_lsn(last_applied).await.unwrap();
+
+        let response = follower.handle_append_entries(1, "leader".into(), 1, 1, missed, 3).await.unwrap();
+        assert!(matches!(response, Message::AppendEntriesResponse { success: true, match_lsn: 3, .. }));
+        let schema = follower.schema.lock().await;
+        assert_eq!(schema.version(), 2);
+        assert_eq!(schema.table_version("users"), Some(2));
+        drop(schema);
+
+        let saved = SchemaVersionTracker::load(&follower_dir.join("state")).unwrap();
+        assert_eq!(saved.version(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_entry_is_refused() {
+        let dir = tempdir().unwrap();
+        let follower = test_follower(dir.path()).await;
+        let refused_before = metrics::VALIDATION_FAILURES.with_label_values(&["statement_type"]).get();
+
+        // An ALTER TABLE entry whose DDL was swapped for a DROP
+        let corrupted = LogEntry::AlterTable { table: "users".to_string(), ddl: "DROP TABLE users".to_string() };
+        let entries = vec![
+            WalEntry::new(1, 1, "leader".to_string(), raw_sql("INSERT INTO users (id) VALUES (1)", "users")),
+            WalEntry::new(2, 1, "leader".to_string(), corrupted),
+            WalEntry::new(3, 1, "leader".to_string(), raw_sql("INSERT INTO users (id) VALUES (2)", "users")),
+        ];
+        let response = follower.handle_append_entries(1, "leader".into(), 0, 0, entries, 3).await.unwrap();
+        match response {
+            Message::AppendEntriesRejected { match_lsn, reason, .. } => {
+                assert_eq!(match_lsn, 1);
+                assert_eq!(reason, VALIDATION_FAILED);
+            }
+            other => panic!("expected AppendEntriesRejected, got {:?}", other),
+        }
+        // Nothing from the refused entry on was applied
+        assert_eq!(follower.last_applied_lsn().await, 1);
+        assert_eq!(metrics::VALIDATION_FAILURES.with_label_values(&["statement_type"]).get(), refused_before + 1);
+    }
+
+    #[test]
+    fn test_schema_catch_up_runs_ddl_first() {
+        let dir = tempdir().unwrap();
+        let mut tracker = SchemaVersionTracker::new(dir.path());
+        let versioned = |mut entry: WalEntry, version: u64| {
+            entry.header.schema_version = version;
+            entry
+        };
+        let create = versioned(ddl(1, "a"), 1);
+        tracker.observe(&create);
+
+        // Not behind: the batch keeps WAL order
+        let (first, rest) = schema_catch_up(vec![versioned(insert(2, "a"), 1)], &tracker);
+        assert!(first.is_empty());
+        assert_eq!(lsns(&rest), vec![2]);
+
+        // Behind: newer DDL moves ahead, DDL that already ran is dropped
+        let batch = vec![
+            create, versioned(insert(2, "a"), 1), versioned(ddl(3, "a"), 2),
+            versioned(insert(4, "a"), 2), versioned(ddl(5, "b"), 3), versioned(insert(6, "b"), 3),
+        ];
+        let (first, rest) = schema_catch_up(batch, &tracker);
+        assert_eq!(lsns(&first), vec![3, 5]);
+        assert_eq!(lsns(&rest), vec![2, 4, 6]);
+    }
+
+    fn insert(lsn: Lsn, table: &str) -> WalEntry {
+        WalEntry::new(lsn, 1, "leader".to_string(), LogEntry::RawSql {
+            sql: format!("INSERT INTO {} VALUES (1)", table),
+            affects_table: Some(table.to_string()),
+            database: None,
+            gtid: None,
+        })
+    }
+
+    fn ddl(lsn: Lsn, table: &str) -> WalEntry {
+        WalEntry::new(lsn, 1, "leader".to_string(), LogEntry::RawSql {
+            sql: format!("ALTER TABLE {} ADD COLUMN c INT", table),
+            affects_table: Some(table.to_string()),
+            database: None,
+            gtid: None,
+        })
+    }
+
+    fn lsns(lane: &[WalEntry]) -> Vec<Lsn> {
+        lane.iter().map(|e| e.header.lsn).collect()
+    }
+
+    #[test]
+    fn test_plan_keeps_same_table_ordered() {
+        let entries = vec![
+            insert(1, "a"), insert(2, "b"), insert(3, "a"),
+            insert(4, "c"), insert(5, "b"), insert(6, "a"),
+        ];
+        let steps = plan_apply(entries, 4);
+        assert_eq!(steps.len(), 1);
+
+        match &steps[0] {
+            ApplyStep::Parallel(lanes) => {
+                assert_eq!(lanes.len(), 3);
+                assert_eq!(lsns(&lanes[0]), vec![1, 3, 6]);
+                assert_eq!(lsns(&lanes[1]), vec![2, 5]);
+                assert_eq!(lsns(&lanes[2]), vec![4]);
+            }
+            other => panic!("expected parallel step, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_plan_shares_lanes_beyond_worker_count() {
+        let entries = vec![insert(1, "a"), insert(2, "b"), insert(3, "c"), insert(4, "a")];
+        let steps = plan_apply(entries, 2);
+
+        match &steps[0] {
+            ApplyStep::Parallel(lanes) => {
+                assert_eq!(lanes.len(), 2);
+                // "a" and "c" share a lane; "a" stays in the lane it started in
+                assert_eq!(lsns(&lanes[0]), vec![1, 3, 4]);
+                assert_eq!(lsns(&lanes[1]), vec![2]);
+            }
+            other => panic!("expected parallel step, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_plan_barrier_orders_surrounding_entries() {
+        // Nothing after the ALTER may run before it, and nothing before it may run after it
+        let entries = vec![insert(1, "a"), insert(2, "b"), ddl(3, "a"), insert(4, "b"), insert(5, "a")];
+        let steps = plan_apply(entries, 4);
+        assert_eq!(steps.len(), 3);
+
+        match &steps[0] {
+            ApplyStep::Parallel(lanes) => {
+                assert_eq!(lanes.iter().map(|l| lsns(l)).collect::<Vec<_>>(), vec![vec![1], vec![2]]);
+            }
+            other => panic!("expected parallel step, got {:?}", other),
+        }
+        match &steps[1] {
+            ApplyStep::Serial(e) => assert_eq!(e.header.lsn, 3),
+            other => panic!("expected serial step, got {:?}", other),
+        }
+        match &steps[2] {
+            ApplyStep::Parallel(lanes) => {
+                assert_eq!(lanes.iter().map(|l| lsns(l)).collect::<Vec<_>>(), vec![vec![4], vec![5]]);
+            }
+            other => panic!("expected parallel step, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_plan_untabled_and_multi_statement_sql_are_barriers() {
+        let untabled = WalEntry::new(2, 1, "leader".to_string(), LogEntry::RawSql {
+            sql: "SET @x = 1".to_string(),
+            affects_table: None,
+            database: None,
+            gtid: None,
+        });
+        let multi = WalEntry::new(3, 1, "leader".to_string(), LogEntry::RawSql {
+            sql: "INSERT INTO a VALUES (1); DELETE FROM b".to_string(),
+            affects_table: Some("a".to_string()),
+            database: None,
+            gtid: None,
+        });
+        let steps = plan_apply(vec![insert(1, "a"), untabled, multi, insert(4, "a")], 4);
+
+        let kinds: Vec<&str> = steps.iter().map(|s| match s {
+            ApplyStep::Serial(_) => "serial",
+            ApplyStep::Parallel(_) => "parallel",
+        }).collect();
+        assert_eq!(kinds, vec!["parallel", "serial", "serial", "parallel"]);
+    }
+
+    #[test]
+    fn test_plan_single_worker_is_serial() {
+        let steps = plan_apply(vec![insert(1, "a"), insert(2, "b"), insert(3, "a")], 1);
+        let order: Vec<Lsn> = steps.iter().map(|s| match s {
+            ApplyStep::Serial(e) => e.header.lsn,
+            other => panic!("expected serial step, got {:?}", other),
+        }).collect();
+        assert_eq!(order, vec![1, 2, 3]);
     }
 }
@@ -3,23 +3,34 @@
 //! Handles leader responsibilities: accepting writes, replicating to followers,
 //! and managing cluster membership.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock, oneshot};
 use tokio::time::interval;
+use uuid::Uuid;
 
 use crate::wal::entry::{Lsn, LogEntry};
 use crate::wal::{WalWriter, WalReader};
-use crate::replication::{Message, ReplicationConfig};
+use crate::replication::{Message, ReplicationConfig, TransactionBuffer, WriteRateLimiter, TRANSACTION_TIMEOUT};
 use crate::executor::MariaDbExecutor;
 use crate::state::{ClusterMembership, StateTracker, NodeStatus};
 use crate::error::{Error, Result};
+use crate::telemetry::{self, Span};
+use crate::metrics;
 
 /// Type alias for pending writes map
 type PendingWritesMap = HashMap<Lsn, PendingWrite>;
 /// Type alias for LSN tracking maps
 type LsnMap = HashMap<String, Lsn>;
+/// Type alias for open replicate_to_follower spans per follower, by entry LSN
+type ReplicationSpanMap = HashMap<String, Vec<(Lsn, Span)>>;
+/// Type alias for unacknowledged batches per follower - (last LSN, sent at), oldest first
+type InFlightMap = HashMap<String, VecDeque<(Lsn, Instant)>>;
+
+/// A WAN follower is sent batches this many times `max_batch_entries`
+const WAN_BATCH_FACTOR: usize = 16;
 
 /// Pending write request
 #[allow(dead_code)]
@@ -30,6 +41,18 @@ struct PendingWrite {
     response: oneshot::Sender<Result<Lsn>>,
 }
 
+/// Batch of new WAL entries sent to followers and not yet committed
+struct InFlightBatch {
+    batch_id: u64,
+    /// Number of entries in the batch
+    entries: usize,
+    /// Followers counted towards quorum that have ACKed the batch. A set, so
+    /// a follower that restarts and ACKs the batch again counts once.
+    acked_by: HashSet<String>,
+    /// Highest LSN in the batch
+    last_lsn: Lsn,
+}
+
 /// Leader node state
 pub struct LeaderNode {
     /// Node ID
@@ -60,9 +83,48 @@ pub struct LeaderNode {
     executor: Option<Arc<MariaDbExecutor>>,
     /// Shutdown signal
     shutdown: RwLock<bool>,
-    /// Track pending replication per peer - (sent_up_to_lsn, sent_at_time)
-    /// If pending, don't send more until ACK received
-    pending_replication: RwLock<std::collections::HashMap<String, (Lsn, std::time::Instant)>>,
+    /// Batches sent to each peer and awaiting ACK. Once `pipeline_depth`
+    /// are pending, nothing more is sent to that peer until an ACK arrives.
+    pending_replication: RwLock<InFlightMap>,
+    /// Batches of new entries in flight, in LSN order; the front one commits
+    /// first, once a quorum has ACKed it
+    pipeline: RwLock<VecDeque<InFlightBatch>>,
+    next_batch_id: AtomicU64,
+    /// Open transactions - writes are buffered here until commit or rollback
+    transactions: Arc<TransactionBuffer>,
+    /// Traced entries sent to each follower and not yet acknowledged
+    replication_spans: RwLock<ReplicationSpanMap>,
+    /// Followers added while running whose ACKs do not count towards quorum
+    /// until they are within `max_lag_lsn` of the commit LSN
+    catching_up: Arc<RwLock<HashSet<String>>>,
+    /// Smoothed AppendEntries round trip to each follower
+    peer_rtt: RwLock<HashMap<String, Duration>>,
+    /// When the oldest unsent entry for each WAN follower started waiting;
+    /// its batch goes out `wan_batch_timeout_ms` later
+    wan_windows: RwLock<HashMap<String, Instant>>,
+    /// `cluster.max_writes_per_second`, shared by every write path into this leader
+    write_limiter: Arc<WriteRateLimiter>,
+}
+
+/// Adds followers to a running cluster (shared with the HTTP API)
+#[derive(Clone)]
+pub struct FollowerAdmission {
+    cluster: Arc<ClusterMembership>,
+    catching_up: Arc<RwLock<HashSet<String>>>,
+}
+
+impl FollowerAdmission {
+    /// Add a follower starting from a snapshot taken at `snapshot_lsn`. The
+    /// node counts towards the quorum size at once, but its ACKs only count
+    /// once it has caught up.
+    pub async fn add_follower(&self, node_id: &str, address: &str, snapshot_lsn: Lsn) -> Result<()> {
+        self.cluster.add_peer(node_id.to_string(), address.to_string()).await?;
+        // Replicate from the snapshot rather than from the start of the WAL
+        self.cluster.update_node(node_id, |node| node.last_applied_lsn = snapshot_lsn).await?;
+        self.catching_up.write().await.insert(node_id.to_string());
+        tracing::info!("Added follower {} at {} (snapshot LSN {})", node_id, address, snapshot_lsn);
+        Ok(())
+    }
 }
 
 impl LeaderNode {
@@ -83,7 +145,6 @@ impl LeaderNode {
             wal_reader: Arc::new(RwLock::new(wal_reader)),
             state_tracker,
             cluster,
-            config,
             term: RwLock::new(1),
             commit_lsn: RwLock::new(0),
             pending_writes: RwLock::new(HashMap::new()),
@@ -92,7 +153,19 @@ impl LeaderNode {
             message_tx,
             executor,
             shutdown: RwLock::new(false),
-            pending_replication: RwLock::new(std::collections::HashMap::new()),
+            pending_replication: RwLock::new(HashMap::new()),
+            pipeline: RwLock::new(VecDeque::new()),
+            next_batch_id: AtomicU64::new(1),
+            transactions: Arc::new(TransactionBuffer::new()),
+            replication_spans: RwLock::new(HashMap::new()),
+            catching_up: Arc::new(RwLock::new(HashSet::new())),
+            peer_rtt: RwLock::new(HashMap::new()),
+            wan_windows: RwLock::new(HashMap::new()),
+            write_limiter: Arc::new(WriteRateLimiter::new(
+                config.max_writes_per_second,
+                config.rate_limit_action,
+            )),
+            config,
         }
     }
 
@@ -152,6 +225,8 @@ impl LeaderNode {
                     
                     self.send_heartbeats().await?;
                     self.check_commit_progress().await?;
+                    self.transactions.expire(TRANSACTION_TIMEOUT).await;
+                    self.write_limiter.update_rate();
                     
                     // Check if we should yield to a higher-priority node
                     if let Some(higher_priority_node) = self.check_for_priority_yield().await {
@@ -253,6 +328,8 @@ impl LeaderNode {
 
     /// Accept a write request
     pub async fn write(&self, entry: LogEntry) -> Result<Lsn> {
+        self.write_limiter.acquire().await?;
+
         // Append to local WAL
         let lsn = self.wal_writer.append(entry.clone()).await?;
 
@@ -295,6 +372,128 @@ impl LeaderNode {
         }
     }
 
+    /// Adds followers to this leader's cluster while it runs
+    pub fn admission(&self) -> FollowerAdmission {
+        FollowerAdmission {
+            cluster: Arc::clone(&self.cluster),
+            catching_up: Arc::clone(&self.catching_up),
+        }
+    }
+
+    /// Record a follower's acknowledged LSN
+    async fn record_follower_ack(&self, node_id: &str, match_lsn: Lsn) {
+        let previous = self.match_lsn.write().await
+            .insert(node_id.to_string(), match_lsn)
+            .unwrap_or(0);
+        self.next_lsn.write().await.insert(node_id.to_string(), match_lsn + 1);
+
+        let commit_lsn = *self.commit_lsn.read().await;
+        let mut catching_up = self.catching_up.write().await;
+        if catching_up.contains(node_id) && commit_lsn.saturating_sub(match_lsn) <= self.config.max_lag_lsn {
+            catching_up.remove(node_id);
+            tracing::info!("Follower {} caught up (LSN {}, commit {}), counting its ACKs", node_id, match_lsn, commit_lsn);
+        }
+
+        // ACKs are cumulative: this one covers every batch up to match_lsn.
+        // match_lsn goes backwards when a follower restarts, so `previous`
+        // can't tell which batches it already acknowledged.
+        if match_lsn < previous {
+            tracing::debug!("Follower {} ACKed LSN {} after {} (restarted?)", node_id, match_lsn, previous);
+        }
+        if !catching_up.contains(node_id) {
+            for batch in self.pipeline.write().await.iter_mut() {
+                if batch.last_lsn <= match_lsn {
+                    batch.acked_by.insert(node_id.to_string());
+                }
+            }
+        }
+    }
+
+    /// Start tracking a batch sent to a follower if it carries entries past
+    /// every batch already in flight (resends to lagging followers do not)
+    async fn track_batch(&self, last_lsn: Lsn) {
+        let commit_lsn = *self.commit_lsn.read().await;
+        let mut pipeline = self.pipeline.write().await;
+        let first_lsn = pipeline.back().map(|b| b.last_lsn).unwrap_or(commit_lsn) + 1;
+        if last_lsn < first_lsn {
+            return;
+        }
+        let batch_id = self.next_batch_id.fetch_add(1, Ordering::Relaxed);
+        tracing::trace!("Batch {} in flight: LSN {}..={}", batch_id, first_lsn, last_lsn);
+        pipeline.push_back(InFlightBatch {
+            batch_id,
+            entries: (last_lsn - first_lsn + 1) as usize,
+            acked_by: HashSet::new(),
+            last_lsn,
+        });
+        metrics::PIPELINE_DEPTH.set(pipeline.len() as i64);
+    }
+
+    /// Retire in-flight batches in order while a quorum (counting ourselves)
+    /// has ACKed them, or the commit LSN has moved past them.
+    /// Returns the last LSN of the newest batch committed this way.
+    async fn commit_batches(&self, quorum_size: usize, commit_lsn: Lsn) -> Option<Lsn> {
+        let mut pipeline = self.pipeline.write().await;
+        let mut committed = None;
+        while let Some(batch) = pipeline.front() {
+            if batch.acked_by.len() + 1 >= quorum_size {
+                tracing::trace!("Batch {} ({} entries) committed at LSN {}", batch.batch_id, batch.entries, batch.last_lsn);
+                committed = Some(batch.last_lsn);
+            } else if batch.last_lsn > commit_lsn {
+                break;
+            }
+            pipeline.pop_front();
+        }
+        metrics::PIPELINE_DEPTH.set(pipeline.len() as i64);
+        committed
+    }
+
+    /// ACKed LSNs of followers that count towards quorum
+    async fn counted_matches(&self) -> Vec<Lsn> {
+        let catching_up = self.catching_up.read().await;
+        self.match_lsn.read().await
+            .iter()
+            .filter(|(id, _)| !catching_up.contains(*id))
+            .map(|(_, lsn)| *lsn)
+            .collect()
+    }
+
+    /// Write rate limit of this leader (shared with the HTTP API)
+    pub fn write_limiter(&self) -> Arc<WriteRateLimiter> {
+        Arc::clone(&self.write_limiter)
+    }
+
+    /// Open transactions buffered on this leader (shared with the HTTP API)
+    pub fn transactions(&self) -> Arc<TransactionBuffer> {
+        Arc::clone(&self.transactions)
+    }
+
+    /// Open a new transaction. Writes are buffered on the leader until the
+    /// transaction is committed or rolled back.
+    pub async fn begin_transaction(&self) -> Uuid {
+        self.transactions.begin().await
+    }
+
+    /// Buffer a write for an open transaction, returning the number of buffered writes
+    pub async fn transaction_write(&self, txn_id: Uuid, entry: LogEntry) -> Result<usize> {
+        self.transactions.write(txn_id, entry).await
+    }
+
+    /// Commit a transaction: append all buffered writes as a single WAL entry
+    /// (replicated to followers in one AppendEntries batch) and wait for quorum
+    pub async fn commit_transaction(&self, txn_id: Uuid) -> Result<Lsn> {
+        match self.transactions.take(txn_id).await? {
+            Some(entry) => self.write(entry).await,
+            None => Ok(self.wal_writer.current_lsn().await),
+        }
+    }
+
+    /// Roll back a transaction, discarding its buffered writes.
+    /// Returns the number of writes discarded.
+    pub async fn rollback_transaction(&self, txn_id: Uuid) -> Result<usize> {
+        self.transactions.rollback(txn_id).await
+    }
+
     /// Send heartbeats to all followers
     async fn send_heartbeats(&self) -> Result<()> {
         let term = *self.term.read().await;
@@ -359,93 +558,140 @@ impl LeaderNode {
                 peer.last_applied_lsn // Fallback to snapshot if node disappeared
             };
             
-            // Check if this peer has pending replication (waiting for ACK)
-            // Skip if we're still waiting, unless it's been more than 5 seconds (stale)
-            // OR the peer's LSN has changed (meaning ACK was received via cluster membership)
-            {
+            // Check which batches for this peer are still waiting for an ACK.
+            // Batches the peer's LSN has reached were ACKed via cluster membership;
+            // if its LSN went backwards (restart) or the oldest batch has waited
+            // more than 5 seconds (stale), everything in flight is resent.
+            let (mut in_flight, last_sent, acked) = {
                 let mut pending = self.pending_replication.write().await;
-                if let Some((pending_lsn, sent_at)) = pending.get(&peer.id) {
-                    // Check if peer's LSN has advanced past our pending LSN OR went backwards (restart)
-                    // This means ACK was received and processed via cluster membership
-                    if current_peer_lsn >= *pending_lsn || current_peer_lsn < peer.last_applied_lsn {
-                        tracing::debug!("Peer {} ACK received (lsn {} vs pending {}), clearing pending", 
-                            peer.id, current_peer_lsn, pending_lsn);
-                        pending.remove(&peer.id);
-                        // Continue to send next batch
-                    } else {
-                        let elapsed = sent_at.elapsed();
-                        if elapsed < std::time::Duration::from_secs(5) {
-                            tracing::trace!("Skipping peer {} - pending ACK for LSN {} ({}ms ago)", 
-                                peer.id, pending_lsn, elapsed.as_millis());
-                            continue;
-                        } else {
-                            tracing::warn!("Peer {} pending ACK timed out after {}s (at lsn {}, pending {}), will retry", 
-                                peer.id, elapsed.as_secs(), current_peer_lsn, pending_lsn);
-                            // Clear the stale pending entry so we can retry
-                            pending.remove(&peer.id);
-                        }
+                let queue = pending.entry(peer.id.clone()).or_default();
+                let acked = if current_peer_lsn < peer.last_applied_lsn {
+                    queue.len()
+                } else {
+                    queue.iter().take_while(|(lsn, _)| current_peer_lsn >= *lsn).count()
+                };
+                if acked > 0 {
+                    tracing::debug!("Peer {} ACK received (lsn {}), clearing {} pending batch(es)",
+                        peer.id, current_peer_lsn, acked);
+                    queue.drain(..acked);
+                }
+                if let Some((pending_lsn, sent_at)) = queue.front() {
+                    let elapsed = sent_at.elapsed();
+                    if elapsed >= Duration::from_secs(5) {
+                        tracing::warn!("Peer {} pending ACK timed out after {}s (at lsn {}, pending {}), will retry",
+                            peer.id, elapsed.as_secs(), current_peer_lsn, pending_lsn);
+                        // Clear the stale pending entries so we can retry
+                        queue.clear();
                     }
                 }
+                let last_sent = queue.back().map(|(lsn, _)| *lsn);
+                (queue.len(), last_sent, acked > 0)
+            };
+            if acked {
+                self.end_replication_spans(&peer.id, current_peer_lsn).await;
+                self.record_follower_ack(&peer.id, current_peer_lsn).await;
             }
 
-            // Use the FRESH peer LSN for replication (current_peer_lsn was fetched at start of loop)
-            let next = current_peer_lsn + 1;
-            tracing::trace!("Peer {} has last_applied_lsn={}, will replicate from next={}", peer.id, current_peer_lsn, next);
+            // Continue after the FRESH peer LSN, or after the batches already in flight
+            let mut next = last_sent.unwrap_or(current_peer_lsn).max(current_peer_lsn) + 1;
 
-            let reader = self.wal_reader.read().await;
-            let entries = match reader.read_batch(next, self.config.max_batch_entries) {
-                Ok(e) => e,
-                Err(e) => {
-                    tracing::error!("Failed to read WAL batch for peer {}: {}", peer.id, e);
-                    continue;
-                }
-            };
-            drop(reader);
+            // WAN peers collect entries for wan_batch_timeout_ms, counted from
+            // when the first unsent one appeared, even while a batch is in flight
+            let wan = self.is_wan_peer(&peer.id).await;
+            if wan && self.wal_writer.current_lsn().await >= next {
+                self.wan_windows.write().await.entry(peer.id.clone()).or_insert_with(Instant::now);
+            }
 
-            if entries.is_empty() {
+            if in_flight >= self.config.pipeline_depth.max(1) {
+                tracing::trace!("Skipping peer {} - {} batch(es) awaiting ACK", peer.id, in_flight);
                 continue;
             }
 
-            let batch_max_lsn = entries.last().map(|e| e.header.lsn).unwrap_or(next);
-            tracing::debug!("Replicating {} entries starting at LSN {} to {}", entries.len(), next, peer.id);
+            let mut batch_entries = self.config.max_batch_entries;
+            if wan {
+                let mut windows = self.wan_windows.write().await;
+                match windows.get(&peer.id) {
+                    Some(opened) if opened.elapsed() >= Duration::from_millis(self.config.wan_batch_timeout_ms) => {
+                        windows.remove(&peer.id);
+                    }
+                    // Nothing to send yet, or still collecting
+                    _ => continue,
+                }
+                batch_entries *= WAN_BATCH_FACTOR;
+            }
+            tracing::trace!("Peer {} has last_applied_lsn={}, will replicate from next={}", peer.id, current_peer_lsn, next);
 
-            // Get prev entry info
-            let (prev_lsn, prev_term) = if next > 1 {
+            // Fill the pipeline: each batch goes out without waiting for the previous ACK
+            let mut batches = Vec::new();
+            while in_flight < self.config.pipeline_depth.max(1) {
                 let reader = self.wal_reader.read().await;
-                if let Ok(Some(prev_entry)) = reader.get(next - 1) {
-                    (prev_entry.header.lsn, prev_entry.header.term)
-                } else {
-                    (0, 0)
+                let entries = match reader.read_batch(next, batch_entries) {
+                    Ok(e) => e,
+                    Err(e) => {
+                        tracing::error!("Failed to read WAL batch for peer {}: {}", peer.id, e);
+                        break;
+                    }
+                };
+                drop(reader);
+
+                if entries.is_empty() {
+                    break;
                 }
-            } else {
-                (0, 0)
-            };
 
-            let msg = Message::AppendEntries {
-                term,
-                leader_id: self.node_id.clone(),
-                prev_lsn,
-                prev_term,
-                entries,
-                leader_commit_lsn: commit_lsn,
-            };
+                let batch_max_lsn = entries.last().map(|e| e.header.lsn).unwrap_or(next);
+                if telemetry::is_enabled() {
+                    self.start_replication_spans(&peer.id, &entries).await;
+                }
+                tracing::debug!("Replicating {} entries starting at LSN {} to {}", entries.len(), next, peer.id);
 
-            // Mark as pending BEFORE sending
-            {
-                let mut pending = self.pending_replication.write().await;
-                pending.insert(peer.id.clone(), (batch_max_lsn, std::time::Instant::now()));
+                // Get prev entry info
+                let (prev_lsn, prev_term) = if next > 1 {
+                    let reader = self.wal_reader.read().await;
+                    if let Ok(Some(prev_entry)) = reader.get(next - 1) {
+                        (prev_entry.header.lsn, prev_entry.header.term)
+                    } else {
+                        (0, 0)
+                    }
+                } else {
+                    (0, 0)
+                };
+
+                let msg = Message::AppendEntries {
+                    term,
+                    leader_id: self.node_id.clone(),
+                    prev_lsn,
+                    prev_term,
+                    entries,
+                    leader_commit_lsn: commit_lsn,
+                };
+
+                // Mark as pending BEFORE sending
+                self.pending_replication.write().await
+                    .entry(peer.id.clone())
+                    .or_default()
+                    .push_back((batch_max_lsn, Instant::now()));
+                self.track_batch(batch_max_lsn).await;
+
+                batches.push(msg);
+                in_flight += 1;
+                next = batch_max_lsn + 1;
             }
 
             // Collect for parallel sending
-            replication_tasks.push((peer.address.clone(), msg));
+            if !batches.is_empty() {
+                replication_tasks.push((peer.address.clone(), batches));
+            }
         }
 
         // PARALLEL: Send to all peers simultaneously using spawned tasks
-        // This is the key optimization - no waiting between peers
-        for (peer_address, msg) in replication_tasks {
+        // This is the key optimization - no waiting between peers.
+        // A peer's pipelined batches are queued in LSN order.
+        for (peer_address, batches) in replication_tasks {
             let tx = self.message_tx.clone();
             tokio::spawn(async move {
-                let _ = tx.send((peer_address, msg)).await;
+                for msg in batches {
+                    let _ = tx.send((peer_address.clone(), msg)).await;
+                }
             });
         }
 
@@ -468,26 +714,25 @@ impl LeaderNode {
         }
 
         if success {
-            // Clear pending replication for this peer - they ACK'd, we can send more
-            {
-                let mut pending = self.pending_replication.write().await;
-                pending.remove(node_id);
+            // Clear the batches this ACK covers - we can send more. The newest
+            // of them gives the round trip for this follower.
+            let acked_sent_at = self.pending_replication.write().await
+                .get_mut(node_id)
+                .and_then(|queue| {
+                    let sent_at = queue.iter().take_while(|(lsn, _)| *lsn <= match_lsn).last().map(|(_, at)| *at);
+                    queue.retain(|(lsn, _)| *lsn > match_lsn);
+                    sent_at
+                });
+            if let Some(sent_at) = acked_sent_at {
+                self.record_rtt(node_id, sent_at.elapsed()).await;
             }
 
-            // Update match_lsn for this follower
-            {
-                let mut matches = self.match_lsn.write().await;
-                matches.insert(node_id.to_string(), match_lsn);
-            }
-
-            // Update next_lsn
-            {
-                let mut nexts = self.next_lsn.write().await;
-                nexts.insert(node_id.to_string(), match_lsn + 1);
-            }
+            // Update match_lsn and next_lsn for this follower
+            self.record_follower_ack(node_id, match_lsn).await;
 
             // Update cluster membership
             self.cluster.record_heartbeat(node_id, match_lsn).await?;
+            self.end_replication_spans(node_id, match_lsn).await;
 
             // Check if we can advance commit
             self.check_commit_progress().await?;
@@ -496,7 +741,7 @@ impl LeaderNode {
             self.acknowledge_writes(match_lsn).await?;
         } else {
             // Follower rejected, decrement next_lsn and retry
-            // Also clear pending so we can retry
+            // Also clear pending so everything in flight is resent
             {
                 let mut pending = self.pending_replication.write().await;
                 pending.remove(node_id);
@@ -512,6 +757,63 @@ impl LeaderNode {
         Ok(())
     }
 
+    /// Fold a measured AppendEntries round trip into the follower's smoothed RTT
+    async fn record_rtt(&self, node_id: &str, sample: Duration) {
+        let was_wan = self.is_wan_peer(node_id).await;
+        let rtt = {
+            let mut rtts = self.peer_rtt.write().await;
+            let rtt = match rtts.get(node_id) {
+                Some(previous) => (*previous * 7 + sample) / 8,
+                None => sample,
+            };
+            rtts.insert(node_id.to_string(), rtt);
+            rtt
+        };
+        metrics::PEER_RTT_MS.with_label_values(&[node_id]).set(rtt.as_secs_f64() * 1000.0);
+
+        if !self.config.wan_mode && was_wan != self.is_wan_peer(node_id).await {
+            tracing::info!("Follower {} round trip is {}ms, {} WAN batching", node_id, rtt.as_millis(),
+                if was_wan { "disabling" } else { "enabling" });
+        }
+    }
+
+    /// Whether replication to this follower is batched for a high-latency link
+    pub async fn is_wan_peer(&self, node_id: &str) -> bool {
+        if self.config.wan_mode {
+            return true;
+        }
+        let threshold = self.config.wan_rtt_threshold_ms;
+        threshold > 0 && self.peer_rtt.read().await
+            .get(node_id)
+            .is_some_and(|rtt| rtt.as_millis() as u64 > threshold)
+    }
+
+    /// Open a replicate_to_follower span for each traced entry not already in flight to this follower
+    async fn start_replication_spans(&self, node_id: &str, entries: &[crate::wal::entry::WalEntry]) {
+        let mut spans = self.replication_spans.write().await;
+        let open = spans.entry(node_id.to_string()).or_default();
+        let in_flight = open.last().map(|(lsn, _)| *lsn).unwrap_or(0);
+        for entry in entries {
+            if entry.header.lsn > in_flight && telemetry::is_traced(&entry.header.trace_id) {
+                let span = Span::child(
+                    "replicate_to_follower",
+                    entry.header.trace_id,
+                    &[("node_id", node_id.to_string())],
+                );
+                open.push((entry.header.lsn, span));
+            }
+        }
+    }
+
+    /// End the replicate_to_follower spans a follower has acknowledged
+    async fn end_replication_spans(&self, node_id: &str, up_to_lsn: Lsn) {
+        let mut spans = self.replication_spans.write().await;
+        if let Some(open) = spans.get_mut(node_id) {
+            // Dropping a span ends it
+            open.retain(|(lsn, _)| *lsn > up_to_lsn);
+        }
+    }
+
     /// Handle a sync request from a follower
     pub async fn handle_sync_request(
         &self,
@@ -545,24 +847,29 @@ impl LeaderNode {
 
     /// Check if we can advance the commit LSN
     async fn check_commit_progress(&self) -> Result<()> {
-        let matches = self.match_lsn.read().await;
         let quorum_size = self.cluster.quorum_size().await;
 
         // Include ourselves in the count
         let current_lsn = self.wal_writer.current_lsn().await;
-        let mut all_lsns: Vec<Lsn> = matches.values().copied().collect();
+        let mut all_lsns = self.counted_matches().await;
         all_lsns.push(current_lsn);
         all_lsns.sort_unstable();
 
         // Find the LSN that has quorum
+        let current_commit = *self.commit_lsn.read().await;
+        let mut new_commit = current_commit;
         if all_lsns.len() >= quorum_size {
             let commit_index = all_lsns.len() - quorum_size;
-            let new_commit = all_lsns[commit_index];
+            new_commit = new_commit.max(all_lsns[commit_index]);
+        }
 
-            let current_commit = *self.commit_lsn.read().await;
-            if new_commit > current_commit {
-                self.advance_commit_lsn(new_commit).await?;
-            }
+        // In-flight batches commit in order once a majority has ACKed them
+        if let Some(batch_lsn) = self.commit_batches(quorum_size, new_commit).await {
+            new_commit = new_commit.max(batch_lsn);
+        }
+
+        if new_commit > current_commit {
+            self.advance_commit_lsn(new_commit).await?;
         }
 
         Ok(())
@@ -578,15 +885,15 @@ impl LeaderNode {
     /// Acknowledge pending writes up to the given LSN
     async fn acknowledge_writes(&self, up_to_lsn: Lsn) -> Result<()> {
         let quorum_size = self.cluster.quorum_size().await;
+        let matches = self.counted_matches().await;
         let mut pending = self.pending_writes.write().await;
-        let matches = self.match_lsn.read().await;
 
         // Count acks for each pending write
         let to_ack: Vec<Lsn> = pending
             .iter()
             .filter(|(lsn, _)| **lsn <= up_to_lsn)
             .filter_map(|(lsn, _pw)| {
-                let ack_count = 1 + matches.values().filter(|m| **m >= *lsn).count();
+                let ack_count = 1 + matches.iter().filter(|m| **m >= *lsn).count();
                 if ack_count >= quorum_size {
                     Some(*lsn)
                 } else {
@@ -595,8 +902,6 @@ impl LeaderNode {
             })
             .collect();
 
-        drop(matches);
-
         // Send acknowledgments
         for lsn in to_ack {
             if let Some(pw) = pending.remove(&lsn) {
@@ -677,6 +982,8 @@ impl LeaderNode {
     /// Request this leader to step down
     pub async fn step_down(&self) -> Result<()> {
         *self.shutdown.write().await = true;
+        self.pipeline.write().await.clear();
+        metrics::PIPELINE_DEPTH.set(0);
         tracing::info!("Leader stepping down");
         Ok(())
     }
@@ -691,41 +998,52 @@ impl LeaderNode {
 mod tests {
     use super::*;
     use tempfile::tempdir;
-    use crate::config::WalConfig;
+    use crate::config::{ColumnFilterConfig, WalConfig};
+    use crate::wal::ColumnFilter;
+    use crate::replication::RateLimitAction;
 
     fn test_wal_config() -> WalConfig {
         WalConfig {
-            batch_size: 10,
             flush_interval_ms: 10,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
             compression: false,
             segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
             retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
             fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
         }
     }
 
     #[tokio::test]
     async fn test_leader_creation() {
         let dir = tempdir().unwrap();
-        let (tx, _rx) = mpsc::channel(100);
-
-        let wal_writer = WalWriter::new(
-            dir.path().to_path_buf(),
-            test_wal_config(),
-            "leader".to_string(),
-        ).await.unwrap();
-
-        let wal_reader = WalReader::new(
-            dir.path().to_path_buf(),
-            1,
-            false,
-        ).unwrap();
+        let leader = test_leader(dir.path()).await;
+        assert_eq!(leader.node_id(), "leader");
+    }
 
-        let state_tracker = Arc::new(StateTracker::new(
-            dir.path().join("state"),
-            "leader".to_string(),
-        ).unwrap());
+    /// Single-node leader (quorum of one, so writes commit immediately)
+    async fn test_leader(dir: &std::path::Path) -> LeaderNode {
+        test_leader_with(dir, ReplicationConfig::default()).await.0
+    }
 
+    /// Leader with the given replication settings and its outbound message queue
+    async fn test_leader_with(
+        dir: &std::path::Path,
+        config: ReplicationConfig,
+    ) -> (LeaderNode, mpsc::Receiver<(String, Message)>) {
+        let (tx, rx) = mpsc::channel(100);
+        let wal_writer = WalWriter::new(dir.to_path_buf(), test_wal_config(), "leader".to_string())
+            .await
+            .unwrap();
+        let wal_reader = WalReader::new(dir.to_path_buf(), 1, false).unwrap();
+        let state_tracker = Arc::new(StateTracker::new(dir.join("state"), "leader".to_string()).unwrap());
         let cluster = Arc::new(ClusterMembership::new(
             "leader".to_string(),
             "localhost:7654".to_string(),
@@ -733,14 +1051,355 @@ mod tests {
             Duration::from_secs(5),
         ));
 
-        let _leader = LeaderNode::new(
+        let leader = LeaderNode::new(
             "leader".to_string(),
             wal_writer,
             wal_reader,
             state_tracker,
             cluster,
-            ReplicationConfig::default(),
+            config,
             tx,
+            None,
         );
+        (leader, rx)
+    }
+
+    fn raw_sql(sql: &str) -> LogEntry {
+        LogEntry::RawSql {
+            sql: sql.to_string(),
+            affects_table: None,
+            database: Some("app".to_string()),
+            gtid: None,
+        }
+    }
+
+    /// Entries a follower would receive when syncing from the start of the WAL
+    async fn replicated_entries(leader: &LeaderNode) -> Vec<LogEntry> {
+        leader.wal_writer.flush().await.unwrap();
+        leader.wal_reader.write().await.refresh_index().unwrap();
+        match leader.handle_sync_request("follower", 1, 100).await.unwrap() {
+            Message::SyncResponse { entries, .. } => entries.into_iter().map(|e| e.entry).collect(),
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filtered_columns_never_reach_followers() {
+        let dir = tempdir().unwrap();
+        let mut leader = test_leader(dir.path()).await;
+        leader.wal_writer = leader.wal_writer.clone().with_column_filter(ColumnFilter::new(&[ColumnFilterConfig {
+            database: "app".to_string(),
+            table: "users".to_string(),
+            exclude_columns: vec!["password_hash".to_string(), "ssn".to_string()],
+        }]));
+
+        for i in 0..100 {
+            let sql = format!(
+                "INSERT INTO users (id, name, password_hash, email, ssn) VALUES ({i}, 'user{i}', 'hash{i}', 'u{i}@example.com', '000-{i}')"
+            );
+            leader.write(raw_sql(&sql)).await.unwrap();
+        }
+
+        let entries = replicated_entries(&leader).await;
+        assert_eq!(entries.len(), 100);
+        for (i, entry) in entries.iter().enumerate() {
+            match entry {
+                LogEntry::RawSql { sql, .. } => assert_eq!(
+                    sql,
+                    &format!("INSERT INTO users (id, name, email) VALUES ({i}, 'user{i}', 'u{i}@example.com')")
+                ),
+                other => panic!("unexpected entry {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commit_replicates_single_batch() {
+        let dir = tempdir().unwrap();
+        let leader = test_leader(dir.path()).await;
+
+        let txn_id = leader.begin_transaction().await;
+        assert_eq!(leader.transaction_write(txn_id, raw_sql("INSERT INTO t VALUES (1)")).await.unwrap(), 1);
+        assert_eq!(leader.transaction_write(txn_id, raw_sql("UPDATE t SET id = 2")).await.unwrap(), 2);
+
+        // Nothing reaches the WAL until commit
+        assert!(replicated_entries(&leader).await.is_empty());
+
+        let lsn = leader.commit_transaction(txn_id).await.unwrap();
+        assert_eq!(lsn, 1);
+        assert!(leader.transactions().is_empty().await);
+
+        let entries = replicated_entries(&leader).await;
+        assert_eq!(entries.len(), 1);
+        match &entries[0] {
+            LogEntry::Transaction { entries, transaction_id } => {
+                assert_eq!(*transaction_id, Some(txn_id));
+                assert_eq!(entries.len(), 2);
+            }
+            other => panic!("expected transaction entry, got {:?}", other),
+        }
+        assert_eq!(entries[0].to_sql(), vec![
+            "START TRANSACTION",
+            "INSERT INTO t VALUES (1)",
+            "UPDATE t SET id = 2",
+            "COMMIT",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_writes_to_one_database() {
+        let dir = tempdir().unwrap();
+        let leader = test_leader(dir.path()).await;
+
+        let txn_id = leader.begin_transaction().await;
+        leader.transaction_write(txn_id, raw_sql("INSERT INTO t VALUES (1)")).await.unwrap();
+        let elsewhere = |database: Option<&str>| LogEntry::RawSql {
+            sql: "INSERT INTO t VALUES (2)".to_string(),
+            affects_table: None,
+            database: database.map(str::to_string),
+            gtid: None,
+        };
+        for database in [Some("other"), None] {
+            assert!(matches!(
+                leader.transaction_write(txn_id, elsewhere(database)).await,
+                Err(Error::TransactionDatabaseMismatch { expected: Some(ref db), .. }) if db == "app"
+            ));
+        }
+        leader.transaction_write(txn_id, raw_sql("UPDATE t SET id = 2")).await.unwrap();
+        leader.commit_transaction(txn_id).await.unwrap();
+
+        // Followers apply the whole transaction in the database it was written in
+        let entries = replicated_entries(&leader).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].database(), Some("app"));
+        assert!(matches!(&entries[0], LogEntry::Transaction { entries, .. } if entries.len() == 2));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rollback_produces_no_entries() {
+        let dir = tempdir().unwrap();
+        let leader = test_leader(dir.path()).await;
+
+        let txn_id = leader.begin_transaction().await;
+        leader.transaction_write(txn_id, raw_sql("DELETE FROM t")).await.unwrap();
+        assert_eq!(leader.rollback_transaction(txn_id).await.unwrap(), 1);
+
+        assert!(matches!(leader.commit_transaction(txn_id).await, Err(Error::TransactionNotFound(_))));
+        assert!(leader.transaction_write(txn_id, raw_sql("DELETE FROM t")).await.is_err());
+        assert!(replicated_entries(&leader).await.is_empty());
+        assert_eq!(leader.wal_writer.current_lsn().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_timeout_rolls_back() {
+        let dir = tempdir().unwrap();
+        let leader = test_leader(dir.path()).await;
+
+        let stale = leader.begin_transaction().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let fresh = leader.begin_transaction().await;
+
+        assert_eq!(leader.transactions().expire(Duration::from_millis(10)).await, 1);
+        assert_eq!(leader.transactions().len().await, 1);
+        assert!(leader.rollback_transaction(stale).await.is_err());
+        assert!(leader.rollback_transaction(fresh).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_added_follower_acks_count_once_caught_up() {
+        let dir = tempdir().unwrap();
+        let leader = test_leader(dir.path()).await;
+
+        // Counted in the quorum size straight away, replicated from the snapshot
+        leader.admission().add_follower("node-2", "10.0.0.2:7654", 4000).await.unwrap();
+        assert_eq!(leader.cluster.quorum_size().await, 2);
+        assert_eq!(leader.cluster.get_node("node-2").await.unwrap().last_applied_lsn, 4000);
+
+        // More than max_lag_lsn (1000) behind the commit: ACKs are not counted
+        leader.advance_commit_lsn(5000).await.unwrap();
+        leader.record_follower_ack("node-2", 3000).await;
+        assert!(leader.counted_matches().await.is_empty());
+
+        leader.record_follower_ack("node-2", 4000).await;
+        assert_eq!(leader.counted_matches().await, vec![4000]);
+    }
+
+    #[tokio::test]
+    async fn test_restarted_follower_acks_batch_once() {
+        let dir = tempdir().unwrap();
+        let leader = test_leader(dir.path()).await;
+        for i in 2..=5 {
+            leader.cluster.add_peer(format!("node-{}", i), format!("10.0.0.{}:7654", i)).await.unwrap();
+        }
+        let quorum_size = leader.cluster.quorum_size().await;
+        assert_eq!(quorum_size, 3);
+        leader.track_batch(2).await;
+
+        leader.record_follower_ack("node-2", 2).await;
+        // node-2 restarts with nothing applied, then catches up and ACKs again
+        leader.record_follower_ack("node-2", 0).await;
+        leader.record_follower_ack("node-2", 2).await;
+        assert_eq!(leader.commit_batches(quorum_size, 0).await, None);
+
+        leader.record_follower_ack("node-3", 2).await;
+        assert_eq!(leader.commit_batches(quorum_size, 0).await, Some(2));
+    }
+
+    /// Replicate ten entries to two followers that ACK every batch in the
+    /// order it arrives. Returns the LSN ranges each follower received, the
+    /// most batches a follower had in flight and the final commit LSN.
+    async fn replicate_with_depth(pipeline_depth: usize) -> (Vec<Vec<(Lsn, Lsn)>>, usize, Lsn) {
+        let dir = tempdir().unwrap();
+        // ACKs come straight back, so a round trip measured here is only
+        // scheduling delay: keep slow runs from turning followers into WAN peers
+        let config = ReplicationConfig { max_batch_entries: 2, pipeline_depth, wan_rtt_threshold_ms: 0, ..Default::default() };
+        let (leader, mut rx) = test_leader_with(dir.path(), config).await;
+        let followers = [("node-2", "10.0.0.2:7654"), ("node-3", "10.0.0.3:7654")];
+        for (id, address) in followers {
+            leader.cluster.add_peer(id.to_string(), address.to_string()).await.unwrap();
+        }
+        for i in 1..=10 {
+            leader.wal_writer.append(raw_sql(&format!("INSERT INTO t VALUES ({})", i))).await.unwrap();
+        }
+        leader.wal_writer.flush().await.unwrap();
+
+        let mut received = vec![Vec::new(); followers.len()];
+        let mut max_in_flight = 0;
+        let mut commits = vec![0];
+        for _ in 0..20 {
+            leader.replicate_to_followers().await.unwrap();
+            // Every earlier batch was ACKed, so what is pending went out this round
+            let sent: usize = leader.pending_replication.read().await.values().map(VecDeque::len).sum();
+
+            let mut round = vec![Vec::new(); followers.len()];
+            for _ in 0..sent {
+                let (address, msg) = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await
+                    .expect("batch never sent")
+                    .unwrap();
+                let Message::AppendEntries { entries, .. } = msg else { panic!("unexpected {:?}", msg) };
+                let follower = followers.iter().position(|(_, a)| *a == address).unwrap();
+                round[follower].push((entries[0].header.lsn, entries.last().unwrap().header.lsn));
+            }
+            max_in_flight = max_in_flight.max(round.iter().map(Vec::len).max().unwrap());
+
+            for (follower, batches) in round.into_iter().enumerate() {
+                for (first, last) in batches {
+                    leader.handle_append_response(followers[follower].0, 1, true, last).await.unwrap();
+                    commits.push(leader.commit_lsn().await);
+                    received[follower].push((first, last));
+                }
+            }
+            // Both followers have ACKed every batch
+            if received.iter().all(|batches| batches.last().is_some_and(|(_, last)| *last == 10)) {
+                break;
+            }
+        }
+        assert!(rx.try_recv().is_err(), "nothing left unsent");
+        // Commits only ever move forward
+        assert!(commits.windows(2).all(|w| w[0] <= w[1]));
+        (received, max_in_flight, leader.commit_lsn().await)
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_batches_commit_in_order() {
+        let (serial, serial_in_flight, serial_commit) = replicate_with_depth(1).await;
+        let (pipelined, pipelined_in_flight, pipelined_commit) = replicate_with_depth(4).await;
+
+        assert_eq!(serial_in_flight, 1);
+        // The pipeline fills before any ACK arrives
+        assert_eq!(pipelined_in_flight, 4);
+
+        // Both modes deliver the same batches, in LSN order, and commit everything
+        let expected = vec![(1, 2), (3, 4), (5, 6), (7, 8), (9, 10)];
+        assert_eq!(serial, vec![expected.clone(), expected.clone()]);
+        assert_eq!(pipelined, serial);
+        assert_eq!(serial_commit, 10);
+        assert_eq!(pipelined_commit, 10);
+    }
+
+    /// Write 20 entries every 10ms for 1.2s to one follower whose ACKs
+    /// arrive 200ms after each batch is sent, as over a cross-region link.
+    /// Returns the leader, the commit LSN reached and the batches sent.
+    async fn replicate_over_wan(dir: &std::path::Path, wan_rtt_threshold_ms: u64) -> (LeaderNode, Lsn, usize) {
+        let config = ReplicationConfig { max_batch_entries: 10, wan_rtt_threshold_ms, ..Default::default() };
+        let (leader, mut rx) = test_leader_with(dir, config).await;
+        leader.cluster.add_peer("node-2".to_string(), "10.0.0.2:7654".to_string()).await.unwrap();
+
+        let rtt = Duration::from_millis(200);
+        let started = Instant::now();
+        let mut acks: VecDeque<(Instant, Lsn)> = VecDeque::new();
+        let mut batches = 0;
+        let mut i = 0;
+        while started.elapsed() < Duration::from_millis(1200) {
+            for _ in 0..20 {
+                i += 1;
+                leader.wal_writer.append(raw_sql(&format!("INSERT INTO t VALUES ({})", i))).await.unwrap();
+            }
+            leader.wal_writer.flush().await.unwrap();
+
+            while acks.front().is_some_and(|(due, _)| *due <= Instant::now()) {
+                let (_, lsn) = acks.pop_front().unwrap();
+                leader.handle_append_response("node-2", 1, true, lsn).await.unwrap();
+            }
+            leader.replicate_to_followers().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+
+            while let Ok((_, msg)) = rx.try_recv() {
+                let Message::AppendEntries { entries, .. } = msg else { continue };
+                batches += 1;
+                acks.push_back((Instant::now() + rtt, entries.last().unwrap().header.lsn));
+            }
+        }
+        let commit = leader.commit_lsn().await;
+        (leader, commit, batches)
+    }
+
+    #[tokio::test]
+    async fn test_wan_batching_improves_throughput_at_200ms_rtt() {
+        let (lan_dir, wan_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let ((lan, lan_commit, lan_batches), (wan, wan_commit, wan_batches)) = tokio::join!(
+            replicate_over_wan(lan_dir.path(), 0),
+            replicate_over_wan(wan_dir.path(), 50),
+        );
+
+        // The round trip is measured from the ACKs either way, but only
+        // switches the follower to WAN batching when detection is on
+        let measured = wan.peer_rtt.read().await["node-2"];
+        assert!(measured >= Duration::from_millis(200) && measured < Duration::from_millis(400), "rtt {:?}", measured);
+        assert!(wan.is_wan_peer("node-2").await);
+        assert!(!lan.is_wan_peer("node-2").await);
+
+        // One batch of max_batch_entries per round trip vs 16x that
+        assert!(lan_commit > 0 && lan_commit <= 60, "lan commit {}", lan_commit);
+        assert!(wan_commit >= 4 * lan_commit, "wan commit {} vs lan {}", wan_commit, lan_commit);
+        assert!(wan_batches <= lan_batches, "wan {} batches vs lan {}", wan_batches, lan_batches);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_write_rate_limit_applies_to_leader_writes() {
+        for rate_limit_action in [RateLimitAction::Delay, RateLimitAction::Reject] {
+            let dir = tempdir().unwrap();
+            let config = ReplicationConfig { max_writes_per_second: 1_000, rate_limit_action, ..Default::default() };
+            let (leader, _rx) = test_leader_with(dir.path(), config).await;
+
+            // 2000 writes/s from 20 connections for two seconds
+            let start = tokio::time::Instant::now();
+            let committed = futures::future::join_all((0..20).map(|w| {
+                let leader = &leader;
+                async move {
+                    let mut ticker = interval(Duration::from_millis(10));
+                    let mut committed = 0u64;
+                    for i in 0..200 {
+                        ticker.tick().await;
+                        if leader.write(raw_sql(&format!("INSERT INTO t VALUES ({}, {})", w, i))).await.is_ok() {
+                            committed += 1;
+                        }
+                    }
+                    committed
+                }
+            })).await;
+            let rate = committed.iter().sum::<u64>() as f64 / start.elapsed().as_secs_f64();
+            assert!((900.0..=1_100.0).contains(&rate), "{:?}: committed {:.0} writes/s", rate_limit_action, rate);
+        }
     }
 }
@@ -5,10 +5,25 @@
 pub mod protocol;
 mod leader;
 mod follower;
+mod transaction;
+mod read_barrier;
+mod rate_limit;
+mod snapshot;
+mod bootstrap;
+mod validation;
 
-pub use protocol::{Message, FrameHeader};
-pub use leader::LeaderNode;
+pub use protocol::{Message, FrameHeader, PROTOCOL_VERSION, LEGACY_PROTOCOL_VERSION};
+pub use leader::{FollowerAdmission, LeaderNode};
 pub use follower::{FollowerNode, ReplicationBatch};
+pub use transaction::{TransactionBuffer, TRANSACTION_TIMEOUT};
+pub use read_barrier::{ReadBarrier, ReadConsistency};
+pub use rate_limit::{RateLimitAction, WriteRateLimiter};
+pub use validation::{validate_entry, ValidationError};
+pub use snapshot::{create_snapshot, load_snapshot, send_snapshot, SnapshotReceiver};
+pub use bootstrap::{
+    bootstrap_from_http, snapshot_content_type, snapshot_stream, SnapshotDecoder, SnapshotDownload, SnapshotHeader,
+    SnapshotPart,
+};
 
 /// Configuration for replication
 #[derive(Debug, Clone)]
@@ -19,6 +34,22 @@ pub struct ReplicationConfig {
     pub heartbeat_interval_ms: u64,
     /// Replication timeout in milliseconds
     pub replication_timeout_ms: u64,
+    /// Concurrent workers used by followers to apply a batch
+    pub parallel_apply_workers: usize,
+    /// Lag (in entries) below which an added node's ACKs count towards quorum
+    pub max_lag_lsn: u64,
+    /// AppendEntries batches kept in flight per follower (1 = no pipelining)
+    pub pipeline_depth: usize,
+    /// Batch replication to every follower as if it were cross-region
+    pub wan_mode: bool,
+    /// How long entries are collected for a WAN follower before sending
+    pub wan_batch_timeout_ms: u64,
+    /// Round trip above which a follower is treated as WAN (0 = never)
+    pub wan_rtt_threshold_ms: u64,
+    /// Writes per second the leader accepts (0 = unlimited)
+    pub max_writes_per_second: u64,
+    /// What happens to writes over `max_writes_per_second`
+    pub rate_limit_action: RateLimitAction,
 }
 
 impl Default for ReplicationConfig {
@@ -27,6 +58,14 @@ impl Default for ReplicationConfig {
             max_batch_entries: 1000,
             heartbeat_interval_ms: 500,
             replication_timeout_ms: 5000,
+            parallel_apply_workers: 4,
+            max_lag_lsn: 1000,
+            pipeline_depth: 1,
+            wan_mode: false,
+            wan_batch_timeout_ms: 200,
+            wan_rtt_threshold_ms: 50,
+            max_writes_per_second: 10_000,
+            rate_limit_action: RateLimitAction::Reject,
         }
     }
 }
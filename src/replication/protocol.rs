@@ -5,8 +5,25 @@
 use serde::{Deserialize, Serialize};
 
 use crate::wal::entry::{Lsn, WalEntry};
+use crate::wal::legacy::LegacyWalEntry;
 use crate::state::NodeState;
 
+/// Wire protocol version this build speaks, carried in every frame header.
+/// Version 0 is the layout from before versioning: entries in the version 1
+/// WAL layout and no message types past `Error`.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// Protocol version of nodes that predate versioning
+pub const LEGACY_PROTOCOL_VERSION: u8 = 0;
+
+/// Variant indices of the messages whose layout differs in version 0
+const APPEND_ENTRIES_VARIANT: u32 = 2;
+const SYNC_RESPONSE_VARIANT: u32 = 7;
+const WRITE_FORWARD_VARIANT: u32 = 20;
+
+/// Index of the first variant version 0 does not have
+const FIRST_V1_VARIANT: u32 = 23;
+
 /// Protocol messages for node communication
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
@@ -176,6 +193,35 @@ pub enum Message {
         code: ErrorCode,
         message: String,
     },
+
+    // New variants go below this line: bincode numbers variants by
+    // position, and protocol version 0 peers know everything above it.
+
+    // ========== Read Consistency ==========
+    /// Read barrier (from leader to followers) - confirms the leader still
+    /// leads and that followers have applied `lsn` before a strong read
+    ReadBarrier {
+        barrier_id: u64,
+        leader_id: String,
+        lsn: Lsn,
+    },
+
+    /// Read barrier response with the follower's applied LSN
+    ReadBarrierResponse {
+        barrier_id: u64,
+        node_id: String,
+        last_applied_lsn: Lsn,
+    },
+
+    // ========== Log Replication ==========
+    /// Append entries refused by the follower after `match_lsn`, with the
+    /// reason (`validation_failed`). The batch stays unacknowledged.
+    AppendEntriesRejected {
+        node_id: String,
+        term: u64,
+        match_lsn: Lsn,
+        reason: String,
+    },
 }
 
 /// Error codes for protocol errors
@@ -206,6 +252,78 @@ impl Message {
         bincode::deserialize(bytes)
     }
 
+    /// Serialize message for a peer speaking protocol `version`. Fails for
+    /// messages and entries the version has no room for.
+    pub fn serialize_for(&self, version: u8) -> Result<Vec<u8>, bincode::Error> {
+        if version >= PROTOCOL_VERSION {
+            return self.serialize();
+        }
+
+        match self {
+            Message::AppendEntries { term, leader_id, prev_lsn, prev_term, entries, leader_commit_lsn } => {
+                bincode::serialize(&(
+                    APPEND_ENTRIES_VARIANT, term, leader_id, prev_lsn, prev_term,
+                    legacy_entries(entries)?, leader_commit_lsn,
+                ))
+            }
+            Message::SyncResponse { from_lsn, entries, has_more } => {
+                bincode::serialize(&(SYNC_RESPONSE_VARIANT, from_lsn, legacy_entries(entries)?, has_more))
+            }
+            Message::WriteForward { entry, client_id } => {
+                let entry = LegacyWalEntry::try_from(entry).map_err(unsupported)?;
+                bincode::serialize(&(WRITE_FORWARD_VARIANT, entry, client_id))
+            }
+            _ => {
+                let bytes = self.serialize()?;
+                if variant_index(&bytes)? >= FIRST_V1_VARIANT {
+                    return Err(unsupported(format!(
+                        "{} needs protocol version {}", self.type_name(), PROTOCOL_VERSION
+                    )));
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Deserialize a message sent by a peer speaking protocol `version`
+    pub fn deserialize_from(bytes: &[u8], version: u8) -> Result<Self, bincode::Error> {
+        if version >= PROTOCOL_VERSION {
+            return Self::deserialize(bytes);
+        }
+
+        match variant_index(bytes)? {
+            APPEND_ENTRIES_VARIANT => {
+                let (_, term, leader_id, prev_lsn, prev_term, entries, leader_commit_lsn):
+                    (u32, u64, String, Lsn, u64, Vec<LegacyWalEntry>, Lsn) = bincode::deserialize(bytes)?;
+                Ok(Message::AppendEntries {
+                    term,
+                    leader_id,
+                    prev_lsn,
+                    prev_term,
+                    entries: entries.into_iter().map(WalEntry::from).collect(),
+                    leader_commit_lsn,
+                })
+            }
+            SYNC_RESPONSE_VARIANT => {
+                let (_, from_lsn, entries, has_more): (u32, Lsn, Vec<LegacyWalEntry>, bool) =
+                    bincode::deserialize(bytes)?;
+                Ok(Message::SyncResponse {
+                    from_lsn,
+                    entries: entries.into_iter().map(WalEntry::from).collect(),
+                    has_more,
+                })
+            }
+            WRITE_FORWARD_VARIANT => {
+                let (_, entry, client_id): (u32, LegacyWalEntry, String) = bincode::deserialize(bytes)?;
+                Ok(Message::WriteForward { entry: entry.into(), client_id })
+            }
+            index if index >= FIRST_V1_VARIANT => Err(unsupported(format!(
+                "message type {} is not part of protocol version {}", index, version
+            ))),
+            _ => Self::deserialize(bytes),
+        }
+    }
+
     /// Get the message type name (for logging)
     pub fn type_name(&self) -> &'static str {
         match self {
@@ -231,11 +349,29 @@ impl Message {
             Message::StatusResponse { .. } => "StatusResponse",
             Message::WriteForward { .. } => "WriteForward",
             Message::WriteForwardResponse { .. } => "WriteForwardResponse",
+            Message::ReadBarrier { .. } => "ReadBarrier",
+            Message::ReadBarrierResponse { .. } => "ReadBarrierResponse",
+            Message::AppendEntriesRejected { .. } => "AppendEntriesRejected",
             Message::Error { .. } => "Error",
         }
     }
 }
 
+/// Leading variant index of a serialized message
+fn variant_index(bytes: &[u8]) -> Result<u32, bincode::Error> {
+    bincode::deserialize(bytes)
+}
+
+fn legacy_entries(entries: &[WalEntry]) -> Result<Vec<LegacyWalEntry>, bincode::Error> {
+    entries.iter()
+        .map(|e| LegacyWalEntry::try_from(e).map_err(unsupported))
+        .collect()
+}
+
+fn unsupported(reason: impl ToString) -> bincode::Error {
+    Box::new(bincode::ErrorKind::Custom(reason.to_string()))
+}
+
 /// Frame header for length-prefixed messages
 #[derive(Debug, Clone, Copy)]
 pub struct FrameHeader {
@@ -243,26 +379,54 @@ pub struct FrameHeader {
     pub length: u64,
     /// Message checksum
     pub checksum: u32,
+    /// `FLAG_*` bits describing the body
+    pub flags: u8,
+    /// Protocol version the body is encoded in (0 for nodes that predate
+    /// versioning, which leave this byte zero)
+    pub version: u8,
 }
 
 impl FrameHeader {
-    /// Header size in bytes (8 for u64 length + 4 for checksum + 4 padding = 16)
+    /// Header size in bytes (8 for u64 length + 4 for checksum + 1 for flags + 1 for version + 2 padding = 16)
     pub const SIZE: usize = 16;
 
+    /// The body is gzip-compressed (`cluster.wan_compression`)
+    pub const FLAG_GZIP: u8 = 0x01;
+
     /// Create a new frame header
     pub fn new(data: &[u8]) -> Self {
         Self {
             length: data.len() as u64,
             checksum: crc32fast::hash(data),
+            flags: 0,
+            version: PROTOCOL_VERSION,
         }
     }
 
+    /// Mark the body as encoded in protocol `version`
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Header for a gzip-compressed body
+    pub fn gzip(data: &[u8]) -> Self {
+        Self { flags: Self::FLAG_GZIP, ..Self::new(data) }
+    }
+
+    /// Whether the body is gzip-compressed
+    pub fn is_gzip(&self) -> bool {
+        self.flags & Self::FLAG_GZIP != 0
+    }
+
     /// Serialize header to bytes
     pub fn to_bytes(&self) -> [u8; Self::SIZE] {
         let mut bytes = [0u8; Self::SIZE];
         bytes[0..8].copy_from_slice(&self.length.to_le_bytes());
         bytes[8..12].copy_from_slice(&self.checksum.to_le_bytes());
-        // bytes[12..16] reserved for future use
+        bytes[12] = self.flags;
+        bytes[13] = self.version;
+        // bytes[14..16] reserved for future use
         bytes
     }
 
@@ -271,6 +435,8 @@ impl FrameHeader {
         Self {
             length: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
             checksum: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            flags: bytes[12],
+            version: bytes[13],
         }
     }
 }
@@ -285,13 +451,14 @@ mod tests {
             term: 1,
             leader_id: "node-1".to_string(),
             commit_lsn: 100,
+            members: Vec::new(),
         };
 
         let bytes = msg.serialize().unwrap();
         let restored = Message::deserialize(&bytes).unwrap();
 
         match restored {
-            Message::Heartbeat { term, leader_id, commit_lsn } => {
+            Message::Heartbeat { term, leader_id, commit_lsn, .. } => {
                 assert_eq!(term, 1);
                 assert_eq!(leader_id, "node-1");
                 assert_eq!(commit_lsn, 100);
@@ -300,6 +467,82 @@ mod tests {
         }
     }
 
+    /// Written by a build from before protocol versioning
+    const V0_APPEND_ENTRIES: &[u8] = include_bytes!("testdata/append_entries_v0.bin");
+    const V0_ERROR: &[u8] = include_bytes!("testdata/error_v0.bin");
+    const V0_APPEND_ENTRIES_RESPONSE: &[u8] = include_bytes!("testdata/append_entries_response_v0.bin");
+
+    fn read_barrier() -> Message {
+        Message::ReadBarrier { barrier_id: 1, leader_id: "node-1".to_string(), lsn: 5 }
+    }
+
+    #[test]
+    fn test_variant_indices() {
+        let index = |msg: &Message| variant_index(&msg.serialize().unwrap()).unwrap();
+        let batch = Message::AppendEntries {
+            term: 1, leader_id: String::new(), prev_lsn: 0, prev_term: 0, entries: Vec::new(), leader_commit_lsn: 0,
+        };
+        let sync = Message::SyncResponse { from_lsn: 0, entries: Vec::new(), has_more: false };
+        let forward = Message::WriteForward {
+            entry: WalEntry::new(1, 1, String::new(), crate::wal::LogEntry::Noop),
+            client_id: String::new(),
+        };
+        let error = Message::Error { code: ErrorCode::Internal, message: String::new() };
+
+        assert_eq!(index(&batch), APPEND_ENTRIES_VARIANT);
+        assert_eq!(index(&sync), SYNC_RESPONSE_VARIANT);
+        assert_eq!(index(&forward), WRITE_FORWARD_VARIANT);
+        assert_eq!(index(&error), FIRST_V1_VARIANT - 1);
+        assert_eq!(index(&read_barrier()), FIRST_V1_VARIANT);
+    }
+
+    #[test]
+    fn test_decode_version_0_messages() {
+        match Message::deserialize_from(V0_APPEND_ENTRIES, LEGACY_PROTOCOL_VERSION).unwrap() {
+            Message::AppendEntries { term, leader_id, entries, .. } => {
+                assert_eq!((term, leader_id.as_str()), (2, "node-1"));
+                assert_eq!(entries.iter().map(|e| e.header.lsn).collect::<Vec<_>>(), vec![1, 2, 3]);
+                assert!(entries.iter().all(|e| e.verify_checksum()));
+            }
+            other => panic!("unexpected message {}", other.type_name()),
+        }
+
+        match Message::deserialize_from(V0_APPEND_ENTRIES_RESPONSE, LEGACY_PROTOCOL_VERSION).unwrap() {
+            Message::AppendEntriesResponse { node_id, term, success, match_lsn } => {
+                assert_eq!((node_id.as_str(), term, success, match_lsn), ("node-2", 2, true, 3));
+            }
+            other => panic!("unexpected message {}", other.type_name()),
+        }
+
+        match Message::deserialize_from(V0_ERROR, LEGACY_PROTOCOL_VERSION).unwrap() {
+            Message::Error { code, message } => {
+                assert_eq!(code, ErrorCode::NotLeader);
+                assert_eq!(message, "not the leader");
+            }
+            other => panic!("unexpected message {}", other.type_name()),
+        }
+    }
+
+    #[test]
+    fn test_encode_for_version_0() {
+        let batch = Message::deserialize_from(V0_APPEND_ENTRIES, LEGACY_PROTOCOL_VERSION).unwrap();
+        assert_eq!(batch.serialize_for(LEGACY_PROTOCOL_VERSION).unwrap(), V0_APPEND_ENTRIES);
+
+        assert!(read_barrier().serialize_for(LEGACY_PROTOCOL_VERSION).is_err());
+        let rejected = Message::AppendEntriesRejected {
+            node_id: "node-2".to_string(),
+            term: 2,
+            match_lsn: 1,
+            reason: "validation_failed".to_string(),
+        };
+        assert!(rejected.serialize_for(LEGACY_PROTOCOL_VERSION).is_err());
+        assert!(Message::deserialize_from(&read_barrier().serialize().unwrap(), LEGACY_PROTOCOL_VERSION).is_err());
+        assert!(matches!(
+            Message::deserialize_from(&read_barrier().serialize_for(PROTOCOL_VERSION).unwrap(), PROTOCOL_VERSION).unwrap(),
+            Message::ReadBarrier { barrier_id: 1, lsn: 5, .. }
+        ));
+    }
+
     #[test]
     fn test_frame_header() {
         let data = b"test message data";
@@ -309,5 +552,9 @@ mod tests {
 
         assert_eq!(header.length, restored.length);
         assert_eq!(header.checksum, restored.checksum);
+        assert!(!restored.is_gzip());
+        assert_eq!(restored.version, PROTOCOL_VERSION);
+        assert_eq!(FrameHeader::from_bytes(&header.with_version(0).to_bytes()).version, 0);
+        assert!(FrameHeader::from_bytes(&FrameHeader::gzip(data).to_bytes()).is_gzip());
     }
 }
@@ -0,0 +1,228 @@
+//! Cluster-wide write rate limiting
+//!
+//! Every write accepted by the leader takes a token from one bucket, whatever
+//! connection it arrived on. The bucket refills at `cluster.max_writes_per_second`
+//! and holds a tenth of a second's worth, so bursts stay short. A write that
+//! finds no token is refused (`reject`, HTTP 429) or waits for one (`delay`).
+
+use std::sync::Mutex;
+use std::time::Duration;
+// Tokio's clock, so tests can pause and advance time
+use tokio::time::Instant;
+
+use crate::error::{Error, Result};
+use crate::metrics;
+
+/// What happens to a write over `cluster.max_writes_per_second`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAction {
+    /// Fail the write with `Error::RateLimited`
+    Reject,
+    /// Hold the write until a token is available
+    Delay,
+}
+
+impl RateLimitAction {
+    /// Parse the `cluster.rate_limit_action` config value
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "reject" => Some(RateLimitAction::Reject),
+            "delay" => Some(RateLimitAction::Delay),
+            _ => None,
+        }
+    }
+}
+
+/// Token bucket shared by all writes
+struct RateLimiter {
+    tokens: f64,
+    last_refill: Instant,
+    rate: f64,
+}
+
+impl RateLimiter {
+    fn burst(&self) -> f64 {
+        (self.rate / 10.0).max(1.0)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst());
+        self.last_refill = now;
+    }
+
+    /// Take a token if one is available
+    fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Reserve a token, returning how long to wait until it is ours. Tokens
+    /// go negative while writes are queued, so waiters are served in order.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Writes admitted since `started`, for `wolfscale_writes_per_second`
+struct WriteWindow {
+    started: Instant,
+    writes: u64,
+}
+
+/// Leader-wide write rate limit
+pub struct WriteRateLimiter {
+    action: RateLimitAction,
+    /// None when `max_writes_per_second` is 0 (unlimited)
+    bucket: Option<Mutex<RateLimiter>>,
+    window: Mutex<WriteWindow>,
+}
+
+impl WriteRateLimiter {
+    /// Allow `max_writes_per_second` writes per second (0 = unlimited)
+    pub fn new(max_writes_per_second: u64, action: RateLimitAction) -> Self {
+        let now = Instant::now();
+        let bucket = (max_writes_per_second > 0).then(|| {
+            let mut limiter = RateLimiter { tokens: 0.0, last_refill: now, rate: max_writes_per_second as f64 };
+            limiter.tokens = limiter.burst();
+            Mutex::new(limiter)
+        });
+        Self {
+            action,
+            bucket,
+            window: Mutex::new(WriteWindow { started: now, writes: 0 }),
+        }
+    }
+
+    /// Take a token for one write, waiting or failing when over the limit
+    pub async fn acquire(&self) -> Result<()> {
+        if let Some(ref bucket) = self.bucket {
+            match self.action {
+                RateLimitAction::Reject => {
+                    let mut limiter = bucket.lock().unwrap();
+                    if !limiter.try_take(Instant::now()) {
+                        metrics::RATE_LIMIT_REJECTIONS.inc();
+                        let retry_after_ms = ((1.0 - limiter.tokens) / limiter.rate * 1000.0).ceil() as u64;
+                        return Err(Error::RateLimited { retry_after_ms });
+                    }
+                }
+                RateLimitAction::Delay => {
+                    let wait = bucket.lock().unwrap().reserve(Instant::now());
+                    if !wait.is_zero() {
+                        metrics::RATE_LIMIT_DELAYS.inc();
+                        tokio::time::sleep(wait).await;
+                    }
+                }
+            }
+        }
+
+        self.window.lock().unwrap().writes += 1;
+        self.update_rate();
+        Ok(())
+    }
+
+    /// Publish the write rate once the current one-second window is over.
+    /// Also called from the leader's heartbeat so the gauge drops to zero
+    /// when writes stop.
+    pub fn update_rate(&self) {
+        let now = Instant::now();
+        let mut window = self.window.lock().unwrap();
+        let elapsed = now.saturating_duration_since(window.started);
+        if elapsed >= Duration::from_secs(1) {
+            metrics::WRITES_PER_SECOND.set(window.writes as f64 / elapsed.as_secs_f64());
+            window.started = now;
+            window.writes = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_steady_rate() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter { tokens: 100.0, last_refill: start, rate: 1000.0 };
+
+        // A tenth of a second's worth up front
+        for _ in 0..100 {
+            assert!(limiter.try_take(start));
+        }
+        assert!(!limiter.try_take(start));
+
+        // 1000 per second: one token per millisecond
+        assert!(limiter.try_take(start + Duration::from_millis(1)));
+        assert!(!limiter.try_take(start + Duration::from_millis(1)));
+
+        // Never more than the burst after idling
+        let later = start + Duration::from_secs(10);
+        for _ in 0..100 {
+            assert!(limiter.try_take(later));
+        }
+        assert!(!limiter.try_take(later));
+    }
+
+    #[test]
+    fn test_reservations_queue_in_order() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter { tokens: 1.0, last_refill: start, rate: 1.0 };
+
+        assert_eq!(limiter.reserve(start), Duration::ZERO);
+        assert_eq!(limiter.reserve(start), Duration::from_secs(1));
+        assert_eq!(limiter.reserve(start), Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_reject_when_empty() {
+        let limiter = WriteRateLimiter::new(10, RateLimitAction::Reject);
+        limiter.acquire().await.unwrap();
+        match limiter.acquire().await {
+            Err(Error::RateLimited { retry_after_ms }) => assert!(retry_after_ms <= 100),
+            other => panic!("expected RateLimited, got {:?}", other),
+        }
+
+        // Unlimited never refuses
+        let unlimited = WriteRateLimiter::new(0, RateLimitAction::Reject);
+        for _ in 0..1000 {
+            unlimited.acquire().await.unwrap();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_holds_at_20k_writes_per_second() {
+        for action in [RateLimitAction::Delay, RateLimitAction::Reject] {
+            let limiter = WriteRateLimiter::new(10_000, action);
+
+            // 200 connections each offering 100 writes/s for two seconds
+            let start = Instant::now();
+            let admitted = futures::future::join_all((0..200).map(|_| {
+                let limiter = &limiter;
+                async move {
+                    let mut ticker = tokio::time::interval(Duration::from_millis(10));
+                    let mut admitted = 0u64;
+                    for _ in 0..200 {
+                        ticker.tick().await;
+                        if limiter.acquire().await.is_ok() {
+                            admitted += 1;
+                        }
+                    }
+                    admitted
+                }
+            })).await;
+            let rate = admitted.iter().sum::<u64>() as f64 / start.elapsed().as_secs_f64();
+            assert!((9_000.0..=11_000.0).contains(&rate), "{:?}: admitted {:.0} writes/s", action, rate);
+        }
+    }
+}
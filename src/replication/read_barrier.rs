@@ -0,0 +1,309 @@
+//! Read Consistency
+//!
+//! `GET /read` serves a query at one of three consistency levels:
+//!
+//! - `eventual` (default): run the query on the local database right away.
+//! - `session`: first wait until this node has applied the LSN of the
+//!   client's last write, carried in a session cookie.
+//! - `strong`: only the leader serves the read. It sends a `ReadBarrier`
+//!   for its current LSN to every follower and waits until a majority of the
+//!   cluster (counting itself) reports having applied that LSN. Followers
+//!   only answer barriers from the leader they follow, so a deposed leader
+//!   cannot gather a majority and never serves a stale read.
+//!
+//! Both waits give up after `cluster.read_barrier_timeout_ms`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, Notify};
+use tokio::time::Instant;
+
+use super::protocol::Message;
+use crate::error::{Error, Result};
+use crate::state::ClusterMembership;
+use crate::wal::entry::Lsn;
+
+/// How often a barrier is resent to followers that have not confirmed it
+const BARRIER_RESEND_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often a session read re-checks the local applied LSN
+const SESSION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Consistency level of a read (`?consistency=`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadConsistency {
+    /// Serve immediately from the local database
+    #[default]
+    Eventual,
+    /// Wait for the local node to apply the session's last write
+    Session,
+    /// Leader confirms a majority has applied its current LSN
+    Strong,
+}
+
+/// A barrier waiting for confirmations
+struct PendingBarrier {
+    lsn: Lsn,
+    /// Followers that reported an applied LSN of at least `lsn`
+    confirmed: HashSet<String>,
+}
+
+/// Coordinates read barriers on the leader and local LSN waits on any node
+pub struct ReadBarrier {
+    node_id: String,
+    cluster: Arc<ClusterMembership>,
+    /// Outgoing message channel: (target address, message)
+    sender: mpsc::Sender<(String, Message)>,
+    timeout: Duration,
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, PendingBarrier>>,
+    /// Signalled whenever a pending barrier gains a confirmation
+    confirmations: Notify,
+}
+
+impl ReadBarrier {
+    /// Create a read barrier coordinator
+    pub fn new(
+        node_id: String,
+        cluster: Arc<ClusterMembership>,
+        sender: mpsc::Sender<(String, Message)>,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            node_id,
+            cluster,
+            sender,
+            timeout,
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            confirmations: Notify::new(),
+        }
+    }
+
+    /// Wait until a majority of the cluster, counting this node, has applied
+    /// `lsn`. Fails with `QuorumNotReached` after the timeout.
+    pub async fn wait_for_quorum(&self, lsn: Lsn) -> Result<()> {
+        let barrier_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.pending.lock().unwrap().insert(barrier_id, PendingBarrier {
+            lsn,
+            confirmed: HashSet::new(),
+        });
+
+        let required = self.cluster.quorum_size().await;
+        let result = tokio::time::timeout(self.timeout, self.gather(barrier_id, lsn, required)).await;
+
+        let confirmed = self.pending.lock().unwrap()
+            .remove(&barrier_id)
+            .map(|b| b.confirmed.len())
+            .unwrap_or(0);
+        result.map_err(|_| {
+            tracing::warn!("Read barrier for LSN {} timed out: {}/{} nodes confirmed", lsn, confirmed + 1, required);
+            Error::QuorumNotReached { reached: confirmed + 1, required }
+        })
+    }
+
+    /// Send the barrier to unconfirmed followers until enough have confirmed
+    async fn gather(&self, barrier_id: u64, lsn: Lsn, required: usize) {
+        let mut next_send = Instant::now();
+        loop {
+            // Register for wakeups before checking, so no confirmation is missed
+            let notified = self.confirmations.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let confirmed = self.confirmed(barrier_id);
+            if confirmed.len() + 1 >= required {
+                return;
+            }
+
+            if Instant::now() >= next_send {
+                for peer in self.cluster.peers().await {
+                    if confirmed.contains(&peer.id) {
+                        continue;
+                    }
+                    let message = Message::ReadBarrier {
+                        barrier_id,
+                        leader_id: self.node_id.clone(),
+                        lsn,
+                    };
+                    let _ = self.sender.send((peer.address.clone(), message)).await;
+                }
+                next_send = Instant::now() + BARRIER_RESEND_INTERVAL;
+            }
+
+            let _ = tokio::time::timeout_at(next_send, notified).await;
+        }
+    }
+
+    fn confirmed(&self, barrier_id: u64) -> HashSet<String> {
+        self.pending.lock().unwrap()
+            .get(&barrier_id)
+            .map(|b| b.confirmed.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record a follower's answer to a barrier
+    pub fn record_response(&self, barrier_id: u64, node_id: &str, last_applied_lsn: Lsn) {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(barrier) = pending.get_mut(&barrier_id) else {
+            return;
+        };
+        // A follower that is still behind is asked again on the next resend
+        if last_applied_lsn >= barrier.lsn && barrier.confirmed.insert(node_id.to_string()) {
+            drop(pending);
+            self.confirmations.notify_waiters();
+        }
+    }
+
+    /// Wait until this node has applied `lsn`, returning its applied LSN.
+    /// Fails with `SyncFailed` after the timeout.
+    pub async fn wait_for_applied(&self, lsn: Lsn) -> Result<Lsn> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let applied = self.cluster.get_self().await.last_applied_lsn;
+            if applied >= lsn {
+                return Ok(applied);
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::SyncFailed {
+                    node_id: self.node_id.clone(),
+                    entries_behind: lsn - applied,
+                });
+            }
+            tokio::time::sleep(SESSION_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn three_node_cluster() -> Arc<ClusterMembership> {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "10.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+        ));
+        cluster.add_peer("node-2".to_string(), "10.0.0.2:7654".to_string()).await.unwrap();
+        cluster.add_peer("node-3".to_string(), "10.0.0.3:7654".to_string()).await.unwrap();
+        cluster
+    }
+
+    fn barrier(cluster: &Arc<ClusterMembership>, timeout_ms: u64) -> (Arc<ReadBarrier>, mpsc::Receiver<(String, Message)>) {
+        let (tx, rx) = mpsc::channel(100);
+        let barrier = ReadBarrier::new("node-1".to_string(), Arc::clone(cluster), tx, Duration::from_millis(timeout_ms));
+        (Arc::new(barrier), rx)
+    }
+
+    #[test]
+    fn test_consistency_parsing() {
+        #[derive(Deserialize)]
+        struct Params {
+            #[serde(default)]
+            consistency: ReadConsistency,
+        }
+        let parse = |json: &str| serde_json::from_str::<Params>(json).map(|p| p.consistency);
+        assert_eq!(parse("{}").unwrap(), ReadConsistency::Eventual);
+        assert_eq!(parse(r#"{"consistency": "eventual"}"#).unwrap(), ReadConsistency::Eventual);
+        assert_eq!(parse(r#"{"consistency": "session"}"#).unwrap(), ReadConsistency::Session);
+        assert_eq!(parse(r#"{"consistency": "strong"}"#).unwrap(), ReadConsistency::Strong);
+        assert!(parse(r#"{"consistency": "linearizable"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_strong_read_waits_for_majority() {
+        let cluster = three_node_cluster().await;
+        let (barrier, mut rx) = barrier(&cluster, 2000);
+
+        let waiter = tokio::spawn({
+            let barrier = Arc::clone(&barrier);
+            async move { barrier.wait_for_quorum(10).await }
+        });
+
+        // Both followers are asked
+        let mut asked = Vec::new();
+        let mut barrier_id = 0;
+        for _ in 0..2 {
+            let (addr, message) = rx.recv().await.unwrap();
+            match message {
+                Message::ReadBarrier { barrier_id: id, leader_id, lsn } => {
+                    assert_eq!((leader_id.as_str(), lsn), ("node-1", 10));
+                    barrier_id = id;
+                }
+                other => panic!("unexpected {}", other.type_name()),
+            }
+            asked.push(addr);
+        }
+        asked.sort();
+        assert_eq!(asked, ["10.0.0.2:7654", "10.0.0.3:7654"]);
+
+        // A follower that has not applied LSN 10 yet does not count
+        barrier.record_response(barrier_id, "node-2", 9);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        // Once it catches up (answering the resent barrier), 2 of 3 is a majority
+        barrier.record_response(barrier_id, "node-2", 10);
+        waiter.await.unwrap().unwrap();
+        assert!(barrier.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_strong_read_fails_without_majority() {
+        // A deposed leader: followers answer the new leader, not this one
+        let cluster = three_node_cluster().await;
+        let (barrier, _rx) = barrier(&cluster, 100);
+        let err = barrier.wait_for_quorum(10).await.unwrap_err();
+        assert!(matches!(err, Error::QuorumNotReached { reached: 1, required: 2 }));
+
+        // Late answers to a finished barrier are ignored
+        barrier.record_response(1, "node-2", 10);
+        assert!(barrier.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_single_node_needs_no_barrier() {
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "10.0.0.1:7654".to_string(),
+            Duration::from_secs(5),
+            Duration::from_secs(10),
+        ));
+        let (barrier, mut rx) = barrier(&cluster, 100);
+        barrier.wait_for_quorum(10).await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_read_waits_for_local_apply() {
+        let cluster = three_node_cluster().await;
+        cluster.record_heartbeat("node-1", 5).await.unwrap();
+        let (barrier, _rx) = barrier(&cluster, 2000);
+
+        // Already applied: no wait
+        assert_eq!(barrier.wait_for_applied(5).await.unwrap(), 5);
+
+        // Waits for replication to apply the session's last write
+        let applier = tokio::spawn({
+            let cluster = Arc::clone(&cluster);
+            async move {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                cluster.record_heartbeat("node-1", 8).await.unwrap();
+            }
+        });
+        assert_eq!(barrier.wait_for_applied(8).await.unwrap(), 8);
+        applier.await.unwrap();
+
+        // Never applied: times out rather than serving stale data
+        let (barrier, _rx) = self::barrier(&cluster, 50);
+        let err = barrier.wait_for_applied(20).await.unwrap_err();
+        assert!(matches!(err, Error::SyncFailed { entries_behind: 12, .. }));
+    }
+}
@@ -0,0 +1,193 @@
+//! Snapshot Transfer
+//!
+//! A node added to a running cluster with `POST /admin/add-node` starts from
+//! a snapshot instead of replaying the whole WAL (which may have been
+//! compacted or expired). The leader records its current LSN, dumps its
+//! database with `mysqldump --single-transaction` and streams the dump to
+//! the new node as `FullSyncStart`, `FullSyncChunk`... and
+//! `FullSyncComplete`. The new node loads it with the `mysql` client and
+//! then follows the leader from the snapshot LSN onwards.
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::protocol::Message;
+use crate::config::DatabaseConfig;
+use crate::error::{Error, Result};
+use crate::network::NetworkClient;
+use crate::wal::entry::Lsn;
+
+/// Bytes of dump carried by each `FullSyncChunk`
+const SNAPSHOT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Build a mysql/mysqldump command connecting to the local database
+fn db_command(program: &str, db: &DatabaseConfig) -> Command {
+    let mut command = Command::new(program);
+    command
+        .arg("-h").arg(&db.host)
+        .arg("-P").arg(db.port.to_string())
+        .arg("-u").arg(&db.user)
+        // Keep the password off the command line (visible in ps)
+        .env("MYSQL_PWD", &db.password)
+        .kill_on_drop(true);
+    command
+}
+
+/// Dump the local database
+pub async fn create_snapshot(db: &DatabaseConfig) -> Result<Vec<u8>> {
+    let mut command = db_command("mysqldump", db);
+    command.args(["--single-transaction", "--routines", "--triggers", "--events"]);
+    match &db.database {
+        Some(database) => command.arg("--databases").arg(database),
+        None => command.arg("--all-databases"),
+    };
+
+    let output = command.output().await
+        .map_err(|e| Error::Replication(format!("Failed to run mysqldump: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::Replication(format!(
+            "mysqldump failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Load a dump into the local database
+pub async fn load_snapshot(db: &DatabaseConfig, dump: &[u8]) -> Result<()> {
+    let mut child = db_command("mysql", db)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Replication(format!("Failed to run mysql: {}", e)))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let written = stdin.write_all(dump).await;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        return Err(Error::Replication(format!(
+            "Failed to load snapshot: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    written?;
+    Ok(())
+}
+
+/// The messages carrying a dump taken at `snapshot_lsn`, in order
+fn snapshot_messages(snapshot_lsn: Lsn, dump: &[u8]) -> Vec<Message> {
+    // An empty dump is still sent as one (empty) chunk
+    let chunks: Vec<&[u8]> = if dump.is_empty() {
+        vec![&[]]
+    } else {
+        dump.chunks(SNAPSHOT_CHUNK_SIZE).collect()
+    };
+    let last = chunks.len() - 1;
+
+    let mut messages = vec![Message::FullSyncStart { tables: Vec::new(), snapshot_lsn }];
+    messages.extend(chunks.into_iter().enumerate().map(|(i, data)| Message::FullSyncChunk {
+        table: String::new(),
+        data: data.to_vec(),
+        is_last: i == last,
+    }));
+    messages.push(Message::FullSyncComplete { snapshot_lsn });
+    messages
+}
+
+/// Stream a dump taken at `snapshot_lsn` to the node at `address`
+///
+/// The messages share one connection, so they arrive in order.
+pub async fn send_snapshot(client: &NetworkClient, address: &str, snapshot_lsn: Lsn, dump: &[u8]) -> Result<()> {
+    for message in snapshot_messages(snapshot_lsn, dump) {
+        client.send_async(address, message).await?;
+    }
+    Ok(())
+}
+
+/// Reassembles a snapshot on the receiving node
+#[derive(Debug, Default)]
+pub struct SnapshotReceiver {
+    /// Snapshot being received: (LSN, dump so far)
+    in_progress: Option<(Lsn, Vec<u8>)>,
+}
+
+impl SnapshotReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether a snapshot is being received
+    pub fn is_receiving(&self) -> bool {
+        self.in_progress.is_some()
+    }
+
+    /// Handle a `FullSync*` message. Returns the snapshot LSN and the
+    /// complete dump once `FullSyncComplete` arrives.
+    pub fn receive(&mut self, message: Message) -> Result<Option<(Lsn, Vec<u8>)>> {
+        match message {
+            Message::FullSyncStart { snapshot_lsn, .. } => {
+                // A restarted transfer replaces an unfinished one
+                self.in_progress = Some((snapshot_lsn, Vec::new()));
+                Ok(None)
+            }
+            Message::FullSyncChunk { data, .. } => match &mut self.in_progress {
+                Some((_, dump)) => {
+                    dump.extend_from_slice(&data);
+                    Ok(None)
+                }
+                None => Err(Error::Replication("Snapshot chunk received before FullSyncStart".into())),
+            },
+            Message::FullSyncComplete { snapshot_lsn } => match self.in_progress.take() {
+                Some((lsn, dump)) if lsn == snapshot_lsn => Ok(Some((lsn, dump))),
+                Some((lsn, _)) => Err(Error::Replication(format!(
+                    "Snapshot completed at LSN {} but started at LSN {}", snapshot_lsn, lsn
+                ))),
+                None => Err(Error::Replication("Snapshot completed before FullSyncStart".into())),
+            },
+            other => Err(Error::Replication(format!("{} is not part of a snapshot", other.type_name()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dump: Vec<u8> = (0..SNAPSHOT_CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let messages = snapshot_messages(42, &dump);
+        // Start, three chunks, complete
+        assert_eq!(messages.len(), 5);
+        assert!(matches!(messages[3], Message::FullSyncChunk { is_last: true, .. }));
+
+        let mut receiver = SnapshotReceiver::new();
+        let mut received = None;
+        for message in messages {
+            assert!(received.is_none());
+            received = receiver.receive(message).unwrap();
+        }
+        assert_eq!(received, Some((42, dump)));
+        assert!(!receiver.is_receiving());
+
+        let messages = snapshot_messages(7, &[]);
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(messages[1], Message::FullSyncChunk { is_last: true, .. }));
+    }
+
+    #[test]
+    fn test_snapshot_receiver_rejects_out_of_order() {
+        let mut receiver = SnapshotReceiver::new();
+        let chunk = Message::FullSyncChunk { table: String::new(), data: vec![1], is_last: true };
+        assert!(receiver.receive(chunk.clone()).is_err());
+        assert!(receiver.receive(Message::FullSyncComplete { snapshot_lsn: 1 }).is_err());
+
+        receiver.receive(Message::FullSyncStart { tables: Vec::new(), snapshot_lsn: 5 }).unwrap();
+        receiver.receive(chunk).unwrap();
+        assert!(receiver.receive(Message::FullSyncComplete { snapshot_lsn: 6 }).is_err());
+        assert!(!receiver.is_receiving());
+    }
+}
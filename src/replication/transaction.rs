@@ -0,0 +1,126 @@
+//! Leader-side Transaction Buffer
+//!
+//! Writes made inside an HTTP API transaction are held in memory on the
+//! leader until the client commits or rolls back. On commit the buffered
+//! writes are wrapped in a single `LogEntry::Transaction`, so they reach the
+//! WAL - and every follower - as one atomic entry. A transaction runs in one
+//! database, set by its first write, so followers can apply it on one
+//! connection. Transactions left open too long are rolled back so an
+//! abandoned client cannot leak memory.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::wal::entry::LogEntry;
+
+/// Open transactions are rolled back automatically after this long
+pub const TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Writes buffered for an open transaction
+struct OpenTransaction {
+    entries: Vec<LogEntry>,
+    /// Database of the first write; every later write must match it
+    database: Option<String>,
+    started: Instant,
+}
+
+/// Open transactions keyed by transaction ID
+#[derive(Default)]
+pub struct TransactionBuffer {
+    open: RwLock<HashMap<Uuid, OpenTransaction>>,
+}
+
+impl TransactionBuffer {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a new transaction
+    pub async fn begin(&self) -> Uuid {
+        let txn_id = Uuid::new_v4();
+        let mut open = self.open.write().await;
+        open.insert(txn_id, OpenTransaction {
+            entries: Vec::new(),
+            database: None,
+            started: Instant::now(),
+        });
+        crate::metrics::OPEN_TRANSACTIONS.set(open.len() as i64);
+        tracing::debug!("Transaction {} started", txn_id);
+        txn_id
+    }
+
+    /// Buffer a write, returning the number of writes buffered so far.
+    /// A write to a different database than the transaction's first is refused.
+    pub async fn write(&self, txn_id: Uuid, entry: LogEntry) -> Result<usize> {
+        let mut open = self.open.write().await;
+        let txn = open.get_mut(&txn_id).ok_or(Error::TransactionNotFound(txn_id))?;
+        let database = entry.database().map(str::to_string);
+        if txn.entries.is_empty() {
+            txn.database = database;
+        } else if database != txn.database {
+            return Err(Error::TransactionDatabaseMismatch {
+                txn_id,
+                expected: txn.database.clone(),
+                requested: database,
+            });
+        }
+        txn.entries.push(entry);
+        Ok(txn.entries.len())
+    }
+
+    /// Close a transaction for commit, returning its buffered writes
+    /// as a single entry (`None` if nothing was written)
+    pub async fn take(&self, txn_id: Uuid) -> Result<Option<LogEntry>> {
+        let mut open = self.open.write().await;
+        let txn = open.remove(&txn_id).ok_or(Error::TransactionNotFound(txn_id))?;
+        crate::metrics::OPEN_TRANSACTIONS.set(open.len() as i64);
+
+        tracing::debug!("Committing transaction {} ({} writes)", txn_id, txn.entries.len());
+        if txn.entries.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(LogEntry::Transaction {
+            entries: txn.entries,
+            transaction_id: Some(txn_id),
+        }))
+    }
+
+    /// Discard a transaction, returning the number of writes dropped
+    pub async fn rollback(&self, txn_id: Uuid) -> Result<usize> {
+        let mut open = self.open.write().await;
+        let txn = open.remove(&txn_id).ok_or(Error::TransactionNotFound(txn_id))?;
+        crate::metrics::OPEN_TRANSACTIONS.set(open.len() as i64);
+        tracing::debug!("Transaction {} rolled back ({} writes discarded)", txn_id, txn.entries.len());
+        Ok(txn.entries.len())
+    }
+
+    /// Roll back transactions open longer than `timeout`, returning how many expired
+    pub async fn expire(&self, timeout: Duration) -> usize {
+        let mut open = self.open.write().await;
+        let before = open.len();
+        open.retain(|txn_id, txn| {
+            let expired = txn.started.elapsed() > timeout;
+            if expired {
+                tracing::warn!("Transaction {} open for over {}s, rolling back", txn_id, timeout.as_secs());
+            }
+            !expired
+        });
+        crate::metrics::OPEN_TRANSACTIONS.set(open.len() as i64);
+        before - open.len()
+    }
+
+    /// Number of open transactions
+    pub async fn len(&self) -> usize {
+        self.open.read().await.len()
+    }
+
+    /// Check if there are no open transactions
+    pub async fn is_empty(&self) -> bool {
+        self.open.read().await.is_empty()
+    }
+}
+
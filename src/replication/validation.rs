@@ -0,0 +1,165 @@
+//! Replicated SQL validation
+//!
+//! Before a follower applies an entry it checks the SQL the entry runs
+//! against what the entry says it does: an `Update` of `orders` must run a
+//! single `UPDATE` of `orders` and nothing else. A statement of the wrong
+//! type, on another table, DDL inside a DML entry, or a statement writing a
+//! table besides the entry's means the entry was corrupted or tampered with
+//! on the way. It is refused, counted in
+//! `wolfscale_validation_failures_total{reason}`, and the follower asks the
+//! leader to send it again.
+//!
+//! Raw SQL entries only carry their table as a hint (the proxy takes it from
+//! the first words of the query), so they are only checked for DDL after DML.
+
+use crate::wal::entry::LogEntry;
+use crate::wal::{statement_shapes, StatementShape};
+
+/// Why an entry was refused
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ValidationError {
+    #[error("expected {expected} but the entry runs {found}")]
+    StatementType { expected: &'static str, found: String },
+
+    #[error("entry is for table {expected} but its SQL writes {found}")]
+    TableMismatch { expected: String, found: String },
+
+    #[error("data change entry runs DDL ({statement})")]
+    DdlInDml { statement: String },
+
+    #[error("entry for table {expected} also writes {table}")]
+    OtherTable { expected: String, table: String },
+}
+
+impl ValidationError {
+    /// `reason` label of `wolfscale_validation_failures_total`
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ValidationError::StatementType { .. } => "statement_type",
+            ValidationError::TableMismatch { .. } => "table_mismatch",
+            ValidationError::DdlInDml { .. } => "ddl_in_dml",
+            ValidationError::OtherTable { .. } => "other_table",
+        }
+    }
+}
+
+/// Check that the SQL an entry runs matches what the entry says it does
+pub fn validate_entry(entry: &LogEntry) -> Result<(), ValidationError> {
+    let (verb, table) = match entry {
+        LogEntry::Insert { table, .. } | LogEntry::Upsert { table, .. } | LogEntry::BulkInsert { table, .. } => {
+            ("INSERT", table)
+        }
+        LogEntry::Update { table, .. } => ("UPDATE", table),
+        LogEntry::Delete { table, .. } => ("DELETE", table),
+        LogEntry::CreateTable { table, .. } | LogEntry::CreateIndex { table, .. } => ("CREATE", table),
+        LogEntry::AlterTable { table, .. } => ("ALTER", table),
+        LogEntry::DropTable { table } | LogEntry::DropIndex { table, .. } => ("DROP", table),
+        LogEntry::Transaction { entries, .. } => return entries.iter().try_for_each(validate_entry),
+        LogEntry::RawSql { sql, .. } => return validate_raw(&statement_shapes(sql)),
+        LogEntry::Noop | LogEntry::IdAllocation { .. } => return Ok(()),
+    };
+
+    let shapes: Vec<StatementShape> = entry.to_sql().iter().flat_map(|sql| statement_shapes(sql)).collect();
+    if shapes.is_empty() {
+        return Err(ValidationError::StatementType { expected: verb, found: "nothing".to_string() });
+    }
+    if matches!(verb, "INSERT" | "UPDATE" | "DELETE") {
+        if let Some(shape) = shapes.iter().find(|s| s.ddl) {
+            return Err(ValidationError::DdlInDml { statement: shape.verb.clone() });
+        }
+    }
+    // Typed entries made outside replication may not name their table
+    let expected = bare_table(table);
+    for (n, shape) in shapes.iter().enumerate() {
+        if shape.verb != verb {
+            return Err(ValidationError::StatementType { expected: verb, found: shape.verb.clone() });
+        }
+        if expected.is_empty() {
+            continue;
+        }
+        let tables: Vec<String> = shape.tables.iter().map(|t| bare_table(t)).collect();
+        if n == 0 && tables.first() != Some(&expected) {
+            let found = tables.first().cloned().unwrap_or_else(|| "no table".to_string());
+            return Err(ValidationError::TableMismatch { expected, found });
+        }
+        if let Some(other) = tables.into_iter().find(|t| *t != expected) {
+            return Err(ValidationError::OtherTable { expected, table: other });
+        }
+    }
+    Ok(())
+}
+
+/// Raw SQL may mix statement types, but DDL doesn't belong after a data change
+fn validate_raw(shapes: &[StatementShape]) -> Result<(), ValidationError> {
+    let mut changed_data = false;
+    for shape in shapes {
+        if changed_data && shape.ddl {
+            return Err(ValidationError::DdlInDml { statement: shape.verb.clone() });
+        }
+        changed_data |= matches!(shape.verb.as_str(), "INSERT" | "REPLACE" | "UPDATE" | "DELETE");
+    }
+    Ok(())
+}
+
+/// A table name, lowercase and without backticks or database (typed entries
+/// quote `db.table` as one identifier)
+fn bare_table(table: &str) -> String {
+    let table = table.replace('`', "").to_lowercase();
+    match table.rsplit_once('.') {
+        Some((_, table)) => table.to_string(),
+        None => table,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::entry::{PrimaryKey, Value};
+
+    fn update(table: &str) -> LogEntry {
+        LogEntry::Update {
+            table: table.to_string(),
+            set_columns: vec!["name".to_string()],
+            set_values: vec![Value::String("x".to_string())],
+            primary_key: PrimaryKey::Int(1),
+            key_columns: vec!["id".to_string()],
+        }
+    }
+
+    fn raw(sql: &str) -> LogEntry {
+        LogEntry::RawSql { sql: sql.to_string(), affects_table: None, database: None, gtid: None }
+    }
+
+    #[test]
+    fn test_valid_entries_pass() {
+        assert_eq!(validate_entry(&update("app.orders")), Ok(()));
+        assert_eq!(validate_entry(&LogEntry::DropTable { table: "orders".to_string() }), Ok(()));
+        assert_eq!(validate_entry(&LogEntry::AlterTable {
+            table: "orders".to_string(),
+            ddl: "ALTER TABLE `orders` ADD COLUMN note TEXT".to_string(),
+        }), Ok(()));
+        assert_eq!(validate_entry(&raw("CREATE TABLE t (id INT); INSERT INTO t VALUES (1)")), Ok(()));
+        assert_eq!(validate_entry(&raw("UPDATE a JOIN b ON a.id = b.id SET a.x = b.x")), Ok(()));
+    }
+
+    #[test]
+    fn test_corrupted_entries_are_refused() {
+        let reason = |entry: &LogEntry| validate_entry(entry).unwrap_err().reason();
+
+        // DDL text that doesn't do what the entry says
+        let alter = |ddl: &str| LogEntry::AlterTable { table: "orders".to_string(), ddl: ddl.to_string() };
+        assert_eq!(reason(&alter("DROP TABLE orders")), "statement_type");
+        assert_eq!(reason(&alter("ALTER TABLE users ADD COLUMN x INT")), "table_mismatch");
+        assert_eq!(reason(&alter("ALTER TABLE orders ADD x INT; ALTER TABLE users DROP x")), "other_table");
+
+        // A table name smuggling in more SQL
+        assert_eq!(reason(&update("orders` SET id = 0; DROP TABLE `users")), "ddl_in_dml");
+        assert_eq!(reason(&update("orders` JOIN `users")), "table_mismatch");
+        assert_eq!(reason(&LogEntry::Transaction {
+            transaction_id: None,
+            entries: vec![update("orders"), update("orders` SET x = 1; DELETE FROM `users")],
+        }), "table_mismatch");
+
+        assert_eq!(reason(&raw("INSERT INTO t VALUES (1); DROP TABLE t")), "ddl_in_dml");
+    }
+}
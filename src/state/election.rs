@@ -2,7 +2,14 @@
 //!
 //! Implements Raft-style leader election with randomized timeouts
 //! to achieve automatic failover when the leader goes down.
+//!
+//! The current term and this node's vote are written to `current_term` and
+//! `voted_for` in the state directory, and fsynced, before they take effect.
+//! A node that crashes and restarts carries on from the term it had reached,
+//! so it can't vote twice in a term or fall back to a stale one.
 
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
@@ -13,6 +20,13 @@ use crate::state::{ClusterMembership, NodeRole, StateTracker};
 use crate::replication::Message;
 use crate::error::Result;
 
+/// File in the state directory holding the current term (8 bytes, little-endian)
+const TERM_FILE: &str = "current_term";
+
+/// File in the state directory holding the node voted for in the current term
+/// (empty if none)
+const VOTED_FOR_FILE: &str = "voted_for";
+
 /// Election state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ElectionState {
@@ -64,6 +78,8 @@ pub struct ElectionCoordinator {
     cluster: Arc<ClusterMembership>,
     /// State tracker for persistence
     state_tracker: Arc<StateTracker>,
+    /// Where the term and vote are persisted
+    state_dir: PathBuf,
     /// Last log LSN (for vote comparison)
     last_log_lsn: RwLock<Lsn>,
     /// Message sender (kept for potential future use)
@@ -80,17 +96,23 @@ impl ElectionCoordinator {
         config: ElectionConfig,
         message_tx: mpsc::Sender<(String, Message)>,
     ) -> Self {
+        let state_dir = state_tracker.data_dir().to_path_buf();
+        let term = load_term(&state_dir);
+        let voted_for = load_voted_for(&state_dir);
+        crate::metrics::ELECTION_TERM.set(term as i64);
+
         Self {
             node_id,
             state: RwLock::new(ElectionState::Follower),
-            term: RwLock::new(1),
-            voted_for: RwLock::new(None),
+            term: RwLock::new(term),
+            voted_for: RwLock::new(voted_for),
             votes_received: RwLock::new(Vec::new()),
             last_heartbeat: RwLock::new(Instant::now()),
             election_timeout: RwLock::new(Self::random_timeout(&config)),
             config,
             cluster,
             state_tracker,
+            state_dir,
             last_log_lsn: RwLock::new(0),
             message_tx,
         }
@@ -124,6 +146,19 @@ impl ElectionCoordinator {
         *self.term.read().await
     }
 
+    /// Persist the current term, fsynced, to `{state_dir}/current_term`
+    pub fn save_term(&self, term: u64) -> Result<()> {
+        write_synced(&self.state_dir.join(TERM_FILE), &term.to_le_bytes())?;
+        crate::metrics::ELECTION_TERM.set(term as i64);
+        Ok(())
+    }
+
+    /// Persist this node's vote in the current term to `{state_dir}/voted_for`
+    fn save_voted_for(&self, candidate_id: Option<&str>) -> Result<()> {
+        write_synced(&self.state_dir.join(VOTED_FOR_FILE), candidate_id.unwrap_or("").as_bytes())?;
+        Ok(())
+    }
+
     /// Check if election timeout has expired
     pub async fn check_timeout(&self) -> bool {
         let last = *self.last_heartbeat.read().await;
@@ -181,15 +216,22 @@ impl ElectionCoordinator {
             return Ok(());
         }
 
-        // Increment term
+        // Increment term, on disk first so a restart can't reuse it
         let new_term = {
             let mut term = self.term.write().await;
+            self.save_term(*term + 1)?;
             *term += 1;
             *term
         };
 
+        // A candidate votes for itself in its new term
+        self.save_voted_for(Some(&self.node_id))?;
+        *self.voted_for.write().await = Some(self.node_id.clone());
+
         // Persist term
         self.state_tracker.set_current_term(new_term).await?;
+        self.state_tracker.set_voted_for(Some(&self.node_id)).await?;
+        self.cluster.set_term(new_term);
 
         tracing::info!(
             "Node {} has lowest ID among active nodes - becoming leader for term {}",
@@ -243,6 +285,7 @@ impl ElectionCoordinator {
         let vote_granted = can_vote && log_ok;
 
         if vote_granted {
+            self.save_voted_for(Some(candidate_id))?;
             *self.voted_for.write().await = Some(candidate_id.to_string());
             self.state_tracker.set_voted_for(Some(candidate_id)).await?;
             self.reset_timer().await;
@@ -337,11 +380,14 @@ impl ElectionCoordinator {
         let current_term = *self.term.read().await;
         
         if new_term > current_term {
+            self.save_term(new_term)?;
             *self.term.write().await = new_term;
             self.state_tracker.set_current_term(new_term).await?;
+            self.cluster.set_term(new_term);
         }
 
         *self.state.write().await = ElectionState::Follower;
+        self.save_voted_for(None)?;
         *self.voted_for.write().await = None;
         self.state_tracker.set_voted_for(None).await?;
 
@@ -359,9 +405,22 @@ impl ElectionCoordinator {
         Ok(())
     }
 
-    /// Become follower with known leader
+    /// Become follower with known leader. The term only ever moves forward.
     pub async fn become_follower(&self, term: u64, leader_id: &str) -> Result<()> {
-        *self.term.write().await = term;
+        {
+            let mut current_term = self.term.write().await;
+            if term > *current_term {
+                self.save_term(term)?;
+                *current_term = term;
+                self.state_tracker.set_current_term(term).await?;
+
+                // A new term: no vote cast in it yet
+                self.save_voted_for(None)?;
+                *self.voted_for.write().await = None;
+                self.state_tracker.set_voted_for(None).await?;
+            }
+            self.cluster.set_term(*current_term);
+        }
         *self.state.write().await = ElectionState::Follower;
         
         self.cluster.set_leader(leader_id).await?;
@@ -463,6 +522,47 @@ impl ElectionCoordinator {
     }
 }
 
+/// Term persisted by an earlier run, or 1 for a new node
+fn load_term(state_dir: &Path) -> u64 {
+    match std::fs::read(state_dir.join(TERM_FILE)) {
+        Ok(bytes) => match <[u8; 8]>::try_from(bytes.as_slice()) {
+            Ok(bytes) => u64::from_le_bytes(bytes).max(1),
+            Err(_) => {
+                tracing::warn!("Ignoring {} with {} bytes, starting from term 1", TERM_FILE, bytes.len());
+                1
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 1,
+        Err(e) => {
+            tracing::warn!("Could not read {}, starting from term 1: {}", TERM_FILE, e);
+            1
+        }
+    }
+}
+
+/// Vote persisted by an earlier run in the current term
+fn load_voted_for(state_dir: &Path) -> Option<String> {
+    let voted_for = std::fs::read_to_string(state_dir.join(VOTED_FOR_FILE)).ok()?;
+    let voted_for = voted_for.trim();
+    (!voted_for.is_empty()).then(|| voted_for.to_string())
+}
+
+/// Replace a file so that either the old or the new contents survive a
+/// crash: write a temp file, fsync it, rename it over the old one, then
+/// fsync the directory so the rename sticks
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    if let Some(dir) = path.parent() {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -509,4 +609,50 @@ mod tests {
         assert_eq!(coordinator.state().await, ElectionState::Follower);
         assert_eq!(coordinator.term().await, 1);
     }
+
+    /// A coordinator for node-1 over the state in `dir`, as the node builds
+    /// it when it starts
+    fn coordinator(dir: &Path) -> ElectionCoordinator {
+        let (tx, _rx) = mpsc::channel(100);
+        let cluster = Arc::new(ClusterMembership::new(
+            "node-1".to_string(),
+            "localhost:7654".to_string(),
+            Duration::from_secs(1),
+            Duration::from_secs(5),
+        ));
+        let state_tracker = Arc::new(StateTracker::new(dir.to_path_buf(), "node-1".to_string()).unwrap());
+        ElectionCoordinator::new("node-1".to_string(), cluster, state_tracker, ElectionConfig::default(), tx)
+    }
+
+    #[tokio::test]
+    async fn test_term_survives_crash_mid_election() {
+        let dir = tempdir().unwrap();
+        let node = coordinator(dir.path());
+        node.become_follower(5, "node-0").await.unwrap();
+        node.start_election().await.unwrap();
+        assert_eq!(node.term().await, 6);
+
+        // Killed mid-election, part way through writing the next term
+        drop(node);
+        std::fs::write(dir.path().join("current_term.tmp"), [7u8, 0, 0]).unwrap();
+
+        let node = coordinator(dir.path());
+        assert_eq!(node.term().await, 6);
+        assert_eq!(*node.voted_for.read().await, Some("node-1".to_string()));
+
+        // It voted for itself in term 6, and a stale leader can't take it back
+        let refused = |msg: Message| matches!(msg, Message::VoteResponse { vote_granted: false, term: 6, .. });
+        assert!(refused(node.handle_vote_request(6, "node-2", 0, 0).await.unwrap()));
+        assert!(refused(node.handle_vote_request(4, "node-2", 0, 0).await.unwrap()));
+        node.become_follower(3, "node-0").await.unwrap();
+        assert_eq!(node.term().await, 6);
+
+        // A later term starts with a fresh vote, which also survives a restart
+        let granted = node.handle_vote_request(7, "node-2", 0, 0).await.unwrap();
+        assert!(matches!(granted, Message::VoteResponse { vote_granted: true, term: 7, .. }));
+        drop(node);
+        let node = coordinator(dir.path());
+        assert_eq!(node.term().await, 7);
+        assert_eq!(*node.voted_for.read().await, Some("node-2".to_string()));
+    }
 }
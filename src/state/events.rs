@@ -0,0 +1,287 @@
+//! Cluster Event Notifications
+//!
+//! `ClusterMembership` reports leadership and membership changes to an
+//! `EventNotifier`, which POSTs them as JSON to `cluster.event_webhook_url`.
+//! Each node reports what it observes: the new leader reports its own
+//! election, followers report the leader they lost, and the leader reports
+//! followers joining and leaving. Several nodes may report the same
+//! `leader_lost`; `reported_by` and `term` tell them apart.
+//!
+//! Notifications are sent in order by a background task, so a slow or
+//! unreachable webhook never holds up an election.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::membership::{NodeRole, NodeState, NodeStatus};
+
+/// Attempts per notification before it is given up
+const WEBHOOK_ATTEMPTS: u32 = 3;
+/// Wait between attempts
+const WEBHOOK_RETRY_BACKOFF: Duration = Duration::from_secs(5);
+/// Time allowed for one POST
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A change in the cluster worth telling operators about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClusterEvent {
+    /// A node became leader
+    LeaderElected,
+    /// The leader stopped sending heartbeats or was replaced
+    LeaderLost,
+    /// A follower started (or resumed) sending heartbeats
+    FollowerJoined,
+    /// A follower was dropped after missing heartbeats
+    FollowerLost,
+}
+
+impl ClusterEvent {
+    /// Every event, the default for `cluster.event_types`
+    pub const ALL: [ClusterEvent; 4] = [
+        ClusterEvent::LeaderElected,
+        ClusterEvent::LeaderLost,
+        ClusterEvent::FollowerJoined,
+        ClusterEvent::FollowerLost,
+    ];
+
+    /// Parse a `cluster.event_types` entry
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == name)
+    }
+
+    /// Name used in config, payloads and metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClusterEvent::LeaderElected => "leader_elected",
+            ClusterEvent::LeaderLost => "leader_lost",
+            ClusterEvent::FollowerJoined => "follower_joined",
+            ClusterEvent::FollowerLost => "follower_lost",
+        }
+    }
+}
+
+/// A cluster member as listed in an event payload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventNode {
+    pub id: String,
+    pub address: String,
+    pub role: NodeRole,
+    pub status: NodeStatus,
+}
+
+impl From<&NodeState> for EventNode {
+    fn from(node: &NodeState) -> Self {
+        Self {
+            id: node.id.clone(),
+            address: node.address.clone(),
+            role: node.role,
+            status: node.status,
+        }
+    }
+}
+
+/// Body of a webhook POST
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventPayload {
+    pub event: ClusterEvent,
+    /// The node the event is about
+    pub node_id: String,
+    /// Election term when the event was observed
+    pub term: u64,
+    pub timestamp_ms: u64,
+    /// The node that observed the event
+    pub reported_by: String,
+    /// Cluster members as the reporting node sees them after the event
+    pub cluster: Vec<EventNode>,
+}
+
+impl EventPayload {
+    pub fn new(event: ClusterEvent, node_id: &str, term: u64, reported_by: &str, cluster: Vec<EventNode>) -> Self {
+        Self {
+            event,
+            node_id: node_id.to_string(),
+            term,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            reported_by: reported_by.to_string(),
+            cluster,
+        }
+    }
+}
+
+/// Sends cluster events to a webhook
+pub struct EventNotifier {
+    events: Vec<ClusterEvent>,
+    tx: mpsc::UnboundedSender<EventPayload>,
+}
+
+impl EventNotifier {
+    /// Start sending `events` to `url` (must be called within a Tokio runtime)
+    pub fn new(url: String, events: Vec<ClusterEvent>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(deliver(url, rx));
+        Self { events, tx }
+    }
+
+    /// Whether `event` is sent to the webhook
+    pub fn wants(&self, event: ClusterEvent) -> bool {
+        self.events.contains(&event)
+    }
+
+    /// Queue a notification; returns immediately
+    pub fn notify(&self, payload: EventPayload) {
+        if self.wants(payload.event) {
+            let _ = self.tx.send(payload);
+        }
+    }
+}
+
+/// POST each queued payload, retrying failures
+async fn deliver(url: String, mut rx: mpsc::UnboundedReceiver<EventPayload>) {
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+
+    while let Some(payload) = rx.recv().await {
+        let event = payload.event.as_str();
+        for attempt in 1..=WEBHOOK_ATTEMPTS {
+            let result = client.post(&url).json(&payload).send().await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => {
+                    crate::metrics::WEBHOOK_SENT.with_label_values(&[event]).inc();
+                    tracing::debug!("Sent {} event for {} to webhook", event, payload.node_id);
+                    break;
+                }
+                Err(e) => {
+                    crate::metrics::WEBHOOK_ERRORS.with_label_values(&[event]).inc();
+                    if attempt < WEBHOOK_ATTEMPTS {
+                        tracing::warn!("Webhook for {} event failed (attempt {}/{}): {} - retrying in {:?}",
+                            event, attempt, WEBHOOK_ATTEMPTS, e, WEBHOOK_RETRY_BACKOFF);
+                        tokio::time::sleep(WEBHOOK_RETRY_BACKOFF).await;
+                    } else {
+                        tracing::warn!("Webhook for {} event failed after {} attempts, giving up: {}",
+                            event, WEBHOOK_ATTEMPTS, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use axum::{routing::post, Json, Router};
+    use tempfile::tempdir;
+    use crate::state::{ClusterMembership, ElectionConfig, ElectionCoordinator, StateTracker};
+
+    /// Start a webhook that records every payload it receives
+    async fn mock_webhook() -> (String, Arc<Mutex<Vec<EventPayload>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&received);
+        let app = Router::new().route("/", post(move |Json(payload): Json<EventPayload>| {
+            let log = Arc::clone(&log);
+            async move { log.lock().unwrap().push(payload); }
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, received)
+    }
+
+    #[test]
+    fn test_event_names() {
+        for event in ClusterEvent::ALL {
+            assert_eq!(ClusterEvent::parse(event.as_str()), Some(event));
+            assert_eq!(serde_json::to_value(event).unwrap(), event.as_str());
+        }
+        assert_eq!(ClusterEvent::parse("leader_changed"), None);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_reports_leader_failover() {
+        let (url, received) = mock_webhook().await;
+        let dir = tempdir().unwrap();
+        let ids = ["node-1", "node-2", "node-3"];
+
+        let mut nodes = Vec::new();
+        for id in ids {
+            let cluster = Arc::new(ClusterMembership::new(
+                id.to_string(),
+                format!("{}:7654", id),
+                Duration::from_millis(50),
+                Duration::from_millis(20),
+            ));
+            for peer in ids.iter().filter(|p| **p != id) {
+                cluster.add_peer(peer.to_string(), format!("{}:7654", peer)).await.unwrap();
+            }
+            cluster.set_event_notifier(EventNotifier::new(url.clone(), ClusterEvent::ALL.to_vec()));
+            let tracker = Arc::new(StateTracker::new(dir.path().join(id), id.to_string()).unwrap());
+            let (tx, _rx) = mpsc::channel(10);
+            let election = ElectionCoordinator::new(id.to_string(), Arc::clone(&cluster), tracker, ElectionConfig::default(), tx);
+            nodes.push((cluster, election));
+        }
+
+        // node-1 leads, the others follow and heartbeat each other
+        nodes[0].1.start_election().await.unwrap();
+        let term = nodes[0].1.term().await;
+        for (cluster, election) in &nodes[1..] {
+            election.become_follower(term, "node-1").await.unwrap();
+            cluster.record_heartbeat("node-1", 0).await.unwrap();
+        }
+        nodes[0].0.record_heartbeat("node-2", 0).await.unwrap();
+        nodes[0].0.record_heartbeat("node-3", 0).await.unwrap();
+
+        // Kill node-1: its heartbeats stop, so the followers drop it
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        for (cluster, _) in &nodes[1..] {
+            cluster.check_timeouts().await;
+        }
+        tokio::time::sleep(Duration::from_millis(70)).await;
+        for (cluster, _) in &nodes[1..] {
+            cluster.check_timeouts().await;
+        }
+        // node-2 now has the lowest ID among live nodes
+        nodes[1].1.start_election().await.unwrap();
+        assert_eq!(nodes[1].1.state().await, crate::state::ElectionState::Leader);
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        let events = loop {
+            let events = received.lock().unwrap().clone();
+            if events.iter().filter(|e| e.event == ClusterEvent::LeaderElected).count() == 2
+                && events.iter().filter(|e| e.event == ClusterEvent::LeaderLost).count() == 2
+            {
+                break events;
+            }
+            assert!(tokio::time::Instant::now() < deadline, "missing webhook events: {:?}", events);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+
+        let joined: Vec<_> = events.iter().filter(|e| e.event == ClusterEvent::FollowerJoined).collect();
+        assert_eq!(joined.len(), 2);
+        assert!(joined.iter().all(|e| e.reported_by == "node-1"));
+
+        let lost: Vec<_> = events.iter().filter(|e| e.event == ClusterEvent::LeaderLost).collect();
+        assert!(lost.iter().all(|e| e.node_id == "node-1" && e.term == term));
+        let mut reporters: Vec<_> = lost.iter().map(|e| e.reported_by.as_str()).collect();
+        reporters.sort();
+        assert_eq!(reporters, ["node-2", "node-3"]);
+
+        let elected = events.iter().rfind(|e| e.event == ClusterEvent::LeaderElected).unwrap();
+        assert_eq!((elected.node_id.as_str(), elected.reported_by.as_str()), ("node-2", "node-2"));
+        assert!(elected.term > term);
+        assert!(elected.timestamp_ms > 0);
+        let leader = elected.cluster.iter().find(|n| n.role == NodeRole::Leader).unwrap();
+        assert_eq!(leader.id, "node-2");
+        assert_eq!(elected.cluster.iter().find(|n| n.id == "node-1").unwrap().status, NodeStatus::Dropped);
+
+        assert!(crate::metrics::WEBHOOK_SENT.with_label_values(&["leader_lost"]).get() >= 2);
+    }
+}
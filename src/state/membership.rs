@@ -3,12 +3,15 @@
 //! Tracks node states, health, and cluster membership.
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
 use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 use crate::wal::entry::Lsn;
 use crate::error::Result;
+use super::events::{ClusterEvent, EventNode, EventNotifier, EventPayload};
 
 /// Node status in the cluster
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -133,6 +136,12 @@ pub struct ClusterMembership {
     heartbeat_timeout: Duration,
     /// Election timeout
     election_timeout: Duration,
+    /// Drained for maintenance: the proxy refuses new clients
+    maintenance: AtomicBool,
+    /// Election term, as last set by the election coordinator
+    term: AtomicU64,
+    /// Where leadership and membership changes are reported
+    notifier: OnceLock<EventNotifier>,
 }
 
 impl ClusterMembership {
@@ -154,6 +163,9 @@ impl ClusterMembership {
             nodes: RwLock::new(nodes),
             heartbeat_timeout,
             election_timeout,
+            maintenance: AtomicBool::new(false),
+            term: AtomicU64::new(0),
+            notifier: OnceLock::new(),
         }
     }
 
@@ -162,6 +174,48 @@ impl ClusterMembership {
         &self.node_id
     }
 
+    /// Put this node into or take it out of maintenance mode
+    pub fn set_maintenance(&self, enabled: bool) {
+        self.maintenance.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether this node is in maintenance mode
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::Relaxed)
+    }
+
+    /// Record the current election term (included in event notifications)
+    pub fn set_term(&self, term: u64) {
+        self.term.store(term, Ordering::Relaxed);
+    }
+
+    /// Report leadership and membership changes to `notifier` from now on
+    pub fn set_event_notifier(&self, notifier: EventNotifier) {
+        if self.notifier.set(notifier).is_err() {
+            tracing::warn!("Cluster event notifier already set - ignoring");
+        }
+    }
+
+    /// Report `event` about `node_id`, if a notifier is set
+    fn notify(&self, nodes: &HashMap<String, NodeState>, event: ClusterEvent, node_id: &str) {
+        let Some(notifier) = self.notifier.get() else { return };
+        if !notifier.wants(event) || node_id.starts_with("peer-") {
+            return;
+        }
+        let mut cluster: Vec<EventNode> = nodes.values()
+            .filter(|n| !n.id.starts_with("peer-"))
+            .map(EventNode::from)
+            .collect();
+        cluster.sort_by(|a, b| a.id.cmp(&b.id));
+        tracing::info!("Cluster event: {} ({})", event.as_str(), node_id);
+        notifier.notify(EventPayload::new(event, node_id, self.term.load(Ordering::Relaxed), &self.node_id, cluster));
+    }
+
+    /// How long a node may go without a heartbeat before it is unhealthy
+    pub fn heartbeat_timeout(&self) -> Duration {
+        self.heartbeat_timeout
+    }
+
     /// Add a peer node
     pub async fn add_peer(&self, id: String, address: String) -> Result<()> {
         let mut nodes = self.nodes.write().await;
@@ -223,7 +277,10 @@ impl ClusterMembership {
     /// Record a heartbeat from a node
     pub async fn record_heartbeat(&self, id: &str, lsn: Lsn) -> Result<()> {
         let mut nodes = self.nodes.write().await;
+        let self_is_leader = nodes.get(&self.node_id).is_some_and(|n| n.role == NodeRole::Leader);
+        let mut joined = false;
         if let Some(node) = nodes.get_mut(id) {
+            let was_away = matches!(node.status, NodeStatus::Joining | NodeStatus::Offline);
             let old_lsn = node.last_applied_lsn;
             node.touch();
             // Only update LSN if the incoming value is non-zero
@@ -257,9 +314,15 @@ impl ClusterMembership {
                 }
                 _ => {}
             }
+
+            // The leader reports followers it starts hearing from
+            joined = was_away && node.status == NodeStatus::Active && self_is_leader && node.role != NodeRole::Leader;
         } else {
             tracing::warn!("record_heartbeat: node '{}' NOT FOUND in cluster membership!", id);
         }
+        if joined {
+            self.notify(&nodes, ClusterEvent::FollowerJoined, id);
+        }
         Ok(())
     }
 
@@ -271,6 +334,8 @@ impl ClusterMembership {
         let mut nodes = self.nodes.write().await;
         let mut timed_out = Vec::new();
         let mut to_remove = Vec::new();
+        let mut lost = Vec::new();
+        let self_is_leader = nodes.get(&self.node_id).is_some_and(|n| n.role == NodeRole::Leader);
         
         // Threshold for complete removal (30 seconds)
         let removal_threshold = Duration::from_secs(30);
@@ -298,6 +363,9 @@ impl ClusterMembership {
                             // Clear leader role when dropped - forces re-election
                             if node.role == NodeRole::Leader {
                                 node.role = NodeRole::Follower;
+                                lost.push((ClusterEvent::LeaderLost, id.clone()));
+                            } else if self_is_leader {
+                                lost.push((ClusterEvent::FollowerLost, id.clone()));
                             }
                             timed_out.push(id.clone());
                         }
@@ -313,6 +381,10 @@ impl ClusterMembership {
             }
         }
         
+        for (event, id) in &lost {
+            self.notify(&nodes, *event, id);
+        }
+
         // Remove nodes that have been dead for too long
         for id in &to_remove {
             tracing::info!("Removing stale node {} from cluster (no heartbeat for 30s)", id);
@@ -355,6 +427,15 @@ impl ClusterMembership {
             .collect()
     }
 
+    /// The follower with the lowest applied LSN and that LSN. Dropped nodes
+    /// are included, since they may still come back and catch up.
+    pub async fn min_follower_lsn(&self) -> Option<(String, Lsn)> {
+        self.real_peers().await
+            .into_iter()
+            .map(|n| (n.id, n.last_applied_lsn))
+            .min_by_key(|(_, lsn)| *lsn)
+    }
+
     /// Get all nodes (excluding synthetic peers)
     pub async fn all_nodes(&self) -> Vec<NodeState> {
         let nodes = self.nodes.read().await;
@@ -373,6 +454,7 @@ impl ClusterMembership {
     /// Set a node as the leader
     pub async fn set_leader(&self, leader_id: &str) -> Result<()> {
         let mut nodes = self.nodes.write().await;
+        let previous = nodes.values().find(|n| n.role == NodeRole::Leader).map(|n| n.id.clone());
         
         // Remove leader role from all nodes
         for node in nodes.values_mut() {
@@ -386,6 +468,18 @@ impl ClusterMembership {
             node.role = NodeRole::Leader;
             node.status = NodeStatus::Active; // Leader must be active!
             node.last_heartbeat = Some(std::time::Instant::now()); // Reset heartbeat
+        } else {
+            return Ok(());
+        }
+
+        if previous.as_deref() != Some(leader_id) {
+            if let Some(previous) = &previous {
+                self.notify(&nodes, ClusterEvent::LeaderLost, previous);
+            }
+            // Only the new leader reports its election
+            if leader_id == self.node_id {
+                self.notify(&nodes, ClusterEvent::LeaderElected, leader_id);
+            }
         }
 
         Ok(())
@@ -514,6 +608,25 @@ mod tests {
         assert_eq!(cluster.quorum_size().await, 2);
     }
 
+    #[tokio::test]
+    async fn test_min_follower_lsn() {
+        let cluster = ClusterMembership::new(
+            "node-1".to_string(),
+            "localhost:7654".to_string(),
+            Duration::from_secs(1),
+            Duration::from_secs(5),
+        );
+        assert!(cluster.min_follower_lsn().await.is_none());
+
+        cluster.add_peer("node-2".to_string(), "localhost:7655".to_string()).await.unwrap();
+        cluster.add_peer("node-3".to_string(), "localhost:7656".to_string()).await.unwrap();
+        cluster.record_heartbeat("node-2", 500).await.unwrap();
+        cluster.record_heartbeat("node-3", 120).await.unwrap();
+        cluster.update_node("node-3", |node| node.status = NodeStatus::Dropped).await.unwrap();
+
+        assert_eq!(cluster.min_follower_lsn().await, Some(("node-3".to_string(), 120)));
+    }
+
     #[tokio::test]
     async fn test_leader_election() {
         let cluster = ClusterMembership::new(
@@ -1,13 +1,16 @@
 //! State Management Module
 //!
 //! Handles persistent state tracking for nodes, including
-//! applied LSN tracking and cluster membership.
+//! applied LSN tracking, cluster membership and notifications of
+//! membership changes.
 
 mod tracker;
 mod membership;
 pub mod election;
+pub mod events;
 
 pub use tracker::StateTracker;
 pub use membership::{NodeState, NodeStatus, NodeRole, ClusterMembership, ClusterSummary};
 pub use election::{ElectionCoordinator, ElectionConfig, ElectionState};
+pub use events::{ClusterEvent, EventNotifier, EventPayload};
 
@@ -3,7 +3,7 @@
 //! Persistent storage for node state, tracking which log entries
 //! have been applied to the local database.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use rusqlite::{Connection, params};
 use tokio::sync::RwLock;
 
@@ -16,6 +16,8 @@ pub struct StateTracker {
     conn: RwLock<Connection>,
     /// Node ID
     node_id: String,
+    /// Directory holding the state database
+    data_dir: PathBuf,
 }
 
 impl StateTracker {
@@ -57,6 +59,7 @@ impl StateTracker {
         Ok(Self {
             conn: RwLock::new(conn),
             node_id,
+            data_dir,
         })
     }
 
@@ -317,6 +320,11 @@ impl StateTracker {
     pub fn node_id(&self) -> &str {
         &self.node_id
     }
+
+    /// Get the directory holding node state
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
 }
 
 impl From<rusqlite::Error> for Error {
@@ -0,0 +1,258 @@
+//! Distributed Tracing
+//!
+//! Optional OpenTelemetry spans for the write path (`otel` feature). The
+//! leader opens an `http_request` root span when a write arrives and records
+//! its trace ID in the WAL entry header. Followers receive the ID with the
+//! entry and attach their `db_execute` span to the same trace, so one write
+//! shows up in Jaeger/Zipkin as a single trace across nodes:
+//!
+//! ```text
+//! http_request (leader)
+//! ├── wal_append (leader)
+//! ├── replicate_to_follower{node_id} (leader, ends on ACK)
+//! └── db_execute (follower)
+//! ```
+//!
+//! The root span ID is the first 8 bytes of the trace ID, which lets a
+//! follower rebuild the parent span context from the trace ID alone.
+//! Without the feature, or with no `telemetry.otlp_endpoint`, trace IDs are
+//! all zeros and every span is a no-op.
+
+use std::future::Future;
+#[cfg(feature = "otel")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "otel")]
+use std::sync::OnceLock;
+
+use crate::config::TelemetryConfig;
+use crate::error::Result;
+
+/// W3C trace ID carried in WAL entry headers (all zeros = untraced)
+pub type TraceId = [u8; 16];
+
+tokio::task_local! {
+    /// Trace of the request being handled by the current task
+    static CURRENT_TRACE: TraceId;
+}
+
+#[cfg(feature = "otel")]
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "otel")]
+static PROVIDER: OnceLock<opentelemetry_sdk::trace::TracerProvider> = OnceLock::new();
+
+/// Tracer name reported to the collector
+#[cfg(feature = "otel")]
+const TRACER_NAME: &str = "wolfscale";
+
+/// Start exporting spans if an OTLP endpoint is configured
+pub fn init(config: &TelemetryConfig, node_id: &str) -> Result<()> {
+    let Some(endpoint) = config.otlp_endpoint.as_deref() else {
+        return Ok(());
+    };
+
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry::KeyValue;
+        use opentelemetry_otlp::WithExportConfig;
+
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()
+            .map_err(|e| crate::Error::Config(format!("OTLP exporter for {}: {}", endpoint, e)))?;
+
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+            .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+            .with_resource(opentelemetry_sdk::Resource::new(vec![
+                KeyValue::new("service.name", "wolfscale"),
+                KeyValue::new("service.instance.id", node_id.to_string()),
+            ]))
+            .build();
+
+        opentelemetry::global::set_tracer_provider(provider.clone());
+        let _ = PROVIDER.set(provider);
+        ENABLED.store(true, Ordering::Release);
+        tracing::info!("Exporting traces to {}", endpoint);
+    }
+
+    #[cfg(not(feature = "otel"))]
+    tracing::warn!(
+        "telemetry.otlp_endpoint = {} ignored (node {}): built without the `otel` feature",
+        endpoint,
+        node_id
+    );
+
+    Ok(())
+}
+
+/// Flush pending spans on shutdown
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Whether spans are being exported
+pub fn is_enabled() -> bool {
+    #[cfg(feature = "otel")]
+    {
+        ENABLED.load(Ordering::Acquire)
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        false
+    }
+}
+
+/// Whether a trace ID refers to a real trace
+pub fn is_traced(trace_id: &TraceId) -> bool {
+    trace_id.iter().any(|&b| b != 0)
+}
+
+/// Allocate a trace ID for a new write (all zeros when tracing is off)
+pub fn new_trace_id() -> TraceId {
+    if !is_enabled() {
+        return TraceId::default();
+    }
+    loop {
+        let id: TraceId = rand::random();
+        // Both the trace ID and the root span ID derived from it must be non-zero
+        if id[..8].iter().any(|&b| b != 0) {
+            return id;
+        }
+    }
+}
+
+/// Run a future with `trace_id` as the current trace of this task
+pub async fn with_trace_id<F: Future>(trace_id: TraceId, f: F) -> F::Output {
+    CURRENT_TRACE.scope(trace_id, f).await
+}
+
+/// Trace of the request being handled by this task (zeros if none)
+pub fn current_trace_id() -> TraceId {
+    CURRENT_TRACE.try_with(|id| *id).unwrap_or_default()
+}
+
+/// A span that ends when dropped
+pub struct Span {
+    #[cfg(feature = "otel")]
+    inner: Option<opentelemetry::global::BoxedSpan>,
+}
+
+impl Span {
+    fn disabled() -> Self {
+        Self {
+            #[cfg(feature = "otel")]
+            inner: None,
+        }
+    }
+
+    /// Start the root span of a trace
+    pub fn root(name: &'static str, trace_id: TraceId) -> Self {
+        if !is_enabled() || !is_traced(&trace_id) {
+            return Self::disabled();
+        }
+
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::{SpanId, TraceId as OtelTraceId, Tracer};
+
+            let tracer = opentelemetry::global::tracer(TRACER_NAME);
+            let span = tracer
+                .span_builder(name)
+                .with_trace_id(OtelTraceId::from_bytes(trace_id))
+                .with_span_id(SpanId::from_bytes(root_span_id(&trace_id)))
+                .start(&tracer);
+            Self { inner: Some(span) }
+        }
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = name;
+            Self::disabled()
+        }
+    }
+
+    /// Start a span under the root span of `trace_id`, possibly on another node
+    pub fn child(name: &'static str, trace_id: TraceId, attributes: &[(&'static str, String)]) -> Self {
+        if !is_enabled() || !is_traced(&trace_id) {
+            return Self::disabled();
+        }
+
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::{
+                SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId as OtelTraceId, TraceState, Tracer,
+            };
+            use opentelemetry::{Context, KeyValue};
+
+            let parent = SpanContext::new(
+                OtelTraceId::from_bytes(trace_id),
+                SpanId::from_bytes(root_span_id(&trace_id)),
+                TraceFlags::SAMPLED,
+                true,
+                TraceState::default(),
+            );
+            let cx = Context::new().with_remote_span_context(parent);
+            let tracer = opentelemetry::global::tracer(TRACER_NAME);
+            let span = tracer
+                .span_builder(name)
+                .with_attributes(
+                    attributes.iter().map(|(k, v)| KeyValue::new(*k, v.clone())).collect::<Vec<_>>(),
+                )
+                .start_with_context(&tracer, &cx);
+            Self { inner: Some(span) }
+        }
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = (name, attributes);
+            Self::disabled()
+        }
+    }
+
+    /// Mark the span as failed
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        #[cfg(feature = "otel")]
+        if let Some(span) = self.inner.as_mut() {
+            use opentelemetry::trace::{Span as _, Status};
+            span.set_status(Status::error(message.into()));
+        }
+        #[cfg(not(feature = "otel"))]
+        let _ = message.into();
+    }
+}
+
+/// Span ID of a trace's root span
+#[cfg(feature = "otel")]
+fn root_span_id(trace_id: &TraceId) -> [u8; 8] {
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&trace_id[..8]);
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_trace_id_scoped_to_task() {
+        assert!(!is_traced(&current_trace_id()));
+
+        let id: TraceId = [7; 16];
+        let seen = with_trace_id(id, async { current_trace_id() }).await;
+        assert_eq!(seen, id);
+        assert!(!is_traced(&current_trace_id()));
+    }
+
+    #[test]
+    fn test_disabled_without_endpoint() {
+        init(&TelemetryConfig::default(), "node-1").unwrap();
+        assert!(!is_enabled());
+        assert!(!is_traced(&new_trace_id()));
+        let mut span = Span::child("db_execute", [1; 16], &[]);
+        span.set_error("ignored");
+    }
+}
@@ -0,0 +1,426 @@
+//! WAL Compaction
+//!
+//! Folds sealed segments into a single segment holding only the latest state
+//! of each row, so a follower catching up replays one entry per key instead
+//! of every intermediate UPDATE.
+//!
+//! Entries are grouped by `(database, table, primary_key)`. Within a group
+//! later UPDATEs are merged into the earlier INSERT/UPDATE and a DELETE
+//! discards everything before it. Entries without a primary key (DDL, raw
+//! SQL, transactions) are always kept and act as barriers: no group is
+//! folded across them, so schema changes stay correctly ordered with the
+//! row changes around them.
+//!
+//! The compacted segment is written to `wal/compacted/` and only replaces
+//! the originals once every follower has applied past its last LSN.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::entry::{LogEntry, Lsn, Value, WalEntry};
+use super::segment::{list_segments, Segment};
+use super::WalPaths;
+use crate::config::WalConfig;
+use crate::error::Result;
+use crate::metrics;
+
+/// Subdirectory of the WAL directory holding a compacted segment awaiting promotion
+const COMPACTED_DIR: &str = "compacted";
+
+/// Grouping key: (database, table, primary key)
+type RowKey = (Option<String>, String, String);
+
+/// Result of a compaction run
+#[derive(Debug, Clone)]
+pub struct CompactionStats {
+    /// Number of segments folded
+    pub segments: usize,
+    /// Entries before compaction
+    pub entries_before: usize,
+    /// Entries after compaction
+    pub entries_after: usize,
+    /// First LSN covered
+    pub first_lsn: Lsn,
+    /// Last LSN covered
+    pub last_lsn: Lsn,
+}
+
+impl CompactionStats {
+    /// Compacted size as a fraction of the original (lower is better)
+    pub fn ratio(&self) -> f64 {
+        if self.entries_before == 0 {
+            1.0
+        } else {
+            self.entries_after as f64 / self.entries_before as f64
+        }
+    }
+}
+
+/// Compacts sealed WAL segments of a data directory
+pub struct WalCompactor {
+    paths: WalPaths,
+    config: WalConfig,
+}
+
+impl WalCompactor {
+    pub fn new(data_dir: PathBuf, config: WalConfig) -> Self {
+        Self {
+            paths: WalPaths::new(data_dir.join("wal")),
+            config,
+        }
+    }
+
+    fn compacted_dir(&self) -> PathBuf {
+        self.paths.base_dir.join(COMPACTED_DIR)
+    }
+
+    /// Compacted segment waiting to replace the originals, if any
+    pub fn pending(&self) -> Result<Option<PathBuf>> {
+        Ok(list_segments(&self.compacted_dir())?.into_iter().next())
+    }
+
+    /// Compact all sealed segments if there are at least `threshold` of them.
+    ///
+    /// The active (last) segment is never touched. Returns None when there is
+    /// nothing to do, including while a previous compaction awaits promotion.
+    pub fn compact(&self, threshold: usize) -> Result<Option<CompactionStats>> {
+        self.remove_superseded()?;
+        if self.pending()?.is_some() {
+            tracing::debug!("Previous WAL compaction still awaiting promotion");
+            return Ok(None);
+        }
+
+        let mut segments = list_segments(&self.paths.base_dir)?;
+        segments.pop(); // active segment
+        if segments.is_empty() || segments.len() < threshold {
+            return Ok(None);
+        }
+
+        let mut entries = Vec::new();
+        let mut first_lsn = None;
+        for path in &segments {
+            let mut segment = Segment::open(path.clone(), self.config.segment_size_mb, self.config.compression)?
+                .with_torn_write_detection(self.config.torn_write_detection);
+            first_lsn.get_or_insert(segment.first_lsn());
+            for result in segment.iter() {
+                entries.push(result?);
+            }
+        }
+        let (Some(first_lsn), Some(last_lsn)) = (first_lsn, entries.last().map(|e| e.header.lsn)) else {
+            return Ok(None);
+        };
+
+        let entries_before = entries.len();
+        let compacted = compact_entries(entries);
+
+        // Same file name as the first original, so promotion is a rename over it
+        let file_name = segments[0].file_name().expect("segment path has a file name");
+        std::fs::create_dir_all(self.compacted_dir())?;
+        let tmp_path = self.compacted_dir().join("compacting.tmp");
+        let mut output = Segment::create(
+            tmp_path.clone(),
+            first_lsn,
+            self.config.segment_size_mb * segments.len() as u64,
+            self.config.compression,
+        )?;
+        for entry in &compacted {
+            output.append(entry)?;
+        }
+        output.seal()?;
+        drop(output);
+        std::fs::rename(&tmp_path, self.compacted_dir().join(file_name))?;
+
+        let stats = CompactionStats {
+            segments: segments.len(),
+            entries_before,
+            entries_after: compacted.len(),
+            first_lsn,
+            last_lsn,
+        };
+        metrics::WAL_COMPACTION_RATIO.set(stats.ratio());
+        tracing::info!(
+            "Compacted {} WAL segments (LSN {}-{}): {} -> {} entries",
+            stats.segments, first_lsn, last_lsn, entries_before, stats.entries_after
+        );
+        Ok(Some(stats))
+    }
+
+    /// Replace the original segments with the pending compacted segment once
+    /// every follower has applied at least `min_applied_lsn`.
+    /// Returns true if a compacted segment was promoted.
+    pub fn promote(&self, min_applied_lsn: Lsn) -> Result<bool> {
+        let Some(pending) = self.pending()? else {
+            return Ok(false);
+        };
+
+        let segment = Segment::open(pending.clone(), self.config.segment_size_mb, self.config.compression)?;
+        if min_applied_lsn < segment.last_lsn() {
+            return Ok(false);
+        }
+        let (first_lsn, last_lsn) = (segment.first_lsn(), segment.last_lsn());
+        drop(segment);
+
+        let file_name = pending.file_name().expect("segment path has a file name");
+        std::fs::rename(&pending, self.paths.base_dir.join(file_name))?;
+        self.remove_superseded()?;
+
+        tracing::info!("Promoted compacted WAL segment covering LSN {}-{}", first_lsn, last_lsn);
+        Ok(true)
+    }
+
+    /// Delete sealed segments whose LSN range is already covered by an earlier
+    /// (compacted) segment. Also finishes a promotion interrupted by a crash.
+    fn remove_superseded(&self) -> Result<()> {
+        let mut segments = list_segments(&self.paths.base_dir)?;
+        segments.pop(); // active segment
+
+        let mut covered_to: Lsn = 0;
+        for path in segments {
+            let segment = Segment::open(path.clone(), self.config.segment_size_mb, self.config.compression)?;
+            if covered_to > 0 && segment.first_lsn() <= covered_to {
+                drop(segment);
+                tracing::debug!("Removing superseded WAL segment {:?}", path);
+                std::fs::remove_file(&path)?;
+                continue;
+            }
+            covered_to = covered_to.max(segment.last_lsn());
+        }
+        Ok(())
+    }
+}
+
+/// Row key of an entry, or None if it must be kept as-is
+fn row_key(entry: &LogEntry) -> Option<RowKey> {
+    let (table, primary_key) = match entry {
+        LogEntry::Insert { table, primary_key, .. }
+        | LogEntry::Update { table, primary_key, .. }
+        | LogEntry::Delete { table, primary_key, .. }
+        | LogEntry::Upsert { table, primary_key, .. } => (table, primary_key),
+        _ => return None,
+    };
+
+    let (database, table) = match table.split_once('.') {
+        Some((db, tbl)) => (Some(db.to_string()), tbl.to_string()),
+        None => (None, table.clone()),
+    };
+    Some((database, table, format!("{:?}", primary_key)))
+}
+
+/// Set `column = value` pairs on a column list, replacing existing columns
+fn merge_columns(columns: &mut Vec<String>, values: &mut Vec<Value>, set_columns: &[String], set_values: &[Value]) {
+    for (column, value) in set_columns.iter().zip(set_values) {
+        match columns.iter().position(|c| c == column) {
+            Some(i) => values[i] = value.clone(),
+            None => {
+                columns.push(column.clone());
+                values.push(value.clone());
+            }
+        }
+    }
+}
+
+/// Fold a new entry into the folded entries of its row
+fn fold(slot: &mut Vec<WalEntry>, next: WalEntry) {
+    let (set_columns, set_values, next_key_columns) = match &next.entry {
+        LogEntry::Delete { .. } => {
+            // DELETE wins over everything before it
+            slot.clear();
+            slot.push(next);
+            return;
+        }
+        LogEntry::Update { set_columns, set_values, key_columns, .. } => (set_columns, set_values, key_columns),
+        _ => {
+            slot.push(next);
+            return;
+        }
+    };
+
+    let merged = match slot.last().map(|e| &e.entry) {
+        Some(LogEntry::Insert { table, columns, values, primary_key })
+        | Some(LogEntry::Upsert { table, columns, values, primary_key, .. }) => {
+            // Replaying an INSERT that may already exist must not fail, so the
+            // merged row is written as an upsert of every column
+            let (mut columns, mut values) = (columns.clone(), values.clone());
+            merge_columns(&mut columns, &mut values, set_columns, set_values);
+            Some(LogEntry::Upsert {
+                table: table.clone(),
+                update_columns: columns.clone(),
+                columns,
+                values,
+                primary_key: primary_key.clone(),
+            })
+        }
+        Some(LogEntry::Update { table, set_columns: prev_columns, set_values: prev_values, primary_key, key_columns })
+            if key_columns == next_key_columns =>
+        {
+            let (mut columns, mut values) = (prev_columns.clone(), prev_values.clone());
+            merge_columns(&mut columns, &mut values, set_columns, set_values);
+            Some(LogEntry::Update {
+                table: table.clone(),
+                set_columns: columns,
+                set_values: values,
+                primary_key: primary_key.clone(),
+                key_columns: key_columns.clone(),
+            })
+        }
+        _ => None,
+    };
+
+    match merged {
+        Some(entry) => {
+            // The merged entry takes the position of the newest change
+            let mut merged = WalEntry::new(next.header.lsn, next.header.term, next.header.origin_node, entry);
+            merged.header.timestamp = next.header.timestamp;
+            *slot.last_mut().unwrap() = merged;
+        }
+        None => slot.push(next),
+    }
+}
+
+/// Compact a run of WAL entries (in LSN order), keeping only the latest
+/// state per row. The output is in LSN order.
+pub fn compact_entries(entries: Vec<WalEntry>) -> Vec<WalEntry> {
+    let mut output = Vec::new();
+    let mut rows: BTreeMap<RowKey, Vec<WalEntry>> = BTreeMap::new();
+
+    fn flush(rows: &mut BTreeMap<RowKey, Vec<WalEntry>>, output: &mut Vec<WalEntry>) {
+        let mut folded: Vec<WalEntry> = std::mem::take(rows).into_values().flatten().collect();
+        folded.sort_by_key(|e| e.header.lsn);
+        output.extend(folded);
+    }
+
+    for entry in entries {
+        match row_key(&entry.entry) {
+            Some(key) => fold(rows.entry(key).or_default(), entry),
+            None => {
+                flush(&mut rows, &mut output);
+                output.push(entry);
+            }
+        }
+    }
+    flush(&mut rows, &mut output);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::entry::PrimaryKey;
+    use crate::wal::WalReader;
+
+    fn test_config() -> WalConfig {
+        WalConfig {
+            flush_interval_ms: 10,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
+            compression: true,
+            segment_size_mb: 64,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
+            retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
+            fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: true,
+            compaction_threshold_segments: 1,
+        }
+    }
+
+    fn update(id: i64, column: &str, value: i64) -> LogEntry {
+        LogEntry::Update {
+            table: "accounts".to_string(),
+            set_columns: vec![column.to_string()],
+            set_values: vec![Value::Int(value)],
+            primary_key: PrimaryKey::Int(id),
+            key_columns: vec!["id".to_string()],
+        }
+    }
+
+    fn wal(entries: Vec<LogEntry>) -> Vec<WalEntry> {
+        entries.into_iter().enumerate()
+            .map(|(i, e)| WalEntry::new(i as Lsn + 1, 1, "node-1".to_string(), e))
+            .collect()
+    }
+
+    #[test]
+    fn test_fold_rules() {
+        let insert = LogEntry::Insert {
+            table: "accounts".to_string(),
+            columns: vec!["id".to_string(), "balance".to_string()],
+            values: vec![Value::Int(1), Value::Int(0)],
+            primary_key: PrimaryKey::Int(1),
+        };
+        let delete = LogEntry::Delete {
+            table: "accounts".to_string(),
+            primary_key: PrimaryKey::Int(2),
+            key_columns: vec!["id".to_string()],
+        };
+        let ddl = LogEntry::AlterTable {
+            table: "accounts".to_string(),
+            ddl: "ALTER TABLE accounts ADD note TEXT".to_string(),
+        };
+
+        let compacted = compact_entries(wal(vec![
+            insert,
+            update(1, "balance", 10),
+            update(2, "balance", 5),
+            update(1, "owner", 7),
+            delete,
+            ddl,
+            update(1, "balance", 20),
+        ]));
+
+        let lsns: Vec<Lsn> = compacted.iter().map(|e| e.header.lsn).collect();
+        assert_eq!(lsns, vec![4, 5, 6, 7]);
+
+        // INSERT + UPDATEs merge into one upsert carrying the newest LSN
+        match &compacted[0].entry {
+            LogEntry::Upsert { columns, values, .. } => {
+                assert_eq!(columns, &vec!["id".to_string(), "balance".to_string(), "owner".to_string()]);
+                assert_eq!(values, &vec![Value::Int(1), Value::Int(10), Value::Int(7)]);
+            }
+            other => panic!("expected upsert, got {:?}", other),
+        }
+        assert!(compacted[0].verify_checksum());
+        // DELETE replaces the earlier UPDATE of row 2; DDL is a barrier
+        assert!(matches!(compacted[1].entry, LogEntry::Delete { .. }));
+        assert!(matches!(compacted[2].entry, LogEntry::AlterTable { .. }));
+        assert!(matches!(compacted[3].entry, LogEntry::Update { .. }));
+    }
+
+    #[test]
+    fn test_compact_repeated_updates_to_one_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = WalPaths::new(dir.path().join("wal"));
+        paths.ensure_dirs().unwrap();
+
+        let mut sealed = Segment::create(paths.segment_path(1), 1, 64, true).unwrap();
+        for (lsn, entry) in (1..=10_000).zip(0..) {
+            sealed.append(&WalEntry::new(lsn, 1, "node-1".to_string(), update(1, "balance", entry))).unwrap();
+        }
+        sealed.seal().unwrap();
+        let mut active = Segment::create(paths.segment_path(10_001), 10_001, 64, true).unwrap();
+        active.append(&WalEntry::new(10_001, 1, "node-1".to_string(), LogEntry::Noop)).unwrap();
+        drop((sealed, active));
+
+        let compactor = WalCompactor::new(dir.path().to_path_buf(), test_config());
+        let stats = compactor.compact(1).unwrap().unwrap();
+        assert_eq!((stats.entries_before, stats.entries_after), (10_000, 1));
+        assert_eq!(stats.last_lsn, 10_000);
+
+        // Originals stay until every follower has applied the compacted range
+        assert!(!compactor.promote(9_999).unwrap());
+        assert!(compactor.promote(10_000).unwrap());
+
+        let reader = WalReader::new(dir.path().to_path_buf(), 64, true).unwrap();
+        let entries = reader.read_from(1).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].header.lsn, 10_000);
+        match &entries[0].entry {
+            LogEntry::Update { set_values, .. } => assert_eq!(set_values, &vec![Value::Int(9_999)]),
+            other => panic!("expected update, got {:?}", other),
+        }
+    }
+}
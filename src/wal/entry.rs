@@ -5,6 +5,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::telemetry::TraceId;
+
 /// Log Sequence Number - unique identifier for each log entry
 pub type Lsn = u64;
 
@@ -171,6 +173,11 @@ pub struct EntryHeader {
     pub body_size: u32,
     /// Whether the body is compressed
     pub compressed: bool,
+    /// Trace of the request that produced this entry (all zeros if untraced)
+    pub trace_id: TraceId,
+    /// Schema version in effect once this entry is applied: the number of
+    /// schema-changing entries written up to and including it (0 if unversioned)
+    pub schema_version: u64,
 }
 
 /// Log entry types representing database operations
@@ -249,6 +256,8 @@ pub enum LogEntry {
     /// Transaction wrapper containing multiple entries
     Transaction {
         entries: Vec<LogEntry>,
+        /// ID of the HTTP API transaction that produced this batch
+        transaction_id: Option<uuid::Uuid>,
     },
 
     /// Raw SQL (for operations that don't fit other categories)
@@ -258,10 +267,21 @@ pub enum LogEntry {
         /// Database context (from USE statement) - prepended as USE before execution
         #[serde(default)]
         database: Option<String>,
+        /// MariaDB GTID (`domain-server_id-seq`) when captured from the binlog
+        gtid: Option<String>,
     },
 
     /// No-op entry (used for leader election heartbeats)
     Noop,
+
+    /// Snowflake IDs handed out by the leader's `/id` endpoints, up to and
+    /// including `highest_id`. Runs no SQL; followers record it so that they
+    /// never reissue an ID after taking over as leader.
+    IdAllocation {
+        /// Node ID bits of the allocated IDs
+        machine_id: u16,
+        highest_id: u64,
+    },
 }
 
 impl LogEntry {
@@ -278,9 +298,9 @@ impl LogEntry {
             | LogEntry::DropTable { table }
             | LogEntry::CreateIndex { table, .. }
             | LogEntry::DropIndex { table, .. } => Some(table),
-            LogEntry::Transaction { entries } => entries.first().and_then(|e| e.table_name()),
+            LogEntry::Transaction { entries, .. } => entries.first().and_then(|e| e.table_name()),
             LogEntry::RawSql { affects_table, .. } => affects_table.as_deref(),
-            LogEntry::Noop => None,
+            LogEntry::Noop | LogEntry::IdAllocation { .. } => None,
         }
     }
 
@@ -296,11 +316,67 @@ impl LogEntry {
         )
     }
 
+    /// Check if this entry changes the schema: typed DDL, or raw SQL that
+    /// creates, alters, drops or renames a table or index
+    pub fn changes_schema(&self) -> bool {
+        match self {
+            LogEntry::RawSql { sql, .. } => {
+                let mut words = sql.split_whitespace().take(4).map(str::to_ascii_uppercase);
+                let verb = words.next().unwrap_or_default();
+                matches!(verb.as_str(), "CREATE" | "ALTER" | "DROP" | "RENAME")
+                    && words.any(|w| w == "TABLE" || w == "INDEX")
+            }
+            LogEntry::Transaction { entries, .. } => entries.iter().any(|e| e.changes_schema()),
+            other => other.is_ddl(),
+        }
+    }
+
     /// Check if this entry is a no-op
     pub fn is_noop(&self) -> bool {
         matches!(self, LogEntry::Noop)
     }
 
+    /// Short operation name used in exports and the audit log
+    pub fn operation_name(&self) -> &'static str {
+        match self {
+            LogEntry::Insert { .. } => "insert",
+            LogEntry::Update { .. } => "update",
+            LogEntry::Delete { .. } => "delete",
+            LogEntry::Upsert { .. } => "upsert",
+            LogEntry::BulkInsert { .. } => "bulk_insert",
+            LogEntry::AlterTable { .. } => "alter_table",
+            LogEntry::CreateTable { .. } => "create_table",
+            LogEntry::DropTable { .. } => "drop_table",
+            LogEntry::CreateIndex { .. } => "create_index",
+            LogEntry::DropIndex { .. } => "drop_index",
+            LogEntry::Transaction { .. } => "transaction",
+            LogEntry::RawSql { .. } => "raw_sql",
+            LogEntry::Noop => "noop",
+            LogEntry::IdAllocation { .. } => "id_allocation",
+        }
+    }
+
+    /// Database the entry was captured in, if recorded. A transaction's
+    /// writes all share one database.
+    pub fn database(&self) -> Option<&str> {
+        match self {
+            LogEntry::RawSql { database, .. } => database.as_deref(),
+            LogEntry::Transaction { entries, .. } => entries.first().and_then(|e| e.database()),
+            _ => None,
+        }
+    }
+
+    /// Primary key of the affected row, for single-row changes
+    pub fn primary_key(&self) -> Option<&PrimaryKey> {
+        match self {
+            LogEntry::Insert { primary_key, .. }
+            | LogEntry::Update { primary_key, .. }
+            | LogEntry::Delete { primary_key, .. }
+            | LogEntry::Upsert { primary_key, .. } => Some(primary_key),
+            _ => None,
+        }
+    }
+
     /// Convert to SQL statement(s)
     pub fn to_sql(&self) -> Vec<String> {
         match self {
@@ -422,7 +498,7 @@ impl LogEntry {
                 vec![format!("DROP INDEX `{}` ON `{}`", index_name, table)]
             }
 
-            LogEntry::Transaction { entries } => {
+            LogEntry::Transaction { entries, .. } => {
                 let mut sql = vec!["START TRANSACTION".to_string()];
                 for entry in entries {
                     sql.extend(entry.to_sql());
@@ -433,7 +509,7 @@ impl LogEntry {
 
             LogEntry::RawSql { sql, .. } => vec![sql.clone()],
 
-            LogEntry::Noop => vec![],
+            LogEntry::Noop | LogEntry::IdAllocation { .. } => vec![],
         }
     }
 
@@ -470,6 +546,8 @@ impl WalEntry {
                 checksum,
                 body_size: serialized.len() as u32,
                 compressed: false,
+                trace_id: TraceId::default(),
+                schema_version: 0,
             },
             entry,
         }
@@ -547,4 +625,70 @@ mod tests {
             _ => panic!("Wrong entry type after deserialize"),
         }
     }
+
+    /// Written by a build from before the trace ID, schema version,
+    /// transaction ID and GTID fields
+    const V1_ENTRY: &[u8] = include_bytes!("testdata/entry_v1.bin");
+
+    #[test]
+    fn test_decode_version_1_entry() {
+        let entry = crate::wal::legacy::decode_entry(V1_ENTRY).unwrap();
+
+        assert_eq!(entry.header.lsn, 9);
+        assert_eq!(entry.header.origin_node, "node-1");
+        assert!(entry.verify_checksum());
+        assert_eq!(entry.header.trace_id, TraceId::default());
+        assert_eq!(entry.header.schema_version, 0);
+        match &entry.entry {
+            LogEntry::Transaction { entries, transaction_id } => {
+                assert_eq!(entries.len(), 2);
+                assert_eq!(*transaction_id, None);
+                match &entries[1] {
+                    LogEntry::RawSql { database, gtid, .. } => {
+                        assert_eq!(database.as_deref(), Some("shop"));
+                        assert_eq!(*gtid, None);
+                    }
+                    other => panic!("unexpected entry {}", other.operation_name()),
+                }
+            }
+            other => panic!("unexpected entry {}", other.operation_name()),
+        }
+    }
+
+    #[test]
+    fn test_gtid_round_trip() {
+        let entry = LogEntry::RawSql {
+            sql: "DELETE FROM audit".to_string(),
+            affects_table: Some("audit".to_string()),
+            database: None,
+            gtid: Some("0-1-42".to_string()),
+        };
+
+        match LogEntry::deserialize(&entry.serialize().unwrap()).unwrap() {
+            LogEntry::RawSql { gtid, .. } => assert_eq!(gtid.as_deref(), Some("0-1-42")),
+            other => panic!("unexpected entry {}", other.operation_name()),
+        }
+    }
+
+    #[test]
+    fn test_transaction_id_round_trip() {
+        let id = uuid::Uuid::new_v4();
+        let entry = LogEntry::Transaction { entries: vec![LogEntry::Noop], transaction_id: Some(id) };
+
+        match LogEntry::deserialize(&entry.serialize().unwrap()).unwrap() {
+            LogEntry::Transaction { transaction_id, .. } => assert_eq!(transaction_id, Some(id)),
+            other => panic!("unexpected entry {}", other.operation_name()),
+        }
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let mut entry = WalEntry::new(1, 1, "node-1".to_string(), LogEntry::Noop);
+        entry.header.trace_id = [7; 16];
+        entry.header.schema_version = 12;
+
+        let restored: WalEntry = bincode::deserialize(&bincode::serialize(&entry).unwrap()).unwrap();
+        assert_eq!(restored.header.trace_id, [7; 16]);
+        assert_eq!(restored.header.schema_version, 12);
+    }
 }
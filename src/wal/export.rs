@@ -0,0 +1,245 @@
+//! WAL Export
+//!
+//! Writes WAL entries to newline-delimited JSON or CSV for offline
+//! analysis. Entries are streamed segment by segment and written as they
+//! are read, so exports of any size run in constant memory.
+
+use std::io::Write;
+
+use serde_json::json;
+
+use super::entry::{LogEntry, Lsn, WalEntry};
+use super::reader::WalReader;
+use crate::error::{Error, Result};
+
+/// Output format for `wolfscale wal export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per entry
+    Json,
+    /// `lsn,timestamp_ms,database,table,operation,sql,primary_key`
+    Csv,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "csv" => Ok(ExportFormat::Csv),
+            other => Err(Error::Config(format!("Unknown export format '{}' (expected json or csv)", other))),
+        }
+    }
+}
+
+/// What to export and how
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// First LSN to export (inclusive)
+    pub from_lsn: Lsn,
+    /// Last LSN to export (inclusive, None = end of log)
+    pub to_lsn: Option<Lsn>,
+    /// Indent JSON objects (ignored for CSV)
+    pub pretty: bool,
+    /// Only export entries affecting this table
+    pub filter_table: Option<String>,
+}
+
+/// CSV column names
+const CSV_HEADER: &str = "lsn,timestamp_ms,database,table,operation,sql,primary_key";
+
+/// Stream matching entries from `reader` to `out`, returning how many were written
+pub fn export_wal<W: Write>(reader: &WalReader, options: &ExportOptions, mut out: W) -> Result<u64> {
+    if options.format == ExportFormat::Csv {
+        writeln!(out, "{}", CSV_HEADER)?;
+    }
+
+    let mut written = 0;
+    for result in reader.stream_from(options.from_lsn) {
+        let entry = result?;
+        if options.to_lsn.is_some_and(|to| entry.header.lsn > to) {
+            break;
+        }
+        if let Some(ref table) = options.filter_table {
+            if !matches_table(&entry.entry, table) {
+                continue;
+            }
+        }
+
+        match options.format {
+            ExportFormat::Json => {
+                let value = entry_to_json(&entry);
+                if options.pretty {
+                    serde_json::to_writer_pretty(&mut out, &value)
+                } else {
+                    serde_json::to_writer(&mut out, &value)
+                }
+                .map_err(|e| Error::Internal(format!("JSON encoding failed: {}", e)))?;
+                writeln!(out)?;
+            }
+            ExportFormat::Csv => write_csv_row(&mut out, &entry)?,
+        }
+        written += 1;
+    }
+
+    out.flush()?;
+    Ok(written)
+}
+
+/// JSON representation of an entry: summary fields plus the full `LogEntry`
+pub fn entry_to_json(entry: &WalEntry) -> serde_json::Value {
+    json!({
+        "lsn": entry.header.lsn,
+        "term": entry.header.term,
+        "timestamp_ms": entry.header.timestamp.timestamp_millis(),
+        "origin_node": entry.header.origin_node,
+        "database": entry.entry.database(),
+        "table": entry.entry.table_name(),
+        "operation": entry.entry.operation_name(),
+        "primary_key": entry.entry.primary_key().map(|pk| pk.to_string()),
+        "sql": entry.entry.to_sql(),
+        "entry": entry.entry,
+    })
+}
+
+fn write_csv_row<W: Write>(out: &mut W, entry: &WalEntry) -> Result<()> {
+    let fields = [
+        entry.header.lsn.to_string(),
+        entry.header.timestamp.timestamp_millis().to_string(),
+        entry.entry.database().unwrap_or_default().to_string(),
+        entry.entry.table_name().unwrap_or_default().to_string(),
+        entry.entry.operation_name().to_string(),
+        entry.entry.to_sql().join("; "),
+        entry.entry.primary_key().map(|pk| pk.to_string()).unwrap_or_default(),
+    ];
+    let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+    writeln!(out, "{}", row.join(","))?;
+    Ok(())
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn matches_table(entry: &LogEntry, table: &str) -> bool {
+    match entry {
+        LogEntry::Transaction { entries, .. } => entries.iter().any(|e| matches_table(e, table)),
+        _ => entry.table_name().is_some_and(|t| {
+            let t = t.trim_matches('`');
+            t == table || t.rsplit('.').next() == Some(table)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::entry::{PrimaryKey, Value};
+    use crate::wal::{Segment, WalPaths};
+
+    fn varied_entry(i: i64) -> LogEntry {
+        let table = if i % 2 == 0 { "users" } else { "orders" }.to_string();
+        match i % 5 {
+            0 => LogEntry::Insert {
+                table,
+                columns: vec!["id".to_string(), "name".to_string()],
+                values: vec![Value::Int(i), Value::String(format!("user, \"{}\"", i))],
+                primary_key: PrimaryKey::Int(i),
+            },
+            1 => LogEntry::Update {
+                table,
+                set_columns: vec!["name".to_string()],
+                set_values: vec![Value::String("line\nbreak".to_string())],
+                primary_key: PrimaryKey::Int(i),
+                key_columns: vec!["id".to_string()],
+            },
+            2 => LogEntry::Delete {
+                table,
+                primary_key: PrimaryKey::Int(i),
+                key_columns: vec!["id".to_string()],
+            },
+            3 => LogEntry::RawSql {
+                sql: format!("UPDATE {} SET n = n + 1", table),
+                affects_table: Some(table),
+                database: Some("app".to_string()),
+                gtid: None,
+            },
+            _ => LogEntry::AlterTable {
+                ddl: format!("ALTER TABLE {} ADD c{} INT", table, i),
+                table,
+            },
+        }
+    }
+
+    fn write_wal(dir: &std::path::Path) -> WalReader {
+        let paths = WalPaths::new(dir.join("wal"));
+        paths.ensure_dirs().unwrap();
+        let mut segment = Segment::create(paths.segment_path(1), 1, 64, true).unwrap();
+        for i in 1..=100 {
+            segment.append(&WalEntry::new(i as Lsn, 1, "node-1".to_string(), varied_entry(i))).unwrap();
+        }
+        drop(segment);
+        WalReader::new(dir.to_path_buf(), 64, true).unwrap()
+    }
+
+    fn options(format: ExportFormat) -> ExportOptions {
+        ExportOptions { format, from_lsn: 1, to_lsn: None, pretty: false, filter_table: None }
+    }
+
+    #[test]
+    fn test_export_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let reader = write_wal(dir.path());
+
+        let mut out = Vec::new();
+        let opts = ExportOptions { from_lsn: 11, to_lsn: Some(60), ..options(ExportFormat::Json) };
+        assert_eq!(export_wal(&reader, &opts, &mut out).unwrap(), 50);
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(out).unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 50);
+        assert_eq!(lines[0]["lsn"], 11);
+        assert_eq!(lines[49]["lsn"], 60);
+        assert_eq!(lines[2]["operation"], "raw_sql");
+        assert_eq!(lines[2]["database"], "app");
+        assert_eq!(lines[0]["entry"]["Update"]["primary_key"]["Int"], 11);
+
+        let mut pretty = Vec::new();
+        let opts = ExportOptions { pretty: true, filter_table: Some("users".to_string()), ..options(ExportFormat::Json) };
+        assert_eq!(export_wal(&reader, &opts, &mut pretty).unwrap(), 50);
+        let values: Vec<serde_json::Value> = serde_json::Deserializer::from_slice(&pretty)
+            .into_iter()
+            .map(|v| v.unwrap())
+            .collect();
+        assert!(values.iter().all(|v| v["table"] == "users"));
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let reader = write_wal(dir.path());
+
+        let mut out = Vec::new();
+        assert_eq!(export_wal(&reader, &options(ExportFormat::Csv), &mut out).unwrap(), 100);
+
+        let mut csv = csv::Reader::from_reader(out.as_slice());
+        assert_eq!(csv.headers().unwrap().iter().collect::<Vec<_>>().join(","), CSV_HEADER);
+        let rows: Vec<csv::StringRecord> = csv.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 100);
+        assert_eq!(&rows[3][0], "4");
+        assert_eq!(&rows[3][4], "alter_table");
+        assert_eq!(&rows[9][4], "insert");
+        assert!(rows[9][5].contains("'user, \"10\"'"));
+        assert_eq!(&rows[9][6], "10");
+        assert!(rows[0][5].contains("line\nbreak"));
+    }
+}
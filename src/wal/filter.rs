@@ -0,0 +1,931 @@
+//! Column Filtering
+//!
+//! Keeps the columns listed in `[[replication.column_filter]]` out of the
+//! WAL, so sensitive data (password hashes, national ID numbers, ...) never
+//! reaches a follower. Typed entries lose the columns directly. Raw SQL
+//! `INSERT`/`REPLACE` and `UPDATE` statements are rewritten: the columns are
+//! dropped from the column list and every row, from `SET` and
+//! `ON DUPLICATE KEY UPDATE`, and from the `AND` terms of the `WHERE` clause.
+//!
+//! A statement that can't be rewritten safely is left out of the entry with
+//! a warning: an `INSERT` without a column list or from a `SELECT`, and an
+//! `UPDATE` whose `WHERE` would be left empty (it would match every row on
+//! the follower) or that tests an excluded column under `OR`.
+//!
+//! The same tokenizer tells the executor's undo log which rows a statement
+//! writes (`row_targets`), and a follower which tables an entry's SQL writes
+//! before it runs it (`statement_shapes`).
+
+use std::collections::{HashMap, HashSet};
+
+use crate::config::ColumnFilterConfig;
+use super::entry::LogEntry;
+
+/// Excluded columns per table, built from `replication.column_filter`
+#[derive(Debug, Clone, Default)]
+pub struct ColumnFilter {
+    /// (database, table) -> excluded columns, all lowercase
+    tables: HashMap<(String, String), HashSet<String>>,
+}
+
+impl ColumnFilter {
+    pub fn new(rules: &[ColumnFilterConfig]) -> Self {
+        let mut tables: HashMap<(String, String), HashSet<String>> = HashMap::new();
+        for rule in rules {
+            tables
+                .entry((rule.database.to_lowercase(), rule.table.to_lowercase()))
+                .or_default()
+                .extend(rule.exclude_columns.iter().map(|c| c.to_lowercase()));
+        }
+        Self { tables }
+    }
+
+    /// Whether no columns are filtered
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Columns excluded from `table`. Without a known database every
+    /// database's rules for the table apply.
+    fn excluded(&self, database: Option<&str>, table: &str) -> HashSet<String> {
+        let table = table.to_lowercase();
+        let database = database.map(str::to_lowercase);
+        self.tables.iter()
+            .filter(|((db, t), _)| *t == table && database.as_ref().is_none_or(|d| d == db))
+            .flat_map(|(_, cols)| cols.iter().cloned())
+            .collect()
+    }
+
+    /// Remove excluded columns from an entry before it is written
+    pub fn apply(&self, entry: &mut LogEntry) {
+        match entry {
+            LogEntry::Insert { table, columns, values, .. } => {
+                let excluded = self.excluded_from(table);
+                retain_columns(columns, &excluded, &mut [values]);
+            }
+            LogEntry::Update { table, set_columns, set_values, .. } => {
+                let excluded = self.excluded_from(table);
+                retain_columns(set_columns, &excluded, &mut [set_values]);
+            }
+            LogEntry::Upsert { table, columns, values, update_columns, .. } => {
+                let excluded = self.excluded_from(table);
+                retain_columns(columns, &excluded, &mut [values]);
+                update_columns.retain(|c| !excluded.contains(&c.to_lowercase()));
+            }
+            LogEntry::BulkInsert { table, columns, rows } => {
+                let excluded = self.excluded_from(table);
+                let mut rows: Vec<&mut Vec<_>> = rows.iter_mut().collect();
+                retain_columns(columns, &excluded, &mut rows);
+            }
+            LogEntry::Transaction { entries, .. } => {
+                for entry in entries {
+                    self.apply(entry);
+                }
+            }
+            LogEntry::RawSql { sql, database, .. } => {
+                if let Some(rewritten) = rewrite_sql(sql, database.as_deref(), &|db, table| self.excluded(db, table)) {
+                    *sql = rewritten;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Excluded columns for a typed entry's (possibly `db.table`) table name
+    fn excluded_from(&self, table: &str) -> HashSet<String> {
+        let table = table.replace('`', "");
+        match table.split_once('.') {
+            Some((db, table)) => self.excluded(Some(db), table),
+            None => self.excluded(None, &table),
+        }
+    }
+}
+
+/// Drop the excluded columns from `columns` and the matching positions of
+/// each list in `values`
+fn retain_columns<T>(columns: &mut Vec<String>, excluded: &HashSet<String>, values: &mut [&mut Vec<T>]) {
+    if excluded.is_empty() {
+        return;
+    }
+    let keep: Vec<bool> = columns.iter().map(|c| !excluded.contains(&c.to_lowercase())).collect();
+    for list in values.iter_mut() {
+        let mut i = 0;
+        list.retain(|_| {
+            i += 1;
+            keep.get(i - 1).copied().unwrap_or(true)
+        });
+    }
+    let mut i = 0;
+    columns.retain(|_| {
+        i += 1;
+        keep[i - 1]
+    });
+}
+
+/// Remove `column` from every statement in `sql`, for a follower whose table
+/// doesn't have it. Returns `None` if nothing referenced it; the result is
+/// empty if no statement is left.
+pub fn drop_column(sql: &str, column: &str) -> Option<String> {
+    let column = column.to_lowercase();
+    rewrite_sql(sql, None, &|_, _| HashSet::from([column.clone()]))
+}
+
+/// Excluded columns for a (database, table)
+type Exclusions<'a> = dyn Fn(Option<&str>, &str) -> HashSet<String> + 'a;
+
+/// Rewrite the statements in `sql` without excluded columns. Returns `None`
+/// when no statement changed.
+fn rewrite_sql(sql: &str, database: Option<&str>, excluded: &Exclusions) -> Option<String> {
+    let tokens = tokenize(sql);
+    let mut edits: Vec<Edit> = Vec::new();
+    let mut changed = false;
+
+    for (start, i) in statement_ranges(sql, &tokens) {
+        let stmt = Statement { sql, tokens: &tokens[start..i], database };
+        match stmt.rewrite(excluded) {
+            Outcome::Keep => {}
+            Outcome::Edit(mut e) => {
+                changed = true;
+                edits.append(&mut e);
+            }
+            Outcome::Drop(reason) => {
+                changed = true;
+                if let Some(reason) = reason {
+                    tracing::warn!("Column filter: not replicating `{}`: {}", stmt.text(), reason);
+                }
+                // Take the statement's terminating semicolon with it
+                let end = tokens.get(i).map_or(stmt.tokens[stmt.tokens.len() - 1].end, |t| t.end);
+                edits.push(Edit { start: stmt.tokens[0].start, end, text: String::new() });
+            }
+        }
+    }
+
+    if !changed {
+        return None;
+    }
+    let mut out = sql.to_string();
+    edits.sort_by_key(|e| std::cmp::Reverse(e.start));
+    for edit in edits {
+        out.replace_range(edit.start..edit.end, &edit.text);
+    }
+    Some(out.trim().to_string())
+}
+
+/// Token ranges of the non-empty statements in `sql`, split at semicolons
+/// outside parentheses
+fn statement_ranges(sql: &str, tokens: &[Token]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut depth = 0i32;
+    for i in 0..=tokens.len() {
+        let end_of_statement = match tokens.get(i) {
+            None => true,
+            Some(t) if t.is_punct(sql, "(") => { depth += 1; false }
+            Some(t) if t.is_punct(sql, ")") => { depth -= 1; false }
+            Some(t) => depth == 0 && t.is_punct(sql, ";"),
+        };
+        if end_of_statement {
+            if i > start {
+                ranges.push((start, i));
+            }
+            start = i + 1;
+        }
+    }
+    ranges
+}
+
+/// Rows a data-changing statement writes, worked out without running it so
+/// they can be saved to the undo log first
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowTarget {
+    /// `UPDATE`/`DELETE`: the rows its `WHERE` (and any `ORDER BY`/`LIMIT`) selects
+    Matching { database: Option<String>, table: String, condition: String },
+    /// `INSERT`/`REPLACE ... VALUES`: the text of each row's values
+    Inserted { database: Option<String>, table: String, columns: Vec<String>, rows: Vec<Vec<String>> },
+}
+
+/// The rows each statement in `sql` writes. Statements that don't touch rows
+/// (`USE`, `SET`, transaction control) are skipped. Returns `None` if any
+/// statement's rows can't be told from its text: DDL, multi-table
+/// statements, `INSERT ... SELECT` or `UPDATE`/`DELETE` without `WHERE`.
+pub fn row_targets(sql: &str) -> Option<Vec<RowTarget>> {
+    let tokens = tokenize(sql);
+    let mut targets = Vec::new();
+    for (start, end) in statement_ranges(sql, &tokens) {
+        let stmt = Statement { sql, tokens: &tokens[start..end], database: None };
+        let first = &stmt.tokens[0];
+        if ["USE", "SET", "BEGIN", "START", "COMMIT", "LOCK", "UNLOCK"].iter().any(|w| first.is_word(sql, w)) {
+            continue;
+        }
+        targets.push(stmt.row_target()?);
+    }
+    Some(targets)
+}
+
+/// What one statement does, as far as its text tells
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementShape {
+    /// First keyword, uppercase (`INSERT`, `ALTER`, `SET`, ...)
+    pub verb: String,
+    /// Whether it changes the schema (`CREATE`, `ALTER`, `DROP`, `TRUNCATE`, `RENAME`)
+    pub ddl: bool,
+    /// Tables it writes, lowercase and without their database. Every table
+    /// a multi-table `UPDATE`/`DELETE` names is listed, written or not.
+    pub tables: Vec<String>,
+}
+
+/// The shape of each statement in `sql`
+pub fn statement_shapes(sql: &str) -> Vec<StatementShape> {
+    let tokens = tokenize(sql);
+    statement_ranges(sql, &tokens).into_iter()
+        .map(|(start, end)| {
+            let stmt = Statement { sql, tokens: &tokens[start..end], database: None };
+            let verb = stmt.tokens[0].text(sql).to_uppercase();
+            let ddl = ["CREATE", "ALTER", "DROP", "TRUNCATE", "RENAME"].contains(&verb.as_str());
+            StatementShape { verb, ddl, tables: stmt.written_tables() }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// Keyword, unquoted identifier or number
+    Word,
+    /// Backtick-quoted identifier
+    Quoted,
+    /// String literal
+    Literal,
+    Punct,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: Kind,
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn text<'a>(&self, sql: &'a str) -> &'a str {
+        &sql[self.start..self.end]
+    }
+
+    fn is_word(&self, sql: &str, word: &str) -> bool {
+        self.kind == Kind::Word && self.text(sql).eq_ignore_ascii_case(word)
+    }
+
+    fn is_punct(&self, sql: &str, punct: &str) -> bool {
+        self.kind == Kind::Punct && self.text(sql) == punct
+    }
+
+    /// Identifier name (unquoted, lowercase) if this token can be one
+    fn name(&self, sql: &str) -> Option<String> {
+        self.ident(sql).map(|n| n.to_lowercase())
+    }
+
+    /// Identifier (unquoted, as written) if this token can be one
+    fn ident(&self, sql: &str) -> Option<String> {
+        match self.kind {
+            Kind::Word => Some(self.text(sql).to_string()),
+            Kind::Quoted => {
+                let text = self.text(sql);
+                let inner = text.strip_prefix('`')?.strip_suffix('`').unwrap_or(&text[1..]);
+                Some(inner.replace("``", "`"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Split SQL into tokens, skipping whitespace and comments
+fn tokenize(sql: &str) -> Vec<Token> {
+    let b = sql.as_bytes();
+    let word_byte = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80;
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < b.len() {
+        let start = i;
+        let c = b[i];
+        let kind = match c {
+            _ if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'#' => {
+                i = sql[i..].find('\n').map_or(b.len(), |n| i + n);
+                continue;
+            }
+            b'-' if b.get(i + 1) == Some(&b'-') && b.get(i + 2).is_none_or(|c| c.is_ascii_whitespace()) => {
+                i = sql[i..].find('\n').map_or(b.len(), |n| i + n);
+                continue;
+            }
+            b'/' if b.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..].find("*/").map_or(b.len(), |n| i + 2 + n + 2);
+                continue;
+            }
+            b'`' | b'\'' | b'"' => {
+                i += 1;
+                while i < b.len() {
+                    // Backslash escapes (not in identifiers) and doubled quotes
+                    let escaped = b[i] == b'\\' && c != b'`';
+                    if escaped || (b[i] == c && b.get(i + 1) == Some(&c)) {
+                        i += 2;
+                    } else if b[i] == c {
+                        i += 1;
+                        break;
+                    } else {
+                        i += 1;
+                    }
+                }
+                if c == b'`' { Kind::Quoted } else { Kind::Literal }
+            }
+            _ if word_byte(c) => {
+                while i < b.len() && word_byte(b[i]) {
+                    i += 1;
+                }
+                Kind::Word
+            }
+            _ => {
+                i += 1;
+                Kind::Punct
+            }
+        };
+        tokens.push(Token { kind, start, end: i.min(b.len()) });
+    }
+    tokens
+}
+
+/// Text replacing `start..end` of the original SQL
+struct Edit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+enum Outcome {
+    Keep,
+    Edit(Vec<Edit>),
+    /// Leave the statement out, with the reason to warn about (None when
+    /// it simply has nothing left to do)
+    Drop(Option<String>),
+}
+
+/// One statement of a raw SQL entry
+struct Statement<'a> {
+    sql: &'a str,
+    tokens: &'a [Token],
+    database: Option<&'a str>,
+}
+
+impl Statement<'_> {
+    fn text(&self) -> &str {
+        &self.sql[self.tokens[0].start..self.tokens[self.tokens.len() - 1].end]
+    }
+
+    fn rewrite(&self, excluded: &Exclusions) -> Outcome {
+        let first = &self.tokens[0];
+        if first.is_word(self.sql, "INSERT") || first.is_word(self.sql, "REPLACE") {
+            self.rewrite_insert(excluded)
+        } else if first.is_word(self.sql, "UPDATE") {
+            self.rewrite_update(excluded)
+        } else {
+            Outcome::Keep
+        }
+    }
+
+    /// Skip the given modifier keywords from `i`
+    fn skip_words(&self, mut i: usize, words: &[&str]) -> usize {
+        while self.tokens.get(i).is_some_and(|t| words.iter().any(|w| t.is_word(self.sql, w))) {
+            i += 1;
+        }
+        i
+    }
+
+    /// `[db.]table` at `i`, returns (database, table, index after it)
+    fn table_name(&self, i: usize) -> Option<(Option<String>, String, usize)> {
+        let first = self.tokens.get(i)?.ident(self.sql)?;
+        if self.tokens.get(i + 1).is_some_and(|t| t.is_punct(self.sql, ".")) {
+            let table = self.tokens.get(i + 2)?.ident(self.sql)?;
+            Some((Some(first), table, i + 3))
+        } else {
+            Some((None, first, i + 1))
+        }
+    }
+
+    /// Excluded columns for the table at `i`, and the index after its name
+    fn excluded_for(&self, i: usize, excluded: &Exclusions) -> Option<(HashSet<String>, usize)> {
+        let (db, table, next) = self.table_name(i)?;
+        let db = db.as_deref().or(self.database);
+        Some((excluded(db, &table), next))
+    }
+
+    /// Index of the first of `words` at paren depth 0 in `from..to`
+    fn find_word(&self, from: usize, to: usize, words: &[&str]) -> Option<usize> {
+        let mut depth = 0;
+        for i in from..to {
+            let t = &self.tokens[i];
+            if t.is_punct(self.sql, "(") {
+                depth += 1;
+            } else if t.is_punct(self.sql, ")") {
+                depth -= 1;
+            } else if depth == 0 && words.iter().any(|w| t.is_word(self.sql, w)) {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    /// Index of the `)` closing the `(` at `open`
+    fn closing_paren(&self, open: usize) -> Option<usize> {
+        let mut depth = 0;
+        for i in open..self.tokens.len() {
+            if self.tokens[i].is_punct(self.sql, "(") {
+                depth += 1;
+            } else if self.tokens[i].is_punct(self.sql, ")") {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+        }
+        None
+    }
+
+    /// Split `from..to` at the given separator at paren depth 0 into index ranges
+    fn split(&self, from: usize, to: usize, is_sep: impl Fn(&Token) -> bool) -> Vec<(usize, usize)> {
+        let mut items = Vec::new();
+        let mut depth = 0;
+        let mut start = from;
+        for i in from..to {
+            let t = &self.tokens[i];
+            if t.is_punct(self.sql, "(") {
+                depth += 1;
+            } else if t.is_punct(self.sql, ")") {
+                depth -= 1;
+            } else if depth == 0 && is_sep(t) {
+                items.push((start, i));
+                start = i + 1;
+            }
+        }
+        if start < to {
+            items.push((start, to));
+        }
+        items
+    }
+
+    fn split_commas(&self, from: usize, to: usize) -> Vec<(usize, usize)> {
+        self.split(from, to, |t| t.is_punct(self.sql, ","))
+    }
+
+    /// Whether any identifier in `from..to` names an excluded column
+    /// (function names, followed by `(`, don't count)
+    fn references(&self, (from, to): (usize, usize), excluded: &HashSet<String>) -> bool {
+        (from..to).any(|i| {
+            let not_call = !self.tokens.get(i + 1).is_some_and(|t| t.is_punct(self.sql, "("));
+            not_call && self.tokens[i].name(self.sql).is_some_and(|n| excluded.contains(&n))
+        })
+    }
+
+    fn item_text(&self, (from, to): (usize, usize)) -> &str {
+        &self.sql[self.tokens[from].start..self.tokens[to - 1].end]
+    }
+
+    /// Replace the span of `items` with the kept ones joined by `sep`
+    fn list_edit(&self, items: &[(usize, usize)], keep: &[bool], sep: &str) -> Edit {
+        let text = items.iter().zip(keep)
+            .filter(|(_, &k)| k)
+            .map(|(&item, _)| self.item_text(item))
+            .collect::<Vec<_>>()
+            .join(sep);
+        Edit {
+            start: self.tokens[items[0].0].start,
+            end: self.tokens[items[items.len() - 1].1 - 1].end,
+            text,
+        }
+    }
+
+    /// Filter an assignment list (`SET`, `ON DUPLICATE KEY UPDATE`). Returns
+    /// the edit, if any, and whether any assignment is left.
+    fn filter_assignments(&self, from: usize, to: usize, excluded: &HashSet<String>) -> (Option<Edit>, bool) {
+        let items = self.split_commas(from, to);
+        let keep: Vec<bool> = items.iter().map(|&item| !self.references(item, excluded)).collect();
+        let any_left = keep.iter().any(|&k| k);
+        if items.is_empty() || keep.iter().all(|&k| k) {
+            return (None, any_left);
+        }
+        (Some(self.list_edit(&items, &keep, ", ")), any_left)
+    }
+
+    fn rewrite_insert(&self, excluded: &Exclusions) -> Outcome {
+        let i = self.skip_words(1, &["LOW_PRIORITY", "DELAYED", "HIGH_PRIORITY", "IGNORE", "INTO"]);
+        let Some((excluded, i)) = self.excluded_for(i, excluded) else { return Outcome::Keep };
+        if excluded.is_empty() {
+            return Outcome::Keep;
+        }
+        let end = self.tokens.len();
+        let on_duplicate = self.find_word(i, end, &["ON"])
+            .filter(|&on| self.tokens.get(on + 1).is_some_and(|t| t.is_word(self.sql, "DUPLICATE")));
+        let body_end = on_duplicate.unwrap_or(end);
+
+        let mut edits = Vec::new();
+        let Some(next) = self.tokens.get(i) else { return Outcome::Keep };
+        if next.is_word(self.sql, "SET") {
+            // INSERT ... SET col = value, ...
+            let (edit, any_left) = self.filter_assignments(i + 1, body_end, &excluded);
+            if !any_left {
+                return Outcome::Drop(Some("every column is filtered".into()));
+            }
+            edits.extend(edit);
+        } else if next.is_punct(self.sql, "(") {
+            let Some(close) = self.closing_paren(i) else { return Outcome::Keep };
+            let columns = self.split_commas(i + 1, close);
+            let keep: Vec<bool> = columns.iter().map(|&c| !self.references(c, &excluded)).collect();
+            if keep.iter().all(|&k| k) {
+                return self.finish_insert(on_duplicate, &excluded, edits);
+            }
+            if !keep.iter().any(|&k| k) {
+                return Outcome::Drop(Some("every column is filtered".into()));
+            }
+            let values = close + 1;
+            if !self.tokens.get(values).is_some_and(|t| t.is_word(self.sql, "VALUES") || t.is_word(self.sql, "VALUE")) {
+                return Outcome::Drop(Some("filtered columns are inserted from a query".into()));
+            }
+            edits.push(self.list_edit(&columns, &keep, ", "));
+            for row in self.split_commas(values + 1, body_end) {
+                let (open, row_end) = row;
+                if !self.tokens[open].is_punct(self.sql, "(") || !self.tokens[row_end - 1].is_punct(self.sql, ")") {
+                    return Outcome::Drop(Some("could not parse the VALUES rows".into()));
+                }
+                let fields = self.split_commas(open + 1, row_end - 1);
+                if fields.len() != columns.len() {
+                    return Outcome::Drop(Some("a row has a different number of values than columns".into()));
+                }
+                edits.push(self.list_edit(&fields, &keep, ", "));
+            }
+        } else {
+            return Outcome::Drop(Some("INSERT into a filtered table needs a column list".into()));
+        }
+        self.finish_insert(on_duplicate, &excluded, edits)
+    }
+
+    /// Filter `ON DUPLICATE KEY UPDATE`, dropping the clause if nothing is left
+    fn finish_insert(&self, on_duplicate: Option<usize>, excluded: &HashSet<String>, mut edits: Vec<Edit>) -> Outcome {
+        if let Some(on) = on_duplicate {
+            let end = self.tokens.len();
+            let from = self.skip_words(on, &["ON", "DUPLICATE", "KEY", "UPDATE"]);
+            match self.filter_assignments(from, end, excluded) {
+                (_, false) => edits.push(Edit {
+                    start: self.tokens[on].start,
+                    end: self.tokens[end - 1].end,
+                    text: String::new(),
+                }),
+                (edit, true) => edits.extend(edit),
+            }
+        }
+        if edits.is_empty() { Outcome::Keep } else { Outcome::Edit(edits) }
+    }
+
+    fn rewrite_update(&self, excluded: &Exclusions) -> Outcome {
+        let i = self.skip_words(1, &["LOW_PRIORITY", "IGNORE"]);
+        let Some((excluded, i)) = self.excluded_for(i, excluded) else { return Outcome::Keep };
+        if excluded.is_empty() {
+            return Outcome::Keep;
+        }
+        let end = self.tokens.len();
+        let Some(set) = self.find_word(i, end, &["SET"]) else { return Outcome::Keep };
+        let where_at = self.find_word(set, end, &["WHERE"]);
+        let set_end = self.find_word(set, end, &["WHERE", "ORDER", "LIMIT"]).unwrap_or(end);
+
+        let mut edits = Vec::new();
+        let (edit, any_left) = self.filter_assignments(set + 1, set_end, &excluded);
+        if !any_left {
+            // Nothing left to change on the follower
+            return Outcome::Drop(None);
+        }
+        edits.extend(edit);
+
+        if let Some(w) = where_at {
+            let where_end = self.find_word(w, end, &["ORDER", "LIMIT"]).unwrap_or(end);
+            if self.references((w + 1, where_end), &excluded) {
+                if self.find_word(w + 1, where_end, &["OR", "XOR"]).is_some()
+                    || (w + 1..where_end).any(|i| self.tokens[i].is_punct(self.sql, "|"))
+                {
+                    return Outcome::Drop(Some("WHERE tests a filtered column under OR".into()));
+                }
+                let terms = self.and_terms(w + 1, where_end);
+                let keep: Vec<bool> = terms.iter().map(|&t| !self.references(t, &excluded)).collect();
+                if !keep.iter().any(|&k| k) {
+                    return Outcome::Drop(Some("WHERE only tests filtered columns".into()));
+                }
+                edits.push(self.list_edit(&terms, &keep, " AND "));
+            }
+        }
+        if edits.is_empty() { Outcome::Keep } else { Outcome::Edit(edits) }
+    }
+
+    fn row_target(&self) -> Option<RowTarget> {
+        let first = &self.tokens[0];
+        let end = self.tokens.len();
+        if first.is_word(self.sql, "INSERT") || first.is_word(self.sql, "REPLACE") {
+            let i = self.skip_words(1, &["LOW_PRIORITY", "DELAYED", "HIGH_PRIORITY", "IGNORE", "INTO"]);
+            let (database, table, i) = self.table_name(i)?;
+            if !self.tokens.get(i)?.is_punct(self.sql, "(") {
+                return None;
+            }
+            let close = self.closing_paren(i)?;
+            let columns = self.split_commas(i + 1, close).into_iter()
+                .map(|(from, to)| (to == from + 1).then(|| self.tokens[from].ident(self.sql)).flatten())
+                .collect::<Option<Vec<_>>>()?;
+            let values = close + 1;
+            if !self.tokens.get(values).is_some_and(|t| t.is_word(self.sql, "VALUES") || t.is_word(self.sql, "VALUE")) {
+                return None;
+            }
+            let on_duplicate = self.find_word(values, end, &["ON"]).unwrap_or(end);
+            let mut rows = Vec::new();
+            for (open, row_end) in self.split_commas(values + 1, on_duplicate) {
+                if !self.tokens[open].is_punct(self.sql, "(") || !self.tokens[row_end - 1].is_punct(self.sql, ")") {
+                    return None;
+                }
+                let fields: Vec<String> = self.split_commas(open + 1, row_end - 1).into_iter()
+                    .map(|field| self.item_text(field).to_string())
+                    .collect();
+                if fields.len() != columns.len() {
+                    return None;
+                }
+                rows.push(fields);
+            }
+            Some(RowTarget::Inserted { database, table, columns, rows })
+        } else if first.is_word(self.sql, "UPDATE") || first.is_word(self.sql, "DELETE") {
+            let i = if first.is_word(self.sql, "UPDATE") {
+                self.skip_words(1, &["LOW_PRIORITY", "IGNORE"])
+            } else {
+                self.skip_words(1, &["LOW_PRIORITY", "QUICK", "IGNORE"])
+            };
+            let i = if first.is_word(self.sql, "DELETE") {
+                self.tokens.get(i).filter(|t| t.is_word(self.sql, "FROM"))?;
+                i + 1
+            } else {
+                i
+            };
+            let (database, table, next) = self.table_name(i)?;
+            // Anything else after the table (an alias, a join, USING) isn't a single-table statement
+            let expected = if first.is_word(self.sql, "UPDATE") { "SET" } else { "WHERE" };
+            if !self.tokens.get(next)?.is_word(self.sql, expected) {
+                return None;
+            }
+            let where_at = self.find_word(next, end, &["WHERE"])?;
+            let condition = self.sql[self.tokens[where_at].start..self.tokens[end - 1].end].to_string();
+            Some(RowTarget::Matching { database, table, condition })
+        } else {
+            None
+        }
+    }
+
+    /// Tables the statement writes (see `StatementShape::tables`)
+    fn written_tables(&self) -> Vec<String> {
+        let sql = self.sql;
+        let first = &self.tokens[0];
+        let end = self.tokens.len();
+        let name_at = |i: usize| self.table_name(i).map(|(_, table, _)| table.to_lowercase());
+        let is_word = |i: usize, word: &str| self.tokens.get(i).is_some_and(|t| t.is_word(sql, word));
+
+        if first.is_word(sql, "INSERT") || first.is_word(sql, "REPLACE") {
+            let i = self.skip_words(1, &["LOW_PRIORITY", "DELAYED", "HIGH_PRIORITY", "IGNORE", "INTO"]);
+            name_at(i).into_iter().collect()
+        } else if first.is_word(sql, "UPDATE") {
+            let i = self.skip_words(1, &["LOW_PRIORITY", "IGNORE"]);
+            let set = self.find_word(i, end, &["SET"]).unwrap_or(end);
+            self.table_refs(i, set)
+        } else if first.is_word(sql, "DELETE") {
+            let Some(from) = self.find_word(1, end, &["FROM"]) else {
+                return Vec::new();
+            };
+            let to = self.find_word(from, end, &["WHERE", "ORDER", "LIMIT"]).unwrap_or(end);
+            self.table_refs(from + 1, to)
+        } else if first.is_word(sql, "CREATE") || first.is_word(sql, "ALTER") || first.is_word(sql, "DROP") {
+            let i = self.skip_words(1, &["OR", "REPLACE", "TEMPORARY", "ONLINE", "OFFLINE", "IGNORE", "UNIQUE", "FULLTEXT", "SPATIAL"]);
+            if is_word(i, "TABLE") {
+                let i = self.skip_words(i + 1, &["IF", "NOT", "EXISTS"]);
+                if first.is_word(sql, "DROP") {
+                    // DROP TABLE a, b
+                    let to = self.find_word(i, end, &["RESTRICT", "CASCADE"]).unwrap_or(end);
+                    self.split_commas(i, to).into_iter().filter_map(|(from, _)| name_at(from)).collect()
+                } else {
+                    name_at(i).into_iter().collect()
+                }
+            } else if is_word(i, "INDEX") {
+                self.find_word(i, end, &["ON"]).and_then(|on| name_at(on + 1)).into_iter().collect()
+            } else {
+                Vec::new()
+            }
+        } else if first.is_word(sql, "TRUNCATE") {
+            name_at(self.skip_words(1, &["TABLE"])).into_iter().collect()
+        } else if first.is_word(sql, "RENAME") && is_word(1, "TABLE") {
+            // RENAME TABLE a TO b, c TO d
+            self.split_commas(2, end).into_iter()
+                .flat_map(|(from, to)| {
+                    let target = self.find_word(from, to, &["TO"]).and_then(|t| name_at(t + 1));
+                    name_at(from).into_iter().chain(target)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Tables named in the table references `from..to` of an `UPDATE` or
+    /// `DELETE`, split at commas, joins and `USING`
+    fn table_refs(&self, from: usize, to: usize) -> Vec<String> {
+        let is_sep = |t: &Token| {
+            t.is_punct(self.sql, ",") || ["JOIN", "STRAIGHT_JOIN", "USING"].iter().any(|w| t.is_word(self.sql, w))
+        };
+        let mut tables: Vec<String> = Vec::new();
+        for (start, _) in self.split(from, to, is_sep) {
+            if let Some((_, table, _)) = self.table_name(start) {
+                let table = table.to_lowercase();
+                if !tables.contains(&table) {
+                    tables.push(table);
+                }
+            }
+        }
+        tables
+    }
+
+    /// Split a condition into its top-level `AND` terms (the `AND` of
+    /// `BETWEEN x AND y` belongs to its term)
+    fn and_terms(&self, from: usize, to: usize) -> Vec<(usize, usize)> {
+        let mut terms = Vec::new();
+        let mut depth = 0;
+        let mut start = from;
+        let mut between = false;
+        let mut i = from;
+        while i < to {
+            let t = &self.tokens[i];
+            if t.is_punct(self.sql, "(") {
+                depth += 1;
+            } else if t.is_punct(self.sql, ")") {
+                depth -= 1;
+            } else if depth == 0 && t.is_word(self.sql, "BETWEEN") {
+                between = true;
+            } else if depth == 0 && t.is_word(self.sql, "AND") {
+                if between {
+                    between = false;
+                } else {
+                    terms.push((start, i));
+                    start = i + 1;
+                }
+            } else if depth == 0 && t.is_punct(self.sql, "&")
+                && self.tokens.get(i + 1).is_some_and(|n| n.is_punct(self.sql, "&"))
+            {
+                terms.push((start, i));
+                start = i + 2;
+                i += 1;
+            }
+            i += 1;
+        }
+        if start < to {
+            terms.push((start, to));
+        }
+        terms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::entry::{PrimaryKey, Value};
+
+    fn filter() -> ColumnFilter {
+        ColumnFilter::new(&[ColumnFilterConfig {
+            database: "app".to_string(),
+            table: "users".to_string(),
+            exclude_columns: vec!["password_hash".to_string(), "ssn".to_string()],
+        }])
+    }
+
+    fn rewrite(sql: &str) -> String {
+        let mut entry = LogEntry::RawSql {
+            sql: sql.to_string(),
+            affects_table: None,
+            database: Some("app".to_string()),
+            gtid: None,
+        };
+        filter().apply(&mut entry);
+        match entry {
+            LogEntry::RawSql { sql, .. } => sql,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_insert_drops_columns_from_every_row() {
+        assert_eq!(
+            rewrite("INSERT INTO users (id, name, `password_hash`, email, ssn) VALUES (1, 'a,b', 'x', 'a@x', '123'), (2, 'c', NOW(), 'c@x', '456')"),
+            "INSERT INTO users (id, name, email) VALUES (1, 'a,b', 'a@x'), (2, 'c', 'c@x')"
+        );
+        assert_eq!(
+            rewrite("INSERT INTO app.users (id, ssn) VALUES (1, '1') ON DUPLICATE KEY UPDATE ssn = VALUES(ssn)"),
+            "INSERT INTO app.users (id) VALUES (1)"
+        );
+        assert_eq!(rewrite("INSERT INTO users SET id = 1, ssn = '1'"), "INSERT INTO users SET id = 1");
+        // Other tables and databases are untouched
+        assert_eq!(rewrite("INSERT INTO orders (id, ssn) VALUES (1, 2)"), "INSERT INTO orders (id, ssn) VALUES (1, 2)");
+        assert_eq!(rewrite("INSERT INTO crm.users (id, ssn) VALUES (1, 2)"), "INSERT INTO crm.users (id, ssn) VALUES (1, 2)");
+        // No column list: can't tell which value is which
+        assert_eq!(rewrite("INSERT INTO users VALUES (1, 'x'); INSERT INTO orders VALUES (1)"), "INSERT INTO orders VALUES (1)");
+    }
+
+    #[test]
+    fn test_update_drops_set_and_where_terms() {
+        assert_eq!(
+            rewrite("UPDATE users SET name = 'n', ssn = '9' WHERE id = 1 AND ssn = '8' AND age BETWEEN 1 AND 9"),
+            "UPDATE users SET name = 'n' WHERE id = 1 AND age BETWEEN 1 AND 9"
+        );
+        assert_eq!(rewrite("UPDATE users SET ssn = '9' WHERE id = 1"), "");
+        assert_eq!(rewrite("UPDATE users SET name = 'n' WHERE ssn = '8'"), "");
+        assert_eq!(rewrite("UPDATE users SET name = 'n' WHERE id = 1 OR ssn = '8'"), "");
+        assert_eq!(rewrite("UPDATE users SET name = 'ssn' WHERE id = 1"), "UPDATE users SET name = 'ssn' WHERE id = 1");
+    }
+
+    #[test]
+    fn test_typed_entries_lose_columns() {
+        let mut entry = LogEntry::Update {
+            table: "users".to_string(),
+            set_columns: vec!["name".to_string(), "SSN".to_string()],
+            set_values: vec![Value::String("n".to_string()), Value::String("1".to_string())],
+            primary_key: PrimaryKey::Int(1),
+            key_columns: vec!["id".to_string()],
+        };
+        filter().apply(&mut entry);
+        match entry {
+            LogEntry::Update { set_columns, set_values, .. } => {
+                assert_eq!(set_columns, vec!["name".to_string()]);
+                assert_eq!(set_values, vec![Value::String("n".to_string())]);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_row_targets() {
+        let targets = row_targets(
+            "USE app; INSERT INTO `Orders` (id, note) VALUES (1, 'a,b'), (2, CONCAT('c', ')')) \
+             ON DUPLICATE KEY UPDATE note = VALUES(note); UPDATE app.users SET name = 'x' WHERE id IN (1, 2) LIMIT 2; \
+             DELETE FROM users WHERE id = 3"
+        ).unwrap();
+        assert_eq!(targets, vec![
+            RowTarget::Inserted {
+                database: None,
+                table: "Orders".to_string(),
+                columns: vec!["id".to_string(), "note".to_string()],
+                rows: vec![
+                    vec!["1".to_string(), "'a,b'".to_string()],
+                    vec!["2".to_string(), "CONCAT('c', ')')".to_string()],
+                ],
+            },
+            RowTarget::Matching {
+                database: Some("app".to_string()),
+                table: "users".to_string(),
+                condition: "WHERE id IN (1, 2) LIMIT 2".to_string(),
+            },
+            RowTarget::Matching { database: None, table: "users".to_string(), condition: "WHERE id = 3".to_string() },
+        ]);
+
+        assert_eq!(row_targets("START TRANSACTION; COMMIT"), Some(vec![]));
+        // Rows that can't be told from the statement
+        assert_eq!(row_targets("UPDATE users SET active = 0"), None);
+        assert_eq!(row_targets("DELETE u FROM users u JOIN bans b ON b.user_id = u.id"), None);
+        assert_eq!(row_targets("INSERT INTO users (id) SELECT id FROM staging"), None);
+        assert_eq!(row_targets("INSERT INTO users VALUES (1)"), None);
+        assert_eq!(row_targets("ALTER TABLE users ADD COLUMN age INT"), None);
+    }
+
+    #[test]
+    fn test_statement_shapes() {
+        let tables = |sql: &str| statement_shapes(sql).into_iter().map(|s| s.tables).collect::<Vec<_>>();
+        assert_eq!(
+            tables("INSERT IGNORE INTO app.`Orders` (id) VALUES (1); UPDATE users u JOIN bans b ON b.id = u.id SET u.x = 1"),
+            vec![vec!["orders".to_string()], vec!["users".to_string(), "bans".to_string()]]
+        );
+        assert_eq!(tables("DELETE FROM t WHERE id IN (SELECT id FROM other)"), vec![vec!["t".to_string()]]);
+        assert_eq!(tables("DELETE a FROM a JOIN b USING (id)"), vec![vec!["a".to_string(), "b".to_string()]]);
+        assert_eq!(tables("CREATE TABLE IF NOT EXISTS t (id INT REFERENCES p (id))"), vec![vec!["t".to_string()]]);
+        assert_eq!(tables("CREATE UNIQUE INDEX i ON t (a)"), vec![vec!["t".to_string()]]);
+        assert_eq!(tables("DROP TABLE IF EXISTS a, b"), vec![vec!["a".to_string(), "b".to_string()]]);
+        assert_eq!(tables("RENAME TABLE a TO b"), vec![vec!["a".to_string(), "b".to_string()]]);
+
+        let shapes = statement_shapes("use app; truncate t");
+        assert_eq!((shapes[0].verb.as_str(), shapes[0].ddl), ("USE", false));
+        assert_eq!((shapes[1].verb.as_str(), shapes[1].ddl), ("TRUNCATE", true));
+        assert!(shapes[0].tables.is_empty());
+    }
+
+    #[test]
+    fn test_drop_column_for_missing_follower_column() {
+        assert_eq!(
+            drop_column("INSERT INTO t (a, b) VALUES (1, 2)", "b").as_deref(),
+            Some("INSERT INTO t (a) VALUES (1)")
+        );
+        assert_eq!(drop_column("INSERT INTO t (a) VALUES (1)", "b"), None);
+    }
+}
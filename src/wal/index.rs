@@ -0,0 +1,240 @@
+//! WAL Segment Index
+//!
+//! SQLite table mapping each LSN to the segment and byte offset holding it.
+//! The writer records entries after they are synced, without waiting for
+//! SQLite to reach disk, so after an unclean shutdown the index may trail
+//! the segments. It is checked at startup and rebuilt from the segments
+//! whenever the two disagree.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::entry::Lsn;
+use super::segment::{list_segments, Segment};
+use super::WalPaths;
+use crate::error::Result;
+
+/// Outcome of an index check or rebuild
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Whether the index was rebuilt
+    pub rebuilt: bool,
+    /// Last LSN in the index before the check
+    pub indexed_lsn: Lsn,
+    /// Highest LSN found in the segment files
+    pub segment_lsn: Lsn,
+    /// Entries in the index afterwards (only counted on rebuild)
+    pub entries: u64,
+    /// Segment where the rebuild stopped at a corrupt entry
+    pub corrupt_segment: Option<u64>,
+}
+
+/// LSN -> (segment, byte offset) index stored in `wal_index.db`
+pub struct WalIndex {
+    conn: Connection,
+}
+
+impl WalIndex {
+    /// Open or create the index database
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        // The index can always be rebuilt from the segments, so it never
+        // waits for fsync
+        conn.execute_batch(
+            r#"
+            PRAGMA journal_mode = WAL;
+            PRAGMA synchronous = OFF;
+
+            CREATE TABLE IF NOT EXISTS wal_index (
+                lsn INTEGER PRIMARY KEY,
+                segment_id INTEGER NOT NULL,
+                byte_offset INTEGER NOT NULL
+            );
+            "#,
+        )?;
+        Ok(Self { conn })
+    }
+
+    /// Record `(lsn, segment_id, byte_offset)` for newly written entries
+    pub fn record(&mut self, entries: &[(Lsn, u64, u64)]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT OR REPLACE INTO wal_index (lsn, segment_id, byte_offset) VALUES (?1, ?2, ?3)",
+            )?;
+            for &(lsn, segment_id, offset) in entries {
+                stmt.execute(params![lsn as i64, segment_id as i64, offset as i64])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Segment and byte offset of the entry with `lsn`
+    pub fn lookup(&self, lsn: Lsn) -> Result<Option<(u64, u64)>> {
+        let location = self.conn
+            .query_row(
+                "SELECT segment_id, byte_offset FROM wal_index WHERE lsn = ?1",
+                params![lsn as i64],
+                |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64)),
+            )
+            .optional()?;
+        Ok(location)
+    }
+
+    /// Highest LSN in the index, 0 if it is empty
+    pub fn last_lsn(&self) -> Result<Lsn> {
+        let lsn: Option<i64> = self.conn.query_row("SELECT MAX(lsn) FROM wal_index", [], |row| row.get(0))?;
+        Ok(lsn.unwrap_or(0) as Lsn)
+    }
+
+    /// Compare the index against the segments and rebuild it if they
+    /// diverge (or always, with `force`)
+    pub fn check(&mut self, paths: &WalPaths, force: bool) -> Result<RecoveryReport> {
+        let indexed_lsn = self.last_lsn()?;
+        let segment_lsn = highest_segment_lsn(paths)?;
+        if !force && indexed_lsn == segment_lsn && !self.references_missing_segment(paths)? {
+            return Ok(RecoveryReport { indexed_lsn, segment_lsn, ..Default::default() });
+        }
+
+        tracing::warn!(
+            "WAL index out of sync (indexed LSN {}, segments end at {}), rebuilding",
+            indexed_lsn, segment_lsn
+        );
+        let (entries, corrupt_segment) = self.rebuild(paths)?;
+        crate::metrics::WAL_RECOVERY_RAN.inc();
+        Ok(RecoveryReport { rebuilt: true, indexed_lsn, segment_lsn, entries, corrupt_segment })
+    }
+
+    /// Replace the index with one built by scanning every segment in order.
+    /// Stops at the first corrupt entry, leaving the index ending at the last
+    /// good one.
+    fn rebuild(&mut self, paths: &WalPaths) -> Result<(u64, Option<u64>)> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM wal_index", [])?;
+
+        let mut entries = 0;
+        let mut corrupt_segment = None;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO wal_index (lsn, segment_id, byte_offset) VALUES (?1, ?2, ?3)",
+            )?;
+            'segments: for path in list_segments(&paths.base_dir)? {
+                let mut segment = Segment::open(path, 64, true)?;
+                let segment_id = segment.id;
+                let mut iter = segment.iter();
+                loop {
+                    let offset = iter.position();
+                    match iter.next() {
+                        Some(Ok(entry)) => {
+                            stmt.execute(params![entry.header.lsn as i64, segment_id as i64, offset as i64])?;
+                            entries += 1;
+                        }
+                        Some(Err(e)) => {
+                            tracing::warn!(
+                                "Corrupt entry in WAL segment {} at offset {}: {}; index ends at the last good entry",
+                                segment_id, offset, e
+                            );
+                            corrupt_segment = Some(segment_id);
+                            break 'segments;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+        tx.commit()?;
+        Ok((entries, corrupt_segment))
+    }
+
+    /// Whether the index points into a segment file that no longer exists
+    fn references_missing_segment(&self, paths: &WalPaths) -> Result<bool> {
+        let mut stmt = self.conn.prepare("SELECT DISTINCT segment_id FROM wal_index")?;
+        let ids = stmt.query_map([], |row| row.get::<_, i64>(0))?;
+        for id in ids {
+            if !paths.segment_path(id? as u64).exists() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Highest LSN present in the segment files. Sealed segments record it in
+/// their header; the active one is scanned.
+fn highest_segment_lsn(paths: &WalPaths) -> Result<Lsn> {
+    for path in list_segments(&paths.base_dir)?.into_iter().rev() {
+        let mut segment = Segment::open(path, 64, true)?;
+        if segment.last_lsn() > 0 {
+            return Ok(segment.last_lsn());
+        }
+        if let Some(lsn) = segment.iter().map_while(|r| r.ok()).last().map(|e| e.header.lsn) {
+            return Ok(lsn);
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::entry::{LogEntry, WalEntry};
+    use tempfile::tempdir;
+
+    fn write_segment(paths: &WalPaths, lsns: std::ops::RangeInclusive<Lsn>) -> Vec<u64> {
+        let mut segment = Segment::create(paths.segment_path(*lsns.start()), *lsns.start(), 1, true).unwrap();
+        lsns.map(|lsn| {
+            let entry = WalEntry::new(lsn, 1, "node-1".to_string(), LogEntry::RawSql {
+                sql: format!("INSERT INTO t VALUES ({})", lsn),
+                affects_table: Some("t".to_string()),
+                database: None,
+                gtid: None,
+            });
+            segment.append(&entry).unwrap()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_rebuild_when_index_trails_segments() {
+        let dir = tempdir().unwrap();
+        let paths = WalPaths::new(dir.path().to_path_buf());
+        let offsets = write_segment(&paths, 1..=10);
+        let mut index = WalIndex::open(&paths.index_path()).unwrap();
+
+        // Only the first five made it into the index before the "crash"
+        let recorded: Vec<_> = (1..=5).map(|lsn| (lsn, 1, offsets[lsn as usize - 1])).collect();
+        index.record(&recorded).unwrap();
+
+        let report = index.check(&paths, false).unwrap();
+        assert!(report.rebuilt);
+        assert_eq!((report.indexed_lsn, report.segment_lsn, report.entries), (5, 10, 10));
+        assert_eq!(index.last_lsn().unwrap(), 10);
+        assert_eq!(index.lookup(7).unwrap(), Some((1, offsets[6])));
+
+        // In sync now, so nothing to do
+        assert!(!index.check(&paths, false).unwrap().rebuilt);
+    }
+
+    #[test]
+    fn test_corrupt_segment_truncates_index() {
+        let dir = tempdir().unwrap();
+        let paths = WalPaths::new(dir.path().to_path_buf());
+        let offsets = write_segment(&paths, 1..=10);
+        write_segment(&paths, 11..=20);
+
+        // Flip a data byte in entry 4 (past its 9-byte frame prefix)
+        let path = paths.segment_path(1);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[offsets[3] as usize + 12] ^= 0xff;
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut index = WalIndex::open(&paths.index_path()).unwrap();
+        let report = index.check(&paths, false).unwrap();
+        assert_eq!(report.corrupt_segment, Some(1));
+        assert_eq!(report.entries, 3);
+        assert_eq!(index.last_lsn().unwrap(), 3);
+        assert_eq!(index.lookup(11).unwrap(), None);
+    }
+}
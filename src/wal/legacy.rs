@@ -0,0 +1,293 @@
+//! Version 1 Entry Layout
+//!
+//! bincode encodes fields by position, so entries written before the trace
+//! ID, schema version, transaction ID and GTID fields existed cannot be
+//! decoded with the current types. These types mirror the original layout.
+//! Version 1 segments and protocol version 0 peers are read through them,
+//! and entries are converted back for peers that still speak version 0.
+
+use serde::{Deserialize, Serialize};
+
+use super::entry::{EntryHeader, LogEntry, Lsn, PrimaryKey, Value, WalEntry};
+use crate::telemetry::TraceId;
+
+/// Entry header as written by version 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyEntryHeader {
+    pub lsn: Lsn,
+    pub term: u64,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub origin_node: String,
+    pub checksum: u32,
+    pub body_size: u32,
+    pub compressed: bool,
+}
+
+/// Log entry as written by version 1. Variant order must not change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LegacyLogEntry {
+    Insert {
+        table: String,
+        columns: Vec<String>,
+        values: Vec<Value>,
+        primary_key: PrimaryKey,
+    },
+    Update {
+        table: String,
+        set_columns: Vec<String>,
+        set_values: Vec<Value>,
+        primary_key: PrimaryKey,
+        key_columns: Vec<String>,
+    },
+    Delete {
+        table: String,
+        primary_key: PrimaryKey,
+        key_columns: Vec<String>,
+    },
+    Upsert {
+        table: String,
+        columns: Vec<String>,
+        values: Vec<Value>,
+        update_columns: Vec<String>,
+        primary_key: PrimaryKey,
+    },
+    BulkInsert {
+        table: String,
+        columns: Vec<String>,
+        rows: Vec<Vec<Value>>,
+    },
+    AlterTable {
+        table: String,
+        ddl: String,
+    },
+    CreateTable {
+        table: String,
+        ddl: String,
+    },
+    DropTable {
+        table: String,
+    },
+    CreateIndex {
+        table: String,
+        index_name: String,
+        ddl: String,
+    },
+    DropIndex {
+        table: String,
+        index_name: String,
+    },
+    Transaction {
+        entries: Vec<LegacyLogEntry>,
+    },
+    RawSql {
+        sql: String,
+        affects_table: Option<String>,
+        database: Option<String>,
+    },
+    Noop,
+}
+
+/// WAL entry as written by version 1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyWalEntry {
+    pub header: LegacyEntryHeader,
+    pub entry: LegacyLogEntry,
+}
+
+impl From<LegacyLogEntry> for LogEntry {
+    fn from(entry: LegacyLogEntry) -> Self {
+        match entry {
+            LegacyLogEntry::Insert { table, columns, values, primary_key } => {
+                LogEntry::Insert { table, columns, values, primary_key }
+            }
+            LegacyLogEntry::Update { table, set_columns, set_values, primary_key, key_columns } => {
+                LogEntry::Update { table, set_columns, set_values, primary_key, key_columns }
+            }
+            LegacyLogEntry::Delete { table, primary_key, key_columns } => {
+                LogEntry::Delete { table, primary_key, key_columns }
+            }
+            LegacyLogEntry::Upsert { table, columns, values, update_columns, primary_key } => {
+                LogEntry::Upsert { table, columns, values, update_columns, primary_key }
+            }
+            LegacyLogEntry::BulkInsert { table, columns, rows } => {
+                LogEntry::BulkInsert { table, columns, rows }
+            }
+            LegacyLogEntry::AlterTable { table, ddl } => LogEntry::AlterTable { table, ddl },
+            LegacyLogEntry::CreateTable { table, ddl } => LogEntry::CreateTable { table, ddl },
+            LegacyLogEntry::DropTable { table } => LogEntry::DropTable { table },
+            LegacyLogEntry::CreateIndex { table, index_name, ddl } => {
+                LogEntry::CreateIndex { table, index_name, ddl }
+            }
+            LegacyLogEntry::DropIndex { table, index_name } => LogEntry::DropIndex { table, index_name },
+            LegacyLogEntry::Transaction { entries } => LogEntry::Transaction {
+                entries: entries.into_iter().map(LogEntry::from).collect(),
+                transaction_id: None,
+            },
+            LegacyLogEntry::RawSql { sql, affects_table, database } => LogEntry::RawSql {
+                sql,
+                affects_table,
+                database,
+                gtid: None,
+            },
+            LegacyLogEntry::Noop => LogEntry::Noop,
+        }
+    }
+}
+
+impl TryFrom<&LogEntry> for LegacyLogEntry {
+    type Error = &'static str;
+
+    /// Drops the fields version 1 has no room for. Fails for entry types
+    /// that did not exist in version 1.
+    fn try_from(entry: &LogEntry) -> Result<Self, Self::Error> {
+        let entry = entry.clone();
+        Ok(match entry {
+            LogEntry::Insert { table, columns, values, primary_key } => {
+                LegacyLogEntry::Insert { table, columns, values, primary_key }
+            }
+            LogEntry::Update { table, set_columns, set_values, primary_key, key_columns } => {
+                LegacyLogEntry::Update { table, set_columns, set_values, primary_key, key_columns }
+            }
+            LogEntry::Delete { table, primary_key, key_columns } => {
+                LegacyLogEntry::Delete { table, primary_key, key_columns }
+            }
+            LogEntry::Upsert { table, columns, values, update_columns, primary_key } => {
+                LegacyLogEntry::Upsert { table, columns, values, update_columns, primary_key }
+            }
+            LogEntry::BulkInsert { table, columns, rows } => {
+                LegacyLogEntry::BulkInsert { table, columns, rows }
+            }
+            LogEntry::AlterTable { table, ddl } => LegacyLogEntry::AlterTable { table, ddl },
+            LogEntry::CreateTable { table, ddl } => LegacyLogEntry::CreateTable { table, ddl },
+            LogEntry::DropTable { table } => LegacyLogEntry::DropTable { table },
+            LogEntry::CreateIndex { table, index_name, ddl } => {
+                LegacyLogEntry::CreateIndex { table, index_name, ddl }
+            }
+            LogEntry::DropIndex { table, index_name } => LegacyLogEntry::DropIndex { table, index_name },
+            LogEntry::Transaction { entries, .. } => LegacyLogEntry::Transaction {
+                entries: entries.iter().map(LegacyLogEntry::try_from).collect::<Result<_, _>>()?,
+            },
+            LogEntry::RawSql { sql, affects_table, database, .. } => {
+                LegacyLogEntry::RawSql { sql, affects_table, database }
+            }
+            LogEntry::Noop => LegacyLogEntry::Noop,
+            LogEntry::IdAllocation { .. } => return Err("IdAllocation entries need protocol version 1"),
+        })
+    }
+}
+
+impl LegacyWalEntry {
+    /// Check the header checksum against the body as it was written
+    pub fn verify_checksum(&self) -> bool {
+        bincode::serialize(&self.entry)
+            .map(|body| crc32fast::hash(&body) == self.header.checksum)
+            .unwrap_or(false)
+    }
+}
+
+impl From<LegacyWalEntry> for WalEntry {
+    /// The checksum covers the body in the current layout, so it is
+    /// recomputed, but only for entries whose original checksum holds:
+    /// a corrupt entry keeps failing `verify_checksum`.
+    fn from(legacy: LegacyWalEntry) -> Self {
+        let intact = legacy.verify_checksum();
+        let entry = LogEntry::from(legacy.entry);
+        let body = entry.serialize().unwrap_or_default();
+        let checksum = if intact { crc32fast::hash(&body) } else { legacy.header.checksum };
+
+        WalEntry {
+            header: EntryHeader {
+                lsn: legacy.header.lsn,
+                term: legacy.header.term,
+                timestamp: legacy.header.timestamp,
+                origin_node: legacy.header.origin_node,
+                checksum,
+                body_size: body.len() as u32,
+                compressed: legacy.header.compressed,
+                trace_id: TraceId::default(),
+                schema_version: 0,
+            },
+            entry,
+        }
+    }
+}
+
+impl TryFrom<&WalEntry> for LegacyWalEntry {
+    type Error = &'static str;
+
+    fn try_from(wal_entry: &WalEntry) -> Result<Self, Self::Error> {
+        let entry = LegacyLogEntry::try_from(&wal_entry.entry)?;
+        let body = bincode::serialize(&entry).unwrap_or_default();
+        let header = &wal_entry.header;
+
+        Ok(LegacyWalEntry {
+            header: LegacyEntryHeader {
+                lsn: header.lsn,
+                term: header.term,
+                timestamp: header.timestamp,
+                origin_node: header.origin_node.clone(),
+                checksum: crc32fast::hash(&body),
+                body_size: body.len() as u32,
+                compressed: header.compressed,
+            },
+            entry,
+        })
+    }
+}
+
+/// Decode an entry written in the version 1 layout
+pub fn decode_entry(bytes: &[u8]) -> Result<WalEntry, bincode::Error> {
+    bincode::deserialize::<LegacyWalEntry>(bytes).map(WalEntry::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_round_trip_drops_new_fields() {
+        let mut entry = WalEntry::new(
+            7,
+            3,
+            "node-1".to_string(),
+            LogEntry::RawSql {
+                sql: "UPDATE t SET a = 1".to_string(),
+                affects_table: Some("t".to_string()),
+                database: Some("shop".to_string()),
+                gtid: Some("0-1-42".to_string()),
+            },
+        );
+        entry.header.schema_version = 4;
+
+        let legacy = LegacyWalEntry::try_from(&entry).unwrap();
+        assert!(legacy.verify_checksum());
+        let bytes = bincode::serialize(&legacy).unwrap();
+        let decoded = decode_entry(&bytes).unwrap();
+
+        assert!(decoded.verify_checksum());
+        assert_eq!(decoded.header.lsn, 7);
+        assert_eq!(decoded.header.schema_version, 0);
+        match decoded.entry {
+            LogEntry::RawSql { database, gtid, .. } => {
+                assert_eq!(database.as_deref(), Some("shop"));
+                assert_eq!(gtid, None);
+            }
+            other => panic!("unexpected entry {}", other.operation_name()),
+        }
+    }
+
+    #[test]
+    fn test_corrupt_legacy_entry_keeps_failing_checksum() {
+        let entry = WalEntry::new(1, 1, "node-1".to_string(), LogEntry::Noop);
+        let mut legacy = LegacyWalEntry::try_from(&entry).unwrap();
+        legacy.header.checksum ^= 1;
+
+        assert!(!WalEntry::from(legacy).verify_checksum());
+    }
+
+    #[test]
+    fn test_id_allocation_has_no_legacy_form() {
+        let entry = LogEntry::IdAllocation { machine_id: 1, highest_id: 99 };
+        assert!(LegacyLogEntry::try_from(&entry).is_err());
+    }
+}
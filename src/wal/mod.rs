@@ -4,14 +4,25 @@
 //! and replication of database operations.
 
 pub mod entry;
+pub(crate) mod legacy;
 mod segment;
 mod writer;
 mod reader;
+mod compaction;
+mod retention;
+mod export;
+mod index;
+mod filter;
 
 pub use entry::{LogEntry, PrimaryKey, Value, EntryHeader, Lsn, WalEntry};
 pub use segment::Segment;
 pub use writer::WalWriter;
 pub use reader::WalReader;
+pub use compaction::{compact_entries, CompactionStats, WalCompactor};
+pub use retention::{RetentionHold, RetentionReport, WalRetention, WalUsage};
+pub use export::{entry_to_json, export_wal, ExportFormat, ExportOptions};
+pub use index::{RecoveryReport, WalIndex};
+pub use filter::{drop_column, row_targets, statement_shapes, ColumnFilter, RowTarget, StatementShape};
 
 use std::path::PathBuf;
 
@@ -4,12 +4,12 @@
 //! sequential iteration and random access by LSN.
 
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::entry::{Lsn, WalEntry};
 use super::segment::{list_segments, Segment};
 use super::WalPaths;
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 /// WAL Reader for accessing log entries
 pub struct WalReader {
@@ -21,6 +21,8 @@ pub struct WalReader {
     compression: bool,
     /// Cached segment index: LSN -> segment path
     segment_index: BTreeMap<Lsn, PathBuf>,
+    /// Stop at a half-written last entry instead of failing
+    torn_write_detection: bool,
 }
 
 impl WalReader {
@@ -33,19 +35,34 @@ impl WalReader {
             segment_size_mb,
             compression,
             segment_index: BTreeMap::new(),
+            torn_write_detection: true,
         };
 
         reader.refresh_index()?;
         Ok(reader)
     }
 
+    /// Enable or disable torn write detection (on by default). Entries after
+    /// a torn one are never returned either way; with detection disabled the
+    /// torn entry is reported as corruption.
+    pub fn with_torn_write_detection(mut self, enabled: bool) -> Self {
+        self.torn_write_detection = enabled;
+        self
+    }
+
+    /// Open a segment with this reader's settings
+    fn open_segment(&self, path: &Path) -> Result<Segment> {
+        Ok(Segment::open(path.to_path_buf(), self.segment_size_mb, self.compression)?
+            .with_torn_write_detection(self.torn_write_detection))
+    }
+
     /// Refresh the segment index
     pub fn refresh_index(&mut self) -> Result<()> {
         self.segment_index.clear();
 
         let segments = list_segments(&self.paths.base_dir)?;
         for path in segments {
-            let segment = Segment::open(path.clone(), self.segment_size_mb, self.compression)?;
+            let segment = self.open_segment(&path)?;
             self.segment_index.insert(segment.first_lsn(), path);
         }
 
@@ -60,7 +77,7 @@ impl WalReader {
     /// Get the last LSN in the log
     pub fn last_lsn(&self) -> Result<Option<Lsn>> {
         if let Some(path) = self.segment_index.values().last() {
-            let mut segment = Segment::open(path.clone(), self.segment_size_mb, self.compression)?;
+            let mut segment = self.open_segment(path)?;
             
             let mut last = None;
             for result in segment.iter() {
@@ -109,7 +126,7 @@ impl WalReader {
 
         // Iterate through segments
         for (_segment_lsn, path) in self.segment_index.range(start_lsn..) {
-            let mut segment = Segment::open(path.clone(), self.segment_size_mb, self.compression)?;
+            let mut segment = self.open_segment(path)?;
             
             for result in segment.iter() {
                 let entry = result?;
@@ -122,6 +139,23 @@ impl WalReader {
         Ok(entries)
     }
 
+    /// Read the entries of the segment starting at `first_lsn`, up to
+    /// `to_lsn` (inclusive)
+    pub fn read_segment(&self, first_lsn: Lsn, to_lsn: Lsn) -> Result<Vec<WalEntry>> {
+        let path = self.segment_index.get(&first_lsn)
+            .ok_or_else(|| Error::Wal(format!("No segment starts at LSN {}", first_lsn)))?;
+        let mut segment = self.open_segment(path)?;
+        let mut entries = Vec::new();
+        for result in segment.iter() {
+            let entry = result?;
+            if entry.header.lsn > to_lsn {
+                break;
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
     /// Read entries in a specific LSN range (inclusive)
     pub fn read_range(&self, from_lsn: Lsn, to_lsn: Lsn) -> Result<Vec<WalEntry>> {
         let mut entries = self.read_from(from_lsn)?;
@@ -151,7 +185,7 @@ impl WalReader {
         };
 
         'outer: for (_, path) in self.segment_index.range(start_lsn..) {
-            let mut segment = Segment::open(path.clone(), self.segment_size_mb, self.compression)?;
+            let mut segment = self.open_segment(path)?;
             
             for result in segment.iter() {
                 let entry = result?;
@@ -178,7 +212,7 @@ impl WalReader {
         let mut count = 0u64;
         
         for path in self.segment_index.values() {
-            let segment = Segment::open(path.clone(), self.segment_size_mb, self.compression)?;
+            let segment = self.open_segment(path)?;
             count += segment.entry_count() as u64;
         }
 
@@ -190,7 +224,7 @@ impl WalReader {
         let mut infos = Vec::new();
 
         for (first_lsn, path) in &self.segment_index {
-            let segment = Segment::open(path.clone(), self.segment_size_mb, self.compression)?;
+            let segment = self.open_segment(path)?;
             infos.push(SegmentInfo {
                 id: segment.id,
                 path: path.clone(),
@@ -225,6 +259,8 @@ pub struct SegmentInfo {
 pub struct WalEntryIterator<'a> {
     reader: &'a WalReader,
     current_segment: Option<(PathBuf, Segment)>,
+    /// Byte offset of the next unread entry in the current segment
+    segment_pos: u64,
     segment_iter: std::collections::btree_map::Range<'a, Lsn, PathBuf>,
     from_lsn: Lsn,
     started: bool,
@@ -244,6 +280,7 @@ impl<'a> WalEntryIterator<'a> {
         Self {
             reader,
             current_segment: None,
+            segment_pos: 0,
             segment_iter: reader.segment_index.range(start_lsn..),
             from_lsn,
             started: false,
@@ -252,12 +289,9 @@ impl<'a> WalEntryIterator<'a> {
 
     fn advance_segment(&mut self) -> Option<()> {
         let (_, path) = self.segment_iter.next()?;
-        let segment = Segment::open(
-            path.clone(),
-            self.reader.segment_size_mb,
-            self.reader.compression,
-        ).ok()?;
+        let segment = self.reader.open_segment(path).ok()?;
         self.current_segment = Some((path.clone(), segment));
+        self.segment_pos = 0;
         Some(())
     }
 }
@@ -274,7 +308,9 @@ impl<'a> Iterator for WalEntryIterator<'a> {
 
         loop {
             if let Some((_, ref mut segment)) = self.current_segment {
-                for result in segment.iter() {
+                let mut entries = segment.iter_from(self.segment_pos);
+                while let Some(result) = entries.next() {
+                    self.segment_pos = entries.position();
                     match result {
                         Ok(entry) if entry.header.lsn >= self.from_lsn => {
                             return Some(Ok(entry));
@@ -303,12 +339,20 @@ mod tests {
 
     fn test_config() -> WalConfig {
         WalConfig {
-            batch_size: 10,
             flush_interval_ms: 10,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
             compression: true,
             segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
             retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
             fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
         }
     }
 
@@ -367,4 +411,63 @@ mod tests {
         assert_eq!(entries.first().unwrap().header.lsn, 5);
         assert_eq!(entries.last().unwrap().header.lsn, 15);
     }
+
+    /// Write `count` uncompressed entries of a bit over 100 bytes each
+    async fn write_entries(dir: &Path, count: i64) {
+        let config = WalConfig { compression: false, ..test_config() };
+        let writer = WalWriter::new(dir.to_path_buf(), config, "test-node".to_string()).await.unwrap();
+        for i in 1..=count {
+            let entry = LogEntry::Insert {
+                table: "test".to_string(),
+                columns: vec!["id".to_string(), "payload".to_string()],
+                values: vec![Value::Int(i), Value::String("x".repeat(100))],
+                primary_key: PrimaryKey::Int(i),
+            };
+            writer.append(entry).await.unwrap();
+        }
+        writer.flush().await.unwrap();
+    }
+
+    /// Zero the last `bytes` bytes of the only segment, like a crash mid-write
+    fn zero_fill_tail(dir: &Path, bytes: u64) {
+        use std::io::{Seek, SeekFrom, Write};
+
+        let path = list_segments(&dir.join("wal")).unwrap().pop().unwrap();
+        let mut file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+        file.seek(SeekFrom::End(-(bytes as i64))).unwrap();
+        file.write_all(&vec![0u8; bytes as usize]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reader_recovers_from_torn_write() {
+        let dir = tempdir().unwrap();
+        write_entries(dir.path(), 100).await;
+        zero_fill_tail(dir.path(), 100);
+
+        let reader = WalReader::new(dir.path().to_path_buf(), 1, false).unwrap();
+        let entries = reader.read_from(1).unwrap();
+        assert_eq!(entries.len(), 99);
+        assert_eq!(entries.last().unwrap().header.lsn, 99);
+        assert_eq!(reader.stream_from(1).count(), 99);
+
+        // Without detection the torn entry is an error
+        let strict = WalReader::new(dir.path().to_path_buf(), 1, false)
+            .unwrap()
+            .with_torn_write_detection(false);
+        assert!(strict.read_from(1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_writer_truncates_torn_tail() {
+        let dir = tempdir().unwrap();
+        write_entries(dir.path(), 10).await;
+        zero_fill_tail(dir.path(), 100);
+
+        // The restarted writer continues after the last complete entry
+        write_entries(dir.path(), 5).await;
+
+        let reader = WalReader::new(dir.path().to_path_buf(), 1, false).unwrap();
+        let lsns: Vec<Lsn> = reader.read_from(1).unwrap().iter().map(|e| e.header.lsn).collect();
+        assert_eq!(lsns, (1..=14).collect::<Vec<_>>());
+    }
 }
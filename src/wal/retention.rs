@@ -0,0 +1,293 @@
+//! WAL Retention
+//!
+//! Deletes sealed segments older than `wal.retention_hours`, oldest first.
+//! With `wal.min_retention_follower_lsn` a segment is only deleted once the
+//! slowest follower has applied past its last LSN, so a follower that falls
+//! far behind can still catch up from the log. `wal.max_retention_override_hours`
+//! caps how long a follower can hold segments, so a node that is gone for
+//! good does not make the WAL grow forever.
+//!
+//! Each pass records its outcome in `wal/retention.json` for `wolfscale wal status`.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::entry::Lsn;
+use super::segment::{list_segments, Segment};
+use super::WalPaths;
+use crate::config::WalConfig;
+use crate::error::Result;
+use crate::metrics;
+
+/// File in the WAL directory holding the last retention pass
+const RETENTION_FILE: &str = "retention.json";
+
+/// A follower keeping segments past `retention_hours`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionHold {
+    pub node_id: String,
+    /// LSN the follower has applied
+    pub applied_lsn: Lsn,
+    /// Expired segments kept for it
+    pub segments: usize,
+}
+
+/// Outcome of a retention pass
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetentionReport {
+    /// Segments deleted
+    pub deleted: usize,
+    /// Set when a follower kept expired segments from being deleted
+    pub held_by: Option<RetentionHold>,
+    /// When the pass ran (Unix seconds)
+    pub checked_at: u64,
+}
+
+/// Segments currently on disk, for `wolfscale wal status`
+#[derive(Debug, Clone, Default)]
+pub struct WalUsage {
+    /// Segment files, including the active one
+    pub segments: usize,
+    pub total_bytes: u64,
+    /// Oldest retained segment and its first LSN
+    pub oldest: Option<(PathBuf, Lsn)>,
+}
+
+/// Applies WAL retention to a data directory
+pub struct WalRetention {
+    paths: WalPaths,
+    config: WalConfig,
+}
+
+impl WalRetention {
+    pub fn new(data_dir: PathBuf, config: WalConfig) -> Self {
+        Self {
+            paths: WalPaths::new(data_dir.join("wal")),
+            config,
+        }
+    }
+
+    /// Delete expired segments. `slowest_follower` is the follower with the
+    /// lowest applied LSN, if there are any.
+    pub fn apply(&self, slowest_follower: Option<(&str, Lsn)>, now: SystemTime) -> Result<RetentionReport> {
+        let mut report = RetentionReport {
+            checked_at: now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            ..Default::default()
+        };
+        if self.config.retention_hours == 0 {
+            return self.record(report);
+        }
+
+        let retention = Duration::from_secs(self.config.retention_hours * 3600);
+        let max_retention = Duration::from_secs(self.config.max_retention_override_hours * 3600);
+        let follower = slowest_follower.filter(|_| self.config.min_retention_follower_lsn);
+
+        let mut segments = list_segments(&self.paths.base_dir)?;
+        segments.pop(); // active segment
+        for path in segments {
+            let age = segment_age(&path, now)?;
+            if age < retention {
+                break;
+            }
+
+            let last_lsn = self.open(&path)?.last_lsn();
+            if let Some((node_id, applied_lsn)) = follower {
+                if last_lsn > applied_lsn && (max_retention.is_zero() || age < max_retention) {
+                    let hold = report.held_by.get_or_insert_with(|| RetentionHold {
+                        node_id: node_id.to_string(),
+                        applied_lsn,
+                        segments: 0,
+                    });
+                    hold.segments += 1;
+                    continue;
+                }
+            }
+
+            // Never leave a gap: once a segment is held, later ones stay too
+            if report.held_by.is_some() {
+                continue;
+            }
+            tracing::debug!("Removing expired WAL segment {:?} (last LSN {})", path, last_lsn);
+            std::fs::remove_file(&path)?;
+            report.deleted += 1;
+        }
+
+        metrics::WAL_RETENTION_HELD_BY_FOLLOWER.reset();
+        if let Some(ref hold) = report.held_by {
+            metrics::WAL_RETENTION_HELD_BY_FOLLOWER
+                .with_label_values(&[&hold.node_id])
+                .set(hold.segments as i64);
+            tracing::warn!(
+                "Follower {} (applied LSN {}) is holding {} expired WAL segments",
+                hold.node_id, hold.applied_lsn, hold.segments
+            );
+        }
+        if report.deleted > 0 {
+            tracing::info!("Deleted {} expired WAL segments", report.deleted);
+        }
+        self.record(report)
+    }
+
+    /// Count the segments on disk
+    pub fn usage(&self) -> Result<WalUsage> {
+        let segments = list_segments(&self.paths.base_dir)?;
+        let mut usage = WalUsage { segments: segments.len(), ..Default::default() };
+        for path in &segments {
+            usage.total_bytes += std::fs::metadata(path)?.len();
+        }
+        if let Some(path) = segments.first() {
+            usage.oldest = Some((path.clone(), self.open(path)?.first_lsn()));
+        }
+        Ok(usage)
+    }
+
+    /// The last pass, as recorded by the node
+    pub fn last_report(&self) -> Option<RetentionReport> {
+        let json = std::fs::read(self.paths.base_dir.join(RETENTION_FILE)).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    fn record(&self, report: RetentionReport) -> Result<RetentionReport> {
+        let json = serde_json::to_vec_pretty(&report)
+            .map_err(|e| crate::error::Error::Wal(e.to_string()))?;
+        std::fs::write(self.paths.base_dir.join(RETENTION_FILE), json)?;
+        Ok(report)
+    }
+
+    fn open(&self, path: &Path) -> Result<Segment> {
+        Segment::open(path.to_path_buf(), self.config.segment_size_mb, self.config.compression)
+    }
+}
+
+/// Time since a segment was last written
+fn segment_age(path: &Path, now: SystemTime) -> Result<Duration> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    Ok(now.duration_since(modified).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::entry::{LogEntry, WalEntry};
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn test_config() -> WalConfig {
+        WalConfig {
+            flush_interval_ms: 10,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
+            compression: false,
+            segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
+            retention_hours: 168,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
+            fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
+        }
+    }
+
+    /// `count` sealed segments of 10 entries each (LSN 1..=10*count), then an
+    /// empty active segment
+    fn write_segments(dir: &Path, count: u64) {
+        let paths = WalPaths::new(dir.join("wal"));
+        paths.ensure_dirs().unwrap();
+        for id in 0..=count {
+            let first_lsn = id * 10 + 1;
+            let mut segment = Segment::create(paths.segment_path(id + 1), first_lsn, 1, false).unwrap();
+            if id == count {
+                break;
+            }
+            for lsn in first_lsn..first_lsn + 10 {
+                let entry = LogEntry::RawSql { sql: "UPDATE t SET n = n + 1".to_string(), database: None, affects_table: None, gtid: None };
+                segment.append(&WalEntry::new(lsn, 1, "leader".to_string(), entry)).unwrap();
+            }
+            segment.seal().unwrap();
+        }
+    }
+
+    fn segment_count(dir: &Path) -> usize {
+        list_segments(&dir.join("wal")).unwrap().len()
+    }
+
+    #[test]
+    fn test_paused_follower_holds_expired_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        write_segments(dir.path(), 100);
+        let retention = WalRetention::new(dir.path().to_path_buf(), test_config());
+        let later = SystemTime::now() + 200 * HOUR;
+
+        // A follower stuck at LSN 5 still needs every segment
+        let report = retention.apply(Some(("node-3", 5)), later).unwrap();
+        assert_eq!(report.deleted, 0);
+        assert_eq!(report.held_by, Some(RetentionHold { node_id: "node-3".to_string(), applied_lsn: 5, segments: 100 }));
+        assert_eq!(segment_count(dir.path()), 101);
+        assert_eq!(metrics::WAL_RETENTION_HELD_BY_FOLLOWER.with_label_values(&["node-3"]).get(), 100);
+        assert_eq!(retention.last_report().unwrap().held_by.unwrap().node_id, "node-3");
+
+        // Once it resumes, what it has applied goes
+        let report = retention.apply(Some(("node-3", 501)), later).unwrap();
+        assert_eq!(report.deleted, 50);
+        assert_eq!(report.held_by.unwrap().segments, 50);
+        assert_eq!(segment_count(dir.path()), 51);
+
+        // Not yet expired: kept whatever the follower has applied
+        let report = retention.apply(Some(("node-3", 2000)), SystemTime::now()).unwrap();
+        assert_eq!(report.deleted, 0);
+        assert!(report.held_by.is_none());
+    }
+
+    #[test]
+    fn test_segment_applied_to_its_last_lsn_is_released() {
+        let dir = tempfile::tempdir().unwrap();
+        write_segments(dir.path(), 10);
+        let retention = WalRetention::new(dir.path().to_path_buf(), test_config());
+
+        // Applied exactly to the end of the third segment: it needs none of the first three
+        let report = retention.apply(Some(("node-3", 30)), SystemTime::now() + 200 * HOUR).unwrap();
+        assert_eq!(report.deleted, 3);
+        assert_eq!(report.held_by.unwrap().segments, 7);
+        assert_eq!(segment_count(dir.path()), 8);
+    }
+
+    #[test]
+    fn test_override_releases_lost_follower() {
+        let dir = tempfile::tempdir().unwrap();
+        write_segments(dir.path(), 10);
+        let retention = WalRetention::new(dir.path().to_path_buf(), test_config());
+
+        let report = retention.apply(Some(("gone", 1)), SystemTime::now() + 721 * HOUR).unwrap();
+        assert_eq!(report.deleted, 10);
+        assert!(report.held_by.is_none());
+        // The active segment is never deleted
+        assert_eq!(segment_count(dir.path()), 1);
+        let usage = retention.usage().unwrap();
+        assert_eq!(usage.segments, 1);
+        assert_eq!(usage.oldest.unwrap().1, 101);
+    }
+
+    #[test]
+    fn test_follower_ignored_when_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        write_segments(dir.path(), 10);
+        let config = WalConfig { min_retention_follower_lsn: false, ..test_config() };
+        let retention = WalRetention::new(dir.path().to_path_buf(), config);
+
+        let report = retention.apply(Some(("node-3", 1)), SystemTime::now() + 200 * HOUR).unwrap();
+        assert_eq!(report.deleted, 10);
+
+        // retention_hours = 0 keeps everything
+        let dir = tempfile::tempdir().unwrap();
+        write_segments(dir.path(), 10);
+        let config = WalConfig { retention_hours: 0, ..test_config() };
+        let retention = WalRetention::new(dir.path().to_path_buf(), config);
+        assert_eq!(retention.apply(None, SystemTime::now() + 10_000 * HOUR).unwrap().deleted, 0);
+        assert_eq!(segment_count(dir.path()), 11);
+    }
+}
@@ -7,13 +7,22 @@ use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 use super::entry::{Lsn, WalEntry};
+use super::legacy;
 use crate::error::{Error, Result};
 
 /// Magic bytes at the start of each segment file
 const SEGMENT_MAGIC: &[u8; 8] = b"WLFSCALE";
 
-/// Segment file version
-const SEGMENT_VERSION: u32 = 1;
+/// Segment file version written by this build. Version 2 frames every
+/// entry with sentinels so a torn write can be told apart from corruption.
+const SEGMENT_VERSION: u32 = 2;
+
+/// Oldest segment version that can still be read: no entry sentinels, and
+/// entries in the version 1 layout (see `legacy`)
+const SEGMENT_VERSION_V1: u32 = 1;
+
+/// Sentinel written before and after every entry (version 2+)
+const ENTRY_SENTINEL: u32 = 0xDEAD_BEEF;
 
 /// Header size in bytes
 const HEADER_SIZE: usize = 32;
@@ -29,6 +38,8 @@ pub struct SegmentHeader {
     pub entry_count: u32,
     /// Whether this segment is sealed (no more writes)
     pub sealed: bool,
+    /// On-disk format version
+    pub version: u32,
 }
 
 impl SegmentHeader {
@@ -39,6 +50,7 @@ impl SegmentHeader {
             last_lsn: 0,
             entry_count: 0,
             sealed: false,
+            version: SEGMENT_VERSION,
         }
     }
 
@@ -46,7 +58,7 @@ impl SegmentHeader {
     pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
         let mut bytes = [0u8; HEADER_SIZE];
         bytes[0..8].copy_from_slice(SEGMENT_MAGIC);
-        bytes[8..12].copy_from_slice(&SEGMENT_VERSION.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
         bytes[12..20].copy_from_slice(&self.first_lsn.to_le_bytes());
         bytes[20..28].copy_from_slice(&self.last_lsn.to_le_bytes());
         bytes[28..32].copy_from_slice(&self.entry_count.to_le_bytes());
@@ -65,7 +77,7 @@ impl SegmentHeader {
         }
 
         let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
-        if version != SEGMENT_VERSION {
+        if version != SEGMENT_VERSION && version != SEGMENT_VERSION_V1 {
             return Err(Error::Wal(format!(
                 "Unsupported segment version: {}",
                 version
@@ -77,6 +89,7 @@ impl SegmentHeader {
             last_lsn: u64::from_le_bytes(bytes[20..28].try_into().unwrap()),
             entry_count: u32::from_le_bytes(bytes[28..32].try_into().unwrap()),
             sealed: false,
+            version,
         })
     }
 }
@@ -91,12 +104,24 @@ pub struct Segment {
     file: File,
     /// Current write position
     write_pos: u64,
+    /// Frames staged by `stage` and not yet written, starting at `write_pos`
+    staged: Vec<u8>,
     /// Segment header
     header: SegmentHeader,
     /// Maximum segment size in bytes
     max_size: u64,
     /// Whether compression is enabled
     compression: bool,
+    /// Treat an incomplete last entry as the end of the segment instead of an error
+    torn_write_detection: bool,
+}
+
+/// Outcome of reading the entry at some offset
+enum ReadOutcome {
+    /// A complete entry and the offset of the one after it
+    Entry(Box<WalEntry>, u64),
+    /// The entry was only partly written (crash mid-write)
+    Torn(String),
 }
 
 impl Segment {
@@ -115,9 +140,11 @@ impl Segment {
             path,
             file,
             write_pos: HEADER_SIZE as u64,
+            staged: Vec::new(),
             header,
             max_size: max_size_mb * 1024 * 1024,
             compression,
+            torn_write_detection: true,
         };
 
         // Write header
@@ -146,14 +173,35 @@ impl Segment {
             path,
             file,
             write_pos,
+            staged: Vec::new(),
             header,
             max_size: max_size_mb * 1024 * 1024,
             compression,
+            torn_write_detection: true,
         })
     }
 
+    /// Enable or disable torn write detection (on by default). When disabled a
+    /// partly written entry is reported as corruption.
+    pub fn with_torn_write_detection(mut self, enabled: bool) -> Self {
+        self.torn_write_detection = enabled;
+        self
+    }
+
     /// Write an entry to the segment
     pub fn append(&mut self, entry: &WalEntry) -> Result<u64> {
+        let pos = self.stage(entry)?;
+        self.write_staged()?;
+        Ok(pos)
+    }
+
+    /// Encode an entry after any already staged, returning the offset it
+    /// will have. Nothing reaches the file until `write_staged`, so a group
+    /// of entries costs a single write.
+    pub fn stage(&mut self, entry: &WalEntry) -> Result<u64> {
+        if self.is_legacy() {
+            return Err(Error::Wal("Version 1 segments are read-only".into()));
+        }
         let serialized = bincode::serialize(entry)?;
         
         let data = if self.compression {
@@ -162,56 +210,121 @@ impl Segment {
             serialized
         };
 
-        // Entry format: [length: u32][compressed: u8][data: bytes][checksum: u32]
+        // Entry format: [sentinel: u32][length: u32][compressed: u8][data: bytes][checksum: u32][sentinel: u32]
+        // (version 1 segments have no sentinels)
         let entry_len = data.len() as u32;
         let checksum = crc32fast::hash(&data);
 
         // Check if we have space
-        let required_space = 4 + 1 + data.len() + 4;
-        if self.write_pos + required_space as u64 > self.max_size {
+        let entry_pos = self.write_pos + self.staged.len() as u64;
+        let required_space = self.frame_overhead() as usize + data.len();
+        if entry_pos + required_space as u64 > self.max_size {
             return Err(Error::Wal("Segment full".into()));
         }
 
-        // Write entry
-        self.file.seek(SeekFrom::Start(self.write_pos))?;
-        self.file.write_all(&entry_len.to_le_bytes())?;
-        self.file.write_all(&[self.compression as u8])?;
-        self.file.write_all(&data)?;
-        self.file.write_all(&checksum.to_le_bytes())?;
+        let sentinels = self.has_sentinels();
+        let frame = &mut self.staged;
+        frame.reserve(required_space);
+        if sentinels {
+            frame.extend_from_slice(&ENTRY_SENTINEL.to_le_bytes());
+        }
+        frame.extend_from_slice(&entry_len.to_le_bytes());
+        frame.push(self.compression as u8);
+        frame.extend_from_slice(&data);
+        frame.extend_from_slice(&checksum.to_le_bytes());
+        if sentinels {
+            frame.extend_from_slice(&ENTRY_SENTINEL.to_le_bytes());
+        }
 
-        let entry_pos = self.write_pos;
-        self.write_pos += required_space as u64;
         self.header.entry_count += 1;
         self.header.last_lsn = entry.header.lsn;
 
         Ok(entry_pos)
     }
 
+    /// Write every staged frame to the file in one go
+    pub fn write_staged(&mut self) -> Result<()> {
+        if self.staged.is_empty() {
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(self.write_pos))?;
+        self.file.write_all(&self.staged)?;
+        self.write_pos += self.staged.len() as u64;
+        self.staged.clear();
+        Ok(())
+    }
+
     /// Read an entry at a specific position
     pub fn read_at(&mut self, pos: u64) -> Result<WalEntry> {
-        self.file.seek(SeekFrom::Start(pos))?;
+        match self.read_entry(pos)? {
+            ReadOutcome::Entry(entry, _) => Ok(*entry),
+            ReadOutcome::Torn(reason) => Err(Error::WalCorrupted { lsn: 0, reason }),
+        }
+    }
 
-        // Read length
-        let mut len_bytes = [0u8; 4];
-        self.file.read_exact(&mut len_bytes)?;
-        let entry_len = u32::from_le_bytes(len_bytes) as usize;
+    /// Read the entry at `pos`. A frame that runs past the end of the file,
+    /// or (version 2+) starts with a valid sentinel but fails its end
+    /// sentinel or checksum, is a torn write when detection is enabled.
+    fn read_entry(&mut self, pos: u64) -> Result<ReadOutcome> {
+        let torn = |reason: String| -> Result<ReadOutcome> {
+            if self.torn_write_detection {
+                Ok(ReadOutcome::Torn(reason))
+            } else {
+                Err(Error::WalCorrupted { lsn: 0, reason })
+            }
+        };
 
-        // Read compression flag
-        let mut compressed_flag = [0u8; 1];
-        self.file.read_exact(&mut compressed_flag)?;
-        let is_compressed = compressed_flag[0] != 0;
+        let sentinels = self.has_sentinels();
+        let remaining = self.write_pos.saturating_sub(pos);
+        let prefix_len = if sentinels { 9 } else { 5 };
+        if remaining < prefix_len {
+            return torn(format!("{} trailing bytes at offset {}", remaining, pos));
+        }
 
-        // Read data
-        let mut data = vec![0u8; entry_len];
-        self.file.read_exact(&mut data)?;
+        self.file.seek(SeekFrom::Start(pos))?;
+        let mut prefix = [0u8; 9];
+        self.file.read_exact(&mut prefix[..prefix_len as usize])?;
+        let prefix = if sentinels {
+            let start = u32::from_le_bytes(prefix[0..4].try_into().unwrap());
+            if start != ENTRY_SENTINEL {
+                return Err(Error::WalCorrupted {
+                    lsn: 0,
+                    reason: format!("Missing entry sentinel at offset {}", pos),
+                });
+            }
+            &prefix[4..9]
+        } else {
+            &prefix[0..5]
+        };
+        let entry_len = u32::from_le_bytes(prefix[0..4].try_into().unwrap()) as u64;
+        let is_compressed = prefix[4] != 0;
+
+        // Validate the declared length before allocating for it
+        let frame_len = self.frame_overhead() + entry_len;
+        if frame_len > remaining {
+            return torn(format!(
+                "Entry at offset {} declares {} bytes but only {} remain",
+                pos, frame_len, remaining
+            ));
+        }
 
-        // Read and verify checksum
-        let mut checksum_bytes = [0u8; 4];
-        self.file.read_exact(&mut checksum_bytes)?;
-        let stored_checksum = u32::from_le_bytes(checksum_bytes);
-        let computed_checksum = crc32fast::hash(&data);
+        // Read data, checksum and (version 2+) end sentinel
+        let mut rest = vec![0u8; (frame_len - prefix_len) as usize];
+        self.file.read_exact(&mut rest)?;
+        let data = &rest[..entry_len as usize];
+        let stored_checksum = u32::from_le_bytes(rest[entry_len as usize..entry_len as usize + 4].try_into().unwrap());
 
-        if stored_checksum != computed_checksum {
+        if sentinels {
+            let end = u32::from_le_bytes(rest[entry_len as usize + 4..].try_into().unwrap());
+            if end != ENTRY_SENTINEL {
+                return torn(format!("Entry at offset {} has no end sentinel", pos));
+            }
+        }
+        if stored_checksum != crc32fast::hash(data) {
+            // Only the last entry can be torn; a bad entry with more after it is corruption
+            if sentinels && pos + frame_len == self.write_pos {
+                return torn(format!("Checksum mismatch for entry at offset {}", pos));
+            }
             return Err(Error::WalCorrupted {
                 lsn: 0, // We don't know the LSN yet
                 reason: "Checksum mismatch".into(),
@@ -219,15 +332,68 @@ impl Segment {
         }
 
         // Decompress if needed
+        let decompressed;
         let serialized = if is_compressed {
-            lz4_flex::decompress_size_prepended(&data)
-                .map_err(|e| Error::Wal(format!("Decompression failed: {}", e)))?
+            decompressed = lz4_flex::decompress_size_prepended(data)
+                .map_err(|e| Error::Wal(format!("Decompression failed: {}", e)))?;
+            &decompressed[..]
         } else {
             data
         };
+        let entry: WalEntry = if self.is_legacy() {
+            legacy::decode_entry(serialized)?
+        } else {
+            bincode::deserialize(serialized)?
+        };
+        Ok(ReadOutcome::Entry(Box::new(entry), pos + frame_len))
+    }
 
-        let entry: WalEntry = bincode::deserialize(&serialized)?;
-        Ok(entry)
+    /// Truncate a torn last entry left behind by a crash, so that new entries
+    /// are appended right after the last complete one. Returns the number of
+    /// bytes dropped. Only the writer may call this: a reader could mistake an
+    /// entry that is still being written for a torn one.
+    pub fn truncate_torn_tail(&mut self) -> Result<u64> {
+        if !self.torn_write_detection {
+            return Ok(0);
+        }
+
+        let mut pos = HEADER_SIZE as u64;
+        while pos < self.write_pos {
+            match self.read_entry(pos)? {
+                ReadOutcome::Entry(_, next) => pos = next,
+                ReadOutcome::Torn(reason) => {
+                    let dropped = self.write_pos - pos;
+                    tracing::warn!(
+                        "Torn write in WAL segment {:?}: {}; truncating {} bytes",
+                        self.path, reason, dropped
+                    );
+                    self.file.set_len(pos)?;
+                    self.file.sync_all()?;
+                    self.write_pos = pos;
+                    return Ok(dropped);
+                }
+            }
+        }
+        Ok(0)
+    }
+
+    /// Framing bytes around each entry's data
+    fn frame_overhead(&self) -> u64 {
+        if self.has_sentinels() {
+            4 + 4 + 1 + 4 + 4
+        } else {
+            4 + 1 + 4
+        }
+    }
+
+    /// Whether this segment was written in the version 1 format. Such
+    /// segments are read-only; new entries go to a new segment.
+    pub fn is_legacy(&self) -> bool {
+        self.header.version == SEGMENT_VERSION_V1
+    }
+
+    fn has_sentinels(&self) -> bool {
+        self.header.version >= SEGMENT_VERSION
     }
 
     /// Iterate over all entries in the segment
@@ -238,6 +404,14 @@ impl Segment {
         }
     }
 
+    /// Iterate over entries starting at a byte offset returned by `SegmentIterator::position`
+    pub fn iter_from(&mut self, pos: u64) -> SegmentIterator<'_> {
+        SegmentIterator {
+            segment: self,
+            pos: pos.max(HEADER_SIZE as u64),
+        }
+    }
+
     /// Sync segment to disk
     pub fn sync(&self) -> Result<()> {
         self.file.sync_all()?;
@@ -246,6 +420,7 @@ impl Segment {
 
     /// Seal the segment (no more writes)
     pub fn seal(&mut self) -> Result<()> {
+        self.write_staged()?;
         self.header.sealed = true;
         self.write_header()?;
         self.sync()
@@ -253,7 +428,7 @@ impl Segment {
 
     /// Check if segment still has space
     pub fn has_space(&self, additional_bytes: usize) -> bool {
-        self.write_pos + additional_bytes as u64 <= self.max_size
+        self.write_pos + (self.staged.len() + additional_bytes) as u64 <= self.max_size
     }
 
     /// Check if segment is sealed
@@ -290,6 +465,13 @@ pub struct SegmentIterator<'a> {
     pos: u64,
 }
 
+impl SegmentIterator<'_> {
+    /// Byte offset of the next entry
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+}
+
 impl<'a> Iterator for SegmentIterator<'a> {
     type Item = Result<WalEntry>;
 
@@ -298,25 +480,25 @@ impl<'a> Iterator for SegmentIterator<'a> {
             return None;
         }
 
-        let result = self.segment.read_at(self.pos);
-        match &result {
-            Ok(entry) => {
-                // Calculate next position
-                let serialized = bincode::serialize(entry).unwrap();
-                let data_len = if self.segment.compression {
-                    lz4_flex::compress_prepend_size(&serialized).len()
-                } else {
-                    serialized.len()
-                };
-                self.pos += 4 + 1 + data_len as u64 + 4; // len + flag + data + checksum
+        match self.segment.read_entry(self.pos) {
+            Ok(ReadOutcome::Entry(entry, next)) => {
+                self.pos = next;
+                Some(Ok(*entry))
+            }
+            Ok(ReadOutcome::Torn(reason)) => {
+                // Everything before the torn entry is intact; it ends the segment.
+                // This is also how an entry still being written looks, so only
+                // the writer (which truncates it) warns.
+                tracing::debug!("Torn write in WAL segment {:?}: {}", self.segment.path, reason);
+                self.pos = self.segment.write_pos;
+                None
             }
-            Err(_) => {
+            Err(e) => {
                 // Stop iteration on error
                 self.pos = self.segment.write_pos;
+                Some(Err(e))
             }
         }
-
-        Some(result)
     }
 }
 
@@ -409,4 +591,40 @@ mod tests {
         }
         assert_eq!(count, 10);
     }
+
+    /// Written by a build from before entry sentinels and the trace ID,
+    /// schema version, transaction ID and GTID fields
+    const V1_SEGMENT: &[u8] = include_bytes!("testdata/wal_v1.log");
+
+    #[test]
+    fn test_reads_version_1_segment() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("wal_v1.log");
+        std::fs::write(&path, V1_SEGMENT).unwrap();
+
+        let mut segment = Segment::open(path, 64, false).unwrap();
+        assert!(segment.is_legacy());
+        let entries: Vec<WalEntry> = segment.iter().collect::<Result<_>>().unwrap();
+
+        assert_eq!(entries.iter().map(|e| e.header.lsn).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(entries.iter().all(|e| e.verify_checksum() && e.header.schema_version == 0));
+        match &entries[1].entry {
+            LogEntry::RawSql { database, gtid, .. } => {
+                assert_eq!(database.as_deref(), Some("shop"));
+                assert_eq!(*gtid, None);
+            }
+            other => panic!("unexpected entry {}", other.operation_name()),
+        }
+        match &entries[2].entry {
+            LogEntry::Transaction { entries, transaction_id } => {
+                assert_eq!(entries.len(), 2);
+                assert_eq!(*transaction_id, None);
+            }
+            other => panic!("unexpected entry {}", other.operation_name()),
+        }
+
+        // Nothing may be appended in the new layout behind version 1 framing
+        let entry = WalEntry::new(4, 2, "node-1".to_string(), LogEntry::Noop);
+        assert!(segment.append(&entry).is_err());
+    }
 }
@@ -1,6 +1,11 @@
 //! WAL Writer
 //!
 //! High-performance, batched writer for the Write-Ahead Log.
+//!
+//! Appends are group-committed: entries collect in a buffer until
+//! `group_commit_max_size` of them are waiting or the oldest has waited
+//! `group_commit_max_delay_us`, then the whole group is written, synced once
+//! and every caller in it is answered together.
 
 use std::collections::VecDeque;
 use std::path::PathBuf;
@@ -9,14 +14,19 @@ use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot, RwLock, broadcast};
 
 use super::entry::{LogEntry, Lsn, WalEntry};
+use super::filter::ColumnFilter;
+use super::index::WalIndex;
 use super::segment::Segment;
 use super::WalPaths;
+use crate::audit::AuditLogger;
 use crate::config::WalConfig;
 use crate::error::{Error, Result};
+use crate::telemetry::{self, Span, TraceId};
 
 /// Write request sent to the writer task
 struct WriteRequest {
     entry: LogEntry,
+    trace_id: TraceId,
     response: oneshot::Sender<Result<Lsn>>,
 }
 
@@ -31,6 +41,8 @@ pub struct WalWriter {
     state: Arc<RwLock<WriterState>>,
     /// Notification channel for instant replication - fires after each flush
     notify_tx: broadcast::Sender<()>,
+    /// Columns removed from entries before they are written
+    column_filter: Option<Arc<ColumnFilter>>,
 }
 
 /// Shared writer state
@@ -39,8 +51,12 @@ struct WriterState {
     current_lsn: Lsn,
     /// Current term (for replication)
     current_term: u64,
+    /// Schema version of the last entry (bumped by every schema change)
+    schema_version: u64,
     /// Node ID
     node_id: String,
+    /// The segment index is being checked or rebuilt; writes wait for it
+    recovery_mode: bool,
 }
 
 /// Internal writer that manages segments
@@ -51,14 +67,24 @@ struct WriterInner {
     config: WalConfig,
     /// Current active segment
     current_segment: Option<Segment>,
+    /// When the current segment was created (or reopened)
+    segment_created_at: Instant,
+    /// Entries written to the current segment by this writer
+    segment_entries: u64,
     /// Write buffer for batching
     buffer: VecDeque<(WalEntry, oneshot::Sender<Result<Lsn>>)>,
     /// Notification sender for instant replication
     notify_tx: broadcast::Sender<()>,
     /// Last flush time
     last_flush: Instant,
+    /// When the oldest buffered entry arrived
+    group_started: Option<Instant>,
     /// Shared state
     state: Arc<RwLock<WriterState>>,
+    /// Audit log, written before an entry is acknowledged
+    audit: Option<AuditLogger>,
+    /// LSN -> segment offset index
+    index: WalIndex,
 }
 
 impl WalWriter {
@@ -68,26 +94,30 @@ impl WalWriter {
         config: WalConfig,
         node_id: String,
     ) -> Result<Self> {
-        Self::with_tuning(data_dir, config, node_id, None).await
+        Self::with_tuning(data_dir, config, node_id, None, None).await
     }
     
-    /// Create a new WAL writer with optional auto-tuning
+    /// Create a new WAL writer with optional auto-tuning and audit log
     pub async fn with_tuning(
         data_dir: PathBuf,
         config: WalConfig,
         node_id: String,
         tuned: Option<&crate::tuning::TunedConfig>,
+        audit: Option<AuditLogger>,
     ) -> Result<Self> {
         let paths = WalPaths::new(data_dir.join("wal"));
         paths.ensure_dirs()?;
 
-        // Find the last LSN from existing segments
-        let last_lsn = Self::find_last_lsn(&paths).await?;
+        // Find the last LSN and schema version from existing segments
+        let (last_lsn, schema_version) = Self::find_last_lsn(&paths).await?;
+        let index = WalIndex::open(&paths.index_path())?;
 
         let state = Arc::new(RwLock::new(WriterState {
             current_lsn: last_lsn,
             current_term: 1,
+            schema_version,
             node_id,
+            recovery_mode: true,
         }));
 
         // Use tuned channel buffer if available, otherwise default to 10000
@@ -106,48 +136,79 @@ impl WalWriter {
             paths,
             config,
             current_segment: None,
+            segment_created_at: Instant::now(),
+            segment_entries: 0,
             buffer: VecDeque::new(),
             last_flush: Instant::now(),
+            group_started: None,
             state: Arc::clone(&state),
             notify_tx: notify_tx_clone,
+            audit,
+            index,
         };
 
         // Spawn writer task
         tokio::spawn(Self::writer_task(inner, receiver));
 
-        Ok(Self { sender, state, notify_tx })
+        Ok(Self { sender, state, notify_tx, column_filter: None })
+    }
+
+    /// Remove the filtered columns from every entry before it is written,
+    /// so they are never replicated
+    pub fn with_column_filter(mut self, filter: ColumnFilter) -> Self {
+        self.column_filter = (!filter.is_empty()).then(|| Arc::new(filter));
+        self
     }
 
-    /// Find the last LSN from existing segments
-    async fn find_last_lsn(paths: &WalPaths) -> Result<Lsn> {
+    /// Find the last LSN and schema version from existing segments
+    async fn find_last_lsn(paths: &WalPaths) -> Result<(Lsn, u64)> {
         let segments = super::segment::list_segments(&paths.base_dir)?;
         
         if let Some(last_path) = segments.last() {
             let mut segment = Segment::open(last_path.clone(), 64, true)?;
             let mut last_lsn = segment.first_lsn();
+            let mut schema_version = None;
             
             for result in segment.iter() {
                 if let Ok(entry) = result {
                     last_lsn = entry.header.lsn;
+                    schema_version = Some(entry.header.schema_version);
                 }
             }
+
+            // A freshly rotated segment has no entries to take the schema version from
+            if schema_version.is_none() && segments.len() > 1 {
+                let mut previous = Segment::open(segments[segments.len() - 2].clone(), 64, true)?;
+                schema_version = previous.iter().flatten().last().map(|e| e.header.schema_version);
+            }
             
-            Ok(last_lsn)
+            Ok((last_lsn, schema_version.unwrap_or(0)))
         } else {
-            Ok(0)
+            Ok((0, 0))
         }
     }
 
     /// Append an entry to the WAL
-    pub async fn append(&self, entry: LogEntry) -> Result<Lsn> {
+    ///
+    /// The entry is tagged with the calling task's trace ID, if any.
+    pub async fn append(&self, mut entry: LogEntry) -> Result<Lsn> {
+        if let Some(filter) = &self.column_filter {
+            filter.apply(&mut entry);
+        }
         let (tx, rx) = oneshot::channel();
+        let trace_id = telemetry::current_trace_id();
+        let mut span = Span::child("wal_append", trace_id, &[]);
 
         self.sender
-            .send(WriteRequest { entry, response: tx })
+            .send(WriteRequest { entry, trace_id, response: tx })
             .await
             .map_err(|_| Error::Wal("Writer task terminated".into()))?;
 
-        rx.await.map_err(|_| Error::Wal("Write cancelled".into()))?
+        let result = rx.await.map_err(|_| Error::Wal("Write cancelled".into()))?;
+        if let Err(ref e) = result {
+            span.set_error(e.to_string());
+        }
+        result
     }
 
     /// Append multiple entries atomically
@@ -164,6 +225,12 @@ impl WalWriter {
         self.state.read().await.current_lsn
     }
 
+    /// Whether the writer is still checking or rebuilding the segment index
+    /// after startup. Appends wait until it is done.
+    pub async fn in_recovery(&self) -> bool {
+        self.state.read().await.recovery_mode
+    }
+
     /// Get the current term
     pub async fn current_term(&self) -> u64 {
         self.state.read().await.current_term
@@ -187,6 +254,7 @@ impl WalWriter {
         self.sender
             .send(WriteRequest {
                 entry: LogEntry::Noop,
+                trace_id: TraceId::default(),
                 response: tx,
             })
             .await
@@ -200,52 +268,53 @@ impl WalWriter {
         mut inner: WriterInner,
         mut receiver: mpsc::Receiver<WriteRequest>,
     ) {
-        let flush_interval = Duration::from_millis(inner.config.flush_interval_ms);
-        let batch_size = inner.config.batch_size;
+        // Requests queue in the channel until the index is known to be good
+        inner.recover_index().await;
+
+        let idle_interval = Duration::from_millis(inner.config.flush_interval_ms);
+        let max_delay = Duration::from_micros(inner.config.group_commit_max_delay_us);
+        let max_size = inner.config.group_commit_max_size.max(1);
 
         loop {
-            // Wait for next request or flush timeout
-            let timeout = flush_interval.saturating_sub(inner.last_flush.elapsed());
+            // Wait for the next request, the current group's deadline, or
+            // (while idle) the next segment age check
+            let timeout = match inner.group_started {
+                Some(started) => max_delay.saturating_sub(started.elapsed()),
+                None => idle_interval.saturating_sub(inner.last_flush.elapsed()),
+            };
             
             tokio::select! {
                 Some(request) = receiver.recv() => {
-                    // Skip no-op entries but still trigger flush
-                    if !request.entry.is_noop() {
-                        // Allocate LSN
-                        let lsn = {
-                            let mut state = inner.state.write().await;
-                            state.current_lsn += 1;
-                            state.current_lsn
-                        };
-
-                        let state = inner.state.read().await;
-                        let wal_entry = WalEntry::new(
-                            lsn,
-                            state.current_term,
-                            state.node_id.clone(),
-                            request.entry,
-                        );
-                        drop(state);
-
-                        inner.buffer.push_back((wal_entry, request.response));
-                    } else {
-                        // No-op entry, just acknowledge
-                        let _ = request.response.send(Ok(0));
+                    inner.enqueue(request).await;
+                    // Pick up whatever else is already waiting so concurrent
+                    // appenders share this group
+                    while inner.buffer.len() < max_size {
+                        match receiver.try_recv() {
+                            Ok(request) => inner.enqueue(request).await,
+                            Err(_) => break,
+                        }
                     }
 
-                    // Flush if batch is full
-                    if inner.buffer.len() >= batch_size {
+                    if inner.buffer.len() >= max_size {
                         if let Err(e) = inner.flush_buffer().await {
                             tracing::error!("WAL flush failed: {}", e);
                         }
                     }
                 }
                 _ = tokio::time::sleep(timeout) => {
-                    // Flush on timeout
                     if !inner.buffer.is_empty() {
                         if let Err(e) = inner.flush_buffer().await {
                             tracing::error!("WAL flush failed: {}", e);
                         }
+                    } else {
+                        inner.last_flush = Instant::now();
+                        if inner.segment_expired() {
+                            // Rotate an old segment even while idle so it can be archived
+                            let next_lsn = inner.state.read().await.current_lsn + 1;
+                            if let Err(e) = inner.rotate(next_lsn) {
+                                tracing::error!("WAL segment rotation failed: {}", e);
+                            }
+                        }
                     }
                 }
             }
@@ -254,6 +323,48 @@ impl WalWriter {
 }
 
 impl WriterInner {
+    /// Check the segment index left by the last run, rebuilding it if an
+    /// unclean shutdown left it out of sync with the segments
+    async fn recover_index(&mut self) {
+        match self.index.check(&self.paths, false) {
+            Ok(report) if report.rebuilt => tracing::info!(
+                "Rebuilt WAL index: {} entries, last LSN {}",
+                report.entries, report.segment_lsn
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::error!("WAL index recovery failed: {}", e),
+        }
+        self.state.write().await.recovery_mode = false;
+    }
+
+    /// Assign the request an LSN and add it to the current group. No-ops
+    /// are answered straight away.
+    async fn enqueue(&mut self, request: WriteRequest) {
+        if request.entry.is_noop() {
+            let _ = request.response.send(Ok(0));
+            return;
+        }
+
+        // Allocate LSN, and a new schema version for schema changes
+        let mut state = self.state.write().await;
+        state.current_lsn += 1;
+        if request.entry.changes_schema() {
+            state.schema_version += 1;
+        }
+        let mut wal_entry = WalEntry::new(
+            state.current_lsn,
+            state.current_term,
+            state.node_id.clone(),
+            request.entry,
+        );
+        wal_entry.header.schema_version = state.schema_version;
+        drop(state);
+        wal_entry.header.trace_id = request.trace_id;
+
+        self.group_started.get_or_insert_with(Instant::now);
+        self.buffer.push_back((wal_entry, request.response));
+    }
+
     /// Flush the write buffer to disk
     async fn flush_buffer(&mut self) -> Result<()> {
         if self.buffer.is_empty() {
@@ -261,35 +372,54 @@ impl WriterInner {
         }
 
         // Ensure we have an active segment
-        self.ensure_segment()?;
+        let first_lsn = self.buffer.front().map(|(entry, _)| entry.header.lsn).unwrap_or_default();
+        self.ensure_segment(first_lsn)?;
 
+        self.group_started = None;
+        crate::metrics::GROUP_COMMIT_BATCH_SIZE.observe(self.buffer.len() as f64);
         let mut responses = Vec::new();
+        let mut indexed = Vec::new();
 
+        // Phase one: encode the whole group into the segment's staging buffer
+        let mut staged = Vec::new();
         while let Some((entry, response)) = self.buffer.pop_front() {
             let lsn = entry.header.lsn;
 
-            // Check if segment needs rotation
+            // Check if segment needs rotation (sealing writes what is staged)
             let needs_rotation = {
                 let segment = self.current_segment.as_ref().unwrap();
-                !segment.has_space(8192)
+                !segment.has_space(8192) || self.segment_expired()
             };
 
             if needs_rotation {
-                // Seal and rotate segment
-                self.current_segment.as_mut().unwrap().seal()?;
-                let new_segment = Segment::create(
-                    self.paths.segment_path(lsn),
-                    lsn,
-                    self.config.segment_size_mb,
-                    self.config.compression,
-                )?;
-                self.current_segment = Some(new_segment);
+                self.rotate(lsn)?;
             }
 
-            // Now append to segment
             let segment = self.current_segment.as_mut().unwrap();
-            match segment.append(&entry) {
-                Ok(_) => {
+            match segment.stage(&entry) {
+                Ok(offset) => {
+                    self.segment_entries += 1;
+                    staged.push((entry, response, segment.id, offset));
+                }
+                Err(e) => {
+                    responses.push((response, Err(e)));
+                }
+            }
+        }
+
+        // Phase two: a single write, then one sync for the whole group
+        if let Err(e) = self.current_segment.as_mut().unwrap().write_staged() {
+            for (_, response, _, _) in staged {
+                let _ = response.send(Err(Error::Wal(format!("WAL write failed: {}", e))));
+            }
+            return Err(e);
+        }
+        for (entry, response, segment_id, offset) in staged {
+            let lsn = entry.header.lsn;
+            // An entry is not acknowledged until it has been audited
+            match self.audit.as_mut().map_or(Ok(()), |audit| audit.log(&entry)) {
+                Ok(()) => {
+                    indexed.push((lsn, segment_id, offset));
                     responses.push((response, Ok(lsn)));
                 }
                 Err(e) => {
@@ -298,10 +428,19 @@ impl WriterInner {
             }
         }
 
-        // Always sync to ensure entries are visible to readers (replication needs this)
-        // The fsync config controls whether we use fsync vs sync_data
-        if let Some(segment) = self.current_segment.as_ref() {
-            segment.sync()?;
+        // One sync for the whole group. Without fsync the entries are
+        // already visible to readers through the page cache.
+        if self.config.fsync {
+            if let Some(segment) = self.current_segment.as_ref() {
+                segment.sync()?;
+            }
+        }
+        if let Some(audit) = self.audit.as_ref() {
+            audit.sync()?;
+        }
+        // A stale index is rebuilt on the next start, so this never fails a write
+        if let Err(e) = self.index.record(&indexed) {
+            tracing::warn!("Failed to update WAL index: {}", e);
         }
 
         // Send responses
@@ -317,41 +456,75 @@ impl WriterInner {
         Ok(())
     }
 
-    /// Ensure we have an active segment
-    fn ensure_segment(&mut self) -> Result<()> {
+    /// Ensure we have an active segment, creating one starting at `next_lsn`
+    /// (the first buffered entry) if needed
+    fn ensure_segment(&mut self, next_lsn: Lsn) -> Result<()> {
         if self.current_segment.is_none() {
             // Find existing segments or create new one
             let segments = super::segment::list_segments(&self.paths.base_dir)?;
 
             if let Some(last_path) = segments.last() {
-                let segment = Segment::open(
+                let mut segment = Segment::open(
                     last_path.clone(),
                     self.config.segment_size_mb,
                     self.config.compression,
-                )?;
-
-                if !segment.is_sealed() && segment.has_space(8192) {
+                )?
+                .with_torn_write_detection(self.config.torn_write_detection);
+
+                if !segment.is_sealed() && !segment.is_legacy() && segment.has_space(8192) {
+                    // New entries must follow the last complete one, not a
+                    // half-written entry from before a crash
+                    segment.truncate_torn_tail()?;
+                    // The header count is only written on seal, so this may
+                    // undercount, which at worst delays a time-based rotation
+                    self.segment_entries = segment.entry_count() as u64;
+                    self.segment_created_at = Instant::now();
                     self.current_segment = Some(segment);
                     return Ok(());
                 }
             }
 
             // Create new segment
-            let state = futures::executor::block_on(self.state.read());
-            let next_lsn = state.current_lsn + 1;
-            drop(state);
-
-            let segment = Segment::create(
-                self.paths.segment_path(next_lsn),
-                next_lsn,
-                self.config.segment_size_mb,
-                self.config.compression,
-            )?;
-            self.current_segment = Some(segment);
+            self.create_segment(next_lsn)?;
         }
 
         Ok(())
     }
+
+    /// Start a new active segment beginning at `first_lsn`
+    fn create_segment(&mut self, first_lsn: Lsn) -> Result<()> {
+        let segment = Segment::create(
+            self.paths.segment_path(first_lsn),
+            first_lsn,
+            self.config.segment_size_mb,
+            self.config.compression,
+        )?;
+        self.current_segment = Some(segment);
+        self.segment_created_at = Instant::now();
+        self.segment_entries = 0;
+        Ok(())
+    }
+
+    /// Seal the active segment and start a new one at `next_lsn`
+    fn rotate(&mut self, next_lsn: Lsn) -> Result<()> {
+        if let Some(segment) = self.current_segment.as_mut() {
+            segment.seal()?;
+            tracing::debug!(
+                "Sealed WAL segment {} ({} entries, {}s old)",
+                segment.id, self.segment_entries, self.segment_created_at.elapsed().as_secs()
+            );
+        }
+        self.create_segment(next_lsn)
+    }
+
+    /// Whether the active segment is older than `max_segment_age_secs` and
+    /// holds at least `min_segment_entries` entries
+    fn segment_expired(&self) -> bool {
+        self.config.max_segment_age_secs > 0
+            && self.current_segment.is_some()
+            && self.segment_entries >= self.config.min_segment_entries
+            && self.segment_created_at.elapsed() >= Duration::from_secs(self.config.max_segment_age_secs)
+    }
 }
 
 #[cfg(test)]
@@ -362,12 +535,20 @@ mod tests {
 
     fn test_config() -> WalConfig {
         WalConfig {
-            batch_size: 10,
             flush_interval_ms: 100,
+            group_commit_max_delay_us: 1000,
+            group_commit_max_size: 100,
             compression: true,
             segment_size_mb: 1,
+            max_segment_age_secs: 3600,
+            min_segment_entries: 1,
             retention_hours: 0,
+            min_retention_follower_lsn: true,
+            max_retention_override_hours: 720,
             fsync: false,
+            torn_write_detection: true,
+            compaction_enabled: false,
+            compaction_threshold_segments: 10,
         }
     }
 
@@ -420,4 +601,201 @@ mod tests {
 
         writer.flush().await.unwrap();
     }
+
+    fn raw_entry(i: i64) -> LogEntry {
+        LogEntry::RawSql {
+            sql: format!("INSERT INTO t VALUES ({})", i),
+            affects_table: Some("t".to_string()),
+            database: None,
+            gtid: None,
+        }
+    }
+
+    fn segments(dir: &std::path::Path) -> Vec<Segment> {
+        super::super::segment::list_segments(&dir.join("wal")).unwrap()
+            .into_iter()
+            .map(|path| Segment::open(path, 1, true).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_group_commit_flushes_on_size_or_delay() {
+        let dir = tempdir().unwrap();
+        let config = WalConfig {
+            group_commit_max_size: 4,
+            group_commit_max_delay_us: 200_000,
+            ..test_config()
+        };
+        let writer = WalWriter::new(dir.path().to_path_buf(), config, "test-node".to_string()).await.unwrap();
+        while writer.in_recovery().await {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        // A lone append waits out the delay for company
+        let start = Instant::now();
+        writer.append(raw_entry(1)).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // A full group goes straight away, all callers answered together
+        let start = Instant::now();
+        let appends: Vec<_> = (0..4).map(|i| {
+            let writer = writer.clone();
+            tokio::spawn(async move { writer.append(raw_entry(i)).await.unwrap() })
+        }).collect();
+        let mut lsns = Vec::new();
+        for append in appends {
+            lsns.push(append.await.unwrap());
+        }
+        assert!(start.elapsed() < Duration::from_millis(200));
+        lsns.sort();
+        assert_eq!(lsns, vec![2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_writer_starts_new_segment_after_version_1_segment() {
+        let dir = tempdir().unwrap();
+        let paths = WalPaths::new(dir.path().join("wal"));
+        paths.ensure_dirs().unwrap();
+        std::fs::write(paths.segment_path(1), include_bytes!("testdata/wal_v1.log")).unwrap();
+
+        let writer = WalWriter::new(dir.path().to_path_buf(), test_config(), "test-node".to_string()).await.unwrap();
+        assert_eq!(writer.append(raw_entry(4)).await.unwrap(), 4);
+        writer.flush().await.unwrap();
+
+        let mut segments = segments(dir.path());
+        assert_eq!(segments.len(), 2);
+        assert!(segments[0].is_legacy());
+        assert_eq!(segments[0].iter().count(), 3);
+        assert!(!segments[1].is_legacy());
+        let lsns: Vec<Lsn> = segments[1].iter().map(|e| e.unwrap().header.lsn).collect();
+        assert_eq!(lsns, vec![4]);
+    }
+
+    /// 8 writers, 100 000 appends, with and without fsync:
+    /// `cargo test --release bench_group_commit -- --ignored --nocapture`
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore]
+    async fn bench_group_commit() {
+        const WRITERS: u64 = 8;
+        const TOTAL: u64 = 100_000;
+        for fsync in [true, false] {
+            let dir = tempdir().unwrap();
+            let config = WalConfig { fsync, segment_size_mb: 64, ..test_config() };
+            let writer = WalWriter::new(dir.path().to_path_buf(), config, "test-node".to_string()).await.unwrap();
+
+            let start = Instant::now();
+            let tasks: Vec<_> = (0..WRITERS).map(|w| {
+                let writer = writer.clone();
+                tokio::spawn(async move {
+                    // Each writer pipelines its appends, like many client connections
+                    let appends: Vec<_> = (0..TOTAL / WRITERS).map(|i| {
+                        let writer = writer.clone();
+                        tokio::spawn(async move { writer.append(raw_entry((w * TOTAL + i) as i64)).await.unwrap() })
+                    }).collect();
+                    for append in appends {
+                        append.await.unwrap();
+                    }
+                })
+            }).collect();
+            for task in tasks {
+                task.await.unwrap();
+            }
+            let rate = TOTAL as f64 / start.elapsed().as_secs_f64();
+            println!("fsync={}: {:.0} writes/sec", fsync, rate);
+        }
+    }
+
+    /// Appends until killed. Run as a child process by `test_recovers_after_sigkill`.
+    #[tokio::test]
+    #[ignore]
+    async fn crash_child_writer() {
+        let Ok(dir) = std::env::var("WOLFSCALE_CRASH_TEST_DIR") else { return };
+        let writer = WalWriter::new(PathBuf::from(dir), test_config(), "test-node".to_string()).await.unwrap();
+        // Enough concurrent appends to fill every batch
+        let tasks: Vec<_> = (0..64).map(|_| {
+            let writer = writer.clone();
+            tokio::spawn(async move {
+                for i in 1.. {
+                    writer.append(raw_entry(i)).await.unwrap();
+                }
+            })
+        }).collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recovers_after_sigkill() {
+        let dir = tempdir().unwrap();
+        let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "wal::writer::tests::crash_child_writer", "--ignored"])
+            .env("WOLFSCALE_CRASH_TEST_DIR", dir.path())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+
+        // Kill it (SIGKILL) mid-write once it has logged a few thousand entries
+        let first_segment = WalPaths::new(dir.path().join("wal")).segment_path(1);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while std::fs::metadata(&first_segment).map(|m| m.len()).unwrap_or(0) < 256 * 1024 {
+            assert!(Instant::now() < deadline, "child writer made no progress");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let last_written = segments(dir.path()).iter_mut()
+            .flat_map(|s| s.iter().map_while(|r| r.ok()).map(|e| e.header.lsn).collect::<Vec<_>>())
+            .last()
+            .unwrap();
+
+        let writer = WalWriter::new(dir.path().to_path_buf(), test_config(), "test-node".to_string()).await.unwrap();
+        assert_eq!(writer.current_lsn().await, last_written);
+        assert_eq!(writer.append(raw_entry(0)).await.unwrap(), last_written + 1);
+        assert!(!writer.in_recovery().await);
+
+        let index = WalIndex::open(&WalPaths::new(dir.path().join("wal")).index_path()).unwrap();
+        assert_eq!(index.last_lsn().unwrap(), last_written + 1);
+        assert!(index.lookup(last_written).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_segment_rotates_by_age() {
+        let dir = tempdir().unwrap();
+        let config = WalConfig { max_segment_age_secs: 1, ..test_config() };
+        let writer = WalWriter::new(dir.path().to_path_buf(), config, "test-node".to_string()).await.unwrap();
+
+        writer.append(raw_entry(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(writer.append(raw_entry(2)).await.unwrap(), 2);
+
+        // The new segment starts at the next LSN
+        let segments = segments(dir.path());
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].last_lsn(), 1);
+        assert_eq!(segments[1].first_lsn(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_segment_age_needs_min_entries() {
+        let dir = tempdir().unwrap();
+        let config = WalConfig { max_segment_age_secs: 1, min_segment_entries: 2, ..test_config() };
+        let writer = WalWriter::new(dir.path().to_path_buf(), config, "test-node".to_string()).await.unwrap();
+
+        // Too few entries to rotate, however old the segment is
+        writer.append(raw_entry(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        writer.append(raw_entry(2)).await.unwrap();
+        assert_eq!(segments(dir.path()).len(), 1);
+
+        // Once it has enough, an idle writer rotates without waiting for a write
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(segments(dir.path()).len(), 2);
+        assert_eq!(writer.append(raw_entry(3)).await.unwrap(), 3);
+        let mut segments = segments(dir.path());
+        assert_eq!(segments[1].first_lsn(), 3);
+        assert_eq!(segments[1].iter().count(), 1);
+    }
 }
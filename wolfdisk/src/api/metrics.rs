@@ -0,0 +1,193 @@
+//! Prometheus metrics served from `GET /metrics`
+//!
+//! Chunk counts and sizes come from scanning the chunk directory at scrape
+//! time; operation counters are kept by the `ChunkStore`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::Ordering;
+
+use crate::storage::{ChunkStore, Scrubber};
+use crate::storage::index::FileIndex;
+
+/// Everything `GET /metrics` reports
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    pub chunk_count: u64,
+    pub chunk_bytes: u64,
+    pub file_count: u64,
+    pub dir_count: u64,
+    pub index_entries: u64,
+    pub chunk_reads: u64,
+    pub chunk_writes: u64,
+    pub chunk_deletes: u64,
+    pub chunk_read_bytes: u64,
+    pub chunk_write_bytes: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub streaming_writes: u64,
+    pub scrub_chunks_checked: u64,
+    pub scrub_corrupt_chunks: u64,
+    pub scrub_repaired_chunks: u64,
+    /// Peer → chunks of the current broadcast round it hasn't received
+    pub replication_lag_chunks: BTreeMap<String, u64>,
+    /// Peer → seconds since it was last heard from
+    pub peer_last_seen_seconds: BTreeMap<String, f64>,
+}
+
+impl Metrics {
+    /// Chunk directory usage and operation counters
+    pub fn read_chunk_store(&mut self, store: &ChunkStore) -> std::io::Result<()> {
+        let (count, bytes) = store.disk_usage().map_err(std::io::Error::other)?;
+        self.chunk_count = count;
+        self.chunk_bytes = bytes;
+        let stats = store.stats();
+        self.chunk_reads = stats.reads.load(Ordering::Relaxed);
+        self.chunk_writes = stats.writes.load(Ordering::Relaxed);
+        self.chunk_deletes = stats.deletes.load(Ordering::Relaxed);
+        self.chunk_read_bytes = stats.read_bytes.load(Ordering::Relaxed);
+        self.chunk_write_bytes = stats.write_bytes.load(Ordering::Relaxed);
+        self.cache_hits = stats.cache_hits.load(Ordering::Relaxed);
+        self.cache_misses = stats.cache_misses.load(Ordering::Relaxed);
+        self.streaming_writes = stats.streaming_writes.load(Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Scrub totals since the node started
+    pub fn read_scrubber(&mut self, scrubber: &Scrubber) {
+        let stats = scrubber.stats();
+        self.scrub_chunks_checked = stats.chunks_checked.load(Ordering::Relaxed);
+        self.scrub_corrupt_chunks = stats.corrupt_chunks.load(Ordering::Relaxed);
+        self.scrub_repaired_chunks = stats.repaired_chunks.load(Ordering::Relaxed);
+    }
+
+    /// File, directory and entry counts of the index
+    pub fn read_index(&mut self, index: &FileIndex) {
+        self.index_entries = index.len() as u64;
+        self.dir_count = index.iter().filter(|(_, e)| e.is_dir).count() as u64;
+        self.file_count = self.index_entries - self.dir_count;
+    }
+
+    /// Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+        };
+        metric("wolfdisk_chunk_count", "gauge", "Chunk files stored locally", self.chunk_count);
+        metric("wolfdisk_chunk_bytes_total", "gauge", "Bytes of chunk files stored locally", self.chunk_bytes);
+        metric("wolfdisk_file_count", "gauge", "Files (and symlinks) in the index", self.file_count);
+        metric("wolfdisk_dir_count", "gauge", "Directories in the index", self.dir_count);
+        metric("wolfdisk_index_entries_total", "gauge", "Entries in the index", self.index_entries);
+        metric("wolfdisk_chunk_store_reads_total", "counter", "Chunks read", self.chunk_reads);
+        metric("wolfdisk_chunk_store_writes_total", "counter", "Chunks written (not counting deduplicated ones)", self.chunk_writes);
+        metric("wolfdisk_chunk_store_deletes_total", "counter", "Chunks deleted", self.chunk_deletes);
+        metric("wolfdisk_chunk_store_read_bytes_total", "counter", "Bytes of chunks read", self.chunk_read_bytes);
+        metric("wolfdisk_chunk_store_write_bytes_total", "counter", "Bytes of chunks written", self.chunk_write_bytes);
+        metric("wolfdisk_cache_hits_total", "counter", "Chunk reads answered by the read cache", self.cache_hits);
+        metric("wolfdisk_cache_misses_total", "counter", "Chunk reads that missed the read cache", self.cache_misses);
+        metric("wolfdisk_streaming_writes_total", "counter", "FUSE writes stored without going through the write buffer", self.streaming_writes);
+        metric("wolfdisk_scrub_chunks_checked_total", "counter", "Chunk files re-hashed by the scrubber", self.scrub_chunks_checked);
+        metric("wolfdisk_scrub_corrupt_chunks_total", "counter", "Chunk files found not to match their hash", self.scrub_corrupt_chunks);
+        metric("wolfdisk_scrub_repaired_chunks_total", "counter", "Corrupt chunk files replaced with a copy from a peer", self.scrub_repaired_chunks);
+
+        let _ = writeln!(out, "# HELP wolfdisk_replication_lag_chunks Chunks of the leader's current broadcast round not yet sent to the peer");
+        let _ = writeln!(out, "# TYPE wolfdisk_replication_lag_chunks gauge");
+        for (peer, chunks) in &self.replication_lag_chunks {
+            let _ = writeln!(out, "wolfdisk_replication_lag_chunks{{peer=\"{}\"}} {}", escape_label(peer), chunks);
+        }
+        let _ = writeln!(out, "# HELP wolfdisk_peer_last_seen_seconds Seconds since the peer was last heard from");
+        let _ = writeln!(out, "# TYPE wolfdisk_peer_last_seen_seconds gauge");
+        for (peer, seconds) in &self.peer_last_seen_seconds {
+            let _ = writeln!(out, "wolfdisk_peer_last_seen_seconds{{peer_id=\"{}\"}} {:.3}", escape_label(peer), seconds);
+        }
+        out
+    }
+
+    /// Read back the output of `render` (unknown lines are skipped)
+    pub fn parse(text: &str) -> Self {
+        let mut metrics = Self::default();
+        for line in text.lines().filter(|l| !l.starts_with('#')) {
+            let Some((series, value)) = line.rsplit_once(' ') else { continue };
+            let Ok(value) = value.parse::<f64>() else { continue };
+            let (name, label) = match series.split_once('{') {
+                Some((name, labels)) => (name, labels.split_once("=\"").map(|(_, v)| unescape_label(v.trim_end_matches("\"}")))),
+                None => (series, None),
+            };
+            let count = value as u64;
+            match (name, label) {
+                ("wolfdisk_chunk_count", _) => metrics.chunk_count = count,
+                ("wolfdisk_chunk_bytes_total", _) => metrics.chunk_bytes = count,
+                ("wolfdisk_file_count", _) => metrics.file_count = count,
+                ("wolfdisk_dir_count", _) => metrics.dir_count = count,
+                ("wolfdisk_index_entries_total", _) => metrics.index_entries = count,
+                ("wolfdisk_chunk_store_reads_total", _) => metrics.chunk_reads = count,
+                ("wolfdisk_chunk_store_writes_total", _) => metrics.chunk_writes = count,
+                ("wolfdisk_chunk_store_deletes_total", _) => metrics.chunk_deletes = count,
+                ("wolfdisk_chunk_store_read_bytes_total", _) => metrics.chunk_read_bytes = count,
+                ("wolfdisk_chunk_store_write_bytes_total", _) => metrics.chunk_write_bytes = count,
+                ("wolfdisk_cache_hits_total", _) => metrics.cache_hits = count,
+                ("wolfdisk_cache_misses_total", _) => metrics.cache_misses = count,
+                ("wolfdisk_streaming_writes_total", _) => metrics.streaming_writes = count,
+                ("wolfdisk_scrub_chunks_checked_total", _) => metrics.scrub_chunks_checked = count,
+                ("wolfdisk_scrub_corrupt_chunks_total", _) => metrics.scrub_corrupt_chunks = count,
+                ("wolfdisk_scrub_repaired_chunks_total", _) => metrics.scrub_repaired_chunks = count,
+                ("wolfdisk_replication_lag_chunks", Some(peer)) => {
+                    metrics.replication_lag_chunks.insert(peer, count);
+                }
+                ("wolfdisk_peer_last_seen_seconds", Some(peer)) => {
+                    metrics.peer_last_seen_seconds.insert(peer, value);
+                }
+                _ => {}
+            }
+        }
+        metrics
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn unescape_label(value: &str) -> String {
+    value.replace("\\n", "\n").replace("\\\"", "\"").replace("\\\\", "\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_chunk_store_metrics() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().join("chunks"), 4096).unwrap();
+        let a = store.store(b"first chunk").unwrap();
+        let b = store.store(b"second").unwrap();
+        // Deduplicated, so not written again
+        store.store(b"second").unwrap();
+        store.get(&a).unwrap();
+        store.delete(&b).unwrap();
+
+        let mut metrics = Metrics::default();
+        metrics.read_chunk_store(&store).unwrap();
+        assert_eq!((metrics.chunk_count, metrics.chunk_bytes), (1, 11));
+        assert_eq!((metrics.chunk_writes, metrics.chunk_write_bytes), (2, 17));
+        assert_eq!((metrics.chunk_reads, metrics.chunk_read_bytes), (1, 11));
+        assert_eq!(metrics.chunk_deletes, 1);
+        // Written chunks go into the read cache
+        assert_eq!((metrics.cache_hits, metrics.cache_misses), (1, 0));
+
+        metrics.streaming_writes = 2;
+        metrics.scrub_corrupt_chunks = 1;
+        metrics.replication_lag_chunks.insert("node-2".to_string(), 3);
+        metrics.peer_last_seen_seconds.insert("node-2".to_string(), 1.5);
+        let text = metrics.render();
+        assert!(text.contains("wolfdisk_chunk_count 1\n"));
+        assert!(text.contains("wolfdisk_streaming_writes_total 2\n"));
+        assert!(text.contains("wolfdisk_scrub_corrupt_chunks_total 1\n"));
+        assert!(text.contains("wolfdisk_replication_lag_chunks{peer=\"node-2\"} 3\n"));
+        assert!(text.contains("wolfdisk_peer_last_seen_seconds{peer_id=\"node-2\"} 1.500\n"));
+        assert_eq!(Metrics::parse(&text), metrics);
+    }
+}
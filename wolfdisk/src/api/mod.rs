@@ -0,0 +1,25 @@
+//! Admin HTTP API for WolfDisk
+//!
+//! A small JSON API bound to `[api] bind` (127.0.0.1:9502 by default):
+//! - `GET /sync/progress` - files and bytes of the current recursive sync
+//! - `GET /tier/status` - bytes held locally and in the cold tier
+//! - `POST /tier/evict[?dry_run=true]` - evict cold chunks now
+//! - `GET /rack/status` - how chunk copies are spread across racks
+//! - `POST /rebalance` - start moving chunks off over-full followers (leader)
+//! - `GET /rebalance/status` - progress of the current or last rebalance
+//! - `POST /scrub` - re-hash every local chunk now
+//! - `GET /scrub/status` - progress of the current or last scrub, and corrupt chunks
+//! - `GET /events[?path=/dir]` - Server-Sent Events for each CREATE, MODIFY,
+//!   DELETE and RENAME
+//! - `GET /metrics` - chunk store, index and peer metrics (Prometheus format)
+//! - `POST /mount/read-write` - stop refusing changes on a `mount.read_only` mount
+
+pub mod metrics;
+pub mod server;
+
+pub use metrics::Metrics;
+pub use server::{
+    fetch_metrics, fetch_rack_status, fetch_rebalance_status, fetch_scrub_status, fetch_sync_progress, fetch_tier_status,
+    request_read_write, request_rebalance, request_scrub, request_tier_evict, watch_events, ApiServer, ClusterView,
+    MountStatus,
+};
@@ -0,0 +1,475 @@
+//! Admin HTTP server and client helpers
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tracing::info;
+
+use super::metrics::Metrics;
+use crate::cluster::ClusterManager;
+use crate::config::ReplicationConfig;
+use crate::fuse::{EventBus, FsEvent};
+use crate::replication::placement::rack_status;
+use crate::replication::{RackStatus, RebalanceStatus, Rebalancer, ReplicationLag, SyncProgress, SyncProgressTracker};
+use crate::storage::index::FileIndex;
+use crate::storage::{ChunkStore, EvictReport, ScrubStatus, Scrubber, TierStatus, TieredChunkStore};
+
+/// Shared state for the admin API
+#[derive(Clone)]
+pub struct ApiState {
+    pub sync_progress: Arc<SyncProgressTracker>,
+    /// Set when `[storage.cold_tier]` is enabled
+    pub tiers: Option<Arc<TieredChunkStore>>,
+    /// Set on cluster nodes, for `/rack/status`
+    pub cluster: Option<ClusterView>,
+    /// Set on cluster nodes, for `/rebalance`
+    pub rebalancer: Option<Arc<Rebalancer>>,
+    /// Set on mounted nodes, for `/events`
+    pub events: Option<EventBus>,
+    /// Set on storage nodes, for `/metrics`
+    pub chunk_store: Option<Arc<ChunkStore>>,
+    /// Set on cluster nodes, for `wolfdisk_replication_lag_chunks`
+    pub replication_lag: Option<Arc<ReplicationLag>>,
+    /// Set on mounted nodes, for `/mount/read-write`
+    pub read_only: Option<Arc<AtomicBool>>,
+    /// Set on storage nodes, for `/scrub`
+    pub scrubber: Option<Arc<Scrubber>>,
+}
+
+/// Whether the FUSE mount refuses changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MountStatus {
+    pub read_only: bool,
+}
+
+/// What `/rack/status` needs to work out replica placement
+#[derive(Clone)]
+pub struct ClusterView {
+    pub cluster: Arc<ClusterManager>,
+    pub file_index: Arc<RwLock<FileIndex>>,
+    pub replication: ReplicationConfig,
+    pub rack: Option<String>,
+}
+
+/// Admin HTTP server
+pub struct ApiServer {
+    bind_addr: String,
+    state: ApiState,
+}
+
+impl ApiServer {
+    pub fn new(bind_addr: String, sync_progress: Arc<SyncProgressTracker>) -> Self {
+        Self {
+            bind_addr,
+            state: ApiState {
+                sync_progress,
+                tiers: None,
+                cluster: None,
+                rebalancer: None,
+                events: None,
+                chunk_store: None,
+                replication_lag: None,
+                read_only: None,
+                scrubber: None,
+            },
+        }
+    }
+
+    /// Serve `/tier/*` for the cold tier
+    pub fn with_tiers(mut self, tiers: Option<Arc<TieredChunkStore>>) -> Self {
+        self.state.tiers = tiers;
+        self
+    }
+
+    /// Serve `/rack/status` from this node's view of the cluster
+    pub fn with_cluster(mut self, cluster: ClusterView) -> Self {
+        self.state.cluster = Some(cluster);
+        self
+    }
+
+    /// Serve `/rebalance` for chunk rebalancing
+    pub fn with_rebalancer(mut self, rebalancer: Arc<Rebalancer>) -> Self {
+        self.state.rebalancer = Some(rebalancer);
+        self
+    }
+
+    /// Stream filesystem changes from `/events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.state.events = Some(events);
+        self
+    }
+
+    /// Report chunk store usage and operations from `/metrics`
+    pub fn with_chunk_store(mut self, chunk_store: Arc<ChunkStore>) -> Self {
+        self.state.chunk_store = Some(chunk_store);
+        self
+    }
+
+    /// Report how far each peer is behind from `/metrics`
+    pub fn with_replication_lag(mut self, lag: Arc<ReplicationLag>) -> Self {
+        self.state.replication_lag = Some(lag);
+        self
+    }
+
+    /// Let `/mount/read-write` lift `mount.read_only`
+    pub fn with_read_only(mut self, read_only: Arc<AtomicBool>) -> Self {
+        self.state.read_only = Some(read_only);
+        self
+    }
+
+    /// Serve `/scrub` and report scrub totals from `/metrics`
+    pub fn with_scrubber(mut self, scrubber: Arc<Scrubber>) -> Self {
+        self.state.scrubber = Some(scrubber);
+        self
+    }
+
+    fn router(self) -> Router {
+        Router::new()
+            .route("/sync/progress", get(handle_sync_progress))
+            .route("/tier/status", get(handle_tier_status))
+            .route("/tier/evict", post(handle_tier_evict))
+            .route("/rack/status", get(handle_rack_status))
+            .route("/rebalance", post(handle_rebalance))
+            .route("/rebalance/status", get(handle_rebalance_status))
+            .route("/scrub", post(handle_scrub))
+            .route("/scrub/status", get(handle_scrub_status))
+            .route("/events", get(handle_events))
+            .route("/metrics", get(handle_metrics))
+            .route("/mount/read-write", post(handle_mount_read_write))
+            .with_state(self.state)
+    }
+
+    /// Start the admin API (call from a tokio runtime)
+    pub async fn run(self) -> std::io::Result<()> {
+        info!("Admin API listening on {}", self.bind_addr);
+
+        let listener = TcpListener::bind(&self.bind_addr).await?;
+        axum::serve(listener, self.router()).await?;
+
+        Ok(())
+    }
+}
+
+async fn handle_sync_progress(State(state): State<ApiState>) -> Json<SyncProgress> {
+    Json(state.sync_progress.progress())
+}
+
+type ApiError = (StatusCode, String);
+
+fn tiers(state: &ApiState) -> Result<Arc<TieredChunkStore>, ApiError> {
+    state.tiers.clone()
+        .ok_or((StatusCode::NOT_FOUND, "Cold tier is not enabled".to_string()))
+}
+
+async fn handle_tier_status(State(state): State<ApiState>) -> Result<Json<TierStatus>, ApiError> {
+    let tiers = tiers(&state)?;
+    tokio::task::spawn_blocking(move || tiers.status())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[derive(Deserialize)]
+struct EvictParams {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+async fn handle_tier_evict(
+    State(state): State<ApiState>,
+    Query(params): Query<EvictParams>,
+) -> Result<Json<EvictReport>, ApiError> {
+    let tiers = tiers(&state)?;
+    tokio::task::spawn_blocking(move || tiers.evict(params.dry_run))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+async fn handle_rack_status(State(state): State<ApiState>) -> Result<Json<RackStatus>, ApiError> {
+    let view = state.cluster
+        .ok_or((StatusCode::NOT_FOUND, "Not running as a cluster node".to_string()))?;
+    tokio::task::spawn_blocking(move || {
+        let hashes: HashSet<[u8; 32]> = {
+            let index = view.file_index.read().unwrap();
+            index.iter().flat_map(|(_, e)| e.all_chunk_hashes()).collect()
+        };
+        rack_status(
+            &view.replication,
+            view.cluster.node_id(),
+            view.rack.as_deref(),
+            view.cluster.is_leader(),
+            &view.cluster.peers(),
+            &hashes,
+        )
+    })
+    .await
+    .map(Json)
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn rebalancer(state: &ApiState) -> Result<Arc<Rebalancer>, ApiError> {
+    state.rebalancer.clone()
+        .ok_or((StatusCode::NOT_FOUND, "Not running as a cluster node".to_string()))
+}
+
+async fn handle_rebalance(State(state): State<ApiState>) -> Result<Json<RebalanceStatus>, ApiError> {
+    rebalancer(&state)?.start()
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
+
+async fn handle_rebalance_status(State(state): State<ApiState>) -> Result<Json<RebalanceStatus>, ApiError> {
+    Ok(Json(rebalancer(&state)?.status()))
+}
+
+fn scrubber(state: &ApiState) -> Result<Arc<Scrubber>, ApiError> {
+    state.scrubber.clone()
+        .ok_or((StatusCode::NOT_FOUND, "This node keeps no chunks to scrub".to_string()))
+}
+
+async fn handle_scrub(State(state): State<ApiState>) -> Result<Json<ScrubStatus>, ApiError> {
+    scrubber(&state)?.start()
+        .map(Json)
+        .map_err(|e| (StatusCode::CONFLICT, e))
+}
+
+async fn handle_scrub_status(State(state): State<ApiState>) -> Result<Json<ScrubStatus>, ApiError> {
+    Ok(Json(scrubber(&state)?.status()))
+}
+
+#[derive(Deserialize)]
+struct EventsParams {
+    /// Only changes at or below this path
+    #[serde(default)]
+    path: String,
+}
+
+/// Stream change events as they happen. A client that falls more than
+/// `EVENT_BUFFER` events behind misses the oldest ones.
+async fn handle_events(
+    State(state): State<ApiState>,
+    Query(params): Query<EventsParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let events = state.events
+        .ok_or((StatusCode::NOT_FOUND, "Filesystem is not mounted".to_string()))?;
+    let stream = BroadcastStream::new(events.subscribe()).filter_map(move |event| {
+        // Lagged subscribers skip what they missed
+        let event = event.ok().filter(|e| e.matches(&params.path))?;
+        Event::default().json_data(&event).ok().map(Ok)
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Metrics in the Prometheus text format
+async fn handle_metrics(State(state): State<ApiState>) -> Result<([(header::HeaderName, &'static str); 1], String), ApiError> {
+    let metrics = tokio::task::spawn_blocking(move || -> std::io::Result<Metrics> {
+        let mut metrics = Metrics::default();
+        if let Some(ref store) = state.chunk_store {
+            metrics.read_chunk_store(store)?;
+        }
+        if let Some(ref view) = state.cluster {
+            metrics.read_index(&view.file_index.read().unwrap());
+            metrics.peer_last_seen_seconds = view.cluster.peers().into_iter()
+                .map(|peer| (peer.node_id, peer.last_seen.elapsed().as_secs_f64()))
+                .collect();
+        }
+        if let Some(ref lag) = state.replication_lag {
+            metrics.replication_lag_chunks = lag.snapshot();
+        }
+        if let Some(ref scrubber) = state.scrubber {
+            metrics.read_scrubber(scrubber);
+        }
+        Ok(metrics)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics.render()))
+}
+
+/// Stop refusing changes through the mount. The kernel keeps a mount made
+/// with `-o ro` read-only, so this only takes effect after a remount.
+async fn handle_mount_read_write(State(state): State<ApiState>) -> Result<Json<MountStatus>, ApiError> {
+    let read_only = state.read_only
+        .ok_or((StatusCode::NOT_FOUND, "Filesystem is not mounted".to_string()))?;
+    if read_only.swap(false, Ordering::Relaxed) {
+        info!("Mount switched to read-write");
+    }
+    Ok(Json(MountStatus { read_only: false }))
+}
+
+/// Send a request to a running node's admin API and return the body of a
+/// 200 reply (used by the CLI, which has no async runtime)
+fn request(bind_addr: &str, method: &str, path: &str, timeout: Duration) -> std::io::Result<String> {
+    let addr = bind_addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    write!(stream, "{} {} HTTP/1.0\r\nHost: {}\r\nContent-Length: 0\r\n\r\n", method, path, bind_addr)?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed HTTP response"))?;
+    if head.split_whitespace().nth(1) != Some("200") {
+        return Err(std::io::Error::other(body.trim().to_string()));
+    }
+    Ok(body.to_string())
+}
+
+/// `request`, parsing the JSON reply
+fn request_json<T: DeserializeOwned>(bind_addr: &str, method: &str, path: &str, timeout: Duration) -> std::io::Result<T> {
+    let body = request(bind_addr, method, path, timeout)?;
+    serde_json::from_str(&body).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Fetch `GET /sync/progress` from a running node
+pub fn fetch_sync_progress(bind_addr: &str, timeout: Duration) -> std::io::Result<SyncProgress> {
+    request_json(bind_addr, "GET", "/sync/progress", timeout)
+}
+
+/// Fetch `GET /tier/status` from a running node
+pub fn fetch_tier_status(bind_addr: &str, timeout: Duration) -> std::io::Result<TierStatus> {
+    request_json(bind_addr, "GET", "/tier/status", timeout)
+}
+
+/// Ask a running node to evict cold chunks now (`POST /tier/evict`)
+pub fn request_tier_evict(bind_addr: &str, dry_run: bool, timeout: Duration) -> std::io::Result<EvictReport> {
+    request_json(bind_addr, "POST", &format!("/tier/evict?dry_run={}", dry_run), timeout)
+}
+
+/// Fetch `GET /rack/status` from a running node
+pub fn fetch_rack_status(bind_addr: &str, timeout: Duration) -> std::io::Result<RackStatus> {
+    request_json(bind_addr, "GET", "/rack/status", timeout)
+}
+
+/// Ask a running leader to start a rebalance (`POST /rebalance`)
+pub fn request_rebalance(bind_addr: &str, timeout: Duration) -> std::io::Result<RebalanceStatus> {
+    request_json(bind_addr, "POST", "/rebalance", timeout)
+}
+
+/// Fetch `GET /rebalance/status` from a running node
+pub fn fetch_rebalance_status(bind_addr: &str, timeout: Duration) -> std::io::Result<RebalanceStatus> {
+    request_json(bind_addr, "GET", "/rebalance/status", timeout)
+}
+
+/// Ask a running node to scrub its chunks now (`POST /scrub`)
+pub fn request_scrub(bind_addr: &str, timeout: Duration) -> std::io::Result<ScrubStatus> {
+    request_json(bind_addr, "POST", "/scrub", timeout)
+}
+
+/// Fetch `GET /scrub/status` from a running node
+pub fn fetch_scrub_status(bind_addr: &str, timeout: Duration) -> std::io::Result<ScrubStatus> {
+    request_json(bind_addr, "GET", "/scrub/status", timeout)
+}
+
+/// Ask a running node to accept changes through its mount again (`POST /mount/read-write`)
+pub fn request_read_write(bind_addr: &str, timeout: Duration) -> std::io::Result<MountStatus> {
+    request_json(bind_addr, "POST", "/mount/read-write", timeout)
+}
+
+/// Fetch `GET /metrics` from a running node
+pub fn fetch_metrics(bind_addr: &str, timeout: Duration) -> std::io::Result<Metrics> {
+    request(bind_addr, "GET", "/metrics", timeout).map(|text| Metrics::parse(&text))
+}
+
+/// Follow `GET /events` on a running node, calling `on_event` for each
+/// change until the connection closes
+pub fn watch_events(bind_addr: &str, timeout: Duration, mut on_event: impl FnMut(FsEvent)) -> std::io::Result<()> {
+    let addr = bind_addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no address"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    write!(stream, "GET /events HTTP/1.0\r\nHost: {}\r\nAccept: text/event-stream\r\n\r\n", bind_addr)?;
+
+    let mut lines = BufReader::new(stream).lines();
+    let status = lines.next().transpose()?.unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("200") {
+        let body: Vec<String> = lines.map_while(|l| l.ok()).skip_while(|l| !l.is_empty()).collect();
+        return Err(std::io::Error::other(body.join("\n").trim().to_string()));
+    }
+    for line in lines {
+        if let Some(data) = line?.strip_prefix("data:") {
+            if let Ok(event) = serde_json::from_str(data.trim()) {
+                on_event(event);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuse::FsOp;
+    use std::path::Path;
+
+    #[test]
+    fn test_event_streamed_within_500ms() {
+        let events = EventBus::new();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let bind_addr = listener.local_addr().unwrap().to_string();
+        let server = ApiServer::new(bind_addr.clone(), Arc::new(SyncProgressTracker::new()))
+            .with_events(events.clone());
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            rt.block_on(async {
+                listener.set_nonblocking(true).unwrap();
+                let listener = TcpListener::from_std(listener).unwrap();
+                axum::serve(listener, server.router()).await.unwrap();
+            });
+        });
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = watch_events(&bind_addr, Duration::from_secs(1), |event| { let _ = tx.send(event); });
+        });
+        // Publish until the watcher has subscribed
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            events.publish(FsEvent::new(FsOp::Create, Path::new("foo/bar")));
+            if let Ok(event) = rx.recv_timeout(Duration::from_millis(500)) {
+                assert_eq!(event, FsEvent { op: FsOp::Create, path: "/foo/bar".to_string(), to: None });
+                break;
+            }
+            assert!(std::time::Instant::now() < deadline, "no event received");
+        }
+
+        events.publish(FsEvent::rename(Path::new("foo/bar"), Path::new("foo/baz")));
+        let event = rx.recv_timeout(Duration::from_millis(500)).unwrap();
+        assert_eq!(event.to.as_deref(), Some("/foo/baz"));
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = FsEvent::new(FsOp::Create, Path::new("foo/bar"));
+        assert_eq!(serde_json::to_string(&event).unwrap(), r#"{"op":"CREATE","path":"/foo/bar"}"#);
+        assert!(event.matches("/foo"));
+        assert!(event.matches("/"));
+        assert!(!event.matches("/fo"));
+    }
+}
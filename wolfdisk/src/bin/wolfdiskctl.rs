@@ -7,6 +7,7 @@
 
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -57,6 +58,8 @@ pub struct ClusterStatus {
     #[serde(default)]
     pub total_size: u64,
     pub peers: Vec<PeerStatus>,
+    #[serde(default)]
+    pub counters: BTreeMap<String, u64>,
     pub updated_at: u64, // Unix timestamp
 }
 
@@ -143,6 +146,15 @@ fn show_status(path: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
     println!("  Files         {}", status.file_count);
     println!("  Total Size    {}", format_size(status.total_size));
     println!("  Peers         {}", status.peers.len());
+    if let (Some(hits), Some(misses)) = (
+        status.counters.get("wolfdisk_readdir_cache_hits_total"),
+        status.counters.get("wolfdisk_readdir_cache_misses_total"),
+    ) {
+        println!("  Readdir Cache {} hits / {} misses", hits, misses);
+    }
+    if let Some(remaining) = status.counters.get("wolfdisk_catch_up_chunks_remaining").filter(|&&n| n > 0) {
+        println!("  Catching Up   {} chunks remaining", remaining);
+    }
     println!();
 
     Ok(())
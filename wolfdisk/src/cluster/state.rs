@@ -5,10 +5,14 @@ use std::time::{Duration, Instant};
 use std::collections::HashMap;
 use std::thread;
 
+use rand::Rng;
 use tracing::{debug, info, warn};
 
 use crate::config::{Config, NodeRole};
 use crate::network::discovery::{Discovery, DiscoveredPeer};
+use crate::network::peer::PeerConnection;
+use crate::network::protocol::{Message, RequestVoteMsg, VoteMsg};
+use crate::network::srv::{SrvDiscovery, SrvUpdate, MISSES_BEFORE_GONE};
 
 /// Cluster state for this node
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,9 +36,43 @@ pub struct PeerInfo {
     pub address: String,
     pub is_leader: bool,
     pub is_client: bool,
+    /// Rack the peer advertises (`node.rack`)
+    pub rack: Option<String>,
     pub last_seen: Instant,
 }
 
+/// Bring the peer map in line with an SRV lookup. Peers found only through
+/// DNS are keyed by their address until broadcast discovery reports their
+/// node ID; addresses already known under a node ID are left alone.
+fn apply_srv_update(peers: &RwLock<HashMap<String, PeerInfo>>, update: &SrvUpdate) {
+    let mut peers = peers.write().unwrap();
+    for address in &update.present {
+        if peers.values().any(|p| p.address == *address && p.node_id != *address) {
+            continue;
+        }
+        peers.insert(address.clone(), PeerInfo {
+            node_id: address.clone(),
+            address: address.clone(),
+            is_leader: false,
+            is_client: false,
+            rack: None,
+            last_seen: Instant::now(),
+        });
+    }
+    for address in &update.removed {
+        peers.remove(address);
+    }
+}
+
+/// Whether a peer address is this node's own: its port is the one we listen
+/// on and its IP belongs to this host
+fn is_own_address(address: &str, bind: &str) -> bool {
+    let (Ok(address), Ok(bind)) = (address.parse::<std::net::SocketAddr>(), bind.parse::<std::net::SocketAddr>()) else {
+        return false;
+    };
+    address.port() == bind.port() && std::net::UdpSocket::bind((address.ip(), 0)).is_ok()
+}
+
 /// Cluster manager - handles leader election and state
 pub struct ClusterManager {
     config: Config,
@@ -56,6 +94,87 @@ pub struct ClusterManager {
     /// Used for delta sync - leader only sends entries that changed since follower's version
     /// The is_deleted flag tracks deletions so followers can remove entries they no longer need
     changelog: Arc<RwLock<Vec<(u64, std::path::PathBuf, bool)>>>,
+    /// Candidate this node voted for in the current term
+    voted_for: Arc<RwLock<Option<String>>>,
+}
+
+/// What a leader election needs from the cluster manager, shared with the
+/// election monitor thread
+#[derive(Clone)]
+struct Election {
+    node_id: String,
+    state: Arc<RwLock<ClusterState>>,
+    leader_id: Arc<RwLock<Option<String>>>,
+    peers: Arc<RwLock<HashMap<String, PeerInfo>>>,
+    term: Arc<RwLock<u64>>,
+    voted_for: Arc<RwLock<Option<String>>>,
+    index_version: Arc<RwLock<u64>>,
+}
+
+impl Election {
+    /// Stand for leader in a new term. Every server peer we know of counts
+    /// towards the cluster size, so a partitioned minority can't win.
+    /// Returns true if this node won and is now leading.
+    fn run(&self) -> bool {
+        let term = {
+            let mut term = self.term.write().unwrap();
+            *term += 1;
+            *term
+        };
+        *self.voted_for.write().unwrap() = Some(self.node_id.clone());
+
+        let voters: Vec<PeerInfo> = self.peers.read().unwrap().values()
+            .filter(|p| !p.is_client && p.node_id != self.node_id)
+            .cloned()
+            .collect();
+        let cluster_size = voters.len() + 1;
+        let majority = cluster_size / 2 + 1;
+        let request = RequestVoteMsg {
+            candidate_id: self.node_id.clone(),
+            term,
+            last_index_version: *self.index_version.read().unwrap(),
+        };
+        info!("Leader lost - standing for election in term {} ({} vote(s) needed)", term, majority);
+
+        let (granted, highest_term) = request_votes(&request, &voters);
+        let votes = granted + 1;
+        if highest_term > term {
+            // Someone is further ahead; wait to hear from them
+            *self.term.write().unwrap() = highest_term;
+            *self.voted_for.write().unwrap() = None;
+            info!("Election lost: a peer is in term {}", highest_term);
+            return false;
+        }
+        if votes < majority || *self.term.read().unwrap() != term {
+            info!("Election lost in term {}: {} of {} vote(s)", term, votes, majority);
+            return false;
+        }
+
+        info!("Won election in term {} with {} vote(s) - becoming leader", term, votes);
+        *self.leader_id.write().unwrap() = Some(self.node_id.clone());
+        *self.state.write().unwrap() = ClusterState::Leading;
+        true
+    }
+}
+
+/// Ask each voter for its vote, in parallel. Unreachable voters count as
+/// refusals. Returns the votes granted and the highest term any voter has.
+fn request_votes(request: &RequestVoteMsg, voters: &[PeerInfo]) -> (usize, u64) {
+    thread::scope(|scope| {
+        let replies: Vec<_> = voters.iter()
+            .map(|peer| scope.spawn(move || {
+                let conn = PeerConnection::connect(peer.node_id.clone(), &peer.address).ok()?;
+                conn.request(&Message::RequestVote(request.clone())).ok()
+            }))
+            .collect();
+        replies.into_iter()
+            .filter_map(|reply| reply.join().ok().flatten())
+            .fold((0, request.term), |(votes, term), reply| match reply {
+                Message::VoteGranted(vote) => (votes + 1, term.max(vote.term)),
+                Message::VoteDenied(vote) => (votes, term.max(vote.term)),
+                _ => (votes, term),
+            })
+    })
 }
 
 impl ClusterManager {
@@ -80,6 +199,7 @@ impl ClusterManager {
             index_version: Arc::new(RwLock::new(0)),
             initial_sync_complete: Arc::new(RwLock::new(false)),
             changelog: Arc::new(RwLock::new(Vec::new())),
+            voted_for: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -218,6 +338,69 @@ impl ClusterManager {
         *self.term.read().unwrap()
     }
 
+    fn election(&self) -> Election {
+        Election {
+            node_id: self.node_id.clone(),
+            state: Arc::clone(&self.state),
+            leader_id: Arc::clone(&self.leader_id),
+            peers: Arc::clone(&self.peers),
+            term: Arc::clone(&self.term),
+            voted_for: Arc::clone(&self.voted_for),
+            index_version: Arc::clone(&self.index_version),
+        }
+    }
+
+    /// Stand for leader now, as the election monitor does once the leader
+    /// has been gone for `cluster.leader_timeout_secs`. Returns true if won.
+    pub fn run_election(&self) -> bool {
+        self.election().run()
+    }
+
+    /// Answer a candidate's `RequestVote`. The vote is granted at most once
+    /// per term, only while no leader is visible, and only to a candidate at
+    /// most one index version behind this node.
+    pub fn handle_request_vote(&self, request: &RequestVoteMsg) -> Message {
+        let granted = self.grant_vote(request);
+        let vote = VoteMsg { voter_id: self.node_id.clone(), term: self.term() };
+        if granted {
+            info!("Voted for {} in term {}", request.candidate_id, request.term);
+            Message::VoteGranted(vote)
+        } else {
+            debug!("Refused vote for {} in term {}", request.candidate_id, request.term);
+            Message::VoteDenied(vote)
+        }
+    }
+
+    fn grant_vote(&self, request: &RequestVoteMsg) -> bool {
+        if !self.config.cluster.enable_auto_failover || self.config.node.role == NodeRole::Client {
+            return false;
+        }
+        let mut term = self.term.write().unwrap();
+        if request.term < *term {
+            return false;
+        }
+        // A leader we can still see stays leader
+        let leader_timeout = Duration::from_secs(self.config.cluster.leader_timeout_secs);
+        if self.is_leader() || self.last_leader_heartbeat.read().unwrap().elapsed() < leader_timeout {
+            return false;
+        }
+        let mut voted_for = self.voted_for.write().unwrap();
+        if request.term > *term {
+            *term = request.term;
+            *voted_for = None;
+        }
+        if request.last_index_version + 1 < self.index_version() {
+            return false;
+        }
+        match voted_for.as_deref() {
+            Some(candidate) if candidate != request.candidate_id => false,
+            _ => {
+                *voted_for = Some(request.candidate_id.clone());
+                true
+            }
+        }
+    }
+
     /// Start the cluster manager
     pub fn start(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         *self.running.write().unwrap() = true;
@@ -230,12 +413,15 @@ impl ClusterManager {
         }
 
         // Start discovery if enabled (including for clients!)
-        if self.config.cluster.discovery.is_some() || !self.config.cluster.peers.is_empty() {
+        let cluster = &self.config.cluster;
+        if cluster.discovery.is_some() || !cluster.peers.is_empty() || cluster.discovery_srv.is_some() {
             let discovery = Discovery::new(
                 self.node_id.clone(),
                 self.config.node.bind.clone(),
                 self.config.node.role,
+                self.config.node.rack.clone(),
             );
+            discovery.set_unicast_peers(&cluster.peers);
             discovery.start()?;
             
             // Start a sync thread to copy discovered peers to our peer map
@@ -267,12 +453,18 @@ impl ClusterManager {
                             *cluster_leader_id.write().unwrap() = Some(dp.node_id.clone());
                         }
                         
+                        // Now known by its node ID, not just the address DNS gave
+                        if dp.address != dp.node_id {
+                            peers.remove(&dp.address);
+                        }
+
                         let is_client_peer = matches!(dp.role, crate::network::discovery::DiscoveryRole::Client);
                         peers.insert(dp.node_id.clone(), PeerInfo {
                             node_id: dp.node_id,
                             address: dp.address,
                             is_leader: dp.is_leader,
                             is_client: is_client_peer,
+                            rack: dp.rack,
                             last_seen: dp.last_seen,
                         });
                     }
@@ -282,6 +474,10 @@ impl ClusterManager {
                 }
             });
             
+            if let Some(ref name) = self.config.cluster.discovery_srv {
+                self.start_srv_discovery(name.clone(), discovery.clone());
+            }
+
             self.discovery = Some(discovery);
             
             info!("Discovery started for node {}", self.node_id);
@@ -295,6 +491,44 @@ impl ClusterManager {
         Ok(())
     }
 
+    /// Look up `cluster.discovery_srv` now and every
+    /// `cluster.discovery_interval_secs`, adding the nodes it lists to the
+    /// peer map and announcing this node to them directly
+    fn start_srv_discovery(&self, name: String, discovery: Discovery) {
+        let peers = Arc::clone(&self.peers);
+        let running = Arc::clone(&self.running);
+        let static_peers = self.config.cluster.peers.clone();
+        let bind = self.config.node.bind.clone();
+        let interval = Duration::from_secs(self.config.cluster.discovery_interval_secs.max(1));
+
+        thread::spawn(move || {
+            let mut srv = SrvDiscovery::new(name);
+            info!("DNS SRV discovery started for {}", srv.name());
+            while *running.read().unwrap() {
+                match srv.refresh() {
+                    Ok(mut update) => {
+                        // The record lists this node too
+                        update.present.retain(|address| !is_own_address(address, &bind));
+                        update.added.retain(|address| !is_own_address(address, &bind));
+                        for address in &update.added {
+                            info!("Discovered peer {} through DNS SRV {}", address, srv.name());
+                        }
+                        for address in &update.removed {
+                            info!("Peer {} missing from DNS SRV {} for {} lookups, dropping it", address, srv.name(), MISSES_BEFORE_GONE);
+                        }
+                        apply_srv_update(&peers, &update);
+
+                        let mut unicast = static_peers.clone();
+                        unicast.extend(update.present);
+                        discovery.set_unicast_peers(&unicast);
+                    }
+                    Err(e) => warn!("DNS SRV lookup of {} failed: {}", srv.name(), e),
+                }
+                thread::sleep(interval);
+            }
+        });
+    }
+
     /// Start the election monitor thread
     /// 
     /// Election model:
@@ -317,6 +551,9 @@ impl ClusterManager {
         let discovery_delay = Duration::from_secs(5);
         let peer_stale_threshold = Duration::from_secs(4);
         let initial_sync_complete = Arc::clone(&self.initial_sync_complete);
+        let auto_failover = self.config.cluster.enable_auto_failover;
+        let leader_timeout = Duration::from_secs(self.config.cluster.leader_timeout_secs);
+        let election = self.election();
 
         thread::spawn(move || {
             info!("Election monitor started for node {} - waiting for discovery", node_id);
@@ -365,12 +602,26 @@ impl ClusterManager {
                             }
                             
                             // But if I have a lower ID, I should become leader
-                            if i_am_lowest && node_id.as_str() < leader.as_str() {
+                            // (with auto failover, an elected leader keeps its term)
+                            if !auto_failover && i_am_lowest && node_id.as_str() < leader.as_str() {
                                 info!("I have lower ID than current leader {} - taking over", leader);
                                 *term.write().unwrap() += 1;
                                 *leader_id.write().unwrap() = Some(node_id.clone());
                                 *state.write().unwrap() = ClusterState::Leading;
                             }
+                        } else if auto_failover && leader_id.read().unwrap().is_some() {
+                            // We had a leader and it has gone quiet. Stand for
+                            // election once it has been silent for the timeout,
+                            // plus jitter so candidates don't split the vote.
+                            let jitter = rand::thread_rng().gen_range(0..=leader_timeout.as_millis() as u64 / 2);
+                            let silent = last_leader_heartbeat.read().unwrap().elapsed();
+                            let due = config_role != NodeRole::Follower
+                                && *initial_sync_complete.read().unwrap()
+                                && silent > leader_timeout + Duration::from_millis(jitter);
+                            if due && !election.run() {
+                                // Give whoever won a full timeout to show up
+                                *last_leader_heartbeat.write().unwrap() = std::time::Instant::now();
+                            }
                         } else if i_am_lowest && config_role != NodeRole::Follower {
                             // I have the lowest ID, but wait for initial sync before
                             // taking leadership. This ensures a restarting node first
@@ -392,8 +643,15 @@ impl ClusterManager {
                         }
                     }
                     ClusterState::Leading => {
+                        // With auto failover, only another live leader with a lower
+                        // ID makes us step down
+                        let outranked = if auto_failover {
+                            current_leader.as_deref().is_some_and(|leader| leader < node_id.as_str())
+                        } else {
+                            !i_am_lowest
+                        };
                         // Check if any peer has a lower ID - I should step down
-                        if !i_am_lowest {
+                        if outranked {
                             warn!("Stepping down - discovered peer with lower node ID");
                             *state.write().unwrap() = ClusterState::Following;
                             *last_leader_heartbeat.write().unwrap() = std::time::Instant::now();
@@ -417,6 +675,7 @@ impl ClusterManager {
             address: peer.address,
             is_leader,
             is_client: matches!(peer.role, crate::network::discovery::DiscoveryRole::Client),
+            rack: peer.rack,
             last_seen: peer.last_seen,
         };
         
@@ -430,11 +689,11 @@ impl ClusterManager {
 
     /// Write cluster status to file for wolfdiskctl
     pub fn write_status_file(&self) {
-        self.write_status_file_with_stats(0, 0);
+        self.write_status_file_with_stats(0, 0, &[]);
     }
 
-    /// Write cluster status with file index stats for wolfdiskctl
-    pub fn write_status_file_with_stats(&self, file_count: usize, total_size: u64) {
+    /// Write cluster status with file index stats and counters for wolfdiskctl
+    pub fn write_status_file_with_stats(&self, file_count: usize, total_size: u64, counters: &[(&str, u64)]) {
         use std::time::{SystemTime, UNIX_EPOCH};
         
         let status_dir = std::path::Path::new(&self.config.node.data_dir);
@@ -464,20 +723,27 @@ impl ClusterManager {
                 "role": peer_role,
                 "is_leader": p.is_leader,
                 "is_client": p.is_client,
+                "rack": p.rack,
                 "last_seen_secs_ago": p.last_seen.elapsed().as_secs()
             })
         }).collect();
+
+        let counters: serde_json::Map<String, serde_json::Value> = counters.iter()
+            .map(|(name, value)| (name.to_string(), serde_json::json!(value)))
+            .collect();
         
         let status = serde_json::json!({
             "node_id": self.node_id,
             "role": role,
             "state": state_str,
             "bind_address": self.config.node.bind,
+            "rack": self.config.node.rack,
             "leader_id": self.leader_id(),
             "index_version": self.index_version(),
             "file_count": file_count,
             "total_size": total_size,
             "peers": peer_statuses,
+            "counters": counters,
             "updated_at": SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
@@ -497,3 +763,110 @@ impl ClusterManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::peer::PeerManager;
+
+    fn node(id: &str, leader_timeout_secs: u64) -> Arc<ClusterManager> {
+        let mut config = Config::default();
+        config.node.id = id.to_string();
+        config.cluster.enable_auto_failover = true;
+        config.cluster.leader_timeout_secs = leader_timeout_secs;
+        Arc::new(ClusterManager::new(config))
+    }
+
+    fn add_peer(cluster: &ClusterManager, id: &str, address: &str) {
+        cluster.peers.write().unwrap().insert(id.to_string(), PeerInfo {
+            node_id: id.to_string(),
+            address: address.to_string(),
+            is_leader: false,
+            is_client: false,
+            rack: None,
+            last_seen: Instant::now(),
+        });
+    }
+
+    /// Answer vote requests over TCP, as the node's peer server does
+    fn serve_votes(cluster: Arc<ClusterManager>) -> String {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let address = format!("127.0.0.1:{}", port);
+        let server = PeerManager::new(cluster.node_id().to_string(), address.clone(), move |_, msg| match msg {
+            Message::RequestVote(request) => Some(cluster.handle_request_vote(&request)),
+            _ => None,
+        });
+        server.start().unwrap();
+        std::mem::forget(server);
+        address
+    }
+
+    fn request(candidate: &str, term: u64, last_index_version: u64) -> RequestVoteMsg {
+        RequestVoteMsg { candidate_id: candidate.to_string(), term, last_index_version }
+    }
+
+    #[test]
+    fn test_follower_elected_after_leader_dies() {
+        // Three nodes: leader "a" is dead, "b" stands, "c" votes over the network
+        let b = node("b", 0);
+        let c = node("c", 0);
+        b.set_index_version(7);
+        c.set_index_version(8);
+        let c_address = serve_votes(Arc::clone(&c));
+        for cluster in [&b, &c] {
+            add_peer(cluster, "a", "127.0.0.1:1");
+            *cluster.leader_id.write().unwrap() = Some("a".to_string());
+        }
+        add_peer(&b, "c", &c_address);
+        add_peer(&c, "b", "127.0.0.1:1");
+
+        let started = Instant::now();
+        assert!(b.run_election());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(b.is_leader());
+        assert_eq!(b.leader_id().as_deref(), Some("b"));
+        assert_eq!((b.term(), c.term()), (1, 1));
+
+        // One vote per term, and never for a candidate missing index updates
+        assert!(matches!(c.handle_request_vote(&request("d", 1, 8)), Message::VoteDenied(_)));
+        assert!(matches!(c.handle_request_vote(&request("d", 2, 6)), Message::VoteDenied(_)));
+        assert!(matches!(c.handle_request_vote(&request("d", 3, 7)), Message::VoteGranted(_)));
+        // A leader can't be voted out
+        assert!(matches!(b.handle_request_vote(&request("d", 4, 8)), Message::VoteDenied(_)));
+    }
+
+    #[test]
+    fn test_no_vote_while_leader_alive() {
+        let follower = node("b", 10);
+        follower.receive_leader_heartbeat();
+        assert!(matches!(follower.handle_request_vote(&request("c", 1, 0)), Message::VoteDenied(_)));
+
+        let mut config = Config::default();
+        config.cluster.leader_timeout_secs = 0;
+        let disabled = ClusterManager::new(config);
+        assert!(matches!(disabled.handle_request_vote(&request("c", 1, 0)), Message::VoteDenied(_)));
+    }
+
+    #[test]
+    fn test_srv_peers_join_and_leave() {
+        let cluster = node("a", 10);
+        add_peer(&cluster, "b", "10.0.0.2:9500");
+        let present = |list: &[&str]| SrvUpdate {
+            present: list.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        };
+
+        // b is already known by its node ID; c is only in DNS
+        apply_srv_update(&cluster.peers, &present(&["10.0.0.2:9500", "10.0.0.3:9500"]));
+        let mut ids: Vec<String> = cluster.peers().into_iter().map(|p| p.node_id).collect();
+        ids.sort();
+        assert_eq!(ids, ["10.0.0.3:9500", "b"]);
+
+        apply_srv_update(&cluster.peers, &SrvUpdate { removed: vec!["10.0.0.3:9500".to_string()], ..present(&["10.0.0.2:9500"]) });
+        assert_eq!(cluster.peers().len(), 1);
+
+        assert!(!is_own_address("10.255.255.1:9500", "0.0.0.0:9500"));
+        assert!(!is_own_address("127.0.0.1:9600", "0.0.0.0:9500"));
+        assert!(is_own_address("127.0.0.1:9500", "0.0.0.0:9500"));
+    }
+}
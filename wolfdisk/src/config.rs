@@ -24,6 +24,22 @@ pub struct Config {
     /// S3-compatible API configuration
     #[serde(default)]
     pub s3: S3Config,
+
+    /// Admin HTTP API (sync progress)
+    #[serde(default)]
+    pub api: ApiConfig,
+
+    /// WebDAV gateway
+    #[serde(default)]
+    pub webdav: WebDavConfig,
+
+    /// Local storage tuning
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Per-directory quotas (`[[quota]]` entries)
+    #[serde(default)]
+    pub quota: Vec<QuotaConfig>,
 }
 
 /// Node configuration
@@ -43,6 +59,10 @@ pub struct NodeConfig {
     /// Data directory for chunks and index
     #[serde(default = "default_data_dir")]
     pub data_dir: PathBuf,
+
+    /// Rack (or zone) this node runs in, for rack-aware replica placement
+    #[serde(default)]
+    pub rack: Option<String>,
 }
 
 fn default_role() -> NodeRole {
@@ -66,6 +86,32 @@ pub struct ClusterConfig {
 
     /// Discovery address (UDP multicast or DNS)
     pub discovery: Option<String>,
+
+    /// DNS SRV record listing the cluster's nodes, e.g.
+    /// `_wolfdisk._tcp.example.com`. Used alongside `peers`.
+    #[serde(default)]
+    pub discovery_srv: Option<String>,
+
+    /// Seconds between lookups of `discovery_srv`
+    #[serde(default = "default_discovery_interval_secs")]
+    pub discovery_interval_secs: u64,
+
+    /// Elect a new leader by majority vote when the leader disappears,
+    /// instead of waiting for it to come back
+    #[serde(default)]
+    pub enable_auto_failover: bool,
+
+    /// Seconds without seeing the leader before followers start an election
+    #[serde(default = "default_leader_timeout_secs")]
+    pub leader_timeout_secs: u64,
+}
+
+fn default_leader_timeout_secs() -> u64 {
+    10
+}
+
+fn default_discovery_interval_secs() -> u64 {
+    60
 }
 
 /// Replication mode
@@ -106,6 +152,19 @@ pub struct ReplicationConfig {
     /// Chunk size in bytes (default 4MB)
     #[serde(default = "default_chunk_size")]
     pub chunk_size: usize,
+
+    /// Per-peer bandwidth limit for replication traffic in megabits/s (0 = unlimited)
+    #[serde(default)]
+    pub max_bandwidth_mbps: u64,
+
+    /// Burst allowance (token bucket capacity) in MB when bandwidth is limited
+    #[serde(default = "default_burst_mb")]
+    pub burst_mb: u64,
+
+    /// Place chunk copies on `factor - 1` followers spread across racks
+    /// instead of sending every chunk to every follower
+    #[serde(default)]
+    pub rack_aware: bool,
 }
 
 fn default_mode() -> ReplicationMode {
@@ -120,6 +179,10 @@ fn default_chunk_size() -> usize {
     4 * 1024 * 1024 // 4MB
 }
 
+fn default_burst_mb() -> u64 {
+    50
+}
+
 /// Mount configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MountConfig {
@@ -130,6 +193,21 @@ pub struct MountConfig {
     /// Allow other users to access the mount
     #[serde(default = "default_allow_other")]
     pub allow_other: bool,
+
+    /// How long readdir listings are cached in milliseconds (0 = disabled)
+    #[serde(default = "default_readdir_cache_ttl_ms")]
+    pub readdir_cache_ttl_ms: u64,
+
+    /// Once this many MB have been written to a file in one contiguous run,
+    /// further writes bypass the write buffer and are stored and replicated
+    /// chunk by chunk as they arrive (0 = always buffer)
+    #[serde(default = "default_streaming_threshold_mb")]
+    pub streaming_threshold_mb: u64,
+
+    /// Refuse every change made through the mount (EROFS), including
+    /// metadata; replicated changes from the leader still apply
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 fn default_mount_path() -> PathBuf {
@@ -140,6 +218,208 @@ fn default_allow_other() -> bool {
     true
 }
 
+fn default_readdir_cache_ttl_ms() -> u64 {
+    2000
+}
+
+fn default_streaming_threshold_mb() -> u64 {
+    16
+}
+
+/// Local storage tuning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// Threads used to load chunks for large reads (1 = sequential)
+    #[serde(default = "default_parallel_read_workers")]
+    pub parallel_read_workers: usize,
+
+    /// Reads spanning more chunks than this are loaded in parallel
+    #[serde(default = "default_parallel_read_threshold")]
+    pub parallel_read_threshold: usize,
+
+    /// Chunk writes submitted to io_uring at once (`io-uring` feature only)
+    #[serde(default = "default_write_batch_size")]
+    pub write_batch_size: usize,
+
+    /// Memory-mapped chunk files kept for hot reads, in MB (`mmap-cache` feature only)
+    #[serde(default = "default_mmap_cache_mb")]
+    pub mmap_cache_mb: u64,
+
+    /// Chunk data kept in memory for repeated reads, in MB (0 disables)
+    #[serde(default = "default_read_cache_mb")]
+    pub read_cache_mb: u64,
+
+    /// Object storage for chunks that have not been read for a while
+    #[serde(default)]
+    pub cold_tier: ColdTierConfig,
+
+    /// Periodically even out chunk counts across followers (leader only)
+    #[serde(default)]
+    pub rebalance_enabled: bool,
+
+    /// How far over the ideal chunk count a node may be before chunks move off it
+    #[serde(default = "default_rebalance_threshold_pct")]
+    pub rebalance_threshold_pct: u64,
+
+    /// Bandwidth used for chunk migrations, per receiving node, in Mbps (0 = unlimited)
+    #[serde(default = "default_rebalance_bandwidth_mbps")]
+    pub rebalance_bandwidth_mbps: u64,
+
+    /// Periodically re-hash every local chunk to catch silent corruption
+    #[serde(default = "default_scrub_enabled")]
+    pub scrub_enabled: bool,
+
+    /// Hours between scrubs
+    #[serde(default = "default_scrub_interval_hours")]
+    pub scrub_interval_hours: u64,
+
+    /// Replace corrupt chunks with a good copy from a cluster peer
+    #[serde(default)]
+    pub scrub_auto_repair: bool,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            parallel_read_workers: default_parallel_read_workers(),
+            parallel_read_threshold: default_parallel_read_threshold(),
+            write_batch_size: default_write_batch_size(),
+            mmap_cache_mb: default_mmap_cache_mb(),
+            read_cache_mb: default_read_cache_mb(),
+            cold_tier: ColdTierConfig::default(),
+            rebalance_enabled: false,
+            rebalance_threshold_pct: default_rebalance_threshold_pct(),
+            rebalance_bandwidth_mbps: default_rebalance_bandwidth_mbps(),
+            scrub_enabled: default_scrub_enabled(),
+            scrub_interval_hours: default_scrub_interval_hours(),
+            scrub_auto_repair: false,
+        }
+    }
+}
+
+fn default_parallel_read_workers() -> usize {
+    4
+}
+
+fn default_parallel_read_threshold() -> usize {
+    4
+}
+
+fn default_write_batch_size() -> usize {
+    32
+}
+
+fn default_mmap_cache_mb() -> u64 {
+    256
+}
+
+fn default_read_cache_mb() -> u64 {
+    512
+}
+
+fn default_rebalance_threshold_pct() -> u64 {
+    10
+}
+
+fn default_rebalance_bandwidth_mbps() -> u64 {
+    10
+}
+
+fn default_scrub_enabled() -> bool {
+    true
+}
+
+fn default_scrub_interval_hours() -> u64 {
+    168
+}
+
+/// Cold tier for chunks (`[storage.cold_tier]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColdTierConfig {
+    /// Move chunks that have not been read recently to object storage
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Object storage provider (only "s3" is supported)
+    #[serde(default = "default_cold_tier_provider")]
+    pub provider: String,
+
+    /// Bucket holding evicted chunks
+    #[serde(default)]
+    pub bucket: String,
+
+    /// S3 access key ID
+    #[serde(default)]
+    pub access_key_id: String,
+
+    /// S3 secret access key
+    #[serde(default)]
+    pub secret_access_key: String,
+
+    /// Endpoint URL for S3-compatible services (e.g. MinIO); empty for AWS
+    #[serde(default)]
+    pub endpoint: String,
+
+    /// Bucket region
+    #[serde(default = "default_cold_tier_region")]
+    pub region: String,
+
+    /// Chunks not read for this many days are evicted
+    #[serde(default = "default_evict_after_days")]
+    pub evict_after_days: u64,
+
+    /// Keep a local copy of evicted chunks when they are read again
+    #[serde(default = "default_rewarm")]
+    pub rewarm: bool,
+}
+
+impl Default for ColdTierConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            provider: default_cold_tier_provider(),
+            bucket: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            endpoint: String::new(),
+            region: default_cold_tier_region(),
+            evict_after_days: default_evict_after_days(),
+            rewarm: true,
+        }
+    }
+}
+
+fn default_cold_tier_provider() -> String {
+    "s3".to_string()
+}
+
+fn default_cold_tier_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_evict_after_days() -> u64 {
+    30
+}
+
+fn default_rewarm() -> bool {
+    true
+}
+
+/// Quota on a directory tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    /// Directory the quota applies to, e.g. "/projects/teamA"
+    pub path: PathBuf,
+
+    /// Maximum total size of files under `path` in bytes (unlimited if unset)
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+
+    /// Maximum number of files under `path` (unlimited if unset)
+    #[serde(default)]
+    pub max_files: Option<u64>,
+}
+
 /// S3-compatible API configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct S3Config {
@@ -186,6 +466,77 @@ fn default_s3_bind() -> String {
     "0.0.0.0:9878".to_string()
 }
 
+/// Admin HTTP API configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiConfig {
+    /// Enable the admin API
+    #[serde(default = "default_api_enabled")]
+    pub enabled: bool,
+
+    /// Bind address for the admin API (local only by default)
+    #[serde(default = "default_api_bind")]
+    pub bind: String,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_api_enabled(),
+            bind: default_api_bind(),
+        }
+    }
+}
+
+fn default_api_enabled() -> bool {
+    true
+}
+
+fn default_api_bind() -> String {
+    "127.0.0.1:9502".to_string()
+}
+
+/// WebDAV gateway configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    /// Enable the WebDAV gateway
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Bind address for the WebDAV gateway
+    #[serde(default = "default_webdav_bind")]
+    pub bind: String,
+
+    /// Username for digest authentication (no authentication if unset)
+    pub username: Option<String>,
+
+    /// `sha256:<hex>` of SHA-256("username:realm:password")
+    pub password_hash: Option<String>,
+
+    /// Digest authentication realm (part of the password hash)
+    #[serde(default = "default_webdav_realm")]
+    pub realm: String,
+}
+
+impl Default for WebDavConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_webdav_bind(),
+            username: None,
+            password_hash: None,
+            realm: default_webdav_realm(),
+        }
+    }
+}
+
+fn default_webdav_bind() -> String {
+    "0.0.0.0:8008".to_string()
+}
+
+fn default_webdav_realm() -> String {
+    "WolfDisk".to_string()
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -196,21 +547,36 @@ impl Default for Config {
                 role: default_role(),
                 bind: default_bind(),
                 data_dir: default_data_dir(),
+                rack: None,
             },
             cluster: ClusterConfig {
                 peers: Vec::new(),
                 discovery: None,
+                discovery_srv: None,
+                discovery_interval_secs: default_discovery_interval_secs(),
+                enable_auto_failover: false,
+                leader_timeout_secs: default_leader_timeout_secs(),
             },
             replication: ReplicationConfig {
                 mode: default_mode(),
                 factor: default_factor(),
                 chunk_size: default_chunk_size(),
+                max_bandwidth_mbps: 0,
+                burst_mb: default_burst_mb(),
+                rack_aware: false,
             },
             mount: MountConfig {
                 path: default_mount_path(),
                 allow_other: default_allow_other(),
+                readdir_cache_ttl_ms: default_readdir_cache_ttl_ms(),
+                streaming_threshold_mb: default_streaming_threshold_mb(),
+                read_only: false,
             },
             s3: S3Config::default(),
+            api: ApiConfig::default(),
+            webdav: WebDavConfig::default(),
+            storage: StorageConfig::default(),
+            quota: Vec::new(),
         }
     }
 }
@@ -244,4 +610,9 @@ impl Config {
     pub fn wal_dir(&self) -> PathBuf {
         self.node.data_dir.join("wal")
     }
+
+    /// Get the snapshots directory path
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.node.data_dir.join("snapshots")
+    }
 }
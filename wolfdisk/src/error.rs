@@ -47,6 +47,10 @@ pub enum Error {
     /// Invalid operation
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+
+    /// Directory quota exceeded
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
 }
 
 impl Error {
@@ -57,6 +61,7 @@ impl Error {
             Error::FileNotFound(_) => libc::ENOENT,
             Error::ChunkNotFound(_) => libc::EIO,
             Error::InvalidOperation(_) => libc::EINVAL,
+            Error::QuotaExceeded(_) => libc::EDQUOT,
             _ => libc::EIO,
         }
     }
@@ -0,0 +1,191 @@
+//! Directory listing cache for readdir
+//!
+//! Building a listing means scanning the whole file index, which is O(n) in
+//! the number of files in the filesystem. Listings are cached per directory
+//! inode for a short TTL and dropped as soon as a child of that directory is
+//! created, removed or renamed, whether through FUSE or a replicated update.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use fuser::FileType;
+
+use crate::storage::InodeTable;
+
+/// Root inode number
+const ROOT_INODE: u64 = 1;
+
+/// Inode of the directory containing `path`, if it is known
+pub(crate) fn parent_inode(inode_table: &InodeTable, path: &Path) -> Option<u64> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => {
+            inode_table.get_inode(&parent.to_path_buf())
+        }
+        _ => Some(ROOT_INODE),
+    }
+}
+
+/// A directory listing as handed to `ReplyDirectory`: (inode, type, name)
+pub type DirListing = Vec<(u64, FileType, String)>;
+
+/// A cached listing and the time it was built
+type CachedListing = (Instant, Arc<DirListing>);
+
+/// TTL cache of directory listings keyed by (inode, generation)
+///
+/// The generation is bumped by `invalidate_all`, so a listing built before a
+/// wholesale index change can never be served afterwards, even if it is
+/// inserted late.
+pub struct DirCache {
+    /// How long a listing stays valid (zero disables the cache)
+    ttl: Duration,
+
+    /// Current cache generation
+    generation: AtomicU64,
+
+    /// Cached listings by (inode, generation)
+    entries: Mutex<HashMap<(u64, u64), CachedListing>>,
+
+    /// wolfdisk_readdir_cache_hits_total
+    hits: AtomicU64,
+
+    /// wolfdisk_readdir_cache_misses_total
+    misses: AtomicU64,
+}
+
+impl DirCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            generation: AtomicU64::new(0),
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Current generation, to be passed to `insert` for a listing built now
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Look up a fresh listing for a directory inode
+    pub fn get(&self, ino: u64) -> Option<Arc<DirListing>> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+
+        let key = (ino, self.generation());
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some((built, listing)) if built.elapsed() < self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(listing.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Cache a listing built during `generation`
+    pub fn insert(&self, ino: u64, generation: u64, listing: Arc<DirListing>) {
+        if self.ttl.is_zero() || generation != self.generation() {
+            return;
+        }
+        self.entries.lock().unwrap().insert((ino, generation), (Instant::now(), listing));
+    }
+
+    /// Drop the cached listing of a directory
+    pub fn invalidate(&self, ino: u64) {
+        let key = (ino, self.generation());
+        self.entries.lock().unwrap().remove(&key);
+    }
+
+    /// Drop the cached listing of the directory containing `path`
+    pub fn invalidate_parent(&self, inode_table: &InodeTable, path: &Path) {
+        if let Some(ino) = parent_inode(inode_table, path) {
+            self.invalidate(ino);
+        }
+    }
+
+    /// Drop every cached listing (e.g. after a full index resync)
+    pub fn invalidate_all(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+
+    /// Total cache hits since startup
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Total cache misses since startup
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn listing(names: &[&str]) -> Arc<DirListing> {
+        Arc::new(names.iter().enumerate()
+            .map(|(i, n)| (i as u64 + 10, FileType::RegularFile, n.to_string()))
+            .collect())
+    }
+
+    #[test]
+    fn test_hit_miss_and_expiry() {
+        let cache = DirCache::new(Duration::from_millis(50));
+        assert!(cache.get(1).is_none());
+
+        cache.insert(1, cache.generation(), listing(&["a", "b"]));
+        assert_eq!(cache.get(1).unwrap().len(), 2);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(cache.get(1).is_none());
+        assert_eq!(cache.misses(), 2);
+    }
+
+    #[test]
+    fn test_invalidate_parent() {
+        let cache = DirCache::new(Duration::from_secs(60));
+        let mut inodes = InodeTable::new();
+        inodes.insert(2, PathBuf::from("docs"));
+
+        cache.insert(ROOT_INODE, cache.generation(), listing(&["docs"]));
+        cache.insert(2, cache.generation(), listing(&["x"]));
+
+        cache.invalidate_parent(&inodes, Path::new("docs/y"));
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(ROOT_INODE).is_some());
+
+        cache.invalidate_parent(&inodes, Path::new("top-level"));
+        assert!(cache.get(ROOT_INODE).is_none());
+    }
+
+    #[test]
+    fn test_stale_generation_not_cached() {
+        let cache = DirCache::new(Duration::from_secs(60));
+        let generation = cache.generation();
+        cache.invalidate_all();
+
+        // A listing built before the resync must not be served after it
+        cache.insert(1, generation, listing(&["old"]));
+        assert!(cache.get(1).is_none());
+    }
+}
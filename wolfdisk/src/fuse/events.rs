@@ -0,0 +1,110 @@
+//! Filesystem change events
+//!
+//! Changes are published on an `EventBus` as `FsEvent`s, which the admin API
+//! streams from `GET /events`. The leader (or a standalone node) publishes
+//! the changes it makes, from the mount or on behalf of other nodes; the
+//! other nodes publish the changes they receive as `IndexUpdate` and
+//! `FileSync` messages, so every node sees each change once.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Events kept for each subscriber before the oldest are dropped
+pub const EVENT_BUFFER: usize = 1000;
+
+/// Kind of change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FsOp {
+    Create,
+    Modify,
+    Delete,
+    Rename,
+}
+
+impl std::fmt::Display for FsOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FsOp::Create => "CREATE",
+            FsOp::Modify => "MODIFY",
+            FsOp::Delete => "DELETE",
+            FsOp::Rename => "RENAME",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A change to one path. Paths are absolute within the filesystem
+/// (`/foo/bar`); a rename has the old path in `path` and the new one in `to`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FsEvent {
+    pub op: FsOp,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+impl FsEvent {
+    pub fn new(op: FsOp, path: &Path) -> Self {
+        Self { op, path: event_path(path), to: None }
+    }
+
+    pub fn rename(from: &Path, to: &Path) -> Self {
+        Self { op: FsOp::Rename, path: event_path(from), to: Some(event_path(to)) }
+    }
+
+    /// Whether the event touches `prefix` or anything below it
+    pub fn matches(&self, prefix: &str) -> bool {
+        let prefix = prefix.trim_end_matches('/');
+        let under = |path: &str| {
+            prefix.is_empty()
+                || path == prefix
+                || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+        };
+        under(&self.path) || self.to.as_deref().is_some_and(under)
+    }
+}
+
+impl std::fmt::Display for FsEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.to {
+            Some(to) => write!(f, "{} {} -> {}", self.op, self.path, to),
+            None => write!(f, "{} {}", self.op, self.path),
+        }
+    }
+}
+
+/// Index paths are relative to the filesystem root
+fn event_path(path: &Path) -> String {
+    format!("/{}", path.to_string_lossy().trim_start_matches('/'))
+}
+
+/// Broadcasts change events to every subscriber
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<FsEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUFFER);
+        Self { tx }
+    }
+
+    /// Publish an event (dropped if nobody is subscribed)
+    pub fn publish(&self, event: FsEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<FsEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -5,7 +5,8 @@
 
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
-use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant, SystemTime};
 
 use fuser::{
@@ -16,10 +17,14 @@ use tracing::{debug, info, warn};
 
 use crate::cluster::ClusterManager;
 use crate::config::Config;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::network::peer::PeerManager;
-use crate::network::protocol::{Message, CreateFileMsg, CreateDirMsg, DeleteFileMsg, DeleteDirMsg, IndexUpdateMsg, IndexOperation, ChunkRefMsg, FileSyncMsg, WriteRequestMsg, RenameFileMsg, CreateSymlinkMsg, ReadRequestMsg, SetAttrMsg};
-use crate::storage::{ChunkStore, FileIndex, FileEntry, InodeTable};
+use crate::network::protocol::{Message, CreateFileMsg, CreateDirMsg, DeleteFileMsg, DeleteDirMsg, IndexUpdateMsg, IndexOperation, ChunkRefMsg, FileSyncMsg, WriteRequestMsg, RenameFileMsg, CreateSymlinkMsg, CreateHardLinkMsg, ReadRequestMsg, SetAttrMsg, SetXattrMsg, RemoveXattrMsg, FallocateMsg};
+use crate::storage::acl::{self, Acl, Caller};
+use crate::storage::{fallocate_supported, ChunkStore, FileIndex, FileEntry, InodeTable, QuotaManager, ReadCache};
+
+use super::dir_cache::{DirCache, DirListing};
+use super::events::{EventBus, FsEvent, FsOp};
 
 /// Messages for the async replication queue
 enum ReplicationMsg {
@@ -36,12 +41,56 @@ const ROOT_INODE: u64 = 1;
 /// Minimum interval between index saves (debounce)
 const INDEX_SAVE_INTERVAL: Duration = Duration::from_secs(5);
 
+/// errno for an operation the leader rejected: EDQUOT for quota errors,
+/// EINVAL for invalid arguments, EIO otherwise
+fn leader_errno(error: Option<&str>) -> i32 {
+    match error {
+        Some(e) if e.starts_with("Quota exceeded") => libc::EDQUOT,
+        Some(e) if e.starts_with("Invalid operation") => libc::EINVAL,
+        _ => libc::EIO,
+    }
+}
+
+/// The uid and groups of the process behind a request. FUSE only passes
+/// the primary group, so the supplementary ones are read from /proc.
+fn caller(req: &Request) -> Caller {
+    let mut groups = vec![req.gid()];
+    if let Ok(status) = std::fs::read_to_string(format!("/proc/{}/status", req.pid())) {
+        if let Some(line) = status.lines().find_map(|line| line.strip_prefix("Groups:")) {
+            groups.extend(line.split_whitespace().filter_map(|gid| gid.parse::<u32>().ok()));
+        }
+    }
+    Caller { uid: req.uid(), groups }
+}
+
+/// Whether `caller` may access `entry` for `mask` (`R_OK`, `W_OK`, `X_OK`),
+/// going by its access ACL or, without one, its permission bits
+fn may_access(entry: &FileEntry, caller: &Caller, mask: i32) -> bool {
+    let want = (mask & (libc::R_OK | libc::W_OK | libc::X_OK)) as u8;
+    if want == 0 {
+        return true;
+    }
+    // root may do anything except execute a file nobody can execute
+    if caller.uid == 0 {
+        return mask & libc::X_OK == 0 || entry.is_dir || entry.permissions & 0o111 != 0;
+    }
+    let acl = entry.access_acl().unwrap_or_else(|| Acl::from_mode(entry.permissions));
+    acl.permits(caller, entry.uid, entry.gid, want)
+}
+
+/// A chunk stored from a write buffer, queued for streaming replication:
+/// (hash, data, file offset, size)
+type FlushedChunk = ([u8; 32], Vec<u8>, u64, u32);
+
 /// Per-inode write buffer for coalescing small FUSE writes into full chunks
 struct WriteBuffer {
     /// Accumulated data not yet stored as chunks
     data: Vec<u8>,
     /// File offset where this buffer starts
     base_offset: u64,
+    /// Bytes written contiguously through this buffer, stored chunks
+    /// included; decides when writes switch to the streaming path
+    run_bytes: u64,
 }
 
 /// Client write-back cache entry.
@@ -90,6 +139,10 @@ pub struct WolfDiskFS {
     /// Per-inode write buffers for coalescing small writes
     write_buffers: RwLock<HashMap<u64, WriteBuffer>>,
 
+    /// Per-inode locks held for a whole write or buffer flush, so concurrent
+    /// writers to one file can't interleave their buffer updates
+    inode_locks: RwLock<HashMap<u64, Arc<Mutex<()>>>>,
+
     /// Inodes that have been written to and need replication on release
     dirty_inodes: RwLock<HashSet<u64>>,
 
@@ -107,6 +160,20 @@ pub struct WolfDiskFS {
     /// Writes are buffered here and only forwarded to the leader on flush/release.
     /// This prevents FUSE from blocking on every write(), keeping Dolphin responsive.
     client_write_cache: RwLock<HashMap<u64, ClientWriteEntry>>,
+
+    /// Cached readdir listings (shared with the replication message handler)
+    dir_cache: Arc<DirCache>,
+
+    /// Per-directory quotas (enforced by the leader only)
+    quotas: Option<Arc<QuotaManager>>,
+
+    /// Change events for `GET /events`
+    events: Option<EventBus>,
+
+    /// `mount.read_only`: every change made through the mount fails with
+    /// EROFS. Replicated changes are applied by the message handler, not
+    /// here, so they still arrive. Cleared by `POST /mount/read-write`.
+    read_only: Arc<AtomicBool>,
 }
 
 impl WolfDiskFS {
@@ -116,7 +183,13 @@ impl WolfDiskFS {
         std::fs::create_dir_all(config.chunks_dir())?;
         std::fs::create_dir_all(config.index_dir())?;
         
-        let chunk_store = Arc::new(ChunkStore::new(config.chunks_dir(), config.replication.chunk_size)?);
+        let chunk_store = Arc::new(
+            ChunkStore::new(config.chunks_dir(), config.replication.chunk_size)?
+                .with_snapshot_dir(config.snapshots_dir())
+                .with_write_batch_size(config.storage.write_batch_size)
+                .with_mmap_cache(config.storage.mmap_cache_mb)
+                .with_read_cache(Arc::new(RwLock::new(ReadCache::with_capacity_mb(config.storage.read_cache_mb))))
+        );
         let file_index = Arc::new(RwLock::new(FileIndex::load_or_create(&config.index_dir())?));
         
         // Build inode table from index
@@ -126,8 +199,15 @@ impl WolfDiskFS {
         };
         let inode_table = Arc::new(RwLock::new(inode_table));
         let next_inode = Arc::new(RwLock::new(max_inode + 1));
+        let dir_cache = Arc::new(DirCache::new(Duration::from_millis(config.mount.readdir_cache_ttl_ms)));
+        let quotas = if config.quota.is_empty() {
+            None
+        } else {
+            Some(Arc::new(QuotaManager::load(&config.quota, &config.index_dir())?))
+        };
         
-        Self::with_cluster(config, None, None, file_index, chunk_store, inode_table, next_inode)
+        Ok(Self::with_cluster(config, None, None, file_index, chunk_store, inode_table, next_inode, dir_cache)?
+            .with_quotas(quotas))
     }
 
     /// Create a new WolfDisk filesystem with cluster support
@@ -139,6 +219,7 @@ impl WolfDiskFS {
         chunk_store: Arc<ChunkStore>,
         inode_table: Arc<RwLock<InodeTable>>,
         next_inode: Arc<RwLock<u64>>,
+        dir_cache: Arc<DirCache>,
     ) -> Result<Self> {
         info!("Initializing WolfDisk filesystem");
 
@@ -198,6 +279,7 @@ impl WolfDiskFS {
         };
 
         Ok(Self {
+            read_only: Arc::new(AtomicBool::new(config.mount.read_only)),
             config,
             chunk_store,
             file_index,
@@ -208,14 +290,50 @@ impl WolfDiskFS {
             cluster,
             peer_manager,
             write_buffers: RwLock::new(HashMap::new()),
+            inode_locks: RwLock::new(HashMap::new()),
             dirty_inodes: RwLock::new(HashSet::new()),
             last_index_save: RwLock::new(Instant::now()),
             index_dirty: RwLock::new(false),
             replication_tx,
             client_write_cache: RwLock::new(HashMap::new()),
+            dir_cache,
+            quotas: None,
+            events: None,
         })
     }
 
+    /// Enforce per-directory quotas (shared with the message handler and S3 server)
+    pub fn with_quotas(mut self, quotas: Option<Arc<QuotaManager>>) -> Self {
+        self.quotas = quotas;
+        self
+    }
+
+    /// Publish change events (shared with the message handler and admin API)
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Share the `mount.read_only` switch with the admin API, which can clear it
+    pub fn with_read_only(mut self, read_only: Arc<AtomicBool>) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    /// Publish a change made through the mount. Only the leader publishes
+    /// here; other nodes see the change when it is replicated to them.
+    fn publish(&self, event: FsEvent) {
+        if let Some(events) = &self.events {
+            if self.is_leader() {
+                events.publish(event);
+            }
+        }
+    }
+
     /// Check if this node is the leader (or standalone)
     fn is_leader(&self) -> bool {
         match &self.cluster {
@@ -224,6 +342,19 @@ impl WolfDiskFS {
         }
     }
 
+    /// Quotas to enforce, if any are configured and this node is the leader.
+    /// Must be called before taking the file index lock.
+    fn leader_quotas(&self) -> Option<&QuotaManager> {
+        let quotas = self.quotas.as_deref()?;
+        if !self.is_leader() {
+            // Changes replicated from the leader are not tracked here
+            quotas.mark_stale();
+            return None;
+        }
+        quotas.rebuild_if_stale(&self.file_index.read().unwrap());
+        Some(quotas)
+    }
+
     /// Check if this node is a client (no local data, proxy to leader)
     fn is_client(&self) -> bool {
         match &self.cluster {
@@ -232,42 +363,36 @@ impl WolfDiskFS {
         }
     }
 
-    /// Send a request to the leader with automatic reconnection on failure.
-    /// If the first attempt fails (e.g. stale connection after leader restart),
-    /// drops the cached connection, reconnects, and retries once.
+    /// Send a request to the leader, reconnecting once if the connection is stale
     fn request_leader(&self, msg: &Message) -> std::result::Result<Message, i32> {
-        let (cluster, peer_manager) = match (&self.cluster, &self.peer_manager) {
-            (Some(c), Some(p)) => (c, p),
-            _ => return Err(libc::EIO),
-        };
-
-        let leader_id = cluster.leader_id().ok_or(libc::ENOENT)?;
-        let leader_addr = cluster.leader_address().ok_or(libc::ENOENT)?;
-
-        // First attempt
-        let conn = peer_manager.get_or_connect_leader(&leader_id, &leader_addr)
-            .map_err(|_| libc::EIO)?;
-        
-        match conn.request(msg) {
-            Ok(response) => return Ok(response),
-            Err(e) => {
-                tracing::warn!("Leader request failed (will reconnect): {}", e);
-                peer_manager.disconnect_leader(&leader_id);
-            }
+        match (&self.cluster, &self.peer_manager) {
+            (Some(cluster), Some(peer_manager)) => peer_manager.request_leader(cluster, msg),
+            _ => Err(libc::EIO),
         }
+    }
 
-        // Retry with fresh connection
-        let conn = peer_manager.get_or_connect_leader(&leader_id, &leader_addr)
-            .map_err(|e| {
-                tracing::error!("Failed to reconnect to leader: {}", e);
-                libc::EIO
-            })?;
-        
-        conn.request(msg).map_err(|e| {
-            tracing::error!("Leader request failed after reconnect: {}", e);
-            peer_manager.disconnect_leader(&leader_id);
-            libc::EIO
-        })
+    /// Read a file on a client from its chunks: cached ones from memory, the
+    /// rest fetched from the leader. `None` (forward the read instead) if the
+    /// file isn't in the index or a chunk can't be fetched.
+    fn read_through_cache(&self, path: &std::path::Path, offset: u64, size: u32) -> Option<Vec<u8>> {
+        let (chunks, file_size) = {
+            let index = self.file_index.read().unwrap();
+            let entry = index.get(path)?;
+            (entry.chunks.clone(), entry.size)
+        };
+        let fetch = |hash: &[u8; 32]| {
+            let msg = Message::GetChunk(crate::network::protocol::GetChunkMsg { hash: *hash });
+            match self.request_leader(&msg) {
+                Ok(Message::ChunkData(resp)) => resp.data
+                    .ok_or_else(|| Error::ChunkNotFound(hex::encode(hash))),
+                _ => Err(Error::Network(format!("Failed to fetch chunk {}", hex::encode(hash)))),
+            }
+        };
+        let mut data = self.chunk_store.read_through(&chunks, offset, size as usize, fetch)
+            .map_err(|e| debug!("Client read through cache failed, forwarding: {}", e))
+            .ok()?;
+        ChunkStore::fill_to_size(&mut data, file_size, offset, size as usize);
+        Some(data)
     }
 
     /// Forward a read to the leader (for client mode)
@@ -303,7 +428,7 @@ impl WolfDiskFS {
             Message::FileOpResponse(resp) if resp.success => Ok(()),
             Message::FileOpResponse(resp) => {
                 warn!("Leader rejected create: {:?}", resp.error);
-                Err(libc::EIO)
+                Err(leader_errno(resp.error.as_deref()))
             }
             _ => Err(libc::EIO),
         }
@@ -326,7 +451,7 @@ impl WolfDiskFS {
             }
             Message::ClientResponse(resp) => {
                 warn!("Leader rejected write: {:?}", resp.error);
-                Err(libc::EIO)
+                Err(leader_errno(resp.error.as_deref()))
             }
             _ => Err(libc::EIO),
         }
@@ -372,7 +497,26 @@ impl WolfDiskFS {
             Message::FileOpResponse(resp) if resp.success => Ok(()),
             Message::FileOpResponse(resp) => {
                 warn!("Leader rejected setattr: {:?}", resp.error);
-                Err(libc::EIO)
+                Err(leader_errno(resp.error.as_deref()))
+            }
+            _ => Err(libc::EIO),
+        }
+    }
+
+    /// Forward a fallocate to the leader
+    fn forward_fallocate_to_leader(&self, path: &str, mode: i32, offset: u64, length: u64) -> std::result::Result<(), i32> {
+        let msg = Message::Fallocate(FallocateMsg {
+            path: path.to_string(),
+            mode,
+            offset,
+            length,
+        });
+
+        match self.request_leader(&msg)? {
+            Message::FileOpResponse(resp) if resp.success => Ok(()),
+            Message::FileOpResponse(resp) => {
+                warn!("Leader rejected fallocate: {:?}", resp.error);
+                Err(leader_errno(resp.error.as_deref()))
             }
             _ => Err(libc::EIO),
         }
@@ -390,6 +534,99 @@ impl WolfDiskFS {
         }
     }
 
+    /// Forward an extended attribute change to the leader.
+    /// `value` of `None` removes the attribute.
+    fn forward_xattr_to_leader(&self, path: &str, name: &str, value: Option<&[u8]>, flags: i32) -> std::result::Result<(), i32> {
+        let msg = match value {
+            Some(value) => Message::SetXattr(SetXattrMsg {
+                path: path.to_string(),
+                name: name.to_string(),
+                value: value.to_vec(),
+                flags,
+            }),
+            None => Message::RemoveXattr(RemoveXattrMsg {
+                path: path.to_string(),
+                name: name.to_string(),
+            }),
+        };
+
+        match self.request_leader(&msg)? {
+            Message::FileOpResponse(resp) if resp.success => Ok(()),
+            Message::FileOpResponse(resp) => {
+                warn!("Leader rejected xattr change: {:?}", resp.error);
+                Err(libc::EIO)
+            }
+            _ => Err(libc::EIO),
+        }
+    }
+
+    /// Set or remove an extended attribute on a path.
+    /// The leader applies the change and broadcasts it; other nodes validate
+    /// against their local index, forward to the leader, then update locally.
+    fn change_xattr(&self, path: &std::path::Path, name: &str, value: Option<&[u8]>, flags: i32) -> std::result::Result<(), i32> {
+        if !self.is_leader() {
+            {
+                let file_index = self.file_index.read().unwrap();
+                let entry = file_index.get(path).ok_or(libc::ENOENT)?;
+                match value {
+                    Some(_) => entry.check_xattr_flags(name, flags)?,
+                    None if !entry.xattrs.contains_key(name) => return Err(libc::ENODATA),
+                    None => {}
+                }
+            }
+
+            self.forward_xattr_to_leader(&path.to_string_lossy(), name, value, flags)?;
+
+            let mut file_index = self.file_index.write().unwrap();
+            if let Some(mut entry) = file_index.get_mut(path) {
+                let _ = entry.update_xattr(name, value.map(|v| v.to_vec()), 0);
+            }
+            return Ok(());
+        }
+
+        {
+            let mut file_index = self.file_index.write().unwrap();
+            let mut entry = file_index.get_mut(path).ok_or(libc::ENOENT)?;
+            entry.update_xattr(name, value.map(|v| v.to_vec()), flags)?;
+        }
+        *self.index_dirty.write().unwrap() = true;
+
+        self.broadcast_index_update(IndexOperation::SetXattr {
+            path: path.to_string_lossy().to_string(),
+            name: name.to_string(),
+            value: value.map(|v| v.to_vec()),
+        });
+        self.maybe_save_index();
+        Ok(())
+    }
+
+    /// Set or remove an ACL through `system.posix_acl_access` or
+    /// `system.posix_acl_default`. It is stored as its `user.acl*` xattr, so
+    /// it reaches the leader and the other nodes like any xattr change.
+    fn change_acl(&self, req: &Request, path: &std::path::Path, name: &str, value: Option<&[u8]>) -> std::result::Result<(), i32> {
+        let stored = acl::stored_name(name).ok_or(libc::EINVAL)?;
+        {
+            let file_index = self.file_index.read().unwrap();
+            let entry = file_index.get(path).ok_or(libc::ENOENT)?;
+            if req.uid() != 0 && req.uid() != entry.uid {
+                return Err(libc::EPERM);
+            }
+            if stored == acl::STORED_DEFAULT_ACL && !entry.is_dir {
+                return Err(if value.is_some() { libc::EACCES } else { libc::ENODATA });
+            }
+        }
+
+        let acl = match value {
+            Some(value) => Acl::from_xattr(value)?,
+            None => None,
+        };
+        match self.change_xattr(path, stored, acl.map(|acl| acl.encode()).as_deref(), 0) {
+            // Removing an ACL that isn't there is not an error
+            Err(libc::ENODATA) => Ok(()),
+            result => result,
+        }
+    }
+
     /// Broadcast an index update to all followers (leader only)
     fn broadcast_index_update(&self, operation: IndexOperation) {
         if !self.is_leader() {
@@ -402,6 +639,8 @@ impl WolfDiskFS {
                 IndexOperation::Delete { path } => std::path::PathBuf::from(path),
                 IndexOperation::Mkdir { path, .. } => std::path::PathBuf::from(path),
                 IndexOperation::Rename { to_path, .. } => std::path::PathBuf::from(to_path),
+                IndexOperation::SetXattr { path, .. } => std::path::PathBuf::from(path),
+                IndexOperation::Link { dst_path, .. } => std::path::PathBuf::from(dst_path),
             };
             let is_delete = matches!(&operation, IndexOperation::Delete { .. });
             let version = if is_delete {
@@ -468,6 +707,7 @@ impl WolfDiskFS {
 
         match self.request_leader(&msg)? {
             Message::FileOpResponse(resp) if resp.success => Ok(()),
+            Message::FileOpResponse(resp) => Err(leader_errno(resp.error.as_deref())),
             _ => Err(libc::EIO),
         }
     }
@@ -481,6 +721,21 @@ impl WolfDiskFS {
 
         match self.request_leader(&msg)? {
             Message::FileOpResponse(resp) if resp.success => Ok(()),
+            Message::FileOpResponse(resp) => Err(leader_errno(resp.error.as_deref())),
+            _ => Err(libc::EIO),
+        }
+    }
+
+    /// Forward a hard link creation to the leader
+    fn forward_link_to_leader(&self, src_path: &str, dst_path: &str) -> std::result::Result<(), i32> {
+        let msg = Message::CreateHardLink(CreateHardLinkMsg {
+            src_path: src_path.to_string(),
+            dst_path: dst_path.to_string(),
+        });
+
+        match self.request_leader(&msg)? {
+            Message::FileOpResponse(resp) if resp.success => Ok(()),
+            Message::FileOpResponse(resp) => Err(leader_errno(resp.error.as_deref())),
             _ => Err(libc::EIO),
         }
     }
@@ -501,8 +756,17 @@ impl WolfDiskFS {
         fh
     }
 
+    /// The lock serializing writes and buffer flushes for an inode
+    fn inode_lock(&self, ino: u64) -> Arc<Mutex<()>> {
+        if let Some(lock) = self.inode_locks.read().unwrap().get(&ino) {
+            return lock.clone();
+        }
+        self.inode_locks.write().unwrap().entry(ino).or_default().clone()
+    }
+
     /// Flush the write buffer for a given inode, storing accumulated data as chunks.
     /// Also streams any flushed chunks to followers for streaming replication.
+    /// Callers outside `write` hold the inode's lock.
     fn flush_write_buffer(&self, ino: u64) {
         let buffer = {
             let mut buffers = self.write_buffers.write().unwrap();
@@ -523,55 +787,283 @@ impl WolfDiskFS {
                 }
             };
 
-            // Collect chunks for streaming replication
-            let mut flushed_chunks: Vec<([u8; 32], Vec<u8>, u64, u32)> = Vec::new();
+            let flushed_chunks = self.store_buffered(&path, buffer.base_offset, &buffer.data);
 
-            // Store the buffered data as chunks
-            let mut file_index = self.file_index.write().unwrap();
-            if let Some(entry) = file_index.get_mut(&path) {
-                let mut offset = buffer.base_offset;
-                let chunk_size = self.config.replication.chunk_size;
-                let mut pos = 0;
-
-                while pos < buffer.data.len() {
-                    let end = (pos + chunk_size).min(buffer.data.len());
-                    let chunk_data = &buffer.data[pos..end];
-
-                    match self.chunk_store.store(chunk_data) {
-                        Ok(hash) => {
-                            let chunk_len = chunk_data.len() as u32;
+            // Stream final chunks to followers (the tail end of the file)
+            for (hash, chunk_data, offset, size) in &flushed_chunks {
+                self.stream_chunk_to_followers(&path, hash, chunk_data, *offset, *size);
+            }
+
+            *self.index_dirty.write().unwrap() = true;
+        }
+    }
+
+    /// Store buffered data starting at `base_offset` as chunks of the file at
+    /// `path`, the last one partial if need be. Returns the stored chunks for
+    /// streaming replication.
+    fn store_buffered(&self, path: &std::path::Path, base_offset: u64, data: &[u8]) -> Vec<FlushedChunk> {
+        let mut flushed_chunks = Vec::new();
+        let mut file_index = self.file_index.write().unwrap();
+        let Some(mut entry) = file_index.get_mut(path) else {
+            return flushed_chunks;
+        };
+
+        let chunks: Vec<&[u8]> = data.chunks(self.config.replication.chunk_size).collect();
+        let results = self.chunk_store.store_many(&chunks);
+        let mut offset = base_offset;
+        for (chunk_data, result) in chunks.into_iter().zip(results) {
+            match result {
+                Ok(hash) => {
+                    let chunk_len = chunk_data.len() as u32;
+                    entry.chunks.push(crate::storage::ChunkRef {
+                        hash,
+                        offset,
+                        size: chunk_len,
+                        tier: crate::storage::Tier::Hot,
+                    });
+                    flushed_chunks.push((hash, chunk_data.to_vec(), offset, chunk_len));
+                }
+                Err(e) => {
+                    warn!("Failed to flush write buffer chunk: {}", e);
+                    break;
+                }
+            }
+            offset += chunk_data.len() as u64;
+        }
+        // A buffer started after a seek can land before chunks already stored
+        entry.chunks.sort_by_key(|c| c.offset);
+
+        if offset > entry.size {
+            entry.size = offset;
+        }
+        entry.modified = SystemTime::now();
+        flushed_chunks
+    }
+
+    /// Store a write's chunks straight from `data`, without copying it into
+    /// the write buffer, and queue each chunk for streaming replication as
+    /// soon as it is stored. Buffered bytes just before `offset` complete the
+    /// first chunk and a partial last chunk stays buffered, so chunks stay
+    /// full size. Memory use stays at a batch of chunks however large the
+    /// file.
+    fn streaming_write(&self, ino: u64, path: &std::path::Path, offset: u64, data: &[u8]) -> Result<()> {
+        let chunk_size = self.config.replication.chunk_size;
+        let batch_size = self.config.storage.write_batch_size.max(1);
+
+        let pending = self.write_buffers.write().unwrap().remove(&ino);
+        let (mut chunk_offset, mut head, run_bytes) = match pending {
+            Some(buffer) if buffer.base_offset + buffer.data.len() as u64 == offset => {
+                (buffer.base_offset, buffer.data, buffer.run_bytes)
+            }
+            pending => {
+                // Not contiguous: buffered data for this inode goes first, so
+                // chunks keep write order
+                if let Some(buffer) = pending {
+                    self.write_buffers.write().unwrap().insert(ino, buffer);
+                    self.flush_write_buffer(ino);
+                }
+                (offset, Vec::new(), 0)
+            }
+        };
+        let run_bytes = run_bytes + data.len() as u64;
+
+        let mut rest = data;
+        if !head.is_empty() {
+            let take = (chunk_size - head.len()).min(rest.len());
+            head.extend_from_slice(&rest[..take]);
+            rest = &rest[take..];
+        }
+        // A head that didn't fill a chunk used up `data`, and stays buffered
+        let head_full = head.len() == chunk_size;
+        let full = rest.len() / chunk_size * chunk_size;
+        let mut tail = if head_full { Vec::new() } else { std::mem::take(&mut head) };
+        tail.extend_from_slice(&rest[full..]);
+        let mut chunks: Vec<&[u8]> = Vec::new();
+        if head_full {
+            chunks.push(&head);
+        }
+        chunks.extend(rest[..full].chunks(chunk_size));
+
+        for batch in chunks.chunks(batch_size) {
+            let results = self.chunk_store.store_many(batch);
+            let mut stored = Vec::with_capacity(batch.len());
+            for (chunk_data, result) in batch.iter().zip(results) {
+                stored.push((result?, *chunk_data, chunk_offset));
+                chunk_offset += chunk_data.len() as u64;
+            }
+
+            {
+                let mut file_index = self.file_index.write().unwrap();
+                let mut entry = file_index.get_mut(path).ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+                for (hash, chunk_data, at) in &stored {
+                    entry.chunks.push(crate::storage::ChunkRef {
+                        hash: *hash,
+                        offset: *at,
+                        size: chunk_data.len() as u32,
+                        tier: crate::storage::Tier::Hot,
+                    });
+                }
+                entry.chunks.sort_by_key(|c| c.offset);
+                entry.size = entry.size.max(chunk_offset);
+                entry.modified = SystemTime::now();
+            }
+
+            for (hash, chunk_data, at) in &stored {
+                self.stream_chunk_to_followers(path, hash, chunk_data, *at, chunk_data.len() as u32);
+            }
+        }
+
+        // The partial last chunk waits for the next write, or the flush
+        let buffered_end = chunk_offset + tail.len() as u64;
+        self.write_buffers.write().unwrap().insert(ino, WriteBuffer {
+            data: tail,
+            base_offset: chunk_offset,
+            run_bytes,
+        });
+        if let Some(mut entry) = self.file_index.write().unwrap().get_mut(path) {
+            entry.size = entry.size.max(buffered_end);
+            entry.modified = SystemTime::now();
+        }
+
+        self.chunk_store.stats().streaming_writes.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        let entry = self.file_index.read().unwrap().get(path).cloned();
+        if let Some(entry) = entry {
+            self.broadcast_file_sync_final(path, &entry);
+        }
+        Ok(())
+    }
+
+    /// Store a write on the leader: through the write buffer, or chunk by
+    /// chunk once the file is being written in a long contiguous run
+    fn leader_write(&self, ino: u64, path: &std::path::Path, offset: u64, data: &[u8]) -> Result<()> {
+        // Once a contiguous run of writes reaches the streaming threshold,
+        // its chunks are stored straight from each write. FUSE hands over at
+        // most a megabyte per call, so it is the run that counts.
+        let streaming_threshold = self.config.mount.streaming_threshold_mb * 1024 * 1024;
+        if streaming_threshold > 0 && self.write_run(ino, offset, data.len()) >= streaming_threshold {
+            self.streaming_write(ino, path, offset, data)?;
+            self.mark_dirty(ino);
+            return Ok(());
+        }
+
+        // We're the leader - buffer the write for coalescing
+        let chunk_size = self.config.replication.chunk_size;
+
+        // Collect chunks that were flushed during this write for streaming replication
+        let mut flushed_chunks: Vec<FlushedChunk> = Vec::new();
+
+        {
+            let mut buffers = self.write_buffers.write().unwrap();
+            let buffer = buffers.entry(ino).or_insert_with(|| WriteBuffer {
+                data: Vec::new(),
+                base_offset: offset,
+                run_bytes: 0,
+            });
+
+            let buffer_end = buffer.base_offset + buffer.data.len() as u64;
+            if offset >= buffer.base_offset && offset <= buffer_end {
+                // Appends to or overlaps the buffered data: write it in place,
+                // so the later write wins where they overlap
+                let at = (offset - buffer.base_offset) as usize;
+                let overlap = (buffer.data.len() - at).min(data.len());
+                buffer.data[at..at + overlap].copy_from_slice(&data[..overlap]);
+                buffer.data.extend_from_slice(&data[overlap..]);
+                buffer.run_bytes += (offset + data.len() as u64).saturating_sub(buffer_end);
+            } else {
+                // A gap after the buffer, or a write before it: store what is
+                // buffered, partial chunks included, and start again here
+                if !buffer.data.is_empty() {
+                    let old_data = std::mem::take(&mut buffer.data);
+                    flushed_chunks.extend(self.store_buffered(path, buffer.base_offset, &old_data));
+                }
+                buffer.data = data.to_vec();
+                buffer.base_offset = offset;
+                buffer.run_bytes = data.len() as u64;
+            }
+
+            // Flush complete chunks from buffer while it exceeds chunk_size,
+            // storing them together so they share io_uring submissions
+            let mut full_chunks = Vec::new();
+            while buffer.data.len() >= chunk_size {
+                let chunk_data: Vec<u8> = buffer.data.drain(..chunk_size).collect();
+                full_chunks.push((buffer.base_offset, chunk_data));
+                buffer.base_offset += chunk_size as u64;
+            }
+            let chunk_slices: Vec<&[u8]> = full_chunks.iter().map(|(_, data)| data.as_slice()).collect();
+            let results = self.chunk_store.store_many(&chunk_slices);
+
+            for ((flush_offset, chunk_data), result) in full_chunks.into_iter().zip(results) {
+                match result {
+                    Ok(hash) => {
+                        let mut file_index = self.file_index.write().unwrap();
+                        if let Some(mut entry) = file_index.get_mut(path) {
                             entry.chunks.push(crate::storage::ChunkRef {
                                 hash,
-                                offset,
-                                size: chunk_len,
+                                offset: flush_offset,
+                                size: chunk_size as u32,
+                                tier: crate::storage::Tier::Hot,
                             });
-                            // Queue for streaming replication
-                            flushed_chunks.push((hash, chunk_data.to_vec(), offset, chunk_len));
-                        }
-                        Err(e) => {
-                            warn!("Failed to flush write buffer chunk: {}", e);
-                            return;
+                            entry.chunks.sort_by_key(|c| c.offset);
+                            let new_end = flush_offset + chunk_size as u64;
+                            if new_end > entry.size {
+                                entry.size = new_end;
+                            }
+                            entry.modified = SystemTime::now();
                         }
+                        // Queue for streaming replication
+                        flushed_chunks.push((hash, chunk_data, flush_offset, chunk_size as u32));
+                    }
+                    Err(e) => {
+                        warn!("Failed to flush full chunk from buffer: {}", e);
                     }
-
-                    offset += chunk_data.len() as u64;
-                    pos = end;
                 }
+            }
+        }
 
-                let new_end = buffer.base_offset + buffer.data.len() as u64;
-                if new_end > entry.size {
-                    entry.size = new_end;
-                }
-                entry.modified = SystemTime::now();
+        // Stream flushed chunks to followers immediately (streaming replication)
+        for (hash, chunk_data, offset, size) in &flushed_chunks {
+            self.stream_chunk_to_followers(path, hash, chunk_data, *offset, *size);
+        }
+
+        // Update file size to include buffered data
+        {
+            let buffers = self.write_buffers.read().unwrap();
+            if let Some(buffer) = buffers.get(&ino) {
+                let buffered_end = buffer.base_offset + buffer.data.len() as u64;
+                let mut file_index = self.file_index.write().unwrap();
+                if let Some(mut entry) = file_index.get_mut(path) {
+                    if buffered_end > entry.size {
+                        entry.size = buffered_end;
+                    }
+                    entry.modified = SystemTime::now();
+                };
             }
-            drop(file_index);
+        }
 
-            // Stream final chunks to followers (the tail end of the file)
-            for (hash, chunk_data, offset, size) in &flushed_chunks {
-                self.stream_chunk_to_followers(&path, hash, chunk_data, *offset, *size);
+        // If chunks were streamed, also send metadata update so file appears on followers
+        if !flushed_chunks.is_empty() {
+            let entry = {
+                let file_index = self.file_index.read().unwrap();
+                file_index.get(path).cloned()
+            };
+            if let Some(entry) = entry {
+                self.broadcast_file_sync_final(path, &entry);
             }
+        }
 
-            *self.index_dirty.write().unwrap() = true;
+        // Mark as dirty for final index sync on release
+        self.mark_dirty(ino);
+        Ok(())
+    }
+
+    /// Bytes in the contiguous run of writes to `ino` that a write of `len`
+    /// bytes at `offset` would make; a write that doesn't follow on from the
+    /// buffered data starts a new run
+    fn write_run(&self, ino: u64, offset: u64, len: usize) -> u64 {
+        match self.write_buffers.read().unwrap().get(&ino) {
+            Some(buffer) if buffer.base_offset + buffer.data.len() as u64 == offset => buffer.run_bytes + len as u64,
+            _ => len as u64,
         }
     }
 
@@ -613,6 +1105,7 @@ impl WolfDiskFS {
         // followers can assemble the complete file index entry.
         // Only include chunk_data for any chunks that might not have been
         // streamed yet (e.g. the final partial chunk from flush_write_buffer).
+        self.publish(FsEvent::new(FsOp::Modify, &path));
         self.broadcast_file_sync_final(&path, &entry);
     }
 
@@ -801,7 +1294,7 @@ impl WolfDiskFS {
             crtime: entry.created,
             kind: if entry.is_dir { FileType::Directory } else { FileType::RegularFile },
             perm: entry.permissions as u16,
-            nlink: if entry.is_dir { 2 } else { 1 },
+            nlink: if entry.is_dir { 2 } else { entry.hard_link_count },
             uid: entry.uid,
             gid: entry.gid,
             rdev: 0,
@@ -809,6 +1302,55 @@ impl WolfDiskFS {
             flags: 0,
         }
     }
+
+    /// Build the listing of a directory by scanning the index, and cache it.
+    /// Returns None if the inode is unknown.
+    fn list_dir(&self, ino: u64) -> Option<Arc<DirListing>> {
+        let inode_table = self.inode_table.read().unwrap();
+        let file_index = self.file_index.read().unwrap();
+        let generation = self.dir_cache.generation();
+
+        // Get directory path
+        let dir_path = if ino == ROOT_INODE {
+            std::path::PathBuf::new()
+        } else {
+            inode_table.get_path(ino)?.clone()
+        };
+
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (ino, FileType::Directory, "..".to_string()),
+        ];
+
+        // Find children
+        for (path, entry) in file_index.iter() {
+            if let Some(parent) = path.parent() {
+                let parent_matches = if ino == ROOT_INODE {
+                    parent.as_os_str().is_empty()
+                } else {
+                    parent == dir_path
+                };
+
+                if parent_matches {
+                    if let Some(name) = path.file_name() {
+                        let child_inode = inode_table.get_inode(path).unwrap_or(0);
+                        let file_type = if entry.is_dir {
+                            FileType::Directory
+                        } else {
+                            FileType::RegularFile
+                        };
+                        entries.push((child_inode, file_type, name.to_string_lossy().to_string()));
+                    }
+                }
+            }
+        }
+
+        // Cache while still holding the locks so a concurrent index update
+        // can't invalidate before we insert
+        let entries = Arc::new(entries);
+        self.dir_cache.insert(ino, generation, entries.clone());
+        Some(entries)
+    }
 }
 
 impl Filesystem for WolfDiskFS {
@@ -934,6 +1476,11 @@ impl Filesystem for WolfDiskFS {
     ) {
         debug!("setattr: ino={}, size={:?}, mode={:?}", ino, size, mode);
 
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         if ino == ROOT_INODE {
             reply.attr(&TTL, &self.root_attr());
             return;
@@ -952,8 +1499,9 @@ impl Filesystem for WolfDiskFS {
 
         // Clear any write buffer for this inode on truncation
         if size.is_some() {
-            let mut buffers = self.write_buffers.write().unwrap();
-            buffers.remove(&ino);
+            let inode_lock = self.inode_lock(ino);
+            let _guard = inode_lock.lock().unwrap();
+            self.write_buffers.write().unwrap().remove(&ino);
         }
 
         // Handle changes on non-leader nodes.
@@ -1005,7 +1553,7 @@ impl Filesystem for WolfDiskFS {
 
             // Update local entry
             let mut file_index = self.file_index.write().unwrap();
-            if let Some(entry) = file_index.get_mut(&path) {
+            if let Some(mut entry) = file_index.get_mut(&path) {
                 if let Some(new_size) = size {
                     if new_size == 0 {
                         for chunk in &entry.chunks {
@@ -1015,7 +1563,7 @@ impl Filesystem for WolfDiskFS {
                     }
                     entry.size = new_size;
                 }
-                if let Some(m) = mode { entry.permissions = m; }
+                if let Some(m) = mode { entry.set_mode(m); }
                 if let Some(u) = uid { entry.uid = u; }
                 if let Some(g) = gid { entry.gid = g; }
                 let now = SystemTime::now();
@@ -1031,7 +1579,7 @@ impl Filesystem for WolfDiskFS {
                         fuser::TimeOrNow::Now => now,
                     };
                 }
-                let attr = self.entry_to_attr(entry, ino);
+                let attr = self.entry_to_attr(&entry, ino);
                 reply.attr(&TTL, &attr);
             } else {
                 reply.error(libc::ENOENT);
@@ -1040,8 +1588,9 @@ impl Filesystem for WolfDiskFS {
         }
 
         // Leader: handle locally
+        let quotas = self.leader_quotas();
         let mut file_index = self.file_index.write().unwrap();
-        let entry = match file_index.get_mut(&path) {
+        let mut entry = match file_index.get_mut(&path) {
             Some(e) => e,
             None => {
                 reply.error(libc::ENOENT);
@@ -1051,6 +1600,12 @@ impl Filesystem for WolfDiskFS {
 
         // Handle size change (truncation)
         if let Some(new_size) = size {
+            if let Some(quotas) = quotas {
+                if let Err(e) = quotas.resize(&path, entry.size, new_size) {
+                    reply.error(e.to_errno());
+                    return;
+                }
+            }
             if new_size == 0 {
                 // Full truncation: delete all chunks
                 for chunk in &entry.chunks {
@@ -1063,12 +1618,12 @@ impl Filesystem for WolfDiskFS {
                 entry.chunks.retain(|chunk| chunk.offset < new_size);
                 entry.size = new_size;
             } else {
-                // Extending: just update size (sparse file)
+                // Extending: just update size; reads past the last chunk return zeros
                 entry.size = new_size;
             }
         }
 
-        if let Some(m) = mode { entry.permissions = m; }
+        if let Some(m) = mode { entry.set_mode(m); }
         if let Some(u) = uid { entry.uid = u; }
         if let Some(g) = gid { entry.gid = g; }
 
@@ -1086,15 +1641,17 @@ impl Filesystem for WolfDiskFS {
             };
         }
 
-        let attr = self.entry_to_attr(entry, ino);
+        let attr = self.entry_to_attr(&entry, ino);
 
         // Drop lock before side effects
+        drop(entry);
         drop(file_index);
 
         *self.index_dirty.write().unwrap() = true;
 
         // Broadcast truncation to followers (non-blocking via replication queue)
         if size.is_some() {
+            self.publish(FsEvent::new(FsOp::Modify, &path));
             let file_index = self.file_index.read().unwrap();
             if let Some(entry) = file_index.get(&path) {
                 let entry_clone = entry.clone();
@@ -1137,6 +1694,14 @@ impl Filesystem for WolfDiskFS {
             None => false,
         };
         if self.is_client() || sync_not_complete {
+            // Clients keep no chunk files; the chunks of files in the synced
+            // index come from the read cache or are fetched once by hash
+            if !sync_not_complete {
+                if let Some(data) = self.read_through_cache(&path, offset as u64, size) {
+                    reply.data(&data);
+                    return;
+                }
+            }
             match self.forward_read_to_leader(&path.to_string_lossy(), offset as u64, size) {
                 Ok(data) => reply.data(&data),
                 Err(errno) => reply.error(errno),
@@ -1190,9 +1755,20 @@ impl Filesystem for WolfDiskFS {
             }
         }
 
-        // Read data from chunks
-        match self.chunk_store.read(&entry.chunks, offset as u64, size as usize) {
+        // Read data from chunks, loading them in parallel when the read spans many.
+        // FUSE callbacks run on their own thread, so this blocks nothing async.
+        let storage = &self.config.storage;
+        let read_result = if storage.parallel_read_workers > 1
+            && ChunkStore::chunks_in_range(&entry.chunks, offset as u64, size as usize).len() > storage.parallel_read_threshold
+        {
+            self.chunk_store.read_parallel(&entry.chunks, offset as u64, size as usize, storage.parallel_read_workers)
+        } else {
+            self.chunk_store.read(&entry.chunks, offset as u64, size as usize)
+        };
+        match read_result {
             Ok(mut data) => {
+                ChunkStore::fill_to_size(&mut data, entry.size, offset as u64, size as usize);
+
                 // Overlay any buffered-but-unflushed write data for read-after-write consistency
                 let buffers = self.write_buffers.read().unwrap();
                 if let Some(buffer) = buffers.get(&ino) {
@@ -1245,6 +1821,11 @@ impl Filesystem for WolfDiskFS {
     ) {
         debug!("write: ino={}, offset={}, size={}", ino, offset, data.len());
 
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         // Get the path for this inode
         let path = {
             let inode_table = self.inode_table.read().unwrap();
@@ -1257,6 +1838,10 @@ impl Filesystem for WolfDiskFS {
             }
         };
 
+        // Writers to the same file take turns for the whole write
+        let inode_lock = self.inode_lock(ino);
+        let _guard = inode_lock.lock().unwrap();
+
         // If we're a follower or client, buffer locally (write-back cache).
         // This returns instantly to FUSE so Dolphin stays responsive.
         // Data is forwarded to the leader on flush()/release().
@@ -1282,204 +1867,54 @@ impl Filesystem for WolfDiskFS {
             // Update local index entry size so getattr returns correct size
             {
                 let mut file_index = self.file_index.write().unwrap();
-                if let Some(entry) = file_index.get_mut(&path) {
+                if let Some(mut entry) = file_index.get_mut(&path) {
                     if new_end > entry.size {
                         entry.size = new_end;
                     }
                     entry.modified = SystemTime::now();
-                }
+                };
             }
 
             reply.written(written);
             return;
         }
 
-        // We're the leader - buffer the write for coalescing
-        let chunk_size = self.config.replication.chunk_size;
-
-        // Collect chunks that were flushed during this write for streaming replication
-        let mut flushed_chunks: Vec<([u8; 32], Vec<u8>, u64, u32)> = Vec::new();
-
-        {
-            let mut buffers = self.write_buffers.write().unwrap();
-            let buffer = buffers.entry(ino).or_insert_with(|| WriteBuffer {
-                data: Vec::new(),
-                base_offset: offset as u64,
-            });
-
-            // Check if write is contiguous with existing buffer
-            let buffer_end = buffer.base_offset + buffer.data.len() as u64;
-            if offset as u64 == buffer_end {
-                // Contiguous: just append
-                buffer.data.extend_from_slice(data);
-            } else {
-                // Non-contiguous: flush existing buffer to chunks, start new one
-                if !buffer.data.is_empty() {
-                    let old_data = std::mem::take(&mut buffer.data);
-                    let old_offset = buffer.base_offset;
-
-                    // Store old buffer data as chunks
-                    let path_for_flush = path.clone();
-                    let mut file_index = self.file_index.write().unwrap();
-                    if let Some(entry) = file_index.get_mut(&path_for_flush) {
-                        let mut pos = 0;
-                        let mut off = old_offset;
-                        while pos < old_data.len() {
-                            let end = (pos + chunk_size).min(old_data.len());
-                            let chunk_data = &old_data[pos..end];
-                            match self.chunk_store.store(chunk_data) {
-                                Ok(hash) => {
-                                    let chunk_len = chunk_data.len() as u32;
-                                    entry.chunks.push(crate::storage::ChunkRef {
-                                        hash,
-                                        offset: off,
-                                        size: chunk_len,
-                                    });
-                                    // Queue for streaming replication
-                                    flushed_chunks.push((hash, chunk_data.to_vec(), off, chunk_len));
-                                }
-                                Err(e) => {
-                                    warn!("Failed to flush non-contiguous buffer: {}", e);
-                                    break;
-                                }
-                            }
-                            off += chunk_data.len() as u64;
-                            pos = end;
-                        }
-                        let new_end = old_offset + old_data.len() as u64;
-                        if new_end > entry.size {
-                            entry.size = new_end;
-                        }
-                        entry.modified = SystemTime::now();
-                    }
-                }
-
-                // Start new buffer
-                buffer.data = data.to_vec();
-                buffer.base_offset = offset as u64;
-            }
-
-            // Flush complete chunks from buffer while it exceeds chunk_size
-            while buffer.data.len() >= chunk_size {
-                let chunk_data: Vec<u8> = buffer.data.drain(..chunk_size).collect();
-                let flush_offset = buffer.base_offset;
-                buffer.base_offset += chunk_size as u64;
-
-                match self.chunk_store.store(&chunk_data) {
-                    Ok(hash) => {
-                        let mut file_index = self.file_index.write().unwrap();
-                        if let Some(entry) = file_index.get_mut(&path) {
-                            entry.chunks.push(crate::storage::ChunkRef {
-                                hash,
-                                offset: flush_offset,
-                                size: chunk_size as u32,
-                            });
-                            let new_end = flush_offset + chunk_size as u64;
-                            if new_end > entry.size {
-                                entry.size = new_end;
-                            }
-                            entry.modified = SystemTime::now();
-                        }
-                        // Queue for streaming replication
-                        flushed_chunks.push((hash, chunk_data, flush_offset, chunk_size as u32));
-                    }
-                    Err(e) => {
-                        warn!("Failed to flush full chunk from buffer: {}", e);
-                    }
-                }
-            }
-        }
-
-        // Stream flushed chunks to followers immediately (streaming replication)
-        for (hash, chunk_data, offset, size) in &flushed_chunks {
-            self.stream_chunk_to_followers(&path, hash, chunk_data, *offset, *size);
-        }
-
-        // Update file size to include buffered data
-        {
-            let buffers = self.write_buffers.read().unwrap();
-            if let Some(buffer) = buffers.get(&ino) {
-                let buffered_end = buffer.base_offset + buffer.data.len() as u64;
-                let mut file_index = self.file_index.write().unwrap();
-                if let Some(entry) = file_index.get_mut(&path) {
-                    if buffered_end > entry.size {
-                        entry.size = buffered_end;
-                    }
-                    entry.modified = SystemTime::now();
-                }
+        // Charge any growth of the file to its directory quotas
+        if let Some(quotas) = self.leader_quotas() {
+            let old_size = self.file_index.read().unwrap().get(&path).map_or(0, |e| e.size);
+            let new_end = offset as u64 + data.len() as u64;
+            if let Err(e) = quotas.resize(&path, old_size, new_end.max(old_size)) {
+                reply.error(e.to_errno());
+                return;
             }
         }
 
-        // If chunks were streamed, also send metadata update so file appears on followers
-        if !flushed_chunks.is_empty() {
-            let entry = {
-                let file_index = self.file_index.read().unwrap();
-                file_index.get(&path).cloned()
-            };
-            if let Some(entry) = entry {
-                self.broadcast_file_sync_final(&path, &entry);
+        match self.leader_write(ino, &path, offset as u64, data) {
+            Ok(()) => reply.written(data.len() as u32),
+            Err(e) => {
+                warn!("Write to {:?} failed: {}", path, e);
+                reply.error(e.to_errno());
             }
         }
-
-        // Mark as dirty for final index sync on release
-        self.mark_dirty(ino);
-
-        reply.written(data.len() as u32);
     }
 
     fn readdir(
         &mut self,
-        _req: &Request,
-        ino: u64,
-        _fh: u64,
-        offset: i64,
-        mut reply: ReplyDirectory,
-    ) {
-        debug!("readdir: ino={}, offset={}", ino, offset);
-
-        let inode_table = self.inode_table.read().unwrap();
-        let file_index = self.file_index.read().unwrap();
-
-        // Get directory path
-        let dir_path = if ino == ROOT_INODE {
-            std::path::PathBuf::new()
-        } else {
-            match inode_table.get_path(ino) {
-                Some(p) => p.clone(),
-                None => {
-                    reply.error(libc::ENOENT);
-                    return;
-                }
-            }
-        };
-
-        let mut entries = vec![
-            (ino, FileType::Directory, ".".to_string()),
-            (ino, FileType::Directory, "..".to_string()),
-        ];
-
-        // Find children
-        for (path, entry) in file_index.iter() {
-            if let Some(parent) = path.parent() {
-                let parent_matches = if ino == ROOT_INODE {
-                    parent.as_os_str().is_empty()
-                } else {
-                    parent == dir_path
-                };
-
-                if parent_matches {
-                    if let Some(name) = path.file_name() {
-                        let child_inode = inode_table.get_inode(path).unwrap_or(0);
-                        let file_type = if entry.is_dir {
-                            FileType::Directory
-                        } else {
-                            FileType::RegularFile
-                        };
-                        entries.push((child_inode, file_type, name.to_string_lossy().to_string()));
-                    }
-                }
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        debug!("readdir: ino={}, offset={}", ino, offset);
+
+        let entries = match self.dir_cache.get(ino).or_else(|| self.list_dir(ino)) {
+            Some(entries) => entries,
+            None => {
+                reply.error(libc::ENOENT);
+                return;
             }
-        }
+        };
 
         // Return entries starting from offset
         for (i, (inode, file_type, name)) in entries.iter().enumerate().skip(offset as usize) {
@@ -1500,8 +1935,14 @@ impl Filesystem for WolfDiskFS {
         _umask: u32,
         reply: ReplyEntry,
     ) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let name_str = name.to_string_lossy();
         debug!("mkdir: parent={}, name={}, mode={:o}", parent, name_str, mode);
+        self.dir_cache.invalidate(parent);
 
         // Get parent path first (needed for forwarding)
         let parent_path = {
@@ -1539,6 +1980,11 @@ impl Filesystem for WolfDiskFS {
                         accessed: now,
                         chunks: Vec::new(),
                         symlink_target: None,
+                        xattrs: HashMap::new(),
+                        version_id: None,
+                        versions: Vec::new(),
+                        hard_link_count: 1,
+                        link_id: None,
                     };
                     let inode = self.allocate_inode();
                     self.inode_table.write().unwrap().insert(inode, dir_path.clone());
@@ -1574,6 +2020,11 @@ impl Filesystem for WolfDiskFS {
             accessed: now,
             chunks: Vec::new(),
             symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
         };
 
         // Allocate inode and add to tables
@@ -1588,6 +2039,8 @@ impl Filesystem for WolfDiskFS {
 
         let attr = self.entry_to_attr(&entry, inode);
         
+        self.publish(FsEvent::new(FsOp::Create, std::path::Path::new(&dir_path_str)));
+
         // Broadcast mkdir to followers
         self.broadcast_index_update(IndexOperation::Mkdir {
             path: dir_path_str,
@@ -1607,8 +2060,14 @@ impl Filesystem for WolfDiskFS {
         _flags: i32,
         reply: fuser::ReplyCreate,
     ) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let name_str = name.to_string_lossy();
         debug!("create: parent={}, name={}, mode={:o}", parent, name_str, mode);
+        self.dir_cache.invalidate(parent);
 
         // Get parent path first (needed for forwarding)
         let parent_path = {
@@ -1646,6 +2105,11 @@ impl Filesystem for WolfDiskFS {
                         accessed: now,
                         chunks: Vec::new(),
                         symlink_target: None,
+                        xattrs: HashMap::new(),
+                        version_id: None,
+                        versions: Vec::new(),
+                        hard_link_count: 1,
+                        link_id: None,
                     };
                     let inode = self.allocate_inode();
                     self.inode_table.write().unwrap().insert(inode, file_path.clone());
@@ -1661,6 +2125,7 @@ impl Filesystem for WolfDiskFS {
         }
 
         // Leader: execute locally
+        let quotas = self.leader_quotas();
         let mut inode_table = self.inode_table.write().unwrap();
         let mut file_index = self.file_index.write().unwrap();
 
@@ -1670,6 +2135,13 @@ impl Filesystem for WolfDiskFS {
             return;
         }
 
+        if let Some(quotas) = quotas {
+            if let Err(e) = quotas.charge(&file_path, 0, 1) {
+                reply.error(e.to_errno());
+                return;
+            }
+        }
+
         // Create entry
         let now = SystemTime::now();
         let entry = FileEntry {
@@ -1683,6 +2155,11 @@ impl Filesystem for WolfDiskFS {
             accessed: now,
             chunks: Vec::new(),
             symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
         };
 
         // Allocate inode and add to tables
@@ -1700,6 +2177,7 @@ impl Filesystem for WolfDiskFS {
         self.open_files.write().unwrap().insert(fh, inode);
 
         let attr = self.entry_to_attr(&entry, inode);
+        self.publish(FsEvent::new(FsOp::Create, std::path::Path::new(&file_path_str)));
         
         // Broadcast file creation to followers
         self.broadcast_index_update(IndexOperation::Upsert {
@@ -1714,8 +2192,14 @@ impl Filesystem for WolfDiskFS {
     }
 
     fn unlink(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let name_str = name.to_string_lossy();
         debug!("unlink: parent={}, name={}", parent, name_str);
+        self.dir_cache.invalidate(parent);
 
         // Get parent path first (needed for forwarding)
         let parent_path = {
@@ -1743,8 +2227,8 @@ impl Filesystem for WolfDiskFS {
                     // Remove local entry
                     let mut inode_table = self.inode_table.write().unwrap();
                     let mut file_index = self.file_index.write().unwrap();
-                    if let Some(entry) = file_index.remove(&file_path) {
-                        for chunk in &entry.chunks {
+                    if let Some(entry) = file_index.unlink(&file_path) {
+                        for chunk in entry.released_chunks() {
                             let _ = self.chunk_store.delete(&chunk.hash);
                         }
                     }
@@ -1757,6 +2241,7 @@ impl Filesystem for WolfDiskFS {
         }
 
         // Leader: execute locally
+        let quotas = self.leader_quotas();
         let mut inode_table = self.inode_table.write().unwrap();
         let mut file_index = self.file_index.write().unwrap();
 
@@ -1777,24 +2262,30 @@ impl Filesystem for WolfDiskFS {
         let file_ino = inode_table.get_inode(&file_path);
         
         // Clear any pending write buffers and dirty flags for the deleted inode
-        // (prevents stale streaming replication data from being sent after delete)
-        if let Some(ino) = file_ino {
+        // (prevents stale streaming replication data from being sent after delete),
+        // unless another hard link keeps the inode alive
+        if let Some(ino) = file_ino.filter(|_| file_index.hard_links(&file_path).len() <= 1) {
             self.write_buffers.write().unwrap().remove(&ino);
+            self.inode_locks.write().unwrap().remove(&ino);
             self.dirty_inodes.write().unwrap().remove(&ino);
         }
         
         // Remove from index and inode table
-        if let Some(entry) = file_index.remove(&file_path) {
-            // Delete chunks
-            for chunk in &entry.chunks {
+        if let Some(entry) = file_index.unlink(&file_path) {
+            // Delete chunks once no hard link uses them
+            for chunk in entry.released_chunks() {
                 let _ = self.chunk_store.delete(&chunk.hash);
             }
+            if let Some(quotas) = quotas {
+                quotas.release(&file_path, entry.size, 1);
+            }
         }
         inode_table.remove_path(&file_path);
         
         // Drop locks before broadcast
         drop(file_index);
         drop(inode_table);
+        self.publish(FsEvent::new(FsOp::Delete, &file_path));
 
         // Broadcast delete to followers via IndexUpdate
         self.broadcast_index_update(IndexOperation::Delete {
@@ -1815,14 +2306,25 @@ impl Filesystem for WolfDiskFS {
             accessed: SystemTime::now(),
             chunks: Vec::new(),
             symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
         });
 
         reply.ok();
     }
 
     fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let name_str = name.to_string_lossy();
         debug!("rmdir: parent={}, name={}", parent, name_str);
+        self.dir_cache.invalidate(parent);
 
         // Get parent path first (needed for forwarding)
         let parent_path = {
@@ -1893,6 +2395,7 @@ impl Filesystem for WolfDiskFS {
         // Drop locks before broadcast
         drop(file_index);
         drop(inode_table);
+        self.publish(FsEvent::new(FsOp::Delete, &dir_path));
 
         // Broadcast delete to followers
         self.broadcast_index_update(IndexOperation::Delete {
@@ -1912,9 +2415,16 @@ impl Filesystem for WolfDiskFS {
         _flags: u32,
         reply: fuser::ReplyEmpty,
     ) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let name_str = name.to_string_lossy();
         let newname_str = newname.to_string_lossy();
         debug!("rename: parent={}, name={}, newparent={}, newname={}", parent, name_str, newparent, newname_str);
+        self.dir_cache.invalidate(parent);
+        self.dir_cache.invalidate(newparent);
 
         // Get source and destination paths
         let (from_path, to_path) = {
@@ -1947,6 +2457,11 @@ impl Filesystem for WolfDiskFS {
             (parent_path.join(name), newparent_path.join(newname))
         };
 
+        // A renamed directory's children move with it
+        if let Some(ino) = self.inode_table.read().unwrap().get_inode(&from_path) {
+            self.dir_cache.invalidate(ino);
+        }
+
         // If not leader, forward to leader
         if !self.is_leader() {
             info!("Forwarding rename to leader: {:?} -> {:?}", from_path, to_path);
@@ -1974,6 +2489,7 @@ impl Filesystem for WolfDiskFS {
         }
 
         // Leader: execute locally
+        let quotas = self.leader_quotas();
         let mut inode_table = self.inode_table.write().unwrap();
         let mut file_index = self.file_index.write().unwrap();
 
@@ -1986,6 +2502,20 @@ impl Filesystem for WolfDiskFS {
             }
         };
 
+        // Renaming a file onto another of its hard links does nothing
+        if from_path != to_path && file_index.hard_links(&from_path).contains(&to_path) {
+            reply.ok();
+            return;
+        }
+
+        // Moving a file under another quota's directory is charged there
+        if let (Some(quotas), false) = (quotas, entry.is_dir) {
+            if let Err(e) = quotas.transfer(&from_path, &to_path, entry.size, 1) {
+                reply.error(e.to_errno());
+                return;
+            }
+        }
+
         // Check destination and handle overwrite
         if let Some(target_entry) = file_index.get(&to_path) {
             if target_entry.is_dir {
@@ -2004,18 +2534,19 @@ impl Filesystem for WolfDiskFS {
                 }
             }
             
-            // Delete target chunks
-            for chunk in &target_entry.chunks {
-                let _ = self.chunk_store.delete(&chunk.hash);
+            if let (Some(quotas), false) = (quotas, target_entry.is_dir) {
+                quotas.release(&to_path, target_entry.size, 1);
             }
             
-            // Remove target from inode table
-            if let Some(target_ino) = inode_table.get_inode(&to_path) {
-                inode_table.remove_inode(target_ino);
-            }
+            // Remove target from inode table (its other hard links keep the inode)
+            inode_table.remove_path(&to_path);
             
-            // Remove target from index
-            file_index.remove(&to_path);
+            // Remove target from index, and its chunks once no hard link uses them
+            if let Some(target_entry) = file_index.unlink(&to_path) {
+                for chunk in target_entry.released_chunks() {
+                    let _ = self.chunk_store.delete(&chunk.hash);
+                }
+            }
         }
 
         // Move entry
@@ -2031,6 +2562,7 @@ impl Filesystem for WolfDiskFS {
 
         drop(file_index);
         drop(inode_table);
+        self.publish(FsEvent::rename(&from_path, &to_path));
 
         // Broadcast rename to followers
         self.broadcast_index_update(IndexOperation::Rename {
@@ -2041,9 +2573,14 @@ impl Filesystem for WolfDiskFS {
         reply.ok();
     }
 
-    fn open(&mut self, _req: &Request, ino: u64, _flags: i32, reply: ReplyOpen) {
+    fn open(&mut self, _req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
         debug!("open: ino={}", ino);
 
+        if self.is_read_only() && (flags & libc::O_ACCMODE != libc::O_RDONLY || flags & libc::O_TRUNC != 0) {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         // Verify file exists
         let inode_table = self.inode_table.read().unwrap();
         if inode_table.get_path(ino).is_none() && ino != ROOT_INODE {
@@ -2079,7 +2616,11 @@ impl Filesystem for WolfDiskFS {
         }
         
         // Flush any pending write buffer for this inode
-        self.flush_write_buffer(ino);
+        {
+            let inode_lock = self.inode_lock(ino);
+            let _guard = inode_lock.lock().unwrap();
+            self.flush_write_buffer(ino);
+        }
         
         // Replicate to followers if this inode was modified (deferred from write)
         self.replicate_dirty(ino);
@@ -2098,9 +2639,15 @@ impl Filesystem for WolfDiskFS {
         target: &std::path::Path,
         reply: ReplyEntry,
     ) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let link_name_str = link_name.to_string_lossy();
         let target_str = target.to_string_lossy();
         debug!("symlink: parent={}, name={}, target={}", parent, link_name_str, target_str);
+        self.dir_cache.invalidate(parent);
 
         // Get parent path
         let parent_path = {
@@ -2138,6 +2685,11 @@ impl Filesystem for WolfDiskFS {
                         accessed: now,
                         chunks: Vec::new(),
                         symlink_target: Some(target_str.to_string()),
+                        xattrs: HashMap::new(),
+                        version_id: None,
+                        versions: Vec::new(),
+                        hard_link_count: 1,
+                        link_id: None,
                     };
                     let inode = self.allocate_inode();
                     self.inode_table.write().unwrap().insert(inode, link_path.clone());
@@ -2169,6 +2721,13 @@ impl Filesystem for WolfDiskFS {
         }
 
         // Leader: create symlink locally
+        if let Some(quotas) = self.leader_quotas() {
+            if let Err(e) = quotas.charge(&link_path, target_str.len() as u64, 1) {
+                reply.error(e.to_errno());
+                return;
+            }
+        }
+
         let now = SystemTime::now();
         let entry = FileEntry {
             size: target_str.len() as u64,
@@ -2181,6 +2740,11 @@ impl Filesystem for WolfDiskFS {
             accessed: now,
             chunks: Vec::new(),
             symlink_target: Some(target_str.to_string()),
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
         };
 
         let inode = self.allocate_inode();
@@ -2188,6 +2752,7 @@ impl Filesystem for WolfDiskFS {
         self.file_index.write().unwrap().insert(link_path.clone(), entry.clone());
 
         info!("Created symlink: {:?} -> {}", link_path, target_str);
+        self.publish(FsEvent::new(FsOp::Create, &link_path));
 
         let attr = FileAttr {
             ino: inode,
@@ -2238,14 +2803,20 @@ impl Filesystem for WolfDiskFS {
 
     fn link(
         &mut self,
-        req: &Request,
+        _req: &Request,
         ino: u64,
         newparent: u64,
         newname: &OsStr,
         reply: ReplyEntry,
     ) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let newname_str = newname.to_string_lossy();
         debug!("link: ino={}, newparent={}, newname={}", ino, newparent, newname_str);
+        self.dir_cache.invalidate(newparent);
 
         // Get source path
         let source_path = {
@@ -2295,28 +2866,57 @@ impl Filesystem for WolfDiskFS {
             return;
         }
 
-        // Create a copy of the source entry at the new path
-        let now = SystemTime::now();
-        let new_entry = FileEntry {
-            size: source_entry.size,
-            is_dir: false,
-            permissions: source_entry.permissions,
-            uid: req.uid(),
-            gid: req.gid(),
-            created: now,
-            modified: source_entry.modified,
-            accessed: now,
-            chunks: source_entry.chunks.clone(),
-            symlink_target: None,
+        if self.file_index.read().unwrap().contains(&link_path) {
+            reply.error(libc::EEXIST);
+            return;
+        }
+
+        if !self.is_leader() {
+            info!("Forwarding link to leader: {:?} -> {:?}", link_path, source_path);
+            if let Err(errno) = self.forward_link_to_leader(&source_path.to_string_lossy(), &link_path.to_string_lossy()) {
+                reply.error(errno);
+                return;
+            }
+        }
+
+        // Links are separate index entries, so each one counts in full
+        let quotas = self.leader_quotas();
+        if let Some(quotas) = &quotas {
+            if let Err(e) = quotas.charge(&link_path, source_entry.size, 1) {
+                reply.error(e.to_errno());
+                return;
+            }
+        }
+
+        // Both names share the source's chunks and inode
+        let new_entry = {
+            let mut inode_table = self.inode_table.write().unwrap();
+            let mut file_index = self.file_index.write().unwrap();
+            match file_index.link(&source_path, link_path.clone()) {
+                Ok(entry) => {
+                    inode_table.insert_link(ino, link_path.clone());
+                    entry
+                }
+                Err(e) => {
+                    if let Some(quotas) = &quotas {
+                        quotas.release(&link_path, source_entry.size, 1);
+                    }
+                    reply.error(e.to_errno());
+                    return;
+                }
+            }
         };
+        *self.index_dirty.write().unwrap() = true;
 
-        let inode = self.allocate_inode();
-        self.inode_table.write().unwrap().insert(inode, link_path.clone());
-        self.file_index.write().unwrap().insert(link_path.clone(), new_entry.clone());
+        info!("Created hard link: {:?} -> {:?} ({} links)", link_path, source_path, new_entry.hard_link_count);
+        self.publish(FsEvent::new(FsOp::Create, &link_path));
 
-        info!("Created hard link: {:?} -> {:?}", link_path, source_path);
+        self.broadcast_index_update(IndexOperation::Link {
+            src_path: source_path.to_string_lossy().to_string(),
+            dst_path: link_path.to_string_lossy().to_string(),
+        });
 
-        let attr = self.entry_to_attr(&new_entry, inode);
+        let attr = self.entry_to_attr(&new_entry, ino);
         reply.entry(&TTL, &attr, 0);
     }
 
@@ -2330,8 +2930,14 @@ impl Filesystem for WolfDiskFS {
         _rdev: u32,
         reply: ReplyEntry,
     ) {
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
         let name_str = name.to_string_lossy();
         debug!("mknod: parent={}, name={}, mode={:o}", parent, name_str, mode);
+        self.dir_cache.invalidate(parent);
 
         // Get parent path
         let parent_path = {
@@ -2370,6 +2976,13 @@ impl Filesystem for WolfDiskFS {
             return;
         }
 
+        if let Some(quotas) = self.leader_quotas() {
+            if let Err(e) = quotas.charge(&file_path, 0, 1) {
+                reply.error(e.to_errno());
+                return;
+            }
+        }
+
         // Create regular file entry
         let permissions = mode & 0o7777;
         let now = SystemTime::now();
@@ -2384,6 +2997,11 @@ impl Filesystem for WolfDiskFS {
             accessed: now,
             chunks: Vec::new(),
             symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
         };
 
         let inode = self.allocate_inode();
@@ -2391,15 +3009,16 @@ impl Filesystem for WolfDiskFS {
         self.file_index.write().unwrap().insert(file_path.clone(), entry.clone());
 
         info!("Created file via mknod: {:?}", file_path);
+        self.publish(FsEvent::new(FsOp::Create, &file_path));
 
         let attr = self.entry_to_attr(&entry, inode);
         reply.entry(&TTL, &attr, 0);
     }
 
-    /// Check file access permissions.
-    /// Dolphin calls this before most file operations. Returning OK for all
-    /// since our getattr already reports correct permissions.
-    fn access(&mut self, _req: &Request, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
+    /// Check file access permissions against the access ACL, or the
+    /// permission bits when there is none.
+    /// Dolphin calls this before most file operations.
+    fn access(&mut self, req: &Request, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
         debug!("access: ino={}, mask={:#o}", ino, mask);
 
         // For root inode, always allow
@@ -2408,75 +3027,174 @@ impl Filesystem for WolfDiskFS {
             return;
         }
 
-        // Check inode exists
-        let inode_table = self.inode_table.read().unwrap();
-        if inode_table.get_path(ino).is_some() {
+        let caller = caller(req);
+        let allowed = {
+            let inode_table = self.inode_table.read().unwrap();
+            let Some(path) = inode_table.get_path(ino) else {
+                reply.error(libc::ENOENT);
+                return;
+            };
+            let file_index = self.file_index.read().unwrap();
+            file_index.get(path).is_none_or(|entry| may_access(entry, &caller, mask))
+        };
+
+        if allowed {
             reply.ok();
         } else {
-            reply.error(libc::ENOENT);
+            reply.error(libc::EACCES);
         }
     }
 
-    /// Get an extended attribute.
+    /// Get an extended attribute (served from the local index).
     /// Dolphin queries xattrs (e.g. security.selinux, user.mime_type).
-    /// We don't support xattrs, so return ENODATA (attribute not found).
     fn getxattr(
         &mut self,
         _req: &Request,
         ino: u64,
         name: &OsStr,
-        _size: u32,
+        size: u32,
         reply: fuser::ReplyXattr,
     ) {
         debug!("getxattr: ino={}, name={:?}", ino, name);
-        reply.error(libc::ENODATA);
+
+        let name = name.to_string_lossy();
+        let value = {
+            let inode_table = self.inode_table.read().unwrap();
+            let file_index = self.file_index.read().unwrap();
+            inode_table.get_path(ino)
+                .and_then(|path| file_index.get(path))
+                .and_then(|entry| match name.as_ref() {
+                    acl::ACCESS_ACL_XATTR => entry.access_acl().map(|acl| acl.to_xattr()),
+                    acl::DEFAULT_ACL_XATTR => entry.default_acl().map(|acl| acl.to_xattr()),
+                    // Stored ACLs are only read through the names above
+                    acl::STORED_ACCESS_ACL | acl::STORED_DEFAULT_ACL => None,
+                    name => entry.xattrs.get(name).cloned(),
+                })
+        };
+
+        match value {
+            None => reply.error(libc::ENODATA),
+            // size == 0 is a query for the buffer size needed
+            Some(value) if size == 0 => reply.size(value.len() as u32),
+            Some(value) if value.len() > size as usize => reply.error(libc::ERANGE),
+            Some(value) => reply.data(&value),
+        }
     }
 
-    /// List extended attribute names.
-    /// Return empty list (size 0) since we don't support xattrs.
+    /// List extended attribute names as a NUL-separated list.
+    /// Stored ACLs are listed under their `system.posix_acl_*` names.
     fn listxattr(&mut self, _req: &Request, ino: u64, size: u32, reply: fuser::ReplyXattr) {
         debug!("listxattr: ino={}, size={}", ino, size);
+
+        let mut names = {
+            let inode_table = self.inode_table.read().unwrap();
+            let file_index = self.file_index.read().unwrap();
+            inode_table.get_path(ino)
+                .and_then(|path| file_index.get(path))
+                .map(|entry| entry.xattrs.keys()
+                    .map(|name| acl::listed_name(name).map(str::to_string).unwrap_or_else(|| name.clone()))
+                    .collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+        names.sort();
+
+        let mut data = Vec::new();
+        for name in names {
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+        }
+
         if size == 0 {
-            // Return the buffer size needed (0 = no xattrs)
-            reply.size(0);
+            reply.size(data.len() as u32);
+        } else if data.len() > size as usize {
+            reply.error(libc::ERANGE);
         } else {
-            // Return empty data
-            reply.data(&[]);
+            reply.data(&data);
         }
     }
 
     /// Set an extended attribute.
-    /// Return ENOSYS to indicate xattrs are not supported. This tells
-    /// the VFS layer to stop trying, preventing repeated calls.
+    /// Honours XATTR_CREATE / XATTR_REPLACE; followers forward to the leader.
     fn setxattr(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         name: &OsStr,
-        _value: &[u8],
-        _flags: i32,
+        value: &[u8],
+        flags: i32,
         _position: u32,
         reply: fuser::ReplyEmpty,
     ) {
-        debug!("setxattr: ino={}, name={:?}", ino, name);
-        reply.error(libc::ENOSYS);
+        debug!("setxattr: ino={}, name={:?}, {} bytes", ino, name, value.len());
+
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
+        if ino == ROOT_INODE {
+            reply.error(libc::ENOTSUP);
+            return;
+        }
+
+        let path = match self.inode_table.read().unwrap().get_path(ino) {
+            Some(p) => p.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let name = name.to_string_lossy();
+        let result = match name.as_ref() {
+            acl::ACCESS_ACL_XATTR | acl::DEFAULT_ACL_XATTR => self.change_acl(req, &path, &name, Some(value)),
+            acl::STORED_ACCESS_ACL | acl::STORED_DEFAULT_ACL => Err(libc::EPERM),
+            name => self.change_xattr(&path, name, Some(value), flags),
+        };
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
     }
 
     /// Remove an extended attribute.
     fn removexattr(
         &mut self,
-        _req: &Request,
+        req: &Request,
         ino: u64,
         name: &OsStr,
         reply: fuser::ReplyEmpty,
     ) {
         debug!("removexattr: ino={}, name={:?}", ino, name);
-        reply.error(libc::ENOSYS);
+
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
+        let path = match self.inode_table.read().unwrap().get_path(ino) {
+            Some(p) => p.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let name = name.to_string_lossy();
+        let result = match name.as_ref() {
+            acl::ACCESS_ACL_XATTR | acl::DEFAULT_ACL_XATTR => self.change_acl(req, &path, &name, None),
+            acl::STORED_ACCESS_ACL | acl::STORED_DEFAULT_ACL => Err(libc::EPERM),
+            name => self.change_xattr(&path, name, None, 0),
+        };
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
     }
 
-    /// Pre-allocate or deallocate space for a file.
-    /// Dolphin/KIO may call this before writing. We treat it as a no-op
-    /// since our chunk-based storage doesn't benefit from pre-allocation.
+    /// Pre-allocate, punch a hole in or collapse a range of a file (the
+    /// modes `fallocate_supported` accepts). Other nodes forward it to the
+    /// leader, which replicates the result like a write.
     fn fallocate(
         &mut self,
         _req: &Request,
@@ -2487,16 +3205,97 @@ impl Filesystem for WolfDiskFS {
         mode: i32,
         reply: fuser::ReplyEmpty,
     ) {
-        debug!("fallocate: ino={}, offset={}, length={}, mode={}", ino, offset, length, mode);
-
-        // Mode 0 = allocate space, which we can just accept as no-op
-        // FALLOC_FL_KEEP_SIZE (1) = allocate but don't change size, also no-op
-        // FALLOC_FL_PUNCH_HOLE (2) = deallocate, we don't support
-        if mode & 0x02 != 0 {
-            // Punch hole not supported
-            reply.error(libc::ENOSYS);
+        debug!("fallocate: ino={}, offset={}, length={}, mode={:#x}", ino, offset, length, mode);
+
+        if self.is_read_only() {
+            reply.error(libc::EROFS);
+            return;
+        }
+
+        if offset < 0 || length <= 0 {
+            reply.error(libc::EINVAL);
+            return;
+        }
+        if !fallocate_supported(mode) {
+            reply.error(libc::EOPNOTSUPP);
+            return;
+        }
+        let (offset, length) = (offset as u64, length as u64);
+
+        let path = {
+            let inode_table = self.inode_table.read().unwrap();
+            match inode_table.get_path(ino) {
+                Some(p) => p.clone(),
+                None => {
+                    reply.error(libc::ENOENT);
+                    return;
+                }
+            }
+        };
+
+        if !self.is_leader() {
+            // Buffered writes go first, so the range change applies to them too
+            if let Err(e) = self.drain_client_writes(ino) {
+                reply.error(e);
+                return;
+            }
+            if let Err(e) = self.forward_fallocate_to_leader(&path.to_string_lossy(), mode, offset, length) {
+                reply.error(e);
+                return;
+            }
+            // The chunks arrive with the leader's file sync; keep the size right until then
+            let mut file_index = self.file_index.write().unwrap();
+            if let Some(mut entry) = file_index.get_mut(&path) {
+                match mode {
+                    0 => entry.size = entry.size.max(offset + length),
+                    libc::FALLOC_FL_COLLAPSE_RANGE => entry.size = entry.size.saturating_sub(length),
+                    _ => {}
+                }
+                entry.modified = SystemTime::now();
+            }
+            reply.ok();
+            return;
+        }
+
+        // Leader: store coalesced writes as chunks before changing the range
+        let inode_lock = self.inode_lock(ino);
+        let _guard = inode_lock.lock().unwrap();
+        self.flush_write_buffer(ino);
+
+        let quotas = self.leader_quotas();
+        let mut file_index = self.file_index.write().unwrap();
+        let mut entry = match file_index.get_mut(&path) {
+            Some(e) => e,
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let mut chunks = entry.chunks.clone();
+        let mut size = entry.size;
+        if let Err(e) = self.chunk_store.fallocate(&mut chunks, &mut size, mode, offset, length) {
+            reply.error(e.to_errno());
             return;
         }
+        if let Some(quotas) = quotas {
+            if let Err(e) = quotas.resize(&path, entry.size, size) {
+                reply.error(e.to_errno());
+                return;
+            }
+        }
+        entry.chunks = chunks;
+        entry.size = size;
+        entry.modified = SystemTime::now();
+        let entry_clone = entry.clone();
+
+        // Drop lock before side effects
+        drop(entry);
+        drop(file_index);
+
+        *self.index_dirty.write().unwrap() = true;
+        self.publish(FsEvent::new(FsOp::Modify, &path));
+        self.broadcast_file_sync_final(&path, &entry_clone);
 
         reply.ok();
     }
@@ -2515,6 +3314,8 @@ impl Filesystem for WolfDiskFS {
 
         // Flush write buffer if this node is the leader
         if self.is_leader() {
+            let inode_lock = self.inode_lock(ino);
+            let _guard = inode_lock.lock().unwrap();
             self.flush_write_buffer(ino);
         }
 
@@ -2554,6 +3355,8 @@ impl Filesystem for WolfDiskFS {
     ) {
         debug!("flush: ino={}", ino);
 
+        let inode_lock = self.inode_lock(ino);
+        let _guard = inode_lock.lock().unwrap();
         if self.is_leader() {
             self.flush_write_buffer(ino);
         } else {
@@ -2600,3 +3403,198 @@ impl Filesystem for WolfDiskFS {
         reply.ok();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use tempfile::tempdir;
+
+    fn errno(result: std::io::Result<impl Sized>) -> Option<i32> {
+        result.err().and_then(|e| e.raw_os_error())
+    }
+
+    #[test]
+    fn test_long_write_run_takes_streaming_path() {
+        const PIECE: usize = 128 * 1024;
+        let data = tempdir().unwrap();
+        let mut config = Config::default();
+        config.node.data_dir = data.path().to_path_buf();
+        config.replication.chunk_size = 256 * 1024;
+        config.mount.streaming_threshold_mb = 1;
+        let fs = WolfDiskFS::new(config).unwrap();
+
+        let path = std::path::PathBuf::from("upload.bin");
+        let ino = fs.allocate_inode();
+        let now = SystemTime::now();
+        fs.inode_table.write().unwrap().insert(ino, path.clone());
+        fs.file_index.write().unwrap().insert(path.clone(), FileEntry {
+            size: 0,
+            is_dir: false,
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+            chunks: Vec::new(),
+            symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
+        });
+
+        // FUSE-sized pieces, with a short one so the run goes off chunk boundaries
+        let mut expected = Vec::new();
+        for i in 0..24u8 {
+            let len = if i == 3 { PIECE - 1000 } else { PIECE };
+            let piece = vec![i; len];
+            fs.leader_write(ino, &path, expected.len() as u64, &piece).unwrap();
+            expected.extend_from_slice(&piece);
+        }
+        let streamed = fs.chunk_store.stats().streaming_writes.load(Ordering::Relaxed);
+        assert!(streamed >= 15, "only {} writes streamed", streamed);
+
+        fs.flush_write_buffer(ino);
+        let entry = fs.file_index.read().unwrap().get(&path).cloned().unwrap();
+        assert_eq!(entry.size, expected.len() as u64);
+        let (last, full) = entry.chunks.split_last().unwrap();
+        assert!(full.iter().all(|c| c.size == 256 * 1024));
+        assert!(last.size <= 256 * 1024);
+        assert_eq!(fs.chunk_store.read(&entry.chunks, 0, expected.len()).unwrap(), expected);
+    }
+
+    /// Mounts through /dev/fuse, so it needs root (or fusermount)
+    #[test]
+    #[ignore]
+    fn test_read_only_mount_refuses_changes() {
+        let data = tempdir().unwrap();
+        let mnt = tempdir().unwrap();
+        let mut config = Config::default();
+        config.node.data_dir = data.path().to_path_buf();
+
+        let read_only = Arc::new(AtomicBool::new(false));
+        let fs = WolfDiskFS::new(config).unwrap().with_read_only(read_only.clone());
+        let _session = fuser::spawn_mount2(fs, mnt.path(), &[fuser::MountOption::FSName("wolfdisk-test".to_string())]).unwrap();
+
+        let file = mnt.path().join("hello.txt");
+        std::fs::write(&file, b"hello").unwrap();
+        std::fs::create_dir(mnt.path().join("dir")).unwrap();
+        let mut open_for_write = std::fs::OpenOptions::new().write(true).open(&file).unwrap();
+
+        read_only.store(true, Ordering::Relaxed);
+
+        // Reads still work
+        let mut contents = String::new();
+        std::fs::File::open(&file).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "hello");
+        assert_eq!(std::fs::read_dir(mnt.path()).unwrap().count(), 2);
+
+        // Every kind of change fails with EROFS
+        assert_eq!(errno(open_for_write.write_all(b"world")), Some(libc::EROFS));
+        assert_eq!(errno(std::fs::OpenOptions::new().append(true).open(&file)), Some(libc::EROFS));
+        assert_eq!(errno(std::fs::File::create(mnt.path().join("new.txt"))), Some(libc::EROFS));
+        assert_eq!(errno(std::fs::create_dir(mnt.path().join("newdir"))), Some(libc::EROFS));
+        assert_eq!(errno(std::fs::remove_file(&file)), Some(libc::EROFS));
+        assert_eq!(errno(std::fs::remove_dir(mnt.path().join("dir"))), Some(libc::EROFS));
+        assert_eq!(errno(std::fs::rename(&file, mnt.path().join("moved.txt"))), Some(libc::EROFS));
+        assert_eq!(errno(std::fs::hard_link(&file, mnt.path().join("link.txt"))), Some(libc::EROFS));
+        assert_eq!(errno(std::os::unix::fs::symlink("hello.txt", mnt.path().join("sym"))), Some(libc::EROFS));
+        assert_eq!(
+            errno(std::fs::set_permissions(&file, std::os::unix::fs::PermissionsExt::from_mode(0o600))),
+            Some(libc::EROFS)
+        );
+
+        // `wolfdisk remount --read-write` lifts it
+        read_only.store(false, Ordering::Relaxed);
+        std::fs::write(mnt.path().join("new.txt"), b"again").unwrap();
+    }
+
+    /// Mounts through /dev/fuse, so it needs root (or fusermount)
+    #[test]
+    #[ignore]
+    fn test_concurrent_writes_to_one_file() {
+        use std::os::unix::fs::FileExt;
+        const HALF: usize = 4 * 1024 * 1024;
+
+        let data = tempdir().unwrap();
+        let mnt = tempdir().unwrap();
+        let mut config = Config::default();
+        config.node.data_dir = data.path().to_path_buf();
+        let fs = WolfDiskFS::new(config).unwrap();
+        let _session = fuser::spawn_mount2(fs, mnt.path(), &[fuser::MountOption::FSName("wolfdisk-test".to_string())]).unwrap();
+
+        // Two writers fill the two halves of one file at the same time, in
+        // small pieces so their writes interleave
+        let file = mnt.path().join("segments.bin");
+        std::fs::File::create(&file).unwrap();
+        let writers: Vec<_> = [(0u64, 0xAAu8), (HALF as u64, 0x55u8)].into_iter().map(|(start, byte)| {
+            let file = file.clone();
+            std::thread::spawn(move || {
+                let out = std::fs::OpenOptions::new().write(true).open(&file).unwrap();
+                let piece = vec![byte; 64 * 1024];
+                for i in 0..HALF / piece.len() {
+                    out.write_all_at(&piece, start + (i * piece.len()) as u64).unwrap();
+                }
+            })
+        }).collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let contents = std::fs::read(&file).unwrap();
+        assert_eq!(contents.len(), 2 * HALF);
+        assert!(contents[..HALF].iter().all(|b| *b == 0xAA), "first writer's data lost");
+        assert!(contents[HALF..].iter().all(|b| *b == 0x55), "second writer's data lost");
+    }
+
+    /// What `setfacl -m u:1000:rw` and `getfacl` do, through the xattr calls
+    /// they make. Mounts through /dev/fuse, so it needs root (or fusermount).
+    #[test]
+    #[ignore]
+    fn test_posix_acl_through_mount() {
+        use std::ffi::CString;
+        use std::os::unix::fs::PermissionsExt;
+
+        let data = tempdir().unwrap();
+        let mnt = tempdir().unwrap();
+        let mut config = Config::default();
+        config.node.data_dir = data.path().to_path_buf();
+        let fs = WolfDiskFS::new(config).unwrap();
+        let _session = fuser::spawn_mount2(fs, mnt.path(), &[fuser::MountOption::FSName("wolfdisk-test".to_string())]).unwrap();
+
+        let file = mnt.path().join("file");
+        std::fs::write(&file, b"shared").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let path = CString::new(file.as_os_str().as_encoded_bytes()).unwrap();
+        let name = CString::new(acl::ACCESS_ACL_XATTR).unwrap();
+
+        // u::rw- u:1000:rw- g::r-- m::rw- o::r--
+        let acl = Acl::new(vec![
+            acl::AclEntry { tag: acl::AclTag::UserObj, id: 0, perms: 6 },
+            acl::AclEntry { tag: acl::AclTag::User, id: 1000, perms: 6 },
+            acl::AclEntry { tag: acl::AclTag::GroupObj, id: 0, perms: 4 },
+            acl::AclEntry { tag: acl::AclTag::Mask, id: 0, perms: 6 },
+            acl::AclEntry { tag: acl::AclTag::Other, id: 0, perms: 4 },
+        ]).unwrap();
+        let value = acl.to_xattr();
+        let set = unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr() as *const libc::c_void, value.len(), 0) };
+        assert_eq!(set, 0, "setxattr: {}", std::io::Error::last_os_error());
+
+        let mut buf = vec![0u8; 256];
+        let len = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+        assert_eq!(&buf[..len as usize], value.as_slice());
+        // The mask shows up as the group bits
+        assert_eq!(std::fs::metadata(&file).unwrap().permissions().mode() & 0o777, 0o664);
+
+        let len = unsafe { libc::listxattr(path.as_ptr(), buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+        assert_eq!(&buf[..len as usize], b"system.posix_acl_access\0");
+
+        // A default ACL only goes on directories
+        let name = CString::new(acl::DEFAULT_ACL_XATTR).unwrap();
+        let set = unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr() as *const libc::c_void, value.len(), 0) };
+        assert_eq!((set, std::io::Error::last_os_error().raw_os_error()), (-1, Some(libc::EACCES)));
+    }
+}
@@ -0,0 +1,105 @@
+//! Kernel cache invalidation for replicated changes
+//!
+//! The kernel caches attributes and directory entries for the attribute TTL,
+//! and file pages until the file is reopened. Changes made through this mount
+//! keep those caches coherent on their own, but changes applied by the
+//! replication message handler never pass through the kernel, so another
+//! node's write could stay invisible here for up to the TTL. The handler
+//! collects the inodes and directory entries an update touches while it holds
+//! the inode table lock, and sends the invalidations once the lock is dropped
+//! (the kernel may need to call back into the filesystem to process them).
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::sync::RwLock;
+
+use tracing::debug;
+
+use super::dir_cache::parent_inode;
+use crate::storage::InodeTable;
+
+/// A kernel cache entry to drop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invalidation {
+    /// Attributes and cached pages of an inode
+    Inode(u64),
+    /// A name in a directory (parent inode, name)
+    Entry(u64, OsString),
+}
+
+impl Invalidation {
+    /// Invalidations for a change to `path`: its inode, if it has one yet,
+    /// and its entry in the parent directory (which may be a cached negative
+    /// lookup for a newly created file)
+    pub fn for_path(inode_table: &InodeTable, path: &Path) -> Vec<Invalidation> {
+        let mut invalidations = Vec::with_capacity(2);
+        if let Some(ino) = inode_table.get_inode(&path.to_path_buf()) {
+            invalidations.push(Invalidation::Inode(ino));
+        }
+        if let (Some(parent), Some(name)) = (parent_inode(inode_table, path), path.file_name()) {
+            invalidations.push(Invalidation::Entry(parent, name.to_os_string()));
+        }
+        invalidations
+    }
+}
+
+/// Sends invalidations to the kernel once the filesystem is mounted
+/// (shared with the replication message handler)
+#[derive(Default)]
+pub struct KernelCache {
+    notifier: RwLock<Option<fuser::Notifier>>,
+}
+
+impl KernelCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start notifying the kernel through the mounted session
+    pub fn set_notifier(&self, notifier: fuser::Notifier) {
+        *self.notifier.write().unwrap() = Some(notifier);
+    }
+
+    /// Drop the given kernel cache entries (a no-op until mounted)
+    pub fn invalidate(&self, invalidations: &[Invalidation]) {
+        let notifier = self.notifier.read().unwrap();
+        let Some(notifier) = notifier.as_ref() else {
+            return;
+        };
+        for invalidation in invalidations {
+            let result = match invalidation {
+                Invalidation::Inode(ino) => notifier.inval_inode(*ino, 0, 0),
+                Invalidation::Entry(parent, name) => notifier.inval_entry(*parent, name),
+            };
+            // ENOENT only means the kernel had nothing cached
+            if let Err(e) = result {
+                if e.raw_os_error() != Some(libc::ENOENT) {
+                    debug!("Kernel cache invalidation {:?} failed: {}", invalidation, e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_invalidations_for_path() {
+        let mut inodes = InodeTable::new();
+        inodes.insert(2, PathBuf::from("docs"));
+        inodes.insert(3, PathBuf::from("docs/a.txt"));
+
+        assert_eq!(Invalidation::for_path(&inodes, Path::new("docs/a.txt")), vec![
+            Invalidation::Inode(3),
+            Invalidation::Entry(2, OsString::from("a.txt")),
+        ]);
+        // A file this node has not seen yet only has a (negative) entry
+        assert_eq!(Invalidation::for_path(&inodes, Path::new("new.txt")), vec![
+            Invalidation::Entry(1, OsString::from("new.txt")),
+        ]);
+        assert!(Invalidation::for_path(&inodes, Path::new("missing/b.txt")).is_empty());
+    }
+}
@@ -1,5 +1,13 @@
 //! FUSE filesystem module
 
+mod dir_cache;
+mod events;
 mod filesystem;
+mod kernel_cache;
+mod snapshot;
 
+pub use dir_cache::DirCache;
+pub use events::{EventBus, FsEvent, FsOp, EVENT_BUFFER};
 pub use filesystem::WolfDiskFS;
+pub use kernel_cache::{Invalidation, KernelCache};
+pub use snapshot::SnapshotFS;
@@ -0,0 +1,352 @@
+//! Read-only FUSE filesystem serving a snapshot
+//!
+//! Exposes a snapshot's frozen `FileIndex` through a separate mount point,
+//! reading chunk data from the shared content-addressed `ChunkStore`.
+//! All mutating operations fail with EROFS.
+
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use fuser::{
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    ReplyOpen, ReplyWrite, Request,
+};
+use tracing::{debug, info, warn};
+
+use crate::storage::{ChunkStore, FileEntry, FileIndex, InodeTable, SnapIndex};
+
+/// TTL for attribute caching (snapshots never change)
+const TTL: Duration = Duration::from_secs(3600);
+
+/// Root inode number
+const ROOT_INODE: u64 = 1;
+
+/// Read-only filesystem over a snapshot's index
+pub struct SnapshotFS {
+    /// Snapshot name (for logging)
+    name: String,
+
+    /// Snapshot creation time, used for the root directory's timestamps
+    created: SystemTime,
+
+    /// Frozen file index
+    index: FileIndex,
+
+    /// Inode to path mapping built from the snapshot index
+    inode_table: InodeTable,
+
+    /// Shared chunk storage
+    chunk_store: Arc<ChunkStore>,
+}
+
+impl SnapshotFS {
+    /// Create a filesystem serving the given snapshot
+    pub fn new(snapshot: SnapIndex, chunk_store: Arc<ChunkStore>) -> Self {
+        let (inode_table, _) = InodeTable::from_index(&snapshot.index);
+        info!("Serving snapshot '{}' ({} entries)", snapshot.info.name, snapshot.index.len());
+
+        Self {
+            name: snapshot.info.name,
+            created: snapshot.info.created.into(),
+            index: snapshot.index,
+            inode_table,
+            chunk_store,
+        }
+    }
+
+    /// Resolve an inode to its index entry
+    fn entry(&self, ino: u64) -> Option<(&PathBuf, &FileEntry)> {
+        let path = self.inode_table.get_path(ino)?;
+        self.index.get(path).map(|e| (path, e))
+    }
+
+    /// Get root directory attributes
+    fn root_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: ROOT_INODE,
+            size: 0,
+            blocks: 0,
+            atime: self.created,
+            mtime: self.created,
+            ctime: self.created,
+            crtime: self.created,
+            kind: FileType::Directory,
+            perm: 0o555,
+            nlink: 2,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        }
+    }
+
+    /// Convert FileEntry to FileAttr (write bits stripped)
+    fn entry_to_attr(&self, entry: &FileEntry, inode: u64) -> FileAttr {
+        let kind = if entry.is_dir {
+            FileType::Directory
+        } else if entry.symlink_target.is_some() {
+            FileType::Symlink
+        } else {
+            FileType::RegularFile
+        };
+
+        FileAttr {
+            ino: inode,
+            size: entry.size,
+            blocks: (entry.size + 511) / 512,
+            atime: entry.accessed,
+            mtime: entry.modified,
+            ctime: entry.modified,
+            crtime: entry.created,
+            kind,
+            perm: (entry.permissions & 0o7555) as u16,
+            nlink: if entry.is_dir { 2 } else { 1 },
+            uid: entry.uid,
+            gid: entry.gid,
+            rdev: 0,
+            blksize: 4096,
+            flags: 0,
+        }
+    }
+}
+
+impl Filesystem for SnapshotFS {
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let parent_path = match self.inode_table.get_path(parent) {
+            Some(p) => p.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let child_path = if parent_path.as_os_str().is_empty() {
+            PathBuf::from(name)
+        } else {
+            parent_path.join(name)
+        };
+
+        match (self.index.get(&child_path), self.inode_table.get_inode(&child_path)) {
+            (Some(entry), Some(inode)) => reply.entry(&TTL, &self.entry_to_attr(entry, inode), 0),
+            _ => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
+        if ino == ROOT_INODE {
+            reply.attr(&TTL, &self.root_attr());
+            return;
+        }
+
+        match self.entry(ino) {
+            Some((_, entry)) => reply.attr(&TTL, &self.entry_to_attr(entry, ino)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        debug!("snapshot {} read: ino={}, offset={}, size={}", self.name, ino, offset, size);
+
+        let entry = match self.entry(ino) {
+            Some((_, e)) => e,
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        match self.chunk_store.read(&entry.chunks, offset as u64, size as usize) {
+            Ok(mut data) => {
+                ChunkStore::fill_to_size(&mut data, entry.size, offset as u64, size as usize);
+                reply.data(&data)
+            }
+            Err(e) => {
+                warn!("Snapshot read error: {}", e);
+                reply.error(e.to_errno());
+            }
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let dir_path = match self.inode_table.get_path(ino) {
+            Some(p) => p.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (ino, FileType::Directory, "..".to_string()),
+        ];
+
+        for (path, entry) in self.index.iter() {
+            if path.parent() != Some(dir_path.as_path()) {
+                continue;
+            }
+            if let Some(name) = path.file_name() {
+                let child_inode = self.inode_table.get_inode(path).unwrap_or(0);
+                let file_type = if entry.is_dir {
+                    FileType::Directory
+                } else if entry.symlink_target.is_some() {
+                    FileType::Symlink
+                } else {
+                    FileType::RegularFile
+                };
+                entries.push((child_inode, file_type, name.to_string_lossy().to_string()));
+            }
+        }
+
+        for (i, (inode, file_type, name)) in entries.iter().enumerate().skip(offset as usize) {
+            if reply.add(*inode, (i + 1) as i64, *file_type, name) {
+                break;
+            }
+        }
+
+        reply.ok();
+    }
+
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        match self.entry(ino) {
+            Some((_, entry)) => match entry.symlink_target {
+                Some(ref target) => reply.data(target.as_bytes()),
+                None => reply.error(libc::EINVAL),
+            },
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn open(&mut self, _req: &Request, ino: u64, flags: i32, reply: ReplyOpen) {
+        if flags & libc::O_ACCMODE != libc::O_RDONLY {
+            reply.error(libc::EROFS);
+            return;
+        }
+        if self.inode_table.get_path(ino).is_none() {
+            reply.error(libc::ENOENT);
+            return;
+        }
+        reply.opened(0, 0);
+    }
+
+    fn opendir(&mut self, _req: &Request, ino: u64, _flags: i32, reply: ReplyOpen) {
+        if self.inode_table.get_path(ino).is_some() {
+            reply.opened(0, 0);
+        } else {
+            reply.error(libc::ENOENT);
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        _size: Option<u64>,
+        _atime: Option<fuser::TimeOrNow>,
+        _mtime: Option<fuser::TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        reply.error(libc::EROFS);
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        _fh: u64,
+        _offset: i64,
+        _data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        reply.error(libc::EROFS);
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        reply.error(libc::EROFS);
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request,
+        _parent: u64,
+        _name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        _flags: i32,
+        reply: fuser::ReplyCreate,
+    ) {
+        reply.error(libc::EROFS);
+    }
+
+    fn unlink(&mut self, _req: &Request, _parent: u64, _name: &OsStr, reply: fuser::ReplyEmpty) {
+        reply.error(libc::EROFS);
+    }
+
+    fn rmdir(&mut self, _req: &Request, _parent: u64, _name: &OsStr, reply: fuser::ReplyEmpty) {
+        reply.error(libc::EROFS);
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request,
+        _parent: u64,
+        _name: &OsStr,
+        _newparent: u64,
+        _newname: &OsStr,
+        _flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        reply.error(libc::EROFS);
+    }
+
+    fn symlink(
+        &mut self,
+        _req: &Request,
+        _parent: u64,
+        _link_name: &OsStr,
+        _target: &std::path::Path,
+        reply: ReplyEntry,
+    ) {
+        reply.error(libc::EROFS);
+    }
+}
@@ -11,6 +11,8 @@ pub mod network;
 pub mod cluster;
 pub mod replication;
 pub mod s3;
+pub mod api;
+pub mod webdav;
 
 pub use config::{Config, NodeRole, ReplicationMode};
 pub use cluster::{ClusterManager, ClusterState};
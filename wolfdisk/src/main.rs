@@ -7,7 +7,7 @@ use std::path::PathBuf;
 use tracing::{info, error, debug};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use wolfdisk::{Config, fuse::WolfDiskFS, storage::{FileIndex, FileEntry, ChunkRef}};
+use wolfdisk::{Config, fuse::{FsEvent, FsOp, Invalidation, WolfDiskFS}, storage::{FileIndex, FileEntry, ChunkRef, Tier}};
 
 #[derive(Parser)]
 #[command(name = "wolfdisk")]
@@ -34,6 +34,24 @@ enum Commands {
         /// Mount point path
         #[arg(short, long)]
         mountpoint: PathBuf,
+
+        /// Refuse every change through the mount (overrides [mount] read_only)
+        #[arg(long)]
+        read_only: bool,
+    },
+
+    /// Stop refusing changes on a running read-only mount
+    Remount {
+        /// Accept writes again (takes effect once the mount is remounted)
+        #[arg(long, required = true)]
+        read_write: bool,
+    },
+
+    /// Run the node and serve files over WebDAV instead of a FUSE mount
+    Webdav {
+        /// Bind address (overrides [webdav] bind)
+        #[arg(short, long)]
+        bind: Option<String>,
     },
 
     /// Unmount the WolfDisk filesystem
@@ -63,6 +81,156 @@ enum Commands {
         #[arg(short, long, default_value = "/var/lib/wolfdisk")]
         data_dir: PathBuf,
     },
+
+    /// Manage copy-on-write snapshots
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCommand,
+    },
+
+    /// Monitor replication sync progress
+    Sync {
+        #[command(subcommand)]
+        action: SyncCommand,
+    },
+
+    /// Manage the cold storage tier
+    Tier {
+        #[command(subcommand)]
+        action: TierCommand,
+    },
+
+    /// Show how chunk copies are placed across racks
+    RackStatus,
+
+    /// Move chunks off over-full followers (through the running leader)
+    Rebalance {
+        /// Show the progress of the current or last rebalance instead
+        #[arg(long)]
+        status: bool,
+    },
+
+    /// Re-hash every local chunk now to find silent corruption (through the running node)
+    Scrub {
+        /// Show the progress and results of the current or last scrub instead
+        #[arg(long)]
+        report: bool,
+    },
+
+    /// Print changes to files as they happen (like `inotifywait -rm`)
+    Watch {
+        /// Directory to watch, inside WolfDisk or under the mount point
+        #[arg(default_value = "/")]
+        path: PathBuf,
+    },
+
+    /// Import a local directory tree straight into the index (run with the node stopped)
+    Import {
+        /// Local directory to import
+        #[arg(long)]
+        src: PathBuf,
+
+        /// Destination directory inside WolfDisk
+        #[arg(long, default_value = "/")]
+        dst: String,
+
+        /// Threads used to chunk files
+        #[arg(long, default_value_t = 8)]
+        workers: usize,
+    },
+
+    /// Remove a path and its chunks straight from the index (run with the node stopped)
+    Rm {
+        /// Path inside WolfDisk
+        path: String,
+
+        /// Remove directories and everything below them
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// List what would be removed without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Confirm removing the root directory
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Check the on-disk index for hard links that disagree (run with the node stopped)
+    Fsck,
+
+    /// Print a presigned S3 URL for an object, signed with the configured S3 keys
+    Presign {
+        /// Object to sign for, as s3://bucket/key
+        url: String,
+
+        /// Seconds the URL stays valid (at most 7 days)
+        #[arg(long, default_value_t = 3600)]
+        expires: u64,
+
+        /// HTTP method the URL allows (GET, HEAD, PUT or DELETE)
+        #[arg(long, default_value = "GET")]
+        method: String,
+
+        /// Base URL clients reach the S3 API on [default: http://<s3.bind>]
+        #[arg(long)]
+        endpoint: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TierCommand {
+    /// Evict chunks not read within evict_after_days to the cold tier
+    Evict {
+        /// Only show what would be evicted
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show bytes held locally and in the cold tier
+    Status,
+}
+
+#[derive(Subcommand)]
+enum SyncCommand {
+    /// Wait until the current sync has finished
+    Wait {
+        /// Give up after this many seconds
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// Snapshot the current file index (no chunk data is copied)
+    Create {
+        /// Snapshot name
+        #[arg(short, long)]
+        name: String,
+    },
+
+    /// List snapshots with creation time and size
+    List,
+
+    /// Delete a snapshot and garbage-collect chunks only it referenced
+    Delete {
+        /// Snapshot name
+        #[arg(short, long)]
+        name: String,
+    },
+
+    /// Mount a snapshot read-only
+    Mount {
+        /// Snapshot name
+        #[arg(short, long)]
+        name: String,
+
+        /// Mount point path
+        #[arg(short, long)]
+        mountpoint: PathBuf,
+    },
 }
 
 fn main() {
@@ -76,7 +244,7 @@ fn main() {
         .init();
 
     // Load config if it exists
-    let config = if cli.config.exists() {
+    let mut config = if cli.config.exists() {
         match Config::load(&cli.config) {
             Ok(c) => c,
             Err(e) => {
@@ -90,8 +258,23 @@ fn main() {
     };
 
     match cli.command {
-        Commands::Mount { mountpoint } => {
-            info!("Mounting WolfDisk at {:?}", mountpoint);
+        Commands::Mount { .. } | Commands::Webdav { .. } => {
+            // `webdav` runs the same node as `mount`, just without FUSE
+            let mountpoint = match cli.command {
+                Commands::Mount { mountpoint, read_only } => {
+                    info!("Mounting WolfDisk at {:?}", mountpoint);
+                    config.mount.read_only |= read_only;
+                    Some(mountpoint)
+                }
+                Commands::Webdav { bind } => {
+                    config.webdav.enabled = true;
+                    if let Some(bind) = bind {
+                        config.webdav.bind = bind;
+                    }
+                    None
+                }
+                _ => unreachable!(),
+            };
             info!("Node ID: {}, Role: {:?}", config.node.id, config.node.role);
             
             // Initialize cluster manager
@@ -116,11 +299,30 @@ fn main() {
             ));
             let file_index_for_handler = file_index.clone();
             
-            // Create chunk store for replication (shared with WolfDiskFS)
-            let chunk_store = std::sync::Arc::new(
-                wolfdisk::storage::ChunkStore::new(config.chunks_dir(), 4 * 1024 * 1024)
-                    .expect("Failed to create chunk store")
-            );
+            // Object storage for evicted chunks
+            let cold_tier: Option<std::sync::Arc<dyn wolfdisk::storage::ColdTier>> = if config.storage.cold_tier.enabled {
+                let tier = wolfdisk::storage::S3ColdTier::new(&config.storage.cold_tier)
+                    .expect("Failed to configure cold tier");
+                info!("Cold tier: bucket {} (evicting chunks unread for {} days)",
+                    config.storage.cold_tier.bucket, config.storage.cold_tier.evict_after_days);
+                Some(std::sync::Arc::new(tier))
+            } else {
+                None
+            };
+
+            // Create chunk store for replication (shared with WolfDiskFS, and
+            // with it the read cache)
+            let read_cache = wolfdisk::storage::ReadCache::with_capacity_mb(config.storage.read_cache_mb);
+            let mut chunk_store = wolfdisk::storage::ChunkStore::new(config.chunks_dir(), 4 * 1024 * 1024)
+                .expect("Failed to create chunk store")
+                .with_snapshot_dir(config.snapshots_dir())
+                .with_write_batch_size(config.storage.write_batch_size)
+                .with_mmap_cache(config.storage.mmap_cache_mb)
+                .with_read_cache(std::sync::Arc::new(std::sync::RwLock::new(read_cache)));
+            if let Some(ref cold) = cold_tier {
+                chunk_store = chunk_store.with_cold_tier(cold.clone(), config.storage.cold_tier.rewarm);
+            }
+            let chunk_store = std::sync::Arc::new(chunk_store);
             let chunk_store_for_handler = chunk_store.clone();
             
             // Build inode table from index (shared with WolfDiskFS)
@@ -133,6 +335,39 @@ fn main() {
             let inode_table_for_handler = inode_table.clone();
             let next_inode_for_handler = next_inode.clone();
             
+            // Directory listing cache (shared with WolfDiskFS, invalidated on replicated changes)
+            let dir_cache = std::sync::Arc::new(wolfdisk::fuse::DirCache::new(
+                std::time::Duration::from_millis(config.mount.readdir_cache_ttl_ms)
+            ));
+            let dir_cache_for_handler = dir_cache.clone();
+            
+            // Kernel cache invalidation for replicated changes (notifier set once mounted)
+            let kernel_cache = std::sync::Arc::new(wolfdisk::fuse::KernelCache::new());
+            let kernel_cache_for_handler = kernel_cache.clone();
+            
+            // Change events (published by WolfDiskFS and on replicated changes), served by the admin API
+            let events = wolfdisk::fuse::EventBus::new();
+            let events_for_handler = events.clone();
+
+            // `mount.read_only` (shared with WolfDiskFS; cleared by `wolfdisk remount --read-write`)
+            let read_only = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(config.mount.read_only));
+            
+            // Per-directory quotas (shared with WolfDiskFS and the S3 API; enforced by the leader)
+            let quotas = if config.quota.is_empty() {
+                None
+            } else {
+                Some(std::sync::Arc::new(
+                    wolfdisk::storage::QuotaManager::load(&config.quota, &config.index_dir())
+                        .expect("Failed to load quota usage")
+                ))
+            };
+            let quotas_for_handler = quotas.clone();
+            
+            // Progress of recursive syncs (FileSync broadcast or applied), served by the admin API
+            let sync_progress = std::sync::Arc::new(wolfdisk::replication::SyncProgressTracker::new());
+            let replication_lag = std::sync::Arc::new(wolfdisk::replication::ReplicationLag::new());
+            let sync_progress_for_handler = sync_progress.clone();
+            
             // Broadcast queue for message handler to queue FileSync broadcasts
             // (path, entry) tuples that need to be broadcast to followers
             let broadcast_queue: std::sync::Arc<std::sync::Mutex<Vec<(std::path::PathBuf, wolfdisk::storage::FileEntry)>>> = 
@@ -151,6 +386,12 @@ fn main() {
                 std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
             let metadata_update_queue_for_handler = metadata_update_queue.clone();
             
+            // Index operation queue for changes that are broadcast as IndexUpdate
+            // rather than FileSync (e.g. extended attributes)
+            let index_update_queue: std::sync::Arc<std::sync::Mutex<Vec<wolfdisk::network::protocol::IndexOperation>>> =
+                std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let index_update_queue_for_handler = index_update_queue.clone();
+            
             // Track if this node is a client (clients don't store chunk data locally)
             let is_client_role = config.node.role == wolfdisk::config::NodeRole::Client;
             let cluster_for_handler = cluster.clone();
@@ -176,25 +417,63 @@ fn main() {
                             }
                             Message::IndexUpdate(update) => {
                                 info!("Received IndexUpdate from {}: {:?}", peer_id, update.operation);
+                                if let Some(ref quotas) = quotas_for_handler {
+                                    quotas.mark_stale();
+                                }
                                 
                                 // Acquire locks in correct order (Inode -> Index) to match readdir and avoid deadlocks/races
                                 let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                 let mut index = file_index_for_handler.write().unwrap();
+
+                                // Drop cached listings of every directory this update touches,
+                                // and note what the kernel has cached for the paths involved
+                                let invalidations = match &update.operation {
+                                    IndexOperation::Delete { path }
+                                    | IndexOperation::Upsert { path, .. }
+                                    | IndexOperation::Mkdir { path, .. } => {
+                                        dir_cache_for_handler.invalidate_parent(&inode_tbl, std::path::Path::new(path));
+                                        Invalidation::for_path(&inode_tbl, std::path::Path::new(path))
+                                    }
+                                    IndexOperation::Rename { from_path, to_path } => {
+                                        dir_cache_for_handler.invalidate_parent(&inode_tbl, std::path::Path::new(from_path));
+                                        dir_cache_for_handler.invalidate_parent(&inode_tbl, std::path::Path::new(to_path));
+                                        if let Some(ino) = inode_tbl.get_inode(&std::path::PathBuf::from(from_path)) {
+                                            dir_cache_for_handler.invalidate(ino);
+                                        }
+                                        let mut invalidations = Invalidation::for_path(&inode_tbl, std::path::Path::new(from_path));
+                                        invalidations.extend(Invalidation::for_path(&inode_tbl, std::path::Path::new(to_path)));
+                                        invalidations
+                                    }
+                                    IndexOperation::SetXattr { path, .. } => {
+                                        inode_tbl.get_inode(&std::path::PathBuf::from(path))
+                                            .map(Invalidation::Inode)
+                                            .into_iter()
+                                            .collect()
+                                    }
+                                    IndexOperation::Link { src_path, dst_path } => {
+                                        dir_cache_for_handler.invalidate_parent(&inode_tbl, std::path::Path::new(dst_path));
+                                        let mut invalidations = Invalidation::for_path(&inode_tbl, std::path::Path::new(src_path));
+                                        invalidations.extend(Invalidation::for_path(&inode_tbl, std::path::Path::new(dst_path)));
+                                        invalidations
+                                    }
+                                };
                                 
                                 // Track chunks to delete after dropping locks
                                 let mut chunks_to_delete = Vec::new();
+                                let mut event = None;
 
                                 match update.operation {
                                     IndexOperation::Delete { path } => {
                                         info!("Replicating delete: {}", path);
                                         let del_path = std::path::PathBuf::from(&path);
                                         
-                                        // Update index
-                                        if let Some(entry) = index.remove(&del_path) {
+                                        // Update index; other hard links keep the chunks
+                                        if let Some(entry) = index.unlink(&del_path) {
                                             if !is_client_role {
-                                                chunks_to_delete = entry.chunks;
+                                                chunks_to_delete = entry.released_chunks().to_vec();
                                             }
                                             info!("Deleted file from follower: {}", path);
+                                            event = Some(FsEvent::new(FsOp::Delete, &del_path));
                                         }
                                         
                                         // Update inode table (atomic with index update)
@@ -207,12 +486,14 @@ fn main() {
                                                 hash: c.hash,
                                                 offset: c.offset,
                                                 size: c.size,
+                                                tier: Tier::Hot,
                                             })
                                             .collect();
                                         let now = std::time::SystemTime::now();
                                         let file_path = std::path::PathBuf::from(&path);
                                         
-                                        // Update index
+                                        // Update index (extended attributes are not part of the upsert)
+                                        let xattrs = index.get(&file_path).map(|e| e.xattrs.clone()).unwrap_or_default();
                                         let old_entry = index.insert(file_path.clone(), FileEntry {
                                             size,
                                             modified: std::time::UNIX_EPOCH + std::time::Duration::from_millis(modified_ms),
@@ -224,8 +505,16 @@ fn main() {
                                             created: now,
                                             accessed: now,
                                             symlink_target: None,
+                                            xattrs,
+                                            version_id: None,
+                                            versions: Vec::new(),
+                                            hard_link_count: 1,
+                                            link_id: None,
                                         });
 
+                                        let op = if old_entry.is_some() { FsOp::Modify } else { FsOp::Create };
+                                        event = Some(FsEvent::new(op, &file_path));
+
                                         // If we overwrote an existing file, clean up its chunks
                                         if let Some(entry) = old_entry {
                                             if !entry.is_dir && !is_client_role {
@@ -250,7 +539,7 @@ fn main() {
                                         let dir_path = std::path::PathBuf::from(&path);
                                         
                                         // Update index
-                                        index.insert(dir_path.clone(), FileEntry {
+                                        let old_entry = index.insert(dir_path.clone(), FileEntry {
                                             size: 0,
                                             modified: now,
                                             permissions,
@@ -261,8 +550,17 @@ fn main() {
                                             created: now,
                                             accessed: now,
                                             symlink_target: None,
+                                            xattrs: std::collections::HashMap::new(),
+                                            version_id: None,
+                                            versions: Vec::new(),
+                                            hard_link_count: 1,
+                                            link_id: None,
                                         });
 
+                                        if old_entry.is_none() {
+                                            event = Some(FsEvent::new(FsOp::Create, &dir_path));
+                                        }
+
                                         // Update inode table if needed
                                         if inode_tbl.get_inode(&dir_path).is_none() {
                                             let mut next_ino = next_inode_for_handler.write().unwrap();
@@ -277,9 +575,9 @@ fn main() {
                                         let to = std::path::PathBuf::from(&to_path);
                                         
                                         // Handle overwrite at destination
-                                        if let Some(target_entry) = index.remove(&to) {
+                                        if let Some(target_entry) = index.unlink(&to) {
                                              if !target_entry.is_dir && !is_client_role {
-                                                 chunks_to_delete.extend(target_entry.chunks);
+                                                 chunks_to_delete.extend_from_slice(target_entry.released_chunks());
                                              }
                                              // Also remove from inode table
                                              // Note: inode_table.remove_path deletes by path, effectively removing the target inode mapping.
@@ -289,6 +587,7 @@ fn main() {
                                         // Move entry
                                         if let Some(entry) = index.remove(&from) {
                                             index.insert(to.clone(), entry);
+                                            event = Some(FsEvent::rename(&from, &to));
                                             
                                             // Update inode table
                                             inode_tbl.remove_path(&from);
@@ -298,11 +597,42 @@ fn main() {
                                             inode_tbl.insert(ino, to);
                                         }
                                     }
+                                    IndexOperation::SetXattr { path, name, value } => {
+                                        debug!("Replicating xattr {} on {}", name, path);
+                                        if let Some(mut entry) = index.get_mut(std::path::Path::new(&path)) {
+                                            let _ = entry.update_xattr(&name, value, 0);
+                                        }
+                                    }
+                                    IndexOperation::Link { src_path, dst_path } => {
+                                        info!("Replicating link: {} -> {}", dst_path, src_path);
+                                        let src = std::path::PathBuf::from(&src_path);
+                                        let dst = std::path::PathBuf::from(&dst_path);
+                                        match index.link(&src, dst.clone()) {
+                                            Ok(_) => {
+                                                event = Some(FsEvent::new(FsOp::Create, &dst));
+                                                match inode_tbl.get_inode(&src) {
+                                                    Some(ino) => inode_tbl.insert_link(ino, dst),
+                                                    None => {
+                                                        let mut next_ino = next_inode_for_handler.write().unwrap();
+                                                        let ino = *next_ino;
+                                                        *next_ino += 1;
+                                                        inode_tbl.insert(ino, src);
+                                                        inode_tbl.insert_link(ino, dst);
+                                                    }
+                                                }
+                                            }
+                                            Err(e) => tracing::warn!("Failed to replicate link {} -> {}: {}", dst_path, src_path, e),
+                                        }
+                                    }
                                 }
                                 
                                 // Drop locks before doing IO (deleting chunks)
                                 drop(index);
                                 drop(inode_tbl);
+                                kernel_cache_for_handler.invalidate(&invalidations);
+                                if let Some(event) = event {
+                                    events_for_handler.publish(event);
+                                }
 
                                 // Delete chunks if any
                                 for chunk in chunks_to_delete {
@@ -312,6 +642,9 @@ fn main() {
                                 None // No response needed for replication
                             }
                             Message::FileSync(sync) => {
+                                if let Some(ref quotas) = quotas_for_handler {
+                                    quotas.mark_stale();
+                                }
                                 // Check if this is a deletion signal (size == u64::MAX)
                                 if sync.size == u64::MAX {
                                     info!("Received FileDelete from {}: {}", peer_id, sync.path);
@@ -321,17 +654,22 @@ fn main() {
                                     // Lock ordering: Inode -> Index
                                     let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                     let mut index = file_index_for_handler.write().unwrap();
+                                    dir_cache_for_handler.invalidate_parent(&inode_tbl, &path);
+                                    let invalidations = Invalidation::for_path(&inode_tbl, &path);
                                     
-                                    let chunks_to_delete = if let Some(entry) = index.remove(&path) {
-                                        info!("Deleted file from follower: {}", sync.path);
-                                        inode_tbl.remove_path(&path);
-                                        entry.chunks
-                                    } else {
-                                        Vec::new()
+                                    let chunks_to_delete = match index.unlink(&path) {
+                                        Some(entry) => {
+                                            info!("Deleted file from follower: {}", sync.path);
+                                            inode_tbl.remove_path(&path);
+                                            events_for_handler.publish(FsEvent::new(FsOp::Delete, &path));
+                                            entry.released_chunks().to_vec()
+                                        }
+                                        None => Vec::new(),
                                     };
                                     
                                     drop(index);
                                     drop(inode_tbl);
+                                    kernel_cache_for_handler.invalidate(&invalidations);
                                     
                                     // Delete chunks
                                     for chunk in chunks_to_delete {
@@ -357,17 +695,30 @@ fn main() {
                                 let mut index = file_index_for_handler.write().unwrap();
                                 
                                 let path = std::path::PathBuf::from(&sync.path);
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &path);
+                                let invalidations = Invalidation::for_path(&inode_tbl, &path);
+                                // Later batches of a multi-batch transfer are part of the same change
+                                let event = if sync.chunks.is_empty() && !sync.chunk_data.is_empty() {
+                                    None
+                                } else if index.contains(&path) {
+                                    Some(FsOp::Modify)
+                                } else {
+                                    Some(FsOp::Create)
+                                };
                                 
                                 // If the incoming message has chunk_refs, use them (authoritative metadata).
                                 // If chunk_refs is empty but we have chunk_data, this is a subsequent batch
                                 // of a multi-batch transfer — only store the chunks, keep existing entry.
                                 if !sync.chunks.is_empty() {
                                     // Authoritative update: replace the full file entry
+                                    // (extended attributes are replicated separately, so keep them)
+                                    let xattrs = index.get(&path).map(|e| e.xattrs.clone()).unwrap_or_default();
                                     let chunk_refs: Vec<ChunkRef> = sync.chunks.iter()
                                         .map(|c| ChunkRef {
                                             hash: c.hash,
                                             offset: c.offset,
                                             size: c.size,
+                                            tier: Tier::Hot,
                                         })
                                         .collect();
                                     
@@ -382,11 +733,16 @@ fn main() {
                                         accessed: std::time::SystemTime::now(),
                                         chunks: chunk_refs,
                                         symlink_target: None,
+                                        xattrs,
+                                        version_id: None,
+                                        versions: Vec::new(),
+                                        hard_link_count: 1,
+                                        link_id: None,
                                     });
                                 } else if !sync.chunk_data.is_empty() {
                                     // Subsequent batch: only storing chunk data, keep existing index entry.
                                     // Update size if it has grown.
-                                    if let Some(entry) = index.get_mut(&path) {
+                                    if let Some(mut entry) = index.get_mut(&path) {
                                         if sync.size > entry.size {
                                             entry.size = sync.size;
                                         }
@@ -399,18 +755,7 @@ fn main() {
                                     // Update the index entry with final metadata + chunk refs.
                                     let chunk_refs: Vec<ChunkRef> = Vec::new();
                                     // If we already have an entry, update it; otherwise create new.
-                                    if let Some(entry) = index.get_mut(&path) {
-                                        // Only increase size, never decrease — prevents out-of-order
-                                        // CreateFile broadcasts (size=0) from overwriting WriteRequest
-                                        // metadata (correct size) that arrived first.
-                                        if sync.size > entry.size {
-                                            entry.size = sync.size;
-                                        }
-                                        entry.permissions = sync.permissions;
-                                        entry.uid = sync.uid;
-                                        entry.gid = sync.gid;
-                                        entry.modified = std::time::UNIX_EPOCH + std::time::Duration::from_millis(sync.modified_ms);
-                                    } else {
+                                    if !index.contains(&path) {
                                         index.insert(path.clone(), FileEntry {
                                             size: sync.size,
                                             is_dir: sync.is_dir,
@@ -422,7 +767,23 @@ fn main() {
                                             accessed: std::time::SystemTime::now(),
                                             chunks: chunk_refs,
                                             symlink_target: None,
+                                            xattrs: std::collections::HashMap::new(),
+                                            version_id: None,
+                                            versions: Vec::new(),
+                                            hard_link_count: 1,
+                                            link_id: None,
                                         });
+                                    } else if let Some(mut entry) = index.get_mut(&path) {
+                                        // Only increase size, never decrease — prevents out-of-order
+                                        // CreateFile broadcasts (size=0) from overwriting WriteRequest
+                                        // metadata (correct size) that arrived first.
+                                        if sync.size > entry.size {
+                                            entry.size = sync.size;
+                                        }
+                                        entry.permissions = sync.permissions;
+                                        entry.uid = sync.uid;
+                                        entry.gid = sync.gid;
+                                        entry.modified = std::time::UNIX_EPOCH + std::time::Duration::from_millis(sync.modified_ms);
                                     }
                                 }
                                 
@@ -437,6 +798,23 @@ fn main() {
                                 
                                 drop(index);
                                 drop(inode_tbl);
+                                kernel_cache_for_handler.invalidate(&invalidations);
+                                if let Some(op) = event {
+                                    events_for_handler.publish(FsEvent::new(op, &path));
+                                }
+                                
+                                // The first batch of a file carries its chunk refs, later ones only data
+                                let data_bytes: u64 = sync.chunk_data.iter().map(|c| c.data.len() as u64).sum();
+                                if !sync.chunks.is_empty() {
+                                    sync_progress_for_handler.file_started(&sync.path, sync.size);
+                                    if sync.chunk_data.is_empty() {
+                                        sync_progress_for_handler.file_done(&sync.path);
+                                    } else {
+                                        sync_progress_for_handler.file_progress(&sync.path, data_bytes);
+                                    }
+                                } else if !sync.chunk_data.is_empty() {
+                                    sync_progress_for_handler.file_progress(&sync.path, data_bytes);
+                                }
                                 
                                 info!("FileSync complete for {}", sync.path);
                                 None
@@ -450,67 +828,81 @@ fn main() {
                                 let path = std::path::PathBuf::from(&write_req.path);
                                 let mut index = file_index_for_handler.write().unwrap();
                                 
-                                if let Some(entry) = index.get_mut(&path) {
-                                    // Track chunks before write to detect new ones
-                                    let chunks_before = entry.chunks.len();
-                                    
-                                    match chunk_store_for_handler.write(&mut entry.chunks, write_req.offset, &write_req.data) {
-                                        Ok(written) => {
-                                            let new_end = write_req.offset + written as u64;
-                                            if new_end > entry.size {
-                                                entry.size = new_end;
-                                            }
-                                            entry.modified = std::time::SystemTime::now();
-                                            
-                                            // Stream any new chunks to followers immediately
-                                            let new_chunks: Vec<_> = entry.chunks[chunks_before..].to_vec();
-                                            let entry_clone = entry.clone();
-                                            let path_clone = path.clone();
-                                            drop(index); // Release lock before streaming
-                                            
-                                            // Queue new chunks for streaming to followers
-                                            if !new_chunks.is_empty() {
-                                                let mut stream_queue = chunk_stream_queue_for_handler.lock().unwrap();
-                                                for chunk_ref in &new_chunks {
-                                                    if let Ok(chunk_data) = chunk_store_for_handler.get(&chunk_ref.hash) {
-                                                        stream_queue.push((chunk_ref.hash, chunk_data));
-                                                    }
-                                                }
-                                            }
-                                            
-                                            // Queue metadata-only update (chunks already streamed above)
-                                            cluster_for_handler.increment_index_version(path_clone.clone());
-                                            metadata_update_queue_for_handler.lock().unwrap().push((path_clone, entry_clone));
-                                            
-                                            info!("Leader wrote {} bytes to {} ({} new chunks streamed)", written, write_req.path, new_chunks.len());
-                                            
-                                            Some(Message::ClientResponse(ClientResponseMsg {
-                                                success: true,
-                                                data: None,
-                                                error: None,
-                                            }))
-                                        }
-                                        Err(e) => {
-                                            tracing::warn!("Leader write error: {}", e);
-                                            Some(Message::ClientResponse(ClientResponseMsg {
-                                                success: false,
-                                                data: None,
-                                                error: Some(format!("Write failed: {}", e)),
-                                            }))
-                                        }
+                                // Charge any growth of the file to its directory quotas
+                                if let (Some(quotas), Some(old_size)) = (&quotas_for_handler, index.get(&path).map(|e| e.size)) {
+                                    quotas.rebuild_if_stale(&index);
+                                    let new_end = write_req.offset + write_req.data.len() as u64;
+                                    if let Err(e) = quotas.resize(&path, old_size, new_end.max(old_size)) {
+                                        return Some(Message::ClientResponse(ClientResponseMsg {
+                                            success: false,
+                                            data: None,
+                                            error: Some(e.to_string()),
+                                        }));
                                     }
-                                } else {
+                                }
+                                
+                                let Some(mut entry) = index.get_mut(&path) else {
                                     // Log all paths in index for debugging
                                     let paths: Vec<String> = index.iter()
                                         .map(|(p, _)| format!("{:?}", p))
                                         .collect();
                                     tracing::warn!("File not found on leader: {:?} (path string: {})", path, write_req.path);
                                     tracing::warn!("Index contains {} entries: {:?}", paths.len(), paths);
-                                    Some(Message::ClientResponse(ClientResponseMsg {
+                                    return Some(Message::ClientResponse(ClientResponseMsg {
                                         success: false,
                                         data: None,
                                         error: Some("File not found".to_string()),
-                                    }))
+                                    }));
+                                };
+                                // Track chunks before write to detect new ones
+                                let chunks_before = entry.chunks.len();
+                                
+                                match chunk_store_for_handler.write(&mut entry.chunks, write_req.offset, &write_req.data) {
+                                    Ok(written) => {
+                                        let new_end = write_req.offset + written as u64;
+                                        if new_end > entry.size {
+                                            entry.size = new_end;
+                                        }
+                                        entry.modified = std::time::SystemTime::now();
+                                        
+                                        // Stream any new chunks to followers immediately
+                                        let new_chunks: Vec<_> = entry.chunks[chunks_before..].to_vec();
+                                        let entry_clone = entry.clone();
+                                        let path_clone = path.clone();
+                                        drop(entry);
+                                        drop(index); // Release lock before streaming
+                                        
+                                        // Queue new chunks for streaming to followers
+                                        if !new_chunks.is_empty() {
+                                            let mut stream_queue = chunk_stream_queue_for_handler.lock().unwrap();
+                                            for chunk_ref in &new_chunks {
+                                                if let Ok(chunk_data) = chunk_store_for_handler.get(&chunk_ref.hash) {
+                                                    stream_queue.push((chunk_ref.hash, chunk_data));
+                                                }
+                                            }
+                                        }
+                                        
+                                        // Queue metadata-only update (chunks already streamed above)
+                                        cluster_for_handler.increment_index_version(path_clone.clone());
+                                        metadata_update_queue_for_handler.lock().unwrap().push((path_clone, entry_clone));
+                                        
+                                        info!("Leader wrote {} bytes to {} ({} new chunks streamed)", written, write_req.path, new_chunks.len());
+                                        events_for_handler.publish(FsEvent::new(FsOp::Modify, &path));
+                                        
+                                        Some(Message::ClientResponse(ClientResponseMsg {
+                                            success: true,
+                                            data: None,
+                                            error: None,
+                                        }))
+                                    }
+                                    Err(e) => {
+                                        tracing::warn!("Leader write error: {}", e);
+                                        Some(Message::ClientResponse(ClientResponseMsg {
+                                            success: false,
+                                            data: None,
+                                            error: Some(format!("Write failed: {}", e)),
+                                        }))
+                                    }
                                 }
                             }
                             Message::CreateFile(create_req) => {
@@ -523,6 +915,7 @@ fn main() {
                                 // Lock ordering: Inode -> Index
                                 let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                 let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &path);
                                 
                                 if index.get(&path).is_some() {
                                     // File already exists
@@ -531,6 +924,16 @@ fn main() {
                                         error: Some("File already exists".to_string()),
                                     }))
                                 } else {
+                                    if let Some(ref quotas) = quotas_for_handler {
+                                        quotas.rebuild_if_stale(&index);
+                                        if let Err(e) = quotas.charge(&path, 0, 1) {
+                                            return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                                success: false,
+                                                error: Some(e.to_string()),
+                                            }));
+                                        }
+                                    }
+                                    
                                     // Create new empty file entry
                                     let entry = FileEntry {
                                         size: 0,
@@ -543,6 +946,11 @@ fn main() {
                                         accessed: std::time::SystemTime::now(),
                                         chunks: Vec::new(),
                                         symlink_target: None,
+                                        xattrs: std::collections::HashMap::new(),
+                                        version_id: None,
+                                        versions: Vec::new(),
+                                        hard_link_count: 1,
+                                        link_id: None,
                                     };
                                     
                                     // Update index
@@ -555,6 +963,7 @@ fn main() {
                                     inode_tbl.insert(ino, path.clone());
                                     
                                     info!("Leader created file: {} with inode {}", create_req.path, ino);
+                                    events_for_handler.publish(FsEvent::new(FsOp::Create, &path));
                                     
                                     // Queue broadcast to followers
                                     cluster_for_handler.increment_index_version(path.clone());
@@ -577,17 +986,26 @@ fn main() {
                                 // Lock ordering: Inode -> Index
                                 let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                 let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &path);
+                                if let Some(ref quotas) = quotas_for_handler {
+                                    quotas.rebuild_if_stale(&index);
+                                }
                                 
-                                if let Some(entry) = index.remove(&path) {
+                                if let Some(entry) = index.unlink(&path) {
                                     // Delete chunks (can do this after dropping locks, or here?)
                                     // For now collect them to delete later or just delete (fast enough usually)
                                     // Or better: drop locks then delete. But we need index lock to remove entry.
-                                    let chunks_to_delete = entry.chunks;
+                                    if let (Some(quotas), false) = (&quotas_for_handler, entry.is_dir) {
+                                        quotas.release(&path, entry.size, 1);
+                                    }
+                                    // Other hard links keep the chunks
+                                    let chunks_to_delete = if entry.hard_link_count == 0 { entry.chunks } else { Vec::new() };
                                     
                                     // Remove from inode table
                                     inode_tbl.remove_path(&path);
                                     
                                     info!("Leader deleted file: {}", del.path);
+                                    events_for_handler.publish(FsEvent::new(FsOp::Delete, &path));
                                     
                                     // Queue delete broadcast to followers
                                     let delete_marker = wolfdisk::storage::FileEntry {
@@ -601,6 +1019,11 @@ fn main() {
                                         accessed: std::time::SystemTime::now(),
                                         chunks: Vec::new(),
                                         symlink_target: None,
+                                        xattrs: std::collections::HashMap::new(),
+                                        version_id: None,
+                                        versions: Vec::new(),
+                                        hard_link_count: 1,
+                                        link_id: None,
                                     };
                                     
                                     // Drop locks before IO
@@ -635,6 +1058,7 @@ fn main() {
                                 // Lock ordering: Inode -> Index
                                 let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                 let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &path);
                                 
                                 if index.get(&path).is_some() {
                                     // Dir already exists
@@ -655,6 +1079,11 @@ fn main() {
                                         accessed: std::time::SystemTime::now(),
                                         chunks: Vec::new(),
                                         symlink_target: None,
+                                        xattrs: std::collections::HashMap::new(),
+                                        version_id: None,
+                                        versions: Vec::new(),
+                                        hard_link_count: 1,
+                                        link_id: None,
                                     };
                                     
                                     // Update index
@@ -667,6 +1096,7 @@ fn main() {
                                     inode_tbl.insert(ino, path.clone());
                                     
                                     info!("Leader created directory: {} with inode {}", dir_req.path, ino);
+                                    events_for_handler.publish(FsEvent::new(FsOp::Create, &path));
                                     
                                     // Queue broadcast to followers
                                     drop(index);
@@ -689,6 +1119,7 @@ fn main() {
                                 // Lock ordering: Inode -> Index
                                 let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                 let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &path);
                                 
                                 match index.get(&path) {
                                     Some(entry) if !entry.is_dir => {
@@ -725,6 +1156,7 @@ fn main() {
                                         inode_tbl.remove_path(&path);
                                         
                                         info!("Leader deleted directory: {}", del.path);
+                                        events_for_handler.publish(FsEvent::new(FsOp::Delete, &path));
                                         
                                         // Queue delete broadcast to followers
                                         let delete_marker = wolfdisk::storage::FileEntry {
@@ -738,6 +1170,11 @@ fn main() {
                                             accessed: std::time::SystemTime::now(),
                                             chunks: Vec::new(),
                                             symlink_target: None,
+                                            xattrs: std::collections::HashMap::new(),
+                                            version_id: None,
+                                            versions: Vec::new(),
+                                            hard_link_count: 1,
+                                            link_id: None,
                                         };
                                         drop(index);
                                         drop(inode_tbl);
@@ -762,6 +1199,11 @@ fn main() {
                                 // Lock ordering: Inode -> Index
                                 let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                 let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &from_path);
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &to_path);
+                                if let Some(ino) = inode_tbl.get_inode(&from_path) {
+                                    dir_cache_for_handler.invalidate(ino);
+                                }
                                 
                                 // Check source exists
                                 let entry = match index.get(&from_path) {
@@ -774,6 +1216,17 @@ fn main() {
                                     }
                                 };
                                 
+                                // Moving a file under another quota's directory is charged there
+                                if let (Some(quotas), false) = (&quotas_for_handler, entry.is_dir) {
+                                    quotas.rebuild_if_stale(&index);
+                                    if let Err(e) = quotas.transfer(&from_path, &to_path, entry.size, 1) {
+                                        return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: false,
+                                            error: Some(e.to_string()),
+                                        }));
+                                    }
+                                }
+                                
                                 // Handle overwrite
                                 if let Some(target_entry) = index.get(&to_path) {
                                      // Check if directory is empty
@@ -787,18 +1240,25 @@ fn main() {
                                          }
                                      }
                                      
-                                     // Delete chunks of target
-                                     for chunk in &target_entry.chunks {
-                                          let _ = chunk_store_for_handler.delete(&chunk.hash);
+                                     if let (Some(quotas), false) = (&quotas_for_handler, target_entry.is_dir) {
+                                          quotas.release(&to_path, target_entry.size, 1);
                                      }
                                      
-                                     // Remove target from index/inode
-                                     index.remove(&to_path);
+                                     // Remove target from index/inode, and its chunks unless
+                                     // another hard link still uses them
+                                     if let Some(target_entry) = index.unlink(&to_path) {
+                                          if target_entry.hard_link_count == 0 {
+                                               for chunk in &target_entry.chunks {
+                                                    let _ = chunk_store_for_handler.delete(&chunk.hash);
+                                               }
+                                          }
+                                     }
                                      inode_tbl.remove_path(&to_path);
                                 }
                                 
-                                // Move entry in index
-                                index.remove(&from_path);
+                                // Move entry in index (re-read: unlinking the target may
+                                // have changed its link count)
+                                let entry = index.remove(&from_path).unwrap_or(entry);
                                 index.insert(to_path.clone(), entry.clone());
                                 
                                 // Update inode table
@@ -810,6 +1270,7 @@ fn main() {
                                 }
                                 
                                 info!("Leader renamed: {} -> {}", rename_req.from_path, rename_req.to_path);
+                                events_for_handler.publish(FsEvent::rename(&from_path, &to_path));
                                 
                                 // Queue broadcast: delete old path, sync new path
                                 let delete_marker = wolfdisk::storage::FileEntry {
@@ -823,6 +1284,11 @@ fn main() {
                                     accessed: std::time::SystemTime::now(),
                                     chunks: Vec::new(),
                                     symlink_target: None,
+                                    xattrs: std::collections::HashMap::new(),
+                                    version_id: None,
+                                    versions: Vec::new(),
+                                    hard_link_count: 1,
+                                    link_id: None,
                                 };
                                 drop(index);
                                 drop(inode_tbl);
@@ -837,6 +1303,100 @@ fn main() {
                                     error: None,
                                 }))
                             }
+                            Message::CopyFile(copy_req) => {
+                                // Handle server-side copy (if we're leader). The copy shares
+                                // the source's chunks, so followers only need the metadata.
+                                info!("Received CopyFile: {} -> {}", copy_req.from_path, copy_req.to_path);
+                                
+                                let from_path = std::path::PathBuf::from(&copy_req.from_path);
+                                let to_path = std::path::PathBuf::from(&copy_req.to_path);
+                                
+                                // Lock ordering: Inode -> Index
+                                let mut inode_tbl = inode_table_for_handler.write().unwrap();
+                                let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &to_path);
+                                
+                                let mut entry = match index.get(&from_path) {
+                                    Some(e) if !e.is_dir => e.clone(),
+                                    Some(_) => {
+                                        return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: false,
+                                            error: Some("Source is a directory".to_string()),
+                                        }));
+                                    }
+                                    None => {
+                                        return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: false,
+                                            error: Some("Source not found".to_string()),
+                                        }));
+                                    }
+                                };
+                                
+                                let existing = index.get(&to_path).cloned();
+                                if existing.as_ref().is_some_and(|e| e.is_dir) {
+                                    return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                        success: false,
+                                        error: Some("Destination is a directory".to_string()),
+                                    }));
+                                }
+                                
+                                if let Some(ref quotas) = quotas_for_handler {
+                                    quotas.rebuild_if_stale(&index);
+                                    if let Some(ref old) = existing {
+                                        quotas.release(&to_path, old.size, 1);
+                                    }
+                                    if let Err(e) = quotas.charge(&to_path, entry.size, 1) {
+                                        if let Some(ref old) = existing {
+                                            let _ = quotas.charge(&to_path, old.size, 1);
+                                        }
+                                        return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: false,
+                                            error: Some(e.to_string()),
+                                        }));
+                                    }
+                                }
+                                
+                                // Drop the overwritten file's chunks unless the copy still uses them
+                                if let Some(ref old) = existing {
+                                    for chunk in &old.chunks {
+                                        if !entry.chunks.iter().any(|c| c.hash == chunk.hash) {
+                                            let _ = chunk_store_for_handler.delete(&chunk.hash);
+                                        }
+                                    }
+                                }
+                                
+                                let now = std::time::SystemTime::now();
+                                entry.created = now;
+                                entry.modified = now;
+                                entry.accessed = now;
+                                entry.version_id = None;
+                                entry.versions = Vec::new();
+                                // A copy is a new file, not another link to the source
+                                entry.link_id = None;
+                                entry.hard_link_count = 1;
+                                index.insert(to_path.clone(), entry.clone());
+                                
+                                if existing.is_none() {
+                                    let mut next_ino = next_inode_for_handler.write().unwrap();
+                                    let ino = *next_ino;
+                                    *next_ino += 1;
+                                    inode_tbl.insert(ino, to_path.clone());
+                                }
+                                
+                                info!("Leader copied: {} -> {}", copy_req.from_path, copy_req.to_path);
+                                let op = if existing.is_some() { FsOp::Modify } else { FsOp::Create };
+                                events_for_handler.publish(FsEvent::new(op, &to_path));
+                                
+                                drop(index);
+                                drop(inode_tbl);
+                                cluster_for_handler.increment_index_version(to_path.clone());
+                                metadata_update_queue_for_handler.lock().unwrap().push((to_path, entry));
+                                
+                                Some(Message::FileOpResponse(FileOpResponseMsg {
+                                    success: true,
+                                    error: None,
+                                }))
+                            }
                             Message::CreateSymlink(symlink_req) => {
                                 // Handle incoming symlink request (if we're leader)
                                 info!("Received CreateSymlink: {} -> {}", symlink_req.link_path, symlink_req.target);
@@ -846,6 +1406,7 @@ fn main() {
                                 // Lock ordering: Inode -> Index
                                 let mut inode_tbl = inode_table_for_handler.write().unwrap();
                                 let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &link_path);
                                 
                                 // Check link doesn't exist
                                 if index.contains(&link_path) {
@@ -855,8 +1416,18 @@ fn main() {
                                     }));
                                 }
                                 
-                                // Create symlink entry
-                                let entry = wolfdisk::storage::FileEntry {
+                                if let Some(ref quotas) = quotas_for_handler {
+                                    quotas.rebuild_if_stale(&index);
+                                    if let Err(e) = quotas.charge(&link_path, symlink_req.target.len() as u64, 1) {
+                                        return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: false,
+                                            error: Some(e.to_string()),
+                                        }));
+                                    }
+                                }
+                                
+                                // Create symlink entry
+                                let entry = wolfdisk::storage::FileEntry {
                                     size: symlink_req.target.len() as u64,
                                     is_dir: false,
                                     permissions: 0o777,
@@ -867,6 +1438,11 @@ fn main() {
                                     accessed: std::time::SystemTime::now(),
                                     chunks: Vec::new(),
                                     symlink_target: Some(symlink_req.target.clone()),
+                                    xattrs: std::collections::HashMap::new(),
+                                    version_id: None,
+                                    versions: Vec::new(),
+                                    hard_link_count: 1,
+                                    link_id: None,
                                 };
                                 
                                 // Insert into index
@@ -879,6 +1455,7 @@ fn main() {
                                 inode_tbl.insert(inode, link_path.clone());
                                 
                                 info!("Leader created symlink: {} -> {}", symlink_req.link_path, symlink_req.target);
+                                events_for_handler.publish(FsEvent::new(FsOp::Create, &link_path));
                                 
                                 // Queue broadcast
                                 drop(index);
@@ -891,6 +1468,65 @@ fn main() {
                                     error: None,
                                 }))
                             }
+                            Message::CreateHardLink(link_req) => {
+                                // Handle incoming hard link request (if we're leader)
+                                info!("Received CreateHardLink: {} -> {}", link_req.dst_path, link_req.src_path);
+                                
+                                let src_path = std::path::PathBuf::from(&link_req.src_path);
+                                let dst_path = std::path::PathBuf::from(&link_req.dst_path);
+                                
+                                // Lock ordering: Inode -> Index
+                                let mut inode_tbl = inode_table_for_handler.write().unwrap();
+                                let mut index = file_index_for_handler.write().unwrap();
+                                dir_cache_for_handler.invalidate_parent(&inode_tbl, &dst_path);
+                                
+                                if let (Some(quotas), Some(source)) = (&quotas_for_handler, index.get(&src_path)) {
+                                    quotas.rebuild_if_stale(&index);
+                                    if let Err(e) = quotas.charge(&dst_path, source.size, 1) {
+                                        return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: false,
+                                            error: Some(e.to_string()),
+                                        }));
+                                    }
+                                }
+                                
+                                if let Err(e) = index.link(&src_path, dst_path.clone()) {
+                                    if let (Some(quotas), Some(source)) = (&quotas_for_handler, index.get(&src_path)) {
+                                        quotas.release(&dst_path, source.size, 1);
+                                    }
+                                    return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                        success: false,
+                                        error: Some(e.to_string()),
+                                    }));
+                                }
+                                
+                                // Both names share the source's inode
+                                match inode_tbl.get_inode(&src_path) {
+                                    Some(ino) => inode_tbl.insert_link(ino, dst_path.clone()),
+                                    None => {
+                                        let mut next_ino = next_inode_for_handler.write().unwrap();
+                                        let ino = *next_ino;
+                                        *next_ino += 1;
+                                        inode_tbl.insert(ino, src_path);
+                                        inode_tbl.insert_link(ino, dst_path.clone());
+                                    }
+                                }
+                                
+                                info!("Leader linked: {} -> {}", link_req.dst_path, link_req.src_path);
+                                events_for_handler.publish(FsEvent::new(FsOp::Create, &dst_path));
+                                
+                                drop(index);
+                                drop(inode_tbl);
+                                index_update_queue_for_handler.lock().unwrap().push(IndexOperation::Link {
+                                    src_path: link_req.src_path,
+                                    dst_path: link_req.dst_path,
+                                });
+                                
+                                Some(Message::FileOpResponse(FileOpResponseMsg {
+                                    success: true,
+                                    error: None,
+                                }))
+                            }
                             Message::SetAttr(setattr_req) => {
                                 // Handle setattr request (truncation, chmod, chown, etc.)
                                 info!("Received SetAttr from {}: {} (size={:?})", 
@@ -899,54 +1535,172 @@ fn main() {
                                 let path = std::path::PathBuf::from(&setattr_req.path);
                                 let mut index = file_index_for_handler.write().unwrap();
                                 
-                                if let Some(entry) = index.get_mut(&path) {
-                                    // Handle truncation
-                                    if let Some(new_size) = setattr_req.size {
-                                        if new_size == 0 {
-                                            // Full truncation: delete all chunks
-                                            for chunk in &entry.chunks {
-                                                let _ = chunk_store_for_handler.delete(&chunk.hash);
-                                            }
-                                            entry.chunks.clear();
-                                            entry.size = 0;
-                                        } else if new_size < entry.size {
-                                            // Partial truncation
-                                            entry.chunks.retain(|chunk| chunk.offset < new_size);
-                                            entry.size = new_size;
-                                        } else {
-                                            entry.size = new_size;
-                                        }
-                                    }
-                                    
-                                    if let Some(perms) = setattr_req.permissions {
-                                        entry.permissions = perms;
+                                if let (Some(quotas), Some(new_size), Some(old_size)) =
+                                    (&quotas_for_handler, setattr_req.size, index.get(&path).map(|e| e.size))
+                                {
+                                    quotas.rebuild_if_stale(&index);
+                                    if let Err(e) = quotas.resize(&path, old_size, new_size) {
+                                        return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: false,
+                                            error: Some(e.to_string()),
+                                        }));
                                     }
-                                    if let Some(uid) = setattr_req.uid {
-                                        entry.uid = uid;
+                                }
+                                
+                                let Some(mut entry) = index.get_mut(&path) else {
+                                    return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                        success: false,
+                                        error: Some("File not found".to_string()),
+                                    }));
+                                };
+                                // Handle truncation
+                                if let Some(new_size) = setattr_req.size {
+                                    if new_size == 0 {
+                                        // Full truncation: delete all chunks
+                                        for chunk in &entry.chunks {
+                                            let _ = chunk_store_for_handler.delete(&chunk.hash);
+                                        }
+                                        entry.chunks.clear();
+                                        entry.size = 0;
+                                    } else if new_size < entry.size {
+                                        // Partial truncation
+                                        entry.chunks.retain(|chunk| chunk.offset < new_size);
+                                        entry.size = new_size;
+                                    } else {
+                                        entry.size = new_size;
                                     }
-                                    if let Some(gid) = setattr_req.gid {
-                                        entry.gid = gid;
+                                }
+                                
+                                if let Some(perms) = setattr_req.permissions {
+                                    entry.set_mode(perms);
+                                }
+                                if let Some(uid) = setattr_req.uid {
+                                    entry.uid = uid;
+                                }
+                                if let Some(gid) = setattr_req.gid {
+                                    entry.gid = gid;
+                                }
+                                if let Some(mtime_ms) = setattr_req.modified_ms {
+                                    entry.modified = std::time::UNIX_EPOCH + std::time::Duration::from_millis(mtime_ms);
+                                }
+                                
+                                info!("Leader applied setattr to {}", setattr_req.path);
+                                events_for_handler.publish(FsEvent::new(FsOp::Modify, &path));
+                                
+                                // Queue broadcast to followers
+                                let entry_clone = entry.clone();
+                                drop(entry);
+                                drop(index);
+                                broadcast_queue_for_handler.lock().unwrap().push((path, entry_clone));
+                                
+                                Some(Message::FileOpResponse(FileOpResponseMsg {
+                                    success: true,
+                                    error: None,
+                                }))
+                            }
+                            Message::Fallocate(fallocate_req) => {
+                                info!("Received Fallocate from {}: {} (mode={:#x}, offset={}, length={})",
+                                    peer_id, fallocate_req.path, fallocate_req.mode, fallocate_req.offset, fallocate_req.length);
+                                
+                                let path = std::path::PathBuf::from(&fallocate_req.path);
+                                let mut index = file_index_for_handler.write().unwrap();
+                                let Some(mut entry) = index.get(&path).cloned() else {
+                                    return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                        success: false,
+                                        error: Some("File not found".to_string()),
+                                    }));
+                                };
+                                
+                                let old_size = entry.size;
+                                let result = chunk_store_for_handler
+                                    .fallocate(&mut entry.chunks, &mut entry.size, fallocate_req.mode, fallocate_req.offset, fallocate_req.length)
+                                    .and_then(|_| match &quotas_for_handler {
+                                        Some(quotas) => {
+                                            quotas.rebuild_if_stale(&index);
+                                            quotas.resize(&path, old_size, entry.size)
+                                        }
+                                        None => Ok(()),
+                                    });
+                                if let Err(e) = result {
+                                    return Some(Message::FileOpResponse(FileOpResponseMsg {
+                                        success: false,
+                                        error: Some(e.to_string()),
+                                    }));
+                                }
+                                
+                                entry.modified = std::time::SystemTime::now();
+                                index.insert(path.clone(), entry.clone());
+                                drop(index);
+                                events_for_handler.publish(FsEvent::new(FsOp::Modify, &path));
+                                broadcast_queue_for_handler.lock().unwrap().push((path, entry));
+                                
+                                Some(Message::FileOpResponse(FileOpResponseMsg {
+                                    success: true,
+                                    error: None,
+                                }))
+                            }
+                            Message::SetXattr(xattr_req) => {
+                                info!("Received SetXattr from {}: {} ({})", peer_id, xattr_req.path, xattr_req.name);
+                                
+                                let path = std::path::PathBuf::from(&xattr_req.path);
+                                let mut index = file_index_for_handler.write().unwrap();
+                                
+                                let result = match index.get_mut(&path) {
+                                    Some(mut entry) => entry
+                                        .update_xattr(&xattr_req.name, Some(xattr_req.value.clone()), xattr_req.flags)
+                                        .map_err(|errno| std::io::Error::from_raw_os_error(errno).to_string()),
+                                    None => Err("File not found".to_string()),
+                                };
+                                drop(index);
+                                
+                                match result {
+                                    Ok(()) => {
+                                        index_update_queue_for_handler.lock().unwrap().push(IndexOperation::SetXattr {
+                                            path: xattr_req.path,
+                                            name: xattr_req.name,
+                                            value: Some(xattr_req.value),
+                                        });
+                                        Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: true,
+                                            error: None,
+                                        }))
                                     }
-                                    if let Some(mtime_ms) = setattr_req.modified_ms {
-                                        entry.modified = std::time::UNIX_EPOCH + std::time::Duration::from_millis(mtime_ms);
+                                    Err(e) => Some(Message::FileOpResponse(FileOpResponseMsg {
+                                        success: false,
+                                        error: Some(e),
+                                    })),
+                                }
+                            }
+                            Message::RemoveXattr(xattr_req) => {
+                                info!("Received RemoveXattr from {}: {} ({})", peer_id, xattr_req.path, xattr_req.name);
+                                
+                                let path = std::path::PathBuf::from(&xattr_req.path);
+                                let mut index = file_index_for_handler.write().unwrap();
+                                
+                                let result = match index.get_mut(&path) {
+                                    Some(mut entry) => entry
+                                        .update_xattr(&xattr_req.name, None, 0)
+                                        .map_err(|errno| std::io::Error::from_raw_os_error(errno).to_string()),
+                                    None => Err("File not found".to_string()),
+                                };
+                                drop(index);
+                                
+                                match result {
+                                    Ok(()) => {
+                                        index_update_queue_for_handler.lock().unwrap().push(IndexOperation::SetXattr {
+                                            path: xattr_req.path,
+                                            name: xattr_req.name,
+                                            value: None,
+                                        });
+                                        Some(Message::FileOpResponse(FileOpResponseMsg {
+                                            success: true,
+                                            error: None,
+                                        }))
                                     }
-                                    
-                                    info!("Leader applied setattr to {}", setattr_req.path);
-                                    
-                                    // Queue broadcast to followers
-                                    let entry_clone = entry.clone();
-                                    drop(index);
-                                    broadcast_queue_for_handler.lock().unwrap().push((path, entry_clone));
-                                    
-                                    Some(Message::FileOpResponse(FileOpResponseMsg {
-                                        success: true,
-                                        error: None,
-                                    }))
-                                } else {
-                                    Some(Message::FileOpResponse(FileOpResponseMsg {
+                                    Err(e) => Some(Message::FileOpResponse(FileOpResponseMsg {
                                         success: false,
-                                        error: Some("File not found".to_string()),
-                                    }))
+                                        error: Some(e),
+                                    })),
                                 }
                             }
                             Message::ReadRequest(read_req) => {
@@ -959,10 +1713,12 @@ fn main() {
                                 match index.get(&file_path) {
                                     Some(entry) => {
                                         let chunks = entry.chunks.clone();
+                                        let file_size = entry.size;
                                         drop(index);
                                         
                                         match chunk_store_for_handler.read(&chunks, read_req.offset, read_req.size as usize) {
-                                            Ok(data) => {
+                                            Ok(mut data) => {
+                                                wolfdisk::storage::ChunkStore::fill_to_size(&mut data, file_size, read_req.offset, read_req.size as usize);
                                                 Some(Message::ClientResponse(ClientResponseMsg {
                                                     success: true,
                                                     data: Some(data),
@@ -1215,6 +1971,38 @@ fn main() {
                                     }))
                                 }
                             }
+                            Message::ChunkInventory(_) => {
+                                // Rebalancer on the leader asking what this node holds
+                                let hashes = chunk_store_for_handler.local_chunks()
+                                    .map(|chunks| chunks.into_iter().map(|c| c.hash).collect())
+                                    .unwrap_or_else(|e| {
+                                        tracing::warn!("Failed to list local chunks: {}", e);
+                                        Vec::new()
+                                    });
+                                Some(Message::ChunkInventoryResponse(ChunkInventoryResponseMsg { hashes }))
+                            }
+                            Message::MigrateChunk(migrate) => {
+                                // Chunk moved here by the rebalancer; confirm before the source drops it
+                                let result = chunk_store_for_handler.store_with_hash(&migrate.hash, &migrate.data);
+                                if let Err(ref e) = result {
+                                    tracing::warn!("Failed to store migrated chunk {}: {}", hex::encode(migrate.hash), e);
+                                }
+                                Some(Message::StoreChunkAck(StoreChunkAckMsg {
+                                    hash: migrate.hash,
+                                    success: result.is_ok(),
+                                    error: result.err().map(|e| e.to_string()),
+                                }))
+                            }
+                            Message::DeleteChunk(delete) => {
+                                // The rebalancer moved this chunk to another node
+                                if !cluster_for_handler.is_leader() {
+                                    debug!("Dropping chunk {} moved off this node", hex::encode(delete.hash));
+                                    if let Err(e) = chunk_store_for_handler.delete(&delete.hash) {
+                                        tracing::warn!("Failed to delete chunk {}: {}", hex::encode(delete.hash), e);
+                                    }
+                                }
+                                None
+                            }
                             Message::GetChunk(get_chunk) => {
                                 // Handle chunk fetch request from follower
                                 debug!("Received GetChunk request from {} for {}", peer_id, hex::encode(&get_chunk.hash));
@@ -1236,6 +2024,9 @@ fn main() {
                                     }
                                 }
                             }
+                            Message::RequestVote(request) => {
+                                Some(cluster_for_handler.handle_request_vote(&request))
+                            }
                             _ => {
                                 debug!("Unhandled message from {}: {:?}", peer_id, msg);
                                 None
@@ -1243,8 +2034,23 @@ fn main() {
                         }
                     },
                 )
+                .with_bandwidth_limit(
+                    config.replication.max_bandwidth_mbps,
+                    config.replication.burst_mb * 1024 * 1024,
+                )
+                .with_chunk_files({
+                    let chunk_store = chunk_store.clone();
+                    move |hash| chunk_store.local_chunk_path(hash)
+                })
             );
             
+            if config.replication.max_bandwidth_mbps > 0 {
+                info!(
+                    "Replication bandwidth limited to {} Mbps per peer (burst {} MB)",
+                    config.replication.max_bandwidth_mbps, config.replication.burst_mb
+                );
+            }
+            
             // Start peer manager to listen for connections
             if let Err(e) = peer_manager.start() {
                 error!("Failed to start peer manager: {}", e);
@@ -1258,10 +2064,16 @@ fn main() {
             let broadcast_queue_for_thread = broadcast_queue.clone();
             let chunk_stream_queue_for_thread = chunk_stream_queue.clone();
             let metadata_update_queue_for_thread = metadata_update_queue.clone();
+            let index_update_queue_for_thread = index_update_queue.clone();
             let cluster_for_broadcast = cluster.clone();
             let file_index_for_broadcast = file_index.clone();
+            let sync_progress_for_broadcast = sync_progress.clone();
+            let replication_lag_for_broadcast = replication_lag.clone();
+            let replication_for_broadcast = config.replication.clone();
+            let rack_for_broadcast = config.node.rack.clone();
             std::thread::spawn(move || {
-                use wolfdisk::network::protocol::{Message, FileSyncMsg, ChunkWithData, StoreChunkMsg, ChunkRefMsg};
+                use wolfdisk::replication::chunk_targets;
+                use wolfdisk::network::protocol::{Message, FileSyncMsg, ChunkWithData, StoreChunkMsg, ChunkRefMsg, IndexUpdateMsg, IndexOperation};
                 loop {
                     // Check queues every 50ms
                     std::thread::sleep(std::time::Duration::from_millis(50));
@@ -1309,19 +2121,28 @@ fn main() {
                         let total_bytes: usize = pending_chunks.iter().map(|(_, d)| d.len()).sum();
                         debug!("Broadcasting {} chunks ({} bytes)", pending_chunks.len(), total_bytes);
 
-                        for (hash, data) in &pending_chunks {
+                        // Optimization: Only send chunks to followers (storage nodes).
+                        // Clients don't store chunks locally, so sending them data is a waste of bandwidth.
+                        // With rack-aware placement only the followers chosen for this chunk get it.
+                        let targets: Vec<_> = pending_chunks.iter()
+                            .map(|(hash, _)| chunk_targets(&replication_for_broadcast, rack_for_broadcast.as_deref(), &peers, hash))
+                            .collect();
+                        let mut round = std::collections::BTreeMap::new();
+                        for peer in targets.iter().flatten() {
+                            *round.entry(peer.node_id.clone()).or_insert(0) += 1;
+                        }
+                        replication_lag_for_broadcast.start_round(round);
+
+                        for ((hash, data), peers) in pending_chunks.iter().zip(&targets) {
                             let msg = Message::StoreChunk(StoreChunkMsg {
                                 hash: *hash,
                                 data: data.clone(),
                             });
                             
-                            // Optimization: Only send chunks to followers (storage nodes).
-                            // Clients don't store chunks locally, so sending them data is a waste of bandwidth.
-                            for peer in &peers {
-                                if peer.is_client {
-                                    continue;
+                            for peer in peers {
+                                if peer_manager_for_broadcast.send_to(&peer.node_id, &msg).is_ok() {
+                                    replication_lag_for_broadcast.delivered(&peer.node_id);
                                 }
-                                let _ = peer_manager_for_broadcast.send_to(&peer.node_id, &msg);
                             }
                         }
                     }
@@ -1366,6 +2187,13 @@ fn main() {
                         queue.drain(..).collect()
                     };
                     
+                    // Count the whole batch up front so progress has a meaningful total
+                    for (path, entry) in &pending {
+                        if entry.size != u64::MAX && !entry.chunks.is_empty() {
+                            sync_progress_for_broadcast.file_started(&path.to_string_lossy(), entry.size);
+                        }
+                    }
+                    
                     for (path, mut entry) in pending {
                         // Re-read the entry from the current index to get the latest state.
                         {
@@ -1436,6 +2264,17 @@ fn main() {
                                     }
                                 }
 
+                                // Rack-aware placement: which of this batch's chunks each follower stores
+                                let placed_by_peer = replication_for_broadcast.rack_aware.then(|| {
+                                    let mut placed: std::collections::HashMap<String, Vec<ChunkWithData>> = std::collections::HashMap::new();
+                                    for chunk in &chunks_with_data {
+                                        for target in chunk_targets(&replication_for_broadcast, rack_for_broadcast.as_deref(), &peers, &chunk.hash) {
+                                            placed.entry(target.node_id.clone()).or_default().push(chunk.clone());
+                                        }
+                                    }
+                                    placed
+                                });
+
                                 // Create two messages: one with data (for followers), one without (for clients)
                                 let msg_full = Message::FileSync(FileSyncMsg {
                                     path: path.to_string_lossy().to_string(),
@@ -1470,13 +2309,53 @@ fn main() {
                                     if peer.is_client {
                                         // Clients get lightweight metadata msg (avoids flooding them with data they don't store)
                                         let _ = peer_manager_for_broadcast.send_to(&peer.node_id, &msg_meta);
+                                    } else if let Some(ref placed) = placed_by_peer {
+                                        // Followers get only the chunks placed on them and fetch the rest on demand
+                                        let msg_placed = Message::FileSync(FileSyncMsg {
+                                            path: path.to_string_lossy().to_string(),
+                                            size: entry.size,
+                                            is_dir: entry.is_dir,
+                                            permissions: entry.permissions,
+                                            uid: entry.uid,
+                                            gid: entry.gid,
+                                            modified_ms,
+                                            chunks: if batch_idx == 0 { chunk_refs.clone() } else { Vec::new() },
+                                            chunk_data: placed.get(&peer.node_id).cloned().unwrap_or_default(),
+                                        });
+                                        let _ = peer_manager_for_broadcast.send_to(&peer.node_id, &msg_placed);
                                     } else {
                                         // Followers get full data
                                         let _ = peer_manager_for_broadcast.send_to(&peer.node_id, &msg_full);
                                     }
                                 }
+                                
+                                let batch_bytes: u64 = chunk_batch.iter().map(|c| c.size as u64).sum();
+                                sync_progress_for_broadcast.file_progress(&path.to_string_lossy(), batch_bytes);
                             }
                         }
+                        sync_progress_for_broadcast.file_done(&path.to_string_lossy());
+                    }
+                    
+                    // Finally, index-only operations (after the entries they refer to exist)
+                    let pending_ops: Vec<_> = {
+                        let mut queue = index_update_queue_for_thread.lock().unwrap();
+                        queue.drain(..).collect()
+                    };
+                    
+                    for operation in pending_ops {
+                        let op_path = match &operation {
+                            IndexOperation::Upsert { path, .. } => std::path::PathBuf::from(path),
+                            IndexOperation::Delete { path } => std::path::PathBuf::from(path),
+                            IndexOperation::Mkdir { path, .. } => std::path::PathBuf::from(path),
+                            IndexOperation::Rename { to_path, .. } => std::path::PathBuf::from(to_path),
+                            IndexOperation::SetXattr { path, .. } => std::path::PathBuf::from(path),
+                            IndexOperation::Link { dst_path, .. } => std::path::PathBuf::from(dst_path),
+                        };
+                        let version = cluster_for_broadcast.increment_index_version(op_path);
+                        peer_manager_for_broadcast.broadcast(&Message::IndexUpdate(IndexUpdateMsg {
+                            version,
+                            operation,
+                        }));
                     }
                 }
             });
@@ -1495,8 +2374,9 @@ fn main() {
                 let sync_file_index = file_index.clone();
                 let sync_inode_table = inode_table.clone();
                 let sync_next_inode = next_inode.clone();
+                let sync_dir_cache = dir_cache.clone();
                 let sync_is_client = config.node.role == wolfdisk::config::NodeRole::Client;
-                let _sync_chunk_store = chunk_store.clone();
+                let sync_chunk_store = chunk_store.clone();
                 let sync_node_id = config.node.id.clone();
                 
                 std::thread::spawn(move || {
@@ -1611,6 +2491,7 @@ fn main() {
                                                     hash: c.hash,
                                                     offset: c.offset,
                                                     size: c.size,
+                                                    tier: Tier::Hot,
                                                 })
                                                 .collect();
                                             
@@ -1637,6 +2518,11 @@ fn main() {
                                                     accessed: std::time::SystemTime::now(),
                                                     chunks: chunk_refs,
                                                     symlink_target: None,
+                                                    xattrs: std::collections::HashMap::new(),
+                                                    version_id: None,
+                                                    versions: Vec::new(),
+                                                    hard_link_count: 1,
+                                                    link_id: None,
                                                 };
                                                 
                                                 index.insert(path.clone(), entry);
@@ -1655,6 +2541,8 @@ fn main() {
                                                 unchanged += 1;
                                             }
                                         }
+                                        // Bulk load: drop every cached listing rather than tracking each directory
+                                        sync_dir_cache.invalidate_all();
                                         // Locks dropped here — FUSE ops can proceed between batches
                                     }
                                     
@@ -1669,17 +2557,29 @@ fn main() {
                                             let mut inode_tbl = sync_inode_table.write().unwrap();
                                             for del_path_str in del_batch {
                                                 let del_path = std::path::PathBuf::from(del_path_str);
-                                                if index.remove(&del_path).is_some() {
+                                                if index.unlink(&del_path).is_some() {
                                                     inode_tbl.remove_path(&del_path);
                                                     removed += 1;
                                                 }
                                             }
+                                            sync_dir_cache.invalidate_all();
                                             // Locks dropped between batches
                                         }
                                         if removed > 0 {
                                             info!("Initial sync: removed {} deleted entries from leader changelog", removed);
                                         }
                                     }
+                                    
+                                    // Fetch chunks written while we were away before declaring
+                                    // the sync complete. Clients read chunks from the leader instead.
+                                    if !sync_is_client {
+                                        let missing = wolfdisk::replication::missing_chunks(&sync_chunk_store, &response.entries);
+                                        if let Err(e) = wolfdisk::replication::catch_up_from_leader(
+                                            &sync_chunk_store, &leader_id, &leader_addr, &missing, |_| {},
+                                        ) {
+                                            warn!("Chunk catch-up incomplete: {} (missing chunks will be fetched on read)", e);
+                                        }
+                                    }
                                 }
                                 Ok(other) => {
                                     warn!("Unexpected response to SyncRequest: {:?}", other);
@@ -1707,12 +2607,15 @@ fn main() {
                 let resync_file_index = file_index.clone();
                 let resync_inode_table = inode_table.clone();
                 let resync_next_inode = next_inode.clone();
+                let resync_dir_cache = dir_cache.clone();
                 let resync_node_id = config.node.id.clone();
+                let resync_chunk_store = chunk_store.clone();
+                let resync_is_client = config.node.role == wolfdisk::config::NodeRole::Client;
                 
                 std::thread::spawn(move || {
                     use wolfdisk::network::protocol::*;
                     use wolfdisk::storage::{ChunkRef, FileEntry};
-                    use tracing::{info, debug};
+                    use tracing::{info, debug, warn};
                     
                     // Wait for initial sync to complete first
                     std::thread::sleep(std::time::Duration::from_secs(15));
@@ -1778,6 +2681,7 @@ fn main() {
                                                 hash: c.hash,
                                                 offset: c.offset,
                                                 size: c.size,
+                                                tier: Tier::Hot,
                                             })
                                             .collect();
                                         
@@ -1792,12 +2696,18 @@ fn main() {
                                             accessed: std::time::SystemTime::now(),
                                             chunks: chunk_refs,
                                             symlink_target: None,
+                                            xattrs: std::collections::HashMap::new(),
+                                            version_id: None,
+                                            versions: Vec::new(),
+                                            hard_link_count: 1,
+                                            link_id: None,
                                         };
                                         
                                         // Only update if missing or if leader has newer/different data
                                         match index.get(&path) {
                                             None => {
                                                 index.insert(path.clone(), new_entry);
+                                                resync_dir_cache.invalidate_parent(&inode_tbl, &path);
                                                 let ino = *next_ino;
                                                 *next_ino += 1;
                                                 inode_tbl.insert(ino, path.clone());
@@ -1806,6 +2716,7 @@ fn main() {
                                             Some(existing) if existing.size != entry_msg.size 
                                                 || existing.chunks.len() != entry_msg.chunks.len() => {
                                                 index.insert(path.clone(), new_entry);
+                                                resync_dir_cache.invalidate_parent(&inode_tbl, &path);
                                                 // Ensure inode exists
                                                 if inode_tbl.get_inode(&path).is_none() {
                                                     let ino = *next_ino;
@@ -1828,7 +2739,8 @@ fn main() {
                                         let mut inode_tbl = resync_inode_table.write().unwrap();
                                         for del_path_str in del_batch {
                                             let del_path = std::path::PathBuf::from(del_path_str);
-                                            if index.remove(&del_path).is_some() {
+                                            if index.unlink(&del_path).is_some() {
+                                                resync_dir_cache.invalidate_parent(&inode_tbl, &del_path);
                                                 inode_tbl.remove_path(&del_path);
                                                 removed += 1;
                                             }
@@ -1837,6 +2749,16 @@ fn main() {
                                     }
                                 }
                                 
+                                // A partitioned node learns about missed files here; pull their chunks too
+                                if !resync_is_client {
+                                    let missing = wolfdisk::replication::missing_chunks(&resync_chunk_store, &response.entries);
+                                    if let Err(e) = wolfdisk::replication::catch_up_from_leader(
+                                        &resync_chunk_store, &leader_id, &leader_addr, &missing, |_| {},
+                                    ) {
+                                        warn!("Periodic re-sync: chunk catch-up incomplete: {}", e);
+                                    }
+                                }
+                                
                                 // Update our version to the leader's version
                                 last_synced_version = response.current_version;
                                 
@@ -1855,42 +2777,29 @@ fn main() {
                 });
             }
             
-            // Create filesystem instance with cluster support (using shared state)
-            let fs = match WolfDiskFS::with_cluster(
-                config.clone(),
-                Some(cluster.clone()),
-                Some(peer_manager.clone()),
-                file_index.clone(),
-                chunk_store.clone(),
-                inode_table.clone(),
-                next_inode.clone(),
-            ) {
-                Ok(fs) => fs,
-                Err(e) => {
-                    error!("Failed to create filesystem: {}", e);
-                    std::process::exit(1);
-                }
-            };
-
-            // Mount options
-            let options = vec![
-                fuser::MountOption::FSName("wolfdisk".to_string()),
-                fuser::MountOption::AutoUnmount,
-                fuser::MountOption::AllowOther,
-            ];
-
             // Start status file writer thread for wolfdiskctl
             let status_cluster = cluster.clone();
             let status_file_index = file_index.clone();
+            let status_dir_cache = dir_cache.clone();
+            let status_quotas = quotas.clone();
             std::thread::spawn(move || {
                 while std::sync::Arc::strong_count(&status_cluster) > 1 {
+                    // Only the leader tracks quota usage; anything else recounts once it takes over
+                    if let (Some(quotas), false) = (&status_quotas, status_cluster.is_leader()) {
+                        quotas.mark_stale();
+                    }
                     let (file_count, total_size) = {
                         let index = status_file_index.read().unwrap();
                         let count = index.len();
                         let size: u64 = index.iter().map(|(_, e)| e.size).sum();
                         (count, size)
                     };
-                    status_cluster.write_status_file_with_stats(file_count, total_size);
+                    status_cluster.write_status_file_with_stats(file_count, total_size, &[
+                        ("wolfdisk_readdir_cache_hits_total", status_dir_cache.hits()),
+                        ("wolfdisk_readdir_cache_misses_total", status_dir_cache.misses()),
+                        ("wolfdisk_catch_up_chunks_remaining",
+                            wolfdisk::replication::CATCH_UP_CHUNKS_REMAINING.load(std::sync::atomic::Ordering::Relaxed)),
+                    ]);
                     std::thread::sleep(std::time::Duration::from_secs(1));
                 }
             });
@@ -1916,6 +2825,106 @@ fn main() {
                 }
             });
 
+            // Evict chunks nobody has read for a while
+            let tiered_store = cold_tier.map(|cold| std::sync::Arc::new(wolfdisk::storage::TieredChunkStore::new(
+                chunk_store.clone(),
+                cold,
+                file_index.clone(),
+                config.index_dir(),
+                config.storage.cold_tier.evict_after_days,
+            )));
+            if let Some(ref tiered) = tiered_store {
+                let tiered = tiered.clone();
+                std::thread::spawn(move || loop {
+                    std::thread::sleep(std::time::Duration::from_secs(3600));
+                    if let Err(e) = tiered.evict(false) {
+                        error!("Cold tier eviction failed: {}", e);
+                    }
+                });
+            }
+
+            // Even out chunk counts across followers
+            let rebalancer = std::sync::Arc::new(wolfdisk::replication::Rebalancer::new(
+                cluster.clone(),
+                chunk_store.clone(),
+                config.storage.rebalance_threshold_pct,
+                config.storage.rebalance_bandwidth_mbps,
+            ));
+            if config.storage.rebalance_enabled {
+                let rebalancer = rebalancer.clone();
+                let cluster = cluster.clone();
+                std::thread::spawn(move || loop {
+                    std::thread::sleep(std::time::Duration::from_secs(3600));
+                    if cluster.is_leader() {
+                        if let Err(e) = rebalancer.start() {
+                            tracing::debug!("Skipping scheduled rebalance: {}", e);
+                        }
+                    }
+                });
+            }
+
+            // Catch chunk files that rot on disk
+            let scrubber = (config.node.role != wolfdisk::config::NodeRole::Client).then(|| {
+                let scrubber = wolfdisk::storage::Scrubber::new(chunk_store.clone());
+                let scrubber = if config.storage.scrub_auto_repair {
+                    let cluster = cluster.clone();
+                    scrubber.with_repair(Box::new(move |hash| wolfdisk::replication::fetch_from_peers(&cluster.peers(), hash)))
+                } else {
+                    scrubber
+                };
+                std::sync::Arc::new(scrubber)
+            });
+            if let (Some(scrubber), true) = (&scrubber, config.storage.scrub_enabled) {
+                scrubber.run_periodically(std::time::Duration::from_secs(config.storage.scrub_interval_hours.max(1) * 3600));
+            }
+
+            // Start admin API server if enabled
+            if config.api.enabled {
+                let api_bind = config.api.bind.clone();
+                let api_sync_progress = sync_progress.clone();
+                let api_tiers = tiered_store.clone();
+                let api_rebalancer = rebalancer.clone();
+                let api_events = events.clone();
+                let api_chunk_store = chunk_store.clone();
+                let api_replication_lag = replication_lag.clone();
+                let api_read_only = mountpoint.as_ref().map(|_| read_only.clone());
+                let api_scrubber = scrubber.clone();
+                let api_cluster = wolfdisk::api::ClusterView {
+                    cluster: cluster.clone(),
+                    file_index: file_index.clone(),
+                    replication: config.replication.clone(),
+                    rack: config.node.rack.clone(),
+                };
+
+                std::thread::spawn(move || {
+                    let rt = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .expect("Failed to create admin API tokio runtime");
+
+                    rt.block_on(async {
+                        let server = wolfdisk::api::ApiServer::new(api_bind, api_sync_progress)
+                            .with_tiers(api_tiers)
+                            .with_cluster(api_cluster)
+                            .with_rebalancer(api_rebalancer)
+                            .with_events(api_events)
+                            .with_chunk_store(api_chunk_store)
+                            .with_replication_lag(api_replication_lag);
+                        let server = match api_read_only {
+                            Some(read_only) => server.with_read_only(read_only),
+                            None => server,
+                        };
+                        let server = match api_scrubber {
+                            Some(scrubber) => server.with_scrubber(scrubber),
+                            None => server,
+                        };
+                        if let Err(e) = server.run().await {
+                            error!("Admin API failed: {}", e);
+                        }
+                    });
+                });
+            }
+
             // Start S3-compatible API server if enabled
             if config.s3.enabled {
                 let s3_file_index = file_index.clone();
@@ -1924,6 +2933,8 @@ fn main() {
                 let s3_next_inode = next_inode.clone();
                 let s3_bind = config.s3.bind.clone();
                 let s3_credentials = config.s3.credentials();
+                let s3_index_dir = config.index_dir();
+                let s3_quotas = quotas.clone();
 
                 std::thread::spawn(move || {
                     let rt = tokio::runtime::Builder::new_multi_thread()
@@ -1940,7 +2951,8 @@ fn main() {
                             s3_inode_table,
                             s3_next_inode,
                             s3_credentials,
-                        );
+                            &s3_index_dir,
+                        ).with_quotas(s3_quotas);
 
                         if let Err(e) = server.run().await {
                             error!("S3 server failed: {}", e);
@@ -1951,8 +2963,102 @@ fn main() {
                 info!("S3-compatible API enabled on {}", config.s3.bind);
             }
 
-            // Mount the filesystem (this blocks)
-            if let Err(e) = fuser::mount2(fs, &mountpoint, &options) {
+            // Start WebDAV gateway if enabled
+            if config.webdav.enabled {
+                let webdav_auth = match wolfdisk::webdav::DigestAuth::from_config(&config.webdav) {
+                    Ok(auth) => auth,
+                    Err(e) => {
+                        error!("Invalid WebDAV config: {}", e);
+                        cluster.stop();
+                        std::process::exit(1);
+                    }
+                };
+                if webdav_auth.is_none() {
+                    tracing::warn!("WebDAV gateway has no username/password_hash, allowing anonymous access");
+                }
+                let webdav_server = wolfdisk::webdav::WebDavServer::new(
+                    config.webdav.bind.clone(),
+                    file_index.clone(),
+                    chunk_store.clone(),
+                    inode_table.clone(),
+                    next_inode.clone(),
+                    cluster.clone(),
+                    peer_manager.clone(),
+                ).with_auth(webdav_auth);
+
+                std::thread::spawn(move || {
+                    let rt = tokio::runtime::Builder::new_multi_thread()
+                        .worker_threads(2)
+                        .enable_all()
+                        .build()
+                        .expect("Failed to create WebDAV tokio runtime");
+
+                    rt.block_on(async {
+                        if let Err(e) = webdav_server.run().await {
+                            error!("WebDAV gateway failed: {}", e);
+                        }
+                    });
+                });
+
+                info!("WebDAV gateway enabled on {}", config.webdav.bind);
+            }
+
+            let Some(mountpoint) = mountpoint else {
+                // `wolfdisk webdav`: no FUSE mount, serve until interrupted
+                let (stop_tx, stop_rx) = std::sync::mpsc::channel();
+                ctrlc::set_handler(move || {
+                    let _ = stop_tx.send(());
+                }).expect("Failed to set Ctrl+C handler");
+                let _ = stop_rx.recv();
+
+                info!("Shutting down");
+                cluster.stop();
+                return;
+            };
+
+            // Create filesystem instance with cluster support (using shared state)
+            let fs = match WolfDiskFS::with_cluster(
+                config.clone(),
+                Some(cluster.clone()),
+                Some(peer_manager.clone()),
+                file_index.clone(),
+                chunk_store.clone(),
+                inode_table.clone(),
+                next_inode.clone(),
+                dir_cache.clone(),
+            ) {
+                Ok(fs) => fs.with_quotas(quotas.clone()).with_events(events.clone()).with_read_only(read_only.clone()),
+                Err(e) => {
+                    error!("Failed to create filesystem: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            // Mount options
+            let mut options = vec![
+                fuser::MountOption::FSName("wolfdisk".to_string()),
+                fuser::MountOption::AutoUnmount,
+                fuser::MountOption::AllowOther,
+            ];
+            if config.mount.read_only {
+                info!("Mounting read-only");
+                options.push(fuser::MountOption::RO);
+            }
+
+
+            // Mount the filesystem, handing its notifier to the message handler
+            let mut session = match fuser::Session::new(fs, &mountpoint, &options) {
+                Ok(session) => session,
+                Err(e) => {
+                    error!("Mount failed: {}", e);
+                    cluster.stop();
+                    std::process::exit(1);
+                }
+            };
+            kernel_cache.set_notifier(session.notifier());
+
+            // Serve requests (this blocks until unmounted)
+            if let Err(e) = session.run() {
                 error!("Mount failed: {}", e);
                 cluster.stop();
                 std::process::exit(1);
@@ -1982,6 +3088,16 @@ fn main() {
             }
         }
 
+        Commands::Remount { .. } => {
+            if let Err(e) = wolfdisk::api::request_read_write(&config.api.bind, std::time::Duration::from_secs(5)) {
+                error!("Remount request to {} failed: {}", config.api.bind, e);
+                std::process::exit(1);
+            }
+            println!("WolfDisk now accepts writes through the mount.");
+            println!("The kernel still holds it read-only: unmount it and mount again without --read-only");
+            println!("(and without [mount] read_only) to write through it.");
+        }
+
         Commands::Status => {
             println!();
             println!("  WolfDisk Status");
@@ -1992,11 +3108,21 @@ fn main() {
             println!("  Role:         {:?}", config.node.role);
             println!("  Bind:         {}", config.node.bind);
             println!("  Data Dir:     {:?}", config.node.data_dir);
+            if let Some(ref rack) = config.node.rack {
+                println!("  Rack:         {}", rack);
+            }
             println!();
             println!("Replication:");
             println!("  Mode:         {:?}", config.replication.mode);
             println!("  Factor:       {}", config.replication.factor);
             println!("  Chunk Size:   {} bytes", config.replication.chunk_size);
+            if config.replication.rack_aware {
+                println!("  Placement:    rack-aware");
+            }
+            if config.replication.max_bandwidth_mbps > 0 {
+                println!("  Bandwidth:    {} Mbps per peer (burst {} MB)",
+                    config.replication.max_bandwidth_mbps, config.replication.burst_mb);
+            }
             println!();
             
             // Check if the mount path exists and is mounted
@@ -2013,6 +3139,9 @@ fn main() {
             if let Some(ref discovery) = config.cluster.discovery {
                 println!("Discovery:      {}", discovery);
             }
+            if let Some(ref srv) = config.cluster.discovery_srv {
+                println!("DNS SRV:        {} (every {}s)", srv, config.cluster.discovery_interval_secs);
+            }
             if !config.cluster.peers.is_empty() {
                 println!("Static Peers:   {:?}", config.cluster.peers);
             }
@@ -2044,7 +3173,12 @@ fn main() {
             // Give discovery time to find peers initially
             std::thread::sleep(std::time::Duration::from_secs(2));
 
+            // Previous sample of the local daemon's /metrics, for rates
+            let mut last_metrics: Option<(wolfdisk::api::Metrics, std::time::Instant)> = None;
+
             while running.load(std::sync::atomic::Ordering::SeqCst) {
+                let metrics = wolfdisk::api::fetch_metrics(&config.api.bind, std::time::Duration::from_millis(200)).ok();
+
                 // Clear screen and move cursor to top
                 print!("\x1B[2J\x1B[1;1H");
                 
@@ -2071,7 +3205,42 @@ fn main() {
                     let status = if peer.last_seen.elapsed().as_secs() < 4 { "●" } else { "○" };
                     let role = if peer.is_leader { "leader" } else if peer.is_client { "client" } else { "follower" };
                     let ago = peer.last_seen.elapsed().as_secs();
-                    println!("  {} {} - {} (seen {}s ago)", status, peer.node_id, role, ago);
+                    let lag = metrics.as_ref()
+                        .and_then(|m| m.replication_lag_chunks.get(&peer.node_id))
+                        .filter(|chunks| **chunks > 0)
+                        .map(|chunks| format!(", {} chunks behind", chunks))
+                        .unwrap_or_default();
+                    println!("  {} {} - {} (seen {}s ago{})", status, peer.node_id, role, ago, lag);
+                }
+
+                if let Some(metrics) = metrics {
+                    println!();
+                    println!("Chunks:       {} ({:.1} MB)", metrics.chunk_count, metrics.chunk_bytes as f64 / 1_048_576.0);
+                    println!("Index:        {} files, {} directories", metrics.file_count, metrics.dir_count);
+                    let lookups = metrics.cache_hits + metrics.cache_misses;
+                    if lookups > 0 {
+                        println!("Read cache:   {:.1}% hits ({} of {} chunk reads)",
+                            metrics.cache_hits as f64 * 100.0 / lookups as f64, metrics.cache_hits, lookups);
+                    }
+                    if let Some((ref previous, at)) = last_metrics {
+                        let secs = at.elapsed().as_secs_f64().max(0.001);
+                        let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / secs;
+                        println!("Reads:        {:.1} chunks/s, {:.2} MB/s",
+                            rate(metrics.chunk_reads, previous.chunk_reads),
+                            rate(metrics.chunk_read_bytes, previous.chunk_read_bytes) / 1_048_576.0);
+                        println!("Writes:       {:.1} chunks/s, {:.2} MB/s",
+                            rate(metrics.chunk_writes, previous.chunk_writes),
+                            rate(metrics.chunk_write_bytes, previous.chunk_write_bytes) / 1_048_576.0);
+                    }
+                    last_metrics = Some((metrics, std::time::Instant::now()));
+                }
+                
+                // Only shown while the local daemon reports a sync in progress
+                if let Ok(progress) = wolfdisk::api::fetch_sync_progress(&config.api.bind, std::time::Duration::from_millis(200)) {
+                    if !progress.is_complete() {
+                        println!();
+                        println!("Sync:         {}", sync_progress_bar(&progress));
+                    }
                 }
                 
                 std::thread::sleep(std::time::Duration::from_secs(1));
@@ -2143,5 +3312,567 @@ fn main() {
             info!("  {}/wal/     - write-ahead log", data_dir.display());
             info!("Initialization complete!");
         }
+
+        Commands::Snapshot { action } => run_snapshot_command(&config, action),
+
+        Commands::Sync { action } => run_sync_command(&config, action),
+
+        Commands::Tier { action } => run_tier_command(&config, action),
+
+        Commands::RackStatus => run_rack_status_command(&config),
+
+        Commands::Rebalance { status } => run_rebalance_command(&config, status),
+        Commands::Scrub { report } => run_scrub_command(&config, report),
+
+        Commands::Watch { path } => run_watch_command(&config, &path),
+
+        Commands::Import { src, dst, workers } => run_import_command(&config, &src, &dst, workers),
+
+        Commands::Rm { path, recursive, dry_run, yes } => run_rm_command(&config, &path, recursive, dry_run, yes),
+
+        Commands::Fsck => run_fsck_command(&config),
+
+        Commands::Presign { url, expires, method, endpoint } => run_presign_command(&config, &url, expires, &method, endpoint),
+    }
+}
+
+/// Handle `wolfdisk import`. The running daemon saves its own index every
+/// few seconds, so this writes the on-disk index and expects the node to be
+/// stopped; a leader then pushes the imported entries to its peers.
+fn run_import_command(config: &Config, src: &std::path::Path, dst: &str, workers: usize) {
+    use wolfdisk::storage::{import_tree, ChunkStore};
+
+    std::fs::create_dir_all(config.chunks_dir()).ok();
+    std::fs::create_dir_all(config.index_dir()).ok();
+    let chunk_store = ChunkStore::new(config.chunks_dir(), config.replication.chunk_size)
+        .map(|store| store.with_snapshot_dir(config.snapshots_dir()))
+        .unwrap_or_else(|e| {
+            error!("Failed to open chunk store: {}", e);
+            std::process::exit(1);
+        });
+    let mut index = FileIndex::load_or_create(&config.index_dir()).unwrap_or_else(|e| {
+        error!("Failed to load file index: {}", e);
+        std::process::exit(1);
+    });
+
+    info!("Importing {} into {}", src.display(), dst);
+    let report = import_tree(src, dst, &chunk_store, &mut index, config.replication.chunk_size, workers, |p| {
+        print!("\r{}/{} files, {:.1} MB imported", p.files, p.total_files, p.bytes as f64 / 1_048_576.0);
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+    }).unwrap_or_else(|e| {
+        error!("Import failed: {}", e);
+        std::process::exit(1);
+    });
+    println!();
+
+    if let Err(e) = index.save(&config.index_dir()) {
+        error!("Failed to save file index: {}", e);
+        std::process::exit(1);
+    }
+
+    if config.node.role == wolfdisk::config::NodeRole::Leader {
+        broadcast_imported(config, &index, &chunk_store, &report.imported);
+    }
+
+    println!("Imported {} files and {} directories ({:.1} MB)",
+        report.files, report.dirs, report.bytes as f64 / 1_048_576.0);
+    for (path, reason) in &report.failed {
+        error!("Skipped {}: {}", path.display(), reason);
+    }
+    if !report.failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Connect to each configured peer, skipping any that are unreachable
+fn connect_peers(config: &Config) -> Vec<wolfdisk::network::peer::PeerConnection> {
+    use wolfdisk::network::peer::PeerConnection;
+
+    config.cluster.peers.iter()
+        .filter_map(|address| match PeerConnection::connect(address.clone(), address) {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                tracing::warn!("Failed to connect to peer {}: {}", address, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Send a `FileSync` for each imported path to the configured peers
+fn broadcast_imported(config: &Config, index: &FileIndex, chunk_store: &wolfdisk::storage::ChunkStore, paths: &[PathBuf]) {
+    use wolfdisk::network::protocol::{ChunkRefMsg, ChunkWithData, FileSyncMsg, Message};
+
+    let peers = connect_peers(config);
+    if peers.is_empty() {
+        return;
+    }
+
+    // Same batching as the daemon's broadcast: ~16 MB per message
+    const BATCH_SIZE: usize = 4;
+    for path in paths {
+        let Some(entry) = index.get(path) else { continue };
+        let chunk_refs: Vec<ChunkRefMsg> = entry.chunks.iter()
+            .map(|c| ChunkRefMsg { hash: c.hash, offset: c.offset, size: c.size })
+            .collect();
+        let modified_ms = entry.modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let batches: Vec<&[wolfdisk::storage::ChunkRef]> = if entry.chunks.is_empty() {
+            vec![&[]]
+        } else {
+            entry.chunks.chunks(BATCH_SIZE).collect()
+        };
+        for (batch_idx, batch) in batches.into_iter().enumerate() {
+            let msg = Message::FileSync(FileSyncMsg {
+                path: path.to_string_lossy().to_string(),
+                is_dir: entry.is_dir,
+                size: entry.size,
+                permissions: entry.permissions,
+                uid: entry.uid,
+                gid: entry.gid,
+                modified_ms,
+                chunks: if batch_idx == 0 { chunk_refs.clone() } else { Vec::new() },
+                chunk_data: batch.iter()
+                    .filter_map(|c| chunk_store.get(&c.hash).ok().map(|data| ChunkWithData { hash: c.hash, data }))
+                    .collect(),
+            });
+            for peer in &peers {
+                if let Err(e) = peer.send(&msg) {
+                    tracing::warn!("Failed to send {} to {}: {}", path.display(), peer.address, e);
+                }
+            }
+        }
+    }
+    info!("Sent {} imported entries to {} peer(s)", paths.len(), peers.len());
+}
+
+/// Handle `wolfdisk rm`. Like `import`, this edits the on-disk index with
+/// the node stopped; a leader then tells its peers to delete the same paths.
+fn run_rm_command(config: &Config, path: &str, recursive: bool, dry_run: bool, yes: bool) {
+    use wolfdisk::storage::{remove_tree, ChunkStore, InodeTable};
+
+    if std::path::Path::new(path).components().all(|c| matches!(c, std::path::Component::RootDir | std::path::Component::CurDir))
+        && !yes && !dry_run
+    {
+        error!("Refusing to remove the root directory without --yes");
+        std::process::exit(1);
+    }
+
+    let chunk_store = ChunkStore::new(config.chunks_dir(), config.replication.chunk_size)
+        .map(|store| store.with_snapshot_dir(config.snapshots_dir()))
+        .unwrap_or_else(|e| {
+            error!("Failed to open chunk store: {}", e);
+            std::process::exit(1);
+        });
+    let mut index = FileIndex::load_or_create(&config.index_dir()).unwrap_or_else(|e| {
+        error!("Failed to load file index: {}", e);
+        std::process::exit(1);
+    });
+    let (mut inodes, _) = InodeTable::from_index(&index);
+
+    let report = remove_tree(path, &mut index, &mut inodes, &chunk_store, recursive, dry_run)
+        .unwrap_or_else(|e| {
+            error!("Remove failed: {}", e);
+            std::process::exit(1);
+        });
+
+    if dry_run {
+        for removed in &report.removed {
+            println!("/{}", removed.display());
+        }
+        println!("Would remove {} files and {} directories ({:.1} MB, {} chunks)",
+            report.files, report.dirs, report.bytes as f64 / 1_048_576.0, report.chunks);
+        return;
+    }
+
+    if let Err(e) = index.save(&config.index_dir()) {
+        error!("Failed to save file index: {}", e);
+        std::process::exit(1);
+    }
+
+    if config.node.role == wolfdisk::config::NodeRole::Leader {
+        broadcast_removed(config, &report.removed);
+    }
+
+    println!("Removed {} files and {} directories ({:.1} MB, {} chunks)",
+        report.files, report.dirs, report.bytes as f64 / 1_048_576.0, report.chunks);
+}
+
+/// Handle `wolfdisk fsck`: every path of a hard-linked file must have the
+/// same chunks and size, and a link count matching its number of paths.
+/// Exits non-zero if any do not.
+fn run_fsck_command(config: &Config) {
+    let index = FileIndex::load_or_create(&config.index_dir()).unwrap_or_else(|e| {
+        error!("Failed to load file index: {}", e);
+        std::process::exit(1);
+    });
+
+    let problems = index.check_hard_links();
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    let linked = index.iter().filter(|(_, e)| e.link_id.is_some()).count();
+    println!("Checked {} entries ({} hard-linked paths): {} problems", index.len(), linked, problems.len());
+    if !problems.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Send an `IndexUpdate` delete for each removed path to the configured peers
+fn broadcast_removed(config: &Config, paths: &[PathBuf]) {
+    use wolfdisk::network::protocol::{IndexOperation, IndexUpdateMsg, Message};
+
+    let peers = connect_peers(config);
+    if peers.is_empty() {
+        return;
+    }
+
+    for path in paths {
+        // Followers apply deletes whatever the version; the daemon assigns
+        // real ones once it is running again
+        let msg = Message::IndexUpdate(IndexUpdateMsg {
+            version: 0,
+            operation: IndexOperation::Delete { path: path.to_string_lossy().to_string() },
+        });
+        for peer in &peers {
+            if let Err(e) = peer.send(&msg) {
+                tracing::warn!("Failed to send delete of {} to {}: {}", path.display(), peer.address, e);
+            }
+        }
+    }
+    info!("Sent {} deletes to {} peer(s)", paths.len(), peers.len());
+}
+
+/// Handle `wolfdisk tier ...` subcommands (through the running daemon,
+/// which owns the index)
+fn run_tier_command(config: &Config, action: TierCommand) {
+    let bind = &config.api.bind;
+    match action {
+        TierCommand::Evict { dry_run } => {
+            // Uploads can take a while
+            match wolfdisk::api::request_tier_evict(bind, dry_run, std::time::Duration::from_secs(3600)) {
+                Ok(report) if report.dry_run => {
+                    println!("Would evict {} chunks ({:.1} MB)", report.chunks, report.bytes as f64 / 1_048_576.0);
+                }
+                Ok(report) => {
+                    println!("Evicted {} chunks ({:.1} MB)", report.chunks, report.bytes as f64 / 1_048_576.0);
+                    if report.failed > 0 {
+                        error!("{} chunks could not be uploaded", report.failed);
+                        std::process::exit(1);
+                    }
+                }
+                Err(e) => {
+                    error!("Eviction failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        TierCommand::Status => match wolfdisk::api::fetch_tier_status(bind, std::time::Duration::from_secs(60)) {
+            Ok(status) => {
+                println!("Hot:  {:>10} chunks  {:>12.1} MB", status.hot_chunks, status.hot_bytes as f64 / 1_048_576.0);
+                println!("Cold: {:>10} chunks  {:>12.1} MB", status.cold_chunks, status.cold_bytes as f64 / 1_048_576.0);
+            }
+            Err(e) => {
+                error!("Failed to query tier status at {}: {}", bind, e);
+                std::process::exit(1);
+            }
+        },
+    }
+}
+
+/// Handle `wolfdisk rack-status`
+fn run_rack_status_command(config: &Config) {
+    let status = match wolfdisk::api::fetch_rack_status(&config.api.bind, std::time::Duration::from_secs(30)) {
+        Ok(status) => status,
+        Err(e) => {
+            error!("Failed to query rack status at {}: {}", config.api.bind, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!();
+    println!("  WolfDisk Rack Placement");
+    println!("  {}", "─".repeat(50));
+    println!();
+    println!("Rack-aware:   {}", if status.rack_aware { "yes" } else { "no" });
+    println!("Factor:       {}", status.factor);
+    println!("Leader:       {}", status.leader.as_deref().unwrap_or("(none)"));
+    println!("Chunks:       {}", status.chunks);
+    println!();
+    println!("  {:12} {:>10}  Nodes", "Rack", "Copies");
+    for rack in &status.racks {
+        println!("  {:12} {:>10}  {}", rack.rack, rack.copies, rack.nodes.join(", "));
+    }
+    println!();
+    for (racks, chunks) in &status.chunks_by_racks {
+        println!("  {} chunk(s) held in {} rack(s)", chunks, racks);
+    }
+}
+
+/// Handle `wolfdisk rebalance [--status]`
+fn run_watch_command(config: &Config, path: &std::path::Path) {
+    // Events carry paths inside WolfDisk, so drop the mount point
+    let inside = path.strip_prefix(&config.mount.path).unwrap_or(path);
+    let prefix = format!("/{}", inside.to_string_lossy().trim_matches('/'));
+
+    let result = wolfdisk::api::watch_events(&config.api.bind, std::time::Duration::from_secs(5), |event| {
+        if event.matches(&prefix) {
+            println!("{}", event);
+        }
+    });
+    if let Err(e) = result {
+        error!("Watching events from {} failed: {}", config.api.bind, e);
+        std::process::exit(1);
+    }
+}
+
+fn run_rebalance_command(config: &Config, show_status: bool) {
+    let timeout = std::time::Duration::from_secs(30);
+    let result = if show_status {
+        wolfdisk::api::fetch_rebalance_status(&config.api.bind, timeout)
+    } else {
+        wolfdisk::api::request_rebalance(&config.api.bind, timeout)
+    };
+    let status = result.unwrap_or_else(|e| {
+        error!("Rebalance request to {} failed: {}", config.api.bind, e);
+        std::process::exit(1);
+    });
+
+    if !show_status {
+        println!("Rebalance started; follow it with `wolfdisk rebalance --status`");
+        return;
+    }
+    let state = if status.running {
+        "running"
+    } else if status.started_at.is_some() {
+        "finished"
+    } else {
+        "never run"
+    };
+    println!("State:        {}", state);
+    println!("Moved:        {}/{} chunks ({:.1} MB), {} failed",
+        status.moved, status.planned, status.bytes_moved as f64 / 1_048_576.0, status.failed);
+    if let Some(ref error) = status.error {
+        println!("Error:        {}", error);
+    }
+    if !status.counts.is_empty() {
+        println!();
+        println!("  {:20} {:>10}  (ideal {})", "Node", "Chunks", status.ideal);
+        for (node, count) in &status.counts {
+            println!("  {:20} {:>10}", node, count);
+        }
+    }
+}
+
+/// Handle `wolfdisk scrub [--report]`
+fn run_scrub_command(config: &Config, report: bool) {
+    let timeout = std::time::Duration::from_secs(30);
+    let result = if report {
+        wolfdisk::api::fetch_scrub_status(&config.api.bind, timeout)
+    } else {
+        wolfdisk::api::request_scrub(&config.api.bind, timeout)
+    };
+    let status = result.unwrap_or_else(|e| {
+        error!("Scrub request to {} failed: {}", config.api.bind, e);
+        std::process::exit(1);
+    });
+
+    if !report {
+        println!("Scrub started; follow it with `wolfdisk scrub --report`");
+        return;
+    }
+    let state = if status.running {
+        "running"
+    } else if status.started_at.is_some() {
+        "finished"
+    } else {
+        "never run"
+    };
+    println!("State:        {}", state);
+    println!("Checked:      {}/{} chunks", status.checked, status.total);
+    println!("Corrupt:      {} found, {} repaired", status.corrupt, status.repaired);
+    if let Some(ref error) = status.error {
+        println!("Error:        {}", error);
+    }
+    if !status.corrupt_chunks.is_empty() {
+        println!();
+        println!("  Corrupt chunks not yet repaired:");
+        for hash in &status.corrupt_chunks {
+            println!("  {}", hash);
+        }
+    }
+}
+
+/// Handle `wolfdisk presign s3://bucket/key`. Signing is local, so the node
+/// doesn't need to be running, but the URL only works against a server with
+/// the same S3 keys.
+fn run_presign_command(config: &Config, url: &str, expires: u64, method: &str, endpoint: Option<String>) {
+    use wolfdisk::s3::auth::{presign_url, MAX_PRESIGN_EXPIRES};
+
+    let Some(credentials) = config.s3.credentials() else {
+        error!("Presigning needs s3.access_key and s3.secret_key in the config");
+        std::process::exit(1);
+    };
+    let object = url.strip_prefix("s3://").and_then(|rest| rest.split_once('/'));
+    let Some((bucket, key)) = object.filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty()) else {
+        error!("Expected s3://bucket/key, got {}", url);
+        std::process::exit(1);
+    };
+    let method = method.to_ascii_uppercase();
+    if !matches!(method.as_str(), "GET" | "HEAD" | "PUT" | "DELETE") {
+        error!("--method must be GET, HEAD, PUT or DELETE");
+        std::process::exit(1);
+    }
+    if !(1..=MAX_PRESIGN_EXPIRES).contains(&expires) {
+        error!("--expires must be between 1 and {} seconds", MAX_PRESIGN_EXPIRES);
+        std::process::exit(1);
+    }
+
+    let endpoint = endpoint.unwrap_or_else(|| format!("http://{}", config.s3.bind.replace("0.0.0.0", "127.0.0.1")));
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    println!("{}", presign_url(&credentials, "us-east-1", &endpoint, &method, &format!("/{}/{}", bucket, key), expires, now));
+}
+
+/// Handle `wolfdisk sync ...` subcommands
+fn run_sync_command(config: &Config, action: SyncCommand) {
+    match action {
+        SyncCommand::Wait { timeout } => {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(timeout);
+            loop {
+                match wolfdisk::api::fetch_sync_progress(&config.api.bind, std::time::Duration::from_secs(2)) {
+                    Ok(progress) if progress.is_complete() => {
+                        println!("Sync complete: {} files, {} bytes", progress.synced_files, progress.synced_bytes);
+                        return;
+                    }
+                    Ok(progress) => {
+                        println!("Syncing: {}/{} files, {}/{} bytes{}",
+                            progress.synced_files, progress.total_files,
+                            progress.synced_bytes, progress.total_bytes,
+                            progress.eta_secs.map(|s| format!(", ETA {}s", s)).unwrap_or_default());
+                    }
+                    Err(e) => {
+                        error!("Failed to query sync progress at {}: {}", config.api.bind, e);
+                        std::process::exit(1);
+                    }
+                }
+                if std::time::Instant::now() >= deadline {
+                    error!("Timed out after {}s waiting for sync", timeout);
+                    std::process::exit(1);
+                }
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+        }
+    }
+}
+
+/// Render a sync progress bar for `wolfdisk stats`
+fn sync_progress_bar(progress: &wolfdisk::replication::SyncProgress) -> String {
+    const WIDTH: u64 = 40;
+    let filled = (WIDTH * progress.synced_bytes).checked_div(progress.total_bytes)
+        .or_else(|| (WIDTH * progress.synced_files).checked_div(progress.total_files))
+        .unwrap_or(0)
+        .min(WIDTH);
+    format!("[{}{}] {}/{} files, {:.1}/{:.1} MB{}",
+        "#".repeat(filled as usize),
+        "-".repeat((WIDTH - filled) as usize),
+        progress.synced_files, progress.total_files,
+        progress.synced_bytes as f64 / 1_048_576.0, progress.total_bytes as f64 / 1_048_576.0,
+        progress.eta_secs.map(|s| format!(", ETA {}s", s)).unwrap_or_default())
+}
+
+/// Handle `wolfdisk snapshot ...` subcommands
+fn run_snapshot_command(config: &Config, action: SnapshotCommand) {
+    use wolfdisk::storage::{ChunkStore, SnapshotManager};
+
+    let snapshots = SnapshotManager::new(config.snapshots_dir());
+    let open_chunk_store = || {
+        ChunkStore::new(config.chunks_dir(), config.replication.chunk_size)
+            .map(|store| store.with_snapshot_dir(config.snapshots_dir()))
+            .unwrap_or_else(|e| {
+                error!("Failed to open chunk store: {}", e);
+                std::process::exit(1);
+            })
+    };
+    // The running daemon persists its index every few seconds, so the
+    // on-disk copy is what a snapshot captures
+    let load_live_index = || {
+        FileIndex::load_or_create(&config.index_dir()).unwrap_or_else(|e| {
+            error!("Failed to load file index: {}", e);
+            std::process::exit(1);
+        })
+    };
+
+    match action {
+        SnapshotCommand::Create { name } => {
+            let live = load_live_index();
+            match snapshots.create(&name, &live) {
+                Ok(info) => {
+                    println!("Created snapshot '{}' ({} entries, {} bytes)", info.name, info.entries, info.size);
+                }
+                Err(e) => {
+                    error!("Failed to create snapshot: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        SnapshotCommand::List => {
+            let list = snapshots.list().unwrap_or_else(|e| {
+                error!("Failed to list snapshots: {}", e);
+                std::process::exit(1);
+            });
+
+            println!();
+            println!("  WolfDisk Snapshots");
+            println!("  {}", "─".repeat(50));
+            println!();
+            if list.is_empty() {
+                println!("  (no snapshots)");
+            }
+            for snap in &list {
+                let created = snap.created.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S");
+                println!("  {:20} {}  {:>14} bytes  {} entries", snap.name, created, snap.size, snap.entries);
+            }
+            println!();
+        }
+
+        SnapshotCommand::Delete { name } => {
+            let live = load_live_index();
+            let chunk_store = open_chunk_store();
+            match snapshots.delete(&name, &live, &chunk_store) {
+                Ok(removed) => println!("Deleted snapshot '{}' ({} chunks freed)", name, removed),
+                Err(e) => {
+                    error!("Failed to delete snapshot: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        SnapshotCommand::Mount { name, mountpoint } => {
+            let snap = snapshots.load(&name).unwrap_or_else(|e| {
+                error!("Failed to load snapshot '{}': {}", name, e);
+                std::process::exit(1);
+            });
+            let chunk_store = std::sync::Arc::new(open_chunk_store());
+            let fs = wolfdisk::fuse::SnapshotFS::new(snap, chunk_store);
+
+            let options = vec![
+                fuser::MountOption::FSName(format!("wolfdisk-snap-{}", name)),
+                fuser::MountOption::RO,
+                fuser::MountOption::AutoUnmount,
+                fuser::MountOption::AllowOther,
+            ];
+
+            info!("Mounting snapshot '{}' read-only at {:?}", name, mountpoint);
+            if let Err(e) = fuser::mount2(fs, &mountpoint, &options) {
+                error!("Mount failed: {}", e);
+                std::process::exit(1);
+            }
+        }
     }
 }
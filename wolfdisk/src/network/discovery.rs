@@ -1,8 +1,10 @@
 //! UDP Broadcast Discovery for WolfDisk nodes
 //!
 //! Uses broadcast (255.255.255.255) for node discovery, same as WolfScale.
+//! Announcements also go straight to known peer addresses (static peers and
+//! DNS SRV results), which reaches nodes on other subnets.
 
-use std::net::SocketAddr;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
@@ -45,6 +47,8 @@ pub struct DiscoveredPeer {
     pub address: String,
     pub role: DiscoveryRole,
     pub is_leader: bool,
+    /// Rack the peer advertises (`node.rack`)
+    pub rack: Option<String>,
     pub last_seen: Instant,
 }
 
@@ -54,24 +58,45 @@ pub struct Discovery {
     node_id: String,
     bind_address: String,
     role: DiscoveryRole,
+    rack: Option<String>,
     peers: Arc<RwLock<HashMap<String, DiscoveredPeer>>>,
     is_leader: Arc<RwLock<bool>>,
     running: Arc<RwLock<bool>>,
+    /// Discovery ports of known peers, announced to directly
+    unicast: Arc<RwLock<Vec<SocketAddr>>>,
 }
 
 impl Discovery {
     /// Create a new discovery service
-    pub fn new(node_id: String, bind_address: String, role: NodeRole) -> Self {
+    pub fn new(node_id: String, bind_address: String, role: NodeRole, rack: Option<String>) -> Self {
         Self {
             node_id,
             bind_address,
             role: role.into(),
+            rack,
             peers: Arc::new(RwLock::new(HashMap::new())),
             is_leader: Arc::new(RwLock::new(false)),
             running: Arc::new(RwLock::new(false)),
+            unicast: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
+    /// Announce this node directly to the nodes at these peer addresses
+    /// (`host:port`), as well as by broadcast
+    pub fn set_unicast_peers(&self, addresses: &[String]) {
+        let targets = addresses.iter()
+            .filter_map(|address| match address.to_socket_addrs() {
+                Ok(mut resolved) => resolved.next(),
+                Err(e) => {
+                    debug!("Cannot resolve peer {} for discovery: {}", address, e);
+                    None
+                }
+            })
+            .map(|addr| SocketAddr::new(addr.ip(), DISCOVERY_PORT))
+            .collect();
+        *self.unicast.write().unwrap() = targets;
+    }
+
     /// Set leader status
     pub fn set_leader(&self, is_leader: bool) {
         *self.is_leader.write().unwrap() = is_leader;
@@ -98,11 +123,13 @@ impl Discovery {
         let node_id = self.node_id.clone();
         let bind_address = self.bind_address.clone();
         let role = self.role;
+        let rack = self.rack.clone();
         let is_leader = Arc::clone(&self.is_leader);
         let running = Arc::clone(&self.running);
+        let unicast = Arc::clone(&self.unicast);
 
         thread::spawn(move || {
-            if let Err(e) = run_broadcaster(node_id, bind_address, role, is_leader, running) {
+            if let Err(e) = run_broadcaster(node_id, bind_address, role, rack, is_leader, unicast, running) {
                 warn!("Discovery broadcaster error: {}", e);
             }
         });
@@ -129,22 +156,25 @@ impl Discovery {
 }
 
 /// Format a discovery broadcast message
-fn format_message(node_id: &str, address: &str, is_server: bool, is_leader: bool) -> String {
+///
+/// The rack is an optional trailing field; older nodes ignore it.
+fn format_message(node_id: &str, address: &str, is_server: bool, is_leader: bool, rack: Option<&str>) -> String {
     let role = if is_server { "S" } else { "C" };
     let leader = if is_leader { "L" } else { "F" };
     format!(
-        "{}|{}|{}|{}|{}|{}",
+        "{}|{}|{}|{}|{}|{}|{}",
         DISCOVERY_PREFIX,
         DISCOVERY_VERSION,
         node_id,
         address,
         role,
-        leader
+        leader,
+        rack.unwrap_or("")
     )
 }
 
 /// Parse a discovery broadcast message
-fn parse_message(message: &str) -> Option<(String, String, bool, bool)> {
+fn parse_message(message: &str) -> Option<(String, String, bool, bool, Option<String>)> {
     let parts: Vec<&str> = message.split('|').collect();
     
     if parts.len() < 6 {
@@ -166,8 +196,9 @@ fn parse_message(message: &str) -> Option<(String, String, bool, bool)> {
     let address = parts[3].to_string();
     let is_server = parts[4] == "S";
     let is_leader = parts[5] == "L";
+    let rack = parts.get(6).filter(|r| !r.is_empty()).map(|r| r.to_string());
 
-    Some((node_id, address, is_server, is_leader))
+    Some((node_id, address, is_server, is_leader, rack))
 }
 
 /// Run the discovery broadcaster
@@ -175,7 +206,9 @@ fn run_broadcaster(
     node_id: String,
     bind_address: String,
     role: DiscoveryRole,
+    rack: Option<String>,
     is_leader: Arc<RwLock<bool>>,
+    unicast: Arc<RwLock<Vec<SocketAddr>>>,
     running: Arc<RwLock<bool>>,
 ) -> std::io::Result<()> {
     // Create UDP socket for broadcasting
@@ -208,7 +241,7 @@ fn run_broadcaster(
         let is_server = matches!(role, DiscoveryRole::Server);
         let leader = *is_leader.read().unwrap();
         
-        let message = format_message(&node_id, &bind_address, is_server, leader);
+        let message = format_message(&node_id, &bind_address, is_server, leader, rack.as_deref());
 
         for addr in &broadcast_addrs {
             match socket.send_to(message.as_bytes(), addr) {
@@ -216,6 +249,11 @@ fn run_broadcaster(
                 Err(e) => debug!("Broadcast send to {} failed: {}", addr, e),
             }
         }
+        for addr in unicast.read().unwrap().iter() {
+            if let Err(e) = socket.send_to(message.as_bytes(), addr) {
+                debug!("Discovery send to {} failed: {}", addr, e);
+            }
+        }
 
         thread::sleep(Duration::from_secs(2));
     }
@@ -250,7 +288,7 @@ fn run_listener(
         match socket.recv_from(&mut buf) {
             Ok((len, src)) => {
                 if let Ok(message) = std::str::from_utf8(&buf[..len]) {
-                    if let Some((msg_node_id, msg_address, is_server, is_leader)) = parse_message(message) {
+                    if let Some((msg_node_id, msg_address, is_server, is_leader, rack)) = parse_message(message) {
                         // Skip our own broadcasts
                         if msg_node_id == node_id {
                             continue;
@@ -278,6 +316,7 @@ fn run_listener(
                                 address: actual_address,
                                 role,
                                 is_leader,
+                                rack,
                                 last_seen: Instant::now(),
                             },
                         );
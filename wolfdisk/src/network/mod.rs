@@ -3,7 +3,10 @@
 pub mod protocol;
 pub mod discovery;
 pub mod peer;
+pub mod rate_limit;
+pub mod srv;
 
 pub use protocol::{Message, encode_message, decode_message};
 pub use discovery::Discovery;
 pub use peer::{PeerConnection, PeerManager};
+pub use rate_limit::RateLimiter;
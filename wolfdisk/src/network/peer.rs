@@ -1,21 +1,32 @@
 //! Peer connection management for WolfDisk nodes
 
 use std::collections::HashMap;
+use std::fs::File;
 use std::io::{Read, Write};
 use std::net::{TcpStream, TcpListener, SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock, Mutex};
 use std::time::Duration;
 use std::thread;
 
 use tracing::{debug, info, warn};
 
-use crate::network::protocol::{Message, encode_message, decode_message};
+use crate::cluster::ClusterManager;
+use crate::network::protocol::{ChunkDataMsg, Message, RAW_CHUNK_FRAME, encode_message, decode_message};
+use crate::network::rate_limit::RateLimiter;
+
+/// Largest frame accepted on an inbound connection
+const MAX_INBOUND_FRAME: usize = 100 * 1024 * 1024;
+
+/// Finds the local file holding a chunk, for serving it without a copy
+pub type ChunkFileLookup = Arc<dyn Fn(&[u8; 32]) -> Option<PathBuf> + Send + Sync>;
 
 /// Connection to a peer node
 pub struct PeerConnection {
     pub node_id: String,
     pub address: String,
     stream: Mutex<TcpStream>,
+    rate_limiter: Option<RateLimiter>,
 }
 
 impl PeerConnection {
@@ -43,14 +54,25 @@ impl PeerConnection {
             node_id,
             address: address.to_string(),
             stream: Mutex::new(stream),
+            rate_limiter: None,
         })
     }
 
+    /// Throttle outgoing messages on this connection
+    pub fn with_rate_limiter(mut self, limiter: Option<RateLimiter>) -> Self {
+        self.rate_limiter = limiter;
+        self
+    }
+
     /// Send a message to the peer
     pub fn send(&self, msg: &Message) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let data = encode_message(msg)?;
         let len = (data.len() as u32).to_le_bytes();
         
+        if let Some(ref limiter) = self.rate_limiter {
+            limiter.acquire(data.len() + len.len());
+        }
+        
         let mut stream = self.stream.lock().unwrap();
         stream.write_all(&len)?;
         stream.write_all(&data)?;
@@ -59,21 +81,20 @@ impl PeerConnection {
         Ok(())
     }
 
+    /// Send a chunk file as a raw chunk frame, which the peer reads as a
+    /// `ChunkData` message. The file goes from the page cache to the socket
+    /// with `sendfile(2)`, without being read into this process.
+    #[cfg(feature = "zero-copy")]
+    pub fn send_file(&self, hash: &[u8; 32], chunk_path: &std::path::Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let file = File::open(chunk_path)?;
+        let mut stream = self.stream.lock().unwrap();
+        write_chunk_file(&mut stream, hash, file, self.rate_limiter.as_ref())
+    }
+
     /// Receive a message from the peer
     pub fn recv(&self) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
         let mut stream = self.stream.lock().unwrap();
-        
-        // Read length prefix
-        let mut len_buf = [0u8; 4];
-        stream.read_exact(&mut len_buf)?;
-        let len = u32::from_le_bytes(len_buf) as usize;
-        
-        // Read message data
-        let mut data = vec![0u8; len];
-        stream.read_exact(&mut data)?;
-        
-        let msg = decode_message(&data)?;
-        Ok(msg)
+        read_frame(&mut stream, usize::MAX)
     }
 
     /// Send and wait for response
@@ -85,12 +106,15 @@ impl PeerConnection {
 
 /// Manages connections to all peers
 pub struct PeerManager {
-    #[allow(dead_code)]
     node_id: String,
     bind_address: String,
     connections: Arc<RwLock<HashMap<String, Arc<PeerConnection>>>>,
     message_handler: Arc<dyn Fn(String, Message) -> Option<Message> + Send + Sync>,
     running: Arc<RwLock<bool>>,
+    /// Per-connection bandwidth limit as (megabits/s, burst bytes); None = unlimited
+    bandwidth_limit: Option<(u64, u64)>,
+    /// Serves GetChunk straight from chunk files (`zero-copy` feature)
+    chunk_files: Option<ChunkFileLookup>,
 }
 
 impl PeerManager {
@@ -105,9 +129,45 @@ impl PeerManager {
             connections: Arc::new(RwLock::new(HashMap::new())),
             message_handler: Arc::new(handler),
             running: Arc::new(RwLock::new(false)),
+            bandwidth_limit: None,
+            chunk_files: None,
         }
     }
 
+    /// Limit each peer connection to `max_bandwidth_mbps` (0 = unlimited)
+    /// with a bucket of `burst_bytes`
+    pub fn with_bandwidth_limit(mut self, max_bandwidth_mbps: u64, burst_bytes: u64) -> Self {
+        self.bandwidth_limit = if max_bandwidth_mbps > 0 {
+            Some((max_bandwidth_mbps, burst_bytes))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Answer GetChunk requests for chunks that `lookup` finds on disk by
+    /// sending the file itself with `sendfile(2)`, instead of reading it into
+    /// a `ChunkData` message. Anything else, or a chunk `lookup` does not
+    /// find, still goes to the message handler. Without the `zero-copy`
+    /// feature this does nothing.
+    pub fn with_chunk_files<F>(self, lookup: F) -> Self
+    where
+        F: Fn(&[u8; 32]) -> Option<PathBuf> + Send + Sync + 'static,
+    {
+        #[cfg(feature = "zero-copy")]
+        return Self { chunk_files: Some(Arc::new(lookup)), ..self };
+        #[cfg(not(feature = "zero-copy"))]
+        {
+            let _ = lookup;
+            self
+        }
+    }
+
+    /// Create a fresh limiter for a new connection
+    fn new_rate_limiter(bandwidth_limit: Option<(u64, u64)>) -> Option<RateLimiter> {
+        bandwidth_limit.map(|(mbps, burst)| RateLimiter::new(mbps, burst))
+    }
+
     /// Start listening for peer connections
     pub fn start(&self) -> std::io::Result<()> {
         *self.running.write().unwrap() = true;
@@ -119,6 +179,8 @@ impl PeerManager {
         
         let handler = Arc::clone(&self.message_handler);
         let running = Arc::clone(&self.running);
+        let bandwidth_limit = self.bandwidth_limit;
+        let chunk_files = self.chunk_files.clone();
         
         thread::spawn(move || {
             info!("Peer server listening on {}", bind_addr);
@@ -128,9 +190,12 @@ impl PeerManager {
                     Ok((stream, addr)) => {
                         debug!("Accepted connection from {}", addr);
                         let handler = Arc::clone(&handler);
+                        let chunk_files = chunk_files.clone();
+                        
+                        let limiter = Self::new_rate_limiter(bandwidth_limit);
                         
                         thread::spawn(move || {
-                            if let Err(e) = handle_peer_connection(stream, addr, handler) {
+                            if let Err(e) = handle_peer_connection(stream, addr, handler, limiter, chunk_files) {
                                 debug!("Peer connection ended: {}", e);
                             }
                         });
@@ -150,7 +215,8 @@ impl PeerManager {
 
     /// Connect to a peer
     pub fn connect(&self, node_id: &str, address: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let conn = PeerConnection::connect(node_id.to_string(), address)?;
+        let conn = PeerConnection::connect(node_id.to_string(), address)?
+            .with_rate_limiter(Self::new_rate_limiter(self.bandwidth_limit));
         self.connections.write().unwrap().insert(node_id.to_string(), Arc::new(conn));
         info!("Connected to peer {} at {}", node_id, address);
         Ok(())
@@ -245,7 +311,8 @@ impl PeerManager {
         }
         
         // Connect to leader
-        let conn = PeerConnection::connect(leader_id.to_string(), leader_addr)?;
+        let conn = PeerConnection::connect(leader_id.to_string(), leader_addr)?
+            .with_rate_limiter(Self::new_rate_limiter(self.bandwidth_limit));
         let conn = Arc::new(conn);
         self.connections.write().unwrap().insert(leader_id.to_string(), conn.clone());
         Ok(conn)
@@ -256,6 +323,46 @@ impl PeerManager {
         self.connections.write().unwrap().remove(leader_id);
     }
 
+    /// Send a request to the leader with automatic reconnection on failure.
+    /// If the first attempt fails (e.g. stale connection after leader restart),
+    /// drops the cached connection, reconnects, and retries once.
+    /// Errors are errno values, for FUSE.
+    pub fn request_leader(&self, cluster: &ClusterManager, msg: &Message) -> std::result::Result<Message, i32> {
+        let leader_id = cluster.leader_id().ok_or(libc::ENOENT)?;
+        let leader_addr = cluster.leader_address().ok_or(libc::ENOENT)?;
+
+        // First attempt
+        let conn = self.get_or_connect_leader(&leader_id, &leader_addr)
+            .map_err(|_| libc::EIO)?;
+
+        match conn.request(msg) {
+            Ok(response) => return Ok(response),
+            Err(e) => {
+                warn!("Leader request failed (will reconnect): {}", e);
+                self.disconnect_leader(&leader_id);
+            }
+        }
+
+        // Retry with fresh connection
+        let conn = self.get_or_connect_leader(&leader_id, &leader_addr)
+            .map_err(|e| {
+                tracing::error!("Failed to reconnect to leader: {}", e);
+                libc::EIO
+            })?;
+
+        conn.request(msg).map_err(|e| {
+            tracing::error!("Leader request failed after reconnect: {}", e);
+            self.disconnect_leader(&leader_id);
+            libc::EIO
+        })
+    }
+
+    /// Run a message through this node's own handler, as if a peer had sent
+    /// it. The leader uses this to apply requests that originate locally.
+    pub fn handle_local(&self, msg: Message) -> Option<Message> {
+        (self.message_handler)(self.node_id.clone(), msg)
+    }
+
     /// Stop the peer manager
     pub fn stop(&self) {
         *self.running.write().unwrap() = false;
@@ -267,6 +374,8 @@ fn handle_peer_connection(
     mut stream: TcpStream,
     addr: SocketAddr,
     handler: Arc<dyn Fn(String, Message) -> Option<Message> + Send + Sync>,
+    rate_limiter: Option<RateLimiter>,
+    chunk_files: Option<ChunkFileLookup>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Inbound connections can afford slightly longer timeouts since they
     // handle sync requests which transfer more data.
@@ -276,30 +385,266 @@ fn handle_peer_connection(
     stream.set_nodelay(true)?;
     
     loop {
-        // Read length prefix
-        let mut len_buf = [0u8; 4];
-        stream.read_exact(&mut len_buf)?;
-        let len = u32::from_le_bytes(len_buf) as usize;
-        
-        if len > 100 * 1024 * 1024 {
-            // Max 100MB message
-            return Err("Message too large".into());
-        }
-        
-        // Read message
-        let mut data = vec![0u8; len];
-        stream.read_exact(&mut data)?;
-        
-        let msg = decode_message(&data)?;
+        let msg = read_frame(&mut stream, MAX_INBOUND_FRAME)?;
         let peer_id = addr.to_string(); // Will be replaced with proper handshake
+
+        // Send locally stored chunks without copying them through this process
+        if let (Message::GetChunk(ref get_chunk), Some(ref lookup)) = (&msg, &chunk_files) {
+            if let Some(file) = lookup(&get_chunk.hash).and_then(|path| File::open(path).ok()) {
+                debug!("Sending chunk {} to {} with sendfile", hex::encode(get_chunk.hash), peer_id);
+                write_chunk_file(&mut stream, &get_chunk.hash, file, rate_limiter.as_ref())?;
+                continue;
+            }
+        }
         
         // Handle message and optionally send response
         if let Some(response) = handler(peer_id, msg) {
             let resp_data = encode_message(&response)?;
             let resp_len = (resp_data.len() as u32).to_le_bytes();
+            if let Some(ref limiter) = rate_limiter {
+                limiter.acquire(resp_data.len() + resp_len.len());
+            }
             stream.write_all(&resp_len)?;
             stream.write_all(&resp_data)?;
             stream.flush()?;
         }
     }
 }
+
+/// Read one length-prefixed frame, of at most `max_len` bytes. A raw chunk
+/// frame comes back as the `ChunkData` message it stands for.
+fn read_frame(stream: &mut TcpStream, max_len: usize) -> Result<Message, Box<dyn std::error::Error + Send + Sync>> {
+    // Read length prefix
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf)?;
+    let prefix = u32::from_le_bytes(len_buf);
+    let len = (prefix & !RAW_CHUNK_FRAME) as usize;
+    if len > max_len {
+        return Err("Message too large".into());
+    }
+
+    if prefix & RAW_CHUNK_FRAME != 0 {
+        let mut hash = [0u8; 32];
+        stream.read_exact(&mut hash)?;
+        let mut data = vec![0u8; len];
+        stream.read_exact(&mut data)?;
+        return Ok(Message::ChunkData(ChunkDataMsg { hash, data: Some(data), error: None }));
+    }
+
+    // Read message data
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data)?;
+    Ok(decode_message(&data)?)
+}
+
+/// Write a chunk file as a raw chunk frame: the header with an ordinary
+/// write, then the file with `sendfile(2)`. Falls back to copying through a
+/// buffer where `sendfile` is unavailable or refuses the file (EINVAL).
+fn write_chunk_file(
+    stream: &mut TcpStream,
+    hash: &[u8; 32],
+    mut file: File,
+    rate_limiter: Option<&RateLimiter>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let len = file.metadata()?.len();
+    if len >= RAW_CHUNK_FRAME as u64 {
+        return Err("Chunk too large for a raw chunk frame".into());
+    }
+
+    let mut header = [0u8; 36];
+    header[..4].copy_from_slice(&(len as u32 | RAW_CHUNK_FRAME).to_le_bytes());
+    header[4..].copy_from_slice(hash);
+    if let Some(limiter) = rate_limiter {
+        limiter.acquire(header.len() + len as usize);
+    }
+    stream.write_all(&header)?;
+
+    let sent = sendfile_all(stream, &file, len)?;
+    if sent < len {
+        // sendfile advances the file offset, so the copy picks up where it stopped
+        let copied = std::io::copy(&mut (&mut file).take(len - sent), stream)?;
+        if sent + copied < len {
+            return Err("Chunk file shrank while it was being sent".into());
+        }
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// Send up to `len` bytes of `file` from its current offset with
+/// `sendfile(2)`, returning how many were sent. Stops early, leaving the rest
+/// to the caller, if the kernel cannot sendfile this file (EINVAL, ENOSYS).
+#[cfg(all(feature = "zero-copy", target_os = "linux"))]
+fn sendfile_all(stream: &TcpStream, file: &File, len: u64) -> std::io::Result<u64> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut sent = 0u64;
+    while sent < len {
+        // Linux sends at most 0x7ffff000 bytes per call
+        let count = (len - sent).min(0x7fff_f000) as usize;
+        let n = unsafe { libc::sendfile(stream.as_raw_fd(), file.as_raw_fd(), std::ptr::null_mut(), count) };
+        if n < 0 {
+            let err = std::io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::EINVAL) | Some(libc::ENOSYS) => {
+                    debug!("sendfile unavailable ({}), copying the chunk instead", err);
+                    break;
+                }
+                _ => return Err(err),
+            }
+        }
+        if n == 0 {
+            // End of file; the copy path reports the short chunk
+            break;
+        }
+        sent += n as u64;
+    }
+    Ok(sent)
+}
+
+/// Without `sendfile` every chunk goes through the copy path
+#[cfg(not(all(feature = "zero-copy", target_os = "linux")))]
+fn sendfile_all(_stream: &TcpStream, _file: &File, _len: u64) -> std::io::Result<u64> {
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::protocol::GetChunkMsg;
+    use tempfile::tempdir;
+
+    type Handler = Arc<dyn Fn(String, Message) -> Option<Message> + Send + Sync>;
+
+    /// Serve peer connections on a local port, as `PeerManager::start` does
+    fn serve(handler: Handler, chunk_files: Option<ChunkFileLookup>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let addr = stream.peer_addr().unwrap();
+                let handler = Arc::clone(&handler);
+                let chunk_files = chunk_files.clone();
+                thread::spawn(move || handle_peer_connection(stream, addr, handler, None, chunk_files));
+            }
+        });
+        address
+    }
+
+    /// Answers GetChunk by reading the chunk file into a ChunkData message
+    fn copy_handler(dir: PathBuf) -> Handler {
+        Arc::new(move |_, msg| match msg {
+            Message::GetChunk(get) => Some(Message::ChunkData(ChunkDataMsg {
+                hash: get.hash,
+                data: std::fs::read(dir.join(hex::encode(get.hash))).ok(),
+                error: None,
+            })),
+            _ => None,
+        })
+    }
+
+    fn chunk_lookup(dir: PathBuf) -> ChunkFileLookup {
+        Arc::new(move |hash| Some(dir.join(hex::encode(hash))).filter(|path| path.exists()))
+    }
+
+    fn fetch(conn: &PeerConnection, hash: [u8; 32]) -> ChunkDataMsg {
+        match conn.request(&Message::GetChunk(GetChunkMsg { hash })).unwrap() {
+            Message::ChunkData(resp) => resp,
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_chunk_files_served_as_raw_frames() {
+        let dir = tempdir().unwrap();
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.path().join(hex::encode([1u8; 32])), &data).unwrap();
+
+        let address = serve(copy_handler(dir.path().to_path_buf()), Some(chunk_lookup(dir.path().to_path_buf())));
+        let conn = PeerConnection::connect("leader".to_string(), &address).unwrap();
+
+        let resp = fetch(&conn, [1u8; 32]);
+        assert_eq!(resp.hash, [1u8; 32]);
+        assert_eq!(resp.data.unwrap(), data);
+
+        // Not on disk: falls through to the handler, on the same connection
+        let resp = fetch(&conn, [2u8; 32]);
+        assert_eq!(resp.hash, [2u8; 32]);
+        assert!(resp.data.is_none());
+        assert_eq!(fetch(&conn, [1u8; 32]).data.unwrap().len(), data.len());
+    }
+
+    #[cfg(feature = "zero-copy")]
+    #[test]
+    fn test_send_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("chunk");
+        std::fs::write(&path, b"raw chunk bytes").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let receiver = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            read_frame(&mut stream, MAX_INBOUND_FRAME).unwrap()
+        });
+
+        let conn = PeerConnection::connect("follower".to_string(), &address).unwrap();
+        conn.send_file(&[7u8; 32], &path).unwrap();
+        match receiver.join().unwrap() {
+            Message::ChunkData(resp) => {
+                assert_eq!(resp.hash, [7u8; 32]);
+                assert_eq!(resp.data.unwrap(), b"raw chunk bytes");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    /// CPU time used by this process so far
+    fn process_cpu_time() -> Duration {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
+        let to_duration = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+        to_duration(usage.ru_utime) + to_duration(usage.ru_stime)
+    }
+
+    /// A leader serving a 4 MB chunk 10 times to each of 100 followers, with
+    /// the chunk copied into ChunkData messages vs. sent as a raw frame:
+    /// `cargo test --release --features zero-copy -- --ignored --nocapture bench_chunk_serving`
+    #[test]
+    #[ignore]
+    fn bench_chunk_serving() {
+        const FOLLOWERS: usize = 100;
+        const FETCHES: usize = 10;
+        let dir = tempdir().unwrap();
+        let data: Vec<u8> = (0..4 * 1024 * 1024u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        std::fs::write(dir.path().join(hex::encode([1u8; 32])), &data).unwrap();
+
+        let run = |chunk_files: Option<ChunkFileLookup>| {
+            let address = serve(copy_handler(dir.path().to_path_buf()), chunk_files);
+            let cpu = process_cpu_time();
+            let start = std::time::Instant::now();
+            let followers: Vec<_> = (0..FOLLOWERS).map(|i| {
+                let address = address.clone();
+                thread::spawn(move || {
+                    let conn = PeerConnection::connect(format!("follower-{}", i), &address).unwrap();
+                    for _ in 0..FETCHES {
+                        assert_eq!(fetch(&conn, [1u8; 32]).data.unwrap().len(), 4 * 1024 * 1024);
+                    }
+                })
+            }).collect();
+            for follower in followers {
+                follower.join().unwrap();
+            }
+            (start.elapsed(), process_cpu_time() - cpu)
+        };
+
+        let bytes = (FOLLOWERS * FETCHES * data.len()) as f64;
+        let (copy_time, copy_cpu) = run(None);
+        let (raw_time, raw_cpu) = run(Some(chunk_lookup(dir.path().to_path_buf())));
+        println!("copy:     {:?} ({:.0} MB/s), {:?} CPU", copy_time, bytes / copy_time.as_secs_f64() / 1e6, copy_cpu);
+        println!("raw:      {:?} ({:.0} MB/s), {:?} CPU (sendfile: {})",
+            raw_time, bytes / raw_time.as_secs_f64() / 1e6, raw_cpu, cfg!(feature = "zero-copy"));
+    }
+}
@@ -22,6 +22,12 @@ pub enum Message {
     ChunkData(ChunkDataMsg),
     /// Request to delete a chunk
     DeleteChunk(DeleteChunkMsg),
+    /// Store a chunk moved here by the rebalancer (answered with StoreChunkAck)
+    MigrateChunk(MigrateChunkMsg),
+    /// Ask a node which chunks it holds
+    ChunkInventory(ChunkInventoryMsg),
+    /// Chunks held by a node
+    ChunkInventoryResponse(ChunkInventoryResponseMsg),
 
     // === Index Operations ===
     /// Update to file index (file created/modified/deleted)
@@ -38,6 +44,10 @@ pub enum Message {
     RenameFile(RenameFileMsg),
     /// Set file/directory attributes (chmod/chown)
     SetAttr(SetAttrMsg),
+    /// Set an extended attribute
+    SetXattr(SetXattrMsg),
+    /// Remove an extended attribute
+    RemoveXattr(RemoveXattrMsg),
 
     // === Client Operations ===
     /// Client requesting file read (forwarded to leader if needed)
@@ -58,6 +68,8 @@ pub enum Message {
     DeleteDir(DeleteDirMsg),
     /// Create a symbolic link
     CreateSymlink(CreateSymlinkMsg),
+    /// Create a hard link
+    CreateHardLink(CreateHardLinkMsg),
     /// Response to file operation
     FileOpResponse(FileOpResponseMsg),
     /// Get file/directory attributes (thin client)
@@ -68,6 +80,18 @@ pub enum Message {
     ReadDir(ReadDirMsg),
     /// Read directory response
     ReadDirResponse(ReadDirResponseMsg),
+    /// Copy a file, sharing the source's chunks (WebDAV COPY)
+    CopyFile(CopyFileMsg),
+    /// Allocate, punch or collapse a range of a file (`fallocate(2)`)
+    Fallocate(FallocateMsg),
+
+    // === Leader Election (`cluster.enable_auto_failover`) ===
+    /// Candidate asking for a vote after the leader disappeared
+    RequestVote(RequestVoteMsg),
+    /// Vote for the candidate
+    VoteGranted(VoteMsg),
+    /// Vote refused
+    VoteDenied(VoteMsg),
 }
 
 /// Node announcement for discovery
@@ -131,6 +155,23 @@ pub struct DeleteChunkMsg {
     pub hash: [u8; 32],
 }
 
+/// Chunk moved to this node by the rebalancer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrateChunkMsg {
+    pub hash: [u8; 32],
+    pub data: Vec<u8>,
+}
+
+/// Request for the hashes of every locally stored chunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkInventoryMsg {}
+
+/// Hashes of every locally stored chunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkInventoryResponseMsg {
+    pub hashes: Vec<[u8; 32]>,
+}
+
 /// Index update message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexUpdateMsg {
@@ -161,6 +202,17 @@ pub enum IndexOperation {
         from_path: String,
         to_path: String,
     },
+    /// Extended attribute set (`Some`) or removed (`None`)
+    SetXattr {
+        path: String,
+        name: String,
+        value: Option<Vec<u8>>,
+    },
+    /// Hard link to an existing file created
+    Link {
+        src_path: String,
+        dst_path: String,
+    },
 }
 
 /// Chunk reference in protocol
@@ -336,6 +388,14 @@ pub struct RenameFileMsg {
     pub to_path: String,
 }
 
+/// Copy file message. The copy references the same chunk hashes, so no
+/// chunk data is moved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyFileMsg {
+    pub from_path: String,
+    pub to_path: String,
+}
+
 /// Set file attributes message (chmod/chown)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SetAttrMsg {
@@ -347,6 +407,32 @@ pub struct SetAttrMsg {
     pub modified_ms: Option<u64>,
 }
 
+/// fallocate message: `mode` is the `fallocate(2)` mode flags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FallocateMsg {
+    pub path: String,
+    pub mode: i32,
+    pub offset: u64,
+    pub length: u64,
+}
+
+/// Set extended attribute message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetXattrMsg {
+    pub path: String,
+    pub name: String,
+    pub value: Vec<u8>,
+    /// XATTR_CREATE / XATTR_REPLACE
+    pub flags: i32,
+}
+
+/// Remove extended attribute message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoveXattrMsg {
+    pub path: String,
+    pub name: String,
+}
+
 /// Create symbolic link message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateSymlinkMsg {
@@ -356,6 +442,39 @@ pub struct CreateSymlinkMsg {
     pub target: String,
 }
 
+/// Create hard link message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateHardLinkMsg {
+    /// Existing file to link to
+    pub src_path: String,
+    /// Path of the new link
+    pub dst_path: String,
+}
+
+/// Vote request from a candidate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestVoteMsg {
+    pub candidate_id: String,
+    pub term: u64,
+    /// The candidate's index version, so voters can refuse a stale candidate
+    pub last_index_version: u64,
+}
+
+/// Answer to a vote request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteMsg {
+    pub voter_id: String,
+    /// The voter's term, which tells a stale candidate to catch up
+    pub term: u64,
+}
+
+/// Set in a frame's length prefix when the frame is a raw chunk rather than
+/// an encoded message: the 32-byte hash followed by the chunk bytes, neither
+/// serialized nor compressed, so the sender can hand the chunk file straight
+/// to the socket. The rest of the prefix is the chunk length. Receivers read
+/// it as a `ChunkData` message.
+pub const RAW_CHUNK_FRAME: u32 = 1 << 31;
+
 /// Serialize and compress a message for transmission
 /// Uses LZ4 compression — extremely fast with good ratios for file data
 pub fn encode_message(msg: &Message) -> Result<Vec<u8>, bincode::Error> {
@@ -0,0 +1,245 @@
+//! DNS SRV peer discovery
+//!
+//! `cluster.discovery_srv` names an SRV record listing the cluster's nodes
+//! (a Kubernetes headless service publishes one for its pods). The record is
+//! looked up every `cluster.discovery_interval_secs`; each target and port
+//! becomes a peer address. An address missing from `MISSES_BEFORE_GONE`
+//! lookups in a row is dropped, so adding or removing records adds or
+//! removes nodes without touching any config.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+use trust_dns_resolver::Resolver;
+
+/// Lookups an address can be missing from before its peer is dropped
+pub const MISSES_BEFORE_GONE: u32 = 3;
+
+/// What changed in one lookup
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SrvUpdate {
+    /// Addresses in the record now, sorted
+    pub present: Vec<String>,
+    /// Addresses seen for the first time
+    pub added: Vec<String>,
+    /// Addresses missing from `MISSES_BEFORE_GONE` lookups in a row
+    pub removed: Vec<String>,
+}
+
+/// Looks up an SRV record and tracks the peers it lists
+pub struct SrvDiscovery {
+    name: String,
+    /// Name servers to ask instead of the system's
+    name_servers: Option<ResolverConfig>,
+    /// Addresses seen, with the lookups in a row each has been missing from
+    known: HashMap<String, u32>,
+}
+
+impl SrvDiscovery {
+    pub fn new(name: String) -> Self {
+        Self { name, name_servers: None, known: HashMap::new() }
+    }
+
+    /// Ask the name server at `address` rather than the ones in /etc/resolv.conf
+    pub fn with_name_server(mut self, address: SocketAddr) -> Self {
+        let group = NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true);
+        self.name_servers = Some(ResolverConfig::from_parts(None, Vec::new(), group));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Resolve the record to `ip:port` peer addresses. A record that doesn't
+    /// exist resolves to no addresses.
+    pub fn lookup(&self) -> Result<Vec<String>, ResolveError> {
+        // A fresh resolver each time, so nothing is served from its cache
+        let resolver = match &self.name_servers {
+            Some(config) => Resolver::new(config.clone(), ResolverOpts::default())?,
+            None => Resolver::from_system_conf()?,
+        };
+        let records = match resolver.srv_lookup(self.name.as_str()) {
+            Ok(records) => records,
+            Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut addresses = Vec::new();
+        for srv in records.iter() {
+            let target = srv.target().to_utf8();
+            match resolver.lookup_ip(target.as_str()) {
+                Ok(ips) => addresses.extend(ips.iter().next().map(|ip| SocketAddr::new(ip, srv.port()).to_string())),
+                Err(e) => tracing::warn!("SRV target {} of {} did not resolve: {}", target, self.name, e),
+            }
+        }
+        addresses.sort();
+        addresses.dedup();
+        Ok(addresses)
+    }
+
+    /// Look the record up and work out which peers came and went
+    pub fn refresh(&mut self) -> Result<SrvUpdate, ResolveError> {
+        let found = self.lookup()?;
+        Ok(self.update(found))
+    }
+
+    fn update(&mut self, present: Vec<String>) -> SrvUpdate {
+        let mut update = SrvUpdate::default();
+        for address in &present {
+            if self.known.insert(address.clone(), 0).is_none() {
+                update.added.push(address.clone());
+            }
+        }
+        for (address, misses) in self.known.iter_mut() {
+            if !present.contains(address) {
+                *misses += 1;
+                if *misses >= MISSES_BEFORE_GONE {
+                    update.removed.push(address.clone());
+                }
+            }
+        }
+        for address in &update.removed {
+            self.known.remove(address);
+        }
+        update.removed.sort();
+        update.present = present;
+        update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    use hickory_server::authority::{Catalog, ZoneType};
+    use hickory_server::proto::rr::rdata::{A, SOA, SRV};
+    use hickory_server::proto::rr::{Name, RData, Record, RecordType};
+    use hickory_server::store::in_memory::InMemoryAuthority;
+    use hickory_server::ServerFuture;
+
+    const SERVICE: &str = "_wolfdisk._tcp.example.com.";
+
+    fn addresses(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_peer_dropped_after_three_misses() {
+        let mut srv = SrvDiscovery::new(SERVICE.to_string());
+        let update = srv.update(addresses(&["10.0.0.1:9500", "10.0.0.2:9500"]));
+        assert_eq!(update.added, addresses(&["10.0.0.1:9500", "10.0.0.2:9500"]));
+
+        // 10.0.0.2 goes missing, comes back, then goes for good
+        for _ in 0..2 {
+            assert!(srv.update(addresses(&["10.0.0.1:9500"])).removed.is_empty());
+        }
+        assert!(srv.update(addresses(&["10.0.0.1:9500", "10.0.0.2:9500"])).added.is_empty());
+        for _ in 0..2 {
+            assert!(srv.update(addresses(&["10.0.0.1:9500"])).removed.is_empty());
+        }
+        let update = srv.update(addresses(&["10.0.0.1:9500"]));
+        assert_eq!(update.removed, addresses(&["10.0.0.2:9500"]));
+
+        // Seen again later: a new peer
+        assert_eq!(srv.update(addresses(&["10.0.0.2:9500"])).added, addresses(&["10.0.0.2:9500"]));
+    }
+
+    /// A name server for example.com holding `_wolfdisk._tcp` records that
+    /// the test can change while it runs
+    struct TestDns {
+        runtime: tokio::runtime::Runtime,
+        authority: Arc<InMemoryAuthority>,
+        address: SocketAddr,
+        serial: u32,
+    }
+
+    impl TestDns {
+        fn start() -> Self {
+            let origin = Name::from_ascii("example.com.").unwrap();
+            let mut authority = InMemoryAuthority::empty(origin.clone(), ZoneType::Primary, false);
+            let soa = SOA::new(
+                Name::from_ascii("ns.example.com.").unwrap(),
+                Name::from_ascii("admin.example.com.").unwrap(),
+                1, 60, 60, 60, 0,
+            );
+            authority.upsert_mut(Record::from_rdata(origin.clone(), 0, RData::SOA(soa)), 1);
+            let authority = Arc::new(authority);
+
+            let mut catalog = Catalog::new();
+            catalog.upsert(origin.into(), Box::new(Arc::clone(&authority)));
+
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            let socket = runtime.block_on(tokio::net::UdpSocket::bind("127.0.0.1:0")).unwrap();
+            let address = socket.local_addr().unwrap();
+            runtime.spawn(async move {
+                let mut server = ServerFuture::new(catalog);
+                server.register_socket(socket);
+                server.block_until_done().await
+            });
+            Self { runtime, authority, address, serial: 1 }
+        }
+
+        /// Publish `node` at 127.0.0.`host` with an SRV record for `port`
+        fn add_node(&mut self, node: &str, host: u8, port: u16) {
+            let target = Name::from_ascii(format!("{}.example.com.", node)).unwrap();
+            let a = Record::from_rdata(target.clone(), 0, RData::A(A::from(Ipv4Addr::new(127, 0, 0, host))));
+            let srv = Record::from_rdata(Name::from_ascii(SERVICE).unwrap(), 0, RData::SRV(SRV::new(10, 10, port, target)));
+            self.serial += 1;
+            self.runtime.block_on(async {
+                self.authority.upsert(a, self.serial).await;
+                self.authority.upsert(srv, self.serial).await;
+            });
+        }
+
+        /// Take `node`'s SRV record away
+        fn remove_node(&mut self, node: &str) {
+            let target = Name::from_ascii(format!("{}.example.com.", node)).unwrap();
+            self.runtime.block_on(async {
+                let mut records = self.authority.records_mut().await;
+                let keep: Vec<Record> = records.iter()
+                    .filter(|(key, _)| key.record_type == RecordType::SRV)
+                    .flat_map(|(_, set)| set.records_without_rrsigs().cloned().collect::<Vec<_>>())
+                    .filter(|record| !matches!(record.data(), Some(RData::SRV(srv)) if *srv.target() == target))
+                    .collect();
+                records.retain(|key, _| key.record_type != RecordType::SRV);
+                drop(records);
+                for record in keep {
+                    self.authority.upsert(record, self.serial).await;
+                }
+            });
+        }
+    }
+
+    #[test]
+    fn test_srv_records_add_and_remove_peers() {
+        let mut dns = TestDns::start();
+        let mut srv = SrvDiscovery::new(SERVICE.to_string()).with_name_server(dns.address);
+
+        // No record yet: no peers, and not an error
+        assert_eq!(srv.refresh().unwrap(), SrvUpdate::default());
+
+        dns.add_node("node1", 1, 9500);
+        dns.add_node("node2", 2, 9600);
+        let update = srv.refresh().unwrap();
+        assert_eq!(update.added, addresses(&["127.0.0.1:9500", "127.0.0.2:9600"]));
+        assert_eq!(update.present, update.added);
+
+        // A third node is scaled up
+        dns.add_node("node3", 3, 9500);
+        assert_eq!(srv.refresh().unwrap().added, addresses(&["127.0.0.3:9500"]));
+
+        // node2 is scaled down: gone after three lookups without it
+        dns.remove_node("node2");
+        for _ in 0..MISSES_BEFORE_GONE - 1 {
+            let update = srv.refresh().unwrap();
+            assert_eq!(update.present, addresses(&["127.0.0.1:9500", "127.0.0.3:9500"]));
+            assert!(update.removed.is_empty());
+        }
+        assert_eq!(srv.refresh().unwrap().removed, addresses(&["127.0.0.2:9600"]));
+    }
+}
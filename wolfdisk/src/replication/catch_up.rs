@@ -0,0 +1,237 @@
+//! Chunk catch-up after an index sync
+//!
+//! A SyncResponse carries the leader's index but not the chunk data, so a
+//! node that was cut off from the leader (or restarted after a long outage)
+//! ends up with index entries pointing at chunks it never received. After the
+//! index is applied, every chunk referenced by the response is checked against
+//! the local store and the missing ones are fetched from the leader with
+//! `GetChunk`, 16 requests per round trip.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::cluster::PeerInfo;
+use crate::network::peer::PeerConnection;
+use crate::network::protocol::{ChunkDataMsg, GetChunkMsg, IndexEntryMsg, Message};
+use crate::storage::chunks::ChunkStore;
+
+/// GetChunk requests sent before waiting for their responses
+pub const CATCH_UP_BATCH_SIZE: usize = 16;
+
+/// Log progress every this many fetched chunks
+const PROGRESS_LOG_INTERVAL: usize = 100;
+
+/// Chunks still to be fetched by the running catch-up (wolfdisk_catch_up_chunks_remaining)
+pub static CATCH_UP_CHUNKS_REMAINING: AtomicU64 = AtomicU64::new(0);
+
+/// Outcome of a catch-up pass
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CatchUpStats {
+    /// Chunks fetched and stored locally
+    pub fetched: usize,
+    /// Chunks the leader could not provide (or returned with a bad hash)
+    pub unavailable: Vec<[u8; 32]>,
+}
+
+/// Chunks referenced by `entries` that are not in the local store, without duplicates
+pub fn missing_chunks(chunk_store: &ChunkStore, entries: &[IndexEntryMsg]) -> Vec<[u8; 32]> {
+    let mut seen = HashSet::new();
+    entries.iter()
+        .flat_map(|e| e.chunks.iter())
+        .filter(|c| seen.insert(c.hash) && !chunk_store.exists(&c.hash))
+        .map(|c| c.hash)
+        .collect()
+}
+
+/// Send a GetChunk for every hash, then read the responses in order.
+/// Use a dedicated connection: a request from another thread sharing it
+/// would take one of our responses.
+pub fn request_chunks(conn: &PeerConnection, hashes: &[[u8; 32]]) -> Result<Vec<ChunkDataMsg>, String> {
+    for hash in hashes {
+        conn.send(&Message::GetChunk(GetChunkMsg { hash: *hash }))
+            .map_err(|e| format!("Failed to request chunk: {}", e))?;
+    }
+    hashes.iter()
+        .map(|_| match conn.recv() {
+            Ok(Message::ChunkData(resp)) => Ok(resp),
+            Ok(other) => Err(format!("Unexpected response to GetChunk: {:?}", other)),
+            Err(e) => Err(format!("Failed to receive chunk: {}", e)),
+        })
+        .collect()
+}
+
+/// A good copy of one chunk from the first storage peer that has it, the
+/// leader (which holds every chunk) first. Used to repair corrupt chunks.
+pub fn fetch_from_peers(peers: &[PeerInfo], hash: &[u8; 32]) -> Option<Vec<u8>> {
+    let mut peers: Vec<&PeerInfo> = peers.iter().filter(|p| !p.is_client).collect();
+    peers.sort_by_key(|p| !p.is_leader);
+    peers.into_iter().find_map(|peer| {
+        let conn = PeerConnection::connect(peer.node_id.clone(), &peer.address).ok()?;
+        let resp = request_chunks(&conn, &[*hash]).ok()?.pop()?;
+        match resp.data {
+            Some(data) if Sha256::digest(&data)[..] == hash[..] => Some(data),
+            Some(_) => {
+                warn!("Copy of chunk {} on {} is corrupt too", hex::encode(hash), peer.node_id);
+                None
+            }
+            None => None,
+        }
+    })
+}
+
+/// Fetch `missing` chunks in batches of `CATCH_UP_BATCH_SIZE` and store them.
+/// `fetch` returns the leader's responses for one batch; `on_progress` is
+/// called with the number of chunks still outstanding after each batch.
+pub fn catch_up_chunks<F, P>(
+    chunk_store: &ChunkStore,
+    missing: &[[u8; 32]],
+    mut fetch: F,
+    mut on_progress: P,
+) -> Result<CatchUpStats, String>
+where
+    F: FnMut(&[[u8; 32]]) -> Result<Vec<ChunkDataMsg>, String>,
+    P: FnMut(usize),
+{
+    let mut stats = CatchUpStats::default();
+    let mut remaining = missing.len();
+    CATCH_UP_CHUNKS_REMAINING.store(remaining as u64, Ordering::Relaxed);
+    on_progress(remaining);
+    if remaining == 0 {
+        return Ok(stats);
+    }
+    info!("Catching up {} missing chunks from leader", remaining);
+
+    let result: Result<(), String> = (|| {
+        for batch in missing.chunks(CATCH_UP_BATCH_SIZE) {
+            for resp in fetch(batch)? {
+                match resp.data {
+                    Some(data) if Sha256::digest(&data)[..] == resp.hash[..] => {
+                        chunk_store.store_with_hash(&resp.hash, &data)
+                            .map_err(|e| format!("Failed to store chunk {}: {}", hex::encode(resp.hash), e))?;
+                        stats.fetched += 1;
+                    }
+                    Some(_) => {
+                        warn!("Chunk {} from leader failed hash verification", hex::encode(resp.hash));
+                        stats.unavailable.push(resp.hash);
+                    }
+                    None => {
+                        warn!("Leader does not have chunk {}: {}", hex::encode(resp.hash),
+                            resp.error.as_deref().unwrap_or("unknown error"));
+                        stats.unavailable.push(resp.hash);
+                    }
+                }
+            }
+
+            let checked_before = missing.len() - remaining;
+            remaining -= batch.len();
+            CATCH_UP_CHUNKS_REMAINING.store(remaining as u64, Ordering::Relaxed);
+            on_progress(remaining);
+            let checked = missing.len() - remaining;
+            if checked / PROGRESS_LOG_INTERVAL > checked_before / PROGRESS_LOG_INTERVAL {
+                info!("Catch-up: {}/{} chunks checked, {} remaining", checked, missing.len(), remaining);
+            }
+        }
+        Ok(())
+    })();
+
+    // Nothing is in flight any more, whether we finished or gave up
+    CATCH_UP_CHUNKS_REMAINING.store(0, Ordering::Relaxed);
+    result?;
+
+    info!("Catch-up complete: {} chunks fetched, {} unavailable", stats.fetched, stats.unavailable.len());
+    Ok(stats)
+}
+
+/// Fetch `missing` chunks from the leader over a dedicated connection
+pub fn catch_up_from_leader<P: FnMut(usize)>(
+    chunk_store: &ChunkStore,
+    leader_id: &str,
+    leader_addr: &str,
+    missing: &[[u8; 32]],
+    on_progress: P,
+) -> Result<CatchUpStats, String> {
+    if missing.is_empty() {
+        return catch_up_chunks(chunk_store, missing, |_| Ok(Vec::new()), on_progress);
+    }
+    let conn = PeerConnection::connect(leader_id.to_string(), leader_addr)
+        .map_err(|e| format!("Failed to connect to leader for catch-up: {}", e))?;
+    catch_up_chunks(chunk_store, missing, |batch| request_chunks(&conn, batch), on_progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::protocol::ChunkRefMsg;
+    use tempfile::tempdir;
+
+    fn write_file(store: &ChunkStore, n: usize) -> IndexEntryMsg {
+        let data = format!("file {} contents", n).repeat(n + 1);
+        let hash = store.store(data.as_bytes()).unwrap();
+        IndexEntryMsg {
+            path: format!("/file{}", n),
+            is_dir: false,
+            size: data.len() as u64,
+            modified_ms: 0,
+            permissions: 0o644,
+            chunks: vec![ChunkRefMsg { hash, offset: 0, size: data.len() as u32 }],
+        }
+    }
+
+    fn serve(leader: &ChunkStore, batch: &[[u8; 32]]) -> Result<Vec<ChunkDataMsg>, String> {
+        assert!(batch.len() <= CATCH_UP_BATCH_SIZE);
+        Ok(batch.iter().map(|hash| ChunkDataMsg {
+            hash: *hash,
+            data: leader.get(hash).ok(),
+            error: None,
+        }).collect())
+    }
+
+    #[test]
+    fn test_catch_up_after_partition() {
+        let leader_dir = tempdir().unwrap();
+        let follower_dir = tempdir().unwrap();
+        let leader = ChunkStore::new(leader_dir.path().to_path_buf(), 1024).unwrap();
+        let follower = ChunkStore::new(follower_dir.path().to_path_buf(), 1024).unwrap();
+
+        // 50 files replicated while connected
+        let mut entries: Vec<IndexEntryMsg> = (0..50).map(|n| write_file(&leader, n)).collect();
+        for entry in &entries {
+            let hash = entry.chunks[0].hash;
+            follower.store_with_hash(&hash, &leader.get(&hash).unwrap()).unwrap();
+        }
+        assert!(missing_chunks(&follower, &entries).is_empty());
+
+        // Follower partitioned while 20 more files are written
+        entries.extend((50..70).map(|n| write_file(&leader, n)));
+        let missing = missing_chunks(&follower, &entries);
+        assert_eq!(missing.len(), 20);
+
+        // Reconnect: the SyncResponse lists all 70 files, catch-up fetches the rest
+        let mut progress = Vec::new();
+        let stats = catch_up_chunks(&follower, &missing, |batch| serve(&leader, batch), |r| progress.push(r)).unwrap();
+        assert_eq!(stats, CatchUpStats { fetched: 20, unavailable: Vec::new() });
+        assert_eq!(progress, vec![20, 4, 0]);
+        assert!(missing_chunks(&follower, &entries).is_empty());
+        for entry in &entries {
+            let hash = entry.chunks[0].hash;
+            assert_eq!(follower.get(&hash).unwrap(), leader.get(&hash).unwrap());
+        }
+        assert_eq!(CATCH_UP_CHUNKS_REMAINING.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_catch_up_rejects_corrupt_chunk() {
+        let dir = tempdir().unwrap();
+        let follower = ChunkStore::new(dir.path().to_path_buf(), 1024).unwrap();
+        let hash = [7u8; 32];
+
+        let stats = catch_up_chunks(&follower, &[hash], |batch| {
+            Ok(vec![ChunkDataMsg { hash: batch[0], data: Some(b"not it".to_vec()), error: None }])
+        }, |_| {}).unwrap();
+        assert_eq!(stats.unavailable, vec![hash]);
+        assert!(!follower.exists(&hash));
+    }
+}
@@ -0,0 +1,38 @@
+//! Per-peer replication lag
+//!
+//! The leader's broadcast thread sends the chunks written since its last
+//! round to each peer chosen for them. A peer's lag is the number of chunks
+//! of the current round that haven't reached it yet: chunks still to be sent
+//! plus sends that failed. It is reset at the start of every round.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Chunks each peer is behind the leader
+#[derive(Debug, Default)]
+pub struct ReplicationLag {
+    chunks: Mutex<BTreeMap<String, u64>>,
+}
+
+impl ReplicationLag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a round that sends `chunks` to each peer
+    pub fn start_round(&self, chunks: BTreeMap<String, u64>) {
+        *self.chunks.lock().unwrap() = chunks;
+    }
+
+    /// A chunk reached `peer`
+    pub fn delivered(&self, peer: &str) {
+        if let Some(pending) = self.chunks.lock().unwrap().get_mut(peer) {
+            *pending = pending.saturating_sub(1);
+        }
+    }
+
+    /// Lag of every peer in the current round
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.chunks.lock().unwrap().clone()
+    }
+}
@@ -5,6 +5,14 @@
 //! - Shared: Single leader accepts writes, followers sync from leader
 //! - Replicated: Quorum-based writes for high availability
 
+pub mod catch_up;
+pub mod lag;
+pub mod placement;
+pub mod rebalance;
 pub mod sync;
 
-pub use sync::{ReplicationManager, SyncState};
+pub use catch_up::{catch_up_from_leader, fetch_from_peers, missing_chunks, CatchUpStats, CATCH_UP_CHUNKS_REMAINING};
+pub use lag::ReplicationLag;
+pub use placement::{chunk_targets, RackStatus, RackUsage};
+pub use rebalance::{plan_rebalance, ChunkMove, RebalanceStatus, Rebalancer};
+pub use sync::{ReplicationManager, SyncProgress, SyncProgressTracker, SyncState};
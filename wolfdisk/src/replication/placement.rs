@@ -0,0 +1,241 @@
+//! Rack-aware replica placement
+//!
+//! With `replication.rack_aware` the leader sends each chunk to `factor - 1`
+//! followers rather than to all of them, chosen to spread the copies over as
+//! many racks as possible: the rack holding the fewest copies so far (the
+//! leader's own copy included) is filled next, and a rack other than the
+//! leader's wins a tie. Which node of a rack takes the copy rotates with the
+//! chunk hash, so load spreads evenly across nodes in the same rack.
+//! Nodes without `node.rack` count as one unnamed rack.
+//!
+//! Followers that were not chosen still get the file's metadata and fetch
+//! chunks from the leader on demand when they are read.
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cluster::PeerInfo;
+use crate::config::ReplicationConfig;
+
+/// Name shown for nodes that have no `node.rack`
+pub const UNASSIGNED_RACK: &str = "(none)";
+
+/// Followers that should store a copy of the chunk `hash` written on a
+/// leader in `leader_rack`. Without `rack_aware` every follower does.
+pub fn chunk_targets<'a>(
+    replication: &ReplicationConfig,
+    leader_rack: Option<&str>,
+    peers: &'a [PeerInfo],
+    hash: &[u8; 32],
+) -> Vec<&'a PeerInfo> {
+    if !replication.rack_aware {
+        return peers.iter().filter(|p| !p.is_client).collect();
+    }
+    select_replicas(hash, leader_rack, peers, replication.factor.saturating_sub(1))
+}
+
+/// Pick `copies` followers for a chunk, spreading them across racks
+pub fn select_replicas<'a>(
+    hash: &[u8; 32],
+    leader_rack: Option<&str>,
+    peers: &'a [PeerInfo],
+    copies: usize,
+) -> Vec<&'a PeerInfo> {
+    let mut racks: BTreeMap<Option<&str>, Vec<&PeerInfo>> = BTreeMap::new();
+    for peer in peers.iter().filter(|p| !p.is_client) {
+        racks.entry(peer.rack.as_deref()).or_default().push(peer);
+    }
+    for nodes in racks.values_mut() {
+        nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+    }
+
+    let seed = u64::from_le_bytes(hash[..8].try_into().expect("8 bytes"));
+    let rack_count = racks.len();
+    // Per rack: copies placed (the leader's included) and nodes taken so far
+    let mut placed: Vec<(Option<&str>, Vec<&PeerInfo>, usize, usize)> = racks
+        .into_iter()
+        .map(|(rack, nodes)| {
+            let copies = usize::from(rack == leader_rack);
+            (rack, nodes, copies, 0)
+        })
+        .collect();
+
+    let mut chosen = Vec::with_capacity(copies);
+    while chosen.len() < copies {
+        // Fewest copies first, then racks other than the leader's, then a
+        // hash-rotated order so ties do not always favour the same rack
+        let next = placed
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, nodes, _, taken))| *taken < nodes.len())
+            .min_by_key(|(i, (rack, _, copies, _))| {
+                let rotation = (*i as u64).wrapping_add(seed) % rack_count as u64;
+                (*copies, *rack == leader_rack, rotation)
+            })
+            .map(|(i, _)| i);
+        let Some(i) = next else { break };
+
+        let (_, nodes, copies, taken) = &mut placed[i];
+        let start = (seed % nodes.len() as u64) as usize;
+        chosen.push(nodes[(start + *taken) % nodes.len()]);
+        *taken += 1;
+        *copies += 1;
+    }
+    chosen
+}
+
+/// Nodes and chunk copies in one rack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RackUsage {
+    pub rack: String,
+    pub nodes: Vec<String>,
+    pub copies: u64,
+}
+
+/// Where the chunks of the file index are placed, served by `GET /rack/status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RackStatus {
+    pub rack_aware: bool,
+    pub factor: usize,
+    pub leader: Option<String>,
+    pub chunks: u64,
+    pub racks: Vec<RackUsage>,
+    /// Number of distinct racks holding a chunk -> chunks with that spread
+    pub chunks_by_racks: BTreeMap<usize, u64>,
+}
+
+/// Work out the placement of `hashes` for a cluster of this node plus `peers`
+pub fn rack_status<'a>(
+    replication: &ReplicationConfig,
+    node_id: &str,
+    node_rack: Option<&str>,
+    is_leader: bool,
+    peers: &[PeerInfo],
+    hashes: impl IntoIterator<Item = &'a [u8; 32]>,
+) -> RackStatus {
+    let mut nodes: Vec<PeerInfo> = peers.to_vec();
+    nodes.push(PeerInfo {
+        node_id: node_id.to_string(),
+        address: String::new(),
+        is_leader,
+        is_client: false,
+        rack: node_rack.map(str::to_string),
+        last_seen: Instant::now(),
+    });
+    let leader = nodes.iter().find(|n| n.is_leader).cloned();
+    let followers: Vec<PeerInfo> = nodes
+        .iter()
+        .filter(|n| !n.is_leader)
+        .cloned()
+        .collect();
+
+    let rack_name = |rack: &Option<String>| rack.clone().unwrap_or_else(|| UNASSIGNED_RACK.to_string());
+    let mut racks: BTreeMap<String, RackUsage> = BTreeMap::new();
+    for node in nodes.iter().filter(|n| !n.is_client) {
+        let name = rack_name(&node.rack);
+        racks
+            .entry(name.clone())
+            .or_insert_with(|| RackUsage { rack: name, nodes: Vec::new(), copies: 0 })
+            .nodes
+            .push(node.node_id.clone());
+    }
+
+    let leader_rack = leader.as_ref().and_then(|l| l.rack.as_deref());
+    let mut chunks = 0;
+    let mut chunks_by_racks = BTreeMap::new();
+    for hash in hashes {
+        chunks += 1;
+        let mut holders: Vec<&PeerInfo> = chunk_targets(replication, leader_rack, &followers, hash);
+        holders.extend(leader.as_ref());
+
+        let mut spread = HashSet::new();
+        for holder in holders {
+            let name = rack_name(&holder.rack);
+            if let Some(usage) = racks.get_mut(&name) {
+                usage.copies += 1;
+            }
+            spread.insert(name);
+        }
+        *chunks_by_racks.entry(spread.len()).or_insert(0) += 1;
+    }
+
+    RackStatus {
+        rack_aware: replication.rack_aware,
+        factor: replication.factor,
+        leader: leader.map(|l| l.node_id),
+        chunks,
+        racks: racks.into_values().collect(),
+        chunks_by_racks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn peer(id: &str, rack: &str) -> PeerInfo {
+        PeerInfo {
+            node_id: id.to_string(),
+            address: format!("{}:9500", id),
+            is_leader: false,
+            is_client: false,
+            rack: Some(rack.to_string()),
+            last_seen: Instant::now(),
+        }
+    }
+
+    fn replication(factor: usize) -> ReplicationConfig {
+        let mut config = crate::config::Config::default().replication;
+        config.factor = factor;
+        config.rack_aware = true;
+        config
+    }
+
+    #[test]
+    fn test_one_copy_per_rack() {
+        let peers = vec![peer("b1", "B"), peer("b2", "B"), peer("c1", "C"), peer("a2", "A")];
+        for seed in 0..32u8 {
+            let chosen = select_replicas(&[seed; 32], Some("A"), &peers, 2);
+            let mut racks: Vec<_> = chosen.iter().map(|p| p.rack.as_deref().unwrap()).collect();
+            racks.sort();
+            assert_eq!(racks, vec!["B", "C"]);
+        }
+    }
+
+    #[test]
+    fn test_chunks_land_in_both_racks() {
+        // 4 nodes across 2 racks; node a1 leads
+        let peers = vec![peer("a2", "A"), peer("b1", "B"), peer("b2", "B")];
+        let hashes: Vec<[u8; 32]> = (0..64u8).map(|i| [i.wrapping_mul(37); 32]).collect();
+        let status = rack_status(&replication(3), "a1", Some("A"), true, &peers, &hashes);
+
+        assert_eq!(status.leader.as_deref(), Some("a1"));
+        assert_eq!(status.chunks, 64);
+        // Every chunk has copies in both racks, three copies in total
+        assert_eq!(status.chunks_by_racks.get(&2), Some(&64));
+        let copies: u64 = status.racks.iter().map(|r| r.copies).sum();
+        assert_eq!(copies, 64 * 3);
+
+        // Copies within rack B are spread over both of its nodes
+        let mut per_node: HashMap<&str, usize> = HashMap::new();
+        for hash in &hashes {
+            for p in select_replicas(hash, Some("A"), &peers, 2) {
+                *per_node.entry(p.node_id.as_str()).or_default() += 1;
+            }
+        }
+        assert!(per_node.get("b1").is_some_and(|n| *n > 0));
+        assert!(per_node.get("b2").is_some_and(|n| *n > 0));
+    }
+
+    #[test]
+    fn test_not_rack_aware_sends_to_all_followers() {
+        let peers = vec![peer("a2", "A"), peer("b1", "B"), peer("b2", "B")];
+        let mut config = replication(2);
+        config.rack_aware = false;
+        assert_eq!(chunk_targets(&config, Some("A"), &peers, &[0; 32]).len(), 3);
+        assert_eq!(chunk_targets(&replication(2), Some("A"), &peers, &[0; 32]).len(), 1);
+    }
+}
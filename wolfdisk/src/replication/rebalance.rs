@@ -0,0 +1,275 @@
+//! Chunk rebalancing across followers
+//!
+//! After a node joins (or comes back empty after a failure) the followers
+//! hold very different numbers of chunks. The leader asks every storage
+//! follower for its chunk inventory, works out the ideal per-node count, and
+//! for each node more than `storage.rebalance_threshold_pct` over it moves
+//! chunks to the emptiest nodes that lack them: the chunk is read from the
+//! leader's own store (which holds every chunk), sent with `MigrateChunk`,
+//! and deleted from the over-full node once the receiver has acknowledged
+//! it. Moves keep the number of copies of each chunk the same.
+//!
+//! Chunks are located by hash, not by node, so the file index needs no
+//! update; a follower that no longer holds a chunk fetches it from the
+//! leader when it is read. Migrations run on their own connections, limited
+//! to `storage.rebalance_bandwidth_mbps`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::cluster::ClusterManager;
+use crate::network::peer::PeerConnection;
+use crate::network::protocol::{ChunkInventoryMsg, DeleteChunkMsg, Message, MigrateChunkMsg};
+use crate::network::rate_limit::RateLimiter;
+use crate::storage::chunks::ChunkStore;
+
+/// One chunk to move from an over-full node to an under-full one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkMove {
+    pub hash: [u8; 32],
+    pub from: String,
+    pub to: String,
+}
+
+/// Progress of the current (or last) rebalance, served by `GET /rebalance/status`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RebalanceStatus {
+    pub running: bool,
+    /// Unix time the current or last run started
+    pub started_at: Option<u64>,
+    /// Unix time the last run finished
+    pub finished_at: Option<u64>,
+    /// Chunks held by each follower when the run started
+    pub counts: BTreeMap<String, u64>,
+    /// Ideal chunks per follower
+    pub ideal: u64,
+    pub planned: usize,
+    pub moved: usize,
+    pub failed: usize,
+    pub bytes_moved: u64,
+    pub error: Option<String>,
+}
+
+/// Plan the moves that bring every node to within `threshold_pct` of the
+/// ideal count. Nodes over the threshold are drained down to the ideal,
+/// each chunk going to the emptiest node that does not already hold it.
+pub fn plan_rebalance(holdings: &BTreeMap<String, HashSet<[u8; 32]>>, threshold_pct: u64) -> Vec<ChunkMove> {
+    if holdings.len() < 2 {
+        return Vec::new();
+    }
+    let total: usize = holdings.values().map(HashSet::len).sum();
+    let ideal = total.div_ceil(holdings.len());
+    let limit = ideal as f64 * (100 + threshold_pct) as f64 / 100.0;
+
+    let mut counts: BTreeMap<&str, usize> = holdings.iter().map(|(node, chunks)| (node.as_str(), chunks.len())).collect();
+    let mut received: BTreeMap<&str, HashSet<[u8; 32]>> = BTreeMap::new();
+    let mut moves = Vec::new();
+
+    let mut over: Vec<&str> = counts.iter().filter(|(_, &c)| c as f64 > limit).map(|(&n, _)| n).collect();
+    over.sort_by_key(|n| std::cmp::Reverse(counts[n]));
+    for from in over {
+        let mut chunks: Vec<&[u8; 32]> = holdings[from].iter().collect();
+        chunks.sort();
+        for hash in chunks {
+            if counts[from] <= ideal {
+                break;
+            }
+            let to = counts.iter()
+                .filter(|(&n, &c)| {
+                    c < ideal
+                        && !holdings[n].contains(hash)
+                        && !received.get(n).is_some_and(|r| r.contains(hash))
+                })
+                .min_by_key(|(_, &c)| c)
+                .map(|(&n, _)| n);
+            let Some(to) = to else { continue };
+
+            *counts.get_mut(from).unwrap() -= 1;
+            *counts.get_mut(to).unwrap() += 1;
+            received.entry(to).or_default().insert(*hash);
+            moves.push(ChunkMove { hash: *hash, from: from.to_string(), to: to.to_string() });
+        }
+    }
+    moves
+}
+
+/// Runs rebalances on the leader and tracks their progress
+pub struct Rebalancer {
+    cluster: Arc<ClusterManager>,
+    chunk_store: Arc<ChunkStore>,
+    threshold_pct: u64,
+    bandwidth_mbps: u64,
+    running: AtomicBool,
+    status: Mutex<RebalanceStatus>,
+}
+
+impl Rebalancer {
+    pub fn new(cluster: Arc<ClusterManager>, chunk_store: Arc<ChunkStore>, threshold_pct: u64, bandwidth_mbps: u64) -> Self {
+        Self {
+            cluster,
+            chunk_store,
+            threshold_pct,
+            bandwidth_mbps,
+            running: AtomicBool::new(false),
+            status: Mutex::new(RebalanceStatus::default()),
+        }
+    }
+
+    /// Progress of the current or last run
+    pub fn status(&self) -> RebalanceStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Start a rebalance on a background thread, unless one is running
+    pub fn start(self: &Arc<Self>) -> Result<RebalanceStatus, String> {
+        if !self.cluster.is_leader() {
+            return Err("Rebalancing runs on the leader".to_string());
+        }
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("A rebalance is already running".to_string());
+        }
+        *self.status.lock().unwrap() = RebalanceStatus {
+            running: true,
+            started_at: Some(unix_now()),
+            ..Default::default()
+        };
+        let this = Arc::clone(self);
+        std::thread::spawn(move || this.run());
+        Ok(self.status())
+    }
+
+    fn run(&self) {
+        let result = self.rebalance();
+        let mut status = self.status.lock().unwrap();
+        status.running = false;
+        status.finished_at = Some(unix_now());
+        match result {
+            Ok(()) => info!(
+                "Rebalance finished: {} of {} chunks moved ({:.1} MB), {} failed",
+                status.moved, status.planned, status.bytes_moved as f64 / 1_048_576.0, status.failed
+            ),
+            Err(e) => {
+                warn!("Rebalance failed: {}", e);
+                status.error = Some(e);
+            }
+        }
+        drop(status);
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    fn rebalance(&self) -> Result<(), String> {
+        // Dedicated connections: requests on the shared ones could pick up
+        // responses meant for other threads
+        let limiter_burst = 4 * 1024 * 1024;
+        let mut conns: BTreeMap<String, PeerConnection> = BTreeMap::new();
+        let mut holdings = BTreeMap::new();
+        for peer in self.cluster.peers().into_iter().filter(|p| !p.is_client && !p.is_leader) {
+            let conn = PeerConnection::connect(peer.node_id.clone(), &peer.address)
+                .map_err(|e| format!("Failed to connect to {}: {}", peer.node_id, e))?
+                .with_rate_limiter((self.bandwidth_mbps > 0).then(|| RateLimiter::new(self.bandwidth_mbps, limiter_burst)));
+            let hashes = match conn.request(&Message::ChunkInventory(ChunkInventoryMsg {})) {
+                Ok(Message::ChunkInventoryResponse(resp)) => resp.hashes,
+                Ok(other) => return Err(format!("Unexpected inventory reply from {}: {:?}", peer.node_id, other)),
+                Err(e) => return Err(format!("Failed to get inventory from {}: {}", peer.node_id, e)),
+            };
+            holdings.insert(peer.node_id.clone(), hashes.into_iter().collect::<HashSet<_>>());
+            conns.insert(peer.node_id, conn);
+        }
+
+        let moves = plan_rebalance(&holdings, self.threshold_pct);
+        {
+            let mut status = self.status.lock().unwrap();
+            status.counts = holdings.iter().map(|(n, c)| (n.clone(), c.len() as u64)).collect();
+            let total: u64 = status.counts.values().sum();
+            status.ideal = total.div_ceil(holdings.len().max(1) as u64);
+            status.planned = moves.len();
+        }
+        info!("Rebalancing {} chunks across {} followers", moves.len(), holdings.len());
+
+        for mv in moves {
+            match self.migrate(&conns[&mv.to], &conns[&mv.from], &mv) {
+                Ok(bytes) => {
+                    let mut status = self.status.lock().unwrap();
+                    status.moved += 1;
+                    status.bytes_moved += bytes;
+                }
+                Err(e) => {
+                    warn!("Failed to move chunk {} from {} to {}: {}", hex::encode(mv.hash), mv.from, mv.to, e);
+                    self.status.lock().unwrap().failed += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy one chunk to `to` and, once it has confirmed, delete it from `from`
+    fn migrate(&self, to: &PeerConnection, from: &PeerConnection, mv: &ChunkMove) -> Result<u64, String> {
+        let data = self.chunk_store.get(&mv.hash).map_err(|e| e.to_string())?;
+        let bytes = data.len() as u64;
+        match to.request(&Message::MigrateChunk(MigrateChunkMsg { hash: mv.hash, data })) {
+            Ok(Message::StoreChunkAck(ack)) if ack.success => {}
+            Ok(Message::StoreChunkAck(ack)) => return Err(ack.error.unwrap_or_else(|| "store failed".to_string())),
+            Ok(other) => return Err(format!("unexpected reply {:?}", other)),
+            Err(e) => return Err(e.to_string()),
+        }
+        from.send(&Message::DeleteChunk(DeleteChunkMsg { hash: mv.hash })).map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(i: u32) -> [u8; 32] {
+        let mut h = [0u8; 32];
+        h[..4].copy_from_slice(&i.to_be_bytes());
+        h
+    }
+
+    fn apply(holdings: &mut BTreeMap<String, HashSet<[u8; 32]>>, moves: &[ChunkMove]) {
+        for mv in moves {
+            assert!(holdings.get_mut(&mv.from).unwrap().remove(&mv.hash));
+            assert!(holdings.get_mut(&mv.to).unwrap().insert(mv.hash));
+        }
+    }
+
+    #[test]
+    fn test_new_node_evens_out_chunk_counts() {
+        // Two followers with a copy each of 300 chunks, then a third joins empty
+        let mut holdings = BTreeMap::new();
+        holdings.insert("node-a".to_string(), (0..300).map(hash).collect::<HashSet<_>>());
+        holdings.insert("node-b".to_string(), (0..300).map(hash).collect::<HashSet<_>>());
+        assert!(plan_rebalance(&holdings, 10).is_empty());
+
+        holdings.insert("node-c".to_string(), HashSet::new());
+        let moves = plan_rebalance(&holdings, 10);
+        apply(&mut holdings, &moves);
+
+        let counts: Vec<usize> = holdings.values().map(HashSet::len).collect();
+        assert_eq!(counts.iter().sum::<usize>(), 600);
+        assert!(counts.iter().all(|&c| c.abs_diff(200) <= 1), "{:?}", counts);
+        // Still two copies of every chunk, on different nodes
+        for i in 0..300 {
+            assert_eq!(holdings.values().filter(|h| h.contains(&hash(i))).count(), 2);
+        }
+        assert!(plan_rebalance(&holdings, 10).is_empty());
+    }
+
+    #[test]
+    fn test_within_threshold_is_left_alone() {
+        let mut holdings = BTreeMap::new();
+        holdings.insert("node-a".to_string(), (0..105).map(hash).collect::<HashSet<_>>());
+        holdings.insert("node-b".to_string(), (200..295).map(hash).collect::<HashSet<_>>());
+        assert!(plan_rebalance(&holdings, 10).is_empty());
+    }
+}
@@ -2,19 +2,24 @@
 //!
 //! Handles chunk synchronization between leader and followers.
 
-use std::sync::{Arc, RwLock};
-use std::collections::HashSet;
+use std::sync::{Arc, Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
 
 use tracing::{debug, info, warn};
 
 use crate::config::{Config, NodeRole};
-use crate::cluster::{ClusterManager, ClusterState};
+use crate::cluster::{ClusterManager, ClusterState, PeerInfo};
 use crate::network::peer::PeerManager;
+use crate::replication::catch_up::{catch_up_from_leader, missing_chunks};
+use crate::replication::placement::chunk_targets;
 use crate::network::protocol::*;
 use crate::storage::chunks::ChunkStore;
 use crate::storage::index::{FileIndex, FileEntry, ChunkRef};
+use crate::storage::tiered::Tier;
 
 /// Sync state for tracking replication progress
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,12 +30,109 @@ pub enum SyncState {
     SyncingIndex,
     /// Syncing chunks from leader
     SyncingChunks,
+    /// Fetching chunks missed while disconnected from the leader
+    CatchingUp { remaining_chunks: usize },
     /// Waiting for leader discovery
     WaitingForLeader,
     /// Standalone node (no peers)
     Standalone,
 }
 
+/// Progress of the current `FileSync` round, as served by `GET /sync/progress`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncProgress {
+    pub total_files: u64,
+    pub synced_files: u64,
+    pub total_bytes: u64,
+    pub synced_bytes: u64,
+    /// Estimated seconds until the round finishes (None until bytes have moved)
+    pub eta_secs: Option<u64>,
+}
+
+impl SyncProgress {
+    /// Whether every file of the round has been synced
+    pub fn is_complete(&self) -> bool {
+        self.synced_files >= self.total_files
+    }
+}
+
+#[derive(Default)]
+struct ProgressCounts {
+    progress: SyncProgress,
+    /// Files started but not finished: path → (expected bytes, bytes so far)
+    in_flight: HashMap<String, (u64, u64)>,
+    started: Option<Instant>,
+}
+
+/// Counts the files and bytes of a recursive sync as `FileSync` messages are
+/// broadcast (leader) or applied (followers). A round starts with the first
+/// file after the previous round completed, so the counts describe the sync
+/// that is currently running.
+#[derive(Default)]
+pub struct SyncProgressTracker {
+    counts: Mutex<ProgressCounts>,
+}
+
+impl SyncProgressTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A file of `bytes` bytes has started syncing
+    pub fn file_started(&self, path: &str, bytes: u64) {
+        let mut counts = self.counts.lock().unwrap();
+        if counts.progress.is_complete() {
+            *counts = ProgressCounts { started: Some(Instant::now()), ..Default::default() };
+        }
+        // A file re-sent before it finished is still one file
+        if counts.in_flight.insert(path.to_string(), (bytes, 0)).is_none() {
+            counts.progress.total_files += 1;
+            counts.progress.total_bytes += bytes;
+        }
+    }
+
+    /// `bytes` more bytes of a started file have been synced; the file is
+    /// done once all of them have
+    pub fn file_progress(&self, path: &str, bytes: u64) {
+        let mut counts = self.counts.lock().unwrap();
+        let Some((expected, received)) = counts.in_flight.get_mut(path) else {
+            return;
+        };
+        let added = bytes.min(*expected - *received);
+        *received += added;
+        let finished = *received >= *expected;
+        counts.progress.synced_bytes += added;
+        if finished {
+            counts.in_flight.remove(path);
+            counts.progress.synced_files += 1;
+        }
+    }
+
+    /// A started file has been synced completely
+    pub fn file_done(&self, path: &str) {
+        let mut counts = self.counts.lock().unwrap();
+        if let Some((expected, received)) = counts.in_flight.remove(path) {
+            counts.progress.synced_bytes += expected - received;
+            counts.progress.synced_files += 1;
+        }
+    }
+
+    /// Current counts, with an ETA from the byte rate so far
+    pub fn progress(&self) -> SyncProgress {
+        let counts = self.counts.lock().unwrap();
+        let mut progress = counts.progress;
+        if let Some(started) = counts.started {
+            let elapsed = started.elapsed().as_secs_f64();
+            if progress.synced_bytes > 0 && elapsed > 0.0 {
+                let rate = progress.synced_bytes as f64 / elapsed;
+                let remaining = progress.total_bytes - progress.synced_bytes;
+                progress.eta_secs = Some((remaining as f64 / rate).ceil() as u64);
+            }
+        }
+        progress
+    }
+}
+
 /// Manages replication of chunks and index between nodes
 pub struct ReplicationManager {
     config: Config,
@@ -41,6 +143,7 @@ pub struct ReplicationManager {
     sync_state: Arc<RwLock<SyncState>>,
     index_version: Arc<RwLock<u64>>,
     pending_chunks: Arc<RwLock<HashSet<[u8; 32]>>>,
+    progress: Arc<SyncProgressTracker>,
     running: Arc<RwLock<bool>>,
 }
 
@@ -77,6 +180,7 @@ impl ReplicationManager {
             sync_state: Arc::new(RwLock::new(initial_state)),
             index_version: Arc::new(RwLock::new(0)),
             pending_chunks: Arc::new(RwLock::new(HashSet::new())),
+            progress: Arc::new(SyncProgressTracker::new()),
             running: Arc::new(RwLock::new(false)),
         }
     }
@@ -86,6 +190,11 @@ impl ReplicationManager {
         *self.sync_state.read().unwrap()
     }
 
+    /// Progress of the current sync (shared with the HTTP API)
+    pub fn sync_progress(&self) -> Arc<SyncProgressTracker> {
+        Arc::clone(&self.progress)
+    }
+
     /// Get current index version
     pub fn index_version(&self) -> u64 {
         *self.index_version.read().unwrap()
@@ -125,7 +234,9 @@ impl ReplicationManager {
 
         match response {
             Message::SyncResponse(sync_resp) => {
+                let missing = missing_chunks(&self.chunk_store, &sync_resp.entries);
                 self.apply_sync_response(sync_resp)?;
+                self.catch_up(&leader_id, &leader_addr, &missing)?;
                 *self.sync_state.write().unwrap() = SyncState::Synced;
                 info!("Sync complete - in sync with leader");
                 Ok(())
@@ -134,6 +245,21 @@ impl ReplicationManager {
         }
     }
 
+    /// Fetch chunks the index references but we never received
+    fn catch_up(&self, leader_id: &str, leader_addr: &str, missing: &[[u8; 32]]) -> Result<(), String> {
+        let stats = catch_up_from_leader(&self.chunk_store, leader_id, leader_addr, missing, |remaining_chunks| {
+            *self.sync_state.write().unwrap() = SyncState::CatchingUp { remaining_chunks };
+        })?;
+
+        let mut pending = self.pending_chunks.write().unwrap();
+        for hash in missing {
+            if !stats.unavailable.contains(hash) {
+                pending.remove(hash);
+            }
+        }
+        Ok(())
+    }
+
     /// Apply sync response from leader
     fn apply_sync_response(&self, response: SyncResponseMsg) -> Result<(), String> {
         info!("Applying sync response: {} entries, version {}",
@@ -155,6 +281,7 @@ impl ReplicationManager {
                     hash: c.hash,
                     offset: c.offset,
                     size: c.size,
+                    tier: Tier::Hot,
                 }
             }).collect();
 
@@ -169,6 +296,11 @@ impl ReplicationManager {
                 accessed: now,
                 chunks,
                 symlink_target: None,
+                xattrs: file_index.get(&path).map(|e| e.xattrs.clone()).unwrap_or_default(),
+                version_id: None,
+                versions: Vec::new(),
+                hard_link_count: 1,
+                link_id: None,
             };
 
             file_index.insert(path, file_entry);
@@ -393,9 +525,9 @@ impl ReplicationManager {
             data: data.to_vec(),
         });
 
-        // Broadcast to all known peers
+        // Followers picked for this chunk (all of them unless rack-aware)
         let peers = self.cluster.peers();
-        for peer in peers {
+        for peer in self.replica_targets(&peers, hash) {
             if let Err(e) = peer_manager.send_to(&peer.node_id, &msg) {
                 warn!("Failed to replicate chunk to {}: {}", peer.node_id, e);
             } else {
@@ -404,6 +536,11 @@ impl ReplicationManager {
         }
     }
 
+    /// Followers among `peers` that should store a copy of a chunk written here
+    pub fn replica_targets<'a>(&self, peers: &'a [PeerInfo], hash: &[u8; 32]) -> Vec<&'a PeerInfo> {
+        chunk_targets(&self.config.replication, self.config.node.rack.as_deref(), peers, hash)
+    }
+
     /// Replicate index update to followers (called after file operations on leader)
     pub fn replicate_index_update(&self, operation: IndexOperation) {
         if !self.cluster.is_leader() {
@@ -424,6 +561,8 @@ impl ReplicationManager {
             IndexOperation::Delete { path } => std::path::PathBuf::from(path),
             IndexOperation::Mkdir { path, .. } => std::path::PathBuf::from(path),
             IndexOperation::Rename { to_path, .. } => std::path::PathBuf::from(to_path),
+            IndexOperation::SetXattr { path, .. } => std::path::PathBuf::from(path),
+            IndexOperation::Link { dst_path, .. } => std::path::PathBuf::from(dst_path),
         };
         let is_delete = matches!(&operation, IndexOperation::Delete { .. });
         let version = if is_delete {
@@ -475,9 +614,15 @@ impl ReplicationManager {
                         hash: c.hash,
                         offset: c.offset,
                         size: c.size,
+                        tier: Tier::Hot,
                     }
                 }).collect();
 
+                // Extended attributes are replicated separately, so keep them
+                let xattrs = file_index.get(std::path::Path::new(&path))
+                    .map(|e| e.xattrs.clone())
+                    .unwrap_or_default();
+
                 let entry = FileEntry {
                     size,
                     is_dir: false,
@@ -489,6 +634,11 @@ impl ReplicationManager {
                     accessed: now,
                     chunks: chunk_refs,
                     symlink_target: None,
+                    xattrs,
+                    version_id: None,
+                    versions: Vec::new(),
+                    hard_link_count: 1,
+                    link_id: None,
                 };
                 file_index.insert(PathBuf::from(&path), entry);
             }
@@ -504,11 +654,16 @@ impl ReplicationManager {
                     accessed: now,
                     chunks: vec![],
                     symlink_target: None,
+                    xattrs: HashMap::new(),
+                    version_id: None,
+                    versions: Vec::new(),
+                    hard_link_count: 1,
+                    link_id: None,
                 };
                 file_index.insert(PathBuf::from(&path), entry);
             }
             IndexOperation::Delete { path } => {
-                file_index.remove(&PathBuf::from(&path));
+                file_index.unlink(&PathBuf::from(&path));
             }
             IndexOperation::Rename { from_path, to_path } => {
                 // Move entry from old path to new path
@@ -516,6 +671,16 @@ impl ReplicationManager {
                     file_index.insert(PathBuf::from(&to_path), entry);
                 }
             }
+            IndexOperation::SetXattr { path, name, value } => {
+                if let Some(mut entry) = file_index.get_mut(&PathBuf::from(&path)) {
+                    let _ = entry.update_xattr(&name, value, 0);
+                }
+            }
+            IndexOperation::Link { src_path, dst_path } => {
+                if let Err(e) = file_index.link(&PathBuf::from(&src_path), PathBuf::from(&dst_path)) {
+                    warn!("Failed to link {} to {}: {}", dst_path, src_path, e);
+                }
+            }
         }
 
         // Update our version
@@ -570,6 +735,8 @@ impl ReplicationManager {
         // Track missing chunks
         let mut pending = self.pending_chunks.write().unwrap();
         for entry in response.entries {
+            self.progress.file_started(&entry.path, entry.size);
+            self.progress.file_done(&entry.path);
             for chunk in entry.chunks {
                 if !self.chunk_store.exists(&chunk.hash) {
                     pending.insert(chunk.hash);
@@ -606,3 +773,42 @@ impl ReplicationManager {
         *self.running.write().unwrap() = false;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_progress_increases_monotonically() {
+        let tracker = SyncProgressTracker::new();
+        let files: Vec<String> = (0..50).map(|i| format!("tree/dir{}/file{}", i % 5, i)).collect();
+        for path in &files {
+            tracker.file_started(path, 10 * 1024 * 1024);
+        }
+        assert_eq!(tracker.progress().total_files, 50);
+        assert_eq!(tracker.progress().total_bytes, 500 * 1024 * 1024);
+
+        // Four 4 MiB batches per file, the last one short
+        let mut last = tracker.progress();
+        for path in &files {
+            for _ in 0..4 {
+                tracker.file_progress(path, 4 * 1024 * 1024);
+                let now = tracker.progress();
+                assert!(now.synced_bytes >= last.synced_bytes);
+                assert!(now.synced_files >= last.synced_files);
+                assert!(now.synced_bytes <= now.total_bytes);
+                last = now;
+            }
+        }
+        assert!(last.is_complete());
+        assert_eq!(last.synced_bytes, last.total_bytes);
+        assert_eq!(last.eta_secs, Some(0));
+
+        // The next file starts a new round
+        tracker.file_started("next", 1);
+        assert_eq!(tracker.progress().total_files, 1);
+        assert_eq!(tracker.progress().synced_files, 0);
+        tracker.file_done("next");
+        assert!(tracker.progress().is_complete());
+    }
+}
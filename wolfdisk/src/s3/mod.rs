@@ -6,5 +6,7 @@
 
 pub mod server;
 pub mod auth;
+pub mod versioning;
+pub mod tagging;
 
 pub use server::S3Server;
@@ -6,10 +6,15 @@
 //! - Files at root level → objects in a virtual "default" bucket
 //!
 //! Supports: ListBuckets, ListObjectsV2, GetObject, PutObject, DeleteObject,
-//! HeadObject, HeadBucket, CreateBucket, DeleteBucket
+//! HeadObject, HeadBucket, CreateBucket, DeleteBucket, Get/PutBucketVersioning,
+//! ListObjectVersions, Get/Put/DeleteObjectTagging and `x-amz-meta-*` metadata.
+//! `GET /bucket?tag-filter=key:value` lists only objects carrying that tag.
+//!
+//! Also serves `GET /quota?path=/dir`, the usage of a directory quota as JSON,
+//! and `POST /presign`, which hands out SigV4 presigned URLs for objects.
 
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path as FsPath, PathBuf};
 use std::sync::{Arc, RwLock};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -21,11 +26,14 @@ use axum::{
     routing::any,
     Router,
 };
+use serde::Deserialize;
 use tokio::net::TcpListener;
 use tracing::{info, error, debug};
 
-use crate::storage::{ChunkStore, FileIndex, FileEntry, ChunkRef, InodeTable};
-use super::auth::{S3Credentials, check_auth};
+use crate::storage::{ChunkStore, FileIndex, FileEntry, ChunkRef, InodeTable, QuotaManager};
+use super::auth::{self, S3Credentials, check_auth, MAX_PRESIGN_EXPIRES};
+use super::versioning::{self, BucketVersioning, VersioningStatus};
+use super::tagging::{self, TagFilter};
 
 /// Shared state for the S3 server
 #[derive(Clone)]
@@ -36,6 +44,8 @@ pub struct S3State {
     pub next_inode: Arc<RwLock<u64>>,
     pub credentials: Option<S3Credentials>,
     pub region: String,
+    pub versioning: Arc<RwLock<BucketVersioning>>,
+    pub quotas: Option<Arc<QuotaManager>>,
 }
 
 /// S3 server that runs alongside WolfDisk FUSE
@@ -53,7 +63,13 @@ impl S3Server {
         inode_table: Arc<RwLock<InodeTable>>,
         next_inode: Arc<RwLock<u64>>,
         credentials: Option<S3Credentials>,
+        index_dir: &FsPath,
     ) -> Self {
+        let versioning = BucketVersioning::load(index_dir).unwrap_or_else(|e| {
+            error!("Failed to load S3 versioning state: {}", e);
+            BucketVersioning::empty(index_dir)
+        });
+
         let state = S3State {
             file_index,
             chunk_store,
@@ -61,11 +77,19 @@ impl S3Server {
             next_inode,
             credentials,
             region: "us-east-1".to_string(),
+            versioning: Arc::new(RwLock::new(versioning)),
+            quotas: None,
         };
 
         Self { bind_addr, state }
     }
 
+    /// Enforce per-directory quotas on PutObject and serve `GET /quota`
+    pub fn with_quotas(mut self, quotas: Option<Arc<QuotaManager>>) -> Self {
+        self.state.quotas = quotas;
+        self
+    }
+
     /// Start the S3 server (call from a tokio runtime)
     pub async fn run(self) -> std::io::Result<()> {
         let app = Router::new()
@@ -110,29 +134,109 @@ async fn handle_path(
     query: Query<HashMap<String, String>>,
     request: Request<Body>,
 ) -> Response {
-    if !authorize(&headers, &state.credentials) {
+    if query.contains_key("X-Amz-Signature") {
+        // Presigned URL: the query-string signature replaces the header
+        if let Some(ref creds) = state.credentials {
+            let params: Vec<(String, String)> = query.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            if let Err(reason) = auth::verify_presigned(
+                creds, &state.region, method.as_str(), &format!("/{}", path), &params, header, now,
+            ) {
+                debug!("Rejected presigned {} /{}: {}", method, path, reason);
+                return error_response(StatusCode::FORBIDDEN, "AccessDenied", reason);
+            }
+        }
+    } else if !authorize(&headers, &state.credentials) {
         return error_response(StatusCode::FORBIDDEN, "AccessDenied", "Access Denied");
     }
 
     // Parse bucket and key from path
     let (bucket, key) = parse_bucket_key(&path);
 
+    // Parse ?versionId up front so every object handler sees the same error
+    let version_id = match query.get("versionId").map(|v| versioning::parse_version_id(v)) {
+        Some(Some(id)) => Some(id),
+        Some(None) => {
+            return error_response(StatusCode::BAD_REQUEST, "InvalidArgument", "Invalid version id specified");
+        }
+        None => None,
+    };
+
     match (method, key) {
+        // ── Quota usage (not an S3 operation) ─────────────────
+        (Method::GET, None) if bucket == "quota" && query.contains_key("path") => {
+            get_quota(state, &query["path"]).await
+        }
+        (Method::POST, None) if bucket == "presign" => {
+            let body_bytes = match axum::body::to_bytes(request.into_body(), 64 * 1024).await {
+                Ok(b) => b,
+                Err(e) => {
+                    return error_response(
+                        StatusCode::BAD_REQUEST,
+                        "InvalidRequest",
+                        &format!("Failed to read body: {}", e),
+                    );
+                }
+            };
+            presign(state, &headers, &body_bytes).await
+        }
+
         // ── Bucket-level operations ────────────────────────────
         (Method::GET, None) => {
-            // Could be ListObjectsV2 or GetBucketLocation
+            // Could be ListObjectsV2, GetBucketLocation, GetBucketVersioning or ListObjectVersions
             if query.contains_key("location") {
                 get_bucket_location(state).await
+            } else if query.contains_key("versioning") {
+                get_bucket_versioning(state, &bucket).await
+            } else if query.contains_key("versions") {
+                let prefix = query.get("prefix").cloned().unwrap_or_default();
+                list_object_versions(state, &bucket, &prefix, false).await
             } else {
                 list_objects(state, &bucket, &query).await
             }
         }
         (Method::HEAD, None) => head_bucket(state, &bucket).await,
+        (Method::PUT, None) if query.contains_key("versioning") => {
+            let body_bytes = match axum::body::to_bytes(request.into_body(), 64 * 1024).await {
+                Ok(b) => b,
+                Err(e) => {
+                    return error_response(
+                        StatusCode::BAD_REQUEST,
+                        "InvalidRequest",
+                        &format!("Failed to read body: {}", e),
+                    );
+                }
+            };
+            put_bucket_versioning(state, &bucket, &String::from_utf8_lossy(&body_bytes)).await
+        }
         (Method::PUT, None) => create_bucket(state, &bucket).await,
         (Method::DELETE, None) => delete_bucket(state, &bucket).await,
 
         // ── Object-level operations ────────────────────────────
-        (Method::GET, Some(key)) => get_object(state, &bucket, &key).await,
+        (Method::GET, Some(key)) if query.contains_key("tagging") => {
+            get_object_tagging(state, &bucket, &key).await
+        }
+        (Method::PUT, Some(key)) if query.contains_key("tagging") => {
+            let body_bytes = match axum::body::to_bytes(request.into_body(), 64 * 1024).await {
+                Ok(b) => b,
+                Err(e) => {
+                    return error_response(
+                        StatusCode::BAD_REQUEST,
+                        "InvalidRequest",
+                        &format!("Failed to read body: {}", e),
+                    );
+                }
+            };
+            put_object_tagging(state, &bucket, &key, &String::from_utf8_lossy(&body_bytes)).await
+        }
+        (Method::DELETE, Some(key)) if query.contains_key("tagging") => {
+            delete_object_tagging(state, &bucket, &key).await
+        }
+        (Method::GET, Some(key)) if query.contains_key("versions") => {
+            list_object_versions(state, &bucket, &key, true).await
+        }
+        (Method::GET, Some(key)) => get_object(state, &bucket, &key, version_id).await,
         (Method::HEAD, Some(key)) => head_object(state, &bucket, &key).await,
         (Method::PUT, Some(key)) => {
             // Read the body
@@ -146,9 +250,12 @@ async fn handle_path(
                     );
                 }
             };
-            put_object(state, &bucket, &key, body_bytes.to_vec()).await
+            put_object(state, &bucket, &key, body_bytes.to_vec(), user_metadata(&headers)).await
         }
-        (Method::DELETE, Some(key)) => delete_object(state, &bucket, &key).await,
+        (Method::DELETE, Some(key)) => match version_id {
+            Some(version_id) => delete_object_version(state, &bucket, &key, version_id).await,
+            None => delete_object(state, &bucket, &key).await,
+        },
 
         _ => error_response(StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed", "Method not allowed"),
     }
@@ -236,6 +343,13 @@ async fn list_objects(
         .and_then(|v| v.parse().ok())
         .unwrap_or(1000);
     let continuation_token = query.get("continuation-token").cloned();
+    let tag_filter = match query.get("tag-filter").map(|f| TagFilter::parse(f)) {
+        Some(Some(filter)) => Some(filter),
+        Some(None) => {
+            return error_response(StatusCode::BAD_REQUEST, "InvalidArgument", "tag-filter must be key or key:value");
+        }
+        None => None,
+    };
 
     let index = state.file_index.read().unwrap();
     let bucket_prefix = PathBuf::from(bucket);
@@ -273,10 +387,13 @@ async fn list_objects(
             continue;
         }
 
-        // Apply prefix filter
+        // Apply prefix and tag filters
         if !key.starts_with(&prefix) {
             continue;
         }
+        if tag_filter.as_ref().is_some_and(|f| !f.matches(entry)) {
+            continue;
+        }
 
         // Handle delimiter (directory grouping)
         if !delimiter.is_empty() {
@@ -406,6 +523,11 @@ async fn create_bucket(state: S3State, bucket: &str) -> Response {
             accessed: now,
             chunks: Vec::new(),
             symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
         };
 
         index.insert(bucket_path.clone(), entry);
@@ -461,17 +583,23 @@ async fn delete_bucket(state: S3State, bucket: &str) -> Response {
     (StatusCode::NO_CONTENT, [(header::CONTENT_TYPE, "application/xml")]).into_response()
 }
 
-/// GET /bucket/key → GetObject
-async fn get_object(state: S3State, bucket: &str, key: &str) -> Response {
+/// GET /bucket/key → GetObject (optionally `?versionId=`)
+async fn get_object(state: S3State, bucket: &str, key: &str, version_id: Option<Option<uuid::Uuid>>) -> Response {
     let object_path = PathBuf::from(bucket).join(key);
 
     let index = state.file_index.read().unwrap();
-    let entry = match index.get(&object_path) {
-        Some(e) if !e.is_dir => e.clone(),
-        Some(_) => {
+    let entry = match (index.get(&object_path), version_id) {
+        (Some(e), _) if e.is_dir => {
             return error_response(StatusCode::NOT_FOUND, "NoSuchKey", "Key is a directory");
         }
-        None => {
+        (Some(_), Some(id)) => match versioning::get_version(&index, &object_path, id) {
+            Some(v) => v,
+            None => {
+                return error_response(StatusCode::NOT_FOUND, "NoSuchVersion", "The specified version does not exist");
+            }
+        },
+        (Some(e), None) => e.clone(),
+        (None, _) => {
             return error_response(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist");
         }
     };
@@ -479,7 +607,10 @@ async fn get_object(state: S3State, bucket: &str, key: &str) -> Response {
 
     // Read all chunk data
     let data = match state.chunk_store.read(&entry.chunks, 0, entry.size as usize) {
-        Ok(d) => d,
+        Ok(mut d) => {
+            ChunkStore::fill_to_size(&mut d, entry.size, 0, entry.size as usize);
+            d
+        }
         Err(e) => {
             error!("S3 GetObject: failed to read chunks for {}/{}: {}", bucket, key, e);
             return error_response(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", "Failed to read object data");
@@ -494,12 +625,14 @@ async fn get_object(state: S3State, bucket: &str, key: &str) -> Response {
 
     debug!("S3 GetObject: {}/{} ({} bytes)", bucket, key, data.len());
 
-    Response::builder()
+    let response = Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "application/octet-stream")
         .header(header::CONTENT_LENGTH, data.len().to_string())
         .header("ETag", etag)
         .header("Last-Modified", format_time_http(&entry.modified))
+        .header("x-amz-version-id", versioning::format_version_id(entry.version_id));
+    with_user_metadata(response, &entry)
         .body(Body::from(data))
         .unwrap()
 }
@@ -517,12 +650,13 @@ async fn head_object(state: S3State, bucket: &str, key: &str) -> Response {
                 "\"d41d8cd98f00b204e9800998ecf8427e\"".to_string()
             };
 
-            Response::builder()
+            let response = Response::builder()
                 .status(StatusCode::OK)
                 .header(header::CONTENT_TYPE, "application/octet-stream")
                 .header(header::CONTENT_LENGTH, entry.size.to_string())
                 .header("ETag", etag)
-                .header("Last-Modified", format_time_http(&entry.modified))
+                .header("Last-Modified", format_time_http(&entry.modified));
+            with_user_metadata(response, entry)
                 .body(Body::empty())
                 .unwrap()
         }
@@ -536,10 +670,26 @@ async fn put_object(
     bucket: &str,
     key: &str,
     data: Vec<u8>,
+    metadata: HashMap<String, String>,
 ) -> Response {
     let bucket_path = PathBuf::from(bucket);
     let object_path = bucket_path.join(key);
 
+    // Enforce directory quotas before anything is created
+    if let Some(ref quotas) = state.quotas {
+        let index = state.file_index.read().unwrap();
+        quotas.rebuild_if_stale(&index);
+        let existing_size = index.get(&object_path).filter(|e| !e.is_dir).map(|e| e.size);
+        drop(index);
+        let charged = match existing_size {
+            Some(old_size) => quotas.resize(&object_path, old_size, data.len() as u64),
+            None => quotas.charge(&object_path, data.len() as u64, 1),
+        };
+        if let Err(e) = charged {
+            return error_response(StatusCode::FORBIDDEN, "QuotaExceeded", &e.to_string());
+        }
+    }
+
     // Ensure bucket exists (auto-create if needed)
     {
         let index = state.file_index.read().unwrap();
@@ -561,6 +711,11 @@ async fn put_object(
                     accessed: now,
                     chunks: Vec::new(),
                     symlink_target: None,
+                    xattrs: HashMap::new(),
+                    version_id: None,
+                    versions: Vec::new(),
+                    hard_link_count: 1,
+                    link_id: None,
                 });
                 let mut next_ino = state.next_inode.write().unwrap();
                 let ino = *next_ino;
@@ -602,6 +757,11 @@ async fn put_object(
                         accessed: now,
                         chunks: Vec::new(),
                         symlink_target: None,
+                        xattrs: HashMap::new(),
+                        version_id: None,
+                        versions: Vec::new(),
+                        hard_link_count: 1,
+                        link_id: None,
                     });
                     let mut next_ino = state.next_inode.write().unwrap();
                     let ino = *next_ino;
@@ -618,6 +778,10 @@ async fn put_object(
         Ok(w) => w,
         Err(e) => {
             error!("S3 PutObject: failed to write chunks for {}/{}: {}", bucket, key, e);
+            if let Some(ref quotas) = state.quotas {
+                // Usage was charged for data that never got written
+                quotas.mark_stale();
+            }
             return error_response(
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "InternalError",
@@ -627,7 +791,7 @@ async fn put_object(
     };
 
     let now = SystemTime::now();
-    let entry = FileEntry {
+    let mut entry = FileEntry {
         size: written as u64,
         is_dir: false,
         permissions: 0o644,
@@ -638,22 +802,26 @@ async fn put_object(
         accessed: now,
         chunks: chunks.clone(),
         symlink_target: None,
+        xattrs: HashMap::new(),
+        version_id: None,
+        versions: Vec::new(),
+        hard_link_count: 1,
+        link_id: None,
     };
+    tagging::set_metadata(&mut entry, &metadata);
 
-    // Insert into index
-    {
+    let versioned = state.versioning.read().unwrap().is_enabled(bucket);
+
+    // Insert into index, keeping the previous entry as a version if enabled
+    let version_id = {
         let mut index = state.file_index.write().unwrap();
         let mut inode_tbl = state.inode_table.write().unwrap();
 
-        let old = index.insert(object_path.clone(), entry);
+        let (version_id, freed) = versioning::put_version(&mut index, object_path.clone(), entry, versioned);
 
-        // Clean up old chunks if overwriting
-        if let Some(old_entry) = old {
-            if !old_entry.is_dir {
-                for chunk in &old_entry.chunks {
-                    let _ = state.chunk_store.delete(&chunk.hash);
-                }
-            }
+        // Clean up old chunks that no version references any more
+        for hash in &freed {
+            let _ = state.chunk_store.delete(hash);
         }
 
         // Allocate inode if new
@@ -663,7 +831,9 @@ async fn put_object(
             *next_ino += 1;
             inode_tbl.insert(ino, object_path);
         }
-    }
+
+        version_id
+    };
 
     let etag = if let Some(chunk) = chunks.first() {
         format!("\"{}\"", hex::encode(&chunk.hash[..16]))
@@ -673,25 +843,42 @@ async fn put_object(
 
     info!("S3 PutObject: {}/{} ({} bytes)", bucket, key, written);
 
-    Response::builder()
+    let mut response = Response::builder()
         .status(StatusCode::OK)
-        .header("ETag", etag)
-        .body(Body::empty())
-        .unwrap()
+        .header("ETag", etag);
+    if versioned {
+        response = response.header("x-amz-version-id", versioning::format_version_id(version_id));
+    }
+    response.body(Body::empty()).unwrap()
 }
 
 /// DELETE /bucket/key → DeleteObject
+///
+/// WolfDisk has no delete markers: on an object with noncurrent versions this
+/// deletes the current version and the previous one becomes current.
 async fn delete_object(state: S3State, bucket: &str, key: &str) -> Response {
     let object_path = PathBuf::from(bucket).join(key);
 
+    let current_version = state.file_index.read().unwrap()
+        .get(&object_path)
+        .filter(|e| !e.is_dir && !e.versions.is_empty())
+        .map(|e| e.version_id);
+    if let Some(version_id) = current_version {
+        return delete_object_version(state, bucket, key, version_id).await;
+    }
+
     let chunks_to_delete = {
         let mut index = state.file_index.write().unwrap();
         let mut inode_tbl = state.inode_table.write().unwrap();
 
-        match index.remove(&object_path) {
+        match index.unlink(&object_path) {
             Some(entry) if !entry.is_dir => {
                 inode_tbl.remove_path(&object_path);
-                entry.chunks
+                if let Some(ref quotas) = state.quotas {
+                    quotas.release(&object_path, entry.size, 1);
+                }
+                // Other hard links keep the chunks
+                if entry.hard_link_count == 0 { entry.chunks } else { Vec::new() }
             }
             Some(entry) => {
                 // Put it back — can't delete a directory this way
@@ -714,6 +901,292 @@ async fn delete_object(state: S3State, bucket: &str, key: &str) -> Response {
     (StatusCode::NO_CONTENT, [(header::CONTENT_TYPE, "application/xml")]).into_response()
 }
 
+/// DELETE /bucket/key?versionId=X → DeleteObject (specific version)
+async fn delete_object_version(
+    state: S3State,
+    bucket: &str,
+    key: &str,
+    version_id: Option<uuid::Uuid>,
+) -> Response {
+    let object_path = PathBuf::from(bucket).join(key);
+
+    let deleted = {
+        let mut index = state.file_index.write().unwrap();
+        let mut inode_tbl = state.inode_table.write().unwrap();
+
+        let deleted = versioning::delete_version(&mut index, &object_path, version_id);
+        if deleted.as_ref().is_some_and(|d| d.object_removed) {
+            inode_tbl.remove_path(&object_path);
+        }
+        if let (Some(quotas), Some(_)) = (&state.quotas, &deleted) {
+            // The current version may have changed size; recount on next use
+            quotas.mark_stale();
+        }
+        deleted
+    };
+
+    // S3 spec: deleting a version that does not exist still returns 204
+    if let Some(deleted) = deleted {
+        for hash in &deleted.freed_chunks {
+            let _ = state.chunk_store.delete(hash);
+        }
+    }
+
+    let version = versioning::format_version_id(version_id);
+    info!("S3 DeleteObject: {}/{} (version {})", bucket, key, version);
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header("x-amz-version-id", version)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// GET /bucket/key?tagging → GetObjectTagging
+async fn get_object_tagging(state: S3State, bucket: &str, key: &str) -> Response {
+    let object_path = PathBuf::from(bucket).join(key);
+    let tags = match state.file_index.read().unwrap().get(&object_path) {
+        Some(entry) if !entry.is_dir => tagging::tags(entry),
+        _ => return error_response(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist"),
+    };
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/xml")],
+        tagging::tagging_xml(&tags),
+    ).into_response()
+}
+
+/// PUT /bucket/key?tagging → PutObjectTagging (replaces the whole tag set)
+async fn put_object_tagging(state: S3State, bucket: &str, key: &str, body: &str) -> Response {
+    let tags = match tagging::parse_tagging(body) {
+        Ok(tags) => tags,
+        Err(reason) => return error_response(StatusCode::BAD_REQUEST, "InvalidTag", reason),
+    };
+
+    let object_path = PathBuf::from(bucket).join(key);
+    let mut index = state.file_index.write().unwrap();
+    let Some(mut entry) = index.get_mut(&object_path).filter(|e| !e.is_dir) else {
+        return error_response(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist");
+    };
+    tagging::set_tags(&mut entry, &tags);
+    drop(entry);
+    drop(index);
+
+    info!("S3 PutObjectTagging: {}/{} ({} tags)", bucket, key, tags.len());
+    (StatusCode::OK, [(header::CONTENT_TYPE, "application/xml")]).into_response()
+}
+
+/// DELETE /bucket/key?tagging → DeleteObjectTagging
+async fn delete_object_tagging(state: S3State, bucket: &str, key: &str) -> Response {
+    let object_path = PathBuf::from(bucket).join(key);
+    let mut index = state.file_index.write().unwrap();
+    let Some(mut entry) = index.get_mut(&object_path).filter(|e| !e.is_dir) else {
+        return error_response(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist");
+    };
+    tagging::set_tags(&mut entry, &HashMap::new());
+    drop(entry);
+    drop(index);
+
+    info!("S3 DeleteObjectTagging: {}/{}", bucket, key);
+    (StatusCode::NO_CONTENT, [(header::CONTENT_TYPE, "application/xml")]).into_response()
+}
+
+/// `x-amz-meta-*` request headers, keyed by the name after the prefix
+fn user_metadata(headers: &HeaderMap) -> HashMap<String, String> {
+    headers.iter()
+        .filter_map(|(name, value)| {
+            let key = name.as_str().strip_prefix(tagging::METADATA_HEADER_PREFIX)?;
+            Some((key.to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect()
+}
+
+/// Add an object's `x-amz-meta-*` headers to a response
+fn with_user_metadata(mut response: axum::http::response::Builder, entry: &FileEntry) -> axum::http::response::Builder {
+    for (key, value) in tagging::metadata(entry) {
+        response = response.header(format!("{}{}", tagging::METADATA_HEADER_PREFIX, key), value);
+    }
+    response
+}
+
+/// GET /quota?path=/dir → usage and limits of the quota on `/dir`
+async fn get_quota(state: S3State, path: &str) -> Response {
+    let report = state.quotas.as_ref().and_then(|quotas| {
+        quotas.rebuild_if_stale(&state.file_index.read().unwrap());
+        quotas.report(FsPath::new(path))
+    });
+    match report {
+        Some(report) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "application/json")],
+            serde_json::to_string(&report).unwrap_or_default(),
+        ).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, "NoSuchQuota", &format!("No quota configured for {}", path)),
+    }
+}
+
+/// Body of `POST /presign`
+#[derive(Debug, Deserialize)]
+struct PresignRequest {
+    bucket: String,
+    key: String,
+    #[serde(default = "default_presign_expires")]
+    expires_in_secs: u64,
+    #[serde(default = "default_presign_method")]
+    method: String,
+}
+
+fn default_presign_expires() -> u64 {
+    3600
+}
+
+fn default_presign_method() -> String {
+    "GET".to_string()
+}
+
+/// POST /presign → `{"url": ...}`, a presigned URL for one object operation.
+/// The URL points at the host the request was sent to, so it works for
+/// clients that reach the server the same way.
+async fn presign(state: S3State, headers: &HeaderMap, body: &[u8]) -> Response {
+    let Some(ref creds) = state.credentials else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "InvalidRequest",
+            "Presigned URLs need s3.access_key and s3.secret_key to be configured",
+        );
+    };
+    let req: PresignRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, "MalformedJSON", &e.to_string()),
+    };
+    let method = req.method.to_ascii_uppercase();
+    if !matches!(method.as_str(), "GET" | "HEAD" | "PUT" | "DELETE") {
+        return error_response(StatusCode::BAD_REQUEST, "InvalidArgument", "method must be GET, HEAD, PUT or DELETE");
+    }
+    if !(1..=MAX_PRESIGN_EXPIRES).contains(&req.expires_in_secs) {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "InvalidArgument",
+            &format!("expires_in_secs must be between 1 and {}", MAX_PRESIGN_EXPIRES),
+        );
+    }
+    if req.bucket.is_empty() || req.bucket.contains('/') || req.key.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "InvalidArgument", "bucket and key are required");
+    }
+    let Some(host) = headers.get(header::HOST).and_then(|v| v.to_str().ok()) else {
+        return error_response(StatusCode::BAD_REQUEST, "InvalidRequest", "Missing Host header");
+    };
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let url = auth::presign_url(
+        creds,
+        &state.region,
+        &format!("{}://{}", scheme, host),
+        &method,
+        &format!("/{}/{}", req.bucket, req.key),
+        req.expires_in_secs,
+        now,
+    );
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        serde_json::json!({ "url": url }).to_string(),
+    ).into_response()
+}
+
+/// GET /bucket?versioning → GetBucketVersioning
+async fn get_bucket_versioning(state: S3State, bucket: &str) -> Response {
+    let status = state.versioning.read().unwrap().status(bucket);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<VersioningConfiguration xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">");
+    if let Some(status) = status {
+        xml.push_str(&format!("<Status>{}</Status>", status.as_str()));
+    }
+    xml.push_str("</VersioningConfiguration>");
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/xml")],
+        xml,
+    ).into_response()
+}
+
+/// PUT /bucket?versioning → PutBucketVersioning
+async fn put_bucket_versioning(state: S3State, bucket: &str, body: &str) -> Response {
+    let status = body
+        .split_once("<Status>")
+        .and_then(|(_, rest)| rest.split_once("</Status>"))
+        .and_then(|(status, _)| VersioningStatus::parse(status));
+    let Some(status) = status else {
+        return error_response(StatusCode::BAD_REQUEST, "MalformedXML", "Status must be Enabled or Suspended");
+    };
+
+    if let Err(e) = state.versioning.write().unwrap().set(bucket, status) {
+        error!("S3 PutBucketVersioning: failed to save state for {}: {}", bucket, e);
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", "Failed to save versioning state");
+    }
+
+    info!("S3: Versioning {} for bucket '{}'", status.as_str(), bucket);
+    (StatusCode::OK, [(header::CONTENT_TYPE, "application/xml")]).into_response()
+}
+
+/// GET /bucket?versions or GET /bucket/key?versions → ListObjectVersions
+///
+/// With `exact` only the object at `key` is listed, otherwise every object
+/// whose key starts with `key`.
+async fn list_object_versions(state: S3State, bucket: &str, key: &str, exact: bool) -> Response {
+    let index = state.file_index.read().unwrap();
+    let bucket_path = PathBuf::from(bucket);
+
+    let mut keys: Vec<String> = if exact {
+        vec![key.to_string()]
+    } else {
+        index.iter()
+            .filter(|(p, e)| !e.is_dir && p.starts_with(&bucket_path))
+            .map(|(p, _)| p.strip_prefix(&bucket_path).unwrap().to_string_lossy().to_string())
+            .filter(|k| k.starts_with(key))
+            .collect()
+    };
+    keys.sort();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<ListVersionsResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n");
+    xml.push_str(&format!("  <Name>{}</Name>\n", xml_escape(bucket)));
+    xml.push_str(&format!("  <Prefix>{}</Prefix>\n", xml_escape(key)));
+    xml.push_str("  <IsTruncated>false</IsTruncated>\n");
+
+    for object_key in &keys {
+        for version in versioning::list_versions(&index, &bucket_path.join(object_key)) {
+            let etag = match version.first_chunk {
+                Some(hash) => format!("\"{}\"", hex::encode(&hash[..16])),
+                None => "\"d41d8cd98f00b204e9800998ecf8427e\"".to_string(),
+            };
+            xml.push_str("  <Version>\n");
+            xml.push_str(&format!("    <Key>{}</Key>\n", xml_escape(object_key)));
+            xml.push_str(&format!("    <VersionId>{}</VersionId>\n", versioning::format_version_id(version.version_id)));
+            xml.push_str(&format!("    <IsLatest>{}</IsLatest>\n", version.is_latest));
+            xml.push_str(&format!("    <LastModified>{}</LastModified>\n", format_time(&version.modified)));
+            xml.push_str(&format!("    <ETag>{}</ETag>\n", etag));
+            xml.push_str(&format!("    <Size>{}</Size>\n", version.size));
+            xml.push_str("    <StorageClass>STANDARD</StorageClass>\n");
+            xml.push_str("  </Version>\n");
+        }
+    }
+
+    xml.push_str("</ListVersionsResult>");
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/xml")],
+        xml,
+    ).into_response()
+}
+
 // ─── Helpers ─────────────────────────────────────────────────────────────────
 
 /// Parse a request path into (bucket, optional key)
@@ -791,7 +1264,7 @@ fn format_time_http(time: &SystemTime) -> String {
 }
 
 /// Convert days since epoch to (year, month, day)
-fn days_to_ymd(days: u64) -> (u64, u64, u64) {
+pub(super) fn days_to_ymd(days: u64) -> (u64, u64, u64) {
     // Civil calendar calculation from days since epoch
     let z = days + 719468;
     let era = z / 146097;
@@ -807,7 +1280,7 @@ fn days_to_ymd(days: u64) -> (u64, u64, u64) {
 }
 
 /// XML-escape a string
-fn xml_escape(s: &str) -> String {
+pub(super) fn xml_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -832,3 +1305,106 @@ fn error_response(status: StatusCode, code: &str, message: &str) -> Response {
         xml,
     ).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_state(dir: &FsPath) -> S3State {
+        S3State {
+            file_index: Arc::new(RwLock::new(FileIndex::new())),
+            chunk_store: Arc::new(ChunkStore::new(dir.join("chunks"), 1024).unwrap()),
+            inode_table: Arc::new(RwLock::new(InodeTable::new())),
+            next_inode: Arc::new(RwLock::new(2)),
+            credentials: None,
+            region: "us-east-1".to_string(),
+            versioning: Arc::new(RwLock::new(BucketVersioning::empty(dir))),
+            quotas: None,
+        }
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    fn tagging_body(tags: &[(&str, &str)]) -> String {
+        let tags: String = tags.iter()
+            .map(|(k, v)| format!("<Tag><Key>{}</Key><Value>{}</Value></Tag>", k, v))
+            .collect();
+        format!("<Tagging><TagSet>{}</TagSet></Tagging>", tags)
+    }
+
+    #[tokio::test]
+    async fn test_object_tagging() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_state(dir.path());
+        for key in ["a.txt", "b.txt"] {
+            let response = put_object(state.clone(), "data", key, b"hello".to_vec(), HashMap::new()).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = put_object_tagging(state.clone(), "data", "a.txt", &tagging_body(&[("env", "prod"), ("team", "web")])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = put_object_tagging(state.clone(), "data", "b.txt", &tagging_body(&[("env", "dev")])).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Only the object tagged env=prod is listed
+        let query = HashMap::from([("tag-filter".to_string(), "env:prod".to_string())]);
+        let xml = body_text(list_objects(state.clone(), "data", &query).await).await;
+        assert!(xml.contains("<Key>a.txt</Key>"));
+        assert!(!xml.contains("<Key>b.txt</Key>"));
+        let query = HashMap::from([("tag-filter".to_string(), "env".to_string())]);
+        let xml = body_text(list_objects(state.clone(), "data", &query).await).await;
+        assert!(xml.contains("<Key>a.txt</Key>") && xml.contains("<Key>b.txt</Key>"));
+
+        let xml = body_text(get_object_tagging(state.clone(), "data", "a.txt").await).await;
+        assert!(xml.contains("<Key>env</Key>\n      <Value>prod</Value>"));
+        assert!(xml.contains("<Key>team</Key>\n      <Value>web</Value>"));
+
+        let response = delete_object_tagging(state.clone(), "data", "a.txt").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let xml = body_text(get_object_tagging(state.clone(), "data", "a.txt").await).await;
+        assert!(!xml.contains("<Tag>"));
+        let query = HashMap::from([("tag-filter".to_string(), "env:prod".to_string())]);
+        let xml = body_text(list_objects(state.clone(), "data", &query).await).await;
+        assert!(!xml.contains("<Contents>"));
+
+        let response = get_object_tagging(state.clone(), "data", "missing.txt").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_tag_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_state(dir.path());
+        put_object(state.clone(), "data", "a.txt", b"hello".to_vec(), HashMap::new()).await;
+
+        let keys: Vec<String> = (0..11).map(|i| format!("k{}", i)).collect();
+        let too_many: Vec<(&str, &str)> = keys.iter().map(|k| (k.as_str(), "v")).collect();
+        let response = put_object_tagging(state.clone(), "data", "a.txt", &tagging_body(&too_many)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let duplicate = tagging_body(&[("env", "prod"), ("env", "dev")]);
+        let response = put_object_tagging(state.clone(), "data", "a.txt", &duplicate).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_user_metadata_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = test_state(dir.path());
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-meta-owner", "alice".parse().unwrap());
+        headers.insert("content-type", "text/plain".parse().unwrap());
+
+        let response = put_object(state.clone(), "data", "a.txt", b"hello".to_vec(), user_metadata(&headers)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get_object(state.clone(), "data", "a.txt", None).await;
+        assert_eq!(response.headers()["x-amz-meta-owner"], "alice");
+        assert!(response.headers().get("x-amz-meta-content-type").is_none());
+        let response = head_object(state.clone(), "data", "a.txt").await;
+        assert_eq!(response.headers()["x-amz-meta-owner"], "alice");
+    }
+}
@@ -0,0 +1,141 @@
+//! S3 object tagging and user metadata
+//!
+//! Tags (`PUT /bucket/key?tagging`) and `x-amz-meta-*` headers are kept in the
+//! object's extended attributes as JSON maps, under `S3_TAGS_XATTR` and
+//! `S3_METADATA_XATTR`, so they are stored, versioned and replicated with the
+//! rest of the `FileEntry`.
+
+use std::collections::HashMap;
+
+use crate::storage::FileEntry;
+
+/// Extended attribute holding an object's tags
+pub const S3_TAGS_XATTR: &str = "user.s3.tags";
+
+/// Extended attribute holding an object's `x-amz-meta-*` metadata
+pub const S3_METADATA_XATTR: &str = "user.s3.metadata";
+
+/// Most tags an object may carry, as in S3
+pub const MAX_TAGS: usize = 10;
+
+const MAX_TAG_KEY_LEN: usize = 128;
+const MAX_TAG_VALUE_LEN: usize = 256;
+
+/// Prefix of user metadata headers
+pub const METADATA_HEADER_PREFIX: &str = "x-amz-meta-";
+
+/// Tags of an object (empty if it has none)
+pub fn tags(entry: &FileEntry) -> HashMap<String, String> {
+    json_xattr(entry, S3_TAGS_XATTR)
+}
+
+/// Replace an object's tags; an empty set removes the attribute
+pub fn set_tags(entry: &mut FileEntry, tags: &HashMap<String, String>) {
+    set_json_xattr(entry, S3_TAGS_XATTR, tags);
+}
+
+/// User metadata of an object, keyed by the name after `x-amz-meta-`
+pub fn metadata(entry: &FileEntry) -> HashMap<String, String> {
+    json_xattr(entry, S3_METADATA_XATTR)
+}
+
+/// Replace an object's user metadata
+pub fn set_metadata(entry: &mut FileEntry, metadata: &HashMap<String, String>) {
+    set_json_xattr(entry, S3_METADATA_XATTR, metadata);
+}
+
+fn json_xattr(entry: &FileEntry, name: &str) -> HashMap<String, String> {
+    entry.xattrs.get(name)
+        .and_then(|value| serde_json::from_slice(value).ok())
+        .unwrap_or_default()
+}
+
+fn set_json_xattr(entry: &mut FileEntry, name: &str, map: &HashMap<String, String>) {
+    if map.is_empty() {
+        entry.xattrs.remove(name);
+    } else {
+        entry.xattrs.insert(name.to_string(), serde_json::to_vec(map).unwrap_or_default());
+    }
+}
+
+/// Parse a `<Tagging><TagSet><Tag><Key>..</Key><Value>..</Value></Tag>..` body
+pub fn parse_tagging(xml: &str) -> Result<HashMap<String, String>, &'static str> {
+    let tag_set = element(xml, "TagSet").ok_or("Missing TagSet")?;
+    let mut tags = HashMap::new();
+    let mut rest = tag_set;
+    while let Some((_, after)) = rest.split_once("<Tag>") {
+        let (tag, remaining) = after.split_once("</Tag>").ok_or("Unterminated Tag")?;
+        let key = xml_unescape(element(tag, "Key").ok_or("Tag without a Key")?);
+        let value = xml_unescape(element(tag, "Value").unwrap_or_default());
+        if key.is_empty() || key.len() > MAX_TAG_KEY_LEN {
+            return Err("Tag keys must be 1 to 128 characters");
+        }
+        if value.len() > MAX_TAG_VALUE_LEN {
+            return Err("Tag values must be at most 256 characters");
+        }
+        if tags.insert(key, value).is_some() {
+            return Err("Cannot provide multiple Tags with the same key");
+        }
+        rest = remaining;
+    }
+    if tags.len() > MAX_TAGS {
+        return Err("Object tags cannot be greater than 10");
+    }
+    Ok(tags)
+}
+
+/// `<Tagging>` document for GetObjectTagging, tags sorted by key
+pub fn tagging_xml(tags: &HashMap<String, String>) -> String {
+    let mut sorted: Vec<_> = tags.iter().collect();
+    sorted.sort();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<Tagging xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\n  <TagSet>\n");
+    for (key, value) in sorted {
+        xml.push_str("    <Tag>\n");
+        xml.push_str(&format!("      <Key>{}</Key>\n", super::server::xml_escape(key)));
+        xml.push_str(&format!("      <Value>{}</Value>\n", super::server::xml_escape(value)));
+        xml.push_str("    </Tag>\n");
+    }
+    xml.push_str("  </TagSet>\n</Tagging>");
+    xml
+}
+
+/// A `tag-filter` query value: `key:value`, or just `key` for any value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl TagFilter {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (key, value) = match s.split_once(':') {
+            Some((key, value)) => (key, Some(value.to_string())),
+            None => (s, None),
+        };
+        (!key.is_empty()).then(|| TagFilter { key: key.to_string(), value })
+    }
+
+    pub fn matches(&self, entry: &FileEntry) -> bool {
+        match (tags(entry).get(&self.key), &self.value) {
+            (Some(actual), Some(wanted)) => actual == wanted,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// Text between `<name>` and `</name>`
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let (_, rest) = xml.split_once(&format!("<{}>", name))?;
+    rest.split_once(&format!("</{}>", name)).map(|(inner, _)| inner)
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
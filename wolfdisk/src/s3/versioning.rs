@@ -0,0 +1,345 @@
+//! S3 object versioning
+//!
+//! Versioning is enabled or suspended per bucket; the state is kept in
+//! `{index_dir}/s3_versioning.json`. While a bucket is versioned, overwriting
+//! an object moves the previous `FileEntry` into the new entry's `versions`
+//! list instead of freeing its chunks. Chunks are only garbage-collected once
+//! no remaining version of the object references them.
+//!
+//! Objects written before versioning was enabled carry no version ID and are
+//! reported as the S3 "null" version.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::storage::{FileEntry, FileIndex};
+
+const VERSIONING_FILENAME: &str = "s3_versioning.json";
+
+/// Version ID reported for objects without one
+pub const NULL_VERSION_ID: &str = "null";
+
+/// Bucket versioning state, as in `<VersioningConfiguration><Status>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VersioningStatus {
+    Enabled,
+    Suspended,
+}
+
+impl VersioningStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VersioningStatus::Enabled => "Enabled",
+            VersioningStatus::Suspended => "Suspended",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "Enabled" => Some(VersioningStatus::Enabled),
+            "Suspended" => Some(VersioningStatus::Suspended),
+            _ => None,
+        }
+    }
+}
+
+/// Per-bucket versioning configuration, persisted as JSON
+pub struct BucketVersioning {
+    path: PathBuf,
+    buckets: HashMap<String, VersioningStatus>,
+}
+
+impl BucketVersioning {
+    /// No bucket versioned yet; state is saved under `index_dir`
+    pub fn empty(index_dir: &Path) -> Self {
+        Self {
+            path: index_dir.join(VERSIONING_FILENAME),
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Load the versioning state from `index_dir`, or start empty
+    pub fn load(index_dir: &Path) -> Result<Self> {
+        let mut state = Self::empty(index_dir);
+        if state.path.exists() {
+            state.buckets = serde_json::from_reader(BufReader::new(File::open(&state.path)?))?;
+        }
+        Ok(state)
+    }
+
+    /// Versioning status of a bucket (None if never configured)
+    pub fn status(&self, bucket: &str) -> Option<VersioningStatus> {
+        self.buckets.get(bucket).copied()
+    }
+
+    /// Whether new PUTs to the bucket create versions
+    pub fn is_enabled(&self, bucket: &str) -> bool {
+        self.status(bucket) == Some(VersioningStatus::Enabled)
+    }
+
+    /// Set a bucket's versioning status and persist it
+    pub fn set(&mut self, bucket: &str, status: VersioningStatus) -> Result<()> {
+        self.buckets.insert(bucket.to_string(), status);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&self.path)?), &self.buckets)?;
+        Ok(())
+    }
+}
+
+/// Format a version ID for S3 responses
+pub fn format_version_id(id: Option<Uuid>) -> String {
+    match id {
+        Some(id) if !id.is_nil() => id.to_string(),
+        _ => NULL_VERSION_ID.to_string(),
+    }
+}
+
+/// Parse a `versionId` query parameter; `Some(None)` is the "null" version
+pub fn parse_version_id(s: &str) -> Option<Option<Uuid>> {
+    if s == NULL_VERSION_ID {
+        Some(None)
+    } else {
+        Uuid::parse_str(s).ok().map(Some)
+    }
+}
+
+/// One version of an object, for ListObjectVersions
+#[derive(Debug, Clone)]
+pub struct VersionInfo {
+    pub version_id: Option<Uuid>,
+    pub is_latest: bool,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub first_chunk: Option<[u8; 32]>,
+}
+
+/// Result of deleting a single version
+#[derive(Debug)]
+pub struct DeletedVersion {
+    /// Chunks no longer referenced by any version of the object
+    pub freed_chunks: Vec<[u8; 32]>,
+    /// Whether the last version was deleted and the object is gone
+    pub object_removed: bool,
+}
+
+/// Chunks of `removed` that `remaining` no longer references
+fn unreferenced(removed: &FileEntry, remaining: Option<&FileEntry>) -> Vec<[u8; 32]> {
+    let kept: HashSet<[u8; 32]> = remaining.map(|e| e.all_chunk_hashes().collect()).unwrap_or_default();
+    let mut freed: Vec<[u8; 32]> = removed.chunks.iter()
+        .map(|c| c.hash)
+        .filter(|h| !kept.contains(h))
+        .collect();
+    freed.sort();
+    freed.dedup();
+    freed
+}
+
+/// Install `entry` as the current version of the object at `path`.
+///
+/// When `versioned`, the new entry gets a fresh version ID and the previous
+/// entry is kept as a noncurrent version. Otherwise the previous entry is
+/// only kept if it is itself a numbered version (S3 "suspended" semantics).
+/// Returns the new version ID and chunks that are no longer referenced.
+pub fn put_version(
+    index: &mut FileIndex,
+    path: PathBuf,
+    mut entry: FileEntry,
+    versioned: bool,
+) -> (Option<Uuid>, Vec<[u8; 32]>) {
+    entry.version_id = versioned.then(Uuid::new_v4);
+
+    let mut freed = Vec::new();
+    if let Some(mut previous) = index.unlink(&path).filter(|e| !e.is_dir) {
+        entry.versions = std::mem::take(&mut previous.versions);
+        if versioned || previous.version_id.is_some() {
+            let id = previous.version_id.unwrap_or(Uuid::nil());
+            entry.versions.push((id, previous));
+        } else if previous.hard_link_count == 0 {
+            freed = unreferenced(&previous, Some(&entry));
+        }
+    }
+
+    let version_id = entry.version_id;
+    index.insert(path, entry);
+    (version_id, freed)
+}
+
+/// Fetch a specific version of an object (without its version history)
+pub fn get_version(index: &FileIndex, path: &Path, version_id: Option<Uuid>) -> Option<FileEntry> {
+    let current = index.get(path).filter(|e| !e.is_dir)?;
+    let mut found = if current.version_id == version_id {
+        current.clone()
+    } else {
+        let wanted = version_id.unwrap_or(Uuid::nil());
+        current.versions.iter().find(|(id, _)| *id == wanted)?.1.clone()
+    };
+    found.versions.clear();
+    found.version_id = version_id;
+    Some(found)
+}
+
+/// Delete a specific version of an object. Deleting the current version
+/// promotes the newest noncurrent version. Returns None if there is no
+/// such version.
+pub fn delete_version(index: &mut FileIndex, path: &Path, version_id: Option<Uuid>) -> Option<DeletedVersion> {
+    let current = index.get(path).filter(|e| !e.is_dir)?;
+
+    if current.version_id != version_id {
+        let wanted = version_id.unwrap_or(Uuid::nil());
+        let pos = current.versions.iter().position(|(id, _)| *id == wanted)?;
+        let mut current = index.get_mut(path)?;
+        let (_, removed) = current.versions.remove(pos);
+        return Some(DeletedVersion {
+            freed_chunks: unreferenced(&removed, Some(&current)),
+            object_removed: false,
+        });
+    }
+
+    let mut removed = index.unlink(path)?;
+    let promoted = removed.versions.pop().map(|(id, mut entry)| {
+        entry.version_id = (!id.is_nil()).then_some(id);
+        entry.versions = std::mem::take(&mut removed.versions);
+        entry
+    });
+    // Other hard links still use the current content
+    let freed_chunks = if removed.hard_link_count == 0 {
+        unreferenced(&removed, promoted.as_ref())
+    } else {
+        Vec::new()
+    };
+    let object_removed = promoted.is_none();
+    if let Some(entry) = promoted {
+        index.insert(path.to_path_buf(), entry);
+    }
+
+    Some(DeletedVersion { freed_chunks, object_removed })
+}
+
+/// All versions of an object, newest first
+pub fn list_versions(index: &FileIndex, path: &Path) -> Vec<VersionInfo> {
+    let Some(current) = index.get(path).filter(|e| !e.is_dir) else {
+        return Vec::new();
+    };
+
+    let info = |id: Option<Uuid>, entry: &FileEntry, is_latest: bool| VersionInfo {
+        version_id: id.filter(|id| !id.is_nil()),
+        is_latest,
+        size: entry.size,
+        modified: entry.modified,
+        first_chunk: entry.chunks.first().map(|c| c.hash),
+    };
+
+    std::iter::once(info(current.version_id, current, true))
+        .chain(current.versions.iter().rev().map(|(id, e)| info(Some(*id), e, false)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ChunkRef, ChunkStore};
+    use tempfile::tempdir;
+
+    fn put(index: &mut FileIndex, store: &ChunkStore, path: &str, data: &[u8], versioned: bool) -> Option<Uuid> {
+        let mut chunks: Vec<ChunkRef> = Vec::new();
+        let size = store.write(&mut chunks, 0, data).unwrap() as u64;
+        let now = SystemTime::now();
+        let entry = FileEntry {
+            size,
+            is_dir: false,
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+            chunks,
+            symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
+        };
+        let (id, freed) = put_version(index, PathBuf::from(path), entry, versioned);
+        for hash in freed {
+            store.delete(&hash).unwrap();
+        }
+        id
+    }
+
+    fn read(index: &FileIndex, store: &ChunkStore, path: &str, id: Option<Uuid>) -> Vec<u8> {
+        let entry = get_version(index, Path::new(path), id).unwrap();
+        store.read(&entry.chunks, 0, entry.size as usize).unwrap()
+    }
+
+    #[test]
+    fn test_put_twice_keeps_both_versions() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().join("chunks"), 4096).unwrap();
+        let mut index = FileIndex::new();
+
+        let v1 = put(&mut index, &store, "bucket/key", b"first version", true);
+        let v2 = put(&mut index, &store, "bucket/key", b"second version", true);
+        assert!(v1.is_some() && v2.is_some() && v1 != v2);
+
+        let versions = list_versions(&index, Path::new("bucket/key"));
+        assert_eq!(versions.len(), 2);
+        assert_eq!((versions[0].version_id, versions[0].is_latest), (v2, true));
+        assert_eq!((versions[1].version_id, versions[1].is_latest), (v1, false));
+
+        assert_eq!(read(&index, &store, "bucket/key", v1), b"first version");
+        assert_eq!(read(&index, &store, "bucket/key", v2), b"second version");
+
+        // Deleting the current version promotes the previous one and frees
+        // only the chunks that no other version references
+        let deleted = delete_version(&mut index, Path::new("bucket/key"), v2).unwrap();
+        assert_eq!(deleted.freed_chunks.len(), 1);
+        assert!(!deleted.object_removed);
+        assert_eq!(index.get(Path::new("bucket/key")).unwrap().version_id, v1);
+
+        let deleted = delete_version(&mut index, Path::new("bucket/key"), v1).unwrap();
+        assert!(deleted.object_removed);
+        assert!(index.get(Path::new("bucket/key")).is_none());
+    }
+
+    #[test]
+    fn test_unversioned_overwrite_replaces_null_version() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().join("chunks"), 4096).unwrap();
+        let mut index = FileIndex::new();
+
+        assert_eq!(put(&mut index, &store, "b/k", b"one", false), None);
+        assert_eq!(put(&mut index, &store, "b/k", b"two", false), None);
+        assert_eq!(list_versions(&index, Path::new("b/k")).len(), 1);
+
+        // Enabling versioning keeps the pre-existing object as the null version
+        let v = put(&mut index, &store, "b/k", b"three", true);
+        let versions = list_versions(&index, Path::new("b/k"));
+        assert_eq!(versions.len(), 2);
+        assert_eq!(format_version_id(versions[1].version_id), NULL_VERSION_ID);
+        assert_eq!(read(&index, &store, "b/k", parse_version_id("null").unwrap()), b"two");
+        assert_eq!(read(&index, &store, "b/k", v), b"three");
+    }
+
+    #[test]
+    fn test_versioning_state_persists() {
+        let dir = tempdir().unwrap();
+        let mut state = BucketVersioning::load(dir.path()).unwrap();
+        assert_eq!(state.status("photos"), None);
+        state.set("photos", VersioningStatus::Enabled).unwrap();
+
+        let state = BucketVersioning::load(dir.path()).unwrap();
+        assert!(state.is_enabled("photos"));
+        assert!(!state.is_enabled("other"));
+    }
+}
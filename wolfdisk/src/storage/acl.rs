@@ -0,0 +1,332 @@
+//! POSIX access control lists
+//!
+//! `setfacl` and `getfacl` write and read `system.posix_acl_access` and
+//! `system.posix_acl_default` in the kernel's binary format. WolfDisk keeps
+//! each ACL among the entry's xattrs as a serialized `Acl` (`user.acl` for
+//! the access ACL, `user.acl.default` for a directory's default ACL), so it
+//! is saved and replicated like any other xattr.
+//!
+//! As in the kernel, the owner, group (or mask) and other entries of an
+//! access ACL are the entry's permission bits: storing an ACL changes the
+//! mode, and `chmod` changes the ACL. An access ACL with only those three
+//! entries says nothing the mode doesn't, so it is kept as the mode alone.
+
+use serde::{Deserialize, Serialize};
+
+/// Access ACL xattr used by `getfacl` / `setfacl`
+pub const ACCESS_ACL_XATTR: &str = "system.posix_acl_access";
+
+/// Default ACL xattr used by `getfacl` / `setfacl` (directories only)
+pub const DEFAULT_ACL_XATTR: &str = "system.posix_acl_default";
+
+/// Where the access ACL is kept in `FileEntry::xattrs`
+pub const STORED_ACCESS_ACL: &str = "user.acl";
+
+/// Where the default ACL is kept in `FileEntry::xattrs`
+pub const STORED_DEFAULT_ACL: &str = "user.acl.default";
+
+/// Version in the header of the kernel's ACL xattr format
+const POSIX_ACL_XATTR_VERSION: u32 = 2;
+
+/// Id the kernel gives entries that aren't for a named user or group
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+/// Kind of ACL entry. The order is the order entries are kept in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AclTag {
+    /// The file's owner
+    UserObj,
+    /// A named user
+    User,
+    /// The file's group
+    GroupObj,
+    /// A named group
+    Group,
+    /// Most any named entry or the group entry may grant
+    Mask,
+    /// Everyone else
+    Other,
+}
+
+impl AclTag {
+    fn from_kernel(tag: u16) -> Option<Self> {
+        match tag {
+            0x01 => Some(AclTag::UserObj),
+            0x02 => Some(AclTag::User),
+            0x04 => Some(AclTag::GroupObj),
+            0x08 => Some(AclTag::Group),
+            0x10 => Some(AclTag::Mask),
+            0x20 => Some(AclTag::Other),
+            _ => None,
+        }
+    }
+
+    fn to_kernel(self) -> u16 {
+        match self {
+            AclTag::UserObj => 0x01,
+            AclTag::User => 0x02,
+            AclTag::GroupObj => 0x04,
+            AclTag::Group => 0x08,
+            AclTag::Mask => 0x10,
+            AclTag::Other => 0x20,
+        }
+    }
+
+    fn is_named(self) -> bool {
+        matches!(self, AclTag::User | AclTag::Group)
+    }
+}
+
+/// One ACL entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AclEntry {
+    pub tag: AclTag,
+    /// uid or gid of a named entry, 0 otherwise
+    pub id: u32,
+    /// rwx bits (4 = read, 2 = write, 1 = execute)
+    pub perms: u8,
+}
+
+/// A POSIX ACL, entries sorted by tag then id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Acl {
+    pub entries: Vec<AclEntry>,
+}
+
+/// Who is asking for access
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub uid: u32,
+    /// Primary group followed by supplementary groups
+    pub groups: Vec<u32>,
+}
+
+impl Acl {
+    /// The three-entry ACL equivalent to permission bits `mode`
+    pub fn from_mode(mode: u32) -> Self {
+        let entry = |tag, shift: u32| AclEntry { tag, id: 0, perms: ((mode >> shift) & 0o7) as u8 };
+        Self { entries: vec![entry(AclTag::UserObj, 6), entry(AclTag::GroupObj, 3), entry(AclTag::Other, 0)] }
+    }
+
+    /// Parse the kernel's xattr format: a little-endian u32 version, then
+    /// 8 bytes per entry (u16 tag, u16 permissions, u32 id). An ACL with no
+    /// entries means "remove it" and parses to None.
+    pub fn from_xattr(data: &[u8]) -> std::result::Result<Option<Self>, libc::c_int> {
+        if data.len() < 4 || !(data.len() - 4).is_multiple_of(8) {
+            return Err(libc::EINVAL);
+        }
+        if u32::from_le_bytes([data[0], data[1], data[2], data[3]]) != POSIX_ACL_XATTR_VERSION {
+            return Err(libc::EOPNOTSUPP);
+        }
+
+        let mut entries = Vec::new();
+        for raw in data[4..].chunks_exact(8) {
+            let tag = AclTag::from_kernel(u16::from_le_bytes([raw[0], raw[1]])).ok_or(libc::EINVAL)?;
+            let perms = u16::from_le_bytes([raw[2], raw[3]]);
+            if perms > 0o7 {
+                return Err(libc::EINVAL);
+            }
+            let id = if tag.is_named() { u32::from_le_bytes([raw[4], raw[5], raw[6], raw[7]]) } else { 0 };
+            entries.push(AclEntry { tag, id, perms: perms as u8 });
+        }
+        if entries.is_empty() {
+            return Ok(None);
+        }
+
+        let acl = Self::new(entries)?;
+        Ok(Some(acl))
+    }
+
+    /// Sort and check entries: one owner, group and other entry, no name
+    /// twice, and a mask if there are named entries
+    pub fn new(mut entries: Vec<AclEntry>) -> std::result::Result<Self, libc::c_int> {
+        entries.sort_by_key(|e| (e.tag, e.id));
+        let count = |tag| entries.iter().filter(|e| e.tag == tag).count();
+        if count(AclTag::UserObj) != 1 || count(AclTag::GroupObj) != 1 || count(AclTag::Other) != 1 {
+            return Err(libc::EINVAL);
+        }
+        let named = count(AclTag::User) + count(AclTag::Group);
+        match count(AclTag::Mask) {
+            0 if named > 0 => return Err(libc::EINVAL),
+            0 | 1 => {}
+            _ => return Err(libc::EINVAL),
+        }
+        if entries.windows(2).any(|w| w[0].tag.is_named() && (w[0].tag, w[0].id) == (w[1].tag, w[1].id)) {
+            return Err(libc::EINVAL);
+        }
+        Ok(Self { entries })
+    }
+
+    /// The kernel's xattr format, as `getfacl` expects it
+    pub fn to_xattr(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(4 + 8 * self.entries.len());
+        data.extend_from_slice(&POSIX_ACL_XATTR_VERSION.to_le_bytes());
+        for entry in &self.entries {
+            let id = if entry.tag.is_named() { entry.id } else { ACL_UNDEFINED_ID };
+            data.extend_from_slice(&entry.tag.to_kernel().to_le_bytes());
+            data.extend_from_slice(&(entry.perms as u16).to_le_bytes());
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        data
+    }
+
+    /// Serialize for `FileEntry::xattrs`
+    pub fn encode(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("ACL serializes")
+    }
+
+    /// Read an ACL kept in `FileEntry::xattrs`
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        bincode::deserialize(data).ok()
+    }
+
+    /// Only the owner, group and other entries
+    pub fn is_minimal(&self) -> bool {
+        self.entries.len() == 3
+    }
+
+    /// Permission bits the ACL stands for: the owner entry, the mask (or
+    /// the group entry without one) and the other entry
+    pub fn mode(&self) -> u32 {
+        let perms = |tag| self.entries.iter().find(|e| e.tag == tag).map(|e| e.perms as u32);
+        let group = perms(AclTag::Mask).or(perms(AclTag::GroupObj)).unwrap_or(0);
+        (perms(AclTag::UserObj).unwrap_or(0) << 6) | (group << 3) | perms(AclTag::Other).unwrap_or(0)
+    }
+
+    /// Apply a `chmod` to the entries `mode()` reads
+    pub fn set_mode(&mut self, mode: u32) {
+        let group_tag = if self.entries.iter().any(|e| e.tag == AclTag::Mask) { AclTag::Mask } else { AclTag::GroupObj };
+        for entry in &mut self.entries {
+            let shift = match entry.tag {
+                AclTag::UserObj => 6,
+                tag if tag == group_tag => 3,
+                AclTag::Other => 0,
+                _ => continue,
+            };
+            entry.perms = ((mode >> shift) & 0o7) as u8;
+        }
+    }
+
+    /// Whether `caller` gets all of the `want` rwx bits on a file owned by
+    /// `owner:group`, checked the way the kernel does: the owner entry, then
+    /// a named user, then every group entry the caller is in (limited by the
+    /// mask), and only then the other entry
+    pub fn permits(&self, caller: &Caller, owner: u32, group: u32, want: u8) -> bool {
+        let mask = self.entries.iter().find(|e| e.tag == AclTag::Mask).map_or(0o7, |e| e.perms);
+        let grants = |perms: u8| perms & want == want;
+
+        if caller.uid == owner {
+            return self.entries.iter().any(|e| e.tag == AclTag::UserObj && grants(e.perms));
+        }
+        if let Some(user) = self.entries.iter().find(|e| e.tag == AclTag::User && e.id == caller.uid) {
+            return grants(user.perms & mask);
+        }
+
+        let mut in_group = false;
+        for entry in &self.entries {
+            let member = match entry.tag {
+                AclTag::GroupObj => caller.groups.contains(&group),
+                AclTag::Group => caller.groups.contains(&entry.id),
+                _ => false,
+            };
+            if member {
+                if grants(entry.perms & mask) {
+                    return true;
+                }
+                in_group = true;
+            }
+        }
+        if in_group {
+            return false;
+        }
+
+        self.entries.iter().any(|e| e.tag == AclTag::Other && grants(e.perms))
+    }
+}
+
+/// The stored name behind an ACL xattr name
+pub fn stored_name(name: &str) -> Option<&'static str> {
+    match name {
+        ACCESS_ACL_XATTR => Some(STORED_ACCESS_ACL),
+        DEFAULT_ACL_XATTR => Some(STORED_DEFAULT_ACL),
+        _ => None,
+    }
+}
+
+/// The name `listxattr` shows for a stored ACL
+pub fn listed_name(stored: &str) -> Option<&'static str> {
+    match stored {
+        STORED_ACCESS_ACL => Some(ACCESS_ACL_XATTR),
+        STORED_DEFAULT_ACL => Some(DEFAULT_ACL_XATTR),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What `setfacl -m u:1000:rw` sends for a file with mode 0644
+    fn setfacl_payload() -> Vec<u8> {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        for (tag, perms, id) in [(0x01u16, 6u16, u32::MAX), (0x02, 6, 1000), (0x04, 4, u32::MAX), (0x10, 6, u32::MAX), (0x20, 4, u32::MAX)] {
+            data.extend_from_slice(&tag.to_le_bytes());
+            data.extend_from_slice(&perms.to_le_bytes());
+            data.extend_from_slice(&id.to_le_bytes());
+        }
+        data
+    }
+
+    fn caller(uid: u32, groups: &[u32]) -> Caller {
+        Caller { uid, groups: groups.to_vec() }
+    }
+
+    #[test]
+    fn test_kernel_format_round_trip() {
+        let acl = Acl::from_xattr(&setfacl_payload()).unwrap().unwrap();
+        assert_eq!(acl.entries.len(), 5);
+        assert_eq!(acl.entries[1], AclEntry { tag: AclTag::User, id: 1000, perms: 6 });
+        assert_eq!(acl.to_xattr(), setfacl_payload());
+        assert_eq!(Acl::decode(&acl.encode()), Some(acl.clone()));
+
+        // The mask stands in for the group bits
+        assert_eq!(acl.mode(), 0o664);
+        assert!(!acl.is_minimal());
+        assert!(Acl::from_mode(0o640).is_minimal());
+        assert_eq!(Acl::from_mode(0o640).mode(), 0o640);
+
+        // An empty ACL removes it; broken ones are refused
+        assert_eq!(Acl::from_xattr(&2u32.to_le_bytes()), Ok(None));
+        assert_eq!(Acl::from_xattr(&setfacl_payload()[..10]), Err(libc::EINVAL));
+        let no_mask: Vec<u8> = setfacl_payload().into_iter().take(4 + 8 * 3).chain(setfacl_payload()[4 + 8 * 4..].iter().copied()).collect();
+        assert_eq!(Acl::from_xattr(&no_mask), Err(libc::EINVAL));
+    }
+
+    #[test]
+    fn test_permission_checks() {
+        // u::rw- u:1000:rw- g::r-- m::rw- o::r-- on a file owned by 0:100
+        let mut acl = Acl::from_xattr(&setfacl_payload()).unwrap().unwrap();
+        assert!(acl.permits(&caller(1000, &[1000]), 0, 100, 6));
+        assert!(!acl.permits(&caller(1000, &[1000]), 0, 100, 1));
+        assert!(acl.permits(&caller(1001, &[100]), 0, 100, 4));
+        assert!(!acl.permits(&caller(1001, &[100]), 0, 100, 2));
+        assert!(!acl.permits(&caller(1002, &[1002]), 0, 100, 2));
+
+        // chmod g-w lowers the mask, which limits the named user too
+        acl.set_mode(0o644);
+        assert_eq!(acl.entries.iter().find(|e| e.tag == AclTag::Mask).unwrap().perms, 4);
+        assert!(acl.permits(&caller(1000, &[1000]), 0, 100, 4));
+        assert!(!acl.permits(&caller(1000, &[1000]), 0, 100, 2));
+
+        // A group entry that matches but doesn't grant blocks the other entry
+        let acl = Acl::new(vec![
+            AclEntry { tag: AclTag::UserObj, id: 0, perms: 7 },
+            AclEntry { tag: AclTag::GroupObj, id: 0, perms: 0 },
+            AclEntry { tag: AclTag::Group, id: 200, perms: 0 },
+            AclEntry { tag: AclTag::Mask, id: 0, perms: 7 },
+            AclEntry { tag: AclTag::Other, id: 0, perms: 4 },
+        ]).unwrap();
+        assert!(!acl.permits(&caller(1003, &[1003, 200]), 0, 100, 4));
+        assert!(acl.permits(&caller(1003, &[1003]), 0, 100, 4));
+    }
+}
@@ -0,0 +1,104 @@
+//! In-memory chunk read cache (`storage.read_cache_mb`)
+//!
+//! Chunks are content-addressed, so a cached chunk never goes stale; it only
+//! has to be dropped when the chunk is deleted. Entries are evicted least
+//! recently used first once their total size exceeds the byte budget.
+
+use std::sync::Arc;
+
+use lru::LruCache;
+
+/// LRU cache of chunk data, bounded by its total size
+pub struct ReadCache {
+    entries: LruCache<[u8; 32], Arc<Vec<u8>>>,
+    used_bytes: u64,
+    capacity_bytes: u64,
+}
+
+impl ReadCache {
+    /// Create a cache holding up to `capacity_bytes` of chunk data (0 disables it)
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            entries: LruCache::unbounded(),
+            used_bytes: 0,
+            capacity_bytes,
+        }
+    }
+
+    /// Create a cache of `capacity_mb` megabytes
+    pub fn with_capacity_mb(capacity_mb: u64) -> Self {
+        Self::new(capacity_mb * 1024 * 1024)
+    }
+
+    /// A cached chunk, marking it most recently used
+    pub fn get(&mut self, hash: &[u8; 32]) -> Option<Arc<Vec<u8>>> {
+        self.entries.get(hash).cloned()
+    }
+
+    /// Cache a chunk, evicting the least recently used ones to make room.
+    /// Chunks larger than the whole budget are not cached.
+    pub fn insert(&mut self, hash: [u8; 32], data: Arc<Vec<u8>>) {
+        let size = data.len() as u64;
+        if size > self.capacity_bytes || self.entries.contains(&hash) {
+            return;
+        }
+        while self.used_bytes + size > self.capacity_bytes {
+            match self.entries.pop_lru() {
+                Some((_, evicted)) => self.used_bytes -= evicted.len() as u64,
+                None => break,
+            }
+        }
+        self.used_bytes += size;
+        self.entries.put(hash, data);
+    }
+
+    /// Drop a chunk (deleted or evicted from the local store)
+    pub fn remove(&mut self, hash: &[u8; 32]) {
+        if let Some(data) = self.entries.pop(hash) {
+            self.used_bytes -= data.len() as u64;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes of chunk data cached
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes
+    }
+
+    pub fn capacity_bytes(&self) -> u64 {
+        self.capacity_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used_over_budget() {
+        let mut cache = ReadCache::new(10);
+        cache.insert([1; 32], Arc::new(vec![1; 4]));
+        cache.insert([2; 32], Arc::new(vec![2; 4]));
+        // Reading chunk 1 makes chunk 2 the oldest
+        assert!(cache.get(&[1; 32]).is_some());
+        cache.insert([3; 32], Arc::new(vec![3; 4]));
+
+        assert!(cache.get(&[2; 32]).is_none());
+        assert_eq!(cache.get(&[1; 32]).unwrap().as_slice(), [1; 4]);
+        assert_eq!((cache.len(), cache.used_bytes()), (2, 8));
+
+        // Larger than the budget: not cached, nothing evicted
+        cache.insert([4; 32], Arc::new(vec![4; 11]));
+        assert_eq!(cache.len(), 2);
+
+        cache.remove(&[1; 32]);
+        assert_eq!((cache.len(), cache.used_bytes()), (1, 4));
+    }
+}
@@ -1,19 +1,40 @@
 //! Chunk storage with content-addressed deduplication
 
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+#[cfg(any(feature = "parallel-reads", feature = "io-uring"))]
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
 
 use sha2::{Sha256, Digest};
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::error::{Error, Result};
-use super::ChunkRef;
+use super::cache::ReadCache;
+use super::tiered::{cold_key, ColdTier};
+use super::{ChunkRef, Tier};
+#[cfg(feature = "io-uring")]
+use super::uring::UringWriter;
+#[cfg(feature = "mmap-cache")]
+use super::mmap_cache::MmapCache;
 
-/// Maximum number of chunks to keep in the read cache
-const DEFAULT_CACHE_CAPACITY: usize = 256;
+/// Read cache size when none is configured (`storage.read_cache_mb`)
+const DEFAULT_READ_CACHE_MB: u64 = 512;
+
+/// Chunk writes submitted to io_uring at once (`storage.write_batch_size`)
+const DEFAULT_WRITE_BATCH_SIZE: usize = 32;
+
+/// A chunk's access time is only rewritten when it is older than this, so
+/// reads don't each cost a metadata write
+const ACCESS_TIME_GRANULARITY: Duration = Duration::from_secs(3600);
+
+/// Hash recorded for an all-zero chunk. Such chunks are never written to
+/// disk; reads return `ChunkRef::size` zero bytes instead.
+pub const SPARSE_CHUNK_HASH: [u8; 32] = [0u8; 32];
 
 /// Content-addressed chunk storage
 pub struct ChunkStore {
@@ -24,58 +45,120 @@ pub struct ChunkStore {
     chunk_size: usize,
 
     /// In-memory read cache: hash -> chunk data
-    read_cache: Mutex<ReadCache>,
+    read_cache: Arc<RwLock<ReadCache>>,
+
+    /// Chunks referenced by snapshots (never deleted while pinned)
+    snapshot_pins: Option<Mutex<SnapshotPins>>,
+
+    /// Thread pool for `read_parallel`, sized by the first call's worker count
+    #[cfg(feature = "parallel-reads")]
+    read_pool: OnceLock<Option<rayon::ThreadPool>>,
+
+    /// Writes submitted to io_uring at once
+    #[cfg_attr(not(feature = "io-uring"), allow(dead_code))]
+    write_batch_size: usize,
+
+    /// io_uring writer thread, started by the first async write
+    #[cfg(feature = "io-uring")]
+    uring: OnceLock<Option<UringWriter>>,
+
+    /// Where chunks missing locally are fetched from
+    cold_tier: Option<ColdTierSource>,
+
+    /// Mapped views of recently read chunk files
+    #[cfg(feature = "mmap-cache")]
+    mmap_cache: Option<MmapCache>,
+
+    /// Operation counters for `GET /metrics`
+    stats: ChunkStoreStats,
 }
 
-/// Simple LRU-style read cache for chunk data
-struct ReadCache {
-    entries: HashMap<[u8; 32], Vec<u8>>,
-    access_order: Vec<[u8; 32]>,
-    capacity: usize,
+/// Chunk operations since the store was opened
+#[derive(Debug, Default)]
+pub struct ChunkStoreStats {
+    /// Chunks returned by `get`, from a cache, disk or the cold tier
+    pub reads: AtomicU64,
+    /// Chunks written to disk (not counting deduplicated ones)
+    pub writes: AtomicU64,
+    /// Chunk files removed by `delete`
+    pub deletes: AtomicU64,
+    pub read_bytes: AtomicU64,
+    pub write_bytes: AtomicU64,
+    /// Chunk reads answered by the read cache
+    pub cache_hits: AtomicU64,
+    /// Chunk reads that had to go to disk, the cold tier or the leader
+    pub cache_misses: AtomicU64,
+    /// FUSE writes large enough to bypass the write buffer
+    pub streaming_writes: AtomicU64,
 }
 
-impl ReadCache {
-    fn new(capacity: usize) -> Self {
-        Self {
-            entries: HashMap::with_capacity(capacity),
-            access_order: Vec::with_capacity(capacity),
-            capacity,
-        }
+impl ChunkStoreStats {
+    fn record_read(&self, bytes: usize) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.read_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
     }
 
-    fn get(&mut self, hash: &[u8; 32]) -> Option<Vec<u8>> {
-        if let Some(data) = self.entries.get(hash) {
-            // Move to end of access order (most recently used)
-            if let Some(pos) = self.access_order.iter().position(|h| h == hash) {
-                self.access_order.remove(pos);
-                self.access_order.push(*hash);
-            }
-            Some(data.clone())
-        } else {
-            None
-        }
+    fn record_write(&self, bytes: usize) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        self.write_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
     }
+}
 
-    fn insert(&mut self, hash: [u8; 32], data: Vec<u8>) {
-        if self.entries.contains_key(&hash) {
-            return;
-        }
+/// The cold tier as seen by reads
+struct ColdTierSource {
+    store: Arc<dyn ColdTier>,
+    /// Keep a local copy of chunks fetched from the cold tier
+    rewarm: bool,
+}
 
-        // Evict oldest entries if at capacity
-        while self.entries.len() >= self.capacity && !self.access_order.is_empty() {
-            let oldest = self.access_order.remove(0);
-            self.entries.remove(&oldest);
-        }
+/// A chunk file in the local store
+#[derive(Debug, Clone)]
+pub struct LocalChunk {
+    pub hash: [u8; 32],
+    pub size: u64,
+    /// Last read (or write) of the chunk
+    pub accessed: SystemTime,
+}
 
-        self.entries.insert(hash, data);
-        self.access_order.push(hash);
+/// Set of chunk hashes referenced by snapshots, rebuilt whenever the
+/// snapshot directory changes (snapshots are immutable once created, so
+/// the list of snapshot directories is enough to detect changes)
+struct SnapshotPins {
+    dir: PathBuf,
+    signature: Vec<(std::ffi::OsString, Option<SystemTime>)>,
+    hashes: HashSet<[u8; 32]>,
+}
+
+impl SnapshotPins {
+    fn current_signature(&self) -> Vec<(std::ffi::OsString, Option<SystemTime>)> {
+        let mut signature: Vec<_> = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+                .map(|e| (e.file_name(), e.metadata().and_then(|m| m.modified()).ok()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        signature.sort();
+        signature
     }
 
-    fn remove(&mut self, hash: &[u8; 32]) {
-        self.entries.remove(hash);
-        if let Some(pos) = self.access_order.iter().position(|h| h == hash) {
-            self.access_order.remove(pos);
+    fn contains(&mut self, hash: &[u8; 32]) -> bool {
+        let signature = self.current_signature();
+        if signature != self.signature {
+            match super::snapshot::referenced_chunks(&self.dir) {
+                Ok(hashes) => {
+                    self.hashes = hashes;
+                    self.signature = signature;
+                }
+                Err(e) => {
+                    // Can't tell what's referenced - err on the side of keeping data
+                    warn!("Failed to load snapshot chunk references: {}", e);
+                    return true;
+                }
+            }
         }
+        self.hashes.contains(hash)
     }
 }
 
@@ -87,10 +170,99 @@ impl ChunkStore {
         Ok(Self {
             base_dir,
             chunk_size,
-            read_cache: Mutex::new(ReadCache::new(DEFAULT_CACHE_CAPACITY)),
+            read_cache: Arc::new(RwLock::new(ReadCache::with_capacity_mb(DEFAULT_READ_CACHE_MB))),
+            snapshot_pins: None,
+            #[cfg(feature = "parallel-reads")]
+            read_pool: OnceLock::new(),
+            write_batch_size: DEFAULT_WRITE_BATCH_SIZE,
+            #[cfg(feature = "io-uring")]
+            uring: OnceLock::new(),
+            cold_tier: None,
+            #[cfg(feature = "mmap-cache")]
+            mmap_cache: None,
+            stats: ChunkStoreStats::default(),
         })
     }
 
+    /// Fetch chunks that are not stored locally from `store`, keeping a
+    /// local copy again if `rewarm` is set. Reads also start recording
+    /// chunk access times, which decide what gets evicted.
+    pub fn with_cold_tier(mut self, store: Arc<dyn ColdTier>, rewarm: bool) -> Self {
+        self.cold_tier = Some(ColdTierSource { store, rewarm });
+        self
+    }
+
+    /// Use `cache` for chunk reads, e.g. one sized by `storage.read_cache_mb`
+    pub fn with_read_cache(mut self, cache: Arc<RwLock<ReadCache>>) -> Self {
+        self.read_cache = cache;
+        self
+    }
+
+    /// The in-memory read cache
+    pub fn read_cache(&self) -> &Arc<RwLock<ReadCache>> {
+        &self.read_cache
+    }
+
+    /// Set how many chunk writes are submitted to io_uring at once
+    /// (only used with the `io-uring` feature)
+    pub fn with_write_batch_size(mut self, batch_size: usize) -> Self {
+        self.write_batch_size = batch_size;
+        self
+    }
+
+    /// Serve reads of recently used chunk files from memory maps, up to
+    /// `capacity_mb` in total (only used with the `mmap-cache` feature; 0 disables)
+    #[cfg_attr(not(feature = "mmap-cache"), allow(unused_mut, unused_variables))]
+    pub fn with_mmap_cache(mut self, capacity_mb: u64) -> Self {
+        #[cfg(feature = "mmap-cache")]
+        {
+            self.mmap_cache = (capacity_mb > 0).then(|| MmapCache::new(capacity_mb * 1024 * 1024));
+        }
+        self
+    }
+
+    /// Protect chunks referenced by snapshots under `dir` from deletion
+    pub fn with_snapshot_dir(mut self, dir: PathBuf) -> Self {
+        self.snapshot_pins = Some(Mutex::new(SnapshotPins {
+            dir,
+            signature: Vec::new(),
+            hashes: HashSet::new(),
+        }));
+        self
+    }
+
+    /// Operation counters
+    pub fn stats(&self) -> &ChunkStoreStats {
+        &self.stats
+    }
+
+    /// Number and total size of the chunk files stored locally
+    pub fn disk_usage(&self) -> Result<(u64, u64)> {
+        let (mut count, mut bytes) = (0, 0);
+        for dir in fs::read_dir(&self.base_dir)? {
+            let dir = dir?;
+            if dir.file_name().len() != 2 || !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())? {
+                count += 1;
+                bytes += file?.metadata()?.len();
+            }
+        }
+        Ok((count, bytes))
+    }
+
+    /// Check whether a chunk is still referenced by a snapshot
+    fn is_pinned(&self, hash: &[u8; 32]) -> bool {
+        match self.snapshot_pins {
+            Some(ref pins) => match pins.lock() {
+                Ok(mut pins) => pins.contains(hash),
+                Err(_) => true,
+            },
+            None => false,
+        }
+    }
+
     /// Get the path for a chunk by its hash
     fn chunk_path(&self, hash: &[u8; 32]) -> PathBuf {
         let hex = hex::encode(hash);
@@ -100,39 +272,123 @@ impl ChunkStore {
 
     /// Store a chunk and return its hash
     pub fn store(&self, data: &[u8]) -> Result<[u8; 32]> {
-        // Calculate hash
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        let hash: [u8; 32] = hasher.finalize().into();
+        let Some((hash, path)) = self.prepare_store(data)? else {
+            return Ok(content_hash(data));
+        };
+
+        // Write chunk to file (no sync_all - let OS page cache handle durability)
+        let mut file = File::create(&path)?;
+        file.write_all(data)?;
+        self.finish_store(hash, data);
+        Ok(hash)
+    }
+
+    /// Store a chunk like `store`, writing it through io_uring with the
+    /// `io-uring` feature. Concurrent calls are submitted to the kernel in
+    /// batches of `storage.write_batch_size`. Without the feature (or if the
+    /// io_uring thread could not start) this is the same as `store`.
+    pub async fn store_async(&self, data: &[u8]) -> Result<[u8; 32]> {
+        #[cfg(feature = "io-uring")]
+        if let Some(writer) = self.uring_writer() {
+            let Some((hash, path)) = self.prepare_store(data)? else {
+                return Ok(content_hash(data));
+            };
+            writer.submit(path, data.to_vec()).await
+                .map_err(|_| Error::Storage("io_uring writer dropped a chunk write".to_string()))??;
+            self.finish_store(hash, data);
+            return Ok(hash);
+        }
 
+        self.store(data)
+    }
+
+    /// Store several chunks, returning each one's hash (or error) in order.
+    /// With the `io-uring` feature the writes are queued together so they
+    /// share io_uring submissions; this blocks the calling thread and must
+    /// not be called from inside an async runtime.
+    pub fn store_many(&self, chunks: &[&[u8]]) -> Vec<Result<[u8; 32]>> {
+        #[cfg(feature = "io-uring")]
+        if let Some(writer) = self.uring_writer() {
+            let pending: Vec<_> = chunks.iter()
+                .map(|data| match self.prepare_store(data) {
+                    Ok(Some((hash, path))) => Ok(Some((hash, writer.submit(path, data.to_vec())))),
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                })
+                .collect();
+            return chunks.iter().zip(pending)
+                .map(|(data, pending)| match pending? {
+                    Some((hash, done)) => {
+                        done.blocking_recv()
+                            .map_err(|_| Error::Storage("io_uring writer dropped a chunk write".to_string()))??;
+                        self.finish_store(hash, data);
+                        Ok(hash)
+                    }
+                    None => Ok(content_hash(data)),
+                })
+                .collect();
+        }
+
+        chunks.iter().map(|data| self.store(data)).collect()
+    }
+
+    /// Hash a new chunk and create its directory. Returns None if the chunk
+    /// is already stored (deduplication) or is all zeros (sparse).
+    fn prepare_store(&self, data: &[u8]) -> Result<Option<([u8; 32], PathBuf)>> {
+        let hash = content_hash(data);
+        if hash == SPARSE_CHUNK_HASH {
+            return Ok(None);
+        }
         let path = self.chunk_path(&hash);
 
         // Check if chunk already exists (deduplication)
         if path.exists() {
-            debug!("Chunk {} already exists (deduplicated)", hex::encode(&hash));
-            return Ok(hash);
+            debug!("Chunk {} already exists (deduplicated)", hex::encode(hash));
+            return Ok(None);
         }
 
         // Create parent directory
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
+        Ok(Some((hash, path)))
+    }
 
-        // Write chunk to file (no sync_all - let OS page cache handle durability)
-        let mut file = File::create(&path)?;
-        file.write_all(data)?;
-
-        // Populate read cache with the data we just wrote
-        if let Ok(mut cache) = self.read_cache.lock() {
-            cache.insert(hash, data.to_vec());
+    /// Populate the read cache with a chunk we just wrote
+    fn finish_store(&self, hash: [u8; 32], data: &[u8]) {
+        self.stats.record_write(data.len());
+        if self.caches_written_chunks() {
+            if let Ok(mut cache) = self.read_cache.write() {
+                cache.insert(hash, Arc::new(data.to_vec()));
+            }
         }
+        debug!("Stored chunk {} ({} bytes)", hex::encode(hash), data.len());
+    }
 
-        debug!("Stored chunk {} ({} bytes)", hex::encode(&hash), data.len());
-        Ok(hash)
+    /// Whether freshly written chunks go into the heap read cache. With the
+    /// mmap cache they are mapped from the page cache on first read instead.
+    fn caches_written_chunks(&self) -> bool {
+        #[cfg(feature = "mmap-cache")]
+        return self.mmap_cache.is_none();
+        #[cfg(not(feature = "mmap-cache"))]
+        true
+    }
+
+    /// The io_uring writer thread, started on first use
+    #[cfg(feature = "io-uring")]
+    fn uring_writer(&self) -> Option<&UringWriter> {
+        self.uring.get_or_init(|| {
+            UringWriter::start(self.write_batch_size)
+                .map_err(|e| warn!("Failed to start io_uring writer, using blocking writes: {}", e))
+                .ok()
+        }).as_ref()
     }
     
     /// Store a chunk with a known hash (for replication)
     pub fn store_with_hash(&self, hash: &[u8; 32], data: &[u8]) -> Result<()> {
+        if *hash == SPARSE_CHUNK_HASH {
+            return Ok(());
+        }
         let path = self.chunk_path(hash);
 
         // Check if chunk already exists (deduplication)
@@ -149,115 +405,389 @@ impl ChunkStore {
         // Write chunk to file (no sync_all - let OS page cache handle durability)
         let mut file = File::create(&path)?;
         file.write_all(data)?;
+        self.stats.record_write(data.len());
 
         // Populate read cache
-        if let Ok(mut cache) = self.read_cache.lock() {
-            cache.insert(*hash, data.to_vec());
+        if self.caches_written_chunks() {
+            if let Ok(mut cache) = self.read_cache.write() {
+                cache.insert(*hash, Arc::new(data.to_vec()));
+            }
         }
 
         debug!("Stored replicated chunk {} ({} bytes)", hex::encode(hash), data.len());
         Ok(())
     }
 
+    /// Write a good copy over a chunk file whose contents no longer match
+    /// its hash, dropping any cached copy of the bad one
+    pub fn replace_chunk(&self, hash: &[u8; 32], data: &[u8]) -> Result<()> {
+        if hash_of(data) != *hash {
+            return Err(Error::Storage(format!("Replacement for chunk {} has the wrong hash", hex::encode(hash))));
+        }
+        let path = self.chunk_path(hash);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write beside it and rename, so the chunk is never half-written
+        let tmp = path.with_extension("repair");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)?;
+        self.stats.record_write(data.len());
+
+        if let Ok(mut cache) = self.read_cache.write() {
+            cache.remove(hash);
+        }
+        #[cfg(feature = "mmap-cache")]
+        if let Some(ref cache) = self.mmap_cache {
+            cache.remove(hash);
+        }
+        Ok(())
+    }
+
     /// Retrieve a chunk by its hash
     pub fn get(&self, hash: &[u8; 32]) -> Result<Vec<u8>> {
-        // Check read cache first
-        if let Ok(mut cache) = self.read_cache.lock() {
-            if let Some(data) = cache.get(hash) {
-                debug!("Cache hit for chunk {}", hex::encode(hash));
-                return Ok(data);
-            }
+        let data = self.get_shared(hash)?;
+        Ok(Arc::try_unwrap(data).unwrap_or_else(|data| data.as_ref().clone()))
+    }
+
+    /// `get` without copying a chunk out of the read cache
+    fn get_shared(&self, hash: &[u8; 32]) -> Result<Arc<Vec<u8>>> {
+        let data = self.load(hash)?;
+        self.stats.record_read(data.len());
+        Ok(data)
+    }
+
+    /// `get_shared` without counting the read
+    fn load(&self, hash: &[u8; 32]) -> Result<Arc<Vec<u8>>> {
+        #[cfg(feature = "mmap-cache")]
+        if let Some(data) = self.mmap_cache.as_ref().and_then(|cache| cache.get(hash, self.cold_tier.is_some())) {
+            return Ok(Arc::new(data));
+        }
+
+        if let Some(data) = self.cached(hash) {
+            return Ok(data);
         }
 
         let path = self.chunk_path(hash);
 
         if !path.exists() {
-            return Err(Error::ChunkNotFound(hex::encode(hash)));
+            return self.get_cold(hash).map(Arc::new);
+        }
+
+        // Mapped chunks bypass the heap read cache; the page cache already holds them
+        #[cfg(feature = "mmap-cache")]
+        if let Some(ref cache) = self.mmap_cache {
+            if let Some(data) = cache.load(hash, &path, self.cold_tier.is_some())? {
+                return Ok(Arc::new(data));
+            }
         }
 
         let mut file = File::open(&path)?;
         let mut data = Vec::new();
         file.read_to_end(&mut data)?;
+        if self.cold_tier.is_some() {
+            touch_accessed(&file);
+        }
 
         // Populate cache
-        if let Ok(mut cache) = self.read_cache.lock() {
+        let data = Arc::new(data);
+        if let Ok(mut cache) = self.read_cache.write() {
             cache.insert(*hash, data.clone());
         }
 
         Ok(data)
     }
 
-    /// Delete a chunk (used for garbage collection)
+    /// Look a chunk up in the read cache, counting the hit or miss
+    fn cached(&self, hash: &[u8; 32]) -> Option<Arc<Vec<u8>>> {
+        let data = self.read_cache.write().ok()?.get(hash);
+        match data {
+            Some(data) => {
+                self.stats.cache_hits.fetch_add(1, Ordering::Relaxed);
+                debug!("Cache hit for chunk {}", hex::encode(hash));
+                Some(data)
+            }
+            None => {
+                self.stats.cache_misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// The file holding a chunk, if it is stored locally. Counts as an
+    /// access for cold-tier eviction, like `get`.
+    pub fn local_chunk_path(&self, hash: &[u8; 32]) -> Option<PathBuf> {
+        let path = self.chunk_path(hash);
+        let file = File::open(&path).ok()?;
+        if self.cold_tier.is_some() {
+            touch_accessed(&file);
+        }
+        Some(path)
+    }
+
+    /// Fetch a chunk that is not stored locally from the cold tier
+    fn get_cold(&self, hash: &[u8; 32]) -> Result<Vec<u8>> {
+        let Some(ref cold) = self.cold_tier else {
+            return Err(Error::ChunkNotFound(hex::encode(hash)));
+        };
+        let data = cold.store.get(&cold_key(hash))?
+            .ok_or_else(|| Error::ChunkNotFound(hex::encode(hash)))?;
+        if hash_of(&data) != *hash {
+            return Err(Error::Storage(format!("Cold tier returned corrupt chunk {}", hex::encode(hash))));
+        }
+        debug!("Fetched chunk {} from the cold tier", hex::encode(hash));
+
+        if cold.rewarm {
+            if let Err(e) = self.store_with_hash(hash, &data) {
+                warn!("Failed to rewarm chunk {}: {}", hex::encode(hash), e);
+            }
+        } else if let Ok(mut cache) = self.read_cache.write() {
+            cache.insert(*hash, Arc::new(data.clone()));
+        }
+        Ok(data)
+    }
+
+    /// Read a local chunk without recording the access
+    pub fn read_local(&self, hash: &[u8; 32]) -> Result<Vec<u8>> {
+        Ok(fs::read(self.chunk_path(hash))?)
+    }
+
+    /// Remove the local copy of a chunk that is safe in the cold tier.
+    /// Unlike `delete`, this also applies to chunks pinned by snapshots.
+    pub fn evict_local(&self, hash: &[u8; 32]) -> Result<()> {
+        if let Ok(mut cache) = self.read_cache.write() {
+            cache.remove(hash);
+        }
+        #[cfg(feature = "mmap-cache")]
+        if let Some(ref cache) = self.mmap_cache {
+            cache.remove(hash);
+        }
+        fs::remove_file(self.chunk_path(hash))?;
+        Ok(())
+    }
+
+    /// Every chunk stored locally
+    pub fn local_chunks(&self) -> Result<Vec<LocalChunk>> {
+        let mut chunks = Vec::new();
+        for dir in fs::read_dir(&self.base_dir)? {
+            let dir = dir?;
+            let prefix = dir.file_name().to_string_lossy().into_owned();
+            if prefix.len() != 2 || !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())? {
+                let file = file?;
+                let name = format!("{}{}", prefix, file.file_name().to_string_lossy());
+                let Some(hash) = hex::decode(&name).ok().and_then(|h| <[u8; 32]>::try_from(h).ok()) else {
+                    continue;
+                };
+                let metadata = file.metadata()?;
+                chunks.push(LocalChunk {
+                    hash,
+                    size: metadata.len(),
+                    accessed: metadata.accessed().or_else(|_| metadata.modified())?,
+                });
+            }
+        }
+        Ok(chunks)
+    }
+
+    /// Delete a chunk (used for garbage collection).
+    /// Chunks still referenced by a snapshot are kept.
     pub fn delete(&self, hash: &[u8; 32]) -> Result<()> {
+        if *hash == SPARSE_CHUNK_HASH {
+            return Ok(());
+        }
+        if self.is_pinned(hash) {
+            debug!("Keeping chunk {} (referenced by snapshot)", hex::encode(hash));
+            return Ok(());
+        }
+
         let path = self.chunk_path(hash);
 
         // Remove from cache
-        if let Ok(mut cache) = self.read_cache.lock() {
+        if let Ok(mut cache) = self.read_cache.write() {
+            cache.remove(hash);
+        }
+        #[cfg(feature = "mmap-cache")]
+        if let Some(ref cache) = self.mmap_cache {
             cache.remove(hash);
         }
 
         if path.exists() {
             fs::remove_file(&path)?;
+            self.stats.deletes.fetch_add(1, Ordering::Relaxed);
             debug!("Deleted chunk {}", hex::encode(hash));
         }
 
+        // The chunk may also have been evicted earlier
+        if let Some(ref cold) = self.cold_tier {
+            if let Err(e) = cold.store.delete(&cold_key(hash)) {
+                warn!("Failed to delete chunk {} from the cold tier: {}", hex::encode(hash), e);
+            }
+        }
+
         Ok(())
     }
 
-    /// Check if a chunk exists
+    /// Check if a chunk exists. Sparse chunks always do.
     pub fn exists(&self, hash: &[u8; 32]) -> bool {
-        self.chunk_path(hash).exists()
+        *hash == SPARSE_CHUNK_HASH || self.chunk_path(hash).exists()
     }
 
-    /// Read data from a file's chunks at a given offset
-    pub fn read(&self, chunks: &[ChunkRef], offset: u64, size: usize) -> Result<Vec<u8>> {
-        if chunks.is_empty() {
-            return Ok(Vec::new());
-        }
+    /// Chunks that overlap `[offset, offset + size)`, in file order
+    pub fn chunks_in_range(chunks: &[ChunkRef], offset: u64, size: usize) -> &[ChunkRef] {
+        let end_offset = offset + size as u64;
+        let first = chunks.iter()
+            .position(|c| c.offset + c.size as u64 > offset)
+            .unwrap_or(chunks.len());
+        let last = chunks[first..].iter()
+            .position(|c| c.offset >= end_offset)
+            .map(|n| first + n)
+            .unwrap_or(chunks.len());
+        &chunks[first..last]
+    }
 
-        let mut result = Vec::with_capacity(size);
+    /// Append the part of a chunk's data that falls inside the read range.
+    /// Returns false once `size` bytes have been collected.
+    fn append_range(result: &mut Vec<u8>, chunk: &ChunkRef, chunk_data: &[u8], offset: u64, size: usize) -> bool {
         let end_offset = offset + size as u64;
-        let mut bytes_read = 0;
+        let chunk_start = chunk.offset;
+        let chunk_end = chunk.offset + chunk.size as u64;
+
+        // Ranges no chunk covers (a file extended by truncate or written
+        // past its end) read as zeros
+        let gap_end = chunk_start.saturating_sub(offset) as usize;
+        if result.len() < gap_end {
+            result.resize(gap_end, 0);
+        }
 
-        for chunk in chunks {
-            let chunk_start = chunk.offset;
-            let chunk_end = chunk.offset + chunk.size as u64;
+        // Calculate how much of this chunk to read
+        let read_start = if chunk_start < offset {
+            (offset - chunk_start) as usize
+        } else {
+            0
+        };
 
-            // Skip chunks before our read range
-            if chunk_end <= offset {
-                continue;
-            }
+        let read_end = if chunk_end > end_offset {
+            chunk_data.len() - (chunk_end - end_offset) as usize
+        } else {
+            chunk_data.len()
+        };
+
+        result.extend_from_slice(&chunk_data[read_start..read_end]);
+        result.len() < size
+    }
+
+    /// Pad a read of `size` bytes at `offset` with zeros up to the end of a
+    /// file of `file_size` bytes. A file extended by truncate keeps no chunk
+    /// refs past its old end, so `read` stops short there.
+    pub fn fill_to_size(data: &mut Vec<u8>, file_size: u64, offset: u64, size: usize) {
+        let len = file_size.saturating_sub(offset).min(size as u64) as usize;
+        if data.len() < len {
+            data.resize(len, 0);
+        }
+    }
+
+    /// Load a chunk of a file, synthesizing sparse chunks without any I/O
+    fn get_ref(&self, chunk: &ChunkRef) -> Result<Arc<Vec<u8>>> {
+        if chunk.hash == SPARSE_CHUNK_HASH {
+            return Ok(Arc::new(vec![0u8; chunk.size as usize]));
+        }
+        self.get_shared(&chunk.hash)
+    }
+
+    /// Read data from a file's chunks at a given offset
+    pub fn read(&self, chunks: &[ChunkRef], offset: u64, size: usize) -> Result<Vec<u8>> {
+        let mut result = Vec::with_capacity(size);
 
-            // Stop if we've passed our read range
-            if chunk_start >= end_offset {
+        for chunk in Self::chunks_in_range(chunks, offset, size) {
+            // Load chunk data (will use cache if available)
+            let chunk_data = self.get_ref(chunk)?;
+            if !Self::append_range(&mut result, chunk, &chunk_data, offset, size) {
                 break;
             }
+        }
 
-            // Load chunk data (will use cache if available)
-            let chunk_data = self.get(&chunk.hash)?;
+        Ok(result)
+    }
 
-            // Calculate how much of this chunk to read
-            let read_start = if chunk_start < offset {
-                (offset - chunk_start) as usize
-            } else {
-                0
-            };
+    /// Read data like `read`, loading the chunks in the range on up to
+    /// `workers` threads and merging them in offset order. Without the
+    /// `parallel-reads` feature this is the same as `read`.
+    pub fn read_parallel(&self, chunks: &[ChunkRef], offset: u64, size: usize, workers: usize) -> Result<Vec<u8>> {
+        let in_range = Self::chunks_in_range(chunks, offset, size);
+        if workers <= 1 || in_range.len() <= 1 {
+            return self.read(chunks, offset, size);
+        }
 
-            let read_end = if chunk_end > end_offset {
-                chunk_data.len() - (chunk_end - end_offset) as usize
-            } else {
-                chunk_data.len()
+        #[cfg(feature = "parallel-reads")]
+        {
+            use rayon::prelude::*;
+
+            let pool = self.read_pool.get_or_init(|| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(workers)
+                    .thread_name(|i| format!("wolfdisk-read-{}", i))
+                    .build()
+                    .map_err(|e| warn!("Failed to start parallel read pool: {}", e))
+                    .ok()
+            });
+            let Some(pool) = pool else {
+                return self.read(chunks, offset, size);
             };
 
-            // Append to result
-            result.extend_from_slice(&chunk_data[read_start..read_end]);
-            bytes_read += read_end - read_start;
+            let parts = pool.install(|| {
+                in_range.par_iter()
+                    .map(|chunk| self.get_ref(chunk))
+                    .collect::<Result<Vec<_>>>()
+            })?;
+
+            let mut result = Vec::with_capacity(size);
+            for (chunk, chunk_data) in in_range.iter().zip(&parts) {
+                if !Self::append_range(&mut result, chunk, chunk_data, offset, size) {
+                    break;
+                }
+            }
+            Ok(result)
+        }
 
-            if bytes_read >= size {
+        #[cfg(not(feature = "parallel-reads"))]
+        self.read(chunks, offset, size)
+    }
+
+    /// Read data like `read` on a node that keeps no chunk files (a cluster
+    /// client): chunks come from the read cache, or from `fetch` (a request
+    /// to the leader), which only puts them in the cache
+    pub fn read_through(
+        &self,
+        chunks: &[ChunkRef],
+        offset: u64,
+        size: usize,
+        mut fetch: impl FnMut(&[u8; 32]) -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let mut result = Vec::with_capacity(size);
+        for chunk in Self::chunks_in_range(chunks, offset, size) {
+            let chunk_data = if chunk.hash == SPARSE_CHUNK_HASH {
+                Arc::new(vec![0u8; chunk.size as usize])
+            } else if let Some(data) = self.cached(&chunk.hash) {
+                data
+            } else {
+                let data = fetch(&chunk.hash)?;
+                if hash_of(&data) != chunk.hash {
+                    return Err(Error::Storage(format!("Fetched corrupt chunk {}", hex::encode(chunk.hash))));
+                }
+                let data = Arc::new(data);
+                if let Ok(mut cache) = self.read_cache.write() {
+                    cache.insert(chunk.hash, data.clone());
+                }
+                data
+            };
+            self.stats.record_read(chunk_data.len());
+            if !Self::append_range(&mut result, chunk, &chunk_data, offset, size) {
                 break;
             }
         }
-
         Ok(result)
     }
 
@@ -292,6 +822,7 @@ impl ChunkStore {
                 hash,
                 offset: current_offset,
                 size: chunk_size as u32,
+                tier: Tier::Hot,
             });
 
             written += chunk_size;
@@ -303,6 +834,203 @@ impl ChunkStore {
 
         Ok(written)
     }
+
+    /// Turn `[offset, offset + length)` into a hole. Chunks entirely inside
+    /// the range become sparse and gaps in it are filled with sparse chunks,
+    /// so reads there return zeros. Chunks only partly inside are kept.
+    /// Returns the hashes that are no longer referenced by `chunks`.
+    pub fn punch_hole(&self, chunks: &mut Vec<ChunkRef>, offset: u64, length: u64) -> Vec<[u8; 32]> {
+        let end = offset + length;
+        let mut released = Vec::new();
+        for chunk in chunks.iter_mut() {
+            let chunk_end = chunk.offset + chunk.size as u64;
+            if chunk.offset >= offset && chunk_end <= end && chunk.hash != SPARSE_CHUNK_HASH {
+                released.push(chunk.hash);
+                chunk.hash = SPARSE_CHUNK_HASH;
+                chunk.tier = Tier::Hot;
+            }
+        }
+        self.allocate(chunks, offset, length);
+        released
+    }
+
+    /// Fill the parts of `[offset, offset + length)` no chunk covers with
+    /// sparse chunks, leaving existing data alone
+    pub fn allocate(&self, chunks: &mut Vec<ChunkRef>, offset: u64, length: u64) {
+        let end = offset + length;
+        let mut holes = Vec::new();
+        let mut cursor = offset;
+
+        for chunk in chunks.iter() {
+            let chunk_end = chunk.offset + chunk.size as u64;
+            if chunk_end <= offset || chunk.offset >= end {
+                continue;
+            }
+            if chunk.offset > cursor {
+                holes.push((cursor, chunk.offset));
+            }
+            cursor = cursor.max(chunk_end);
+        }
+        if cursor < end {
+            holes.push((cursor, end));
+        }
+
+        for (mut start, hole_end) in holes {
+            while start < hole_end {
+                let size = (hole_end - start).min(self.chunk_size as u64);
+                chunks.push(ChunkRef {
+                    hash: SPARSE_CHUNK_HASH,
+                    offset: start,
+                    size: size as u32,
+                    tier: Tier::Hot,
+                });
+                start += size;
+            }
+        }
+        chunks.sort_by_key(|c| c.offset);
+    }
+
+    /// Zero `[offset, offset + length)` like `punch_hole`, also rewriting the
+    /// chunks only partly inside the range with that part zeroed
+    pub fn zero_range(&self, chunks: &mut Vec<ChunkRef>, offset: u64, length: u64) -> Result<Vec<[u8; 32]>> {
+        let end = offset + length;
+        let mut released = Vec::new();
+        for chunk in chunks.iter_mut() {
+            let chunk_end = chunk.offset + chunk.size as u64;
+            let partial = chunk.offset < offset || chunk_end > end;
+            if chunk_end <= offset || chunk.offset >= end || !partial || chunk.hash == SPARSE_CHUNK_HASH {
+                continue;
+            }
+            let mut data = self.get_ref(chunk)?.as_ref().clone();
+            let zero_start = offset.saturating_sub(chunk.offset) as usize;
+            let zero_end = (end.min(chunk_end) - chunk.offset) as usize;
+            data[zero_start..zero_end].fill(0);
+            released.push(chunk.hash);
+            chunk.hash = self.store(&data)?;
+            chunk.tier = Tier::Hot;
+        }
+        released.extend(self.punch_hole(chunks, offset, length));
+        Ok(released)
+    }
+
+    /// Remove `[offset, offset + length)` from a file, moving everything
+    /// after it down by `length`. Chunks partly inside the range are split,
+    /// keeping the data outside it. Returns the hashes that are no longer
+    /// referenced by `chunks`.
+    pub fn collapse_range(&self, chunks: &mut Vec<ChunkRef>, offset: u64, length: u64) -> Result<Vec<[u8; 32]>> {
+        let end = offset + length;
+        let mut released = Vec::new();
+        let mut kept = Vec::with_capacity(chunks.len());
+        for chunk in chunks.iter() {
+            let chunk_end = chunk.offset + chunk.size as u64;
+            if chunk_end <= offset {
+                kept.push(chunk.clone());
+                continue;
+            }
+            if chunk.offset >= end {
+                kept.push(ChunkRef { offset: chunk.offset - length, ..chunk.clone() });
+                continue;
+            }
+            if chunk.offset < offset || chunk_end > end {
+                let data = self.get_ref(chunk)?;
+                if chunk.offset < offset {
+                    let head = &data[..(offset - chunk.offset) as usize];
+                    kept.push(ChunkRef { hash: self.store(head)?, offset: chunk.offset, size: head.len() as u32, tier: Tier::Hot });
+                }
+                if chunk_end > end {
+                    let tail = &data[(end - chunk.offset) as usize..];
+                    kept.push(ChunkRef { hash: self.store(tail)?, offset, size: tail.len() as u32, tier: Tier::Hot });
+                }
+            }
+            if chunk.hash != SPARSE_CHUNK_HASH {
+                released.push(chunk.hash);
+            }
+        }
+        kept.sort_by_key(|c| c.offset);
+        *chunks = kept;
+        Ok(released)
+    }
+
+    /// Apply `fallocate(2)` to a file's chunks and size (see
+    /// `fallocate_supported` for the modes). Returns the hashes that are no
+    /// longer referenced by `chunks`.
+    pub fn fallocate(&self, chunks: &mut Vec<ChunkRef>, size: &mut u64, mode: i32, offset: u64, length: u64) -> Result<Vec<[u8; 32]>> {
+        if length == 0 {
+            return Err(Error::InvalidOperation("fallocate length must be positive".to_string()));
+        }
+        let end = offset.checked_add(length)
+            .ok_or_else(|| Error::InvalidOperation("fallocate range overflows".to_string()))?;
+        match mode {
+            0 => {
+                self.allocate(chunks, offset, length);
+                *size = (*size).max(end);
+                Ok(Vec::new())
+            }
+            libc::FALLOC_FL_KEEP_SIZE => {
+                if offset < *size {
+                    self.allocate(chunks, offset, end.min(*size) - offset);
+                }
+                Ok(Vec::new())
+            }
+            FALLOC_PUNCH_HOLE => {
+                if offset >= *size {
+                    return Ok(Vec::new());
+                }
+                self.zero_range(chunks, offset, end.min(*size) - offset)
+            }
+            libc::FALLOC_FL_COLLAPSE_RANGE => {
+                if end >= *size {
+                    return Err(Error::InvalidOperation("collapsed range must end before end of file".to_string()));
+                }
+                let released = self.collapse_range(chunks, offset, length)?;
+                *size -= length;
+                Ok(released)
+            }
+            _ => Err(Error::Io(std::io::Error::from_raw_os_error(libc::EOPNOTSUPP))),
+        }
+    }
+}
+
+/// `FALLOC_FL_PUNCH_HOLE`, which must come with `FALLOC_FL_KEEP_SIZE`
+const FALLOC_PUNCH_HOLE: i32 = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+
+/// Whether `ChunkStore::fallocate` handles `mode`: plain allocation (0 or
+/// `FALLOC_FL_KEEP_SIZE`), `FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE` and
+/// `FALLOC_FL_COLLAPSE_RANGE`
+pub fn fallocate_supported(mode: i32) -> bool {
+    matches!(mode, 0 | libc::FALLOC_FL_KEEP_SIZE | FALLOC_PUNCH_HOLE | libc::FALLOC_FL_COLLAPSE_RANGE)
+}
+
+/// Record a read in the chunk file's access time. Set explicitly, since
+/// chunk directories are often on `noatime`/`relatime` filesystems.
+pub(super) fn touch_accessed(file: &File) {
+    let now = SystemTime::now();
+    let stale = file.metadata()
+        .and_then(|m| m.accessed())
+        .map(|accessed| now.duration_since(accessed).unwrap_or_default() >= ACCESS_TIME_GRANULARITY)
+        .unwrap_or(true);
+    if stale {
+        if let Err(e) = file.set_times(fs::FileTimes::new().set_accessed(now)) {
+            debug!("Failed to update chunk access time: {}", e);
+        }
+    }
+}
+
+/// The hash a chunk is stored under: `SPARSE_CHUNK_HASH` if it is all
+/// zeros, otherwise its SHA-256
+fn content_hash(data: &[u8]) -> [u8; 32] {
+    if data.iter().all(|&b| b == 0) {
+        SPARSE_CHUNK_HASH
+    } else {
+        hash_of(data)
+    }
+}
+
+/// SHA-256 of a chunk's data (its content address)
+fn hash_of(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finalize().into()
 }
 
 #[cfg(test)]
@@ -333,5 +1061,291 @@ mod tests {
 
         assert_eq!(hash1, hash2);
     }
-}
 
+    #[test]
+    fn test_read_parallel_matches_sequential() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 1024).unwrap();
+
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        let mut chunks = Vec::new();
+        store.write(&mut chunks, 0, &data).unwrap();
+        assert!(chunks.len() > 4);
+
+        for (offset, size) in [(0, data.len()), (1500, 9000), (4096, 1), (19_999, 100)] {
+            let expected = store.read(&chunks, offset as u64, size).unwrap();
+            let end = (offset + size).min(data.len());
+            assert_eq!(expected, &data[offset..end]);
+            assert_eq!(store.read_parallel(&chunks, offset as u64, size, 4).unwrap(), expected);
+        }
+        assert_eq!(ChunkStore::chunks_in_range(&chunks, 1500, 9000).len(), 10);
+    }
+
+    #[test]
+    fn test_store_many_matches_store() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 1024).unwrap()
+            .with_write_batch_size(4);
+
+        let existing = store.store(b"already here").unwrap();
+        let data: Vec<Vec<u8>> = (0..10).map(|i| format!("chunk {}", i).into_bytes()).collect();
+        let mut chunks: Vec<&[u8]> = data.iter().map(|d| d.as_slice()).collect();
+        chunks.push(b"already here");
+
+        let hashes: Vec<[u8; 32]> = store.store_many(&chunks).into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(hashes.len(), 11);
+        assert_eq!(hashes[10], existing);
+        for (hash, data) in hashes.iter().zip(&chunks) {
+            assert_eq!(*hash, hash_of(data));
+            // Read from disk, not the cache
+            assert_eq!(fs::read(store.chunk_path(hash)).unwrap(), *data);
+        }
+    }
+
+    #[test]
+    fn test_sparse_file() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 4 << 20).unwrap();
+        assert_eq!(store.store(&[0u8; 4096]).unwrap(), SPARSE_CHUNK_HASH);
+
+        // A 1 GB file with 1 MB of data in the middle
+        let file_size: u64 = 1 << 30;
+        let data_offset: u64 = 512 << 20;
+        let data: Vec<u8> = (0..1u32 << 20).map(|i| (i % 251) as u8 + 1).collect();
+        let mut chunks = Vec::new();
+        store.write(&mut chunks, data_offset, &data).unwrap();
+        store.punch_hole(&mut chunks, 0, data_offset);
+        let data_end = data_offset + data.len() as u64;
+        store.punch_hole(&mut chunks, data_end, file_size - data_end);
+
+        assert_eq!(chunks.iter().map(|c| c.size as u64).sum::<u64>(), file_size);
+        let on_disk: u64 = store.local_chunks().unwrap().iter().map(|c| c.size).sum();
+        assert_eq!(on_disk, data.len() as u64);
+
+        assert_eq!(store.read(&chunks, 0, 4096).unwrap(), vec![0u8; 4096]);
+        assert_eq!(store.read(&chunks, file_size - 4096, 4096).unwrap(), vec![0u8; 4096]);
+        let around = store.read(&chunks, data_offset - 10, 20).unwrap();
+        assert_eq!(&around[..10], &[0u8; 10]);
+        assert_eq!(&around[10..], &data[..10]);
+
+        // Punching over the data releases its chunk
+        let released = store.punch_hole(&mut chunks, data_offset, data.len() as u64);
+        assert_eq!(released, vec![hash_of(&data)]);
+        assert_eq!(store.read(&chunks, data_offset, 4096).unwrap(), vec![0u8; 4096]);
+    }
+
+    #[test]
+    fn test_read_zero_fills_missing_chunks() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 4096).unwrap();
+        let data = vec![7u8; 1000];
+        let mut chunks = Vec::new();
+        store.write(&mut chunks, 0, &data).unwrap();
+
+        // Written past the end, then extended by truncate: neither leaves refs for the gaps
+        store.write(&mut chunks, 10_000, &data).unwrap();
+        let file_size: u64 = 20_000;
+        assert_eq!(chunks.len(), 2);
+
+        let mut read = store.read(&chunks, 500, 10_000).unwrap();
+        ChunkStore::fill_to_size(&mut read, file_size, 500, 10_000);
+        assert_eq!(read.len(), 10_000);
+        assert_eq!(&read[..500], &data[..500]);
+        assert!(read[500..9_500].iter().all(|&b| b == 0));
+        assert_eq!(&read[9_500..], &data[..500]);
+
+        let mut tail = store.read(&chunks, 10_500, 20_000).unwrap();
+        ChunkStore::fill_to_size(&mut tail, file_size, 10_500, 20_000);
+        assert_eq!(tail.len(), 9_500);
+        assert_eq!(&tail[..500], &data[500..]);
+        assert!(tail[500..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_fallocate() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 4096).unwrap();
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8 + 1).collect();
+        let mut chunks = Vec::new();
+        store.write(&mut chunks, 0, &data).unwrap();
+        let mut size = data.len() as u64;
+
+        // Mode 0 extends the file with sparse chunks, using no disk
+        store.fallocate(&mut chunks, &mut size, 0, 8_000, 12_000).unwrap();
+        assert_eq!(size, 20_000);
+        assert_eq!(chunks.iter().map(|c| c.size as u64).sum::<u64>(), 20_000);
+        assert_eq!(store.local_chunks().unwrap().len(), 3);
+        assert_eq!(store.read(&chunks, 0, 10_000).unwrap(), data);
+        assert_eq!(store.read(&chunks, 10_000, 10_000).unwrap(), vec![0u8; 10_000]);
+
+        // Allocating inside the file keeps the data and the size
+        store.fallocate(&mut chunks, &mut size, libc::FALLOC_FL_KEEP_SIZE, 0, 30_000).unwrap();
+        assert_eq!(size, 20_000);
+        assert_eq!(store.read(&chunks, 0, 10_000).unwrap(), data);
+
+        // Punching zeroes the range, including the parts of chunks it cuts through
+        let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+        store.fallocate(&mut chunks, &mut size, mode, 1_000, 8_000).unwrap();
+        assert_eq!(size, 20_000);
+        let mut expected = data.clone();
+        expected[1_000..9_000].fill(0);
+        assert_eq!(store.read(&chunks, 0, 10_000).unwrap(), expected);
+        // The chunk entirely inside the hole is sparse
+        assert!(chunks.iter().any(|c| c.offset == 4096 && c.hash == SPARSE_CHUNK_HASH));
+
+        // Collapsing removes the range and moves the rest down
+        store.fallocate(&mut chunks, &mut size, libc::FALLOC_FL_COLLAPSE_RANGE, 500, 9_000).unwrap();
+        assert_eq!(size, 11_000);
+        let mut expected: Vec<u8> = expected[..500].iter().chain(&expected[9_500..]).copied().collect();
+        expected.resize(11_000, 0);
+        assert_eq!(store.read(&chunks, 0, 11_000).unwrap(), expected);
+        assert_eq!(chunks.iter().map(|c| c.size as u64).sum::<u64>(), 11_000);
+
+        // Collapsing up to the end of the file is refused, as are other modes
+        assert!(store.fallocate(&mut chunks, &mut size, libc::FALLOC_FL_COLLAPSE_RANGE, 1_000, 10_000).is_err());
+        assert!(store.fallocate(&mut chunks, &mut size, 0, 0, 0).is_err());
+        assert!(!fallocate_supported(libc::FALLOC_FL_ZERO_RANGE));
+    }
+
+    #[tokio::test]
+    async fn test_store_async_roundtrip() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 1024).unwrap();
+
+        let hash = store.store_async(b"async chunk").await.unwrap();
+        assert_eq!(hash, store.store(b"async chunk").unwrap());
+        assert_eq!(store.get(&hash).unwrap(), b"async chunk");
+    }
+
+    /// Throughput of 10 000 4 KB chunk writes:
+    /// `cargo test --release --features io-uring -- --ignored --nocapture bench_small_chunk_writes`
+    #[test]
+    #[ignore]
+    fn bench_small_chunk_writes() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 4096).unwrap()
+            .with_write_batch_size(DEFAULT_WRITE_BATCH_SIZE);
+        let data: Vec<Vec<u8>> = (0..10_000u32)
+            .map(|i| {
+                let mut chunk = vec![0u8; 4096];
+                chunk[..4].copy_from_slice(&i.to_le_bytes());
+                chunk
+            })
+            .collect();
+
+        let start = std::time::Instant::now();
+        for batch in data.chunks(DEFAULT_WRITE_BATCH_SIZE) {
+            let chunks: Vec<&[u8]> = batch.iter().map(|d| d.as_slice()).collect();
+            for result in store.store_many(&chunks) {
+                result.unwrap();
+            }
+        }
+        let elapsed = start.elapsed();
+        println!("{} chunk writes in {:?} ({:.0} writes/sec, io-uring: {})",
+            data.len(), elapsed, data.len() as f64 / elapsed.as_secs_f64(), cfg!(feature = "io-uring"));
+    }
+
+    #[test]
+    fn test_read_through_fetches_each_chunk_once() {
+        let dir = tempdir().unwrap();
+        let leader = ChunkStore::new(dir.path().join("leader"), 1024).unwrap();
+        let data: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        let mut chunks = Vec::new();
+        leader.write(&mut chunks, 0, &data).unwrap();
+
+        let client = ChunkStore::new(dir.path().join("client"), 1024).unwrap();
+        let mut fetched = 0;
+        for _ in 0..3 {
+            let read = client.read_through(&chunks, 100, 2000, |hash| {
+                fetched += 1;
+                leader.get(hash)
+            });
+            assert_eq!(read.unwrap(), data[100..2100]);
+        }
+        assert_eq!(fetched, 3);
+        assert_eq!(client.stats().cache_hits.load(Ordering::Relaxed), 6);
+        assert!(client.local_chunks().unwrap().is_empty());
+
+        // A chunk that doesn't match its hash is not cached
+        let client = ChunkStore::new(dir.path().join("client2"), 1024).unwrap();
+        assert!(client.read_through(&chunks, 2500, 100, |_| Ok(vec![0xff; 952])).is_err());
+        assert!(client.read_cache().write().unwrap().is_empty());
+    }
+
+    /// Reading a 100 MB file 100 times in 1 MB reads (as FUSE issues them)
+    /// with a cold (disabled) vs. warm read cache:
+    /// `cargo test --release -- --ignored --nocapture bench_read_cache`
+    #[test]
+    #[ignore]
+    fn bench_read_cache() {
+        let dir = tempdir().unwrap();
+        let data: Vec<u8> = (0..100 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+        let mut chunks = Vec::new();
+        ChunkStore::new(dir.path().to_path_buf(), 4 * 1024 * 1024).unwrap()
+            .write(&mut chunks, 0, &data).unwrap();
+
+        let mut timings = Vec::new();
+        for cache_mb in [0, 512] {
+            let store = ChunkStore::new(dir.path().to_path_buf(), 4 * 1024 * 1024).unwrap()
+                .with_read_cache(Arc::new(RwLock::new(ReadCache::with_capacity_mb(cache_mb))));
+            let read_file = || {
+                for offset in (0..data.len()).step_by(1024 * 1024) {
+                    assert_eq!(store.read(&chunks, offset as u64, 1024 * 1024).unwrap().len(), 1024 * 1024);
+                }
+            };
+            // Warm up: fills the cache (if any) and the page cache
+            read_file();
+            let start = std::time::Instant::now();
+            for _ in 0..100 {
+                read_file();
+            }
+            timings.push(start.elapsed());
+        }
+        println!("100 reads of a 100 MB file: {:?} cold, {:?} from the read cache ({:.1}x)",
+            timings[0], timings[1], timings[0].as_secs_f64() / timings[1].as_secs_f64());
+    }
+
+    /// Reading one 4 MB chunk 10 000 times, opening the file each time vs. from the mmap cache:
+    /// `cargo test --release --features mmap-cache -- --ignored --nocapture bench_hot_chunk_reads`
+    #[cfg(feature = "mmap-cache")]
+    #[test]
+    #[ignore]
+    fn bench_hot_chunk_reads() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 4 * 1024 * 1024).unwrap()
+            .with_mmap_cache(256);
+        let data: Vec<u8> = (0..4 * 1024 * 1024u32).map(|i| (i % 251) as u8).collect();
+        let hash = store.store(&data).unwrap();
+
+        let start = std::time::Instant::now();
+        for _ in 0..10_000 {
+            assert_eq!(store.read_local(&hash).unwrap().len(), data.len());
+        }
+        let uncached = start.elapsed();
+
+        let start = std::time::Instant::now();
+        for _ in 0..10_000 {
+            assert_eq!(store.get(&hash).unwrap().len(), data.len());
+        }
+        let mapped = start.elapsed();
+
+        println!("10 000 reads of a 4 MB chunk: {:?} opening the file, {:?} from the mmap cache ({:.1}x)",
+            uncached, mapped, uncached.as_secs_f64() / mapped.as_secs_f64());
+    }
+
+    #[cfg(feature = "mmap-cache")]
+    #[test]
+    fn test_mmap_cache_invalidated_on_delete() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().to_path_buf(), 1024).unwrap()
+            .with_mmap_cache(1);
+
+        let hash = store.store(b"mapped chunk").unwrap();
+        assert_eq!(store.get(&hash).unwrap(), b"mapped chunk");
+        assert_eq!(store.mmap_cache.as_ref().unwrap().len(), 1);
+
+        store.delete(&hash).unwrap();
+        assert!(store.mmap_cache.as_ref().unwrap().is_empty());
+        assert!(store.get(&hash).is_err());
+    }
+}
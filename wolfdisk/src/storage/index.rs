@@ -3,13 +3,17 @@
 use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{BufReader, BufWriter};
+use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info};
+use uuid::Uuid;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+use super::acl::{self, Acl};
+use super::tiered::Tier;
 
 /// Reference to a chunk in storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +26,10 @@ pub struct ChunkRef {
 
     /// Size of this chunk in bytes
     pub size: u32,
+
+    /// Cold once the chunk has been evicted to the cold tier
+    #[serde(default)]
+    pub tier: Tier,
 }
 
 /// File metadata entry
@@ -57,9 +65,110 @@ pub struct FileEntry {
     /// Symlink target path (if this is a symlink)
     #[serde(default)]
     pub symlink_target: Option<String>,
+
+    /// Extended attributes (e.g. `security.selinux`, `user.*`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub xattrs: HashMap<String, Vec<u8>>,
+
+    /// S3 version ID of this entry (None for the "null" version)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<Uuid>,
+
+    /// Noncurrent S3 versions, oldest first; the nil UUID is the "null" version
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<(Uuid, FileEntry)>,
+
+    /// Paths naming this file (hard links), reported as `nlink`
+    #[serde(default = "default_hard_link_count")]
+    pub hard_link_count: u32,
+
+    /// Shared by every path of a hard-linked file; None while it has one path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link_id: Option<Uuid>,
+}
+
+fn default_hard_link_count() -> u32 {
+    1
+}
+
+impl FileEntry {
+    /// Chunks freed by removing this entry with `FileIndex::unlink`: none
+    /// while another hard link still uses them
+    pub fn released_chunks(&self) -> &[ChunkRef] {
+        if self.hard_link_count == 0 { &self.chunks } else { &[] }
+    }
+
+    /// Hashes of every chunk referenced by this entry, including its
+    /// noncurrent versions
+    pub fn all_chunk_hashes(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        self.chunks.iter()
+            .chain(self.versions.iter().flat_map(|(_, v)| v.chunks.iter()))
+            .map(|c| c.hash)
+    }
+
+    /// Check whether an xattr may be set under `XATTR_CREATE` / `XATTR_REPLACE`
+    /// semantics, returning the errno to report if not
+    pub fn check_xattr_flags(&self, name: &str, flags: i32) -> std::result::Result<(), libc::c_int> {
+        let exists = self.xattrs.contains_key(name);
+        if flags & libc::XATTR_CREATE != 0 && exists {
+            return Err(libc::EEXIST);
+        }
+        if flags & libc::XATTR_REPLACE != 0 && !exists {
+            return Err(libc::ENODATA);
+        }
+        Ok(())
+    }
+
+    /// Set (`Some`) or remove (`None`) an extended attribute.
+    /// Setting the stored access ACL also sets the permission bits from it.
+    pub fn update_xattr(&mut self, name: &str, value: Option<Vec<u8>>, flags: i32) -> std::result::Result<(), libc::c_int> {
+        match value {
+            Some(value) => {
+                self.check_xattr_flags(name, flags)?;
+                if name == acl::STORED_ACCESS_ACL {
+                    let acl = Acl::decode(&value).ok_or(libc::EINVAL)?;
+                    self.permissions = (self.permissions & !0o777) | acl.mode();
+                    if acl.is_minimal() {
+                        self.xattrs.remove(name);
+                        return Ok(());
+                    }
+                }
+                self.xattrs.insert(name.to_string(), value);
+            }
+            None => {
+                if self.xattrs.remove(name).is_none() {
+                    return Err(libc::ENODATA);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The access ACL, if it says more than the permission bits
+    pub fn access_acl(&self) -> Option<Acl> {
+        self.xattrs.get(acl::STORED_ACCESS_ACL).and_then(|value| Acl::decode(value))
+    }
+
+    /// A directory's default ACL
+    pub fn default_acl(&self) -> Option<Acl> {
+        self.xattrs.get(acl::STORED_DEFAULT_ACL).and_then(|value| Acl::decode(value))
+    }
+
+    /// `chmod`: set the mode, keeping the access ACL in step with it
+    pub fn set_mode(&mut self, mode: u32) {
+        self.permissions = mode;
+        if let Some(mut acl) = self.access_acl() {
+            acl.set_mode(mode);
+            self.xattrs.insert(acl::STORED_ACCESS_ACL.to_string(), acl.encode());
+        }
+    }
 }
 
 /// File metadata index
+///
+/// Every path of a hard-linked file has its own entry, sharing the file's
+/// `link_id`. Changes made through `get_mut` or `insert` are copied to the
+/// other paths, so the entries always agree on content and metadata.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileIndex {
     /// Path to entry mapping
@@ -67,6 +176,37 @@ pub struct FileIndex {
 
     /// Index version for compatibility
     version: u32,
+
+    /// Paths of each hard-linked file, rebuilt from `link_id` on load
+    #[serde(skip)]
+    links: HashMap<Uuid, Vec<PathBuf>>,
+}
+
+/// A mutable index entry. Changes to a hard-linked file reach its other
+/// paths when this is dropped.
+pub struct EntryMut<'a> {
+    index: &'a mut FileIndex,
+    path: PathBuf,
+}
+
+impl Deref for EntryMut<'_> {
+    type Target = FileEntry;
+
+    fn deref(&self) -> &FileEntry {
+        &self.index.entries[&self.path]
+    }
+}
+
+impl DerefMut for EntryMut<'_> {
+    fn deref_mut(&mut self) -> &mut FileEntry {
+        self.index.entries.get_mut(&self.path).expect("entry exists while borrowed")
+    }
+}
+
+impl Drop for EntryMut<'_> {
+    fn drop(&mut self) {
+        self.index.sync_links(&self.path);
+    }
 }
 
 const INDEX_VERSION: u32 = 1;
@@ -78,6 +218,7 @@ impl FileIndex {
         Self {
             entries: HashMap::new(),
             version: INDEX_VERSION,
+            links: HashMap::new(),
         }
     }
 
@@ -89,7 +230,8 @@ impl FileIndex {
             info!("Loading file index from {:?}", index_path);
             let file = File::open(&index_path)?;
             let reader = BufReader::new(file);
-            let index: FileIndex = serde_json::from_reader(reader)?;
+            let mut index: FileIndex = serde_json::from_reader(reader)?;
+            index.rebuild_links();
             
             if index.version != INDEX_VERSION {
                 info!("Index version mismatch, creating new index");
@@ -123,8 +265,11 @@ impl FileIndex {
     }
 
     /// Get a mutable entry by path
-    pub fn get_mut(&mut self, path: &Path) -> Option<&mut FileEntry> {
-        self.entries.get_mut(path)
+    pub fn get_mut(&mut self, path: &Path) -> Option<EntryMut<'_>> {
+        if !self.entries.contains_key(path) {
+            return None;
+        }
+        Some(EntryMut { index: self, path: path.to_path_buf() })
     }
 
     /// Check if path exists
@@ -132,14 +277,180 @@ impl FileIndex {
         self.entries.contains_key(path)
     }
 
-    /// Insert or update an entry
-    pub fn insert(&mut self, path: PathBuf, entry: FileEntry) -> Option<FileEntry> {
-        self.entries.insert(path, entry)
+    /// Insert or update an entry. Updating one path of a hard-linked file
+    /// updates all of them.
+    pub fn insert(&mut self, path: PathBuf, mut entry: FileEntry) -> Option<FileEntry> {
+        if entry.link_id.is_none() {
+            match self.entries.get(&path).filter(|old| old.link_id.is_some()) {
+                Some(old) => {
+                    entry.link_id = old.link_id;
+                    entry.hard_link_count = old.hard_link_count;
+                }
+                None => entry.hard_link_count = 1,
+            }
+        }
+        let link_id = entry.link_id;
+        if let Some(id) = link_id {
+            let paths = self.links.entry(id).or_default();
+            if !paths.contains(&path) {
+                paths.push(path.clone());
+            }
+        }
+
+        let old = self.entries.insert(path.clone(), entry);
+        if let Some(old_id) = old.as_ref().and_then(|old| old.link_id) {
+            if Some(old_id) != link_id {
+                self.forget_link(old_id, &path);
+            }
+        }
+        self.sync_links(&path);
+        old
     }
 
-    /// Remove an entry
+    /// Remove an entry. Other paths of a hard-linked file keep their link
+    /// count, as when the entry is moved to a new path; use `unlink` to
+    /// delete a name.
     pub fn remove(&mut self, path: &Path) -> Option<FileEntry> {
-        self.entries.remove(path)
+        let entry = self.entries.remove(path)?;
+        if let Some(id) = entry.link_id {
+            self.forget_link(id, path);
+        }
+        Some(entry)
+    }
+
+    /// Remove one name of a file. The returned entry's `hard_link_count` is
+    /// the number of names left: only at 0 may its chunks be freed.
+    pub fn unlink(&mut self, path: &Path) -> Option<FileEntry> {
+        let mut entry = self.remove(path)?;
+        let remaining = entry.link_id
+            .and_then(|id| self.links.get(&id))
+            .cloned()
+            .unwrap_or_default();
+        entry.hard_link_count = remaining.len() as u32;
+        if remaining.len() == 1 {
+            self.links.remove(&entry.link_id.unwrap());
+        }
+        for other in &remaining {
+            if let Some(e) = self.entries.get_mut(other) {
+                e.hard_link_count = remaining.len() as u32;
+                if remaining.len() == 1 {
+                    e.link_id = None;
+                }
+            }
+        }
+        Some(entry)
+    }
+
+    /// Add `dst` as another name for the file at `src`. Returns the new entry.
+    pub fn link(&mut self, src: &Path, dst: PathBuf) -> Result<FileEntry> {
+        if self.entries.contains_key(&dst) {
+            return Err(Error::InvalidOperation(format!("{} already exists", dst.display())));
+        }
+        let source = self.entries.get(src).ok_or_else(|| Error::FileNotFound(src.display().to_string()))?;
+        if source.is_dir {
+            return Err(Error::InvalidOperation(format!("{} is a directory", src.display())));
+        }
+        let id = source.link_id.unwrap_or_else(Uuid::new_v4);
+        let mut entry = source.clone();
+        entry.version_id = None;
+        entry.versions = Vec::new();
+
+        let paths = self.links.entry(id).or_insert_with(|| vec![src.to_path_buf()]);
+        paths.push(dst.clone());
+        let count = paths.len() as u32;
+        for path in paths.iter() {
+            if let Some(e) = self.entries.get_mut(path) {
+                e.link_id = Some(id);
+                e.hard_link_count = count;
+            }
+        }
+        entry.link_id = Some(id);
+        entry.hard_link_count = count;
+        self.entries.insert(dst, entry.clone());
+        Ok(entry)
+    }
+
+    /// Every path of the file at `path`, `path` included
+    pub fn hard_links(&self, path: &Path) -> Vec<PathBuf> {
+        self.entries.get(path)
+            .and_then(|e| e.link_id)
+            .and_then(|id| self.links.get(&id))
+            .cloned()
+            .unwrap_or_else(|| vec![path.to_path_buf()])
+    }
+
+    /// Problems with hard links: paths of one file that disagree on their
+    /// chunks or size, or whose link count is wrong
+    pub fn check_hard_links(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (id, paths) in &self.links {
+            let entries: Vec<(&PathBuf, &FileEntry)> = paths.iter()
+                .filter_map(|p| self.entries.get(p).map(|e| (p, e)))
+                .collect();
+            let Some(&(first_path, first)) = entries.first() else { continue };
+            for &(path, entry) in &entries {
+                if entry.hard_link_count as usize != paths.len() {
+                    problems.push(format!("{}: link count {} but {} paths share link {}",
+                        path.display(), entry.hard_link_count, paths.len(), id));
+                }
+                if path == first_path {
+                    continue;
+                }
+                let same_chunks = entry.chunks.len() == first.chunks.len()
+                    && entry.chunks.iter().zip(&first.chunks)
+                        .all(|(a, b)| a.hash == b.hash && a.offset == b.offset && a.size == b.size);
+                if !same_chunks || entry.size != first.size {
+                    problems.push(format!("{}: content differs from its hard link {}",
+                        path.display(), first_path.display()));
+                }
+            }
+        }
+        for (path, entry) in &self.entries {
+            if entry.link_id.is_none() && entry.hard_link_count != 1 && !entry.is_dir {
+                problems.push(format!("{}: link count {} without a link", path.display(), entry.hard_link_count));
+            }
+        }
+        problems.sort();
+        problems
+    }
+
+    /// Copy a hard-linked entry to the file's other paths. S3 versions
+    /// belong to each path and are left alone.
+    fn sync_links(&mut self, path: &Path) {
+        let Some(entry) = self.entries.get(path) else { return };
+        let Some(paths) = entry.link_id.and_then(|id| self.links.get(&id)) else { return };
+        let mut shared = entry.clone();
+        shared.versions = Vec::new();
+        for other in paths.iter().filter(|p| p.as_path() != path) {
+            if let Some(e) = self.entries.get_mut(other) {
+                let versions = std::mem::take(&mut e.versions);
+                let version_id = e.version_id;
+                *e = shared.clone();
+                e.versions = versions;
+                e.version_id = version_id;
+            }
+        }
+    }
+
+    fn forget_link(&mut self, id: Uuid, path: &Path) {
+        if let Some(paths) = self.links.get_mut(&id) {
+            paths.retain(|p| p != path);
+            if paths.is_empty() {
+                self.links.remove(&id);
+            }
+        }
+    }
+
+    fn rebuild_links(&mut self) {
+        self.links.clear();
+        for (path, entry) in &self.entries {
+            if let Some(id) = entry.link_id {
+                self.links.entry(id).or_default().push(path.clone());
+            }
+        }
+        for paths in self.links.values_mut() {
+            paths.sort();
+        }
     }
 
     /// Get all paths
@@ -175,3 +486,163 @@ impl From<serde_json::Error> for crate::error::Error {
         crate::error::Error::Storage(format!("JSON error: {}", e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use crate::storage::{ChunkStore, InodeTable};
+
+    fn file_entry() -> FileEntry {
+        let now = SystemTime::now();
+        FileEntry {
+            size: 0,
+            is_dir: false,
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+            chunks: Vec::new(),
+            symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
+        }
+    }
+
+    #[test]
+    fn test_xattrs_persist_across_save_load() {
+        let dir = tempdir().unwrap();
+        let mut index = FileIndex::new();
+
+        let mut entry = file_entry();
+        entry.update_xattr("security.selinux", Some(b"system_u:object_r:httpd_sys_content_t:s0\0".to_vec()), 0).unwrap();
+        entry.update_xattr("user.mime_type", Some(b"text/plain".to_vec()), 0).unwrap();
+        index.insert(PathBuf::from("www/index.html"), entry);
+        index.insert(PathBuf::from("plain.txt"), file_entry());
+        index.save(dir.path()).unwrap();
+
+        let loaded = FileIndex::load_or_create(dir.path()).unwrap();
+        let xattrs = &loaded.get(Path::new("www/index.html")).unwrap().xattrs;
+        assert_eq!(xattrs.len(), 2);
+        assert_eq!(xattrs["security.selinux"], b"system_u:object_r:httpd_sys_content_t:s0\0");
+        assert_eq!(xattrs["user.mime_type"], b"text/plain");
+        assert!(loaded.get(Path::new("plain.txt")).unwrap().xattrs.is_empty());
+    }
+
+    #[test]
+    fn test_xattr_flags() {
+        let mut entry = file_entry();
+        assert_eq!(entry.update_xattr("user.a", Some(b"1".to_vec()), libc::XATTR_REPLACE), Err(libc::ENODATA));
+        entry.update_xattr("user.a", Some(b"1".to_vec()), libc::XATTR_CREATE).unwrap();
+        assert_eq!(entry.update_xattr("user.a", Some(b"2".to_vec()), libc::XATTR_CREATE), Err(libc::EEXIST));
+        entry.update_xattr("user.a", Some(b"2".to_vec()), libc::XATTR_REPLACE).unwrap();
+        assert_eq!(entry.xattrs["user.a"], b"2");
+
+        entry.update_xattr("user.a", None, 0).unwrap();
+        assert_eq!(entry.update_xattr("user.a", None, 0), Err(libc::ENODATA));
+    }
+
+    #[test]
+    fn test_access_acl_tracks_mode() {
+        use crate::storage::acl::{AclEntry, AclTag};
+
+        let mut entry = file_entry();
+        entry.permissions = libc::S_IFREG | 0o644;
+        let acl = Acl::new(vec![
+            AclEntry { tag: AclTag::UserObj, id: 0, perms: 6 },
+            AclEntry { tag: AclTag::User, id: 1000, perms: 6 },
+            AclEntry { tag: AclTag::GroupObj, id: 0, perms: 4 },
+            AclEntry { tag: AclTag::Mask, id: 0, perms: 6 },
+            AclEntry { tag: AclTag::Other, id: 0, perms: 4 },
+        ]).unwrap();
+        entry.update_xattr(acl::STORED_ACCESS_ACL, Some(acl.encode()), 0).unwrap();
+        assert_eq!(entry.permissions, libc::S_IFREG | 0o664);
+
+        // chmod moves the mask
+        entry.set_mode(libc::S_IFREG | 0o600);
+        let mask = entry.access_acl().unwrap().entries.into_iter().find(|e| e.tag == AclTag::Mask).unwrap();
+        assert_eq!(mask.perms, 0);
+
+        // `setfacl -b`: only the mode is left
+        entry.update_xattr(acl::STORED_ACCESS_ACL, Some(Acl::from_mode(0o640).encode()), 0).unwrap();
+        assert_eq!(entry.permissions, libc::S_IFREG | 0o640);
+        assert!(entry.access_acl().is_none());
+    }
+
+    #[test]
+    fn test_hard_link_survives_unlink() {
+        let dir = tempdir().unwrap();
+        let store = ChunkStore::new(dir.path().join("chunks"), 1024).unwrap();
+        let mut index = FileIndex::new();
+
+        let mut entry = file_entry();
+        entry.size = store.write(&mut entry.chunks, 0, &[7u8; 3000]).unwrap() as u64;
+        index.insert(PathBuf::from("a.txt"), entry);
+        let linked = index.link(Path::new("a.txt"), PathBuf::from("docs/b.txt")).unwrap();
+        assert_eq!(linked.hard_link_count, 2);
+        assert_eq!(index.get(Path::new("a.txt")).unwrap().hard_link_count, 2);
+        assert!(index.link(Path::new("a.txt"), PathBuf::from("docs/b.txt")).is_err());
+
+        // A write through one name shows through the other
+        {
+            let mut entry = index.get_mut(Path::new("docs/b.txt")).unwrap();
+            let written = store.write(&mut entry.chunks, 3000, b"tail").unwrap();
+            entry.size += written as u64;
+        }
+        assert_eq!(index.get(Path::new("a.txt")).unwrap().size, 3004);
+        assert!(index.check_hard_links().is_empty());
+
+        // Removing one name releases nothing, so the other still reads
+        let removed = index.unlink(Path::new("a.txt")).unwrap();
+        assert_eq!(removed.hard_link_count, 1);
+        assert!(removed.released_chunks().is_empty());
+        let survivor = index.get(Path::new("docs/b.txt")).unwrap();
+        assert_eq!((survivor.hard_link_count, survivor.link_id), (1, None));
+        let chunks = survivor.chunks.clone();
+        assert!(chunks.iter().all(|c| store.exists(&c.hash)));
+        assert_eq!(store.read(&chunks, 2998, 6).unwrap(), b"\x07\x07tail");
+
+        // The last name releases every chunk, which can then be deleted
+        let last = index.unlink(Path::new("docs/b.txt")).unwrap();
+        assert_eq!(last.hard_link_count, 0);
+        assert_eq!(last.released_chunks().len(), chunks.len());
+        for chunk in last.released_chunks() {
+            store.delete(&chunk.hash).unwrap();
+        }
+        assert!(chunks.iter().all(|c| !store.exists(&c.hash)));
+        assert!(index.is_empty());
+        assert!(index.check_hard_links().is_empty());
+    }
+
+    #[test]
+    fn test_hard_links_persist_across_save_load() {
+        let dir = tempdir().unwrap();
+        let mut index = FileIndex::new();
+        index.insert(PathBuf::from("a"), file_entry());
+        index.link(Path::new("a"), PathBuf::from("b")).unwrap();
+        index.link(Path::new("b"), PathBuf::from("c")).unwrap();
+        index.save(dir.path()).unwrap();
+
+        let mut loaded = FileIndex::load_or_create(dir.path()).unwrap();
+        let mut paths = loaded.hard_links(Path::new("c"));
+        paths.sort();
+        assert_eq!(paths, [PathBuf::from("a"), PathBuf::from("b"), PathBuf::from("c")]);
+        let (inodes, _) = InodeTable::from_index(&loaded);
+        assert_eq!(inodes.get_inode(&PathBuf::from("a")), inodes.get_inode(&PathBuf::from("c")));
+
+        // Moving a name keeps it in the group
+        let moved = loaded.remove(Path::new("b")).unwrap();
+        loaded.insert(PathBuf::from("d"), moved);
+        assert_eq!(loaded.hard_links(Path::new("d")).len(), 3);
+        assert!(loaded.check_hard_links().is_empty());
+
+        // A path whose chunks drifted from its links is reported
+        loaded.entries.get_mut(Path::new("c")).unwrap().size = 10;
+        assert_eq!(loaded.check_hard_links().len(), 1);
+    }
+}
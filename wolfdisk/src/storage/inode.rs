@@ -1,5 +1,6 @@
 //! Inode table for FUSE
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::path::PathBuf;
 
@@ -13,6 +14,9 @@ pub struct InodeTable {
 
     /// Path to inode mapping
     path_to_inode: HashMap<PathBuf, u64>,
+
+    /// Further paths of hard-linked inodes, beyond the one in `inode_to_path`
+    link_targets: HashMap<u64, Vec<PathBuf>>,
 }
 
 /// Root inode number
@@ -24,6 +28,7 @@ impl InodeTable {
         let mut table = Self {
             inode_to_path: HashMap::new(),
             path_to_inode: HashMap::new(),
+            link_targets: HashMap::new(),
         };
 
         // Add root directory
@@ -37,21 +42,60 @@ impl InodeTable {
         let mut table = Self::new();
         let mut max_inode = ROOT_INODE;
 
-        // Assign inodes to all index entries
-        for path in index.paths() {
+        // Assign inodes to all index entries; the paths of a hard-linked
+        // file share one
+        let mut linked = HashMap::new();
+        for (path, entry) in index.iter() {
+            if let Some(&inode) = entry.link_id.as_ref().and_then(|id| linked.get(id)) {
+                table.insert_link(inode, path.clone());
+                continue;
+            }
             max_inode += 1;
             table.insert(max_inode, path.clone());
+            if let Some(id) = entry.link_id {
+                linked.insert(id, max_inode);
+            }
         }
 
         (table, max_inode)
     }
 
-    /// Insert a mapping
+    /// Insert a mapping. An inode that still has another path gets this
+    /// one as a hard link.
     pub fn insert(&mut self, inode: u64, path: PathBuf) {
+        if let Some(current) = self.inode_to_path.get(&inode) {
+            if *current != path && self.path_to_inode.get(current) == Some(&inode) {
+                self.insert_link(inode, path);
+                return;
+            }
+        }
         self.inode_to_path.insert(inode, path.clone());
         self.path_to_inode.insert(path, inode);
     }
 
+    /// Add another path (a hard link) for an existing inode
+    pub fn insert_link(&mut self, inode: u64, path: PathBuf) {
+        if self.path_to_inode.get(&path) == Some(&inode) {
+            return;
+        }
+        if let Entry::Vacant(slot) = self.inode_to_path.entry(inode) {
+            slot.insert(path.clone());
+            self.path_to_inode.insert(path, inode);
+            return;
+        }
+        self.path_to_inode.insert(path.clone(), inode);
+        self.link_targets.entry(inode).or_default().push(path);
+    }
+
+    /// Every path of an inode, the one `get_path` returns first
+    pub fn paths(&self, inode: u64) -> Vec<PathBuf> {
+        self.inode_to_path.get(&inode)
+            .into_iter()
+            .chain(self.link_targets.get(&inode).into_iter().flatten())
+            .cloned()
+            .collect()
+    }
+
     /// Get path by inode
     pub fn get_path(&self, inode: u64) -> Option<&PathBuf> {
         self.inode_to_path.get(&inode)
@@ -62,18 +106,30 @@ impl InodeTable {
         self.path_to_inode.get(path).copied()
     }
 
-    /// Remove by path
+    /// Remove by path. A hard-linked inode keeps its other paths.
     pub fn remove_path(&mut self, path: &PathBuf) -> Option<u64> {
-        if let Some(inode) = self.path_to_inode.remove(path) {
-            self.inode_to_path.remove(&inode);
-            Some(inode)
+        let inode = self.path_to_inode.remove(path)?;
+        if let Some(links) = self.link_targets.get_mut(&inode) {
+            links.retain(|p| p != path);
+            if self.inode_to_path.get(&inode) == Some(path) {
+                // Another name takes over as the inode's path
+                let next = links.remove(0);
+                self.inode_to_path.insert(inode, next);
+            }
+            if links.is_empty() {
+                self.link_targets.remove(&inode);
+            }
         } else {
-            None
+            self.inode_to_path.remove(&inode);
         }
+        Some(inode)
     }
 
-    /// Remove by inode
+    /// Remove by inode, with all its paths
     pub fn remove_inode(&mut self, inode: u64) -> Option<PathBuf> {
+        for link in self.link_targets.remove(&inode).unwrap_or_default() {
+            self.path_to_inode.remove(&link);
+        }
         if let Some(path) = self.inode_to_path.remove(&inode) {
             self.path_to_inode.remove(&path);
             Some(path)
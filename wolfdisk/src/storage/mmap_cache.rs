@@ -0,0 +1,178 @@
+//! Memory-mapped views of hot chunk files (`mmap-cache` feature)
+//!
+//! Chunks are content-addressed and never rewritten in place, so a mapping
+//! stays valid until the chunk is deleted. Reads that hit the cache copy
+//! straight out of the page cache without opening the file again.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use memmap2::Mmap;
+
+use crate::error::Result;
+use super::chunks::touch_accessed;
+
+/// LRU set of mapped chunk files, bounded by their total size
+pub struct MmapCache {
+    inner: Mutex<Inner>,
+    capacity_bytes: u64,
+}
+
+struct Inner {
+    maps: HashMap<[u8; 32], Mapped>,
+    /// Access tick -> hash, oldest first
+    order: BTreeMap<u64, [u8; 32]>,
+    used_bytes: u64,
+    tick: u64,
+}
+
+struct Mapped {
+    map: Arc<Mmap>,
+    /// Kept open so reads can still record access times for the cold tier
+    file: Arc<File>,
+    tick: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, hash: &[u8; 32]) -> Option<(Arc<Mmap>, Arc<File>)> {
+        self.tick += 1;
+        let tick = self.tick;
+        let mapped = self.maps.get_mut(hash)?;
+        self.order.remove(&mapped.tick);
+        self.order.insert(tick, *hash);
+        mapped.tick = tick;
+        Some((mapped.map.clone(), mapped.file.clone()))
+    }
+
+    fn remove(&mut self, hash: &[u8; 32]) {
+        if let Some(mapped) = self.maps.remove(hash) {
+            self.order.remove(&mapped.tick);
+            self.used_bytes -= mapped.map.len() as u64;
+        }
+    }
+}
+
+impl MmapCache {
+    /// Create a cache holding up to `capacity_bytes` of mapped chunks
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                maps: HashMap::new(),
+                order: BTreeMap::new(),
+                used_bytes: 0,
+                tick: 0,
+            }),
+            capacity_bytes,
+        }
+    }
+
+    /// Copy a cached chunk's bytes, if it is mapped.
+    /// `track_access` records the read in the file's access time.
+    pub fn get(&self, hash: &[u8; 32], track_access: bool) -> Option<Vec<u8>> {
+        // Copy outside the lock; the Arc keeps the mapping alive if it is evicted meanwhile
+        let (map, file) = self.inner.lock().ok()?.touch(hash)?;
+        if track_access {
+            touch_accessed(&file);
+        }
+        Some(map.to_vec())
+    }
+
+    /// Map the chunk file at `path` and return its bytes.
+    /// Returns None when the file is empty or too large to cache.
+    pub fn load(&self, hash: &[u8; 32], path: &Path, track_access: bool) -> Result<Option<Vec<u8>>> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len == 0 || len > self.capacity_bytes {
+            return Ok(None);
+        }
+
+        // SAFETY: chunk files are written once under their content hash and
+        // only ever removed, never truncated or rewritten, so the mapped bytes
+        // cannot change underneath us. Unlinking the file keeps the mapping valid.
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+        let data = map.to_vec();
+        if track_access {
+            touch_accessed(&file);
+        }
+
+        let Ok(mut inner) = self.inner.lock() else {
+            return Ok(Some(data));
+        };
+        if inner.maps.contains_key(hash) {
+            inner.touch(hash);
+            return Ok(Some(data));
+        }
+        while inner.used_bytes + len > self.capacity_bytes {
+            let Some((_, oldest)) = inner.order.pop_first() else { break };
+            if let Some(mapped) = inner.maps.remove(&oldest) {
+                inner.used_bytes -= mapped.map.len() as u64;
+            }
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.order.insert(tick, *hash);
+        inner.maps.insert(*hash, Mapped { map, file: Arc::new(file), tick });
+        inner.used_bytes += len;
+        Ok(Some(data))
+    }
+
+    /// Drop a chunk's mapping (its file is being deleted)
+    pub fn remove(&self, hash: &[u8; 32]) {
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.remove(hash);
+        }
+    }
+
+    /// Number of mapped chunks
+    pub fn len(&self) -> usize {
+        self.inner.lock().map(|i| i.maps.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total size of the mapped chunks in bytes
+    pub fn used_bytes(&self) -> u64 {
+        self.inner.lock().map(|i| i.used_bytes).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lru_eviction_by_size() {
+        let dir = tempdir().unwrap();
+        let cache = MmapCache::new(10);
+        let write = |name: &str, len: usize| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, vec![name.as_bytes()[0]; len]).unwrap();
+            path
+        };
+        let (a, b, c) = (write("a", 4), write("b", 4), write("c", 4));
+
+        assert_eq!(cache.load(&[1; 32], &a, false).unwrap().unwrap(), vec![b'a'; 4]);
+        cache.load(&[2; 32], &b, false).unwrap();
+        // Touch `a` so `b` is the least recently used
+        assert!(cache.get(&[1; 32], false).is_some());
+        cache.load(&[3; 32], &c, false).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.used_bytes(), 8);
+        assert!(cache.get(&[2; 32], false).is_none());
+        assert_eq!(cache.get(&[3; 32], false).unwrap(), vec![b'c'; 4]);
+
+        // Larger than the whole cache: read but not kept
+        let big = write("d", 11);
+        assert_eq!(cache.load(&[4; 32], &big, false).unwrap(), None);
+
+        cache.remove(&[1; 32]);
+        assert!(cache.get(&[1; 32], false).is_none());
+        assert_eq!(cache.used_bytes(), 4);
+    }
+}
@@ -1,9 +1,29 @@
 //! Storage module for chunks and file index
 
+pub mod acl;
+pub mod cache;
 pub mod chunks;
+pub mod import;
 pub mod index;
 pub mod inode;
+#[cfg(feature = "mmap-cache")]
+pub mod mmap_cache;
+pub mod quota;
+pub mod remove;
+pub mod scrub;
+pub mod snapshot;
+pub mod tiered;
+#[cfg(feature = "io-uring")]
+pub mod uring;
 
-pub use chunks::ChunkStore;
-pub use index::{FileIndex, FileEntry, ChunkRef};
+pub use acl::{Acl, AclEntry, AclTag};
+pub use cache::ReadCache;
+pub use chunks::{fallocate_supported, ChunkStore, ChunkStoreStats};
+pub use import::{import_tree, ImportProgress, ImportReport};
+pub use index::{ChunkRef, EntryMut, FileEntry, FileIndex};
 pub use inode::InodeTable;
+pub use quota::{QuotaManager, QuotaReport, QuotaUsage};
+pub use remove::{remove_tree, RemoveReport};
+pub use scrub::{ChunkFetcher, ScrubStatus, Scrubber};
+pub use snapshot::{SnapshotManager, SnapIndex, SnapshotInfo};
+pub use tiered::{ColdTier, EvictReport, S3ColdTier, Tier, TierStatus, TieredChunkStore};
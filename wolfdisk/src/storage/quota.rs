@@ -0,0 +1,330 @@
+//! Per-directory quotas
+//!
+//! Each `[[quota]]` entry limits the total size and number of files under a
+//! directory tree. A file counts against every quota whose path is one of its
+//! ancestors, and an operation that would take any of them over its limit
+//! fails with `EDQUOT`. Directories themselves are not counted.
+//!
+//! Usage is tracked per quota path and saved to `{index_dir}/quotas.json`
+//! after every change (written to a temporary file and renamed into place).
+//! In cluster mode only the leader tracks usage; a node that applies changes
+//! it did not track (as a follower) marks its usage stale, and it is recounted
+//! from the file index the next time it is needed.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::QuotaConfig;
+use crate::error::{Error, Result};
+use crate::storage::FileIndex;
+
+const QUOTA_FILENAME: &str = "quotas.json";
+
+/// Current usage of one quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub used_bytes: u64,
+    pub used_files: u64,
+}
+
+/// Usage and limits of one quota, as returned by `GET /quota`
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaReport {
+    pub path: String,
+    pub used_bytes: u64,
+    pub max_bytes: Option<u64>,
+    pub used_files: u64,
+    pub max_files: Option<u64>,
+}
+
+/// Enforces the configured quotas and tracks their usage
+pub struct QuotaManager {
+    /// Configured quotas, with paths relative to the filesystem root
+    limits: Vec<QuotaConfig>,
+    usage: Mutex<HashMap<PathBuf, QuotaUsage>>,
+    state_path: PathBuf,
+    /// Usage must be recounted from the index before it is used
+    stale: AtomicBool,
+}
+
+impl QuotaManager {
+    /// Set up the configured quotas, loading saved usage from `index_dir`
+    pub fn load(limits: &[QuotaConfig], index_dir: &Path) -> Result<Self> {
+        let limits: Vec<QuotaConfig> = limits.iter()
+            .map(|q| QuotaConfig { path: normalize(&q.path), ..q.clone() })
+            .collect();
+        let state_path = index_dir.join(QUOTA_FILENAME);
+
+        let mut usage = HashMap::new();
+        let mut stale = true;
+        if state_path.exists() {
+            let saved: HashMap<PathBuf, QuotaUsage> =
+                serde_json::from_reader(BufReader::new(File::open(&state_path)?))?;
+            // A quota added since the last save has never been counted
+            stale = limits.iter().any(|q| !saved.contains_key(&q.path));
+            usage = saved.into_iter()
+                .filter(|(path, _)| limits.iter().any(|q| &q.path == path))
+                .collect();
+        }
+
+        Ok(Self {
+            limits,
+            usage: Mutex::new(usage),
+            state_path,
+            stale: AtomicBool::new(stale),
+        })
+    }
+
+    /// Whether any quota is configured
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Forget the tracked usage; it is recounted on next use
+    pub fn mark_stale(&self) {
+        self.stale.store(true, Ordering::Relaxed);
+    }
+
+    /// Recount usage from the index if it has been marked stale
+    pub fn rebuild_if_stale(&self, index: &FileIndex) {
+        if self.stale.swap(false, Ordering::Relaxed) {
+            self.rebuild(index);
+        }
+    }
+
+    /// Recount the usage of every quota from the index
+    pub fn rebuild(&self, index: &FileIndex) {
+        let mut usage: HashMap<PathBuf, QuotaUsage> = self.limits.iter()
+            .map(|q| (q.path.clone(), QuotaUsage::default()))
+            .collect();
+        for (path, entry) in index.iter().filter(|(_, e)| !e.is_dir) {
+            for quota in self.covering(path) {
+                let u = usage.get_mut(&quota.path).expect("every quota has usage");
+                u.used_bytes += entry.size;
+                u.used_files += 1;
+            }
+        }
+
+        let mut current = self.usage.lock().unwrap();
+        *current = usage;
+        self.save(&current);
+        info!("Recounted usage of {} quotas", self.limits.len());
+    }
+
+    /// Account for `bytes` and `files` added at `path`, or fail with
+    /// `QuotaExceeded` (and change nothing) if a quota would be exceeded
+    pub fn charge(&self, path: &Path, bytes: u64, files: u64) -> Result<()> {
+        let quotas: Vec<&QuotaConfig> = self.covering(path).collect();
+        self.apply(&quotas, &[], bytes, files)
+    }
+
+    /// Account for `bytes` and `files` removed from `path`
+    pub fn release(&self, path: &Path, bytes: u64, files: u64) {
+        let quotas: Vec<&QuotaConfig> = self.covering(path).collect();
+        let _ = self.apply(&[], &quotas, bytes, files);
+    }
+
+    /// Account for a file at `path` changing size from `old_size` to `new_size`
+    pub fn resize(&self, path: &Path, old_size: u64, new_size: u64) -> Result<()> {
+        if new_size > old_size {
+            self.charge(path, new_size - old_size, 0)
+        } else {
+            self.release(path, old_size - new_size, 0);
+            Ok(())
+        }
+    }
+
+    /// Account for `bytes` and `files` moving from `from` to `to` (a rename).
+    /// Only quotas covering one side but not the other change.
+    pub fn transfer(&self, from: &Path, to: &Path, bytes: u64, files: u64) -> Result<()> {
+        let from_quotas: Vec<&QuotaConfig> = self.covering(from).collect();
+        let to_quotas: Vec<&QuotaConfig> = self.covering(to).collect();
+        let charged: Vec<&QuotaConfig> = to_quotas.iter()
+            .filter(|q| !from_quotas.iter().any(|f| f.path == q.path))
+            .copied()
+            .collect();
+        let released: Vec<&QuotaConfig> = from_quotas.iter()
+            .filter(|q| !to_quotas.iter().any(|t| t.path == q.path))
+            .copied()
+            .collect();
+        self.apply(&charged, &released, bytes, files)
+    }
+
+    /// Usage and limits of the quota configured on `path`
+    pub fn report(&self, path: &Path) -> Option<QuotaReport> {
+        let path = normalize(path);
+        let quota = self.limits.iter().find(|q| q.path == path)?;
+        let usage = self.usage.lock().unwrap().get(&path).copied().unwrap_or_default();
+        Some(QuotaReport {
+            path: format!("/{}", path.display()),
+            used_bytes: usage.used_bytes,
+            max_bytes: quota.max_bytes,
+            used_files: usage.used_files,
+            max_files: quota.max_files,
+        })
+    }
+
+    /// Quotas whose directory contains `path`
+    fn covering<'a>(&'a self, path: &Path) -> impl Iterator<Item = &'a QuotaConfig> {
+        let path = normalize(path);
+        self.limits.iter().filter(move |q| path.starts_with(&q.path))
+    }
+
+    fn apply(&self, charged: &[&QuotaConfig], released: &[&QuotaConfig], bytes: u64, files: u64) -> Result<()> {
+        if (charged.is_empty() && released.is_empty()) || (bytes == 0 && files == 0) {
+            return Ok(());
+        }
+
+        let mut usage = self.usage.lock().unwrap();
+        for quota in charged {
+            let used = usage.get(&quota.path).copied().unwrap_or_default();
+            if let Some(max) = quota.max_bytes.filter(|&max| used.used_bytes + bytes > max) {
+                return Err(Error::QuotaExceeded(format!(
+                    "/{} is limited to {} bytes ({} used)", quota.path.display(), max, used.used_bytes)));
+            }
+            if let Some(max) = quota.max_files.filter(|&max| used.used_files + files > max) {
+                return Err(Error::QuotaExceeded(format!(
+                    "/{} is limited to {} files ({} used)", quota.path.display(), max, used.used_files)));
+            }
+        }
+
+        for quota in charged {
+            let used = usage.entry(quota.path.clone()).or_default();
+            used.used_bytes += bytes;
+            used.used_files += files;
+        }
+        for quota in released {
+            let used = usage.entry(quota.path.clone()).or_default();
+            used.used_bytes = used.used_bytes.saturating_sub(bytes);
+            used.used_files = used.used_files.saturating_sub(files);
+        }
+        self.save(&usage);
+        Ok(())
+    }
+
+    /// Write usage to a temporary file and rename it over the state file
+    fn save(&self, usage: &HashMap<PathBuf, QuotaUsage>) {
+        let tmp_path = self.state_path.with_extension("json.tmp");
+        let result = (|| -> Result<()> {
+            if let Some(dir) = self.state_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            serde_json::to_writer(BufWriter::new(File::create(&tmp_path)?), usage)?;
+            fs::rename(&tmp_path, &self.state_path)?;
+            Ok(())
+        })();
+        if let Err(e) = result {
+            warn!("Failed to save quota usage to {:?}: {}", self.state_path, e);
+        }
+    }
+}
+
+/// Quota and index paths are relative to the filesystem root
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::FileEntry;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    fn quota(path: &str, max_bytes: Option<u64>, max_files: Option<u64>) -> QuotaConfig {
+        QuotaConfig { path: PathBuf::from(path), max_bytes, max_files }
+    }
+
+    fn file_entry(size: u64) -> FileEntry {
+        let now = SystemTime::now();
+        FileEntry {
+            size,
+            is_dir: false,
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+            chunks: Vec::new(),
+            symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
+        }
+    }
+
+    #[test]
+    fn test_write_past_quota_fails() {
+        let dir = tempdir().unwrap();
+        let quotas = QuotaManager::load(&[quota("/projects/teamA", Some(1000), Some(3))], dir.path()).unwrap();
+        quotas.rebuild_if_stale(&FileIndex::new());
+
+        // Fill the directory to exactly its byte quota
+        let file = Path::new("projects/teamA/data.bin");
+        quotas.charge(file, 0, 1).unwrap();
+        for _ in 0..10 {
+            quotas.charge(file, 100, 0).unwrap();
+        }
+
+        // The next write fails with EDQUOT and is not counted
+        let err = quotas.charge(file, 1, 0).unwrap_err();
+        assert_eq!(err.to_errno(), libc::EDQUOT);
+        let report = quotas.report(Path::new("/projects/teamA")).unwrap();
+        assert_eq!((report.used_bytes, report.max_bytes), (1000, Some(1000)));
+
+        // Nested directories count too; other directories are unaffected
+        quotas.charge(Path::new("projects/teamA/sub/a"), 0, 1).unwrap();
+        quotas.charge(Path::new("projects/teamA/sub/b"), 0, 1).unwrap();
+        assert_eq!(quotas.charge(Path::new("projects/teamA/sub/c"), 0, 1).unwrap_err().to_errno(), libc::EDQUOT);
+        quotas.charge(Path::new("projects/teamB/big.bin"), 1 << 30, 1).unwrap();
+        quotas.charge(Path::new("projects/teamAB/x"), 5000, 1).unwrap();
+
+        // Truncating frees space; moving a file out frees its slot
+        quotas.resize(file, 1000, 400).unwrap();
+        quotas.charge(file, 600, 0).unwrap();
+        quotas.transfer(Path::new("projects/teamA/sub/a"), Path::new("projects/teamB/a"), 0, 1).unwrap();
+        quotas.charge(Path::new("projects/teamA/sub/c"), 0, 1).unwrap();
+        assert_eq!(quotas.transfer(Path::new("projects/teamB/big.bin"), file, 1 << 30, 1).unwrap_err().to_errno(), libc::EDQUOT);
+    }
+
+    #[test]
+    fn test_usage_persists_and_rebuilds() {
+        let dir = tempdir().unwrap();
+        let limits = [quota("/projects/teamA", Some(1 << 20), None)];
+        let quotas = QuotaManager::load(&limits, dir.path()).unwrap();
+        quotas.rebuild_if_stale(&FileIndex::new());
+        quotas.charge(Path::new("projects/teamA/one"), 300, 1).unwrap();
+        quotas.charge(Path::new("projects/teamA/two"), 200, 1).unwrap();
+        quotas.release(Path::new("projects/teamA/one"), 300, 1);
+
+        // Saved usage is picked up on restart without a recount
+        let reloaded = QuotaManager::load(&limits, dir.path()).unwrap();
+        reloaded.rebuild_if_stale(&FileIndex::new());
+        let report = reloaded.report(Path::new("projects/teamA")).unwrap();
+        assert_eq!((report.used_bytes, report.used_files), (200, 1));
+        assert_eq!(report.path, "/projects/teamA");
+
+        // A stale manager recounts from the index
+        let mut index = FileIndex::new();
+        index.insert(PathBuf::from("projects/teamA/a"), file_entry(10));
+        index.insert(PathBuf::from("projects/teamA/sub/b"), file_entry(20));
+        index.insert(PathBuf::from("projects/other"), file_entry(40));
+        reloaded.mark_stale();
+        reloaded.rebuild_if_stale(&index);
+        let report = reloaded.report(Path::new("projects/teamA")).unwrap();
+        assert_eq!((report.used_bytes, report.used_files), (30, 2));
+        assert!(reloaded.report(Path::new("projects")).is_none());
+    }
+}
@@ -0,0 +1,136 @@
+//! Recursive removal of an index subtree
+//!
+//! `wolfdisk rm --recursive` drops every entry under a path from the file
+//! index and inode table and deletes the chunks that no remaining entry
+//! references. Like `import`, it works on the on-disk index with the node
+//! stopped.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::error::{Error, Result};
+use super::chunks::ChunkStore;
+use super::import::index_path;
+use super::index::FileIndex;
+use super::inode::InodeTable;
+
+/// Outcome of a recursive remove
+#[derive(Debug, Default)]
+pub struct RemoveReport {
+    /// Index paths removed (or that would be, on a dry run), children
+    /// before their parents
+    pub removed: Vec<PathBuf>,
+    pub files: usize,
+    pub dirs: usize,
+    pub bytes: u64,
+    /// Chunks no longer referenced by any entry
+    pub chunks: usize,
+}
+
+/// Remove `path` (`/` for everything) and all entries below it. Without
+/// `recursive` a non-empty directory is refused; with `dry_run` nothing is
+/// changed and the report lists what would go.
+pub fn remove_tree(
+    path: &str,
+    index: &mut FileIndex,
+    inodes: &mut InodeTable,
+    chunk_store: &ChunkStore,
+    recursive: bool,
+    dry_run: bool,
+) -> Result<RemoveReport> {
+    let root = index_path(path)?;
+    let is_root = root.as_os_str().is_empty();
+    if !is_root && index.get(&root).is_none() {
+        return Err(Error::FileNotFound(path.to_string()));
+    }
+
+    let mut paths: Vec<PathBuf> = index.iter()
+        .map(|(p, _)| p.clone())
+        .filter(|p| !p.as_os_str().is_empty() && p.starts_with(&root))
+        .collect();
+    if !recursive && (is_root || paths.len() > 1) {
+        return Err(Error::InvalidOperation(format!("{} is a non-empty directory", path)));
+    }
+    // Leaves first: deeper paths before shallower ones, so a directory
+    // always goes after everything in it
+    paths.sort_by(|a, b| {
+        b.components().count().cmp(&a.components().count()).then_with(|| a.cmp(b))
+    });
+
+    let mut report = RemoveReport::default();
+    let mut released = HashSet::new();
+    for p in &paths {
+        let Some(entry) = index.get(p) else { continue };
+        if entry.is_dir {
+            report.dirs += 1;
+        } else {
+            report.files += 1;
+            report.bytes += entry.size;
+        }
+        released.extend(entry.all_chunk_hashes());
+    }
+
+    // Deduplicated chunks may still back files outside the subtree
+    let removing: HashSet<&PathBuf> = paths.iter().collect();
+    for (p, entry) in index.iter() {
+        if !removing.contains(p) {
+            for hash in entry.all_chunk_hashes() {
+                released.remove(&hash);
+            }
+        }
+    }
+    report.chunks = released.len();
+
+    if !dry_run {
+        for p in &paths {
+            index.unlink(p);
+            inodes.remove_path(p);
+        }
+        for hash in &released {
+            chunk_store.delete(hash)?;
+        }
+    }
+    report.removed = paths;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::import::import_tree;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_remove_tree_deletes_entries_and_chunks() {
+        let src = tempdir().unwrap();
+        let data = tempdir().unwrap();
+        // Three levels, 50 files
+        for i in 0..50 {
+            let dir = src.path().join(format!("a{}/b{}", i % 3, i % 5));
+            fs::create_dir_all(&dir).unwrap();
+            let contents: Vec<u8> = (0..1000 + i * 97).map(|b| (b * (i + 1) % 251) as u8).collect();
+            fs::write(dir.join(format!("f{}.bin", i)), &contents).unwrap();
+        }
+
+        let store = ChunkStore::new(data.path().join("chunks"), 4096).unwrap();
+        let mut index = FileIndex::new();
+        import_tree(src.path(), "/tree", &store, &mut index, 4096, 4, |_| {}).unwrap();
+        let (mut inodes, _) = InodeTable::from_index(&index);
+        assert!(!store.local_chunks().unwrap().is_empty());
+
+        // Refused without --recursive
+        assert!(remove_tree("/tree", &mut index, &mut inodes, &store, false, false).is_err());
+
+        let dry = remove_tree("/tree", &mut index, &mut inodes, &store, true, true).unwrap();
+        assert_eq!(dry.files, 50);
+        assert!(index.get(&PathBuf::from("tree")).is_some());
+
+        let report = remove_tree("/tree", &mut index, &mut inodes, &store, true, false).unwrap();
+        assert_eq!(report.files, 50);
+        assert_eq!(report.removed.last(), Some(&PathBuf::from("tree")));
+        assert!(index.iter().all(|(p, _)| p.as_os_str().is_empty()));
+        assert!(inodes.get_inode(&PathBuf::from("tree")).is_none());
+        assert!(store.local_chunks().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+//! Background chunk scrubbing
+//!
+//! A chunk file is named after the SHA-256 of its contents, so bit rot that
+//! leaves the file's metadata alone can still be caught by reading it back
+//! and hashing it again. With `storage.scrub_enabled` every local chunk is
+//! checked once per `storage.scrub_interval_hours`. A chunk whose contents
+//! no longer match its name is logged and kept in the corrupt set until it
+//! checks out again; with `storage.scrub_auto_repair` a good copy is fetched
+//! from a cluster peer and written over it.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use super::chunks::ChunkStore;
+use crate::error::Result;
+
+/// Fetches a good copy of a chunk from elsewhere (a cluster peer)
+pub type ChunkFetcher = Box<dyn Fn(&[u8; 32]) -> Option<Vec<u8>> + Send + Sync>;
+
+/// Progress of the current (or last) scrub, served by `GET /scrub/status`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScrubStatus {
+    pub running: bool,
+    /// Unix time the current or last run started
+    pub started_at: Option<u64>,
+    /// Unix time the last run finished
+    pub finished_at: Option<u64>,
+    /// Chunk files to check in this run
+    pub total: u64,
+    pub checked: u64,
+    /// Corrupt chunks found in this run
+    pub corrupt: u64,
+    /// Of those, replaced with a good copy from a peer
+    pub repaired: u64,
+    /// Chunks known to be corrupt (hex hashes), across runs
+    pub corrupt_chunks: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Totals since the node started, for `GET /metrics`
+#[derive(Debug, Default)]
+pub struct ScrubStats {
+    pub chunks_checked: AtomicU64,
+    pub corrupt_chunks: AtomicU64,
+    pub repaired_chunks: AtomicU64,
+}
+
+/// Checks local chunk files against their hashes
+pub struct Scrubber {
+    chunk_store: Arc<ChunkStore>,
+    /// Set when `storage.scrub_auto_repair` is on
+    fetcher: Option<ChunkFetcher>,
+    running: AtomicBool,
+    status: Mutex<ScrubStatus>,
+    corrupt_chunks: Mutex<HashSet<[u8; 32]>>,
+    stats: ScrubStats,
+}
+
+impl Scrubber {
+    pub fn new(chunk_store: Arc<ChunkStore>) -> Self {
+        Self {
+            chunk_store,
+            fetcher: None,
+            running: AtomicBool::new(false),
+            status: Mutex::new(ScrubStatus::default()),
+            corrupt_chunks: Mutex::new(HashSet::new()),
+            stats: ScrubStats::default(),
+        }
+    }
+
+    /// Repair corrupt chunks with copies from `fetcher`
+    pub fn with_repair(mut self, fetcher: ChunkFetcher) -> Self {
+        self.fetcher = Some(fetcher);
+        self
+    }
+
+    /// Progress of the current or last run
+    pub fn status(&self) -> ScrubStatus {
+        let mut status = self.status.lock().unwrap().clone();
+        let mut corrupt: Vec<String> = self.corrupt_chunks.lock().unwrap().iter().map(hex::encode).collect();
+        corrupt.sort();
+        status.corrupt_chunks = corrupt;
+        status
+    }
+
+    /// Chunks found corrupt and not repaired since
+    pub fn corrupt_chunks(&self) -> HashSet<[u8; 32]> {
+        self.corrupt_chunks.lock().unwrap().clone()
+    }
+
+    pub fn stats(&self) -> &ScrubStats {
+        &self.stats
+    }
+
+    /// Start a scrub on a background thread, unless one is running
+    pub fn start(self: &Arc<Self>) -> std::result::Result<ScrubStatus, String> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("A scrub is already running".to_string());
+        }
+        let this = Arc::clone(self);
+        std::thread::spawn(move || {
+            this.scrub();
+            this.running.store(false, Ordering::SeqCst);
+        });
+        Ok(self.status())
+    }
+
+    /// Scrub every `interval`, the first time one interval after starting
+    pub fn run_periodically(self: &Arc<Self>, interval: Duration) {
+        let this = Arc::clone(self);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            if !this.running.swap(true, Ordering::SeqCst) {
+                this.scrub();
+                this.running.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+
+    /// Check every local chunk now, returning the outcome
+    pub fn scrub(&self) -> ScrubStatus {
+        *self.status.lock().unwrap() = ScrubStatus {
+            running: true,
+            started_at: Some(unix_now()),
+            ..Default::default()
+        };
+        let result = self.check_all();
+        {
+            let mut status = self.status.lock().unwrap();
+            status.running = false;
+            status.finished_at = Some(unix_now());
+            match result {
+                Ok(()) => info!(
+                    "Scrub finished: {} chunks checked, {} corrupt, {} repaired",
+                    status.checked, status.corrupt, status.repaired
+                ),
+                Err(e) => {
+                    warn!("Scrub failed: {}", e);
+                    status.error = Some(e.to_string());
+                }
+            }
+        }
+        self.status()
+    }
+
+    fn check_all(&self) -> Result<()> {
+        let chunks = self.chunk_store.local_chunks()?;
+        self.status.lock().unwrap().total = chunks.len() as u64;
+
+        for chunk in chunks {
+            let intact = match self.chunk_store.read_local(&chunk.hash) {
+                Ok(data) => hash_of(&data) == chunk.hash,
+                // Deleted since the listing
+                Err(crate::error::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                // An unreadable sector is as bad as a flipped bit
+                Err(_) => false,
+            };
+            self.stats.chunks_checked.fetch_add(1, Ordering::Relaxed);
+            self.status.lock().unwrap().checked += 1;
+            if intact {
+                self.corrupt_chunks.lock().unwrap().remove(&chunk.hash);
+                continue;
+            }
+
+            warn!("Chunk {} is corrupt: its contents no longer match its hash", hex::encode(chunk.hash));
+            self.stats.corrupt_chunks.fetch_add(1, Ordering::Relaxed);
+            self.status.lock().unwrap().corrupt += 1;
+            if self.repair(&chunk.hash) {
+                self.corrupt_chunks.lock().unwrap().remove(&chunk.hash);
+                self.stats.repaired_chunks.fetch_add(1, Ordering::Relaxed);
+                self.status.lock().unwrap().repaired += 1;
+            } else {
+                self.corrupt_chunks.lock().unwrap().insert(chunk.hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace a corrupt chunk with a good copy, if auto-repair is on and a peer has one
+    fn repair(&self, hash: &[u8; 32]) -> bool {
+        let Some(ref fetcher) = self.fetcher else {
+            return false;
+        };
+        let Some(data) = fetcher(hash).filter(|data| hash_of(data) == *hash) else {
+            warn!("No good copy of corrupt chunk {} found on any peer", hex::encode(hash));
+            return false;
+        };
+        match self.chunk_store.replace_chunk(hash, &data) {
+            Ok(()) => {
+                info!("Repaired chunk {} from a peer", hex::encode(hash));
+                true
+            }
+            Err(e) => {
+                warn!("Failed to repair chunk {}: {}", hex::encode(hash), e);
+                false
+            }
+        }
+    }
+}
+
+fn hash_of(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Flip a byte in the chunk file, as bit rot would
+    fn corrupt(dir: &std::path::Path, hash: &[u8; 32]) {
+        let hex = hex::encode(hash);
+        let path = dir.join(&hex[0..2]).join(&hex[2..]);
+        let mut data = std::fs::read(&path).unwrap();
+        data[3] ^= 0x40;
+        std::fs::write(&path, data).unwrap();
+    }
+
+    #[test]
+    fn test_scrub_detects_and_repairs_corruption() {
+        let dir = tempdir().unwrap();
+        let store = Arc::new(ChunkStore::new(dir.path().to_path_buf(), 4096).unwrap());
+        let good = store.store(b"a chunk that stays intact").unwrap();
+        let bad = store.store(b"a chunk that rots on disk").unwrap();
+        corrupt(dir.path(), &bad);
+
+        // Without repair the chunk is only reported
+        let scrubber = Scrubber::new(store.clone());
+        let status = scrubber.scrub();
+        assert_eq!((status.total, status.checked, status.corrupt, status.repaired), (2, 2, 1, 0));
+        assert_eq!(status.corrupt_chunks, vec![hex::encode(bad)]);
+        assert_eq!(scrubber.corrupt_chunks(), HashSet::from([bad]));
+        assert_eq!(scrubber.stats().corrupt_chunks.load(Ordering::Relaxed), 1);
+
+        // A peer with a good copy fixes it; a peer sending garbage does not
+        let garbage = Scrubber::new(store.clone()).with_repair(Box::new(|_| Some(b"garbage".to_vec())));
+        assert_eq!(garbage.scrub().repaired, 0);
+        let scrubber = Scrubber::new(store.clone())
+            .with_repair(Box::new(|_| Some(b"a chunk that rots on disk".to_vec())));
+        let status = scrubber.scrub();
+        assert_eq!((status.corrupt, status.repaired), (1, 1));
+        assert!(status.corrupt_chunks.is_empty());
+        assert_eq!(scrubber.stats().repaired_chunks.load(Ordering::Relaxed), 1);
+        assert_eq!(store.read_local(&bad).unwrap(), b"a chunk that rots on disk");
+        assert_eq!(store.read_local(&good).unwrap(), b"a chunk that stays intact");
+
+        let status = scrubber.scrub();
+        assert_eq!((status.checked, status.corrupt), (2, 0));
+        assert_eq!(scrubber.stats().chunks_checked.load(Ordering::Relaxed), 4);
+    }
+}
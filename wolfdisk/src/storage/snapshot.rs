@@ -0,0 +1,316 @@
+//! Copy-on-write snapshots of the file index
+//!
+//! A snapshot is a frozen copy of every `FileEntry` in the live `FileIndex`,
+//! stored under `{data_dir}/snapshots/{name}/`. Because the `ChunkStore` is
+//! content-addressed, no chunk data is copied: the snapshot simply keeps
+//! referencing the old chunk hashes while new writes to the live filesystem
+//! produce new chunks. The chunk store consults the snapshot directory before
+//! deleting a chunk so that data still referenced by a snapshot survives.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::{Error, Result};
+use super::{ChunkStore, FileIndex};
+
+/// Snapshot metadata filename (alongside the snapshot's index.json)
+const SNAPSHOT_META_FILENAME: &str = "snapshot.json";
+
+/// Metadata describing a snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// Snapshot name
+    pub name: String,
+
+    /// Creation time
+    pub created: chrono::DateTime<chrono::Utc>,
+
+    /// Total logical size of all files in the snapshot (bytes)
+    pub size: u64,
+
+    /// Number of index entries (files, directories and symlinks)
+    pub entries: usize,
+}
+
+/// Frozen copy of the file index belonging to a snapshot
+pub struct SnapIndex {
+    /// Snapshot metadata
+    pub info: SnapshotInfo,
+
+    /// The snapshot's file index (read-only by convention)
+    pub index: FileIndex,
+}
+
+/// Manages snapshots stored under a single directory
+pub struct SnapshotManager {
+    /// Base directory containing one subdirectory per snapshot
+    base_dir: PathBuf,
+}
+
+impl SnapshotManager {
+    /// Create a snapshot manager rooted at `base_dir` (usually `{data_dir}/snapshots`)
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self { base_dir }
+    }
+
+    /// Directory holding a given snapshot
+    fn snapshot_dir(&self, name: &str) -> PathBuf {
+        self.base_dir.join(name)
+    }
+
+    /// Reject names that would escape the snapshot directory
+    fn validate_name(name: &str) -> Result<()> {
+        if name.is_empty()
+            || name.starts_with('.')
+            || name.contains('/')
+            || name.contains('\\')
+        {
+            return Err(Error::InvalidOperation(format!("Invalid snapshot name: '{}'", name)));
+        }
+        Ok(())
+    }
+
+    /// Check whether a snapshot exists
+    pub fn exists(&self, name: &str) -> bool {
+        self.snapshot_dir(name).join(SNAPSHOT_META_FILENAME).exists()
+    }
+
+    /// Create a snapshot by duplicating every entry of the live index
+    pub fn create(&self, name: &str, live: &FileIndex) -> Result<SnapshotInfo> {
+        Self::validate_name(name)?;
+        if self.snapshot_dir(name).exists() {
+            return Err(Error::InvalidOperation(format!("Snapshot '{}' already exists", name)));
+        }
+        fs::create_dir_all(&self.base_dir)?;
+
+        let mut index = FileIndex::new();
+        for (path, entry) in live.iter() {
+            index.insert(path.clone(), entry.clone());
+        }
+
+        let info = SnapshotInfo {
+            name: name.to_string(),
+            created: chrono::Utc::now(),
+            size: index.iter().filter(|(_, e)| !e.is_dir).map(|(_, e)| e.size).sum(),
+            entries: index.len(),
+        };
+
+        // Build in a temporary directory and rename into place so a crash
+        // never leaves a half-written snapshot that pins nothing
+        let tmp_dir = self.base_dir.join(format!(".tmp-{}", name));
+        if tmp_dir.exists() {
+            fs::remove_dir_all(&tmp_dir)?;
+        }
+        index.save(&tmp_dir)?;
+        let writer = BufWriter::new(File::create(tmp_dir.join(SNAPSHOT_META_FILENAME))?);
+        serde_json::to_writer_pretty(writer, &info)?;
+        fs::rename(&tmp_dir, self.snapshot_dir(name))?;
+
+        info!("Created snapshot '{}' ({} entries, {} bytes)", name, info.entries, info.size);
+        Ok(info)
+    }
+
+    /// Load a snapshot's metadata and index
+    pub fn load(&self, name: &str) -> Result<SnapIndex> {
+        Self::validate_name(name)?;
+        let dir = self.snapshot_dir(name);
+        let meta_path = dir.join(SNAPSHOT_META_FILENAME);
+        if !meta_path.exists() {
+            return Err(Error::FileNotFound(format!("snapshot '{}'", name)));
+        }
+
+        let info: SnapshotInfo = serde_json::from_reader(BufReader::new(File::open(&meta_path)?))?;
+        let index = FileIndex::load_or_create(&dir)?;
+        Ok(SnapIndex { info, index })
+    }
+
+    /// List all snapshots, oldest first
+    pub fn list(&self) -> Result<Vec<SnapshotInfo>> {
+        let mut snapshots = Vec::new();
+        if !self.base_dir.exists() {
+            return Ok(snapshots);
+        }
+
+        for dir_entry in fs::read_dir(&self.base_dir)? {
+            let meta_path = dir_entry?.path().join(SNAPSHOT_META_FILENAME);
+            if !meta_path.exists() {
+                continue;
+            }
+            let info: SnapshotInfo = serde_json::from_reader(BufReader::new(File::open(&meta_path)?))?;
+            snapshots.push(info);
+        }
+
+        snapshots.sort_by_key(|s| s.created);
+        Ok(snapshots)
+    }
+
+    /// All chunk hashes referenced by any snapshot
+    pub fn referenced_chunks(&self) -> Result<HashSet<[u8; 32]>> {
+        referenced_chunks(&self.base_dir)
+    }
+
+    /// Delete a snapshot and garbage-collect chunks that are no longer
+    /// referenced by the live index or any remaining snapshot.
+    /// Returns the number of chunks removed.
+    pub fn delete(&self, name: &str, live: &FileIndex, chunk_store: &ChunkStore) -> Result<usize> {
+        let snap = self.load(name)?;
+        fs::remove_dir_all(self.snapshot_dir(name))?;
+
+        let mut still_referenced = self.referenced_chunks()?;
+        for (_, entry) in live.iter() {
+            still_referenced.extend(entry.all_chunk_hashes());
+        }
+
+        let mut removed = 0;
+        let mut seen = HashSet::new();
+        for (_, entry) in snap.index.iter() {
+            for chunk in &entry.chunks {
+                if still_referenced.contains(&chunk.hash) || !seen.insert(chunk.hash) {
+                    continue;
+                }
+                if chunk_store.exists(&chunk.hash) {
+                    chunk_store.delete(&chunk.hash)?;
+                    removed += 1;
+                }
+            }
+        }
+
+        info!("Deleted snapshot '{}' ({} chunks garbage-collected)", name, removed);
+        Ok(removed)
+    }
+}
+
+/// Collect every chunk hash referenced by the snapshots under `base_dir`
+pub(crate) fn referenced_chunks(base_dir: &Path) -> Result<HashSet<[u8; 32]>> {
+    let mut hashes = HashSet::new();
+    if !base_dir.exists() {
+        return Ok(hashes);
+    }
+
+    for dir_entry in fs::read_dir(base_dir)? {
+        let dir = dir_entry?.path();
+        if !dir.join(SNAPSHOT_META_FILENAME).exists() {
+            continue;
+        }
+        let index = FileIndex::load_or_create(&dir)?;
+        for (_, entry) in index.iter() {
+            hashes.extend(entry.all_chunk_hashes());
+        }
+    }
+    Ok(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::SystemTime;
+    use tempfile::tempdir;
+
+    use crate::storage::FileEntry;
+
+    fn file_entry() -> FileEntry {
+        let now = SystemTime::now();
+        FileEntry {
+            size: 0,
+            is_dir: false,
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+            chunks: Vec::new(),
+            symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
+        }
+    }
+
+    fn write_file(index: &mut FileIndex, store: &ChunkStore, path: &str, data: &[u8]) {
+        let mut entry = file_entry();
+        store.write(&mut entry.chunks, 0, data).unwrap();
+        entry.size = data.len() as u64;
+        index.insert(PathBuf::from(path), entry);
+    }
+
+    fn read_file(index: &FileIndex, store: &ChunkStore, path: &str) -> Vec<u8> {
+        let entry = index.get(Path::new(path)).unwrap();
+        store.read(&entry.chunks, 0, entry.size as usize).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_preserves_overwritten_data() {
+        let dir = tempdir().unwrap();
+        let snapshots = SnapshotManager::new(dir.path().join("snapshots"));
+        let store = ChunkStore::new(dir.path().join("chunks"), 16)
+            .unwrap()
+            .with_snapshot_dir(dir.path().join("snapshots"));
+        let mut live = FileIndex::new();
+
+        write_file(&mut live, &store, "a.txt", b"original contents of a");
+        snapshots.create("snap1", &live).unwrap();
+
+        // Overwrite the live file and delete the chunks it no longer uses,
+        // exactly as the filesystem does on truncate/overwrite
+        let old_chunks = live.get(Path::new("a.txt")).unwrap().chunks.clone();
+        write_file(&mut live, &store, "a.txt", b"new data");
+        for chunk in &old_chunks {
+            store.delete(&chunk.hash).unwrap();
+        }
+
+        let snap = snapshots.load("snap1").unwrap();
+        assert_eq!(read_file(&live, &store, "a.txt"), b"new data");
+        assert_eq!(read_file(&snap.index, &store, "a.txt"), b"original contents of a");
+    }
+
+    #[test]
+    fn test_snapshot_list_and_delete_gc() {
+        let dir = tempdir().unwrap();
+        let snapshots = SnapshotManager::new(dir.path().join("snapshots"));
+        let store = ChunkStore::new(dir.path().join("chunks"), 1024)
+            .unwrap()
+            .with_snapshot_dir(dir.path().join("snapshots"));
+        let mut live = FileIndex::new();
+
+        write_file(&mut live, &store, "shared.txt", b"kept by live index");
+        write_file(&mut live, &store, "gone.txt", b"only in snapshot");
+        let info = snapshots.create("snap1", &live).unwrap();
+        assert_eq!(info.entries, 2);
+        assert_eq!(info.size, 34);
+        assert!(snapshots.create("snap1", &live).is_err());
+
+        let gone_hash = live.get(Path::new("gone.txt")).unwrap().chunks[0].hash;
+        let shared_hash = live.get(Path::new("shared.txt")).unwrap().chunks[0].hash;
+        live.remove(Path::new("gone.txt"));
+
+        let listed = snapshots.list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "snap1");
+
+        let removed = snapshots.delete("snap1", &live, &store).unwrap();
+        assert_eq!(removed, 1);
+        assert!(!store.exists(&gone_hash));
+        assert!(store.exists(&shared_hash));
+        assert!(snapshots.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_bad_names() {
+        let dir = tempdir().unwrap();
+        let snapshots = SnapshotManager::new(dir.path().join("snapshots"));
+        let live = FileIndex::new();
+
+        assert!(snapshots.create("../escape", &live).is_err());
+        assert!(snapshots.create(".hidden", &live).is_err());
+        assert!(snapshots.create("", &live).is_err());
+    }
+}
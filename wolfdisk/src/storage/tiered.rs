@@ -0,0 +1,365 @@
+//! Tiered chunk storage with an object-storage cold tier
+//!
+//! Chunks that have not been read for `storage.cold_tier.evict_after_days`
+//! are uploaded to a bucket and removed from the local chunk store. The
+//! chunk store fetches anything it no longer has locally from the cold tier
+//! (see `ChunkStore::with_cold_tier`), so evicted data stays readable; the
+//! content hash is checked on the way back. Eviction marks the affected
+//! `ChunkRef`s as `Tier::Cold` in the local index, which is what
+//! `wolfdisk tier status` counts.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use s3::creds::Credentials;
+use s3::{Bucket, Region};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::ColdTierConfig;
+use crate::error::{Error, Result};
+use super::{ChunkStore, FileIndex};
+
+/// Where a chunk is stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    /// On this node's local disk
+    #[default]
+    Hot,
+    /// Evicted to the cold tier (a local copy may exist again after a read)
+    Cold,
+}
+
+/// Object key of a chunk in the cold tier
+pub fn cold_key(hash: &[u8; 32]) -> String {
+    format!("chunks/{}", hex::encode(hash))
+}
+
+/// Object storage holding evicted chunks
+pub trait ColdTier: Send + Sync {
+    /// Upload an object
+    fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    /// Download an object, or None if it does not exist
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Delete an object (deleting a missing object is not an error)
+    fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// S3 (or S3-compatible, e.g. MinIO) cold tier
+pub struct S3ColdTier {
+    bucket: Box<Bucket>,
+    /// The S3 client is async; chunk reads happen on FUSE threads
+    runtime: tokio::runtime::Runtime,
+}
+
+impl S3ColdTier {
+    pub fn new(config: &ColdTierConfig) -> Result<Self> {
+        if config.provider != "s3" {
+            return Err(Error::Config(format!("Unsupported cold tier provider '{}'", config.provider)));
+        }
+        if config.bucket.is_empty() {
+            return Err(Error::Config("storage.cold_tier.bucket is required".to_string()));
+        }
+        let credentials = Credentials::new(
+            Some(&config.access_key_id),
+            Some(&config.secret_access_key),
+            None,
+            None,
+            None,
+        )
+        .map_err(|e| Error::Config(format!("Invalid cold tier credentials: {}", e)))?;
+        let region = if config.endpoint.is_empty() {
+            config.region.parse().unwrap_or(Region::UsEast1)
+        } else {
+            Region::Custom { region: config.region.clone(), endpoint: config.endpoint.clone() }
+        };
+        let bucket = Bucket::new(&config.bucket, region, credentials)
+            .map_err(|e| Error::Config(format!("Invalid cold tier bucket: {}", e)))?
+            .with_path_style();
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("wolfdisk-cold-tier")
+            .enable_all()
+            .build()?;
+        Ok(Self { bucket, runtime })
+    }
+
+    /// Run a request to completion from synchronous code
+    fn block_on<F: Future + Send>(&self, future: F) -> F::Output
+    where
+        F::Output: Send,
+    {
+        // block_on panics inside a runtime; hop to a plain thread there
+        if tokio::runtime::Handle::try_current().is_ok() {
+            std::thread::scope(|scope| {
+                scope.spawn(|| self.runtime.block_on(future)).join().expect("cold tier request panicked")
+            })
+        } else {
+            self.runtime.block_on(future)
+        }
+    }
+}
+
+fn s3_error(operation: &str, key: &str, detail: impl std::fmt::Display) -> Error {
+    Error::Storage(format!("Cold tier {} of {} failed: {}", operation, key, detail))
+}
+
+impl ColdTier for S3ColdTier {
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let response = self.block_on(self.bucket.put_object(key, data))
+            .map_err(|e| s3_error("upload", key, e))?;
+        match response.status_code() {
+            200..=299 => Ok(()),
+            status => Err(s3_error("upload", key, format!("HTTP {}", status))),
+        }
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let response = self.block_on(self.bucket.get_object(key))
+            .map_err(|e| s3_error("download", key, e))?;
+        match response.status_code() {
+            200..=299 => Ok(Some(response.to_vec())),
+            404 => Ok(None),
+            status => Err(s3_error("download", key, format!("HTTP {}", status))),
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let response = self.block_on(self.bucket.delete_object(key))
+            .map_err(|e| s3_error("delete", key, e))?;
+        match response.status_code() {
+            200..=299 | 404 => Ok(()),
+            status => Err(s3_error("delete", key, format!("HTTP {}", status))),
+        }
+    }
+}
+
+/// Result of an eviction pass
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvictReport {
+    /// Chunks evicted (or, for a dry run, that would be)
+    pub chunks: u64,
+    pub bytes: u64,
+    /// Chunks that could not be uploaded
+    pub failed: u64,
+    pub dry_run: bool,
+}
+
+/// Bytes held by each tier
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierStatus {
+    pub hot_chunks: u64,
+    pub hot_bytes: u64,
+    pub cold_chunks: u64,
+    pub cold_bytes: u64,
+}
+
+/// A local chunk store backed by a cold tier
+pub struct TieredChunkStore {
+    hot: Arc<ChunkStore>,
+    cold: Arc<dyn ColdTier>,
+    file_index: Arc<RwLock<FileIndex>>,
+    index_dir: PathBuf,
+    evict_after: Duration,
+}
+
+impl TieredChunkStore {
+    /// `hot` should already fetch missing chunks from `cold`
+    /// (`ChunkStore::with_cold_tier`)
+    pub fn new(
+        hot: Arc<ChunkStore>,
+        cold: Arc<dyn ColdTier>,
+        file_index: Arc<RwLock<FileIndex>>,
+        index_dir: PathBuf,
+        evict_after_days: u64,
+    ) -> Self {
+        Self {
+            hot,
+            cold,
+            file_index,
+            index_dir,
+            evict_after: Duration::from_secs(evict_after_days * 24 * 3600),
+        }
+    }
+
+    /// Read a chunk from whichever tier holds it
+    pub fn get(&self, hash: &[u8; 32]) -> Result<Vec<u8>> {
+        self.hot.get(hash)
+    }
+
+    /// Move chunks not read within `evict_after_days` to the cold tier. With
+    /// `dry_run`, only report what would be moved.
+    pub fn evict(&self, dry_run: bool) -> Result<EvictReport> {
+        let cutoff = SystemTime::now() - self.evict_after;
+        let mut report = EvictReport { dry_run, ..Default::default() };
+        let mut evicted = HashSet::new();
+
+        for chunk in self.hot.local_chunks()? {
+            if chunk.accessed > cutoff {
+                continue;
+            }
+            if dry_run {
+                report.chunks += 1;
+                report.bytes += chunk.size;
+                continue;
+            }
+            let uploaded = self.hot.read_local(&chunk.hash)
+                .and_then(|data| self.cold.put(&cold_key(&chunk.hash), &data))
+                .and_then(|_| self.hot.evict_local(&chunk.hash));
+            match uploaded {
+                Ok(()) => {
+                    report.chunks += 1;
+                    report.bytes += chunk.size;
+                    evicted.insert(chunk.hash);
+                }
+                Err(e) => {
+                    warn!("Failed to evict chunk {}: {}", hex::encode(chunk.hash), e);
+                    report.failed += 1;
+                }
+            }
+        }
+
+        if !evicted.is_empty() {
+            let mut index = self.file_index.write().unwrap();
+            let paths: Vec<PathBuf> = index.paths().cloned().collect();
+            for path in paths {
+                let Some(mut entry) = index.get_mut(&path) else { continue };
+                let entry = &mut *entry;
+                let versions = entry.versions.iter_mut().map(|(_, v)| &mut v.chunks);
+                for chunks in std::iter::once(&mut entry.chunks).chain(versions) {
+                    for chunk in chunks.iter_mut().filter(|c| evicted.contains(&c.hash)) {
+                        chunk.tier = Tier::Cold;
+                    }
+                }
+            }
+            index.save(&self.index_dir)?;
+            info!("Evicted {} chunks ({} bytes) to the cold tier", report.chunks, report.bytes);
+        }
+        Ok(report)
+    }
+
+    /// Count local chunks, and chunks marked cold that are not local
+    pub fn status(&self) -> Result<TierStatus> {
+        let mut status = TierStatus::default();
+        for chunk in self.hot.local_chunks()? {
+            status.hot_chunks += 1;
+            status.hot_bytes += chunk.size;
+        }
+
+        let index = self.file_index.read().unwrap();
+        let mut seen = HashSet::new();
+        for (_, entry) in index.iter() {
+            let versions = entry.versions.iter().flat_map(|(_, v)| v.chunks.iter());
+            for chunk in entry.chunks.iter().chain(versions) {
+                if chunk.tier == Tier::Cold && seen.insert(chunk.hash) && !self.hot.exists(&chunk.hash) {
+                    status.cold_chunks += 1;
+                    status.cold_bytes += chunk.size as u64;
+                }
+            }
+        }
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use crate::storage::FileEntry;
+    use tempfile::tempdir;
+
+    /// In-memory stand-in for a bucket
+    #[derive(Default)]
+    struct MemoryTier {
+        objects: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    impl ColdTier for MemoryTier {
+        fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+            self.objects.lock().unwrap().insert(key.to_string(), data.to_vec());
+            Ok(())
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+            Ok(self.objects.lock().unwrap().get(key).cloned())
+        }
+
+        fn delete(&self, key: &str) -> Result<()> {
+            self.objects.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    fn set_accessed(chunks_dir: &std::path::Path, hash: &[u8; 32], when: SystemTime) {
+        let hex = hex::encode(hash);
+        let file = std::fs::File::open(chunks_dir.join(&hex[..2]).join(&hex[2..])).unwrap();
+        file.set_times(std::fs::FileTimes::new().set_accessed(when)).unwrap();
+    }
+
+    #[test]
+    fn test_evict_and_read_back() {
+        let dir = tempdir().unwrap();
+        let cold = Arc::new(MemoryTier::default());
+        let hot = Arc::new(
+            ChunkStore::new(dir.path().join("chunks"), 4).unwrap()
+                .with_cold_tier(cold.clone(), true)
+        );
+        let index = Arc::new(RwLock::new(FileIndex::new()));
+        let tiered = TieredChunkStore::new(hot.clone(), cold.clone(), index.clone(), dir.path().join("index"), 30);
+
+        let mut chunks = Vec::new();
+        hot.write(&mut chunks, 0, b"old data new").unwrap();
+        let now = SystemTime::now();
+        index.write().unwrap().insert(PathBuf::from("f"), FileEntry {
+            size: 12,
+            is_dir: false,
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            created: now,
+            modified: now,
+            accessed: now,
+            chunks: chunks.clone(),
+            symlink_target: None,
+            xattrs: HashMap::new(),
+            version_id: None,
+            versions: Vec::new(),
+            hard_link_count: 1,
+            link_id: None,
+        });
+        // "old " and "data" were last read 40 days ago
+        for chunk in &chunks[..2] {
+            set_accessed(&dir.path().join("chunks"), &chunk.hash, now - Duration::from_secs(40 * 24 * 3600));
+        }
+
+        let dry = tiered.evict(true).unwrap();
+        assert_eq!((dry.chunks, dry.bytes), (2, 8));
+        assert_eq!(hot.local_chunks().unwrap().len(), 3);
+
+        let report = tiered.evict(false).unwrap();
+        assert_eq!((report.chunks, report.bytes, report.failed), (2, 8, 0));
+        assert_eq!(cold.objects.lock().unwrap().len(), 2);
+        assert_eq!(tiered.status().unwrap(), TierStatus { hot_chunks: 1, hot_bytes: 4, cold_chunks: 2, cold_bytes: 8 });
+        let tiers: Vec<Tier> = index.read().unwrap().get(&PathBuf::from("f")).unwrap()
+            .chunks.iter().map(|c| c.tier).collect();
+        assert_eq!(tiers, [Tier::Cold, Tier::Cold, Tier::Hot]);
+
+        // Reads fetch evicted chunks and keep them locally again
+        assert_eq!(hot.read(&chunks, 0, 12).unwrap(), b"old data new");
+        assert_eq!(tiered.status().unwrap().cold_chunks, 0);
+        assert_eq!(tiered.get(&chunks[0].hash).unwrap(), b"old ");
+
+        // A corrupt object is rejected
+        let key = cold_key(&chunks[1].hash);
+        hot.evict_local(&chunks[1].hash).unwrap();
+        cold.objects.lock().unwrap().insert(key, b"evil".to_vec());
+        assert!(hot.get(&chunks[1].hash).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! io_uring chunk writes (`io-uring` feature)
+//!
+//! Chunk writes are handed to a dedicated thread running a `tokio-uring`
+//! runtime. The thread waits for a write, takes up to
+//! `storage.write_batch_size` writes that are already queued, and starts them
+//! all before awaiting any, so the batch reaches the kernel in a single
+//! submission instead of one blocking `write` + context switch per chunk.
+//! Each write creates the chunk file, writes it with `write_at` and syncs it
+//! before its caller is told it completed.
+
+use std::io;
+use std::path::PathBuf;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio_uring::buf::IoBuf;
+use tracing::{debug, error};
+
+/// A chunk file to write
+struct WriteRequest {
+    path: PathBuf,
+    data: Vec<u8>,
+    done: oneshot::Sender<io::Result<()>>,
+}
+
+/// Handle to the io_uring writer thread
+pub struct UringWriter {
+    requests: mpsc::UnboundedSender<WriteRequest>,
+}
+
+impl UringWriter {
+    /// Start the writer thread, submitting up to `batch_size` writes at once
+    pub fn start(batch_size: usize) -> io::Result<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let batch_size = batch_size.max(1);
+        std::thread::Builder::new()
+            .name("wolfdisk-uring".to_string())
+            .spawn(move || tokio_uring::start(run(rx, batch_size)))?;
+        Ok(Self { requests: tx })
+    }
+
+    /// Queue a write of `data` to a new file at `path`. The receiver
+    /// resolves once the data has been synced to disk.
+    pub fn submit(&self, path: PathBuf, data: Vec<u8>) -> oneshot::Receiver<io::Result<()>> {
+        let (done, rx) = oneshot::channel();
+        if let Err(mpsc::error::SendError(request)) = self.requests.send(WriteRequest { path, data, done }) {
+            let _ = request.done.send(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "io_uring writer thread has stopped",
+            )));
+        }
+        rx
+    }
+}
+
+/// Writer thread main loop
+async fn run(mut requests: mpsc::UnboundedReceiver<WriteRequest>, batch_size: usize) {
+    let mut batch = Vec::with_capacity(batch_size);
+    while let Some(first) = requests.recv().await {
+        batch.push(first);
+        while batch.len() < batch_size {
+            match requests.try_recv() {
+                Ok(request) => batch.push(request),
+                Err(_) => break,
+            }
+        }
+        debug!("Submitting {} chunk writes", batch.len());
+
+        // Start every write before awaiting any, so they are submitted together
+        let writes: Vec<_> = batch.drain(..)
+            .map(|request| tokio_uring::spawn(async move {
+                let result = write_file(request.path, request.data).await;
+                let _ = request.done.send(result);
+            }))
+            .collect();
+        for write in writes {
+            if let Err(e) = write.await {
+                error!("io_uring chunk write task failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Create `path`, write all of `data` to it and sync it
+async fn write_file(path: PathBuf, data: Vec<u8>) -> io::Result<()> {
+    let file = tokio_uring::fs::File::create(&path).await?;
+    let len = data.len();
+    let mut buf = data;
+    let mut written = 0;
+    while written < len {
+        let (result, slice) = file.write_at(buf.slice(written..), written as u64).await;
+        buf = slice.into_inner();
+        match result? {
+            0 => return Err(io::ErrorKind::WriteZero.into()),
+            n => written += n,
+        }
+    }
+    file.sync_all().await?;
+    file.close().await
+}
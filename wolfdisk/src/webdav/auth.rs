@@ -0,0 +1,244 @@
+//! WebDAV digest authentication (RFC 7616, SHA-256 with qop=auth)
+//!
+//! The password never crosses the wire: the client proves it knows
+//! SHA-256("username:realm:password"), which is also what the config stores
+//! as `password_hash`. Nonces are stateless — a timestamp signed with a
+//! per-process secret — and expire after five minutes, after which the client
+//! is asked to retry with a fresh one (`stale=true`).
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::config::WebDavConfig;
+use crate::error::{Error, Result};
+
+/// How long a nonce stays valid
+const NONCE_LIFETIME_SECS: u64 = 300;
+
+/// Outcome of checking an Authorization header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthResult {
+    /// Credentials are valid
+    Ok,
+    /// Missing or wrong credentials
+    Denied,
+    /// Correct credentials with an expired nonce
+    Stale,
+}
+
+/// Digest authentication for a single configured user
+pub struct DigestAuth {
+    username: String,
+    realm: String,
+    /// Hex SHA-256 of "username:realm:password"
+    ha1: String,
+    /// Signs nonces so they need no server-side state
+    secret: [u8; 32],
+}
+
+impl DigestAuth {
+    /// Create from a username, a `sha256:<hex>` password hash and the realm it was made for
+    pub fn new(username: &str, password_hash: &str, realm: &str) -> Result<Self> {
+        let ha1 = password_hash
+            .strip_prefix("sha256:")
+            .filter(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| {
+                Error::Config("webdav.password_hash must be \"sha256:<64 hex digits>\"".to_string())
+            })?;
+
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        Ok(Self {
+            username: username.to_string(),
+            realm: realm.to_string(),
+            ha1: ha1.to_ascii_lowercase(),
+            secret,
+        })
+    }
+
+    /// Build from the `[webdav]` config; `None` if no user is configured
+    pub fn from_config(config: &WebDavConfig) -> Result<Option<Self>> {
+        match (&config.username, &config.password_hash) {
+            (Some(user), Some(hash)) => Self::new(user, hash, &config.realm).map(Some),
+            (None, None) => Ok(None),
+            _ => Err(Error::Config(
+                "webdav.username and webdav.password_hash must be set together".to_string(),
+            )),
+        }
+    }
+
+    /// `WWW-Authenticate` header value for a 401 response
+    pub fn challenge(&self, stale: bool) -> String {
+        format!(
+            "Digest realm=\"{}\", qop=\"auth\", algorithm=SHA-256, nonce=\"{}\", stale={}",
+            self.realm,
+            self.make_nonce(unix_now()),
+            if stale { "true" } else { "false" }
+        )
+    }
+
+    /// Check an `Authorization` header for a request to `request_uri`
+    pub fn verify(&self, method: &str, request_uri: &str, header: Option<&str>) -> AuthResult {
+        let params = match header.and_then(|h| h.strip_prefix("Digest ")) {
+            Some(p) => parse_params(p),
+            None => return AuthResult::Denied,
+        };
+        let get = |key: &str| params.get(key).map(String::as_str);
+
+        let (Some(username), Some(nonce), Some(uri), Some(response)) =
+            (get("username"), get("nonce"), get("uri"), get("response"))
+        else {
+            return AuthResult::Denied;
+        };
+        // The response only covers `uri`, so it must be the resource being requested
+        if username != self.username || get("realm") != Some(self.realm.as_str()) || uri != request_uri {
+            return AuthResult::Denied;
+        }
+        if !get("algorithm").unwrap_or("SHA-256").eq_ignore_ascii_case("SHA-256") {
+            return AuthResult::Denied;
+        }
+
+        let Some(issued) = self.check_nonce(nonce) else {
+            return AuthResult::Denied;
+        };
+
+        let ha2 = sha256_hex(&format!("{}:{}", method, uri));
+        let expected = match get("qop") {
+            Some("auth") => {
+                let (Some(nc), Some(cnonce)) = (get("nc"), get("cnonce")) else {
+                    return AuthResult::Denied;
+                };
+                sha256_hex(&format!("{}:{}:{}:{}:auth:{}", self.ha1, nonce, nc, cnonce, ha2))
+            }
+            None => sha256_hex(&format!("{}:{}:{}", self.ha1, nonce, ha2)),
+            Some(_) => return AuthResult::Denied,
+        };
+        if !constant_time_eq(expected.as_bytes(), response.to_ascii_lowercase().as_bytes()) {
+            return AuthResult::Denied;
+        }
+
+        if unix_now().saturating_sub(issued) > NONCE_LIFETIME_SECS {
+            AuthResult::Stale
+        } else {
+            AuthResult::Ok
+        }
+    }
+
+    fn make_nonce(&self, timestamp: u64) -> String {
+        format!("{:x}.{}", timestamp, self.sign(timestamp))
+    }
+
+    /// Timestamp of a nonce this process issued
+    fn check_nonce(&self, nonce: &str) -> Option<u64> {
+        let (ts, mac) = nonce.split_once('.')?;
+        let timestamp = u64::from_str_radix(ts, 16).ok()?;
+        constant_time_eq(self.sign(timestamp).as_bytes(), mac.as_bytes()).then_some(timestamp)
+    }
+
+    fn sign(&self, timestamp: u64) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.secret);
+        hasher.update(timestamp.to_be_bytes());
+        hex::encode(&hasher.finalize()[..16])
+    }
+}
+
+/// Parse `key=value, key="quoted, value"` pairs
+fn parse_params(s: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = s.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().to_ascii_lowercase();
+        let after = after.trim_start();
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else {
+            match after.find(',') {
+                Some(end) => (after[..end].trim(), &after[end..]),
+                None => (after.trim(), ""),
+            }
+        };
+        params.insert(key, value.to_string());
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+    params
+}
+
+fn sha256_hex(s: &str) -> String {
+    hex::encode(Sha256::digest(s.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> DigestAuth {
+        let hash = format!("sha256:{}", sha256_hex("admin:WolfDisk:secret"));
+        DigestAuth::new("admin", &hash, "WolfDisk").unwrap()
+    }
+
+    /// Authorization header a client would send for `password`
+    fn client_header(nonce: &str, password: &str, method: &str, uri: &str) -> String {
+        let ha1 = sha256_hex(&format!("admin:WolfDisk:{}", password));
+        let ha2 = sha256_hex(&format!("{}:{}", method, uri));
+        let response = sha256_hex(&format!("{}:{}:00000001:abcdef:auth:{}", ha1, nonce, ha2));
+        format!(
+            "Digest username=\"admin\", realm=\"WolfDisk\", nonce=\"{}\", uri=\"{}\", \
+             algorithm=SHA-256, qop=auth, nc=00000001, cnonce=\"abcdef\", response=\"{}\"",
+            nonce, uri, response
+        )
+    }
+
+    #[test]
+    fn test_digest_roundtrip() {
+        let auth = auth();
+        let nonce = auth.make_nonce(unix_now());
+
+        let header = client_header(&nonce, "secret", "PROPFIND", "/docs/a%20b.txt");
+        assert_eq!(auth.verify("PROPFIND", "/docs/a%20b.txt", Some(&header)), AuthResult::Ok);
+        // Signed for a different method or resource
+        assert_eq!(auth.verify("DELETE", "/docs/a%20b.txt", Some(&header)), AuthResult::Denied);
+        assert_eq!(auth.verify("PROPFIND", "/docs/other.txt", Some(&header)), AuthResult::Denied);
+
+        let wrong = client_header(&nonce, "guess", "PROPFIND", "/docs/a%20b.txt");
+        assert_eq!(auth.verify("PROPFIND", "/docs/a%20b.txt", Some(&wrong)), AuthResult::Denied);
+        assert_eq!(auth.verify("PROPFIND", "/docs/a%20b.txt", None), AuthResult::Denied);
+    }
+
+    #[test]
+    fn test_nonces_expire_and_cannot_be_forged() {
+        let auth = auth();
+
+        let old = auth.make_nonce(unix_now() - NONCE_LIFETIME_SECS - 10);
+        let header = client_header(&old, "secret", "GET", "/");
+        assert_eq!(auth.verify("GET", "/", Some(&header)), AuthResult::Stale);
+
+        let forged = format!("{:x}.{}", unix_now(), "0".repeat(32));
+        let header = client_header(&forged, "secret", "GET", "/");
+        assert_eq!(auth.verify("GET", "/", Some(&header)), AuthResult::Denied);
+    }
+
+    #[test]
+    fn test_password_hash_format() {
+        assert!(DigestAuth::new("admin", "secret", "WolfDisk").is_err());
+        assert!(DigestAuth::new("admin", "sha256:abc", "WolfDisk").is_err());
+    }
+}
@@ -0,0 +1,11 @@
+//! WebDAV gateway for WolfDisk
+//!
+//! Serves the filesystem over WebDAV (RFC 4918) so it can be mounted with
+//! davfs2, Windows Explorer or macOS Finder, or opened from office apps,
+//! on machines that cannot use FUSE.
+
+pub mod server;
+pub mod auth;
+
+pub use server::WebDavServer;
+pub use auth::DigestAuth;
@@ -0,0 +1,896 @@
+//! WebDAV server for WolfDisk
+//!
+//! Maps WebDAV methods to WolfDisk operations:
+//! - PROPFIND → file index lookup (Depth 0, 1 or infinity)
+//! - GET/HEAD → chunk store read, from the leader if the chunks are not local
+//! - PUT → CreateFile (or truncating SetAttr) followed by WriteRequests
+//! - DELETE → DeleteFile/DeleteDir, recursively for collections
+//! - MKCOL → CreateDir
+//! - MOVE → RenameFile
+//! - COPY → CopyFile (the copy shares the source's chunks)
+//! - LOCK/UNLOCK/PROPPATCH → accepted but not enforced or stored, which is
+//!   enough for clients (davfs2, Office, Finder) that insist on them
+//!
+//! Writes go to the leader exactly like FUSE writes do, so WebDAV changes
+//! replicate the same way. On the leader they run through the local peer
+//! message handler.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use tokio::net::TcpListener;
+use tracing::{debug, info, warn};
+
+use crate::cluster::ClusterManager;
+use crate::network::peer::PeerManager;
+use crate::network::protocol::*;
+use crate::storage::{ChunkStore, FileEntry, FileIndex, InodeTable};
+use super::auth::{AuthResult, DigestAuth};
+
+/// Largest PUT body accepted (same limit as S3 PutObject)
+const MAX_PUT_SIZE: usize = 512 * 1024 * 1024;
+
+/// Bytes per WriteRequest/ReadRequest sent to the leader
+const TRANSFER_PIECE: usize = 4 * 1024 * 1024;
+
+/// How long clients are told their (unenforced) locks last
+const LOCK_TIMEOUT_SECS: u64 = 3600;
+
+/// Shared state for the WebDAV server
+#[derive(Clone)]
+pub struct WebDavState {
+    pub file_index: Arc<RwLock<FileIndex>>,
+    pub chunk_store: Arc<ChunkStore>,
+    pub inode_table: Arc<RwLock<InodeTable>>,
+    pub next_inode: Arc<RwLock<u64>>,
+    pub cluster: Arc<ClusterManager>,
+    pub peer_manager: Arc<PeerManager>,
+    pub auth: Option<Arc<DigestAuth>>,
+}
+
+/// WebDAV server that runs alongside (or instead of) the FUSE mount
+pub struct WebDavServer {
+    bind_addr: String,
+    state: WebDavState,
+}
+
+impl WebDavServer {
+    /// Create a new WebDAV server
+    pub fn new(
+        bind_addr: String,
+        file_index: Arc<RwLock<FileIndex>>,
+        chunk_store: Arc<ChunkStore>,
+        inode_table: Arc<RwLock<InodeTable>>,
+        next_inode: Arc<RwLock<u64>>,
+        cluster: Arc<ClusterManager>,
+        peer_manager: Arc<PeerManager>,
+    ) -> Self {
+        let state = WebDavState {
+            file_index,
+            chunk_store,
+            inode_table,
+            next_inode,
+            cluster,
+            peer_manager,
+            auth: None,
+        };
+
+        Self { bind_addr, state }
+    }
+
+    /// Require digest authentication
+    pub fn with_auth(mut self, auth: Option<DigestAuth>) -> Self {
+        self.state.auth = auth.map(Arc::new);
+        self
+    }
+
+    /// Start the WebDAV server (call from a tokio runtime)
+    pub async fn run(self) -> std::io::Result<()> {
+        // WebDAV routing is by method on any path
+        let app = Router::new()
+            .fallback(handle)
+            .with_state(self.state);
+
+        info!("WebDAV gateway listening on {}", self.bind_addr);
+
+        let listener = TcpListener::bind(&self.bind_addr).await?;
+        axum::serve(listener, app).await?;
+
+        Ok(())
+    }
+}
+
+// ─── Dispatch ────────────────────────────────────────────────────────────────
+
+async fn handle(State(state): State<WebDavState>, request: Request<Body>) -> Response {
+    let method = request.method().as_str().to_string();
+    let headers = request.headers().clone();
+
+    if let Some(ref auth) = state.auth {
+        let uri = request.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+        let authorization = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+        match auth.verify(&method, uri, authorization) {
+            AuthResult::Ok => {}
+            result => return unauthorized(auth, result == AuthResult::Stale),
+        }
+    }
+
+    let Some(path) = index_path(request.uri().path()) else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    debug!("WebDAV {} {:?}", method, path);
+
+    match method.as_str() {
+        "OPTIONS" => options(),
+        "PROPFIND" => propfind(&state, &path, &headers),
+        "PROPPATCH" => proppatch(&state, &path),
+        "GET" => get(&state, &path, true).await,
+        "HEAD" => get(&state, &path, false).await,
+        "PUT" => match axum::body::to_bytes(request.into_body(), MAX_PUT_SIZE).await {
+            Ok(body) => put(&state, &path, &body).await,
+            Err(_) => status(StatusCode::PAYLOAD_TOO_LARGE),
+        },
+        "DELETE" => delete(&state, &path).await,
+        "MKCOL" => match axum::body::to_bytes(request.into_body(), 64 * 1024).await {
+            Ok(body) if body.is_empty() => mkcol(&state, &path).await,
+            _ => status(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        },
+        "MOVE" => transfer(&state, &path, &headers, true).await,
+        "COPY" => transfer(&state, &path, &headers, false).await,
+        "LOCK" => lock(&path, &headers),
+        "UNLOCK" => status(StatusCode::NO_CONTENT),
+        _ => status(StatusCode::METHOD_NOT_ALLOWED),
+    }
+}
+
+// ─── Read operations ─────────────────────────────────────────────────────────
+
+/// OPTIONS → advertise class 1 and 2 (locking) compliance
+fn options() -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("DAV", "1, 2")
+        .header("MS-Author-Via", "DAV")
+        .header(
+            header::ALLOW,
+            "OPTIONS, PROPFIND, PROPPATCH, GET, HEAD, PUT, DELETE, MKCOL, MOVE, COPY, LOCK, UNLOCK",
+        )
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// PROPFIND → properties of a resource and, depending on Depth, its children
+fn propfind(state: &WebDavState, path: &Path, headers: &HeaderMap) -> Response {
+    let depth = headers.get("Depth").and_then(|v| v.to_str().ok()).unwrap_or("infinity");
+
+    let index = state.file_index.read().unwrap();
+    let Some(entry) = lookup(&index, path) else {
+        return status(StatusCode::NOT_FOUND);
+    };
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n");
+    xml.push_str(&propfind_response(path, &entry));
+
+    if entry.is_dir && depth != "0" {
+        let mut children: Vec<_> = index
+            .iter()
+            .filter(|(p, e)| e.symlink_target.is_none() && p.as_path() != path)
+            .filter(|(p, _)| {
+                if depth == "1" {
+                    p.parent() == Some(path)
+                } else {
+                    p.starts_with(path)
+                }
+            })
+            .collect();
+        children.sort_by(|a, b| a.0.cmp(b.0));
+        for (child_path, child) in children {
+            xml.push_str(&propfind_response(child_path, child));
+        }
+    }
+    xml.push_str("</D:multistatus>\n");
+
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(xml))
+        .unwrap()
+}
+
+/// One `<D:response>` element of a multistatus body
+fn propfind_response(path: &Path, entry: &FileEntry) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let mut props = format!(
+        "<D:displayname>{}</D:displayname>\
+         <D:getlastmodified>{}</D:getlastmodified>\
+         <D:creationdate>{}</D:creationdate>",
+        xml_escape(&name),
+        http_date(entry.modified),
+        chrono::DateTime::<chrono::Utc>::from(entry.created).to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    );
+    if entry.is_dir {
+        props.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        props.push_str(&format!(
+            "<D:resourcetype/>\
+             <D:getcontentlength>{}</D:getcontentlength>\
+             <D:getcontenttype>application/octet-stream</D:getcontenttype>\
+             <D:getetag>{}</D:getetag>",
+            entry.size,
+            xml_escape(&etag(entry)),
+        ));
+    }
+
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>{}</D:prop>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+        xml_escape(&href(path, entry.is_dir)),
+        props
+    )
+}
+
+/// PROPPATCH → report success without storing anything (clients such as
+/// Windows use it to set timestamps and fail the upload if it is refused)
+fn proppatch(state: &WebDavState, path: &Path) -> Response {
+    if lookup(&state.file_index.read().unwrap(), path).is_none() {
+        return status(StatusCode::NOT_FOUND);
+    }
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n\
+         <D:response><D:href>{}</D:href><D:propstat><D:prop/>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n</D:multistatus>\n",
+        xml_escape(&href(path, false))
+    );
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(xml))
+        .unwrap()
+}
+
+/// GET/HEAD → file contents, or a plain HTML listing for collections
+async fn get(state: &WebDavState, path: &Path, with_body: bool) -> Response {
+    let Some(entry) = lookup(&state.file_index.read().unwrap(), path) else {
+        return status(StatusCode::NOT_FOUND);
+    };
+
+    if entry.is_dir {
+        return listing(state, path);
+    }
+
+    let builder = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::ETAG, etag(&entry))
+        .header(header::LAST_MODIFIED, http_date(entry.modified));
+    if !with_body {
+        return builder
+            .header(header::CONTENT_LENGTH, entry.size.to_string())
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    match read_file(state, path, entry).await {
+        Ok(data) => builder.body(Body::from(data)).unwrap(),
+        Err(e) => {
+            warn!("WebDAV GET {:?} failed: {}", path, e);
+            status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Read a whole file from local chunks, falling back to the leader for
+/// chunks this node does not hold (clients, or a write not yet replicated)
+async fn read_file(state: &WebDavState, path: &Path, entry: FileEntry) -> Result<Vec<u8>, String> {
+    let state = state.clone();
+    let path_str = path.to_string_lossy().to_string();
+
+    tokio::task::spawn_blocking(move || {
+        let size = entry.size as usize;
+        // A file extended by truncate isn't covered either, but the leader
+        // holds everything there is and zero-fills the rest
+        let covered = entry.chunks.iter().map(|c| c.offset + c.size as u64).max().unwrap_or(0) >= entry.size;
+        if covered || state.cluster.is_leader() {
+            match state.chunk_store.read(&entry.chunks, 0, size) {
+                Ok(mut data) => {
+                    ChunkStore::fill_to_size(&mut data, entry.size, 0, size);
+                    return Ok(data);
+                }
+                Err(e) => debug!("Local read of {} failed, asking the leader: {}", path_str, e),
+            }
+        }
+        if state.cluster.is_leader() {
+            return Err("chunks missing on the leader".to_string());
+        }
+
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let msg = Message::ReadRequest(ReadRequestMsg {
+                path: path_str.clone(),
+                offset: data.len() as u64,
+                size: (size - data.len()).min(TRANSFER_PIECE) as u32,
+            });
+            match state.peer_manager.request_leader(&state.cluster, &msg) {
+                Ok(Message::ClientResponse(ClientResponseMsg { success: true, data: Some(piece), .. })) => {
+                    if piece.is_empty() {
+                        break;
+                    }
+                    data.extend_from_slice(&piece);
+                }
+                Ok(Message::ClientResponse(resp)) => return Err(resp.error.unwrap_or_default()),
+                Ok(_) => return Err("unexpected response from leader".to_string()),
+                Err(errno) => return Err(format!("leader unreachable (errno {})", errno)),
+            }
+        }
+        Ok(data)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Minimal HTML directory listing for browsers
+fn listing(state: &WebDavState, path: &Path) -> Response {
+    let index = state.file_index.read().unwrap();
+    let mut children: Vec<_> = index
+        .iter()
+        .filter(|(p, e)| e.symlink_target.is_none() && p.parent() == Some(path) && p.as_path() != path)
+        .map(|(p, e)| (p.clone(), e.is_dir, e.size))
+        .collect();
+    drop(index);
+    children.sort();
+
+    let title = xml_escape(&href(path, true));
+    let mut html = format!("<!DOCTYPE html>\n<html><head><title>{0}</title></head><body><h1>{0}</h1><ul>\n", title);
+    if !path.as_os_str().is_empty() {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for (child, is_dir, size) in children {
+        let name = child.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let suffix = if is_dir { "/".to_string() } else { format!(" ({} bytes)", size) };
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}{}</a></li>\n",
+            xml_escape(&href(&child, is_dir)),
+            xml_escape(&name),
+            xml_escape(&suffix)
+        ));
+    }
+    html.push_str("</ul></body></html>\n");
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(html))
+        .unwrap()
+}
+
+// ─── Write operations ────────────────────────────────────────────────────────
+
+/// PUT → create or replace a file
+async fn put(state: &WebDavState, path: &Path, body: &[u8]) -> Response {
+    if path.as_os_str().is_empty() {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let existing = {
+        let index = state.file_index.read().unwrap();
+        if !parent_exists(&index, path) {
+            return status(StatusCode::CONFLICT);
+        }
+        lookup(&index, path)
+    };
+    let path_str = path.to_string_lossy().to_string();
+
+    let start = match existing {
+        Some(ref e) if e.is_dir => return status(StatusCode::METHOD_NOT_ALLOWED),
+        Some(_) => Message::SetAttr(SetAttrMsg {
+            path: path_str.clone(),
+            permissions: None,
+            uid: None,
+            gid: None,
+            size: Some(0),
+            modified_ms: None,
+        }),
+        None => Message::CreateFile(CreateFileMsg {
+            path: path_str.clone(),
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+        }),
+    };
+    if let Err(resp) = submit(state, start).await {
+        return resp;
+    }
+
+    for (i, piece) in body.chunks(TRANSFER_PIECE).enumerate() {
+        let msg = Message::WriteRequest(WriteRequestMsg {
+            path: path_str.clone(),
+            offset: (i * TRANSFER_PIECE) as u64,
+            data: piece.to_vec(),
+        });
+        if let Err(resp) = submit(state, msg).await {
+            return resp;
+        }
+    }
+
+    // Contents come from the leader until its sync arrives
+    let mut entry = existing.clone().unwrap_or_else(|| new_entry(false, 0o644));
+    entry.size = body.len() as u64;
+    entry.chunks.clear();
+    entry.modified = SystemTime::now();
+    mirror(state, &[], vec![(path.to_path_buf(), entry)]);
+
+    info!("WebDAV PUT {} ({} bytes)", path_str, body.len());
+    status(if existing.is_some() { StatusCode::NO_CONTENT } else { StatusCode::CREATED })
+}
+
+/// DELETE → remove a file, or a collection and everything in it
+async fn delete(state: &WebDavState, path: &Path) -> Response {
+    if path.as_os_str().is_empty() {
+        return status(StatusCode::FORBIDDEN);
+    }
+    if !state.file_index.read().unwrap().contains(path) {
+        return status(StatusCode::NOT_FOUND);
+    }
+
+    match remove_tree(state, path).await {
+        Ok(()) => {
+            info!("WebDAV DELETE {:?}", path);
+            status(StatusCode::NO_CONTENT)
+        }
+        Err(resp) => resp,
+    }
+}
+
+/// Delete `path` and, if it is a directory, everything under it: files
+/// first, then directories deepest first (the leader only removes empty ones)
+async fn remove_tree(state: &WebDavState, path: &Path) -> Result<(), Response> {
+    let (mut files, mut dirs) = (Vec::new(), Vec::new());
+    {
+        let index = state.file_index.read().unwrap();
+        for (p, e) in index.iter().filter(|(p, _)| p.starts_with(path)) {
+            if e.is_dir {
+                dirs.push(p.clone());
+            } else {
+                files.push(p.clone());
+            }
+        }
+    }
+    dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+
+    let mut removed = Vec::new();
+    for file in files {
+        submit(state, Message::DeleteFile(DeleteFileMsg { path: file.to_string_lossy().to_string() })).await?;
+        removed.push(file);
+    }
+    for dir in dirs {
+        submit(state, Message::DeleteDir(DeleteDirMsg { path: dir.to_string_lossy().to_string() })).await?;
+        removed.push(dir);
+    }
+    mirror(state, &removed, Vec::new());
+    Ok(())
+}
+
+/// MKCOL → create a directory
+async fn mkcol(state: &WebDavState, path: &Path) -> Response {
+    {
+        let index = state.file_index.read().unwrap();
+        if path.as_os_str().is_empty() || index.contains(path) {
+            return status(StatusCode::METHOD_NOT_ALLOWED);
+        }
+        if !parent_exists(&index, path) {
+            return status(StatusCode::CONFLICT);
+        }
+    }
+
+    if let Err(resp) = create_dir(state, path, 0o755).await {
+        return resp;
+    }
+    info!("WebDAV MKCOL {:?}", path);
+    status(StatusCode::CREATED)
+}
+
+async fn create_dir(state: &WebDavState, path: &Path, mode: u32) -> Result<(), Response> {
+    submit(
+        state,
+        Message::CreateDir(CreateDirMsg {
+            path: path.to_string_lossy().to_string(),
+            mode,
+            uid: 0,
+            gid: 0,
+        }),
+    )
+    .await?;
+    mirror(state, &[], vec![(path.to_path_buf(), new_entry(true, mode))]);
+    Ok(())
+}
+
+/// MOVE/COPY → rename or copy a file or collection to the Destination header
+async fn transfer(state: &WebDavState, from: &Path, headers: &HeaderMap, is_move: bool) -> Response {
+    let Some(to) = headers
+        .get("Destination")
+        .and_then(|v| v.to_str().ok())
+        .and_then(destination_path)
+    else {
+        return status(StatusCode::BAD_REQUEST);
+    };
+    let overwrite = headers.get("Overwrite").and_then(|v| v.to_str().ok()) != Some("F");
+    let depth_zero = headers.get("Depth").and_then(|v| v.to_str().ok()) == Some("0");
+
+    if from.as_os_str().is_empty() || to.as_os_str().is_empty() || to.starts_with(from) {
+        return status(StatusCode::FORBIDDEN);
+    }
+
+    let (source, dest_exists) = {
+        let index = state.file_index.read().unwrap();
+        let Some(source) = lookup(&index, from) else {
+            return status(StatusCode::NOT_FOUND);
+        };
+        if !parent_exists(&index, &to) {
+            return status(StatusCode::CONFLICT);
+        }
+        (source, index.contains(&to))
+    };
+
+    if dest_exists {
+        if !overwrite {
+            return status(StatusCode::PRECONDITION_FAILED);
+        }
+        if let Err(resp) = remove_tree(state, &to).await {
+            return resp;
+        }
+    }
+
+    let result = if !source.is_dir {
+        transfer_file(state, from, &to, &source, is_move).await
+    } else {
+        transfer_dir(state, from, &to, &source, is_move, depth_zero && !is_move).await
+    };
+    if let Err(resp) = result {
+        return resp;
+    }
+
+    info!("WebDAV {} {:?} -> {:?}", if is_move { "MOVE" } else { "COPY" }, from, to);
+    status(if dest_exists { StatusCode::NO_CONTENT } else { StatusCode::CREATED })
+}
+
+async fn transfer_file(
+    state: &WebDavState,
+    from: &Path,
+    to: &Path,
+    entry: &FileEntry,
+    is_move: bool,
+) -> Result<(), Response> {
+    let from_path = from.to_string_lossy().to_string();
+    let to_path = to.to_string_lossy().to_string();
+    if is_move {
+        submit(state, Message::RenameFile(RenameFileMsg { from_path, to_path })).await?;
+        mirror(state, &[from.to_path_buf()], vec![(to.to_path_buf(), entry.clone())]);
+    } else {
+        submit(state, Message::CopyFile(CopyFileMsg { from_path, to_path })).await?;
+        mirror(state, &[], vec![(to.to_path_buf(), entry.clone())]);
+    }
+    Ok(())
+}
+
+/// Recreate a directory tree under `to`, moving or copying its files, then
+/// remove the source directories when moving
+async fn transfer_dir(
+    state: &WebDavState,
+    from: &Path,
+    to: &Path,
+    entry: &FileEntry,
+    is_move: bool,
+    only_collection: bool,
+) -> Result<(), Response> {
+    create_dir(state, to, entry.permissions).await?;
+    if only_collection {
+        return Ok(());
+    }
+
+    let mut descendants: Vec<(PathBuf, FileEntry)> = {
+        let index = state.file_index.read().unwrap();
+        index
+            .iter()
+            .filter(|(p, _)| p.starts_with(from) && p.as_path() != from)
+            .map(|(p, e)| (p.clone(), e.clone()))
+            .collect()
+    };
+    // Parents sort before their children
+    descendants.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut moved_dirs = Vec::new();
+    for (path, child) in &descendants {
+        let Ok(relative) = path.strip_prefix(from) else { continue };
+        let target = to.join(relative);
+        if child.is_dir {
+            create_dir(state, &target, child.permissions).await?;
+            moved_dirs.push(path.clone());
+        } else {
+            transfer_file(state, path, &target, child, is_move).await?;
+        }
+    }
+
+    if is_move {
+        moved_dirs.push(from.to_path_buf());
+        moved_dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+        for dir in &moved_dirs {
+            submit(state, Message::DeleteDir(DeleteDirMsg { path: dir.to_string_lossy().to_string() })).await?;
+        }
+        mirror(state, &moved_dirs, Vec::new());
+    }
+    Ok(())
+}
+
+/// LOCK → hand out a lock token without enforcing it
+fn lock(path: &Path, headers: &HeaderMap) -> Response {
+    let token = headers
+        .get("If")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split('<').nth(1))
+        .and_then(|v| v.split('>').next())
+        .filter(|t| t.starts_with("opaquelocktoken:"))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("opaquelocktoken:{}", uuid::Uuid::new_v4()));
+
+    let xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>\
+         <D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:depth>infinity</D:depth><D:timeout>Second-{}</D:timeout>\
+         <D:locktoken><D:href>{}</D:href></D:locktoken>\
+         <D:lockroot><D:href>{}</D:href></D:lockroot>\
+         </D:activelock></D:lockdiscovery></D:prop>\n",
+        LOCK_TIMEOUT_SECS,
+        xml_escape(&token),
+        xml_escape(&href(path, false))
+    );
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+        .header("Lock-Token", format!("<{}>", token))
+        .body(Body::from(xml))
+        .unwrap()
+}
+
+// ─── Leader interaction ──────────────────────────────────────────────────────
+
+/// Apply a change through the leader: its own message handler if this node
+/// leads, otherwise over the network
+async fn submit(state: &WebDavState, msg: Message) -> Result<(), Response> {
+    let cluster = state.cluster.clone();
+    let peer_manager = state.peer_manager.clone();
+
+    let response = tokio::task::spawn_blocking(move || {
+        if cluster.is_leader() {
+            peer_manager.handle_local(msg).ok_or(libc::EIO)
+        } else {
+            peer_manager.request_leader(&cluster, &msg)
+        }
+    })
+    .await
+    .map_err(|_| status(StatusCode::INTERNAL_SERVER_ERROR))?;
+
+    let error = match response {
+        Ok(Message::FileOpResponse(FileOpResponseMsg { success: true, .. }))
+        | Ok(Message::ClientResponse(ClientResponseMsg { success: true, .. })) => return Ok(()),
+        Ok(Message::FileOpResponse(resp)) => resp.error.unwrap_or_default(),
+        Ok(Message::ClientResponse(resp)) => resp.error.unwrap_or_default(),
+        Ok(_) => "unexpected response from leader".to_string(),
+        Err(libc::ENOENT) => return Err(status(StatusCode::SERVICE_UNAVAILABLE)),
+        Err(_) => return Err(status(StatusCode::BAD_GATEWAY)),
+    };
+
+    warn!("WebDAV: leader rejected change: {}", error);
+    Err(status(if error.starts_with("Quota exceeded") {
+        StatusCode::INSUFFICIENT_STORAGE
+    } else {
+        StatusCode::CONFLICT
+    }))
+}
+
+/// Reflect a change the leader accepted in the local index until its sync
+/// arrives, so clients see their own writes. The leader's handler has
+/// already applied it there.
+fn mirror(state: &WebDavState, removed: &[PathBuf], added: Vec<(PathBuf, FileEntry)>) {
+    if state.cluster.is_leader() {
+        return;
+    }
+
+    // Lock ordering: Inode -> Index
+    let mut inode_tbl = state.inode_table.write().unwrap();
+    let mut index = state.file_index.write().unwrap();
+    for path in removed {
+        index.unlink(path);
+        inode_tbl.remove_path(path);
+    }
+    for (path, entry) in added {
+        if inode_tbl.get_inode(&path).is_none() {
+            let mut next_ino = state.next_inode.write().unwrap();
+            inode_tbl.insert(*next_ino, path.clone());
+            *next_ino += 1;
+        }
+        index.insert(path, entry);
+    }
+}
+
+// ─── Helpers ─────────────────────────────────────────────────────────────────
+
+/// Entry for a path; the root is a directory that is not in the index.
+/// Symlinks are not exposed over WebDAV.
+fn lookup(index: &FileIndex, path: &Path) -> Option<FileEntry> {
+    if path.as_os_str().is_empty() {
+        return Some(new_entry(true, 0o755));
+    }
+    index.get(path).filter(|e| e.symlink_target.is_none()).cloned()
+}
+
+fn parent_exists(index: &FileIndex, path: &Path) -> bool {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => index.get(parent).is_some_and(|e| e.is_dir),
+        _ => true,
+    }
+}
+
+fn new_entry(is_dir: bool, permissions: u32) -> FileEntry {
+    let now = SystemTime::now();
+    FileEntry {
+        size: 0,
+        is_dir,
+        permissions,
+        uid: 0,
+        gid: 0,
+        modified: now,
+        created: now,
+        accessed: now,
+        chunks: Vec::new(),
+        symlink_target: None,
+        xattrs: std::collections::HashMap::new(),
+        version_id: None,
+        versions: Vec::new(),
+        hard_link_count: 1,
+        link_id: None,
+    }
+}
+
+/// Index path for a request path: percent-decoded and relative, with no `..`
+fn index_path(uri_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(uri_path)?;
+    let mut path = PathBuf::new();
+    for part in decoded.split('/') {
+        match part {
+            "" | "." => continue,
+            ".." => return None,
+            part => path.push(part),
+        }
+    }
+    Some(path)
+}
+
+/// Index path for a Destination header, which may be an absolute URL
+fn destination_path(destination: &str) -> Option<PathBuf> {
+    let path = match destination.split_once("://") {
+        Some((_, rest)) => rest.find('/').map(|i| &rest[i..]).unwrap_or("/"),
+        None => destination,
+    };
+    index_path(path.split(['?', '#']).next().unwrap_or(path))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// URL path for an index path, with a trailing slash for collections
+fn href(path: &Path, is_dir: bool) -> String {
+    let mut href = String::new();
+    for part in path.iter() {
+        href.push('/');
+        for b in part.to_string_lossy().bytes() {
+            if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+                href.push(b as char);
+            } else {
+                href.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    if is_dir || href.is_empty() {
+        href.push('/');
+    }
+    href
+}
+
+fn etag(entry: &FileEntry) -> String {
+    let modified = entry.modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("\"{:x}-{:x}\"", entry.size, modified.as_nanos())
+}
+
+fn http_date(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn status(code: StatusCode) -> Response {
+    Response::builder().status(code).body(Body::empty()).unwrap()
+}
+
+fn unauthorized(auth: &DigestAuth, stale: bool) -> Response {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(header::WWW_AUTHENTICATE, auth.challenge(stale))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_paths() {
+        assert_eq!(index_path("/"), Some(PathBuf::new()));
+        assert_eq!(index_path("/docs/a%20b.txt"), Some(PathBuf::from("docs/a b.txt")));
+        assert_eq!(index_path("/docs/"), Some(PathBuf::from("docs")));
+        assert_eq!(index_path("/docs/../etc/passwd"), None);
+        assert_eq!(index_path("/bad%2"), None);
+
+        assert_eq!(
+            destination_path("http://node1:8008/docs/new%20name.txt"),
+            Some(PathBuf::from("docs/new name.txt"))
+        );
+        assert_eq!(destination_path("/docs/x"), Some(PathBuf::from("docs/x")));
+    }
+
+    #[test]
+    fn test_href_round_trips() {
+        let path = PathBuf::from("Reports 2026/Q1 & Q2.xlsx");
+        let encoded = href(&path, false);
+        assert_eq!(encoded, "/Reports%202026/Q1%20%26%20Q2.xlsx");
+        assert_eq!(index_path(&encoded), Some(path));
+        assert_eq!(href(Path::new("docs"), true), "/docs/");
+        assert_eq!(href(Path::new(""), true), "/");
+    }
+
+    #[test]
+    fn test_propfind_entry() {
+        let mut entry = new_entry(false, 0o644);
+        entry.size = 42;
+        let xml = propfind_response(Path::new("docs/<notes>.txt"), &entry);
+        assert!(xml.contains("<D:href>/docs/%3Cnotes%3E.txt</D:href>"));
+        assert!(xml.contains("<D:displayname>&lt;notes&gt;.txt</D:displayname>"));
+        assert!(xml.contains("<D:getcontentlength>42</D:getcontentlength>"));
+        assert!(xml.contains("<D:resourcetype/>"));
+
+        let xml = propfind_response(Path::new("docs"), &new_entry(true, 0o755));
+        assert!(xml.contains("<D:href>/docs/</D:href>"));
+        assert!(xml.contains("<D:collection/>"));
+    }
+}
@@ -0,0 +1,483 @@
+//! Packet capture to PCAP files
+//!
+//! `wolfnet capture` asks the running daemon to record tunnel traffic by
+//! dropping a `CaptureRequest` at `CAPTURE_REQUEST_PATH`, which the daemon
+//! picks up within a second. Plaintext IP packets are recorded as they leave
+//! and enter the TUN interface (after decryption, before encryption), in a
+//! `LINKTYPE_RAW` file that Wireshark and tcpdump read directly.
+//!
+//! With `encrypted`, the WolfNet UDP datagrams on the wire are recorded too.
+//! One PCAP file has a single link type, so the file becomes
+//! `LINKTYPE_ETHERNET`: plaintext packets get a fake Ethernet header, and
+//! datagrams a fake Ethernet, IPv4 and UDP header between the peer's endpoint
+//! and our listen port.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Where `wolfnet capture` leaves its request for the daemon
+pub const CAPTURE_REQUEST_PATH: &str = "/var/run/wolfnet/capture.json";
+
+/// Raw IPv4/IPv6 packets, as read from TUN
+pub const LINKTYPE_RAW: u32 = 101;
+/// Ethernet frames
+pub const LINKTYPE_ETHERNET: u32 = 1;
+
+/// Microsecond-resolution PCAP, native byte order
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+/// Longest record kept
+const SNAPLEN: u32 = 262_144;
+const GLOBAL_HEADER_LEN: u64 = 24;
+const RECORD_HEADER_LEN: u64 = 16;
+
+/// MAC addresses of the fake Ethernet headers (locally administered)
+const LOCAL_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+const REMOTE_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+/// A capture for the daemon to run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureRequest {
+    /// PCAP file to write (absolute)
+    pub output: PathBuf,
+    pub duration_secs: u64,
+    /// Only packets to or from this WolfNet peer
+    pub peer: Option<Ipv4Addr>,
+    /// Also record the encrypted UDP datagrams
+    pub encrypted: bool,
+    /// Stop once the file reaches this many bytes
+    pub max_size: u64,
+}
+
+impl CaptureRequest {
+    /// Leave the request for the daemon
+    pub fn submit(&self) -> io::Result<()> {
+        let path = Path::new(CAPTURE_REQUEST_PATH);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        std::fs::write(path, json)
+    }
+
+    /// Whether the daemon has picked up the submitted request
+    pub fn taken() -> bool {
+        !Path::new(CAPTURE_REQUEST_PATH).exists()
+    }
+
+    /// Take a pending request, if there is one (daemon side)
+    pub fn take() -> Option<Result<Self, String>> {
+        let path = Path::new(CAPTURE_REQUEST_PATH);
+        let content = std::fs::read_to_string(path).ok()?;
+        let _ = std::fs::remove_file(path);
+        Some(serde_json::from_str(&content).map_err(|e| format!("invalid capture request: {}", e)))
+    }
+}
+
+/// Parse a size such as `50M`, `512K`, `1G` or a plain byte count
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let (digits, multiplier) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&size[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits.trim().parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{}' (expected e.g. 50M)", size))
+}
+
+/// Writes a libpcap file: the global header, then one record per packet
+pub struct PcapWriter<W: Write> {
+    out: W,
+    written: u64,
+    max_size: u64,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Write the global header for `link_type`. Records that would take the
+    /// file past `max_size` bytes are refused.
+    pub fn new(mut out: W, link_type: u32, max_size: u64) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(GLOBAL_HEADER_LEN as usize);
+        header.extend_from_slice(&PCAP_MAGIC.to_ne_bytes());
+        header.extend_from_slice(&2u16.to_ne_bytes()); // version 2.4
+        header.extend_from_slice(&4u16.to_ne_bytes());
+        header.extend_from_slice(&0i32.to_ne_bytes()); // timestamps in UTC
+        header.extend_from_slice(&0u32.to_ne_bytes()); // sigfigs
+        header.extend_from_slice(&SNAPLEN.to_ne_bytes());
+        header.extend_from_slice(&link_type.to_ne_bytes());
+        out.write_all(&header)?;
+        Ok(Self { out, written: GLOBAL_HEADER_LEN, max_size })
+    }
+
+    /// Write one packet captured at `timestamp`. Returns false, writing
+    /// nothing, if the file would grow past its maximum size.
+    pub fn write_packet(&mut self, timestamp: SystemTime, packet: &[u8]) -> io::Result<bool> {
+        let captured = &packet[..packet.len().min(SNAPLEN as usize)];
+        if self.written + RECORD_HEADER_LEN + captured.len() as u64 > self.max_size {
+            return Ok(false);
+        }
+        let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut header = [0u8; RECORD_HEADER_LEN as usize];
+        header[0..4].copy_from_slice(&(since_epoch.as_secs() as u32).to_ne_bytes());
+        header[4..8].copy_from_slice(&since_epoch.subsec_micros().to_ne_bytes());
+        header[8..12].copy_from_slice(&(captured.len() as u32).to_ne_bytes());
+        header[12..16].copy_from_slice(&(packet.len() as u32).to_ne_bytes());
+        self.out.write_all(&header)?;
+        self.out.write_all(captured)?;
+        self.written += RECORD_HEADER_LEN + captured.len() as u64;
+        Ok(true)
+    }
+
+    /// Bytes in the file so far
+    pub fn bytes_written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// A running capture, shared by the packet loop and the UDP socket
+pub struct Capture {
+    writer: Mutex<PcapWriter<BufWriter<File>>>,
+    request: CaptureRequest,
+    deadline: Instant,
+    /// Our UDP listen port, for the fake UDP headers
+    listen_port: u16,
+    /// UDP endpoint of `request.peer`, refreshed by the daemon
+    peer_endpoint: Mutex<Option<SocketAddr>>,
+    packets: AtomicU64,
+    full: AtomicBool,
+}
+
+impl Capture {
+    /// Create the output file and start recording
+    pub fn start(request: CaptureRequest, listen_port: u16) -> io::Result<Self> {
+        let link_type = if request.encrypted { LINKTYPE_ETHERNET } else { LINKTYPE_RAW };
+        let file = BufWriter::new(File::create(&request.output)?);
+        let writer = PcapWriter::new(file, link_type, request.max_size)?;
+        Ok(Self {
+            writer: Mutex::new(writer),
+            deadline: Instant::now() + Duration::from_secs(request.duration_secs),
+            request,
+            listen_port,
+            peer_endpoint: Mutex::new(None),
+            packets: AtomicU64::new(0),
+            full: AtomicBool::new(false),
+        })
+    }
+
+    pub fn request(&self) -> &CaptureRequest {
+        &self.request
+    }
+
+    /// Record a plaintext IP packet exchanged with WolfNet peer `peer`
+    pub fn record_ip(&self, packet: &[u8], peer: Option<Ipv4Addr>) {
+        if let Some(wanted) = self.request.peer {
+            let addresses = ipv4_addresses(packet);
+            let matches = peer == Some(wanted)
+                || addresses.is_some_and(|(src, dst)| src == wanted || dst == wanted);
+            if !matches {
+                return;
+            }
+        }
+        if self.request.encrypted {
+            let mut frame = Vec::with_capacity(14 + packet.len());
+            push_ethernet_header(&mut frame, LOCAL_MAC, LOCAL_MAC);
+            frame.extend_from_slice(packet);
+            self.record(&frame);
+        } else {
+            self.record(packet);
+        }
+    }
+
+    /// Record a WolfNet UDP datagram sent to or received from `remote`.
+    /// Ignored unless the capture asked for encrypted traffic.
+    pub fn record_udp(&self, datagram: &[u8], remote: SocketAddr, outbound: bool) {
+        if !self.request.encrypted {
+            return;
+        }
+        if self.request.peer.is_some() && *self.peer_endpoint.lock().unwrap() != Some(remote) {
+            return;
+        }
+        let SocketAddr::V4(remote) = remote else { return };
+        let local = (Ipv4Addr::UNSPECIFIED, self.listen_port);
+        let remote = (*remote.ip(), remote.port());
+        let (src, dst, src_mac, dst_mac) = if outbound {
+            (local, remote, LOCAL_MAC, REMOTE_MAC)
+        } else {
+            (remote, local, REMOTE_MAC, LOCAL_MAC)
+        };
+
+        let mut frame = Vec::with_capacity(42 + datagram.len());
+        push_ethernet_header(&mut frame, dst_mac, src_mac);
+        push_udp_headers(&mut frame, src, dst, datagram.len());
+        frame.extend_from_slice(datagram);
+        self.record(&frame);
+    }
+
+    fn record(&self, frame: &[u8]) {
+        if self.full.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut writer = self.writer.lock().unwrap();
+        match writer.write_packet(SystemTime::now(), frame) {
+            Ok(true) => { self.packets.fetch_add(1, Ordering::Relaxed); }
+            Ok(false) => self.full.store(true, Ordering::Relaxed),
+            Err(e) => {
+                tracing::warn!("Capture to {} failed: {}", self.request.output.display(), e);
+                self.full.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Set the UDP endpoint of the peer being captured
+    pub fn set_peer_endpoint(&self, endpoint: Option<SocketAddr>) {
+        *self.peer_endpoint.lock().unwrap() = endpoint;
+    }
+
+    /// Whether the duration has passed or the file is full
+    pub fn is_done(&self) -> bool {
+        self.full.load(Ordering::Relaxed) || Instant::now() >= self.deadline
+    }
+
+    /// Flush the file. Returns the packets recorded and the file size.
+    pub fn finish(&self) -> (u64, u64) {
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = writer.flush() {
+            tracing::warn!("Capture to {} failed: {}", self.request.output.display(), e);
+        }
+        (self.packets.load(Ordering::Relaxed), writer.bytes_written())
+    }
+}
+
+/// Source and destination of an IPv4 packet
+fn ipv4_addresses(packet: &[u8]) -> Option<(Ipv4Addr, Ipv4Addr)> {
+    if packet.len() < 20 || packet[0] >> 4 != 4 {
+        return None;
+    }
+    let src = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+    let dst = Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]);
+    Some((src, dst))
+}
+
+fn push_ethernet_header(frame: &mut Vec<u8>, dst: [u8; 6], src: [u8; 6]) {
+    frame.extend_from_slice(&dst);
+    frame.extend_from_slice(&src);
+    frame.extend_from_slice(&0x0800u16.to_be_bytes()); // IPv4
+}
+
+/// IPv4 and UDP headers for a `payload_len`-byte datagram (UDP checksum left
+/// out, which IPv4 allows)
+fn push_udp_headers(frame: &mut Vec<u8>, src: (Ipv4Addr, u16), dst: (Ipv4Addr, u16), payload_len: usize) {
+    let udp_len = (8 + payload_len).min(u16::MAX as usize) as u16;
+    let total_len = (20 + udp_len as usize).min(u16::MAX as usize) as u16;
+    let mut ip = [0u8; 20];
+    ip[0] = 0x45;
+    ip[2..4].copy_from_slice(&total_len.to_be_bytes());
+    ip[8] = 64; // TTL
+    ip[9] = 17; // UDP
+    ip[12..16].copy_from_slice(&src.0.octets());
+    ip[16..20].copy_from_slice(&dst.0.octets());
+    let checksum = ipv4_checksum(&ip);
+    ip[10..12].copy_from_slice(&checksum.to_be_bytes());
+    frame.extend_from_slice(&ip);
+
+    frame.extend_from_slice(&src.1.to_be_bytes());
+    frame.extend_from_slice(&dst.1.to_be_bytes());
+    frame.extend_from_slice(&udp_len.to_be_bytes());
+    frame.extend_from_slice(&0u16.to_be_bytes());
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header.chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_ne_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    /// An IPv4 packet header from `src` to `dst`
+    fn ip_packet(src: [u8; 4], dst: [u8; 4]) -> Vec<u8> {
+        let mut packet = vec![0u8; 28];
+        packet[0] = 0x45;
+        packet[12..16].copy_from_slice(&src);
+        packet[16..20].copy_from_slice(&dst);
+        packet
+    }
+
+    /// A capture into a fresh file under the temp dir
+    fn capture(name: &str, peer: Option<Ipv4Addr>, encrypted: bool) -> Capture {
+        let output = std::env::temp_dir().join(format!("wolfnet-test-{}-{}.pcap", name, std::process::id()));
+        let request = CaptureRequest { output, duration_secs: 60, peer, encrypted, max_size: 1 << 20 };
+        Capture::start(request, 9600).unwrap()
+    }
+
+    /// Finish `capture` and return its records, removing the file
+    fn records(capture: Capture) -> (u32, Vec<Vec<u8>>) {
+        capture.finish();
+        let data = std::fs::read(&capture.request().output).unwrap();
+        std::fs::remove_file(&capture.request().output).unwrap();
+        let mut records = Vec::new();
+        let mut at = GLOBAL_HEADER_LEN as usize;
+        while at < data.len() {
+            let len = u32_at(&data, at + 8) as usize;
+            at += RECORD_HEADER_LEN as usize;
+            records.push(data[at..at + len].to_vec());
+            at += len;
+        }
+        (u32_at(&data, 20), records)
+    }
+
+    #[test]
+    fn test_global_header() {
+        let writer = PcapWriter::new(Vec::new(), LINKTYPE_RAW, 1 << 20).unwrap();
+        let header = &writer.out;
+        assert_eq!(header.len(), 24);
+        assert_eq!(u32_at(header, 0), 0xa1b2_c3d4);
+        assert_eq!(header[4..8], [2u16.to_ne_bytes(), 4u16.to_ne_bytes()].concat());
+        assert_eq!(u32_at(header, 8), 0);
+        assert_eq!(u32_at(header, 12), 0);
+        assert_eq!(u32_at(header, 16), SNAPLEN);
+        assert_eq!(u32_at(header, 20), LINKTYPE_RAW);
+        assert_eq!(writer.bytes_written(), 24);
+    }
+
+    #[test]
+    fn test_record_header() {
+        let mut writer = PcapWriter::new(Vec::new(), LINKTYPE_RAW, 1 << 20).unwrap();
+        let timestamp = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+        assert!(writer.write_packet(timestamp, b"packet").unwrap());
+
+        let record = &writer.out[24..];
+        assert_eq!(u32_at(record, 0), 1_700_000_000);
+        assert_eq!(u32_at(record, 4), 123_456);
+        assert_eq!(u32_at(record, 8), 6);
+        assert_eq!(u32_at(record, 12), 6);
+        assert_eq!(&record[16..], b"packet");
+        assert_eq!(writer.bytes_written(), 24 + 16 + 6);
+    }
+
+    #[test]
+    fn test_long_packets_truncated_to_snaplen() {
+        let mut writer = PcapWriter::new(Vec::new(), LINKTYPE_RAW, 1 << 20).unwrap();
+        let packet = vec![0xAB; SNAPLEN as usize + 100];
+        writer.write_packet(UNIX_EPOCH, &packet).unwrap();
+        assert_eq!(u32_at(&writer.out, 24 + 8), SNAPLEN);
+        assert_eq!(u32_at(&writer.out, 24 + 12), SNAPLEN + 100);
+        assert_eq!(writer.out.len(), 24 + 16 + SNAPLEN as usize);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let mut writer = PcapWriter::new(Vec::new(), LINKTYPE_RAW, 24 + 2 * (16 + 10)).unwrap();
+        assert!(writer.write_packet(UNIX_EPOCH, &[0; 10]).unwrap());
+        assert!(!writer.write_packet(UNIX_EPOCH, &[0; 11]).unwrap(), "would pass the limit");
+        assert!(writer.write_packet(UNIX_EPOCH, &[0; 10]).unwrap());
+        assert!(!writer.write_packet(UNIX_EPOCH, &[0; 1]).unwrap());
+        assert_eq!(writer.out.len() as u64, writer.bytes_written());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("50M"), Ok(50 << 20));
+        assert_eq!(parse_size("512k"), Ok(512 << 10));
+        assert_eq!(parse_size(" 1G "), Ok(1 << 30));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("-5M").is_err());
+        assert!(parse_size("99999999999G").is_err());
+    }
+
+    #[test]
+    fn test_plaintext_capture_filters_by_peer() {
+        let capture = capture("plain", Some(Ipv4Addr::new(10, 0, 10, 2)), false);
+        let to_peer = ip_packet([10, 0, 10, 1], [10, 0, 10, 2]);
+        let to_other = ip_packet([10, 0, 10, 1], [10, 0, 10, 3]);
+        let container = ip_packet([10, 0, 10, 1], [172, 17, 0, 5]);
+        capture.record_ip(&to_peer, Some(Ipv4Addr::new(10, 0, 10, 2)));
+        capture.record_ip(&to_other, Some(Ipv4Addr::new(10, 0, 10, 3)));
+        // Routed through the peer to a subnet behind it
+        capture.record_ip(&container, Some(Ipv4Addr::new(10, 0, 10, 2)));
+        // Datagrams are only recorded for encrypted captures
+        capture.record_udp(b"datagram", "192.0.2.2:9600".parse().unwrap(), true);
+
+        let (link_type, records) = records(capture);
+        assert_eq!(link_type, LINKTYPE_RAW);
+        assert_eq!(records, [to_peer, container]);
+    }
+
+    #[test]
+    fn test_encrypted_capture_frames() {
+        let capture = capture("encrypted", None, true);
+        let packet = ip_packet([10, 0, 10, 1], [10, 0, 10, 2]);
+        capture.record_ip(&packet, None);
+        capture.record_udp(b"datagram", "192.0.2.2:9700".parse().unwrap(), true);
+        capture.record_udp(b"reply", "192.0.2.2:9700".parse().unwrap(), false);
+
+        let (link_type, records) = records(capture);
+        assert_eq!(link_type, LINKTYPE_ETHERNET);
+        assert_eq!(records.len(), 3);
+
+        // Plaintext packets get an Ethernet header only
+        assert_eq!(records[0][12..14], [0x08, 0x00]);
+        assert_eq!(records[0][14..], packet[..]);
+
+        // Datagrams get Ethernet + IPv4 + UDP between the endpoint and our port
+        let frame = &records[1];
+        assert_eq!(frame[0..6], REMOTE_MAC);
+        assert_eq!(frame[6..12], LOCAL_MAC);
+        let ip = &frame[14..34];
+        assert_eq!(ip[0], 0x45);
+        assert_eq!(u16::from_be_bytes([ip[2], ip[3]]), 20 + 8 + 8);
+        assert_eq!(ip[9], 17);
+        assert_eq!(ip[16..20], [192, 0, 2, 2]);
+        assert_eq!(ipv4_checksum(ip), 0, "header checksum verifies");
+        let udp = &frame[34..42];
+        assert_eq!(u16::from_be_bytes([udp[0], udp[1]]), 9600);
+        assert_eq!(u16::from_be_bytes([udp[2], udp[3]]), 9700);
+        assert_eq!(u16::from_be_bytes([udp[4], udp[5]]), 8 + 8);
+        assert_eq!(&frame[42..], b"datagram");
+
+        // Inbound datagrams run the other way
+        let frame = &records[2];
+        assert_eq!(frame[0..6], LOCAL_MAC);
+        assert_eq!(frame[26..30], [192, 0, 2, 2]);
+        assert_eq!(u16::from_be_bytes([frame[34], frame[35]]), 9700);
+        assert_eq!(&frame[42..], b"reply");
+    }
+
+    #[test]
+    fn test_encrypted_capture_filters_by_peer_endpoint() {
+        let capture = capture("endpoint", Some(Ipv4Addr::new(10, 0, 10, 2)), true);
+        let endpoint: SocketAddr = "192.0.2.2:9600".parse().unwrap();
+        capture.record_udp(b"unknown yet", endpoint, true);
+        capture.set_peer_endpoint(Some(endpoint));
+        capture.record_udp(b"peer", endpoint, true);
+        capture.record_udp(b"other", "192.0.2.3:9600".parse().unwrap(), true);
+
+        let (_, records) = records(capture);
+        assert_eq!(records.len(), 1);
+        assert_eq!(&records[0][42..], b"peer");
+    }
+}
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::net::Ipv4Addr;
 
+use crate::obfuscation::Obfuscator;
+
 /// Main configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -17,6 +19,18 @@ pub struct Config {
     /// Configured peers
     #[serde(default)]
     pub peers: Vec<PeerConfig>,
+
+    /// Per-peer forwarding policy, applied to the iptables FORWARD chain
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peer_rules: Vec<PeerRule>,
+
+    /// Split tunneling: which destinations go through the tunnel (`[[route]]`)
+    #[serde(default, rename = "route", skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<SplitRoute>,
+
+    /// Container/VM subnets routed between nodes (`[[subnet_routes]]`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subnet_routes: Vec<SubnetRoute>,
 }
 
 /// Network configuration
@@ -41,13 +55,138 @@ pub struct NetworkConfig {
     #[serde(default)]
     pub gateway: bool,
 
+    /// How to choose among `[[peers]]` marked `is_hub` for traffic that has
+    /// no other route
+    #[serde(default)]
+    pub hub_selection: HubSelection,
+
     /// Enable LAN auto-discovery
     #[serde(default = "default_true")]
     pub discovery: bool,
 
+    /// Advertise and browse for peers with mDNS/DNS-SD (`_wolfnet._udp`)
+    #[serde(default)]
+    pub mdns_discovery: bool,
+
     /// MTU for the TUN interface
     #[serde(default = "default_mtu")]
     pub mtu: u16,
+
+    /// Obfuscate tunnel packets to resist DPI fingerprinting
+    #[serde(default)]
+    pub obfuscation: ObfuscationMode,
+
+    /// Hex-encoded key for XOR obfuscation (must match on every node)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub obfuscation_key: Option<String>,
+
+    /// Mark a peer dead after this many seconds without a packet from it
+    #[serde(default = "default_peer_timeout_secs")]
+    pub peer_timeout_secs: u64,
+
+    /// Packets to hold per subnet while every route to it is down, replayed
+    /// once its host is back (further packets are dropped)
+    #[serde(default = "default_failover_buffer_packets")]
+    pub failover_buffer_packets: usize,
+
+    /// Route a peer through a relay once its direct path loses more than this
+    /// fraction of packets (switches back below 2%)
+    #[serde(default = "default_failover_loss_threshold")]
+    pub failover_loss_threshold: f64,
+
+    /// Warn when a peer's connection quality score (0–100) drops below this
+    #[serde(default = "default_quality_threshold")]
+    pub quality_threshold: u8,
+
+    /// Also restart the handshake with a peer whose quality drops below
+    /// `quality_threshold`, as if it had timed out
+    #[serde(default)]
+    pub quality_reconnect: bool,
+
+    /// Re-probe each peer's path MTU this often (0 = don't probe, keep `mtu`)
+    #[serde(default = "default_mtu_probe_interval_secs")]
+    pub mtu_probe_interval_secs: u64,
+
+    /// Serve the gRPC management API here (e.g. "127.0.0.1:50051"). It can
+    /// add and remove peers without authentication, so keep it on loopback.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_listen: Option<String>,
+
+    /// Relay traffic for peers behind symmetric NAT on UDP `listen_port + 1`
+    /// (gateway nodes only)
+    #[serde(default)]
+    pub turn_server: bool,
+
+    /// Gateway TURN relays (host:port) to allocate on and fall back to when a
+    /// peer can't be reached directly
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turn_servers: Vec<String>,
+
+    /// DNS servers reachable through the tunnel (e.g. "10.0.10.254")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_servers: Vec<String>,
+
+    /// Domains resolved through `dns_servers`; everything else keeps using
+    /// the system resolver
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dns_domains: Vec<String>,
+
+    /// Also run a DNS proxy on 127.0.0.1:5353 that splits queries between
+    /// `dns_servers` and the system resolver by domain
+    #[serde(default)]
+    pub dns_proxy: bool,
+
+    /// Also write each packet sent into the tunnel, in plaintext behind a
+    /// fake Ethernet header, to this interface (e.g. "eth1") for a network tap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_interface: Option<String>,
+
+    /// Also send each packet received from the tunnel, decrypted, to the
+    /// subscribers of an AF_UNIX datagram socket at this path (e.g.
+    /// "/var/run/wolfnet/mirror.sock")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_socket: Option<PathBuf>,
+
+    /// Watch for this machine's addresses changing (roaming between Wi-Fi
+    /// and mobile networks) and resume sessions with peers from the new
+    /// address instead of waiting for them to time out
+    #[serde(default)]
+    pub mobility_support: bool,
+}
+
+/// How the hub for traffic without a direct route is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HubSelection {
+    /// Lowest measured round-trip time
+    #[default]
+    Latency,
+    /// Any reachable hub, kept until it goes offline
+    Random,
+    /// The first reachable hub in config order
+    First,
+}
+
+/// Packet obfuscation mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObfuscationMode {
+    #[default]
+    None,
+    Xor,
+}
+
+/// Where the node's private key is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyBackend {
+    /// `private_key_file` on disk
+    #[default]
+    File,
+    /// A PKCS#11 token (HSM, SoftHSM)
+    Pkcs11,
+    /// A TPM 2.0 (not usable: TPMs have no Curve25519)
+    Tpm2,
 }
 
 /// Security configuration
@@ -56,16 +195,60 @@ pub struct SecurityConfig {
     /// Path to the private key file
     #[serde(default = "default_key_path")]
     pub private_key_file: PathBuf,
+
+    /// Where the private key is kept
+    #[serde(default)]
+    pub key_backend: KeyBackend,
+
+    /// PKCS#11 module (shared library) for `key_backend = "pkcs11"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkcs11_module: Option<PathBuf>,
+
+    /// PKCS#11 slot holding the key
+    #[serde(default)]
+    pub pkcs11_slot: u64,
+
+    /// User PIN (falls back to the WOLFNET_PKCS11_PIN environment variable)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkcs11_pin: Option<String>,
+
+    /// Label of the key pair on the token (generated if missing)
+    #[serde(default = "default_pkcs11_key_label")]
+    pub pkcs11_key_label: String,
+
+    /// Revoked peer public keys, one base64 key per line. Handshakes from
+    /// these peers are ignored and any session with them is dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crl_file: Option<PathBuf>,
+
+    /// Re-read `crl_file` this often (it is also re-read on SIGHUP)
+    #[serde(default = "default_crl_refresh_secs")]
+    pub crl_refresh_secs: u64,
 }
 
 impl Default for SecurityConfig {
     fn default() -> Self {
         Self {
             private_key_file: default_key_path(),
+            key_backend: KeyBackend::File,
+            pkcs11_module: None,
+            pkcs11_slot: 0,
+            pkcs11_pin: None,
+            pkcs11_key_label: default_pkcs11_key_label(),
+            crl_file: None,
+            crl_refresh_secs: default_crl_refresh_secs(),
         }
     }
 }
 
+fn default_pkcs11_key_label() -> String {
+    "wolfnet".to_string()
+}
+
+fn default_crl_refresh_secs() -> u64 {
+    3600
+}
+
 /// Configured peer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PeerConfig {
@@ -80,6 +263,84 @@ pub struct PeerConfig {
 
     /// Optional friendly name
     pub name: Option<String>,
+
+    /// Route traffic with no direct path through this peer
+    #[serde(default)]
+    pub is_hub: bool,
+}
+
+/// Forwarding rule restricting what a peer may reach through this node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerRule {
+    /// WolfNet IP of the peer this rule applies to
+    pub peer_ip: String,
+
+    /// Whether matching traffic is allowed or dropped
+    pub action: RuleAction,
+
+    /// Protocol to match
+    #[serde(default)]
+    pub proto: RuleProto,
+
+    /// Destination port to match (requires tcp/udp; `any` expands to both)
+    pub dest_port: Option<u16>,
+
+    /// Destination network in CIDR notation (e.g. "192.168.1.0/24")
+    pub dest_network: Option<String>,
+}
+
+/// Action taken for traffic matching a peer rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Allow,
+    Deny,
+}
+
+/// Protocol matched by a peer rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleProto {
+    Tcp,
+    Udp,
+    #[default]
+    Any,
+}
+
+/// Route for a destination network, installed by the daemon (`[[route]]`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitRoute {
+    /// Destination network in CIDR notation (e.g. "192.168.100.0/24")
+    pub destination: String,
+
+    /// Whether the destination is reached through the tunnel or the local network
+    pub via: RouteVia,
+}
+
+/// Where a split-tunnel route sends traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouteVia {
+    /// The WolfNet TUN interface
+    Wolfnet,
+    /// The local default gateway, overriding any tunnel route
+    Local,
+}
+
+/// A subnet reached through a WolfNet node (`[[subnet_routes]]`), e.g. a
+/// Docker or LXC bridge network
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubnetRoute {
+    /// Subnet in CIDR notation (e.g. "172.17.0.0/24")
+    pub network: String,
+
+    /// WolfNet IP of the node hosting the subnet; unset means this node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+
+    /// Tell peers about this subnet over peer exchange (local subnets only)
+    #[serde(default)]
+    pub advertise: bool,
 }
 
 fn default_interface() -> String { "wolfnet0".into() }
@@ -87,6 +348,11 @@ fn default_subnet() -> u8 { 24 }
 fn default_port() -> u16 { 9600 }
 fn default_true() -> bool { true }
 fn default_mtu() -> u16 { 1400 }
+fn default_peer_timeout_secs() -> u64 { 60 }
+fn default_failover_buffer_packets() -> usize { 100 }
+fn default_failover_loss_threshold() -> f64 { 0.05 }
+fn default_quality_threshold() -> u8 { 20 }
+fn default_mtu_probe_interval_secs() -> u64 { 300 }
 fn default_key_path() -> PathBuf { PathBuf::from("/etc/wolfnet/private.key") }
 
 /// Status information written by daemon, read by wolfnetctl
@@ -100,6 +366,32 @@ pub struct NodeStatus {
     pub interface: String,
     pub uptime_secs: u64,
     pub peers: Vec<PeerStatus>,
+    /// Subnet routes failed over since startup (`wolfnet_route_failover_total`)
+    #[serde(default)]
+    pub route_failover_total: u64,
+    /// Failed-over routes restored since startup (`wolfnet_route_restore_total`)
+    #[serde(default)]
+    pub route_restore_total: u64,
+    /// WolfNet IP of the hub in use (`wolfnet_active_hub`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_hub: Option<String>,
+    /// Known hubs, configured and learned through PEX
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hubs: Vec<HubStatus>,
+}
+
+/// A hub peer, as shown by `wolfnet hub list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubStatus {
+    pub hostname: String,
+    pub address: String,
+    /// Smoothed round-trip time in microseconds (None if never measured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_us: Option<u64>,
+    /// Answering probes, so it can be selected
+    pub available: bool,
+    /// Currently selected
+    pub active: bool,
 }
 
 /// Status of a single peer
@@ -119,6 +411,33 @@ pub struct PeerStatus {
     /// If learned via PEX, the IP of the peer that told us about this one
     #[serde(skip_serializing_if = "Option::is_none")]
     pub relay_via: Option<String>,
+    /// Smoothed round-trip time in microseconds (None if never measured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_us: Option<u64>,
+    /// Smoothed packet loss on the direct path in percent (None if never measured)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loss_pct: Option<f64>,
+    /// Direct path quality score, 0–100 (None until first scored)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    /// Packets sent to this peer
+    #[serde(default)]
+    pub pkts_sent: u64,
+    /// Packets received from this peer
+    #[serde(default)]
+    pub pkts_received: u64,
+    /// Discovered path MTU (None until probed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_mtu: Option<u16>,
+    /// Unanswered reconnect handshakes since the peer went silent
+    #[serde(default)]
+    pub reconnect_attempts: u32,
+    /// Seconds until the next reconnect handshake (None while connected)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+    /// Public key is listed in `security.crl_file`
+    #[serde(default)]
+    pub revoked: bool,
 }
 
 impl Config {
@@ -182,6 +501,18 @@ impl Config {
         Ok(self.network.address.parse()?)
     }
 
+    /// Build the packet obfuscator for the configured mode (None when disabled)
+    pub fn obfuscator(&self) -> Result<Option<Obfuscator>, Box<dyn std::error::Error>> {
+        match self.network.obfuscation {
+            ObfuscationMode::None => Ok(None),
+            ObfuscationMode::Xor => {
+                let key = self.network.obfuscation_key.as_deref()
+                    .ok_or("obfuscation = \"xor\" requires obfuscation_key")?;
+                Ok(Some(Obfuscator::from_hex(key)?))
+            }
+        }
+    }
+
     /// Get the subnet as "address/mask" string
     pub fn cidr(&self) -> String {
         format!("{}/{}", self.network.address, self.network.subnet)
@@ -197,11 +528,33 @@ impl Default for Config {
                 subnet: default_subnet(),
                 listen_port: default_port(),
                 gateway: false,
+                hub_selection: HubSelection::Latency,
                 discovery: true,
+                mdns_discovery: false,
                 mtu: default_mtu(),
+                obfuscation: ObfuscationMode::None,
+                obfuscation_key: None,
+                peer_timeout_secs: default_peer_timeout_secs(),
+                failover_buffer_packets: default_failover_buffer_packets(),
+                failover_loss_threshold: default_failover_loss_threshold(),
+                quality_threshold: default_quality_threshold(),
+                quality_reconnect: false,
+                mtu_probe_interval_secs: default_mtu_probe_interval_secs(),
+                grpc_listen: None,
+                turn_server: false,
+                turn_servers: Vec::new(),
+                dns_servers: Vec::new(),
+                dns_domains: Vec::new(),
+                dns_proxy: false,
+                mirror_interface: None,
+                mirror_socket: None,
+                mobility_support: false,
             },
             security: SecurityConfig::default(),
             peers: Vec::new(),
+            peer_rules: Vec::new(),
+            routes: Vec::new(),
+            subnet_routes: Vec::new(),
         }
     }
 }
@@ -0,0 +1,137 @@
+//! Peer certificate revocation list
+//!
+//! `security.crl_file` lists the X25519 public keys of peers that must no
+//! longer reach this node, one base64 key per line (blank lines and `#`
+//! comments are ignored). The daemon reads it at startup, every
+//! `security.crl_refresh_secs` and on SIGHUP. `wolfnet revoke` appends a key
+//! and signals the daemon through `PID_FILE`, so revoking a compromised node
+//! does not mean regenerating every peer config.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+use crate::crypto::parse_public_key;
+
+/// Where the daemon records its process ID for `wolfnet revoke`
+pub const PID_FILE: &str = "/var/run/wolfnet/wolfnet.pid";
+
+/// Raw public key bytes of revoked peers
+pub type RevokedKeys = HashSet<[u8; 32]>;
+
+/// Parse CRL contents. Lines that are not a valid public key are skipped
+/// with a warning rather than failing the whole list.
+pub fn parse(content: &str) -> RevokedKeys {
+    content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match parse_public_key(line) {
+            Ok(key) => Some(key.to_bytes()),
+            Err(e) => {
+                tracing::warn!("Ignoring invalid CRL entry '{}': {}", line, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Read the CRL; a missing file revokes nothing
+pub fn load(path: &Path) -> Result<RevokedKeys, Box<dyn std::error::Error>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(parse(&content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(RevokedKeys::new()),
+        Err(e) => Err(format!("Failed to read CRL {}: {}", path.display(), e).into()),
+    }
+}
+
+/// Add a base64 public key to the CRL. Returns false if it was already listed.
+pub fn revoke(path: &Path, public_key: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let key = parse_public_key(public_key)?.to_bytes();
+    if load(path)?.contains(&key) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    // Don't glue the key onto a last line that lacks its newline
+    let needs_newline = std::fs::read(path)?.last().is_some_and(|b| *b != b'\n');
+    if needs_newline {
+        writeln!(file)?;
+    }
+    writeln!(file, "{}", public_key.trim())?;
+    Ok(true)
+}
+
+/// Ask the running daemon to re-read its config and CRL. Returns its PID.
+pub fn signal_daemon() -> Result<i32, Box<dyn std::error::Error>> {
+    let pid: i32 = std::fs::read_to_string(PID_FILE)
+        .map_err(|e| format!("{}: {}", PID_FILE, e))?
+        .trim()
+        .parse()?;
+    if unsafe { libc::kill(pid, libc::SIGHUP) } != 0 {
+        return Err(format!("Failed to signal PID {}: {}", pid, std::io::Error::last_os_error()).into());
+    }
+    Ok(pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::KeyPair;
+
+    fn crl_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("wolfnet-test-crl-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_invalid_lines() {
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        let content = format!(
+            "# revoked 2026-10-01\n{}\n\n  {}  \nnot-a-key\n{}\n",
+            a.public_key_base64(), b.public_key_base64(), a.public_key_base64(),
+        );
+        let keys = parse(&content);
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(a.public.as_bytes()));
+        assert!(keys.contains(b.public.as_bytes()));
+    }
+
+    #[test]
+    fn test_load_missing_file_revokes_nothing() {
+        assert!(load(&crl_path("missing")).unwrap().is_empty());
+        // A directory can't be read as a CRL
+        assert!(load(&std::env::temp_dir()).is_err());
+    }
+
+    #[test]
+    fn test_revoke_appends_once() {
+        let path = crl_path("revoke");
+        let _ = std::fs::remove_file(&path);
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+
+        assert!(revoke(&path, &a.public_key_base64()).unwrap());
+        assert!(!revoke(&path, &format!(" {}\n", a.public_key_base64())).unwrap());
+        assert!(revoke(&path, &b.public_key_base64()).is_ok_and(|added| added));
+        assert!(revoke(&path, "not-a-key").is_err());
+
+        let keys = load(&path).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(a.public.as_bytes()) && keys.contains(b.public.as_bytes()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_revoke_after_line_without_newline() {
+        let path = crl_path("newline");
+        let (a, b) = (KeyPair::generate(), KeyPair::generate());
+        std::fs::write(&path, a.public_key_base64()).unwrap();
+
+        assert!(revoke(&path, &b.public_key_base64()).unwrap());
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, format!("{}\n{}\n", a.public_key_base64(), b.public_key_base64()));
+        assert_eq!(load(&path).unwrap().len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
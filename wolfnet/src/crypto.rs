@@ -1,26 +1,125 @@
 //! Cryptographic primitives for WolfNet
 //!
 //! Uses X25519 for key exchange and ChaCha20-Poly1305 for authenticated encryption.
+//! Peer exchange messages and invite tokens are additionally signed with
+//! XEdDSA: an Ed25519 signature made with the X25519 secret itself, so anyone
+//! holding a node's X25519 public key can verify it.
+//!
+//! The X25519 secret lives either in a key file or on a PKCS#11 token
+//! (`security.key_backend`); see `crate::pkcs11`.
 
 use std::path::Path;
 use x25519_dalek::{PublicKey, StaticSecret};
 use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, KeyInit}};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use sha2::{Sha256, Digest};
+use sha2::Sha512;
+use curve25519_dalek::{EdwardsPoint, MontgomeryPoint, Scalar};
+use curve25519_dalek::scalar::clamp_integer;
+use ring::signature::{UnparsedPublicKey, ED25519};
+
+use crate::config::{KeyBackend, SecurityConfig};
+use crate::pkcs11::Pkcs11Key;
+
+/// Domain separator for the deterministic XEdDSA nonce
+const SIGNING_NONCE_CONTEXT: &[u8] = b"wolfnet-xeddsa-nonce";
 
+/// Hashed with the shared secret to derive the session ID
+const SESSION_ID_CONTEXT: &[u8] = b"wolfnet-session-id";
+
+/// Where the X25519 private key lives
+pub enum SecretKey {
+    /// In memory, loaded from `security.private_key_file`
+    File(StaticSecret),
+    /// On a PKCS#11 token; never leaves it
+    Pkcs11(Pkcs11Key),
+}
 
 /// X25519 keypair for this node
 pub struct KeyPair {
-    pub secret: StaticSecret,
+    pub secret: SecretKey,
     pub public: PublicKey,
+    /// XEdDSA signing key; None when the secret is on a token
+    signing: Option<SigningKey>,
+}
+
+/// Ed25519 form of an X25519 secret, negated where needed so its public key
+/// has the sign bit clear and can be recovered from the X25519 public key
+struct SigningKey {
+    scalar: Scalar,
+    public: [u8; 32],
+}
+
+impl SigningKey {
+    fn from_x25519(secret: &StaticSecret) -> Self {
+        let scalar = Scalar::from_bytes_mod_order(clamp_integer(secret.to_bytes()));
+        let public = EdwardsPoint::mul_base(&scalar).compress();
+        let scalar = if public.as_bytes()[31] & 0x80 != 0 { -scalar } else { scalar };
+        Self { scalar, public: EdwardsPoint::mul_base(&scalar).compress().to_bytes() }
+    }
+
+    fn sign(&self, message: &[u8]) -> [u8; 64] {
+        let mut hasher = Sha512::new();
+        hasher.update(SIGNING_NONCE_CONTEXT);
+        hasher.update(self.scalar.as_bytes());
+        hasher.update(message);
+        let r = Scalar::from_hash(hasher);
+        let big_r = EdwardsPoint::mul_base(&r).compress();
+
+        let mut hasher = Sha512::new();
+        hasher.update(big_r.as_bytes());
+        hasher.update(self.public);
+        hasher.update(message);
+        let s = r + Scalar::from_hash(hasher) * self.scalar;
+
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(big_r.as_bytes());
+        signature[32..].copy_from_slice(s.as_bytes());
+        signature
+    }
 }
 
 impl KeyPair {
+    fn from_static(secret: StaticSecret) -> Self {
+        let public = PublicKey::from(&secret);
+        let signing = Some(SigningKey::from_x25519(&secret));
+        Self { secret: SecretKey::File(secret), public, signing }
+    }
+
     /// Generate a new random keypair
     pub fn generate() -> Self {
-        let secret = StaticSecret::random_from_rng(rand::rngs::OsRng);
-        let public = PublicKey::from(&secret);
-        Self { secret, public }
+        Self::from_static(StaticSecret::random_from_rng(rand::rngs::OsRng))
+    }
+
+    /// Load the keypair from the configured backend
+    pub fn from_config(security: &SecurityConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        match security.key_backend {
+            KeyBackend::File => Self::load_or_generate(&security.private_key_file),
+            KeyBackend::Pkcs11 => {
+                let module = security.pkcs11_module.as_ref()
+                    .ok_or("security.pkcs11_module must be set when key_backend = \"pkcs11\"")?;
+                let pin = match &security.pkcs11_pin {
+                    Some(pin) => pin.clone(),
+                    None => std::env::var("WOLFNET_PKCS11_PIN")
+                        .map_err(|_| "set security.pkcs11_pin or WOLFNET_PKCS11_PIN")?,
+                };
+                Self::load_pkcs11(module, security.pkcs11_slot, &pin, &security.pkcs11_key_label)
+            }
+            // TPM 2.0 only specifies NIST/BN/SM2 curves for ECDH
+            KeyBackend::Tpm2 => Err("key_backend = \"tpm2\" is not supported: TPM 2.0 has no Curve25519, \
+                so an X25519 key cannot be held in a TPM. Use key_backend = \"pkcs11\" with a token that \
+                supports CKM_EC_MONTGOMERY_KEY_PAIR_GEN".into()),
+        }
+    }
+
+    /// Use the key pair labelled `label` on a PKCS#11 token, generating it
+    /// there if it does not exist. The private key never leaves the token.
+    ///
+    /// The token only does Diffie-Hellman, so such a node cannot sign: it
+    /// sends no peer exchange and cannot issue invite tokens.
+    pub fn load_pkcs11(module_path: &Path, slot: u64, pin: &str, label: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (key, public) = Pkcs11Key::load(module_path, slot, pin, label)?;
+        Ok(Self { secret: SecretKey::Pkcs11(key), public: PublicKey::from(public), signing: None })
     }
 
     /// Load a keypair from a private key file (32 bytes, base64 encoded)
@@ -32,9 +131,7 @@ impl KeyPair {
         }
         let mut key_bytes = [0u8; 32];
         key_bytes.copy_from_slice(&bytes);
-        let secret = StaticSecret::from(key_bytes);
-        let public = PublicKey::from(&secret);
-        Ok(Self { secret, public })
+        Ok(Self::from_static(StaticSecret::from(key_bytes)))
     }
 
     /// Save the private key to a file
@@ -42,7 +139,10 @@ impl KeyPair {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let encoded = BASE64.encode(self.secret.to_bytes());
+        let SecretKey::File(secret) = &self.secret else {
+            return Err("private key is held on a token and cannot be saved".into());
+        };
+        let encoded = BASE64.encode(secret.to_bytes());
         std::fs::write(path, &encoded)?;
         // Restrict permissions (owner-only)
         #[cfg(unix)]
@@ -84,6 +184,39 @@ impl KeyPair {
     pub fn my_peer_id(&self) -> [u8; 4] {
         Self::peer_id(&self.public)
     }
+
+    /// X25519 shared secret with a peer
+    pub fn diffie_hellman(&self, peer_public: &PublicKey) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+        match &self.secret {
+            SecretKey::File(secret) => Ok(*secret.diffie_hellman(peer_public).as_bytes()),
+            SecretKey::Pkcs11(key) => key.diffie_hellman(peer_public.as_bytes()),
+        }
+    }
+
+    /// Whether this node can sign: false when the key is on a PKCS#11 token
+    pub fn can_sign(&self) -> bool {
+        self.signing.is_some()
+    }
+
+    /// Sign a message with this node's X25519 key. None if the key is on a
+    /// PKCS#11 token, which cannot sign.
+    pub fn sign(&self, message: &[u8]) -> Option<[u8; 64]> {
+        self.signing.as_ref().map(|key| key.sign(message))
+    }
+}
+
+/// Ed25519 public key that verifies signatures made by the holder of
+/// `public`. None if `public` is not a valid curve point.
+pub fn verifying_key(public: &PublicKey) -> Option<[u8; 32]> {
+    MontgomeryPoint(*public.as_bytes()).to_edwards(0).map(|point| point.compress().to_bytes())
+}
+
+/// Verify a signature produced by `KeyPair::sign` for the node with X25519
+/// public key `signer`
+pub fn verify_signature(signer: &PublicKey, message: &[u8], signature: &[u8; 64]) -> bool {
+    verifying_key(signer).is_some_and(|key| {
+        UnparsedPublicKey::new(&ED25519, key).verify(message, signature).is_ok()
+    })
 }
 
 /// Session cipher for a peer connection
@@ -156,6 +289,17 @@ impl SessionCipher {
     }
 }
 
+/// Session ID both sides of a key exchange derive from the shared secret.
+/// It names the session in resume handshakes without revealing the secret.
+pub fn session_id(shared_secret: &[u8; 32]) -> [u8; 16] {
+    let mut hasher = Sha256::new();
+    hasher.update(SESSION_ID_CONTEXT);
+    hasher.update(shared_secret);
+    let mut id = [0u8; 16];
+    id.copy_from_slice(&hasher.finalize()[..16]);
+    id
+}
+
 /// Parse a base64-encoded public key into PublicKey
 pub fn parse_public_key(b64: &str) -> Result<PublicKey, Box<dyn std::error::Error>> {
     let bytes = BASE64.decode(b64.trim())?;
@@ -166,3 +310,110 @@ pub fn parse_public_key(b64: &str) -> Result<PublicKey, Box<dyn std::error::Erro
     arr.copy_from_slice(&bytes);
     Ok(PublicKey::from(arr))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_signature_verifies_against_x25519_public_key() {
+        for _ in 0..16 {
+            let kp = KeyPair::generate();
+            let signature = kp.sign(b"message").unwrap();
+            assert!(verify_signature(&kp.public, b"message", &signature));
+            assert!(!verify_signature(&kp.public, b"massage", &signature));
+            assert!(!verify_signature(&KeyPair::generate().public, b"message", &signature));
+        }
+    }
+
+    #[test]
+    fn test_verifying_key_matches_signing_key() {
+        let kp = KeyPair::generate();
+        assert_eq!(verifying_key(&kp.public), kp.signing.as_ref().map(|key| key.public));
+    }
+
+    fn pkcs11_security(module: Option<&str>, pin: Option<&str>) -> SecurityConfig {
+        SecurityConfig {
+            key_backend: KeyBackend::Pkcs11,
+            pkcs11_module: module.map(PathBuf::from),
+            pkcs11_pin: pin.map(String::from),
+            ..SecurityConfig::default()
+        }
+    }
+
+    fn load_error(security: &SecurityConfig) -> String {
+        match KeyPair::from_config(security) {
+            Ok(_) => panic!("key backend {:?} should fail to load", security.key_backend),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_key_backend_config() {
+        let config: crate::config::Config = toml::from_str(r#"
+            [network]
+            address = "10.0.10.2"
+
+            [security]
+            key_backend = "pkcs11"
+            pkcs11_module = "/usr/lib/softhsm/libsofthsm2.so"
+            pkcs11_slot = 3
+        "#).unwrap();
+        let security = &config.security;
+        assert_eq!(security.key_backend, KeyBackend::Pkcs11);
+        assert_eq!(security.pkcs11_module.as_deref(), Some(Path::new("/usr/lib/softhsm/libsofthsm2.so")));
+        assert_eq!(security.pkcs11_slot, 3);
+        assert_eq!(security.pkcs11_pin, None);
+        assert_eq!(security.pkcs11_key_label, "wolfnet");
+
+        assert_eq!(SecurityConfig::default().key_backend, KeyBackend::File);
+    }
+
+    #[test]
+    fn test_file_backend_generates_then_reloads_key() {
+        let path = std::env::temp_dir().join(format!("wolfnet-test-key-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let security = SecurityConfig { private_key_file: path.clone(), ..SecurityConfig::default() };
+
+        let generated = KeyPair::from_config(&security).unwrap();
+        let loaded = KeyPair::from_config(&security).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(generated.public, loaded.public);
+        assert!(loaded.can_sign(), "file keys can sign");
+        assert!(loaded.sign(b"message").is_some());
+    }
+
+    #[test]
+    fn test_pkcs11_backend_requires_module() {
+        assert!(load_error(&pkcs11_security(None, Some("1234"))).contains("pkcs11_module must be set"));
+    }
+
+    #[test]
+    fn test_pkcs11_backend_requires_pin() {
+        if std::env::var_os("WOLFNET_PKCS11_PIN").is_some() {
+            return;
+        }
+        let error = load_error(&pkcs11_security(Some("/nonexistent/libpkcs11.so"), None));
+        assert!(error.contains("WOLFNET_PKCS11_PIN"), "{}", error);
+    }
+
+    #[test]
+    fn test_pkcs11_backend_without_token_fails() {
+        let error = load_error(&pkcs11_security(Some("/nonexistent/libpkcs11.so"), Some("1234")));
+        assert!(error.contains("cannot load PKCS#11 module"), "{}", error);
+
+        // A library that loads but is not a PKCS#11 module
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        {
+            let error = load_error(&pkcs11_security(Some("libc.so.6"), Some("1234")));
+            assert!(error.contains("is not a PKCS#11 module"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_tpm2_backend_rejected() {
+        let security = SecurityConfig { key_backend: KeyBackend::Tpm2, ..SecurityConfig::default() };
+        assert!(load_error(&security).contains("not supported"));
+    }
+}
@@ -5,6 +5,9 @@
 //!   wolfnetctl list servers    - List all servers on the network
 //!   wolfnetctl peers           - Show detailed peer info
 //!   wolfnetctl info            - Show full network summary
+//!
+//! Status comes from the daemon's status file, or with `--grpc ADDR` from
+//! its gRPC management API.
 
 use std::path::PathBuf;
 use clap::{Parser, Subcommand};
@@ -12,9 +15,16 @@ use clap::{Parser, Subcommand};
 /// Status file location (written by wolfnet daemon)
 const STATUS_FILE: &str = "/var/run/wolfnet/status.json";
 
+/// `info` warns about peers losing more than this percentage of packets
+const HIGH_LOSS_WARN_PCT: f64 = 10.0;
+
 #[derive(Parser)]
 #[command(name = "wolfnetctl", version, about = "WolfNet control utility")]
 struct Cli {
+    /// Query the daemon's gRPC API (network.grpc_listen) instead of the status file
+    #[arg(long, global = true, value_name = "ADDR")]
+    grpc: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -50,6 +60,12 @@ struct NodeStatus {
     interface: String,
     uptime_secs: u64,
     peers: Vec<PeerStatus>,
+    #[serde(default)]
+    route_failover_total: u64,
+    #[serde(default)]
+    route_restore_total: u64,
+    #[serde(default)]
+    active_hub: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -67,11 +83,26 @@ struct PeerStatus {
     relay_via: Option<String>,
     #[serde(default)]
     is_gateway: bool,
+    #[serde(default)]
+    rtt_us: Option<u64>,
+    #[serde(default)]
+    loss_pct: Option<f64>,
+    #[serde(default)]
+    quality: Option<u8>,
+    #[serde(default)]
+    effective_mtu: Option<u16>,
+    #[serde(default)]
+    retry_in_secs: Option<u64>,
+    #[serde(default)]
+    revoked: bool,
 }
 
 fn main() {
     let cli = Cli::parse();
-    let status = load_status();
+    let status = match cli.grpc {
+        Some(ref addr) => fetch_status_grpc(addr),
+        None => load_status(),
+    };
 
     match cli.command {
         Commands::Status => cmd_status(&status),
@@ -100,6 +131,60 @@ fn load_status() -> NodeStatus {
     })
 }
 
+fn fetch_status_grpc(addr: &str) -> NodeStatus {
+    use wolfnet::grpc::proto::{management_client::ManagementClient, GetStatusRequest, ListPeersRequest};
+
+    let endpoint = if addr.contains("://") { addr.to_string() } else { format!("http://{}", addr) };
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()
+        .unwrap_or_else(|e| {
+            eprintln!("Error starting runtime: {}", e);
+            std::process::exit(1);
+        });
+    let result: Result<NodeStatus, Box<dyn std::error::Error>> = runtime.block_on(async {
+        let mut client = ManagementClient::connect(endpoint).await?;
+        let node = client.get_status(GetStatusRequest {}).await?.into_inner();
+        let mut stream = client.list_peers(ListPeersRequest {}).await?.into_inner();
+        let mut peers = Vec::new();
+        while let Some(p) = stream.message().await? {
+            peers.push(PeerStatus {
+                hostname: p.hostname,
+                address: p.address,
+                endpoint: p.endpoint,
+                public_key: p.public_key,
+                last_seen_secs: p.last_seen_secs,
+                rx_bytes: p.rx_bytes,
+                tx_bytes: p.tx_bytes,
+                connected: p.connected,
+                relay_via: p.relay_via,
+                is_gateway: p.is_gateway,
+                rtt_us: p.rtt_us,
+                loss_pct: p.loss_pct,
+                quality: p.quality.map(|q| q.min(100) as u8),
+                effective_mtu: p.effective_mtu.map(|m| m as u16),
+                retry_in_secs: p.retry_in_secs,
+                revoked: p.revoked,
+            });
+        }
+        Ok(NodeStatus {
+            hostname: node.hostname,
+            address: node.address,
+            public_key: node.public_key,
+            listen_port: node.listen_port as u16,
+            gateway: node.gateway,
+            interface: node.interface,
+            uptime_secs: node.uptime_secs,
+            peers,
+            route_failover_total: node.route_failover_total,
+            route_restore_total: node.route_restore_total,
+            active_hub: Some(node.active_hub).filter(|hub| !hub.is_empty()),
+        })
+    });
+    result.unwrap_or_else(|e| {
+        eprintln!("Error querying WolfNet gRPC API at {}: {}", addr, e);
+        std::process::exit(1);
+    })
+}
+
 fn cmd_status(status: &NodeStatus) {
     println!();
     println!("  🐺 WolfNet Status");
@@ -115,6 +200,12 @@ fn cmd_status(status: &NodeStatus) {
         status.peers.len(),
         status.peers.iter().filter(|p| p.connected).count(),
     );
+    if let Some(hub) = &status.active_hub {
+        println!("  Hub:         {}", hub);
+    }
+    if status.route_failover_total > 0 {
+        println!("  Failovers:   {} ({} restored)", status.route_failover_total, status.route_restore_total);
+    }
     println!();
 }
 
@@ -163,28 +254,36 @@ fn cmd_peers(status: &NodeStatus) {
 
     println!();
     println!("  🐺 WolfNet Peers");
-    println!("  ─────────────────────────────────────────────────────────────────────");
-    println!("  {:<16} {:<16} {:<24} {:<10} {}",
-        "HOSTNAME", "WOLFNET IP", "ENDPOINT", "STATUS", "LAST SEEN");
-    println!("  ─────────────────────────────────────────────────────────────────────");
+    println!("  ───────────────────────────────────────────────────────────────────────────────────────────────────────────");
+    println!("  {:<16} {:<16} {:<24} {:<22} {:<10} {:<10} {:<8} {:<6} {}",
+        "HOSTNAME", "WOLFNET IP", "ENDPOINT", "STATUS", "RTT", "PKT_LOSS%", "QUALITY", "MTU", "LAST SEEN");
+    println!("  ───────────────────────────────────────────────────────────────────────────────────────────────────────────");
 
     for peer in &status.peers {
-        let status_str = if peer.connected {
+        let status_str = if peer.revoked {
+            "REVOKED".to_string()
+        } else if peer.connected {
             "online".to_string()
         } else if peer.relay_via.is_some() {
             format!("via {}", peer.relay_via.as_deref().unwrap_or("?"))
+        } else if let Some(retry) = peer.retry_in_secs {
+            format!("DEAD (retry in {}s)", retry)
         } else {
             "offline".to_string()
         };
-        let status_icon = if peer.connected { "●" } else if peer.relay_via.is_some() { "◉" } else { "○" };
+        let status_icon = if peer.revoked { "✗" } else if peer.connected { "●" } else if peer.relay_via.is_some() { "◉" } else { "○" };
         let last_seen = if peer.last_seen_secs == u64::MAX {
             "never".to_string()
         } else {
             format_duration(peer.last_seen_secs)
         };
+        let rtt = peer.rtt_us.map_or("-".to_string(), format_rtt);
+        let loss = peer.loss_pct.map_or("-".to_string(), |l| format!("{:.1}", l));
+        let quality = peer.quality.map_or("-".to_string(), |q| q.to_string());
+        let mtu = peer.effective_mtu.map_or("-".to_string(), |m| m.to_string());
         let host = if peer.hostname.is_empty() { "-" } else { &peer.hostname };
-        println!("  {:<16} {:<16} {:<24} {} {:<14} {}",
-            host, peer.address, peer.endpoint, status_icon, status_str, last_seen);
+        println!("  {:<16} {:<16} {:<24} {} {:<20} {:<10} {:<10} {:<8} {:<6} {}",
+            host, peer.address, peer.endpoint, status_icon, status_str, rtt, loss, quality, mtu, last_seen);
     }
 
     // Traffic summary
@@ -200,6 +299,17 @@ fn cmd_info(status: &NodeStatus) {
     if !status.peers.is_empty() {
         cmd_peers(status);
     }
+    let lossy: Vec<&PeerStatus> = status.peers.iter()
+        .filter(|p| p.loss_pct.is_some_and(|l| l > HIGH_LOSS_WARN_PCT))
+        .collect();
+    for peer in &lossy {
+        let host = if peer.hostname.is_empty() { "-" } else { &peer.hostname };
+        println!("  ⚠  {} ({}) is losing {:.1}% of packets",
+            host, peer.address, peer.loss_pct.unwrap_or(0.0));
+    }
+    if !lossy.is_empty() {
+        println!();
+    }
 }
 
 fn format_duration(secs: u64) -> String {
@@ -209,6 +319,11 @@ fn format_duration(secs: u64) -> String {
     format!("{}d {}h", secs / 86400, (secs % 86400) / 3600)
 }
 
+fn format_rtt(us: u64) -> String {
+    if us < 1000 { return format!("{} µs", us); }
+    format!("{:.1} ms", us as f64 / 1000.0)
+}
+
 fn format_bytes(bytes: u64) -> String {
     if bytes < 1024 { return format!("{} B", bytes); }
     if bytes < 1024 * 1024 { return format!("{:.1} KB", bytes as f64 / 1024.0); }
@@ -0,0 +1,421 @@
+//! DNS over the tunnel
+//!
+//! `dns_servers` and `dns_domains` make internal names (`db.internal`)
+//! resolve through DNS servers on the WolfNet side, while every other name
+//! keeps using the system resolver. On startup the daemon writes the
+//! resolver settings to `RESOLV_FRAGMENT` and hands them to the system:
+//!
+//! - systemd-resolved (`resolvectl`): the servers are set on the WolfNet
+//!   interface with the domains as routing-only domains (`~internal`), so
+//!   only those names go to them. Reverted on shutdown (and dropped by
+//!   resolved anyway when the interface goes away).
+//! - resolvconf: the fragment is registered as `<interface>.wolfnet`. Plain
+//!   resolv.conf can't route by domain, so the servers join the global list
+//!   and the domains become search domains; use `dns_proxy` where that is a
+//!   problem. Deleted on shutdown.
+//!
+//! With `dns_proxy = true` a small forwarder on `DNS_PROXY_ADDR` sends
+//! queries for `dns_domains` to `dns_servers` (their routes go through the
+//! TUN) and everything else to the nameservers that were in
+//! `/etc/resolv.conf` at startup. Point a local forwarder (dnsmasq,
+//! unbound) or individual applications at it.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{debug, info, warn};
+
+/// Resolver configuration written for the system resolver
+pub const RESOLV_FRAGMENT: &str = "/etc/wolfnet/resolv.conf";
+
+/// Where the optional DNS proxy listens
+pub const DNS_PROXY_ADDR: &str = "127.0.0.1:5353";
+
+/// How long the proxy waits for an upstream answer
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Which resolver manager took the WolfNet DNS settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsBackend {
+    Resolved,
+    Resolvconf,
+    /// Neither is installed: only the fragment was written
+    None,
+}
+
+/// Parse the configured servers, skipping invalid ones
+pub fn parse_servers(servers: &[String]) -> Vec<Ipv4Addr> {
+    servers.iter().filter_map(|s| match s.parse() {
+        Ok(ip) => Some(ip),
+        Err(e) => { warn!("Ignoring DNS server '{}': {}", s, e); None }
+    }).collect()
+}
+
+/// Lowercase a domain and strip leading/trailing dots (and a `~` prefix)
+pub fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_start_matches('~').trim_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `name` is `domain` or a name below it
+pub fn in_domain(name: &str, domain: &str) -> bool {
+    let name = normalize_domain(name);
+    let domain = normalize_domain(domain);
+    !domain.is_empty()
+        && (name == domain || name.strip_suffix(&domain).is_some_and(|rest| rest.ends_with('.')))
+}
+
+/// Contents of `RESOLV_FRAGMENT`
+pub fn resolv_fragment(servers: &[Ipv4Addr], domains: &[String]) -> String {
+    let mut out = String::from("# Generated by WolfNet — removed when the daemon stops\n");
+    for server in servers {
+        out.push_str(&format!("nameserver {}\n", server));
+    }
+    let domains: Vec<String> = domains.iter().map(|d| normalize_domain(d)).filter(|d| !d.is_empty()).collect();
+    if !domains.is_empty() {
+        out.push_str(&format!("search {}\n", domains.join(" ")));
+    }
+    out
+}
+
+fn have_command(name: &str) -> bool {
+    Command::new("sh")
+        .args(["-c", &format!("command -v {}", name)])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// Whether systemd-resolved is running (resolvectl alone isn't enough)
+fn resolved_active() -> bool {
+    have_command("resolvectl") && Path::new("/run/systemd/resolve").is_dir()
+}
+
+fn run(cmd: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    let status = Command::new(cmd).args(args).status()?;
+    if !status.success() {
+        return Err(format!("{} {} failed ({})", cmd, args.join(" "), status).into());
+    }
+    Ok(())
+}
+
+/// Write `RESOLV_FRAGMENT` and register the servers for `domains` with
+/// systemd-resolved or resolvconf. Replaces any earlier registration.
+pub fn apply_dns(interface: &str, servers: &[Ipv4Addr], domains: &[String]) -> Result<DnsBackend, Box<dyn std::error::Error>> {
+    let fragment = resolv_fragment(servers, domains);
+    if let Some(dir) = Path::new(RESOLV_FRAGMENT).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(RESOLV_FRAGMENT, &fragment)?;
+
+    if resolved_active() {
+        let servers: Vec<String> = servers.iter().map(Ipv4Addr::to_string).collect();
+        let routing: Vec<String> = domains.iter()
+            .map(|d| normalize_domain(d))
+            .filter(|d| !d.is_empty())
+            .map(|d| format!("~{}", d))
+            .collect();
+        let mut args = vec!["dns", interface];
+        args.extend(servers.iter().map(String::as_str));
+        run("resolvectl", &args)?;
+        let mut args = vec!["domain", interface];
+        args.extend(routing.iter().map(String::as_str));
+        run("resolvectl", &args)?;
+        // Never make the tunnel the default route for DNS
+        let _ = Command::new("resolvectl").args(["default-route", interface, "false"]).status();
+        return Ok(DnsBackend::Resolved);
+    }
+
+    if have_command("resolvconf") {
+        let record = format!("{}.wolfnet", interface);
+        let mut child = Command::new("resolvconf")
+            .args(["-a", &record])
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            stdin.write_all(fragment.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(format!("resolvconf -a {} failed ({})", record, status).into());
+        }
+        return Ok(DnsBackend::Resolvconf);
+    }
+
+    Ok(DnsBackend::None)
+}
+
+/// Undo `apply_dns`
+pub fn remove_dns(interface: &str) {
+    if resolved_active() {
+        let _ = Command::new("resolvectl").args(["revert", interface]).stderr(Stdio::null()).status();
+    }
+    if have_command("resolvconf") {
+        let _ = Command::new("resolvconf")
+            .args(["-d", &format!("{}.wolfnet", interface)])
+            .stderr(Stdio::null())
+            .status();
+    }
+    if let Err(e) = std::fs::remove_file(RESOLV_FRAGMENT) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            warn!("Failed to remove {}: {}", RESOLV_FRAGMENT, e);
+        }
+    }
+}
+
+/// Active per-domain routing as reported by the resolver manager, one line each
+pub fn list_overrides(interface: &str) -> Vec<String> {
+    if resolved_active() {
+        let mut lines = Vec::new();
+        for what in ["dns", "domain"] {
+            if let Ok(output) = Command::new("resolvectl").args([what, interface]).output() {
+                lines.extend(String::from_utf8_lossy(&output.stdout).lines().map(|l| l.trim().to_string()));
+            }
+        }
+        return lines.into_iter().filter(|l| !l.is_empty()).collect();
+    }
+    std::fs::read_to_string(RESOLV_FRAGMENT)
+        .map(|s| s.lines().filter(|l| !l.starts_with('#') && !l.trim().is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Nameservers listed in a resolv.conf
+pub fn system_nameservers(resolv_conf: &str) -> Vec<Ipv4Addr> {
+    resolv_conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|rest| rest.trim().parse().ok())
+        .collect()
+}
+
+/// Query name of a DNS message (lowercase, without the trailing dot)
+pub fn query_name(packet: &[u8]) -> Option<String> {
+    // 12-byte header, then QNAME as length-prefixed labels
+    if packet.len() < 12 || u16::from_be_bytes([packet[4], packet[5]]) == 0 {
+        return None;
+    }
+    let mut labels = Vec::new();
+    let mut pos = 12;
+    loop {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers can't appear in a question we're sent first
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        let label = packet.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        pos += 1 + len;
+    }
+    Some(labels.join("."))
+}
+
+/// SERVFAIL answer to `query`
+fn servfail(query: &[u8]) -> Vec<u8> {
+    let mut reply = query.to_vec();
+    if reply.len() >= 4 {
+        reply[2] |= 0x80; // QR
+        reply[3] = (reply[3] & 0xF0) | 2; // RCODE = SERVFAIL
+    }
+    reply
+}
+
+/// Forwards queries for the tunnel domains to the WolfNet DNS servers and
+/// the rest to the system resolver
+#[derive(Debug, Clone)]
+pub struct DnsProxy {
+    domains: Vec<String>,
+    tunnel: Vec<SocketAddr>,
+    system: Vec<SocketAddr>,
+}
+
+impl DnsProxy {
+    pub fn new(domains: &[String], tunnel: Vec<SocketAddr>, system: Vec<SocketAddr>) -> Self {
+        let domains = domains.iter().map(|d| normalize_domain(d)).filter(|d| !d.is_empty()).collect();
+        Self { domains, tunnel, system }
+    }
+
+    /// The servers a query for `name` goes to
+    pub fn upstreams(&self, name: &str) -> &[SocketAddr] {
+        if self.domains.iter().any(|d| in_domain(name, d)) {
+            &self.tunnel
+        } else {
+            &self.system
+        }
+    }
+
+    /// Forward one query and return the answer (SERVFAIL if no server answers)
+    pub fn resolve(&self, query: &[u8]) -> Vec<u8> {
+        let Some(name) = query_name(query) else { return servfail(query) };
+        for upstream in self.upstreams(&name) {
+            match exchange(*upstream, query) {
+                Ok(answer) => {
+                    debug!("DNS {} answered by {}", name, upstream);
+                    return answer;
+                }
+                Err(e) => debug!("DNS {} via {} failed: {}", name, upstream, e),
+            }
+        }
+        servfail(query)
+    }
+
+    /// Serve queries on `socket` until `running` is cleared (call from a thread)
+    pub fn run(self, socket: UdpSocket, running: Arc<AtomicBool>) {
+        socket.set_read_timeout(Some(Duration::from_secs(1))).ok();
+        let proxy = Arc::new(self);
+        let mut buf = [0u8; 4096];
+        while running.load(Ordering::Relaxed) {
+            let Ok((n, src)) = socket.recv_from(&mut buf) else { continue };
+            let query = buf[..n].to_vec();
+            let (proxy, reply_socket) = match socket.try_clone() {
+                Ok(s) => (proxy.clone(), s),
+                Err(_) => continue,
+            };
+            // One thread per query so a slow upstream doesn't hold up the rest
+            std::thread::spawn(move || {
+                let _ = reply_socket.send_to(&proxy.resolve(&query), src);
+            });
+        }
+    }
+}
+
+/// Send `query` to `server` and wait for the answer with the same ID
+fn exchange(server: SocketAddr, query: &[u8]) -> std::io::Result<Vec<u8>> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+    socket.send_to(query, server)?;
+    let mut buf = [0u8; 4096];
+    loop {
+        let (n, from) = socket.recv_from(&mut buf)?;
+        if from == server && n >= 2 && buf[..2] == query[..2] {
+            return Ok(buf[..n].to_vec());
+        }
+    }
+}
+
+/// Start the DNS proxy on `DNS_PROXY_ADDR`
+pub fn start_proxy(domains: &[String], servers: &[Ipv4Addr], running: Arc<AtomicBool>) -> Result<(), Box<dyn std::error::Error>> {
+    let proxy_addr: SocketAddr = DNS_PROXY_ADDR.parse()?;
+    let system: Vec<SocketAddr> = system_nameservers(&std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default())
+        .into_iter()
+        .filter(|ip| !servers.contains(ip))
+        .map(|ip| SocketAddr::from((ip, 53)))
+        .filter(|addr| *addr != proxy_addr)
+        .collect();
+    if system.is_empty() {
+        warn!("DNS proxy: no system nameservers in /etc/resolv.conf, only {} will resolve", domains.join(", "));
+    }
+    let tunnel = servers.iter().map(|ip| SocketAddr::from((*ip, 53))).collect();
+    let socket = UdpSocket::bind(proxy_addr)?;
+    let proxy = DnsProxy::new(domains, tunnel, system);
+    std::thread::spawn(move || proxy.run(socket, running));
+    info!("DNS proxy listening on {}", DNS_PROXY_ADDR);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A query for `name` (type A, class IN) with ID 0x1234
+    fn query(name: &str) -> Vec<u8> {
+        let mut packet = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label.as_bytes());
+        }
+        packet.extend_from_slice(&[0, 0, 1, 0, 1]);
+        packet
+    }
+
+    fn addr(last: u8) -> SocketAddr {
+        SocketAddr::from(([10, 0, 10, last], 53))
+    }
+
+    #[test]
+    fn test_domain_matching() {
+        assert!(in_domain("db.internal", "internal"));
+        assert!(in_domain("a.b.internal.", "~Internal"));
+        assert!(in_domain("internal", ".internal."));
+        assert!(!in_domain("notinternal", "internal"));
+        assert!(!in_domain("internal.example.com", "internal"));
+        assert!(!in_domain("db.internal", ""));
+        assert_eq!(normalize_domain(" ~Corp.Example. "), "corp.example");
+    }
+
+    #[test]
+    fn test_resolver_selection() {
+        let proxy = DnsProxy::new(&["internal".into(), "~corp.example".into(), "".into()],
+            vec![addr(53)], vec![SocketAddr::from(([192, 168, 1, 1], 53))]);
+        assert_eq!(proxy.upstreams("db.internal"), [addr(53)]);
+        assert_eq!(proxy.upstreams("git.corp.example"), [addr(53)]);
+        assert_eq!(proxy.upstreams("example.com"), [SocketAddr::from(([192, 168, 1, 1], 53))]);
+        // The empty domain doesn't capture every name
+        assert_eq!(proxy.upstreams("corp.example.net"), [SocketAddr::from(([192, 168, 1, 1], 53))]);
+    }
+
+    #[test]
+    fn test_query_name() {
+        assert_eq!(query_name(&query("DB.Internal")).as_deref(), Some("db.internal"));
+        let mut no_question = query("db.internal");
+        no_question[5] = 0;
+        assert_eq!(query_name(&no_question), None);
+        assert_eq!(query_name(&query("db.internal")[..16]), None, "truncated");
+        let mut compressed = query("db.internal");
+        compressed[12] = 0xC0;
+        assert_eq!(query_name(&compressed), None);
+    }
+
+    #[test]
+    fn test_resolver_config() {
+        let servers = parse_servers(&["10.0.10.53".into(), "dns.internal".into(), "10.0.10.54".into()]);
+        assert_eq!(servers, [Ipv4Addr::new(10, 0, 10, 53), Ipv4Addr::new(10, 0, 10, 54)]);
+        assert_eq!(resolv_fragment(&servers, &["~Internal".into(), "corp.example.".into()]),
+            "# Generated by WolfNet — removed when the daemon stops\n\
+             nameserver 10.0.10.53\nnameserver 10.0.10.54\nsearch internal corp.example\n");
+        assert_eq!(system_nameservers("# comment\nnameserver 192.168.1.1\nsearch lan\nnameserver ::1\n nameserver 1.1.1.1\n"),
+            [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(1, 1, 1, 1)]);
+    }
+
+    #[test]
+    fn test_proxy_forwards_by_domain() {
+        // Upstreams that answer with their own marker byte appended
+        let upstream = |marker: u8| {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let addr = socket.local_addr().unwrap();
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 512];
+                while let Ok((n, src)) = socket.recv_from(&mut buf) {
+                    let mut answer = buf[..n].to_vec();
+                    answer.push(marker);
+                    let _ = socket.send_to(&answer, src);
+                }
+            });
+            addr
+        };
+        let proxy = DnsProxy::new(&["internal".into()], vec![upstream(1)], vec![upstream(2)]);
+
+        assert_eq!(proxy.resolve(&query("db.internal")).last(), Some(&1));
+        assert_eq!(proxy.resolve(&query("example.com")).last(), Some(&2));
+    }
+
+    #[test]
+    fn test_servfail_without_upstream() {
+        let proxy = DnsProxy::new(&["internal".into()], Vec::new(), Vec::new());
+        let answer = proxy.resolve(&query("db.internal"));
+        assert_eq!(&answer[..2], [0x12, 0x34]);
+        assert_eq!(answer[2] & 0x80, 0x80, "a response");
+        assert_eq!(answer[3] & 0x0F, 2, "SERVFAIL");
+
+        // Malformed queries too
+        assert_eq!(proxy.resolve(&[0x12, 0x34, 0x01, 0x00])[3] & 0x0F, 2);
+    }
+}
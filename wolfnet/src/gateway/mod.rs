@@ -0,0 +1,308 @@
+//! Gateway functionality for WolfNet
+//!
+//! Enables NAT/masquerading so nodes on the WolfNet can access the internet
+//! through a designated gateway node, and enforces per-peer forwarding
+//! policy (`[[peer_rules]]`) via the iptables FORWARD chain.
+
+use std::net::Ipv4Addr;
+
+use tracing::warn;
+
+use crate::config::{PeerRule, RuleAction, RuleProto};
+
+/// Comment prefix attached to every per-peer FORWARD rule we install,
+/// so they can be listed and removed without touching anything else
+pub const PEER_RULE_COMMENT_PREFIX: &str = "WolfNet-peer-";
+
+/// Detect the default internet-facing interface by parsing the routing table
+pub fn detect_external_interface() -> Option<String> {
+    let output = std::process::Command::new("ip")
+        .args(["route", "show", "default"])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Parse: "default via X.X.X.X dev ethN ..."
+    for part in stdout.split_whitespace().collect::<Vec<_>>().windows(2) {
+        if part[0] == "dev" {
+            return Some(part[1].to_string());
+        }
+    }
+    None
+}
+
+/// Enable gateway mode: IP forwarding + NAT masquerading
+pub fn enable_gateway(wolfnet_interface: &str, subnet: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let ext_iface = detect_external_interface()
+        .ok_or("Could not detect external network interface")?;
+
+
+
+    // Enable IP forwarding
+    std::fs::write("/proc/sys/net/ipv4/ip_forward", "1")?;
+
+
+    // Add iptables MASQUERADE rule for WolfNet traffic going to the internet
+    let status = std::process::Command::new("iptables")
+        .args(["-t", "nat", "-A", "POSTROUTING", "-s", subnet, "-o", &ext_iface, "-j", "MASQUERADE"])
+        .status()?;
+    if !status.success() {
+        warn!("iptables MASQUERADE rule may have failed");
+    }
+
+    // Allow forwarding from wolfnet interface to external
+    let status = std::process::Command::new("iptables")
+        .args(["-A", "FORWARD", "-i", wolfnet_interface, "-o", &ext_iface, "-j", "ACCEPT"])
+        .status()?;
+    if !status.success() {
+        warn!("iptables FORWARD rule (out) may have failed");
+    }
+
+    // Allow established/related traffic back
+    let status = std::process::Command::new("iptables")
+        .args(["-A", "FORWARD", "-i", &ext_iface, "-o", wolfnet_interface, "-m", "state", "--state", "ESTABLISHED,RELATED", "-j", "ACCEPT"])
+        .status()?;
+    if !status.success() {
+        warn!("iptables FORWARD rule (in) may have failed");
+    }
+
+    // Block all other inbound traffic to wolfnet (truly private)
+    let status = std::process::Command::new("iptables")
+        .args(["-A", "INPUT", "-i", &ext_iface, "-d", subnet, "-j", "DROP"])
+        .status()?;
+    if !status.success() {
+        warn!("iptables INPUT DROP rule may have failed");
+    }
+
+
+    Ok(())
+}
+
+/// Clean up gateway rules on shutdown
+pub fn disable_gateway(wolfnet_interface: &str, subnet: &str) {
+    let ext_iface = detect_external_interface().unwrap_or_default();
+    if ext_iface.is_empty() { return; }
+
+
+
+    let _ = std::process::Command::new("iptables")
+        .args(["-t", "nat", "-D", "POSTROUTING", "-s", subnet, "-o", &ext_iface, "-j", "MASQUERADE"])
+        .status();
+    let _ = std::process::Command::new("iptables")
+        .args(["-D", "FORWARD", "-i", wolfnet_interface, "-o", &ext_iface, "-j", "ACCEPT"])
+        .status();
+    let _ = std::process::Command::new("iptables")
+        .args(["-D", "FORWARD", "-i", &ext_iface, "-o", wolfnet_interface, "-m", "state", "--state", "ESTABLISHED,RELATED", "-j", "ACCEPT"])
+        .status();
+    let _ = std::process::Command::new("iptables")
+        .args(["-D", "INPUT", "-i", &ext_iface, "-d", subnet, "-j", "DROP"])
+        .status();
+}
+
+/// Add a default route through the gateway on a client node
+pub fn add_gateway_route(gateway_ip: &str, wolfnet_interface: &str) -> Result<(), Box<dyn std::error::Error>> {
+
+    let status = std::process::Command::new("ip")
+        .args(["route", "add", "default", "via", gateway_ip, "dev", wolfnet_interface, "metric", "500"])
+        .status()?;
+    if !status.success() {
+        warn!("Failed to add default route via gateway");
+    }
+    Ok(())
+}
+
+/// Build the iptables argument lists (minus the `-I FORWARD <pos>` prefix) for a single peer rule.
+/// A rule with a destination port and `proto = "any"` expands to one tcp and one udp rule,
+/// since iptables can only match ports for a specific protocol.
+fn peer_rule_args(wolfnet_interface: &str, index: usize, rule: &PeerRule) -> Result<Vec<Vec<String>>, Box<dyn std::error::Error>> {
+    let peer_ip: Ipv4Addr = rule.peer_ip.parse()
+        .map_err(|e| format!("invalid peer_ip '{}': {}", rule.peer_ip, e))?;
+
+    let protos: Vec<Option<&str>> = match (rule.proto, rule.dest_port) {
+        (RuleProto::Tcp, _) => vec![Some("tcp")],
+        (RuleProto::Udp, _) => vec![Some("udp")],
+        (RuleProto::Any, Some(_)) => vec![Some("tcp"), Some("udp")],
+        (RuleProto::Any, None) => vec![None],
+    };
+    let target = match rule.action {
+        RuleAction::Allow => "ACCEPT",
+        RuleAction::Deny => "DROP",
+    };
+    let comment = format!("{}{}-{}", PEER_RULE_COMMENT_PREFIX, index, peer_ip);
+
+    let mut rules = Vec::new();
+    for proto in protos {
+        let mut args: Vec<String> = vec!["-i".into(), wolfnet_interface.into(), "-s".into(), peer_ip.to_string()];
+        if let Some(ref net) = rule.dest_network {
+            args.extend(["-d".into(), net.clone()]);
+        }
+        if let Some(p) = proto {
+            args.extend(["-p".into(), p.into()]);
+            if let Some(port) = rule.dest_port {
+                args.extend(["--dport".into(), port.to_string()]);
+            }
+        }
+        args.extend(["-m".into(), "comment".into(), "--comment".into(), comment.clone(), "-j".into(), target.into()]);
+        rules.push(args);
+    }
+    Ok(rules)
+}
+
+/// Argument lists for every valid rule in `rules`, in config order, each
+/// with the index of the rule it came from. Invalid rules are skipped.
+fn peer_rules_args(wolfnet_interface: &str, rules: &[PeerRule]) -> Vec<(usize, Vec<String>)> {
+    let mut all = Vec::new();
+    for (index, rule) in rules.iter().enumerate() {
+        match peer_rule_args(wolfnet_interface, index, rule) {
+            Ok(arg_sets) => all.extend(arg_sets.into_iter().map(|args| (index, args))),
+            Err(e) => warn!("Skipping peer rule #{}: {}", index, e),
+        }
+    }
+    all
+}
+
+/// Install per-peer FORWARD rules, replacing any previously installed ones.
+/// Rules are inserted at the top of the chain in config order, so they take
+/// precedence over the gateway's blanket ACCEPT rules. Returns the number of
+/// iptables rules installed.
+pub fn apply_peer_rules(wolfnet_interface: &str, rules: &[PeerRule]) -> Result<usize, Box<dyn std::error::Error>> {
+    flush_peer_rules();
+
+    let mut position = 1;
+    for (index, args) in peer_rules_args(wolfnet_interface, rules) {
+        let status = std::process::Command::new("iptables")
+            .args(["-I", "FORWARD", &position.to_string()])
+            .args(&args)
+            .status()?;
+        if status.success() {
+            position += 1;
+        } else {
+            warn!("iptables peer rule #{} for {} may have failed", index, rules[index].peer_ip);
+        }
+    }
+    Ok(position - 1)
+}
+
+/// Whether an `iptables -S` line is a rule we installed: its comment, not
+/// just any part of it, starts with `PEER_RULE_COMMENT_PREFIX`
+fn is_peer_rule(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    words.next() == Some("-A")
+        && words.skip_while(|w| *w != "--comment").nth(1)
+            .is_some_and(|c| c.trim_matches('"').starts_with(PEER_RULE_COMMENT_PREFIX))
+}
+
+/// Turn an `iptables -S` line ("-A FORWARD <spec>") into the arguments that
+/// delete it ("-D FORWARD <spec>"). Our comments never contain spaces, so
+/// splitting on whitespace round-trips the rule spec.
+fn delete_args(line: &str) -> Vec<String> {
+    line.split_whitespace()
+        .map(|a| if a == "-A" { "-D".to_string() } else { a.trim_matches('"').to_string() })
+        .collect()
+}
+
+/// List installed per-peer FORWARD rules (in `iptables -S` format)
+pub fn list_peer_rules() -> Vec<String> {
+    let output = match std::process::Command::new("iptables").args(["-S", "FORWARD"]).output() {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| is_peer_rule(line))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Remove every FORWARD rule carrying the WolfNet peer-rule comment.
+/// Returns the number of rules removed.
+pub fn flush_peer_rules() -> usize {
+    let mut removed = 0;
+    for line in list_peer_rules() {
+        let ok = std::process::Command::new("iptables")
+            .args(delete_args(&line))
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if ok {
+            removed += 1;
+        } else {
+            warn!("Failed to remove peer rule: {}", line);
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(peer_ip: &str, action: RuleAction, proto: RuleProto, dest_port: Option<u16>, dest_network: Option<&str>) -> PeerRule {
+        PeerRule {
+            peer_ip: peer_ip.to_string(),
+            action,
+            proto,
+            dest_port,
+            dest_network: dest_network.map(str::to_string),
+        }
+    }
+
+    fn joined(args: &[String]) -> String {
+        args.join(" ")
+    }
+
+    #[test]
+    fn test_peer_rule_args() {
+        let rules = [
+            rule("10.0.10.2", RuleAction::Allow, RuleProto::Tcp, Some(22), Some("192.168.1.0/24")),
+            rule("10.0.10.3", RuleAction::Deny, RuleProto::Any, None, None),
+        ];
+        let args = peer_rules_args("wolfnet0", &rules);
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0].0, 0);
+        assert_eq!(joined(&args[0].1),
+            "-i wolfnet0 -s 10.0.10.2 -d 192.168.1.0/24 -p tcp --dport 22 -m comment --comment WolfNet-peer-0-10.0.10.2 -j ACCEPT");
+        assert_eq!(args[1].0, 1);
+        assert_eq!(joined(&args[1].1),
+            "-i wolfnet0 -s 10.0.10.3 -m comment --comment WolfNet-peer-1-10.0.10.3 -j DROP");
+    }
+
+    #[test]
+    fn test_any_proto_with_port_expands_to_tcp_and_udp() {
+        let args = peer_rule_args("wolfnet0", 4, &rule("10.0.10.2", RuleAction::Deny, RuleProto::Any, Some(53), None)).unwrap();
+        assert_eq!(args.len(), 2);
+        assert!(joined(&args[0]).contains("-p tcp --dport 53"));
+        assert!(joined(&args[1]).contains("-p udp --dport 53"));
+        assert!(args.iter().all(|a| joined(a).ends_with("--comment WolfNet-peer-4-10.0.10.2 -j DROP")));
+    }
+
+    #[test]
+    fn test_invalid_rule_is_skipped() {
+        let rules = [
+            rule("not-an-ip", RuleAction::Allow, RuleProto::Any, None, None),
+            rule("10.0.10.5", RuleAction::Allow, RuleProto::Udp, None, None),
+        ];
+        assert!(peer_rule_args("wolfnet0", 0, &rules[0]).is_err());
+        let args = peer_rules_args("wolfnet0", &rules);
+        assert_eq!(args.len(), 1);
+        assert_eq!(args[0].0, 1);
+        assert!(joined(&args[0].1).contains("-p udp -m comment"));
+    }
+
+    #[test]
+    fn test_peer_rule_comment_matching() {
+        let ours = "-A FORWARD -s 10.0.10.2/32 -i wolfnet0 -p tcp -m tcp --dport 22 -m comment --comment WolfNet-peer-0-10.0.10.2 -j ACCEPT";
+        let quoted = "-A FORWARD -s 10.0.10.3/32 -i wolfnet0 -m comment --comment \"WolfNet-peer-1-10.0.10.3\" -j DROP";
+        assert!(is_peer_rule(ours));
+        assert!(is_peer_rule(quoted));
+
+        // Other rules, including ones that only mention the prefix elsewhere
+        assert!(!is_peer_rule("-P FORWARD ACCEPT"));
+        assert!(!is_peer_rule("-A FORWARD -i wolfnet0 -j ACCEPT"));
+        assert!(!is_peer_rule("-A FORWARD -m comment --comment not-WolfNet-peer-1 -j ACCEPT"));
+        assert!(!is_peer_rule("-A FORWARD -m comment --comment WolfNet-gateway -j ACCEPT"));
+        assert!(!is_peer_rule("-N WolfNet-peer-chain"));
+
+        assert_eq!(joined(&delete_args(quoted)),
+            "-D FORWARD -s 10.0.10.3/32 -i wolfnet0 -m comment --comment WolfNet-peer-1-10.0.10.3 -j DROP");
+    }
+}
@@ -0,0 +1,371 @@
+//! gRPC management API
+//!
+//! Lets tools query and change a running daemon's peer table instead of
+//! reading the status file. Served on `network.grpc_listen`; the service
+//! shares the daemon's `PeerManager`, so changes take effect immediately but
+//! are not written back to the config file.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use crate::config::PeerStatus;
+use crate::crypto::KeyPair;
+use crate::peer::{Peer, PeerManager};
+use crate::transport::resolve_endpoint;
+
+pub mod proto {
+    tonic::include_proto!("wolfnet.management");
+}
+
+use proto::management_server::{Management, ManagementServer};
+use proto::{
+    GetStatusRequest, ListPeersRequest, NodeStatus, OperationResult, PeerConfig, PeerInfo,
+    RemovePeerRequest, TrafficStats, TrafficStatsRequest, UpdateEndpointRequest,
+};
+
+/// This node's identity, as reported by `GetStatus`
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    pub hostname: String,
+    pub address: String,
+    pub public_key: String,
+    pub listen_port: u16,
+    pub gateway: bool,
+    pub interface: String,
+}
+
+/// The `Management` service over the daemon's live peer table
+pub struct ManagementService {
+    node: NodeInfo,
+    keypair: Arc<KeyPair>,
+    peer_manager: Arc<PeerManager>,
+    start_time: Instant,
+}
+
+impl ManagementService {
+    pub fn new(node: NodeInfo, keypair: Arc<KeyPair>, peer_manager: Arc<PeerManager>, start_time: Instant) -> Self {
+        Self { node, keypair, peer_manager, start_time }
+    }
+
+    /// Serve the API on `addr` until the runtime shuts down
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+        info!("gRPC management API listening on {}", addr);
+        tonic::transport::Server::builder()
+            .add_service(ManagementServer::new(self))
+            .serve(addr)
+            .await
+    }
+
+    fn add_peer(&self, config: &PeerConfig) -> Result<(), String> {
+        let public_key = crate::crypto::parse_public_key(&config.public_key)
+            .map_err(|e| format!("invalid public key: {}", e))?;
+        let ip = parse_peer_ip(&config.allowed_ip)?;
+        if self.peer_manager.with_peer_by_ip(&ip, |_| ()).is_some() {
+            return Err(format!("peer {} already exists", ip));
+        }
+
+        let mut peer = Peer::new(public_key, ip);
+        peer.hostname = config.name.clone();
+        if !config.endpoint.is_empty() {
+            peer.endpoint = Some(resolve_endpoint(&config.endpoint)
+                .ok_or_else(|| format!("cannot resolve endpoint '{}'", config.endpoint))?);
+            peer.configured_endpoint = Some(config.endpoint.clone());
+        }
+        peer.establish_session(&self.keypair);
+        self.peer_manager.add_peer(peer);
+        info!("gRPC: added peer {}", ip);
+        Ok(())
+    }
+
+    fn remove_peer(&self, peer_ip: &str) -> Result<(), String> {
+        let ip = parse_peer_ip(peer_ip)?;
+        if !self.peer_manager.remove_peer(&ip) {
+            return Err(format!("unknown peer {}", ip));
+        }
+        info!("gRPC: removed peer {}", ip);
+        Ok(())
+    }
+
+    fn update_endpoint(&self, peer_ip: &str, endpoint: &str) -> Result<(), String> {
+        let ip = parse_peer_ip(peer_ip)?;
+        let addr = resolve_endpoint(endpoint)
+            .ok_or_else(|| format!("cannot resolve endpoint '{}'", endpoint))?;
+        // Keep the string so hostnames are re-resolved like configured ones
+        self.peer_manager.with_peer_by_ip(&ip, |peer| peer.configured_endpoint = Some(endpoint.to_string()))
+            .ok_or_else(|| format!("unknown peer {}", ip))?;
+        self.peer_manager.update_endpoint(&ip, addr);
+        info!("gRPC: endpoint for {} -> {}", ip, addr);
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl Management for ManagementService {
+    async fn get_status(&self, _request: Request<GetStatusRequest>) -> Result<Response<NodeStatus>, Status> {
+        let peers = self.peer_manager.status();
+        let (route_failover_total, route_restore_total) = self.peer_manager.route_failover_counts();
+        Ok(Response::new(NodeStatus {
+            hostname: self.node.hostname.clone(),
+            address: self.node.address.clone(),
+            public_key: self.node.public_key.clone(),
+            listen_port: self.node.listen_port as u32,
+            gateway: self.node.gateway,
+            interface: self.node.interface.clone(),
+            uptime_secs: self.start_time.elapsed().as_secs(),
+            peers: peers.len() as u32,
+            connected_peers: peers.iter().filter(|p| p.connected).count() as u32,
+            route_failover_total,
+            route_restore_total,
+            active_hub: self.peer_manager.active_hub().map(|ip| ip.to_string()).unwrap_or_default(),
+        }))
+    }
+
+    type ListPeersStream = Pin<Box<dyn Stream<Item = Result<PeerInfo, Status>> + Send>>;
+
+    async fn list_peers(&self, _request: Request<ListPeersRequest>) -> Result<Response<Self::ListPeersStream>, Status> {
+        let peers = self.peer_manager.status().into_iter().map(PeerInfo::from);
+        Ok(Response::new(Box::pin(tokio_stream::iter(peers).map(Ok))))
+    }
+
+    async fn add_peer(&self, request: Request<PeerConfig>) -> Result<Response<OperationResult>, Status> {
+        Ok(Response::new(operation_result(self.add_peer(request.get_ref()))))
+    }
+
+    async fn remove_peer(&self, request: Request<RemovePeerRequest>) -> Result<Response<OperationResult>, Status> {
+        Ok(Response::new(operation_result(self.remove_peer(&request.get_ref().peer_ip))))
+    }
+
+    async fn update_endpoint(&self, request: Request<UpdateEndpointRequest>) -> Result<Response<OperationResult>, Status> {
+        let req = request.get_ref();
+        Ok(Response::new(operation_result(self.update_endpoint(&req.peer_ip, &req.endpoint))))
+    }
+
+    async fn get_traffic_stats(&self, request: Request<TrafficStatsRequest>) -> Result<Response<TrafficStats>, Status> {
+        let ip = parse_peer_ip(&request.get_ref().peer_ip).map_err(Status::invalid_argument)?;
+        self.peer_manager.with_peer_by_ip(&ip, |peer| TrafficStats {
+            peer_ip: ip.to_string(),
+            rx_bytes: peer.rx_bytes,
+            tx_bytes: peer.tx_bytes,
+            pkts_sent: peer.pkts_sent,
+            pkts_received: peer.pkts_received,
+            loss_pct: peer.loss_rate.map(|l| l * 100.0),
+        })
+        .map(Response::new)
+        .ok_or_else(|| Status::not_found(format!("unknown peer {}", ip)))
+    }
+}
+
+impl From<PeerStatus> for PeerInfo {
+    fn from(p: PeerStatus) -> Self {
+        Self {
+            hostname: p.hostname,
+            address: p.address,
+            endpoint: p.endpoint,
+            public_key: p.public_key,
+            last_seen_secs: p.last_seen_secs,
+            rx_bytes: p.rx_bytes,
+            tx_bytes: p.tx_bytes,
+            connected: p.connected,
+            is_gateway: p.is_gateway,
+            relay_via: p.relay_via,
+            rtt_us: p.rtt_us,
+            loss_pct: p.loss_pct,
+            quality: p.quality.map(u32::from),
+            effective_mtu: p.effective_mtu.map(u32::from),
+            retry_in_secs: p.retry_in_secs,
+            revoked: p.revoked,
+        }
+    }
+}
+
+fn parse_peer_ip(s: &str) -> Result<Ipv4Addr, String> {
+    s.parse().map_err(|e| format!("invalid peer IP '{}': {}", s, e))
+}
+
+fn operation_result(result: Result<(), String>) -> OperationResult {
+    match result {
+        Ok(()) => OperationResult { success: true, error: String::new() },
+        Err(error) => OperationResult { success: false, error },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+    use tonic::Code;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    fn service() -> (ManagementService, Arc<PeerManager>) {
+        let keypair = Arc::new(KeyPair::generate());
+        let node = NodeInfo {
+            hostname: "node-a".into(),
+            address: "10.0.10.1".into(),
+            public_key: keypair.public_key_base64(),
+            listen_port: 9600,
+            gateway: true,
+            interface: "wolfnet0".into(),
+        };
+        let peer_manager = Arc::new(PeerManager::new());
+        (ManagementService::new(node, keypair, peer_manager.clone(), Instant::now()), peer_manager)
+    }
+
+    fn peer_config(ip: &str, endpoint: &str) -> PeerConfig {
+        PeerConfig {
+            public_key: KeyPair::generate().public_key_base64(),
+            endpoint: endpoint.into(),
+            allowed_ip: ip.into(),
+            name: "node-b".into(),
+        }
+    }
+
+    fn add(service: &ManagementService, config: PeerConfig) -> OperationResult {
+        block_on(Management::add_peer(service, Request::new(config))).unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_get_status() {
+        let (service, peer_manager) = service();
+        let keypair = KeyPair::generate();
+        let mut connected = Peer::new(keypair.public, Ipv4Addr::new(10, 0, 10, 2));
+        connected.establish_session(&keypair);
+        connected.mark_alive();
+        peer_manager.add_peer(connected);
+        peer_manager.add_peer(Peer::new(KeyPair::generate().public, Ipv4Addr::new(10, 0, 10, 3)));
+
+        let status = block_on(service.get_status(Request::new(GetStatusRequest {}))).unwrap().into_inner();
+        assert_eq!(status.hostname, "node-a");
+        assert_eq!(status.address, "10.0.10.1");
+        assert_eq!(status.listen_port, 9600);
+        assert!(status.gateway);
+        assert_eq!(status.interface, "wolfnet0");
+        assert_eq!(status.peers, 2);
+        assert_eq!(status.connected_peers, 1);
+        assert_eq!(status.active_hub, "");
+    }
+
+    #[test]
+    fn test_list_peers() {
+        let (service, _) = service();
+        assert!(add(&service, peer_config("10.0.10.2", "192.0.2.2:9600")).success);
+        assert!(add(&service, peer_config("10.0.10.3", "")).success);
+
+        let peers: Vec<PeerInfo> = block_on(async {
+            let stream = service.list_peers(Request::new(ListPeersRequest {})).await.unwrap().into_inner();
+            stream.map(Result::unwrap).collect().await
+        });
+        let mut endpoints: Vec<(String, String)> = peers.into_iter().map(|p| (p.address, p.endpoint)).collect();
+        endpoints.sort();
+        assert_eq!(endpoints, [
+            ("10.0.10.2".to_string(), "192.0.2.2:9600".to_string()),
+            ("10.0.10.3".to_string(), "-".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_add_peer() {
+        let (service, peer_manager) = service();
+        let config = peer_config("10.0.10.2", "192.0.2.2:9600");
+        let key = config.public_key.clone();
+        assert_eq!(add(&service, config), OperationResult { success: true, error: String::new() });
+
+        let ip = Ipv4Addr::new(10, 0, 10, 2);
+        let (hostname, public_key, has_session) = peer_manager
+            .with_peer_by_ip(&ip, |p| (p.hostname.clone(), BASE64.encode(p.public_key.as_bytes()), p.cipher.is_some()))
+            .unwrap();
+        assert_eq!(hostname, "node-b");
+        assert_eq!(public_key, key);
+        assert!(has_session, "ready to handshake");
+        assert_eq!(peer_manager.find_ip_by_endpoint(&"192.0.2.2:9600".parse().unwrap()), Some(ip));
+    }
+
+    #[test]
+    fn test_add_peer_rejects_bad_input() {
+        let (service, peer_manager) = service();
+        assert!(add(&service, peer_config("10.0.10.2", "")).success);
+
+        let duplicate = add(&service, peer_config("10.0.10.2", ""));
+        assert!(!duplicate.success);
+        assert!(duplicate.error.contains("already exists"), "{}", duplicate.error);
+
+        let mut bad_key = peer_config("10.0.10.3", "");
+        bad_key.public_key = "not a key".into();
+        assert!(add(&service, bad_key).error.contains("invalid public key"));
+        assert!(add(&service, peer_config("10.0.10", "")).error.contains("invalid peer IP"));
+        assert!(add(&service, peer_config("10.0.10.4", "no port")).error.contains("cannot resolve endpoint"));
+        assert_eq!(peer_manager.all_ips(), [Ipv4Addr::new(10, 0, 10, 2)]);
+    }
+
+    #[test]
+    fn test_remove_peer() {
+        let (service, peer_manager) = service();
+        assert!(add(&service, peer_config("10.0.10.2", "192.0.2.2:9600")).success);
+        let remove = |peer_ip: &str| block_on(Management::remove_peer(&service,
+            Request::new(RemovePeerRequest { peer_ip: peer_ip.into() }))).unwrap().into_inner();
+
+        assert!(remove("10.0.10.2").success);
+        assert!(peer_manager.all_ips().is_empty());
+        assert_eq!(peer_manager.find_ip_by_endpoint(&"192.0.2.2:9600".parse().unwrap()), None);
+
+        assert!(remove("10.0.10.2").error.contains("unknown peer"));
+        assert!(remove("bogus").error.contains("invalid peer IP"));
+    }
+
+    #[test]
+    fn test_update_endpoint() {
+        let (service, peer_manager) = service();
+        assert!(add(&service, peer_config("10.0.10.2", "192.0.2.2:9600")).success);
+        let update = |peer_ip: &str, endpoint: &str| block_on(Management::update_endpoint(&service,
+            Request::new(UpdateEndpointRequest { peer_ip: peer_ip.into(), endpoint: endpoint.into() }))).unwrap().into_inner();
+
+        assert!(update("10.0.10.2", "198.51.100.7:9700").success);
+        let ip = Ipv4Addr::new(10, 0, 10, 2);
+        let (endpoint, configured) = peer_manager.with_peer_by_ip(&ip, |p| (p.endpoint, p.configured_endpoint.clone())).unwrap();
+        assert_eq!(endpoint, Some("198.51.100.7:9700".parse().unwrap()));
+        assert_eq!(configured.as_deref(), Some("198.51.100.7:9700"));
+        assert_eq!(peer_manager.find_ip_by_endpoint(&"192.0.2.2:9600".parse().unwrap()), None);
+        assert_eq!(peer_manager.find_ip_by_endpoint(&"198.51.100.7:9700".parse().unwrap()), Some(ip));
+
+        assert!(update("10.0.10.9", "198.51.100.7:9700").error.contains("unknown peer"));
+        assert!(update("10.0.10.2", "no port").error.contains("cannot resolve endpoint"));
+    }
+
+    #[test]
+    fn test_get_traffic_stats() {
+        let (service, peer_manager) = service();
+        assert!(add(&service, peer_config("10.0.10.2", "")).success);
+        peer_manager.with_peer_by_ip(&Ipv4Addr::new(10, 0, 10, 2), |p| {
+            p.rx_bytes = 1000;
+            p.tx_bytes = 2000;
+            p.pkts_received = 10;
+            p.pkts_sent = 20;
+            p.loss_rate = Some(0.25);
+        });
+        let stats = |peer_ip: &str| block_on(service.get_traffic_stats(
+            Request::new(TrafficStatsRequest { peer_ip: peer_ip.into() })))
+            .map(Response::into_inner)
+            .map_err(|status| status.code());
+
+        let traffic = stats("10.0.10.2").unwrap();
+        assert_eq!(traffic, TrafficStats {
+            peer_ip: "10.0.10.2".into(),
+            rx_bytes: 1000,
+            tx_bytes: 2000,
+            pkts_sent: 20,
+            pkts_received: 10,
+            loss_pct: Some(25.0),
+        });
+        assert_eq!(stats("10.0.10.9"), Err(Code::NotFound));
+        assert_eq!(stats("bogus"), Err(Code::InvalidArgument));
+    }
+}
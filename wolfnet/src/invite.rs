@@ -0,0 +1,141 @@
+//! Signed, expiring invite tokens
+//!
+//! An invite token is `base64(payload JSON) "." base64(signature)`. The
+//! payload carries the inviting node's X25519 public key, endpoint and
+//! address plus a `valid_until` UNIX timestamp. The signature is made with
+//! that X25519 key (XEdDSA, as for peer exchange) and verified against the
+//! key in the payload, so a token cannot be altered, re-signed by another key
+//! or have its expiry extended without the inviting node's private key.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{parse_public_key, verify_signature, KeyPair};
+
+/// Default token lifetime in seconds
+pub const DEFAULT_INVITE_TTL_SECS: u64 = 3600;
+
+/// Contents of an invite token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invite {
+    /// Inviting node's X25519 public key (base64)
+    pub pk: String,
+    /// Inviting node's public endpoint (ip:port)
+    pub ep: String,
+    /// Inviting node's WolfNet IP
+    pub ip: String,
+    /// Network prefix length
+    pub sn: u8,
+    /// Inviting node's listen port
+    pub pt: u16,
+    /// UNIX timestamp after which the token is rejected
+    pub valid_until: u64,
+}
+
+/// Current UNIX time in seconds
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// UNIX time `ttl_secs` from now, or None if that overflows
+pub fn expiry_time(ttl_secs: u64) -> Option<u64> {
+    unix_now().checked_add(ttl_secs)
+}
+
+/// Serialize and sign an invite
+pub fn create_token(kp: &KeyPair, invite: &Invite) -> Result<String, Box<dyn std::error::Error>> {
+    let payload = serde_json::to_vec(invite)?;
+    let signature = kp.sign(&payload)
+        .ok_or("a key held on a PKCS#11 token cannot sign invite tokens; add the peer to the config instead")?;
+    Ok(format!("{}.{}", BASE64.encode(&payload), BASE64.encode(signature)))
+}
+
+/// Decode a token, check its signature and that it has not expired
+pub fn verify_token(token: &str) -> Result<Invite, Box<dyn std::error::Error>> {
+    let (payload_b64, sig_b64) = token.trim().split_once('.')
+        .ok_or("unsigned invite token (generate a new one with 'wolfnet invite')")?;
+    let payload = BASE64.decode(payload_b64)?;
+    let signature: [u8; 64] = BASE64.decode(sig_b64)?.try_into()
+        .map_err(|_| "invalid invite token signature length")?;
+
+    let invite: Invite = serde_json::from_slice(&payload)?;
+    let signer = parse_public_key(&invite.pk)?;
+    if !verify_signature(&signer, &payload, &signature) {
+        return Err("invite token signature is invalid".into());
+    }
+
+    let now = unix_now();
+    if invite.valid_until < now {
+        return Err(format!("invite token expired {}s ago", now - invite.valid_until).into());
+    }
+    Ok(invite)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invite(kp: &KeyPair, valid_until: u64) -> Invite {
+        Invite {
+            pk: kp.public_key_base64(),
+            ep: "203.0.113.7:9600".to_string(),
+            ip: "10.0.10.1".to_string(),
+            sn: 24,
+            pt: 9600,
+            valid_until,
+        }
+    }
+
+    #[test]
+    fn test_valid_token_round_trips() {
+        let kp = KeyPair::generate();
+        let token = create_token(&kp, &invite(&kp, expiry_time(60).unwrap())).unwrap();
+        let decoded = verify_token(&token).unwrap();
+        assert_eq!(decoded.pk, kp.public_key_base64());
+        assert_eq!(decoded.ep, "203.0.113.7:9600");
+    }
+
+    #[test]
+    fn test_tampered_token_is_rejected() {
+        let kp = KeyPair::generate();
+        let token = create_token(&kp, &invite(&kp, expiry_time(60).unwrap())).unwrap();
+        let (payload, signature) = token.split_once('.').unwrap();
+
+        // Point the invite at another endpoint, keeping the signature
+        let mut changed: Invite = serde_json::from_slice(&BASE64.decode(payload).unwrap()).unwrap();
+        changed.ep = "198.51.100.1:9600".to_string();
+        let forged = format!("{}.{}", BASE64.encode(serde_json::to_vec(&changed).unwrap()), signature);
+        assert!(verify_token(&forged).is_err());
+
+        // Extend the expiry, keeping the signature
+        changed = serde_json::from_slice(&BASE64.decode(payload).unwrap()).unwrap();
+        changed.valid_until += 86_400;
+        let forged = format!("{}.{}", BASE64.encode(serde_json::to_vec(&changed).unwrap()), signature);
+        assert!(verify_token(&forged).is_err());
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let kp = KeyPair::generate();
+        let token = create_token(&kp, &invite(&kp, unix_now() - 10)).unwrap();
+        let err = verify_token(&token).unwrap_err().to_string();
+        assert!(err.contains("expired"), "{}", err);
+    }
+
+    #[test]
+    fn test_token_re_signed_by_another_key_is_rejected() {
+        let kp = KeyPair::generate();
+        let attacker = KeyPair::generate();
+        // The payload still names the inviting node's key
+        let token = create_token(&attacker, &invite(&kp, expiry_time(60).unwrap())).unwrap();
+        assert!(verify_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_expiry_time_overflow() {
+        assert!(expiry_time(DEFAULT_INVITE_TTL_SECS).is_some());
+        assert!(expiry_time(u64::MAX).is_none());
+    }
+}
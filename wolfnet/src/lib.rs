@@ -9,6 +9,19 @@ pub mod tun;
 pub mod peer;
 pub mod transport;
 pub mod gateway;
+pub mod obfuscation;
+pub mod invite;
+pub mod mdns;
+pub mod split_tunnel;
+pub mod subnet_routes;
+pub mod pkcs11;
+pub mod grpc;
+pub mod turn;
+pub mod dns;
+pub mod capture;
+pub mod mirror;
+pub mod crl;
+pub mod mobility;
 
 pub use config::Config;
 pub use crypto::KeyPair;
@@ -6,21 +6,31 @@
 //! Supports automatic peer exchange (PEX) so joining one node
 //! automatically gives you access to all its peers.
 
-use std::net::{UdpSocket, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::net::{UdpSocket, Ipv4Addr, SocketAddr, TcpStream};
 use std::io::{Read, Write};
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 use std::time::{Duration, Instant};
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
-use tracing::{info, warn, error};
+use tracing::{debug, info, warn, error};
 
 
 use wolfnet::config::{Config, NodeStatus};
 use wolfnet::crypto::KeyPair;
-use wolfnet::peer::{Peer, PeerManager};
+use wolfnet::invite::{create_token, expiry_time, unix_now, verify_token, Invite, DEFAULT_INVITE_TTL_SECS};
+use wolfnet::peer::{Peer, PeerManager, HUB_PROBE_INTERVAL};
+use wolfnet::obfuscation::ObfuscatedSocket;
 use wolfnet::tun::{self, TunDevice};
-use wolfnet::transport;
+use wolfnet::transport::{self, resolve_endpoint};
+use wolfnet::mdns;
+use wolfnet::turn::{self, TurnClient, TurnServer};
+use wolfnet::capture::{Capture, CaptureRequest};
+use wolfnet::mirror::Mirror;
+use wolfnet::mobility::{self, ADDRESS_CHANGE_SETTLE};
+
+/// Written by the daemon every 5 seconds, read by `wolfnet hub list` and wolfnetctl
+const STATUS_FILE: &str = "/var/run/wolfnet/status.json";
 
 #[derive(Parser)]
 #[command(name = "wolfnet", version, about = "WolfNet — Secure private mesh networking")]
@@ -33,6 +43,10 @@ struct Cli {
     #[arg(long)]
     debug: bool,
 
+    /// Daemon: wait until this many peers have answered a handshake before forwarding traffic
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    wait_for_peers: usize,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -56,12 +70,126 @@ enum Commands {
         address: String,
     },
     /// Generate an invite token for a new peer to join your network
-    Invite,
+    Invite {
+        /// Seconds until the token expires
+        #[arg(long, default_value_t = DEFAULT_INVITE_TTL_SECS)]
+        expires_in: u64,
+    },
     /// Join a WolfNet network using an invite token
     Join {
         /// The invite token from 'wolfnet invite'
         token: String,
     },
+    /// Check an invite token's signature and expiry without joining
+    VerifyToken {
+        /// The invite token to check
+        token: String,
+    },
+    /// Manage per-peer firewall rules
+    Rules {
+        #[command(subcommand)]
+        action: RulesCommand,
+    },
+    /// Inspect split-tunnel routes
+    SplitTunnel {
+        #[command(subcommand)]
+        action: SplitTunnelCommand,
+    },
+    /// Inspect DNS routing through the tunnel
+    Dns {
+        #[command(subcommand)]
+        action: DnsCommand,
+    },
+    /// Manage subnet routes to containers/VMs behind WolfNet nodes
+    Route {
+        #[command(subcommand)]
+        action: RouteCommand,
+    },
+    /// Record tunnel traffic from the running daemon to a PCAP file
+    Capture {
+        /// PCAP file to write
+        #[arg(short, long, default_value = "/tmp/wolfnet.pcap")]
+        output: PathBuf,
+        /// Seconds to capture for
+        #[arg(short, long, default_value_t = 10)]
+        duration: u64,
+        /// Only packets to or from this peer (WolfNet IP)
+        #[arg(long)]
+        peer: Option<Ipv4Addr>,
+        /// Also record the encrypted UDP datagrams (as Ethernet frames)
+        #[arg(long)]
+        encrypted: bool,
+        /// Stop when the file reaches this size (e.g. 50M, 1G)
+        #[arg(long, default_value = "50M")]
+        max_size: String,
+    },
+    /// Watch the packets the running daemon mirrors (`network.mirror_socket`)
+    Mirror {
+        #[command(subcommand)]
+        action: MirrorCommand,
+    },
+    /// Inspect the hubs that carry traffic with no direct route
+    Hub {
+        #[command(subcommand)]
+        action: HubCommand,
+    },
+    /// Revoke a peer's key: add it to `security.crl_file` and tell the daemon
+    Revoke {
+        /// The peer's public key (base64)
+        #[arg(long)]
+        pubkey: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MirrorCommand {
+    /// Subscribe to the mirror socket and print each packet as a hexdump
+    Start {
+        /// The daemon's `network.mirror_socket`
+        #[arg(long, default_value = "/var/run/wolfnet/mirror.sock")]
+        socket: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum RulesCommand {
+    /// Show configured peer rules and the iptables rules currently installed
+    List,
+    /// Remove all installed peer rules from iptables
+    Flush,
+}
+
+#[derive(Subcommand)]
+enum SplitTunnelCommand {
+    /// Show configured `[[route]]` entries and the routes currently installed
+    List,
+}
+
+#[derive(Subcommand)]
+enum RouteCommand {
+    /// Route a subnet through a WolfNet node, e.g. `route add 172.17.0.0/24 via 10.0.10.2`
+    Add {
+        /// Subnet in CIDR notation
+        network: String,
+        #[arg(value_name = "via", value_parser = ["via"], hide_possible_values = true)]
+        via: String,
+        /// WolfNet IP of the node hosting the subnet
+        host: Ipv4Addr,
+    },
+    /// Show configured subnet routes and the routes installed (including learned ones)
+    List,
+}
+
+#[derive(Subcommand)]
+enum HubCommand {
+    /// Show known hubs with their RTT and which one is selected
+    List,
+}
+
+#[derive(Subcommand)]
+enum DnsCommand {
+    /// Show the domains resolved through the tunnel and what the resolver has active
+    ListOverrides,
 }
 
 fn main() {
@@ -74,7 +202,7 @@ fn main() {
 
     // Commands that need root access (for /etc/wolfnet/)
     match &cli.command {
-        Some(Commands::Invite) | Some(Commands::Join { .. }) | None => {
+        Some(Commands::Invite { .. }) | Some(Commands::Join { .. }) | Some(Commands::Rules { .. }) | Some(Commands::SplitTunnel { .. }) | Some(Commands::Dns { .. }) | Some(Commands::Route { .. }) | Some(Commands::Capture { .. }) | Some(Commands::Mirror { .. }) | Some(Commands::Hub { .. }) | Some(Commands::Revoke { .. }) | None => {
             if unsafe { libc::geteuid() } != 0 {
                 eprintln!("✗ This command needs root access (to read /etc/wolfnet/).");
                 eprintln!("  Run with: sudo wolfnet {}", std::env::args().skip(1).collect::<Vec<_>>().join(" "));
@@ -89,9 +217,20 @@ fn main() {
         Some(Commands::Pubkey) => cmd_pubkey(&cli.config),
         Some(Commands::Token) => cmd_token(&cli.config),
         Some(Commands::Init { address }) => cmd_init(&cli.config, &address),
-        Some(Commands::Invite) => cmd_invite(&cli.config),
+        Some(Commands::Invite { expires_in }) => cmd_invite(&cli.config, expires_in),
         Some(Commands::Join { token }) => cmd_join(&cli.config, &token),
-        None => run_daemon(&cli.config),
+        Some(Commands::VerifyToken { token }) => cmd_verify_token(&token),
+        Some(Commands::Rules { action }) => cmd_rules(&cli.config, action),
+        Some(Commands::SplitTunnel { action }) => cmd_split_tunnel(&cli.config, action),
+        Some(Commands::Dns { action }) => cmd_dns(&cli.config, action),
+        Some(Commands::Route { action }) => cmd_route(&cli.config, action),
+        Some(Commands::Capture { output, duration, peer, encrypted, max_size }) => {
+            cmd_capture(output, duration, peer, encrypted, &max_size)
+        }
+        Some(Commands::Mirror { action }) => cmd_mirror(action),
+        Some(Commands::Hub { action }) => cmd_hub(&cli.config, action),
+        Some(Commands::Revoke { pubkey }) => cmd_revoke(&cli.config, &pubkey),
+        None => run_daemon(&cli.config, cli.wait_for_peers),
     }
 }
 
@@ -108,7 +247,7 @@ fn cmd_genkey(output: &PathBuf) {
 
 fn cmd_pubkey(config_path: &PathBuf) {
     let config = load_config(config_path);
-    match KeyPair::load_or_generate(&config.security.private_key_file) {
+    match KeyPair::from_config(&config.security) {
         Ok(kp) => println!("{}", kp.public_key_base64()),
         Err(e) => { error!("Failed to load key: {}", e); std::process::exit(1); }
     }
@@ -116,7 +255,7 @@ fn cmd_pubkey(config_path: &PathBuf) {
 
 fn cmd_token(config_path: &PathBuf) {
     let config = load_config(config_path);
-    match KeyPair::load_or_generate(&config.security.private_key_file) {
+    match KeyPair::from_config(&config.security) {
         Ok(kp) => {
             let pubkey = kp.public_key_base64();
             let bind = format!("0.0.0.0:{}", config.network.listen_port);
@@ -152,31 +291,6 @@ fn cmd_init(config_path: &PathBuf, address: &str) {
     }
 }
 
-/// Resolve an endpoint string to a SocketAddr.
-/// Supports both IP:port (e.g. "203.0.113.5:9600") and hostname:port (e.g. "myhome.dyndns.org:9600").
-fn resolve_endpoint(ep: &str) -> Option<SocketAddr> {
-    // Try direct parse first (fastest path for IP:port)
-    if let Ok(addr) = ep.parse::<SocketAddr>() {
-        return Some(addr);
-    }
-    // Fall back to DNS resolution (supports hostnames like myhome.dyndns.org:9600)
-    match ep.to_socket_addrs() {
-        Ok(mut addrs) => {
-            let result = addrs.next();
-            if let Some(_addr) = result {
-
-            } else {
-                warn!("DNS resolution for '{}' returned no addresses", ep);
-            }
-            result
-        }
-        Err(e) => {
-            warn!("Failed to resolve endpoint '{}': {}", ep, e);
-            None
-        }
-    }
-}
-
 /// Auto-detect our public IP address
 fn detect_public_ip() -> Option<String> {
     // Try multiple services in case one is down
@@ -203,11 +317,19 @@ fn detect_public_ip() -> Option<String> {
     None
 }
 
-fn cmd_invite(config_path: &PathBuf) {
+fn cmd_invite(config_path: &PathBuf, expires_in: u64) {
+    let valid_until = expiry_time(expires_in).unwrap_or_else(|| {
+        error!("--expires-in {} is too large", expires_in);
+        std::process::exit(1);
+    });
     let config = load_config(config_path);
-    let kp = KeyPair::load_or_generate(&config.security.private_key_file).unwrap_or_else(|e| {
+    let kp = KeyPair::from_config(&config.security).unwrap_or_else(|e| {
         error!("{}", e); std::process::exit(1);
     });
+    if !kp.can_sign() {
+        error!("The private key is on a PKCS#11 token, which cannot sign invite tokens; add the peer to the config instead");
+        std::process::exit(1);
+    }
 
     // Auto-detect public IP
     let public_ip = detect_public_ip();
@@ -220,24 +342,26 @@ fn cmd_invite(config_path: &PathBuf) {
         }
     };
 
-    // Build invite token as JSON → base64
-    let invite = serde_json::json!({
-        "pk": kp.public_key_base64(),
-        "ep": endpoint,
-        "ip": config.network.address,
-        "sn": config.network.subnet,
-        "pt": config.network.listen_port,
+    // Build a signed invite token that expires after `expires_in` seconds
+    let invite = Invite {
+        pk: kp.public_key_base64(),
+        ep: endpoint.clone(),
+        ip: config.network.address.clone(),
+        sn: config.network.subnet,
+        pt: config.network.listen_port,
+        valid_until,
+    };
+    let token = create_token(&kp, &invite).unwrap_or_else(|e| {
+        error!("Cannot create invite token: {}", e);
+        std::process::exit(1);
     });
-    let token = base64::Engine::encode(
-        &base64::engine::general_purpose::STANDARD,
-        invite.to_string().as_bytes(),
-    );
 
     println!();
     println!("  🐺 WolfNet Invite Token");
     println!("  ─────────────────────────────────────");
     println!("Your network: {}/{}", config.network.address, config.network.subnet);
     println!("Public endpoint: {}", endpoint);
+    println!("Expires in: {}s", expires_in);
     println!();
     println!("Share this token with the peer you want to invite:");
     println!();
@@ -247,29 +371,18 @@ fn cmd_invite(config_path: &PathBuf) {
 }
 
 fn cmd_join(config_path: &PathBuf, token: &str) {
-    use base64::Engine;
     use wolfnet::config::PeerConfig;
 
-    // Decode token
-    let decoded = base64::engine::general_purpose::STANDARD.decode(token.trim()).unwrap_or_else(|e| {
+    // Decode token and check its signature and expiry
+    let invite = verify_token(token).unwrap_or_else(|e| {
         error!("Invalid invite token: {}", e);
         std::process::exit(1);
     });
-    let invite: serde_json::Value = serde_json::from_slice(&decoded).unwrap_or_else(|e| {
-        error!("Invalid invite token format: {}", e);
-        std::process::exit(1);
-    });
 
-    let peer_pubkey = invite["pk"].as_str().unwrap_or_else(|| {
-        error!("Token missing public key"); std::process::exit(1);
-    });
-    let peer_endpoint = invite["ep"].as_str().unwrap_or_else(|| {
-        error!("Token missing endpoint"); std::process::exit(1);
-    });
-    let peer_ip = invite["ip"].as_str().unwrap_or_else(|| {
-        error!("Token missing IP"); std::process::exit(1);
-    });
-    let subnet = invite["sn"].as_u64().unwrap_or(24) as u8;
+    let peer_pubkey = invite.pk.as_str();
+    let peer_endpoint = invite.ep.as_str();
+    let peer_ip = invite.ip.as_str();
+    let subnet = invite.sn;
 
     // Load or create config
     let mut config = if config_path.exists() {
@@ -333,12 +446,13 @@ fn cmd_join(config_path: &PathBuf, token: &str) {
                 endpoint: Some(peer_endpoint.to_string()),
                 allowed_ip: peer_ip.to_string(),
                 name: Some("invited-peer".to_string()),
+                is_hub: false,
             });
         }
     }
 
     // Generate or load our keypair
-    let kp = KeyPair::load_or_generate(&config.security.private_key_file).unwrap_or_else(|e| {
+    let kp = KeyPair::from_config(&config.security).unwrap_or_else(|e| {
         error!("Key error: {}", e);
         std::process::exit(1);
     });
@@ -355,17 +469,15 @@ fn cmd_join(config_path: &PathBuf, token: &str) {
         Some(ip) => format!("{}:{}", ip, config.network.listen_port),
         None => format!("{}:{}", config.network.address, config.network.listen_port),
     };
-    let reverse = serde_json::json!({
-        "pk": kp.public_key_base64(),
-        "ep": my_endpoint,
-        "ip": config.network.address,
-        "sn": subnet,
-        "pt": config.network.listen_port,
-    });
-    let reverse_token = base64::Engine::encode(
-        &base64::engine::general_purpose::STANDARD,
-        reverse.to_string().as_bytes(),
-    );
+    let reverse = Invite {
+        pk: kp.public_key_base64(),
+        ep: my_endpoint,
+        ip: config.network.address.clone(),
+        sn: subnet,
+        pt: config.network.listen_port,
+        valid_until: expiry_time(DEFAULT_INVITE_TTL_SECS).expect("default TTL fits"),
+    };
+    let reverse_token = create_token(&kp, &reverse);
 
     println!();
     println!("  🐺 WolfNet — Joined!");
@@ -375,14 +487,339 @@ fn cmd_join(config_path: &PathBuf, token: &str) {
     println!("✓ Your WolfNet IP: {}/{}", config.network.address, subnet);
     println!("✓ Peer added: {} ({})", peer_ip, peer_endpoint);
     println!();
-    println!("Now run this on the inviting node to complete the link:");
-    println!();
-    println!("  sudo wolfnet --config /etc/wolfnet/config.toml join {}", reverse_token);
+    match reverse_token {
+        Ok(reverse_token) => {
+            println!("Now run this on the inviting node to complete the link:");
+            println!();
+            println!("  sudo wolfnet --config /etc/wolfnet/config.toml join {}", reverse_token);
+        }
+        Err(e) => {
+            println!("⚠ No reverse token: {}", e);
+            println!("Add this node to the inviting node's config to complete the link:");
+            println!();
+            println!("  public_key = \"{}\"", reverse.pk);
+            println!("  endpoint = \"{}\"", reverse.ep);
+            println!("  allowed_ip = \"{}\"", reverse.ip);
+        }
+    }
     println!();
     println!("Then restart WolfNet on both nodes:");
     println!("  sudo systemctl restart wolfnet");
 }
 
+fn cmd_verify_token(token: &str) {
+    match verify_token(token) {
+        Ok(invite) => {
+            println!("✓ Token is valid");
+            println!("  Peer: {} ({})", invite.ip, invite.ep);
+            println!("  Public key: {}", invite.pk);
+            println!("  Expires in: {}s", invite.valid_until.saturating_sub(unix_now()));
+        }
+        Err(e) => {
+            eprintln!("✗ Token rejected: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_rules(config_path: &PathBuf, action: RulesCommand) {
+    match action {
+        RulesCommand::List => {
+            let config = load_config(config_path);
+            println!("Configured peer rules ({}):", config.peer_rules.len());
+            for (i, rule) in config.peer_rules.iter().enumerate() {
+                println!("  #{} {:?} {} proto={:?} port={} dest={}",
+                    i,
+                    rule.action,
+                    rule.peer_ip,
+                    rule.proto,
+                    rule.dest_port.map(|p| p.to_string()).unwrap_or_else(|| "*".into()),
+                    rule.dest_network.as_deref().unwrap_or("*"),
+                );
+            }
+            let installed = wolfnet::gateway::list_peer_rules();
+            println!();
+            println!("Installed iptables rules ({}):", installed.len());
+            for line in installed {
+                println!("  {}", line);
+            }
+        }
+        RulesCommand::Flush => {
+            let removed = wolfnet::gateway::flush_peer_rules();
+            println!("✓ Removed {} peer rule(s)", removed);
+        }
+    }
+}
+
+fn cmd_split_tunnel(config_path: &PathBuf, action: SplitTunnelCommand) {
+    match action {
+        SplitTunnelCommand::List => {
+            let config = load_config(config_path);
+            println!("Configured routes ({}):", config.routes.len());
+            for (i, route) in config.routes.iter().enumerate() {
+                println!("  #{} {} via {:?}", i, route.destination, route.via);
+            }
+            let installed = wolfnet::split_tunnel::list_split_routes();
+            println!();
+            println!("Active split routes ({}):", installed.len());
+            for line in installed {
+                println!("  {}", line);
+            }
+        }
+    }
+}
+
+fn cmd_dns(config_path: &PathBuf, action: DnsCommand) {
+    match action {
+        DnsCommand::ListOverrides => {
+            let config = load_config(config_path);
+            let servers = wolfnet::dns::parse_servers(&config.network.dns_servers);
+            let servers: Vec<String> = servers.iter().map(Ipv4Addr::to_string).collect();
+            println!("Configured domains ({}):", config.network.dns_domains.len());
+            for domain in &config.network.dns_domains {
+                println!("  {} → {}", wolfnet::dns::normalize_domain(domain), servers.join(", "));
+            }
+            if config.network.dns_proxy {
+                println!("  (DNS proxy on {})", wolfnet::dns::DNS_PROXY_ADDR);
+            }
+            let active = wolfnet::dns::list_overrides(&config.network.interface);
+            println!();
+            println!("Active resolver settings ({}):", active.len());
+            for line in active {
+                println!("  {}", line);
+            }
+        }
+    }
+}
+
+fn cmd_route(config_path: &PathBuf, action: RouteCommand) {
+    match action {
+        RouteCommand::Add { network, host, .. } => {
+            let mut config = load_config(config_path);
+            let (net, prefix) = match wolfnet::split_tunnel::parse_cidr(&network) {
+                Ok(n) => n,
+                Err(e) => { eprintln!("✗ {}", e); std::process::exit(1); }
+            };
+            let cidr = format!("{}/{}", net, prefix);
+            config.subnet_routes.retain(|r| {
+                wolfnet::split_tunnel::parse_cidr(&r.network).map_or(true, |n| n != (net, prefix))
+            });
+            let local = config.ip_addr().is_ok_and(|ip| ip == host);
+            config.subnet_routes.push(wolfnet::config::SubnetRoute {
+                network: cidr.clone(),
+                via: if local { None } else { Some(host.to_string()) },
+                advertise: local,
+            });
+            if let Err(e) = config.save(config_path) {
+                eprintln!("✗ Failed to save config: {}", e);
+                std::process::exit(1);
+            }
+            println!("✓ Route {} via {} added to {:?}", cidr, host, config_path);
+            println!("  Apply with: sudo systemctl reload wolfnet (or send SIGHUP to the daemon)");
+        }
+        RouteCommand::List => {
+            let config = load_config(config_path);
+            println!("Configured subnet routes ({}):", config.subnet_routes.len());
+            for route in &config.subnet_routes {
+                let via = route.via.as_deref().unwrap_or("this node");
+                let advertised = if route.advertise { " (advertised)" } else { "" };
+                println!("  {} via {}{}", route.network, via, advertised);
+            }
+            let installed = wolfnet::subnet_routes::list_subnet_routes();
+            println!();
+            println!("Installed subnet routes ({}):", installed.len());
+            for line in installed {
+                println!("  {}", line);
+            }
+        }
+    }
+}
+
+fn cmd_capture(output: PathBuf, duration: u64, peer: Option<Ipv4Addr>, encrypted: bool, max_size: &str) {
+    use wolfnet::capture::CaptureRequest;
+
+    let max_size = wolfnet::capture::parse_size(max_size).unwrap_or_else(|e| {
+        eprintln!("✗ {}", e);
+        std::process::exit(1);
+    });
+    // The daemon has its own working directory
+    let output = std::path::absolute(&output).unwrap_or(output);
+    let request = CaptureRequest { output: output.clone(), duration_secs: duration, peer, encrypted, max_size };
+    if let Err(e) = request.submit() {
+        eprintln!("✗ Failed to write {}: {}", wolfnet::capture::CAPTURE_REQUEST_PATH, e);
+        std::process::exit(1);
+    }
+
+    let submitted = Instant::now();
+    while !CaptureRequest::taken() {
+        if submitted.elapsed() > Duration::from_secs(5) {
+            let _ = std::fs::remove_file(wolfnet::capture::CAPTURE_REQUEST_PATH);
+            eprintln!("✗ The WolfNet daemon did not start the capture — is it running?");
+            std::process::exit(1);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let what = match peer {
+        Some(ip) => format!("traffic with {}", ip),
+        None => "all traffic".to_string(),
+    };
+    println!("Capturing {} for {}s to {:?}...", what, duration, output);
+
+    // The daemon checks once a second whether the capture is over
+    std::thread::sleep(Duration::from_secs(duration) + Duration::from_millis(1500));
+    match std::fs::metadata(&output) {
+        Ok(meta) => {
+            println!("✓ Wrote {} bytes to {:?}", meta.len(), output);
+            println!("  Open with: wireshark {:?} (or tcpdump -r)", output);
+        }
+        Err(e) => {
+            eprintln!("✗ No capture written to {:?}: {} (see the daemon log)", output, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_mirror(action: MirrorCommand) {
+    match action {
+        MirrorCommand::Start { socket } => {
+            let subscription = wolfnet::mirror::subscribe(&socket).unwrap_or_else(|e| {
+                eprintln!("✗ Failed to subscribe to {:?}: {} — is the daemon running with network.mirror_socket set?", socket, e);
+                std::process::exit(1);
+            });
+            println!("Mirroring inbound packets from {:?} (Ctrl-C to stop)", socket);
+            let mut buf = vec![0u8; 65536];
+            let mut count = 0u64;
+            loop {
+                match subscription.recv(&mut buf) {
+                    Ok(n) => {
+                        count += 1;
+                        println!("#{} {}", count, wolfnet::mirror::summary(&buf[..n]));
+                        print!("{}", wolfnet::mirror::hexdump(&buf[..n]));
+                        println!();
+                    }
+                    Err(e) => {
+                        eprintln!("✗ Mirror socket closed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn cmd_hub(config_path: &PathBuf, action: HubCommand) {
+    match action {
+        HubCommand::List => {
+            let config = load_config(config_path);
+            let status: Option<NodeStatus> = std::fs::read_to_string(STATUS_FILE).ok()
+                .and_then(|json| serde_json::from_str(&json).ok());
+            let Some(status) = status else {
+                // Daemon not running: only the configured hubs are known
+                let hubs: Vec<_> = config.peers.iter().filter(|pc| pc.is_hub).collect();
+                println!("Configured hubs ({}), selection: {:?} (daemon not running):", hubs.len(), config.network.hub_selection);
+                for pc in hubs {
+                    println!("  {:<15} {}", pc.allowed_ip, pc.name.as_deref().unwrap_or("-"));
+                }
+                return;
+            };
+            println!("Hubs ({}), selection: {:?}:", status.hubs.len(), config.network.hub_selection);
+            for hub in &status.hubs {
+                let rtt = hub.rtt_us.map_or("-".to_string(), |us| format!("{:.1} ms", us as f64 / 1000.0));
+                let state = match (hub.active, hub.available) {
+                    (true, _) => "selected",
+                    (false, true) => "available",
+                    (false, false) => "offline",
+                };
+                let name = if hub.hostname.is_empty() { "-" } else { &hub.hostname };
+                println!("  {} {:<15} {:<20} {:>10}  {}", if hub.active { "●" } else { " " }, hub.address, name, rtt, state);
+            }
+        }
+    }
+}
+
+fn cmd_revoke(config_path: &PathBuf, pubkey: &str) {
+    let config = load_config(config_path);
+    let Some(crl_file) = config.security.crl_file else {
+        eprintln!("✗ No CRL configured — set security.crl_file in {:?} first", config_path);
+        std::process::exit(1);
+    };
+    match wolfnet::crl::revoke(&crl_file, pubkey) {
+        Ok(true) => println!("✓ Revoked {} in {:?}", pubkey.trim(), crl_file),
+        Ok(false) => println!("✓ {} is already revoked in {:?}", pubkey.trim(), crl_file),
+        Err(e) => {
+            eprintln!("✗ Failed to revoke key: {}", e);
+            std::process::exit(1);
+        }
+    }
+    match wolfnet::crl::signal_daemon() {
+        Ok(pid) => println!("  Sent SIGHUP to the daemon (PID {})", pid),
+        Err(e) => println!("  Daemon not signalled ({}) — the key takes effect when it next starts", e),
+    }
+}
+
+/// Re-read the CRL into the peer manager. Returns the number of revoked keys.
+fn reload_crl(crl_file: &std::path::Path, peer_manager: &PeerManager) -> usize {
+    match wolfnet::crl::load(crl_file) {
+        Ok(keys) => {
+            let count = keys.len();
+            for ip in peer_manager.set_revoked_keys(keys) {
+                warn!("Peer {} is revoked — session dropped", ip);
+            }
+            count
+        }
+        Err(e) => {
+            warn!("CRL not reloaded, keeping the previous list: {}", e);
+            0
+        }
+    }
+}
+
+/// Load the configured subnet routes into the peer manager and install the
+/// kernel routes for every subnet hosted elsewhere. Returns the number installed.
+fn apply_subnet_routes(config: &Config, peer_manager: &PeerManager, tun_name: &str, wolfnet_ip: Ipv4Addr) -> usize {
+    let routes = wolfnet::subnet_routes::configured_routes(config, wolfnet_ip);
+    // Packets for local subnets arrive on the TUN and leave on the bridge
+    if routes.iter().any(|r| r.host == wolfnet_ip) {
+        if let Err(e) = std::fs::write("/proc/sys/net/ipv4/ip_forward", "1") {
+            warn!("Failed to enable IP forwarding: {}", e);
+        }
+    }
+    peer_manager.set_configured_routes(&routes);
+    match wolfnet::subnet_routes::sync_subnet_routes(tun_name, &peer_manager.subnet_routes(), wolfnet_ip) {
+        Ok(n) => n,
+        Err(e) => { warn!("Subnet route setup failed: {}", e); 0 }
+    }
+}
+
+/// End the running packet capture, if any, and flush its file
+fn stop_capture(capture: &mut Option<Arc<Capture>>, socket: &ObfuscatedSocket) {
+    if let Some(stopped) = capture.take() {
+        socket.set_capture(None);
+        let (packets, bytes) = stopped.finish();
+        info!("Packet capture finished: {} packet(s), {} bytes in {}", packets, bytes, stopped.request().output.display());
+    }
+}
+
+/// WolfNet IPs of the `[[peers]]` marked `is_hub`, in config order
+fn configured_hubs(config: &Config) -> Vec<Ipv4Addr> {
+    config.peers.iter()
+        .filter(|pc| pc.is_hub)
+        .filter_map(|pc| pc.allowed_ip.parse().ok())
+        .collect()
+}
+
+/// Public IPs of the configured peers (kept off split-tunnel routes)
+fn peer_endpoint_ips(config: &Config) -> Vec<Ipv4Addr> {
+    config.peers.iter()
+        .filter_map(|pc| pc.endpoint.as_deref())
+        .filter_map(resolve_endpoint)
+        .filter_map(|addr| match addr.ip() {
+            std::net::IpAddr::V4(ip) => Some(ip),
+            std::net::IpAddr::V6(_) => None,
+        })
+        .collect()
+}
+
 fn load_config(path: &PathBuf) -> Config {
     if path.exists() {
         Config::load(path).unwrap_or_else(|e| {
@@ -394,7 +831,34 @@ fn load_config(path: &PathBuf) -> Config {
     }
 }
 
-fn run_daemon(config_path: &PathBuf) {
+/// How a subnet broadcast reaches one peer
+enum BroadcastPath {
+    Direct,
+    /// Through our allocation on this TURN relay
+    Turn(SocketAddr),
+    /// Through the PEX relay peer that told us about it
+    Relay(Ipv4Addr),
+}
+
+/// Encrypt `packet` for `dest_ip` and send it through the TURN relay `server`
+fn send_via_turn(
+    socket: &ObfuscatedSocket,
+    keypair: &KeyPair,
+    peer_manager: &PeerManager,
+    server: SocketAddr,
+    dest_ip: &Ipv4Addr,
+    packet: &[u8],
+) -> bool {
+    let sent = peer_manager.with_peer_by_ip(dest_ip, |peer| {
+        let (counter, ciphertext) = peer.encrypt(packet).ok()?;
+        let pkt = transport::build_data_packet(&keypair.my_peer_id(), counter, &ciphertext);
+        let indication = turn::build_indication(turn::PKT_TURN_SEND, *dest_ip, &pkt);
+        socket.send_to(&indication, server).ok()
+    });
+    sent.flatten().is_some()
+}
+
+fn run_daemon(config_path: &PathBuf, wait_for_peers: usize) {
     let config = load_config(config_path);
     let wolfnet_ip: Ipv4Addr = config.ip_addr().unwrap_or_else(|e| {
         error!("Invalid address '{}': {}", config.network.address, e);
@@ -404,11 +868,18 @@ fn run_daemon(config_path: &PathBuf) {
     info!("WolfNet starting — {} on {}", wolfnet_ip, config.network.interface);
 
     // Load or generate keypair
-    let keypair = Arc::new(KeyPair::load_or_generate(&config.security.private_key_file).unwrap_or_else(|e| {
+    let keypair = Arc::new(KeyPair::from_config(&config.security).unwrap_or_else(|e| {
         error!("Key error: {}", e);
         std::process::exit(1);
     }));
     info!("Public key: {}", keypair.public_key_base64());
+    if !keypair.can_sign() {
+        // The token only does key exchange; say so once rather than letting
+        // peers quietly miss this node's peer exchange
+        warn!("The private key is on a PKCS#11 token, which cannot sign:");
+        warn!("  peer exchange is OFF — this node shares no peers, routes or hubs, so list it in the other nodes' [[peers]]");
+        warn!("  'wolfnet invite' is unavailable, and after a network change peers reconnect with a full handshake");
+    }
 
     // Create TUN device
     let tun = TunDevice::create(&config.network.interface).unwrap_or_else(|e| {
@@ -422,16 +893,33 @@ fn run_daemon(config_path: &PathBuf) {
     });
 
     // Create UDP socket
+    let obfuscator = config.obfuscator().unwrap_or_else(|e| {
+        error!("Invalid obfuscation config: {}", e);
+        std::process::exit(1);
+    });
     let bind_addr = format!("0.0.0.0:{}", config.network.listen_port);
-    let socket = Arc::new(UdpSocket::bind(&bind_addr).unwrap_or_else(|e| {
+    let udp = UdpSocket::bind(&bind_addr).unwrap_or_else(|e| {
         error!("Failed to bind UDP {}: {}", bind_addr, e);
         std::process::exit(1);
-    }));
+    });
+    if config.network.mtu_probe_interval_secs > 0 {
+        if let Err(e) = transport::set_dont_fragment(&udp) {
+            warn!("Failed to set Don't Fragment on UDP socket, path MTU probes may overestimate: {}", e);
+        }
+    }
+    let socket = Arc::new(ObfuscatedSocket::new(udp, obfuscator));
     socket.set_read_timeout(Some(Duration::from_millis(50))).ok();
     info!("Listening on UDP {}", bind_addr);
+    if socket.is_obfuscated() {
+        info!("Traffic obfuscation enabled (xor)");
+    }
 
     // Initialize peer manager and add configured peers
     let peer_manager = Arc::new(PeerManager::new());
+    if let Some(crl_file) = &config.security.crl_file {
+        let revoked = reload_crl(crl_file, &peer_manager);
+        info!("Loaded {} revoked key(s) from {}", revoked, crl_file.display());
+    }
     for pc in &config.peers {
         match wolfnet::crypto::parse_public_key(&pc.public_key) {
             Ok(pub_key) => {
@@ -441,6 +929,7 @@ fn run_daemon(config_path: &PathBuf) {
                 };
                 let mut peer = Peer::new(pub_key, ip);
                 peer.hostname = pc.name.clone().unwrap_or_default();
+                peer.is_hub = pc.is_hub;
                 if let Some(ref ep) = pc.endpoint {
                     // Store original endpoint string for periodic re-resolution (DynDNS support)
                     peer.configured_endpoint = Some(ep.clone());
@@ -449,7 +938,7 @@ fn run_daemon(config_path: &PathBuf) {
                     }
                 }
                 // Pre-establish session (we have the keys)
-                peer.establish_session(&keypair.secret, &keypair.public);
+                peer.establish_session(&keypair);
                 peer_manager.add_peer(peer);
             }
             Err(e) => warn!("Invalid peer public key: {}", e),
@@ -481,19 +970,82 @@ fn run_daemon(config_path: &PathBuf) {
         }
     }
 
+    // Per-peer forwarding policy (replaces any stale rules from a previous run)
+    if !config.peer_rules.is_empty() {
+        match wolfnet::gateway::apply_peer_rules(tun.name(), &config.peer_rules) {
+            Ok(n) => info!("Installed {} peer firewall rule(s)", n),
+            Err(e) => warn!("Peer rule setup failed: {}", e),
+        }
+    }
+
+    // Split-tunnel routes (replaces any stale routes from a previous run)
+    if !config.routes.is_empty() {
+        match wolfnet::split_tunnel::apply_split_routes(tun.name(), &config.routes, &peer_endpoint_ips(&config)) {
+            Ok(n) => info!("Installed {} split-tunnel route(s)", n),
+            Err(e) => warn!("Split-tunnel route setup failed: {}", e),
+        }
+    }
+
+    // Subnet routes for containers/VMs on this node and others
+    if !config.subnet_routes.is_empty() {
+        let n = apply_subnet_routes(&config, &peer_manager, tun.name(), wolfnet_ip);
+        info!("Installed {} subnet route(s)", n);
+    }
+
     // Running flag for graceful shutdown
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
     ctrlc_handler(r);
 
+    // Resolve the internal domains through the tunnel's DNS servers
+    let dns_servers = wolfnet::dns::parse_servers(&config.network.dns_servers);
+    if !dns_servers.is_empty() {
+        if config.network.dns_domains.is_empty() {
+            warn!("dns_servers is set but dns_domains is empty — not changing the system resolver");
+        } else {
+            match wolfnet::dns::apply_dns(tun.name(), &dns_servers, &config.network.dns_domains) {
+                Ok(backend) => info!("Resolving {} via {:?} ({:?})", config.network.dns_domains.join(", "), config.network.dns_servers, backend),
+                Err(e) => warn!("DNS setup failed: {}", e),
+            }
+        }
+        if config.network.dns_proxy {
+            if let Err(e) = wolfnet::dns::start_proxy(&config.network.dns_domains, &dns_servers, running.clone()) {
+                warn!("Failed to start DNS proxy on {}: {}", wolfnet::dns::DNS_PROXY_ADDR, e);
+            }
+        }
+    }
+
     // Register SIGHUP for config hot-reload (checked in main loop)
     unsafe {
         libc::signal(libc::SIGHUP, handle_reload as *const () as libc::sighandler_t);
     }
+    // So `wolfnet revoke` can find us
+    std::fs::create_dir_all("/var/run/wolfnet").ok();
+    if let Err(e) = std::fs::write(wolfnet::crl::PID_FILE, std::process::id().to_string()) {
+        warn!("Failed to write {}: {}", wolfnet::crl::PID_FILE, e);
+    }
 
     let hostname = hostname::get().map(|h| h.to_string_lossy().to_string()).unwrap_or_else(|_| "unknown".into());
     let start_time = Instant::now();
 
+    // TURN relay for peers behind symmetric NAT, on the port after the tunnel's
+    let mut turn_listening = false;
+    if config.network.turn_server && !is_gateway {
+        warn!("turn_server is only available on gateway nodes — not starting the TURN relay");
+    } else if config.network.turn_server {
+        let turn_bind = format!("0.0.0.0:{}", config.network.listen_port.wrapping_add(1));
+        match UdpSocket::bind(&turn_bind) {
+            Ok(udp) => {
+                let turn_socket = ObfuscatedSocket::new(udp, config.obfuscator().ok().flatten());
+                let r = running.clone();
+                std::thread::spawn(move || TurnServer::new().run(turn_socket, r));
+                info!("TURN relay listening on UDP {}", turn_bind);
+                turn_listening = true;
+            }
+            Err(e) => warn!("Failed to bind TURN relay on UDP {}: {}", turn_bind, e),
+        }
+    }
+
     // Spawn discovery threads
     if config.network.discovery {
         let r = running.clone();
@@ -509,11 +1061,36 @@ fn run_daemon(config_path: &PathBuf) {
         let kp = keypair.clone();
         let pm = peer_manager.clone();
         let nid = hostname.clone();
+        if turn_listening && config.network.listen_port.wrapping_add(1) == transport::DISCOVERY_PORT {
+            // Other nodes still find us from our broadcasts
+            warn!("TURN relay took the LAN discovery port {} — not listening for discovery broadcasts", transport::DISCOVERY_PORT);
+        } else {
+            std::thread::spawn(move || {
+                transport::run_discovery_listener(nid, kp, pm, r);
+            });
+        }
+    }
+    if config.network.mdns_discovery {
+        let r = running.clone();
+        let pk = keypair.public;
+        let h = hostname.clone();
+        let gw = is_gateway;
+        let lp = config.network.listen_port;
+        let pm = peer_manager.clone();
         std::thread::spawn(move || {
-            transport::run_discovery_listener(nid, kp, pm, r);
+            mdns::run_mdns_discovery(pk, wolfnet_ip, lp, h, gw, pm, r);
         });
     }
 
+    // Watch for our own addresses changing, to resume sessions from the new one
+    let address_changed = Arc::new(AtomicBool::new(false));
+    if config.network.mobility_support {
+        match mobility::spawn_watcher(address_changed.clone(), running.clone()) {
+            Ok(()) => info!("Mobility support on: sessions resume after network changes"),
+            Err(e) => warn!("Network change watcher not started: {}", e),
+        }
+    }
+
     // Spawn status writer thread
     {
         let r = running.clone();
@@ -525,9 +1102,10 @@ fn run_daemon(config_path: &PathBuf) {
         let gw = is_gateway;
         let iface = config.network.interface.clone();
         std::thread::spawn(move || {
-            let status_path = PathBuf::from("/var/run/wolfnet/status.json");
+            let status_path = PathBuf::from(STATUS_FILE);
             std::fs::create_dir_all("/var/run/wolfnet").ok();
             while r.load(Ordering::Relaxed) {
+                let (route_failover_total, route_restore_total) = pm.route_failover_counts();
                 let status = NodeStatus {
                     hostname: h.clone(),
                     address: addr.clone(),
@@ -537,6 +1115,10 @@ fn run_daemon(config_path: &PathBuf) {
                     interface: iface.clone(),
                     uptime_secs: start_time.elapsed().as_secs(),
                     peers: pm.status(),
+                    route_failover_total,
+                    route_restore_total,
+                    active_hub: pm.active_hub().map(|ip| ip.to_string()),
+                    hubs: pm.hub_status(),
                 };
                 if let Ok(json) = serde_json::to_string_pretty(&status) {
                     let _ = std::fs::write(&status_path, json);
@@ -546,6 +1128,37 @@ fn run_daemon(config_path: &PathBuf) {
         });
     }
 
+    // gRPC management API, on its own runtime alongside the packet threads
+    let _grpc_runtime = config.network.grpc_listen.as_deref().and_then(|listen| {
+        let addr: SocketAddr = match listen.parse() {
+            Ok(addr) => addr,
+            Err(e) => { warn!("Invalid grpc_listen '{}': {}", listen, e); return None; }
+        };
+        let runtime = match tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(e) => { warn!("Failed to start gRPC runtime: {}", e); return None; }
+        };
+        let service = wolfnet::grpc::ManagementService::new(
+            wolfnet::grpc::NodeInfo {
+                hostname: hostname.clone(),
+                address: config.network.address.clone(),
+                public_key: keypair.public_key_base64(),
+                listen_port: config.network.listen_port,
+                gateway: is_gateway,
+                interface: config.network.interface.clone(),
+            },
+            keypair.clone(),
+            peer_manager.clone(),
+            start_time,
+        );
+        runtime.spawn(async move {
+            if let Err(e) = service.serve(addr).await {
+                error!("gRPC server error: {}", e);
+            }
+        });
+        Some(runtime)
+    });
+
     // Spawn TUN reader thread
     let (tun_tx, tun_rx) = std::sync::mpsc::channel::<Vec<u8>>();
     {
@@ -570,19 +1183,69 @@ fn run_daemon(config_path: &PathBuf) {
         });
     }
 
+    // Handshake every configured peer at once rather than on the first tick of the loop
+    let report = transport::send_handshakes(&socket, &keypair, &peer_manager, wolfnet_ip, config.network.listen_port, &hostname, is_gateway);
+    if report.sent + report.failed > 0 {
+        info!("Sent handshakes to {} peer(s), {} failed", report.sent, report.failed);
+    }
+    if wait_for_peers > 0 {
+        info!("Waiting for {} peer(s) to answer", wait_for_peers);
+        let handshake = transport::build_handshake(&keypair, wolfnet_ip, config.network.listen_port, &hostname, is_gateway);
+        let answered = transport::wait_for_peers(&socket, &keypair, &peer_manager, &handshake, wait_for_peers, &running);
+        if answered >= wait_for_peers {
+            info!("{} peer(s) answered", answered);
+        }
+    }
+
     // Main event loop
     info!("WolfNet running — {} ({}) on {}", hostname, wolfnet_ip, tun.name());
     let mut recv_buf = [0u8; 65536];
     let mut last_handshake = Instant::now();
     let mut last_keepalive = Instant::now();
     let mut last_pex = Instant::now();
+    let mut last_ping = Instant::now();
+    let mut last_hub_probe = Instant::now();
+    let mut hub_selection = config.network.hub_selection;
+    let mut last_loss_sample = Instant::now();
+    let mut last_mtu_check = Instant::now();
+    let mut tun_mtu = config.network.mtu;
     let mut last_dns_resolve = Instant::now();
     let mut last_route_reload = Instant::now();
+    let mut last_route_retest = Instant::now();
+    let mut crl_file = config.security.crl_file.clone();
+    let mut crl_refresh = Duration::from_secs(config.security.crl_refresh_secs.max(1));
+    let mut last_crl_reload = Instant::now();
+    // Set when an address change is seen; resumes go out once it settles
+    let mut last_address_change: Option<Instant> = None;
+    // Packets buffered for failed routes, sent again ahead of new TUN traffic
+    let mut route_replay: std::collections::VecDeque<Vec<u8>> = std::collections::VecDeque::new();
+    let mut last_capture_check = Instant::now();
+    let mut capture: Option<Arc<Capture>> = None;
+    let mirror = match Mirror::open(config.network.mirror_interface.as_deref(), config.network.mirror_socket.as_deref()) {
+        Ok(mirror) => {
+            if let Some(mirror) = &mirror {
+                info!("Mirroring plaintext traffic: {}", mirror.describe());
+            }
+            mirror
+        }
+        Err(e) => {
+            warn!("Traffic mirroring not started: {}", e);
+            None
+        }
+    };
+    let mut turn_client = TurnClient::new(config.network.turn_servers.clone());
     let tun_fd = tun.raw_fd();
 
     while running.load(Ordering::Relaxed) {
         // 1. Process packets from TUN (outbound: encrypt and send via UDP)
-        while let Ok(packet) = tun_rx.try_recv() {
+        while let Some(packet) = route_replay.pop_front().or_else(|| tun_rx.try_recv().ok()) {
+            if let Some(capture) = &capture {
+                let peer = tun::get_dest_ip(&packet).map(|dest| peer_manager.find_route(&dest).unwrap_or(dest));
+                capture.record_ip(&packet, peer);
+            }
+            if let Some(mirror) = &mirror {
+                mirror.outbound(&packet);
+            }
             if let Some(dest_ip) = tun::get_dest_ip(&packet) {
                 // Handle subnet broadcast — send to ALL peers (direct + relayed)
                 // This enables services like WolfDisk autodiscovery across the tunnel
@@ -594,7 +1257,8 @@ fn run_daemon(config_path: &PathBuf) {
                 );
                 if dest_ip == subnet_broadcast || dest_ip == Ipv4Addr::BROADCAST {
                     // Collect relay info first (to avoid holding locks while sending)
-                    let mut relay_targets: Vec<(Ipv4Addr, Option<Ipv4Addr>)> = Vec::new();
+                    let turn_relay = turn_client.relay();
+                    let mut relay_targets: Vec<(Ipv4Addr, BroadcastPath)> = Vec::new();
                     for ip in peer_manager.all_ips() {
                         if ip == wolfnet_ip { continue; }
                         let info = peer_manager.with_peer_by_ip(&ip, |peer| {
@@ -602,9 +1266,11 @@ fn run_daemon(config_path: &PathBuf) {
                         });
                         if let Some((connected, relay_via)) = info {
                             if connected {
-                                relay_targets.push((ip, None)); // direct
+                                relay_targets.push((ip, BroadcastPath::Direct));
+                            } else if let Some(server) = turn_relay {
+                                relay_targets.push((ip, BroadcastPath::Turn(server))); // via TURN relay
                             } else if let Some(relay) = relay_via {
-                                relay_targets.push((ip, Some(relay))); // via relay
+                                relay_targets.push((ip, BroadcastPath::Relay(relay))); // via PEX relay
                             }
                         }
                     }
@@ -612,7 +1278,7 @@ fn run_daemon(config_path: &PathBuf) {
                     let mut relayed_via: std::collections::HashSet<Ipv4Addr> = std::collections::HashSet::new();
                     for (ip, relay) in &relay_targets {
                         match relay {
-                            None => {
+                            BroadcastPath::Direct => {
                                 // Direct send
                                 peer_manager.with_peer_by_ip(ip, |peer| {
                                     if let Some(endpoint) = peer.endpoint {
@@ -623,7 +1289,10 @@ fn run_daemon(config_path: &PathBuf) {
                                     }
                                 });
                             }
-                            Some(relay_ip) => {
+                            BroadcastPath::Turn(server) => {
+                                send_via_turn(&socket, &keypair, &peer_manager, *server, ip, &packet);
+                            }
+                            BroadcastPath::Relay(relay_ip) => {
                                 // Send via relay — but only once per relay peer
                                 // The relay will re-broadcast to its connected peers
                                 if relayed_via.insert(*relay_ip) {
@@ -645,6 +1314,23 @@ fn run_daemon(config_path: &PathBuf) {
                     continue;
                 }
 
+                // Prefer a relay when the direct path is dropping packets, or when
+                // the relay path's quality score beats the direct path's
+                let preferred_relay = peer_manager.find_loss_relay(&dest_ip)
+                    .or_else(|| peer_manager.find_quality_relay(&dest_ip));
+                if let Some(relay_ip) = preferred_relay {
+                    let relayed = peer_manager.with_peer_by_ip(&relay_ip, |relay_peer| {
+                        if let Some(endpoint) = relay_peer.endpoint {
+                            if let Ok((counter, ciphertext)) = relay_peer.encrypt(&packet) {
+                                let pkt = transport::build_data_packet(&keypair.my_peer_id(), counter, &ciphertext);
+                                return socket.send_to(&pkt, endpoint).is_ok();
+                            }
+                        }
+                        false
+                    });
+                    if relayed.unwrap_or(false) { continue; }
+                }
+
                 // Try direct peer first
                 let sent = peer_manager.with_peer_by_ip(&dest_ip, |peer| {
                     if let Some(endpoint) = peer.endpoint {
@@ -684,9 +1370,17 @@ fn run_daemon(config_path: &PathBuf) {
                         } else { false }
                     });
                     if routed.unwrap_or(false) { continue; }
+                    // Every route to the subnet is down: hold the packet until one is restored
+                    if peer_manager.buffer_for_route(&dest_ip, &packet, config.network.failover_buffer_packets) { continue; }
+                }
+
+                // Not directly connected — a TURN relay reaches peers behind
+                // symmetric NAT that PEX relays can't, so try it first
+                if let Some(server) = turn_client.relay() {
+                    if send_via_turn(&socket, &keypair, &peer_manager, server, &dest_ip, &packet) { continue; }
                 }
 
-                // Not directly connected — try relay via PEX-learned route
+                // Try relay via PEX-learned route
                 let relay_ip = peer_manager.find_relay_for(&dest_ip);
                 if let Some(relay_ip) = relay_ip {
                     peer_manager.with_peer_by_ip(&relay_ip, |relay_peer| {
@@ -709,8 +1403,8 @@ fn run_daemon(config_path: &PathBuf) {
                     continue;
                 }
 
-                // Fall back to gateway routing
-                if let Some(gw_ip) = peer_manager.find_gateway() {
+                // Fall back to the selected hub, or any gateway
+                if let Some(gw_ip) = peer_manager.active_hub().or_else(|| peer_manager.find_gateway()) {
                     peer_manager.with_peer_by_ip(&gw_ip, |gw_peer| {
                         if let Some(endpoint) = gw_peer.endpoint {
                             match gw_peer.encrypt(&packet) {
@@ -753,26 +1447,40 @@ fn run_daemon(config_path: &PathBuf) {
                 let data = &recv_buf[..n];
                 match data[0] {
                     transport::PKT_HANDSHAKE => {
-                        if let Some((pub_key, peer_ip, _peer_port, is_gw, peer_hostname)) = transport::parse_handshake(data) {
-                            // Use the actual UDP source address — NOT the advertised port.
-                            // Over NAT, the source port differs from listen_port.
-                            let endpoint = src;
-                            peer_manager.update_from_discovery(&pub_key, endpoint, peer_ip, &peer_hostname, is_gw);
-                            peer_manager.with_peer_by_ip(&peer_ip, |peer| {
-                                // Always re-establish session on handshake.
-                                // A handshake means the peer is (re)connecting — their send
-                                // counter resets to 0, so we must reset our recv counter too.
-                                // Without this, a peer restart causes all their new packets
-                                // to be rejected as "replay" because the old recv_counter
-                                // is higher than their new send counter.
-                                peer.establish_session(&keypair.secret, &keypair.public);
-                                peer.last_seen = Some(Instant::now());
-                            });
+                        if let Some(peer_ip) = transport::accept_handshake(data, src, &keypair, &peer_manager) {
+                            debug!("Handshake from {} ({})", peer_ip, src);
                             // Send handshake back
                             let reply = transport::build_handshake(&keypair, wolfnet_ip, config.network.listen_port, &hostname, is_gateway);
                             let _ = socket.send_to(&reply, src);
                         }
                     }
+                    transport::PKT_RESUME => {
+                        if let Some((resume, resumed)) = transport::accept_resume(data, src, &peer_manager) {
+                            if !resumed {
+                                // We lost the session (restart, timeout): start a new one
+                                let reply = transport::build_handshake(&keypair, wolfnet_ip, config.network.listen_port, &hostname, is_gateway);
+                                let _ = socket.send_to(&reply, src);
+                            } else if !resume.is_reply {
+                                let reply = transport::build_resume(&keypair, wolfnet_ip, &resume.session_id, None, true);
+                                let _ = socket.send_to(&reply, src);
+                            }
+                        }
+                    }
+                    transport::PKT_MTU_PROBE => {
+                        // Answer path MTU probes from peers we have a session with
+                        if let Some((peer_id_bytes, size)) = transport::parse_mtu_probe(data) {
+                            let peer_ip = peer_manager.find_ip_by_endpoint(&src)
+                                .or_else(|| peer_manager.find_ip_by_id(&peer_id_bytes));
+                            if let Some(peer_ip) = peer_ip {
+                                peer_manager.with_peer_by_ip(&peer_ip, |peer| {
+                                    if let Ok((ctr, ct)) = peer.encrypt(&transport::build_mtu_ack(size)) {
+                                        let pkt = transport::build_data_packet(&keypair.my_peer_id(), ctr, &ct);
+                                        let _ = socket.send_to(&pkt, src);
+                                    }
+                                });
+                            }
+                        }
+                    }
                     transport::PKT_DATA => {
                         if let Some((peer_id_bytes, counter, ciphertext)) = transport::parse_data_packet(data) {
                             // Find peer by source address, or fall back to peer_id (endpoint roaming)
@@ -784,8 +1492,9 @@ fn run_daemon(config_path: &PathBuf) {
 
                             if let Some(peer_ip) = peer_ip {
                                 let decrypted = peer_manager.with_peer_by_ip(&peer_ip, |peer| {
-                                    peer.decrypt(counter, ciphertext)
-                                });
+                                    // Revoked peers have no session: drop their traffic quietly
+                                    (!peer.revoked).then(|| peer.decrypt(counter, ciphertext))
+                                }).flatten();
                                 match decrypted {
                                     Some(Ok(plaintext)) => {
                                     // Update endpoint if it changed (roaming)
@@ -797,9 +1506,17 @@ fn run_daemon(config_path: &PathBuf) {
 
                                     // Check if this is a PEX message
                                     if plaintext.len() > 1 && plaintext[0] == transport::PKT_PEER_EXCHANGE {
-                                        if let Some(entries) = transport::parse_peer_exchange(&plaintext) {
-
-                                            peer_manager.add_from_pex(&entries, peer_ip, wolfnet_ip, &keypair);
+                                        let sender = peer_manager.with_peer_by_ip(&peer_ip, |peer| peer.public_key);
+                                        let message = sender.and_then(|sender_key| {
+                                            transport::parse_peer_exchange(&plaintext, &sender_key)
+                                        });
+                                        if let Some(message) = message {
+                                            if peer_manager.add_from_pex(&message.entries, peer_ip, wolfnet_ip, &keypair) {
+                                                // Learned subnets changed — route them into the tunnel
+                                                if let Err(e) = wolfnet::subnet_routes::sync_subnet_routes(tun.name(), &peer_manager.subnet_routes(), wolfnet_ip) {
+                                                    warn!("Subnet route update failed: {}", e);
+                                                }
+                                            }
 
                                             // Enable IP forwarding if we have multiple peers (we're a relay)
                                             if peer_manager.count() >= 2 {
@@ -809,6 +1526,28 @@ fn run_daemon(config_path: &PathBuf) {
                                         continue;
                                     }
 
+                                    // Latency probes: answer pings, fold pong RTTs into the peer's average
+                                    if let Some((kind, timestamp_us)) = transport::parse_probe(&plaintext) {
+                                        peer_manager.with_peer_by_ip(&peer_ip, |peer| {
+                                            if kind == transport::PKT_PING {
+                                                let pong = transport::build_probe(transport::PKT_PONG, timestamp_us);
+                                                if let Ok((ctr, ct)) = peer.encrypt(&pong) {
+                                                    let pkt = transport::build_data_packet(&keypair.my_peer_id(), ctr, &ct);
+                                                    let _ = socket.send_to(&pkt, src);
+                                                }
+                                            } else {
+                                                let rtt = transport::now_micros().saturating_sub(timestamp_us);
+                                                peer.record_rtt(rtt);
+                                            }
+                                        });
+                                        continue;
+                                    }
+
+                                    if let Some(size) = transport::parse_mtu_ack(&plaintext) {
+                                        peer_manager.with_peer_by_ip(&peer_ip, |peer| peer.record_mtu_ack(size));
+                                        continue;
+                                    }
+
                                     // If a relayed handshake arrives inside an encrypted data packet,
                                     // just ignore it — handshakes should only be processed when they
                                     // arrive as raw UDP packets (handled in the PKT_HANDSHAKE case above).
@@ -818,6 +1557,12 @@ fn run_daemon(config_path: &PathBuf) {
                                         continue;
                                     }
 
+                                    if let Some(capture) = &capture {
+                                        capture.record_ip(&plaintext, Some(peer_ip));
+                                    }
+                                    if let Some(mirror) = &mirror {
+                                        mirror.inbound(&plaintext);
+                                    }
 
                                     // Check if this packet is for us or needs relaying
                                     if let Some(dest_ip) = tun::get_dest_ip(&plaintext) {
@@ -849,8 +1594,12 @@ fn run_daemon(config_path: &PathBuf) {
                                                 });
                                             }
                                         } else {
-                                            // Relay: re-encrypt and forward to the destination peer
-                                            let forwarded = peer_manager.with_peer_by_ip(&dest_ip, |dest_peer| {
+                                            // Relay: re-encrypt and forward to the destination peer, or
+                                            // to the hub we reach it through (never back to the sender)
+                                            let next_hop = peer_manager.find_relay_for(&dest_ip)
+                                                .filter(|hop| *hop != peer_ip && *hop != wolfnet_ip)
+                                                .unwrap_or(dest_ip);
+                                            let forwarded = peer_manager.with_peer_by_ip(&next_hop, |dest_peer| {
                                                 if let Some(endpoint) = dest_peer.endpoint {
                                                     match dest_peer.encrypt(&plaintext) {
                                                         Ok((ctr, ct)) => {
@@ -910,6 +1659,35 @@ fn run_daemon(config_path: &PathBuf) {
                             }
                         }
                     }
+                    turn::PKT_TURN_ALLOCATE_OK => {
+                        if let Some(lifetime) = turn::parse_allocate_ok(data) {
+                            turn_client.confirm(src, lifetime);
+                        }
+                    }
+                    turn::PKT_TURN_DATA if turn_client.is_server(&src) => {
+                        // Relayed data packet: decrypt it with the sender's session, but
+                        // leave the peer's direct path state (endpoint, liveness) alone
+                        let relayed = turn::parse_indication(turn::PKT_TURN_DATA, data)
+                            .and_then(|(peer_ip, inner)| Some((peer_ip, transport::parse_data_packet(inner)?)));
+                        if let Some((peer_ip, (_peer_id, counter, ciphertext))) = relayed {
+                            let decrypted = peer_manager.with_peer_by_ip(&peer_ip, |peer| {
+                                let plaintext = peer.cipher.as_mut()?.decrypt(counter, ciphertext).ok()?;
+                                peer.rx_bytes += plaintext.len() as u64;
+                                Some(plaintext)
+                            }).flatten();
+                            if let Some(plaintext) = decrypted {
+                                if let Some(capture) = &capture {
+                                    capture.record_ip(&plaintext, Some(peer_ip));
+                                }
+                                if let Some(mirror) = &mirror {
+                                    mirror.inbound(&plaintext);
+                                }
+                                if tun::get_dest_ip(&plaintext).is_some() {
+                                    unsafe { libc::write(tun_fd, plaintext.as_ptr() as *const _, plaintext.len()) };
+                                }
+                            }
+                        }
+                    }
                     transport::PKT_KEEPALIVE => {
                         let mut peer_ip_opt = peer_manager.find_ip_by_endpoint(&src);
                         
@@ -929,7 +1707,7 @@ fn run_daemon(config_path: &PathBuf) {
 
                         if let Some(peer_ip) = peer_ip_opt {
                             peer_manager.with_peer_by_ip(&peer_ip, |peer| {
-                                peer.last_seen = Some(Instant::now());
+                                peer.mark_alive();
                             });
                         }
                     }
@@ -940,12 +1718,26 @@ fn run_daemon(config_path: &PathBuf) {
             Err(_e) => {}
         }
 
-        // 3. Periodic handshakes (every 10s)
-        if last_handshake.elapsed() > Duration::from_secs(10) {
+        // 3. Dead peer detection and reconnect handshakes (checked every second;
+        //    each dead peer is retried on its own exponential backoff)
+        if last_handshake.elapsed() > Duration::from_secs(1) {
+            peer_manager.check_liveness(Duration::from_secs(config.network.peer_timeout_secs));
+            peer_manager.fail_over_routes();
             transport::send_handshakes(&socket, &keypair, &peer_manager, wolfnet_ip, config.network.listen_port, &hostname, is_gateway);
             last_handshake = Instant::now();
         }
 
+        // 3b. Our address changed: once it has settled (addresses often come
+        //     and go in a burst), ask peers to resume their sessions from it
+        if address_changed.swap(false, Ordering::Relaxed) {
+            last_address_change = Some(Instant::now());
+        }
+        if last_address_change.is_some_and(|t| t.elapsed() > ADDRESS_CHANGE_SETTLE) {
+            let sent = transport::send_resume_handshakes(&socket, &keypair, &peer_manager, wolfnet_ip, config.network.listen_port);
+            info!("Network changed: asked {} peer(s) to resume their sessions", sent);
+            last_address_change = None;
+        }
+
         // 4. Periodic keepalives (every 25s)
         if last_keepalive.elapsed() > Duration::from_secs(25) {
             transport::send_keepalives(&socket, &keypair, &peer_manager);
@@ -958,6 +1750,61 @@ fn run_daemon(config_path: &PathBuf) {
             last_pex = Instant::now();
         }
 
+        // 5b. Periodic latency probes (every 30s) — feeds relay path selection
+        if last_ping.elapsed() > Duration::from_secs(30) {
+            transport::send_pings(&socket, &keypair, &peer_manager);
+            last_ping = Instant::now();
+        }
+
+        // 5f. Hub probes and selection (every 5s) — traffic with no other route
+        //     moves off a hub that stops answering
+        if last_hub_probe.elapsed() > HUB_PROBE_INTERVAL {
+            peer_manager.select_hub(hub_selection);
+            transport::send_hub_pings(&socket, &keypair, &peer_manager);
+            last_hub_probe = Instant::now();
+        }
+
+        // 5c. Packet loss sampling (every 30s) — fails lossy direct paths over to relays
+        if last_loss_sample.elapsed() > Duration::from_secs(30) {
+            peer_manager.update_loss(config.network.failover_loss_threshold);
+            peer_manager.update_quality(
+                Duration::from_secs(config.network.peer_timeout_secs),
+                config.network.quality_threshold,
+                config.network.quality_reconnect,
+            );
+            last_loss_sample = Instant::now();
+        }
+
+        // 5e. Failed subnet routes (every 30s) — restore those whose host is back
+        //     and replay what was buffered for them; retry the rest now
+        if last_route_retest.elapsed() > Duration::from_secs(30) {
+            route_replay.extend(peer_manager.restore_routes());
+            last_route_retest = Instant::now();
+        }
+
+        // 5d. Path MTU discovery (checked every second; each peer is re-probed every
+        //     mtu_probe_interval_secs) — the TUN MTU follows the smallest path MTU
+        if config.network.mtu_probe_interval_secs > 0 && last_mtu_check.elapsed() > Duration::from_secs(1) {
+            let interval = Duration::from_secs(config.network.mtu_probe_interval_secs);
+            if peer_manager.check_mtu_probes(interval) {
+                if let Some(mtu) = peer_manager.min_effective_mtu().filter(|m| *m != tun_mtu) {
+                    match tun.set_mtu(mtu) {
+                        Ok(()) => {
+                            info!("TUN MTU set to {} (smallest peer path MTU)", mtu);
+                            tun_mtu = mtu;
+                        }
+                        Err(e) => warn!("{}", e),
+                    }
+                }
+            }
+            transport::send_mtu_probes(&socket, &keypair, &peer_manager);
+            last_mtu_check = Instant::now();
+        }
+
+        // 5e. TURN allocations (refreshed every 2 minutes, retried every 10s
+        //     until a relay answers)
+        turn_client.tick(&socket, wolfnet_ip);
+
         // 6. Periodic DNS re-resolution for hostname-based endpoints (every 60s)
         //    This supports DynDNS — if a peer's hostname resolves to a new IP,
         //    we update the endpoint so handshakes reach them at the new address.
@@ -989,6 +1836,46 @@ fn run_daemon(config_path: &PathBuf) {
             last_route_reload = Instant::now();
         }
 
+        // 6c. Packet capture requested by `wolfnet capture` (checked every second),
+        //     new subscribers to the mirror socket
+        if last_capture_check.elapsed() > Duration::from_secs(1) {
+            if let Some(mirror) = &mirror {
+                mirror.accept_subscribers();
+            }
+            if capture.as_ref().is_some_and(|c| c.is_done()) {
+                stop_capture(&mut capture, &socket);
+            }
+            if let Some(request) = CaptureRequest::take() {
+                stop_capture(&mut capture, &socket);
+                match request {
+                    Ok(request) => match Capture::start(request.clone(), config.network.listen_port) {
+                        Ok(started) => {
+                            info!("Capturing packets to {} for {}s", request.output.display(), request.duration_secs);
+                            let started = Arc::new(started);
+                            socket.set_capture(Some(started.clone()));
+                            capture = Some(started);
+                        }
+                        Err(e) => warn!("Packet capture to {} not started: {}", request.output.display(), e),
+                    },
+                    Err(e) => warn!("Packet capture not started: {}", e),
+                }
+            }
+            if let Some(capture) = &capture {
+                let endpoint = capture.request().peer
+                    .and_then(|ip| peer_manager.with_peer_by_ip(&ip, |peer| peer.endpoint).flatten());
+                capture.set_peer_endpoint(endpoint);
+            }
+            last_capture_check = Instant::now();
+        }
+
+        // 6d. Periodic CRL reload (`security.crl_refresh_secs`)
+        if last_crl_reload.elapsed() > crl_refresh {
+            if let Some(crl_file) = &crl_file {
+                reload_crl(crl_file, &peer_manager);
+            }
+            last_crl_reload = Instant::now();
+        }
+
         // 7. Config hot-reload on SIGHUP — add new peers without restarting
         if RELOAD_FLAG.swap(false, Ordering::SeqCst) {
             info!("SIGHUP received — reloading config...");
@@ -1034,13 +1921,14 @@ fn run_daemon(config_path: &PathBuf) {
                                     // New peer — add it
                                     let mut peer = Peer::new(pub_key, ip);
                                     peer.hostname = pc.name.clone().unwrap_or_default();
+                                    peer.is_hub = pc.is_hub;
                                     if let Some(ref ep) = pc.endpoint {
                                         peer.configured_endpoint = Some(ep.clone());
                                         if let Some(addr) = resolve_endpoint(ep) {
                                             peer.endpoint = Some(addr);
                                         }
                                     }
-                                    peer.establish_session(&keypair.secret, &keypair.public);
+                                    peer.establish_session(&keypair);
                                     peer_manager.add_peer(peer);
                                     added += 1;
                                 }
@@ -1050,8 +1938,45 @@ fn run_daemon(config_path: &PathBuf) {
                     }
                     info!("Config reload complete: {} new peer(s), {} updated", added, updated);
 
+                    // Hubs may have been added, removed or reordered
+                    peer_manager.set_hubs(&configured_hubs(&new_config));
+                    hub_selection = new_config.network.hub_selection;
+
+                    // Re-read the CRL (wolfnet revoke signals us after adding a key)
+                    crl_file = new_config.security.crl_file.clone();
+                    crl_refresh = Duration::from_secs(new_config.security.crl_refresh_secs.max(1));
+                    match &crl_file {
+                        Some(path) => info!("Reload: {} revoked key(s)", reload_crl(path, &peer_manager)),
+                        None => { peer_manager.set_revoked_keys(Default::default()); }
+                    }
+                    last_crl_reload = Instant::now();
+
                     // Also reload subnet routes
                     peer_manager.load_routes(&routes_path);
+
+                    // Re-apply per-peer firewall rules
+                    match wolfnet::gateway::apply_peer_rules(tun.name(), &new_config.peer_rules) {
+                        Ok(n) => info!("Reload: installed {} peer firewall rule(s)", n),
+                        Err(e) => warn!("Reload: peer rule setup failed: {}", e),
+                    }
+
+                    // Re-apply split-tunnel routes
+                    match wolfnet::split_tunnel::apply_split_routes(tun.name(), &new_config.routes, &peer_endpoint_ips(&new_config)) {
+                        Ok(n) => info!("Reload: installed {} split-tunnel route(s)", n),
+                        Err(e) => warn!("Reload: split-tunnel route setup failed: {}", e),
+                    }
+
+                    // Re-apply subnet routes
+                    let n = apply_subnet_routes(&new_config, &peer_manager, tun.name(), wolfnet_ip);
+                    info!("Reload: installed {} subnet route(s)", n);
+
+                    // Re-apply DNS routing (the proxy keeps its startup settings)
+                    let dns_servers = wolfnet::dns::parse_servers(&new_config.network.dns_servers);
+                    if dns_servers.is_empty() || new_config.network.dns_domains.is_empty() {
+                        wolfnet::dns::remove_dns(tun.name());
+                    } else if let Err(e) = wolfnet::dns::apply_dns(tun.name(), &dns_servers, &new_config.network.dns_domains) {
+                        warn!("Reload: DNS setup failed: {}", e);
+                    }
                 }
                 Err(e) => warn!("Config reload failed: {}", e),
             }
@@ -1060,10 +1985,18 @@ fn run_daemon(config_path: &PathBuf) {
 
     // Cleanup
     info!("Shutting down WolfNet...");
+    stop_capture(&mut capture, &socket);
     if config.network.gateway {
         wolfnet::gateway::disable_gateway(tun.name(), &config.cidr());
     }
+    wolfnet::gateway::flush_peer_rules();
+    wolfnet::split_tunnel::flush_split_routes();
+    wolfnet::subnet_routes::flush_subnet_routes();
+    if !config.network.dns_servers.is_empty() || std::path::Path::new(wolfnet::dns::RESOLV_FRAGMENT).exists() {
+        wolfnet::dns::remove_dns(tun.name());
+    }
     let _ = std::fs::remove_file("/var/run/wolfnet/status.json");
+    let _ = std::fs::remove_file(wolfnet::crl::PID_FILE);
     info!("WolfNet stopped.");
 }
 
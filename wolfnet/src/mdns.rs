@@ -0,0 +1,240 @@
+//! mDNS/DNS-SD peer discovery
+//!
+//! Registers this node as a `_wolfnet._udp` service and browses for other
+//! nodes doing the same. Unlike the broadcast discovery in `transport`, this
+//! works on networks that block broadcast between VLANs but forward mDNS, and
+//! nodes show up in `avahi-browse _wolfnet._udp`.
+//!
+//! The service port is the tunnel listen port. TXT records carry `hostname`,
+//! `wolfnet_ip`, `listen_port`, `public_key` (base64) and `gateway` (`1`/`0`).
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use tracing::{debug, info, warn};
+
+use crate::peer::PeerManager;
+
+/// DNS-SD service type WolfNet nodes register under
+pub const SERVICE_TYPE: &str = "_wolfnet._udp.local.";
+
+/// A peer advertised over mDNS
+#[derive(Debug, Clone)]
+pub struct MdnsPeer {
+    pub public_key: x25519_dalek::PublicKey,
+    pub endpoint: SocketAddr,
+    pub wolfnet_ip: Ipv4Addr,
+    pub hostname: String,
+    pub is_gateway: bool,
+}
+
+/// Build the service record advertising this node
+pub fn build_service(
+    public_key: &x25519_dalek::PublicKey,
+    wolfnet_ip: Ipv4Addr,
+    listen_port: u16,
+    hostname: &str,
+    is_gateway: bool,
+) -> Result<ServiceInfo, Box<dyn std::error::Error>> {
+    // Include the WolfNet IP so several nodes on one host get distinct instances
+    let instance = format!("{}-{}", hostname, wolfnet_ip);
+    let host_name = format!("{}.local.", hostname);
+    let properties = [
+        ("hostname", hostname.to_string()),
+        ("wolfnet_ip", wolfnet_ip.to_string()),
+        ("listen_port", listen_port.to_string()),
+        ("public_key", BASE64.encode(public_key.as_bytes())),
+        ("gateway", if is_gateway { "1" } else { "0" }.to_string()),
+    ];
+    // No addresses given: the daemon advertises the host's current ones
+    let service = ServiceInfo::new(SERVICE_TYPE, &instance, &host_name, "", listen_port, &properties[..])?
+        .enable_addr_auto();
+    Ok(service)
+}
+
+/// Parse a resolved service, returning None if it is incomplete
+pub fn parse_service(service: &ServiceInfo) -> Option<MdnsPeer> {
+    let public_key = crate::crypto::parse_public_key(service.get_property_val_str("public_key")?).ok()?;
+    let wolfnet_ip: Ipv4Addr = service.get_property_val_str("wolfnet_ip")?.parse().ok()?;
+    let listen_port: u16 = service.get_property_val_str("listen_port")
+        .and_then(|p| p.parse().ok())
+        .unwrap_or_else(|| service.get_port());
+    let hostname = service.get_property_val_str("hostname").unwrap_or_default().to_string();
+    let is_gateway = service.get_property_val_str("gateway") == Some("1");
+
+    // Prefer a LAN address; loopback only reaches nodes on this host
+    let addresses = service.get_addresses_v4();
+    let ip = addresses.iter().find(|ip| !ip.is_loopback())
+        .or_else(|| addresses.iter().next())
+        .copied()?;
+
+    Some(MdnsPeer {
+        public_key,
+        endpoint: SocketAddr::new(IpAddr::V4(*ip), listen_port),
+        wolfnet_ip,
+        hostname,
+        is_gateway,
+    })
+}
+
+/// Advertise this node and feed discovered peers to the peer manager until
+/// `running` is cleared (call from a thread)
+pub fn run_mdns_discovery(
+    public_key: x25519_dalek::PublicKey,
+    wolfnet_ip: Ipv4Addr,
+    listen_port: u16,
+    hostname: String,
+    is_gateway: bool,
+    peer_manager: Arc<PeerManager>,
+    running: Arc<AtomicBool>,
+) {
+    let daemon = match ServiceDaemon::new() {
+        Ok(d) => d,
+        Err(e) => { warn!("mDNS discovery unavailable: {}", e); return; }
+    };
+
+    match build_service(&public_key, wolfnet_ip, listen_port, &hostname, is_gateway) {
+        Ok(service) => {
+            let fullname = service.get_fullname().to_string();
+            match daemon.register(service) {
+                Ok(()) => info!("Registered mDNS service {}", fullname),
+                Err(e) => warn!("mDNS service registration failed: {}", e),
+            }
+        }
+        Err(e) => warn!("Invalid mDNS service record: {}", e),
+    }
+
+    let events = match daemon.browse(SERVICE_TYPE) {
+        Ok(rx) => rx,
+        Err(e) => { warn!("mDNS browse failed: {}", e); let _ = daemon.shutdown(); return; }
+    };
+
+    while running.load(Ordering::Relaxed) {
+        let Ok(event) = events.recv_timeout(Duration::from_secs(1)) else { continue };
+        if let ServiceEvent::ServiceResolved(service) = event {
+            let Some(peer) = parse_service(&service) else {
+                debug!("Ignoring incomplete mDNS record {}", service.get_fullname());
+                continue;
+            };
+            if peer.public_key == public_key { continue; }
+
+            debug!("mDNS discovered {} ({}) at {}", peer.hostname, peer.wolfnet_ip, peer.endpoint);
+            // Like broadcast discovery, only endpoints are updated; the
+            // handshake mechanism sets up sessions
+            peer_manager.update_from_discovery(&peer.public_key, peer.endpoint, peer.wolfnet_ip, &peer.hostname, peer.is_gateway);
+        }
+    }
+
+    let _ = daemon.shutdown();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::crypto::KeyPair;
+
+    /// What a browser resolves for `service`, seen at `addresses`
+    fn resolved(service: &ServiceInfo, addresses: &str, properties: HashMap<String, String>) -> ServiceInfo {
+        ServiceInfo::new(SERVICE_TYPE, "node", service.get_hostname(), addresses, service.get_port(), properties).unwrap()
+    }
+
+    fn txt(service: &ServiceInfo) -> HashMap<String, String> {
+        service.get_properties().clone().into_property_map_str()
+    }
+
+    #[test]
+    fn test_service_txt_records() {
+        let key = KeyPair::generate().public;
+        let service = build_service(&key, Ipv4Addr::new(10, 0, 10, 2), 9600, "node-a", true).unwrap();
+        assert_eq!(service.get_fullname(), format!("node-a-10.0.10.2.{}", SERVICE_TYPE));
+        assert_eq!(service.get_port(), 9600);
+
+        let txt = txt(&service);
+        assert_eq!(txt["hostname"], "node-a");
+        assert_eq!(txt["wolfnet_ip"], "10.0.10.2");
+        assert_eq!(txt["listen_port"], "9600");
+        assert_eq!(txt["public_key"], BASE64.encode(key.as_bytes()));
+        assert_eq!(txt["gateway"], "1");
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let key = KeyPair::generate().public;
+        let service = build_service(&key, Ipv4Addr::new(10, 0, 10, 2), 9600, "node-a", false).unwrap();
+        let peer = parse_service(&resolved(&service, "127.0.0.1,192.168.1.20", txt(&service))).unwrap();
+
+        assert_eq!(peer.public_key, key);
+        assert_eq!(peer.wolfnet_ip, Ipv4Addr::new(10, 0, 10, 2));
+        assert_eq!(peer.hostname, "node-a");
+        assert!(!peer.is_gateway);
+        // The LAN address wins over loopback
+        assert_eq!(peer.endpoint, "192.168.1.20:9600".parse().unwrap());
+
+        // A node on this host is still reachable over loopback
+        let peer = parse_service(&resolved(&service, "127.0.0.1", txt(&service))).unwrap();
+        assert_eq!(peer.endpoint, "127.0.0.1:9600".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_falls_back_to_service_port() {
+        let key = KeyPair::generate().public;
+        let service = build_service(&key, Ipv4Addr::new(10, 0, 10, 2), 9700, "node-a", false).unwrap();
+        let mut properties = txt(&service);
+        properties.remove("listen_port");
+        let peer = parse_service(&resolved(&service, "192.168.1.20", properties)).unwrap();
+        assert_eq!(peer.endpoint.port(), 9700);
+    }
+
+    #[test]
+    fn test_incomplete_records_ignored() {
+        let key = KeyPair::generate().public;
+        let service = build_service(&key, Ipv4Addr::new(10, 0, 10, 2), 9600, "node-a", false).unwrap();
+
+        // No IPv4 address resolved yet
+        assert!(parse_service(&resolved(&service, "", txt(&service))).is_none());
+
+        for (field, value) in [("public_key", None), ("public_key", Some("not-a-key")),
+                               ("wolfnet_ip", None), ("wolfnet_ip", Some("10.0.10"))] {
+            let mut properties = txt(&service);
+            match value {
+                Some(v) => { properties.insert(field.into(), v.into()); }
+                None => { properties.remove(field); }
+            }
+            assert!(parse_service(&resolved(&service, "192.168.1.20", properties)).is_none(),
+                "{} = {:?} should be rejected", field, value);
+        }
+    }
+
+    #[test]
+    fn test_discovered_peer_merged() {
+        let key = KeyPair::generate().public;
+        let manager = PeerManager::new();
+        let ip = Ipv4Addr::new(10, 0, 10, 2);
+        let discover = |addresses: &str, wolfnet_ip: Ipv4Addr| {
+            let service = build_service(&key, wolfnet_ip, 9600, "node-a", false).unwrap();
+            let peer = parse_service(&resolved(&service, addresses, txt(&service))).unwrap();
+            manager.update_from_discovery(&peer.public_key, peer.endpoint, peer.wolfnet_ip, &peer.hostname, peer.is_gateway);
+        };
+
+        discover("192.168.1.20", ip);
+        let old_endpoint: SocketAddr = "192.168.1.20:9600".parse().unwrap();
+        assert_eq!(manager.find_ip_by_endpoint(&old_endpoint), Some(ip));
+        assert_eq!(manager.with_peer_by_ip(&ip, |p| p.hostname.clone()).unwrap(), "node-a");
+
+        // A new address for the same node replaces the old endpoint
+        discover("192.168.1.30", ip);
+        assert_eq!(manager.find_ip_by_endpoint(&old_endpoint), None);
+        assert_eq!(manager.with_peer_by_ip(&ip, |p| p.endpoint).unwrap(), Some("192.168.1.30:9600".parse().unwrap()));
+
+        // The same key under a new WolfNet IP moves the peer
+        let new_ip = Ipv4Addr::new(10, 0, 10, 9);
+        discover("192.168.1.30", new_ip);
+        assert!(manager.with_peer_by_ip(&ip, |_| ()).is_none());
+        assert_eq!(manager.with_peer_by_ip(&new_ip, |p| p.public_key).unwrap(), key);
+    }
+}
@@ -0,0 +1,332 @@
+//! Traffic mirroring for security monitoring
+//!
+//! Copies of the plaintext IP packets crossing the tunnel, for tools that
+//! cannot see inside the encrypted UDP datagrams:
+//!
+//! - `network.mirror_interface`: every packet sent into the tunnel is also
+//!   written to that interface through a `PF_PACKET` raw socket, behind a
+//!   fake Ethernet header, so a network tap on it captures the original IP
+//!   traffic.
+//! - `network.mirror_socket`: every packet received from the tunnel (after
+//!   decryption) is sent as one datagram to each subscriber of an `AF_UNIX`
+//!   datagram socket at that path. A subscriber binds its own socket, sends
+//!   any datagram to `mirror_socket` and receives packets from then on, until
+//!   its socket goes away. `wolfnet mirror start` is such a subscriber.
+//!
+//! Mirroring never holds up the tunnel: copies the interface or a subscriber
+//! cannot take straight away are dropped.
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::tun;
+
+/// MAC addresses of the fake Ethernet header (locally administered)
+const SRC_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+const DST_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+/// What a subscriber sends to `mirror_socket` to start receiving packets
+pub const SUBSCRIBE: &[u8] = b"subscribe";
+
+/// The plaintext packet in a fake Ethernet frame
+pub fn ethernet_frame(packet: &[u8]) -> Vec<u8> {
+    let ethertype = match packet.first().map(|b| b >> 4) {
+        Some(6) => ETHERTYPE_IPV6,
+        _ => ETHERTYPE_IPV4,
+    };
+    let mut frame = Vec::with_capacity(14 + packet.len());
+    frame.extend_from_slice(&DST_MAC);
+    frame.extend_from_slice(&SRC_MAC);
+    frame.extend_from_slice(&ethertype.to_be_bytes());
+    frame.extend_from_slice(packet);
+    frame
+}
+
+/// A `PF_PACKET` raw socket bound to one interface, for sending frames only
+struct RawInterface {
+    name: String,
+    fd: OwnedFd,
+}
+
+impl RawInterface {
+    fn open(name: &str) -> io::Result<Self> {
+        let c_name = std::ffi::CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let ifindex = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Protocol 0: the socket receives nothing, it is only written to
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_ifindex = ifindex as i32;
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { name: name.to_string(), fd })
+    }
+
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        let sent = unsafe { libc::send(self.fd.as_raw_fd(), frame.as_ptr() as *const _, frame.len(), 0) };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// The `mirror_socket` listener and its subscribers
+struct MirrorSocket {
+    path: PathBuf,
+    socket: UnixDatagram,
+    subscribers: Mutex<Vec<SocketAddr>>,
+}
+
+impl MirrorSocket {
+    fn bind(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Left behind by a previous run
+        let _ = std::fs::remove_file(path);
+        let socket = UnixDatagram::bind(path)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self { path: path.to_path_buf(), socket, subscribers: Mutex::new(Vec::new()) })
+    }
+
+    /// Take in the subscription requests waiting on the socket
+    fn accept(&self) {
+        let mut buf = [0u8; 64];
+        while let Ok((_, addr)) = self.socket.recv_from(&mut buf) {
+            if addr.is_unnamed() {
+                continue; // nowhere to send packets to
+            }
+            let mut subscribers = self.subscribers.lock().unwrap();
+            if !subscribers.iter().any(|s| same_addr(s, &addr)) {
+                tracing::info!("Mirror subscriber connected on {}", self.path.display());
+                subscribers.push(addr);
+            }
+        }
+    }
+
+    fn send(&self, packet: &[u8]) {
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            match self.socket.send_to_addr(packet, subscriber) {
+                Ok(_) => true,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => true,
+                Err(_) => {
+                    tracing::info!("Mirror subscriber on {} went away", self.path.display());
+                    false
+                }
+            }
+        });
+    }
+}
+
+impl Drop for MirrorSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn same_addr(a: &SocketAddr, b: &SocketAddr) -> bool {
+    match (a.as_pathname(), b.as_pathname()) {
+        (Some(a), Some(b)) => a == b,
+        (None, None) => a.as_abstract_name() == b.as_abstract_name(),
+        _ => false,
+    }
+}
+
+/// The configured mirrors
+pub struct Mirror {
+    interface: Option<RawInterface>,
+    socket: Option<MirrorSocket>,
+}
+
+impl Mirror {
+    /// Open `interface` and bind `socket`. Returns None if neither is set.
+    pub fn open(interface: Option<&str>, socket: Option<&Path>) -> io::Result<Option<Self>> {
+        if interface.is_none() && socket.is_none() {
+            return Ok(None);
+        }
+        let interface = interface.map(RawInterface::open).transpose()
+            .map_err(|e| io::Error::new(e.kind(), format!("mirror interface {}: {}", interface.unwrap_or_default(), e)))?;
+        let socket = socket.map(MirrorSocket::bind).transpose()
+            .map_err(|e| io::Error::new(e.kind(), format!("mirror socket {}: {}", socket.unwrap_or(Path::new("")).display(), e)))?;
+        Ok(Some(Self { interface, socket }))
+    }
+
+    /// Where packets are mirrored to, for logging
+    pub fn describe(&self) -> String {
+        let mut targets = Vec::new();
+        if let Some(interface) = &self.interface {
+            targets.push(format!("outbound to {}", interface.name));
+        }
+        if let Some(socket) = &self.socket {
+            targets.push(format!("inbound to {}", socket.path.display()));
+        }
+        targets.join(", ")
+    }
+
+    /// Mirror a plaintext packet about to be sent into the tunnel
+    pub fn outbound(&self, packet: &[u8]) {
+        if let Some(interface) = &self.interface {
+            if let Err(e) = interface.send(&ethernet_frame(packet)) {
+                if e.kind() != io::ErrorKind::WouldBlock {
+                    tracing::debug!("Mirror to {} failed: {}", interface.name, e);
+                }
+            }
+        }
+    }
+
+    /// Mirror a plaintext packet received from the tunnel
+    pub fn inbound(&self, packet: &[u8]) {
+        if let Some(socket) = &self.socket {
+            socket.send(packet);
+        }
+    }
+
+    /// Take in new `mirror_socket` subscribers (called from the daemon loop)
+    pub fn accept_subscribers(&self) {
+        if let Some(socket) = &self.socket {
+            socket.accept();
+        }
+    }
+}
+
+/// Subscribe to the daemon's `mirror_socket`. The returned socket receives
+/// one mirrored packet per datagram.
+pub fn subscribe(path: &Path) -> io::Result<UnixDatagram> {
+    let name = format!("wolfnet-mirror-{}", std::process::id());
+    let socket = UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(name.as_bytes())?)?;
+    socket.connect(path)?;
+    socket.send(SUBSCRIBE)?;
+    Ok(socket)
+}
+
+/// One line summarising a packet, e.g. `10.0.10.2 > 10.0.10.1 proto 6, 60 bytes`
+pub fn summary(packet: &[u8]) -> String {
+    match (tun::get_src_ip(packet), tun::get_dest_ip(packet)) {
+        (Some(src), Some(dst)) => format!("{} > {} proto {}, {} bytes", src, dst, packet[9], packet.len()),
+        _ => format!("{} bytes", packet.len()),
+    }
+}
+
+/// `hexdump -C` style dump: offset, 16 bytes in hex, then the printable ones
+pub fn hexdump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let mut hex = String::with_capacity(49);
+        for (j, byte) in line.iter().enumerate() {
+            if j == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", byte));
+        }
+        let ascii: String = line.iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        out.push_str(&format!("{:08x}  {:<49} |{}|\n", i * 16, hex, ascii));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn ipv4_packet() -> Vec<u8> {
+        let mut packet = vec![0u8; 60];
+        packet[0] = 0x45;
+        packet[9] = 6;
+        packet[12..16].copy_from_slice(&[10, 0, 10, 2]);
+        packet[16..20].copy_from_slice(&[10, 0, 10, 1]);
+        packet
+    }
+
+    #[test]
+    fn test_ethernet_frame() {
+        let packet = ipv4_packet();
+        let frame = ethernet_frame(&packet);
+        assert_eq!(frame[0..6], DST_MAC);
+        assert_eq!(frame[6..12], SRC_MAC);
+        assert_eq!(frame[12..14], [0x08, 0x00]);
+        assert_eq!(frame[14..], packet[..]);
+
+        let ipv6 = [0x60, 0, 0, 0];
+        assert_eq!(ethernet_frame(&ipv6)[12..14], [0x86, 0xdd]);
+        assert_eq!(ethernet_frame(&[]).len(), 14);
+    }
+
+    #[test]
+    fn test_open_targets() {
+        assert!(Mirror::open(None, None).unwrap().is_none(), "mirroring off");
+        let error = Mirror::open(Some("wolfnet-no-such-if"), None).err().unwrap();
+        assert!(error.to_string().starts_with("mirror interface wolfnet-no-such-if:"), "{}", error);
+    }
+
+    #[test]
+    fn test_socket_mirror_subscribers() {
+        let path = std::env::temp_dir().join(format!("wolfnet-test-mirror-{}.sock", std::process::id()));
+        let mirror = Mirror::open(None, Some(&path)).unwrap().unwrap();
+        assert_eq!(mirror.describe(), format!("inbound to {}", path.display()));
+
+        let subscriber = subscribe(&path).unwrap();
+        subscriber.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        // Nothing goes out before the subscription is taken in
+        mirror.inbound(b"early");
+        mirror.accept_subscribers();
+        // Subscribing twice doesn't double the copies
+        subscriber.send(SUBSCRIBE).unwrap();
+        mirror.accept_subscribers();
+
+        let packet = ipv4_packet();
+        mirror.inbound(&packet);
+        // Outbound packets only go to the mirror interface
+        mirror.outbound(b"outbound");
+        let mut buf = [0u8; 256];
+        let n = subscriber.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], packet);
+        assert!(subscriber.recv(&mut buf).is_err(), "one copy per packet");
+
+        // A subscriber that went away is dropped
+        drop(subscriber);
+        mirror.inbound(&packet);
+        assert!(mirror.socket.as_ref().unwrap().subscribers.lock().unwrap().is_empty());
+
+        drop(mirror);
+        assert!(!path.exists(), "socket removed on shutdown");
+    }
+
+    #[test]
+    fn test_summary_and_hexdump() {
+        assert_eq!(summary(&ipv4_packet()), "10.0.10.2 > 10.0.10.1 proto 6, 60 bytes");
+        assert_eq!(summary(&[0x45, 0]), "2 bytes");
+
+        let dump = hexdump(b"WolfNet mirror\x00\x01 test");
+        assert_eq!(dump,
+            "00000000  57 6f 6c 66 4e 65 74 20  6d 69 72 72 6f 72 00 01  |WolfNet mirror..|\n\
+             00000010  20 74 65 73 74                                    | test|\n");
+    }
+}
@@ -0,0 +1,131 @@
+//! Network change detection for session resumption
+//!
+//! With `network.mobility_support`, a thread listens on an `AF_NETLINK`
+//! route socket for IPv4 addresses being added to or removed from any
+//! interface — a laptop moving from Wi-Fi to a mobile hotspot, a DHCP lease
+//! changing. The daemon then sends every connected peer a resume handshake
+//! from its new address, so sessions carry on with their nonce counters
+//! instead of waiting for the peers to time out and re-handshake.
+
+use std::io;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Quiet time after an address change before resume handshakes go out,
+/// since an interface coming up adds and removes addresses in a burst
+pub const ADDRESS_CHANGE_SETTLE: Duration = Duration::from_millis(500);
+
+/// How often the watcher wakes up to check whether the daemon is stopping
+const WAKE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A netlink route socket subscribed to IPv4 address changes
+struct AddressWatch {
+    fd: OwnedFd,
+}
+
+impl AddressWatch {
+    fn open() -> io::Result<Self> {
+        let fd = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as u16;
+        addr.nl_groups = libc::RTMGRP_IPV4_IFADDR as u32;
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let timeout = libc::timeval { tv_sec: 0, tv_usec: WAKE_INTERVAL.as_micros() as libc::suseconds_t };
+        let set = unsafe {
+            libc::setsockopt(
+                fd.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const libc::timeval as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if set < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { fd })
+    }
+
+    /// Wait up to `WAKE_INTERVAL` for messages; true if an address was added or removed
+    fn address_changed(&self, buf: &mut [u8]) -> io::Result<bool> {
+        let n = unsafe { libc::recv(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            // Messages were dropped because we fell behind: assume a change was among them
+            if err.raw_os_error() == Some(libc::ENOBUFS) {
+                return Ok(true);
+            }
+            return match err.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted => Ok(false),
+                _ => Err(err),
+            };
+        }
+        Ok(has_address_change(&buf[..n as usize]))
+    }
+}
+
+/// Whether a batch of netlink messages holds an RTM_NEWADDR or RTM_DELADDR
+fn has_address_change(mut data: &[u8]) -> bool {
+    let header_len = std::mem::size_of::<libc::nlmsghdr>();
+    while data.len() >= header_len {
+        let len = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let kind = u16::from_ne_bytes([data[4], data[5]]);
+        if kind == libc::RTM_NEWADDR || kind == libc::RTM_DELADDR {
+            return true;
+        }
+        if len < header_len || len > data.len() {
+            break;
+        }
+        // Messages are padded to 4 bytes
+        data = &data[((len + 3) & !3).min(data.len())..];
+    }
+    false
+}
+
+/// Watch for address changes on a background thread, setting `changed`
+/// each time one is seen. The thread stops when `running` is cleared.
+pub fn spawn_watcher(changed: Arc<AtomicBool>, running: Arc<AtomicBool>) -> io::Result<()> {
+    let watch = AddressWatch::open()?;
+    std::thread::Builder::new()
+        .name("wolfnet-mobility".to_string())
+        .spawn(move || {
+            let mut buf = vec![0u8; 16384];
+            while running.load(Ordering::Relaxed) {
+                match watch.address_changed(&mut buf) {
+                    Ok(true) => changed.store(true, Ordering::Relaxed),
+                    Ok(false) => {}
+                    Err(e) => {
+                        tracing::warn!("Network change watcher stopped: {}", e);
+                        return;
+                    }
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Local address packets to `target` leave from, after the routing table
+/// has been consulted. No packet is sent.
+pub fn outbound_address(target: SocketAddr) -> Option<IpAddr> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(target).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
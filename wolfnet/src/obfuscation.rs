@@ -0,0 +1,243 @@
+//! Traffic obfuscation for WolfNet
+//!
+//! Hides WolfNet's packet headers from passive deep-packet inspection.
+//! Every datagram on the tunnel socket gets a 1-4 byte random padding prefix
+//! (so packet lengths vary) and is then XOR-masked with a repeating key.
+//! This is not encryption — confidentiality still comes from X25519 +
+//! ChaCha20-Poly1305 — it only stops the type byte and fixed-size headers
+//! from being fingerprinted.
+//!
+//! Wire format before masking:
+//! [1: pad header] [0-3: random padding] [N: WolfNet packet]
+//! The low two bits of the pad header hold (prefix length - 1); the rest is random.
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::capture::Capture;
+
+/// Maximum padding prefix length in bytes (including the pad header)
+pub const MAX_PADDING: usize = 4;
+
+/// Repeating-key XOR mask with random length padding
+#[derive(Clone)]
+pub struct Obfuscator {
+    key: Vec<u8>,
+}
+
+impl Obfuscator {
+    /// Create from a hex-encoded key
+    pub fn from_hex(key_hex: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let key = hex::decode(key_hex.trim())?;
+        if key.is_empty() {
+            return Err("obfuscation_key must not be empty".into());
+        }
+        Ok(Self { key })
+    }
+
+    fn mask(&self, data: &mut [u8]) {
+        for (b, k) in data.iter_mut().zip(self.key.iter().cycle()) {
+            *b ^= k;
+        }
+    }
+
+    /// Pad and mask a packet for sending
+    pub fn obfuscate(&self, packet: &[u8]) -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        let pad_len = rng.gen_range(1..=MAX_PADDING);
+
+        let mut out = Vec::with_capacity(pad_len + packet.len());
+        out.push((rng.gen::<u8>() & !0x03) | (pad_len - 1) as u8);
+        out.extend((1..pad_len).map(|_| rng.gen::<u8>()));
+        out.extend_from_slice(packet);
+        self.mask(&mut out);
+        out
+    }
+
+    /// Unmask a received datagram in place and strip its padding.
+    /// Returns the length of the WolfNet packet now at the start of `buf`,
+    /// or None if the datagram is too short to be valid.
+    pub fn deobfuscate(&self, buf: &mut [u8]) -> Option<usize> {
+        if buf.is_empty() {
+            return None;
+        }
+        self.mask(buf);
+
+        let pad_len = (buf[0] & 0x03) as usize + 1;
+        if buf.len() <= pad_len {
+            return None;
+        }
+        buf.copy_within(pad_len.., 0);
+        Some(buf.len() - pad_len)
+    }
+}
+
+/// UDP socket that transparently obfuscates everything it sends and receives
+pub struct ObfuscatedSocket {
+    inner: UdpSocket,
+    obfuscator: Option<Obfuscator>,
+    /// Packet capture recording the datagrams, see `crate::capture`
+    capture: RwLock<Option<Arc<Capture>>>,
+}
+
+impl ObfuscatedSocket {
+    /// Wrap a socket; with `None` packets pass through unchanged
+    pub fn new(inner: UdpSocket, obfuscator: Option<Obfuscator>) -> Self {
+        Self { inner, obfuscator, capture: RwLock::new(None) }
+    }
+
+    /// Record the WolfNet packets sent and received (before obfuscation)
+    /// to `capture`, or stop recording with `None`
+    pub fn set_capture(&self, capture: Option<Arc<Capture>>) {
+        *self.capture.write().unwrap() = capture;
+    }
+
+    /// Whether obfuscation is active
+    pub fn is_obfuscated(&self) -> bool {
+        self.obfuscator.is_some()
+    }
+
+    /// Send a WolfNet packet, returning the packet length on success
+    pub fn send_to<A: ToSocketAddrs>(&self, packet: &[u8], addr: A) -> io::Result<usize> {
+        if let Some(capture) = self.capture.read().unwrap().as_ref() {
+            if let Some(remote) = addr.to_socket_addrs()?.next() {
+                capture.record_udp(packet, remote, true);
+            }
+        }
+        match self.obfuscator {
+            Some(ref obf) => {
+                self.inner.send_to(&obf.obfuscate(packet), addr)?;
+                Ok(packet.len())
+            }
+            None => self.inner.send_to(packet, addr),
+        }
+    }
+
+    /// Receive a WolfNet packet into `buf`. Datagrams that fail to
+    /// deobfuscate are reported as a zero-length packet.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let (n, src) = self.inner.recv_from(buf)?;
+        let n = match self.obfuscator {
+            Some(ref obf) => obf.deobfuscate(&mut buf[..n]).unwrap_or(0),
+            None => n,
+        };
+        if n > 0 {
+            if let Some(capture) = self.capture.read().unwrap().as_ref() {
+                capture.record_udp(&buf[..n], src, false);
+            }
+        }
+        Ok((n, src))
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ObfuscationMode};
+
+    const KEY: &str = "a1b2c3d4e5f60718";
+
+    #[test]
+    fn test_round_trip() {
+        let obf = Obfuscator::from_hex(KEY).unwrap();
+        for len in [1, 2, 3, 17, 64, 1400] {
+            let packet: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut wire = obf.obfuscate(&packet);
+            assert!(wire.len() > packet.len() && wire.len() <= packet.len() + MAX_PADDING);
+
+            let n = obf.deobfuscate(&mut wire).unwrap();
+            assert_eq!(&wire[..n], &packet[..]);
+        }
+    }
+
+    #[test]
+    fn test_padding_varies_and_header_is_masked() {
+        let obf = Obfuscator::from_hex(KEY).unwrap();
+        let packet = [0x01u8; 32];
+
+        let lengths: std::collections::HashSet<usize> =
+            (0..200).map(|_| obf.obfuscate(&packet).len()).collect();
+        assert!(lengths.len() > 1, "padding length never varied");
+        assert!(lengths.iter().all(|l| (33..=36).contains(l)));
+
+        // The packet itself is never sent in the clear
+        let wire = obf.obfuscate(&packet);
+        assert_ne!(&wire[wire.len() - packet.len()..], &packet[..]);
+    }
+
+    #[test]
+    fn test_wrong_key_does_not_recover_packet() {
+        let obf = Obfuscator::from_hex(KEY).unwrap();
+        let other = Obfuscator::from_hex("0011223344556677").unwrap();
+        let packet = b"wolfnet handshake payload";
+
+        let mut wire = obf.obfuscate(packet);
+        if let Some(n) = other.deobfuscate(&mut wire) {
+            assert_ne!(&wire[..n], &packet[..]);
+        }
+    }
+
+    #[test]
+    fn test_short_datagrams_rejected() {
+        let obf = Obfuscator::from_hex(KEY).unwrap();
+        assert_eq!(obf.deobfuscate(&mut []), None);
+
+        // A datagram holding only its padding carries no packet
+        let mut wire = obf.obfuscate(&[]);
+        assert_eq!(obf.deobfuscate(&mut wire), None);
+    }
+
+    #[test]
+    fn test_invalid_keys_rejected() {
+        assert!(Obfuscator::from_hex("").is_err());
+        assert!(Obfuscator::from_hex("not hex").is_err());
+        assert!(Obfuscator::from_hex("abc").is_err());
+        assert!(Obfuscator::from_hex(" 00ff ").is_ok());
+    }
+
+    #[test]
+    fn test_config_obfuscator() {
+        let mut config = Config::default();
+        assert!(config.obfuscator().unwrap().is_none());
+
+        config.network.obfuscation = ObfuscationMode::Xor;
+        assert!(config.obfuscator().is_err(), "xor without a key must fail");
+
+        config.network.obfuscation_key = Some(KEY.into());
+        assert!(config.obfuscator().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_socket_round_trip() {
+        let obf = Obfuscator::from_hex(KEY).unwrap();
+        let a = ObfuscatedSocket::new(UdpSocket::bind("127.0.0.1:0").unwrap(), Some(obf.clone()));
+        let b = ObfuscatedSocket::new(UdpSocket::bind("127.0.0.1:0").unwrap(), Some(obf));
+        let plain = UdpSocket::bind("127.0.0.1:0").unwrap();
+        b.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        plain.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        assert!(a.is_obfuscated());
+
+        let packet = b"\x01hello";
+        assert_eq!(a.send_to(packet, b.local_addr().unwrap()).unwrap(), packet.len());
+        let mut buf = [0u8; 64];
+        let (n, _) = b.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], packet);
+
+        // What actually crosses the wire is masked
+        a.send_to(packet, plain.local_addr().unwrap()).unwrap();
+        let (n, _) = plain.recv_from(&mut buf).unwrap();
+        assert_ne!(&buf[..n], packet);
+    }
+}
@@ -3,18 +3,77 @@
 //! Tracks connected peers, their keys, endpoints, and session state.
 //! Supports peer exchange (PEX) for automatic mesh topology propagation.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::{Ipv4Addr, SocketAddr};
 #[allow(unused_imports)]
-use std::sync::{Arc, RwLock};
-use std::time::Instant;
-use x25519_dalek::{PublicKey, StaticSecret};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use x25519_dalek::PublicKey;
 
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
-use crate::crypto::{SessionCipher, KeyPair};
+use crate::config::{HubSelection, HubStatus};
+use crate::crypto::{self, SessionCipher, KeyPair};
+use crate::crl::RevokedKeys;
 use crate::transport::PexEntry;
 
+/// First reconnect handshake delay after a peer is marked dead
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(5);
+/// Upper bound for the reconnect handshake delay
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(300);
+/// Loss rate below which a peer that failed over to a relay goes back to the direct path
+const LOSS_RECOVERY_THRESHOLD: f64 = 0.02;
+/// Fewer packets than this in a sampling window say nothing useful about loss
+const MIN_LOSS_SAMPLE_PACKETS: u64 = 2;
+/// Tunnelled packet sizes tried in each path MTU probe round
+pub const MTU_PROBE_SIZES: [u16; 5] = [1200, 1300, 1380, 1400, 1420];
+/// A probe not acknowledged within this long is considered dropped
+pub const MTU_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How much the path MTU shrinks after a round in which no probe got through
+const MTU_PROBE_STEP_DOWN: u16 = 20;
+/// Never shrink the path MTU below the IPv4 minimum reassembly size
+const MIN_PATH_MTU: u16 = 576;
+/// RTT samples kept for the quality score's percentile
+const RTT_SAMPLE_WINDOW: usize = 20;
+/// Percentile of recent RTTs the quality score is based on
+const QUALITY_RTT_PERCENTILE: f64 = 0.9;
+/// RTT at or below which a path gets the full latency score
+const QUALITY_RTT_GOOD_US: u64 = 20_000;
+/// RTT at or above which a path gets no latency score
+const QUALITY_RTT_BAD_US: u64 = 500_000;
+/// Packet loss at or above which a path gets no loss score
+const QUALITY_LOSS_BAD: f64 = 0.2;
+/// Keepalive interval; a peer heard from within it gets the full freshness score
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(25);
+/// A relay path must score this much higher than the direct path to be used,
+/// so paths with similar scores don't flap
+const QUALITY_SWITCH_MARGIN: u8 = 10;
+/// Hubs are pinged this often, so a dead one is noticed quickly
+pub const HUB_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+/// A hub silent for this long is passed over; with selection rerun every
+/// probe interval, traffic moves to another hub within 15s
+const HUB_TIMEOUT: Duration = Duration::from_secs(10);
+/// In latency mode, another hub must be this much faster (as a fraction of
+/// the current hub's RTT) to take over, so hubs with similar RTTs don't flap
+const HUB_SWITCH_RATIO: f64 = 0.8;
+
+/// Reachability of a peer's direct UDP path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    /// Heard from within `network.peer_timeout_secs`
+    Connected,
+    /// Silent for too long (or never heard from); reconnect handshakes back off
+    Disconnected,
+}
+
+/// Path MTU probe round in flight
+struct MtuProbe {
+    sent_at: Instant,
+    sizes: Vec<u16>,
+    largest_acked: Option<u16>,
+}
+
 /// Information about a known peer
 pub struct Peer {
     /// Peer's public key
@@ -31,6 +90,8 @@ pub struct Peer {
     pub cipher: Option<SessionCipher>,
     /// Whether this peer is a gateway
     pub is_gateway: bool,
+    /// Whether traffic with no direct path may be routed through this peer
+    pub is_hub: bool,
     /// Last time we heard from this peer
     pub last_seen: Option<Instant>,
     /// Bytes received from this peer
@@ -44,6 +105,50 @@ pub struct Peer {
     pub relay_via: Option<Ipv4Addr>,
     /// Original configured endpoint string (may be a hostname:port for DNS re-resolution)
     pub configured_endpoint: Option<String>,
+    /// Smoothed round-trip time to this peer in microseconds (None until a ping is answered)
+    pub avg_rtt_us: Option<u64>,
+    /// Most recent RTT samples in microseconds, oldest first
+    rtt_samples: VecDeque<u64>,
+    /// RTTs other peers reported to this peer via PEX (relay WolfNet IP → relay-to-peer RTT in µs)
+    pub relay_rtts: HashMap<Ipv4Addr, u64>,
+    /// Whether the direct path is considered alive
+    pub link_state: LinkState,
+    /// Reconnect handshakes sent since the peer was last heard from
+    pub reconnect_attempts: u32,
+    /// When the next reconnect handshake is due (None = immediately)
+    pub next_reconnect: Option<Instant>,
+    /// Packets encrypted for this peer
+    pub pkts_sent: u64,
+    /// Packets successfully decrypted from this peer
+    pub pkts_received: u64,
+    /// Highest send counter seen from this peer in the current session
+    pub max_rx_counter: Option<u64>,
+    /// (max_rx_counter, pkts_received) at the start of the current loss sampling window
+    loss_window: Option<(u64, u64)>,
+    /// Smoothed inbound packet loss on the direct path, 0.0–1.0 (None until measured)
+    pub loss_rate: Option<f64>,
+    /// Loss exceeded `network.failover_loss_threshold`; prefer a relay until it recovers
+    pub lossy: bool,
+    /// Direct path quality score, 0–100 (None until first scored)
+    pub quality: Option<u8>,
+    /// Quality fell below `network.quality_threshold` (warned once per drop)
+    low_quality: bool,
+    /// Best relay path to this peer and its quality score
+    pub relay_quality: Option<(Ipv4Addr, u8)>,
+    /// Largest tunnelled packet known to reach this peer (None until probed)
+    pub effective_mtu: Option<u16>,
+    /// Probe round waiting for acks
+    mtu_probe: Option<MtuProbe>,
+    /// When the next probe round is due (None = now)
+    next_mtu_probe: Option<Instant>,
+    /// Listed in `security.crl_file`: no session is kept with this peer
+    pub revoked: bool,
+    /// ID of the current session, sent in resume handshakes after a network change
+    pub session_id: Option<[u8; 16]>,
+    /// Timestamp of the last resume handshake accepted from this peer; resumes
+    /// no newer than it are replays. Handshakes derive the same session ID, so
+    /// they leave it alone.
+    pub last_resume_ms: Option<u64>,
 }
 
 impl Peer {
@@ -58,26 +163,258 @@ impl Peer {
             hostname: String::new(),
             cipher: None,
             is_gateway: false,
+            is_hub: false,
             last_seen: None,
             rx_bytes: 0,
             tx_bytes: 0,
             last_handshake: None,
             relay_via: None,
             configured_endpoint: None,
+            avg_rtt_us: None,
+            rtt_samples: VecDeque::new(),
+            relay_rtts: HashMap::new(),
+            link_state: LinkState::Disconnected,
+            reconnect_attempts: 0,
+            next_reconnect: None,
+            pkts_sent: 0,
+            pkts_received: 0,
+            max_rx_counter: None,
+            loss_window: None,
+            loss_rate: None,
+            lossy: false,
+            quality: None,
+            low_quality: false,
+            relay_quality: None,
+            effective_mtu: None,
+            mtu_probe: None,
+            next_mtu_probe: None,
+            revoked: false,
+            session_id: None,
+            last_resume_ms: None,
         }
     }
 
     /// Establish a session with this peer using our secret key
-    pub fn establish_session(&mut self, my_secret: &StaticSecret, my_public: &PublicKey) {
-        let shared = my_secret.diffie_hellman(&self.public_key);
-        self.cipher = Some(SessionCipher::new(shared.as_bytes(), my_public, &self.public_key));
+    pub fn establish_session(&mut self, keypair: &KeyPair) {
+        if self.revoked {
+            return;
+        }
+        let shared = match keypair.diffie_hellman(&self.public_key) {
+            Ok(shared) => shared,
+            Err(e) => {
+                tracing::warn!("Key exchange with {} failed: {}", self.wolfnet_ip, e);
+                return;
+            }
+        };
+        self.cipher = Some(SessionCipher::new(&shared, &keypair.public, &self.public_key));
+        self.session_id = Some(crypto::session_id(&shared));
+        self.last_handshake = Some(Instant::now());
+        // The peer's send counter restarts with the session
+        self.max_rx_counter = None;
+        self.loss_window = None;
+        // A (re)connecting peer may be on a different path: probe it again
+        self.mtu_probe = None;
+        self.next_mtu_probe = None;
+    }
+
+    /// Carry on the current session after the peer moved to a new network.
+    /// The cipher and its nonce counters are kept, so nothing in flight is
+    /// lost to a fresh key exchange; the new path is probed again.
+    pub fn resume_session(&mut self, timestamp_ms: u64) {
+        self.last_resume_ms = Some(timestamp_ms);
         self.last_handshake = Some(Instant::now());
+        // The resume came straight from the peer: no relay needed
+        self.relay_via = None;
+        self.mtu_probe = None;
+        self.next_mtu_probe = None;
+        self.mark_alive();
+    }
+
+    /// Fold a new RTT sample into the moving average (EWMA, weight 1/8 like TCP SRTT)
+    pub fn record_rtt(&mut self, sample_us: u64) {
+        self.avg_rtt_us = Some(match self.avg_rtt_us {
+            Some(avg) => (avg * 7 + sample_us) / 8,
+            None => sample_us,
+        });
+        if self.rtt_samples.len() == RTT_SAMPLE_WINDOW {
+            self.rtt_samples.pop_front();
+        }
+        self.rtt_samples.push_back(sample_us);
+    }
+
+    /// The `pct` percentile (0.0–1.0) of recent RTT samples
+    pub fn rtt_percentile(&self, pct: f64) -> Option<u64> {
+        let mut samples: Vec<u64> = self.rtt_samples.iter().copied().collect();
+        samples.sort_unstable();
+        let rank = ((pct * samples.len() as f64).ceil() as usize).clamp(1, samples.len().max(1));
+        samples.get(rank - 1).copied()
+    }
+
+    /// Score the direct path (0 while it is down); returns the new score
+    pub fn update_quality(&mut self, timeout: Duration) -> u8 {
+        let score = if self.is_connected() {
+            connection_quality(
+                self.rtt_percentile(QUALITY_RTT_PERCENTILE),
+                self.loss_rate,
+                self.last_seen.map(|t| t.elapsed()),
+                timeout,
+            )
+        } else {
+            0
+        };
+        self.quality = Some(score);
+        score
+    }
+
+    /// Close the current loss sampling window and fold it into the loss EWMA (weight 1/4)
+    /// The peer's send counter advances once per packet, so the counter range seen
+    /// in the window is how many packets it sent us and the gap to what actually
+    /// decrypted is what the direct path dropped.
+    pub fn sample_loss(&mut self) {
+        let Some(max_counter) = self.max_rx_counter else { return };
+        let Some((start_counter, start_received)) = self.loss_window.replace((max_counter, self.pkts_received)) else {
+            return;
+        };
+        let sent = max_counter.saturating_sub(start_counter);
+        if sent < MIN_LOSS_SAMPLE_PACKETS {
+            return;
+        }
+        let received = self.pkts_received.saturating_sub(start_received).min(sent);
+        let sample = 1.0 - received as f64 / sent as f64;
+        self.loss_rate = Some(match self.loss_rate {
+            Some(avg) => (avg * 3.0 + sample) / 4.0,
+            None => sample,
+        });
+    }
 
+    /// Update the failover flag from the loss rate; returns true when it changes
+    /// Fails over above `threshold` and only comes back below 2% so a path hovering
+    /// around the threshold doesn't flap.
+    pub fn update_lossy(&mut self, threshold: f64) -> bool {
+        let loss = self.loss_rate.unwrap_or(0.0);
+        let lossy = if self.lossy { loss >= LOSS_RECOVERY_THRESHOLD } else { loss > threshold };
+        let changed = lossy != self.lossy;
+        self.lossy = lossy;
+        changed
+    }
+
+    /// Start a path MTU probe round if one is due; returns the sizes to probe
+    /// Besides the standard sizes, a path MTU already stepped down below them
+    /// is probed too, so repeated failures keep narrowing it.
+    pub fn start_mtu_probe(&mut self) -> Vec<u16> {
+        if self.mtu_probe.is_some() || self.next_mtu_probe.is_some_and(|t| Instant::now() < t) {
+            return Vec::new();
+        }
+        let mut sizes = MTU_PROBE_SIZES.to_vec();
+        if let Some(mtu) = self.effective_mtu.filter(|m| *m < MTU_PROBE_SIZES[0]) {
+            sizes.insert(0, mtu);
+        }
+        self.mtu_probe = Some(MtuProbe { sent_at: Instant::now(), sizes: sizes.clone(), largest_acked: None });
+        sizes
+    }
+
+    /// Record the peer's ack for a probe of `size` bytes
+    pub fn record_mtu_ack(&mut self, size: u16) {
+        if let Some(probe) = self.mtu_probe.as_mut().filter(|p| p.sizes.contains(&size)) {
+            probe.largest_acked = probe.largest_acked.max(Some(size));
+        }
+    }
+
+    /// Close the probe round once its acks had time to arrive; returns the new
+    /// path MTU when it changed. The largest acknowledged size wins; if nothing
+    /// got through, the MTU steps down by 20 bytes and the next round starts
+    /// right away instead of after `interval`.
+    pub fn finish_mtu_probe(&mut self, interval: Duration) -> Option<u16> {
+        if self.mtu_probe.as_ref()?.sent_at.elapsed() < MTU_PROBE_TIMEOUT {
+            return None;
+        }
+        let probe = self.mtu_probe.take()?;
+        let mtu = match probe.largest_acked {
+            Some(size) => {
+                self.next_mtu_probe = Some(Instant::now() + interval);
+                size
+            }
+            None => {
+                self.next_mtu_probe = None;
+                let current = self.effective_mtu.unwrap_or(MTU_PROBE_SIZES[0]);
+                current.saturating_sub(MTU_PROBE_STEP_DOWN).max(MIN_PATH_MTU)
+            }
+        };
+        let changed = self.effective_mtu != Some(mtu);
+        self.effective_mtu = Some(mtu);
+        changed.then_some(mtu)
+    }
+
+    /// Mark the peer revoked or reinstated. Revoking drops the session, so
+    /// nothing more is encrypted for or accepted from it.
+    pub fn set_revoked(&mut self, revoked: bool) {
+        self.revoked = revoked;
+        if revoked {
+            self.cipher = None;
+            self.session_id = None;
+            self.link_state = LinkState::Disconnected;
+        }
+    }
+
+    /// Whether this hub can carry traffic: connected directly and answering
+    /// the frequent hub probes
+    fn hub_available(&self) -> bool {
+        self.is_hub && self.is_connected() && self.endpoint.is_some()
+            && self.last_seen.is_some_and(|t| t.elapsed() < HUB_TIMEOUT)
     }
 
     /// Check if this peer has an active session
     pub fn is_connected(&self) -> bool {
-        self.cipher.is_some() && self.last_seen.map_or(false, |t| t.elapsed().as_secs() < 120)
+        self.cipher.is_some() && self.link_state == LinkState::Connected
+    }
+
+    /// Record that a handshake, keepalive or data packet arrived from this peer
+    pub fn mark_alive(&mut self) {
+        self.last_seen = Some(Instant::now());
+        if self.link_state == LinkState::Disconnected {
+            if self.reconnect_attempts > 0 {
+                tracing::info!("Peer {} ({}) reconnected after {} attempt(s)",
+                    self.hostname, self.wolfnet_ip, self.reconnect_attempts);
+            }
+            self.link_state = LinkState::Connected;
+            self.reconnect_attempts = 0;
+            self.next_reconnect = None;
+        }
+    }
+
+    /// Treat the direct path as dead so reconnect handshakes start right away
+    pub fn force_reconnect(&mut self) {
+        self.link_state = LinkState::Disconnected;
+        self.reconnect_attempts = 0;
+        self.next_reconnect = None;
+    }
+
+    /// Mark the peer dead if nothing was received for `timeout`; returns true on transition
+    pub fn check_timeout(&mut self, timeout: Duration) -> bool {
+        let silent = self.last_seen.is_none_or(|t| t.elapsed() >= timeout);
+        if self.link_state == LinkState::Connected && silent {
+            self.link_state = LinkState::Disconnected;
+            self.reconnect_attempts = 0;
+            self.next_reconnect = None;
+            return true;
+        }
+        false
+    }
+
+    /// Delay before the reconnect handshake after `attempts` unanswered ones (5s doubling to 300s)
+    pub fn reconnect_backoff(attempts: u32) -> Duration {
+        RECONNECT_BACKOFF_MIN.saturating_mul(1 << attempts.min(16)).min(RECONNECT_BACKOFF_MAX)
+    }
+
+    /// Whether a reconnect handshake should be sent now
+    pub fn reconnect_due(&self) -> bool {
+        !self.is_connected() && self.next_reconnect.is_none_or(|t| Instant::now() >= t)
+    }
+
+    /// Schedule the next reconnect handshake after sending one
+    pub fn record_reconnect_attempt(&mut self) {
+        self.next_reconnect = Some(Instant::now() + Self::reconnect_backoff(self.reconnect_attempts));
+        self.reconnect_attempts = self.reconnect_attempts.saturating_add(1);
     }
 
     /// Encrypt a packet for this peer
@@ -85,6 +422,7 @@ impl Peer {
         let cipher = self.cipher.as_mut().ok_or("No session established")?;
         let result = cipher.encrypt(data)?;
         self.tx_bytes += data.len() as u64;
+        self.pkts_sent += 1;
         Ok(result)
     }
 
@@ -93,11 +431,132 @@ impl Peer {
         let cipher = self.cipher.as_mut().ok_or("No session established")?;
         let result = cipher.decrypt(counter, data)?;
         self.rx_bytes += result.len() as u64;
-        self.last_seen = Some(Instant::now());
+        self.pkts_received += 1;
+        match self.max_rx_counter {
+            // Counter went far backwards: the peer restarted without a handshake reaching us
+            Some(max) if counter + 32 < max => {
+                self.max_rx_counter = Some(counter);
+                self.loss_window = None;
+            }
+            Some(max) if counter <= max => {}
+            _ => self.max_rx_counter = Some(counter),
+        }
+        self.mark_alive();
         Ok(result)
     }
 }
 
+/// Where a subnet route came from. When two routes cover the same subnet the
+/// earlier variant wins, so peers can't override what this node configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RouteSource {
+    /// A subnet on this node (`[[subnet_routes]]` without `via`)
+    Local,
+    /// Configured with `via` or `wolfnet route add`
+    Static,
+    /// Learned over peer exchange from the given peer
+    Pex(Ipv4Addr),
+}
+
+/// A subnet reached through a WolfNet node
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubnetRoute {
+    pub network: Ipv4Addr,
+    pub prefix: u8,
+    /// WolfNet IP of the node hosting the subnet
+    pub host: Ipv4Addr,
+    pub source: RouteSource,
+    /// Shared with peers over PEX (local subnets only)
+    pub advertise: bool,
+    /// The host went offline; used only when no other route covers the subnet
+    pub unreachable: bool,
+}
+
+impl SubnetRoute {
+    /// Whether `ip` is inside this subnet
+    pub fn contains(&self, ip: &Ipv4Addr) -> bool {
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+        u32::from(*ip) & mask == u32::from(self.network)
+    }
+
+    /// Whether this subnet and `other` share any address
+    fn overlaps(&self, other: &SubnetRoute) -> bool {
+        self.contains(&other.network) || other.contains(&self.network)
+    }
+
+    /// The subnet in CIDR notation
+    pub fn cidr(&self) -> String {
+        format!("{}/{}", self.network, self.prefix)
+    }
+
+    /// Whether this route and `other` cover exactly the same subnet
+    fn same_subnet(&self, other: &SubnetRoute) -> bool {
+        self.network == other.network && self.prefix == other.prefix
+    }
+}
+
+/// The route to use among those matching `filter`: the most specific subnet,
+/// then reachable before unreachable, then by source, with learned routes
+/// ordered by the quality of their host's link
+fn best_route<'a>(
+    routes: &'a [SubnetRoute],
+    peers: &HashMap<Ipv4Addr, Peer>,
+    filter: impl Fn(&SubnetRoute) -> bool,
+) -> Option<&'a SubnetRoute> {
+    routes.iter()
+        .filter(|r| filter(r))
+        .min_by_key(|r| {
+            let learned = matches!(r.source, RouteSource::Pex(_));
+            let quality = peers.get(&r.host).and_then(|p| p.quality).unwrap_or(0);
+            (std::cmp::Reverse(r.prefix), r.unreachable, learned, std::cmp::Reverse(quality), r.source)
+        })
+}
+
+/// Advertised subnets shorter than this are ignored, so a peer can't pull
+/// large parts of the address space into the tunnel
+const MIN_ADVERTISED_PREFIX: u8 = 8;
+
+/// Replace the routes `sender` told us `host` serves. Subnets that are too
+/// wide, or overlap a WolfNet address or one of our own subnets, are ignored.
+/// Returns whether the routing table changed.
+fn learn_routes(
+    routes: &mut Vec<SubnetRoute>,
+    subnets: &[String],
+    host: Ipv4Addr,
+    sender: Ipv4Addr,
+    wolfnet_ips: &[Ipv4Addr],
+) -> bool {
+    let source = RouteSource::Pex(sender);
+    let old: Vec<SubnetRoute> = routes.iter()
+        .filter(|r| r.source == source && r.host == host)
+        .copied()
+        .collect();
+    routes.retain(|r| !(r.source == source && r.host == host));
+
+    let mut learned: Vec<SubnetRoute> = Vec::new();
+    for cidr in subnets {
+        let Ok((network, prefix)) = crate::split_tunnel::parse_cidr(cidr) else { continue };
+        let mut route = SubnetRoute { network, prefix, host, source, advertise: false, unreachable: false };
+        // Routes through other hosts are kept as failover alternatives
+        if prefix < MIN_ADVERTISED_PREFIX
+            || wolfnet_ips.iter().any(|ip| route.contains(ip))
+            || routes.iter().any(|r| r.source == RouteSource::Local && r.overlaps(&route))
+            || routes.iter().any(|r| r.same_subnet(&route) && r.host == host && r.source < source)
+            || learned.contains(&route)
+        {
+            continue;
+        }
+        route.unreachable = old.iter().any(|r| r.same_subnet(&route) && r.unreachable);
+        learned.push(route);
+    }
+    let changed = learned != old;
+    routes.extend(learned);
+    changed
+}
+
+/// Packets held per subnet (network, prefix) while its routes are down
+type RouteBuffers = HashMap<(Ipv4Addr, u8), VecDeque<Vec<u8>>>;
+
 /// Manages all known peers
 pub struct PeerManager {
     /// Peers indexed by WolfNet IP
@@ -108,6 +567,21 @@ pub struct PeerManager {
     endpoint_to_ip: Arc<RwLock<HashMap<SocketAddr, Ipv4Addr>>>,
     /// Subnet routes: container/VM IP → host peer IP (for routing to containers on remote nodes)
     subnet_routes: Arc<RwLock<HashMap<Ipv4Addr, Ipv4Addr>>>,
+    /// Subnet routes by prefix: configured, local, and learned from PEX
+    prefix_routes: Arc<RwLock<Vec<SubnetRoute>>>,
+    /// Packets for subnets whose routes are all unreachable, by subnet,
+    /// replayed when a route is restored
+    route_buffers: Arc<Mutex<RouteBuffers>>,
+    /// Routes marked unreachable since startup
+    route_failovers: AtomicU64,
+    /// Unreachable routes restored since startup
+    route_restores: AtomicU64,
+    /// Public keys listed in `security.crl_file`
+    revoked_keys: Arc<RwLock<RevokedKeys>>,
+    /// Hub peers, configured ones first in config order, then learned ones
+    hubs: Arc<RwLock<Vec<Ipv4Addr>>>,
+    /// Hub currently carrying traffic with no other route
+    active_hub: Arc<RwLock<Option<Ipv4Addr>>>,
 }
 
 impl PeerManager {
@@ -117,14 +591,26 @@ impl PeerManager {
             id_to_ip: Arc::new(RwLock::new(HashMap::new())),
             endpoint_to_ip: Arc::new(RwLock::new(HashMap::new())),
             subnet_routes: Arc::new(RwLock::new(HashMap::new())),
+            prefix_routes: Arc::new(RwLock::new(Vec::new())),
+            route_buffers: Arc::new(Mutex::new(HashMap::new())),
+            route_failovers: AtomicU64::new(0),
+            route_restores: AtomicU64::new(0),
+            revoked_keys: Arc::new(RwLock::new(RevokedKeys::new())),
+            hubs: Arc::new(RwLock::new(Vec::new())),
+            active_hub: Arc::new(RwLock::new(None)),
         }
     }
 
     /// Add a peer
-    pub fn add_peer(&self, peer: Peer) {
+    pub fn add_peer(&self, mut peer: Peer) {
+        if self.is_revoked(&peer.public_key) {
+            peer.set_revoked(true);
+        }
         let ip = peer.wolfnet_ip;
         let peer_id = peer.peer_id;
-
+        if peer.is_hub {
+            self.add_hub(ip);
+        }
 
         if let Some(endpoint) = peer.endpoint {
             self.endpoint_to_ip.write().unwrap().insert(endpoint, ip);
@@ -133,6 +619,23 @@ impl PeerManager {
         self.peers_by_ip.write().unwrap().insert(ip, peer);
     }
 
+    /// Remove a peer, returning false if it was not known
+    pub fn remove_peer(&self, ip: &Ipv4Addr) -> bool {
+        let Some(peer) = self.peers_by_ip.write().unwrap().remove(ip) else {
+            return false;
+        };
+        if let Some(endpoint) = peer.endpoint {
+            self.endpoint_to_ip.write().unwrap().remove(&endpoint);
+        }
+        self.id_to_ip.write().unwrap().remove(&peer.peer_id);
+        self.hubs.write().unwrap().retain(|hub| hub != ip);
+        self.prefix_routes.write().unwrap().retain(|r| match r.source {
+            RouteSource::Pex(sender) => sender != *ip && r.host != *ip,
+            _ => true,
+        });
+        true
+    }
+
     /// Get a mutable reference to a peer by WolfNet IP (via callback to avoid lock issues)
     pub fn with_peer_by_ip<F, R>(&self, ip: &Ipv4Addr, f: F) -> Option<R>
     where F: FnOnce(&mut Peer) -> R {
@@ -225,6 +728,7 @@ impl PeerManager {
         
         // Entirely new peer discovered on LAN
         let mut peer = Peer::new(*public_key, wolfnet_ip);
+        peer.revoked = self.is_revoked(public_key);
         peer.endpoint = Some(endpoint);
         peer.hostname = hostname.to_string();
         peer.is_gateway = is_gateway;
@@ -236,11 +740,121 @@ impl PeerManager {
 
     }
 
+    /// Whether a public key is listed in the CRL
+    pub fn is_revoked(&self, public_key: &PublicKey) -> bool {
+        self.revoked_keys.read().unwrap().contains(public_key.as_bytes())
+    }
+
+    /// Replace the CRL, dropping the sessions of peers it now lists and
+    /// letting ones removed from it handshake again. Returns the IPs of the
+    /// peers newly revoked.
+    pub fn set_revoked_keys(&self, keys: RevokedKeys) -> Vec<Ipv4Addr> {
+        let mut newly_revoked = Vec::new();
+        for peer in self.peers_by_ip.write().unwrap().values_mut() {
+            let revoked = keys.contains(peer.public_key.as_bytes());
+            if revoked && !peer.revoked {
+                newly_revoked.push(peer.wolfnet_ip);
+            }
+            if revoked != peer.revoked {
+                peer.set_revoked(revoked);
+            }
+        }
+        *self.revoked_keys.write().unwrap() = keys;
+        newly_revoked
+    }
+
     /// Get all peer IPs
     pub fn all_ips(&self) -> Vec<Ipv4Addr> {
         self.peers_by_ip.read().unwrap().keys().copied().collect()
     }
 
+    /// Mark peers silent for longer than `timeout` as disconnected
+    pub fn check_liveness(&self, timeout: Duration) {
+        let mut peers = self.peers_by_ip.write().unwrap();
+        for peer in peers.values_mut() {
+            if peer.check_timeout(timeout) {
+                tracing::warn!("Peer {} ({}) silent for {}s, marking disconnected",
+                    peer.hostname, peer.wolfnet_ip, timeout.as_secs());
+            }
+        }
+    }
+
+    /// Mark the routes of hosts that went offline unreachable, so traffic
+    /// fails over to another route for the subnet (every second)
+    pub fn fail_over_routes(&self) {
+        let peers = self.peers_by_ip.read().unwrap();
+        let mut routes = self.prefix_routes.write().unwrap();
+        for i in 0..routes.len() {
+            let host_down = peers.get(&routes[i].host)
+                .is_some_and(|p| p.link_state == LinkState::Disconnected && p.last_seen.is_some());
+            if routes[i].unreachable || !host_down {
+                continue;
+            }
+            routes[i].unreachable = true;
+            self.route_failovers.fetch_add(1, Ordering::Relaxed);
+            let route = routes[i];
+            match best_route(&routes, &peers, |r| r.same_subnet(&route) && !r.unreachable) {
+                Some(alt) => tracing::warn!("Route {} via {} unreachable, failing over to {}",
+                    route.cidr(), route.host, alt.host),
+                None => tracing::warn!("Route {} via {} unreachable, buffering packets until it is restored",
+                    route.cidr(), route.host),
+            }
+        }
+    }
+
+    /// Restore unreachable routes whose host is back, returning the packets
+    /// buffered for them, and have hosts still down retried now (every 30s)
+    pub fn restore_routes(&self) -> Vec<Vec<u8>> {
+        let mut peers = self.peers_by_ip.write().unwrap();
+        let mut routes = self.prefix_routes.write().unwrap();
+        let mut buffers = self.route_buffers.lock().unwrap();
+        let mut replay = Vec::new();
+        for route in routes.iter_mut().filter(|r| r.unreachable) {
+            match peers.get_mut(&route.host) {
+                Some(peer) if peer.is_connected() => {
+                    route.unreachable = false;
+                    self.route_restores.fetch_add(1, Ordering::Relaxed);
+                    let buffered = buffers.remove(&(route.network, route.prefix)).unwrap_or_default();
+                    tracing::info!("Route {} via {} restored, replaying {} buffered packet(s)",
+                        route.cidr(), route.host, buffered.len());
+                    replay.extend(buffered);
+                }
+                Some(peer) => peer.next_reconnect = None,
+                None => {}
+            }
+        }
+        // Drop what was buffered for subnets no longer waiting on a route
+        buffers.retain(|&(network, prefix), _| {
+            routes.iter().any(|r| r.network == network && r.prefix == prefix && r.unreachable)
+        });
+        replay
+    }
+
+    /// Hold a packet for `dest_ip` when every route to its subnet is
+    /// unreachable, keeping up to `limit` per subnet. Returns whether the
+    /// packet was taken (buffered or dropped because the buffer is full).
+    pub fn buffer_for_route(&self, dest_ip: &Ipv4Addr, packet: &[u8], limit: usize) -> bool {
+        let peers = self.peers_by_ip.read().unwrap();
+        let routes = self.prefix_routes.read().unwrap();
+        let Some(route) = best_route(&routes, &peers, |r| r.contains(dest_ip)) else {
+            return false;
+        };
+        if !route.unreachable {
+            return false;
+        }
+        let mut buffers = self.route_buffers.lock().unwrap();
+        let buffer = buffers.entry((route.network, route.prefix)).or_default();
+        if buffer.len() < limit {
+            buffer.push_back(packet.to_vec());
+        }
+        true
+    }
+
+    /// Routes failed over and restored since startup
+    pub fn route_failover_counts(&self) -> (u64, u64) {
+        (self.route_failovers.load(Ordering::Relaxed), self.route_restores.load(Ordering::Relaxed))
+    }
+
     /// Find a connected gateway peer to route traffic through
     /// Returns the WolfNet IP of the first connected gateway peer
     pub fn find_gateway(&self) -> Option<Ipv4Addr> {
@@ -250,6 +864,100 @@ impl PeerManager {
             .map(|(ip, _)| *ip)
     }
 
+    fn add_hub(&self, ip: Ipv4Addr) {
+        let mut hubs = self.hubs.write().unwrap();
+        if !hubs.contains(&ip) {
+            hubs.push(ip);
+        }
+    }
+
+    /// Mark the configured hubs, in config order; other peers stop being hubs
+    /// unless they were learned as hubs through PEX
+    pub fn set_hubs(&self, configured: &[Ipv4Addr]) {
+        let mut peers = self.peers_by_ip.write().unwrap();
+        let mut hubs = self.hubs.write().unwrap();
+        let learned: Vec<Ipv4Addr> = hubs.iter()
+            .filter(|ip| !configured.contains(ip) && peers.get(ip).is_some_and(|p| p.relay_via.is_some()))
+            .copied()
+            .collect();
+        *hubs = configured.iter().chain(&learned).copied().collect();
+        for (ip, peer) in peers.iter_mut() {
+            peer.is_hub = hubs.contains(ip);
+        }
+    }
+
+    /// WolfNet IPs of all known hubs
+    pub fn hub_ips(&self) -> Vec<Ipv4Addr> {
+        self.hubs.read().unwrap().clone()
+    }
+
+    /// The hub currently selected for traffic with no other route
+    pub fn active_hub(&self) -> Option<Ipv4Addr> {
+        *self.active_hub.read().unwrap()
+    }
+
+    /// Choose the hub for traffic with no other route (every `HUB_PROBE_INTERVAL`).
+    /// The current hub is kept while it is available, except in latency mode
+    /// when another hub is clearly faster. Returns the new hub if it changed.
+    pub fn select_hub(&self, selection: HubSelection) -> Option<Ipv4Addr> {
+        let peers = self.peers_by_ip.read().unwrap();
+        let hubs = self.hubs.read().unwrap();
+        let mut active = self.active_hub.write().unwrap();
+
+        let available: Vec<&Peer> = hubs.iter()
+            .filter_map(|ip| peers.get(ip))
+            .filter(|p| p.hub_available())
+            .collect();
+        let rtt = |p: &Peer| p.avg_rtt_us.unwrap_or(u64::MAX / 2);
+        let current = active.and_then(|ip| available.iter().find(|p| p.wolfnet_ip == ip).copied());
+
+        let chosen = match (selection, current) {
+            (HubSelection::Latency, _) => {
+                let fastest = available.iter().copied().min_by_key(|p| rtt(p));
+                match (current, fastest) {
+                    (Some(current), Some(fastest)) if (rtt(fastest) as f64) >= rtt(current) as f64 * HUB_SWITCH_RATIO => Some(current),
+                    (_, fastest) => fastest,
+                }
+            }
+            (_, Some(current)) => Some(current),
+            (HubSelection::Random, None) => {
+                use rand::seq::SliceRandom;
+                available.choose(&mut rand::thread_rng()).copied()
+            }
+            (HubSelection::First, None) => available.first().copied(),
+        };
+
+        let chosen_ip = chosen.map(|p| p.wolfnet_ip);
+        if chosen_ip == *active {
+            return None;
+        }
+        match (chosen, *active) {
+            (Some(hub), Some(previous)) => tracing::warn!("Hub {} no longer preferred, switching to {} ({})",
+                previous, hub.hostname, hub.wolfnet_ip),
+            (Some(hub), None) => tracing::info!("Using hub {} ({})", hub.hostname, hub.wolfnet_ip),
+            (None, Some(previous)) => tracing::warn!("Hub {} is offline and no other hub is reachable", previous),
+            (None, None) => {}
+        }
+        *active = chosen_ip;
+        chosen_ip
+    }
+
+    /// Known hubs with their RTT and selection state
+    pub fn hub_status(&self) -> Vec<HubStatus> {
+        let peers = self.peers_by_ip.read().unwrap();
+        let active = self.active_hub();
+        self.hubs.read().unwrap().iter()
+            .filter_map(|ip| peers.get(ip))
+            .map(|p| HubStatus {
+                hostname: p.hostname.clone(),
+                address: p.wolfnet_ip.to_string(),
+                rtt_us: p.avg_rtt_us,
+                available: p.hub_available(),
+                active: active == Some(p.wolfnet_ip),
+            })
+            .collect()
+    }
+
     /// Find the relay peer for a given destination IP
     /// If we learned about dest_ip via PEX from another peer, return that peer's IP
     pub fn find_relay_for(&self, dest_ip: &Ipv4Addr) -> Option<Ipv4Addr> {
@@ -263,10 +971,191 @@ impl PeerManager {
         None
     }
 
+    /// Sample every peer's packet loss and flip lossy peers onto relays (every 30s)
+    pub fn update_loss(&self, threshold: f64) {
+        let mut peers = self.peers_by_ip.write().unwrap();
+        for peer in peers.values_mut() {
+            peer.sample_loss();
+            if peer.update_lossy(threshold) {
+                let loss_pct = peer.loss_rate.unwrap_or(0.0) * 100.0;
+                if peer.lossy {
+                    tracing::warn!("Peer {} ({}) losing {:.1}% of packets, preferring a relay path",
+                        peer.hostname, peer.wolfnet_ip, loss_pct);
+                } else {
+                    tracing::info!("Peer {} ({}) loss down to {:.1}%, back to the direct path",
+                        peer.hostname, peer.wolfnet_ip, loss_pct);
+                }
+            }
+        }
+    }
+
+    /// Score every peer's direct path and best relay path (every 30s)
+    /// Peers whose direct path drops below `threshold` are logged and, with
+    /// `reconnect`, handed to the dead-peer reconnect logic.
+    pub fn update_quality(&self, timeout: Duration, threshold: u8, reconnect: bool) {
+        let mut peers = self.peers_by_ip.write().unwrap();
+        for peer in peers.values_mut() {
+            let connected = peer.is_connected();
+            let score = peer.update_quality(timeout);
+            let low = connected && score < threshold;
+            if low && !peer.low_quality {
+                tracing::warn!("Peer {} ({}) connection quality down to {}{}",
+                    peer.hostname, peer.wolfnet_ip, score,
+                    if reconnect { ", reconnecting" } else { "" });
+                if reconnect {
+                    peer.force_reconnect();
+                }
+            }
+            peer.low_quality = low;
+        }
+
+        // A relay path is scored on the relay link, with the RTT the relay
+        // reported for its hop to the destination added on
+        let relay_scores: Vec<(Ipv4Addr, Option<(Ipv4Addr, u8)>)> = peers.iter()
+            .map(|(dest_ip, dest)| {
+                let candidates = dest.relay_rtts.iter()
+                    .map(|(relay_ip, hop_rtt)| (*relay_ip, Some(*hop_rtt)))
+                    .chain(dest.relay_via.filter(|ip| !dest.relay_rtts.contains_key(ip)).map(|ip| (ip, None)));
+                let best = candidates
+                    .filter(|(relay_ip, _)| relay_ip != dest_ip)
+                    .filter_map(|(relay_ip, hop_rtt)| {
+                        let relay = peers.get(&relay_ip)?;
+                        if !relay.is_connected() || relay.endpoint.is_none() {
+                            return None;
+                        }
+                        let rtt = relay.rtt_percentile(QUALITY_RTT_PERCENTILE)
+                            .and_then(|rtt| hop_rtt.map(|hop| rtt + hop));
+                        let score = connection_quality(rtt, relay.loss_rate, relay.last_seen.map(|t| t.elapsed()), timeout);
+                        Some((relay_ip, score))
+                    })
+                    .max_by_key(|(_, score)| *score);
+                (*dest_ip, best)
+            })
+            .collect();
+        for (dest_ip, best) in relay_scores {
+            if let Some(dest) = peers.get_mut(&dest_ip) {
+                dest.relay_quality = best;
+            }
+        }
+    }
+
+    /// Find a relay whose path to `dest_ip` scores clearly higher than the
+    /// direct path. Returns None until the direct path has been scored.
+    pub fn find_quality_relay(&self, dest_ip: &Ipv4Addr) -> Option<Ipv4Addr> {
+        let peers = self.peers_by_ip.read().unwrap();
+        let dest = peers.get(dest_ip)?;
+        let direct = dest.quality?;
+        let (relay_ip, relay_score) = dest.relay_quality?;
+        let relay = peers.get(&relay_ip)?;
+        (relay_score >= direct.saturating_add(QUALITY_SWITCH_MARGIN)
+            && relay.is_connected() && relay.endpoint.is_some())
+            .then_some(relay_ip)
+    }
+
+    /// Close finished path MTU probe rounds; returns true if any peer's MTU changed
+    pub fn check_mtu_probes(&self, interval: Duration) -> bool {
+        let mut peers = self.peers_by_ip.write().unwrap();
+        let mut changed = false;
+        for peer in peers.values_mut() {
+            let previous = peer.effective_mtu;
+            if let Some(mtu) = peer.finish_mtu_probe(interval) {
+                match previous {
+                    Some(old) if mtu < old => tracing::warn!("Path MTU to {} ({}) dropped from {} to {}",
+                        peer.hostname, peer.wolfnet_ip, old, mtu),
+                    _ => tracing::info!("Path MTU to {} ({}) is {}", peer.hostname, peer.wolfnet_ip, mtu),
+                }
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Smallest path MTU among probed peers, which every tunnelled packet must fit
+    pub fn min_effective_mtu(&self) -> Option<u16> {
+        let peers = self.peers_by_ip.read().unwrap();
+        peers.values().filter_map(|p| p.effective_mtu).min()
+    }
+
+    /// Find a relay for a destination whose direct path is dropping packets
+    /// Picks the relay with the lowest total RTT among those that reported one,
+    /// falling back to the PEX relay. Returns None when the direct path is fine
+    /// or no connected relay exists, in which case the lossy direct path is kept.
+    pub fn find_loss_relay(&self, dest_ip: &Ipv4Addr) -> Option<Ipv4Addr> {
+        let peers = self.peers_by_ip.read().unwrap();
+        let dest = peers.get(dest_ip)?;
+        if !dest.lossy {
+            return None;
+        }
+        let usable = |relay_ip: &Ipv4Addr| {
+            relay_ip != dest_ip && peers.get(relay_ip)
+                .is_some_and(|relay| relay.is_connected() && relay.endpoint.is_some() && !relay.lossy)
+        };
+
+        dest.relay_rtts.iter()
+            .filter(|(relay_ip, _)| usable(relay_ip))
+            .min_by_key(|(relay_ip, hop_rtt)| {
+                peers[*relay_ip].avg_rtt_us.unwrap_or(u64::MAX / 2) + *hop_rtt
+            })
+            .map(|(relay_ip, _)| *relay_ip)
+            .or(dest.relay_via.filter(usable))
+    }
+
     /// Find the host peer for a container/VM IP via subnet routes
-    /// Returns the WolfNet IP of the host that owns this container
+    /// Returns the WolfNet IP of the host that owns this container.
+    /// Exact container routes win, then the most specific subnet, preferring
+    /// routes whose host is reachable.
     pub fn find_route(&self, dest_ip: &Ipv4Addr) -> Option<Ipv4Addr> {
-        self.subnet_routes.read().unwrap().get(dest_ip).copied()
+        if let Some(host) = self.subnet_routes.read().unwrap().get(dest_ip) {
+            return Some(*host);
+        }
+        let peers = self.peers_by_ip.read().unwrap();
+        let routes = self.prefix_routes.read().unwrap();
+        best_route(&routes, &peers, |r| r.contains(dest_ip)).map(|r| r.host)
+    }
+
+    /// Add a route for `network/prefix` through `host`, replacing any route
+    /// for the same subnet from an equal or lower priority source.
+    /// Returns false if the route was not added.
+    pub fn add_route(&self, network: Ipv4Addr, prefix: u8, host: Ipv4Addr, source: RouteSource) -> bool {
+        if prefix > 32 {
+            return false;
+        }
+        let route = SubnetRoute { network, prefix, host, source, advertise: false, unreachable: false };
+        let mut routes = self.prefix_routes.write().unwrap();
+        if routes.iter().any(|r| r.same_subnet(&route) && r.source < source) {
+            return false;
+        }
+        routes.retain(|r| !(r.same_subnet(&route) && r.source >= source));
+        routes.push(route);
+        true
+    }
+
+    /// Replace the configured routes (`Local` and `Static`) with `routes`.
+    /// Learned routes that now clash with a configured one are dropped,
+    /// except those through another host, kept for failover.
+    pub fn set_configured_routes(&self, routes: &[SubnetRoute]) {
+        let mut table = self.prefix_routes.write().unwrap();
+        let old = std::mem::take(&mut *table);
+        table.extend(old.iter().filter(|r| matches!(r.source, RouteSource::Pex(_))));
+        for route in routes {
+            table.retain(|r| !(r.same_subnet(route) && (route.source == RouteSource::Local || r.host == route.host)));
+            let mut route = *route;
+            route.unreachable = old.iter().any(|r| r.same_subnet(&route) && r.host == route.host && r.unreachable);
+            table.push(route);
+        }
+    }
+
+    /// All subnet routes
+    pub fn subnet_routes(&self) -> Vec<SubnetRoute> {
+        self.prefix_routes.read().unwrap().clone()
+    }
+
+    /// Local subnets to advertise to peers, in CIDR notation
+    pub fn advertised_subnets(&self, my_ip: Ipv4Addr) -> Vec<String> {
+        self.prefix_routes.read().unwrap().iter()
+            .filter(|r| r.source == RouteSource::Local && r.advertise && r.host == my_ip)
+            .map(SubnetRoute::cidr)
+            .collect()
     }
 
     /// Load subnet routes from a JSON file (container_ip → host_peer_ip)
@@ -307,6 +1196,7 @@ impl PeerManager {
     /// Excludes the requesting peer's own IP and our own IP
     pub fn get_pex_entries(&self, my_ip: Ipv4Addr) -> Vec<PexEntry> {
         let peers = self.peers_by_ip.read().unwrap();
+        let routes = self.prefix_routes.read().unwrap();
         peers.values()
             .filter(|p| p.wolfnet_ip != my_ip)
             .map(|p| {
@@ -316,22 +1206,38 @@ impl PeerManager {
                     endpoint: p.endpoint.map(|e| e.to_string()),
                     hostname: p.hostname.clone(),
                     is_gateway: p.is_gateway,
+                    is_hub: p.is_hub,
+                    // Only advertise RTTs for paths we use directly
+                    rtt_us: if p.relay_via.is_none() && p.is_connected() { p.avg_rtt_us } else { None },
+                    // Pass on what we learned, so subnets reach nodes the host can't
+                    subnets: routes.iter()
+                        .filter(|r| r.host == p.wolfnet_ip && matches!(r.source, RouteSource::Pex(_)))
+                        .map(SubnetRoute::cidr)
+                        .fold(Vec::new(), |mut subnets, cidr| {
+                            if !subnets.contains(&cidr) { subnets.push(cidr); }
+                            subnets
+                        }),
                 }
             })
             .collect()
     }
 
     /// Process received PEX entries from a peer
-    /// Adds new peers we haven't seen before, marking them as relay-via the sender
+    /// Adds new peers we haven't seen before, marking them as relay-via the sender,
+    /// and learns the subnets they route. Returns whether the subnet routes changed.
     pub fn add_from_pex(
         &self,
         entries: &[PexEntry],
         sender_ip: Ipv4Addr,
         my_ip: Ipv4Addr,
         keypair: &KeyPair,
-    ) {
+    ) -> bool {
         let mut peers = self.peers_by_ip.write().unwrap();
 
+        let mut wolfnet_ips: Vec<Ipv4Addr> = peers.keys().copied().collect();
+        wolfnet_ips.push(my_ip);
+        let mut routes_changed = false;
+
         for entry in entries {
             // Skip ourselves
             let entry_ip: Ipv4Addr = match entry.wolfnet_ip.parse() {
@@ -340,6 +1246,24 @@ impl PeerManager {
             };
             if entry_ip == my_ip { continue; }
 
+            routes_changed |= learn_routes(
+                &mut self.prefix_routes.write().unwrap(),
+                &entry.subnets,
+                entry_ip,
+                sender_ip,
+                &wolfnet_ips,
+            );
+
+            // Remember how fast the sender reaches this peer, for relay path selection
+            if entry_ip != sender_ip {
+                if let Some(existing) = peers.get_mut(&entry_ip) {
+                    match entry.rtt_us {
+                        Some(rtt) => { existing.relay_rtts.insert(sender_ip, rtt); }
+                        None => { existing.relay_rtts.remove(&sender_ip); }
+                    }
+                }
+            }
+
             // Skip if we already know this peer directly (LAN discovery or configured)
             if let Some(existing) = peers.get(&entry_ip) {
                 if existing.is_connected() || existing.relay_via.is_none() {
@@ -379,7 +1303,9 @@ impl PeerManager {
             let mut peer = Peer::new(pub_key, entry_ip);
             peer.hostname = entry.hostname.clone();
             peer.is_gateway = entry.is_gateway;
+            peer.is_hub = entry.is_hub;
             peer.relay_via = Some(sender_ip);
+            peer.revoked = self.is_revoked(&pub_key);
 
             // Parse endpoint if available
             if let Some(ref ep_str) = entry.endpoint {
@@ -390,13 +1316,23 @@ impl PeerManager {
             }
 
             // Establish crypto session so we can encrypt/decrypt
-            peer.establish_session(&keypair.secret, &keypair.public);
+            peer.establish_session(keypair);
 
             let peer_id = peer.peer_id;
             self.id_to_ip.write().unwrap().insert(peer_id, entry_ip);
+            if peer.is_hub {
+                self.add_hub(entry_ip);
+            }
 
             peers.insert(entry_ip, peer);
         }
+
+        // Forget subnets of hosts the sender no longer mentions
+        let mentioned: Vec<&str> = entries.iter().map(|e| e.wolfnet_ip.as_str()).collect();
+        let mut routes = self.prefix_routes.write().unwrap();
+        let before = routes.len();
+        routes.retain(|r| r.source != RouteSource::Pex(sender_ip) || mentioned.contains(&r.host.to_string().as_str()));
+        routes_changed || routes.len() != before
     }
 
     /// Collect status info for all peers
@@ -414,7 +1350,855 @@ impl PeerManager {
                 connected: p.is_connected(),
                 is_gateway: p.is_gateway,
                 relay_via: p.relay_via.map(|ip| ip.to_string()),
+                rtt_us: p.avg_rtt_us,
+                loss_pct: p.loss_rate.map(|l| l * 100.0),
+                quality: p.quality,
+                pkts_sent: p.pkts_sent,
+                pkts_received: p.pkts_received,
+                effective_mtu: p.effective_mtu,
+                reconnect_attempts: p.reconnect_attempts,
+                retry_in_secs: if p.link_state == LinkState::Disconnected {
+                    Some(p.next_reconnect.map_or(0, |t| t.saturating_duration_since(Instant::now()).as_secs()))
+                } else {
+                    None
+                },
+                revoked: p.revoked,
             }
         }).collect()
     }
 }
+
+/// Connection quality score, 0–100 (higher is better). Latency (the given RTT
+/// percentile) and packet loss are worth 40 points each, scaled linearly
+/// between good and bad bounds; freshness is worth 20, full within one
+/// keepalive interval of the last packet and none at `timeout`. An unmeasured
+/// RTT gets half the latency points and unmeasured loss all of the loss points.
+pub fn connection_quality(rtt_us: Option<u64>, loss: Option<f64>, silent_for: Option<Duration>, timeout: Duration) -> u8 {
+    let latency = rtt_us.map_or(0.5, |rtt| {
+        linear_score(rtt as f64, QUALITY_RTT_GOOD_US as f64, QUALITY_RTT_BAD_US as f64)
+    });
+    let loss = linear_score(loss.unwrap_or(0.0), 0.0, QUALITY_LOSS_BAD);
+    let freshness = silent_for.map_or(0.0, |silent| {
+        linear_score(silent.as_secs_f64(), KEEPALIVE_INTERVAL.as_secs_f64(), timeout.as_secs_f64())
+    });
+    (latency * 40.0 + loss * 40.0 + freshness * 20.0).round() as u8
+}
+
+/// 1.0 at or below `good`, 0.0 at or above `bad`, linear in between
+fn linear_score(value: f64, good: f64, bad: f64) -> f64 {
+    if bad <= good {
+        return if value <= good { 1.0 } else { 0.0 };
+    }
+    (1.0 - (value - good) / (bad - good)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> Ipv4Addr {
+        Ipv4Addr::new(10, 0, 10, last)
+    }
+
+    /// A peer with a live session, reached directly at 192.0.2.<last>:9600
+    fn connected_peer(keypair: &KeyPair, last: u8) -> Peer {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(last));
+        peer.endpoint = Some(SocketAddr::from(([192, 0, 2, last], 9600)));
+        peer.establish_session(keypair);
+        peer.mark_alive();
+        peer
+    }
+
+    fn pex_entry(peer_ip: Ipv4Addr, rtt_us: Option<u64>) -> PexEntry {
+        PexEntry {
+            public_key: BASE64.encode(KeyPair::generate().public.as_bytes()),
+            wolfnet_ip: peer_ip.to_string(),
+            endpoint: None,
+            hostname: String::new(),
+            is_gateway: false,
+            is_hub: false,
+            rtt_us,
+            subnets: Vec::new(),
+        }
+    }
+
+    /// Both ends of one session: (node 1's view of node 2, node 2's view of node 1)
+    fn session_pair() -> (Peer, Peer) {
+        let (kp1, kp2) = (KeyPair::generate(), KeyPair::generate());
+        let mut to_2 = Peer::new(kp2.public, ip(2));
+        to_2.establish_session(&kp1);
+        let mut to_1 = Peer::new(kp1.public, ip(1));
+        to_1.establish_session(&kp2);
+        (to_2, to_1)
+    }
+
+    /// Have `sender` send `count` packets, of which `receiver` gets those `deliver` keeps
+    fn send_packets(sender: &mut Peer, receiver: &mut Peer, count: u64, deliver: impl Fn(u64) -> bool) {
+        for i in 0..count {
+            let (counter, ciphertext) = sender.encrypt(b"data").unwrap();
+            if deliver(i) {
+                receiver.decrypt(counter, &ciphertext).unwrap();
+            }
+        }
+    }
+
+    /// Let the peer's MTU probe round run out its timeout
+    fn expire_mtu_probe(peer: &mut Peer) {
+        if let Some(probe) = peer.mtu_probe.as_mut() {
+            probe.sent_at -= MTU_PROBE_TIMEOUT;
+        }
+    }
+
+    #[test]
+    fn test_rtt_smoothing() {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(2));
+        assert_eq!(peer.avg_rtt_us, None);
+        peer.record_rtt(8_000);
+        assert_eq!(peer.avg_rtt_us, Some(8_000));
+        // Weight 1/8 on each new sample
+        peer.record_rtt(16_000);
+        assert_eq!(peer.avg_rtt_us, Some(9_000));
+        for _ in 0..100 {
+            peer.record_rtt(1_000);
+        }
+        assert!(peer.avg_rtt_us.unwrap() < 1_100);
+    }
+
+    #[test]
+    fn test_rtt_percentile_uses_recent_samples() {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(2));
+        assert_eq!(peer.rtt_percentile(0.9), None);
+        for rtt in 1..=10 {
+            peer.record_rtt(rtt * 1_000);
+        }
+        assert_eq!(peer.rtt_percentile(0.9), Some(9_000));
+        assert_eq!(peer.rtt_percentile(0.0), Some(1_000));
+        assert_eq!(peer.rtt_percentile(1.0), Some(10_000));
+
+        // Only the last RTT_SAMPLE_WINDOW samples count
+        for _ in 0..RTT_SAMPLE_WINDOW {
+            peer.record_rtt(50_000);
+        }
+        assert_eq!(peer.rtt_percentile(0.0), Some(50_000));
+    }
+
+    #[test]
+    fn test_pex_carries_direct_rtts() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        let mut direct = connected_peer(&keypair, 2);
+        direct.record_rtt(4_000);
+        manager.add_peer(direct);
+        let mut relayed = connected_peer(&keypair, 3);
+        relayed.record_rtt(9_000);
+        relayed.relay_via = Some(ip(2));
+        manager.add_peer(relayed);
+        let mut silent = Peer::new(KeyPair::generate().public, ip(4));
+        silent.record_rtt(1_000);
+        manager.add_peer(silent);
+
+        let entries = manager.get_pex_entries(ip(1));
+        let rtt = |last| entries.iter().find(|e| e.wolfnet_ip == ip(last).to_string()).unwrap().rtt_us;
+        assert_eq!(rtt(2), Some(4_000));
+        // Only paths used directly are advertised
+        assert_eq!(rtt(3), None);
+        assert_eq!(rtt(4), None);
+    }
+
+    #[test]
+    fn test_pex_records_relay_rtts() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        manager.add_peer(connected_peer(&keypair, 3));
+
+        // Peer 2 reaches peer 3 in 3ms
+        manager.add_from_pex(&[pex_entry(ip(3), Some(3_000))], ip(2), ip(1), &keypair);
+        let relay_rtts = manager.with_peer_by_ip(&ip(3), |p| p.relay_rtts.clone()).unwrap();
+        assert_eq!(relay_rtts.get(&ip(2)), Some(&3_000));
+
+        // The sender's own entry says nothing about relaying through itself
+        manager.add_from_pex(&[pex_entry(ip(2), Some(1_000))], ip(2), ip(1), &keypair);
+        assert!(manager.with_peer_by_ip(&ip(2), |p| p.relay_rtts.is_empty()).unwrap());
+
+        // Once peer 2 stops reaching peer 3 directly, the RTT is forgotten
+        manager.add_from_pex(&[pex_entry(ip(3), None)], ip(2), ip(1), &keypair);
+        assert!(manager.with_peer_by_ip(&ip(3), |p| p.relay_rtts.is_empty()).unwrap());
+    }
+
+    #[test]
+    fn test_reconnect_backoff_doubles_to_cap() {
+        assert_eq!(Peer::reconnect_backoff(0), Duration::from_secs(5));
+        assert_eq!(Peer::reconnect_backoff(1), Duration::from_secs(10));
+        assert_eq!(Peer::reconnect_backoff(3), Duration::from_secs(40));
+        assert_eq!(Peer::reconnect_backoff(6), RECONNECT_BACKOFF_MAX);
+        assert_eq!(Peer::reconnect_backoff(u32::MAX), RECONNECT_BACKOFF_MAX);
+    }
+
+    #[test]
+    fn test_reconnect_attempts_schedule_backoff() {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(2));
+        assert!(peer.reconnect_due());
+
+        peer.record_reconnect_attempt();
+        assert_eq!(peer.reconnect_attempts, 1);
+        assert!(!peer.reconnect_due());
+        let wait = peer.next_reconnect.unwrap().saturating_duration_since(Instant::now());
+        assert!(wait > Duration::from_secs(4) && wait <= RECONNECT_BACKOFF_MIN);
+
+        peer.record_reconnect_attempt();
+        let wait = peer.next_reconnect.unwrap().saturating_duration_since(Instant::now());
+        assert!(wait > Duration::from_secs(9) && wait <= Duration::from_secs(10));
+
+        // Once the wait has passed the next handshake is due
+        peer.next_reconnect = Some(Instant::now() - Duration::from_secs(1));
+        assert!(peer.reconnect_due());
+    }
+
+    #[test]
+    fn test_backoff_resets_when_peer_answers() {
+        let keypair = KeyPair::generate();
+        let mut peer = connected_peer(&keypair, 2);
+        peer.link_state = LinkState::Disconnected;
+        for _ in 0..4 {
+            peer.record_reconnect_attempt();
+        }
+        assert_eq!(peer.reconnect_attempts, 4);
+
+        peer.mark_alive();
+        assert_eq!(peer.link_state, LinkState::Connected);
+        assert_eq!(peer.reconnect_attempts, 0);
+        assert_eq!(peer.next_reconnect, None);
+        assert!(!peer.reconnect_due(), "connected peers need no reconnect");
+    }
+
+    #[test]
+    fn test_silent_peer_marked_dead() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        let mut silent = connected_peer(&keypair, 3);
+        silent.last_seen = Some(Instant::now() - Duration::from_secs(60));
+        silent.reconnect_attempts = 7;
+        manager.add_peer(silent);
+
+        manager.check_liveness(Duration::from_secs(30));
+        let state = |last| manager.with_peer_by_ip(&ip(last), |p| (p.link_state, p.reconnect_attempts)).unwrap();
+        assert_eq!(state(2), (LinkState::Connected, 0));
+        // A fresh death starts the backoff from the minimum
+        assert_eq!(state(3), (LinkState::Disconnected, 0));
+        assert!(manager.with_peer_by_ip(&ip(3), |p| p.reconnect_due()).unwrap());
+
+        // The transition is only reported once
+        let mut peer = connected_peer(&keypair, 4);
+        peer.last_seen = Some(Instant::now() - Duration::from_secs(60));
+        assert!(peer.check_timeout(Duration::from_secs(30)));
+        assert!(!peer.check_timeout(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_loss_window() {
+        let (mut sender, mut receiver) = session_pair();
+        receiver.sample_loss();
+        assert_eq!(receiver.loss_rate, None, "nothing received yet");
+
+        // The first sample only opens the window
+        send_packets(&mut sender, &mut receiver, 1, |_| true);
+        receiver.sample_loss();
+        assert_eq!(receiver.loss_rate, None);
+
+        // 20 of 100 dropped, but never the last one
+        send_packets(&mut sender, &mut receiver, 100, |i| i % 5 != 0);
+        receiver.sample_loss();
+        assert!((receiver.loss_rate.unwrap() - 0.2).abs() < 1e-9);
+
+        // A clean window pulls the average down by a quarter
+        send_packets(&mut sender, &mut receiver, 100, |_| true);
+        receiver.sample_loss();
+        assert!((receiver.loss_rate.unwrap() - 0.15).abs() < 1e-9);
+
+        // Windows with too few packets say nothing
+        send_packets(&mut sender, &mut receiver, 1, |_| true);
+        receiver.sample_loss();
+        assert!((receiver.loss_rate.unwrap() - 0.15).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_loss_window_restarts_with_sender() {
+        let (kp1, kp2) = (KeyPair::generate(), KeyPair::generate());
+        let sender_session = || {
+            let mut to_1 = Peer::new(kp1.public, ip(1));
+            to_1.establish_session(&kp2);
+            to_1
+        };
+        let mut receiver = Peer::new(kp2.public, ip(2));
+        receiver.establish_session(&kp1);
+
+        let mut sender = sender_session();
+        send_packets(&mut sender, &mut receiver, 150, |_| true);
+        receiver.sample_loss();
+        assert_eq!(receiver.max_rx_counter, Some(149));
+
+        // The sender restarts its counter without a handshake reaching us;
+        // its low counters must not read as 150 lost packets
+        let mut sender = sender_session();
+        send_packets(&mut sender, &mut receiver, 1, |_| true);
+        assert_eq!(receiver.max_rx_counter, Some(0));
+        send_packets(&mut sender, &mut receiver, 50, |_| true);
+        receiver.sample_loss();
+        assert_eq!(receiver.loss_rate, None);
+        send_packets(&mut sender, &mut receiver, 50, |_| true);
+        receiver.sample_loss();
+        assert_eq!(receiver.loss_rate, Some(0.0));
+    }
+
+    #[test]
+    fn test_lossy_thresholds() {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(2));
+        assert!(!peer.update_lossy(0.05));
+
+        peer.loss_rate = Some(0.05);
+        assert!(!peer.update_lossy(0.05), "failover needs loss above the threshold");
+        peer.loss_rate = Some(0.08);
+        assert!(peer.update_lossy(0.05));
+        assert!(peer.lossy);
+        assert!(!peer.update_lossy(0.05), "only transitions are reported");
+
+        // Below the threshold but above the recovery level stays on the relay
+        peer.loss_rate = Some(0.03);
+        assert!(!peer.update_lossy(0.05));
+        assert!(peer.lossy);
+        peer.loss_rate = Some(0.01);
+        assert!(peer.update_lossy(0.05));
+        assert!(!peer.lossy);
+    }
+
+    #[test]
+    fn test_lossy_peer_uses_relay() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        for (last, rtt) in [(2, 10_000), (3, 2_000), (4, 1_000)] {
+            let mut relay = connected_peer(&keypair, last);
+            relay.record_rtt(rtt);
+            manager.add_peer(relay);
+        }
+        let mut dest = connected_peer(&keypair, 5);
+        dest.relay_rtts.insert(ip(2), 1_000);
+        dest.relay_rtts.insert(ip(3), 5_000);
+        dest.relay_rtts.insert(ip(4), 3_000);
+        dest.loss_rate = Some(0.3);
+        manager.add_peer(dest);
+
+        assert_eq!(manager.find_loss_relay(&ip(5)), None, "not failed over yet");
+        manager.update_loss(0.05);
+        // Lowest total RTT: 1ms + 3ms through peer 4
+        assert_eq!(manager.find_loss_relay(&ip(5)), Some(ip(4)));
+
+        // A relay that is itself lossy is skipped
+        manager.with_peer_by_ip(&ip(4), |p| p.lossy = true);
+        assert_eq!(manager.find_loss_relay(&ip(5)), Some(ip(3)));
+
+        // Fail back once the direct path recovers
+        manager.with_peer_by_ip(&ip(5), |p| { p.loss_rate = Some(0.0); p.max_rx_counter = None; });
+        manager.update_loss(0.05);
+        assert_eq!(manager.find_loss_relay(&ip(5)), None);
+    }
+
+    #[test]
+    fn test_mtu_probe_takes_largest_ack() {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(2));
+        assert_eq!(peer.start_mtu_probe(), MTU_PROBE_SIZES);
+        assert!(peer.start_mtu_probe().is_empty(), "one round at a time");
+
+        peer.record_mtu_ack(1300);
+        peer.record_mtu_ack(1400);
+        peer.record_mtu_ack(1500);
+        assert_eq!(peer.finish_mtu_probe(Duration::from_secs(600)), None, "still waiting for acks");
+
+        expire_mtu_probe(&mut peer);
+        assert_eq!(peer.finish_mtu_probe(Duration::from_secs(600)), Some(1400));
+        assert_eq!(peer.effective_mtu, Some(1400));
+
+        // The next round waits for the interval
+        assert!(peer.start_mtu_probe().is_empty());
+        peer.next_mtu_probe = Some(Instant::now());
+        assert_eq!(peer.start_mtu_probe(), MTU_PROBE_SIZES);
+        peer.record_mtu_ack(1400);
+        expire_mtu_probe(&mut peer);
+        assert_eq!(peer.finish_mtu_probe(Duration::from_secs(600)), None, "unchanged");
+    }
+
+    #[test]
+    fn test_mtu_probe_steps_down_without_acks() {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(2));
+        peer.start_mtu_probe();
+        expire_mtu_probe(&mut peer);
+        assert_eq!(peer.finish_mtu_probe(Duration::from_secs(600)), Some(1180));
+
+        // Failed rounds retry at once, probing the stepped down size too
+        assert_eq!(peer.start_mtu_probe(), [1180, 1200, 1300, 1380, 1400, 1420]);
+        expire_mtu_probe(&mut peer);
+        assert_eq!(peer.finish_mtu_probe(Duration::from_secs(600)), Some(1160));
+
+        peer.start_mtu_probe();
+        peer.record_mtu_ack(1160);
+        expire_mtu_probe(&mut peer);
+        assert_eq!(peer.finish_mtu_probe(Duration::from_secs(600)), None, "1160 confirmed");
+
+        // Never below the IPv4 minimum
+        peer.effective_mtu = Some(590);
+        peer.next_mtu_probe = None;
+        for _ in 0..3 {
+            peer.start_mtu_probe();
+            expire_mtu_probe(&mut peer);
+            peer.finish_mtu_probe(Duration::from_secs(600));
+        }
+        assert_eq!(peer.effective_mtu, Some(MIN_PATH_MTU));
+    }
+
+    #[test]
+    fn test_tun_mtu_follows_smallest_path_mtu() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        assert_eq!(manager.min_effective_mtu(), None);
+        for (last, acked) in [(2, 1420), (3, 1300)] {
+            let mut peer = connected_peer(&keypair, last);
+            peer.start_mtu_probe();
+            peer.record_mtu_ack(acked);
+            expire_mtu_probe(&mut peer);
+            manager.add_peer(peer);
+        }
+        // A peer never probed doesn't hold the MTU down
+        manager.add_peer(connected_peer(&keypair, 4));
+
+        let interval = Duration::from_secs(600);
+        assert!(manager.check_mtu_probes(interval));
+        assert_eq!(manager.min_effective_mtu(), Some(1300));
+        assert!(!manager.check_mtu_probes(interval), "nothing changed");
+
+        // The path to peer 3 shrinks
+        manager.with_peer_by_ip(&ip(3), |p| {
+            p.next_mtu_probe = None;
+            p.start_mtu_probe();
+            expire_mtu_probe(p);
+        });
+        assert!(manager.check_mtu_probes(interval));
+        assert_eq!(manager.min_effective_mtu(), Some(1280));
+    }
+
+    #[test]
+    fn test_connection_quality() {
+        let timeout = Duration::from_secs(120);
+        let fresh = Some(Duration::from_secs(1));
+        assert_eq!(connection_quality(Some(10_000), Some(0.0), fresh, timeout), 100);
+        // Each component on its own
+        assert_eq!(connection_quality(Some(500_000), Some(0.0), fresh, timeout), 60);
+        assert_eq!(connection_quality(Some(260_000), Some(0.0), fresh, timeout), 80);
+        assert_eq!(connection_quality(Some(10_000), Some(0.1), fresh, timeout), 80);
+        assert_eq!(connection_quality(Some(10_000), Some(0.5), fresh, timeout), 60);
+        assert_eq!(connection_quality(Some(10_000), Some(0.0), Some(timeout), timeout), 80);
+        assert_eq!(connection_quality(Some(10_000), Some(0.0), None, timeout), 80);
+        // Unmeasured RTT gets half marks, unmeasured loss full marks
+        assert_eq!(connection_quality(None, None, fresh, timeout), 80);
+        assert_eq!(connection_quality(Some(900_000), Some(1.0), Some(timeout), timeout), 0);
+    }
+
+    #[test]
+    fn test_down_path_scores_zero() {
+        let mut peer = Peer::new(KeyPair::generate().public, ip(2));
+        assert_eq!(peer.update_quality(Duration::from_secs(120)), 0);
+        assert_eq!(peer.quality, Some(0));
+
+        let mut peer = connected_peer(&KeyPair::generate(), 2);
+        peer.record_rtt(10_000);
+        assert_eq!(peer.update_quality(Duration::from_secs(120)), 100);
+    }
+
+    #[test]
+    fn test_relay_ranking() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        let mut dest = connected_peer(&keypair, 5);
+        dest.record_rtt(400_000);
+        dest.loss_rate = Some(0.1);
+        dest.relay_rtts.insert(ip(2), 5_000);
+        dest.relay_rtts.insert(ip(3), 100_000);
+        dest.relay_rtts.insert(ip(4), 1_000);
+        manager.add_peer(dest);
+        for (last, rtt) in [(2, 5_000), (3, 100_000)] {
+            let mut relay = connected_peer(&keypair, last);
+            relay.record_rtt(rtt);
+            manager.add_peer(relay);
+        }
+        // The best hop, but the relay itself is down
+        manager.add_peer(Peer::new(KeyPair::generate().public, ip(4)));
+
+        manager.update_quality(Duration::from_secs(120), 0, false);
+        let scores = manager.with_peer_by_ip(&ip(5), |p| (p.quality, p.relay_quality)).unwrap();
+        assert_eq!(scores, (Some(48), Some((ip(2), 100))));
+        assert_eq!(manager.find_quality_relay(&ip(5)), Some(ip(2)));
+
+        // Back to direct once that path scores within the margin
+        manager.with_peer_by_ip(&ip(5), |p| p.quality = Some(91));
+        assert_eq!(manager.find_quality_relay(&ip(5)), None);
+        manager.with_peer_by_ip(&ip(5), |p| p.quality = Some(90));
+        assert_eq!(manager.find_quality_relay(&ip(5)), Some(ip(2)));
+
+        // Never before the direct path was scored
+        manager.with_peer_by_ip(&ip(5), |p| p.quality = None);
+        assert_eq!(manager.find_quality_relay(&ip(5)), None);
+    }
+
+    #[test]
+    fn test_low_quality_triggers_reconnect() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        let mut peer = connected_peer(&keypair, 2);
+        peer.record_rtt(500_000);
+        peer.loss_rate = Some(0.2);
+        manager.add_peer(peer);
+
+        manager.update_quality(Duration::from_secs(120), 50, false);
+        let state = manager.with_peer_by_ip(&ip(2), |p| (p.low_quality, p.link_state)).unwrap();
+        assert_eq!(state, (true, LinkState::Connected), "only logged without reconnect");
+
+        manager.with_peer_by_ip(&ip(2), |p| p.low_quality = false);
+        manager.update_quality(Duration::from_secs(120), 50, true);
+        let state = manager.with_peer_by_ip(&ip(2), |p| (p.low_quality, p.link_state)).unwrap();
+        assert_eq!(state, (true, LinkState::Disconnected));
+    }
+
+    /// The PEX entry a node sends for itself to advertise its subnets
+    fn subnet_entry(host: Ipv4Addr, subnets: &[&str]) -> PexEntry {
+        PexEntry { subnets: subnets.iter().map(|s| s.to_string()).collect(), ..pex_entry(host, None) }
+    }
+
+    fn learned_routes(manager: &PeerManager) -> Vec<(String, Ipv4Addr, RouteSource)> {
+        let mut routes: Vec<_> = manager.subnet_routes().iter()
+            .filter(|r| matches!(r.source, RouteSource::Pex(_)))
+            .map(|r| (r.cidr(), r.host, r.source))
+            .collect();
+        routes.sort_by(|a, b| a.0.cmp(&b.0));
+        routes
+    }
+
+    #[test]
+    fn test_pex_routes_applied() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        manager.add_peer(connected_peer(&keypair, 3));
+
+        assert!(manager.add_from_pex(&[subnet_entry(ip(2), &["172.17.0.0/24"])], ip(2), ip(1), &keypair));
+        assert_eq!(manager.find_route(&Ipv4Addr::new(172, 17, 0, 5)), Some(ip(2)));
+        assert_eq!(manager.find_route(&Ipv4Addr::new(172, 18, 0, 5)), None);
+        assert!(!manager.add_from_pex(&[subnet_entry(ip(2), &["172.17.0.0/24"])], ip(2), ip(1), &keypair),
+            "nothing new");
+
+        // Subnets learned from others are passed on, with the host they belong to
+        let entries = manager.get_pex_entries(ip(1));
+        let entry = entries.iter().find(|e| e.wolfnet_ip == ip(2).to_string()).unwrap();
+        assert_eq!(entry.subnets, ["172.17.0.0/24"]);
+        assert!(entries.iter().find(|e| e.wolfnet_ip == ip(3).to_string()).unwrap().subnets.is_empty());
+    }
+
+    #[test]
+    fn test_unsafe_pex_routes_ignored() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        manager.set_configured_routes(&[SubnetRoute {
+            network: Ipv4Addr::new(172, 17, 0, 0), prefix: 16, host: ip(1),
+            source: RouteSource::Local, advertise: true, unreachable: false,
+        }]);
+
+        let advertised = ["0.0.0.0/0", "10.0.0.0/7", "10.0.10.0/24", "172.17.5.0/24", "bogus", "172.30.0.0/24"];
+        manager.add_from_pex(&[subnet_entry(ip(2), &advertised)], ip(2), ip(1), &keypair);
+        // Too wide, covering WolfNet addresses or our own subnet: only the last is safe
+        assert_eq!(learned_routes(&manager), [("172.30.0.0/24".to_string(), ip(2), RouteSource::Pex(ip(2)))]);
+        assert_eq!(manager.find_route(&Ipv4Addr::new(172, 17, 5, 1)), Some(ip(1)));
+    }
+
+    #[test]
+    fn test_pex_routes_withdrawn() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        manager.add_peer(connected_peer(&keypair, 3));
+        manager.add_from_pex(&[
+            subnet_entry(ip(2), &["172.17.0.0/24", "172.18.0.0/24"]),
+            subnet_entry(ip(3), &["172.19.0.0/24"]),
+        ], ip(2), ip(1), &keypair);
+        assert_eq!(learned_routes(&manager).len(), 3);
+
+        // A subnet dropped from the host's entry is withdrawn
+        assert!(manager.add_from_pex(&[
+            subnet_entry(ip(2), &["172.18.0.0/24"]),
+            subnet_entry(ip(3), &["172.19.0.0/24"]),
+        ], ip(2), ip(1), &keypair));
+        assert_eq!(manager.find_route(&Ipv4Addr::new(172, 17, 0, 5)), None);
+        assert_eq!(learned_routes(&manager).len(), 2);
+
+        // So is everything for a host the sender no longer mentions
+        assert!(manager.add_from_pex(&[subnet_entry(ip(2), &["172.18.0.0/24"])], ip(2), ip(1), &keypair));
+        assert_eq!(learned_routes(&manager), [("172.18.0.0/24".to_string(), ip(2), RouteSource::Pex(ip(2)))]);
+
+        // And removing the sender forgets what it told us
+        manager.remove_peer(&ip(2));
+        assert!(learned_routes(&manager).is_empty());
+    }
+
+    #[test]
+    fn test_configured_routes_win_over_pex() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        manager.add_peer(connected_peer(&keypair, 3));
+        assert!(manager.add_route(Ipv4Addr::new(172, 20, 0, 0), 24, ip(3), RouteSource::Static));
+        manager.add_from_pex(&[subnet_entry(ip(2), &["172.20.0.0/24"])], ip(2), ip(1), &keypair);
+
+        // The learned route is kept for failover but the configured one is used
+        assert_eq!(learned_routes(&manager).len(), 1);
+        assert_eq!(manager.find_route(&Ipv4Addr::new(172, 20, 0, 9)), Some(ip(3)));
+        assert!(!manager.add_route(Ipv4Addr::new(172, 20, 0, 0), 24, ip(2), RouteSource::Pex(ip(2))));
+    }
+
+    fn static_route(host: Ipv4Addr) -> SubnetRoute {
+        SubnetRoute {
+            network: Ipv4Addr::new(172, 17, 0, 0), prefix: 24, host,
+            source: RouteSource::Static, advertise: false, unreachable: false,
+        }
+    }
+
+    /// Have `peer` fall silent long enough to be marked dead
+    fn take_offline(manager: &PeerManager, peer: Ipv4Addr) {
+        manager.with_peer_by_ip(&peer, |p| p.last_seen = Some(Instant::now() - Duration::from_secs(60)));
+        manager.check_liveness(Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_routes_fail_over_to_backup_and_back() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        manager.add_peer(connected_peer(&keypair, 3));
+        manager.set_configured_routes(&[static_route(ip(2))]);
+        // Peer 3 serves the same subnet as a backup
+        manager.add_from_pex(&[subnet_entry(ip(3), &["172.17.0.0/24"])], ip(3), ip(1), &keypair);
+        let container = Ipv4Addr::new(172, 17, 0, 5);
+        assert_eq!(manager.find_route(&container), Some(ip(2)));
+
+        manager.fail_over_routes();
+        assert_eq!(manager.find_route(&container), Some(ip(2)), "host still up");
+
+        take_offline(&manager, ip(2));
+        manager.fail_over_routes();
+        assert_eq!(manager.find_route(&container), Some(ip(3)));
+        assert!(!manager.buffer_for_route(&container, b"packet", 8), "the backup carries the traffic");
+        manager.fail_over_routes();
+        assert_eq!(manager.route_failover_counts(), (1, 0), "counted once");
+
+        // Still down: the route stays failed over, and the host is retried now
+        manager.with_peer_by_ip(&ip(2), |p| p.record_reconnect_attempt());
+        assert!(manager.restore_routes().is_empty());
+        assert_eq!(manager.find_route(&container), Some(ip(3)));
+        assert!(manager.with_peer_by_ip(&ip(2), |p| p.reconnect_due()).unwrap());
+
+        manager.with_peer_by_ip(&ip(2), |p| p.mark_alive());
+        manager.restore_routes();
+        assert_eq!(manager.find_route(&container), Some(ip(2)));
+        assert_eq!(manager.route_failover_counts(), (1, 1));
+    }
+
+    #[test]
+    fn test_packets_buffered_until_route_restored() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(connected_peer(&keypair, 2));
+        manager.set_configured_routes(&[static_route(ip(2))]);
+        let container = Ipv4Addr::new(172, 17, 0, 5);
+        assert!(!manager.buffer_for_route(&container, b"packet", 2), "route is up");
+
+        take_offline(&manager, ip(2));
+        manager.fail_over_routes();
+        // No backup: the route is kept and packets wait for it
+        assert_eq!(manager.find_route(&container), Some(ip(2)));
+        for packet in [b"one", b"two", b"3rd"] {
+            assert!(manager.buffer_for_route(&container, packet, 2));
+        }
+        assert!(!manager.buffer_for_route(&Ipv4Addr::new(172, 18, 0, 5), b"elsewhere", 2));
+
+        manager.with_peer_by_ip(&ip(2), |p| p.mark_alive());
+        // Replayed in order, past the limit dropped
+        assert_eq!(manager.restore_routes(), [b"one".to_vec(), b"two".to_vec()]);
+        assert!(manager.restore_routes().is_empty());
+        assert!(!manager.buffer_for_route(&container, b"packet", 2));
+    }
+
+    #[test]
+    fn test_revoked_peer_session_dropped() {
+        let (kp1, kp2) = (KeyPair::generate(), KeyPair::generate());
+        let manager = PeerManager::new();
+        let mut to_2 = Peer::new(kp2.public, ip(2));
+        to_2.establish_session(&kp1);
+        to_2.mark_alive();
+        manager.add_peer(to_2);
+        manager.add_peer(connected_peer(&kp1, 3));
+        let mut to_1 = Peer::new(kp1.public, ip(1));
+        to_1.establish_session(&kp2);
+        let (counter, ciphertext) = to_1.encrypt(b"data").unwrap();
+        assert_eq!(manager.with_peer_by_ip(&ip(2), |p| p.decrypt(counter, &ciphertext).unwrap()).unwrap(), b"data");
+
+        let revoked: RevokedKeys = [*kp2.public.as_bytes()].into();
+        assert_eq!(manager.set_revoked_keys(revoked.clone()), [ip(2)]);
+        assert!(manager.is_revoked(&kp2.public));
+        manager.with_peer_by_ip(&ip(2), |p| {
+            assert!(p.revoked && p.cipher.is_none() && p.session_id.is_none());
+            assert_eq!(p.link_state, LinkState::Disconnected);
+            // Its packets are rejected and a handshake doesn't bring it back
+            let (counter, ciphertext) = to_1.encrypt(b"more").unwrap();
+            assert!(p.decrypt(counter, &ciphertext).is_err());
+            assert!(p.encrypt(b"data").is_err());
+            p.establish_session(&kp1);
+            assert!(p.cipher.is_none());
+        });
+        // Other peers are untouched, and the same CRL again revokes no one new
+        assert!(manager.with_peer_by_ip(&ip(3), |p| !p.revoked && p.cipher.is_some()).unwrap());
+        assert!(manager.set_revoked_keys(revoked).is_empty());
+
+        // Taken off the CRL, it may handshake again
+        assert!(manager.set_revoked_keys(RevokedKeys::new()).is_empty());
+        manager.with_peer_by_ip(&ip(2), |p| {
+            assert!(!p.revoked);
+            p.establish_session(&kp1);
+            let (counter, ciphertext) = to_1.encrypt(b"back").unwrap();
+            assert_eq!(p.decrypt(counter, &ciphertext).unwrap(), b"back");
+        });
+    }
+
+    #[test]
+    fn test_revoked_key_applies_to_new_peers() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        let revoked = KeyPair::generate().public;
+        manager.set_revoked_keys([*revoked.as_bytes()].into());
+
+        let mut peer = Peer::new(revoked, ip(2));
+        peer.establish_session(&keypair);
+        manager.add_peer(peer);
+        assert!(manager.with_peer_by_ip(&ip(2), |p| p.revoked && p.cipher.is_none()).unwrap());
+
+        manager.update_from_discovery(&revoked, SocketAddr::from(([192, 0, 2, 3], 9600)), ip(3), "lan", false);
+        assert!(manager.with_peer_by_ip(&ip(3), |p| p.revoked).unwrap());
+    }
+
+    /// A connected hub at 10.0.10.<last> with the given smoothed RTT
+    fn hub(keypair: &KeyPair, last: u8, rtt_us: u64) -> Peer {
+        let mut peer = connected_peer(keypair, last);
+        peer.is_hub = true;
+        peer.avg_rtt_us = Some(rtt_us);
+        peer
+    }
+
+    #[test]
+    fn test_hub_selection_by_latency() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(hub(&keypair, 2, 30_000));
+        manager.add_peer(hub(&keypair, 3, 20_000));
+        manager.add_peer(hub(&keypair, 4, 25_000));
+        manager.add_peer(connected_peer(&keypair, 5));
+        assert_eq!(manager.hub_ips(), [ip(2), ip(3), ip(4)]);
+        assert_eq!(manager.active_hub(), None);
+
+        assert_eq!(manager.select_hub(HubSelection::Latency), Some(ip(3)));
+        assert_eq!(manager.select_hub(HubSelection::Latency), None, "unchanged");
+
+        // Faster, but not by enough to switch
+        manager.with_peer_by_ip(&ip(4), |p| p.avg_rtt_us = Some(17_000));
+        assert_eq!(manager.select_hub(HubSelection::Latency), None);
+        assert_eq!(manager.active_hub(), Some(ip(3)));
+
+        manager.with_peer_by_ip(&ip(4), |p| p.avg_rtt_us = Some(15_000));
+        assert_eq!(manager.select_hub(HubSelection::Latency), Some(ip(4)));
+        let status = manager.hub_status();
+        assert_eq!(status.iter().filter(|h| h.active).map(|h| h.address.as_str()).collect::<Vec<_>>(), ["10.0.10.4"]);
+        assert!(status.iter().all(|h| h.available));
+    }
+
+    #[test]
+    fn test_hub_failover() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        manager.add_peer(hub(&keypair, 2, 20_000));
+        manager.add_peer(hub(&keypair, 3, 30_000));
+        let silence = |peer: Ipv4Addr| manager.with_peer_by_ip(&peer, |p| {
+            p.last_seen = Some(Instant::now() - HUB_TIMEOUT);
+        });
+
+        for selection in [HubSelection::First, HubSelection::Latency] {
+            manager.with_peer_by_ip(&ip(2), |p| p.mark_alive());
+            manager.select_hub(selection);
+            assert_eq!(manager.active_hub(), Some(ip(2)));
+
+            // Silent past the hub timeout, though not yet marked dead
+            silence(ip(2));
+            assert!(manager.with_peer_by_ip(&ip(2), |p| p.is_connected()).unwrap());
+            assert_eq!(manager.select_hub(selection), Some(ip(3)));
+            assert!(!manager.hub_status()[0].available);
+        }
+
+        // Back again: kept in first mode until the current hub fails...
+        manager.with_peer_by_ip(&ip(2), |p| p.mark_alive());
+        assert_eq!(manager.select_hub(HubSelection::First), None);
+        assert_eq!(manager.active_hub(), Some(ip(3)));
+        // ...but faster in latency mode
+        assert_eq!(manager.select_hub(HubSelection::Latency), Some(ip(2)));
+
+        silence(ip(2));
+        silence(ip(3));
+        assert_eq!(manager.select_hub(HubSelection::Latency), None);
+        assert_eq!(manager.active_hub(), None, "no hub reachable");
+    }
+
+    #[test]
+    fn test_random_hub_kept_while_available() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        for last in 2..6 {
+            manager.add_peer(hub(&keypair, last, 20_000));
+        }
+        let chosen = manager.select_hub(HubSelection::Random).unwrap();
+        assert!(manager.hub_ips().contains(&chosen));
+        for _ in 0..10 {
+            assert_eq!(manager.select_hub(HubSelection::Random), None);
+        }
+        manager.with_peer_by_ip(&chosen, |p| p.set_revoked(true));
+        let next = manager.select_hub(HubSelection::Random).unwrap();
+        assert_ne!(next, chosen);
+    }
+
+    #[test]
+    fn test_set_hubs_keeps_config_order_and_learned_hubs() {
+        let keypair = KeyPair::generate();
+        let manager = PeerManager::new();
+        for last in 2..6 {
+            manager.add_peer(connected_peer(&keypair, last));
+        }
+        // Learned through PEX from 10.0.10.2
+        let mut learned = Peer::new(KeyPair::generate().public, ip(6));
+        learned.is_hub = true;
+        learned.relay_via = Some(ip(2));
+        manager.add_peer(learned);
+
+        manager.set_hubs(&[ip(4), ip(3)]);
+        assert_eq!(manager.hub_ips(), [ip(4), ip(3), ip(6)]);
+        manager.set_hubs(&[ip(5)]);
+        assert_eq!(manager.hub_ips(), [ip(5), ip(6)]);
+        assert!(!manager.with_peer_by_ip(&ip(4), |p| p.is_hub).unwrap());
+        assert!(manager.with_peer_by_ip(&ip(6), |p| p.is_hub).unwrap());
+        // A hub learned through PEX is never selected: it isn't reached directly
+        assert_eq!(manager.select_hub(HubSelection::First), Some(ip(5)));
+        assert_eq!(manager.hub_status().len(), 2);
+    }
+}
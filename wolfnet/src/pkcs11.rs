@@ -0,0 +1,386 @@
+//! PKCS#11 key storage for WolfNet
+//!
+//! Keeps the node's X25519 private key on a PKCS#11 token (an HSM, or SoftHSM
+//! for testing) so it never exists in process memory. The key is a Montgomery
+//! EC key (`CKK_EC_MONTGOMERY`, curve25519) marked sensitive and
+//! non-extractable; Diffie-Hellman runs on the token through
+//! `CKM_ECDH1_DERIVE` and only the shared secret comes back.
+//!
+//! The module is loaded at runtime with `dlopen`, so no PKCS#11 library is
+//! needed unless `security.key_backend = "pkcs11"`. Only the handful of
+//! Cryptoki functions WolfNet needs are bound.
+
+use std::ffi::{c_ulong, c_void, CString};
+use std::path::Path;
+use std::ptr;
+use std::sync::Mutex;
+
+use tracing::info;
+
+type CkRv = c_ulong;
+type CkUlong = c_ulong;
+type CkSessionHandle = c_ulong;
+type CkObjectHandle = c_ulong;
+
+const CKR_OK: CkRv = 0;
+const CKR_USER_ALREADY_LOGGED_IN: CkRv = 0x100;
+const CKR_CRYPTOKI_ALREADY_INITIALIZED: CkRv = 0x191;
+
+const CKF_RW_SESSION: CkUlong = 0x2;
+const CKF_SERIAL_SESSION: CkUlong = 0x4;
+const CKF_OS_LOCKING_OK: CkUlong = 0x2;
+const CKU_USER: CkUlong = 1;
+
+const CKA_CLASS: CkUlong = 0x0;
+const CKA_TOKEN: CkUlong = 0x1;
+const CKA_PRIVATE: CkUlong = 0x2;
+const CKA_LABEL: CkUlong = 0x3;
+const CKA_VALUE: CkUlong = 0x11;
+const CKA_KEY_TYPE: CkUlong = 0x100;
+const CKA_SENSITIVE: CkUlong = 0x103;
+const CKA_DERIVE: CkUlong = 0x10C;
+const CKA_VALUE_LEN: CkUlong = 0x161;
+const CKA_EXTRACTABLE: CkUlong = 0x162;
+const CKA_EC_PARAMS: CkUlong = 0x180;
+const CKA_EC_POINT: CkUlong = 0x181;
+
+const CKO_PUBLIC_KEY: CkUlong = 0x2;
+const CKO_PRIVATE_KEY: CkUlong = 0x3;
+const CKO_SECRET_KEY: CkUlong = 0x4;
+const CKK_GENERIC_SECRET: CkUlong = 0x10;
+
+const CKM_EC_MONTGOMERY_KEY_PAIR_GEN: CkUlong = 0x1056;
+const CKM_ECDH1_DERIVE: CkUlong = 0x1050;
+const CKD_NULL: CkUlong = 0x1;
+
+/// DER-encoded OID 1.3.101.110 (X25519), used as CKA_EC_PARAMS
+const X25519_EC_PARAMS: &[u8] = &[0x06, 0x03, 0x2B, 0x65, 0x6E];
+
+const CK_TRUE: u8 = 1;
+const CK_FALSE: u8 = 0;
+
+#[repr(C)]
+struct Attribute {
+    kind: CkUlong,
+    value: *mut c_void,
+    value_len: CkUlong,
+}
+
+#[repr(C)]
+struct Mechanism {
+    mechanism: CkUlong,
+    parameter: *mut c_void,
+    parameter_len: CkUlong,
+}
+
+#[repr(C)]
+struct Ecdh1DeriveParams {
+    kdf: CkUlong,
+    shared_data_len: CkUlong,
+    shared_data: *mut u8,
+    public_data_len: CkUlong,
+    public_data: *mut u8,
+}
+
+#[repr(C)]
+struct InitializeArgs {
+    create_mutex: *const c_void,
+    destroy_mutex: *const c_void,
+    lock_mutex: *const c_void,
+    unlock_mutex: *const c_void,
+    flags: CkUlong,
+    reserved: *mut c_void,
+}
+
+/// `CK_FUNCTION_LIST`, in the order of the PKCS#11 v2.40 header. Entries
+/// WolfNet does not call are left as opaque pointers.
+#[repr(C)]
+struct FunctionList {
+    version: [u8; 2],
+    initialize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+    finalize: unsafe extern "C" fn(*mut c_void) -> CkRv,
+    _get_info_to_set_pin: [*const c_void; 10],
+    open_session: unsafe extern "C" fn(CkUlong, CkUlong, *mut c_void, *const c_void, *mut CkSessionHandle) -> CkRv,
+    close_session: unsafe extern "C" fn(CkSessionHandle) -> CkRv,
+    _close_all_sessions_to_set_operation_state: [*const c_void; 4],
+    login: unsafe extern "C" fn(CkSessionHandle, CkUlong, *const u8, CkUlong) -> CkRv,
+    _logout_to_copy_object: [*const c_void; 3],
+    destroy_object: unsafe extern "C" fn(CkSessionHandle, CkObjectHandle) -> CkRv,
+    _get_object_size: *const c_void,
+    get_attribute_value: unsafe extern "C" fn(CkSessionHandle, CkObjectHandle, *mut Attribute, CkUlong) -> CkRv,
+    _set_attribute_value: *const c_void,
+    find_objects_init: unsafe extern "C" fn(CkSessionHandle, *const Attribute, CkUlong) -> CkRv,
+    find_objects: unsafe extern "C" fn(CkSessionHandle, *mut CkObjectHandle, CkUlong, *mut CkUlong) -> CkRv,
+    find_objects_final: unsafe extern "C" fn(CkSessionHandle) -> CkRv,
+    _encrypt_init_to_generate_key: [*const c_void; 30],
+    generate_key_pair: unsafe extern "C" fn(
+        CkSessionHandle, *const Mechanism,
+        *const Attribute, CkUlong,
+        *const Attribute, CkUlong,
+        *mut CkObjectHandle, *mut CkObjectHandle,
+    ) -> CkRv,
+    _wrap_key_to_unwrap_key: [*const c_void; 2],
+    derive_key: unsafe extern "C" fn(
+        CkSessionHandle, *const Mechanism, CkObjectHandle,
+        *const Attribute, CkUlong, *mut CkObjectHandle,
+    ) -> CkRv,
+}
+
+fn check(rv: CkRv, call: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if rv == CKR_OK {
+        Ok(())
+    } else {
+        Err(format!("PKCS#11 {} failed: CKR 0x{:x}", call, rv).into())
+    }
+}
+
+/// Attribute pointing at a value that outlives the call
+fn attr<T>(kind: CkUlong, value: &T) -> Attribute {
+    Attribute { kind, value: value as *const T as *mut c_void, value_len: std::mem::size_of::<T>() as CkUlong }
+}
+
+fn attr_bytes(kind: CkUlong, value: &[u8]) -> Attribute {
+    Attribute { kind, value: value.as_ptr() as *mut c_void, value_len: value.len() as CkUlong }
+}
+
+/// Open session to a token, logged in as the user
+struct Session {
+    library: *mut c_void,
+    functions: *const FunctionList,
+    handle: CkSessionHandle,
+}
+
+impl Session {
+    fn f(&self) -> &FunctionList {
+        // SAFETY: the module keeps its function list alive while loaded
+        unsafe { &*self.functions }
+    }
+
+    fn find(&self, template: &[Attribute]) -> Result<Option<CkObjectHandle>, Box<dyn std::error::Error>> {
+        let f = self.f();
+        let mut object: CkObjectHandle = 0;
+        let mut count: CkUlong = 0;
+        unsafe {
+            check((f.find_objects_init)(self.handle, template.as_ptr(), template.len() as CkUlong), "C_FindObjectsInit")?;
+            let rv = (f.find_objects)(self.handle, &mut object, 1, &mut count);
+            (f.find_objects_final)(self.handle);
+            check(rv, "C_FindObjects")?;
+        }
+        Ok((count > 0).then_some(object))
+    }
+
+    fn attribute(&self, object: CkObjectHandle, kind: CkUlong) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let f = self.f();
+        let mut template = [Attribute { kind, value: ptr::null_mut(), value_len: 0 }];
+        unsafe {
+            check((f.get_attribute_value)(self.handle, object, template.as_mut_ptr(), 1), "C_GetAttributeValue")?;
+            let mut value = vec![0u8; template[0].value_len as usize];
+            template[0].value = value.as_mut_ptr() as *mut c_void;
+            check((f.get_attribute_value)(self.handle, object, template.as_mut_ptr(), 1), "C_GetAttributeValue")?;
+            value.truncate(template[0].value_len as usize);
+            Ok(value)
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        unsafe {
+            let f = &*self.functions;
+            (f.close_session)(self.handle);
+            (f.finalize)(ptr::null_mut());
+            libc::dlclose(self.library);
+        }
+    }
+}
+
+/// X25519 private key held on a PKCS#11 token
+pub struct Pkcs11Key {
+    session: Mutex<Session>,
+    private_key: CkObjectHandle,
+}
+
+// SAFETY: the raw pointers are only dereferenced with the session locked, and
+// the library is initialised with CKF_OS_LOCKING_OK
+unsafe impl Send for Pkcs11Key {}
+unsafe impl Sync for Pkcs11Key {}
+
+impl Pkcs11Key {
+    /// Open `module_path`, log in to `slot` with `pin` and find the key pair
+    /// labelled `label`, generating it on the token if there is none.
+    /// Returns the key and its public half.
+    pub fn load(module_path: &Path, slot: u64, pin: &str, label: &str) -> Result<(Self, [u8; 32]), Box<dyn std::error::Error>> {
+        let session = open_session(module_path, slot, pin)?;
+
+        let label_bytes = label.as_bytes();
+        let private_class = CKO_PRIVATE_KEY;
+        let public_class = CKO_PUBLIC_KEY;
+        let find_private = [attr(CKA_CLASS, &private_class), attr_bytes(CKA_LABEL, label_bytes)];
+        let find_public = [attr(CKA_CLASS, &public_class), attr_bytes(CKA_LABEL, label_bytes)];
+
+        let (private_key, public_key) = match (session.find(&find_private)?, session.find(&find_public)?) {
+            (Some(private), Some(public)) => (private, public),
+            (None, None) => {
+                info!("No key '{}' on PKCS#11 slot {}, generating one", label, slot);
+                generate_key_pair(&session, label_bytes)?
+            }
+            _ => return Err(format!("PKCS#11 token has only half of key pair '{}'", label).into()),
+        };
+
+        let point = session.attribute(public_key, CKA_EC_POINT)?;
+        let public = decode_ec_point(&point)
+            .ok_or_else(|| format!("unexpected CKA_EC_POINT for '{}' ({} bytes); is it an X25519 key?", label, point.len()))?;
+
+        Ok((Self { session: Mutex::new(session), private_key }, public))
+    }
+
+    /// X25519 with the token's private key, returning the shared secret
+    pub fn diffie_hellman(&self, peer_public: &[u8; 32]) -> Result<[u8; 32], Box<dyn std::error::Error>> {
+        let session = self.session.lock().unwrap();
+        let f = session.f();
+
+        let mut public_data = *peer_public;
+        let mut params = Ecdh1DeriveParams {
+            kdf: CKD_NULL,
+            shared_data_len: 0,
+            shared_data: ptr::null_mut(),
+            public_data_len: public_data.len() as CkUlong,
+            public_data: public_data.as_mut_ptr(),
+        };
+        let mechanism = Mechanism {
+            mechanism: CKM_ECDH1_DERIVE,
+            parameter: &mut params as *mut Ecdh1DeriveParams as *mut c_void,
+            parameter_len: std::mem::size_of::<Ecdh1DeriveParams>() as CkUlong,
+        };
+        let class = CKO_SECRET_KEY;
+        let key_type = CKK_GENERIC_SECRET;
+        let value_len: CkUlong = 32;
+        let template = [
+            attr(CKA_CLASS, &class),
+            attr(CKA_KEY_TYPE, &key_type),
+            attr(CKA_VALUE_LEN, &value_len),
+            attr(CKA_TOKEN, &CK_FALSE),
+            attr(CKA_SENSITIVE, &CK_FALSE),
+            attr(CKA_EXTRACTABLE, &CK_TRUE),
+        ];
+
+        let mut derived: CkObjectHandle = 0;
+        unsafe {
+            check((f.derive_key)(
+                session.handle, &mechanism, self.private_key,
+                template.as_ptr(), template.len() as CkUlong, &mut derived,
+            ), "C_DeriveKey")?;
+        }
+        let value = session.attribute(derived, CKA_VALUE);
+        unsafe { (f.destroy_object)(session.handle, derived); }
+
+        let value = value?;
+        if value.len() != 32 {
+            return Err(format!("PKCS#11 derived {} bytes, expected 32", value.len()).into());
+        }
+        let mut shared = [0u8; 32];
+        shared.copy_from_slice(&value);
+        Ok(shared)
+    }
+}
+
+fn open_session(module_path: &Path, slot: u64, pin: &str) -> Result<Session, Box<dyn std::error::Error>> {
+    let path = CString::new(module_path.as_os_str().as_encoded_bytes())?;
+    let library = unsafe { libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+    if library.is_null() {
+        return Err(format!("cannot load PKCS#11 module {}", module_path.display()).into());
+    }
+
+    let symbol = unsafe { libc::dlsym(library, c"C_GetFunctionList".as_ptr()) };
+    if symbol.is_null() {
+        unsafe { libc::dlclose(library); }
+        return Err(format!("{} is not a PKCS#11 module (no C_GetFunctionList)", module_path.display()).into());
+    }
+    let get_function_list: unsafe extern "C" fn(*mut *const FunctionList) -> CkRv =
+        unsafe { std::mem::transmute(symbol) };
+
+    let mut functions: *const FunctionList = ptr::null();
+    let rv = unsafe { get_function_list(&mut functions) };
+    if rv != CKR_OK || functions.is_null() {
+        unsafe { libc::dlclose(library); }
+        return Err(format!("PKCS#11 C_GetFunctionList failed: CKR 0x{:x}", rv).into());
+    }
+    let f = unsafe { &*functions };
+
+    let mut init_args = InitializeArgs {
+        create_mutex: ptr::null(),
+        destroy_mutex: ptr::null(),
+        lock_mutex: ptr::null(),
+        unlock_mutex: ptr::null(),
+        flags: CKF_OS_LOCKING_OK,
+        reserved: ptr::null_mut(),
+    };
+    let rv = unsafe { (f.initialize)(&mut init_args as *mut InitializeArgs as *mut c_void) };
+    if rv != CKR_OK && rv != CKR_CRYPTOKI_ALREADY_INITIALIZED {
+        unsafe { libc::dlclose(library); }
+        return Err(format!("PKCS#11 C_Initialize failed: CKR 0x{:x}", rv).into());
+    }
+
+    let mut handle: CkSessionHandle = 0;
+    let rv = unsafe {
+        (f.open_session)(slot as CkUlong, CKF_SERIAL_SESSION | CKF_RW_SESSION, ptr::null_mut(), ptr::null(), &mut handle)
+    };
+    if rv != CKR_OK {
+        unsafe {
+            (f.finalize)(ptr::null_mut());
+            libc::dlclose(library);
+        }
+        return Err(format!("PKCS#11 C_OpenSession on slot {} failed: CKR 0x{:x}", slot, rv).into());
+    }
+    // From here on Drop cleans up
+    let session = Session { library, functions, handle };
+
+    let rv = unsafe { (f.login)(handle, CKU_USER, pin.as_ptr(), pin.len() as CkUlong) };
+    if rv != CKR_USER_ALREADY_LOGGED_IN {
+        check(rv, "C_Login")?;
+    }
+    Ok(session)
+}
+
+/// Generate a sensitive, non-extractable X25519 key pair on the token
+fn generate_key_pair(session: &Session, label: &[u8]) -> Result<(CkObjectHandle, CkObjectHandle), Box<dyn std::error::Error>> {
+    let mechanism = Mechanism { mechanism: CKM_EC_MONTGOMERY_KEY_PAIR_GEN, parameter: ptr::null_mut(), parameter_len: 0 };
+    let public_template = [
+        attr(CKA_TOKEN, &CK_TRUE),
+        attr_bytes(CKA_LABEL, label),
+        attr_bytes(CKA_EC_PARAMS, X25519_EC_PARAMS),
+    ];
+    let private_template = [
+        attr(CKA_TOKEN, &CK_TRUE),
+        attr(CKA_PRIVATE, &CK_TRUE),
+        attr(CKA_SENSITIVE, &CK_TRUE),
+        attr(CKA_EXTRACTABLE, &CK_FALSE),
+        attr(CKA_DERIVE, &CK_TRUE),
+        attr_bytes(CKA_LABEL, label),
+    ];
+
+    let mut public: CkObjectHandle = 0;
+    let mut private: CkObjectHandle = 0;
+    unsafe {
+        check((session.f().generate_key_pair)(
+            session.handle, &mechanism,
+            public_template.as_ptr(), public_template.len() as CkUlong,
+            private_template.as_ptr(), private_template.len() as CkUlong,
+            &mut public, &mut private,
+        ), "C_GenerateKeyPair")?;
+    }
+    Ok((private, public))
+}
+
+/// CKA_EC_POINT of a Montgomery key: either the raw 32-byte u-coordinate or
+/// that wrapped in a DER OCTET STRING, depending on the token
+fn decode_ec_point(point: &[u8]) -> Option<[u8; 32]> {
+    let raw = match point {
+        [0x04, 0x20, rest @ ..] if rest.len() == 32 => rest,
+        _ if point.len() == 32 => point,
+        _ => return None,
+    };
+    let mut public = [0u8; 32];
+    public.copy_from_slice(raw);
+    Some(public)
+}
@@ -0,0 +1,299 @@
+//! Split tunneling for WolfNet
+//!
+//! `[[route]]` entries choose per destination whether traffic goes through
+//! the tunnel (`via = "wolfnet"`) or the local network (`via = "local"`).
+//! Routes are installed in the main table tagged with protocol
+//! `SPLIT_ROUTE_PROTO`, so they can be listed and removed without touching
+//! anything else; as usual the most specific destination wins.
+//!
+//! `0.0.0.0/0` is installed as `0.0.0.0/1` + `128.0.0.0/1`, so it overrides
+//! the existing default route (including the gateway route WolfNet adds on
+//! client nodes) instead of replacing it.
+//!
+//! A `wolfnet` destination that covers a peer's public endpoint would send the
+//! tunnel's own UDP packets into the tunnel. Rather than marking the socket
+//! and adding fwmark policy rules, each such endpoint gets a /32 route via the
+//! local gateway, which the kernel prefers over the wider tunnel route.
+
+use std::net::Ipv4Addr;
+
+use tracing::warn;
+
+use crate::config::{RouteVia, SplitRoute};
+
+/// Routing protocol number tagging every route we install (0x57, "W").
+/// Values above RTPROT_STATIC are free for routing daemons.
+pub const SPLIT_ROUTE_PROTO: &str = "87";
+
+/// Parse "a.b.c.d/n" into its network address and prefix length
+pub fn parse_cidr(cidr: &str) -> Result<(Ipv4Addr, u8), Box<dyn std::error::Error>> {
+    let (addr, prefix) = cidr.split_once('/').unwrap_or((cidr, "32"));
+    let addr: Ipv4Addr = addr.parse().map_err(|e| format!("invalid address '{}': {}", cidr, e))?;
+    let prefix: u8 = prefix.parse().map_err(|e| format!("invalid prefix '{}': {}", cidr, e))?;
+    if prefix > 32 {
+        return Err(format!("invalid prefix '{}': must be 0-32", cidr).into());
+    }
+    Ok((Ipv4Addr::from(u32::from(addr) & prefix_mask(prefix)), prefix))
+}
+
+fn prefix_mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+/// Whether `ip` is inside the network `(net, prefix)`
+fn contains((net, prefix): (Ipv4Addr, u8), ip: Ipv4Addr) -> bool {
+    u32::from(ip) & prefix_mask(prefix) == u32::from(net)
+}
+
+/// The local default gateway (address, interface), ignoring routes through
+/// the WolfNet interface itself
+pub fn local_gateway(wolfnet_interface: &str) -> Option<(Ipv4Addr, String)> {
+    let output = std::process::Command::new("ip")
+        .args(["route", "show", "default"])
+        .output()
+        .ok()?;
+    // Parse: "default via X.X.X.X dev ethN ..."
+    String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let value = |key: &str| words.windows(2).find(|w| w[0] == key).map(|w| w[1]);
+        let dev = value("dev")?;
+        if dev == wolfnet_interface {
+            return None;
+        }
+        Some((value("via")?.parse().ok()?, dev.to_string()))
+    })
+}
+
+/// Build the `ip route replace` argument lists for the configured routes.
+/// `endpoints` are the peers' public IPs, kept off the tunnel.
+fn route_args(
+    wolfnet_interface: &str,
+    routes: &[SplitRoute],
+    gateway: Option<&(Ipv4Addr, String)>,
+    endpoints: &[Ipv4Addr],
+) -> Vec<Vec<String>> {
+    let via_tunnel = || vec!["dev".to_string(), wolfnet_interface.to_string()];
+    let via_local = |gw: &(Ipv4Addr, String)| vec!["via".to_string(), gw.0.to_string(), "dev".to_string(), gw.1.clone()];
+
+    let mut args = Vec::new();
+    let mut add = |destination: String, target: Vec<String>| {
+        let mut a = vec![destination];
+        a.extend(target);
+        a.extend(["proto".to_string(), SPLIT_ROUTE_PROTO.to_string()]);
+        args.push(a);
+    };
+
+    let mut tunnel_networks = Vec::new();
+    for (index, route) in routes.iter().enumerate() {
+        let network = match parse_cidr(&route.destination) {
+            Ok(n) => n,
+            Err(e) => { warn!("Skipping route #{}: {}", index, e); continue; }
+        };
+        let target = match route.via {
+            RouteVia::Wolfnet => {
+                tunnel_networks.push(network);
+                via_tunnel()
+            }
+            RouteVia::Local => match gateway {
+                Some(gw) => via_local(gw),
+                None => { warn!("Skipping route #{} ({}): no local default gateway", index, route.destination); continue; }
+            },
+        };
+        // Two halves override the default route without replacing it
+        if network.1 == 0 {
+            add("0.0.0.0/1".into(), target.clone());
+            add("128.0.0.0/1".into(), target);
+        } else {
+            add(format!("{}/{}", network.0, network.1), target);
+        }
+    }
+
+    if let Some(gw) = gateway {
+        for endpoint in endpoints {
+            if tunnel_networks.iter().any(|n| contains(*n, *endpoint)) {
+                add(format!("{}/32", endpoint), via_local(gw));
+            }
+        }
+    }
+    args
+}
+
+/// Install the split-tunnel routes, replacing any previously installed ones.
+/// Returns the number of routes installed.
+pub fn apply_split_routes(
+    wolfnet_interface: &str,
+    routes: &[SplitRoute],
+    endpoints: &[Ipv4Addr],
+) -> Result<usize, Box<dyn std::error::Error>> {
+    flush_split_routes();
+
+    let gateway = local_gateway(wolfnet_interface);
+    let mut installed = 0;
+    for args in route_args(wolfnet_interface, routes, gateway.as_ref(), endpoints) {
+        let status = std::process::Command::new("ip")
+            .args(["route", "replace"])
+            .args(&args)
+            .status()?;
+        if status.success() {
+            installed += 1;
+        } else {
+            warn!("Failed to add route {}", args.join(" "));
+        }
+    }
+    Ok(installed)
+}
+
+/// List installed split-tunnel routes (in `ip route show` format)
+pub fn list_split_routes() -> Vec<String> {
+    let output = match std::process::Command::new("ip")
+        .args(["route", "show", "proto", SPLIT_ROUTE_PROTO])
+        .output()
+    {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Remove every route tagged with `SPLIT_ROUTE_PROTO`.
+/// Returns the number of routes removed.
+pub fn flush_split_routes() -> usize {
+    let installed = list_split_routes().len();
+    if installed == 0 {
+        return 0;
+    }
+    let ok = std::process::Command::new("ip")
+        .args(["route", "flush", "proto", SPLIT_ROUTE_PROTO])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if !ok {
+        warn!("Failed to remove split-tunnel routes");
+        return 0;
+    }
+    installed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn route(destination: &str, via: RouteVia) -> SplitRoute {
+        SplitRoute { destination: destination.into(), via }
+    }
+
+    fn gateway() -> (Ipv4Addr, String) {
+        (Ipv4Addr::new(192, 168, 1, 1), "eth0".into())
+    }
+
+    fn line(args: &[String]) -> String {
+        args.join(" ")
+    }
+
+    #[test]
+    fn test_parse_cidr() {
+        assert_eq!(parse_cidr("192.168.100.0/24").unwrap(), (Ipv4Addr::new(192, 168, 100, 0), 24));
+        // Host bits are cleared and a bare address is a /32
+        assert_eq!(parse_cidr("192.168.100.77/24").unwrap(), (Ipv4Addr::new(192, 168, 100, 0), 24));
+        assert_eq!(parse_cidr("8.8.8.8").unwrap(), (Ipv4Addr::new(8, 8, 8, 8), 32));
+        assert_eq!(parse_cidr("10.1.2.3/0").unwrap(), (Ipv4Addr::UNSPECIFIED, 0));
+
+        for bad in ["", "10.0.0/8", "10.0.0.0/33", "10.0.0.0/x", "example.com/24"] {
+            assert!(parse_cidr(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_contains() {
+        let net = parse_cidr("10.20.0.0/16").unwrap();
+        assert!(contains(net, Ipv4Addr::new(10, 20, 255, 1)));
+        assert!(!contains(net, Ipv4Addr::new(10, 21, 0, 1)));
+        assert!(contains((Ipv4Addr::UNSPECIFIED, 0), Ipv4Addr::new(203, 0, 113, 9)));
+    }
+
+    #[test]
+    fn test_route_config() {
+        let config: Config = toml::from_str(r#"
+            [network]
+            address = "10.0.10.2"
+
+            [[route]]
+            destination = "0.0.0.0/0"
+            via = "wolfnet"
+
+            [[route]]
+            destination = "192.168.1.0/24"
+            via = "local"
+        "#).unwrap();
+        assert_eq!(config.routes.len(), 2);
+        assert_eq!(config.routes[0].destination, "0.0.0.0/0");
+        assert_eq!(config.routes[0].via, RouteVia::Wolfnet);
+        assert_eq!(config.routes[1].via, RouteVia::Local);
+
+        let bad_via = r#"
+            [network]
+            address = "10.0.10.2"
+
+            [[route]]
+            destination = "0.0.0.0/0"
+            via = "vpn"
+        "#;
+        assert!(toml::from_str::<Config>(bad_via).is_err());
+
+        // No [[route]] entries means no split tunneling
+        let config: Config = toml::from_str("[network]\naddress = \"10.0.10.2\"").unwrap();
+        assert!(config.routes.is_empty());
+    }
+
+    #[test]
+    fn test_route_args() {
+        let routes = [
+            route("172.16.0.0/12", RouteVia::Wolfnet),
+            route("192.168.1.0/24", RouteVia::Local),
+        ];
+        let args: Vec<String> = route_args("wolfnet0", &routes, Some(&gateway()), &[])
+            .iter().map(|a| line(a)).collect();
+        assert_eq!(args, [
+            "172.16.0.0/12 dev wolfnet0 proto 87",
+            "192.168.1.0/24 via 192.168.1.1 dev eth0 proto 87",
+        ]);
+    }
+
+    #[test]
+    fn test_default_route_split_in_halves() {
+        let args: Vec<String> = route_args("wolfnet0", &[route("0.0.0.0/0", RouteVia::Wolfnet)], Some(&gateway()), &[])
+            .iter().map(|a| line(a)).collect();
+        assert_eq!(args, ["0.0.0.0/1 dev wolfnet0 proto 87", "128.0.0.0/1 dev wolfnet0 proto 87"]);
+    }
+
+    #[test]
+    fn test_tunnelled_endpoints_stay_local() {
+        let endpoints = [Ipv4Addr::new(203, 0, 113, 5), Ipv4Addr::new(198, 51, 100, 7)];
+        let routes = [route("203.0.113.0/24", RouteVia::Wolfnet)];
+        let args: Vec<String> = route_args("wolfnet0", &routes, Some(&gateway()), &endpoints)
+            .iter().map(|a| line(a)).collect();
+        // Only the endpoint inside a tunnelled network needs an exception
+        assert_eq!(args, [
+            "203.0.113.0/24 dev wolfnet0 proto 87",
+            "203.0.113.5/32 via 192.168.1.1 dev eth0 proto 87",
+        ]);
+    }
+
+    #[test]
+    fn test_skipped_routes() {
+        let routes = [
+            route("not-a-network", RouteVia::Wolfnet),
+            route("192.168.1.0/24", RouteVia::Local),
+            route("10.50.0.0/16", RouteVia::Wolfnet),
+        ];
+        // Without a local gateway, local routes and endpoint exceptions are impossible
+        let args: Vec<String> = route_args("wolfnet0", &routes, None, &[Ipv4Addr::new(10, 50, 0, 1)])
+            .iter().map(|a| line(a)).collect();
+        assert_eq!(args, ["10.50.0.0/16 dev wolfnet0 proto 87"]);
+    }
+}
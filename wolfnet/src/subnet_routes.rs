@@ -0,0 +1,154 @@
+//! Subnet routes for containers and VMs behind WolfNet nodes
+//!
+//! `[[subnet_routes]]` entries name subnets hosted on a node, such as a
+//! Docker or LXC bridge. Local subnets with `advertise = true` are shared
+//! over peer exchange; entries with `via` point at another node by hand.
+//! Packets for these subnets are forwarded by `PeerManager::find_route`.
+//!
+//! For the kernel to hand those packets to WolfNet in the first place, every
+//! subnet hosted elsewhere gets a route through the TUN interface, tagged
+//! with protocol `SUBNET_ROUTE_PROTO` so it can be listed and removed
+//! without touching anything else.
+
+use std::net::Ipv4Addr;
+
+use tracing::warn;
+
+use crate::config::Config;
+use crate::peer::{RouteSource, SubnetRoute};
+use crate::split_tunnel::parse_cidr;
+
+/// Routing protocol number tagging the subnet routes we install (one above
+/// the split-tunnel routes)
+pub const SUBNET_ROUTE_PROTO: &str = "88";
+
+/// The routes configured in `[[subnet_routes]]`. Invalid entries are skipped
+/// with a warning.
+pub fn configured_routes(config: &Config, my_ip: Ipv4Addr) -> Vec<SubnetRoute> {
+    let mut routes = Vec::new();
+    for (index, entry) in config.subnet_routes.iter().enumerate() {
+        let (network, prefix) = match parse_cidr(&entry.network) {
+            Ok(n) => n,
+            Err(e) => { warn!("Skipping subnet route #{}: {}", index, e); continue; }
+        };
+        let (host, source) = match entry.via.as_deref() {
+            None => (my_ip, RouteSource::Local),
+            Some(via) => match via.parse::<Ipv4Addr>() {
+                Ok(ip) if ip == my_ip => (my_ip, RouteSource::Local),
+                Ok(ip) => (ip, RouteSource::Static),
+                Err(e) => { warn!("Skipping subnet route #{} ({}): invalid via '{}': {}", index, entry.network, via, e); continue; }
+            },
+        };
+        let advertise = entry.advertise && source == RouteSource::Local;
+        if entry.advertise && !advertise {
+            warn!("Subnet route #{} ({}) is hosted by {}, not advertising it", index, entry.network, host);
+        }
+        routes.push(SubnetRoute { network, prefix, host, source, advertise, unreachable: false });
+    }
+    routes
+}
+
+/// Install a TUN route for every subnet hosted on another node and remove the
+/// ones no longer needed. Returns the number of routes installed.
+pub fn sync_subnet_routes(
+    wolfnet_interface: &str,
+    routes: &[SubnetRoute],
+    my_ip: Ipv4Addr,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut wanted: Vec<String> = routes.iter()
+        .filter(|r| r.host != my_ip)
+        .map(SubnetRoute::cidr)
+        .collect();
+    wanted.sort();
+    wanted.dedup();
+
+    for line in list_subnet_routes() {
+        let Some(destination) = line.split_whitespace().next() else { continue };
+        // `ip route` prints host routes without the /32
+        let destination = if destination.contains('/') { destination.to_string() } else { format!("{}/32", destination) };
+        if !wanted.contains(&destination) {
+            let _ = std::process::Command::new("ip")
+                .args(["route", "del", &destination, "proto", SUBNET_ROUTE_PROTO])
+                .status();
+        }
+    }
+
+    let mut installed = 0;
+    for destination in &wanted {
+        let status = std::process::Command::new("ip")
+            .args(["route", "replace", destination, "dev", wolfnet_interface, "proto", SUBNET_ROUTE_PROTO])
+            .status()?;
+        if status.success() {
+            installed += 1;
+        } else {
+            warn!("Failed to add subnet route {} dev {}", destination, wolfnet_interface);
+        }
+    }
+    Ok(installed)
+}
+
+/// List installed subnet routes (in `ip route show` format)
+pub fn list_subnet_routes() -> Vec<String> {
+    let output = match std::process::Command::new("ip")
+        .args(["route", "show", "proto", SUBNET_ROUTE_PROTO])
+        .output()
+    {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Remove every route tagged with `SUBNET_ROUTE_PROTO`
+pub fn flush_subnet_routes() {
+    if list_subnet_routes().is_empty() {
+        return;
+    }
+    let ok = std::process::Command::new("ip")
+        .args(["route", "flush", "proto", SUBNET_ROUTE_PROTO])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false);
+    if !ok {
+        warn!("Failed to remove subnet routes");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SubnetRoute as SubnetRouteConfig;
+
+    #[test]
+    fn test_configured_routes() {
+        let my_ip = Ipv4Addr::new(10, 0, 10, 1);
+        let entry = |network: &str, via: Option<&str>, advertise| {
+            SubnetRouteConfig { network: network.into(), via: via.map(String::from), advertise }
+        };
+        let config = Config {
+            subnet_routes: vec![
+                entry("172.17.0.0/24", None, true),
+                entry("172.18.0.9/24", Some("10.0.10.1"), false),
+                entry("172.19.0.0/24", Some("10.0.10.2"), true),
+                entry("172.20.0.0/33", None, true),
+                entry("172.21.0.0/24", Some("node-b"), false),
+            ],
+            ..Config::default()
+        };
+
+        let routes: Vec<(String, Ipv4Addr, RouteSource, bool)> = configured_routes(&config, my_ip).iter()
+            .map(|r| (r.cidr(), r.host, r.source, r.advertise))
+            .collect();
+        assert_eq!(routes, [
+            ("172.17.0.0/24".to_string(), my_ip, RouteSource::Local, true),
+            // `via` this node is a local subnet
+            ("172.18.0.0/24".to_string(), my_ip, RouteSource::Local, false),
+            // Only this node's own subnets are advertised
+            ("172.19.0.0/24".to_string(), Ipv4Addr::new(10, 0, 10, 2), RouteSource::Static, false),
+        ]);
+    }
+}
@@ -3,13 +3,16 @@
 //! Handles UDP packet framing, handshake protocol, discovery broadcasts,
 //! and peer exchange (PEX) for automatic mesh topology propagation.
 
-use std::net::{UdpSocket, SocketAddr, Ipv4Addr};
-use std::sync::Arc;
-use tracing::warn;
+use std::collections::HashSet;
+use std::net::{UdpSocket, SocketAddr, Ipv4Addr, ToSocketAddrs};
+use std::sync::{mpsc, Arc, atomic::{AtomicBool, Ordering}};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
 use crate::crypto::KeyPair;
-use crate::peer::PeerManager;
+use crate::obfuscation::ObfuscatedSocket;
+use crate::peer::{Peer, PeerManager};
 
 /// Packet types
 pub const PKT_HANDSHAKE: u8 = 0x01;
@@ -17,6 +20,40 @@ pub const PKT_DATA: u8 = 0x03;
 pub const PKT_KEEPALIVE: u8 = 0x04;
 pub const PKT_DISCOVERY: u8 = 0x05;
 pub const PKT_PEER_EXCHANGE: u8 = 0x06;
+pub const PKT_PING: u8 = 0x07;
+pub const PKT_PONG: u8 = 0x08;
+pub const PKT_MTU_PROBE: u8 = 0x09;
+pub const PKT_MTU_ACK: u8 = 0x0A;
+pub const PKT_RESUME: u8 = 0x0B;
+
+/// Bytes a data packet adds around a tunnelled IP packet: type, peer ID and
+/// counter header plus the 16-byte Poly1305 tag
+pub const DATA_PACKET_OVERHEAD: usize = 13 + 16;
+
+/// Resolve an endpoint string to a SocketAddr.
+/// Supports both IP:port (e.g. "203.0.113.5:9600") and hostname:port (e.g. "myhome.dyndns.org:9600").
+pub fn resolve_endpoint(ep: &str) -> Option<SocketAddr> {
+    // Try direct parse first (fastest path for IP:port)
+    if let Ok(addr) = ep.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+    // Fall back to DNS resolution (supports hostnames like myhome.dyndns.org:9600)
+    match ep.to_socket_addrs() {
+        Ok(mut addrs) => {
+            let result = addrs.next();
+            if let Some(_addr) = result {
+
+            } else {
+                warn!("DNS resolution for '{}' returned no addresses", ep);
+            }
+            result
+        }
+        Err(e) => {
+            warn!("Failed to resolve endpoint '{}': {}", ep, e);
+            None
+        }
+    }
+}
 
 /// Discovery port (UDP broadcast)
 pub const DISCOVERY_PORT: u16 = 9601;
@@ -82,52 +119,451 @@ pub fn build_keepalive(peer_id: &[u8; 4]) -> Vec<u8> {
     pkt
 }
 
-/// Send handshakes to all peers that don't have active sessions
-/// When a peer is offline, we try BOTH the last-known endpoint AND the original
-/// configured endpoint (from config.toml), because the last-known endpoint may
-/// be a stale LAN address from discovery that's no longer reachable.
+/// Current wall-clock time in microseconds, used as the ping timestamp
+pub fn now_micros() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+/// Build a latency probe (sent encrypted inside a data packet):
+/// [1: PKT_PING or PKT_PONG] [8: sender timestamp in µs]
+/// A pong echoes the ping's timestamp unchanged so the prober can compute RTT
+/// against its own clock.
+pub fn build_probe(kind: u8, timestamp_us: u64) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(9);
+    pkt.push(kind);
+    pkt.extend_from_slice(&timestamp_us.to_le_bytes());
+    pkt
+}
+
+/// Parse a ping or pong, returns (kind, timestamp_us)
+pub fn parse_probe(data: &[u8]) -> Option<(u8, u64)> {
+    if data.len() != 9 || (data[0] != PKT_PING && data[0] != PKT_PONG) {
+        return None;
+    }
+    Some((data[0], u64::from_le_bytes(data[1..9].try_into().ok()?)))
+}
+
+/// Send an encrypted ping to every directly connected peer
+pub fn send_pings(socket: &ObfuscatedSocket, keypair: &KeyPair, peer_manager: &PeerManager) {
+    let my_id = keypair.my_peer_id();
+    for ip in peer_manager.all_ips() {
+        peer_manager.with_peer_by_ip(&ip, |peer| {
+            if peer.is_connected() && peer.relay_via.is_none() {
+                if let Some(endpoint) = peer.endpoint {
+                    let ping = build_probe(PKT_PING, now_micros());
+                    if let Ok((counter, ciphertext)) = peer.encrypt(&ping) {
+                        let _ = socket.send_to(&build_data_packet(&my_id, counter, &ciphertext), endpoint);
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Ping every directly connected hub, more often than `send_pings`, so that
+/// hub selection notices a dead hub within seconds
+pub fn send_hub_pings(socket: &ObfuscatedSocket, keypair: &KeyPair, peer_manager: &PeerManager) {
+    let my_id = keypair.my_peer_id();
+    for ip in peer_manager.hub_ips() {
+        peer_manager.with_peer_by_ip(&ip, |peer| {
+            // Also ping hubs marked dead, so one that comes back is noticed
+            if let Some(endpoint) = peer.endpoint {
+                let ping = build_probe(PKT_PING, now_micros());
+                if let Ok((counter, ciphertext)) = peer.encrypt(&ping) {
+                    let _ = socket.send_to(&build_data_packet(&my_id, counter, &ciphertext), endpoint);
+                }
+            }
+        });
+    }
+}
+
+/// Set the Don't Fragment bit on everything the socket sends and never
+/// fragment locally, so an oversized packet is dropped (or fails with
+/// EMSGSIZE) instead of squeezing through in pieces and hiding the path MTU
+pub fn set_dont_fragment(socket: &UdpSocket) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    let value: libc::c_int = libc::IP_PMTUDISC_DO;
+    let rc = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            &value as *const _ as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Build a path MTU probe:
+/// [1: PKT_MTU_PROBE] [4: sender peer_id] [2: size] [padding]
+/// The probe is as long as a data packet carrying a `size`-byte IP packet
+/// (plus the most padding obfuscation can add), so if it arrives, tunnelled
+/// packets up to `size` bytes fit the path. Probes are sent unencrypted so
+/// dropped ones don't show up as gaps in the session counter (packet loss).
+pub fn build_mtu_probe(peer_id: &[u8; 4], size: u16) -> Vec<u8> {
+    let len = (size as usize + DATA_PACKET_OVERHEAD + crate::obfuscation::MAX_PADDING).max(7);
+    let mut pkt = Vec::with_capacity(len);
+    pkt.push(PKT_MTU_PROBE);
+    pkt.extend_from_slice(peer_id);
+    pkt.extend_from_slice(&size.to_le_bytes());
+    pkt.resize(len, 0);
+    pkt
+}
+
+/// Parse a path MTU probe, returns (peer_id, size)
+pub fn parse_mtu_probe(data: &[u8]) -> Option<([u8; 4], u16)> {
+    if data.len() < 7 || data[0] != PKT_MTU_PROBE {
+        return None;
+    }
+    let size = u16::from_le_bytes([data[5], data[6]]);
+    // Only a probe that arrived whole proves anything about the path
+    if data.len() != build_mtu_probe(&[0; 4], size).len() {
+        return None;
+    }
+    Some((data[1..5].try_into().ok()?, size))
+}
+
+/// Build the answer to an MTU probe (sent encrypted inside a data packet):
+/// [1: PKT_MTU_ACK] [2: size]
+pub fn build_mtu_ack(size: u16) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(3);
+    pkt.push(PKT_MTU_ACK);
+    pkt.extend_from_slice(&size.to_le_bytes());
+    pkt
+}
+
+/// Parse an MTU ack, returns the probe size it confirms
+pub fn parse_mtu_ack(data: &[u8]) -> Option<u16> {
+    if data.len() != 3 || data[0] != PKT_MTU_ACK {
+        return None;
+    }
+    Some(u16::from_le_bytes([data[1], data[2]]))
+}
+
+/// Start a path MTU probe round with every directly connected peer that is due one
+pub fn send_mtu_probes(socket: &ObfuscatedSocket, keypair: &KeyPair, peer_manager: &PeerManager) {
+    let my_id = keypair.my_peer_id();
+    for ip in peer_manager.all_ips() {
+        peer_manager.with_peer_by_ip(&ip, |peer| {
+            if !peer.is_connected() || peer.relay_via.is_some() {
+                return;
+            }
+            let Some(endpoint) = peer.endpoint else { return };
+            for size in peer.start_mtu_probe() {
+                // EMSGSIZE for sizes over the local interface MTU: same as a lost probe
+                let _ = socket.send_to(&build_mtu_probe(&my_id, size), endpoint);
+            }
+        });
+    }
+}
+
+/// Longest wait for the handshake sends to one peer
+pub const HANDSHAKE_SEND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Interval between handshakes to peers that haven't answered while waiting for them
+const HANDSHAKE_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Peers a round of handshakes went out to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HandshakeReport {
+    /// Peers at least one handshake was sent to
+    pub sent: usize,
+    /// Peers every send failed (or timed out) for
+    pub failed: usize,
+}
+
+/// A resume handshake, asking a peer to carry on an existing session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeHandshake {
+    pub public_key: x25519_dalek::PublicKey,
+    pub wolfnet_ip: Ipv4Addr,
+    pub session_id: [u8; 16],
+    /// Public endpoint the sender believes it now has (None if unknown)
+    pub endpoint: Option<SocketAddr>,
+    /// Sent in answer to a resume handshake, so not to be answered again
+    pub is_reply: bool,
+    /// When it was sent, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+}
+
+/// A resume handshake sent longer ago than this (by the receiver's clock) is
+/// not trusted to move the session; the sender gets a full handshake instead
+const RESUME_MAX_AGE: Duration = Duration::from_secs(60);
+
+fn unix_time_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Build a resume handshake:
+/// [1: type] [1: flags, bit 0 = reply] [32: public_key] [4: wolfnet_ip] [16: session_id] [4: endpoint ip] [2: endpoint port]
+/// [8: timestamp_ms] [64: signature over everything before it]
+///
+/// The signature and timestamp stop a captured resume from being replayed
+/// to move the session to another address. A key on a PKCS#11 token can't
+/// sign, so its resumes are refused and peers answer with a full handshake.
+pub fn build_resume(keypair: &KeyPair, wolfnet_ip: Ipv4Addr, session_id: &[u8; 16], endpoint: Option<SocketAddr>, is_reply: bool) -> Vec<u8> {
+    build_resume_at(keypair, wolfnet_ip, session_id, endpoint, is_reply, unix_time_ms())
+}
+
+fn build_resume_at(
+    keypair: &KeyPair,
+    wolfnet_ip: Ipv4Addr,
+    session_id: &[u8; 16],
+    endpoint: Option<SocketAddr>,
+    is_reply: bool,
+    timestamp_ms: u64,
+) -> Vec<u8> {
+    let (ip, port) = match endpoint {
+        Some(SocketAddr::V4(addr)) => (*addr.ip(), addr.port()),
+        _ => (Ipv4Addr::UNSPECIFIED, 0),
+    };
+    let mut pkt = Vec::with_capacity(132);
+    pkt.push(PKT_RESUME);
+    pkt.push(u8::from(is_reply));
+    pkt.extend_from_slice(keypair.public.as_bytes());
+    pkt.extend_from_slice(&wolfnet_ip.octets());
+    pkt.extend_from_slice(session_id);
+    pkt.extend_from_slice(&ip.octets());
+    pkt.extend_from_slice(&port.to_le_bytes());
+    pkt.extend_from_slice(&timestamp_ms.to_le_bytes());
+    pkt.extend_from_slice(&keypair.sign(&pkt).unwrap_or([0; 64]));
+    pkt
+}
+
+/// Parse a resume handshake. Resumes not signed by the key they carry are
+/// discarded.
+pub fn parse_resume(data: &[u8]) -> Option<ResumeHandshake> {
+    if data.len() < 132 || data[0] != PKT_RESUME {
+        return None;
+    }
+    let key_bytes: [u8; 32] = data[2..34].try_into().ok()?;
+    let public_key = x25519_dalek::PublicKey::from(key_bytes);
+    let signature: [u8; 64] = data[68..132].try_into().ok()?;
+    if !crate::crypto::verify_signature(&public_key, &data[..68], &signature) {
+        debug!("Resume handshake with an invalid signature, discarding");
+        return None;
+    }
+    let session_id: [u8; 16] = data[38..54].try_into().ok()?;
+    let endpoint_ip = Ipv4Addr::new(data[54], data[55], data[56], data[57]);
+    let endpoint_port = u16::from_le_bytes([data[58], data[59]]);
+    Some(ResumeHandshake {
+        public_key,
+        wolfnet_ip: Ipv4Addr::new(data[34], data[35], data[36], data[37]),
+        session_id,
+        endpoint: (!endpoint_ip.is_unspecified() && endpoint_port != 0)
+            .then(|| SocketAddr::from((endpoint_ip, endpoint_port))),
+        is_reply: data[1] & 1 != 0,
+        timestamp_ms: u64::from_le_bytes(data[60..68].try_into().ok()?),
+    })
+}
+
+/// Where to send handshakes (full or resume) to a peer: the last-known endpoint
+/// and, if it differs, the endpoint configured in config.toml, because the
+/// last-known endpoint may be a stale LAN address from discovery that's no
+/// longer reachable
+fn handshake_targets(peer: &Peer) -> Vec<SocketAddr> {
+    let mut targets: Vec<SocketAddr> = peer.endpoint.into_iter().collect();
+    if let Some(configured_addr) = peer.configured_endpoint.as_deref().and_then(|ep| ep.parse::<SocketAddr>().ok()) {
+        if peer.endpoint != Some(configured_addr) {
+            targets.push(configured_addr);
+        }
+    }
+    targets
+}
+
+/// Send handshakes to peers without an active session whose reconnect backoff has elapsed.
+/// Each peer gets its own thread, so one slow send doesn't hold up the rest;
+/// sends still pending after `HANDSHAKE_SEND_TIMEOUT` count as failed.
 pub fn send_handshakes(
-    socket: &UdpSocket,
+    socket: &Arc<ObfuscatedSocket>,
     keypair: &KeyPair,
     peer_manager: &PeerManager,
     wolfnet_ip: Ipv4Addr,
     listen_port: u16,
     hostname: &str,
     is_gateway: bool,
-) {
-    let handshake = build_handshake(keypair, wolfnet_ip, listen_port, hostname, is_gateway);
-    for ip in peer_manager.all_ips() {
-        peer_manager.with_peer_by_ip(&ip, |peer| {
-            if !peer.is_connected() {
-                // Try last-known endpoint (may be a LAN address from discovery)
-                if let Some(endpoint) = peer.endpoint {
+) -> HandshakeReport {
+    // Dead peers are retried with exponential backoff (5s doubling to 300s)
+    let due: Vec<Vec<SocketAddr>> = peer_manager.all_ips().iter()
+        .filter_map(|ip| peer_manager.with_peer_by_ip(ip, |peer| {
+            (!peer.revoked && peer.reconnect_due()).then(|| {
+                peer.record_reconnect_attempt();
+                handshake_targets(peer)
+            })
+        }).flatten())
+        .filter(|targets| !targets.is_empty())
+        .collect();
+    if due.is_empty() {
+        return HandshakeReport::default();
+    }
 
-                    if let Err(_e) = socket.send_to(&handshake, endpoint) {
+    let handshake = Arc::new(build_handshake(keypair, wolfnet_ip, listen_port, hostname, is_gateway));
+    let (tx, rx) = mpsc::channel();
+    let peers = due.len();
+    for targets in due {
+        let socket = socket.clone();
+        let handshake = handshake.clone();
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            let sent = targets.iter().filter(|addr| socket.send_to(&handshake, *addr).is_ok()).count();
+            let _ = tx.send(sent > 0);
+        });
+    }
+    drop(tx);
 
-                    }
-                }
+    let deadline = Instant::now() + HANDSHAKE_SEND_TIMEOUT;
+    let mut sent = 0;
+    while let Ok(ok) = rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        sent += usize::from(ok);
+    }
+    let report = HandshakeReport { sent, failed: peers - sent };
+    debug!("Handshakes sent to {} peer(s), {} failed", report.sent, report.failed);
+    report
+}
 
-                // Also try the configured endpoint from config.toml if it differs
-                // from the current endpoint. This ensures we reach the peer via their
-                // public IP even when discovery has overwritten the endpoint with a
-                // stale LAN address.
-                if let Some(ref configured_ep) = peer.configured_endpoint {
-                    if let Ok(configured_addr) = configured_ep.parse::<SocketAddr>() {
-                        if peer.endpoint != Some(configured_addr) {
+/// Set up the session with the peer a handshake packet came from, returning its WolfNet IP
+pub fn accept_handshake(data: &[u8], src: SocketAddr, keypair: &KeyPair, peer_manager: &PeerManager) -> Option<Ipv4Addr> {
+    let (pub_key, peer_ip, _peer_port, is_gw, peer_hostname) = parse_handshake(data)?;
+    if peer_manager.is_revoked(&pub_key) {
+        tracing::warn!("Ignoring handshake from revoked peer {} ({})", peer_ip, src);
+        return None;
+    }
+    // Use the actual UDP source address — NOT the advertised port.
+    // Over NAT, the source port differs from listen_port.
+    peer_manager.update_from_discovery(&pub_key, src, peer_ip, &peer_hostname, is_gw);
+    peer_manager.with_peer_by_ip(&peer_ip, |peer| {
+        // Always re-establish session on handshake.
+        // A handshake means the peer is (re)connecting — their send
+        // counter resets to 0, so we must reset our recv counter too.
+        // Without this, a peer restart causes all their new packets
+        // to be rejected as "replay" because the old recv_counter
+        // is higher than their new send counter.
+        peer.establish_session(keypair);
+        peer.mark_alive();
+    });
+    Some(peer_ip)
+}
 
-                            if let Err(_e) = socket.send_to(&handshake, configured_addr) {
+/// Resume the session a resume handshake names. Returns the handshake and
+/// whether it was resumed; a peer we have no matching session with, or whose
+/// resume is stale or replayed, gets `false` and should be sent a full
+/// handshake instead.
+pub fn accept_resume(data: &[u8], src: SocketAddr, peer_manager: &PeerManager) -> Option<(ResumeHandshake, bool)> {
+    let resume = parse_resume(data)?;
+    if peer_manager.is_revoked(&resume.public_key) {
+        tracing::warn!("Ignoring resume from revoked peer {} ({})", resume.wolfnet_ip, src);
+        return None;
+    }
+    let known = peer_manager.with_peer_by_ip(&resume.wolfnet_ip, |peer| {
+        peer.public_key == resume.public_key && peer.cipher.is_some() && peer.session_id == Some(resume.session_id)
+    }).unwrap_or(false);
+    if !known {
+        debug!("No session to resume with {} ({})", resume.wolfnet_ip, src);
+        return Some((resume, false));
+    }
+    let age_ms = unix_time_ms().abs_diff(resume.timestamp_ms);
+    let replayed = peer_manager.with_peer_by_ip(&resume.wolfnet_ip, |peer| {
+        peer.last_resume_ms >= Some(resume.timestamp_ms)
+    }).unwrap_or(true);
+    if replayed || age_ms > RESUME_MAX_AGE.as_millis() as u64 {
+        warn!("Ignoring stale or replayed resume from {} ({})", resume.wolfnet_ip, src);
+        return Some((resume, false));
+    }
 
-                            }
-                        }
+    // As with handshakes, the UDP source address is what reaches the peer;
+    // the endpoint it advertises may be behind NAT
+    peer_manager.update_endpoint(&resume.wolfnet_ip, src);
+    peer_manager.with_peer_by_ip(&resume.wolfnet_ip, |peer| peer.resume_session(resume.timestamp_ms));
+    match resume.endpoint {
+        Some(advertised) if advertised != src => {
+            debug!("Resumed session with {} at {} (advertised {})", resume.wolfnet_ip, src, advertised)
+        }
+        _ => debug!("Resumed session with {} at {}", resume.wolfnet_ip, src),
+    }
+    Some((resume, true))
+}
+
+/// After our address changed, ask every peer with a session to resume it
+/// from our new endpoint: the address the route to the peer now leaves from,
+/// with `listen_port`. Peers without a session are left to `send_handshakes`.
+/// Returns the number of peers a resume handshake was sent to.
+pub fn send_resume_handshakes(
+    socket: &ObfuscatedSocket,
+    keypair: &KeyPair,
+    peer_manager: &PeerManager,
+    wolfnet_ip: Ipv4Addr,
+    listen_port: u16,
+) -> usize {
+    let mut sent = 0;
+    for ip in peer_manager.all_ips() {
+        let due = peer_manager.with_peer_by_ip(&ip, |peer| {
+            if peer.revoked || peer.cipher.is_none() {
+                return None;
+            }
+            peer.session_id.map(|id| (id, handshake_targets(peer)))
+        }).flatten();
+        if let Some((session_id, targets)) = due {
+            let endpoint = targets.first()
+                .and_then(|target| crate::mobility::outbound_address(*target))
+                .map(|ip| SocketAddr::new(ip, listen_port));
+            let resume = build_resume(keypair, wolfnet_ip, &session_id, endpoint, false);
+            if targets.iter().filter(|addr| socket.send_to(&resume, **addr).is_ok()).count() > 0 {
+                sent += 1;
+            }
+        }
+    }
+    debug!("Resume handshakes sent to {} peer(s)", sent);
+    sent
+}
+
+/// Answer handshakes until `count` peers have sent one, resending `handshake`
+/// every second to the peers still silent. Other packets are dropped. Returns
+/// the number of peers heard from, which is less than `count` only if
+/// `running` was cleared.
+pub fn wait_for_peers(
+    socket: &ObfuscatedSocket,
+    keypair: &KeyPair,
+    peer_manager: &PeerManager,
+    handshake: &[u8],
+    count: usize,
+    running: &AtomicBool,
+) -> usize {
+    let mut answered = HashSet::new();
+    let mut last_retry = Instant::now();
+    let mut buf = [0u8; 65536];
+    while answered.len() < count && running.load(Ordering::Relaxed) {
+        if let Ok((n, src)) = socket.recv_from(&mut buf) {
+            if n > 0 && buf[0] == PKT_HANDSHAKE {
+                if let Some(peer_ip) = accept_handshake(&buf[..n], src, keypair, peer_manager) {
+                    let _ = socket.send_to(handshake, src);
+                    if answered.insert(peer_ip) {
+                        debug!("Peer {} answered ({}/{})", peer_ip, answered.len(), count);
                     }
                 }
             }
-        });
+        }
+
+        if last_retry.elapsed() >= HANDSHAKE_RETRY_INTERVAL {
+            for ip in peer_manager.all_ips().iter().filter(|ip| !answered.contains(*ip)) {
+                for addr in peer_manager.with_peer_by_ip(ip, |peer| handshake_targets(peer)).unwrap_or_default() {
+                    let _ = socket.send_to(handshake, addr);
+                }
+            }
+            last_retry = Instant::now();
+        }
     }
+    answered.len()
 }
 
 /// Send keepalives to all connected peers
-pub fn send_keepalives(socket: &UdpSocket, keypair: &KeyPair, peer_manager: &PeerManager) {
+pub fn send_keepalives(socket: &ObfuscatedSocket, keypair: &KeyPair, peer_manager: &PeerManager) {
     let my_id = keypair.my_peer_id();
     let keepalive = build_keepalive(&my_id);
     for ip in peer_manager.all_ips() {
@@ -189,36 +625,96 @@ pub struct PexEntry {
     pub hostname: String,
     /// Whether this peer is a gateway
     pub is_gateway: bool,
+    /// Whether this peer is a hub
+    #[serde(default)]
+    pub is_hub: bool,
+    /// Sender's smoothed RTT to this peer in µs, if it reaches it directly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_us: Option<u64>,
+    /// Subnets (CIDR) routed through this peer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subnets: Vec<String>,
+}
+
+/// A signed peer exchange message
+pub struct PexMessage {
+    /// Shared peer entries
+    pub entries: Vec<PexEntry>,
+    /// XEdDSA signature over the sender's X25519 public key and the entries
+    pub signature: [u8; 64],
+}
+
+/// Bytes covered by a PEX signature. Including the sender's X25519 key binds
+/// the peer list to the session it was sent over.
+fn pex_signed_bytes(sender: &x25519_dalek::PublicKey, entries_json: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(32 + entries_json.len());
+    msg.extend_from_slice(sender.as_bytes());
+    msg.extend_from_slice(entries_json);
+    msg
 }
 
 /// Build a peer exchange packet:
-/// [1: type] [N: JSON array of PexEntry]
-pub fn build_peer_exchange(my_ip: Ipv4Addr, peer_manager: &PeerManager) -> Vec<u8> {
-    let entries = peer_manager.get_pex_entries(my_ip);
+/// [1: type] [64: signature] [N: JSON array of PexEntry]
+///
+/// Subnets this node advertises go in an entry for the sender itself, which
+/// receivers already know and only read the subnets from. A node whose key is
+/// on a PKCS#11 token cannot sign, so it gets just the type byte back and
+/// sends nothing.
+pub fn build_peer_exchange(keypair: &KeyPair, my_ip: Ipv4Addr, peer_manager: &PeerManager) -> Vec<u8> {
+    let mut entries = peer_manager.get_pex_entries(my_ip);
+    let subnets = peer_manager.advertised_subnets(my_ip);
+    if !subnets.is_empty() {
+        entries.push(PexEntry {
+            public_key: keypair.public_key_base64(),
+            wolfnet_ip: my_ip.to_string(),
+            endpoint: None,
+            hostname: String::new(),
+            is_gateway: false,
+            is_hub: false,
+            rtt_us: None,
+            subnets,
+        });
+    }
     let mut pkt = Vec::new();
     pkt.push(PKT_PEER_EXCHANGE);
     if let Ok(json) = serde_json::to_vec(&entries) {
-        pkt.extend_from_slice(&json);
+        if let Some(signature) = keypair.sign(&pex_signed_bytes(&keypair.public, &json)) {
+            pkt.extend_from_slice(&signature);
+            pkt.extend_from_slice(&json);
+        }
     }
     pkt
 }
 
-/// Parse a peer exchange packet
-pub fn parse_peer_exchange(data: &[u8]) -> Option<Vec<PexEntry>> {
-    if data.is_empty() || data[0] != PKT_PEER_EXCHANGE {
+/// Parse and verify a peer exchange packet from `sender`, the configured
+/// public key of the peer whose session it arrived on. The signature must
+/// verify against that key. Unsigned, badly-signed or malformed messages
+/// return None.
+pub fn parse_peer_exchange(data: &[u8], sender: &x25519_dalek::PublicKey) -> Option<PexMessage> {
+    if data.len() < 1 + 64 || data[0] != PKT_PEER_EXCHANGE {
+        return None;
+    }
+    let mut signature = [0u8; 64];
+    signature.copy_from_slice(&data[1..65]);
+    let json = &data[65..];
+
+    if !crate::crypto::verify_signature(sender, &pex_signed_bytes(sender, json), &signature) {
+        warn!("PEX from {} has an invalid signature, discarding", BASE64.encode(sender.as_bytes()));
         return None;
     }
-    serde_json::from_slice(&data[1..]).ok()
+
+    let entries = serde_json::from_slice(json).ok()?;
+    Some(PexMessage { entries, signature })
 }
 
 /// Send peer exchange to all connected peers
 pub fn send_peer_exchange(
-    socket: &UdpSocket,
+    socket: &ObfuscatedSocket,
     keypair: &KeyPair,
     peer_manager: &PeerManager,
     my_ip: Ipv4Addr,
 ) {
-    let pex_packet = build_peer_exchange(my_ip, peer_manager);
+    let pex_packet = build_peer_exchange(keypair, my_ip, peer_manager);
     if pex_packet.len() <= 1 {
 
         return;
@@ -311,3 +807,356 @@ pub fn run_discovery_listener(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pex_packet(keypair: &KeyPair) -> Vec<u8> {
+        let peers = PeerManager::new();
+        peers.add_peer(crate::peer::Peer::new(KeyPair::generate().public, Ipv4Addr::new(10, 0, 10, 3)));
+        build_peer_exchange(keypair, Ipv4Addr::new(10, 0, 10, 1), &peers)
+    }
+
+    #[test]
+    fn test_probe_round_trip() {
+        let ping = build_probe(PKT_PING, 1_234_567);
+        assert_eq!(ping.len(), 9);
+        assert_eq!(parse_probe(&ping), Some((PKT_PING, 1_234_567)));
+        // A pong echoes the ping's timestamp
+        assert_eq!(parse_probe(&build_probe(PKT_PONG, 1_234_567)), Some((PKT_PONG, 1_234_567)));
+
+        assert_eq!(parse_probe(&ping[..8]), None);
+        assert_eq!(parse_probe(&build_probe(PKT_KEEPALIVE, 1)), None);
+    }
+
+    #[test]
+    fn test_mtu_probe_round_trip() {
+        let probe = build_mtu_probe(&[1, 2, 3, 4], 1400);
+        // As long as a data packet carrying 1400 bytes, plus obfuscation padding
+        assert_eq!(probe.len(), 1400 + DATA_PACKET_OVERHEAD + crate::obfuscation::MAX_PADDING);
+        assert_eq!(parse_mtu_probe(&probe), Some(([1, 2, 3, 4], 1400)));
+
+        // A probe cut short says nothing about the path
+        assert_eq!(parse_mtu_probe(&probe[..probe.len() - 1]), None);
+        assert_eq!(parse_mtu_probe(&probe[..6]), None);
+
+        assert_eq!(parse_mtu_ack(&build_mtu_ack(1400)), Some(1400));
+        assert_eq!(parse_mtu_ack(&build_mtu_ack(1400)[..2]), None);
+        assert_eq!(parse_mtu_ack(&[PKT_MTU_PROBE, 0x78, 0x05]), None);
+    }
+
+    #[test]
+    fn test_peer_exchange_verifies_against_sender_key() {
+        let sender = KeyPair::generate();
+        let message = parse_peer_exchange(&pex_packet(&sender), &sender.public).unwrap();
+        assert_eq!(message.entries.len(), 1);
+        assert_eq!(message.entries[0].wolfnet_ip, "10.0.10.3");
+    }
+
+    #[test]
+    fn test_peer_exchange_advertises_local_subnets() {
+        let sender = KeyPair::generate();
+        let my_ip = Ipv4Addr::new(10, 0, 10, 1);
+        let peers = PeerManager::new();
+        let local = |network: [u8; 4], advertise| crate::peer::SubnetRoute {
+            network: Ipv4Addr::from(network), prefix: 24, host: my_ip,
+            source: crate::peer::RouteSource::Local, advertise, unreachable: false,
+        };
+        peers.set_configured_routes(&[local([172, 17, 0, 0], false)]);
+        let message = parse_peer_exchange(&build_peer_exchange(&sender, my_ip, &peers), &sender.public).unwrap();
+        assert!(message.entries.is_empty(), "nothing to advertise");
+
+        peers.set_configured_routes(&[local([172, 17, 0, 0], false), local([172, 18, 0, 0], true)]);
+        let message = parse_peer_exchange(&build_peer_exchange(&sender, my_ip, &peers), &sender.public).unwrap();
+        assert_eq!(message.entries.len(), 1);
+        assert_eq!(message.entries[0].wolfnet_ip, "10.0.10.1");
+        assert_eq!(message.entries[0].public_key, sender.public_key_base64());
+        assert_eq!(message.entries[0].subnets, ["172.18.0.0/24"]);
+    }
+
+    #[test]
+    fn test_tampered_peer_exchange_is_rejected() {
+        let sender = KeyPair::generate();
+        let mut pkt = pex_packet(&sender);
+        let at = pkt.windows(9).position(|w| w == b"10.0.10.3").unwrap();
+        pkt[at + 8] = b'9';
+        assert!(parse_peer_exchange(&pkt, &sender.public).is_none());
+
+        let mut pkt = pex_packet(&sender);
+        pkt[1] ^= 1;
+        assert!(parse_peer_exchange(&pkt, &sender.public).is_none());
+    }
+
+    #[test]
+    fn test_peer_exchange_signed_by_other_key_is_rejected() {
+        let sender = KeyPair::generate();
+        let attacker = KeyPair::generate();
+        assert!(parse_peer_exchange(&pex_packet(&attacker), &sender.public).is_none());
+    }
+
+    /// A node listening on localhost, polling like the daemon does
+    fn node() -> (KeyPair, Arc<ObfuscatedSocket>, PeerManager) {
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        udp.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+        (KeyPair::generate(), Arc::new(ObfuscatedSocket::new(udp, None)), PeerManager::new())
+    }
+
+    fn configured_peer(public_key: x25519_dalek::PublicKey, ip: Ipv4Addr, endpoint: SocketAddr) -> Peer {
+        let mut peer = Peer::new(public_key, ip);
+        peer.endpoint = Some(endpoint);
+        peer.configured_endpoint = Some(endpoint.to_string());
+        peer
+    }
+
+    #[test]
+    fn test_five_peer_network_connects() {
+        let nodes: Vec<_> = (0..5).map(|_| node()).collect();
+        let ip = |i: usize| Ipv4Addr::new(10, 0, 10, i as u8 + 1);
+        for (i, (_, _, peers)) in nodes.iter().enumerate() {
+            for (j, (keypair, socket, _)) in nodes.iter().enumerate().filter(|(j, _)| *j != i) {
+                peers.add_peer(configured_peer(keypair.public, ip(j), socket.local_addr().unwrap()));
+            }
+        }
+
+        let started = Instant::now();
+        let running = AtomicBool::new(true);
+        let answered: Vec<usize> = std::thread::scope(|scope| {
+            let handles: Vec<_> = nodes.iter().enumerate().map(|(i, (keypair, socket, peers))| {
+                let running = &running;
+                scope.spawn(move || {
+                    let report = send_handshakes(socket, keypair, peers, ip(i), 9600, "node", false);
+                    assert_eq!(report, HandshakeReport { sent: 4, failed: 0 });
+                    let handshake = build_handshake(keypair, ip(i), 9600, "node", false);
+                    wait_for_peers(socket, keypair, peers, &handshake, 4, running)
+                })
+            }).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let elapsed = started.elapsed();
+
+        assert_eq!(answered, [4; 5]);
+        for (_, _, peers) in &nodes {
+            assert!(peers.status().iter().all(|p| p.connected));
+        }
+        assert!(elapsed < Duration::from_millis(200), "took {:?}", elapsed);
+    }
+
+    #[test]
+    fn test_send_handshakes_report() {
+        let (keypair, socket, peers) = node();
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ip = |last| Ipv4Addr::new(10, 0, 10, last);
+        peers.add_peer(configured_peer(KeyPair::generate().public, ip(2), listener.local_addr().unwrap()));
+        // An IPv6 endpoint can't be reached from an IPv4 socket
+        peers.add_peer(configured_peer(KeyPair::generate().public, ip(3), "[::1]:9600".parse().unwrap()));
+        // No endpoint: the peer has to contact us
+        peers.add_peer(Peer::new(KeyPair::generate().public, ip(4)));
+
+        let report = send_handshakes(&socket, &keypair, &peers, ip(1), 9600, "node", false);
+        assert_eq!(report, HandshakeReport { sent: 1, failed: 1 });
+        let mut buf = [0u8; 512];
+        let (n, _) = listener.recv_from(&mut buf).unwrap();
+        assert_eq!(parse_handshake(&buf[..n]).map(|h| h.1), Some(ip(1)));
+
+        // Nothing is due again until the reconnect backoff runs out
+        assert_eq!(send_handshakes(&socket, &keypair, &peers, ip(1), 9600, "node", false), HandshakeReport::default());
+    }
+
+    #[test]
+    fn test_wait_for_peers_stops_when_shut_down() {
+        let (keypair, socket, peers) = node();
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        silent.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        peers.add_peer(configured_peer(KeyPair::generate().public, Ipv4Addr::new(10, 0, 10, 2), silent.local_addr().unwrap()));
+        let handshake = build_handshake(&keypair, Ipv4Addr::new(10, 0, 10, 1), 9600, "node", false);
+
+        let running = AtomicBool::new(true);
+        let started = Instant::now();
+        let answered = std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| wait_for_peers(&socket, &keypair, &peers, &handshake, 1, &running));
+            // The silent peer is asked again after a second
+            let mut buf = [0u8; 512];
+            let (n, _) = silent.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..n], handshake);
+            assert!(started.elapsed() >= HANDSHAKE_RETRY_INTERVAL);
+            running.store(false, Ordering::Relaxed);
+            waiter.join().unwrap()
+        });
+        assert_eq!(answered, 0);
+        assert!(started.elapsed() < HANDSHAKE_RETRY_INTERVAL * 2, "returns soon after shutdown");
+    }
+
+    #[test]
+    fn test_wait_for_peers_counts_each_peer_once() {
+        let (keypair, socket, peers) = node();
+        let (other, other_socket, _) = node();
+        let handshake = build_handshake(&keypair, Ipv4Addr::new(10, 0, 10, 1), 9600, "node", false);
+        let running = AtomicBool::new(true);
+
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| wait_for_peers(&socket, &keypair, &peers, &handshake, 2, &running));
+            let theirs = build_handshake(&other, Ipv4Addr::new(10, 0, 10, 2), 9600, "other", false);
+            // A repeated handshake from the same peer, and a packet that isn't one
+            for packet in [&theirs[..], &theirs[..], &[PKT_KEEPALIVE, 0, 0, 0, 0][..]] {
+                other_socket.send_to(packet, socket.local_addr().unwrap()).unwrap();
+            }
+            // Each handshake is answered
+            let mut buf = [0u8; 512];
+            for _ in 0..2 {
+                let (n, _) = other_socket.recv_from(&mut buf).unwrap();
+                assert_eq!(&buf[..n], handshake);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!waiter.is_finished(), "one peer is not two");
+            running.store(false, Ordering::Relaxed);
+            assert_eq!(waiter.join().unwrap(), 1);
+        });
+        assert!(peers.with_peer_by_ip(&Ipv4Addr::new(10, 0, 10, 2), |p| p.is_connected()).unwrap());
+    }
+
+    #[test]
+    fn test_revoked_peer_handshake_ignored() {
+        let keypair = KeyPair::generate();
+        let revoked = KeyPair::generate();
+        let peers = PeerManager::new();
+        let ip = Ipv4Addr::new(10, 0, 10, 2);
+        let src = SocketAddr::from(([192, 0, 2, 2], 9600));
+        peers.set_revoked_keys([*revoked.public.as_bytes()].into());
+
+        let handshake = build_handshake(&revoked, ip, 9600, "revoked", false);
+        assert_eq!(accept_handshake(&handshake, src, &keypair, &peers), None);
+        assert!(peers.all_ips().is_empty(), "not learned from its handshake");
+        let resume = build_resume(&revoked, ip, &[7; 16], None, false);
+        assert_eq!(accept_resume(&resume, src, &peers), None);
+
+        peers.set_revoked_keys(Default::default());
+        assert_eq!(accept_handshake(&handshake, src, &keypair, &peers), Some(ip));
+        assert!(peers.with_peer_by_ip(&ip, |p| p.is_connected()).unwrap());
+    }
+
+    #[test]
+    fn test_resume_round_trip() {
+        let keypair = KeyPair::generate();
+        let ip = Ipv4Addr::new(10, 0, 10, 2);
+        let endpoint = SocketAddr::from(([198, 51, 100, 7], 9600));
+        let resume = parse_resume(&build_resume_at(&keypair, ip, &[7; 16], Some(endpoint), false, 1_234)).unwrap();
+        assert_eq!(resume, ResumeHandshake {
+            public_key: keypair.public, wolfnet_ip: ip, session_id: [7; 16],
+            endpoint: Some(endpoint), is_reply: false, timestamp_ms: 1_234,
+        });
+        let reply = parse_resume(&build_resume(&keypair, ip, &[7; 16], None, true)).unwrap();
+        assert!(reply.is_reply && reply.endpoint.is_none());
+        assert!(unix_time_ms() - reply.timestamp_ms < 1_000);
+
+        let pkt = build_resume(&keypair, ip, &[7; 16], Some(endpoint), false);
+        assert_eq!(parse_resume(&pkt[..pkt.len() - 1]), None);
+        // Pointing the session at another endpoint breaks the signature
+        let mut moved = pkt.clone();
+        moved[57] ^= 1;
+        assert_eq!(parse_resume(&moved), None);
+        // As does claiming another node's key
+        let mut forged = build_resume(&KeyPair::generate(), ip, &[7; 16], None, false);
+        forged[2..34].copy_from_slice(keypair.public.as_bytes());
+        assert_eq!(parse_resume(&forged), None);
+    }
+
+    /// `a`'s peer manager with a session to `b`, reached at `endpoint`
+    fn resumable(a: &KeyPair, b: &KeyPair, b_ip: Ipv4Addr, endpoint: SocketAddr) -> PeerManager {
+        let peers = PeerManager::new();
+        let mut peer = configured_peer(b.public, b_ip, endpoint);
+        peer.establish_session(a);
+        peer.mark_alive();
+        peers.add_peer(peer);
+        peers
+    }
+
+    #[test]
+    fn test_session_resumes_after_address_change() {
+        let (kp1, sock1, _) = node();
+        let (kp2, sock2, _) = node();
+        let (ip1, ip2) = (Ipv4Addr::new(10, 0, 10, 1), Ipv4Addr::new(10, 0, 10, 2));
+        let peers1 = resumable(&kp1, &kp2, ip2, sock2.local_addr().unwrap());
+        let peers2 = resumable(&kp2, &kp1, ip1, sock1.local_addr().unwrap());
+        let mut buf = [0u8; 2048];
+        let send = |from: &PeerManager, to_ip: Ipv4Addr, payload: &[u8]| {
+            from.with_peer_by_ip(&to_ip, |p| p.encrypt(payload).unwrap()).unwrap()
+        };
+        // Traffic before the move, so the resumed session's counters are past zero
+        for _ in 0..3 {
+            send(&peers2, ip1, b"before");
+        }
+        let (counter, ciphertext) = send(&peers2, ip1, b"before");
+        peers1.with_peer_by_ip(&ip2, |p| p.decrypt(counter, &ciphertext).unwrap());
+
+        // Node 2 moves to a new address
+        let started = Instant::now();
+        let (_, moved, _) = node();
+        assert_eq!(send_resume_handshakes(&moved, &kp2, &peers2, ip2, 9600), 1);
+        let (n, src) = sock1.recv_from(&mut buf).unwrap();
+        assert_eq!(src, moved.local_addr().unwrap());
+        let (resume, resumed) = accept_resume(&buf[..n], src, &peers1).unwrap();
+        assert!(resumed);
+        assert_eq!(resume.wolfnet_ip, ip2);
+        assert_eq!(resume.endpoint.map(|e| e.port()), Some(9600));
+        assert_eq!(peers1.find_ip_by_endpoint(&src), Some(ip2));
+        assert_eq!(peers1.find_ip_by_endpoint(&sock2.local_addr().unwrap()), None);
+
+        // Node 1 answers at the new address, and node 2 takes the reply
+        let reply = build_resume(&kp1, ip1, &resume.session_id, None, true);
+        let target = peers1.with_peer_by_ip(&ip2, |p| p.endpoint.unwrap()).unwrap();
+        sock1.send_to(&reply, target).unwrap();
+        let (n, src) = moved.recv_from(&mut buf).unwrap();
+        let (reply, resumed) = accept_resume(&buf[..n], src, &peers2).unwrap();
+        assert!(resumed && reply.is_reply);
+
+        // Both directions carry on with the same session and counters
+        let (counter, ciphertext) = send(&peers2, ip1, b"after");
+        assert_eq!(counter, 4);
+        assert_eq!(peers1.with_peer_by_ip(&ip2, |p| p.decrypt(counter, &ciphertext).unwrap()).unwrap(), b"after");
+        let (counter, ciphertext) = send(&peers1, ip2, b"hello");
+        assert_eq!(peers2.with_peer_by_ip(&ip1, |p| p.decrypt(counter, &ciphertext).unwrap()).unwrap(), b"hello");
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_stale_or_replayed_resume_rejected() {
+        let (kp1, kp2) = (KeyPair::generate(), KeyPair::generate());
+        let ip2 = Ipv4Addr::new(10, 0, 10, 2);
+        let old = SocketAddr::from(([192, 0, 2, 2], 9600));
+        let (new, attacker) = (SocketAddr::from(([198, 51, 100, 2], 9600)), SocketAddr::from(([203, 0, 113, 9], 4444)));
+        let peers = resumable(&kp1, &kp2, ip2, old);
+        let session_id = peers.with_peer_by_ip(&ip2, |p| p.session_id.unwrap()).unwrap();
+        let endpoint = || peers.with_peer_by_ip(&ip2, |p| p.endpoint.unwrap()).unwrap();
+        let now = unix_time_ms();
+
+        // Sent too long ago, or claiming to be from the future
+        for at in [now - 2 * RESUME_MAX_AGE.as_millis() as u64, now + 2 * RESUME_MAX_AGE.as_millis() as u64] {
+            let stale = build_resume_at(&kp2, ip2, &session_id, None, false, at);
+            assert!(!accept_resume(&stale, new, &peers).unwrap().1);
+        }
+        // Naming another session
+        assert!(!accept_resume(&build_resume(&kp2, ip2, &[7; 16], None, false), new, &peers).unwrap().1);
+        assert_eq!(endpoint(), old);
+
+        let resume = build_resume_at(&kp2, ip2, &session_id, None, false, now);
+        assert!(accept_resume(&resume, new, &peers).unwrap().1);
+        assert_eq!(endpoint(), new);
+
+        // Captured and sent again, from anywhere
+        assert!(!accept_resume(&resume, attacker, &peers).unwrap().1);
+        assert!(!accept_resume(&resume, new, &peers).unwrap().1);
+        // An older resume arriving late
+        let earlier = build_resume_at(&kp2, ip2, &session_id, None, false, now - 1_000);
+        assert!(!accept_resume(&earlier, attacker, &peers).unwrap().1);
+        assert_eq!(endpoint(), new);
+        assert_eq!(peers.find_ip_by_endpoint(&attacker), None);
+
+        // A later one moves the session again, even after a new handshake
+        peers.with_peer_by_ip(&ip2, |p| p.establish_session(&kp1));
+        let later = build_resume_at(&kp2, ip2, &session_id, None, false, now + 1);
+        assert!(accept_resume(&later, old, &peers).unwrap().1);
+        assert!(!accept_resume(&resume, new, &peers).unwrap().1, "replay still refused");
+        assert_eq!(endpoint(), old);
+    }
+}
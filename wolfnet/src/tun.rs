@@ -81,11 +81,8 @@ impl TunDevice {
         }
 
         // Set MTU
-        let status = std::process::Command::new("ip")
-            .args(["link", "set", "dev", &self.name, "mtu", &mtu.to_string()])
-            .status()?;
-        if !status.success() {
-            warn!("Failed to set MTU on {}", self.name);
+        if let Err(e) = self.set_mtu(mtu) {
+            warn!("{}", e);
         }
 
         // Bring interface up
@@ -100,6 +97,17 @@ impl TunDevice {
         Ok(())
     }
 
+    /// Change the interface MTU
+    pub fn set_mtu(&self, mtu: u16) -> Result<(), Box<dyn std::error::Error>> {
+        let status = std::process::Command::new("ip")
+            .args(["link", "set", "dev", &self.name, "mtu", &mtu.to_string()])
+            .status()?;
+        if !status.success() {
+            return Err(format!("Failed to set MTU on {}", self.name).into());
+        }
+        Ok(())
+    }
+
     /// Read a packet from the TUN device (blocking if data available)
     /// Returns number of bytes read, or 0 if would block
     pub fn read(&self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
@@ -0,0 +1,337 @@
+//! TURN-style relay for peers behind symmetric NAT
+//!
+//! Two nodes behind symmetric NATs can't hole-punch: each NAT picks a new
+//! external port per destination, so the endpoint a peer learns is never the
+//! one that would let its packets in. A gateway with `turn_server = true`
+//! listens on `listen_port + 1` and relays between them, a simplified RFC 5766
+//! with just Allocate and Send/Data indications:
+//!
+//! - Allocate `[0x10] [4: wolfnet ip]` binds the client's NAT-mapped address
+//!   to its WolfNet IP for `ALLOCATION_TTL`; answered with Allocate OK
+//!   `[0x11] [4: lifetime secs]`. Clients refresh well before it runs out.
+//! - Send `[0x12] [4: dest wolfnet ip] [N: WolfNet packet]` from an allocated
+//!   client is forwarded to the destination's allocation as
+//!   Data `[0x13] [4: source wolfnet ip] [N: WolfNet packet]`.
+//!
+//! The relayed packets are ordinary data packets encrypted with the two
+//! peers' session, so the relay can't read or forge them.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, info};
+
+use crate::obfuscation::ObfuscatedSocket;
+use crate::transport::resolve_endpoint;
+
+pub const PKT_TURN_ALLOCATE: u8 = 0x10;
+pub const PKT_TURN_ALLOCATE_OK: u8 = 0x11;
+pub const PKT_TURN_SEND: u8 = 0x12;
+pub const PKT_TURN_DATA: u8 = 0x13;
+
+/// How long an allocation lives without a refresh
+pub const ALLOCATION_TTL: Duration = Duration::from_secs(600);
+
+/// Clients re-allocate this often while they have a live allocation
+const REFRESH_INTERVAL: Duration = Duration::from_secs(120);
+
+/// ...and this often while a server hasn't answered yet
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Build an Allocate request for `wolfnet_ip`
+pub fn build_allocate(wolfnet_ip: Ipv4Addr) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(5);
+    pkt.push(PKT_TURN_ALLOCATE);
+    pkt.extend_from_slice(&wolfnet_ip.octets());
+    pkt
+}
+
+/// Build an Allocate OK response
+pub fn build_allocate_ok(lifetime: Duration) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(5);
+    pkt.push(PKT_TURN_ALLOCATE_OK);
+    pkt.extend_from_slice(&(lifetime.as_secs() as u32).to_be_bytes());
+    pkt
+}
+
+/// Parse an Allocate OK response, returns the allocation lifetime
+pub fn parse_allocate_ok(data: &[u8]) -> Option<Duration> {
+    if data.len() != 5 || data[0] != PKT_TURN_ALLOCATE_OK { return None; }
+    Some(Duration::from_secs(u32::from_be_bytes(data[1..5].try_into().ok()?) as u64))
+}
+
+/// Wrap a WolfNet packet in a Send (`PKT_TURN_SEND`) or Data (`PKT_TURN_DATA`) indication
+pub fn build_indication(kind: u8, ip: Ipv4Addr, packet: &[u8]) -> Vec<u8> {
+    let mut pkt = Vec::with_capacity(5 + packet.len());
+    pkt.push(kind);
+    pkt.extend_from_slice(&ip.octets());
+    pkt.extend_from_slice(packet);
+    pkt
+}
+
+/// Parse a Send or Data indication of the given kind, returns (ip, wrapped packet)
+pub fn parse_indication(kind: u8, data: &[u8]) -> Option<(Ipv4Addr, &[u8])> {
+    if data.len() < 6 || data[0] != kind { return None; }
+    Some((Ipv4Addr::new(data[1], data[2], data[3], data[4]), &data[5..]))
+}
+
+/// A client's relay allocation
+#[derive(Debug, Clone, Copy)]
+struct Allocation {
+    /// The client's WolfNet IP, which peers address it by
+    wolfnet_ip: Ipv4Addr,
+    expires: Instant,
+}
+
+/// Allocation table of the relay server, keyed by client (NAT-mapped) address
+#[derive(Debug, Default)]
+pub struct TurnServer {
+    allocations: HashMap<SocketAddr, Allocation>,
+}
+
+impl TurnServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of live allocations
+    pub fn allocations(&self) -> usize {
+        self.allocations.len()
+    }
+
+    /// Handle one datagram from `src`, returns the datagram to send and where
+    pub fn handle(&mut self, data: &[u8], src: SocketAddr, now: Instant) -> Option<(Vec<u8>, SocketAddr)> {
+        match *data.first()? {
+            PKT_TURN_ALLOCATE if data.len() == 5 => {
+                let wolfnet_ip = Ipv4Addr::new(data[1], data[2], data[3], data[4]);
+                // A client whose NAT mapping changed re-allocates from a new address
+                self.allocations.retain(|addr, a| *addr == src || a.wolfnet_ip != wolfnet_ip);
+                let fresh = self.allocations.insert(src, Allocation { wolfnet_ip, expires: now + ALLOCATION_TTL }).is_none();
+                if fresh {
+                    info!("TURN allocation for {} at {}", wolfnet_ip, src);
+                }
+                Some((build_allocate_ok(ALLOCATION_TTL), src))
+            }
+            PKT_TURN_SEND => {
+                let (dest_ip, packet) = parse_indication(PKT_TURN_SEND, data)?;
+                let sender = self.allocations.get(&src).filter(|a| a.expires > now)?.wolfnet_ip;
+                let (&dest, _) = self.allocations.iter()
+                    .find(|(_, a)| a.wolfnet_ip == dest_ip && a.expires > now)?;
+                Some((build_indication(PKT_TURN_DATA, sender, packet), dest))
+            }
+            _ => None,
+        }
+    }
+
+    /// Drop expired allocations
+    pub fn expire(&mut self, now: Instant) {
+        self.allocations.retain(|addr, a| {
+            let live = a.expires > now;
+            if !live {
+                debug!("TURN allocation for {} at {} expired", a.wolfnet_ip, addr);
+            }
+            live
+        });
+    }
+
+    /// Serve relay requests on `socket` until `running` is cleared (call from a thread)
+    pub fn run(mut self, socket: ObfuscatedSocket, running: Arc<AtomicBool>) {
+        socket.set_read_timeout(Some(Duration::from_secs(1))).ok();
+        let mut buf = [0u8; 65536];
+        let mut last_expire = Instant::now();
+        while running.load(Ordering::Relaxed) {
+            if let Ok((n, src)) = socket.recv_from(&mut buf) {
+                if let Some((reply, dest)) = self.handle(&buf[..n], src, Instant::now()) {
+                    let _ = socket.send_to(&reply, dest);
+                }
+            }
+            if last_expire.elapsed() > Duration::from_secs(10) {
+                self.expire(Instant::now());
+                last_expire = Instant::now();
+            }
+        }
+    }
+}
+
+/// Keeps allocations on the configured relay servers and picks one to send through
+#[derive(Debug)]
+pub struct TurnClient {
+    /// Configured servers, in order of preference
+    servers: Vec<String>,
+    /// Resolved address of each server (None until it resolves)
+    resolved: Vec<Option<SocketAddr>>,
+    /// When each server last confirmed our allocation, and for how long
+    confirmed: HashMap<SocketAddr, (Instant, Duration)>,
+    last_allocate: Option<Instant>,
+}
+
+impl TurnClient {
+    pub fn new(servers: Vec<String>) -> Self {
+        let resolved = vec![None; servers.len()];
+        Self { servers, resolved, confirmed: HashMap::new(), last_allocate: None }
+    }
+
+    /// Send (or refresh) allocations when due. Allocating on every server,
+    /// not just the preferred one, keeps us reachable by peers that prefer a
+    /// different one.
+    pub fn tick(&mut self, socket: &ObfuscatedSocket, wolfnet_ip: Ipv4Addr) {
+        if self.servers.is_empty() { return; }
+        let interval = if self.relay().is_some() { REFRESH_INTERVAL } else { RETRY_INTERVAL };
+        if self.last_allocate.is_some_and(|t| t.elapsed() < interval) { return; }
+        self.last_allocate = Some(Instant::now());
+
+        let request = build_allocate(wolfnet_ip);
+        for (server, addr) in self.servers.iter().zip(self.resolved.iter_mut()) {
+            // Re-resolve every time so a relay behind DynDNS can move
+            if let Some(resolved) = resolve_endpoint(server) {
+                *addr = Some(resolved);
+            }
+            if let Some(addr) = addr {
+                let _ = socket.send_to(&request, *addr);
+            }
+        }
+    }
+
+    /// Whether `src` is one of our relay servers
+    pub fn is_server(&self, src: &SocketAddr) -> bool {
+        self.resolved.contains(&Some(*src))
+    }
+
+    /// Record an Allocate OK from `src`
+    pub fn confirm(&mut self, src: SocketAddr, lifetime: Duration) {
+        if !self.is_server(&src) { return; }
+        if self.confirmed.insert(src, (Instant::now(), lifetime)).is_none() {
+            info!("TURN relay allocated on {}", src);
+        }
+    }
+
+    /// The preferred server holding a live allocation for us
+    pub fn relay(&self) -> Option<SocketAddr> {
+        self.resolved.iter().flatten().copied().find(|addr| {
+            self.confirmed.get(addr).is_some_and(|(at, lifetime)| at.elapsed() < *lifetime)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+
+    fn addr(last: u8) -> SocketAddr {
+        SocketAddr::from(([198, 51, 100, last], 40000 + last as u16))
+    }
+
+    fn ip(last: u8) -> Ipv4Addr {
+        Ipv4Addr::new(10, 0, 10, last)
+    }
+
+    /// A server with clients 10.0.10.2 and 10.0.10.3 allocated at `now`
+    fn server_with_clients(now: Instant) -> TurnServer {
+        let mut server = TurnServer::new();
+        for last in [2, 3] {
+            let (reply, dest) = server.handle(&build_allocate(ip(last)), addr(last), now).unwrap();
+            assert_eq!(parse_allocate_ok(&reply), Some(ALLOCATION_TTL));
+            assert_eq!(dest, addr(last));
+        }
+        server
+    }
+
+    #[test]
+    fn test_packet_round_trip() {
+        assert_eq!(build_allocate(ip(2)), [PKT_TURN_ALLOCATE, 10, 0, 10, 2]);
+        assert_eq!(parse_allocate_ok(&build_allocate_ok(ALLOCATION_TTL)), Some(ALLOCATION_TTL));
+        assert_eq!(parse_allocate_ok(&build_allocate(ip(2))), None);
+
+        let send = build_indication(PKT_TURN_SEND, ip(3), b"packet");
+        assert_eq!(parse_indication(PKT_TURN_SEND, &send), Some((ip(3), &b"packet"[..])));
+        assert_eq!(parse_indication(PKT_TURN_DATA, &send), None);
+        // An indication must carry a packet
+        assert_eq!(parse_indication(PKT_TURN_SEND, &build_indication(PKT_TURN_SEND, ip(3), b"")), None);
+    }
+
+    #[test]
+    fn test_allocation() {
+        let now = Instant::now();
+        let mut server = server_with_clients(now);
+        assert_eq!(server.allocations(), 2);
+
+        // Refreshing keeps one allocation per client
+        server.handle(&build_allocate(ip(2)), addr(2), now).unwrap();
+        assert_eq!(server.allocations(), 2);
+
+        // A client behind a new NAT mapping replaces its old allocation
+        server.handle(&build_allocate(ip(2)), addr(9), now).unwrap();
+        assert_eq!(server.allocations(), 2);
+        let (_, dest) = server.handle(&build_indication(PKT_TURN_SEND, ip(2), b"x"), addr(3), now).unwrap();
+        assert_eq!(dest, addr(9));
+
+        // Malformed requests get no answer
+        assert!(server.handle(&[PKT_TURN_ALLOCATE, 10, 0, 10], addr(4), now).is_none());
+        assert!(server.handle(&[], addr(4), now).is_none());
+        assert_eq!(server.allocations(), 2);
+    }
+
+    #[test]
+    fn test_relay_packet() {
+        let now = Instant::now();
+        let mut server = server_with_clients(now);
+        let (data, dest) = server.handle(&build_indication(PKT_TURN_SEND, ip(3), b"packet"), addr(2), now).unwrap();
+        assert_eq!(dest, addr(3));
+        // The destination learns who sent it, not where to
+        assert_eq!(parse_indication(PKT_TURN_DATA, &data), Some((ip(2), &b"packet"[..])));
+    }
+
+    #[test]
+    fn test_only_allocated_clients_relay() {
+        let now = Instant::now();
+        let mut server = server_with_clients(now);
+        let send = build_indication(PKT_TURN_SEND, ip(3), b"packet");
+        assert!(server.handle(&send, addr(7), now).is_none(), "sender has no allocation");
+        assert!(server.handle(&build_indication(PKT_TURN_SEND, ip(7), b"packet"), addr(2), now).is_none(),
+            "destination has no allocation");
+        assert!(server.handle(&build_indication(PKT_TURN_DATA, ip(3), b"packet"), addr(2), now).is_none(),
+            "clients only send Send indications");
+
+        // Allocations that ran out no longer relay, and are dropped
+        let later = now + ALLOCATION_TTL;
+        assert!(server.handle(&send, addr(2), later).is_none());
+        server.handle(&build_allocate(ip(2)), addr(2), later).unwrap();
+        assert!(server.handle(&send, addr(2), later).is_none(), "destination expired");
+        server.expire(later);
+        assert_eq!(server.allocations(), 1);
+    }
+
+    #[test]
+    fn test_client_allocates_and_picks_relay() {
+        let relay = UdpSocket::bind("127.0.0.1:0").unwrap();
+        relay.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        let relay_addr = relay.local_addr().unwrap();
+        let socket = ObfuscatedSocket::new(UdpSocket::bind("127.0.0.1:0").unwrap(), None);
+
+        let mut client = TurnClient::new(vec![relay_addr.to_string()]);
+        assert_eq!(client.relay(), None);
+        client.tick(&socket, ip(2));
+        let mut buf = [0u8; 64];
+        let (n, _) = relay.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], build_allocate(ip(2)));
+        assert!(client.is_server(&relay_addr));
+
+        // Confirmations from anyone else are ignored
+        client.confirm(addr(9), ALLOCATION_TTL);
+        assert_eq!(client.relay(), None);
+        client.confirm(relay_addr, ALLOCATION_TTL);
+        assert_eq!(client.relay(), Some(relay_addr));
+
+        // Not due again until the refresh interval
+        client.tick(&socket, ip(2));
+        assert!(relay.recv_from(&mut buf).is_err());
+
+        // An allocation that ran out is not used
+        client.confirm(relay_addr, Duration::ZERO);
+        assert_eq!(client.relay(), None);
+    }
+}